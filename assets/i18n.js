@@ -0,0 +1,368 @@
+// Dashboard translations. Static markup is tagged with data-i18n (text),
+// data-i18n-title and data-i18n-placeholder; scripts call t(key, vars).
+// The language is the saved choice, else the first supported browser
+// locale, else English. Missing keys fall back to English.
+const I18N = {
+  en: {
+    'lang.name': 'English',
+    'title.dashboard': 's5 Dashboard',
+    'title.login': 's5 Dashboard - Sign in',
+    'login.username': 'Username',
+    'login.username_hint': '(empty: sign in with the API token)',
+    'login.secret': 'Password or API token',
+    'login.submit': 'Sign in',
+    'login.invalid': 'Invalid credentials',
+    'login.failed': 'Sign-in failed ({status})',
+    'error': 'Error: {msg}',
+    'header.theme': 'Toggle light/dark theme',
+    'header.lang': 'Switch language',
+    'header.logout': 'Sign out',
+    'status.connecting': 'Connecting...',
+    'status.connected': 'Connected',
+    'status.disconnected': 'Disconnected',
+    'status.polling': 'Polling',
+    'status.error': 'Error',
+    'card.active_connections': 'Active Connections',
+    'card.banned_ips': 'Banned IPs',
+    'card.total_users': 'Total Users',
+    'card.uptime': 'Uptime',
+    'col.username': 'Username',
+    'col.user': 'User',
+    'col.forwarding': 'Forwarding',
+    'col.shell': 'Shell',
+    'col.active': 'Active',
+    'col.ip': 'IP',
+    'col.expires': 'Expires',
+    'col.group': 'Group',
+    'col.members': 'Members',
+    'col.daily_bw': 'Daily BW',
+    'col.monthly_bw': 'Monthly BW',
+    'col.total_bw': 'Total BW',
+    'col.conns_day': 'Conns (day)',
+    'col.rate': 'Rate',
+    'col.id': 'ID',
+    'col.target': 'Target',
+    'col.protocol': 'Protocol',
+    'col.up': 'Up',
+    'col.down': 'Down',
+    'col.duration': 'Duration',
+    'col.fingerprint': 'Fingerprint',
+    'panel.users': 'Users',
+    'panel.bans': 'Banned IPs',
+    'panel.controls': 'Controls',
+    'panel.connections': 'Connection Info',
+    'panel.groups': 'Groups',
+    'panel.sessions': 'Active Sessions',
+    'panel.quotas': 'Quota Usage',
+    'panel.audit': 'Audit Log (live)',
+    'panel.throughput': 'Throughput (last 60 s)',
+    'panel.enrollments': 'Keys Awaiting Approval',
+    'panel.expiring_keys': 'Keys Expiring Soon',
+    'empty.bans': 'No banned IPs',
+    'empty.connections': 'No active connections',
+    'empty.groups': 'No groups',
+    'empty.sessions': 'No active sessions',
+    'empty.quotas': 'No quota data',
+    'empty.enrollments': 'No keys awaiting approval',
+    'yes': 'Yes',
+    'no': 'No',
+    'on': 'ON',
+    'off': 'OFF',
+    'ban.permanent': 'permanent',
+    'ban.unban': 'Unban',
+    'user.password_expired': 'password expired',
+    'user.locked': 'locked',
+    'user.disabled': 'disabled',
+    'user.unlock': 'Unlock',
+    'enroll.approve': 'Approve',
+    'enroll.reject': 'Reject',
+    'key.expired': 'expired',
+    'role.required': 'Requires the {role} role',
+    'controls.maintenance': 'Maintenance mode:',
+    'controls.toggle_maintenance': 'Toggle Maintenance',
+    'controls.reload': 'Reload Config',
+    'action.maintenance_sent': 'Maintenance toggle sent via WS',
+    'action.maintenance': 'Maintenance: {state}',
+    'action.reloaded': 'Reloaded ({count} users)',
+    'action.unban_sent': 'Unban request sent via WS for {ip}',
+    'action.unbanned': 'Unbanned {ip}',
+    'action.unlocked': 'Unlocked {user}',
+    'action.kick_sent': 'Kick request sent via WS for {user}',
+    'action.kicked': 'Kicked {user}',
+    'action.enroll_approve': 'Key of {user} approved',
+    'action.enroll_reject': 'Key of {user} rejected',
+    'action.group_saved': 'Policy of group {group} saved',
+    'detail.session': 'Session {id}',
+    'detail.source': 'Source',
+    'detail.connection': 'Connection',
+    'detail.rate_up': 'Rate up',
+    'detail.rate_down': 'Rate down',
+    'detail.ssh': 'SSH',
+    'detail.client': 'Client',
+    'detail.auth': 'Auth',
+    'detail.channels': 'Open channels',
+    'detail.kex': 'Kex',
+    'detail.host_key': 'Host key',
+    'detail.cipher': 'Cipher',
+    'detail.mac': 'MAC',
+    'detail.compression': 'Compression',
+    'detail.not_ssh': 'Not an SSH session',
+    'detail.destinations': 'Destinations ({count})',
+    'detail.quota': 'Quota',
+    'detail.hourly_bw': 'Hourly BW',
+    'detail.conns_month': 'Conns (month)',
+    'group.title': 'Group {name}',
+    'group.inherits': 'Inherits from',
+    'group.members': 'Members ({count})',
+    'group.policy': 'Policy',
+    'group.bandwidth': 'Bandwidth per connection (Kbps)',
+    'group.aggregate_bandwidth': 'Bandwidth per user (Kbps)',
+    'group.max_connections': 'Connections per user',
+    'group.inherit': 'inherit',
+    'group.save': 'Save',
+    'panel.geo': 'Session Map',
+    'geo.count': '({located} located, {unlocated} unknown)',
+    'geo.cluster': '{sessions} session(s) from {countries}: {users}',
+    'panel.security': 'Security Events',
+    'security.window_1h': 'Last hour',
+    'security.window_6h': 'Last 6 hours',
+    'security.window_24h': 'Last 24 hours',
+    'security.auth_failure': 'Auth failures',
+    'security.ban': 'Bans',
+    'security.ip_guard': 'ip_guard blocks',
+    'security.events': '{kind} at {time}',
+    'empty.security_events': 'No matching audit entries',
+    'title.self': 'My account',
+    'login.self_link': 'SSH user? Manage your account',
+    'self.password': 'Password',
+    'self.totp': 'TOTP code (if enabled)',
+    'self.tokens': 'API tokens',
+    'self.tokens_hint': 'Read-only tokens for scripts: they can read your quota and sessions, nothing else.',
+    'self.token_name': 'Token name',
+    'self.created': 'Created',
+    'self.create': 'Create',
+    'self.revoke': 'Revoke',
+    'self.revoke_confirm': 'Revoke token {id}?',
+    'self.token_once': 'Copy this token now, it will not be shown again:',
+    'self.no_tokens': 'No API tokens',
+    'self.managed': 'Managed groups',
+    'self.reset_quota': 'Reset quota',
+    'self.reset_confirm': 'Reset the quota counters of {user}?',
+    'self.disable': 'Disable',
+    'self.enable': 'Enable',
+    'self.disable_confirm': 'Disable {user}? Their sessions are closed.',
+    'title.invite': 'Create your account',
+    'invite.welcome': 'Welcome, {user}',
+    'invite.expires': 'This link expires at {time}.',
+    'invite.password': 'Choose a password',
+    'invite.confirm': 'Confirm password',
+    'invite.mismatch': 'The passwords do not match',
+    'invite.public_key': 'SSH public key (optional)',
+    'invite.submit': 'Create account',
+    'invite.invalid': 'This invitation link is invalid, expired or already used.',
+    'invite.done': 'Account {user} created. You can now sign in over SSH or on the account page.',
+  },
+  fr: {
+    'lang.name': 'Français',
+    'title.dashboard': 'Tableau de bord s5',
+    'title.login': 'Tableau de bord s5 - Connexion',
+    'login.username': "Nom d'utilisateur",
+    'login.username_hint': '(vide : connexion avec le jeton API)',
+    'login.secret': 'Mot de passe ou jeton API',
+    'login.submit': 'Se connecter',
+    'login.invalid': 'Identifiants invalides',
+    'login.failed': 'Échec de la connexion ({status})',
+    'error': 'Erreur : {msg}',
+    'header.theme': 'Basculer thème clair/sombre',
+    'header.lang': 'Changer de langue',
+    'header.logout': 'Se déconnecter',
+    'status.connecting': 'Connexion...',
+    'status.connected': 'Connecté',
+    'status.disconnected': 'Déconnecté',
+    'status.polling': 'Interrogation',
+    'status.error': 'Erreur',
+    'card.active_connections': 'Connexions actives',
+    'card.banned_ips': 'IP bannies',
+    'card.total_users': 'Utilisateurs',
+    'card.uptime': 'Disponibilité',
+    'col.username': "Nom d'utilisateur",
+    'col.user': 'Utilisateur',
+    'col.forwarding': 'Redirection',
+    'col.shell': 'Shell',
+    'col.active': 'Actives',
+    'col.ip': 'IP',
+    'col.expires': 'Expiration',
+    'col.group': 'Groupe',
+    'col.members': 'Membres',
+    'col.daily_bw': 'Volume jour',
+    'col.monthly_bw': 'Volume mois',
+    'col.total_bw': 'Volume total',
+    'col.conns_day': 'Connexions (jour)',
+    'col.rate': 'Débit',
+    'col.id': 'ID',
+    'col.target': 'Cible',
+    'col.protocol': 'Protocole',
+    'col.up': 'Envoyé',
+    'col.down': 'Reçu',
+    'col.duration': 'Durée',
+    'col.fingerprint': 'Empreinte',
+    'panel.users': 'Utilisateurs',
+    'panel.bans': 'IP bannies',
+    'panel.controls': 'Commandes',
+    'panel.connections': 'Connexions',
+    'panel.groups': 'Groupes',
+    'panel.sessions': 'Sessions actives',
+    'panel.quotas': 'Consommation des quotas',
+    'panel.audit': "Journal d'audit (direct)",
+    'panel.throughput': 'Débit (60 dernières s)',
+    'panel.enrollments': "Clés en attente d'approbation",
+    'panel.expiring_keys': 'Clés bientôt expirées',
+    'empty.bans': 'Aucune IP bannie',
+    'empty.connections': 'Aucune connexion active',
+    'empty.groups': 'Aucun groupe',
+    'empty.sessions': 'Aucune session active',
+    'empty.quotas': 'Aucune donnée de quota',
+    'empty.enrollments': "Aucune clé en attente d'approbation",
+    'yes': 'Oui',
+    'no': 'Non',
+    'on': 'ACTIF',
+    'off': 'INACTIF',
+    'ban.permanent': 'permanent',
+    'ban.unban': 'Débannir',
+    'user.password_expired': 'mot de passe expiré',
+    'user.locked': 'verrouillé',
+    'user.disabled': 'désactivé',
+    'user.unlock': 'Déverrouiller',
+    'enroll.approve': 'Approuver',
+    'enroll.reject': 'Refuser',
+    'key.expired': 'expirée',
+    'role.required': 'Nécessite le rôle {role}',
+    'controls.maintenance': 'Mode maintenance :',
+    'controls.toggle_maintenance': 'Basculer la maintenance',
+    'controls.reload': 'Recharger la config',
+    'action.maintenance_sent': 'Bascule de maintenance envoyée via WS',
+    'action.maintenance': 'Maintenance : {state}',
+    'action.reloaded': 'Configuration rechargée ({count} utilisateurs)',
+    'action.unban_sent': 'Débannissement de {ip} envoyé via WS',
+    'action.unbanned': '{ip} débannie',
+    'action.unlocked': '{user} déverrouillé',
+    'action.kick_sent': 'Déconnexion de {user} envoyée via WS',
+    'action.kicked': '{user} déconnecté',
+    'action.enroll_approve': 'Clé de {user} approuvée',
+    'action.enroll_reject': 'Clé de {user} refusée',
+    'action.group_saved': 'Politique du groupe {group} enregistrée',
+    'detail.session': 'Session {id}',
+    'detail.source': 'Source',
+    'detail.connection': 'Connexion',
+    'detail.rate_up': 'Débit montant',
+    'detail.rate_down': 'Débit descendant',
+    'detail.ssh': 'SSH',
+    'detail.client': 'Client',
+    'detail.auth': 'Authentification',
+    'detail.channels': 'Canaux ouverts',
+    'detail.kex': 'Échange de clés',
+    'detail.host_key': "Clé d'hôte",
+    'detail.cipher': 'Chiffrement',
+    'detail.mac': 'MAC',
+    'detail.compression': 'Compression',
+    'detail.not_ssh': "Pas une session SSH",
+    'detail.destinations': 'Destinations ({count})',
+    'detail.quota': 'Quota',
+    'detail.hourly_bw': 'Volume heure',
+    'detail.conns_month': 'Connexions (mois)',
+    'group.title': 'Groupe {name}',
+    'group.inherits': 'Hérite de',
+    'group.members': 'Membres ({count})',
+    'group.policy': 'Politique',
+    'group.bandwidth': 'Débit par connexion (Kbps)',
+    'group.aggregate_bandwidth': 'Débit par utilisateur (Kbps)',
+    'group.max_connections': 'Connexions par utilisateur',
+    'group.inherit': 'hériter',
+    'group.save': 'Enregistrer',
+    'panel.geo': 'Carte des sessions',
+    'geo.count': '({located} localisées, {unlocated} inconnues)',
+    'geo.cluster': '{sessions} session(s) depuis {countries} : {users}',
+    'panel.security': 'Événements de sécurité',
+    'security.window_1h': 'Dernière heure',
+    'security.window_6h': '6 dernières heures',
+    'security.window_24h': '24 dernières heures',
+    'security.auth_failure': "Échecs d'authentification",
+    'security.ban': 'Bannissements',
+    'security.ip_guard': 'Blocages ip_guard',
+    'security.events': '{kind} à {time}',
+    'empty.security_events': "Aucune entrée d'audit correspondante",
+    'title.self': 'Mon compte',
+    'login.self_link': 'Utilisateur SSH ? Gérer votre compte',
+    'self.password': 'Mot de passe',
+    'self.totp': 'Code TOTP (si activé)',
+    'self.tokens': 'Jetons API',
+    'self.tokens_hint': 'Jetons en lecture seule pour les scripts : ils lisent votre quota et vos sessions, rien de plus.',
+    'self.token_name': 'Nom du jeton',
+    'self.created': 'Créé le',
+    'self.create': 'Créer',
+    'self.revoke': 'Révoquer',
+    'self.revoke_confirm': 'Révoquer le jeton {id} ?',
+    'self.token_once': 'Copiez ce jeton maintenant, il ne sera plus affiché :',
+    'self.no_tokens': 'Aucun jeton API',
+    'self.managed': 'Groupes gérés',
+    'self.reset_quota': 'Remettre le quota à zéro',
+    'self.reset_confirm': 'Remettre à zéro les compteurs de quota de {user} ?',
+    'self.disable': 'Désactiver',
+    'self.enable': 'Réactiver',
+    'self.disable_confirm': 'Désactiver {user} ? Ses sessions sont fermées.',
+    'title.invite': 'Créer votre compte',
+    'invite.welcome': 'Bienvenue, {user}',
+    'invite.expires': 'Ce lien expire à {time}.',
+    'invite.password': 'Choisissez un mot de passe',
+    'invite.confirm': 'Confirmez le mot de passe',
+    'invite.mismatch': 'Les mots de passe ne correspondent pas',
+    'invite.public_key': 'Clé publique SSH (facultative)',
+    'invite.submit': 'Créer le compte',
+    'invite.invalid': "Ce lien d'invitation est invalide, expiré ou déjà utilisé.",
+    'invite.done': 'Compte {user} créé. Vous pouvez vous connecter en SSH ou sur la page du compte.',
+  },
+};
+
+function detectLang() {
+  const saved = localStorage.getItem('lang');
+  if (saved && I18N[saved]) return saved;
+  for (const l of navigator.languages || [navigator.language || '']) {
+    const base = String(l).toLowerCase().split('-')[0];
+    if (I18N[base]) return base;
+  }
+  return 'en';
+}
+let lang = detectLang();
+
+function t(key, vars) {
+  const s = (I18N[lang] && I18N[lang][key]) ?? I18N.en[key] ?? key;
+  return vars ? s.replace(/\{(\w+)\}/g, (m, k) => (k in vars ? vars[k] : m)) : s;
+}
+
+// Translate tagged elements under `root`. Scripts can tag dynamic elements
+// too (el.dataset.i18n = key) so they follow later language switches.
+function applyI18n(root) {
+  root = root || document;
+  root.querySelectorAll('[data-i18n]').forEach(el => { el.textContent = t(el.dataset.i18n); });
+  root.querySelectorAll('[data-i18n-title]').forEach(el => { el.title = t(el.dataset.i18nTitle); });
+  root.querySelectorAll('[data-i18n-placeholder]').forEach(el => { el.placeholder = t(el.dataset.i18nPlaceholder); });
+  document.documentElement.lang = lang;
+  const toggle = document.getElementById('langToggle');
+  if (toggle) toggle.textContent = lang.toUpperCase();
+}
+
+function setLang(next) {
+  if (!I18N[next]) return;
+  lang = next;
+  localStorage.setItem('lang', next);
+  applyI18n();
+  document.dispatchEvent(new CustomEvent('langchange', {detail: next}));
+}
+
+// Cycle through the available bundles
+function toggleLang() {
+  const codes = Object.keys(I18N);
+  setLang(codes[(codes.indexOf(lang) + 1) % codes.length]);
+}
+
+document.addEventListener('DOMContentLoaded', () => applyI18n());
@@ -0,0 +1,121 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use s5::proxy::buffer_pool::BufferPool;
+use s5::proxy::forwarder::{self, RelayConfig};
+use std::hint::black_box;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const BUFFER_SIZE: usize = 8192;
+const THREADS: usize = 8;
+const PER_THREAD: usize = 1000;
+
+fn bench_buffer_acquire(c: &mut Criterion) {
+    let pool = BufferPool::new(BUFFER_SIZE, 1024);
+    let mut group = c.benchmark_group("relay_buffer");
+    group.bench_function("vec_alloc", |b| {
+        b.iter(|| {
+            let buf = vec![0u8; BUFFER_SIZE];
+            black_box(&buf);
+        });
+    });
+    group.bench_function("pooled", |b| {
+        b.iter(|| {
+            let buf = pool.get();
+            black_box(&*buf);
+        });
+    });
+    group.finish();
+}
+
+/// Many tunnels starting and ending at once, one buffer each.
+fn bench_buffer_acquire_contended(c: &mut Criterion) {
+    let pool = BufferPool::new(BUFFER_SIZE, 1024);
+    let mut group = c.benchmark_group("relay_buffer_contended");
+    group.throughput(Throughput::Elements((THREADS * PER_THREAD) as u64));
+    group.bench_function("vec_alloc", |b| {
+        b.iter(|| {
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..PER_THREAD {
+                            black_box(vec![0u8; BUFFER_SIZE]);
+                        }
+                    });
+                }
+            });
+        });
+    });
+    group.bench_function("pooled", |b| {
+        b.iter(|| {
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..PER_THREAD {
+                            let buf = pool.get();
+                            black_box(&*buf);
+                        }
+                    });
+                }
+            });
+        });
+    });
+    group.finish();
+}
+
+fn relay_config() -> RelayConfig {
+    RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "bench".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit: None,
+        session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    }
+}
+
+/// One short tunnel end to end: 64 KiB up, relay torn down.
+fn bench_relay_short_tunnel(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let payload = vec![0x5au8; 64 * 1024];
+    let mut group = c.benchmark_group("relay");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+    group.bench_function("short_tunnel_64k", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let (mut client, relay_client) = tokio::io::duplex(BUFFER_SIZE);
+                let (mut server, relay_server) = tokio::io::duplex(BUFFER_SIZE);
+                let relay = tokio::spawn(forwarder::relay_with_reason(
+                    relay_client,
+                    relay_server,
+                    relay_config(),
+                ));
+                let len = payload.len();
+                let reader = tokio::spawn(async move {
+                    let mut sink = vec![0u8; len];
+                    server.read_exact(&mut sink).await.unwrap();
+                    sink
+                });
+                client.write_all(&payload).await.unwrap();
+                black_box(reader.await.unwrap());
+                // Both ends gone: each relay direction sees EOF
+                drop(client);
+                let _ = relay.await;
+            });
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_buffer_acquire,
+    bench_buffer_acquire_contended,
+    bench_relay_short_tunnel
+);
+criterion_main!(benches);
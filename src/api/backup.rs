@@ -1,4 +1,5 @@
 use crate::api::{ApiResponse, AppState};
+use crate::security::ban::BanKey;
 use axum::{extract::State, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -15,6 +16,9 @@ pub struct BackupPayload {
 pub struct BanEntry {
     pub ip: String,
     pub remaining_secs: u64,
+    /// Tenant the ban applies to (absent = server-wide)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -32,11 +36,12 @@ pub async fn backup_handler(State(state): State<AppState>) -> impl IntoResponse
     let ban_list = security.ban_manager().banned_ips();
     let bans: Vec<BanEntry> = ban_list
         .into_iter()
-        .map(|(ip, expires)| {
+        .map(|(key, expires)| {
             let remaining = expires.saturating_duration_since(std::time::Instant::now());
             BanEntry {
-                ip: ip.to_string(),
+                ip: key.ip.to_string(),
                 remaining_secs: remaining.as_secs(),
+                tenant: key.tenant,
             }
         })
         .collect();
@@ -83,9 +88,10 @@ pub async fn restore_handler(
         for ban in &payload.bans {
             if ban.remaining_secs > 0 {
                 if let Ok(ip) = ban.ip.parse() {
-                    security
-                        .ban_manager()
-                        .ban(ip, std::time::Duration::from_secs(ban.remaining_secs));
+                    security.ban_manager().ban(
+                        BanKey::new(ip, ban.tenant.as_deref()),
+                        std::time::Duration::from_secs(ban.remaining_secs),
+                    );
                     restored_bans += 1;
                 }
             }
@@ -1,34 +1,46 @@
+use super::rbac::Principal;
 use super::{ApiResponse, AppState};
+use crate::security::ban::BanKey;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
+    Extension, Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+use std::time::Duration;
 
 #[derive(Serialize)]
 pub struct BanInfo {
     pub ip: String,
     pub remaining_secs: u64,
+    /// Tenant whose logins the ban covers (null = server-wide)
+    pub tenant: Option<String>,
 }
 
-pub async fn list_bans(State(state): State<AppState>) -> impl IntoResponse {
+/// GET /api/bans — bans in force; a tenant principal gets its tenant's.
+pub async fn list_bans(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
     let security = state.security.read().await;
     let banned = security.ban_manager().banned_ips();
     let now = std::time::Instant::now();
 
     let bans: Vec<BanInfo> = banned
-        .iter()
-        .map(|(ip, expiry)| {
-            let remaining = if *expiry > now {
-                (*expiry - now).as_secs()
+        .into_iter()
+        .filter(|(key, _)| principal.sees_ban(key))
+        .map(|(key, expiry)| {
+            let remaining = if expiry > now {
+                (expiry - now).as_secs()
             } else {
                 0
             };
             BanInfo {
-                ip: ip.to_string(),
+                ip: key.ip.to_string(),
                 remaining_secs: remaining,
+                tenant: key.tenant,
             }
         })
         .collect();
@@ -36,6 +48,95 @@ pub async fn list_bans(State(state): State<AppState>) -> impl IntoResponse {
     ApiResponse::ok(bans)
 }
 
+/// The tenant a ban request acts on: a tenant principal's own, else the
+/// requested `[[tenants]]` entry (None = server-wide).
+fn ban_tenant(
+    state: &AppState,
+    principal: &Principal,
+    requested: Option<String>,
+) -> Result<Option<String>, &'static str> {
+    if principal.tenant.is_some() {
+        return Ok(principal.tenant.clone());
+    }
+    match requested {
+        Some(tenant) if !state.tenants.iter().any(|t| t.name == tenant) => Err("unknown tenant"),
+        requested => Ok(requested),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    pub ip: String,
+    /// Defaults to `security.ban_duration`
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// Ban for the logins of this `[[tenants]]` entry only (default:
+    /// server-wide; always the caller's tenant for tenant principals)
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+pub async fn create_ban(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(req): Json<BanRequest>,
+) -> impl IntoResponse {
+    let ip: IpAddr = match req.ip.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return ApiResponse::err(StatusCode::BAD_REQUEST, "invalid IP address").into_response()
+        }
+    };
+    let tenant = match ban_tenant(&state, &principal, req.tenant) {
+        Ok(tenant) => tenant,
+        Err(msg) => return ApiResponse::err(StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let security = state.security.read().await;
+    let bans = security.ban_manager();
+    if !bans.is_enabled() {
+        return ApiResponse::err(
+            StatusCode::CONFLICT,
+            "banning is disabled (security.ban_enabled = false)",
+        )
+        .into_response();
+    }
+    if bans.is_whitelisted(&ip) {
+        return ApiResponse::err(StatusCode::CONFLICT, "IP is in security.ban_whitelist")
+            .into_response();
+    }
+    let duration = req
+        .duration_secs
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| bans.ban_duration());
+    if duration.is_zero() {
+        return ApiResponse::err(
+            StatusCode::BAD_REQUEST,
+            "duration_secs must be greater than 0",
+        )
+        .into_response();
+    }
+
+    bans.ban(BanKey::new(ip, tenant.as_deref()), duration);
+    if let Some(ref audit) = state.audit {
+        audit.log_ban_created_for(&ip, tenant.as_deref(), duration.as_secs());
+    }
+    ApiResponse::ok(BanInfo {
+        ip: ip.to_string(),
+        remaining_secs: duration.as_secs(),
+        tenant,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct UnbanQuery {
+    /// Lift the ban for this `[[tenants]]` entry (default: the server-wide
+    /// ban; always the caller's tenant for tenant principals)
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct UnbanResult {
     pub ip: String,
@@ -44,7 +145,9 @@ pub struct UnbanResult {
 
 pub async fn delete_ban(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Path(ip_str): Path<String>,
+    Query(query): Query<UnbanQuery>,
 ) -> impl IntoResponse {
     let ip: IpAddr = match ip_str.parse() {
         Ok(ip) => ip,
@@ -52,9 +155,16 @@ pub async fn delete_ban(
             return ApiResponse::err(StatusCode::BAD_REQUEST, "invalid IP address").into_response()
         }
     };
+    let tenant = match ban_tenant(&state, &principal, query.tenant) {
+        Ok(tenant) => tenant,
+        Err(msg) => return ApiResponse::err(StatusCode::BAD_REQUEST, msg).into_response(),
+    };
 
     let security = state.security.read().await;
-    if security.ban_manager().unban(&ip) {
+    if security
+        .ban_manager()
+        .unban(BanKey::new(ip, tenant.as_deref()))
+    {
         ApiResponse::ok(UnbanResult {
             ip: ip_str,
             unbanned: true,
@@ -0,0 +1,75 @@
+use super::rbac::Principal;
+use super::{ApiResponse, AppState};
+use crate::proxy::capture::{CaptureError, CaptureMode};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CaptureRequest {
+    /// Seconds to capture for (default: one minute, capped by
+    /// `max_duration_secs`)
+    pub duration_secs: Option<u64>,
+    #[serde(default)]
+    pub mode: CaptureMode,
+}
+
+/// GET /api/sessions/:id/capture — the capture running on a session.
+pub async fn get_capture(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.proxy_engine.session_capture(&id) {
+        Some(info) => ApiResponse::ok(info).into_response(),
+        None => ApiResponse::err(StatusCode::NOT_FOUND, "no capture running").into_response(),
+    }
+}
+
+/// POST /api/sessions/:id/capture — start writing the session's forwarded
+/// traffic to a pcapng file. The body is optional.
+pub async fn start_capture(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    body: Option<Json<CaptureRequest>>,
+) -> impl IntoResponse {
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    match state.proxy_engine.start_capture(
+        &id,
+        request.mode,
+        request.duration_secs,
+        &principal.name,
+    ) {
+        Ok(info) => ApiResponse::ok_with_status(StatusCode::CREATED, info).into_response(),
+        Err(e) => {
+            let status = match &e {
+                CaptureError::Disabled | CaptureError::SessionNotFound => StatusCode::NOT_FOUND,
+                CaptureError::InvalidDuration(_) => StatusCode::BAD_REQUEST,
+                CaptureError::AlreadyRunning => StatusCode::CONFLICT,
+                CaptureError::Io(err) => {
+                    error!(session = %id, error = %err, "Failed to start session capture");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            ApiResponse::err(status, e.to_string()).into_response()
+        }
+    }
+}
+
+/// DELETE /api/sessions/:id/capture — stop the capture and return its
+/// summary once the file is closed.
+pub async fn stop_capture(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.proxy_engine.stop_capture(&id, &principal.name).await {
+        Some(summary) => ApiResponse::ok(summary).into_response(),
+        None => ApiResponse::err(StatusCode::NOT_FOUND, "no capture running").into_response(),
+    }
+}
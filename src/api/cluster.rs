@@ -0,0 +1,125 @@
+use super::sessions::{to_response, SessionResponse};
+use super::{ApiResponse, AppState};
+use crate::cluster::ClusterStatus;
+use crate::config::types::ClusterBackend;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+const NO_CLUSTER: &str = "cluster mode is disabled ([cluster] enabled = false)";
+
+const READ_ONLY: &str = "read-only observer ([cluster] observer = true)";
+
+#[derive(Serialize)]
+pub struct ClusterNode {
+    pub node_id: String,
+    pub updated_at: DateTime<Utc>,
+    pub sessions: usize,
+    /// The node answering this request
+    pub is_self: bool,
+}
+
+#[derive(Serialize)]
+pub struct ClusterInfo {
+    pub node_id: String,
+    pub backend: ClusterBackend,
+    /// Redis URL (None with `backend = "gossip"`)
+    pub url: Option<String>,
+    /// Gossip peers (empty with `backend = "redis"`)
+    pub peers: Vec<String>,
+    pub sync_interval_secs: u64,
+    /// This node only reads the shared state
+    pub observer: bool,
+    #[serde(flatten)]
+    pub status: ClusterStatus,
+    pub nodes: Vec<ClusterNode>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ClusterSession {
+    node_id: String,
+    #[serde(flatten)]
+    session: SessionResponse,
+}
+
+fn backend_error(e: anyhow::Error) -> axum::response::Response {
+    ApiResponse::err(
+        StatusCode::BAD_GATEWAY,
+        format!("cluster backend unavailable: {:#}", e),
+    )
+    .into_response()
+}
+
+/// GET /api/cluster — this node, the outcome of its last sync and the nodes
+/// publishing sessions.
+pub async fn cluster_info(State(state): State<AppState>) -> impl IntoResponse {
+    let Some(cluster) = state.cluster else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CLUSTER).into_response();
+    };
+    let nodes = match cluster.nodes().await {
+        Ok(nodes) => nodes,
+        Err(e) => return backend_error(e),
+    };
+    ApiResponse::ok(ClusterInfo {
+        node_id: cluster.node_id().to_string(),
+        backend: cluster.config().backend,
+        url: (cluster.config().backend == ClusterBackend::Redis)
+            .then(|| cluster.config().url.clone()),
+        peers: match cluster.config().backend {
+            ClusterBackend::Redis => Vec::new(),
+            ClusterBackend::Gossip => cluster.config().peers.clone(),
+        },
+        sync_interval_secs: cluster.config().sync_interval_secs,
+        observer: cluster.is_observer(),
+        status: cluster.status(),
+        nodes: nodes
+            .into_iter()
+            .map(|n| ClusterNode {
+                is_self: n.node_id == cluster.node_id(),
+                sessions: n.sessions.len(),
+                node_id: n.node_id,
+                updated_at: n.updated_at,
+            })
+            .collect(),
+    })
+    .into_response()
+}
+
+/// GET /api/cluster/sessions — active sessions of every node, as of each
+/// node's last sync.
+pub async fn cluster_sessions(State(state): State<AppState>) -> impl IntoResponse {
+    let Some(cluster) = state.cluster else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CLUSTER).into_response();
+    };
+    let nodes = match cluster.nodes().await {
+        Ok(nodes) => nodes,
+        Err(e) => return backend_error(e),
+    };
+    let sessions: Vec<ClusterSession> = nodes
+        .into_iter()
+        .flat_map(|node| {
+            let node_id = node.node_id;
+            node.sessions.into_iter().map(move |s| ClusterSession {
+                node_id: node_id.clone(),
+                session: to_response(s),
+            })
+        })
+        .collect();
+    ApiResponse::ok(sessions).into_response()
+}
+
+/// Route layer: on an observer, refuse every request that could change
+/// state.
+pub async fn read_only(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    let observer = state.cluster.as_ref().is_some_and(|c| c.is_observer());
+    if observer && !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return ApiResponse::err(StatusCode::FORBIDDEN, READ_ONLY).into_response();
+    }
+    next.run(req).await
+}
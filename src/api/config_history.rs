@@ -0,0 +1,93 @@
+use super::rbac::Principal;
+use super::{reload, ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+};
+use serde::Serialize;
+use tracing::{error, warn};
+
+const NO_HISTORY: &str = "config history needs a config file (not available in env-var mode)";
+
+#[derive(Serialize)]
+pub struct RollbackResult {
+    /// Snapshot written back to the config file
+    pub snapshot_id: u64,
+    pub users_count: usize,
+}
+
+/// GET /api/config/history — applied configurations, newest (current) first.
+/// Sources are not returned: they hold password hashes and tokens.
+pub async fn list_config_history(State(state): State<AppState>) -> impl IntoResponse {
+    match state.config_history {
+        Some(ref history) => ApiResponse::ok(history.list()).into_response(),
+        None => ApiResponse::err(StatusCode::NOT_FOUND, NO_HISTORY).into_response(),
+    }
+}
+
+/// POST /api/config/rollback/:id — write snapshot `id` back to the config
+/// file and apply it, like a reload.
+pub async fn rollback_config(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    let (Some(history), Some(path)) = (state.config_history.clone(), state.config_path.clone())
+    else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_HISTORY).into_response();
+    };
+    let Some(snapshot) = history.get(id) else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "config snapshot not found")
+            .into_response();
+    };
+    // Files it refers to (host keys, GeoIP databases...) may have changed
+    let new_config = match crate::config::parse_config(&snapshot.content) {
+        Ok(config) => config,
+        Err(e) => {
+            return ApiResponse::err(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("snapshot {} is no longer valid: {:#}", id, e),
+            )
+            .into_response()
+        }
+    };
+
+    let content = snapshot.content.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        crate::config::persist::replace_config(&path, &content)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|r| r);
+    if let Err(e) = written {
+        error!(snapshot = id, error = %format!("{:#}", e), "Failed to write config rollback");
+        return ApiResponse::err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to write config: {:#}", e),
+        )
+        .into_response();
+    }
+
+    match reload::apply_config(&state, &new_config, &snapshot.content, "rollback").await {
+        Ok(users_count) => {
+            warn!(
+                snapshot = id,
+                sha256 = %snapshot.sha256,
+                by = %principal.name,
+                "Config rolled back"
+            );
+            if let Some(ref audit) = state.audit {
+                audit.log_event(AuditEvent::config_rollback(&snapshot, &principal.name));
+            }
+            ApiResponse::ok(RollbackResult {
+                snapshot_id: id,
+                users_count,
+            })
+            .into_response()
+        }
+        Err(e) => ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
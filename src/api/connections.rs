@@ -1,5 +1,6 @@
+use super::rbac::Principal;
 use super::{ApiResponse, AppState};
-use axum::{extract::State, response::IntoResponse};
+use axum::{extract::State, response::IntoResponse, Extension};
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -14,8 +15,11 @@ pub struct UserConnectionInfo {
     pub connections: u32,
 }
 
-pub async fn list_connections(State(state): State<AppState>) -> impl IntoResponse {
-    let active = state.proxy_engine.active_connections();
+pub async fn list_connections(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    let mut active = state.proxy_engine.active_connections();
 
     let auth = state.auth_service.read().await;
     let store = auth.user_store();
@@ -23,12 +27,17 @@ pub async fn list_connections(State(state): State<AppState>) -> impl IntoRespons
 
     let user_connections: Vec<UserConnectionInfo> = usernames
         .iter()
+        .filter(|name| principal.sees(name))
         .map(|name| UserConnectionInfo {
             username: name.clone(),
             connections: state.proxy_engine.user_connections(name),
         })
         .filter(|uc| uc.connections > 0)
         .collect();
+    // A tenant only counts its own users
+    if principal.tenant.is_some() {
+        active = user_connections.iter().map(|uc| uc.connections).sum();
+    }
 
     ApiResponse::ok(ConnectionsInfo {
         active_connections: active,
@@ -0,0 +1,198 @@
+//! Cross-origin access (`api.allowed_origins`) and CSRF protection.
+//!
+//! Without configured origins the API stays same-origin only: no CORS headers
+//! are emitted and cross-origin state-changing requests are rejected.
+//! State-changing requests that carry cookies must also echo the `s5_csrf`
+//! cookie in an `X-CSRF-Token` header (double-submit). Requests authenticated
+//! with an `Authorization` header are not CSRF-able and skip the token check.
+
+use super::{ApiResponse, AppState};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+/// Cookie holding the double-submit CSRF token.
+pub const CSRF_COOKIE: &str = "s5_csrf";
+
+/// Header the client echoes the CSRF token in.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+const ALLOWED_HEADERS: &str = "authorization, content-type, x-csrf-token";
+const PREFLIGHT_MAX_AGE_SECS: &str = "600";
+
+/// Normalize a configured origin to its serialized form (`scheme://host[:port]`).
+/// Returns None for anything that is not a bare http(s) origin.
+pub fn normalize_origin(origin: &str) -> Option<String> {
+    let url = url::Url::parse(origin).ok()?;
+    if !matches!(url.scheme(), "http" | "https")
+        || url.path() != "/"
+        || url.query().is_some()
+        || url.fragment().is_some()
+        || !url.username().is_empty()
+        || url.password().is_some()
+    {
+        return None;
+    }
+    Some(url.origin().ascii_serialization())
+}
+
+/// True when `origin` (an `Origin` header value) may make requests: either it
+/// is the API's own origin (matches `Host`) or it is explicitly allowed.
+pub fn origin_allowed(origin: &str, host: Option<&str>, allowed: &[String]) -> bool {
+    if allowed.iter().any(|a| a == origin) {
+        return true;
+    }
+    let authority = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"));
+    matches!((authority, host), (Some(a), Some(h)) if a.eq_ignore_ascii_case(h))
+}
+
+pub(crate) fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// Double-submit check: the header token must match the cookie token.
+pub fn csrf_token_valid(headers: &HeaderMap) -> bool {
+    use subtle::ConstantTimeEq;
+    let cookie = cookie_value(headers, CSRF_COOKIE);
+    let header = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok());
+    match (cookie, header) {
+        (Some(c), Some(h)) if !c.is_empty() => {
+            c.len() == h.len() && bool::from(c.as_bytes().ct_eq(h.as_bytes()))
+        }
+        _ => false,
+    }
+}
+
+/// CORS + CSRF middleware for `/api/*`.
+pub async fn cors_csrf_middleware(
+    State(state): State<AppState>,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> axum::response::Response {
+    if !req.uri().path().starts_with("/api/") {
+        return next.run(req).await;
+    }
+
+    let headers = req.headers();
+    let origin = headers
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    let origin_ok = origin
+        .as_deref()
+        .is_none_or(|o| origin_allowed(o, host, &state.allowed_origins));
+    // Only explicitly configured origins get CORS headers; same-origin needs none
+    let cors_origin = origin
+        .filter(|o| state.allowed_origins.iter().any(|a| a == o))
+        .and_then(|o| HeaderValue::from_str(&o).ok());
+
+    // Preflight
+    if req.method() == Method::OPTIONS
+        && headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+    {
+        let Some(allow_origin) = cors_origin else {
+            return (StatusCode::FORBIDDEN, "origin not allowed").into_response();
+        };
+        return (
+            StatusCode::NO_CONTENT,
+            [
+                (header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin),
+                (
+                    header::ACCESS_CONTROL_ALLOW_METHODS,
+                    HeaderValue::from_static(ALLOWED_METHODS),
+                ),
+                (
+                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                    HeaderValue::from_static(ALLOWED_HEADERS),
+                ),
+                (
+                    header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                    HeaderValue::from_static("true"),
+                ),
+                (
+                    header::ACCESS_CONTROL_MAX_AGE,
+                    HeaderValue::from_static(PREFLIGHT_MAX_AGE_SECS),
+                ),
+                (header::VARY, HeaderValue::from_static("origin")),
+            ],
+        )
+            .into_response();
+    }
+
+    let state_changing = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    // WebSocket upgrades are GETs but carry dashboard actions
+    let ws_upgrade = headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+
+    if (state_changing || ws_upgrade) && !origin_ok {
+        return ApiResponse::<()>::err(StatusCode::FORBIDDEN, "cross-origin request rejected")
+            .into_response();
+    }
+    if state_changing
+        && !headers.contains_key(header::AUTHORIZATION)
+        && headers.contains_key(header::COOKIE)
+        && !csrf_token_valid(headers)
+    {
+        return ApiResponse::<()>::err(StatusCode::FORBIDDEN, "missing or invalid CSRF token")
+            .into_response();
+    }
+
+    let mut response = next.run(req).await;
+    if let Some(allow_origin) = cors_origin {
+        let h = response.headers_mut();
+        h.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        h.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+        h.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    response
+}
+
+/// `SameSite` attribute of the API's cookies: cross-origin frontends
+/// (`allowed_origins`) need them sent on credentialed requests.
+pub(crate) fn same_site(state: &AppState) -> &'static str {
+    if state.allowed_origins.is_empty() {
+        "SameSite=Strict"
+    } else {
+        "SameSite=None; Secure"
+    }
+}
+
+#[derive(Serialize)]
+struct CsrfTokenResponse {
+    csrf_token: String,
+}
+
+/// GET /api/csrf-token — issue a CSRF token and set it as the `s5_csrf` cookie.
+pub async fn csrf_token_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; {}",
+        CSRF_COOKIE,
+        token,
+        same_site(&state)
+    );
+    (
+        [(header::SET_COOKIE, cookie)],
+        ApiResponse::ok(CsrfTokenResponse { csrf_token: token }),
+    )
+}
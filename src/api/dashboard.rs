@@ -1,17 +1,61 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use super::{session, AppState};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
 
-pub async fn serve_dashboard() -> impl IntoResponse {
+const CSP: &str = "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; connect-src 'self' ws: wss:";
+
+fn html_page(body: &'static str) -> Response {
+    static_asset("text/html; charset=utf-8", body)
+}
+
+fn static_asset(content_type: &'static str, body: &'static str) -> Response {
     (
         StatusCode::OK,
         [
-            ("content-type", "text/html; charset=utf-8"),
-            (
-                "content-security-policy",
-                "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; connect-src 'self' ws: wss:",
-            ),
-            ("x-frame-options", "DENY"),
-            ("x-content-type-options", "nosniff"),
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_SECURITY_POLICY, CSP),
+            (header::X_FRAME_OPTIONS, "DENY"),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (header::CACHE_CONTROL, "no-store"),
         ],
-        include_str!("../../assets/dashboard.html"),
+        body,
+    )
+        .into_response()
+}
+
+/// GET /dashboard — requires a login session, otherwise redirects to the login form.
+pub async fn serve_dashboard(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if session::session_principal(&state, &headers).is_none() {
+        return Redirect::to("/dashboard/login").into_response();
+    }
+    html_page(include_str!("../../assets/dashboard.html"))
+}
+
+/// GET /dashboard/login — token login form (exchanged for a session cookie).
+pub async fn serve_login() -> Response {
+    html_page(include_str!("../../assets/login.html"))
+}
+
+/// GET /dashboard/me — self-service page where SSH users sign in with their
+/// own password and manage their read-only API tokens.
+pub async fn serve_self_service() -> Response {
+    html_page(include_str!("../../assets/self.html"))
+}
+
+/// GET /dashboard/invite — where an invited user chooses a password and
+/// uploads a public key. The token travels in the URL fragment, so it never
+/// reaches request logs.
+pub async fn serve_invite() -> Response {
+    html_page(include_str!("../../assets/invite.html"))
+}
+
+/// GET /dashboard/i18n.js — translation bundles shared by the dashboard pages.
+pub async fn serve_i18n() -> Response {
+    static_asset(
+        "text/javascript; charset=utf-8",
+        include_str!("../../assets/i18n.js"),
     )
 }
@@ -0,0 +1,110 @@
+use super::{ApiResponse, AppState};
+use crate::config::{self, redact::redact_config, types::AppConfig};
+use crate::diagnostics::{self, errors::recent_errors, VersionInfo};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::{error, info};
+
+/// Open session counts, without user or destination details.
+#[derive(Serialize)]
+struct SessionCounts {
+    active_connections: u32,
+    sessions: usize,
+    /// Sessions per protocol (`ssh`, `socks5`)
+    by_protocol: BTreeMap<String, usize>,
+    connection_caps: crate::proxy::client_caps::CapsSnapshot,
+}
+
+/// The configuration as the server would load it now (no file: environment).
+fn current_config(state: &AppState) -> anyhow::Result<AppConfig> {
+    match state.config_path {
+        Some(ref path) => config::load_effective_config(path),
+        None => {
+            let config = config::env::build_config_from_env()?;
+            config::parse_config_validate(&config)?;
+            Ok(config)
+        }
+    }
+}
+
+fn to_json(value: &impl Serialize) -> Vec<u8> {
+    serde_json::to_vec_pretty(value).unwrap_or_default()
+}
+
+/// GET /api/debug/bundle — a tarball for bug reports: redacted config,
+/// version, diagnostic checks, recent warnings and errors, session counts
+/// and runtime stats.
+pub async fn debug_bundle(State(state): State<AppState>) -> impl IntoResponse {
+    let mut files: Vec<(&str, Vec<u8>)> = Vec::new();
+
+    let version = VersionInfo::current(state.start_time.elapsed().as_secs());
+    files.push(("version.json", to_json(&version)));
+
+    match current_config(&state) {
+        Ok(config) => {
+            let redacted = toml::to_string_pretty(&redact_config(&config))
+                .unwrap_or_else(|e| format!("# serialization failed: {e}\n"));
+            files.push(("config.toml", redacted.into_bytes()));
+            let mut checks = Vec::new();
+            if let Some(ref path) = state.config_path {
+                checks.extend(diagnostics::config_file_check(path));
+            }
+            checks.extend(diagnostics::run_checks(&config));
+            files.push(("checks.json", to_json(&checks)));
+        }
+        Err(e) => files.push(("config-error.txt", format!("{e:#}\n").into_bytes())),
+    }
+
+    files.push(("errors.json", to_json(&recent_errors())));
+
+    let sessions = state.proxy_engine.get_sessions();
+    let mut by_protocol = BTreeMap::new();
+    for session in &sessions {
+        *by_protocol.entry(session.protocol.clone()).or_insert(0) += 1;
+    }
+    let counts = SessionCounts {
+        active_connections: state.proxy_engine.active_connections(),
+        sessions: sessions.len(),
+        by_protocol,
+        connection_caps: state.proxy_engine.client_caps().snapshot(),
+    };
+    files.push(("sessions.json", to_json(&counts)));
+    files.push(("runtime.json", to_json(&diagnostics::runtime_stats())));
+
+    let root = format!("s5-debug-{}", version.generated_at.format("%Y%m%dT%H%M%SZ"));
+    let bundle = {
+        let root = root.clone();
+        tokio::task::spawn_blocking(move || diagnostics::tar_gz(&root, &files)).await
+    };
+    match bundle {
+        Ok(Ok(body)) => {
+            info!(bytes = body.len(), "Debug bundle generated");
+            (
+                [
+                    (header::CONTENT_TYPE, "application/gzip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{root}.tar.gz\""),
+                    ),
+                ],
+                body,
+            )
+                .into_response()
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "Failed to build debug bundle");
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "failed to build bundle")
+                .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Debug bundle task failed");
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "failed to build bundle")
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::api::{ApiResponse, AppState};
+use crate::flows::{FlowQuery, FlowRecord};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct FlowPage {
+    flows: Vec<FlowRecord>,
+    /// Pass as `before` to fetch the next (older) page; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_before: Option<i64>,
+}
+
+/// GET /api/flows — stored connection flows, newest first.
+pub async fn list_flows(
+    State(state): State<AppState>,
+    Query(query): Query<FlowQuery>,
+) -> impl IntoResponse {
+    let Some(ref flow_log) = state.flow_log else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "flow log not enabled").into_response();
+    };
+
+    let limit = query.effective_limit();
+    match flow_log.query(query).await {
+        Ok(flows) => {
+            let next_before = if flows.len() == limit {
+                flows.last().and_then(|f| f.id)
+            } else {
+                None
+            };
+            ApiResponse::ok(FlowPage { flows, next_before }).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Flow query failed");
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "flow query failed").into_response()
+        }
+    }
+}
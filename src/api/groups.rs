@@ -1,18 +1,32 @@
-use crate::api::{ApiResponse, AppState};
+use crate::api::rbac::Principal;
+use crate::api::{reload, ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::auth::AuthService;
+use crate::config::persist::{self, GroupPolicy};
 use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
+    Extension, Json,
 };
 use serde::Serialize;
+use tracing::warn;
+
+const NO_CONFIG_FILE: &str = "group editing needs a config file (not available in env-var mode)";
 
 #[derive(Serialize)]
 struct GroupStats {
     name: String,
+    /// Parent group (`inherits`)
+    inherits: Option<String>,
+    /// Policy set on the group itself; unset fields are inherited
+    policy: GroupPolicy,
     member_count: usize,
     active_connections: u32,
     total_daily_bytes: u64,
     total_monthly_bytes: u64,
+    /// Members' combined throughput over the last second
+    current_rate_bps: u64,
     members: Vec<GroupMemberInfo>,
 }
 
@@ -22,104 +36,135 @@ struct GroupMemberInfo {
     active_connections: u32,
     daily_bytes: u64,
     monthly_bytes: u64,
+    current_rate_bps: u64,
 }
 
-pub async fn list_groups(State(state): State<AppState>) -> impl IntoResponse {
-    let auth = state.auth_service.read().await;
-    let group_names = auth.user_store().group_names();
-
-    let mut groups = Vec::new();
-    for name in &group_names {
-        let members_users = auth.user_store().users_in_group(name);
-        let mut active_connections: u32 = 0;
-        let mut total_daily_bytes: u64 = 0;
-        let mut total_monthly_bytes: u64 = 0;
-        let mut members = Vec::new();
-
-        for user in &members_users {
-            let conns = state.proxy_engine.user_connections(&user.username);
-            active_connections += conns;
-
-            let (daily, monthly) = if let Some(ref qt) = state.quota_tracker {
-                let usage = qt.get_user_usage(&user.username);
-                (usage.daily_bytes, usage.monthly_bytes)
-            } else {
-                (0, 0)
-            };
-            total_daily_bytes += daily;
-            total_monthly_bytes += monthly;
-
-            members.push(GroupMemberInfo {
-                username: user.username.clone(),
-                active_connections: conns,
-                daily_bytes: daily,
-                monthly_bytes: monthly,
-            });
+/// Groups defined in `[[groups]]` or named by a user, sorted.
+pub(crate) fn group_names(auth: &AuthService) -> Vec<String> {
+    let mut names = auth.user_store().group_names();
+    for group in auth.groups() {
+        if !names.contains(&group.name) {
+            names.push(group.name.clone());
         }
-
-        groups.push(GroupStats {
-            name: name.clone(),
-            member_count: members_users.len(),
-            active_connections,
-            total_daily_bytes,
-            total_monthly_bytes,
-            members,
-        });
     }
-
-    ApiResponse::ok(groups)
+    names.sort();
+    names
 }
 
-pub async fn get_group(
-    State(state): State<AppState>,
-    Path(name): Path<String>,
-) -> impl IntoResponse {
-    let auth = state.auth_service.read().await;
-    let members_users = auth.user_store().users_in_group(&name);
-
-    if members_users.is_empty() {
-        // Check if the group exists at all (might have no current users)
-        let group_names = auth.user_store().group_names();
-        if !group_names.contains(&name) {
-            return ApiResponse::err(StatusCode::NOT_FOUND, format!("group '{}' not found", name))
-                .into_response();
-        }
-    }
-
+fn group_stats(state: &AppState, auth: &AuthService, name: &str) -> GroupStats {
+    let configured = auth.groups().iter().find(|g| g.name == name);
+    let members_users = auth.user_store().users_in_group(name);
     let mut active_connections: u32 = 0;
     let mut total_daily_bytes: u64 = 0;
     let mut total_monthly_bytes: u64 = 0;
+    let mut current_rate_bps: u64 = 0;
     let mut members = Vec::new();
 
     for user in &members_users {
         let conns = state.proxy_engine.user_connections(&user.username);
         active_connections += conns;
 
-        let (daily, monthly) = if let Some(ref qt) = state.quota_tracker {
+        let (daily, monthly, rate) = if let Some(ref qt) = state.quota_tracker {
             let usage = qt.get_user_usage(&user.username);
-            (usage.daily_bytes, usage.monthly_bytes)
+            (
+                usage.daily_bytes,
+                usage.monthly_bytes,
+                usage.current_rate_bps,
+            )
         } else {
-            (0, 0)
+            (0, 0, 0)
         };
         total_daily_bytes += daily;
         total_monthly_bytes += monthly;
+        current_rate_bps += rate;
 
         members.push(GroupMemberInfo {
             username: user.username.clone(),
             active_connections: conns,
             daily_bytes: daily,
             monthly_bytes: monthly,
+            current_rate_bps: rate,
         });
     }
+    members.sort_by(|a, b| a.username.cmp(&b.username));
 
-    let stats = GroupStats {
-        name,
+    GroupStats {
+        name: name.to_string(),
+        inherits: configured.and_then(|g| g.inherits.clone()),
+        policy: configured.map(GroupPolicy::of).unwrap_or_default(),
         member_count: members_users.len(),
         active_connections,
         total_daily_bytes,
         total_monthly_bytes,
+        current_rate_bps,
         members,
+    }
+}
+
+fn not_found(name: &str) -> axum::response::Response {
+    ApiResponse::err(StatusCode::NOT_FOUND, format!("group '{}' not found", name)).into_response()
+}
+
+pub async fn list_groups(State(state): State<AppState>) -> impl IntoResponse {
+    let auth = state.auth_service.read().await;
+    let groups: Vec<GroupStats> = group_names(&auth)
+        .iter()
+        .map(|name| group_stats(&state, &auth, name))
+        .collect();
+
+    ApiResponse::ok(groups)
+}
+
+pub async fn get_group(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let auth = state.auth_service.read().await;
+    if !group_names(&auth).contains(&name) {
+        return not_found(&name);
+    }
+
+    ApiResponse::ok(group_stats(&state, &auth, &name)).into_response()
+}
+
+/// PUT /api/groups/:name/policy — write the group's bandwidth, connection,
+/// forwarding and shell policy to the config file and apply it, like a
+/// reload. Fields left out or `null` are removed so members inherit them.
+pub async fn set_group_policy(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(name): Path<String>,
+    Json(policy): Json<GroupPolicy>,
+) -> impl IntoResponse {
+    let Some(path) = state.config_path.clone() else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CONFIG_FILE).into_response();
     };
+    if !group_names(&*state.auth_service.read().await).contains(&name) {
+        return not_found(&name);
+    }
 
-    ApiResponse::ok(stats).into_response()
+    // Refused before anything is written when the result would not load
+    let edit = {
+        let name = name.clone();
+        let policy = policy.clone();
+        move |content: &str| persist::apply_group_policy(content, &name, &policy)
+    };
+    let (updated, new_config) = match reload::edit_config_file(path, edit).await {
+        Ok(edited) => edited,
+        Err(resp) => return resp,
+    };
+    if let Err(e) = reload::apply_config(&state, &new_config, &updated, "api").await {
+        return ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+    warn!(group = %name, by = %principal.name, ?policy, "Group policy updated");
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::group_policy_updated(
+            &name,
+            &policy,
+            &principal.name,
+        ));
+    }
+
+    let auth = state.auth_service.read().await;
+    ApiResponse::ok(group_stats(&state, &auth, &name)).into_response()
 }
@@ -0,0 +1,92 @@
+//! Per-IP rate limiting and token brute-force protection for `/api/*`.
+//!
+//! Invalid credentials are fed into the shared [`SecurityManager`] ban logic
+//! (same threshold, window, whitelist and audit events as SSH auth failures),
+//! and each consecutive failure from an IP doubles the response delay.
+//!
+//! [`SecurityManager`]: crate::security::SecurityManager
+
+use crate::config::types::ApiConfig;
+use crate::security::rate_limit::IpRateLimiter;
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Upper bound for the progressive failure delay.
+pub const MAX_FAILURE_DELAY: Duration = Duration::from_secs(5);
+
+/// Consecutive-failure counters are forgotten after this much quiet time.
+const FAILURE_RESET_AFTER: Duration = Duration::from_secs(600);
+
+/// Maximum number of IPs tracked for failure delays and rate limiting.
+const MAX_TRACKED_IPS: usize = 100_000;
+
+pub struct ApiGuard {
+    limiter: IpRateLimiter,
+    /// IP -> (consecutive failures, last failure)
+    failures: DashMap<IpAddr, (u32, Instant)>,
+    base_delay: Duration,
+}
+
+impl ApiGuard {
+    pub fn new(config: &ApiConfig) -> Self {
+        Self {
+            limiter: IpRateLimiter::new(config.rate_limit_per_minute, MAX_TRACKED_IPS),
+            failures: DashMap::new(),
+            base_delay: Duration::from_millis(config.auth_failure_delay_ms),
+        }
+    }
+
+    /// Returns true if the IP is within its per-minute request budget.
+    pub fn check_rate(&self, ip: &IpAddr) -> bool {
+        self.limiter.check(ip)
+    }
+
+    /// Record an invalid token and return how long to stall the response.
+    pub fn record_failure(&self, ip: &IpAddr) -> Duration {
+        let now = Instant::now();
+        if !self.failures.contains_key(ip) && self.failures.len() >= MAX_TRACKED_IPS {
+            self.cleanup_stale();
+            if self.failures.len() >= MAX_TRACKED_IPS {
+                return MAX_FAILURE_DELAY.min(self.base_delay);
+            }
+        }
+
+        let mut entry = self.failures.entry(*ip).or_insert((0, now));
+        if now.duration_since(entry.1) >= FAILURE_RESET_AFTER {
+            entry.0 = 0;
+        }
+        entry.0 = entry.0.saturating_add(1);
+        entry.1 = now;
+        self.delay_for(entry.0)
+    }
+
+    /// Clear the failure streak after a successful authentication.
+    pub fn record_success(&self, ip: &IpAddr) {
+        self.failures.remove(ip);
+    }
+
+    /// Delay for the `n`-th consecutive failure: `base * 2^(n-1)`, capped.
+    pub fn delay_for(&self, consecutive_failures: u32) -> Duration {
+        if self.base_delay.is_zero() || consecutive_failures == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32 << (consecutive_failures - 1).min(16);
+        self.base_delay
+            .saturating_mul(factor)
+            .min(MAX_FAILURE_DELAY)
+    }
+
+    /// Number of consecutive failures currently recorded for an IP.
+    pub fn consecutive_failures(&self, ip: &IpAddr) -> u32 {
+        self.failures.get(ip).map(|e| e.0).unwrap_or(0)
+    }
+
+    /// Drop idle failure counters and rate limiter entries.
+    pub fn cleanup_stale(&self) {
+        let now = Instant::now();
+        self.failures
+            .retain(|_, (_, last)| now.duration_since(*last) < FAILURE_RESET_AFTER);
+        self.limiter.cleanup_stale(FAILURE_RESET_AFTER);
+    }
+}
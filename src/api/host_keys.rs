@@ -0,0 +1,11 @@
+use super::{ApiResponse, AppState};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+
+/// GET /api/host-keys — served host keys and rotation status, for
+/// distributing the new key to clients before the old one retires.
+pub async fn list_host_keys(State(state): State<AppState>) -> impl IntoResponse {
+    let Some(ref host_keys) = state.host_keys else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "host keys not available").into_response();
+    };
+    ApiResponse::ok(host_keys.status(std::time::SystemTime::now())).into_response()
+}
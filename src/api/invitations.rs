@@ -0,0 +1,341 @@
+//! Invitation links (`/api/invitations`, `/api/invite`).
+//!
+//! Admins create, list and revoke invitations; the invitee completes one on
+//! `/dashboard/invite` through the two unauthenticated `/api/invite` routes,
+//! which take the link's token instead of credentials. See
+//! [`crate::auth::invitation`].
+
+use super::rbac::Principal;
+use super::{reload, ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::auth::invitation::{Invitation, InvitationError};
+use crate::auth::self_service::{validate_new_password, PasswordChangeError};
+use crate::auth::{password_policy, pubkey};
+use crate::config::import::{append_users, ImportedUser};
+use crate::config::persist;
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+const DISABLED: &str = "invitations are disabled (invitations.enabled = false)";
+const NO_CONFIG_FILE: &str = "invitations need a config file (not available in env-var mode)";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateInvitationRequest {
+    pub username: String,
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Overrides `invitations.ttl_hours` for this link
+    #[serde(default)]
+    pub ttl_hours: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedInvitation {
+    #[serde(flatten)]
+    pub invitation: Invitation,
+    /// Secret part of the link, shown once
+    pub token: String,
+    /// Link to hand to the invitee (relative when `invitations.public_url`
+    /// is unset)
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+pub struct InviteTokenRequest {
+    pub token: String,
+}
+
+#[derive(Serialize)]
+pub struct InviteInfo {
+    pub username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInviteRequest {
+    pub token: String,
+    pub password: String,
+    /// One `authorized_keys` line
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AcceptedInvite {
+    pub username: String,
+}
+
+/// POST /api/invitations — invite a new user. The account is only created
+/// when the invitee completes the link.
+pub async fn create_invitation(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(body): Json<CreateInvitationRequest>,
+) -> Response {
+    if state.config_path.is_none() {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CONFIG_FILE).into_response();
+    }
+    let (settings, exists, group_known) = {
+        let auth = state.auth_service.read().await;
+        (
+            auth.invitations().clone(),
+            auth.user_store().get(&body.username).is_some(),
+            body.group
+                .as_ref()
+                .is_none_or(|group| auth.groups().iter().any(|g| &g.name == group)),
+        )
+    };
+    if !settings.enabled {
+        return ApiResponse::err(StatusCode::NOT_FOUND, DISABLED).into_response();
+    }
+    let ttl_hours = body.ttl_hours.unwrap_or(settings.ttl_hours);
+    if !(1..=8_760).contains(&ttl_hours) {
+        return ApiResponse::err(
+            StatusCode::BAD_REQUEST,
+            "ttl_hours must be between 1 and 8760",
+        )
+        .into_response();
+    }
+    if exists {
+        return ApiResponse::err(
+            StatusCode::CONFLICT,
+            format!("user '{}' already exists", body.username),
+        )
+        .into_response();
+    }
+    if !group_known {
+        return ApiResponse::err(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("group '{}' not found", body.group.unwrap_or_default()),
+        )
+        .into_response();
+    }
+
+    let created = state.invitations.create(
+        &body.username,
+        body.group,
+        &principal.name,
+        chrono::Duration::hours(ttl_hours as i64),
+        settings.max_pending,
+    );
+    let (invitation, token) = match created {
+        Ok(created) => created,
+        Err(e) => {
+            let status = match e {
+                InvitationError::InvalidUsername => StatusCode::BAD_REQUEST,
+                InvitationError::AlreadyInvited(_) | InvitationError::TooMany(_) => {
+                    StatusCode::CONFLICT
+                }
+            };
+            return ApiResponse::err(status, e.to_string()).into_response();
+        }
+    };
+
+    let base = settings.public_url.as_deref().unwrap_or("");
+    let url = format!("{}/dashboard/invite#{}", base.trim_end_matches('/'), token);
+    info!(
+        user = %invitation.username,
+        id = %invitation.id,
+        by = %principal.name,
+        expires_at = %invitation.expires_at,
+        "Invitation created"
+    );
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::user_invited(&invitation));
+    }
+    ApiResponse::ok_with_status(
+        StatusCode::CREATED,
+        CreatedInvitation {
+            invitation,
+            token,
+            url,
+        },
+    )
+    .into_response()
+}
+
+/// GET /api/invitations — pending invitations, oldest first.
+pub async fn list_invitations(State(state): State<AppState>) -> impl IntoResponse {
+    ApiResponse::ok(state.invitations.list())
+}
+
+/// DELETE /api/invitations/:id — withdraw an unused invitation.
+pub async fn revoke_invitation(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.invitations.revoke(&id) {
+        Some(invitation) => {
+            info!(
+                user = %invitation.username,
+                id = %id,
+                by = %principal.name,
+                "Invitation revoked"
+            );
+            ApiResponse::ok(invitation).into_response()
+        }
+        None => ApiResponse::err(StatusCode::NOT_FOUND, "invitation not found").into_response(),
+    }
+}
+
+/// Reject disabled invitations and unknown tokens. A wrong token counts as
+/// an API auth failure.
+async fn open_invitation(
+    state: &AppState,
+    peer: Option<SocketAddr>,
+    token: &str,
+) -> Result<Invitation, Response> {
+    if !state.auth_service.read().await.invitations().enabled {
+        return Err(ApiResponse::err(StatusCode::NOT_FOUND, DISABLED).into_response());
+    }
+    match state.invitations.find(token) {
+        Some(invitation) => Ok(invitation),
+        None => Err(super::reject_invalid_credentials(state, peer).await),
+    }
+}
+
+/// POST /api/invite — what an invitation link is for (unauthenticated, the
+/// token is the credential).
+pub async fn inspect_invite(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(body): Json<InviteTokenRequest>,
+) -> Response {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    match open_invitation(&state, peer, &body.token).await {
+        Ok(invitation) => ApiResponse::ok(InviteInfo {
+            username: invitation.username,
+            group: invitation.group,
+            expires_at: invitation.expires_at,
+        })
+        .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// POST /api/invite/accept — create the invited account with the chosen
+/// password (and public key), written to the config file and applied like a
+/// reload. The link is used up once the account exists.
+pub async fn accept_invite(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(body): Json<AcceptInviteRequest>,
+) -> Response {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    let password = Zeroizing::new(body.password);
+    let invitation = match open_invitation(&state, peer, &body.token).await {
+        Ok(invitation) => invitation,
+        Err(resp) => return resp,
+    };
+    let Some(path) = state.config_path.clone() else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CONFIG_FILE).into_response();
+    };
+
+    let (policy, hash_settings) = {
+        let auth = state.auth_service.read().await;
+        (auth.password_policy().clone(), auth.hash_settings())
+    };
+    if let Err(e) = validate_new_password("", &password)
+        .and_then(|()| password_policy::check(&policy, &password).map_err(Into::into))
+    {
+        return ApiResponse::err(StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+    let public_key = body
+        .public_key
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty());
+    if let Some(ref key) = public_key {
+        if key.lines().count() != 1 || pubkey::parse_authorized_key(key).is_err() {
+            return ApiResponse::err(StatusCode::BAD_REQUEST, "invalid public key").into_response();
+        }
+    }
+    let password_hash =
+        match tokio::task::spawn_blocking(move || hash_settings.hash(&password)).await {
+            Ok(Ok(hash)) => hash,
+            _ => {
+                return ApiResponse::err(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    PasswordChangeError::Hash.to_string(),
+                )
+                .into_response()
+            }
+        };
+
+    if state
+        .auth_service
+        .read()
+        .await
+        .user_store()
+        .get(&invitation.username)
+        .is_some()
+    {
+        return ApiResponse::err(
+            StatusCode::CONFLICT,
+            format!("user '{}' already exists", invitation.username),
+        )
+        .into_response();
+    }
+    let user = ImportedUser {
+        username: invitation.username.clone(),
+        password_hash: Some(password_hash),
+        authorized_keys: public_key.iter().cloned().collect(),
+    };
+    let group = invitation.group.clone();
+    let edit = move |content: &str| {
+        let (updated, added) = append_users(content, std::slice::from_ref(&user))?;
+        if added.is_empty() {
+            anyhow::bail!("user '{}' already exists", user.username);
+        }
+        let changed_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let updated =
+            persist::set_user_field(&updated, &user.username, "password_changed_at", &changed_at)?;
+        match group {
+            Some(ref group) => persist::set_user_field(&updated, &user.username, "group", group),
+            None => Ok(updated),
+        }
+    };
+    let (updated, new_config) = match reload::edit_config_file(path, edit).await {
+        Ok(edited) => edited,
+        Err(resp) => return resp,
+    };
+    if let Err(e) = reload::apply_config(&state, &new_config, &updated, "api").await {
+        return ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+
+    state.invitations.redeem(&body.token);
+    super::accept_credentials(&state, peer);
+    let source_ip = peer.map(|a| a.ip().to_string()).unwrap_or_default();
+    warn!(
+        user = %invitation.username,
+        id = %invitation.id,
+        ip = %source_ip,
+        "User created from invitation"
+    );
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::invitation_accepted(
+            &invitation,
+            &source_ip,
+            public_key.is_some(),
+        ));
+    }
+    ApiResponse::ok_with_status(
+        StatusCode::CREATED,
+        AcceptedInvite {
+            username: invitation.username,
+        },
+    )
+    .into_response()
+}
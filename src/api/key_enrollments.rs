@@ -0,0 +1,137 @@
+use super::rbac::Principal;
+use super::{ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::security::key_enrollment::EnrollmentStatus;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+};
+use tracing::{error, info, warn};
+
+const DISABLED: &str = "key enrollment is disabled (key_enrollment.enabled = false)";
+
+/// GET /api/key-enrollments — enrollment requests, oldest first.
+pub async fn list_key_enrollments(State(state): State<AppState>) -> impl IntoResponse {
+    let security = state.security.read().await;
+    match security.key_enrollment() {
+        Some(queue) => ApiResponse::ok(queue.list()).into_response(),
+        None => ApiResponse::err(StatusCode::NOT_FOUND, DISABLED).into_response(),
+    }
+}
+
+/// POST /api/key-enrollments/:id/approve — add the key to the user's
+/// `authorized_keys` (config file first, then the live user store) and let
+/// the held login through.
+pub async fn approve_key_enrollment(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let request = {
+        let security = state.security.read().await;
+        let Some(queue) = security.key_enrollment() else {
+            return ApiResponse::err(StatusCode::NOT_FOUND, DISABLED).into_response();
+        };
+        match queue.get(&id) {
+            Some(request) => request,
+            None => {
+                return ApiResponse::err(StatusCode::NOT_FOUND, "enrollment request not found")
+                    .into_response()
+            }
+        }
+    };
+    if request.status != EnrollmentStatus::Pending {
+        return ApiResponse::err(StatusCode::CONFLICT, "enrollment request already decided")
+            .into_response();
+    }
+
+    let key_line = format!("{} s5-enrolled-{}", request.public_key, request.id);
+    match state.config_path.clone() {
+        Some(path) => {
+            let user = request.username.clone();
+            let line = key_line.clone();
+            let persisted = tokio::task::spawn_blocking(move || {
+                crate::config::persist::add_user_authorized_key(&path, &user, &line)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r);
+            if let Err(e) = persisted {
+                error!(
+                    request_id = %id,
+                    user = %request.username,
+                    error = %format!("{:#}", e),
+                    "Failed to persist enrolled key"
+                );
+                return ApiResponse::err(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to persist key: {:#}", e),
+                )
+                .into_response();
+            }
+        }
+        None => {
+            warn!(
+                user = %request.username,
+                "No config file to persist enrolled key, applying in memory only"
+            );
+        }
+    }
+    if !state
+        .auth_service
+        .write()
+        .await
+        .add_authorized_key(&request.username, &key_line)
+    {
+        return ApiResponse::err(StatusCode::CONFLICT, "user no longer exists").into_response();
+    }
+
+    decide(&state, &id, true, &principal).await
+}
+
+/// POST /api/key-enrollments/:id/reject — refuse the key; further logins
+/// with it fail without a new request until the request expires.
+pub async fn reject_key_enrollment(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    decide(&state, &id, false, &principal).await
+}
+
+async fn decide(
+    state: &AppState,
+    id: &str,
+    approved: bool,
+    principal: &Principal,
+) -> axum::response::Response {
+    let security = state.security.read().await;
+    let Some(queue) = security.key_enrollment() else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, DISABLED).into_response();
+    };
+    let Some(request) = queue.decide(id, approved, &principal.name) else {
+        return ApiResponse::err(
+            StatusCode::CONFLICT,
+            "enrollment request not found or already decided",
+        )
+        .into_response();
+    };
+    info!(
+        request_id = %id,
+        user = %request.username,
+        fingerprint = %request.fingerprint,
+        approved,
+        by = %principal.name,
+        "Key enrollment decided"
+    );
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::key_enrollment_decided(
+            &request,
+            approved,
+            &principal.name,
+        ));
+    }
+    ApiResponse::ok(request).into_response()
+}
@@ -1,8 +1,11 @@
+use super::rbac::Principal;
 use super::{ApiResponse, AppState};
+use crate::proxy::close::CloseReason;
 use axum::{
     extract::{Path, State},
+    http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
 
@@ -20,13 +23,19 @@ fn default_kick_message() -> String {
 pub struct KickResponse {
     pub kicked: bool,
     pub username: String,
+    /// Relayed sessions closed (`admin_kill`)
+    pub sessions_closed: usize,
 }
 
 pub async fn kick_user(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Path(username): Path<String>,
     body: Option<Json<KickRequest>>,
 ) -> impl IntoResponse {
+    if !principal.sees(&username) {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "user not found").into_response();
+    }
     let message = body
         .map(|b| b.message.clone())
         .unwrap_or_else(default_kick_message);
@@ -36,5 +45,13 @@ pub async fn kick_user(
     } else {
         false
     };
-    ApiResponse::ok(KickResponse { kicked, username })
+    let sessions_closed = state
+        .proxy_engine
+        .kill_sessions(CloseReason::AdminKill, |s| s.username == username);
+    ApiResponse::ok(KickResponse {
+        kicked: kicked || sessions_closed > 0,
+        username,
+        sessions_closed,
+    })
+    .into_response()
 }
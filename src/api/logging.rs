@@ -0,0 +1,77 @@
+use super::rbac::Principal;
+use super::{ApiResponse, AppState};
+use crate::log_filter::{self, LogFilterError};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde::Deserialize;
+use tracing::warn;
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// `EnvFilter` directives: a level (`debug`) and/or per-module
+    /// directives (`info,s5::proxy=trace`)
+    pub level: String,
+}
+
+/// GET /api/logging/level — the filter in effect and the startup filter.
+pub async fn get_log_level() -> impl IntoResponse {
+    match log_filter::level() {
+        Some(level) => ApiResponse::ok(level).into_response(),
+        None => ApiResponse::err(
+            StatusCode::NOT_FOUND,
+            LogFilterError::NotInstalled.to_string(),
+        )
+        .into_response(),
+    }
+}
+
+/// PUT /api/logging/level — replace the log filter until the next restart.
+pub async fn set_log_level(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(body): Json<LogLevelRequest>,
+) -> impl IntoResponse {
+    apply(&state, &principal, log_filter::set_level(&body.level))
+}
+
+/// DELETE /api/logging/level — return to the startup filter.
+pub async fn reset_log_level(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    apply(&state, &principal, log_filter::reset_level())
+}
+
+fn apply(
+    state: &AppState,
+    principal: &Principal,
+    result: Result<String, LogFilterError>,
+) -> axum::response::Response {
+    let previous = match result {
+        Ok(previous) => previous,
+        Err(e @ LogFilterError::Invalid(_)) => {
+            return ApiResponse::err(StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+        Err(e @ LogFilterError::NotInstalled) => {
+            return ApiResponse::err(StatusCode::NOT_FOUND, e.to_string()).into_response()
+        }
+    };
+    let Some(level) = log_filter::level() else {
+        return ApiResponse::err(
+            StatusCode::NOT_FOUND,
+            LogFilterError::NotInstalled.to_string(),
+        )
+        .into_response();
+    };
+    if previous != level.level {
+        warn!(
+            previous = %previous,
+            level = %level.level,
+            by = %principal.name,
+            "Log level changed"
+        );
+        if let Some(ref audit) = state.audit {
+            audit.log_log_level_changed(&previous, &level.level, &principal.name);
+        }
+    }
+    ApiResponse::ok(level).into_response()
+}
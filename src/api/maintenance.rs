@@ -1,17 +1,39 @@
 use super::{ApiResponse, AppState};
-use axum::{extract::State, response::IntoResponse};
-use serde::Serialize;
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
 
 #[derive(Serialize)]
 pub struct MaintenanceStatus {
     pub maintenance: bool,
 }
 
-pub async fn toggle_maintenance(State(state): State<AppState>) -> impl IntoResponse {
-    let prev = state
-        .maintenance
-        .fetch_xor(true, std::sync::atomic::Ordering::SeqCst);
-    let new_state = !prev;
+/// Optional body of `POST /api/maintenance`; without it the mode is toggled.
+#[derive(Debug, Default, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: Option<bool>,
+}
+
+/// POST /api/maintenance — turn maintenance mode on or off. While it is on,
+/// new non-admin SSH logins are disconnected with `server.maintenance_message`
+/// and established sessions carry on.
+pub async fn toggle_maintenance(
+    State(state): State<AppState>,
+    body: Option<Json<MaintenanceRequest>>,
+) -> impl IntoResponse {
+    let requested = body.and_then(|Json(b)| b.enabled);
+    let new_state = match requested {
+        Some(enabled) => {
+            let prev = state.maintenance.swap(enabled, Ordering::SeqCst);
+            if prev == enabled {
+                return ApiResponse::ok(MaintenanceStatus {
+                    maintenance: enabled,
+                });
+            }
+            enabled
+        }
+        None => !state.maintenance.fetch_xor(true, Ordering::SeqCst),
+    };
 
     if let Some(ref audit) = state.audit {
         audit.log_maintenance_toggled(new_state, "api");
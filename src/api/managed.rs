@@ -0,0 +1,223 @@
+//! Delegated group administration (`/api/self/managed/*`).
+//!
+//! A user listed in a group's `managers` reaches these routes with their own
+//! self-service credentials and may view the members' usage and sessions,
+//! reset their quota counters and disable or re-enable their logins. Only
+//! direct members (`group = "<name>"`) are in reach: never other groups,
+//! child groups or server-wide settings.
+
+use super::rbac::Principal;
+use super::self_service::SelfUser;
+use super::{reload, ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::config::persist;
+use crate::config::types::{DashboardRole, UserRole};
+use crate::proxy::close::CloseReason;
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+const NO_CONFIG_FILE: &str = "disabling users needs a config file (not available in env-var mode)";
+
+#[derive(Serialize)]
+pub struct ManagedGroup {
+    pub name: String,
+    pub members: Vec<ManagedMember>,
+}
+
+#[derive(Serialize)]
+pub struct ManagedMember {
+    pub username: String,
+    pub disabled: bool,
+    pub active_connections: u32,
+    pub daily_bytes: u64,
+    pub monthly_bytes: u64,
+}
+
+#[derive(Deserialize)]
+pub struct SetDisabledRequest {
+    pub disabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct SetDisabledResponse {
+    pub username: String,
+    pub disabled: bool,
+    /// Relayed sessions closed (`admin_kill`)
+    pub sessions_closed: usize,
+}
+
+/// Refuse unless `username` is a member of a group `manager` manages.
+/// Unknown users and users of another group get the same 404; managers
+/// cannot act on their own account, on another manager or on a member
+/// holding an admin or dashboard role.
+async fn check_member(state: &AppState, manager: &str, username: &str) -> Result<(), Response> {
+    let auth = state.auth_service.read().await;
+    if !auth.manages(manager, username) {
+        return Err(ApiResponse::err(
+            StatusCode::NOT_FOUND,
+            format!("user '{}' not found", username),
+        )
+        .into_response());
+    }
+    if username == manager {
+        return Err(ApiResponse::err(
+            StatusCode::FORBIDDEN,
+            "managers cannot act on their own account",
+        )
+        .into_response());
+    }
+    let privileged = !auth.managed_groups(username).is_empty()
+        || auth
+            .user_store()
+            .get(username)
+            .is_some_and(|u| u.role == UserRole::Admin)
+        || state
+            .dashboard_accounts
+            .iter()
+            .any(|a| a.username == username);
+    if privileged {
+        return Err(ApiResponse::err(
+            StatusCode::FORBIDDEN,
+            format!("user '{}' is a manager or holds an admin role", username),
+        )
+        .into_response());
+    }
+    Ok(())
+}
+
+/// GET /api/self/managed — the groups the caller manages and their members.
+pub async fn list_managed(
+    State(state): State<AppState>,
+    Extension(SelfUser(manager)): Extension<SelfUser>,
+) -> impl IntoResponse {
+    let auth = state.auth_service.read().await;
+    let groups: Vec<ManagedGroup> = auth
+        .managed_groups(&manager)
+        .into_iter()
+        .map(|group| {
+            let mut members: Vec<ManagedMember> = auth
+                .user_store()
+                .users_in_group(&group.name)
+                .into_iter()
+                .map(|user| {
+                    let usage = state
+                        .quota_tracker
+                        .as_ref()
+                        .map(|qt| qt.get_user_usage(&user.username));
+                    ManagedMember {
+                        username: user.username.clone(),
+                        disabled: user.disabled,
+                        active_connections: state.proxy_engine.user_connections(&user.username),
+                        daily_bytes: usage.as_ref().map_or(0, |u| u.daily_bytes),
+                        monthly_bytes: usage.as_ref().map_or(0, |u| u.monthly_bytes),
+                    }
+                })
+                .collect();
+            members.sort_by(|a, b| a.username.cmp(&b.username));
+            ManagedGroup {
+                name: group.name.clone(),
+                members,
+            }
+        })
+        .collect();
+    ApiResponse::ok(groups)
+}
+
+/// GET /api/self/managed/sessions — live sessions of the caller's members.
+pub async fn managed_sessions(
+    State(state): State<AppState>,
+    Extension(SelfUser(manager)): Extension<SelfUser>,
+) -> impl IntoResponse {
+    let auth = state.auth_service.read().await;
+    let sessions: Vec<_> = auth
+        .managed_groups(&manager)
+        .into_iter()
+        .flat_map(|group| auth.user_store().users_in_group(&group.name))
+        .flat_map(|user| state.proxy_engine.get_user_sessions(&user.username))
+        .map(super::sessions::to_response)
+        .collect();
+    ApiResponse::ok(sessions)
+}
+
+/// POST /api/self/managed/users/:username/quota/reset — zero some or all of
+/// a member's quota counters (same body as `/api/users/:username/quota/reset`).
+pub async fn reset_member_quota(
+    State(state): State<AppState>,
+    Extension(SelfUser(manager)): Extension<SelfUser>,
+    Path(username): Path<String>,
+    body: Bytes,
+) -> Response {
+    if let Err(resp) = check_member(&state, &manager, &username).await {
+        return resp;
+    }
+    // Audited under the manager's name
+    let principal = Principal::new(&manager, DashboardRole::Operator);
+    super::quotas::reset_quota_counters(State(state), Extension(principal), Path(username), body)
+        .await
+        .into_response()
+}
+
+/// PUT /api/self/managed/users/:username/disabled — turn a member's logins
+/// off or back on. Written to the config file and applied like a reload;
+/// disabling also closes the member's sessions.
+pub async fn set_member_disabled(
+    State(state): State<AppState>,
+    Extension(SelfUser(manager)): Extension<SelfUser>,
+    Path(username): Path<String>,
+    Json(body): Json<SetDisabledRequest>,
+) -> Response {
+    if let Err(resp) = check_member(&state, &manager, &username).await {
+        return resp;
+    }
+    let Some(path) = state.config_path.clone() else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_CONFIG_FILE).into_response();
+    };
+
+    let edit = {
+        let username = username.clone();
+        move |content: &str| persist::apply_user_disabled(content, &username, body.disabled)
+    };
+    let (updated, new_config) = match reload::edit_config_file(path, edit).await {
+        Ok(edited) => edited,
+        Err(resp) => return resp,
+    };
+    if let Err(e) = reload::apply_config(&state, &new_config, &updated, "api").await {
+        return ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+
+    let mut sessions_closed = 0;
+    if body.disabled {
+        state.user_sessions.remove_principal(&username);
+        if let Some(ref tx) = state.broadcast_tx {
+            let _ = tx.send((
+                "__KICK__:Account disabled".to_string(),
+                vec![username.clone()],
+            ));
+        }
+        sessions_closed = state
+            .proxy_engine
+            .kill_sessions(CloseReason::AdminKill, |s| s.username == username);
+    }
+    warn!(user = %username, by = %manager, disabled = body.disabled, "User login state changed");
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::account_disabled(
+            &username,
+            body.disabled,
+            &manager,
+        ));
+    }
+
+    ApiResponse::ok(SetDisabledResponse {
+        username,
+        disabled: body.disabled,
+        sessions_closed,
+    })
+    .into_response()
+}
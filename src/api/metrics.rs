@@ -0,0 +1,13 @@
+use super::{ApiResponse, AppState};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+
+/// GET /api/metrics — every Prometheus metric as structured JSON, for
+/// monitoring that does not scrape `/metrics`.
+pub async fn metrics_snapshot(State(state): State<AppState>) -> impl IntoResponse {
+    match crate::metrics::json::snapshot(&state.metrics) {
+        Ok(snapshot) => ApiResponse::ok(snapshot).into_response(),
+        Err(_) => {
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "encoding error").into_response()
+        }
+    }
+}
@@ -1,39 +1,64 @@
 pub mod backup;
 pub mod bans;
 pub mod broadcast;
+pub mod capture;
+pub mod cluster;
+pub mod config_history;
 pub mod connections;
+pub mod cors;
 pub mod dashboard;
+pub mod debug;
+pub mod flows;
 pub mod groups;
+pub mod guard;
+pub mod host_keys;
+pub mod invitations;
+pub mod key_enrollments;
 pub mod kick;
+pub mod logging;
 pub mod maintenance;
+pub mod managed;
+pub mod metrics;
+pub mod openapi;
 pub mod pagination;
 pub mod quotas;
+pub mod rbac;
 pub mod reload;
+pub mod reports;
+pub mod security;
+pub mod self_service;
+pub mod session;
 pub mod sessions;
 pub mod sse;
 pub mod ssh_config;
+pub mod ssh_tunnel;
+#[cfg(unix)]
+pub mod unix_socket;
 pub mod users;
 pub mod ws;
 
 use crate::audit::AuditLogger;
 use crate::auth::AuthService;
+use crate::config::types::{DashboardAccount, DashboardRole, TenantConfig};
 use crate::metrics::MetricsRegistry;
 use crate::proxy::ProxyEngine;
 use crate::quota::QuotaTracker;
 use crate::security::SecurityManager;
 use crate::webhooks::WebhookDispatcher;
 use axum::{
-    extract::{DefaultBodyLimit, MatchedPath, State},
+    extract::{ConnectInfo, DefaultBodyLimit, MatchedPath, State},
     http::{header, StatusCode},
     middleware::{self, Next},
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use dashmap::DashMap;
 use prometheus_client::encoding::text::encode;
 use serde::Serialize;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::info;
@@ -137,6 +162,99 @@ pub struct AppState {
     pub ssh_listen_addr: Option<String>,
     pub quota_tracker: Option<Arc<QuotaTracker>>,
     pub webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+    /// Listener/config/drain flags for `/readyz` (None = not tracked, treated as ready)
+    pub readiness: Option<Arc<Readiness>>,
+    /// Per-IP rate limiting and invalid-token delays (None = disabled)
+    pub api_guard: Option<Arc<guard::ApiGuard>>,
+    /// Normalized origins allowed for cross-origin requests (`api.allowed_origins`)
+    pub allowed_origins: Arc<Vec<String>>,
+    /// Dashboard login sessions (cookie-backed)
+    pub sessions: Arc<session::SessionStore>,
+    /// Self-service page sessions of SSH users (`s5_user_session` cookie);
+    /// the management routes never accept them
+    pub user_sessions: Arc<session::SessionStore>,
+    /// Role-bearing dashboard accounts (`[[api.accounts]]`)
+    pub dashboard_accounts: Arc<Vec<DashboardAccount>>,
+    /// `[[tenants]]`, for tenant API tokens
+    pub tenants: Arc<Vec<TenantConfig>>,
+    /// Pending invitation links (`/api/invitations`), kept in memory only
+    pub invitations: Arc<crate::auth::invitation::InvitationStore>,
+    /// Connection flow store for `/api/flows` (None = `[logging.flows]` disabled)
+    pub flow_log: Option<Arc<crate::flows::FlowLog>>,
+    /// SSH host keys for `/api/host-keys`, reloaded with the config
+    pub host_keys: Option<Arc<crate::ssh::host_keys::HostKeyRing>>,
+    /// Applied configurations for `/api/config/history` (None = no config file)
+    pub config_history: Option<Arc<crate::config::history::ConfigHistory>>,
+    /// Shared state backend for `/api/cluster` (None = `[cluster]` disabled)
+    pub cluster: Option<Arc<crate::cluster::Cluster>>,
+    /// Hands `/ssh` WebSocket tunnels to the SSH listener (None = `api.ssh_websocket` off)
+    pub ssh_tunnel: Option<ssh_tunnel::TunnelSender>,
+    /// Reverse proxies whose `X-Forwarded-For` is believed for `/ssh`
+    /// tunnels (`api.trusted_proxies`)
+    pub trusted_proxies: Arc<Vec<ipnet::IpNet>>,
+}
+
+/// Process readiness flags, updated by the server supervisor and reported
+/// by the unauthenticated `/readyz` probe.
+#[derive(Debug)]
+pub struct Readiness {
+    ssh_listener_bound: AtomicBool,
+    /// Observer: no SSH listener to wait for
+    observer: AtomicBool,
+    config_valid: AtomicBool,
+    draining: AtomicBool,
+}
+
+impl Readiness {
+    /// Initial state: config loaded and valid, SSH listener not bound yet.
+    pub fn new() -> Self {
+        Self {
+            ssh_listener_bound: AtomicBool::new(false),
+            observer: AtomicBool::new(false),
+            config_valid: AtomicBool::new(true),
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_ssh_listener_bound(&self, bound: bool) {
+        self.ssh_listener_bound.store(bound, Ordering::Relaxed);
+    }
+
+    /// A read-only observer runs no SSH listener.
+    pub fn set_observer(&self) {
+        self.observer.store(true, Ordering::Relaxed);
+    }
+
+    /// Record the outcome of the last config (re)load.
+    pub fn set_config_valid(&self, valid: bool) {
+        self.config_valid.store(valid, Ordering::Relaxed);
+    }
+
+    pub fn set_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn ssh_listener_bound(&self) -> bool {
+        self.ssh_listener_bound.load(Ordering::Relaxed)
+    }
+
+    pub fn observer(&self) -> bool {
+        self.observer.load(Ordering::Relaxed)
+    }
+
+    pub fn config_valid(&self) -> bool {
+        self.config_valid.load(Ordering::Relaxed)
+    }
+
+    pub fn draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Start the metrics/health HTTP server with graceful shutdown support.
@@ -153,7 +271,9 @@ pub async fn start_metrics_server(
         .route("/readyz", get(metrics_readyz_handler))
         .with_state((metrics, maintenance));
 
-    let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+    let listener = crate::handover::global()
+        .bind("metrics", listen_addr)
+        .await?;
     info!(addr = %listen_addr, "Metrics server listening");
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown.cancelled_owned())
@@ -170,7 +290,11 @@ async fn metrics_handler(
     }
     (
         StatusCode::OK,
-        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        // OpenMetrics is required for exemplars (correlation IDs on histograms)
+        [(
+            header::CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
         buffer,
     )
         .into_response()
@@ -199,6 +323,9 @@ async fn metrics_readyz_handler(
             auth: "ok", // Not available on metrics server; assume ok
             metrics: "ok",
             maintenance: if ready { "disabled" } else { "enabled" },
+            ssh_listener: "unchecked",
+            config: "unchecked",
+            drain: "unchecked",
         },
     };
 
@@ -223,6 +350,9 @@ struct ReadyzChecks {
     auth: &'static str,
     metrics: &'static str,
     maintenance: &'static str,
+    ssh_listener: &'static str,
+    config: &'static str,
+    drain: &'static str,
 }
 
 async fn readyz_handler(State(state): State<AppState>) -> impl IntoResponse {
@@ -239,7 +369,19 @@ async fn readyz_handler(State(state): State<AppState>) -> impl IntoResponse {
 
     let maintenance_check = !maint;
 
-    let all_ok = auth_ok && metrics_ok && maintenance_check;
+    // Server-tracked flags; without a tracker (embedded/test use) they pass
+    let readiness = state.readiness.as_deref();
+    let observer = readiness.is_some_and(|r| r.observer());
+    let ssh_ok = observer || readiness.is_none_or(|r| r.ssh_listener_bound());
+    let config_ok = readiness.is_none_or(|r| r.config_valid());
+    let drain_ok = readiness.is_none_or(|r| !r.draining());
+    let tracked = |ok: bool, failed: &'static str| match (readiness, ok) {
+        (None, _) => "unchecked",
+        (Some(_), true) => "ok",
+        (Some(_), false) => failed,
+    };
+
+    let all_ok = auth_ok && metrics_ok && maintenance_check && ssh_ok && config_ok && drain_ok;
 
     let body = ReadyzResponse {
         ready: all_ok,
@@ -251,6 +393,13 @@ async fn readyz_handler(State(state): State<AppState>) -> impl IntoResponse {
             } else {
                 "enabled"
             },
+            ssh_listener: if observer {
+                "observer"
+            } else {
+                tracked(ssh_ok, "not_bound")
+            },
+            config: tracked(config_ok, "reload_failed"),
+            drain: tracked(drain_ok, "draining"),
         },
     };
 
@@ -289,15 +438,94 @@ async fn api_metrics_middleware(
     response
 }
 
+/// Per-IP guard for `/api/*`: rejects banned IPs and enforces the request
+/// rate limit before any authentication is attempted.
+async fn api_guard_middleware(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> impl IntoResponse {
+    let (Some(guard), Some(ConnectInfo(addr))) = (state.api_guard.as_ref(), connect_info) else {
+        return next.run(req).await;
+    };
+    if !req.uri().path().starts_with("/api/") {
+        return next.run(req).await;
+    }
+
+    let ip = addr.ip();
+    if state.security.read().await.is_banned(&ip) {
+        return (StatusCode::FORBIDDEN, "forbidden").into_response();
+    }
+    if !guard.check_rate(&ip) {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, "60")],
+            "too many requests",
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+/// Count an invalid credential against the caller's IP: feeds the shared
+/// auto-ban logic and stalls the response progressively.
+pub(crate) async fn reject_invalid_credentials(
+    state: &AppState,
+    peer: Option<SocketAddr>,
+) -> axum::response::Response {
+    count_invalid_credentials(state, peer).await;
+    (StatusCode::UNAUTHORIZED, "unauthorized").into_response()
+}
+
+/// What [`reject_invalid_credentials`] does before answering, for handlers
+/// that answer otherwise.
+pub(crate) async fn count_invalid_credentials(state: &AppState, peer: Option<SocketAddr>) {
+    if let (Some(guard), Some(addr)) = (state.api_guard.as_ref(), peer) {
+        let ip = addr.ip();
+        let delay = guard.record_failure(&ip);
+        state.security.read().await.record_auth_failure(&ip, None);
+        tracing::warn!(
+            ip = %ip,
+            consecutive = guard.consecutive_failures(&ip),
+            "Invalid API credentials"
+        );
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// The `[[tenants]]` entry whose `api_token` is `provided`.
+pub(crate) fn tenant_for_token<'a>(state: &'a AppState, provided: &[u8]) -> Option<&'a str> {
+    use subtle::ConstantTimeEq;
+    state.tenants.iter().find_map(|tenant| {
+        let expected = tenant.api_token.as_deref()?.as_bytes();
+        (provided.len() == expected.len() && bool::from(provided.ct_eq(expected)))
+            .then_some(tenant.name.as_str())
+    })
+}
+
+/// Clear the caller's failure streak after a successful authentication.
+pub(crate) fn accept_credentials(state: &AppState, peer: Option<SocketAddr>) {
+    if let (Some(guard), Some(addr)) = (state.api_guard.as_ref(), peer) {
+        guard.record_success(&addr.ip());
+    }
+}
+
 /// Bearer token auth middleware.
-/// Accepts `Authorization: Bearer <token>` header, `?token=<token>` query param,
-/// or `?ticket=<ticket>` HMAC ticket (for SSE connections).
-/// The query param fallback is needed for SSE (EventSource can't set headers) and browser dashboard.
+/// Accepts `Authorization: Bearer <token>` header, a dashboard session cookie
+/// (`POST /api/login`), or `?ticket=<ticket>` HMAC ticket (for SSE connections).
+/// The ticket fallback is needed for SSE (EventSource can't set headers).
+/// On success the caller's [`rbac::Principal`] is attached as a request extension.
 async fn auth_middleware(
     State(state): State<AppState>,
-    req: axum::http::Request<axum::body::Body>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut req: axum::http::Request<axum::body::Body>,
     next: Next,
 ) -> impl IntoResponse {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+
     // Defense-in-depth: if token is empty, reject all requests (config validation
     // should prevent this, but guard against misconfiguration).
     if state.api_token.is_empty() {
@@ -317,26 +545,54 @@ async fn auth_middleware(
         if h.starts_with("Bearer ") {
             let provided = &h.as_bytes()[7..];
             if provided.len() == expected.len() && bool::from(provided.ct_eq(expected)) {
+                accept_credentials(&state, peer);
+                req.extensions_mut().insert(rbac::Principal::api_token());
                 return next.run(req).await;
             }
-            return (StatusCode::UNAUTHORIZED, "unauthorized").into_response();
+            if let Some(tenant) = tenant_for_token(&state, provided) {
+                accept_credentials(&state, peer);
+                req.extensions_mut()
+                    .insert(rbac::Principal::tenant_token(tenant));
+                return next.run(req).await;
+            }
+            return reject_invalid_credentials(&state, peer).await;
         }
     }
 
+    // Dashboard session cookie (state-changing requests are CSRF-checked upstream)
+    if let Some(principal) = session::session_principal(&state, req.headers()) {
+        req.extensions_mut().insert(principal);
+        return next.run(req).await;
+    }
+
     // API-001: Only accept HMAC ticket for query-based auth (no raw token in URL).
     // The ?ticket= mechanism uses short-lived HMAC-signed tokens issued via POST /api/sse-ticket.
+    let mut ticket_presented = false;
     if let Some(query) = req.uri().query() {
         for pair in query.split('&') {
             if let Some(value) = pair.strip_prefix("ticket=") {
+                ticket_presented = true;
                 // URL-decode the ticket (encodeURIComponent encodes ':' as '%3A')
                 let decoded = percent_encoding::percent_decode_str(value).decode_utf8_lossy();
-                if verify_sse_ticket(&decoded, &state.api_token) {
+                if let Some((role, tenant)) = verify_sse_ticket_scope(&decoded, &state.api_token) {
+                    let name = if role == DashboardRole::Admin {
+                        rbac::TOKEN_PRINCIPAL
+                    } else {
+                        "sse-ticket"
+                    };
+                    req.extensions_mut()
+                        .insert(rbac::Principal::new(name, role).with_tenant(tenant));
                     return next.run(req).await;
                 }
             }
         }
     }
 
+    // Only wrong credentials count as failures; a bare request without any
+    // is just unauthenticated.
+    if ticket_presented {
+        return reject_invalid_credentials(&state, peer).await;
+    }
     (StatusCode::UNAUTHORIZED, "unauthorized").into_response()
 }
 
@@ -347,6 +603,10 @@ struct StatusInfo {
     active_connections: u32,
     total_users: usize,
     maintenance: bool,
+    /// `server.max_connections*` usage
+    connection_caps: crate::proxy::client_caps::CapsSnapshot,
+    /// `[sandbox]` state, read from the kernel
+    sandbox: crate::sandbox::SandboxStatus,
 }
 
 async fn status_handler(State(state): State<AppState>) -> impl IntoResponse {
@@ -366,6 +626,8 @@ async fn status_handler(State(state): State<AppState>) -> impl IntoResponse {
         active_connections: active,
         total_users,
         maintenance: maint,
+        connection_caps: state.proxy_engine.client_caps().snapshot(),
+        sandbox: crate::sandbox::status(),
     })
 }
 
@@ -409,7 +671,11 @@ struct SseTicketResponse {
 
 /// P0-2: Issue an HMAC-SHA256 ticket for SSE connections.
 /// POST /api/sse-ticket (requires Bearer auth) -> { ticket, expires_in }
-async fn sse_ticket_handler(State(state): State<AppState>) -> impl IntoResponse {
+/// Tickets carry the caller's role so a viewer cannot escalate via `?ticket=`.
+async fn sse_ticket_handler(
+    State(state): State<AppState>,
+    axum::Extension(principal): axum::Extension<rbac::Principal>,
+) -> impl IntoResponse {
     use hmac::{Hmac, Mac};
     use sha2::Sha256;
 
@@ -429,10 +695,21 @@ async fn sse_ticket_handler(State(state): State<AppState>) -> impl IntoResponse
             return (StatusCode::INTERNAL_SERVER_ERROR, "hmac error").into_response();
         }
     };
-    mac.update(format!("{}:{}", timestamp, nonce).as_bytes());
+    // Admin tickets keep the original 3-part format; other roles append the
+    // (signed) role as a 4th part, `role@tenant` for tenant principals.
+    let role_suffix = match (principal.role, &principal.tenant) {
+        (DashboardRole::Admin, None) => None,
+        (role, None) => Some(role.as_str().to_string()),
+        (role, Some(tenant)) => Some(format!("{}@{}", role, tenant)),
+    };
+    let role_suffix = role_suffix.as_deref();
+    mac.update(ticket_message(timestamp, nonce, role_suffix).as_bytes());
     let signature = hex::encode(mac.finalize().into_bytes());
 
-    let ticket = format!("{}:{}:{}", timestamp, nonce, signature);
+    let ticket = match role_suffix {
+        None => format!("{}:{}:{}", timestamp, nonce, signature),
+        Some(role) => format!("{}:{}:{}:{}", timestamp, nonce, signature, role),
+    };
 
     ApiResponse::ok(SseTicketResponse {
         ticket,
@@ -441,27 +718,52 @@ async fn sse_ticket_handler(State(state): State<AppState>) -> impl IntoResponse
     .into_response()
 }
 
+/// Signed part of a ticket: `timestamp:nonce[:role]`.
+fn ticket_message(timestamp: u64, nonce: u128, role: Option<&str>) -> String {
+    match role {
+        None => format!("{}:{}", timestamp, nonce),
+        Some(role) => format!("{}:{}:{}", timestamp, nonce, role),
+    }
+}
+
 /// Verify an SSE ticket (HMAC-SHA256 with timestamp and nonce).
-/// Ticket format: `timestamp:nonce:signature`
+/// Ticket format: `timestamp:nonce:signature[:role]`
 pub fn verify_sse_ticket(ticket: &str, api_token: &str) -> bool {
+    verify_sse_ticket_role(ticket, api_token).is_some()
+}
+
+/// Verify an SSE ticket and return the role it was issued for
+/// (tickets without a role part are admin tickets).
+pub fn verify_sse_ticket_role(ticket: &str, api_token: &str) -> Option<DashboardRole> {
+    verify_sse_ticket_scope(ticket, api_token).map(|(role, _)| role)
+}
+
+/// Verify an SSE ticket and return the role and tenant it was issued for.
+pub fn verify_sse_ticket_scope(
+    ticket: &str,
+    api_token: &str,
+) -> Option<(DashboardRole, Option<String>)> {
     use hmac::{Hmac, Mac};
     use sha2::Sha256;
 
-    let parts: Vec<&str> = ticket.splitn(3, ':').collect();
-    if parts.len() != 3 {
-        return false;
+    let parts: Vec<&str> = ticket.splitn(4, ':').collect();
+    if parts.len() < 3 {
+        return None;
     }
-
-    let timestamp: u64 = match parts[0].parse() {
-        Ok(t) => t,
-        Err(_) => return false,
+    let role_suffix = parts.get(3).copied();
+    let (role, tenant) = match role_suffix.map(|r| r.split_once('@').unwrap_or((r, ""))) {
+        None => (DashboardRole::Admin, None),
+        // An explicit "admin" suffix is never issued; reject it
+        Some((r, tenant)) => (
+            DashboardRole::parse(r).filter(|r| *r != DashboardRole::Admin)?,
+            (!tenant.is_empty()).then(|| tenant.to_string()),
+        ),
     };
 
+    let timestamp: u64 = parts[0].parse().ok()?;
+
     // Validate nonce is a valid u128 (prevents malformed tickets)
-    let nonce: u128 = match parts[1].parse() {
-        Ok(n) => n,
-        Err(_) => return false,
-    };
+    let nonce: u128 = parts[1].parse().ok()?;
 
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -470,15 +772,12 @@ pub fn verify_sse_ticket(ticket: &str, api_token: &str) -> bool {
 
     // Check ticket age
     if now.saturating_sub(timestamp) > SSE_TICKET_VALIDITY_SECS {
-        return false;
+        return None;
     }
 
     let signing_key = format!("s5-sse-ticket:{}", api_token);
-    let mut mac = match Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()) {
-        Ok(m) => m,
-        Err(_) => return false,
-    };
-    mac.update(format!("{}:{}", timestamp, nonce).as_bytes());
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()).ok()?;
+    mac.update(ticket_message(timestamp, nonce, role_suffix).as_bytes());
     let expected = hex::encode(mac.finalize().into_bytes());
 
     // Constant-time comparison
@@ -491,7 +790,7 @@ pub fn verify_sse_ticket(ticket: &str, api_token: &str) -> bool {
         // Replay protection: reject if this ticket was already used
         let ticket_key = format!("{}:{}", timestamp, nonce);
         if used_tickets().contains_key(&ticket_key) {
-            return false;
+            return None;
         }
         // Enforce capacity bound to prevent memory exhaustion under sustained traffic
         if used_tickets().len() >= MAX_USED_TICKETS {
@@ -501,7 +800,17 @@ pub fn verify_sse_ticket(ticket: &str, api_token: &str) -> bool {
         used_tickets().insert(ticket_key, timestamp + SSE_TICKET_VALIDITY_SECS);
     }
 
-    valid
+    valid.then_some((role, tenant))
+}
+
+/// Where the management API listens.
+#[derive(Debug, Clone)]
+pub enum ApiBind {
+    /// TCP address (`api.listen`).
+    Tcp(String),
+    /// Unix socket path and file mode (`api.unix_socket`).
+    #[cfg(unix)]
+    Unix { path: PathBuf, mode: u32 },
 }
 
 /// Start the management API server with graceful shutdown support.
@@ -510,56 +819,225 @@ pub async fn start_api_server(
     state: AppState,
     shutdown: tokio_util::sync::CancellationToken,
 ) -> anyhow::Result<()> {
-    // Authenticated routes
+    serve_api(ApiBind::Tcp(listen_addr.to_string()), state, shutdown).await
+}
+
+/// Start the management API server on a TCP address or a Unix socket.
+pub async fn serve_api(
+    bind: ApiBind,
+    state: AppState,
+    shutdown: tokio_util::sync::CancellationToken,
+) -> anyhow::Result<()> {
+    let app = api_router(state);
+    match bind {
+        ApiBind::Tcp(listen_addr) => {
+            let listener = crate::handover::global().bind("api", &listen_addr).await?;
+            info!(addr = %listen_addr, "API server listening");
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown.cancelled_owned())
+            .await?;
+        }
+        #[cfg(unix)]
+        ApiBind::Unix { path, mode } => unix_socket::serve(&path, mode, app, shutdown).await?,
+    }
+    Ok(())
+}
+
+fn api_router(state: AppState) -> Router {
+    // Operator routes: day-to-day actions on sessions, bans and quotas
+    let operator = Router::new()
+        .route("/api/bans", post(bans::create_ban))
+        .route("/api/bans/:ip", delete(bans::delete_ban))
+        .route("/api/maintenance", post(maintenance::toggle_maintenance))
+        .route("/api/broadcast", post(broadcast::broadcast_message))
+        .route("/api/kick/:username", post(kick::kick_user))
+        .route("/api/users/:username/lock", delete(users::unlock_user))
+        .route("/api/sessions/:id", delete(sessions::kill_session))
+        .route(
+            "/api/quotas/:username/reset",
+            post(quotas::reset_user_quota),
+        )
+        .route(
+            "/api/users/:username/quota/reset",
+            post(quotas::reset_quota_counters),
+        )
+        .route(
+            "/api/users/:username/quota",
+            patch(quotas::adjust_user_quota),
+        )
+        .route_layer(middleware::from_fn(rbac::require_operator))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cluster::read_only,
+        ));
+
+    // Admin routes: configuration and state
+    let admin = Router::new()
+        .route("/api/reload", post(reload::reload_config))
+        .route(
+            "/api/config/history",
+            get(config_history::list_config_history),
+        )
+        .route(
+            "/api/config/rollback/:id",
+            post(config_history::rollback_config),
+        )
+        .route(
+            "/api/logging/level",
+            get(logging::get_log_level)
+                .put(logging::set_log_level)
+                .delete(logging::reset_log_level),
+        )
+        .route("/api/groups/:name/policy", put(groups::set_group_policy))
+        .route(
+            "/api/invitations",
+            get(invitations::list_invitations).post(invitations::create_invitation),
+        )
+        .route(
+            "/api/invitations/:id",
+            delete(invitations::revoke_invitation),
+        )
+        .route("/api/backup", get(backup::backup_handler))
+        .route("/api/debug/bundle", get(debug::debug_bundle))
+        .route(
+            "/api/security/ip-intel/refresh",
+            post(security::refresh_ip_intel),
+        )
+        .route("/api/restore", post(backup::restore_handler))
+        .route(
+            "/api/key-enrollments/:id/approve",
+            post(key_enrollments::approve_key_enrollment),
+        )
+        .route(
+            "/api/key-enrollments/:id/reject",
+            post(key_enrollments::reject_key_enrollment),
+        )
+        .route(
+            "/api/sessions/:id/capture",
+            get(capture::get_capture)
+                .post(capture::start_capture)
+                .delete(capture::stop_capture),
+        )
+        .route_layer(middleware::from_fn(rbac::require_admin))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cluster::read_only,
+        ));
+
+    // Authenticated routes (viewer and up); role layers run after auth
     let authed = Router::new()
         .route("/api/health", get(api_health_handler))
         .route("/api/status", get(status_handler))
+        .route("/api/metrics", get(metrics::metrics_snapshot))
+        .route("/api/me", get(rbac::me))
         .route("/api/users", get(users::list_users))
         .route("/api/connections", get(connections::list_connections))
+        .route("/api/flows", get(flows::list_flows))
         .route("/api/bans", get(bans::list_bans))
-        .route("/api/bans/{ip}", delete(bans::delete_ban))
-        .route("/api/maintenance", post(maintenance::toggle_maintenance))
-        .route("/api/reload", post(reload::reload_config))
-        .route("/api/broadcast", post(broadcast::broadcast_message))
-        .route("/api/kick/{username}", post(kick::kick_user))
+        .route("/api/security/timeline", get(security::security_timeline))
+        .route("/api/security/events", get(security::security_events))
+        .route("/api/security/ip-intel", get(security::ip_intel_status))
         .route("/api/ssh-config", get(ssh_config::ssh_config_snippet))
-        .route("/api/quotas", get(quotas::list_quotas))
-        .route("/api/quotas/:username", get(quotas::get_user_quota))
+        .route("/api/host-keys", get(host_keys::list_host_keys))
         .route(
-            "/api/quotas/:username/reset",
-            post(quotas::reset_user_quota),
+            "/api/key-enrollments",
+            get(key_enrollments::list_key_enrollments),
         )
+        .route("/api/keys/expiring", get(users::list_expiring_keys))
+        .route("/api/quotas", get(quotas::list_quotas))
+        .route("/api/quotas/:username", get(quotas::get_user_quota))
+        .route("/api/reports/usage", get(reports::usage_report))
+        .route("/api/cluster", get(cluster::cluster_info))
+        .route("/api/cluster/sessions", get(cluster::cluster_sessions))
         .route("/api/groups", get(groups::list_groups))
         .route("/api/groups/:name", get(groups::get_group))
         .route("/api/sessions", get(sessions::list_sessions))
-        .route("/api/sessions/:username", get(sessions::get_user_sessions))
+        .route("/api/sessions/history", get(sessions::session_history))
+        .route("/api/sessions/:id", get(sessions::get_session))
         .route("/api/sse-ticket", post(sse_ticket_handler))
-        .route("/api/backup", get(backup::backup_handler))
-        .route("/api/restore", post(backup::restore_handler))
         .route("/api/ws", get(ws::ws_handler))
         .route("/api/events", get(sse::sse_events))
+        .merge(operator)
+        .merge(admin)
+        .route_layer(middleware::from_fn(rbac::tenant_routes))
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
         ));
 
-    // Unauthenticated routes: liveness/readiness probes + static dashboard (no sensitive data)
-    let app = Router::new()
+    // Self-service routes: authenticated with the caller's personal token or
+    // self-service page session; read-only tokens only reach the caller's own
+    // status, quota and sessions
+    let self_full = Router::new()
+        .route("/api/self/password", post(self_service::change_password))
+        .route(
+            "/api/self/tokens",
+            get(self_service::list_tokens).post(self_service::create_token),
+        )
+        .route("/api/self/tokens/:id", delete(self_service::revoke_token))
+        .route("/api/self/managed", get(managed::list_managed))
+        .route("/api/self/managed/sessions", get(managed::managed_sessions))
+        .route(
+            "/api/self/managed/users/:username/quota/reset",
+            post(managed::reset_member_quota),
+        )
+        .route(
+            "/api/self/managed/users/:username/disabled",
+            put(managed::set_member_disabled),
+        )
+        .route_layer(middleware::from_fn(self_service::require_full_scope))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cluster::read_only,
+        ));
+    let self_routes = Router::new()
+        .route("/api/self", get(self_service::status))
+        .route("/api/self/quota", get(self_service::quota))
+        .route("/api/self/sessions", get(self_service::sessions))
+        .merge(self_full)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            self_service::user_token_middleware,
+        ));
+
+    // Unauthenticated routes: liveness/readiness probes, static dashboard and API spec (no sensitive data)
+    // and the SSH WebSocket tunnel (SSH authenticates its clients), plus invitation links (the
+    // token is the credential)
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
         .route("/livez", get(|| async { "ok" }))
         .route("/readyz", get(readyz_handler))
         .route("/dashboard", get(dashboard::serve_dashboard))
+        .route("/dashboard/login", get(dashboard::serve_login))
+        .route("/dashboard/me", get(dashboard::serve_self_service))
+        .route("/dashboard/invite", get(dashboard::serve_invite))
+        .route("/dashboard/i18n.js", get(dashboard::serve_i18n))
+        .route("/api/login", post(session::login))
+        .route("/api/logout", post(session::logout))
+        .route("/api/self/login", post(self_service::login))
+        .route("/api/self/logout", post(self_service::logout))
+        .route("/api/invite", post(invitations::inspect_invite))
+        .route("/api/invite/accept", post(invitations::accept_invite))
+        .route("/api/openapi.json", get(openapi::openapi_json))
+        .route("/api/csrf-token", get(cors::csrf_token_handler))
+        .route("/ssh", get(ssh_tunnel::ssh_tunnel_handler))
         .merge(authed)
+        .merge(self_routes)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_guard_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             api_metrics_middleware,
         ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            cors::cors_csrf_middleware,
+        ))
         .layer(DefaultBodyLimit::max(64 * 1024))
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind(listen_addr).await?;
-    info!(addr = %listen_addr, "API server listening");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown.cancelled_owned())
-        .await?;
-    Ok(())
+        .with_state(state)
 }
@@ -0,0 +1,2249 @@
+//! OpenAPI 3.0 document for the management API, served at `/api/openapi.json`.
+//!
+//! The spec is hand-maintained: it is built from the [`ENDPOINTS`] table and
+//! the schemas in [`components`], not derived from the handlers or their
+//! types. Unit tests check that every routed path is listed and every listed
+//! path is routed, with its role, and call every documented endpoint: the
+//! handler must accept a sample of the request schema, and its answer must
+//! match the response schema (no missing required or undocumented fields).
+//! `Object` schemas are free-form and not checked.
+
+use axum::response::IntoResponse;
+use serde_json::{json, Map, Value};
+
+/// How an endpoint is authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    /// No credentials required (probes, dashboard, this document).
+    None,
+    /// Any dashboard role: the API token, or a session of any `[[api.accounts]]` role.
+    Viewer,
+    /// `operator` or `admin` role.
+    Operator,
+    /// `admin` role: the API token or an admin account session.
+    Admin,
+    /// Personal user token (`Authorization: Bearer <username>:<token>`) or
+    /// self-service page session; read-only tokens are refused.
+    User,
+    /// Like [`Auth::User`], read-only personal tokens included.
+    UserRead,
+}
+
+/// One documented operation.
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    pub method: &'static str,
+    /// Path in OpenAPI form (`{param}` placeholders).
+    pub path: &'static str,
+    pub tag: &'static str,
+    pub summary: &'static str,
+    pub auth: Auth,
+    /// Component schema of the JSON request body, if any.
+    pub request: Option<&'static str>,
+    /// Component schema of the envelope `data` field (or raw body for probes).
+    pub response: Option<&'static str>,
+    /// Query parameters as `(name, required, description)`.
+    pub query: &'static [(&'static str, bool, &'static str)],
+}
+
+const fn ep(
+    method: &'static str,
+    path: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    auth: Auth,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        tag,
+        summary,
+        auth,
+        request: None,
+        response: None,
+        query: &[],
+    }
+}
+
+const fn with_response(mut e: Endpoint, schema: &'static str) -> Endpoint {
+    e.response = Some(schema);
+    e
+}
+
+const fn with_request(mut e: Endpoint, schema: &'static str) -> Endpoint {
+    e.request = Some(schema);
+    e
+}
+
+const fn with_query(
+    mut e: Endpoint,
+    query: &'static [(&'static str, bool, &'static str)],
+) -> Endpoint {
+    e.query = query;
+    e
+}
+
+/// Every route served by the API listener.
+pub const ENDPOINTS: &[Endpoint] = &[
+    // Probes
+    ep(
+        "get",
+        "/healthz",
+        "probes",
+        "Process-alive probe",
+        Auth::None,
+    ),
+    ep("get", "/livez", "probes", "Liveness probe", Auth::None),
+    with_response(
+        ep("get", "/readyz", "probes", "Readiness probe", Auth::None),
+        "Readyz",
+    ),
+    ep(
+        "get",
+        "/dashboard",
+        "dashboard",
+        "Web dashboard UI (redirects to the login form without a session)",
+        Auth::None,
+    ),
+    ep(
+        "get",
+        "/dashboard/login",
+        "dashboard",
+        "Dashboard login form",
+        Auth::None,
+    ),
+    ep(
+        "get",
+        "/dashboard/me",
+        "self-service",
+        "Self-service page: SSH users sign in and manage their API tokens",
+        Auth::None,
+    ),
+    ep(
+        "get",
+        "/dashboard/invite",
+        "self-service",
+        "Invitation page: an invited user chooses a password and uploads a public key",
+        Auth::None,
+    ),
+    ep(
+        "get",
+        "/dashboard/i18n.js",
+        "dashboard",
+        "Dashboard translation bundles",
+        Auth::None,
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/login",
+                "dashboard",
+                "Exchange the admin token or account credentials for an s5_session cookie",
+                Auth::None,
+            ),
+            "LoginResponse",
+        ),
+        "LoginRequest",
+    ),
+    ep(
+        "post",
+        "/api/logout",
+        "dashboard",
+        "End the dashboard session",
+        Auth::None,
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/self/login",
+                "self-service",
+                "Exchange an SSH user's password for an s5_user_session cookie",
+                Auth::None,
+            ),
+            "SelfStatus",
+        ),
+        "UserLoginRequest",
+    ),
+    ep(
+        "post",
+        "/api/self/logout",
+        "self-service",
+        "End the self-service page session",
+        Auth::None,
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/invite",
+                "self-service",
+                "Username and expiry of an invitation link (the token is the credential)",
+                Auth::None,
+            ),
+            "InviteInfo",
+        ),
+        "InviteTokenRequest",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/invite/accept",
+                "self-service",
+                "Complete an invitation link: create the account with a password and public key",
+                Auth::None,
+            ),
+            "AcceptedInvite",
+        ),
+        "AcceptInviteRequest",
+    ),
+    ep(
+        "get",
+        "/api/openapi.json",
+        "meta",
+        "This OpenAPI document",
+        Auth::None,
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/csrf-token",
+            "meta",
+            "Issue a CSRF token (also set as the s5_csrf cookie)",
+            Auth::None,
+        ),
+        "CsrfToken",
+    ),
+    // Server
+    with_response(
+        ep(
+            "get",
+            "/api/health",
+            "server",
+            "Health details",
+            Auth::Viewer,
+        ),
+        "HealthDetail",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/status",
+            "server",
+            "Server status",
+            Auth::Viewer,
+        ),
+        "StatusInfo",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/metrics",
+            "server",
+            "All Prometheus metrics as JSON",
+            Auth::Viewer,
+        ),
+        "MetricsSnapshot",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/me",
+            "dashboard",
+            "Caller's identity and role",
+            Auth::Viewer,
+        ),
+        "Me",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/maintenance",
+                "server",
+                "Toggle or set maintenance mode",
+                Auth::Operator,
+            ),
+            "MaintenanceStatus",
+        ),
+        "MaintenanceRequest",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/reload",
+            "server",
+            "Reload configuration from disk",
+            Auth::Admin,
+        ),
+        "ReloadResult",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/logging/level",
+            "server",
+            "Log filter in effect and the startup filter",
+            Auth::Admin,
+        ),
+        "LogLevel",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "put",
+                "/api/logging/level",
+                "server",
+                "Change the log level or per-module directives until restart",
+                Auth::Admin,
+            ),
+            "LogLevel",
+        ),
+        "LogLevelRequest",
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/logging/level",
+            "server",
+            "Return to the startup log filter",
+            Auth::Admin,
+        ),
+        "LogLevel",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/config/history",
+            "server",
+            "Applied configurations, newest first",
+            Auth::Admin,
+        ),
+        "ConfigSnapshotList",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/config/rollback/{id}",
+            "server",
+            "Write an earlier configuration back to disk and apply it",
+            Auth::Admin,
+        ),
+        "RollbackResult",
+    ),
+    with_query(
+        ep(
+            "get",
+            "/api/ssh-config",
+            "server",
+            "Generate an SSH config snippet (text/plain)",
+            Auth::Viewer,
+        ),
+        &[
+            ("user", true, "Username to generate the snippet for"),
+            (
+                "host",
+                false,
+                "Hostname clients connect to (default: localhost)",
+            ),
+        ],
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/host-keys",
+            "server",
+            "SSH host keys and rotation status",
+            Auth::Viewer,
+        ),
+        "HostKeys",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/backup",
+            "server",
+            "Export bans and quotas",
+            Auth::Admin,
+        ),
+        "BackupPayload",
+    ),
+    ep(
+        "get",
+        "/api/debug/bundle",
+        "server",
+        "Diagnostic tarball for bug reports (redacted config, checks, recent errors)",
+        Auth::Admin,
+    ),
+    with_request(
+        ep(
+            "post",
+            "/api/restore",
+            "server",
+            "Import bans and quotas",
+            Auth::Admin,
+        ),
+        "BackupPayload",
+    ),
+    // Users & groups
+    with_query(
+        with_response(
+            ep("get", "/api/users", "users", "List users", Auth::Viewer),
+            "UserInfoList",
+        ),
+        &[("details", false, "Include live connection and quota data")],
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/users/{username}/lock",
+            "users",
+            "Lift an account lock",
+            Auth::Operator,
+        ),
+        "UnlockResult",
+    ),
+    with_response(
+        ep("get", "/api/groups", "users", "List groups", Auth::Viewer),
+        "GroupList",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/groups/{name}",
+            "users",
+            "Get a group",
+            Auth::Viewer,
+        ),
+        "Group",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "put",
+                "/api/groups/{name}/policy",
+                "users",
+                "Write a group's bandwidth, connection, forwarding and shell policy and apply it",
+                Auth::Admin,
+            ),
+            "Group",
+        ),
+        "GroupPolicy",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/key-enrollments",
+            "users",
+            "Public keys awaiting (or given) admin approval",
+            Auth::Viewer,
+        ),
+        "KeyEnrollmentList",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/key-enrollments/{id}/approve",
+            "users",
+            "Approve a key: add it to the user's authorized_keys",
+            Auth::Admin,
+        ),
+        "KeyEnrollment",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/key-enrollments/{id}/reject",
+            "users",
+            "Reject a key",
+            Auth::Admin,
+        ),
+        "KeyEnrollment",
+    ),
+    with_response(
+        with_query(
+            ep(
+                "get",
+                "/api/keys/expiring",
+                "users",
+                "Authorized keys expiring soon (expiry-time), expired ones included",
+                Auth::Viewer,
+            ),
+            &[("days", false, "Look-ahead in days (default 14)")],
+        ),
+        "ExpiringKeyList",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/invitations",
+            "users",
+            "Pending invitation links, oldest first",
+            Auth::Admin,
+        ),
+        "InvitationList",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/invitations",
+                "users",
+                "Invite a new user: returns a single-use link (shown once)",
+                Auth::Admin,
+            ),
+            "CreatedInvitation",
+        ),
+        "CreateInvitationRequest",
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/invitations/{id}",
+            "users",
+            "Revoke an unused invitation",
+            Auth::Admin,
+        ),
+        "Invitation",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/kick/{username}",
+                "users",
+                "Disconnect a user",
+                Auth::Operator,
+            ),
+            "KickResponse",
+        ),
+        "KickRequest",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/self/password",
+                "self-service",
+                "Change the caller's own password",
+                Auth::User,
+            ),
+            "ChangePasswordResponse",
+        ),
+        "ChangePasswordRequest",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self",
+            "self-service",
+            "Who the caller is authenticated as, and the token scope",
+            Auth::UserRead,
+        ),
+        "SelfStatus",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self/quota",
+            "self-service",
+            "The caller's quota usage and limits",
+            Auth::UserRead,
+        ),
+        "Object",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self/sessions",
+            "self-service",
+            "The caller's live sessions",
+            Auth::UserRead,
+        ),
+        "ObjectList",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self/tokens",
+            "self-service",
+            "The caller's read-only personal API tokens",
+            Auth::User,
+        ),
+        "PersonalTokenList",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/self/tokens",
+                "self-service",
+                "Create a read-only personal API token (the secret is returned once)",
+                Auth::User,
+            ),
+            "CreatedToken",
+        ),
+        "CreateTokenRequest",
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/self/tokens/{id}",
+            "self-service",
+            "Revoke one of the caller's personal API tokens",
+            Auth::User,
+        ),
+        "PersonalToken",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self/managed",
+            "self-service",
+            "Groups the caller manages (`managers`) and their members' usage",
+            Auth::User,
+        ),
+        "ManagedGroupList",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/self/managed/sessions",
+            "self-service",
+            "Live sessions of the members of the caller's managed groups",
+            Auth::User,
+        ),
+        "ObjectList",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/self/managed/users/{username}/quota/reset",
+                "self-service",
+                "Reset some or all quota counters of a managed group member",
+                Auth::User,
+            ),
+            "Object",
+        ),
+        "QuotaResetRequest",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "put",
+                "/api/self/managed/users/{username}/disabled",
+                "self-service",
+                "Disable or re-enable the logins of a managed group member",
+                Auth::User,
+            ),
+            "SetDisabledResponse",
+        ),
+        "SetDisabledRequest",
+    ),
+    // Traffic
+    with_response(
+        ep(
+            "get",
+            "/api/connections",
+            "traffic",
+            "Active proxy connections",
+            Auth::Viewer,
+        ),
+        "ConnectionsInfo",
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/flows",
+                "traffic",
+                "Recorded connection flows, newest first",
+                Auth::Viewer,
+            ),
+            "FlowPage",
+        ),
+        &[
+            ("user", false, "Only flows of this user"),
+            ("source_ip", false, "Only flows from this client IP"),
+            ("dest", false, "Destination hostname or resolved IP"),
+            ("port", false, "Destination port"),
+            ("close_reason", false, "`closed` or an error type"),
+            ("correlation_id", false, "Connection correlation ID"),
+            ("app_protocol", false, "`tls`, `http`, `ssh` or `unknown`"),
+            ("since", false, "Started at or after (RFC 3339)"),
+            ("until", false, "Started before (RFC 3339)"),
+            ("before", false, "Row ID cursor from `next_before`"),
+            ("limit", false, "Maximum rows (default 100, max 1000)"),
+        ],
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/sessions",
+                "traffic",
+                "Active SSH sessions",
+                Auth::Viewer,
+            ),
+            "ObjectList",
+        ),
+        &[("correlation_id", false, "Only sessions of this connection")],
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/sessions/{id}",
+            "traffic",
+            "Session detail by session ID, or sessions of the user named `id`",
+            Auth::Viewer,
+        ),
+        "Object",
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/sessions/{id}",
+            "traffic",
+            "Close a live session",
+            Auth::Operator,
+        ),
+        "KillResponse",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/sessions/{id}/capture",
+            "traffic",
+            "Capture running on a live session",
+            Auth::Admin,
+        ),
+        "CaptureInfo",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/sessions/{id}/capture",
+                "traffic",
+                "Start writing a live session's traffic to a pcapng file",
+                Auth::Admin,
+            ),
+            "CaptureInfo",
+        ),
+        "CaptureRequest",
+    ),
+    with_response(
+        ep(
+            "delete",
+            "/api/sessions/{id}/capture",
+            "traffic",
+            "Stop a session capture",
+            Auth::Admin,
+        ),
+        "CaptureSummary",
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/sessions/history",
+                "traffic",
+                "Recently closed sessions with their close reason, newest first",
+                Auth::Viewer,
+            ),
+            "ObjectList",
+        ),
+        &[
+            ("user", false, "Only sessions of this user"),
+            (
+                "reason",
+                false,
+                "Only this close reason (e.g. `idle_timeout`)",
+            ),
+            ("limit", false, "Maximum rows (default 100, max 1000)"),
+        ],
+    ),
+    with_response(
+        ep("get", "/api/quotas", "traffic", "Quota usage", Auth::Viewer),
+        "ObjectList",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/quotas/{username}",
+            "traffic",
+            "Quota usage of one user",
+            Auth::Viewer,
+        ),
+        "Object",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/quotas/{username}/reset",
+            "traffic",
+            "Reset a user's quota counters",
+            Auth::Operator,
+        ),
+        "QuotaResetResult",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/users/{username}/quota/reset",
+                "traffic",
+                "Reset some or all of a user's quota counters (all when `counters` is empty)",
+                Auth::Operator,
+            ),
+            "Object",
+        ),
+        "QuotaResetRequest",
+    ),
+    with_request(
+        with_response(
+            ep(
+                "patch",
+                "/api/users/{username}/quota",
+                "traffic",
+                "Grant a user one-off extra quota, added to earlier grants",
+                Auth::Operator,
+            ),
+            "Object",
+        ),
+        "QuotaGrant",
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/reports/usage",
+                "traffic",
+                "Per-user bytes, connections and session hours of one month (JSON or CSV)",
+                Auth::Viewer,
+            ),
+            "UsageReport",
+        ),
+        &[
+            (
+                "month",
+                false,
+                "Month as `YYYY-MM` (default: current month, UTC)",
+            ),
+            ("format", false, "`json` (default) or `csv`"),
+        ],
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/cluster",
+            "server",
+            "This node, its last cluster sync and the nodes sharing sessions",
+            Auth::Viewer,
+        ),
+        "ClusterInfo",
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/cluster/sessions",
+            "traffic",
+            "Active sessions of every cluster node, with their node_id",
+            Auth::Viewer,
+        ),
+        "ObjectList",
+    ),
+    // Security
+    with_response(
+        ep(
+            "get",
+            "/api/bans",
+            "security",
+            "List banned IPs",
+            Auth::Viewer,
+        ),
+        "BanInfoList",
+    ),
+    with_request(
+        with_response(
+            ep("post", "/api/bans", "security", "Ban an IP", Auth::Operator),
+            "BanInfo",
+        ),
+        "BanRequest",
+    ),
+    with_query(
+        with_response(
+            ep(
+                "delete",
+                "/api/bans/{ip}",
+                "security",
+                "Lift an IP ban",
+                Auth::Operator,
+            ),
+            "UnbanResult",
+        ),
+        &[(
+            "tenant",
+            false,
+            "Lift the ban for this tenant (default: the server-wide ban)",
+        )],
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/security/timeline",
+                "security",
+                "Auth failures, bans and ip_guard blocks over time",
+                Auth::Viewer,
+            ),
+            "SecurityTimeline",
+        ),
+        &[(
+            "window",
+            false,
+            "Seconds back from now (default 3600, max 86400)",
+        )],
+    ),
+    with_query(
+        with_response(
+            ep(
+                "get",
+                "/api/security/events",
+                "security",
+                "Audit entries behind the security timeline, newest first",
+                Auth::Viewer,
+            ),
+            "ObjectList",
+        ),
+        &[
+            ("kind", false, "`auth_failure`, `ban` or `ip_guard`"),
+            ("from", false, "At or after (RFC 3339; default 24 h ago)"),
+            ("to", false, "Before (RFC 3339; default now)"),
+            ("limit", false, "Maximum events (default 100, max 1000)"),
+        ],
+    ),
+    with_response(
+        ep(
+            "get",
+            "/api/security/ip-intel",
+            "security",
+            "Source and size of the Tor exit and datacenter ranges",
+            Auth::Viewer,
+        ),
+        "IpIntelStatus",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/security/ip-intel/refresh",
+            "security",
+            "Download fresh Tor exit and datacenter ranges",
+            Auth::Admin,
+        ),
+        "IpIntelStatus",
+    ),
+    // Realtime
+    with_request(
+        with_response(
+            ep(
+                "post",
+                "/api/broadcast",
+                "realtime",
+                "Broadcast a message to connected users",
+                Auth::Operator,
+            ),
+            "BroadcastResponse",
+        ),
+        "BroadcastRequest",
+    ),
+    with_response(
+        ep(
+            "post",
+            "/api/sse-ticket",
+            "realtime",
+            "Issue a short-lived SSE ticket",
+            Auth::Viewer,
+        ),
+        "SseTicket",
+    ),
+    with_query(
+        ep(
+            "get",
+            "/api/events",
+            "realtime",
+            "Server-Sent Events stream",
+            Auth::Viewer,
+        ),
+        &[("ticket", false, "Ticket from POST /api/sse-ticket")],
+    ),
+    ep(
+        "get",
+        "/api/ws",
+        "realtime",
+        "WebSocket stream of live updates",
+        Auth::Viewer,
+    ),
+    // SSH authenticates the tunnel itself (`api.ssh_websocket`)
+    ep(
+        "get",
+        "/ssh",
+        "realtime",
+        "SSH transport over WebSocket binary frames",
+        Auth::None,
+    ),
+];
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+const CAPTURE_INFO_REQUIRED: [&str; 9] = [
+    "session_id",
+    "username",
+    "target_host",
+    "target_port",
+    "path",
+    "mode",
+    "started_at",
+    "expires_at",
+    "started_by",
+];
+
+/// `CaptureInfo` properties, followed by `extra` (`CaptureSummary` flattens it).
+fn capture_info_props<'a>(extra: &[(&'a str, Value)]) -> Vec<(&'a str, Value)> {
+    let date = || json!({ "type": "string", "format": "date-time" });
+    let mut props = vec![
+        ("session_id", string()),
+        ("correlation_id", string()),
+        ("username", string()),
+        ("target_host", string()),
+        ("target_port", int()),
+        ("path", string()),
+        (
+            "mode",
+            json!({ "type": "string", "enum": ["full", "headers"] }),
+        ),
+        ("started_at", date()),
+        ("expires_at", date()),
+        ("started_by", string()),
+    ];
+    props.extend(extra.iter().cloned());
+    props
+}
+
+fn object(props: &[(&str, Value)], required: &[&str]) -> Value {
+    let properties: Map<String, Value> = props
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn int() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn array_of(name: &str) -> Value {
+    json!({ "type": "array", "items": schema_ref(name) })
+}
+
+fn role() -> Value {
+    json!({ "type": "string", "enum": ["viewer", "operator", "admin"] })
+}
+
+fn security_schemes() -> Value {
+    json!({
+        "adminToken": {
+            "type": "http",
+            "scheme": "bearer",
+            "description": "Admin API token (`api.token`); grants the admin role."
+        },
+        "sessionCookie": {
+            "type": "apiKey",
+            "in": "cookie",
+            "name": "s5_session",
+            "description": "Dashboard session from POST /api/login; carries the account's role."
+        },
+        "userToken": {
+            "type": "http",
+            "scheme": "bearer",
+            "description": "Personal token as `<username>:<token>`, checked against `api_token_hash`."
+        }
+    })
+}
+
+/// The schemas are built one `json!` at a time: a single literal this size
+/// exceeds the macro recursion limit.
+fn components() -> Value {
+    let schemas: Map<String, Value> = [
+        (
+            "Envelope",
+            object(
+                &[
+                    ("success", boolean()),
+                    ("data", json!({})),
+                    ("error", string()),
+                ],
+                &["success"],
+            ),
+        ),
+        (
+            "Object",
+            json!({ "type": "object", "additionalProperties": true }),
+        ),
+        ("ObjectList", array_of("Object")),
+        (
+            "Readyz",
+            object(
+                &[
+                    ("ready", boolean()),
+                    (
+                        "checks",
+                        json!({
+                            "type": "object",
+                            "additionalProperties": { "type": "string" }
+                        }),
+                    ),
+                ],
+                &["ready", "checks"],
+            ),
+        ),
+        (
+            "HealthDetail",
+            object(
+                &[
+                    ("status", string()),
+                    ("maintenance", boolean()),
+                    ("active_connections", int()),
+                    ("uptime_secs", int()),
+                ],
+                &["status", "maintenance", "active_connections", "uptime_secs"],
+            ),
+        ),
+        (
+            "StatusInfo",
+            object(
+                &[
+                    ("status", string()),
+                    ("uptime_secs", int()),
+                    ("active_connections", int()),
+                    ("total_users", int()),
+                    ("maintenance", boolean()),
+                    ("connection_caps", schema_ref("ConnectionCaps")),
+                    ("sandbox", schema_ref("SandboxStatus")),
+                ],
+                &[
+                    "status",
+                    "uptime_secs",
+                    "active_connections",
+                    "total_users",
+                    "maintenance",
+                    "connection_caps",
+                    "sandbox",
+                ],
+            ),
+        ),
+        (
+            "SandboxStatus",
+            object(
+                &[
+                    ("applied", boolean()),
+                    ("uid", int()),
+                    ("gid", int()),
+                    ("capabilities", string()),
+                    ("capability_bounding_set", string()),
+                    ("no_new_privs", boolean()),
+                    ("seccomp", string()),
+                    ("denied_syscalls", int()),
+                ],
+                &["applied", "denied_syscalls"],
+            ),
+        ),
+        (
+            "ConnectionCaps",
+            object(
+                &[
+                    ("active", int()),
+                    ("max", int()),
+                    ("queued", int()),
+                    ("queue_size", int()),
+                    ("saturation", json!({ "type": "number" })),
+                    ("ssh", int()),
+                    ("socks5", int()),
+                    ("masque", int()),
+                    ("max_per_user", int()),
+                ],
+                &[
+                    "active",
+                    "max",
+                    "queued",
+                    "queue_size",
+                    "saturation",
+                    "ssh",
+                    "socks5",
+                    "masque",
+                    "max_per_user",
+                ],
+            ),
+        ),
+        ("MaintenanceRequest", object(&[("enabled", boolean())], &[])),
+        (
+            "MaintenanceStatus",
+            object(&[("maintenance", boolean())], &["maintenance"]),
+        ),
+        (
+            "ReloadResult",
+            object(&[("users_count", int())], &["users_count"]),
+        ),
+        (
+            "LogLevelRequest",
+            object(&[("level", string())], &["level"]),
+        ),
+        (
+            "LogLevel",
+            object(
+                &[("level", string()), ("default", string())],
+                &["level", "default"],
+            ),
+        ),
+        ("ConfigSnapshotList", array_of("ConfigSnapshot")),
+        (
+            "ConfigSnapshot",
+            object(
+                &[
+                    ("id", int()),
+                    (
+                        "applied_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "source",
+                        json!({
+                            "type": "string",
+                            "enum": ["startup", "api", "signal", "rollback"]
+                        }),
+                    ),
+                    ("users_count", int()),
+                    ("sha256", string()),
+                ],
+                &["id", "applied_at", "source", "users_count", "sha256"],
+            ),
+        ),
+        (
+            "RollbackResult",
+            object(
+                &[("snapshot_id", int()), ("users_count", int())],
+                &["snapshot_id", "users_count"],
+            ),
+        ),
+        (
+            "UserInfo",
+            object(
+                &[
+                    ("username", string()),
+                    ("allow_forwarding", boolean()),
+                    ("allow_shell", boolean()),
+                    ("authorized_keys_count", int()),
+                    ("source_ips", json!({ "type": "array", "items": string() })),
+                    ("expires_at", json!({ "type": "string", "nullable": true })),
+                    (
+                        "password_changed_at",
+                        json!({ "type": "string", "nullable": true }),
+                    ),
+                    ("password_expired", boolean()),
+                    ("locked", boolean()),
+                    (
+                        "lock_remaining_secs",
+                        json!({ "type": "integer", "nullable": true }),
+                    ),
+                    ("disabled", boolean()),
+                    ("quota_plan", string()),
+                    ("current_connections", int()),
+                    ("total_bytes_transferred", json!({ "type": "number" })),
+                    ("quota_usage", schema_ref("Object")),
+                ],
+                &[
+                    "username",
+                    "allow_forwarding",
+                    "allow_shell",
+                    "authorized_keys_count",
+                    "source_ips",
+                    "password_expired",
+                    "locked",
+                ],
+            ),
+        ),
+        ("UserInfoList", array_of("UserInfo")),
+        (
+            "GroupPolicy",
+            object(
+                &[
+                    (
+                        "max_bandwidth_kbps",
+                        json!({ "type": "integer", "nullable": true }),
+                    ),
+                    (
+                        "max_aggregate_bandwidth_kbps",
+                        json!({ "type": "integer", "nullable": true }),
+                    ),
+                    (
+                        "max_connections_per_user",
+                        json!({ "type": "integer", "nullable": true }),
+                    ),
+                    (
+                        "allow_forwarding",
+                        json!({ "type": "boolean", "nullable": true }),
+                    ),
+                    (
+                        "allow_shell",
+                        json!({ "type": "boolean", "nullable": true }),
+                    ),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "GroupMember",
+            object(
+                &[
+                    ("username", string()),
+                    ("active_connections", int()),
+                    ("daily_bytes", int()),
+                    ("monthly_bytes", int()),
+                    ("current_rate_bps", int()),
+                ],
+                &["username", "active_connections"],
+            ),
+        ),
+        (
+            "Group",
+            object(
+                &[
+                    ("name", string()),
+                    ("inherits", json!({ "type": "string", "nullable": true })),
+                    ("policy", schema_ref("GroupPolicy")),
+                    ("member_count", int()),
+                    ("active_connections", int()),
+                    ("total_daily_bytes", int()),
+                    ("total_monthly_bytes", int()),
+                    ("current_rate_bps", int()),
+                    ("members", array_of("GroupMember")),
+                ],
+                &[
+                    "name",
+                    "policy",
+                    "member_count",
+                    "active_connections",
+                    "members",
+                ],
+            ),
+        ),
+        ("GroupList", array_of("Group")),
+        (
+            "UnlockResult",
+            object(
+                &[("username", string()), ("unlocked", boolean())],
+                &["username", "unlocked"],
+            ),
+        ),
+        ("KickRequest", object(&[("message", string())], &[])),
+        (
+            "KickResponse",
+            object(
+                &[
+                    ("kicked", boolean()),
+                    ("username", string()),
+                    ("sessions_closed", int()),
+                ],
+                &["kicked", "username", "sessions_closed"],
+            ),
+        ),
+        (
+            "KillResponse",
+            object(
+                &[("session_id", string()), ("killed", boolean())],
+                &["session_id", "killed"],
+            ),
+        ),
+        (
+            "CaptureRequest",
+            object(
+                &[
+                    ("duration_secs", int()),
+                    (
+                        "mode",
+                        json!({ "type": "string", "enum": ["full", "headers"] }),
+                    ),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "CaptureInfo",
+            object(&capture_info_props(&[]), &CAPTURE_INFO_REQUIRED),
+        ),
+        (
+            "CaptureSummary",
+            object(
+                &capture_info_props(&[
+                    (
+                        "ended_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "reason",
+                        json!({
+                            "type": "string",
+                            "enum": [
+                                "expired",
+                                "size_limit",
+                                "session_closed",
+                                "stopped",
+                                "write_error"
+                            ]
+                        }),
+                    ),
+                    ("stopped_by", string()),
+                    ("packets", int()),
+                    ("payload_bytes", int()),
+                    ("file_bytes", int()),
+                    ("dropped", int()),
+                ]),
+                &CAPTURE_INFO_REQUIRED,
+            ),
+        ),
+        (
+            "ChangePasswordRequest",
+            object(
+                &[("current_password", string()), ("new_password", string())],
+                &["current_password", "new_password"],
+            ),
+        ),
+        (
+            "ChangePasswordResponse",
+            object(
+                &[("username", string()), ("persisted", boolean())],
+                &["username", "persisted"],
+            ),
+        ),
+        (
+            "UserLoginRequest",
+            object(
+                &[
+                    ("username", string()),
+                    ("password", string()),
+                    ("totp_code", string()),
+                ],
+                &["username", "password"],
+            ),
+        ),
+        (
+            "SelfStatus",
+            object(
+                &[
+                    ("username", string()),
+                    (
+                        "scope",
+                        json!({ "type": "string", "enum": ["read", "full"] }),
+                    ),
+                ],
+                &["username", "scope"],
+            ),
+        ),
+        (
+            "PersonalToken",
+            object(
+                &[
+                    ("id", string()),
+                    ("name", string()),
+                    ("created_at", json!({ "type": "string", "nullable": true })),
+                ],
+                &["id", "name"],
+            ),
+        ),
+        ("PersonalTokenList", array_of("PersonalToken")),
+        (
+            "CreateTokenRequest",
+            object(&[("name", string())], &["name"]),
+        ),
+        (
+            "ManagedGroup",
+            object(
+                &[
+                    ("name", string()),
+                    (
+                        "members",
+                        json!({
+                            "type": "array",
+                            "items": object(
+                                &[
+                                    ("username", string()),
+                                    ("disabled", boolean()),
+                                    ("active_connections", int()),
+                                    ("daily_bytes", int()),
+                                    ("monthly_bytes", int()),
+                                ],
+                                &["username", "disabled"],
+                            )
+                        }),
+                    ),
+                ],
+                &["name", "members"],
+            ),
+        ),
+        ("ManagedGroupList", array_of("ManagedGroup")),
+        (
+            "SetDisabledRequest",
+            object(&[("disabled", boolean())], &["disabled"]),
+        ),
+        (
+            "SetDisabledResponse",
+            object(
+                &[
+                    ("username", string()),
+                    ("disabled", boolean()),
+                    ("sessions_closed", int()),
+                ],
+                &["username", "disabled", "sessions_closed"],
+            ),
+        ),
+        (
+            "CreatedToken",
+            object(
+                &[
+                    ("id", string()),
+                    ("name", string()),
+                    ("created_at", string()),
+                    ("token", string()),
+                ],
+                &["id", "name", "token"],
+            ),
+        ),
+        (
+            "ConnectionsInfo",
+            object(
+                &[
+                    ("active_connections", int()),
+                    (
+                        "user_connections",
+                        json!({
+                            "type": "array",
+                            "items": object(
+                                &[("username", string()), ("connections", int())],
+                                &["username", "connections"],
+                            )
+                        }),
+                    ),
+                ],
+                &["active_connections", "user_connections"],
+            ),
+        ),
+        (
+            "Flow",
+            object(
+                &[
+                    ("id", int()),
+                    (
+                        "started_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    ("username", string()),
+                    ("protocol", string()),
+                    ("source_ip", string()),
+                    ("dest_host", string()),
+                    ("dest_ip", json!({ "type": "string", "nullable": true })),
+                    ("dest_port", int()),
+                    ("bytes_up", int()),
+                    ("bytes_down", int()),
+                    ("duration_ms", int()),
+                    ("close_reason", string()),
+                    ("correlation_id", string()),
+                    ("app_protocol", string()),
+                    ("server_name", string()),
+                    ("first_line", string()),
+                ],
+                &[
+                    "id",
+                    "started_at",
+                    "username",
+                    "protocol",
+                    "source_ip",
+                    "dest_host",
+                    "dest_port",
+                    "bytes_up",
+                    "bytes_down",
+                    "duration_ms",
+                    "close_reason",
+                ],
+            ),
+        ),
+        (
+            "FlowPage",
+            object(
+                &[("flows", array_of("Flow")), ("next_before", int())],
+                &["flows"],
+            ),
+        ),
+        (
+            "QuotaResetResult",
+            object(
+                &[("username", string()), ("reset", boolean())],
+                &["username", "reset"],
+            ),
+        ),
+        (
+            "QuotaResetRequest",
+            object(
+                &[(
+                    "counters",
+                    json!({
+                        "type": "array",
+                        "items": {
+                            "type": "string",
+                            "enum": [
+                                "hourly_bytes",
+                                "daily_bytes",
+                                "monthly_bytes",
+                                "total_bytes",
+                                "daily_connections",
+                                "monthly_connections"
+                            ]
+                        }
+                    }),
+                )],
+                &[],
+            ),
+        ),
+        (
+            "QuotaGrant",
+            object(
+                &[
+                    ("daily_bytes", int()),
+                    ("monthly_bytes", int()),
+                    ("total_bytes", int()),
+                    ("daily_connections", int()),
+                    ("monthly_connections", int()),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "UserUsage",
+            object(
+                &[
+                    ("username", string()),
+                    ("bytes_up", int()),
+                    ("bytes_down", int()),
+                    ("bytes_total", int()),
+                    ("connections", int()),
+                    ("logins", int()),
+                    ("session_secs", int()),
+                    ("session_hours", json!({ "type": "number" })),
+                ],
+                &[
+                    "username",
+                    "bytes_up",
+                    "bytes_down",
+                    "bytes_total",
+                    "connections",
+                    "logins",
+                    "session_secs",
+                    "session_hours",
+                ],
+            ),
+        ),
+        (
+            "UsageReport",
+            object(
+                &[
+                    ("month", string()),
+                    ("users", array_of("UserUsage")),
+                    ("total", schema_ref("UserUsage")),
+                ],
+                &["month", "users", "total"],
+            ),
+        ),
+        (
+            "ClusterNode",
+            object(
+                &[
+                    ("node_id", string()),
+                    (
+                        "updated_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    ("sessions", int()),
+                    ("is_self", boolean()),
+                ],
+                &["node_id", "updated_at", "sessions", "is_self"],
+            ),
+        ),
+        (
+            "ClusterInfo",
+            object(
+                &[
+                    ("node_id", string()),
+                    (
+                        "backend",
+                        json!({ "type": "string", "enum": ["redis", "gossip"] }),
+                    ),
+                    ("url", json!({ "type": "string", "nullable": true })),
+                    ("peers", json!({ "type": "array", "items": string() })),
+                    ("sync_interval_secs", int()),
+                    ("observer", boolean()),
+                    (
+                        "last_sync_at",
+                        json!({ "type": "string", "nullable": true }),
+                    ),
+                    ("last_error", json!({ "type": "string", "nullable": true })),
+                    ("failed_syncs", int()),
+                    ("nodes", array_of("ClusterNode")),
+                ],
+                &[
+                    "node_id",
+                    "backend",
+                    "url",
+                    "peers",
+                    "sync_interval_secs",
+                    "observer",
+                    "failed_syncs",
+                    "nodes",
+                ],
+            ),
+        ),
+        (
+            "BanInfo",
+            object(
+                &[
+                    ("ip", string()),
+                    ("remaining_secs", int()),
+                    ("tenant", json!({ "type": "string", "nullable": true })),
+                ],
+                &["ip", "remaining_secs"],
+            ),
+        ),
+        ("BanInfoList", array_of("BanInfo")),
+        (
+            "BanRequest",
+            object(
+                &[
+                    ("ip", string()),
+                    ("duration_secs", int()),
+                    ("tenant", string()),
+                ],
+                &["ip"],
+            ),
+        ),
+        (
+            "TimelineBucket",
+            object(
+                &[
+                    ("start", json!({ "type": "string", "format": "date-time" })),
+                    ("auth_failure", int()),
+                    ("ban", int()),
+                    ("ip_guard", int()),
+                ],
+                &["start", "auth_failure", "ban", "ip_guard"],
+            ),
+        ),
+        (
+            "SecurityTimeline",
+            object(
+                &[
+                    ("window_secs", int()),
+                    ("step_secs", int()),
+                    ("buckets", array_of("TimelineBucket")),
+                ],
+                &["window_secs", "step_secs", "buckets"],
+            ),
+        ),
+        (
+            "MetricsSnapshot",
+            object(
+                &[
+                    (
+                        "timestamp",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    ("metrics", array_of("MetricFamily")),
+                ],
+                &["timestamp", "metrics"],
+            ),
+        ),
+        (
+            "MetricFamily",
+            object(
+                &[
+                    ("name", string()),
+                    ("type", string()),
+                    ("help", string()),
+                    ("unit", string()),
+                    ("samples", array_of("MetricSample")),
+                ],
+                &["name", "type", "help", "samples"],
+            ),
+        ),
+        (
+            "MetricSample",
+            object(
+                &[
+                    ("name", string()),
+                    (
+                        "labels",
+                        json!({ "type": "object", "additionalProperties": { "type": "string" } }),
+                    ),
+                    ("value", json!({ "type": "number", "nullable": true })),
+                ],
+                &["name", "labels", "value"],
+            ),
+        ),
+        (
+            "HostKeys",
+            object(
+                &[
+                    ("keys", array_of("HostKey")),
+                    ("retire_at", int()),
+                    ("retired", boolean()),
+                ],
+                &["keys", "retired"],
+            ),
+        ),
+        (
+            "HostKey",
+            object(
+                &[
+                    (
+                        "role",
+                        json!({ "type": "string", "enum": ["current", "new"] }),
+                    ),
+                    ("path", string()),
+                    ("algorithm", string()),
+                    ("fingerprint", string()),
+                    ("public_key", string()),
+                    ("served", boolean()),
+                ],
+                &[
+                    "role",
+                    "path",
+                    "algorithm",
+                    "fingerprint",
+                    "public_key",
+                    "served",
+                ],
+            ),
+        ),
+        ("ExpiringKeyList", array_of("ExpiringKey")),
+        (
+            "ExpiringKey",
+            object(
+                &[
+                    ("username", string()),
+                    ("fingerprint", string()),
+                    ("comment", string()),
+                    (
+                        "created_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "expires_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    ("expired", boolean()),
+                ],
+                &["username", "fingerprint", "expires_at", "expired"],
+            ),
+        ),
+        ("KeyEnrollmentList", array_of("KeyEnrollment")),
+        (
+            "KeyEnrollment",
+            object(
+                &[
+                    ("id", string()),
+                    ("username", string()),
+                    ("fingerprint", string()),
+                    ("public_key", string()),
+                    ("source_ip", string()),
+                    ("hassh", string()),
+                    (
+                        "requested_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "status",
+                        json!({ "type": "string", "enum": ["pending", "approved", "rejected"] }),
+                    ),
+                    ("decided_by", string()),
+                ],
+                &[
+                    "id",
+                    "username",
+                    "fingerprint",
+                    "public_key",
+                    "source_ip",
+                    "requested_at",
+                    "status",
+                ],
+            ),
+        ),
+        ("InvitationList", array_of("Invitation")),
+        (
+            "Invitation",
+            object(
+                &[
+                    ("id", string()),
+                    ("username", string()),
+                    ("group", string()),
+                    ("created_by", string()),
+                    (
+                        "created_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "expires_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                ],
+                &["id", "username", "created_by", "created_at", "expires_at"],
+            ),
+        ),
+        (
+            "CreatedInvitation",
+            object(
+                &[
+                    ("id", string()),
+                    ("username", string()),
+                    ("group", string()),
+                    ("created_by", string()),
+                    (
+                        "created_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    (
+                        "expires_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                    ("token", string()),
+                    ("url", string()),
+                ],
+                &["id", "username", "expires_at", "token", "url"],
+            ),
+        ),
+        (
+            "CreateInvitationRequest",
+            object(
+                &[
+                    ("username", string()),
+                    ("group", string()),
+                    ("ttl_hours", int()),
+                ],
+                &["username"],
+            ),
+        ),
+        (
+            "InviteTokenRequest",
+            object(&[("token", string())], &["token"]),
+        ),
+        (
+            "InviteInfo",
+            object(
+                &[
+                    ("username", string()),
+                    ("group", string()),
+                    (
+                        "expires_at",
+                        json!({ "type": "string", "format": "date-time" }),
+                    ),
+                ],
+                &["username", "expires_at"],
+            ),
+        ),
+        (
+            "AcceptInviteRequest",
+            object(
+                &[
+                    ("token", string()),
+                    ("password", string()),
+                    ("public_key", string()),
+                ],
+                &["token", "password"],
+            ),
+        ),
+        (
+            "AcceptedInvite",
+            object(&[("username", string())], &["username"]),
+        ),
+        (
+            "UnbanResult",
+            object(
+                &[("ip", string()), ("unbanned", boolean())],
+                &["ip", "unbanned"],
+            ),
+        ),
+        (
+            "IpIntelStatus",
+            object(
+                &[
+                    (
+                        "source",
+                        json!({ "type": "string", "enum": ["embedded", "file", "refresh"] }),
+                    ),
+                    ("tor_entries", int()),
+                    ("datacenter_entries", int()),
+                    (
+                        "deny",
+                        json!({
+                            "type": "array",
+                            "items": {
+                                "type": "string",
+                                "enum": ["source_is_tor", "source_is_datacenter"]
+                            }
+                        }),
+                    ),
+                ],
+                &["source", "tor_entries", "datacenter_entries", "deny"],
+            ),
+        ),
+        (
+            "BroadcastRequest",
+            object(
+                &[
+                    ("message", string()),
+                    ("users", json!({ "type": "array", "items": string() })),
+                ],
+                &["message"],
+            ),
+        ),
+        (
+            "BroadcastResponse",
+            object(&[("delivered_to", int())], &["delivered_to"]),
+        ),
+        (
+            "LoginRequest",
+            object(
+                &[
+                    ("token", string()),
+                    ("username", string()),
+                    ("password", string()),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "LoginResponse",
+            object(
+                &[
+                    ("username", string()),
+                    ("role", role()),
+                    ("idle_timeout_secs", int()),
+                ],
+                &["username", "role", "idle_timeout_secs"],
+            ),
+        ),
+        (
+            "Me",
+            object(
+                &[
+                    ("name", string()),
+                    ("role", role()),
+                    ("tenant", string()),
+                    ("can_operate", boolean()),
+                    ("can_administer", boolean()),
+                ],
+                &["name", "role", "can_operate", "can_administer"],
+            ),
+        ),
+        (
+            "CsrfToken",
+            object(&[("csrf_token", string())], &["csrf_token"]),
+        ),
+        (
+            "SseTicket",
+            object(
+                &[("ticket", string()), ("expires_in", int())],
+                &["ticket", "expires_in"],
+            ),
+        ),
+        (
+            "BackupPayload",
+            object(
+                &[
+                    ("version", string()),
+                    ("timestamp", string()),
+                    ("bans", array_of("BanInfo")),
+                    (
+                        "quotas",
+                        json!({
+                            "type": "object",
+                            "additionalProperties": schema_ref("Object")
+                        }),
+                    ),
+                ],
+                &["version", "timestamp", "bans", "quotas"],
+            ),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, schema)| (name.to_string(), schema))
+    .collect();
+    json!({ "securitySchemes": security_schemes(), "schemas": schemas })
+}
+
+fn operation(e: &Endpoint) -> Value {
+    let mut op = Map::new();
+    op.insert("tags".into(), json!([e.tag]));
+    op.insert("summary".into(), json!(e.summary));
+    op.insert(
+        "operationId".into(),
+        json!(format!(
+            "{}{}",
+            e.method,
+            e.path.replace(['/', '.', '-'], "_").replace(['{', '}'], "")
+        )),
+    );
+
+    let mut params: Vec<Value> = e
+        .path
+        .split('/')
+        .filter_map(|seg| seg.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } })
+        })
+        .collect();
+    params.extend(e.query.iter().map(|(name, required, desc)| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": required,
+            "description": desc,
+            "schema": { "type": "string" }
+        })
+    }));
+    if !params.is_empty() {
+        op.insert("parameters".into(), Value::Array(params));
+    }
+
+    if let Some(req) = e.request {
+        op.insert(
+            "requestBody".into(),
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema_ref(req) } }
+            }),
+        );
+    }
+
+    // Probes return their body as-is; everything under /api uses the envelope
+    let ok_schema = match (e.response, e.path.starts_with("/api/")) {
+        (Some(data), true) => json!({
+            "allOf": [
+                schema_ref("Envelope"),
+                { "type": "object", "properties": { "data": schema_ref(data) } }
+            ]
+        }),
+        (Some(data), false) => schema_ref(data),
+        (None, _) => json!({}),
+    };
+    let mut responses = Map::new();
+    responses.insert(
+        "200".into(),
+        json!({
+            "description": "OK",
+            "content": { "application/json": { "schema": ok_schema } }
+        }),
+    );
+    let min_role = match e.auth {
+        Auth::Viewer => Some("viewer"),
+        Auth::Operator => Some("operator"),
+        Auth::Admin => Some("admin"),
+        Auth::None | Auth::User | Auth::UserRead => None,
+    };
+    match e.auth {
+        Auth::None => {
+            op.insert("security".into(), json!([]));
+        }
+        Auth::User | Auth::UserRead => {
+            responses.insert("401".into(), json!({ "description": "Unauthorized" }));
+            if e.auth == Auth::User {
+                responses.insert(
+                    "403".into(),
+                    json!({ "description": "Read-only personal token" }),
+                );
+            }
+            op.insert(
+                "security".into(),
+                json!([{ "userToken": [] }, { "userSession": [] }]),
+            );
+        }
+        _ => {
+            responses.insert("401".into(), json!({ "description": "Unauthorized" }));
+            op.insert(
+                "security".into(),
+                json!([{ "adminToken": [] }, { "sessionCookie": [] }]),
+            );
+        }
+    }
+    if let Some(role) = min_role {
+        if role != "viewer" {
+            responses.insert(
+                "403".into(),
+                json!({ "description": format!("Requires the '{}' role", role) }),
+            );
+        }
+        op.insert("x-s5-min-role".into(), json!(role));
+    }
+    if e.path.starts_with("/api/") {
+        responses.insert(
+            "default".into(),
+            json!({
+                "description": "Error envelope",
+                "content": { "application/json": { "schema": schema_ref("Envelope") } }
+            }),
+        );
+    }
+    op.insert("responses".into(), Value::Object(responses));
+    Value::Object(op)
+}
+
+/// Build the OpenAPI 3.0 document.
+pub fn spec() -> Value {
+    let mut paths = Map::new();
+    for e in ENDPOINTS {
+        let item = paths
+            .entry(e.path.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(ops) = item {
+            ops.insert(e.method.to_string(), operation(e));
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "s5 management API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Admin endpoints return `{ success, data, error }` envelopes. \
+                            This document is maintained by hand and checked against the \
+                            server's routes and the JSON its handlers read and write."
+        },
+        "security": [{ "adminToken": [] }],
+        "paths": paths,
+        "components": components(),
+    })
+}
+
+/// GET /api/openapi.json — unauthenticated, describes routes only.
+pub async fn openapi_json() -> impl IntoResponse {
+    axum::Json(spec())
+}
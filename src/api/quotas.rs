@@ -1,11 +1,17 @@
+use super::rbac::Principal;
 use super::{ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::config::types::QuotaConfig;
+use crate::quota::{QuotaCounter, QuotaGrant, UserQuotaUsage};
 use axum::{
+    body::Bytes,
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
+    Extension, Json,
 };
-use serde::Serialize;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
 #[derive(Serialize)]
 pub struct QuotaSummary {
@@ -24,10 +30,42 @@ pub struct QuotaSummary {
     pub total_bytes_limit: u64,
     pub daily_connections_limit: u32,
     pub monthly_connections_limit: u32,
+    /// One-off extras on top of the limits (`PATCH /api/users/{name}/quota`)
+    pub granted: QuotaGrant,
+}
+
+impl QuotaSummary {
+    /// Combine tracked usage with the user's configured limits.
+    pub(crate) fn new(
+        username: String,
+        usage: &UserQuotaUsage,
+        quotas: Option<&QuotaConfig>,
+    ) -> Self {
+        Self {
+            username,
+            daily_bytes: usage.daily_bytes,
+            daily_connections: usage.daily_connections,
+            monthly_bytes: usage.monthly_bytes,
+            monthly_connections: usage.monthly_connections,
+            current_rate_bps: usage.current_rate_bps,
+            hourly_bytes: usage.hourly_bytes,
+            total_bytes: usage.total_bytes,
+            daily_bytes_limit: quotas.map_or(0, |q| q.daily_bandwidth_bytes),
+            monthly_bytes_limit: quotas.map_or(0, |q| q.monthly_bandwidth_bytes),
+            hourly_bytes_limit: quotas.map_or(0, |q| q.bandwidth_per_hour_bytes),
+            total_bytes_limit: quotas.map_or(0, |q| q.total_bandwidth_bytes),
+            daily_connections_limit: quotas.map_or(0, |q| q.daily_connection_limit),
+            monthly_connections_limit: quotas.map_or(0, |q| q.monthly_connection_limit),
+            granted: usage.granted,
+        }
+    }
 }
 
 /// GET /api/quotas — summary of all tracked users' quota usage.
-pub async fn list_quotas(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn list_quotas(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
     let Some(ref qt) = state.quota_tracker else {
         return ApiResponse::ok(Vec::<QuotaSummary>::new()).into_response();
     };
@@ -36,28 +74,14 @@ pub async fn list_quotas(State(state): State<AppState>) -> impl IntoResponse {
     let usernames = qt.tracked_users();
     let summaries: Vec<QuotaSummary> = usernames
         .into_iter()
+        .filter(|username| principal.sees(username))
         .map(|username| {
             let usage = qt.get_user_usage(&username);
             let quotas = auth
                 .user_store()
                 .get(&username)
                 .and_then(|u| u.quotas.as_ref());
-            QuotaSummary {
-                username,
-                daily_bytes: usage.daily_bytes,
-                daily_connections: usage.daily_connections,
-                monthly_bytes: usage.monthly_bytes,
-                monthly_connections: usage.monthly_connections,
-                current_rate_bps: usage.current_rate_bps,
-                hourly_bytes: usage.hourly_bytes,
-                total_bytes: usage.total_bytes,
-                daily_bytes_limit: quotas.map_or(0, |q| q.daily_bandwidth_bytes),
-                monthly_bytes_limit: quotas.map_or(0, |q| q.monthly_bandwidth_bytes),
-                hourly_bytes_limit: quotas.map_or(0, |q| q.bandwidth_per_hour_bytes),
-                total_bytes_limit: quotas.map_or(0, |q| q.total_bandwidth_bytes),
-                daily_connections_limit: quotas.map_or(0, |q| q.daily_connection_limit),
-                monthly_connections_limit: quotas.map_or(0, |q| q.monthly_connection_limit),
-            }
+            QuotaSummary::new(username, &usage, quotas)
         })
         .collect();
 
@@ -67,8 +91,12 @@ pub async fn list_quotas(State(state): State<AppState>) -> impl IntoResponse {
 /// GET /api/quotas/:username — detail for a specific user.
 pub async fn get_user_quota(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Path(username): Path<String>,
 ) -> impl IntoResponse {
+    if !principal.sees(&username) {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "user not found").into_response();
+    }
     let Some(ref qt) = state.quota_tracker else {
         return ApiResponse::err(StatusCode::NOT_FOUND, "quota tracking not available")
             .into_response();
@@ -80,23 +108,7 @@ pub async fn get_user_quota(
         .get(&username)
         .and_then(|u| u.quotas.as_ref());
     let usage = qt.get_user_usage(&username);
-    ApiResponse::ok(QuotaSummary {
-        username,
-        daily_bytes: usage.daily_bytes,
-        daily_connections: usage.daily_connections,
-        monthly_bytes: usage.monthly_bytes,
-        monthly_connections: usage.monthly_connections,
-        current_rate_bps: usage.current_rate_bps,
-        hourly_bytes: usage.hourly_bytes,
-        total_bytes: usage.total_bytes,
-        daily_bytes_limit: quotas.map_or(0, |q| q.daily_bandwidth_bytes),
-        monthly_bytes_limit: quotas.map_or(0, |q| q.monthly_bandwidth_bytes),
-        hourly_bytes_limit: quotas.map_or(0, |q| q.bandwidth_per_hour_bytes),
-        total_bytes_limit: quotas.map_or(0, |q| q.total_bandwidth_bytes),
-        daily_connections_limit: quotas.map_or(0, |q| q.daily_connection_limit),
-        monthly_connections_limit: quotas.map_or(0, |q| q.monthly_connection_limit),
-    })
-    .into_response()
+    ApiResponse::ok(QuotaSummary::new(username, &usage, quotas)).into_response()
 }
 
 #[derive(Serialize)]
@@ -108,6 +120,7 @@ pub struct QuotaResetResult {
 /// POST /api/quotas/:username/reset — reset quotas for a specific user.
 pub async fn reset_user_quota(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Path(username): Path<String>,
 ) -> impl IntoResponse {
     let Some(ref qt) = state.quota_tracker else {
@@ -117,6 +130,14 @@ pub async fn reset_user_quota(
 
     qt.reset_user(&username);
     info!(user = %username, "Quota reset via API");
+    if let Some(ref audit) = state.audit {
+        // Everything but the hourly window
+        audit.log_event(AuditEvent::quota_reset(
+            &username,
+            &QuotaCounter::ALL[1..],
+            &principal.name,
+        ));
+    }
 
     ApiResponse::ok(QuotaResetResult {
         username,
@@ -124,3 +145,119 @@ pub async fn reset_user_quota(
     })
     .into_response()
 }
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaResetRequest {
+    /// Counters to reset; all of them when empty or left out
+    #[serde(default)]
+    pub counters: Vec<QuotaCounter>,
+}
+
+/// Quota of a configured user, or the error response when there is none.
+async fn user_quota_summary(
+    state: &AppState,
+    username: &str,
+) -> Result<QuotaSummary, axum::response::Response> {
+    let Some(ref qt) = state.quota_tracker else {
+        return Err(
+            ApiResponse::err(StatusCode::NOT_FOUND, "quota tracking not available").into_response(),
+        );
+    };
+    let auth = state.auth_service.read().await;
+    let Some(user) = auth.user_store().get(username) else {
+        return Err(ApiResponse::err(
+            StatusCode::NOT_FOUND,
+            format!("user '{}' not found", username),
+        )
+        .into_response());
+    };
+    let usage = qt.get_user_usage(username);
+    Ok(QuotaSummary::new(
+        username.to_string(),
+        &usage,
+        user.quotas.as_ref(),
+    ))
+}
+
+/// POST /api/users/:username/quota/reset — zero some or all of a user's
+/// counters, e.g. after a false-positive burn. Granted extras are kept.
+pub async fn reset_quota_counters(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(username): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    // Checked first so an unknown user gets a 404 before anything changes
+    if let Err(resp) = user_quota_summary(&state, &username).await {
+        return resp;
+    }
+    // Parsed by hand: a malformed body must not fall back to resetting everything
+    let requested = if body.is_empty() {
+        Vec::new()
+    } else {
+        match serde_json::from_slice::<QuotaResetRequest>(&body) {
+            Ok(req) => req.counters,
+            Err(e) => {
+                return ApiResponse::err(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+                    .into_response()
+            }
+        }
+    };
+    let counters = if requested.is_empty() {
+        QuotaCounter::ALL.to_vec()
+    } else {
+        requested
+    };
+    if let Some(ref qt) = state.quota_tracker {
+        qt.reset_counters(&username, &counters);
+    }
+    warn!(user = %username, by = %principal.name, ?counters, "Quota counters reset");
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::quota_reset(
+            &username,
+            &counters,
+            &principal.name,
+        ));
+    }
+
+    match user_quota_summary(&state, &username).await {
+        Ok(summary) => ApiResponse::ok(summary).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// PATCH /api/users/:username/quota — grant one-off extra allowance on top
+/// of the user's configured quotas. Amounts add to earlier grants.
+pub async fn adjust_user_quota(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(username): Path<String>,
+    Json(grant): Json<QuotaGrant>,
+) -> impl IntoResponse {
+    if let Err(resp) = user_quota_summary(&state, &username).await {
+        return resp;
+    }
+    if grant.is_empty() {
+        return ApiResponse::err(StatusCode::BAD_REQUEST, "nothing to grant").into_response();
+    }
+    let Some(ref qt) = state.quota_tracker else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "quota tracking not available")
+            .into_response();
+    };
+    let total = qt.grant_user(&username, &grant);
+    warn!(user = %username, by = %principal.name, ?grant, "Extra quota granted");
+    if let Some(ref audit) = state.audit {
+        audit.log_event(AuditEvent::quota_adjusted(
+            &username,
+            &grant,
+            &total,
+            &principal.name,
+        ));
+    }
+
+    match user_quota_summary(&state, &username).await {
+        Ok(summary) => ApiResponse::ok(summary).into_response(),
+        Err(resp) => resp,
+    }
+}
@@ -0,0 +1,190 @@
+//! Role-based access control for the management API and dashboard.
+//!
+//! Every authenticated request carries a [`Principal`] request extension set by
+//! the auth middleware: the bearer token and token-based dashboard logins are
+//! `admin`, `[[api.accounts]]` logins carry the account's role. Route groups
+//! are gated with [`require_operator`] / [`require_admin`].
+//!
+//! A principal may be limited to one `[[tenants]]` entry (a tenant API token
+//! or a tenant account): it reaches only the [`TENANT_ROUTES`], which show
+//! and act on the users of that tenant.
+
+use super::{ApiResponse, AppState};
+use crate::config::types::DashboardRole;
+use axum::{
+    extract::{MatchedPath, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Serialize;
+
+/// Name recorded for requests authenticated with the shared API token.
+pub const TOKEN_PRINCIPAL: &str = "api-token";
+
+/// The authenticated caller of an API request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: DashboardRole,
+    /// `[[tenants]]` entry the caller is limited to (None = whole server)
+    pub tenant: Option<String>,
+}
+
+impl Principal {
+    pub fn new(name: impl Into<String>, role: DashboardRole) -> Self {
+        Self {
+            name: name.into(),
+            role,
+            tenant: None,
+        }
+    }
+
+    /// Limit this principal to the users of `tenant`.
+    pub fn with_tenant(mut self, tenant: Option<String>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// Principal for a `[[tenants]]` API token.
+    pub fn tenant_token(tenant: &str) -> Self {
+        Self::new(format!("tenant:{tenant}"), DashboardRole::Operator)
+            .with_tenant(Some(tenant.to_string()))
+    }
+
+    /// Principal for the shared API token (full access).
+    pub fn api_token() -> Self {
+        Self::new(TOKEN_PRINCIPAL, DashboardRole::Admin)
+    }
+
+    /// True if this principal's role is at least `min`.
+    pub fn allows(&self, min: DashboardRole) -> bool {
+        self.role >= min
+    }
+
+    /// True if this principal may see and act on `username`.
+    pub fn sees(&self, username: &str) -> bool {
+        self.tenant
+            .as_deref()
+            .is_none_or(|tenant| crate::auth::tenant::in_tenant(username, tenant))
+    }
+
+    /// True if this principal may see and lift the ban `key`: a tenant
+    /// principal only those of its tenant.
+    pub fn sees_ban(&self, key: &crate::security::ban::BanKey) -> bool {
+        self.tenant.is_none() || self.tenant == key.tenant
+    }
+}
+
+/// Routes open to tenant principals. Their handlers filter by
+/// [`Principal::sees`]; everything else is server-wide and answered `403`.
+pub const TENANT_ROUTES: &[&str] = &[
+    "/api/health",
+    "/api/me",
+    "/api/users",
+    "/api/connections",
+    "/api/quotas",
+    "/api/quotas/:username",
+    "/api/sessions",
+    "/api/sessions/history",
+    "/api/sessions/:id",
+    "/api/kick/:username",
+    "/api/bans",
+    "/api/bans/:ip",
+    "/api/sse-ticket",
+    "/api/events",
+    "/api/ws",
+];
+
+/// Route layer: keep tenant principals to [`TENANT_ROUTES`].
+pub async fn tenant_routes(req: Request<axum::body::Body>, next: Next) -> Response {
+    let Some(principal) = req.extensions().get::<Principal>() else {
+        return next.run(req).await;
+    };
+    let Some(tenant) = &principal.tenant else {
+        return next.run(req).await;
+    };
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+    if TENANT_ROUTES.contains(&route) {
+        return next.run(req).await;
+    }
+    tracing::debug!(
+        principal = %principal.name,
+        tenant = %tenant,
+        path = %req.uri().path(),
+        "API request outside the tenant's routes"
+    );
+    ApiResponse::<()>::err(StatusCode::FORBIDDEN, "not available to tenant accounts")
+        .into_response()
+}
+
+/// The rejection is boxed: `Response` is too large for an `Err` variant.
+fn check_role(req: &Request<axum::body::Body>, min: DashboardRole) -> Result<(), Box<Response>> {
+    match req.extensions().get::<Principal>() {
+        Some(p) if p.allows(min) => Ok(()),
+        Some(p) => {
+            tracing::debug!(
+                principal = %p.name,
+                role = %p.role,
+                required = %min,
+                path = %req.uri().path(),
+                "API request denied by role"
+            );
+            Err(Box::new(
+                ApiResponse::<()>::err(StatusCode::FORBIDDEN, format!("requires role '{}'", min))
+                    .into_response(),
+            ))
+        }
+        // Route layered without auth: fail closed
+        None => Err(Box::new(
+            (StatusCode::UNAUTHORIZED, "unauthorized").into_response(),
+        )),
+    }
+}
+
+/// Route layer: reject callers below `operator`.
+pub async fn require_operator(req: Request<axum::body::Body>, next: Next) -> Response {
+    match check_role(&req, DashboardRole::Operator) {
+        Ok(()) => next.run(req).await,
+        Err(resp) => *resp,
+    }
+}
+
+/// Route layer: reject callers below `admin`.
+pub async fn require_admin(req: Request<axum::body::Body>, next: Next) -> Response {
+    match check_role(&req, DashboardRole::Admin) {
+        Ok(()) => next.run(req).await,
+        Err(resp) => *resp,
+    }
+}
+
+#[derive(Serialize)]
+struct MeResponse {
+    name: String,
+    role: DashboardRole,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    can_operate: bool,
+    can_administer: bool,
+}
+
+/// GET /api/me — who the caller is authenticated as, and what they may do
+/// (nothing beyond viewing on a read-only observer).
+pub async fn me(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    let writable = !state.cluster.as_ref().is_some_and(|c| c.is_observer());
+    ApiResponse::ok(MeResponse {
+        can_operate: writable && principal.allows(DashboardRole::Operator),
+        can_administer: writable && principal.allows(DashboardRole::Admin),
+        name: principal.name,
+        role: principal.role,
+        tenant: principal.tenant,
+    })
+}
@@ -1,6 +1,13 @@
 use super::{ApiResponse, AppState};
-use axum::{extract::State, response::IntoResponse};
+use crate::config::persist::{self, UpdateError};
+use crate::config::types::AppConfig;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use serde::Serialize;
+use std::path::PathBuf;
 use tracing::{error, info};
 
 #[derive(Serialize)]
@@ -20,30 +27,22 @@ pub async fn reload_config(State(state): State<AppState>) -> impl IntoResponse {
         }
     };
 
-    match crate::config::load_config(&config_path) {
-        Ok(new_config) => {
-            let users_count = new_config.users.len();
-            match state.auth_service.write().await.reload(&new_config) {
-                Ok(()) => {
-                    state.security.write().await.reload(&new_config);
-                    if let Some(ref audit) = state.audit {
-                        audit.log_config_reload(users_count, true, None);
-                    }
+    match crate::config::load_config_with_source(&config_path) {
+        Ok((new_config, content)) => {
+            match apply_config(&state, &new_config, &content, "api").await {
+                Ok(users_count) => {
                     info!(users = users_count, "Config reloaded via API");
                     ApiResponse::ok(ReloadResult { users_count }).into_response()
                 }
-                Err(e) => {
-                    error!(error = %e, "Failed to reload auth service via API");
-                    if let Some(ref audit) = state.audit {
-                        audit.log_config_reload(0, false, Some(e.to_string()));
-                    }
-                    ApiResponse::err(axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
-                        .into_response()
-                }
+                Err(e) => ApiResponse::err(axum::http::StatusCode::INTERNAL_SERVER_ERROR, e)
+                    .into_response(),
             }
         }
         Err(e) => {
             error!(error = %e, "Failed to load config via API");
+            if let Some(ref readiness) = state.readiness {
+                readiness.set_config_valid(false);
+            }
             if let Some(ref audit) = state.audit {
                 audit.log_config_reload(0, false, Some(e.to_string()));
             }
@@ -52,3 +51,94 @@ pub async fn reload_config(State(state): State<AppState>) -> impl IntoResponse {
         }
     }
 }
+
+/// Apply a loaded configuration (users, security policy, host keys) and
+/// record it in the config history as applied by `source`. Returns the
+/// number of users, or the error that left the previous config in place.
+pub(crate) async fn apply_config(
+    state: &AppState,
+    new_config: &AppConfig,
+    content: &str,
+    source: &str,
+) -> Result<usize, String> {
+    let users_count = new_config.users.len();
+    if let Err(e) = state.auth_service.write().await.reload(new_config) {
+        error!(error = %e, "Failed to reload auth service via API");
+        if let Some(ref readiness) = state.readiness {
+            readiness.set_config_valid(false);
+        }
+        if let Some(ref audit) = state.audit {
+            audit.log_config_reload(0, false, Some(e.to_string()));
+        }
+        return Err(e.to_string());
+    }
+    state.security.write().await.reload(new_config);
+    if let Some(ref host_keys) = state.host_keys {
+        if let Err(e) = host_keys.reload(&new_config.server) {
+            error!(error = %e, "Failed to reload host keys, keeping current ones");
+        }
+    }
+    if let Some(ref history) = state.config_history {
+        history.set_keep(new_config.api.config_history);
+        history.record(content, source, users_count);
+    }
+    if let Some(ref readiness) = state.readiness {
+        readiness.set_config_valid(true);
+    }
+    if let Some(ref audit) = state.audit {
+        audit.log_config_reload(users_count, true, None);
+    }
+    Ok(users_count)
+}
+
+/// Edit the config file at `path` with `edit` (see
+/// [`persist::update_config`]) and parse the result. Returns the written
+/// content and its config, or the response to send: 422 when the edit is
+/// refused, 409 when the file was edited by hand meanwhile, 500 when it
+/// cannot be read or written.
+pub(crate) async fn edit_config_file(
+    path: PathBuf,
+    edit: impl FnOnce(&str) -> anyhow::Result<String> + Send + 'static,
+) -> Result<(String, AppConfig), Response> {
+    let edited = tokio::task::spawn_blocking(move || {
+        let mut new_config = None;
+        let updated = persist::update_config(&path, |content| {
+            let updated = edit(content)?;
+            new_config = Some(crate::config::parse_config(&updated)?);
+            Ok(Some(updated))
+        })?;
+        Ok::<_, UpdateError>(
+            updated
+                .zip(new_config)
+                .expect("set once the edit has been written"),
+        )
+    })
+    .await;
+    let err = match edited {
+        Ok(Ok(edited)) => return Ok(edited),
+        Ok(Err(e)) => e,
+        Err(e) => UpdateError::Write(e.into()),
+    };
+    let response = match err {
+        UpdateError::Edit(e) => {
+            return Err(
+                ApiResponse::err(StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e))
+                    .into_response(),
+            )
+        }
+        UpdateError::Changed(_) => {
+            return Err(
+                ApiResponse::err(StatusCode::CONFLICT, format!("{}; retry", err)).into_response(),
+            )
+        }
+        UpdateError::Read(e) => {
+            error!(error = %format!("{:#}", e), "Failed to read config for edit");
+            format!("failed to read config: {:#}", e)
+        }
+        UpdateError::Write(e) => {
+            error!(error = %format!("{:#}", e), "Failed to write edited config");
+            format!("failed to write config: {:#}", e)
+        }
+    };
+    Err(ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, response).into_response())
+}
@@ -0,0 +1,50 @@
+use super::{ApiResponse, AppState};
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// `YYYY-MM` (default: the current month, UTC)
+    month: Option<String>,
+    /// `json` (default) or `csv`
+    format: Option<String>,
+}
+
+/// GET /api/reports/usage — per-user totals of one month, from closed
+/// sessions.
+pub async fn usage_report(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> impl IntoResponse {
+    let month = match query.month {
+        Some(month) if crate::reports::parse_month(&month).is_none() => {
+            return ApiResponse::err(StatusCode::BAD_REQUEST, "month must be YYYY-MM")
+                .into_response();
+        }
+        Some(month) => month,
+        None => crate::reports::month_of(Utc::now()),
+    };
+    let report = state.proxy_engine.usage_history().report(&month);
+    match query.format.as_deref() {
+        None | Some("json") => ApiResponse::ok(report).into_response(),
+        Some("csv") => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"s5-usage-{}.csv\"", month),
+                ),
+            ],
+            report.to_csv(),
+        )
+            .into_response(),
+        Some(_) => {
+            ApiResponse::err(StatusCode::BAD_REQUEST, "format must be json or csv").into_response()
+        }
+    }
+}
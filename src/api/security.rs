@@ -0,0 +1,122 @@
+use crate::api::{ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::security::timeline::{SecurityKind, SecurityTimeline, TimelineBucket, RETENTION_SECS};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+const NO_AUDIT: &str = "audit logging not available";
+/// Default chart window.
+const DEFAULT_WINDOW_SECS: i64 = 3600;
+/// Default number of events per request.
+const DEFAULT_EVENT_LIMIT: usize = 100;
+/// Maximum number of events per request.
+const MAX_EVENT_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct TimelineQuery {
+    /// Seconds back from now (default 3600, max 86400).
+    pub window: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct Timeline {
+    window_secs: i64,
+    step_secs: i64,
+    /// Oldest first, one per step, empty steps included
+    buckets: Vec<TimelineBucket>,
+}
+
+#[derive(Deserialize)]
+pub struct SecurityEventQuery {
+    /// `auth_failure`, `ban` or `ip_guard`; all kinds when absent.
+    pub kind: Option<SecurityKind>,
+    /// Start of the range, inclusive (RFC 3339; default 24 h ago).
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive (RFC 3339; default now).
+    pub to: Option<DateTime<Utc>>,
+    /// Number of events (default 100, max 1000).
+    pub limit: Option<usize>,
+}
+
+fn timeline(state: &AppState) -> Option<&SecurityTimeline> {
+    state
+        .audit
+        .as_ref()
+        .map(|audit| &**audit.security_timeline())
+}
+
+/// GET /api/security/timeline — auth failures, bans and ip_guard blocks per
+/// step over the last `window` seconds.
+pub async fn security_timeline(
+    State(state): State<AppState>,
+    Query(query): Query<TimelineQuery>,
+) -> impl IntoResponse {
+    let Some(timeline) = timeline(&state) else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_AUDIT).into_response();
+    };
+
+    let window_secs = query
+        .window
+        .unwrap_or(DEFAULT_WINDOW_SECS)
+        .clamp(60, RETENTION_SECS);
+    let step_secs = SecurityTimeline::step_for(window_secs);
+    ApiResponse::ok(Timeline {
+        window_secs,
+        step_secs,
+        buckets: timeline.buckets(Utc::now(), window_secs, step_secs),
+    })
+    .into_response()
+}
+
+/// GET /api/security/events — the audit entries behind the timeline, newest
+/// first.
+pub async fn security_events(
+    State(state): State<AppState>,
+    Query(query): Query<SecurityEventQuery>,
+) -> impl IntoResponse {
+    let Some(timeline) = timeline(&state) else {
+        return ApiResponse::err(StatusCode::NOT_FOUND, NO_AUDIT).into_response();
+    };
+
+    let now = Utc::now();
+    // `to` defaults a second ahead so an event logged this instant is included
+    let to = query.to.unwrap_or(now + Duration::seconds(1));
+    let from = query
+        .from
+        .unwrap_or(now - Duration::seconds(RETENTION_SECS));
+    if from >= to {
+        return ApiResponse::err(StatusCode::BAD_REQUEST, "'from' must be before 'to'")
+            .into_response();
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENT_LIMIT)
+        .min(MAX_EVENT_LIMIT);
+    let events: Vec<AuditEvent> = timeline.events(query.kind, from, to, limit);
+    ApiResponse::ok(events).into_response()
+}
+
+/// GET /api/security/ip-intel — where the `[ip_intel]` ranges came from and
+/// how many there are.
+pub async fn ip_intel_status(State(state): State<AppState>) -> impl IntoResponse {
+    ApiResponse::ok(state.security.read().await.ip_intel().status())
+}
+
+/// POST /api/security/ip-intel/refresh — download fresh Tor exit and
+/// datacenter ranges (written to `ip_intel.path` when set).
+pub async fn refresh_ip_intel(State(state): State<AppState>) -> impl IntoResponse {
+    let ip_intel = state.security.read().await.ip_intel().clone();
+    match ip_intel.refresh().await {
+        Ok(status) => ApiResponse::ok(status).into_response(),
+        Err(e) => ApiResponse::err(
+            StatusCode::BAD_GATEWAY,
+            format!("IP intel refresh failed: {}", e),
+        )
+        .into_response(),
+    }
+}
@@ -0,0 +1,483 @@
+//! Self-service endpoints (`/api/self/*`) for SSH users, authenticated with
+//! a personal token or a self-service page session instead of the admin
+//! token.
+//!
+//! Read-only tokens (`api_tokens`) reach the GET routes for the caller's own
+//! status, quota and sessions; everything else needs [`SelfScope::Full`].
+
+use super::rbac::Principal;
+use super::session::{session_cookie_header, SESSION_MAX_LIFETIME};
+use super::{ApiResponse, AppState};
+use crate::audit::events::AuditEvent;
+use crate::auth::personal_token::{self, TokenError};
+use crate::auth::self_service::{self, PasswordChangeError};
+use crate::config::types::{DashboardRole, PersonalTokenConfig};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Cookie carrying a self-service page session.
+pub const USER_SESSION_COOKIE: &str = "s5_user_session";
+
+/// Username authenticated by [`user_token_middleware`].
+#[derive(Debug, Clone)]
+pub struct SelfUser(pub String);
+
+/// What the authenticated self-service caller may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfScope {
+    /// Read-only personal token: own status, quota and sessions
+    Read,
+    /// Page session (signed in with the password) or the `api_token_hash`
+    /// token
+    Full,
+}
+
+fn source_ip(connect_info: Option<ConnectInfo<SocketAddr>>) -> String {
+    connect_info
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Self-service auth, either:
+/// - `Authorization: Bearer <username>:<token>`: a read-only `api_tokens`
+///   token (`s5r_...`) or the token of `api_token_hash`;
+/// - the `s5_user_session` cookie of the self-service page.
+///
+/// The admin token and dashboard sessions are not accepted here, so a
+/// self-service route can never act on behalf of another user.
+pub async fn user_token_middleware(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> impl IntoResponse {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    let (username, scope) = match bearer {
+        Some(value) => {
+            let Some((username, token)) = value
+                .split_once(':')
+                .map(|(user, token)| (user.to_string(), Zeroizing::new(token.to_string())))
+            else {
+                return (StatusCode::UNAUTHORIZED, "unauthorized").into_response();
+            };
+            let auth = state.auth_service.read().await;
+            let scope = if token.starts_with(personal_token::TOKEN_PREFIX) {
+                auth.auth_read_token(&username, &token)
+                    .map(|_| SelfScope::Read)
+            } else {
+                auth.auth_api_token(&username, &token)
+                    .then_some(SelfScope::Full)
+            };
+            drop(auth);
+            match scope {
+                Some(scope) => (username, scope),
+                None => return super::reject_invalid_credentials(&state, peer).await,
+            }
+        }
+        None => {
+            let principal = super::cors::cookie_value(req.headers(), USER_SESSION_COOKIE)
+                .and_then(|id| state.user_sessions.touch(id));
+            match principal {
+                Some(principal) => (principal.name, SelfScope::Full),
+                None => return (StatusCode::UNAUTHORIZED, "unauthorized").into_response(),
+            }
+        }
+    };
+    super::accept_credentials(&state, peer);
+
+    req.extensions_mut().insert(SelfUser(username));
+    req.extensions_mut().insert(scope);
+    next.run(req).await
+}
+
+/// Route layer: reject read-only personal tokens.
+pub async fn require_full_scope(
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    match req.extensions().get::<SelfScope>() {
+        Some(SelfScope::Full) => next.run(req).await,
+        Some(SelfScope::Read) => ApiResponse::<()>::err(
+            StatusCode::FORBIDDEN,
+            "read-only API token: sign in on the self-service page instead",
+        )
+        .into_response(),
+        None => (StatusCode::UNAUTHORIZED, "unauthorized").into_response(),
+    }
+}
+
+/// Self-service page login with the SSH password (and TOTP code when the
+/// user has one).
+#[derive(Deserialize)]
+pub struct UserLoginRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub totp_code: Option<String>,
+}
+
+/// POST /api/self/login — exchange an SSH user's password for a
+/// self-service page session cookie (`s5_user_session`).
+pub async fn login(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(body): Json<UserLoginRequest>,
+) -> impl IntoResponse {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    let password = Zeroizing::new(body.password);
+    let locked = state
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .is_locked(&body.username);
+    let valid = {
+        let auth = state.auth_service.read().await;
+        // Verified for locked users too, so timing does not reveal the lock
+        auth.auth_password(&body.username, &password)
+            && auth.verify_totp(&body.username, body.totp_code.as_deref().unwrap_or(""))
+    };
+    if !valid || locked {
+        record_account_failure(&state, &body.username, peer).await;
+        return super::reject_invalid_credentials(&state, peer).await;
+    }
+    super::accept_credentials(&state, peer);
+    state
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .record_success(&body.username);
+
+    tracing::info!(
+        ip = %peer.map(|a| a.ip().to_string()).unwrap_or_default(),
+        user = %body.username,
+        "Self-service session created"
+    );
+    let id = state
+        .user_sessions
+        .create(Principal::new(&body.username, DashboardRole::Viewer));
+    (
+        [(
+            header::SET_COOKIE,
+            session_cookie_header(&state, USER_SESSION_COOKIE, &id, SESSION_MAX_LIFETIME),
+        )],
+        ApiResponse::ok(SelfStatus {
+            username: body.username,
+            scope: SelfScope::Full,
+        }),
+    )
+        .into_response()
+}
+
+/// Count a failed page login against `username`'s account
+/// (`security.lock_after_failures`), as SSH and SOCKS5 logins do, and lock
+/// the account once the threshold is reached.
+async fn record_account_failure(state: &AppState, username: &str, peer: Option<SocketAddr>) {
+    let (failures, duration) = {
+        let security = state.security.read().await;
+        let lockout = security.account_lockout();
+        let Some(failures) = lockout.record_failure(username) else {
+            return;
+        };
+        (failures, lockout.lock_duration())
+    };
+    let source = peer.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    tracing::warn!(
+        user = %username,
+        ip = %source.ip(),
+        protocol = "api",
+        failures = failures,
+        lock_secs = duration.as_secs(),
+        "Account locked after repeated failed logins"
+    );
+    if let Some(ref audit) = state.audit {
+        audit.log_account_locked(username, &source, "api", "", failures, duration.as_secs());
+    }
+    state.metrics.account_lockouts_total.inc();
+}
+
+/// POST /api/self/logout — end the caller's self-service page session.
+pub async fn logout(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(id) = super::cors::cookie_value(&headers, USER_SESSION_COOKIE) {
+        state.user_sessions.remove(id);
+    }
+    (
+        [(
+            header::SET_COOKIE,
+            session_cookie_header(&state, USER_SESSION_COOKIE, "", Duration::ZERO),
+        )],
+        ApiResponse::ok(serde_json::json!({ "logged_out": true })),
+    )
+        .into_response()
+}
+
+#[derive(Serialize)]
+pub struct SelfStatus {
+    pub username: String,
+    pub scope: SelfScope,
+}
+
+/// GET /api/self — who the caller is authenticated as, and with which scope.
+pub async fn status(
+    Extension(SelfUser(username)): Extension<SelfUser>,
+    Extension(scope): Extension<SelfScope>,
+) -> impl IntoResponse {
+    ApiResponse::ok(SelfStatus { username, scope })
+}
+
+/// GET /api/self/quota — the caller's quota usage and limits.
+pub async fn quota(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+) -> impl IntoResponse {
+    // The path is the caller's own name, so the principal needs no tenant
+    let principal = Principal::new(&username, DashboardRole::Viewer);
+    super::quotas::get_user_quota(State(state), Extension(principal), Path(username))
+        .await
+        .into_response()
+}
+
+/// GET /api/self/sessions — the caller's live sessions.
+pub async fn sessions(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+) -> impl IntoResponse {
+    let sessions: Vec<_> = state
+        .proxy_engine
+        .get_user_sessions(&username)
+        .into_iter()
+        .map(super::sessions::to_response)
+        .collect();
+    ApiResponse::ok(sessions)
+}
+
+/// A personal token as listed (never its secret or digest).
+#[derive(Serialize)]
+pub struct TokenInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: Option<String>,
+}
+
+impl From<&PersonalTokenConfig> for TokenInfo {
+    fn from(token: &PersonalTokenConfig) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            created_at: token.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub info: TokenInfo,
+    /// Shown once; use as `Authorization: Bearer <username>:<token>`
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+}
+
+fn token_error(username: &str, e: TokenError) -> Response {
+    match e {
+        TokenError::NotFound | TokenError::UnknownUser => {
+            ApiResponse::err(StatusCode::NOT_FOUND, e.to_string()).into_response()
+        }
+        TokenError::TooMany => {
+            ApiResponse::err(StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        e if e.is_client_error() => {
+            ApiResponse::err(StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+        e => {
+            tracing::error!(user = %username, error = %e, "Personal token update failed");
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "token update failed")
+                .into_response()
+        }
+    }
+}
+
+/// GET /api/self/tokens — the caller's read-only personal tokens.
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+) -> impl IntoResponse {
+    let auth = state.auth_service.read().await;
+    let tokens: Vec<TokenInfo> = auth
+        .user_store()
+        .get(&username)
+        .map(|u| u.api_tokens.iter().map(TokenInfo::from).collect())
+        .unwrap_or_default();
+    ApiResponse::ok(tokens)
+}
+
+/// POST /api/self/tokens — create a read-only personal token; the secret is
+/// returned once.
+pub async fn create_token(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(body): Json<CreateTokenRequest>,
+) -> impl IntoResponse {
+    let result = personal_token::create(
+        &state.auth_service,
+        state.config_path.as_deref(),
+        &username,
+        &body.name,
+    )
+    .await;
+    match result {
+        Ok((token, secret)) => {
+            if let Some(ref audit) = state.audit {
+                audit.log_event(AuditEvent::api_token_created(
+                    &username,
+                    &source_ip(connect_info),
+                    &token,
+                ));
+            }
+            ApiResponse::ok_with_status(
+                StatusCode::CREATED,
+                CreatedToken {
+                    info: TokenInfo::from(&token),
+                    token: secret.to_string(),
+                },
+            )
+            .into_response()
+        }
+        Err(e) => token_error(&username, e),
+    }
+}
+
+/// DELETE /api/self/tokens/:id — revoke one of the caller's tokens.
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = personal_token::revoke(
+        &state.auth_service,
+        state.config_path.as_deref(),
+        &username,
+        &id,
+    )
+    .await;
+    match result {
+        Ok(token) => {
+            if let Some(ref audit) = state.audit {
+                audit.log_event(AuditEvent::api_token_revoked(
+                    &username,
+                    &source_ip(connect_info),
+                    &token,
+                ));
+            }
+            ApiResponse::ok(TokenInfo::from(&token)).into_response()
+        }
+        Err(e) => token_error(&username, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize)]
+pub struct ChangePasswordResponse {
+    pub username: String,
+    pub persisted: bool,
+}
+
+/// POST /api/self/password — rotate the caller's own password. A wrong
+/// current password counts like a failed page login; a change ends the
+/// caller's other self-service page sessions.
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(SelfUser(username)): Extension<SelfUser>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(body): Json<ChangePasswordRequest>,
+) -> impl IntoResponse {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    let locked = state
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .is_locked(&username);
+    let result = if locked {
+        Err(PasswordChangeError::InvalidCurrentPassword)
+    } else {
+        self_service::change_password(
+            &state.auth_service,
+            state.config_path.as_deref(),
+            &username,
+            &body.current_password,
+            &body.new_password,
+        )
+        .await
+    };
+
+    if let Some(ref audit) = state.audit {
+        audit.log_password_changed(
+            &username,
+            &source_ip(connect_info),
+            "api",
+            result.is_ok(),
+            result.as_ref().err().map(|e| e.to_string()),
+        );
+    }
+
+    match result {
+        Ok(()) => {
+            let current = super::cors::cookie_value(&headers, USER_SESSION_COOKIE);
+            state
+                .user_sessions
+                .remove_principal_except(&username, current);
+            ApiResponse::ok(ChangePasswordResponse {
+                username,
+                persisted: state.config_path.is_some(),
+            })
+            .into_response()
+        }
+        Err(PasswordChangeError::InvalidCurrentPassword) => {
+            record_account_failure(&state, &username, peer).await;
+            super::count_invalid_credentials(&state, peer).await;
+            ApiResponse::err(
+                StatusCode::FORBIDDEN,
+                PasswordChangeError::InvalidCurrentPassword.to_string(),
+            )
+            .into_response()
+        }
+        Err(e) if e.is_client_error() => {
+            ApiResponse::err(StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+        Err(e) => {
+            tracing::error!(user = %username, error = %e, "Password change failed");
+            ApiResponse::err(StatusCode::INTERNAL_SERVER_ERROR, "password change failed")
+                .into_response()
+        }
+    }
+}
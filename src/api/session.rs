@@ -0,0 +1,297 @@
+//! Dashboard login sessions: the admin token, a tenant token or an
+//! `[[api.accounts]]` username/password is exchanged once for an HttpOnly
+//! `s5_session` cookie that expires after a period of inactivity. Each
+//! session carries the [`Principal`] (name, role and tenant) it was created for.
+
+use super::rbac::Principal;
+use super::{ApiResponse, AppState};
+use crate::config::types::DashboardRole;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Cookie carrying the session ID.
+pub const SESSION_COOKIE: &str = "s5_session";
+
+/// Sessions are dropped after this long regardless of activity.
+pub const SESSION_MAX_LIFETIME: Duration = Duration::from_secs(12 * 3600);
+
+/// Default idle expiry (`api.session_idle_timeout`).
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Upper bound on concurrently live sessions.
+const MAX_SESSIONS: usize = 1024;
+
+struct Session {
+    principal: Principal,
+    created: Instant,
+    last_seen: Instant,
+}
+
+/// In-memory session table keyed by the SHA-256 of the session ID, so the
+/// raw cookie value never sits in server memory after issuance.
+pub struct SessionStore {
+    sessions: DashMap<String, Session>,
+    idle_timeout: Duration,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+fn hash_id(id: &str) -> String {
+    hex::encode(Sha256::digest(id.as_bytes()))
+}
+
+impl SessionStore {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Create a session for `principal` and return its ID (the cookie value).
+    pub fn create(&self, principal: Principal) -> String {
+        if self.sessions.len() >= MAX_SESSIONS {
+            self.prune();
+            if self.sessions.len() >= MAX_SESSIONS {
+                // Evict the least recently used session to make room
+                let oldest = self
+                    .sessions
+                    .iter()
+                    .min_by_key(|e| e.value().last_seen)
+                    .map(|e| e.key().clone());
+                if let Some(key) = oldest {
+                    self.sessions.remove(&key);
+                }
+            }
+        }
+        let id = hex::encode(rand::random::<[u8; 32]>());
+        let now = Instant::now();
+        self.sessions.insert(
+            hash_id(&id),
+            Session {
+                principal,
+                created: now,
+                last_seen: now,
+            },
+        );
+        id
+    }
+
+    /// Validate a session ID, refresh its idle timer and return its principal.
+    pub fn touch(&self, id: &str) -> Option<Principal> {
+        let key = hash_id(id);
+        let now = Instant::now();
+        if let Some(mut s) = self.sessions.get_mut(&key) {
+            if !self.is_expired(&s, now) {
+                s.last_seen = now;
+                return Some(s.principal.clone());
+            }
+        } else {
+            return None;
+        }
+        self.sessions.remove(&key);
+        None
+    }
+
+    /// Invalidate a session. Returns true if it existed.
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.remove(&hash_id(id)).is_some()
+    }
+
+    /// Invalidate every session of the principal named `name`.
+    pub fn remove_principal(&self, name: &str) {
+        self.sessions.retain(|_, s| s.principal.name != name);
+    }
+
+    /// Invalidate every session of the principal named `name` but `keep`.
+    pub fn remove_principal_except(&self, name: &str, keep: Option<&str>) {
+        let keep = keep.map(hash_id);
+        self.sessions
+            .retain(|key, s| s.principal.name != name || Some(key) == keep.as_ref());
+    }
+
+    /// Drop expired sessions.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.sessions.retain(|_, s| !self.is_expired(s, now));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    fn is_expired(&self, s: &Session, now: Instant) -> bool {
+        now.duration_since(s.last_seen) >= self.idle_timeout
+            || now.duration_since(s.created) >= SESSION_MAX_LIFETIME
+    }
+}
+
+/// Session ID from the request's `s5_session` cookie, if any.
+pub fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    super::cors::cookie_value(headers, SESSION_COOKIE)
+}
+
+/// Principal of the request's live session cookie, if any (refreshes it).
+pub fn session_principal(state: &AppState, headers: &HeaderMap) -> Option<Principal> {
+    session_cookie(headers).and_then(|id| state.sessions.touch(id))
+}
+
+/// `Set-Cookie` value for the session cookie `name`.
+pub(crate) fn session_cookie_header(
+    state: &AppState,
+    name: &str,
+    value: &str,
+    max_age: Duration,
+) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; {}; Max-Age={}",
+        name,
+        value,
+        super::cors::same_site(state),
+        max_age.as_secs()
+    )
+}
+
+/// Login with the admin token, a tenant token or a dashboard account.
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoginResponse {
+    username: String,
+    role: DashboardRole,
+    idle_timeout_secs: u64,
+}
+
+fn token_matches(state: &AppState, provided: &str) -> bool {
+    use subtle::ConstantTimeEq;
+    let expected = state.api_token.as_bytes();
+    !expected.is_empty()
+        && provided.len() == expected.len()
+        && bool::from(provided.as_bytes().ct_eq(expected))
+}
+
+/// Verify `[[api.accounts]]` credentials off the async runtime (argon2 is slow).
+async fn verify_account(
+    state: &AppState,
+    username: &str,
+    password: Zeroizing<String>,
+) -> Option<Principal> {
+    let account = state
+        .dashboard_accounts
+        .iter()
+        .find(|a| a.username == username)
+        .cloned();
+    // Unknown usernames still pay for a hash verification (no timing oracle)
+    let hash = account
+        .as_ref()
+        .map(|a| a.password_hash.clone())
+        .unwrap_or_else(|| crate::auth::DUMMY_HASH.to_string());
+    let ok = tokio::task::spawn_blocking(move || {
+        crate::auth::password::verify_password(&password, &hash)
+    })
+    .await
+    .unwrap_or(false);
+    let account = account?;
+    ok.then(|| Principal::new(username, account.role).with_tenant(account.tenant))
+}
+
+/// POST /api/login — exchange the admin token or account credentials for a
+/// session cookie.
+pub async fn login(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(body): Json<LoginRequest>,
+) -> impl IntoResponse {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+
+    let principal = match (body.token, body.username, body.password) {
+        (Some(token), _, _) => {
+            let token = Zeroizing::new(token);
+            if token_matches(&state, &token) {
+                Some(Principal::api_token())
+            } else {
+                super::tenant_for_token(&state, token.as_bytes()).map(Principal::tenant_token)
+            }
+        }
+        (None, Some(username), Some(password)) => {
+            verify_account(&state, &username, Zeroizing::new(password)).await
+        }
+        _ => {
+            return ApiResponse::<()>::err(
+                axum::http::StatusCode::BAD_REQUEST,
+                "expected 'token' or 'username' and 'password'",
+            )
+            .into_response();
+        }
+    };
+    let Some(principal) = principal else {
+        return super::reject_invalid_credentials(&state, peer).await;
+    };
+    super::accept_credentials(&state, peer);
+
+    let response = LoginResponse {
+        username: principal.name.clone(),
+        role: principal.role,
+        idle_timeout_secs: state.sessions.idle_timeout().as_secs(),
+    };
+    tracing::info!(
+        ip = %peer.map(|a| a.ip().to_string()).unwrap_or_default(),
+        principal = %principal.name,
+        role = %principal.role,
+        "Dashboard session created"
+    );
+    let id = state.sessions.create(principal);
+    (
+        [(
+            header::SET_COOKIE,
+            session_cookie_header(&state, SESSION_COOKIE, &id, SESSION_MAX_LIFETIME),
+        )],
+        ApiResponse::ok(response),
+    )
+        .into_response()
+}
+
+/// POST /api/logout — invalidate the caller's session and clear the cookie.
+pub async fn logout(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(id) = session_cookie(&headers) {
+        state.sessions.remove(id);
+    }
+    (
+        [(
+            header::SET_COOKIE,
+            session_cookie_header(&state, SESSION_COOKIE, "", Duration::ZERO),
+        )],
+        ApiResponse::ok(serde_json::json!({ "logged_out": true })),
+    )
+        .into_response()
+}
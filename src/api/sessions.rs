@@ -1,13 +1,20 @@
+use crate::api::quotas::QuotaSummary;
+use crate::api::rbac::Principal;
 use crate::api::{ApiResponse, AppState};
+use crate::proxy::close::{CloseReason, ClosedSession};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::IntoResponse,
+    Extension,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
-struct SessionResponse {
+pub(crate) struct SessionResponse {
     session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
     username: String,
     target_host: String,
     target_port: u16,
@@ -19,10 +26,11 @@ struct SessionResponse {
     protocol: String,
 }
 
-fn to_response(snap: crate::proxy::SessionSnapshot) -> SessionResponse {
+pub(crate) fn to_response(snap: crate::proxy::SessionSnapshot) -> SessionResponse {
     let duration = chrono::Utc::now().signed_duration_since(snap.started_at);
     SessionResponse {
         session_id: snap.session_id,
+        correlation_id: snap.correlation_id,
         username: snap.username,
         target_host: snap.target_host,
         target_port: snap.target_port,
@@ -35,25 +43,199 @@ fn to_response(snap: crate::proxy::SessionSnapshot) -> SessionResponse {
     }
 }
 
-pub async fn list_sessions(State(state): State<AppState>) -> impl IntoResponse {
-    let sessions: Vec<SessionResponse> = state
-        .proxy_engine
-        .get_sessions()
+#[derive(Deserialize)]
+pub struct SessionsQuery {
+    /// Only sessions opened by this connection (as logged in `conn_id`)
+    correlation_id: Option<String>,
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<SessionsQuery>,
+) -> impl IntoResponse {
+    let snapshots = match query.correlation_id {
+        Some(cid) => state.proxy_engine.get_correlated_sessions(&cid),
+        None => state.proxy_engine.get_sessions(),
+    };
+    let sessions: Vec<SessionResponse> = snapshots
         .into_iter()
+        .filter(|s| principal.sees(&s.username))
         .map(to_response)
         .collect();
     ApiResponse::ok(sessions)
 }
 
-pub async fn get_user_sessions(
+#[derive(Serialize)]
+struct TransferRate {
+    up_bytes_per_sec: f64,
+    down_bytes_per_sec: f64,
+}
+
+#[derive(Serialize)]
+struct SshDetail {
+    client_version: Option<String>,
+    /// HASSH fingerprint of the client implementation
+    hassh: Option<String>,
+    algorithms: Option<crate::ssh::handshake::NegotiatedAlgorithms>,
+    auth_method: Option<String>,
+    open_channels: u32,
+    session_channels: u32,
+    forwarding_channels: u32,
+}
+
+#[derive(Serialize)]
+struct SessionDetail {
+    #[serde(flatten)]
+    session: SessionResponse,
+    rate: TransferRate,
+    /// Every live session opened by the same connection, this one included
+    destinations: Vec<SessionResponse>,
+    /// SSH connection details; null for SOCKS5 sessions
+    ssh: Option<SshDetail>,
+    quota: Option<QuotaSummary>,
+    /// `[proxy.priority]` class; null without classes
+    priority_class: Option<String>,
+}
+
+/// GET /api/sessions/:id — detail of one live session when `id` is a
+/// session ID, otherwise the sessions of the user named `id`.
+pub async fn get_session(
     State(state): State<AppState>,
-    Path(username): Path<String>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
 ) -> impl IntoResponse {
+    let Some(snap) = state.proxy_engine.get_session(&id) else {
+        if !principal.sees(&id) {
+            return ApiResponse::ok(Vec::<SessionResponse>::new()).into_response();
+        }
+        return get_user_sessions(&state, &id).into_response();
+    };
+    if !principal.sees(&snap.username) {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "session not found").into_response();
+    }
+    let (up, down) = state.proxy_engine.session_rate(&id).unwrap_or_default();
+    let (destinations, ssh) = match snap.correlation_id.as_deref() {
+        Some(cid) => (
+            state.proxy_engine.get_correlated_sessions(cid),
+            state
+                .proxy_engine
+                .get_connection(cid)
+                .map(|conn| SshDetail {
+                    client_version: conn.client_version,
+                    hassh: conn.hassh,
+                    algorithms: conn.algorithms,
+                    auth_method: conn.auth_method,
+                    open_channels: conn.open_channels,
+                    session_channels: conn.session_channels,
+                    forwarding_channels: conn.forwarding_channels,
+                }),
+        ),
+        None => (vec![snap.clone()], None),
+    };
+    let quota = match state.quota_tracker {
+        Some(ref qt) => {
+            let auth = state.auth_service.read().await;
+            let quotas = auth
+                .user_store()
+                .get(&snap.username)
+                .and_then(|u| u.quotas.as_ref());
+            let usage = qt.get_user_usage(&snap.username);
+            Some(QuotaSummary::new(snap.username.clone(), &usage, quotas))
+        }
+        None => None,
+    };
+    ApiResponse::ok(SessionDetail {
+        session: to_response(snap),
+        rate: TransferRate {
+            up_bytes_per_sec: up,
+            down_bytes_per_sec: down,
+        },
+        destinations: destinations.into_iter().map(to_response).collect(),
+        ssh,
+        quota,
+        priority_class: state.proxy_engine.session_priority(&id),
+    })
+    .into_response()
+}
+
+fn get_user_sessions(state: &AppState, username: &str) -> impl IntoResponse {
     let sessions: Vec<SessionResponse> = state
         .proxy_engine
-        .get_user_sessions(&username)
+        .get_user_sessions(username)
         .into_iter()
         .map(to_response)
         .collect();
     ApiResponse::ok(sessions)
 }
+
+#[derive(Serialize)]
+struct ClosedSessionResponse {
+    #[serde(flatten)]
+    session: SessionResponse,
+    ended_at: String,
+    close_reason: CloseReason,
+}
+
+fn to_closed_response(closed: ClosedSession) -> ClosedSessionResponse {
+    let duration = closed
+        .ended_at
+        .signed_duration_since(closed.session.started_at);
+    let mut session = to_response(closed.session);
+    session.duration_secs = duration.num_seconds().max(0) as u64;
+    ClosedSessionResponse {
+        session,
+        ended_at: closed.ended_at.to_rfc3339(),
+        close_reason: closed.close_reason,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    user: Option<String>,
+    reason: Option<CloseReason>,
+    /// Maximum rows (default 100, max 1000)
+    limit: Option<usize>,
+}
+
+/// GET /api/sessions/history — recently closed sessions, newest first.
+pub async fn session_history(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).min(1000);
+    let closed = state.proxy_engine.closed_sessions(limit, |c| {
+        principal.sees(&c.session.username)
+            && query.user.as_ref().is_none_or(|u| c.session.username == *u)
+            && query.reason.is_none_or(|r| c.close_reason == r)
+    });
+    let sessions: Vec<ClosedSessionResponse> = closed.into_iter().map(to_closed_response).collect();
+    ApiResponse::ok(sessions)
+}
+
+#[derive(Serialize)]
+pub struct KillResponse {
+    pub session_id: String,
+    pub killed: bool,
+}
+
+/// DELETE /api/sessions/:id — close a live session (`admin_kill`).
+pub async fn kill_session(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let visible = state
+        .proxy_engine
+        .get_session(&id)
+        .is_some_and(|s| principal.sees(&s.username));
+    if !visible || !state.proxy_engine.kill_session(&id, CloseReason::AdminKill) {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "session not found").into_response();
+    }
+    ApiResponse::ok(KillResponse {
+        session_id: id,
+        killed: true,
+    })
+    .into_response()
+}
@@ -1,11 +1,15 @@
+use crate::api::rbac::Principal;
 use crate::api::AppState;
 use crate::audit::events::AuditEvent;
+use crate::proxy::geo_map::SessionMap;
+use crate::proxy::throughput::ThroughputSample;
 use axum::{
     extract::State,
     response::{
         sse::{Event, Sse},
         IntoResponse,
     },
+    Extension,
 };
 use serde::Serialize;
 use std::convert::Infallible;
@@ -25,6 +29,11 @@ pub struct SsePayload {
     quotas: Vec<SseQuotaInfo>,
     groups: Vec<SseGroupInfo>,
     sessions: SseSessionSummary,
+    /// Aggregate throughput of the last minute, one sample per second
+    throughput: Vec<ThroughputSample>,
+    /// Clustered source locations of the sessions (GeoIP City database only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<SessionMap>,
     recent_events: Vec<AuditEvent>,
 }
 
@@ -37,6 +46,8 @@ struct SseSessionSummary {
 #[derive(Serialize)]
 struct SseSessionInfo {
     session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
     username: String,
     target_host: String,
     target_port: u16,
@@ -60,6 +71,9 @@ struct UserInfo {
     username: String,
     allow_forwarding: bool,
     allow_shell: bool,
+    password_expired: bool,
+    locked: bool,
+    disabled: bool,
 }
 
 #[derive(Serialize)]
@@ -77,9 +91,14 @@ struct SseQuotaInfo {
 struct BanInfo {
     ip: String,
     expires_at: Option<String>,
+    /// null = server-wide
+    tenant: Option<String>,
 }
 
-pub async fn sse_events(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn sse_events(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
     // API-001: Auth is handled by the router middleware (Bearer header or HMAC ticket).
     // No duplicate auth check needed here.
 
@@ -87,8 +106,9 @@ pub async fn sse_events(State(state): State<AppState>) -> impl IntoResponse {
         tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(Duration::from_secs(2)))
             .map(move |_| {
                 let state = state.clone();
+                let principal = principal.clone();
                 async move {
-                    let payload = build_payload(&state).await;
+                    let payload = build_payload(&state, &principal).await;
                     let json = serde_json::to_string(&payload).unwrap_or_default();
                     Ok::<_, Infallible>(Event::default().data(json))
                 }
@@ -104,19 +124,30 @@ pub async fn sse_events(State(state): State<AppState>) -> impl IntoResponse {
         .into_response()
 }
 
-/// Build the SSE/WS payload. Public for WebSocket reuse.
-pub async fn build_ws_payload(state: &AppState) -> SsePayload {
-    build_payload(state).await
-}
-
-async fn build_payload(state: &AppState) -> SsePayload {
+/// Payload as seen by `principal`: a tenant principal gets its own users,
+/// quotas, sessions and bans, and none of the server-wide parts (groups,
+/// throughput, map, audit events). Public for WebSocket reuse.
+pub async fn build_payload(state: &AppState, principal: &Principal) -> SsePayload {
+    let server_wide = principal.tenant.is_none();
     let active_connections = state.proxy_engine.active_connections();
     let uptime_secs = state.start_time.elapsed().as_secs();
     let maintenance = state.maintenance.load(std::sync::atomic::Ordering::Relaxed);
 
+    let locked: std::collections::HashSet<String> = state
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .locked_accounts()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
     let auth = state.auth_service.read().await;
-    let usernames = auth.user_store().usernames();
+    let mut usernames = auth.user_store().usernames();
+    usernames.retain(|u| principal.sees(u));
     let total_users = usernames.len();
+    let now = chrono::Utc::now();
     let users: Vec<UserInfo> = usernames
         .iter()
         .filter_map(|u| auth.user_store().get(u))
@@ -124,10 +155,21 @@ async fn build_payload(state: &AppState) -> SsePayload {
             username: u.username.clone(),
             allow_forwarding: u.allow_forwarding,
             allow_shell: u.allow_shell,
+            password_expired: crate::auth::password_policy::is_expired(
+                auth.password_policy(),
+                u.password_changed_at,
+                now,
+            ),
+            locked: locked.contains(&u.username),
+            disabled: u.disabled,
         })
         .collect();
 
-    let group_names = auth.user_store().group_names();
+    let group_names = if server_wide {
+        super::groups::group_names(&auth)
+    } else {
+        Vec::new()
+    };
     let groups: Vec<SseGroupInfo> = group_names
         .iter()
         .map(|gname| {
@@ -155,15 +197,17 @@ async fn build_payload(state: &AppState) -> SsePayload {
     drop(auth);
 
     let security = state.security.read().await;
-    let ban_list = security.ban_manager().banned_ips();
+    let mut ban_list = security.ban_manager().banned_ips();
+    ban_list.retain(|(key, _)| principal.sees_ban(key));
     let banned_count = ban_list.len();
     let bans: Vec<BanInfo> = ban_list
         .into_iter()
-        .map(|(ip, expires)| {
+        .map(|(key, expires)| {
             let remaining = expires.saturating_duration_since(std::time::Instant::now());
             BanInfo {
-                ip: ip.to_string(),
+                ip: key.ip.to_string(),
                 expires_at: Some(format!("{}s", remaining.as_secs())),
+                tenant: key.tenant,
             }
         })
         .collect();
@@ -184,6 +228,7 @@ async fn build_payload(state: &AppState) -> SsePayload {
     let quotas: Vec<SseQuotaInfo> = if let Some(ref qt) = state.quota_tracker {
         qt.tracked_users()
             .iter()
+            .filter(|u| principal.sees(u))
             .map(|u| {
                 let usage = qt.get_user_usage(u);
                 SseQuotaInfo {
@@ -201,7 +246,8 @@ async fn build_payload(state: &AppState) -> SsePayload {
         vec![]
     };
 
-    let session_snapshots = state.proxy_engine.get_sessions();
+    let mut session_snapshots = state.proxy_engine.get_sessions();
+    session_snapshots.retain(|s| principal.sees(&s.username));
     let sessions = SseSessionSummary {
         total_active: session_snapshots.len(),
         sessions: session_snapshots
@@ -210,6 +256,7 @@ async fn build_payload(state: &AppState) -> SsePayload {
                 let duration = chrono::Utc::now().signed_duration_since(s.started_at);
                 SseSessionInfo {
                     session_id: s.session_id,
+                    correlation_id: s.correlation_id,
                     username: s.username,
                     target_host: s.target_host,
                     target_port: s.target_port,
@@ -225,11 +272,16 @@ async fn build_payload(state: &AppState) -> SsePayload {
     let recent_events = state
         .audit
         .as_ref()
+        .filter(|_| server_wide)
         .map(|a| a.get_recent_events(50))
         .unwrap_or_default();
 
     SsePayload {
-        active_connections,
+        active_connections: if server_wide {
+            active_connections
+        } else {
+            connections.values().sum()
+        },
         banned_count,
         total_users,
         maintenance,
@@ -240,6 +292,14 @@ async fn build_payload(state: &AppState) -> SsePayload {
         quotas,
         groups,
         sessions,
+        throughput: if server_wide {
+            state.proxy_engine.throughput_history()
+        } else {
+            Vec::new()
+        },
+        geo: server_wide
+            .then(|| state.proxy_engine.session_map())
+            .flatten(),
         recent_events,
     }
 }
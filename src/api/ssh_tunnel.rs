@@ -0,0 +1,150 @@
+//! SSH over WebSocket (`api.ssh_websocket`, `GET /ssh`).
+//!
+//! Clients whose egress only allows HTTP(S) reach the SSH server through the
+//! API port: each binary frame carries raw SSH transport bytes. The upgraded
+//! connection is bridged to an in-memory pipe and handed to the main SSH
+//! listener, so it goes through the same ban check, pre-auth limits,
+//! connection caps and authentication as a TCP client. TLS (`wss://`) is
+//! terminated by the reverse proxy in front of the API; tunnels coming
+//! through one listed in `api.trusted_proxies` take their client address
+//! from `X-Forwarded-For`, so bans and per-IP limits see the real client.
+
+use crate::api::AppState;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Largest WebSocket message accepted (SSH packets are far smaller).
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+/// Buffer of the pipe between the WebSocket and the SSH session.
+const PIPE_SIZE: usize = 64 * 1024;
+/// Tunnels waiting for the SSH accept loop.
+pub const QUEUE_SIZE: usize = 64;
+
+/// An upgraded `/ssh` connection, waiting for the SSH accept loop.
+pub struct Tunnel {
+    /// SSH side of the pipe bridged to the WebSocket
+    pub stream: DuplexStream,
+    /// Client address as seen by the API listener
+    pub peer: SocketAddr,
+}
+
+pub type TunnelSender = mpsc::Sender<Tunnel>;
+pub type TunnelReceiver = mpsc::Receiver<Tunnel>;
+
+/// Channel from the API's `/ssh` route to the main SSH listener.
+pub fn channel() -> (TunnelSender, TunnelReceiver) {
+    mpsc::channel(QUEUE_SIZE)
+}
+
+/// Client address of a tunnel from `peer`. Behind trusted proxies, the
+/// rightmost `X-Forwarded-For` entry that is not itself a trusted proxy;
+/// the peer when it is not trusted or the header is missing or invalid.
+pub fn client_addr(peer: SocketAddr, headers: &HeaderMap, trusted: &[IpNet]) -> SocketAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer.ip()) {
+        return peer;
+    }
+    let mut forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|entry| entry.trim().parse::<IpAddr>())
+        .collect::<Vec<_>>();
+    while let Some(entry) = forwarded.pop() {
+        match entry {
+            Ok(ip) if is_trusted(&ip) => continue,
+            Ok(ip) => return SocketAddr::new(ip, peer.port()),
+            Err(_) => break,
+        }
+    }
+    peer
+}
+
+pub async fn ssh_tunnel_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(tunnels) = state.ssh_tunnel.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // No peer address over `api.unix_socket` (rejected by config validation)
+    let Some(ConnectInfo(peer)) = connect_info else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let peer = client_addr(peer, &headers, &state.trusted_proxies);
+    if tunnels.is_closed() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "SSH server not running").into_response();
+    }
+    // Browsers may only open the tunnel from an allowed origin, as on /api/ws
+    if let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) {
+        let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+        if !crate::api::cors::origin_allowed(origin, host, &state.allowed_origins) {
+            return (StatusCode::FORBIDDEN, "origin not allowed").into_response();
+        }
+    }
+    if state.security.read().await.is_banned(&peer.ip()) {
+        state.metrics.record_connection_rejected("banned");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| async move {
+            let (stream, bridge_side) = tokio::io::duplex(PIPE_SIZE);
+            if tunnels.send(Tunnel { stream, peer }).await.is_err() {
+                debug!(peer = %peer, "SSH server gone, closing WebSocket tunnel");
+                return;
+            }
+            debug!(peer = %peer, "SSH WebSocket tunnel opened");
+            bridge(socket, bridge_side).await;
+            debug!(peer = %peer, "SSH WebSocket tunnel closed");
+        })
+        .into_response()
+}
+
+/// Copy binary frames into `pipe` and `pipe` output into binary frames until
+/// either side closes. Text frames are a protocol error and end the tunnel.
+async fn bridge(mut socket: WebSocket, pipe: DuplexStream) {
+    let (mut pipe_rx, mut pipe_tx) = tokio::io::split(pipe);
+    let mut buf = vec![0u8; PIPE_SIZE];
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    if pipe_tx.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+                Some(Ok(Message::Text(_))) => {
+                    debug!("Text frame on SSH WebSocket tunnel, closing");
+                    break;
+                }
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            read = pipe_rx.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if socket.send(Message::Binary(buf[..n].to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        }
+    }
+    let _ = pipe_tx.shutdown().await;
+    let _ = socket.send(Message::Close(None)).await;
+}
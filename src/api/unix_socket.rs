@@ -0,0 +1,82 @@
+//! Management API on a Unix socket (`api.unix_socket`).
+//!
+//! Local tooling and reverse proxies reach the API through a socket file
+//! whose permissions (`api.unix_socket_mode`) gate access before the token
+//! is checked. Requests carry no peer address, so the per-IP rate limit and
+//! auth-failure delays do not apply.
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use tokio::net::UnixListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Bind `path` and apply `mode`. A stale socket left by a previous run is
+/// replaced; a live one, or any other kind of file, is an error.
+pub fn bind(path: &Path, mode: u32) -> anyhow::Result<UnixListener> {
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("{} is in use by another process", path.display());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+/// Serve `app` on `path` until `shutdown`, then remove the socket file.
+pub async fn serve(
+    path: &Path,
+    mode: u32,
+    app: Router,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let listener = bind(path, mode)?;
+    crate::handover::global().mark_bound("api");
+    info!(
+        path = %path.display(),
+        mode = format!("{:04o}", mode),
+        "API server listening on Unix socket"
+    );
+
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!(error = %e, "API Unix socket accept failed");
+                    continue;
+                }
+            },
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            tokio::pin!(conn);
+            let result = tokio::select! {
+                result = conn.as_mut() => result,
+                _ = shutdown.cancelled() => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+            };
+            if let Err(e) = result {
+                debug!(error = %e, "API Unix socket connection error");
+            }
+        });
+    }
+
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
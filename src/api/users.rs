@@ -1,9 +1,14 @@
+use super::rbac::Principal;
 use super::{ApiResponse, AppState};
+use crate::auth::{password_policy, pubkey};
 use crate::quota::UserQuotaUsage;
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::IntoResponse,
+    Extension,
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -19,6 +24,18 @@ pub struct UserInfo {
     pub authorized_keys_count: usize,
     pub source_ips: Vec<String>,
     pub expires_at: Option<String>,
+    pub password_changed_at: Option<String>,
+    /// Older than `[security.password_policy] max_age_days`
+    pub password_expired: bool,
+    /// Locked by `security.lock_after_failures`
+    pub locked: bool,
+    /// Seconds until the lock expires (None = not locked)
+    pub lock_remaining_secs: Option<u64>,
+    /// Logins refused (`disabled`, e.g. by a group manager)
+    pub disabled: bool,
+    /// `[quota_plans]` entry the user's quotas come from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_plan: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_connections: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,16 +46,21 @@ pub struct UserInfo {
 
 pub async fn list_users(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Query(query): Query<UsersQuery>,
 ) -> impl IntoResponse {
     let include_details = super::is_truthy(query.details.as_deref());
 
+    let security = state.security.read().await;
+    let lockout = security.account_lockout();
     let auth = state.auth_service.read().await;
     let store = auth.user_store();
     let usernames = store.usernames();
+    let now = Utc::now();
 
     let users: Vec<UserInfo> = usernames
         .iter()
+        .filter(|name| principal.sees(name))
         .filter_map(|name| {
             store.get(name).map(|u| {
                 let (current_connections, total_bytes_transferred, quota_usage) = if include_details
@@ -60,6 +82,7 @@ pub async fn list_users(
                     (None, None, None)
                 };
 
+                let lock_remaining = lockout.remaining(name);
                 UserInfo {
                     username: u.username.clone(),
                     allow_forwarding: u.allow_forwarding,
@@ -67,6 +90,17 @@ pub async fn list_users(
                     authorized_keys_count: u.authorized_keys.len(),
                     source_ips: u.source_ips.iter().map(|ip| ip.to_string()).collect(),
                     expires_at: u.expires_at.map(|e| e.to_rfc3339()),
+                    password_changed_at: u.password_changed_at.map(|t| t.to_rfc3339()),
+                    password_expired: password_policy::is_expired(
+                        auth.password_policy(),
+                        u.password_changed_at,
+                        now,
+                    ),
+                    locked: lock_remaining.is_some(),
+                    // Rounded up so a locked account never shows 0
+                    lock_remaining_secs: lock_remaining.map(|d| d.as_secs() + 1),
+                    disabled: u.disabled,
+                    quota_plan: u.quota_plan.clone(),
                     current_connections,
                     total_bytes_transferred,
                     quota_usage,
@@ -77,3 +111,62 @@ pub async fn list_users(
 
     ApiResponse::ok(users)
 }
+
+#[derive(Deserialize)]
+pub struct ExpiringKeysQuery {
+    days: Option<i64>,
+}
+
+/// `GET /api/keys/expiring?days=14`: authorized keys whose `expiry-time`
+/// is within `days` (default 14) or already past, soonest first.
+pub async fn list_expiring_keys(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<ExpiringKeysQuery>,
+) -> impl IntoResponse {
+    let days = query.days.unwrap_or(pubkey::KEY_EXPIRY_REMINDER_DAYS);
+    if !(0..=3650).contains(&days) {
+        return ApiResponse::err(StatusCode::BAD_REQUEST, "days must be between 0 and 3650")
+            .into_response();
+    }
+    let keys: Vec<pubkey::ExpiringKey> = state
+        .auth_service
+        .read()
+        .await
+        .expiring_keys(Utc::now(), chrono::Duration::days(days))
+        .into_iter()
+        .filter(|k| principal.sees(&k.username))
+        .collect();
+    ApiResponse::ok(keys).into_response()
+}
+
+#[derive(Serialize)]
+pub struct UnlockResult {
+    pub username: String,
+    pub unlocked: bool,
+}
+
+/// `DELETE /api/users/{username}/lock`: lift an account lock
+/// (`security.lock_after_failures`) before it expires.
+pub async fn unlock_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    let unlocked = state
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .unlock(&username);
+    if !unlocked {
+        return ApiResponse::err(StatusCode::NOT_FOUND, "account not locked").into_response();
+    }
+    if let Some(ref audit) = state.audit {
+        audit.log_account_unlocked(&username);
+    }
+    ApiResponse::ok(UnlockResult {
+        username,
+        unlocked: true,
+    })
+    .into_response()
+}
@@ -1,10 +1,15 @@
+use crate::api::rbac::Principal;
 use crate::api::AppState;
+use crate::config::types::DashboardRole;
+use crate::proxy::close::CloseReason;
+use crate::security::ban::BanKey;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
         State,
     },
     response::IntoResponse,
+    Extension,
 };
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -23,6 +28,13 @@ struct WsCommand {
     message: Option<String>,
 }
 
+/// Per-second throughput frame sent between full dashboard payloads.
+#[derive(Serialize)]
+struct WsThroughput {
+    r#type: &'static str,
+    sample: crate::proxy::throughput::ThroughputSample,
+}
+
 #[derive(Serialize)]
 struct WsResponse {
     success: bool,
@@ -31,31 +43,46 @@ struct WsResponse {
     error: Option<String>,
 }
 
-pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
     // API-001: Auth is handled by the router middleware (Bearer header or HMAC ticket).
-    // No duplicate auth check needed here.
-    ws.on_upgrade(move |socket| handle_ws(socket, state))
+    // No duplicate auth check needed here; commands are gated on the caller's role.
+    ws.on_upgrade(move |socket| handle_ws(socket, state, principal))
         .into_response()
 }
 
-async fn handle_ws(mut socket: WebSocket, state: AppState) {
+async fn handle_ws(mut socket: WebSocket, state: AppState, principal: Principal) {
     debug!("WebSocket client connected");
 
     let mut interval = tokio::time::interval(Duration::from_secs(2));
+    let mut throughput_interval = tokio::time::interval(Duration::from_secs(1));
 
     loop {
         tokio::select! {
             _ = interval.tick() => {
-                let payload = crate::api::sse::build_ws_payload(&state).await;
+                let payload = crate::api::sse::build_payload(&state, &principal).await;
                 let json = serde_json::to_string(&payload).unwrap_or_default();
                 if socket.send(Message::Text(json)).await.is_err() {
                     break;
                 }
             }
+            _ = throughput_interval.tick() => {
+                let Some(sample) = state.proxy_engine.latest_throughput() else {
+                    continue;
+                };
+                let frame = WsThroughput { r#type: "throughput", sample };
+                let json = serde_json::to_string(&frame).unwrap_or_default();
+                if socket.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
             msg = socket.recv() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        let response = handle_command(&text, &state).await;
+                        let response = handle_command(&text, &state, &principal).await;
                         let json = serde_json::to_string(&response).unwrap_or_default();
                         if socket.send(Message::Text(json)).await.is_err() {
                             break;
@@ -70,7 +97,7 @@ async fn handle_ws(mut socket: WebSocket, state: AppState) {
     debug!("WebSocket client disconnected");
 }
 
-async fn handle_command(text: &str, state: &AppState) -> WsResponse {
+async fn handle_command(text: &str, state: &AppState, principal: &Principal) -> WsResponse {
     let cmd: WsCommand = match serde_json::from_str(text) {
         Ok(c) => c,
         Err(e) => {
@@ -82,14 +109,37 @@ async fn handle_command(text: &str, state: &AppState) -> WsResponse {
         }
     };
 
+    // Every command is an operator action; viewers only get the live stream
+    if !principal.allows(DashboardRole::Operator) {
+        warn!(principal = %principal.name, action = %cmd.action, "WebSocket command denied by role");
+        return WsResponse {
+            success: false,
+            action: cmd.action,
+            error: Some("requires role 'operator'".to_string()),
+        };
+    }
+
+    // Server-wide switches are not the business of a tenant principal
+    if principal.tenant.is_some() && matches!(cmd.action.as_str(), "maintenance" | "broadcast") {
+        warn!(principal = %principal.name, action = %cmd.action, "WebSocket command denied to tenant");
+        return WsResponse {
+            success: false,
+            action: cmd.action,
+            error: Some("not available to tenant accounts".to_string()),
+        };
+    }
+
     match cmd.action.as_str() {
         "kick" => {
             if let Some(username) = &cmd.username {
                 let auth = state.auth_service.read().await;
-                if auth.user_store().get(username).is_some() {
+                if principal.sees(username) && auth.user_store().get(username).is_some() {
                     if let Some(ref tx) = state.broadcast_tx {
                         let _ = tx.send(("__kick__".to_string(), vec![username.clone()]));
                     }
+                    state
+                        .proxy_engine
+                        .kill_sessions(CloseReason::AdminKill, |s| s.username == *username);
                     WsResponse {
                         success: true,
                         action: "kick".to_string(),
@@ -124,8 +174,10 @@ async fn handle_command(text: &str, state: &AppState) -> WsResponse {
         "unban" => {
             if let Some(ip_str) = &cmd.ip {
                 if let Ok(ip) = ip_str.parse() {
+                    // Server-wide ban, or the tenant principal's own
+                    let key = BanKey::new(ip, principal.tenant.as_deref());
                     let security = state.security.read().await;
-                    security.ban_manager().unban(&ip);
+                    security.ban_manager().unban(key);
                     WsResponse {
                         success: true,
                         action: "unban".to_string(),
@@ -0,0 +1,310 @@
+//! Archiving of rotated audit files to S3-compatible object storage
+//! (`[logging.audit.archive]`).
+//!
+//! On each rotation the audit writer hard-links the freshly rotated file into
+//! a spool directory next to the audit log (`audit.json.archive/`) under a
+//! timestamped name, so later rotations can shift or drop it freely. A
+//! background task uploads spooled files oldest first with a SigV4-signed
+//! `PUT` and removes them once stored. A failed upload is retried every
+//! `retry_interval_secs`; files still spooled at shutdown are uploaded after
+//! the next start. `s5_audit_archive_lag_seconds` reports how long the oldest
+//! spooled file has been waiting.
+
+use crate::config::types::AuditArchiveConfig;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::gauge::Gauge;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use url::Url;
+
+/// Characters left as-is in object paths (RFC 3986 unreserved, plus `/`).
+const PATH_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Headers covered by the request signature.
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Prometheus handles updated by the archiver.
+pub struct ArchiveMetrics {
+    /// Age of the oldest file waiting for upload (0 = none)
+    pub lag_seconds: Gauge,
+    pub uploads: Counter,
+    pub failures: Counter,
+}
+
+/// Spool of rotated audit files and their uploader.
+pub struct AuditArchiver {
+    config: AuditArchiveConfig,
+    spool: PathBuf,
+    /// File name of the audit log, the stem of spooled names
+    file_name: String,
+    client: reqwest::Client,
+    wake: Notify,
+    metrics: OnceLock<ArchiveMetrics>,
+}
+
+/// Spool directory of the audit log at `audit_path`.
+pub fn spool_dir(audit_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.archive", audit_path.display()))
+}
+
+/// URL of object `key` in the configured bucket.
+pub fn object_url(config: &AuditArchiveConfig, key: &str) -> anyhow::Result<Url> {
+    let mut url = Url::parse(&config.endpoint)?;
+    let key = utf8_percent_encode(key, PATH_ENCODE).to_string();
+    if config.path_style {
+        let bucket = utf8_percent_encode(&config.bucket, PATH_ENCODE);
+        url.set_path(&format!("/{bucket}/{key}"));
+    } else {
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("endpoint has no host"))?;
+        let host = format!("{}.{}", config.bucket, host);
+        url.set_host(Some(&host))?;
+        url.set_path(&format!("/{key}"));
+    }
+    Ok(url)
+}
+
+/// AWS Signature Version 4 headers for a `PUT` of a body hashing to
+/// `payload_hash` (hex SHA-256) at `url`.
+pub fn sign_put(
+    config: &AuditArchiveConfig,
+    url: &Url,
+    payload_hash: &str,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = &amz_date[..8];
+    let host = url.host_str().unwrap_or_default();
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n\
+         {SIGNED_HEADERS}\n{payload_hash}",
+        url.path()
+    );
+    let scope = format!("{date}/{}/s3/aws4_request", config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac_sha256(format!("AWS4{}", config.secret_access_key).as_bytes(), date);
+    for part in [config.region.as_str(), "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part);
+    }
+    let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+
+    vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload_hash.to_string()),
+        (
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, \
+                 Signature={signature}",
+                config.access_key_id
+            ),
+        ),
+    ]
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl AuditArchiver {
+    /// Archiver for the audit log at `audit_path`. Nothing is uploaded until
+    /// [`spawn`](Self::spawn) or [`flush`](Self::flush).
+    pub fn new(config: &AuditArchiveConfig, audit_path: &Path) -> Arc<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Arc::new(Self {
+            config: config.clone(),
+            spool: spool_dir(audit_path),
+            file_name: audit_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "audit.json".to_string()),
+            client,
+            wake: Notify::new(),
+            metrics: OnceLock::new(),
+        })
+    }
+
+    /// Start the upload task.
+    pub fn spawn(self: &Arc<Self>) {
+        let archiver = self.clone();
+        tokio::spawn(async move {
+            info!(
+                endpoint = %archiver.config.endpoint,
+                bucket = %archiver.config.bucket,
+                "Audit archiving enabled"
+            );
+            let retry = Duration::from_secs(archiver.config.retry_interval_secs);
+            loop {
+                if let Err(e) = archiver.flush().await {
+                    warn!(
+                        error = %format!("{e:#}"),
+                        retry_secs = retry.as_secs(),
+                        "Audit archive upload failed"
+                    );
+                }
+                tokio::select! {
+                    _ = archiver.wake.notified() => {}
+                    _ = tokio::time::sleep(retry) => {}
+                }
+            }
+        });
+    }
+
+    /// Wire the Prometheus metrics.
+    pub fn set_metrics(&self, metrics: ArchiveMetrics) {
+        let _ = self.metrics.set(metrics);
+    }
+
+    /// Spool directory
+    pub fn spool(&self) -> &Path {
+        &self.spool
+    }
+
+    /// Queue the just-rotated file at `rotated` for upload.
+    pub async fn stage(&self, rotated: &Path) {
+        if let Err(e) = tokio::fs::create_dir_all(&self.spool).await {
+            warn!(path = %self.spool.display(), error = %e, "Failed to create audit archive spool");
+            return;
+        }
+        let stamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ");
+        let mut target = self.spool.join(format!("{}.{stamp}", self.file_name));
+        let mut n = 1;
+        while tokio::fs::try_exists(&target).await.unwrap_or(false) {
+            target = self.spool.join(format!("{}.{stamp}-{n}", self.file_name));
+            n += 1;
+        }
+        // A hard link is free; copy where the filesystem has none
+        let staged = match tokio::fs::hard_link(rotated, &target).await {
+            Ok(()) => Ok(()),
+            Err(_) => tokio::fs::copy(rotated, &target).await.map(|_| ()),
+        };
+        match staged {
+            Ok(()) => self.wake.notify_one(),
+            Err(e) => warn!(
+                path = %rotated.display(),
+                error = %e,
+                "Failed to queue rotated audit file for archiving"
+            ),
+        }
+    }
+
+    /// Spooled files, oldest first.
+    pub async fn pending(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let Ok(mut dir) = tokio::fs::read_dir(&self.spool).await else {
+            return files;
+        };
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if entry.file_type().await.is_ok_and(|t| t.is_file()) {
+                files.push(entry.path());
+            }
+        }
+        // Timestamped names sort chronologically
+        files.sort();
+        files
+    }
+
+    /// Upload every spooled file, oldest first, stopping at the first
+    /// failure. Returns the number of files uploaded.
+    pub async fn flush(&self) -> anyhow::Result<usize> {
+        let mut uploaded = 0;
+        let mut result = Ok(());
+        for path in self.pending().await {
+            if let Err(e) = self.upload(&path).await {
+                if let Some(m) = self.metrics.get() {
+                    m.failures.inc();
+                }
+                result = Err(e.context(format!("uploading {}", path.display())));
+                break;
+            }
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!(path = %path.display(), error = %e, "Failed to remove archived audit file");
+            }
+            if let Some(m) = self.metrics.get() {
+                m.uploads.inc();
+            }
+            uploaded += 1;
+        }
+        self.update_lag().await;
+        result.map(|()| uploaded)
+    }
+
+    /// Object name of a spooled file
+    pub fn object_key(&self, path: &Path) -> String {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        format!("{}{}", self.config.prefix, name)
+    }
+
+    async fn upload(&self, path: &Path) -> anyhow::Result<()> {
+        let url = object_url(&self.config, &self.object_key(path))?;
+        let file = path.to_path_buf();
+        let (body, payload_hash) = tokio::task::spawn_blocking(move || {
+            std::fs::read(&file).map(|body| {
+                let hash = hex::encode(Sha256::digest(&body));
+                (body, hash)
+            })
+        })
+        .await??;
+
+        let mut request = self.client.put(url.clone()).body(body);
+        for (name, value) in sign_put(&self.config, &url, &payload_hash, Utc::now()) {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let body: String = body.chars().take(200).collect();
+            anyhow::bail!("HTTP {status}: {body}");
+        }
+        Ok(())
+    }
+
+    async fn update_lag(&self) {
+        let Some(metrics) = self.metrics.get() else {
+            return;
+        };
+        let oldest = match self.pending().await.first() {
+            Some(path) => tokio::fs::metadata(path)
+                .await
+                .and_then(|m| m.modified())
+                .ok(),
+            None => None,
+        };
+        let lag = oldest
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map_or(0, |d| d.as_secs());
+        metrics.lag_seconds.set(lag as i64);
+    }
+}
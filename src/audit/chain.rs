@@ -0,0 +1,293 @@
+//! Tamper-evident audit log (`logging.audit_chain`).
+//!
+//! Every record written to the audit file gets a `seq` number and the
+//! `prev_hash` of the previous line (SHA-256 of its exact bytes), so editing,
+//! removing or reordering lines breaks the chain. Periodic `audit.checkpoint`
+//! records commit to the current head hash and, when a signing key is
+//! configured, carry an HMAC-SHA256 signature over it: an attacker who rewrites
+//! the tail of the log cannot forge checkpoints without the key.
+//!
+//! The chain continues across rotated files; verify them oldest first with
+//! `s5 verify-audit audit.json.2 audit.json.1 audit.json`.
+
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// `prev_hash` of the very first record.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Event type of checkpoint records.
+pub const CHECKPOINT_EVENT: &str = "audit.checkpoint";
+
+/// Chain settings for [`AuditLogger::with_chain`](super::AuditLogger::with_chain).
+#[derive(Clone, Default)]
+pub struct AuditChainOptions {
+    pub enabled: bool,
+    /// How often to emit a checkpoint (zero = only on shutdown).
+    pub checkpoint_interval: Duration,
+    /// HMAC key for checkpoint signatures (None = unsigned checkpoints).
+    pub signing_key: Option<String>,
+}
+
+/// Running chain state of the audit writer.
+pub struct AuditChain {
+    next_seq: u64,
+    prev_hash: String,
+    signing_key: Option<Zeroizing<Vec<u8>>>,
+    /// Records sealed since the last checkpoint
+    pending: u64,
+}
+
+/// SHA-256 (hex) of one audit line, without its trailing newline.
+pub fn line_hash(line: &str) -> String {
+    hex::encode(Sha256::digest(line.as_bytes()))
+}
+
+/// HMAC-SHA256 (hex) committing to the chain head at `seq`.
+pub fn checkpoint_signature(key: &[u8], seq: u64, head_hash: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(format!("s5-audit-checkpoint:{}:{}", seq, head_hash).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+impl AuditChain {
+    /// Start a fresh chain at the genesis hash.
+    pub fn new(signing_key: Option<&str>) -> Self {
+        Self {
+            next_seq: 0,
+            prev_hash: GENESIS_HASH.to_string(),
+            signing_key: signing_key.map(|k| Zeroizing::new(k.as_bytes().to_vec())),
+            pending: 0,
+        }
+    }
+
+    /// Continue the chain after `last_line`, the newest record already on disk.
+    /// Falls back to a fresh chain if that line is not a chained record.
+    pub fn resume(last_line: &str, signing_key: Option<&str>) -> Self {
+        let mut chain = Self::new(signing_key);
+        let seq = serde_json::from_str::<Value>(last_line)
+            .ok()
+            .and_then(|v| v.get("seq").and_then(Value::as_u64));
+        if let Some(seq) = seq {
+            chain.next_seq = seq + 1;
+            chain.prev_hash = line_hash(last_line);
+        }
+        chain
+    }
+
+    /// Sequence number the next record will get.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Hash of the newest sealed record.
+    pub fn head_hash(&self) -> &str {
+        &self.prev_hash
+    }
+
+    /// True if records were sealed since the last checkpoint.
+    pub fn has_pending(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Add `seq` and `prev_hash` to a JSON object record and return the line
+    /// to write (without newline). Non-object values are wrapped as `{"data": ...}`.
+    pub fn seal(&mut self, record: Value) -> String {
+        let mut obj = match record {
+            Value::Object(map) => map,
+            other => {
+                let mut map = Map::new();
+                map.insert("data".into(), other);
+                map
+            }
+        };
+        obj.insert("seq".into(), Value::from(self.next_seq));
+        obj.insert("prev_hash".into(), Value::from(self.prev_hash.clone()));
+        let line = Value::Object(obj).to_string();
+        self.prev_hash = line_hash(&line);
+        self.next_seq += 1;
+        self.pending += 1;
+        line
+    }
+
+    /// Build and seal an `audit.checkpoint` record committing to the current head.
+    pub fn checkpoint(&mut self) -> String {
+        let head_seq = self.next_seq.saturating_sub(1);
+        let mut record = Map::new();
+        record.insert("event_type".into(), Value::from(CHECKPOINT_EVENT));
+        record.insert(
+            "timestamp".into(),
+            Value::from(chrono::Utc::now().to_rfc3339()),
+        );
+        record.insert("head_seq".into(), Value::from(head_seq));
+        record.insert("head_hash".into(), Value::from(self.prev_hash.clone()));
+        if let Some(key) = &self.signing_key {
+            record.insert(
+                "signature".into(),
+                Value::from(checkpoint_signature(key, head_seq, &self.prev_hash)),
+            );
+        }
+        let line = self.seal(Value::Object(record));
+        self.pending = 0;
+        line
+    }
+}
+
+/// Why a chain failed to verify. Line numbers are 1-based across all inputs.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ChainError {
+    #[error("line {line}: not a chained audit record")]
+    Malformed { line: usize },
+    #[error("line {line}: expected seq {expected}, found {found}")]
+    SeqGap {
+        line: usize,
+        expected: u64,
+        found: u64,
+    },
+    #[error("line {line}: prev_hash does not match the preceding record")]
+    HashMismatch { line: usize },
+    #[error("line {line}: checkpoint does not match the chain head")]
+    CheckpointMismatch { line: usize },
+    #[error("line {line}: checkpoint signature is missing or invalid")]
+    BadSignature { line: usize },
+}
+
+/// Summary of a successfully verified chain.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChainReport {
+    pub records: u64,
+    pub checkpoints: u64,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    /// Hash of the last record (pin this externally to detect truncation)
+    pub head_hash: Option<String>,
+    /// Records after the last checkpoint (not covered by a signature)
+    pub unsealed_tail: u64,
+}
+
+/// Verify chained audit lines in order. The first record is trusted as the
+/// starting point unless it is seq 0, which must link to [`GENESIS_HASH`].
+/// With a `signing_key`, every checkpoint must carry a valid signature.
+pub fn verify_lines<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    signing_key: Option<&str>,
+) -> Result<ChainReport, ChainError> {
+    let mut report = ChainReport::default();
+    let mut prev: Option<(u64, String)> = None;
+
+    for (idx, raw) in lines.into_iter().enumerate() {
+        let line = idx + 1;
+        let raw = raw.trim_end_matches(['\r', '\n']);
+        if raw.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(raw).map_err(|_| ChainError::Malformed { line })?;
+        let seq = value
+            .get("seq")
+            .and_then(Value::as_u64)
+            .ok_or(ChainError::Malformed { line })?;
+        let prev_hash = value
+            .get("prev_hash")
+            .and_then(Value::as_str)
+            .ok_or(ChainError::Malformed { line })?;
+
+        match &prev {
+            Some((prev_seq, hash)) => {
+                if seq != prev_seq + 1 {
+                    return Err(ChainError::SeqGap {
+                        line,
+                        expected: prev_seq + 1,
+                        found: seq,
+                    });
+                }
+                if prev_hash != hash {
+                    return Err(ChainError::HashMismatch { line });
+                }
+            }
+            None if seq == 0 && prev_hash != GENESIS_HASH => {
+                return Err(ChainError::HashMismatch { line });
+            }
+            None => {}
+        }
+
+        if value.get("event_type").and_then(Value::as_str) == Some(CHECKPOINT_EVENT) {
+            let head_seq = value.get("head_seq").and_then(Value::as_u64);
+            let head_hash = value.get("head_hash").and_then(Value::as_str);
+            if head_hash != Some(prev_hash) || head_seq != Some(seq.saturating_sub(1)) {
+                return Err(ChainError::CheckpointMismatch { line });
+            }
+            if let Some(key) = signing_key {
+                use subtle::ConstantTimeEq;
+                let expected =
+                    checkpoint_signature(key.as_bytes(), seq.saturating_sub(1), prev_hash);
+                let provided = value.get("signature").and_then(Value::as_str).unwrap_or("");
+                if !bool::from(provided.as_bytes().ct_eq(expected.as_bytes())) {
+                    return Err(ChainError::BadSignature { line });
+                }
+            }
+            report.checkpoints += 1;
+            report.unsealed_tail = 0;
+        } else {
+            report.unsealed_tail += 1;
+        }
+
+        report.records += 1;
+        report.first_seq.get_or_insert(seq);
+        report.last_seq = Some(seq);
+        prev = Some((seq, line_hash(raw)));
+    }
+
+    report.head_hash = prev.map(|(_, h)| h);
+    Ok(report)
+}
+
+/// Last non-empty line of `path`, if the file exists and has one.
+pub(crate) async fn last_line(path: &Path) -> Option<String> {
+    let content = tokio::fs::read_to_string(path).await.ok()?;
+    content
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .map(str::to_string)
+}
+
+/// `s5 verify-audit`: verify one or more audit files, oldest first.
+pub fn verify_files_cli(paths: &[PathBuf], signing_key: Option<&str>) -> anyhow::Result<()> {
+    let mut contents = Vec::with_capacity(paths.len());
+    for path in paths {
+        contents.push(
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?,
+        );
+    }
+    let report = verify_lines(contents.iter().flat_map(|c| c.lines()), signing_key)?;
+
+    println!("Audit chain OK.");
+    println!("  Records: {}", report.records);
+    if let (Some(first), Some(last)) = (report.first_seq, report.last_seq) {
+        println!("  Sequence: {}..={}", first, last);
+    }
+    println!(
+        "  Checkpoints: {}{}",
+        report.checkpoints,
+        if signing_key.is_some() {
+            " (signatures verified)"
+        } else {
+            " (signatures not checked: no key)"
+        }
+    );
+    if report.unsealed_tail > 0 {
+        println!(
+            "  Records after the last checkpoint: {}",
+            report.unsealed_tail
+        );
+    }
+    if let Some(head) = report.head_hash {
+        println!("  Head hash: {}", head);
+    }
+    Ok(())
+}
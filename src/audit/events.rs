@@ -1,6 +1,20 @@
+use crate::auth::invitation::Invitation;
+use crate::auth::pubkey::ExpiringKey;
+use crate::config::history::ConfigSnapshot;
+use crate::config::persist::GroupPolicy;
+use crate::config::types::PersonalTokenConfig;
+use crate::proxy::capture::{CaptureInfo, CaptureSummary};
+use crate::proxy::close::CloseReason;
+use crate::proxy::LiveSession;
+use crate::quota::{QuotaCounter, QuotaGrant};
+use crate::security::impossible_travel::ImpossibleTravel;
+use crate::security::key_enrollment::KeyEnrollmentRequest;
+use crate::security::login_anomaly::LoginAnomaly;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "event_type")]
@@ -13,6 +27,12 @@ pub enum AuditEvent {
         username: String,
         source_ip: String,
         method: String,
+        /// HASSH of the SSH client (SSH logins only)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hassh: Option<String>,
+        /// Added by the `on_auth` hook (`[scripting]`)
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        labels: BTreeMap<String, String>,
     },
     #[serde(rename = "auth.failure")]
     AuthFailure {
@@ -22,6 +42,9 @@ pub enum AuditEvent {
         username: String,
         source_ip: String,
         method: String,
+        /// HASSH of the SSH client (SSH logins only)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hassh: Option<String>,
     },
     #[serde(rename = "proxy.complete")]
     ProxyComplete {
@@ -40,6 +63,12 @@ pub enum AuditEvent {
         resolved_ip: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         via_proxy: Option<String>,
+        /// Why the session ended (see [`CloseReason`])
+        #[serde(skip_serializing_if = "Option::is_none")]
+        close_reason: Option<String>,
+        /// Added by the `on_session_close` hook (`[scripting]`)
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        labels: BTreeMap<String, String>,
     },
     #[serde(rename = "acl.deny")]
     AclDeny {
@@ -61,11 +90,16 @@ pub enum AuditEvent {
         timestamp: DateTime<Utc>,
         ip: String,
         duration_secs: u64,
+        /// Tenant whose logins the ban covers (None = server-wide)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
     },
     #[serde(rename = "ban.expired")]
     BanExpired {
         timestamp: DateTime<Utc>,
         ip: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
     },
     #[serde(rename = "connection.new")]
     ConnectionNew {
@@ -92,6 +126,29 @@ pub enum AuditEvent {
         error: Option<String>,
     },
 
+    /// An earlier configuration was written back and applied
+    /// (`POST /api/config/rollback/{id}`).
+    #[serde(rename = "config.rollback")]
+    ConfigRollback {
+        timestamp: DateTime<Utc>,
+        /// Config history snapshot restored
+        snapshot_id: u64,
+        sha256: String,
+        users_count: usize,
+        rolled_back_by: String,
+    },
+
+    /// A group's policy was written to the config file and applied
+    /// (`PUT /api/groups/{name}/policy`).
+    #[serde(rename = "config.group_updated")]
+    GroupPolicyUpdated {
+        timestamp: DateTime<Utc>,
+        group: String,
+        /// Policy after the change; unset fields are inherited
+        policy: GroupPolicy,
+        updated_by: String,
+    },
+
     #[serde(rename = "quota.exceeded")]
     QuotaExceeded {
         timestamp: DateTime<Utc>,
@@ -103,6 +160,75 @@ pub enum AuditEvent {
         limit: u64,
     },
 
+    /// Some of a user's quota counters were reset through the API.
+    #[serde(rename = "quota.reset")]
+    QuotaReset {
+        timestamp: DateTime<Utc>,
+        username: String,
+        counters: Vec<QuotaCounter>,
+        reset_by: String,
+    },
+
+    /// A user was granted one-off extra quota (`PATCH /api/users/{name}/quota`).
+    #[serde(rename = "quota.adjusted")]
+    QuotaAdjusted {
+        timestamp: DateTime<Utc>,
+        username: String,
+        /// Added by this call
+        granted: QuotaGrant,
+        /// Allowances in effect afterwards
+        total_granted: QuotaGrant,
+        adjusted_by: String,
+    },
+
+    /// A session held more than `limits.max_buffered_bytes` and was closed.
+    #[serde(rename = "session.memory_exceeded")]
+    SessionMemoryExceeded {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        session_id: String,
+        username: String,
+        target_host: String,
+        target_port: u16,
+        buffered_bytes: u64,
+        max_buffered_bytes: u64,
+    },
+
+    /// A session reached `limits.max_connection_bytes` and was closed.
+    #[serde(rename = "session.transfer_exceeded")]
+    SessionTransferExceeded {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        session_id: String,
+        username: String,
+        source_ip: String,
+        target_host: String,
+        target_port: u16,
+        bytes_up: u64,
+        bytes_down: u64,
+        max_connection_bytes: u64,
+    },
+
+    /// One direction of a session went past `limits.upload_alert_bytes` or
+    /// `limits.download_alert_bytes`. The session stays open.
+    #[serde(rename = "session.transfer_alert")]
+    SessionTransferAlert {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        session_id: String,
+        username: String,
+        source_ip: String,
+        target_host: String,
+        target_port: u16,
+        /// `upload` or `download`
+        direction: String,
+        bytes: u64,
+        threshold: u64,
+    },
+
     #[serde(rename = "session.authenticated")]
     SessionAuthenticated {
         timestamp: DateTime<Utc>,
@@ -142,6 +268,269 @@ pub enum AuditEvent {
         enabled: bool,
         source: String,
     },
+
+    #[serde(rename = "logging.level_changed")]
+    LogLevelChanged {
+        timestamp: DateTime<Utc>,
+        previous: String,
+        level: String,
+        changed_by: String,
+    },
+
+    #[serde(rename = "user.password_changed")]
+    PasswordChanged {
+        timestamp: DateTime<Utc>,
+        username: String,
+        source_ip: String,
+        /// Channel used for the change ("ssh" or "api")
+        via: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+
+    /// Personal API token created on the self-service page.
+    #[serde(rename = "user.token_created")]
+    ApiTokenCreated {
+        timestamp: DateTime<Utc>,
+        username: String,
+        source_ip: String,
+        token_id: String,
+        name: String,
+    },
+
+    /// Personal API token revoked on the self-service page.
+    #[serde(rename = "user.token_revoked")]
+    ApiTokenRevoked {
+        timestamp: DateTime<Utc>,
+        username: String,
+        source_ip: String,
+        token_id: String,
+        name: String,
+    },
+
+    /// Account locked after `security.lock_after_failures` failed logins.
+    #[serde(rename = "user.locked")]
+    AccountLocked {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        username: String,
+        /// Source of the failure that locked the account
+        source_ip: String,
+        protocol: String,
+        failures: u32,
+        lock_secs: u64,
+    },
+
+    /// Account lock lifted by an operator.
+    #[serde(rename = "user.unlocked")]
+    AccountUnlocked {
+        timestamp: DateTime<Utc>,
+        username: String,
+    },
+
+    /// Logins turned off (`disabled = true`) or back on by a group manager.
+    #[serde(rename = "user.disabled")]
+    AccountDisabled {
+        timestamp: DateTime<Utc>,
+        username: String,
+        disabled: bool,
+        updated_by: String,
+    },
+
+    /// Invitation link created for a new user (`POST /api/invitations`).
+    #[serde(rename = "user.invited")]
+    UserInvited {
+        timestamp: DateTime<Utc>,
+        username: String,
+        invitation_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+        expires_at: DateTime<Utc>,
+        created_by: String,
+    },
+
+    /// Account created by completing an invitation link.
+    #[serde(rename = "user.invitation_accepted")]
+    InvitationAccepted {
+        timestamp: DateTime<Utc>,
+        username: String,
+        invitation_id: String,
+        source_ip: String,
+        /// Whether a public key was uploaded along with the password
+        public_key: bool,
+    },
+
+    #[serde(rename = "honeypot.triggered")]
+    HoneypotTriggered {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        /// Always "high": a decoy login is never legitimate
+        severity: &'static str,
+        username: String,
+        source_ip: String,
+        protocol: String,
+        /// Seconds the source was banned for (None = not banned)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ban_secs: Option<u64>,
+    },
+
+    /// One source failed logins as many usernames
+    /// (`[security.credential_spraying]`).
+    #[serde(rename = "auth.spraying")]
+    CredentialSpraying {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        source_ip: String,
+        protocol: String,
+        /// Distinct usernames tried within the window, sorted
+        usernames: Vec<String>,
+        window_secs: u64,
+        /// Seconds the source was banned for (None = not banned)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ban_secs: Option<u64>,
+    },
+
+    /// Successful login that broke the user's habits (`[login_anomaly]`).
+    #[serde(rename = "auth.anomaly")]
+    LoginAnomaly {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        username: String,
+        source_ip: String,
+        protocol: String,
+        /// `new_country`, `new_asn` and/or `unusual_hour`
+        reasons: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        country: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        asn: Option<u32>,
+        /// UTC hour of the login
+        hour: u32,
+    },
+
+    /// Login too far from one of the user's recent logins to have travelled
+    /// in between (`[impossible_travel]`).
+    #[serde(rename = "auth.impossible_travel")]
+    ImpossibleTravel {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        username: String,
+        source_ip: String,
+        protocol: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        country: Option<String>,
+        previous_ip: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        previous_country: Option<String>,
+        distance_km: u64,
+        elapsed_secs: u64,
+        speed_kmh: u64,
+        /// Whether the login was refused
+        blocked: bool,
+    },
+
+    /// A user signed in with a public key awaiting admin approval
+    /// (`[key_enrollment]`).
+    #[serde(rename = "key_enrollment.requested")]
+    KeyEnrollmentRequested {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        request_id: String,
+        username: String,
+        source_ip: String,
+        fingerprint: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hassh: Option<String>,
+    },
+
+    /// An admin approved or rejected an enrollment request.
+    #[serde(rename = "key_enrollment.decided")]
+    KeyEnrollmentDecided {
+        timestamp: DateTime<Utc>,
+        request_id: String,
+        username: String,
+        fingerprint: String,
+        approved: bool,
+        decided_by: String,
+    },
+
+    /// Daily reminder for an authorized key whose `expiry-time` is near.
+    #[serde(rename = "key.expiring")]
+    KeyExpiring {
+        timestamp: DateTime<Utc>,
+        username: String,
+        fingerprint: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+        expires_at: DateTime<Utc>,
+        /// Whole days left, 0 on the last day
+        days_left: i64,
+    },
+
+    /// SSH hop through the bastion (`ssh -J`), logged when the hop connects.
+    #[serde(rename = "ssh.jump")]
+    SshJump {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        username: String,
+        source_ip: String,
+        /// Final destination as requested by the client
+        target_host: String,
+        target_port: u16,
+        /// Address actually connected to (None = resolved by an upstream proxy)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resolved_ip: Option<String>,
+        /// Jump target (or ACL rule) that allowed the hop
+        #[serde(skip_serializing_if = "Option::is_none")]
+        matched_rule: Option<String>,
+    },
+
+    /// An admin started recording a live session to a pcapng file
+    /// (`[logging.capture]`).
+    #[serde(rename = "capture.started")]
+    CaptureStarted {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        session_id: String,
+        username: String,
+        target_host: String,
+        target_port: u16,
+        /// `full` or `headers`
+        mode: String,
+        path: String,
+        duration_secs: u64,
+        started_by: String,
+    },
+
+    /// A session capture ended and its file was closed.
+    #[serde(rename = "capture.stopped")]
+    CaptureStopped {
+        timestamp: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+        session_id: String,
+        username: String,
+        path: String,
+        /// `expired`, `size_limit`, `session_closed`, `stopped` or `write_error`
+        reason: String,
+        packets: u64,
+        payload_bytes: u64,
+        file_bytes: u64,
+        /// Chunks not written because the writer fell behind
+        dropped: u64,
+        /// Admin who stopped it (None = ended on its own)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stopped_by: Option<String>,
+    },
 }
 
 impl AuditEvent {
@@ -152,6 +541,8 @@ impl AuditEvent {
             username: username.to_string(),
             source_ip: source.ip().to_string(),
             method: method.to_string(),
+            hassh: None,
+            labels: BTreeMap::new(),
         }
     }
 
@@ -167,6 +558,8 @@ impl AuditEvent {
             username: username.to_string(),
             source_ip: source.ip().to_string(),
             method: method.to_string(),
+            hassh: None,
+            labels: BTreeMap::new(),
         }
     }
 
@@ -177,6 +570,7 @@ impl AuditEvent {
             username: username.to_string(),
             source_ip: source.ip().to_string(),
             method: method.to_string(),
+            hassh: None,
         }
     }
 
@@ -192,6 +586,7 @@ impl AuditEvent {
             username: username.to_string(),
             source_ip: source.ip().to_string(),
             method: method.to_string(),
+            hassh: None,
         }
     }
 
@@ -219,6 +614,8 @@ impl AuditEvent {
             source_ip: source.ip().to_string(),
             resolved_ip,
             via_proxy: None,
+            close_reason: None,
+            labels: BTreeMap::new(),
         }
     }
 
@@ -247,9 +644,44 @@ impl AuditEvent {
             source_ip: source.ip().to_string(),
             resolved_ip,
             via_proxy: None,
+            close_reason: None,
+            labels: BTreeMap::new(),
         }
     }
 
+    /// Attach the close reason to a `proxy.complete` event (no-op otherwise).
+    pub fn with_close_reason(mut self, reason: CloseReason) -> Self {
+        if let Self::ProxyComplete {
+            ref mut close_reason,
+            ..
+        } = self
+        {
+            *close_reason = Some(reason.as_str().to_string());
+        }
+        self
+    }
+
+    /// Attach the client's HASSH to an `auth.*` event (no-op otherwise).
+    pub fn with_hassh(mut self, value: Option<String>) -> Self {
+        if let Self::AuthSuccess { ref mut hassh, .. } | Self::AuthFailure { ref mut hassh, .. } =
+            self
+        {
+            *hassh = value;
+        }
+        self
+    }
+
+    /// Attach the labels of a `[scripting]` hook to an `auth.success` or
+    /// `proxy.complete` event (no-op otherwise).
+    pub fn with_labels(mut self, value: BTreeMap<String, String>) -> Self {
+        if let Self::AuthSuccess { ref mut labels, .. }
+        | Self::ProxyComplete { ref mut labels, .. } = self
+        {
+            *labels = value;
+        }
+        self
+    }
+
     pub fn acl_deny(
         username: &str,
         host: &str,
@@ -341,6 +773,25 @@ impl AuditEvent {
         }
     }
 
+    pub fn config_rollback(snapshot: &ConfigSnapshot, by: &str) -> Self {
+        Self::ConfigRollback {
+            timestamp: Utc::now(),
+            snapshot_id: snapshot.id,
+            sha256: snapshot.sha256.clone(),
+            users_count: snapshot.users_count,
+            rolled_back_by: by.to_string(),
+        }
+    }
+
+    pub fn group_policy_updated(group: &str, policy: &GroupPolicy, by: &str) -> Self {
+        Self::GroupPolicyUpdated {
+            timestamp: Utc::now(),
+            group: group.to_string(),
+            policy: policy.clone(),
+            updated_by: by.to_string(),
+        }
+    }
+
     pub fn quota_exceeded(
         username: &str,
         quota_type: &str,
@@ -374,6 +825,78 @@ impl AuditEvent {
         }
     }
 
+    pub fn quota_reset(username: &str, counters: &[QuotaCounter], by: &str) -> Self {
+        Self::QuotaReset {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            counters: counters.to_vec(),
+            reset_by: by.to_string(),
+        }
+    }
+
+    pub fn quota_adjusted(
+        username: &str,
+        granted: &QuotaGrant,
+        total_granted: &QuotaGrant,
+        by: &str,
+    ) -> Self {
+        Self::QuotaAdjusted {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            granted: *granted,
+            total_granted: *total_granted,
+            adjusted_by: by.to_string(),
+        }
+    }
+
+    pub fn session_memory_exceeded(session: &LiveSession, buffered_bytes: u64) -> Self {
+        Self::SessionMemoryExceeded {
+            timestamp: Utc::now(),
+            correlation_id: session.correlation_id.clone(),
+            session_id: session.session_id.clone(),
+            username: session.username.clone(),
+            target_host: session.target_host.clone(),
+            target_port: session.target_port,
+            buffered_bytes,
+            max_buffered_bytes: session.memory.budget(),
+        }
+    }
+
+    pub fn session_transfer_exceeded(session: &LiveSession) -> Self {
+        Self::SessionTransferExceeded {
+            timestamp: Utc::now(),
+            correlation_id: session.correlation_id.clone(),
+            session_id: session.session_id.clone(),
+            username: session.username.clone(),
+            source_ip: session.source_ip.clone(),
+            target_host: session.target_host.clone(),
+            target_port: session.target_port,
+            bytes_up: session.bytes_up.load(Ordering::Relaxed),
+            bytes_down: session.bytes_down.load(Ordering::Relaxed),
+            max_connection_bytes: session.transfer.max_bytes,
+        }
+    }
+
+    pub fn session_transfer_alert(
+        session: &LiveSession,
+        upload: bool,
+        bytes: u64,
+        threshold: u64,
+    ) -> Self {
+        Self::SessionTransferAlert {
+            timestamp: Utc::now(),
+            correlation_id: session.correlation_id.clone(),
+            session_id: session.session_id.clone(),
+            username: session.username.clone(),
+            source_ip: session.source_ip.clone(),
+            target_host: session.target_host.clone(),
+            target_port: session.target_port,
+            direction: if upload { "upload" } else { "download" }.to_string(),
+            bytes,
+            threshold,
+        }
+    }
+
     pub fn session_authenticated(
         username: &str,
         source: &SocketAddr,
@@ -477,18 +1000,306 @@ impl AuditEvent {
         }
     }
 
+    pub fn log_level_changed(previous: &str, level: &str, changed_by: &str) -> Self {
+        Self::LogLevelChanged {
+            timestamp: Utc::now(),
+            previous: previous.to_string(),
+            level: level.to_string(),
+            changed_by: changed_by.to_string(),
+        }
+    }
+
+    pub fn password_changed(
+        username: &str,
+        source_ip: &str,
+        via: &str,
+        success: bool,
+        error: Option<String>,
+    ) -> Self {
+        Self::PasswordChanged {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            source_ip: source_ip.to_string(),
+            via: via.to_string(),
+            success,
+            error,
+        }
+    }
+
+    pub fn api_token_created(username: &str, source_ip: &str, token: &PersonalTokenConfig) -> Self {
+        Self::ApiTokenCreated {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            source_ip: source_ip.to_string(),
+            token_id: token.id.clone(),
+            name: token.name.clone(),
+        }
+    }
+
+    pub fn api_token_revoked(username: &str, source_ip: &str, token: &PersonalTokenConfig) -> Self {
+        Self::ApiTokenRevoked {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            source_ip: source_ip.to_string(),
+            token_id: token.id.clone(),
+            name: token.name.clone(),
+        }
+    }
+
+    pub fn account_locked_with_cid(
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        failures: u32,
+        lock_secs: u64,
+    ) -> Self {
+        Self::AccountLocked {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            username: username.to_string(),
+            source_ip: source.ip().to_string(),
+            protocol: protocol.to_string(),
+            failures,
+            lock_secs,
+        }
+    }
+
+    pub fn account_unlocked(username: &str) -> Self {
+        Self::AccountUnlocked {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+        }
+    }
+
+    pub fn account_disabled(username: &str, disabled: bool, by: &str) -> Self {
+        Self::AccountDisabled {
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            disabled,
+            updated_by: by.to_string(),
+        }
+    }
+
+    pub fn user_invited(invitation: &Invitation) -> Self {
+        Self::UserInvited {
+            timestamp: Utc::now(),
+            username: invitation.username.clone(),
+            invitation_id: invitation.id.clone(),
+            group: invitation.group.clone(),
+            expires_at: invitation.expires_at,
+            created_by: invitation.created_by.clone(),
+        }
+    }
+
+    pub fn invitation_accepted(invitation: &Invitation, source_ip: &str, public_key: bool) -> Self {
+        Self::InvitationAccepted {
+            timestamp: Utc::now(),
+            username: invitation.username.clone(),
+            invitation_id: invitation.id.clone(),
+            source_ip: source_ip.to_string(),
+            public_key,
+        }
+    }
+
+    pub fn honeypot_triggered(
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        ban_secs: Option<u64>,
+    ) -> Self {
+        Self::HoneypotTriggered {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            severity: "high",
+            username: username.to_string(),
+            source_ip: source.ip().to_string(),
+            protocol: protocol.to_string(),
+            ban_secs,
+        }
+    }
+
+    pub fn credential_spraying_with_cid(
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        usernames: Vec<String>,
+        window_secs: u64,
+        ban_secs: Option<u64>,
+    ) -> Self {
+        Self::CredentialSpraying {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            source_ip: source.ip().to_string(),
+            protocol: protocol.to_string(),
+            usernames,
+            window_secs,
+            ban_secs,
+        }
+    }
+
+    pub fn login_anomaly_with_cid(
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        anomaly: &LoginAnomaly,
+    ) -> Self {
+        Self::LoginAnomaly {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            username: username.to_string(),
+            source_ip: source.ip().to_string(),
+            protocol: protocol.to_string(),
+            reasons: anomaly.reasons.iter().map(|r| r.to_string()).collect(),
+            country: anomaly.country.clone(),
+            asn: anomaly.asn,
+            hour: anomaly.hour,
+        }
+    }
+
+    pub fn impossible_travel_with_cid(
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        travel: &ImpossibleTravel,
+        blocked: bool,
+    ) -> Self {
+        Self::ImpossibleTravel {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            username: username.to_string(),
+            source_ip: source.ip().to_string(),
+            protocol: protocol.to_string(),
+            country: travel.country.clone(),
+            previous_ip: travel.previous_ip.to_string(),
+            previous_country: travel.previous_country.clone(),
+            distance_km: travel.distance_km,
+            elapsed_secs: travel.elapsed_secs,
+            speed_kmh: travel.speed_kmh,
+            blocked,
+        }
+    }
+
+    pub fn key_enrollment_requested_with_cid(request: &KeyEnrollmentRequest, cid: &str) -> Self {
+        Self::KeyEnrollmentRequested {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            request_id: request.id.clone(),
+            username: request.username.clone(),
+            source_ip: request.source_ip.clone(),
+            fingerprint: request.fingerprint.clone(),
+            hassh: request.hassh.clone(),
+        }
+    }
+
+    pub fn key_enrollment_decided(
+        request: &KeyEnrollmentRequest,
+        approved: bool,
+        by: &str,
+    ) -> Self {
+        Self::KeyEnrollmentDecided {
+            timestamp: Utc::now(),
+            request_id: request.id.clone(),
+            username: request.username.clone(),
+            fingerprint: request.fingerprint.clone(),
+            approved,
+            decided_by: by.to_string(),
+        }
+    }
+
+    pub fn key_expiring(key: &ExpiringKey, now: DateTime<Utc>) -> Self {
+        Self::KeyExpiring {
+            timestamp: now,
+            username: key.username.clone(),
+            fingerprint: key.fingerprint.clone(),
+            comment: key.comment.clone(),
+            expires_at: key.expires_at,
+            days_left: (key.expires_at - now).num_days().max(0),
+        }
+    }
+
+    pub fn ssh_jump_with_cid(
+        username: &str,
+        host: &str,
+        port: u16,
+        resolved_ip: Option<String>,
+        source_ip: &str,
+        matched_rule: Option<String>,
+        cid: &str,
+    ) -> Self {
+        Self::SshJump {
+            timestamp: Utc::now(),
+            correlation_id: Some(cid.to_string()),
+            username: username.to_string(),
+            source_ip: source_ip.to_string(),
+            target_host: host.to_string(),
+            target_port: port,
+            resolved_ip,
+            matched_rule,
+        }
+    }
+
+    pub fn capture_started(info: &CaptureInfo, duration_secs: u64) -> Self {
+        Self::CaptureStarted {
+            timestamp: Utc::now(),
+            correlation_id: info.correlation_id.clone(),
+            session_id: info.session_id.clone(),
+            username: info.username.clone(),
+            target_host: info.target_host.clone(),
+            target_port: info.target_port,
+            mode: info.mode.as_str().to_string(),
+            path: info.path.display().to_string(),
+            duration_secs,
+            started_by: info.started_by.clone(),
+        }
+    }
+
+    pub fn capture_stopped(summary: &CaptureSummary) -> Self {
+        Self::CaptureStopped {
+            timestamp: Utc::now(),
+            correlation_id: summary.info.correlation_id.clone(),
+            session_id: summary.info.session_id.clone(),
+            username: summary.info.username.clone(),
+            path: summary.info.path.display().to_string(),
+            reason: summary.reason.as_str().to_string(),
+            packets: summary.packets,
+            payload_bytes: summary.payload_bytes,
+            file_bytes: summary.file_bytes,
+            dropped: summary.dropped,
+            stopped_by: summary.stopped_by.clone(),
+        }
+    }
+
     pub fn ban_created(ip: &std::net::IpAddr, duration_secs: u64) -> Self {
+        Self::ban_created_for(ip, None, duration_secs)
+    }
+
+    /// A ban of `ip` for the logins of `tenant` (None = server-wide).
+    pub fn ban_created_for(
+        ip: &std::net::IpAddr,
+        tenant: Option<&str>,
+        duration_secs: u64,
+    ) -> Self {
         Self::BanCreated {
             timestamp: Utc::now(),
             ip: ip.to_string(),
             duration_secs,
+            tenant: tenant.map(str::to_string),
         }
     }
 
     pub fn ban_expired(ip: &std::net::IpAddr) -> Self {
+        Self::ban_expired_for(ip, None)
+    }
+
+    pub fn ban_expired_for(ip: &std::net::IpAddr, tenant: Option<&str>) -> Self {
         Self::BanExpired {
             timestamp: Utc::now(),
             ip: ip.to_string(),
+            tenant: tenant.map(str::to_string),
         }
     }
 
@@ -504,16 +1315,48 @@ impl AuditEvent {
             Self::ConnectionNew { .. } => "connection.new",
             Self::ConnectionClosed { .. } => "connection.closed",
             Self::ConfigReload { .. } => "config.reload",
+            Self::ConfigRollback { .. } => "config.rollback",
+            Self::GroupPolicyUpdated { .. } => "config.group_updated",
             Self::QuotaExceeded { .. } => "quota.exceeded",
+            Self::QuotaReset { .. } => "quota.reset",
+            Self::QuotaAdjusted { .. } => "quota.adjusted",
+            Self::SessionMemoryExceeded { .. } => "session.memory_exceeded",
+            Self::SessionTransferExceeded { .. } => "session.transfer_exceeded",
+            Self::SessionTransferAlert { .. } => "session.transfer_alert",
             Self::SessionAuthenticated { .. } => "session.authenticated",
             Self::SessionEnded { .. } => "session.ended",
             Self::RateLimitExceeded { .. } => "rate_limit.exceeded",
             Self::MaintenanceToggled { .. } => "maintenance.toggled",
+            Self::LogLevelChanged { .. } => "logging.level_changed",
+            Self::PasswordChanged { .. } => "user.password_changed",
+            Self::ApiTokenCreated { .. } => "user.token_created",
+            Self::ApiTokenRevoked { .. } => "user.token_revoked",
+            Self::AccountLocked { .. } => "user.locked",
+            Self::AccountUnlocked { .. } => "user.unlocked",
+            Self::AccountDisabled { .. } => "user.disabled",
+            Self::UserInvited { .. } => "user.invited",
+            Self::InvitationAccepted { .. } => "user.invitation_accepted",
+            Self::HoneypotTriggered { .. } => "honeypot.triggered",
+            Self::CredentialSpraying { .. } => "auth.spraying",
+            Self::LoginAnomaly { .. } => "auth.anomaly",
+            Self::ImpossibleTravel { .. } => "auth.impossible_travel",
+            Self::KeyEnrollmentRequested { .. } => "key_enrollment.requested",
+            Self::KeyEnrollmentDecided { .. } => "key_enrollment.decided",
+            Self::KeyExpiring { .. } => "key.expiring",
+            Self::SshJump { .. } => "ssh.jump",
+            Self::CaptureStarted { .. } => "capture.started",
+            Self::CaptureStopped { .. } => "capture.stopped",
         }
     }
 
     /// Whether this event is critical and should use priority delivery.
-    /// Critical events: ACL denials, bans, config reloads, auth failures.
+    /// Critical events: ACL denials, bans, config reloads, rollbacks and group policy
+    /// changes, log level changes, auth failures, quota, memory budget and
+    /// transfer cap hits, transfer alerts,
+    /// quota resets and grants,
+    /// password changes, personal API token changes, account locks and disables,
+    /// invitations, honeypot logins, credential spraying, login anomalies, impossible travel,
+    /// key enrollments, session captures.
     pub fn is_critical(&self) -> bool {
         matches!(
             self,
@@ -521,10 +1364,34 @@ impl AuditEvent {
                 | Self::BanCreated { .. }
                 | Self::BanExpired { .. }
                 | Self::ConfigReload { .. }
+                | Self::ConfigRollback { .. }
+                | Self::GroupPolicyUpdated { .. }
                 | Self::AuthFailure { .. }
                 | Self::QuotaExceeded { .. }
+                | Self::QuotaReset { .. }
+                | Self::QuotaAdjusted { .. }
+                | Self::SessionMemoryExceeded { .. }
+                | Self::SessionTransferExceeded { .. }
+                | Self::SessionTransferAlert { .. }
                 | Self::RateLimitExceeded { .. }
                 | Self::MaintenanceToggled { .. }
+                | Self::LogLevelChanged { .. }
+                | Self::PasswordChanged { .. }
+                | Self::ApiTokenCreated { .. }
+                | Self::ApiTokenRevoked { .. }
+                | Self::AccountLocked { .. }
+                | Self::AccountUnlocked { .. }
+                | Self::AccountDisabled { .. }
+                | Self::UserInvited { .. }
+                | Self::InvitationAccepted { .. }
+                | Self::HoneypotTriggered { .. }
+                | Self::CredentialSpraying { .. }
+                | Self::LoginAnomaly { .. }
+                | Self::ImpossibleTravel { .. }
+                | Self::KeyEnrollmentRequested { .. }
+                | Self::KeyEnrollmentDecided { .. }
+                | Self::CaptureStarted { .. }
+                | Self::CaptureStopped { .. }
         )
     }
 }
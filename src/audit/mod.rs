@@ -1,6 +1,14 @@
+pub mod archive;
+pub mod chain;
 pub mod events;
 
+use crate::notifications::Notifier;
+use crate::security::impossible_travel::ImpossibleTravel;
+use crate::security::login_anomaly::LoginAnomaly;
+use crate::security::timeline::SecurityTimeline;
 use crate::webhooks::WebhookDispatcher;
+use archive::AuditArchiver;
+use chain::{AuditChain, AuditChainOptions};
 use events::AuditEvent;
 use std::collections::VecDeque;
 use std::net::SocketAddr;
@@ -20,6 +28,8 @@ pub struct AuditLogger {
     dropped_count: AtomicU64,
     dropped_metric: std::sync::OnceLock<prometheus_client::metrics::counter::Counter>,
     recent_events: Arc<Mutex<VecDeque<AuditEvent>>>,
+    /// Auth failures, bans and ip_guard blocks for the dashboard timeline
+    security_timeline: Arc<SecurityTimeline>,
 }
 
 impl AuditLogger {
@@ -28,6 +38,29 @@ impl AuditLogger {
         max_size_bytes: u64,
         max_files: u32,
         webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+    ) -> Self {
+        Self::with_chain(
+            log_path,
+            max_size_bytes,
+            max_files,
+            webhook_dispatcher,
+            AuditChainOptions::default(),
+            None,
+            None,
+        )
+    }
+
+    /// Like [`AuditLogger::new`], with optional hash chaining and checkpoints
+    /// for the audit file (see [`chain`]), email notifications and archiving
+    /// of rotated files (see [`archive`]).
+    pub fn with_chain(
+        log_path: Option<PathBuf>,
+        max_size_bytes: u64,
+        max_files: u32,
+        webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+        chain: AuditChainOptions,
+        notifier: Option<Arc<Notifier>>,
+        archive: Option<Arc<AuditArchiver>>,
     ) -> Self {
         let (sender, receiver) = mpsc::channel(AUDIT_CHANNEL_CAPACITY);
 
@@ -37,6 +70,9 @@ impl AuditLogger {
             max_size_bytes,
             max_files,
             webhook_dispatcher,
+            chain,
+            notifier,
+            archive,
         ));
 
         Self {
@@ -44,6 +80,7 @@ impl AuditLogger {
             dropped_count: AtomicU64::new(0),
             dropped_metric: std::sync::OnceLock::new(),
             recent_events: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_EVENTS_CAPACITY))),
+            security_timeline: Arc::new(SecurityTimeline::new()),
         }
     }
 
@@ -56,6 +93,7 @@ impl AuditLogger {
             dropped_count: AtomicU64::new(0),
             dropped_metric: std::sync::OnceLock::new(),
             recent_events: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_EVENTS_CAPACITY))),
+            security_timeline: Arc::new(SecurityTimeline::new()),
         }
     }
 
@@ -187,13 +225,144 @@ impl AuditLogger {
         self.try_send(event);
     }
 
+    pub fn log_log_level_changed(&self, previous: &str, level: &str, changed_by: &str) {
+        let event = AuditEvent::log_level_changed(previous, level, changed_by);
+        self.try_send(event);
+    }
+
+    pub fn log_password_changed(
+        &self,
+        username: &str,
+        source_ip: &str,
+        via: &str,
+        success: bool,
+        error: Option<String>,
+    ) {
+        let event = AuditEvent::password_changed(username, source_ip, via, success, error);
+        self.try_send(event);
+    }
+
+    pub fn log_account_locked(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        failures: u32,
+        lock_secs: u64,
+    ) {
+        let event = AuditEvent::account_locked_with_cid(
+            username, source, protocol, cid, failures, lock_secs,
+        );
+        self.try_send(event);
+    }
+
+    pub fn log_account_unlocked(&self, username: &str) {
+        self.try_send(AuditEvent::account_unlocked(username));
+    }
+
+    pub fn log_honeypot_triggered(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        ban_secs: Option<u64>,
+    ) {
+        let event = AuditEvent::honeypot_triggered(username, source, protocol, cid, ban_secs);
+        self.try_send(event);
+    }
+
+    pub fn log_credential_spraying(
+        &self,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        usernames: Vec<String>,
+        window_secs: u64,
+        ban_secs: Option<u64>,
+    ) {
+        let event = AuditEvent::credential_spraying_with_cid(
+            source,
+            protocol,
+            cid,
+            usernames,
+            window_secs,
+            ban_secs,
+        );
+        self.try_send(event);
+    }
+
+    pub fn log_login_anomaly(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        anomaly: &LoginAnomaly,
+    ) {
+        let event = AuditEvent::login_anomaly_with_cid(username, source, protocol, cid, anomaly);
+        self.try_send(event);
+    }
+
+    pub fn log_impossible_travel(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+        travel: &ImpossibleTravel,
+        blocked: bool,
+    ) {
+        let event = AuditEvent::impossible_travel_with_cid(
+            username, source, protocol, cid, travel, blocked,
+        );
+        self.try_send(event);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn log_ssh_jump_cid(
+        &self,
+        username: &str,
+        host: &str,
+        port: u16,
+        resolved_ip: Option<String>,
+        source_ip: &str,
+        matched_rule: Option<String>,
+        cid: &str,
+    ) {
+        let event = AuditEvent::ssh_jump_with_cid(
+            username,
+            host,
+            port,
+            resolved_ip,
+            source_ip,
+            matched_rule,
+            cid,
+        );
+        self.try_send(event);
+    }
+
     pub fn log_ban_created(&self, ip: &std::net::IpAddr, duration_secs: u64) {
-        let event = AuditEvent::ban_created(ip, duration_secs);
+        self.log_ban_created_for(ip, None, duration_secs);
+    }
+
+    pub fn log_ban_created_for(
+        &self,
+        ip: &std::net::IpAddr,
+        tenant: Option<&str>,
+        duration_secs: u64,
+    ) {
+        let event = AuditEvent::ban_created_for(ip, tenant, duration_secs);
         self.try_send(event);
     }
 
     pub fn log_ban_expired(&self, ip: &std::net::IpAddr) {
-        let event = AuditEvent::ban_expired(ip);
+        self.log_ban_expired_for(ip, None);
+    }
+
+    pub fn log_ban_expired_for(&self, ip: &std::net::IpAddr, tenant: Option<&str>) {
+        let event = AuditEvent::ban_expired_for(ip, tenant);
         self.try_send(event);
     }
 
@@ -296,6 +465,19 @@ impl AuditLogger {
         self.try_send(event);
     }
 
+    pub fn log_quota_exceeded_cid(
+        &self,
+        username: &str,
+        quota_type: &str,
+        current_usage: u64,
+        limit: u64,
+        cid: &str,
+    ) {
+        let event =
+            AuditEvent::quota_exceeded_with_cid(username, quota_type, current_usage, limit, cid);
+        self.try_send(event);
+    }
+
     pub fn log_rate_limit_exceeded_cid(
         &self,
         username: &str,
@@ -318,6 +500,11 @@ impl AuditLogger {
         buf.iter().skip(skip).cloned().collect()
     }
 
+    /// Security event history (`/api/security/timeline`).
+    pub fn security_timeline(&self) -> &Arc<SecurityTimeline> {
+        &self.security_timeline
+    }
+
     fn try_send(&self, event: AuditEvent) {
         // Store a clone in the in-memory ring buffer before sending
         {
@@ -327,6 +514,7 @@ impl AuditLogger {
             }
             buf.push_back(event.clone());
         }
+        self.security_timeline.record(&event);
         let is_critical = event.is_critical();
 
         // M-6: Critical events use reserve() with a short timeout to avoid being
@@ -403,21 +591,31 @@ impl AuditLogger {
     }
 }
 
-async fn audit_writer_task(
-    mut receiver: mpsc::Receiver<AuditEvent>,
-    log_path: Option<PathBuf>,
+/// Append-only audit file with size-based rotation.
+struct AuditFile {
+    path: PathBuf,
+    file: Option<tokio::fs::File>,
+    current_size: u64,
     max_size_bytes: u64,
     max_files: u32,
-    webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
-) {
-    let mut file = if let Some(path) = &log_path {
+    /// Receives each rotated file (`[logging.audit.archive]`)
+    archive: Option<Arc<AuditArchiver>>,
+}
+
+impl AuditFile {
+    async fn open(
+        path: PathBuf,
+        max_size_bytes: u64,
+        max_files: u32,
+        archive: Option<Arc<AuditArchiver>>,
+    ) -> Self {
         if let Some(parent) = path.parent() {
             let _ = tokio::fs::create_dir_all(parent).await;
         }
-        match tokio::fs::OpenOptions::new()
+        let file = match tokio::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(path)
+            .open(&path)
             .await
         {
             Ok(f) => Some(f),
@@ -425,64 +623,140 @@ async fn audit_writer_task(
                 error!(path = %path.display(), error = %e, "Failed to open audit log");
                 None
             }
+        };
+        // Track current file size
+        let current_size = if file.is_some() {
+            tokio::fs::metadata(&path)
+                .await
+                .map(|m| m.len())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        Self {
+            path,
+            file,
+            current_size,
+            max_size_bytes,
+            max_files,
+            archive,
+        }
+    }
+
+    async fn write_line(&mut self, json: &str) {
+        let Some(ref mut f) = self.file else {
+            return;
+        };
+        let line = format!("{}\n", json);
+        let line_bytes = line.as_bytes();
+        if let Err(e) = f.write_all(line_bytes).await {
+            error!(error = %e, "Failed to write audit log");
+            return;
+        }
+        if let Err(e) = f.flush().await {
+            error!(error = %e, "Failed to flush audit log");
+        }
+        self.current_size += line_bytes.len() as u64;
+
+        // Check rotation
+        if self.max_size_bytes > 0 && self.current_size >= self.max_size_bytes {
+            drop(self.file.take());
+            let rotated = rotate_audit_files(&self.path, self.max_files).await;
+            if let (Some(rotated), Some(archive)) = (rotated, &self.archive) {
+                archive.stage(&rotated).await;
+            }
+            match tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await
+            {
+                Ok(new_file) => {
+                    self.file = Some(new_file);
+                    self.current_size = 0;
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to reopen audit log after rotation");
+                }
+            }
         }
-    } else {
-        None
+    }
+}
+
+/// Resume the chain from the newest record on disk (current file, or the
+/// most recent rotated one if the current file is empty).
+async fn resume_chain(path: &std::path::Path, options: &AuditChainOptions) -> AuditChain {
+    let key = options.signing_key.as_deref();
+    let rotated = PathBuf::from(format!("{}.1", path.display()));
+    for candidate in [path, rotated.as_path()] {
+        if let Some(line) = chain::last_line(candidate).await {
+            return AuditChain::resume(&line, key);
+        }
+    }
+    AuditChain::new(key)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn audit_writer_task(
+    mut receiver: mpsc::Receiver<AuditEvent>,
+    log_path: Option<PathBuf>,
+    max_size_bytes: u64,
+    max_files: u32,
+    webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+    chain_options: AuditChainOptions,
+    notifier: Option<Arc<Notifier>>,
+    archive: Option<Arc<AuditArchiver>>,
+) {
+    let mut chain = match (&log_path, chain_options.enabled) {
+        (Some(path), true) => Some(resume_chain(path, &chain_options).await),
+        _ => None,
+    };
+    let mut file = match log_path {
+        Some(path) => Some(AuditFile::open(path, max_size_bytes, max_files, archive).await),
+        None => None,
     };
 
-    // Track current file size
-    let mut current_size: u64 = if let (Some(ref path), Some(_)) = (&log_path, &file) {
-        tokio::fs::metadata(path)
-            .await
-            .map(|m| m.len())
-            .unwrap_or(0)
-    } else {
-        0
+    // Checkpoint timer (only with a chain and a non-zero interval)
+    let mut checkpoint_timer = match &chain {
+        Some(_) if !chain_options.checkpoint_interval.is_zero() => {
+            let period = chain_options.checkpoint_interval;
+            Some(tokio::time::interval_at(
+                tokio::time::Instant::now() + period,
+                period,
+            ))
+        }
+        _ => None,
     };
 
-    while let Some(event) = receiver.recv().await {
-        match serde_json::to_string(&event) {
-            Ok(json) => {
-                debug!(event = %json, "Audit event");
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            _ = next_checkpoint(&mut checkpoint_timer) => {
+                write_checkpoint(&mut chain, &mut file).await;
+                continue;
+            }
+        };
+
+        match serde_json::to_value(&event) {
+            Ok(value) => {
                 // Dispatch to webhooks (fire-and-forget)
                 if let Some(ref dispatcher) = webhook_dispatcher {
-                    if let Ok(data) = serde_json::to_value(&event) {
-                        dispatcher.dispatch(event.event_type(), data);
-                    }
+                    dispatcher.dispatch(event.event_type(), value.clone());
+                }
+                // Queue email notifications (sent in batches by the notifier)
+                if let Some(ref notifier) = notifier {
+                    notifier.observe(&event);
                 }
+                let json = match chain {
+                    Some(ref mut c) => c.seal(value),
+                    None => value.to_string(),
+                };
+                debug!(event = %json, "Audit event");
                 if let Some(ref mut f) = file {
-                    let line = format!("{}\n", json);
-                    let line_bytes = line.as_bytes();
-                    if let Err(e) = f.write_all(line_bytes).await {
-                        error!(error = %e, "Failed to write audit log");
-                        continue;
-                    }
-                    if let Err(e) = f.flush().await {
-                        error!(error = %e, "Failed to flush audit log");
-                    }
-                    current_size += line_bytes.len() as u64;
-
-                    // Check rotation
-                    if max_size_bytes > 0 && current_size >= max_size_bytes {
-                        if let Some(ref path) = log_path {
-                            drop(file.take());
-                            rotate_audit_files(path, max_files).await;
-                            match tokio::fs::OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(path)
-                                .await
-                            {
-                                Ok(new_file) => {
-                                    file = Some(new_file);
-                                    current_size = 0;
-                                }
-                                Err(e) => {
-                                    error!(error = %e, "Failed to reopen audit log after rotation");
-                                }
-                            }
-                        }
-                    }
+                    f.write_line(&json).await;
                 }
             }
             Err(e) => {
@@ -490,10 +764,33 @@ async fn audit_writer_task(
             }
         }
     }
+
+    // Seal the tail on shutdown
+    write_checkpoint(&mut chain, &mut file).await;
+}
+
+/// Wait for the next checkpoint tick; pends forever when checkpoints are off.
+async fn next_checkpoint(timer: &mut Option<tokio::time::Interval>) {
+    match timer {
+        Some(t) => {
+            t.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn write_checkpoint(chain: &mut Option<AuditChain>, file: &mut Option<AuditFile>) {
+    if let (Some(c), Some(f)) = (chain.as_mut(), file.as_mut()) {
+        if c.has_pending() {
+            let line = c.checkpoint();
+            f.write_line(&line).await;
+        }
+    }
 }
 
 /// Rotate audit log files: audit.json -> audit.json.1, audit.json.1 -> audit.json.2, etc.
-async fn rotate_audit_files(path: &std::path::Path, max_files: u32) {
+/// Returns the path of the rotated file, unless the rename failed.
+async fn rotate_audit_files(path: &std::path::Path, max_files: u32) -> Option<PathBuf> {
     // Shift existing rotated files
     for i in (1..max_files).rev() {
         let from = format!("{}.{}", path.display(), i);
@@ -501,8 +798,12 @@ async fn rotate_audit_files(path: &std::path::Path, max_files: u32) {
         let _ = tokio::fs::rename(&from, &to).await;
     }
     // Rename current file to .1
-    let rotated = format!("{}.1", path.display());
-    if let Err(e) = tokio::fs::rename(path, &rotated).await {
-        error!(error = %e, "Failed to rotate audit log");
+    let rotated = PathBuf::from(format!("{}.1", path.display()));
+    match tokio::fs::rename(path, &rotated).await {
+        Ok(()) => Some(rotated),
+        Err(e) => {
+            error!(error = %e, "Failed to rotate audit log");
+            None
+        }
     }
 }
@@ -0,0 +1,311 @@
+//! External password verification (`auth_backend = "external"`).
+//!
+//! The hook is either a program, which reads the credentials as JSON on stdin
+//! and allows the login by exiting with status 0, or an HTTP endpoint, which
+//! receives them as a JSON POST and allows the login with a 2xx reply
+//! (401/403 deny). Both may answer with a JSON object to deny explicitly or to
+//! set the user's group and quotas. Errors and timeouts deny the login.
+
+use super::user::User;
+use super::AuthService;
+use crate::config::types::{AppConfig, AuthBackend, ExternalAuthConfig, QuotaConfig, UserConfig};
+use crate::webhooks;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Largest hook answer accepted.
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Credentials sent to the hook.
+#[derive(Serialize)]
+pub struct ExternalAuthRequest<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    pub source_ip: String,
+    /// `ssh`, `socks5` or `masque`
+    pub protocol: &'a str,
+}
+
+/// Hook answer. An empty answer allows the login with the user's defaults.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalAuthResponse {
+    #[serde(default = "default_allow")]
+    pub allow: bool,
+    /// Group from `[[groups]]` (replaces the configured one)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Quotas (replace the configured ones)
+    #[serde(default)]
+    pub quotas: Option<QuotaConfig>,
+}
+
+fn default_allow() -> bool {
+    true
+}
+
+impl ExternalAuthResponse {
+    fn denied() -> Self {
+        Self {
+            allow: false,
+            group: None,
+            quotas: None,
+        }
+    }
+
+    /// Parse a hook answer; blank output means "allow".
+    pub fn parse(body: &[u8]) -> Result<Self> {
+        if body.len() > MAX_RESPONSE_BYTES {
+            anyhow::bail!("external auth answer exceeds {} bytes", MAX_RESPONSE_BYTES);
+        }
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Self {
+                allow: true,
+                ..Self::denied()
+            });
+        }
+        serde_json::from_slice(body).context("invalid external auth answer")
+    }
+}
+
+/// Configured external auth hook.
+pub struct ExternalAuth {
+    config: ExternalAuthConfig,
+    client: reqwest::Client,
+    /// Source of user profiles, groups and global defaults
+    app: Arc<AppConfig>,
+}
+
+impl std::fmt::Debug for ExternalAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExternalAuth")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExternalAuth {
+    /// `None` unless `auth_backend = "external"`.
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        if config.security.auth_backend != AuthBackend::External {
+            return None;
+        }
+        let external = config.security.external_auth.clone()?;
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Some(Self {
+            config: external,
+            client,
+            app: Arc::new(config.clone()),
+        })
+    }
+
+    /// Ask the hook about a login (bounded by `timeout_secs`).
+    pub async fn check(&self, request: &ExternalAuthRequest<'_>) -> Result<ExternalAuthResponse> {
+        let body = serde_json::to_string(request)?;
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let check = async {
+            match self.config.url {
+                Some(ref url) => self.post(url, body).await,
+                None => self.run(body).await,
+            }
+        };
+        tokio::time::timeout(timeout, check)
+            .await
+            .context("external auth timed out")?
+    }
+
+    async fn run(&self, body: String) -> Result<ExternalAuthResponse> {
+        let (program, args) = self
+            .config
+            .command
+            .split_first()
+            .context("external auth command is empty")?;
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("starting {}", program))?;
+
+        // A program that decides without reading stdin closes the pipe early
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(body.as_bytes()).await;
+        }
+        let mut output = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            stdout
+                .take(MAX_RESPONSE_BYTES as u64 + 1)
+                .read_to_end(&mut output)
+                .await?;
+        }
+        let status = child.wait().await?;
+        if !status.success() {
+            return Ok(ExternalAuthResponse::denied());
+        }
+        ExternalAuthResponse::parse(&output)
+    }
+
+    async fn post(&self, url: &str, body: String) -> Result<ExternalAuthResponse> {
+        let client =
+            webhooks::guarded_client(&self.client, url, self.config.allow_private_ips).await?;
+        let mut request = client.post(url).header("Content-Type", "application/json");
+        if let Some(ref secret) = self.config.secret {
+            request = request.header("X-Signature-256", webhooks::signature(secret, &body)?);
+        }
+        let response = request.body(body).send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Ok(ExternalAuthResponse::denied());
+        }
+        if !status.is_success() {
+            anyhow::bail!("external auth endpoint returned status {}", status);
+        }
+        if response
+            .content_length()
+            .is_some_and(|len| len > MAX_RESPONSE_BYTES as u64)
+        {
+            anyhow::bail!("external auth answer exceeds {} bytes", MAX_RESPONSE_BYTES);
+        }
+        ExternalAuthResponse::parse(&response.bytes().await?)
+    }
+
+    /// Runtime user for an allowed login: the `[[users]]` entry of the same
+    /// name (or a bare profile) with the hook's group and quotas applied.
+    pub fn user_for(&self, username: &str, response: &ExternalAuthResponse) -> Result<User> {
+        let app = &self.app;
+        let mut profile = app
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .cloned()
+            .unwrap_or_else(|| bare_profile(username));
+        if let Some(ref group) = response.group {
+            if !app.groups.iter().any(|g| g.name == *group) {
+                anyhow::bail!("external auth returned unknown group '{}'", group);
+            }
+            profile.group = Some(group.clone());
+        }
+        if let Some(ref quotas) = response.quotas {
+            profile.quotas = Some(quotas.clone());
+            profile.quota_plan = None;
+        }
+        let user = User::from_config(
+            &profile,
+            &app.groups,
+            &app.acl,
+            &app.limits,
+            &app.server,
+            &app.shell,
+        )?;
+        Ok(user.with_quota_plan(&app.quota_plans))
+    }
+}
+
+/// Profile of a user known only to the external hook.
+fn bare_profile(username: &str) -> UserConfig {
+    UserConfig {
+        username: username.to_string(),
+        password_hash: None,
+        authorized_keys: Vec::new(),
+        allow_forwarding: None,
+        allow_shell: true,
+        max_new_connections_per_minute: 0,
+        max_bandwidth_kbps: 0,
+        source_ips: Vec::new(),
+        ip_guard_exemptions: None,
+        jump_targets: None,
+        unix_sockets: None,
+        allowed_hassh: None,
+        subsystems: None,
+        impossible_travel: None,
+        password_changed_at: None,
+        expires_at: None,
+        disabled: false,
+        require_security_key: false,
+        allow_vpn: false,
+        vpn_address: None,
+        tags: Vec::new(),
+        authorized_keys_url: None,
+        upstream_proxy: None,
+        acl: Default::default(),
+        totp_secret: None,
+        totp_enabled: false,
+        max_aggregate_bandwidth_kbps: 0,
+        group: None,
+        role: Default::default(),
+        shell_permissions: None,
+        terminal: None,
+        motd: None,
+        quotas: None,
+        quota_plan: None,
+        time_access: None,
+        auth_methods: None,
+        idle_warning_secs: None,
+        colors: None,
+        connect_retry: None,
+        connect_retry_delay_ms: None,
+        aliases: HashMap::new(),
+        max_connections: None,
+        rate_limits: None,
+        api_token_hash: None,
+        api_tokens: Vec::new(),
+    }
+}
+
+/// Verify a password with the external hook, if one is configured.
+///
+/// Returns `None` with the local backend. On success the user is added to (or
+/// refreshed in) the user store, so sessions see the hook's attributes. The
+/// hook is called without holding the auth lock.
+pub async fn authenticate(
+    auth: &RwLock<AuthService>,
+    username: &str,
+    password: &str,
+    source: &SocketAddr,
+    protocol: &str,
+) -> Option<bool> {
+    let external = auth.read().await.external()?;
+    let request = ExternalAuthRequest {
+        username,
+        password,
+        source_ip: source.ip().to_string(),
+        protocol,
+    };
+    let response = match external.check(&request).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!(user = %username, error = %format!("{:#}", e), "External auth failed");
+            return Some(false);
+        }
+    };
+    if !response.allow {
+        debug!(user = %username, "External auth denied");
+        return Some(false);
+    }
+    let user = match external.user_for(username, &response) {
+        Ok(user) => user,
+        Err(e) => {
+            warn!(user = %username, error = %e, "External auth answer rejected");
+            return Some(false);
+        }
+    };
+    if !user.can_log_in() {
+        debug!(user = %username, "User account expired or disabled");
+        return Some(false);
+    }
+    auth.write().await.insert_external_user(user);
+    Some(true)
+}
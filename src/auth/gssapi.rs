@@ -0,0 +1,369 @@
+//! Kerberos logins over SSH (`[gssapi]`, feature `gssapi`): the acceptor
+//! side of `gssapi-with-mic` (RFC 4462) and the mapping of client
+//! principals to s5 users.
+//!
+//! The GSS-API library (MIT `libgssapi_krb5.so.2`) is loaded on first use
+//! rather than linked, so s5 builds without its headers and runs on hosts
+//! without it as long as `[gssapi]` is not set.
+
+use crate::config::types::GssapiConfig;
+use anyhow::{Context, Result};
+use regex::Regex;
+use russh::server::GssapiStep;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::ptr;
+use std::sync::OnceLock;
+use tracing::debug;
+
+/// Kerberos V5 mechanism (1.2.840.113554.1.2.2), DER-encoded as SSH carries it
+pub const KRB5_MECHANISM: &[u8] = &[
+    0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x12, 0x01, 0x02, 0x02,
+];
+
+const LIBRARY_NAME: &CStr = c"libgssapi_krb5.so.2";
+
+const GSS_S_COMPLETE: u32 = 0;
+const GSS_S_CONTINUE_NEEDED: u32 = 1;
+/// Calling and routine error bits of a major status
+const GSS_S_ERROR_MASK: u32 = 0xffff_0000;
+
+/// `gss_buffer_desc`
+#[repr(C)]
+struct Buffer {
+    length: usize,
+    value: *mut c_void,
+}
+
+impl Buffer {
+    fn empty() -> Self {
+        Self {
+            length: 0,
+            value: ptr::null_mut(),
+        }
+    }
+
+    /// An input buffer over `data`, which the library only reads
+    fn borrowed(data: &[u8]) -> Self {
+        Self {
+            length: data.len(),
+            value: data.as_ptr() as *mut c_void,
+        }
+    }
+}
+
+type Handle = *mut c_void;
+
+type AcceptSecContext = unsafe extern "C" fn(
+    *mut u32,
+    *mut Handle,
+    Handle,
+    *mut Buffer,
+    Handle,
+    *mut Handle,
+    *mut Handle,
+    *mut Buffer,
+    *mut u32,
+    *mut u32,
+    *mut Handle,
+) -> u32;
+type VerifyMic = unsafe extern "C" fn(*mut u32, Handle, *mut Buffer, *mut Buffer, *mut u32) -> u32;
+type DisplayName = unsafe extern "C" fn(*mut u32, Handle, *mut Buffer, *mut Handle) -> u32;
+type ReleaseBuffer = unsafe extern "C" fn(*mut u32, *mut Buffer) -> u32;
+type ReleaseName = unsafe extern "C" fn(*mut u32, *mut Handle) -> u32;
+type DeleteSecContext = unsafe extern "C" fn(*mut u32, *mut Handle, *mut Buffer) -> u32;
+type RegisterAcceptorIdentity = unsafe extern "C" fn(*const c_char) -> u32;
+
+/// The GSS-API calls s5 makes
+struct Library {
+    accept_sec_context: AcceptSecContext,
+    verify_mic: VerifyMic,
+    display_name: DisplayName,
+    release_buffer: ReleaseBuffer,
+    release_name: ReleaseName,
+    delete_sec_context: DeleteSecContext,
+    register_acceptor_identity: RegisterAcceptorIdentity,
+}
+
+static LIBRARY: OnceLock<Result<Library, String>> = OnceLock::new();
+
+/// The GSS-API library, loaded on the first call; never unloaded.
+fn library() -> Result<&'static Library> {
+    LIBRARY
+        .get_or_init(Library::load)
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+impl Library {
+    fn load() -> Result<Self, String> {
+        // SAFETY: dlerror returns a string owned by the loader, copied
+        // before the next dl call
+        let dl_error = || unsafe {
+            let error = libc::dlerror();
+            if error.is_null() {
+                "unknown error".to_string()
+            } else {
+                CStr::from_ptr(error).to_string_lossy().into_owned()
+            }
+        };
+        // SAFETY: a NUL-terminated name; loading runs the library's
+        // initializers, which MIT krb5 keeps free of side effects
+        let handle =
+            unsafe { libc::dlopen(LIBRARY_NAME.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(format!("loading {:?}: {}", LIBRARY_NAME, dl_error()));
+        }
+        let symbol = |name: &CStr| {
+            // SAFETY: `handle` stays open for the life of the process
+            let symbol = unsafe { libc::dlsym(handle, name.as_ptr()) };
+            if symbol.is_null() {
+                Err(format!("{:?}: no symbol {:?}", LIBRARY_NAME, name))
+            } else {
+                Ok(symbol)
+            }
+        };
+        // SAFETY: each symbol is cast to its prototype in gssapi.h
+        unsafe {
+            Ok(Self {
+                accept_sec_context: std::mem::transmute::<*mut c_void, AcceptSecContext>(symbol(
+                    c"gss_accept_sec_context",
+                )?),
+                verify_mic: std::mem::transmute::<*mut c_void, VerifyMic>(symbol(
+                    c"gss_verify_mic",
+                )?),
+                display_name: std::mem::transmute::<*mut c_void, DisplayName>(symbol(
+                    c"gss_display_name",
+                )?),
+                release_buffer: std::mem::transmute::<*mut c_void, ReleaseBuffer>(symbol(
+                    c"gss_release_buffer",
+                )?),
+                release_name: std::mem::transmute::<*mut c_void, ReleaseName>(symbol(
+                    c"gss_release_name",
+                )?),
+                delete_sec_context: std::mem::transmute::<*mut c_void, DeleteSecContext>(symbol(
+                    c"gss_delete_sec_context",
+                )?),
+                register_acceptor_identity: std::mem::transmute::<
+                    *mut c_void,
+                    RegisterAcceptorIdentity,
+                >(symbol(
+                    c"krb5_gss_register_acceptor_identity",
+                )?),
+            })
+        }
+    }
+
+    /// Copy out and release a buffer the library allocated; None when empty.
+    fn take_buffer(&self, buffer: &mut Buffer) -> Option<Vec<u8>> {
+        if buffer.value.is_null() {
+            return None;
+        }
+        // SAFETY: the library filled `buffer` with `length` bytes at `value`
+        let data = unsafe { std::slice::from_raw_parts(buffer.value as *const u8, buffer.length) }
+            .to_vec();
+        let mut minor = 0;
+        // SAFETY: released once, then never read again
+        unsafe { (self.release_buffer)(&mut minor, buffer) };
+        (!data.is_empty()).then_some(data)
+    }
+}
+
+/// Client principals mapped to s5 users: `principals` as they are, then the
+/// first of `rules` whose pattern matches the whole principal.
+#[derive(Debug)]
+pub struct PrincipalMap {
+    principals: HashMap<String, String>,
+    rules: Vec<(Regex, String)>,
+}
+
+impl PrincipalMap {
+    pub fn new(config: &GssapiConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Regex::new(&format!("^(?:{})$", rule.pattern))
+                    .with_context(|| format!("rules: invalid pattern '{}'", rule.pattern))
+                    .map(|pattern| (pattern, rule.user.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            principals: config.principals.clone(),
+            rules,
+        })
+    }
+
+    /// The user `principal` logs in as, or None when nothing maps it.
+    pub fn user(&self, principal: &str) -> Option<String> {
+        if let Some(user) = self.principals.get(principal) {
+            return Some(user.clone());
+        }
+        self.rules.iter().find_map(|(pattern, user)| {
+            let captures = pattern.captures(principal)?;
+            let mut expanded = String::new();
+            captures.expand(user, &mut expanded);
+            (!expanded.is_empty()).then_some(expanded)
+        })
+    }
+}
+
+/// `[gssapi]`, as the SSH server uses it
+#[derive(Debug)]
+pub struct Gssapi {
+    principals: PrincipalMap,
+    keytab: Option<CString>,
+}
+
+impl Gssapi {
+    pub fn new(config: &GssapiConfig) -> Result<Self> {
+        let keytab = config
+            .keytab
+            .as_ref()
+            .map(|path| CString::new(path.as_os_str().as_bytes()))
+            .transpose()
+            .context("gssapi.keytab")?;
+        Ok(Self {
+            principals: PrincipalMap::new(config)?,
+            keytab,
+        })
+    }
+
+    pub fn principals(&self) -> &PrincipalMap {
+        &self.principals
+    }
+
+    /// A security context for one `gssapi-with-mic` attempt, accepting
+    /// with the keys of `keytab`.
+    pub fn acceptor(&self) -> Result<Acceptor> {
+        let library = library()?;
+        if let Some(ref keytab) = self.keytab {
+            // SAFETY: the library copies the NUL-terminated path
+            let major = unsafe { (library.register_acceptor_identity)(keytab.as_ptr()) };
+            if major & GSS_S_ERROR_MASK != 0 {
+                anyhow::bail!("gssapi.keytab {:?} refused (major {:#x})", keytab, major);
+            }
+        }
+        Ok(Acceptor {
+            library,
+            context: ptr::null_mut(),
+            principal: None,
+        })
+    }
+}
+
+/// The acceptor side of one GSS-API security context.
+pub struct Acceptor {
+    library: &'static Library,
+    context: Handle,
+    /// Client principal, once the context is established
+    principal: Option<String>,
+}
+
+// SAFETY: the context is owned by one SSH connection and only used through
+// `&mut self`, one call at a time; GSS-API contexts are not tied to a thread
+unsafe impl Send for Acceptor {}
+// SAFETY: `&self` only reads `principal`
+unsafe impl Sync for Acceptor {}
+
+impl Acceptor {
+    /// Process a context token of the client.
+    pub fn accept(&mut self, token: &[u8]) -> GssapiStep {
+        let (mut minor, mut name) = (0, ptr::null_mut());
+        let mut input = Buffer::borrowed(token);
+        let mut output = Buffer::empty();
+        // SAFETY: `input` borrows `token` for the call; the library fills
+        // `output` and `name`, released below, and updates `context`
+        let major = unsafe {
+            (self.library.accept_sec_context)(
+                &mut minor,
+                &mut self.context,
+                ptr::null_mut(),
+                &mut input,
+                ptr::null_mut(),
+                &mut name,
+                ptr::null_mut(),
+                &mut output,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        let reply = self.library.take_buffer(&mut output);
+        if !name.is_null() {
+            if major == GSS_S_COMPLETE {
+                self.principal = self.display_name(name);
+            }
+            // SAFETY: the name was allocated by the call above
+            unsafe { (self.library.release_name)(&mut minor, &mut name) };
+        }
+        if major & GSS_S_ERROR_MASK != 0 {
+            debug!(major = %format!("{:#x}", major), minor, "GSS-API context refused");
+            return GssapiStep::Reject(reply);
+        }
+        if major & GSS_S_CONTINUE_NEEDED != 0 {
+            return GssapiStep::Continue(reply.unwrap_or_default());
+        }
+        if self.principal.is_none() {
+            return GssapiStep::Reject(reply);
+        }
+        GssapiStep::Established(reply)
+    }
+
+    /// Check the client's MIC over `data` with the established context.
+    pub fn verify_mic(&mut self, data: &[u8], mic: &[u8]) -> bool {
+        if self.principal.is_none() {
+            return false;
+        }
+        let mut minor = 0;
+        let mut message = Buffer::borrowed(data);
+        let mut token = Buffer::borrowed(mic);
+        // SAFETY: both buffers borrow slices that outlive the call
+        let major = unsafe {
+            (self.library.verify_mic)(
+                &mut minor,
+                self.context,
+                &mut message,
+                &mut token,
+                ptr::null_mut(),
+            )
+        };
+        if major != GSS_S_COMPLETE {
+            debug!(major = %format!("{:#x}", major), minor, "GSS-API MIC refused");
+        }
+        major == GSS_S_COMPLETE
+    }
+
+    /// The client principal (`alice@EXAMPLE.COM`), once the context is
+    /// established.
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    fn display_name(&self, name: Handle) -> Option<String> {
+        let mut minor = 0;
+        let mut output = Buffer::empty();
+        // SAFETY: `name` is a live name; `output` is released by take_buffer
+        let major =
+            unsafe { (self.library.display_name)(&mut minor, name, &mut output, ptr::null_mut()) };
+        if major != GSS_S_COMPLETE {
+            return None;
+        }
+        let name = self.library.take_buffer(&mut output)?;
+        let name = String::from_utf8(name).ok()?;
+        Some(name.trim_end_matches('\0').to_string())
+    }
+}
+
+impl Drop for Acceptor {
+    fn drop(&mut self) {
+        if !self.context.is_null() {
+            let mut minor = 0;
+            // SAFETY: deleted once; no output token is asked for
+            unsafe {
+                (self.library.delete_sec_context)(&mut minor, &mut self.context, ptr::null_mut())
+            };
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Single-use invitation links for onboarding users (`[invitations]`).
+//!
+//! An admin invites a username with `POST /api/invitations` and hands the
+//! returned link to the new user. Opening it (`/dashboard/invite#<token>`)
+//! lets them choose a password and optionally add a public key; only then is
+//! the `[[users]]` entry written to the config file. A link works once and
+//! expires after `ttl_hours`.
+//!
+//! Only the SHA-256 of each token is kept. Pending invitations live in
+//! memory and are dropped on restart.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+
+/// Longest username an invitation may create.
+pub const MAX_USERNAME_LENGTH: usize = 64;
+
+/// One entry of `GET /api/invitations`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Invitation {
+    pub id: String,
+    pub username: String,
+    /// `[[groups]]` entry the account is created in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// API principal that created the invitation
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Why [`InvitationStore::create`] refused an invitation.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum InvitationError {
+    #[error("username must be 1-64 letters, digits, '.', '_', '-' or '@'")]
+    InvalidUsername,
+    #[error("user '{0}' already has a pending invitation")]
+    AlreadyInvited(String),
+    #[error("too many pending invitations (max {0})")]
+    TooMany(usize),
+}
+
+struct Entry {
+    invitation: Invitation,
+    token_sha256: [u8; 32],
+}
+
+/// Invitations created but not used yet.
+#[derive(Default)]
+pub struct InvitationStore {
+    entries: Mutex<Vec<Entry>>,
+}
+
+/// True if an invitation may create a user named `username`.
+pub fn valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= MAX_USERNAME_LENGTH
+        && !username.starts_with(['-', '.', '@'])
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@'))
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+impl InvitationStore {
+    /// Record an invitation for `username`, valid for `ttl`. Returns it with
+    /// the secret token, which is not kept and cannot be shown again.
+    pub fn create(
+        &self,
+        username: &str,
+        group: Option<String>,
+        created_by: &str,
+        ttl: Duration,
+        max_pending: usize,
+    ) -> Result<(Invitation, String), InvitationError> {
+        if !valid_username(username) {
+            return Err(InvitationError::InvalidUsername);
+        }
+        let now = Utc::now();
+        let mut entries = self.lock(now);
+        if entries.iter().any(|e| e.invitation.username == username) {
+            return Err(InvitationError::AlreadyInvited(username.to_string()));
+        }
+        if entries.len() >= max_pending {
+            return Err(InvitationError::TooMany(max_pending));
+        }
+
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        let invitation = Invitation {
+            id: crate::utils::generate_correlation_id(),
+            username: username.to_string(),
+            group,
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at: now + ttl,
+        };
+        entries.push(Entry {
+            invitation: invitation.clone(),
+            token_sha256: digest(&token),
+        });
+        Ok((invitation, token))
+    }
+
+    /// All invitations not yet used or expired, oldest first.
+    pub fn list(&self) -> Vec<Invitation> {
+        self.lock(Utc::now())
+            .iter()
+            .map(|e| e.invitation.clone())
+            .collect()
+    }
+
+    /// Withdraw an invitation. Returns it, or None if `id` is unknown.
+    pub fn revoke(&self, id: &str) -> Option<Invitation> {
+        let mut entries = self.lock(Utc::now());
+        let index = entries.iter().position(|e| e.invitation.id == id)?;
+        Some(entries.remove(index).invitation)
+    }
+
+    /// The pending invitation `token` opens, if any.
+    pub fn find(&self, token: &str) -> Option<Invitation> {
+        let wanted = digest(token);
+        self.lock(Utc::now())
+            .iter()
+            .find(|e| bool::from(e.token_sha256.ct_eq(&wanted)))
+            .map(|e| e.invitation.clone())
+    }
+
+    /// Use up the invitation `token` opens. Returns None if it is unknown,
+    /// expired or already used.
+    pub fn redeem(&self, token: &str) -> Option<Invitation> {
+        let wanted = digest(token);
+        let mut entries = self.lock(Utc::now());
+        let index = entries
+            .iter()
+            .position(|e| bool::from(e.token_sha256.ct_eq(&wanted)))?;
+        Some(entries.remove(index).invitation)
+    }
+
+    /// Lock the store with expired invitations removed.
+    fn lock(&self, now: DateTime<Utc>) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|e| e.invitation.expires_at > now);
+        entries
+    }
+}
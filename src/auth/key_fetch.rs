@@ -0,0 +1,203 @@
+//! Public keys published at a user's `authorized_keys_url` (for example
+//! `https://github.com/<user>.keys` or an IdP endpoint), so keys are rotated
+//! upstream instead of in the config file.
+//!
+//! The URL is fetched when the user signs in with a key that is not in
+//! their configured `authorized_keys`, and the parsed keys are cached per
+//! URL for `refresh_secs`. Later fetches send the cached `ETag` in
+//! `If-None-Match`; `304 Not Modified` keeps the cached keys. While the URL
+//! is unreachable, cached keys keep working for `max_stale_secs` after the
+//! last successful fetch. The cache lives in memory.
+//!
+//! Lines may carry `expiry-time` (see [`pubkey::key_options`]); expired keys
+//! are dropped when the list is fetched.
+
+use super::pubkey;
+use crate::config::types::AuthorizedKeysFetchConfig;
+use anyhow::{Context, Result};
+use russh::keys::PublicKey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Largest key list accepted.
+pub const MAX_RESPONSE_BYTES: usize = 256 * 1024;
+
+/// Keys kept from one URL; further lines are ignored.
+pub const MAX_KEYS: usize = 100;
+
+struct CachedKeys {
+    keys: Arc<Vec<PublicKey>>,
+    etag: Option<String>,
+    /// Last 200 or 304 answer
+    fetched_at: Instant,
+    /// Last request, answered or not
+    checked_at: Instant,
+}
+
+enum Fetched {
+    NotModified,
+    Keys(Vec<PublicKey>, Option<String>),
+}
+
+/// Fetches and caches `authorized_keys_url` key lists.
+pub struct KeyFetcher {
+    config: AuthorizedKeysFetchConfig,
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, CachedKeys>>,
+}
+
+impl std::fmt::Debug for KeyFetcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyFetcher")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parse an authorized_keys document. Blank lines, comments, lines that do
+/// not parse and keys past their `expiry-time` are skipped; at most
+/// [`MAX_KEYS`] keys are kept.
+pub fn parse_keys(body: &str) -> Vec<PublicKey> {
+    let now = chrono::Utc::now();
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|line| {
+            pubkey::key_options(line).is_ok_and(|o| o.expires_at.is_none_or(|t| t > now))
+        })
+        .filter_map(|line| pubkey::parse_authorized_key(line).ok())
+        .take(MAX_KEYS)
+        .collect()
+}
+
+/// The URL without its query string, for logs (it may carry a token).
+fn redacted(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+impl KeyFetcher {
+    pub fn new(config: &AuthorizedKeysFetchConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(config.timeout_secs))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            config: config.clone(),
+            client,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &AuthorizedKeysFetchConfig {
+        &self.config
+    }
+
+    /// True if `key` is among the keys published at `url`.
+    pub async fn authorizes(&self, url: &str, key: &PublicKey) -> bool {
+        self.keys(url).await.iter().any(|k| k == key)
+    }
+
+    /// Keys published at `url`: cached while fresh, otherwise fetched again.
+    /// Empty when the URL fails and no usable cached copy is left.
+    pub async fn keys(&self, url: &str) -> Arc<Vec<PublicKey>> {
+        let now = Instant::now();
+        let cached = self
+            .lock()
+            .get(url)
+            .map(|c| (c.keys.clone(), c.etag.clone(), c.fetched_at, c.checked_at));
+        let refresh = Duration::from_secs(self.config.refresh_secs);
+        if let Some((ref keys, _, _, checked_at)) = cached {
+            if now.duration_since(checked_at) < refresh {
+                return keys.clone();
+            }
+        }
+
+        let etag = cached.as_ref().and_then(|(_, etag, _, _)| etag.clone());
+        match self.fetch(url, etag.as_deref()).await {
+            Ok(Fetched::Keys(keys, etag)) => {
+                debug!(url = %redacted(url), keys = keys.len(), "Fetched authorized keys");
+                let keys = Arc::new(keys);
+                self.lock().insert(
+                    url.to_string(),
+                    CachedKeys {
+                        keys: keys.clone(),
+                        etag,
+                        fetched_at: now,
+                        checked_at: now,
+                    },
+                );
+                keys
+            }
+            Ok(Fetched::NotModified) => {
+                if let Some(entry) = self.lock().get_mut(url) {
+                    entry.fetched_at = now;
+                    entry.checked_at = now;
+                }
+                cached.map(|(keys, ..)| keys).unwrap_or_default()
+            }
+            Err(e) => {
+                let Some((keys, _, fetched_at, _)) = cached else {
+                    warn!(
+                        url = %redacted(url),
+                        error = %format!("{:#}", e),
+                        "Failed to fetch authorized keys"
+                    );
+                    return Arc::default();
+                };
+                let stale = now.duration_since(fetched_at)
+                    >= Duration::from_secs(self.config.max_stale_secs);
+                warn!(
+                    url = %redacted(url),
+                    error = %format!("{:#}", e),
+                    stale,
+                    "Failed to refresh authorized keys"
+                );
+                if let Some(entry) = self.lock().get_mut(url) {
+                    entry.checked_at = now;
+                }
+                if stale {
+                    Arc::default()
+                } else {
+                    keys
+                }
+            }
+        }
+    }
+
+    async fn fetch(&self, url: &str, etag: Option<&str>) -> Result<Fetched> {
+        let mut request = self.client.get(url);
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let mut response = request.send().await.context("request failed")?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_MODIFIED && etag.is_some() {
+            return Ok(Fetched::NotModified);
+        }
+        if !status.is_success() {
+            anyhow::bail!("HTTP {}", status);
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.context("reading response")? {
+            if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+                anyhow::bail!("key list exceeds {} bytes", MAX_RESPONSE_BYTES);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8(body).context("key list is not UTF-8")?;
+        Ok(Fetched::Keys(parse_keys(&body), etag))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CachedKeys>> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
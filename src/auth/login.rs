@@ -0,0 +1,370 @@
+//! Password logins shared by SSH (`password` and `keyboard-interactive`),
+//! SOCKS5 and MASQUE: the credentials (external hook, or the stored hash
+//! and TOTP), then lockout, impossible travel and the `on_auth` hook, and
+//! the records of a successful login. Proxy logins then go through
+//! [`admit_proxy_login`]: source IPs, access hours, client caps, rate
+//! limits and quotas.
+
+use crate::audit::events::AuditEvent;
+use crate::auth::password::extract_totp_from_password;
+use crate::auth::user::User;
+use crate::context::AppContext;
+use crate::proxy::client_caps::UserSlot;
+use crate::reports::Login;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// One password login attempt.
+pub struct PasswordAttempt<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    /// Code answered to its own prompt; otherwise, for users with TOTP, the
+    /// code is taken from the end of the password
+    pub totp_code: Option<String>,
+    pub peer_addr: &'a SocketAddr,
+    /// `"ssh"`, `"socks5"` or `"masque"`: the `totp_required_for` entry and
+    /// the protocol of the lockout, travel and session records
+    pub protocol: &'a str,
+    /// Method of the `auth.success` event: the SSH method, or the protocol
+    /// for SOCKS5 and MASQUE
+    pub audit_method: &'a str,
+    /// Method of the session, `+totp` appended when a code was checked
+    pub method: &'a str,
+    /// HASSH of an SSH client, checked against the user's `allowed_hassh`
+    pub hassh: Option<String>,
+    pub conn_id: &'a str,
+}
+
+/// How a password login ended.
+pub enum PasswordOutcome {
+    /// Logged in and recorded as such
+    Accepted {
+        /// The user as read along with the credentials
+        user: Option<Arc<User>>,
+        /// Session method (`password`, `password+totp`, ...)
+        method: String,
+    },
+    /// Refused; the caller records the failure
+    Rejected {
+        /// A TOTP code was checked after a matching password
+        with_totp: bool,
+    },
+}
+
+/// Check a password login and, when it goes through, record it: audit
+/// events, metrics, lockout reset, login anomalies and the upgrade of an
+/// outdated hash. Bans, decoys and tenants are checked by the caller
+/// before, failures recorded by the caller after.
+pub async fn authenticate_password(
+    ctx: &AppContext,
+    attempt: &PasswordAttempt<'_>,
+) -> PasswordOutcome {
+    let username = attempt.username;
+    let totp_required = ctx
+        .config
+        .security
+        .totp_required_for
+        .iter()
+        .any(|p| p == attempt.protocol);
+
+    // External backend: the hook decides (and handles any second factor itself)
+    let verify_start = Instant::now();
+    let external = crate::auth::external::authenticate(
+        &ctx.auth_service,
+        username,
+        attempt.password,
+        attempt.peer_addr,
+        attempt.protocol,
+    )
+    .await;
+
+    // Single auth_service read for the TOTP check, password and user lookup;
+    // the verified password is kept when its stored hash is to be upgraded
+    let (verified, with_totp, user, rehash_password) = {
+        let auth = ctx.auth_service.read().await;
+        let user = auth.user_store().get(username).cloned();
+        if let Some(ok) = external {
+            (ok, false, user, None)
+        } else {
+            let has_totp = totp_required
+                && user
+                    .as_ref()
+                    .is_some_and(|u| u.totp_enabled && u.totp_secret.is_some());
+            let (password, totp_code) = match &attempt.totp_code {
+                Some(code) => (attempt.password.to_string(), Some(code.clone())),
+                None if has_totp => extract_totp_from_password(attempt.password),
+                None => (attempt.password.to_string(), None),
+            };
+            let password_ok = auth.auth_password(username, &password);
+            // A user with TOTP needs a code, which fails the login when missing
+            let verified = password_ok
+                && (!has_totp
+                    || totp_code
+                        .as_ref()
+                        .is_some_and(|code| auth.verify_totp(username, code)));
+            let rehash_password = (verified && auth.needs_rehash(username)).then_some(password);
+            (verified, has_totp && password_ok, user, rehash_password)
+        }
+    };
+    if attempt.protocol == "ssh" {
+        let outcome = if verified {
+            crate::metrics::outcomes::SUCCESS
+        } else {
+            crate::metrics::outcomes::FAILURE
+        };
+        ctx.metrics.record_ssh_auth(
+            attempt.method,
+            outcome,
+            verify_start.elapsed().as_secs_f64(),
+        );
+    }
+
+    let allowed =
+        verified && (attempt.protocol != "ssh" || is_hassh_allowed(user.as_deref(), attempt));
+    let allowed = allowed
+        && !ctx
+            .is_account_locked(username, attempt.peer_addr, attempt.conn_id)
+            .await;
+    let allowed = allowed
+        && ctx
+            .check_impossible_travel(
+                username,
+                attempt.peer_addr,
+                attempt.protocol,
+                attempt.conn_id,
+            )
+            .await;
+    let success = AuditEvent::auth_success_with_cid(
+        username,
+        attempt.peer_addr,
+        attempt.audit_method,
+        attempt.conn_id,
+    )
+    .with_hassh(attempt.hassh.clone());
+    let success = if allowed {
+        ctx.run_auth_hook(success).await
+    } else {
+        None
+    };
+    let Some(success) = success else {
+        return PasswordOutcome::Rejected { with_totp };
+    };
+
+    let method = if with_totp {
+        format!("{}+totp", attempt.method)
+    } else {
+        attempt.method.to_string()
+    };
+    info!(
+        conn_id = %attempt.conn_id,
+        user = %username,
+        ip = %attempt.peer_addr,
+        protocol = %attempt.protocol,
+        method = %method,
+        "Password auth success"
+    );
+    ctx.audit.log_event(success);
+    ctx.audit.log_session_authenticated_cid(
+        username,
+        attempt.peer_addr,
+        attempt.protocol,
+        &method,
+        attempt.conn_id,
+    );
+    ctx.metrics.record_auth_success(username, &method);
+    ctx.security
+        .read()
+        .await
+        .account_lockout()
+        .record_success(username);
+    ctx.check_login_anomaly(
+        username,
+        attempt.peer_addr,
+        attempt.protocol,
+        attempt.conn_id,
+    )
+    .await;
+    if let Some(password) = rehash_password {
+        ctx.spawn_password_rehash(username, &password);
+    }
+    PasswordOutcome::Accepted { user, method }
+}
+
+/// Check the SSH client against the user's `allowed_hassh` pins, after the
+/// credentials were accepted: a mismatch points at credentials reused from
+/// another SSH client.
+fn is_hassh_allowed(user: Option<&User>, attempt: &PasswordAttempt<'_>) -> bool {
+    let allowed = user.is_none_or(|u| u.is_hassh_allowed(attempt.hassh.as_deref()));
+    if !allowed {
+        warn!(
+            conn_id = %attempt.conn_id,
+            user = %attempt.username,
+            ip = %attempt.peer_addr,
+            hassh = attempt.hassh.as_deref().unwrap_or("-"),
+            "Valid credentials from an SSH client not in the user's allowed_hassh"
+        );
+    }
+    allowed
+}
+
+/// Record a refused SOCKS5 or MASQUE login: audit event, metrics, and the
+/// failure counts behind auto-ban and account lockout.
+pub async fn record_password_failure(
+    ctx: &AppContext,
+    attempt: &PasswordAttempt<'_>,
+    with_totp: bool,
+) {
+    warn!(
+        conn_id = %attempt.conn_id,
+        user = %attempt.username,
+        ip = %attempt.peer_addr,
+        protocol = %attempt.protocol,
+        "Password auth failed"
+    );
+    ctx.record_login_failure(
+        attempt.username,
+        attempt.peer_addr,
+        attempt.protocol,
+        attempt.conn_id,
+    )
+    .await;
+    ctx.record_account_failure(
+        attempt.username,
+        attempt.peer_addr,
+        attempt.protocol,
+        attempt.conn_id,
+    )
+    .await;
+    let audit_method = if with_totp {
+        format!("{}+totp", attempt.audit_method)
+    } else {
+        attempt.audit_method.to_string()
+    };
+    ctx.audit
+        .log_auth_failure_cid(
+            attempt.username,
+            attempt.peer_addr,
+            &audit_method,
+            attempt.conn_id,
+        )
+        .await;
+    ctx.metrics
+        .record_auth_failure(if with_totp { "totp" } else { "password" });
+    ctx.metrics
+        .record_error(crate::metrics::error_types::AUTH_FAILURE);
+    ctx.metrics.record_connection_rejected("auth_failed");
+}
+
+/// What an admitted proxy login holds until its tunnel closes.
+pub struct Admission {
+    pub user_slot: UserSlot,
+    /// Counted in the usage reports when dropped
+    pub login: Login,
+}
+
+/// Why an authenticated proxy login was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// Source IP outside the user's `source_ips`
+    SourceIp,
+    /// Outside the user's access hours or days
+    AccessHours,
+    /// `server.max_connections_per_user` reached
+    ClientCap,
+    /// Per-minute or multi-window connection rate exceeded
+    RateLimited,
+    /// Connection or bandwidth quota used up
+    Quota,
+    /// The user may not open tunnels
+    Forwarding,
+}
+
+/// Checks of an authenticated SOCKS5 or MASQUE login before its tunnel is
+/// opened: source IPs, access hours, the per-user client cap, rate limits,
+/// quotas (the connection is counted) and `allow_forwarding`. Refusals are
+/// logged, audited and counted here.
+pub async fn admit_proxy_login(
+    ctx: &AppContext,
+    user: &User,
+    peer_addr: &SocketAddr,
+    protocol: &str,
+    conn_id: &str,
+) -> Result<Admission, Refusal> {
+    let username = user.username.as_str();
+    if !user.is_source_ip_allowed(&peer_addr.ip()) {
+        warn!(conn_id = %conn_id, user = %username, ip = %peer_addr.ip(), protocol = %protocol, "Login from IP not in user's allowed source_ips");
+        return Err(Refusal::SourceIp);
+    }
+
+    // Time-based access check
+    if !user.check_time_access() {
+        warn!(conn_id = %conn_id, user = %username, ip = %peer_addr.ip(), protocol = %protocol, "Login denied: outside allowed access hours/days");
+        ctx.metrics.record_connection_rejected("time_access_denied");
+        return Err(Refusal::AccessHours);
+    }
+
+    // Per-user client cap (server.max_connections_per_user)
+    let user_slot = match ctx.proxy_engine.client_caps().try_acquire_user(username) {
+        Ok(slot) => slot,
+        Err(rejection) => {
+            warn!(conn_id = %conn_id, user = %username, protocol = %protocol, "Per-user client cap reached");
+            ctx.metrics
+                .record_connection_rejected(rejection.metric_reason());
+            return Err(Refusal::ClientCap);
+        }
+    };
+    let login = ctx.proxy_engine.usage_history().start_login(username);
+
+    if !ctx
+        .security
+        .read()
+        .await
+        .check_rate_limit(username, user.max_new_connections_per_minute)
+    {
+        warn!(conn_id = %conn_id, user = %username, protocol = %protocol, limit = user.max_new_connections_per_minute, "Rate limit exceeded (legacy)");
+        ctx.audit
+            .log_rate_limit_exceeded_cid(username, peer_addr, "legacy_per_minute", conn_id);
+        ctx.metrics.record_connection_rejected("rate_limited");
+        return Err(Refusal::RateLimited);
+    }
+
+    // Multi-window rate limiting (QuotaTracker)
+    if let Err(reason) =
+        ctx.quota_tracker
+            .check_connection_rate(username, &user.rate_limits, &ctx.config.limits)
+    {
+        warn!(conn_id = %conn_id, user = %username, protocol = %protocol, reason = %reason, "Quota rate limit exceeded");
+        ctx.audit
+            .log_rate_limit_exceeded_cid(username, peer_addr, &reason, conn_id);
+        ctx.metrics.record_connection_rejected("rate_limited");
+        return Err(Refusal::RateLimited);
+    }
+
+    // Count the connection (daily/monthly quotas), then check that the
+    // bandwidth quotas are not already used up
+    let quota = ctx
+        .quota_tracker
+        .record_connection(username, user.quotas.as_ref())
+        .and_then(|()| {
+            ctx.quota_tracker
+                .check_bandwidth_quota(username, user.quotas.as_ref())
+        });
+    if let Err(reason) = quota {
+        warn!(conn_id = %conn_id, user = %username, protocol = %protocol, reason = %reason, "Quota exceeded");
+        ctx.audit
+            .log_quota_exceeded_cid(username, &reason, 0, 0, conn_id);
+        ctx.metrics
+            .record_error(crate::metrics::error_types::QUOTA_EXCEEDED);
+        ctx.metrics.record_connection_rejected("quota_exceeded");
+        return Err(Refusal::Quota);
+    }
+
+    if !user.allow_forwarding {
+        warn!(conn_id = %conn_id, user = %username, protocol = %protocol, "Forwarding denied");
+        return Err(Refusal::Forwarding);
+    }
+
+    Ok(Admission { user_slot, login })
+}
@@ -1,14 +1,34 @@
 pub mod certificate;
+pub mod external;
+#[cfg(feature = "gssapi")]
+pub mod gssapi;
+#[cfg(all(feature = "gssapi", not(target_os = "linux")))]
+compile_error!("the `gssapi` feature is only supported on Linux");
+pub mod invitation;
+pub mod key_fetch;
+pub mod login;
 pub mod password;
+pub mod password_policy;
+pub mod personal_token;
 pub mod pubkey;
+pub mod rehash;
+pub mod self_service;
+pub mod tenant;
 pub mod user;
 
-use crate::config::types::AppConfig;
+use crate::config::types::{
+    AppConfig, GroupConfig, InvitationConfig, PasswordPolicyConfig, PersonalTokenConfig,
+    QuotaPlanConfig,
+};
 use anyhow::Result;
 use certificate::TrustedCa;
 use dashmap::DashMap;
+use external::ExternalAuth;
+use key_fetch::KeyFetcher;
+use password::HashSettings;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::Arc;
-use user::UserStore;
+use user::{User, UserStore};
 
 /// TOTP replay protection: tracks (username, code) -> expiry_timestamp.
 /// Prevents the same TOTP code from being reused within its validity window.
@@ -23,26 +43,50 @@ fn used_totp_codes() -> &'static DashMap<(String, String), u64> {
 const MAX_TOTP_CODES: usize = 10_000;
 
 /// Dummy hash for timing-safe user enumeration prevention (M-1)
-const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$AAAAAAAAAAAAAAAAAAAAAA$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+pub(crate) const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$AAAAAAAAAAAAAAAAAAAAAA$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
 
 /// Central authentication service
 #[derive(Debug)]
 pub struct AuthService {
     user_store: Arc<UserStore>,
+    /// `[[groups]]` as configured, before inheritance
+    groups: Vec<GroupConfig>,
+    /// `[quota_plans]`, for the reset scheduler
+    quota_plans: BTreeMap<String, QuotaPlanConfig>,
     /// Pre-parsed trusted CA keys for SSH certificate authentication
     trusted_cas: Arc<Vec<TrustedCa>>,
+    /// Password hook when `auth_backend = "external"`
+    external: Option<Arc<ExternalAuth>>,
+    /// Users added to the store by the external hook
+    external_users: HashSet<String>,
+    /// Rules for new passwords (`[security.password_policy]`)
+    password_policy: PasswordPolicyConfig,
+    /// How new password hashes are made
+    hash_settings: HashSettings,
+    /// `security.rehash_on_login`
+    rehash_on_login: bool,
+    /// `[invitations]`
+    invitations: InvitationConfig,
+    /// `authorized_keys_url` lists, cached across reloads
+    key_fetcher: Arc<KeyFetcher>,
+    /// `[gssapi]`, for `gssapi-with-mic` logins
+    #[cfg(feature = "gssapi")]
+    gssapi: Option<Arc<gssapi::Gssapi>>,
 }
 
 impl AuthService {
     pub fn new(config: &AppConfig) -> Result<Self> {
-        let user_store = Arc::new(UserStore::from_config(
-            &config.users,
-            &config.groups,
-            &config.acl,
-            &config.limits,
-            &config.server,
-            &config.shell,
-        )?);
+        let user_store = Arc::new(
+            UserStore::from_config(
+                &config.users,
+                &config.groups,
+                &config.acl,
+                &config.limits,
+                &config.server,
+                &config.shell,
+            )?
+            .with_quota_plans(&config.quota_plans),
+        );
         let trusted_cas = Arc::new(certificate::parse_trusted_ca_keys(
             &config.security.trusted_user_ca_keys,
         ));
@@ -54,7 +98,18 @@ impl AuthService {
         }
         Ok(Self {
             user_store,
+            groups: config.groups.clone(),
+            quota_plans: config.quota_plans.clone(),
             trusted_cas,
+            external: ExternalAuth::new(config).map(Arc::new),
+            external_users: HashSet::new(),
+            password_policy: config.security.password_policy.clone(),
+            hash_settings: HashSettings::from_config(&config.security),
+            rehash_on_login: config.security.rehash_on_login,
+            invitations: config.invitations.clone(),
+            key_fetcher: Arc::new(KeyFetcher::new(&config.authorized_keys_fetch)),
+            #[cfg(feature = "gssapi")]
+            gssapi: gssapi_from_config(config)?,
         })
     }
 
@@ -62,11 +117,130 @@ impl AuthService {
         &self.user_store
     }
 
+    /// Groups as configured (`[[groups]]`), without their `inherits` chain
+    /// applied
+    pub fn groups(&self) -> &[GroupConfig] {
+        &self.groups
+    }
+
+    /// Groups listing `username` in `managers`, in config order
+    pub fn managed_groups(&self, username: &str) -> Vec<&GroupConfig> {
+        self.groups
+            .iter()
+            .filter(|g| g.managers.iter().any(|m| m == username))
+            .collect()
+    }
+
+    /// True if `manager` manages the group `username` is a member of
+    pub fn manages(&self, manager: &str, username: &str) -> bool {
+        let Some(group) = self
+            .user_store
+            .get(username)
+            .and_then(|u| u.group.as_deref())
+        else {
+            return false;
+        };
+        self.managed_groups(manager).iter().any(|g| g.name == group)
+    }
+
+    /// Named quota plans (`[quota_plans]`)
+    pub fn quota_plans(&self) -> &BTreeMap<String, QuotaPlanConfig> {
+        &self.quota_plans
+    }
+
     /// Get trusted CA keys (for certificate authentication)
     pub fn trusted_cas(&self) -> &[TrustedCa] {
         &self.trusted_cas
     }
 
+    /// Rules for new passwords and password age
+    pub fn password_policy(&self) -> &PasswordPolicyConfig {
+        &self.password_policy
+    }
+
+    /// Onboarding link settings (`[invitations]`)
+    pub fn invitations(&self) -> &InvitationConfig {
+        &self.invitations
+    }
+
+    /// Configured keys whose `expiry-time` falls before `now + within`,
+    /// expired ones included, soonest first
+    pub fn expiring_keys(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+        within: chrono::Duration,
+    ) -> Vec<pubkey::ExpiringKey> {
+        let mut keys: Vec<pubkey::ExpiringKey> = self
+            .user_store
+            .usernames()
+            .iter()
+            .filter_map(|name| self.user_store.get(name))
+            .flat_map(|user| {
+                user.dated_keys.iter().filter_map(|d| {
+                    let expires_at = d.expires_at.filter(|t| *t <= now + within)?;
+                    Some(pubkey::ExpiringKey {
+                        username: user.username.clone(),
+                        fingerprint: crate::security::key_enrollment::key_fingerprint(&d.key),
+                        comment: d.comment.clone(),
+                        created_at: d.created_at,
+                        expires_at,
+                        expired: expires_at <= now,
+                    })
+                })
+            })
+            .collect();
+        keys.sort_by(|a, b| {
+            a.expires_at
+                .cmp(&b.expires_at)
+                .then_with(|| a.username.cmp(&b.username))
+        });
+        keys
+    }
+
+    /// `username`'s `authorized_keys_url` with the fetcher to check it, for
+    /// use once the auth service lock is released
+    pub fn remote_keys(&self, username: &str) -> Option<(Arc<KeyFetcher>, String)> {
+        let user = self.user_store.get(username)?;
+        if !user.can_log_in() {
+            return None;
+        }
+        let url = user.authorized_keys_url.clone()?;
+        Some((self.key_fetcher.clone(), url))
+    }
+
+    /// How new password hashes are made
+    pub fn hash_settings(&self) -> HashSettings {
+        self.hash_settings
+    }
+
+    /// Whether `username`'s stored hash should be upgraded after a
+    /// successful password login (`security.rehash_on_login`).
+    pub fn needs_rehash(&self, username: &str) -> bool {
+        self.rehash_on_login
+            && self
+                .user_store
+                .get(username)
+                .and_then(|u| u.password_hash.as_deref())
+                .is_some_and(|hash| self.hash_settings.needs_rehash(hash))
+    }
+
+    /// External password hook, if `auth_backend = "external"`
+    pub fn external(&self) -> Option<Arc<ExternalAuth>> {
+        self.external.clone()
+    }
+
+    /// `[gssapi]`, when Kerberos logins are on
+    #[cfg(feature = "gssapi")]
+    pub fn gssapi(&self) -> Option<Arc<gssapi::Gssapi>> {
+        self.gssapi.clone()
+    }
+
+    /// Add or refresh a user allowed by the external hook.
+    pub fn insert_external_user(&mut self, user: User) {
+        self.external_users.insert(user.username.clone());
+        self.user_store = Arc::new(self.user_store.with_user(user));
+    }
+
     /// Authenticate with password
     pub fn auth_password(&self, username: &str, password: &str) -> bool {
         let user = match self.user_store.get(username) {
@@ -89,8 +263,8 @@ impl AuthService {
             }
         };
 
-        if user.is_expired() {
-            tracing::debug!(username = %username, "User account expired");
+        if !user.can_log_in() {
+            tracing::debug!(username = %username, "User account expired or disabled");
             return false;
         }
 
@@ -103,6 +277,30 @@ impl AuthService {
         password_valid
     }
 
+    /// Authenticate a personal API token (self-service endpoints).
+    /// Same timing on unknown users, users without a token, and bad tokens.
+    pub fn auth_api_token(&self, username: &str, token: &str) -> bool {
+        let hash = self
+            .user_store
+            .get(username)
+            .filter(|u| u.can_log_in())
+            .and_then(|u| u.api_token_hash.clone());
+        match hash {
+            Some(hash) => password::verify_password(token, &hash),
+            None => {
+                let _ = password::verify_password(token, DUMMY_HASH);
+                false
+            }
+        }
+    }
+
+    /// Authenticate a read-only personal token (`api_tokens`), returning its ID.
+    pub fn auth_read_token(&self, username: &str, token: &str) -> Option<String> {
+        let user = self.user_store.get(username).filter(|u| u.can_log_in());
+        let tokens = user.map(|u| u.api_tokens.as_slice()).unwrap_or_default();
+        personal_token::verify(tokens, token).map(|t| t.id.clone())
+    }
+
     /// Verify TOTP code for a user. Returns true if:
     /// - User doesn't have TOTP enabled (skip check)
     /// - TOTP code is valid
@@ -186,12 +384,28 @@ impl AuthService {
             None => return false,
         };
 
-        if user.is_expired() {
+        if !user.can_log_in() {
+            return false;
+        }
+        if !user.accepts_key_type(key.algorithm().as_str()) {
+            tracing::warn!(
+                user = %username,
+                key_type = %key.algorithm(),
+                "Public key refused: require_security_key is set"
+            );
             return false;
         }
 
         // Phase 1: Check individual authorized keys
         if pubkey::key_matches_parsed(key, &user.parsed_authorized_keys) {
+            if user.key_expired(key, chrono::Utc::now()) {
+                tracing::warn!(
+                    user = %username,
+                    fingerprint = %crate::security::key_enrollment::key_fingerprint(key),
+                    "Public key refused: expiry-time has passed"
+                );
+                return false;
+            }
             return true;
         }
 
@@ -226,23 +440,103 @@ impl AuthService {
             None => return false,
         };
 
-        if user.is_expired() {
+        if !user.can_log_in() {
+            return false;
+        }
+        if !user.accepts_key_type(cert.public_key().algorithm().as_str()) {
+            tracing::warn!(
+                user = %username,
+                key_type = %cert.public_key().algorithm(),
+                "Certificate refused: require_security_key is set"
+            );
             return false;
         }
 
         certificate::verify_certificate(cert, &self.trusted_cas, username)
     }
 
+    /// Swap in a new password hash for `username` without a full reload.
+    /// Returns false if the user does not exist.
+    pub fn set_password_hash(&mut self, username: &str, password_hash: String) -> bool {
+        match self.user_store.with_password_hash(username, password_hash) {
+            Some(store) => {
+                self.user_store = Arc::new(store);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Swap in `username`'s personal API tokens without a full reload.
+    /// Returns false if the user does not exist.
+    pub fn set_api_tokens(&mut self, username: &str, tokens: Vec<PersonalTokenConfig>) -> bool {
+        match self.user_store.with_api_tokens(username, tokens) {
+            Some(store) => {
+                self.user_store = Arc::new(store);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace `username`'s hash `old_hash` with the upgraded `new_hash` in
+    /// the live user store. Returns false if the user is unknown or the hash
+    /// changed in the meantime.
+    pub fn upgrade_password_hash(
+        &mut self,
+        username: &str,
+        old_hash: &str,
+        new_hash: String,
+    ) -> bool {
+        match self
+            .user_store
+            .with_upgraded_password_hash(username, old_hash, new_hash)
+        {
+            Some(store) => {
+                self.user_store = Arc::new(store);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add an authorized key to `username` in the live user store
+    /// (key enrollment). Returns false if the user is unknown or the key
+    /// does not parse.
+    pub fn add_authorized_key(&mut self, username: &str, key_line: &str) -> bool {
+        match self.user_store.with_authorized_key(username, key_line) {
+            Some(store) => {
+                self.user_store = Arc::new(store);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Reload user store and trusted CA keys from new config
     pub fn reload(&mut self, config: &AppConfig) -> Result<()> {
-        let new_store = Arc::new(UserStore::from_config(
+        let mut new_store = UserStore::from_config(
             &config.users,
             &config.groups,
             &config.acl,
             &config.limits,
             &config.server,
             &config.shell,
-        )?);
+        )?
+        .with_quota_plans(&config.quota_plans);
+        // Users from the external hook keep their attributes until next login
+        let external = ExternalAuth::new(config).map(Arc::new);
+        if external.is_some() {
+            for name in &self.external_users {
+                if let Some(user) = self.user_store.get(name) {
+                    if new_store.get(name).is_none() {
+                        new_store = new_store.with_user(User::clone(user));
+                    }
+                }
+            }
+        } else {
+            self.external_users.clear();
+        }
         let new_trusted_cas = Arc::new(certificate::parse_trusted_ca_keys(
             &config.security.trusted_user_ca_keys,
         ));
@@ -252,12 +546,35 @@ impl AuthService {
                 "Reloaded trusted CA keys for SSH certificate authentication"
             );
         }
-        self.user_store = new_store;
+        self.user_store = Arc::new(new_store);
+        self.groups = config.groups.clone();
+        self.quota_plans = config.quota_plans.clone();
         self.trusted_cas = new_trusted_cas;
+        self.external = external;
+        self.password_policy = config.security.password_policy.clone();
+        self.hash_settings = HashSettings::from_config(&config.security);
+        self.rehash_on_login = config.security.rehash_on_login;
+        self.invitations = config.invitations.clone();
+        if self.key_fetcher.config() != &config.authorized_keys_fetch {
+            self.key_fetcher = Arc::new(KeyFetcher::new(&config.authorized_keys_fetch));
+        }
+        #[cfg(feature = "gssapi")]
+        {
+            self.gssapi = gssapi_from_config(config)?;
+        }
         Ok(())
     }
 }
 
+#[cfg(feature = "gssapi")]
+fn gssapi_from_config(config: &AppConfig) -> Result<Option<Arc<gssapi::Gssapi>>> {
+    config
+        .gssapi
+        .as_ref()
+        .map(|gssapi| gssapi::Gssapi::new(gssapi).map(Arc::new))
+        .transpose()
+}
+
 /// Enforce the TOTP replay map hard cap on a given DashMap.
 /// Extracted to allow unit testing without touching the global static.
 ///
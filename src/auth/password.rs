@@ -1,3 +1,5 @@
+use super::password_policy;
+use crate::config::types::{PasswordHashAlgorithm, SecurityConfig};
 use anyhow::Result;
 use argon2::password_hash::rand_core::RngCore;
 use argon2::{
@@ -5,6 +7,99 @@ use argon2::{
     Argon2,
 };
 
+/// How new password hashes are made: `security.password_hash_algorithm`
+/// and the `security.argon2_*` parameters. bcrypt and scrypt hashes use the
+/// recommended costs of their crates (bcrypt cost 12; scrypt log N 17, r 8,
+/// p 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashSettings {
+    pub algorithm: PasswordHashAlgorithm,
+    pub argon2_memory_cost: u32,
+    pub argon2_time_cost: u32,
+    pub argon2_parallelism: u32,
+}
+
+impl Default for HashSettings {
+    fn default() -> Self {
+        Self::from_config(&SecurityConfig::default())
+    }
+}
+
+impl HashSettings {
+    pub fn from_config(security: &SecurityConfig) -> Self {
+        Self {
+            algorithm: security.password_hash_algorithm,
+            argon2_memory_cost: security.argon2_memory_cost,
+            argon2_time_cost: security.argon2_time_cost,
+            argon2_parallelism: security.argon2_parallelism,
+        }
+    }
+
+    /// Hash `password` with these settings.
+    pub fn hash(&self, password: &str) -> Result<String> {
+        match self.algorithm {
+            PasswordHashAlgorithm::Argon2id => hash_password_with_params(
+                password,
+                self.argon2_memory_cost,
+                self.argon2_time_cost,
+                self.argon2_parallelism,
+            ),
+            PasswordHashAlgorithm::Bcrypt => bcrypt::hash(password, bcrypt::DEFAULT_COST)
+                .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e)),
+            PasswordHashAlgorithm::Scrypt => {
+                let salt = SaltString::generate(&mut OsRng);
+                let hash = scrypt::Scrypt
+                    .hash_password(password.as_bytes(), &salt)
+                    .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))?;
+                Ok(hash.to_string())
+            }
+        }
+    }
+
+    /// Whether `hash` should be replaced by one made with these settings: it
+    /// uses another algorithm, or Argon2 with other parameters. Hashes in an
+    /// unknown format are left alone.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Some(algorithm) = hash_algorithm(hash) else {
+            return false;
+        };
+        if algorithm != self.algorithm {
+            return true;
+        }
+        if algorithm != PasswordHashAlgorithm::Argon2id {
+            return false;
+        }
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+        let Ok(params) = argon2::Params::try_from(&parsed) else {
+            return false;
+        };
+        parsed.algorithm != argon2::ARGON2ID_IDENT
+            || parsed.version != Some(argon2::Version::V0x13 as u32)
+            || params.m_cost() != self.argon2_memory_cost
+            || params.t_cost() != self.argon2_time_cost
+            || params.p_cost() != self.argon2_parallelism
+    }
+}
+
+/// The algorithm family of `hash`, from its prefix. `$argon2i$` and
+/// `$argon2d$` count as Argon2id (and are re-hashed to it).
+pub fn hash_algorithm(hash: &str) -> Option<PasswordHashAlgorithm> {
+    if hash.starts_with("$argon2") {
+        Some(PasswordHashAlgorithm::Argon2id)
+    } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+    {
+        Some(PasswordHashAlgorithm::Bcrypt)
+    } else if hash.starts_with("$scrypt$") {
+        Some(PasswordHashAlgorithm::Scrypt)
+    } else {
+        None
+    }
+}
+
 /// Hash a password using Argon2id with configurable parameters.
 ///
 /// - `memory_cost`: memory in KiB (default 19456 = 19 MiB)
@@ -34,8 +129,15 @@ pub fn hash_password(password: &str) -> Result<String> {
     hash_password_with_params(password, 19456, 2, 1)
 }
 
-/// Verify a password against an Argon2id hash
+/// Verify a password against an Argon2, bcrypt or scrypt hash, told apart
+/// by its prefix
 pub fn verify_password(password: &str, hash: &str) -> bool {
+    if hash_algorithm(hash) == Some(PasswordHashAlgorithm::Bcrypt) {
+        return bcrypt::verify(password, hash).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Failed to parse password hash");
+            false
+        });
+    }
     let parsed = match PasswordHash::new(hash) {
         Ok(h) => h,
         Err(e) => {
@@ -43,6 +145,11 @@ pub fn verify_password(password: &str, hash: &str) -> bool {
             return false;
         }
     };
+    if parsed.algorithm == scrypt::ALG_ID {
+        return scrypt::Scrypt
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok();
+    }
     Argon2::default()
         .verify_password(password.as_bytes(), &parsed)
         .is_ok()
@@ -67,8 +174,10 @@ pub fn generate_password(length: usize) -> String {
     String::from_utf8(password).expect("charset is ASCII")
 }
 
-/// CLI entrypoint for hash-password subcommand
-pub fn hash_password_cli(password: Option<&str>) -> Result<()> {
+/// CLI entrypoint for hash-password subcommand. The password must satisfy
+/// `security.password_policy` and is hashed with the `security` hash
+/// settings (those of the config, if there is one).
+pub fn hash_password_cli(password: Option<&str>, security: &SecurityConfig) -> Result<()> {
     let password = match password {
         Some(p) => p.to_string(),
         None => {
@@ -82,8 +191,11 @@ pub fn hash_password_cli(password: Option<&str>) -> Result<()> {
     if password.is_empty() {
         anyhow::bail!("password must not be empty");
     }
+    if let Err(violation) = password_policy::check(&security.password_policy, &password) {
+        anyhow::bail!("{violation}");
+    }
 
-    let hash = hash_password(&password)?;
+    let hash = HashSettings::from_config(security).hash(&password)?;
     println!("{}", hash);
     Ok(())
 }
@@ -0,0 +1,184 @@
+//! Password policy (`[security.password_policy]`): rules applied to new
+//! passwords (self-service API, shell `passwd`, `s5 hash-password`) and the
+//! age after which an existing password is flagged.
+
+use crate::config::types::PasswordPolicyConfig;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Passwords refused with `deny_common`, lowercase. A candidate matches when
+/// it equals an entry once lowercased, with or without trailing digits and
+/// symbols (`Password123!` matches `password`).
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "password",
+    "qwerty",
+    "qwertyuiop",
+    "azerty",
+    "azertyuiop",
+    "abc123",
+    "111111",
+    "123123",
+    "000000",
+    "654321",
+    "987654321",
+    "iloveyou",
+    "admin",
+    "administrator",
+    "root",
+    "toor",
+    "welcome",
+    "letmein",
+    "monkey",
+    "dragon",
+    "football",
+    "baseball",
+    "soccer",
+    "hockey",
+    "master",
+    "shadow",
+    "sunshine",
+    "princess",
+    "superman",
+    "batman",
+    "trustno1",
+    "starwars",
+    "whatever",
+    "freedom",
+    "login",
+    "passw0rd",
+    "p@ssw0rd",
+    "p@ssword",
+    "changeme",
+    "default",
+    "secret",
+    "guest",
+    "test",
+    "testing",
+    "qazwsx",
+    "1q2w3e4r",
+    "1qaz2wsx",
+    "zaq12wsx",
+    "asdfghjkl",
+    "asdfgh",
+    "zxcvbnm",
+    "michael",
+    "jennifer",
+    "jordan",
+    "hunter",
+    "ranger",
+    "buster",
+    "soleil",
+    "bonjour",
+    "motdepasse",
+    "passwort",
+    "contraseña",
+    "senha",
+    "access",
+    "computer",
+    "internet",
+    "server",
+    "ssh",
+    "proxy",
+    "socks",
+    "linux",
+    "ubuntu",
+    "raspberry",
+    "oracle",
+    "mysql",
+    "postgres",
+    "cisco",
+    "summer",
+    "winter",
+    "spring",
+    "autumn",
+    "hello",
+    "charlie",
+    "killer",
+    "pepper",
+    "ginger",
+    "cheese",
+    "cookie",
+    "flower",
+    "pokemon",
+    "naruto",
+    "mustang",
+    "matrix",
+    "lovely",
+    "babygirl",
+    "family",
+    "samsung",
+    "apple",
+    "google",
+    "facebook",
+];
+
+/// Why a password was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PolicyViolation {
+    #[error("password must be at least {0} characters")]
+    TooShort(usize),
+    #[error(
+        "password must mix at least {0} of lowercase letters, uppercase letters, digits and symbols"
+    )]
+    TooFewClasses(u8),
+    #[error("password is too common")]
+    Common,
+}
+
+/// Check `password` against `policy`.
+pub fn check(policy: &PasswordPolicyConfig, password: &str) -> Result<(), PolicyViolation> {
+    if password.chars().count() < policy.min_length {
+        return Err(PolicyViolation::TooShort(policy.min_length));
+    }
+    if character_classes(password) < policy.min_classes {
+        return Err(PolicyViolation::TooFewClasses(policy.min_classes));
+    }
+    if policy.deny_common && is_common(password) {
+        return Err(PolicyViolation::Common);
+    }
+    Ok(())
+}
+
+/// How many of lowercase, uppercase, digits and symbols `password` uses.
+pub fn character_classes(password: &str) -> u8 {
+    let mut classes = [false; 4];
+    for c in password.chars() {
+        let class = if c.is_lowercase() {
+            0
+        } else if c.is_uppercase() {
+            1
+        } else if c.is_numeric() {
+            2
+        } else {
+            3
+        };
+        classes[class] = true;
+    }
+    classes.iter().filter(|&&c| c).count() as u8
+}
+
+/// Whether `password` is on the built-in list of common passwords.
+pub fn is_common(password: &str) -> bool {
+    let lower = password.to_lowercase();
+    let stem = lower.trim_end_matches(|c: char| !c.is_alphabetic());
+    COMMON_PASSWORDS.contains(&lower.as_str()) || COMMON_PASSWORDS.contains(&stem)
+}
+
+/// Whether a password last set at `changed_at` is older than `max_age_days`.
+/// Passwords with no recorded change date are never flagged.
+pub fn is_expired(
+    policy: &PasswordPolicyConfig,
+    changed_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> bool {
+    match changed_at {
+        Some(changed_at) if policy.max_age_days > 0 => {
+            now - changed_at > chrono::Duration::days(i64::from(policy.max_age_days))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,183 @@
+//! Read-only personal API tokens (`[[users]] api_tokens`).
+//!
+//! Users create them on the self-service page to script checks of their own
+//! quota and sessions (`Authorization: Bearer <username>:<token>`). The token
+//! is shown once; the config keeps only its SHA-256, which is enough for 256
+//! random bits and cheap to check on every request, unlike the Argon2
+//! `api_token_hash`.
+
+use super::AuthService;
+use crate::config::types::PersonalTokenConfig;
+use chrono::{SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use zeroize::Zeroizing;
+
+/// Prefix of every personal token, so they are recognizable in scripts and
+/// secret scanners.
+pub const TOKEN_PREFIX: &str = "s5r_";
+
+/// Tokens one user may hold.
+pub const MAX_TOKENS_PER_USER: usize = 10;
+
+/// Maximum length of a token label.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Reasons creating or revoking a token can be refused or fail.
+#[derive(Debug, Error)]
+pub enum TokenError {
+    #[error("token name must not be empty")]
+    EmptyName,
+    #[error("token name must be at most {} characters", MAX_NAME_LENGTH)]
+    NameTooLong,
+    #[error("at most {} tokens per user", MAX_TOKENS_PER_USER)]
+    TooMany,
+    #[error("token not found")]
+    NotFound,
+    #[error("unknown user")]
+    UnknownUser,
+    #[error("failed to persist tokens: {0}")]
+    Persist(String),
+}
+
+impl TokenError {
+    /// Whether the error was caused by the request (as opposed to the server).
+    pub fn is_client_error(&self) -> bool {
+        !matches!(self, Self::Persist(_))
+    }
+}
+
+/// Hex SHA-256 of `token`, as stored in `sha256`.
+pub fn digest(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// A fresh token labelled `name`, and its secret value.
+pub fn generate(name: &str) -> (PersonalTokenConfig, Zeroizing<String>) {
+    let secret = Zeroizing::new(format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        hex::encode(rand::random::<[u8; 32]>())
+    ));
+    let token = PersonalTokenConfig {
+        id: hex::encode(rand::random::<[u8; 4]>()),
+        name: name.to_string(),
+        sha256: digest(&secret),
+        created_at: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+    };
+    (token, secret)
+}
+
+/// The entry of `tokens` matching `token`. Every digest is compared in
+/// constant time.
+pub fn verify<'a>(
+    tokens: &'a [PersonalTokenConfig],
+    token: &str,
+) -> Option<&'a PersonalTokenConfig> {
+    use subtle::ConstantTimeEq;
+    let provided = digest(token);
+    tokens.iter().fold(None, |found, t| {
+        let eq = bool::from(provided.as_bytes().ct_eq(t.sha256.as_bytes()));
+        found.or(eq.then_some(t))
+    })
+}
+
+fn validate_name(name: &str) -> Result<(), TokenError> {
+    if name.trim().is_empty() {
+        return Err(TokenError::EmptyName);
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(TokenError::NameTooLong);
+    }
+    Ok(())
+}
+
+/// Create a token for `username` and return it with its secret.
+///
+/// Like a self-service password change, the token list is written to
+/// `config_path` first, then swapped into the live user store; without a
+/// config file the token lasts until the next restart or reload.
+pub async fn create(
+    auth: &RwLock<AuthService>,
+    config_path: Option<&Path>,
+    username: &str,
+    name: &str,
+) -> Result<(PersonalTokenConfig, Zeroizing<String>), TokenError> {
+    let name = name.trim();
+    validate_name(name)?;
+    let (token, secret) = generate(name);
+    update(auth, config_path, username, |tokens| {
+        if tokens.len() >= MAX_TOKENS_PER_USER {
+            return Err(TokenError::TooMany);
+        }
+        tokens.push(token.clone());
+        Ok(())
+    })
+    .await?;
+    tracing::info!(user = %username, token = %token.id, "Personal API token created");
+    Ok((token, secret))
+}
+
+/// Revoke `username`'s token `id`, persisted like [`create`].
+pub async fn revoke(
+    auth: &RwLock<AuthService>,
+    config_path: Option<&Path>,
+    username: &str,
+    id: &str,
+) -> Result<PersonalTokenConfig, TokenError> {
+    let mut revoked = None;
+    update(auth, config_path, username, |tokens| {
+        let pos = tokens
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or(TokenError::NotFound)?;
+        revoked = Some(tokens.remove(pos));
+        Ok(())
+    })
+    .await?;
+    tracing::info!(user = %username, token = %id, "Personal API token revoked");
+    revoked.ok_or(TokenError::NotFound)
+}
+
+/// Apply `edit` to `username`'s token list, write it back and swap it in.
+/// The write lock is held throughout so concurrent edits cannot drop one
+/// another's changes.
+async fn update(
+    auth: &RwLock<AuthService>,
+    config_path: Option<&Path>,
+    username: &str,
+    edit: impl FnOnce(&mut Vec<PersonalTokenConfig>) -> Result<(), TokenError>,
+) -> Result<(), TokenError> {
+    let mut auth = auth.write().await;
+    let mut tokens = auth
+        .user_store()
+        .get(username)
+        .map(|u| u.api_tokens.clone())
+        .ok_or(TokenError::UnknownUser)?;
+    edit(&mut tokens)?;
+
+    match config_path {
+        Some(path) => {
+            let path = path.to_path_buf();
+            let user = username.to_string();
+            let list = tokens.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::config::persist::set_user_api_tokens(&path, &user, &list)
+            })
+            .await
+            .map_err(|e| TokenError::Persist(e.to_string()))?
+            .map_err(|e| TokenError::Persist(format!("{:#}", e)))?;
+        }
+        None => {
+            tracing::warn!(
+                user = %username,
+                "No config file to persist API tokens, applying in memory only"
+            );
+        }
+    }
+
+    auth.set_api_tokens(username, tokens);
+    Ok(())
+}
@@ -1,11 +1,100 @@
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use russh::keys::PublicKey;
+use serde::Serialize;
 use tracing::warn;
 
+/// Days before `expiry-time` that a key is reported as expiring (dashboard
+/// and `key.expiring` reminders).
+pub const KEY_EXPIRY_REMINDER_DAYS: i64 = 14;
+
+/// Options in front of an authorized_keys line. Only the dates are
+/// supported: a line with any other option (`command=`, `from=`, ...) is
+/// refused rather than accepted without its restriction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyOptions {
+    /// `created-time="YYYYMMDD"` (s5 extension, informational)
+    pub created_at: Option<DateTime<Utc>>,
+    /// `expiry-time="YYYYMMDD[HHMM[SS]]"` (as in OpenSSH): refused from then on
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Split `[options] keytype base64 [comment]` into the options and the rest.
+fn split_options(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    let first = line.split_whitespace().next().unwrap_or("");
+    if ["ssh-", "ecdsa-", "sk-"]
+        .iter()
+        .any(|p| first.starts_with(p))
+    {
+        return ("", line);
+    }
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return (&line[..i], line[i..].trim_start()),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+/// True for FIDO2 security key types (`sk-ssh-ed25519@openssh.com`,
+/// `sk-ecdsa-sha2-nistp256@openssh.com`), whose private key never leaves
+/// the hardware authenticator.
+pub fn is_security_key(algorithm: &str) -> bool {
+    algorithm.starts_with("sk-")
+}
+
+/// Authenticator flag of a security key signature: the key was touched.
+pub const SK_USER_PRESENT: u8 = 0x01;
+/// Authenticator flag of a security key signature: the key also verified
+/// the user (PIN or biometrics), as keys made with `-O verify-required` do.
+pub const SK_USER_VERIFIED: u8 = 0x04;
+
+/// Parse an OpenSSH time spec (`YYYYMMDD`, `YYYYMMDDHHMM` or
+/// `YYYYMMDDHHMMSS`, optionally ending in `Z`), always read as UTC.
+pub fn parse_time_spec(spec: &str) -> Result<DateTime<Utc>> {
+    let digits = spec.strip_suffix(['Z', 'z']).unwrap_or(spec);
+    let parsed = match digits.len() {
+        8 => NaiveDate::parse_from_str(digits, "%Y%m%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0)),
+        12 => NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M").ok(),
+        14 => NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M%S").ok(),
+        _ => None,
+    };
+    parsed
+        .map(|t| t.and_utc())
+        .ok_or_else(|| anyhow::anyhow!("invalid time '{}' (expected YYYYMMDD[HHMM[SS]])", spec))
+}
+
+/// The options of an authorized_keys line (none for a plain key).
+pub fn key_options(line: &str) -> Result<KeyOptions> {
+    let (options, _) = split_options(line);
+    let mut parsed = KeyOptions::default();
+    if options.is_empty() {
+        return Ok(parsed);
+    }
+    for option in options.split(',') {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'));
+        match (name.to_ascii_lowercase().as_str(), value) {
+            ("expiry-time", Some(spec)) => parsed.expires_at = Some(parse_time_spec(spec)?),
+            ("created-time", Some(spec)) => parsed.created_at = Some(parse_time_spec(spec)?),
+            _ => anyhow::bail!("unsupported authorized_keys option: {}", option),
+        }
+    }
+    Ok(parsed)
+}
+
 /// Parse an OpenSSH authorized_keys line into a PublicKey
 pub fn parse_authorized_key(line: &str) -> Result<PublicKey> {
+    key_options(line)?;
+    let (_, key) = split_options(line);
     let key = russh::keys::parse_public_key_base64(
-        line.split_whitespace()
+        key.split_whitespace()
             .nth(1)
             .ok_or_else(|| anyhow::anyhow!("invalid authorized_key format: {}", line))?,
     )
@@ -13,6 +102,55 @@ pub fn parse_authorized_key(line: &str) -> Result<PublicKey> {
     Ok(key)
 }
 
+/// An authorized key with a creation or expiry date.
+#[derive(Debug, Clone)]
+pub struct DatedKey {
+    pub key: PublicKey,
+    pub comment: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The keys of `key_lines` that carry `created-time` or `expiry-time`.
+pub fn dated_keys(key_lines: &[String]) -> Vec<DatedKey> {
+    key_lines
+        .iter()
+        .filter_map(|line| {
+            let options = key_options(line).ok()?;
+            if options == KeyOptions::default() {
+                return None;
+            }
+            let key = parse_authorized_key(line).ok()?;
+            let comment = split_options(line)
+                .1
+                .split_whitespace()
+                .skip(2)
+                .collect::<Vec<_>>();
+            Some(DatedKey {
+                key,
+                comment: (!comment.is_empty()).then(|| comment.join(" ")),
+                created_at: options.created_at,
+                expires_at: options.expires_at,
+            })
+        })
+        .collect()
+}
+
+/// One entry of `GET /api/keys/expiring`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiringKey {
+    pub username: String,
+    /// OpenSSH-style `SHA256:<base64>` fingerprint
+    pub fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    /// Already refused at login
+    pub expired: bool,
+}
+
 /// Pre-parse authorized_keys strings into PublicKey objects.
 /// Invalid keys are logged and skipped.
 pub fn parse_authorized_keys(key_lines: &[String]) -> Vec<PublicKey> {
@@ -0,0 +1,68 @@
+//! Transparent hash upgrade after a successful password login
+//! (`security.rehash_on_login`).
+//!
+//! A stored hash that uses another algorithm than
+//! `security.password_hash_algorithm`, or other Argon2id parameters, is
+//! replaced by a fresh hash of the password that was just verified. The
+//! config file is updated first, and only if it still holds the old hash, so
+//! a concurrent password change is never overwritten.
+
+use super::AuthService;
+use anyhow::Result;
+use std::path::Path;
+use tokio::sync::RwLock;
+
+/// Re-hash `username`'s verified `password` if its stored hash is outdated.
+/// Returns whether the hash was replaced. Without `config_path` (env-var or
+/// demo mode) only the live user store is updated.
+pub async fn upgrade_password_hash(
+    auth: &RwLock<AuthService>,
+    config_path: Option<&Path>,
+    username: &str,
+    password: &str,
+) -> Result<bool> {
+    let (old_hash, settings) = {
+        let auth = auth.read().await;
+        if !auth.needs_rehash(username) {
+            return Ok(false);
+        }
+        let Some(old_hash) = auth
+            .user_store()
+            .get(username)
+            .and_then(|u| u.password_hash.clone())
+        else {
+            return Ok(false);
+        };
+        (old_hash, auth.hash_settings())
+    };
+
+    let password = zeroize::Zeroizing::new(password.to_string());
+    let new_hash = tokio::task::spawn_blocking(move || settings.hash(&password)).await??;
+
+    if let Some(path) = config_path {
+        let path = path.to_path_buf();
+        let user = username.to_string();
+        let old = old_hash.clone();
+        let new = new_hash.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            crate::config::persist::upgrade_user_password_hash(&path, &user, &old, &new)
+        })
+        .await??;
+        if !written {
+            return Ok(false);
+        }
+    }
+
+    let upgraded = auth
+        .write()
+        .await
+        .upgrade_password_hash(username, &old_hash, new_hash);
+    if upgraded {
+        tracing::info!(
+            user = %username,
+            algorithm = ?settings.algorithm,
+            "Password hash upgraded"
+        );
+    }
+    Ok(upgraded)
+}
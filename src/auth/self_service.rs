@@ -0,0 +1,110 @@
+//! Self-service credential management (users rotating their own password).
+
+use super::password_policy::{self, PolicyViolation};
+use super::AuthService;
+use chrono::Utc;
+use std::path::Path;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+/// Minimum length accepted for a new password.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Maximum length accepted for a new password (bounds Argon2 input size).
+pub const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Reasons a self-service password change can be refused or fail.
+#[derive(Debug, Error)]
+pub enum PasswordChangeError {
+    #[error("current password is incorrect")]
+    InvalidCurrentPassword,
+    #[error("new password must be at least {} characters", MIN_PASSWORD_LENGTH)]
+    TooShort,
+    #[error("new password must be at most {} characters", MAX_PASSWORD_LENGTH)]
+    TooLong,
+    #[error("new password must differ from the current password")]
+    Unchanged,
+    #[error("new {0}")]
+    Policy(#[from] PolicyViolation),
+    #[error("failed to hash new password")]
+    Hash,
+    #[error("failed to persist new password: {0}")]
+    Persist(String),
+}
+
+impl PasswordChangeError {
+    /// Whether the error was caused by the request (as opposed to the server).
+    pub fn is_client_error(&self) -> bool {
+        !matches!(self, Self::Hash | Self::Persist(_))
+    }
+}
+
+/// Check a candidate password against the built-in policy.
+pub fn validate_new_password(current: &str, new: &str) -> Result<(), PasswordChangeError> {
+    let len = new.chars().count();
+    if len < MIN_PASSWORD_LENGTH {
+        return Err(PasswordChangeError::TooShort);
+    }
+    if len > MAX_PASSWORD_LENGTH {
+        return Err(PasswordChangeError::TooLong);
+    }
+    if new == current {
+        return Err(PasswordChangeError::Unchanged);
+    }
+    Ok(())
+}
+
+/// Rotate `username`'s password after verifying the current one.
+///
+/// The new hash is written back to `config_path` first (so a SIGHUP reload
+/// keeps it), then swapped into the live user store. When the server runs
+/// without a config file (env-var or demo mode) the change is in-memory only.
+pub async fn change_password(
+    auth: &RwLock<AuthService>,
+    config_path: Option<&Path>,
+    username: &str,
+    current: &str,
+    new: &str,
+) -> Result<(), PasswordChangeError> {
+    // auth_password also rejects unknown and expired users, with the same
+    // timing as a wrong password.
+    let (policy, hash_settings) = {
+        let auth = auth.read().await;
+        if !auth.auth_password(username, current) {
+            return Err(PasswordChangeError::InvalidCurrentPassword);
+        }
+        (auth.password_policy().clone(), auth.hash_settings())
+    };
+    validate_new_password(current, new)?;
+    password_policy::check(&policy, new)?;
+
+    let new_owned = zeroize::Zeroizing::new(new.to_string());
+    let new_hash = tokio::task::spawn_blocking(move || hash_settings.hash(&new_owned))
+        .await
+        .map_err(|_| PasswordChangeError::Hash)?
+        .map_err(|_| PasswordChangeError::Hash)?;
+
+    match config_path {
+        Some(path) => {
+            let path = path.to_path_buf();
+            let user = username.to_string();
+            let hash = new_hash.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::config::persist::set_user_password(&path, &user, &hash, Utc::now())
+            })
+            .await
+            .map_err(|e| PasswordChangeError::Persist(e.to_string()))?
+            .map_err(|e| PasswordChangeError::Persist(format!("{:#}", e)))?;
+        }
+        None => {
+            tracing::warn!(
+                user = %username,
+                "No config file to persist password change, applying in memory only"
+            );
+        }
+    }
+
+    auth.write().await.set_password_hash(username, new_hash);
+    tracing::info!(user = %username, "Password changed");
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Tenants (`[[tenants]]`): isolated user namespaces on one server.
+//!
+//! A tenant's users are the `[[users]]` entries named `<user>@<tenant>`, so
+//! sessions, quotas and audit records of two tenants never share a key. The
+//! tenant is selected by the login name (`alice@acme`) or by the listener:
+//! on a `[[server.listeners]]` entry with `tenant = "acme"`, `alice` logs in
+//! as `alice@acme` and nobody outside that tenant can log in.
+
+use crate::config::types::TenantConfig;
+
+/// The tenant `username` belongs to (None = default namespace).
+pub fn tenant_of<'a>(tenants: &[TenantConfig], username: &'a str) -> Option<&'a str> {
+    let (_, suffix) = username.rsplit_once('@')?;
+    tenants.iter().any(|t| t.name == suffix).then_some(suffix)
+}
+
+/// True if `username` is a user of `tenant`.
+pub fn in_tenant(username: &str, tenant: &str) -> bool {
+    username
+        .strip_suffix(tenant)
+        .and_then(|rest| rest.strip_suffix('@'))
+        .is_some_and(|user| !user.is_empty())
+}
+
+/// The account a client logs in as, given the name it sent and the tenant
+/// of the listener it reached. None if that listener does not serve it.
+pub fn login_name(
+    tenants: &[TenantConfig],
+    listener_tenant: Option<&str>,
+    user: &str,
+) -> Option<String> {
+    let Some(tenant) = listener_tenant else {
+        return Some(user.to_string());
+    };
+    if in_tenant(user, tenant) {
+        return Some(user.to_string());
+    }
+    match tenant_of(tenants, user) {
+        // Another tenant's suffix
+        Some(_) => None,
+        None => Some(format!("{user}@{tenant}")),
+    }
+}
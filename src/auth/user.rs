@@ -1,14 +1,16 @@
 use crate::auth::pubkey;
-use crate::config::acl::ParsedAcl;
+use crate::config::acl::{AclRule, ParsedAcl};
 use crate::config::types::{
-    GlobalAclConfig, GroupConfig, LimitsConfig, MotdConfig, QuotaConfig, RateLimitsConfig,
-    ServerConfig, ShellConfig, ShellPermissions, TimeAccessConfig, UserConfig, UserRole,
+    GlobalAclConfig, GroupConfig, ImpossibleTravelAction, LimitsConfig, MotdConfig, QuotaConfig,
+    QuotaPlanConfig, RateLimitsConfig, ServerConfig, ShellConfig, ShellPermissions,
+    TimeAccessConfig, UserConfig, UserRole,
 };
+use crate::ssh::terminal::TerminalPolicy;
 use anyhow::Result;
-use chrono::{Datelike, Timelike, Utc};
+use chrono::{DateTime, Datelike, Timelike, Utc};
 use ipnet::IpNet;
 use russh::keys::PublicKey;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 /// Runtime user data (parsed from config with group/global inheritance resolved)
@@ -18,12 +20,48 @@ pub struct User {
     pub password_hash: Option<String>,
     pub authorized_keys: Vec<String>,
     pub parsed_authorized_keys: Vec<PublicKey>,
+    /// Keys with `created-time`/`expiry-time` options
+    pub dated_keys: Vec<pubkey::DatedKey>,
+    /// More keys fetched on login (`authorized_keys_url`)
+    pub authorized_keys_url: Option<String>,
+    /// Only FIDO2 security keys accepted (`require_security_key`)
+    pub require_security_key: bool,
+    /// Labels for `[proxy.priority]` classes and `[policy]` rules (own, then
+    /// the group's)
+    pub tags: Vec<String>,
+    /// Group override of `logging.flows.payload_metadata`
+    pub payload_metadata: Option<bool>,
+    /// Group override of `security.blocked_ports`
+    pub blocked_ports: Option<Vec<u16>>,
     pub allow_forwarding: bool,
     pub allow_shell: bool,
     pub max_new_connections_per_minute: u32,
     pub max_bandwidth_kbps: u64,
     pub source_ips: Vec<IpNet>,
+    /// Private/reserved ranges reachable despite ip_guard (resolved: user > group > none)
+    pub ip_guard_exemptions: Vec<IpNet>,
+    /// Permitted `ssh -J` hops (resolved: user > group; `None` = regular ACL)
+    pub jump_targets: Option<Vec<AclRule>>,
+    /// Unix socket paths reachable over `direct-streamlocal` (resolved: user > group > none)
+    pub unix_sockets: Vec<String>,
+    /// HASSH fingerprints the SSH client must match, lowercase
+    /// (resolved: user > group; empty = any client)
+    pub allowed_hassh: Vec<String>,
+    /// The group's `fs_allow`: Landlock confinement of host commands
+    pub fs_allow: Option<Vec<String>>,
+    /// `[[subsystems]]` the user may open (resolved: user > group > none)
+    pub subsystems: Vec<String>,
+    /// Layer-3 tunnels allowed (`allow_vpn`)
+    pub allow_vpn: bool,
+    /// Fixed tunnel address (`vpn_address`)
+    pub vpn_address: Option<std::net::Ipv4Addr>,
+    /// `[impossible_travel]` action for this user (`None` = server default)
+    pub impossible_travel: Option<ImpossibleTravelAction>,
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Logins refused (`disabled`)
+    pub disabled: bool,
+    /// Last password change, if recorded (`password_changed_at`)
+    pub password_changed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub upstream_proxy: Option<String>,
     pub acl: ParsedAcl,
     pub totp_enabled: bool,
@@ -37,8 +75,12 @@ pub struct User {
     pub shell_permissions: ShellPermissions,
     /// Resolved MOTD config: user > group > None
     pub motd_config: Option<MotdConfig>,
-    /// Per-user/group quotas
+    /// Session environment and PTY policy (resolved field by field: user > group > defaults)
+    pub terminal: TerminalPolicy,
+    /// Per-user/group quotas (a plan's, when `quota_plan` is set)
     pub quotas: Option<QuotaConfig>,
+    /// `[quota_plans]` entry the quotas come from (resolved: user > group)
+    pub quota_plan: Option<String>,
     /// Time-based access restrictions
     pub time_access: Option<TimeAccessConfig>,
     /// Auth method chain (e.g. ["pubkey", "password"])
@@ -57,6 +99,10 @@ pub struct User {
     pub max_connections: u32,
     /// Resolved multi-window rate limits (user > group > server defaults)
     pub rate_limits: RateLimitsConfig,
+    /// Argon2 hash of the personal API token (self-service endpoints)
+    pub api_token_hash: Option<String>,
+    /// Read-only personal API tokens (`api_tokens`)
+    pub api_tokens: Vec<crate::config::types::PersonalTokenConfig>,
 }
 
 impl std::fmt::Debug for User {
@@ -68,11 +114,17 @@ impl std::fmt::Debug for User {
                 "authorized_keys",
                 &format!("[{} keys]", self.authorized_keys.len()),
             )
+            .field("authorized_keys_url", &self.authorized_keys_url)
+            .field("require_security_key", &self.require_security_key)
+            .field("tags", &self.tags)
+            .field("payload_metadata", &self.payload_metadata)
             .field("allow_forwarding", &self.allow_forwarding)
             .field("allow_shell", &self.allow_shell)
             .field("group", &self.group)
             .field("role", &self.role)
             .field("expires_at", &self.expires_at)
+            .field("disabled", &self.disabled)
+            .field("password_changed_at", &self.password_changed_at)
             .field("idle_warning_secs", &self.idle_warning_secs)
             .field("colors", &self.colors)
             .field("connect_retry", &self.connect_retry)
@@ -94,11 +146,12 @@ impl User {
         server: &ServerConfig,
         shell: &ShellConfig,
     ) -> Result<Self> {
-        // Find the user's group (if any)
-        let group_cfg = cfg
+        // Find the user's group (if any), with its `inherits` chain flattened
+        let resolved_group = cfg
             .group
-            .as_ref()
-            .and_then(|name| groups.iter().find(|g| &g.name == name));
+            .as_deref()
+            .and_then(|name| crate::config::types::resolve_group(name, groups));
+        let group_cfg = resolved_group.as_ref();
 
         // --- ACL: merge global > group > user ---
         let group_acl = group_cfg.map(|g| &g.acl);
@@ -111,22 +164,75 @@ impl User {
             })?),
             None => None,
         };
+        let password_changed_at = match &cfg.password_changed_at {
+            Some(s) => Some(s.parse::<chrono::DateTime<chrono::Utc>>().map_err(|e| {
+                anyhow::anyhow!("invalid password_changed_at for '{}': {}", cfg.username, e)
+            })?),
+            None => None,
+        };
 
         let parsed_authorized_keys = pubkey::parse_authorized_keys(&cfg.authorized_keys);
 
-        // --- allow_forwarding: user explicit > group > user default (true) ---
-        let allow_forwarding =
-            group_cfg
-                .and_then(|g| g.allow_forwarding)
-                .map_or(cfg.allow_forwarding, |group_val| {
-                    // User config `allow_forwarding` defaults to true via serde;
-                    // if group restricts it, group wins unless user explicitly sets it.
-                    // Since serde always provides a value, we use the user's value directly
-                    // (user config is authoritative when present).
-                    cfg.allow_forwarding && group_val
-                });
-
-        // --- allow_shell: same logic ---
+        // --- allow_forwarding: user > group > true ---
+        let allow_forwarding = cfg
+            .allow_forwarding
+            .or_else(|| group_cfg.and_then(|g| g.allow_forwarding))
+            .unwrap_or(true);
+
+        // --- ip_guard_exemptions: user > group > none ---
+        let ip_guard_exemptions = cfg
+            .ip_guard_exemptions
+            .clone()
+            .or_else(|| group_cfg.and_then(|g| g.ip_guard_exemptions.clone()))
+            .unwrap_or_default();
+
+        // --- jump_targets: user > group > none ---
+        let jump_targets = cfg
+            .jump_targets
+            .as_ref()
+            .or_else(|| group_cfg.and_then(|g| g.jump_targets.as_ref()))
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|r| AclRule::parse(r))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        // --- tags: user's own, then the group's ---
+        let mut tags = cfg.tags.clone();
+        for tag in group_cfg
+            .and_then(|g| g.tags.as_ref())
+            .into_iter()
+            .flatten()
+        {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+
+        // --- unix_sockets: user > group > none ---
+        let unix_sockets = cfg
+            .unix_sockets
+            .clone()
+            .or_else(|| group_cfg.and_then(|g| g.unix_sockets.clone()))
+            .unwrap_or_default();
+
+        // --- allowed_hassh: user > group > none ---
+        let allowed_hassh = cfg
+            .allowed_hassh
+            .as_ref()
+            .or_else(|| group_cfg.and_then(|g| g.allowed_hassh.as_ref()))
+            .map(|list| list.iter().map(|h| h.to_ascii_lowercase()).collect())
+            .unwrap_or_default();
+
+        // --- subsystems: user > group > none ---
+        let subsystems = cfg
+            .subsystems
+            .clone()
+            .or_else(|| group_cfg.and_then(|g| g.subsystems.clone()))
+            .unwrap_or_default();
+
         let allow_shell = group_cfg
             .and_then(|g| g.allow_shell)
             .map_or(cfg.allow_shell, |group_val| cfg.allow_shell && group_val);
@@ -171,17 +277,24 @@ impl User {
             group_cfg.and_then(|g| g.shell_permissions.as_ref()),
         );
 
+        // --- terminal: user > group > defaults, field by field ---
+        let terminal = TerminalPolicy::resolve(
+            cfg.terminal.as_ref(),
+            group_cfg.and_then(|g| g.terminal.as_ref()),
+        );
+
         // --- motd_config: user > group > None ---
         let motd_config = cfg
             .motd
             .clone()
             .or_else(|| group_cfg.and_then(|g| g.motd.clone()));
 
-        // --- quotas: user > group > None ---
-        let quotas = cfg
-            .quotas
-            .clone()
-            .or_else(|| group_cfg.and_then(|g| g.quotas.clone()));
+        // --- quotas or quota_plan: user > group > None ---
+        let (quotas, quota_plan) = if cfg.quotas.is_some() || cfg.quota_plan.is_some() {
+            (cfg.quotas.clone(), cfg.quota_plan.clone())
+        } else {
+            group_cfg.map_or((None, None), |g| (g.quotas.clone(), g.quota_plan.clone()))
+        };
 
         // --- time_access: user > group > None ---
         let time_access = cfg
@@ -237,12 +350,29 @@ impl User {
             password_hash: cfg.password_hash.clone(),
             authorized_keys: cfg.authorized_keys.clone(),
             parsed_authorized_keys,
+            dated_keys: pubkey::dated_keys(&cfg.authorized_keys),
+            authorized_keys_url: cfg.authorized_keys_url.clone(),
+            require_security_key: cfg.require_security_key,
+            tags,
+            payload_metadata: group_cfg.and_then(|g| g.payload_metadata),
+            blocked_ports: group_cfg.and_then(|g| g.blocked_ports.clone()),
             allow_forwarding,
             allow_shell,
             max_new_connections_per_minute,
             max_bandwidth_kbps,
             source_ips: cfg.source_ips.clone(),
+            ip_guard_exemptions,
+            jump_targets,
+            unix_sockets,
+            allowed_hassh,
+            fs_allow: group_cfg.and_then(|g| g.fs_allow.clone()),
+            subsystems,
+            allow_vpn: cfg.allow_vpn,
+            vpn_address: cfg.vpn_address,
+            impossible_travel: cfg.impossible_travel,
             expires_at,
+            disabled: cfg.disabled,
+            password_changed_at,
             upstream_proxy: cfg.upstream_proxy.clone(),
             acl,
             totp_enabled: cfg.totp_enabled,
@@ -251,8 +381,10 @@ impl User {
             group: cfg.group.clone(),
             role,
             shell_permissions,
+            terminal,
             motd_config,
             quotas,
+            quota_plan,
             time_access,
             auth_methods,
             idle_warning_secs,
@@ -262,9 +394,19 @@ impl User {
             aliases: cfg.aliases.clone(),
             max_connections,
             rate_limits,
+            api_token_hash: cfg.api_token_hash.clone(),
+            api_tokens: cfg.api_tokens.clone(),
         })
     }
 
+    /// Take the quotas of this user's `quota_plan` from `plans`.
+    pub fn with_quota_plan(mut self, plans: &BTreeMap<String, QuotaPlanConfig>) -> Self {
+        if let Some(plan) = self.quota_plan.as_ref().and_then(|name| plans.get(name)) {
+            self.quotas = Some(plan.quotas.clone());
+        }
+        self
+    }
+
     pub fn is_expired(&self) -> bool {
         if let Some(exp) = &self.expires_at {
             chrono::Utc::now() > *exp
@@ -273,6 +415,32 @@ impl User {
         }
     }
 
+    /// False once the account is expired or `disabled`.
+    pub fn can_log_in(&self) -> bool {
+        !self.disabled && !self.is_expired()
+    }
+
+    /// False if the user requires security keys and `algorithm` (an SSH key
+    /// type name) is not one.
+    pub fn accepts_key_type(&self, algorithm: &str) -> bool {
+        !self.require_security_key || pubkey::is_security_key(algorithm)
+    }
+
+    /// False if a security key signature with these authenticator `flags`
+    /// was made without a touch, or without user verification when the user
+    /// requires security keys.
+    pub fn accepts_security_key_flags(&self, flags: u8) -> bool {
+        flags & pubkey::SK_USER_PRESENT != 0
+            && (!self.require_security_key || flags & pubkey::SK_USER_VERIFIED != 0)
+    }
+
+    /// True if `key` is one of the user's keys and its `expiry-time` is past.
+    pub fn key_expired(&self, key: &PublicKey, now: DateTime<Utc>) -> bool {
+        self.dated_keys
+            .iter()
+            .any(|d| &d.key == key && d.expires_at.is_some_and(|t| t <= now))
+    }
+
     /// Check if a source IP is allowed for this user.
     /// Returns true if source_ips is empty (no restriction) or if the IP matches any entry.
     pub fn is_source_ip_allowed(&self, ip: &std::net::IpAddr) -> bool {
@@ -283,6 +451,15 @@ impl User {
         self.source_ips.iter().any(|net| net.contains(&ip))
     }
 
+    /// Check the SSH client's HASSH against the user's pins. Always true
+    /// without pins; with pins, a client whose KEXINIT was not seen fails.
+    pub fn is_hassh_allowed(&self, hassh: Option<&str>) -> bool {
+        if self.allowed_hassh.is_empty() {
+            return true;
+        }
+        hassh.is_some_and(|h| self.allowed_hassh.iter().any(|a| a.eq_ignore_ascii_case(h)))
+    }
+
     /// Check if current time is within the user's allowed access hours and days.
     ///
     /// Returns `true` if:
@@ -424,6 +601,22 @@ impl UserStore {
         Ok(Self { users })
     }
 
+    /// Resolve every user's `quota_plan` against `plans`.
+    pub fn with_quota_plans(self, plans: &BTreeMap<String, QuotaPlanConfig>) -> Self {
+        let users = self
+            .users
+            .into_iter()
+            .map(|(name, user)| {
+                if user.quota_plan.is_none() {
+                    return (name, user);
+                }
+                let user = Arc::unwrap_or_clone(user).with_quota_plan(plans);
+                (name, Arc::new(user))
+            })
+            .collect();
+        Self { users }
+    }
+
     pub fn get(&self, username: &str) -> Option<&Arc<User>> {
         self.users.get(username)
     }
@@ -460,6 +653,82 @@ impl UserStore {
             .filter(|u| u.group.as_deref() == Some(group))
             .collect()
     }
+
+    /// Build a copy of this store with `username`'s password hash replaced.
+    ///
+    /// The store is shared behind an `Arc` by live sessions, so updates are
+    /// copy-on-write: only the changed user is re-allocated, every other entry
+    /// keeps pointing at the same `Arc<User>`. Returns `None` for unknown users.
+    pub fn with_password_hash(&self, username: &str, password_hash: String) -> Option<Self> {
+        let existing = self.users.get(username)?;
+        let mut updated = User::clone(existing);
+        updated.password_hash = Some(password_hash);
+        updated.password_changed_at = Some(chrono::Utc::now());
+        let mut users = self.users.clone();
+        users.insert(username.to_string(), Arc::new(updated));
+        Some(Self { users })
+    }
+
+    /// Build a copy of this store with `username`'s personal API tokens
+    /// replaced, copy-on-write like [`Self::with_password_hash`]. Returns
+    /// `None` for unknown users.
+    pub fn with_api_tokens(
+        &self,
+        username: &str,
+        tokens: Vec<crate::config::types::PersonalTokenConfig>,
+    ) -> Option<Self> {
+        let existing = self.users.get(username)?;
+        let mut updated = User::clone(existing);
+        updated.api_tokens = tokens;
+        let mut users = self.users.clone();
+        users.insert(username.to_string(), Arc::new(updated));
+        Some(Self { users })
+    }
+
+    /// Build a copy of this store with `username`'s hash `old_hash` replaced
+    /// by `new_hash`, a hash of the same password (rehash on login), keeping
+    /// `password_changed_at`. Returns `None` for unknown users and when the
+    /// hash is no longer `old_hash`.
+    pub fn with_upgraded_password_hash(
+        &self,
+        username: &str,
+        old_hash: &str,
+        new_hash: String,
+    ) -> Option<Self> {
+        let existing = self.users.get(username)?;
+        if existing.password_hash.as_deref() != Some(old_hash) {
+            return None;
+        }
+        let mut updated = User::clone(existing);
+        updated.password_hash = Some(new_hash);
+        let mut users = self.users.clone();
+        users.insert(username.to_string(), Arc::new(updated));
+        Some(Self { users })
+    }
+
+    /// Build a copy of this store with `key_line` appended to `username`'s
+    /// authorized keys (copy-on-write, see [`UserStore::with_password_hash`]).
+    /// Returns `None` for unknown users and keys that do not parse.
+    pub fn with_authorized_key(&self, username: &str, key_line: &str) -> Option<Self> {
+        let existing = self.users.get(username)?;
+        let key = pubkey::parse_authorized_key(key_line).ok()?;
+        let mut updated = User::clone(existing);
+        if !updated.parsed_authorized_keys.contains(&key) {
+            updated.authorized_keys.push(key_line.to_string());
+            updated.parsed_authorized_keys.push(key);
+        }
+        let mut users = self.users.clone();
+        users.insert(username.to_string(), Arc::new(updated));
+        Some(Self { users })
+    }
+
+    /// Build a copy of this store with `user` added or replaced
+    /// (copy-on-write, see [`UserStore::with_password_hash`]).
+    pub fn with_user(&self, user: User) -> Self {
+        let mut users = self.users.clone();
+        users.insert(user.username.clone(), Arc::new(user));
+        Self { users }
+    }
 }
 
 #[cfg(test)]
@@ -487,12 +756,24 @@ mod tests {
             socks5_tls_key: None,
             dns_cache_ttl: -1,
             dns_cache_max_entries: 1000,
+            dns_prefetch_hosts: 0,
             connect_retry: 2,
             connect_retry_delay_ms: 500,
             bookmarks_path: None,
             ssh_keepalive_interval_secs: 15,
             ssh_keepalive_max: 3,
             ssh_auth_timeout: 120,
+            ssh_window_size: 2 * 1024 * 1024,
+            ssh_max_packet_size: 32 * 1024,
+            ssh_stall_threshold_ms: 5000,
+            max_connections: 0,
+            max_connections_per_user: 0,
+            connection_queue_timeout_ms: 0,
+            connection_queue_size: 0,
+            listeners: Vec::new(),
+            host_key_rotation: crate::config::types::HostKeyRotationConfig::default(),
+            maintenance_message: "Server is under maintenance. Please try again later.".to_string(),
+            socket_handover: false,
         }
     }
 
@@ -505,12 +786,18 @@ mod tests {
             username: username.to_string(),
             password_hash: Some("hash".to_string()),
             authorized_keys: Vec::new(),
-            allow_forwarding: true,
+            allow_forwarding: Some(true),
             allow_shell: true,
             max_new_connections_per_minute: 0,
             max_bandwidth_kbps: 0,
             source_ips: Vec::new(),
             expires_at: None,
+            disabled: false,
+            require_security_key: false,
+            allow_vpn: false,
+            vpn_address: None,
+            tags: Vec::new(),
+            authorized_keys_url: None,
             upstream_proxy: None,
             acl: Default::default(),
             totp_secret: None,
@@ -519,8 +806,10 @@ mod tests {
             group: None,
             role: UserRole::default(),
             shell_permissions: None,
+            terminal: None,
             motd: None,
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: None,
             idle_warning_secs: None,
@@ -530,6 +819,15 @@ mod tests {
             aliases: HashMap::new(),
             max_connections: None,
             rate_limits: None,
+            api_token_hash: None,
+            api_tokens: Vec::new(),
+            ip_guard_exemptions: None,
+            jump_targets: None,
+            unix_sockets: None,
+            allowed_hassh: None,
+            subsystems: None,
+            impossible_travel: None,
+            password_changed_at: None,
         }
     }
 
@@ -561,6 +859,8 @@ mod tests {
 
         let group = GroupConfig {
             name: "devs".to_string(),
+            inherits: None,
+            managers: Vec::new(),
             acl: Default::default(),
             max_connections_per_user: None,
             max_bandwidth_kbps: Some(5000),
@@ -573,12 +873,14 @@ mod tests {
                 show_bandwidth: false,
                 ..ShellPermissions::default()
             }),
+            terminal: None,
             motd: Some(MotdConfig {
                 enabled: true,
                 template: Some("Hello {user}!".to_string()),
                 colors: false,
             }),
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: Some(vec!["pubkey".to_string()]),
             idle_warning_secs: Some(30),
@@ -587,6 +889,15 @@ mod tests {
             connect_retry: Some(5),
             connect_retry_delay_ms: Some(2000),
             rate_limits: None,
+            ip_guard_exemptions: None,
+            jump_targets: None,
+            unix_sockets: None,
+            allowed_hassh: None,
+            fs_allow: None,
+            subsystems: None,
+            payload_metadata: None,
+            blocked_ports: None,
+            tags: None,
         };
 
         let user = User::from_config(
@@ -622,6 +933,8 @@ mod tests {
 
         let group = GroupConfig {
             name: "devs".to_string(),
+            inherits: None,
+            managers: Vec::new(),
             acl: Default::default(),
             max_connections_per_user: None,
             max_bandwidth_kbps: None,
@@ -630,8 +943,10 @@ mod tests {
             allow_forwarding: None,
             allow_shell: None,
             shell_permissions: None,
+            terminal: None,
             motd: None,
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: None,
             idle_warning_secs: Some(30),
@@ -640,6 +955,15 @@ mod tests {
             connect_retry: Some(5),
             connect_retry_delay_ms: None,
             rate_limits: None,
+            ip_guard_exemptions: None,
+            jump_targets: None,
+            unix_sockets: None,
+            allowed_hassh: None,
+            fs_allow: None,
+            subsystems: None,
+            payload_metadata: None,
+            blocked_ports: None,
+            tags: None,
         };
 
         let user = User::from_config(
@@ -106,6 +106,43 @@ pub enum Command {
         #[arg(long, default_value = "toml")]
         format: String,
     },
+    /// Check the configuration and the host for common problems (key and
+    /// database files, log directories, listener ports, API exposure)
+    Doctor {
+        /// Print the checks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Verify a hash-chained audit log (`logging.audit_chain`)
+    VerifyAudit {
+        /// Audit files, oldest first (e.g. audit.json.2 audit.json.1 audit.json)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Checkpoint signing key (`logging.audit_signing_key`)
+        #[arg(long, env = "S5_AUDIT_SIGNING_KEY", hide_env_values = true)]
+        key: Option<String>,
+    },
+    /// Download Tor exit and datacenter ranges for `[ip_intel]`
+    UpdateIntel {
+        /// Dataset file to write (default: ip_intel.path, else stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert htpasswd / authorized_keys files into [[users]] entries
+    #[command(arg_required_else_help = true)]
+    Import {
+        /// htpasswd file (user:hash per line; only Argon2 hashes are kept)
+        #[arg(long)]
+        passwd_file: Option<PathBuf>,
+        /// Directory of per-user key files (<dir>/<user>) or home
+        /// directories (<dir>/<user>/.ssh/authorized_keys)
+        #[arg(long)]
+        authorized_keys_dir: Option<PathBuf>,
+        /// Add the users to the config file (-c) instead of printing them;
+        /// users it already defines are left untouched
+        #[arg(long)]
+        write: bool,
+    },
     /// Backup server state (bans, quotas) via API
     Backup {
         /// Output file path (stdout if omitted)
@@ -130,6 +167,26 @@ pub enum Command {
         #[arg(long)]
         token: String,
     },
+    /// Query and control a running server through its management API
+    Ctl {
+        /// API address: http(s)://host:port or unix:/path/to/api.sock
+        /// (default: api.unix_socket or api.listen from the config file)
+        #[arg(long, env = "S5_API_ADDR")]
+        api_addr: Option<String>,
+        /// API bearer token (default: api.token from the config file)
+        #[arg(long, env = "S5_API_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Print the response data as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(subcommand)]
+        action: CtlCommand,
+    },
+    /// Manage the Windows service
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommand,
+    },
     /// Start a demo server with pre-populated realistic data
     Demo {
         /// SSH listen port
@@ -146,3 +203,71 @@ pub enum Command {
         password: String,
     },
 }
+
+/// `s5 ctl` actions, one API call each.
+#[derive(Subcommand, Debug, Clone)]
+pub enum CtlCommand {
+    /// Server status, uptime and connection counts
+    Status,
+    /// List active sessions
+    Sessions {
+        /// Only sessions of this user
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Disconnect a user's SSH connections and close their sessions
+    Kick {
+        username: String,
+        /// Message shown to the user
+        #[arg(long)]
+        message: Option<String>,
+    },
+    /// Ban an IP address
+    Ban {
+        ip: String,
+        /// Ban length in seconds (default: security.ban_duration)
+        #[arg(long)]
+        duration: Option<u64>,
+    },
+    /// Lift an IP ban
+    Unban { ip: String },
+    /// List banned IPs
+    Bans,
+    /// Lift an account lock (security.lock_after_failures)
+    Unlock { username: String },
+    /// Reload the configuration file
+    Reload,
+    /// Show or change the log filter until the next restart
+    LogLevel {
+        /// New level or directives, e.g. `debug` or `info,s5::proxy=trace`
+        level: Option<String>,
+        /// Return to the startup filter
+        #[arg(long, conflicts_with = "level")]
+        reset: bool,
+    },
+}
+
+/// `s5 service` actions (Windows only).
+#[derive(Subcommand, Debug, Clone)]
+pub enum ServiceCommand {
+    /// Register s5 with the Service Control Manager (auto start) and the
+    /// Event Log, using the current --config path
+    Install {
+        /// Service name
+        #[arg(long, default_value = "s5")]
+        name: String,
+    },
+    /// Stop and remove the service and its Event Log source
+    Uninstall {
+        /// Service name
+        #[arg(long, default_value = "s5")]
+        name: String,
+    },
+    /// Entry point used by the Service Control Manager
+    #[command(hide = true)]
+    Run {
+        /// Service name
+        #[arg(long, default_value = "s5")]
+        name: String,
+    },
+}
@@ -0,0 +1,367 @@
+//! Peer-to-peer cluster backend (`backend = "gossip"`), for small
+//! active/active deployments without a Redis server.
+//!
+//! Every node keeps a replica of the shared state and, each sync, POSTs it
+//! to every peer, which merges it and answers with its own. The replica is
+//! conflict-free, so exchanges can be lost, repeated or reordered and the
+//! nodes still converge:
+//! - bans are last-writer-wins registers, a lifted ban staying as a
+//!   tombstone until it would have expired
+//! - counters keep one value per node, merged by maximum and summed; a reset
+//!   starts a new epoch that replaces the older ones
+//!
+//! Exchanges are signed with HMAC-SHA256 over `cluster.secret` but not
+//! encrypted: peers belong on a private network.
+
+use super::{BanChanges, BanMap, CounterOp, CounterUpdate, NodeReport};
+use crate::config::types::ClusterConfig;
+use crate::security::ban::BanKey;
+use anyhow::{Context, Result};
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+/// Path peers POST their state to.
+pub const GOSSIP_PATH: &str = "/gossip";
+
+/// Header carrying the HMAC of the body.
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Largest exchange accepted (a node's session list).
+const MAX_MESSAGE: usize = 64 * 1024 * 1024;
+
+/// A ban as replicated between nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanRecord {
+    /// Unix seconds
+    pub expires_at: u64,
+    /// Unix milliseconds of the last change; the later change wins
+    pub updated_at: u64,
+    /// Lifted before its expiry
+    #[serde(default)]
+    pub lifted: bool,
+}
+
+impl BanRecord {
+    fn order(&self) -> (u64, bool, u64) {
+        (self.updated_at, self.lifted, self.expires_at)
+    }
+}
+
+/// A shared counter: each node's contribution since the last reset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedCounter {
+    /// Number of resets; a later epoch replaces the earlier ones
+    pub epoch: u64,
+    pub nodes: BTreeMap<String, u64>,
+    /// Unix seconds after which the counter is dropped (None = never)
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+impl SharedCounter {
+    pub fn value(&self) -> u64 {
+        self.nodes
+            .values()
+            .fold(0u64, |sum, v| sum.saturating_add(*v))
+    }
+
+    pub fn merge(&mut self, other: &SharedCounter) {
+        if other.epoch > self.epoch {
+            let expires_at = self.expires_at.max(other.expires_at);
+            *self = other.clone();
+            self.expires_at = expires_at;
+            return;
+        }
+        if other.epoch == self.epoch {
+            for (node, value) in &other.nodes {
+                let mine = self.nodes.entry(node.clone()).or_default();
+                *mine = (*mine).max(*value);
+            }
+        }
+        self.expires_at = self.expires_at.max(other.expires_at);
+    }
+}
+
+/// The replicated state of the cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GossipState {
+    /// By `ip` or `ip@tenant`
+    #[serde(default)]
+    pub bans: BTreeMap<BanKey, BanRecord>,
+    /// By key and field, e.g. `quota:alice:day:2026-03-01/bytes`
+    #[serde(default)]
+    pub counters: BTreeMap<String, SharedCounter>,
+}
+
+impl GossipState {
+    /// Merge `other` in. Commutative, associative and idempotent.
+    pub fn merge(&mut self, other: &GossipState) {
+        for (key, theirs) in &other.bans {
+            match self.bans.get_mut(key) {
+                Some(mine) if mine.order() >= theirs.order() => {}
+                Some(mine) => *mine = *theirs,
+                None => {
+                    self.bans.insert(key.clone(), *theirs);
+                }
+            }
+        }
+        for (key, theirs) in &other.counters {
+            self.counters.entry(key.clone()).or_default().merge(theirs);
+        }
+    }
+
+    /// Drop bans and counters past their expiry.
+    pub fn prune(&mut self, now: u64) {
+        self.bans.retain(|_, ban| ban.expires_at > now);
+        self.counters
+            .retain(|_, counter| counter.expires_at.is_none_or(|at| at > now));
+    }
+
+    /// Bans in force, by IP and tenant.
+    pub fn live_bans(&self, now: u64) -> BanMap {
+        self.bans
+            .iter()
+            .filter(|(_, ban)| !ban.lifted && ban.expires_at > now)
+            .map(|(key, ban)| (key.clone(), ban.expires_at))
+            .collect()
+    }
+
+    /// Record `changes` made by this node at `now_ms`.
+    pub fn write_bans(&mut self, changes: &BanChanges, now_ms: u64) {
+        for (key, expires_at) in &changes.publish {
+            self.bans.insert(
+                key.clone(),
+                BanRecord {
+                    expires_at: *expires_at,
+                    updated_at: now_ms,
+                    lifted: false,
+                },
+            );
+        }
+        for key in &changes.withdraw {
+            if let Some(ban) = self.bans.get_mut(key) {
+                ban.lifted = true;
+                ban.updated_at = now_ms;
+            }
+        }
+    }
+
+    /// Apply this node's counter updates; returns the cluster totals.
+    pub fn update_counters(&mut self, node_id: &str, ops: &[CounterOp], now: u64) -> Vec<u64> {
+        ops.iter()
+            .map(|op| {
+                let key = format!("{}/{}", op.key, op.field);
+                if op.update == CounterUpdate::Add(0) {
+                    return self.counters.get(&key).map_or(0, SharedCounter::value);
+                }
+                let counter = self.counters.entry(key).or_default();
+                match op.update {
+                    CounterUpdate::Add(n) => {
+                        let mine = counter.nodes.entry(node_id.to_string()).or_default();
+                        *mine = mine.saturating_add(n);
+                    }
+                    CounterUpdate::Set(n) => {
+                        counter.epoch += 1;
+                        counter.nodes = BTreeMap::from([(node_id.to_string(), n)]);
+                    }
+                }
+                if let Some(ttl) = op.ttl_secs {
+                    counter.expires_at = counter.expires_at.max(Some(now + ttl));
+                }
+                counter.value()
+            })
+            .collect()
+    }
+}
+
+/// One exchange, sent by a node and answered by its peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipMessage {
+    pub node_id: String,
+    pub state: GossipState,
+    /// The sender's sessions (None with `share_sessions = false`)
+    #[serde(default)]
+    pub report: Option<NodeReport>,
+}
+
+/// This node's replica and its peers.
+pub struct Gossip {
+    node_id: String,
+    secret: String,
+    peers: Vec<String>,
+    client: reqwest::Client,
+    state: Mutex<GossipState>,
+    /// Latest session list of each node, this one included
+    reports: Mutex<BTreeMap<String, NodeReport>>,
+}
+
+impl Gossip {
+    pub fn new(config: &ClusterConfig, node_id: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default();
+        Self {
+            node_id: node_id.to_string(),
+            secret: config.secret.clone().unwrap_or_default(),
+            peers: config.peers.clone(),
+            client,
+            state: Mutex::new(GossipState::default()),
+            reports: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Run `f` on the replica.
+    pub fn with_state<T>(&self, f: impl FnOnce(&mut GossipState) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn reports(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, NodeReport>> {
+        self.reports.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record this node's sessions, sent with the next exchanges.
+    pub fn publish(&self, report: NodeReport) {
+        self.reports().insert(report.node_id.clone(), report);
+    }
+
+    /// Session lists of the nodes heard from within `max_age`.
+    pub fn nodes(&self, max_age: Duration) -> Vec<NodeReport> {
+        let oldest = chrono::Utc::now() - chrono::Duration::seconds(max_age.as_secs() as i64);
+        let mut reports = self.reports();
+        reports.retain(|_, report| report.updated_at >= oldest);
+        reports.values().cloned().collect()
+    }
+
+    fn message(&self) -> Result<String> {
+        let message = GossipMessage {
+            node_id: self.node_id.clone(),
+            state: self.with_state(|state| state.clone()),
+            report: self.reports().get(&self.node_id).cloned(),
+        };
+        Ok(serde_json::to_string(&message)?)
+    }
+
+    /// Check and merge a peer's message (request or answer).
+    fn absorb(&self, body: &str, signature: &str) -> Result<()> {
+        let expected = crate::webhooks::signature(&self.secret, body)?;
+        if !bool::from(subtle::ConstantTimeEq::ct_eq(
+            expected.as_bytes(),
+            signature.as_bytes(),
+        )) {
+            anyhow::bail!("bad signature");
+        }
+        let message: GossipMessage = serde_json::from_str(body).context("malformed message")?;
+        if message.node_id == self.node_id {
+            anyhow::bail!("peer uses this node's node_id '{}'", self.node_id);
+        }
+        self.with_state(|state| {
+            state.merge(&message.state);
+            state.prune(super::unix_secs());
+        });
+        if let Some(report) = message.report {
+            if report.node_id == message.node_id {
+                self.reports().insert(report.node_id.clone(), report);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a peer's exchange: merge its state and answer with ours.
+    pub fn receive(&self, body: &str, signature: &str) -> Result<(String, String)> {
+        self.absorb(body, signature)?;
+        let reply = self.message()?;
+        let signature = crate::webhooks::signature(&self.secret, &reply)?;
+        Ok((reply, signature))
+    }
+
+    /// Exchange states with every peer. Fails if any peer could not be
+    /// reached, after trying them all.
+    pub async fn exchange(&self) -> Result<()> {
+        self.with_state(|state| state.prune(super::unix_secs()));
+        let body = self.message()?;
+        let signature = crate::webhooks::signature(&self.secret, &body)?;
+        let mut failed = Vec::new();
+        for peer in &self.peers {
+            if let Err(e) = self.exchange_with(peer, &body, &signature).await {
+                failed.push(format!("{}: {:#}", peer, e));
+            }
+        }
+        if !failed.is_empty() {
+            anyhow::bail!("gossip with {}", failed.join("; "));
+        }
+        Ok(())
+    }
+
+    async fn exchange_with(&self, peer: &str, body: &str, signature: &str) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("http://{}{}", peer, GOSSIP_PATH))
+            .header(SIGNATURE_HEADER, signature)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        let signature = response
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let reply = response.text().await?;
+        self.absorb(&reply, &signature)
+    }
+
+    /// Accept exchanges from peers on `listen` until `shutdown`.
+    pub async fn serve(self: &Arc<Self>, listen: &str, shutdown: CancellationToken) -> Result<()> {
+        let listener = crate::handover::global()
+            .bind("cluster", listen)
+            .await
+            .with_context(|| format!("binding cluster.listen {}", listen))?;
+        info!(listen = %listen, peers = self.peers.len(), "Gossip listener started");
+        let app = axum::Router::new()
+            .route(GOSSIP_PATH, axum::routing::post(receive))
+            .layer(DefaultBodyLimit::max(MAX_MESSAGE))
+            .with_state(self.clone());
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async move { shutdown.cancelled().await })
+                .await;
+        });
+        Ok(())
+    }
+}
+
+async fn receive(
+    State(gossip): State<Arc<Gossip>>,
+    headers: HeaderMap,
+    body: String,
+) -> impl IntoResponse {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match gossip.receive(&body, signature) {
+        Ok((reply, signature)) => (
+            [
+                (SIGNATURE_HEADER, signature),
+                ("Content-Type", "application/json".to_string()),
+            ],
+            reply,
+        )
+            .into_response(),
+        Err(e) => {
+            debug!(error = %format!("{:#}", e), "Rejected gossip message");
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
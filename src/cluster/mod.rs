@@ -0,0 +1,722 @@
+//! Cluster mode (`[cluster]`): several instances behind a load balancer
+//! share bans, quota counters and session lists, through Redis or directly
+//! between peers (see [`gossip`]).
+//!
+//! Every `sync_interval_secs` each node:
+//! - publishes its new bans to a shared hash (`ip` or `ip@tenant` ->
+//!   expiry), enforces the
+//!   bans of other nodes and lifts those removed elsewhere
+//! - adds each user's counter growth since the last sync to shared per-day,
+//!   per-month and lifetime counters, then takes the cluster totals as its
+//!   own; a counter that went down locally (a reset) overwrites the shared
+//!   one
+//! - publishes its live sessions under its `node_id`, listed by
+//!   `GET /api/cluster/sessions`
+//!
+//! An observer (`observer = true`) only reads: it enforces nothing, since
+//! it relays nothing, and follows the shared bans and the counters of every
+//! configured user for its API and dashboard.
+//!
+//! Quotas are still enforced locally, so a user can overshoot by what other
+//! nodes relay within one interval. Hourly windows and rate limits stay per
+//! node. The backend being unreachable never blocks traffic: nodes carry on
+//! alone and catch up on the next successful sync.
+
+pub mod gossip;
+pub mod redis;
+
+use crate::config::types::{AppConfig, ClusterBackend, ClusterConfig};
+use crate::proxy::{ProxyEngine, SessionSnapshot};
+use crate::quota::{QuotaTracker, UsageCounters};
+use crate::security::ban::BanKey;
+use crate::security::SecurityManager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use gossip::Gossip;
+use redis::{cmd, Command, RedisConnection, Reply};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// A node's session list outlives its last sync by this many intervals.
+const NODE_TTL_INTERVALS: u64 = 3;
+
+/// Shared daily counters expire this long after their last update.
+const DAY_KEY_TTL_SECS: u64 = 2 * 86400;
+
+/// Shared monthly counters expire this long after their last update.
+const MONTH_KEY_TTL_SECS: u64 = 32 * 86400;
+
+/// Bans by IP and tenant, with their expiry in unix seconds.
+pub type BanMap = HashMap<BanKey, u64>;
+
+/// What one sync changes, locally and in the shared hash.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BanChanges {
+    /// Local bans to publish
+    pub publish: Vec<(BanKey, u64)>,
+    /// Shared bans to delete: lifted here, or expired
+    pub withdraw: Vec<BanKey>,
+    /// Bans of other nodes to enforce here
+    pub apply: Vec<(BanKey, u64)>,
+    /// Local bans lifted on another node
+    pub lift: Vec<BanKey>,
+}
+
+/// Shared bans as of the last sync, to tell new bans from lifted ones.
+#[derive(Debug, Default)]
+pub struct BanSync {
+    seen: HashSet<BanKey>,
+}
+
+impl BanSync {
+    /// Compare this node's bans with the shared ones.
+    pub fn reconcile(&self, local: &BanMap, shared: &BanMap, now: u64) -> BanChanges {
+        let mut changes = BanChanges::default();
+        let keys: BTreeSet<BanKey> = local.keys().chain(shared.keys()).cloned().collect();
+        for key in keys {
+            let seen = self.seen.contains(&key);
+            match (local.get(&key).copied(), shared.get(&key).copied()) {
+                (Some(_), None) if seen => changes.lift.push(key.clone()),
+                (Some(l), None) => changes.publish.push((key.clone(), l)),
+                (None, Some(s)) if s <= now || seen => changes.withdraw.push(key.clone()),
+                (None, Some(s)) => changes.apply.push((key.clone(), s)),
+                // Banned again on either side: the later expiry wins (local
+                // expiries are rounded down, hence the second of slack)
+                (Some(l), Some(s)) if l > s + 1 => changes.publish.push((key.clone(), l)),
+                (Some(l), Some(s)) if s > l + 1 => changes.apply.push((key.clone(), s)),
+                _ => {}
+            }
+        }
+        changes
+    }
+
+    /// Record the shared bans once `changes` are written.
+    pub fn commit(&mut self, shared: &BanMap, changes: &BanChanges) {
+        self.seen = shared
+            .keys()
+            .chain(changes.publish.iter().map(|(key, _)| key))
+            .filter(|key| !changes.withdraw.contains(key))
+            .cloned()
+            .collect();
+    }
+}
+
+/// How one shared counter is brought up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterUpdate {
+    /// Add this node's growth; the reply is the cluster total
+    Add(u64),
+    /// The counter went down here: overwrite the shared one
+    Set(u64),
+}
+
+impl CounterUpdate {
+    pub fn new(local: u64, baseline: u64) -> Self {
+        if local >= baseline {
+            Self::Add(local - baseline)
+        } else {
+            Self::Set(local)
+        }
+    }
+}
+
+/// One shared counter update: the counter's key and field, and how long
+/// the key lives after the update (None = forever).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CounterOp {
+    pub key: String,
+    pub field: &'static str,
+    pub update: CounterUpdate,
+    pub ttl_secs: Option<u64>,
+}
+
+/// A user's shared counters as of the last sync.
+#[derive(Debug, Clone, Default)]
+struct QuotaBaseline {
+    day: String,
+    month: String,
+    counters: UsageCounters,
+}
+
+impl QuotaBaseline {
+    /// The baseline for `day` and `month`: a new period starts from zero.
+    fn in_period(&self, day: &str, month: &str) -> UsageCounters {
+        let mut counters = self.counters;
+        if self.day != day {
+            counters.daily_bytes = 0;
+            counters.daily_connections = 0;
+        }
+        if self.month != month {
+            counters.monthly_bytes = 0;
+            counters.monthly_connections = 0;
+        }
+        counters
+    }
+}
+
+/// Sync state kept by the background task.
+#[derive(Default)]
+pub struct SyncState {
+    bans: BanSync,
+    quotas: HashMap<String, QuotaBaseline>,
+}
+
+/// One node's entry in the shared session registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    pub node_id: String,
+    pub updated_at: DateTime<Utc>,
+    pub sessions: Vec<SessionSnapshot>,
+}
+
+/// Outcome of the last syncs, for `GET /api/cluster`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClusterStatus {
+    pub last_sync_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub failed_syncs: u64,
+}
+
+type RedisSlot = tokio::sync::Mutex<Option<RedisConnection<Box<dyn redis::Stream>>>>;
+
+enum Backend {
+    /// Connected on first use
+    Redis(RedisSlot),
+    Gossip(Arc<Gossip>),
+}
+
+/// Connection to the shared state backend.
+pub struct Cluster {
+    config: ClusterConfig,
+    node_id: String,
+    /// Users whose counters an observer follows
+    users: Vec<String>,
+    backend: Backend,
+    status: std::sync::Mutex<ClusterStatus>,
+}
+
+impl Cluster {
+    /// `None` unless `[cluster] enabled`.
+    pub fn new(config: &AppConfig) -> Option<Arc<Self>> {
+        let cluster = &config.cluster;
+        if !cluster.enabled {
+            return None;
+        }
+        let node_id = if cluster.node_id.is_empty() {
+            host_name()
+        } else {
+            cluster.node_id.clone()
+        };
+        let backend = match cluster.backend {
+            ClusterBackend::Redis => Backend::Redis(tokio::sync::Mutex::new(None)),
+            ClusterBackend::Gossip => Backend::Gossip(Arc::new(Gossip::new(cluster, &node_id))),
+        };
+        let users = match cluster.observer {
+            true => config.users.iter().map(|u| u.username.clone()).collect(),
+            false => Vec::new(),
+        };
+        Some(Arc::new(Self {
+            config: cluster.clone(),
+            node_id,
+            users,
+            backend,
+            status: std::sync::Mutex::new(ClusterStatus::default()),
+        }))
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn config(&self) -> &ClusterConfig {
+        &self.config
+    }
+
+    /// Read-only instance (`observer = true`).
+    pub fn is_observer(&self) -> bool {
+        self.config.observer
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.config.key_prefix, name)
+    }
+
+    fn node_key(&self, node_id: &str) -> String {
+        self.key(&format!("node:{}", node_id))
+    }
+
+    /// The peer-to-peer backend, with `backend = "gossip"`.
+    pub fn gossip(&self) -> Option<&Arc<Gossip>> {
+        match &self.backend {
+            Backend::Gossip(gossip) => Some(gossip),
+            Backend::Redis(_) => None,
+        }
+    }
+
+    /// With `backend = "gossip"`, accept exchanges from peers on
+    /// `cluster.listen` until `shutdown`.
+    pub async fn listen(&self, shutdown: CancellationToken) -> Result<()> {
+        match self.gossip() {
+            Some(gossip) => gossip.serve(&self.config.listen, shutdown).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Run `commands` as one Redis pipeline, connecting first if needed. A
+    /// failed connection is dropped and reopened on the next call.
+    async fn run(&self, commands: &[Command]) -> Result<Vec<Reply>> {
+        let Backend::Redis(conn) = &self.backend else {
+            anyhow::bail!("not a Redis cluster backend");
+        };
+        let mut conn = conn.lock().await;
+        if conn.is_none() {
+            *conn = Some(redis::connect(&self.config).await?);
+        }
+        let result = match conn.as_mut() {
+            Some(c) => c.pipeline(commands).await,
+            None => Ok(Vec::new()),
+        };
+        if result.is_err() {
+            *conn = None;
+        }
+        result
+    }
+
+    /// Session lists of every live node, by node ID. Entries of nodes that
+    /// stopped syncing are removed.
+    pub async fn nodes(&self) -> Result<Vec<NodeReport>> {
+        if let Some(gossip) = self.gossip() {
+            return Ok(gossip.nodes(self.node_ttl()));
+        }
+        let nodes_key = self.key("nodes");
+        let ids: Vec<String> = match self
+            .run(&[cmd(&["SMEMBERS", nodes_key.as_str()])])
+            .await?
+            .pop()
+        {
+            Some(Reply::Array(ids)) => ids
+                .into_iter()
+                .filter_map(|r| r.as_text().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut mget = vec!["MGET".to_string()];
+        mget.extend(ids.iter().map(|id| self.node_key(id)));
+        let reports = match self.run(&[mget]).await?.pop() {
+            Some(Reply::Array(reports)) => reports,
+            _ => Vec::new(),
+        };
+        let mut nodes = Vec::new();
+        let mut gone = vec!["SREM".to_string(), nodes_key];
+        for (id, report) in ids.into_iter().zip(reports) {
+            match report {
+                Reply::Text(json) => match serde_json::from_str::<NodeReport>(&json) {
+                    Ok(node) => nodes.push(node),
+                    Err(e) => warn!(node = %id, error = %e, "Ignoring unreadable cluster node"),
+                },
+                _ => gone.push(id),
+            }
+        }
+        if gone.len() > 2 {
+            let _ = self.run(&[gone]).await;
+        }
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        Ok(nodes)
+    }
+
+    /// Exchange bans, quota counters and sessions with the backend once.
+    pub async fn sync_once(
+        &self,
+        security: &RwLock<SecurityManager>,
+        tracker: &QuotaTracker,
+        engine: &ProxyEngine,
+        state: &mut SyncState,
+    ) -> Result<()> {
+        if self.config.share_bans {
+            self.sync_bans(security, &mut state.bans).await?;
+        }
+        if self.config.share_quotas {
+            self.sync_quotas(tracker, &mut state.quotas, Utc::now())
+                .await?;
+        }
+        if self.config.share_sessions && !self.is_observer() {
+            let report = NodeReport {
+                node_id: self.node_id.clone(),
+                updated_at: Utc::now(),
+                sessions: engine.get_sessions(),
+            };
+            match self.gossip() {
+                Some(gossip) => gossip.publish(report),
+                None => {
+                    self.run(&[
+                        cmd(&[
+                            "SET".to_string(),
+                            self.node_key(&self.node_id),
+                            serde_json::to_string(&report)?,
+                            "EX".to_string(),
+                            self.node_ttl().as_secs().to_string(),
+                        ]),
+                        cmd(&["SADD".to_string(), self.key("nodes"), self.node_id.clone()]),
+                    ])
+                    .await?;
+                }
+            }
+        }
+        if let Some(gossip) = self.gossip() {
+            gossip.exchange().await?;
+        }
+        Ok(())
+    }
+
+    /// How long a node's session list outlives its last sync.
+    fn node_ttl(&self) -> Duration {
+        Duration::from_secs(self.config.sync_interval_secs * NODE_TTL_INTERVALS)
+    }
+
+    async fn sync_bans(
+        &self,
+        security: &RwLock<SecurityManager>,
+        bans: &mut BanSync,
+    ) -> Result<()> {
+        let bans_key = self.key("bans");
+        let now = unix_secs();
+        let local: BanMap = {
+            let security = security.read().await;
+            let at = Instant::now();
+            security
+                .ban_manager()
+                .banned_ips()
+                .into_iter()
+                .map(|(key, expiry)| (key, now + expiry.saturating_duration_since(at).as_secs()))
+                .collect()
+        };
+        let shared: BanMap = match self.gossip() {
+            Some(gossip) => gossip.with_state(|state| state.live_bans(now)),
+            None => self
+                .run(&[cmd(&["HGETALL", bans_key.as_str()])])
+                .await?
+                .pop()
+                .map(Reply::into_pairs)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(key, expiry)| Some((key.parse().ok()?, expiry.parse().ok()?)))
+                .collect(),
+        };
+
+        let mut changes = bans.reconcile(&local, &shared, now);
+        if self.is_observer() {
+            changes.publish.clear();
+            changes.withdraw.clear();
+        }
+        match self.gossip() {
+            Some(gossip) => {
+                gossip.with_state(|state| state.write_bans(&changes, unix_millis()));
+            }
+            None => {
+                let mut commands: Vec<Command> = changes
+                    .publish
+                    .iter()
+                    .map(|(key, expiry)| {
+                        cmd(&[
+                            "HSET".to_string(),
+                            bans_key.clone(),
+                            key.to_string(),
+                            expiry.to_string(),
+                        ])
+                    })
+                    .collect();
+                commands.extend(
+                    changes
+                        .withdraw
+                        .iter()
+                        .map(|key| cmd(&["HDEL".to_string(), bans_key.clone(), key.to_string()])),
+                );
+                self.run(&commands).await?;
+            }
+        }
+        bans.commit(&shared, &changes);
+
+        if !changes.apply.is_empty() || !changes.lift.is_empty() {
+            let security = security.read().await;
+            let manager = security.ban_manager();
+            for (key, expiry) in &changes.apply {
+                manager.import_ban(key.clone(), Duration::from_secs(expiry.saturating_sub(now)));
+            }
+            for key in &changes.lift {
+                manager.unban(key.clone());
+            }
+            info!(
+                applied = changes.apply.len(),
+                lifted = changes.lift.len(),
+                "Bans synced from the cluster"
+            );
+        }
+        Ok(())
+    }
+
+    async fn sync_quotas(
+        &self,
+        tracker: &QuotaTracker,
+        baselines: &mut HashMap<String, QuotaBaseline>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+        let mut users = tracker.tracked_users();
+        for user in &self.users {
+            if !users.contains(user) {
+                users.push(user.clone());
+            }
+        }
+        baselines.retain(|user, _| users.contains(user));
+
+        let mut ops = Vec::new();
+        let mut pending = Vec::new();
+        for user in users {
+            let local = tracker.get_user_usage(&user).counters();
+            let base = baselines
+                .get(&user)
+                .map(|b| b.in_period(&day, &month))
+                .unwrap_or_default();
+            let day_key = format!("quota:{}:day:{}", user, day);
+            let month_key = format!("quota:{}:month:{}", user, month);
+            let total_key = format!("quota:{}:total", user);
+            let day_ttl = Some(DAY_KEY_TTL_SECS);
+            let month_ttl = Some(MONTH_KEY_TTL_SECS);
+            let counters = [
+                (
+                    &day_key,
+                    "bytes",
+                    local.daily_bytes,
+                    base.daily_bytes,
+                    day_ttl,
+                ),
+                (
+                    &day_key,
+                    "connections",
+                    local.daily_connections.into(),
+                    base.daily_connections.into(),
+                    day_ttl,
+                ),
+                (
+                    &month_key,
+                    "bytes",
+                    local.monthly_bytes,
+                    base.monthly_bytes,
+                    month_ttl,
+                ),
+                (
+                    &month_key,
+                    "connections",
+                    local.monthly_connections.into(),
+                    base.monthly_connections.into(),
+                    month_ttl,
+                ),
+                (
+                    &total_key,
+                    "bytes",
+                    local.total_bytes,
+                    base.total_bytes,
+                    None,
+                ),
+            ];
+            for (key, field, local, base, ttl_secs) in counters {
+                ops.push(CounterOp {
+                    key: key.clone(),
+                    field,
+                    update: CounterUpdate::new(local, base),
+                    ttl_secs,
+                });
+            }
+            pending.push((user, local));
+        }
+        let totals = self
+            .update_counters(&ops, now.timestamp().max(0) as u64)
+            .await?;
+
+        for ((user, local), totals) in pending.into_iter().zip(totals.chunks(5)) {
+            let values: Option<Vec<u64>> = totals.iter().copied().collect();
+            let Some(&[day_bytes, day_conns, month_bytes, month_conns, total_bytes]) =
+                values.as_deref()
+            else {
+                warn!(user = %user, "Unexpected reply syncing quota counters");
+                continue;
+            };
+            let shared = UsageCounters {
+                daily_bytes: day_bytes,
+                daily_connections: day_conns.min(u32::MAX.into()) as u32,
+                monthly_bytes: month_bytes,
+                monthly_connections: month_conns.min(u32::MAX.into()) as u32,
+                total_bytes,
+            };
+            tracker.rebase_user_usage(&user, &local, &shared);
+            baselines.insert(
+                user,
+                QuotaBaseline {
+                    day: day.clone(),
+                    month: month.clone(),
+                    counters: shared,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Apply `ops` to the shared counters; returns each counter's cluster
+    /// total (None for an unexpected reply). Adding nothing only reads.
+    async fn update_counters(&self, ops: &[CounterOp], now: u64) -> Result<Vec<Option<u64>>> {
+        if let Some(gossip) = self.gossip() {
+            let totals = gossip.with_state(|state| state.update_counters(&self.node_id, ops, now));
+            return Ok(totals.into_iter().map(Some).collect());
+        }
+        let mut commands: Vec<Command> = ops
+            .iter()
+            .map(|op| {
+                let (verb, n) = match op.update {
+                    CounterUpdate::Add(0) => {
+                        return cmd(&["HGET".to_string(), self.key(&op.key), op.field.to_string()]);
+                    }
+                    CounterUpdate::Add(n) => ("HINCRBY", n),
+                    CounterUpdate::Set(n) => ("HSET", n),
+                };
+                cmd(&[
+                    verb.to_string(),
+                    self.key(&op.key),
+                    op.field.to_string(),
+                    n.to_string(),
+                ])
+            })
+            .collect();
+        let expiring: BTreeSet<(&str, u64)> = ops
+            .iter()
+            .filter(|op| op.update != CounterUpdate::Add(0))
+            .filter_map(|op| Some((op.key.as_str(), op.ttl_secs?)))
+            .collect();
+        commands.extend(
+            expiring
+                .into_iter()
+                .map(|(key, ttl)| cmd(&["EXPIRE".to_string(), self.key(key), ttl.to_string()])),
+        );
+        let replies = self.run(&commands).await?;
+        Ok(ops
+            .iter()
+            .zip(&replies)
+            .map(|(op, reply)| match op.update {
+                CounterUpdate::Set(n) => Some(n),
+                CounterUpdate::Add(_) if *reply == Reply::Nil => Some(0),
+                CounterUpdate::Add(_) => reply.as_int().map(|v| v.max(0) as u64),
+            })
+            .collect())
+    }
+
+    /// Remove this node's session list (on shutdown).
+    async fn leave(&self) -> Result<()> {
+        if self.gossip().is_some() {
+            return Ok(());
+        }
+        self.run(&[
+            cmd(&["DEL".to_string(), self.node_key(&self.node_id)]),
+            cmd(&["SREM".to_string(), self.key("nodes"), self.node_id.clone()]),
+        ])
+        .await
+        .map(|_| ())
+    }
+
+    fn record(&self, result: &Result<()>) {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => {
+                status.last_sync_at = Some(Utc::now());
+                status.last_error = None;
+            }
+            Err(e) => {
+                status.last_error = Some(format!("{:#}", e));
+                status.failed_syncs += 1;
+            }
+        }
+    }
+
+    /// Sync every `sync_interval_secs` until `shutdown`, then sync once more
+    /// and leave the session registry. Startup-only: not hot-reloaded.
+    pub fn spawn(
+        self: &Arc<Self>,
+        security: Arc<RwLock<SecurityManager>>,
+        tracker: Arc<QuotaTracker>,
+        engine: Arc<ProxyEngine>,
+        shutdown: CancellationToken,
+    ) {
+        let cluster = self.clone();
+        info!(
+            node_id = %cluster.node_id,
+            backend = ?cluster.config.backend,
+            observer = cluster.config.observer,
+            interval_secs = cluster.config.sync_interval_secs,
+            "Cluster mode enabled"
+        );
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(cluster.config.sync_interval_secs));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut state = SyncState::default();
+            let mut healthy = true;
+            loop {
+                let stopping = tokio::select! {
+                    _ = shutdown.cancelled() => true,
+                    _ = interval.tick() => false,
+                };
+                let result = cluster
+                    .sync_once(&security, &tracker, &engine, &mut state)
+                    .await;
+                match (&result, healthy) {
+                    (Err(e), true) => warn!(error = %format!("{:#}", e), "Cluster sync failed"),
+                    (Ok(()), false) => info!("Cluster sync recovered"),
+                    _ => {}
+                }
+                healthy = result.is_ok();
+                cluster.record(&result);
+                if stopping {
+                    if cluster.config.share_sessions {
+                        let _ = cluster.leave().await;
+                    }
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn unix_secs() -> u64 {
+    unix_millis() / 1000
+}
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Default `node_id`: the machine's host name.
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+        .unwrap_or_else(|| "s5".to_string())
+}
@@ -0,0 +1,308 @@
+//! Minimal Redis client (RESP2) for the cluster state backend.
+//!
+//! Commands are pipelined: a batch is written at once and its replies read
+//! back in order. `rediss://` URLs use TLS, with server certificates checked
+//! against the Mozilla root store.
+
+use crate::config::types::ClusterConfig;
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+/// Longest status or length line accepted.
+const MAX_LINE: usize = 4096;
+
+/// Largest bulk string accepted (a node's session list).
+const MAX_BULK: usize = 64 * 1024 * 1024;
+
+/// Most elements accepted in one array reply.
+const MAX_ARRAY: usize = 1_000_000;
+
+/// Where the backend is, from `cluster.url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisAddr {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub db: u32,
+}
+
+impl RedisAddr {
+    /// Parse `redis://host[:port][/db]` or `rediss://...`. Credentials go in
+    /// `cluster.username` / `cluster.password`, not in the URL.
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("invalid URL '{}': {}", url, e))?;
+        let tls = match parsed.scheme() {
+            "redis" => false,
+            "rediss" => true,
+            other => {
+                return Err(format!(
+                    "unsupported scheme '{}' (expected redis or rediss)",
+                    other
+                ))
+            }
+        };
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            return Err("credentials belong in cluster.username and cluster.password".to_string());
+        }
+        let host = parsed
+            .host_str()
+            .filter(|h| !h.is_empty())
+            .ok_or_else(|| format!("missing host in '{}'", url))?;
+        let db = match parsed.path().trim_start_matches('/') {
+            "" => 0,
+            db => db
+                .parse()
+                .map_err(|_| format!("invalid database '{}' in '{}'", db, url))?,
+        };
+        if parsed.query().is_some() || parsed.fragment().is_some() {
+            return Err(format!("unexpected query in '{}'", url));
+        }
+        Ok(Self {
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port: parsed.port().unwrap_or(6379),
+            tls,
+            db,
+        })
+    }
+}
+
+/// One reply. Simple and bulk strings are both [`Reply::Text`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Nil,
+    Int(i64),
+    Text(String),
+    Array(Vec<Reply>),
+    /// `-ERR ...` for one command of a pipeline
+    Error(String),
+}
+
+impl Reply {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Reply::Int(n) => Some(*n),
+            Reply::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Reply::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Field/value pairs of an `HGETALL` reply.
+    pub fn into_pairs(self) -> Vec<(String, String)> {
+        let Reply::Array(items) = self else {
+            return Vec::new();
+        };
+        let mut pairs = Vec::with_capacity(items.len() / 2);
+        let mut items = items.into_iter();
+        while let (Some(Reply::Text(k)), Some(Reply::Text(v))) = (items.next(), items.next()) {
+            pairs.push((k, v));
+        }
+        pairs
+    }
+}
+
+/// A command and its arguments.
+pub type Command = Vec<String>;
+
+/// Build a command from its words.
+pub fn cmd<S: ToString>(words: &[S]) -> Command {
+    words.iter().map(|w| w.to_string()).collect()
+}
+
+/// RESP encoding of `command`: an array of bulk strings.
+pub fn encode(command: &[String]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", command.len()).into_bytes();
+    for word in command {
+        out.extend_from_slice(format!("${}\r\n", word.len()).as_bytes());
+        out.extend_from_slice(word.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// A connected stream, plain or TLS.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Connect to `config.url`, authenticate and select the database.
+pub async fn connect(config: &ClusterConfig) -> Result<RedisConnection<Box<dyn Stream>>> {
+    let addr = RedisAddr::parse(&config.url).map_err(anyhow::Error::msg)?;
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let tcp = tokio::time::timeout(timeout, TcpStream::connect((addr.host.as_str(), addr.port)))
+        .await
+        .context("Redis connect timed out")?
+        .with_context(|| format!("connecting to {}:{}", addr.host, addr.port))?;
+    let _ = tcp.set_nodelay(true);
+    let stream: Box<dyn Stream> = if addr.tls {
+        Box::new(tls_connect(&addr.host, tcp).await?)
+    } else {
+        Box::new(tcp)
+    };
+    let mut conn = RedisConnection::new(stream, timeout);
+    if let Some(ref password) = config.password {
+        let auth = match config.username {
+            Some(ref user) => cmd(&["AUTH", user.as_str(), password.as_str()]),
+            None => cmd(&["AUTH", password.as_str()]),
+        };
+        conn.query(auth).await.context("Redis AUTH")?;
+    }
+    if addr.db != 0 {
+        conn.query(cmd(&["SELECT".to_string(), addr.db.to_string()]))
+            .await
+            .context("Redis SELECT")?;
+    }
+    Ok(conn)
+}
+
+async fn tls_connect(
+    host: &str,
+    tcp: TcpStream,
+) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())
+        .with_context(|| format!("invalid Redis server name: {}", host))?;
+    tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .connect(name, tcp)
+        .await
+        .context("Redis TLS handshake")
+}
+
+/// One Redis connection over an established stream.
+pub struct RedisConnection<S> {
+    stream: BufReader<S>,
+    timeout: Duration,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> RedisConnection<S> {
+    pub fn new(stream: S, timeout: Duration) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            timeout,
+        }
+    }
+
+    /// Run one command; an error reply is an error.
+    pub async fn query(&mut self, command: Command) -> Result<Reply> {
+        let reply = self.pipeline(&[command]).await?.pop().unwrap_or(Reply::Nil);
+        match reply {
+            Reply::Error(e) => anyhow::bail!("Redis error: {}", e),
+            reply => Ok(reply),
+        }
+    }
+
+    /// Send `commands` at once and read their replies, in order. Error
+    /// replies are returned as [`Reply::Error`] so the stream stays in step.
+    pub async fn pipeline(&mut self, commands: &[Command]) -> Result<Vec<Reply>> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut out = Vec::new();
+        for command in commands {
+            out.extend_from_slice(&encode(command));
+        }
+        tokio::time::timeout(self.timeout, async {
+            let stream = self.stream.get_mut();
+            stream.write_all(&out).await?;
+            stream.flush().await
+        })
+        .await
+        .context("Redis write timed out")??;
+
+        let mut replies = Vec::with_capacity(commands.len());
+        for _ in commands {
+            let reply = tokio::time::timeout(self.timeout, self.read_reply(0))
+                .await
+                .context("Redis reply timed out")??;
+            replies.push(reply);
+        }
+        Ok(replies)
+    }
+
+    async fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        let n = (&mut self.stream)
+            .take(MAX_LINE as u64 + 2)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if n == 0 {
+            anyhow::bail!("Redis server closed the connection");
+        }
+        if !line.ends_with(b"\r\n") {
+            anyhow::bail!("malformed Redis reply line");
+        }
+        line.truncate(line.len() - 2);
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn read_reply(
+        &mut self,
+        depth: u8,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Reply>> + Send + '_>> {
+        Box::pin(async move {
+            let line = self.read_line().await?;
+            let Some(kind) = line.bytes().next() else {
+                anyhow::bail!("empty Redis reply line");
+            };
+            let rest = line.get(1..).unwrap_or_default();
+            let len = || -> Result<i64> {
+                rest.parse()
+                    .with_context(|| format!("malformed Redis length: {:?}", rest))
+            };
+            match kind {
+                b'+' => Ok(Reply::Text(rest.to_string())),
+                b'-' => Ok(Reply::Error(rest.to_string())),
+                b':' => Ok(Reply::Int(len()?)),
+                b'$' => {
+                    let n = len()?;
+                    if n < 0 {
+                        return Ok(Reply::Nil);
+                    }
+                    let n = n as usize;
+                    if n > MAX_BULK {
+                        anyhow::bail!("Redis reply too large ({} bytes)", n);
+                    }
+                    let mut data = vec![0u8; n + 2];
+                    self.stream.read_exact(&mut data).await?;
+                    if !data.ends_with(b"\r\n") {
+                        anyhow::bail!("malformed Redis bulk string");
+                    }
+                    data.truncate(n);
+                    Ok(Reply::Text(String::from_utf8_lossy(&data).into_owned()))
+                }
+                b'*' => {
+                    let n = len()?;
+                    if n < 0 {
+                        return Ok(Reply::Nil);
+                    }
+                    if n as usize > MAX_ARRAY || depth >= 4 {
+                        anyhow::bail!("Redis reply too large");
+                    }
+                    let mut items = Vec::with_capacity(n as usize);
+                    for _ in 0..n {
+                        items.push(self.read_reply(depth + 1).await?);
+                    }
+                    Ok(Reply::Array(items))
+                }
+                _ => anyhow::bail!("malformed Redis reply: {:?}", line),
+            }
+        })
+    }
+}
@@ -338,6 +338,11 @@ impl AclRule {
 }
 
 impl PortMatch {
+    /// Parse "*", "443" or "8000-9000"
+    pub fn parse(port: &str) -> Result<Self, AclError> {
+        parse_port_match(port)
+    }
+
     pub fn matches(&self, port: u16) -> bool {
         match self {
             PortMatch::Any => true,
@@ -20,12 +20,20 @@ pub fn can_build_from_env() -> bool {
     if std::env::var("S5_USER_0_USERNAME").is_ok() {
         return true;
     }
-    // Single-user mode
+    // Single-user mode, or accounts provided by the external auth hook
+    has_single_user_env() || external_auth_env()
+}
+
+fn has_single_user_env() -> bool {
     std::env::var("S5_PASSWORD_HASH").is_ok()
         || std::env::var("S5_PASSWORD_HASH_FILE").is_ok()
         || std::env::var("S5_AUTHORIZED_KEYS").is_ok()
 }
 
+fn external_auth_env() -> bool {
+    opt_env("S5_AUTH_BACKEND").is_some_and(|s| s.eq_ignore_ascii_case("external"))
+}
+
 /// Build a complete AppConfig from environment variables.
 /// Supports both single-user (flat vars) and multi-user (indexed S5_USER_<N>_*) modes.
 pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
@@ -34,6 +42,9 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
     // Determine user list: multi-user indexed mode vs single-user flat mode
     let users = if std::env::var("S5_USER_0_USERNAME").is_ok() {
         collect_indexed_users()?
+    } else if external_auth_env() && !has_single_user_env() {
+        // Users are created by the external auth hook on login
+        Vec::new()
     } else {
         vec![build_single_user_from_env()?]
     };
@@ -56,12 +67,29 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
             socks5_tls_key: opt_env("S5_SOCKS5_TLS_KEY").map(PathBuf::from),
             dns_cache_ttl: parse_env("S5_DNS_CACHE_TTL", -1),
             dns_cache_max_entries: parse_env("S5_DNS_CACHE_MAX_ENTRIES", 1000),
+            dns_prefetch_hosts: parse_env("S5_DNS_PREFETCH_HOSTS", 0),
             connect_retry: parse_env("S5_CONNECT_RETRY", 0),
             connect_retry_delay_ms: parse_env("S5_CONNECT_RETRY_DELAY_MS", 1000),
             bookmarks_path: opt_env("S5_BOOKMARKS_PATH").map(PathBuf::from),
             ssh_keepalive_interval_secs: parse_env("S5_SSH_KEEPALIVE_INTERVAL", 15),
             ssh_keepalive_max: parse_env("S5_SSH_KEEPALIVE_MAX", 3),
             ssh_auth_timeout: parse_env("S5_SSH_AUTH_TIMEOUT", 120),
+            ssh_window_size: parse_env("S5_SSH_WINDOW_SIZE", 2 * 1024 * 1024),
+            ssh_max_packet_size: parse_env("S5_SSH_MAX_PACKET_SIZE", 32 * 1024),
+            ssh_stall_threshold_ms: parse_env("S5_SSH_STALL_THRESHOLD_MS", 5000),
+            max_connections: parse_env("S5_SERVER_MAX_CONNECTIONS", 0),
+            max_connections_per_user: parse_env("S5_SERVER_MAX_CONNECTIONS_PER_USER", 0),
+            connection_queue_timeout_ms: parse_env("S5_CONNECTION_QUEUE_TIMEOUT_MS", 0),
+            connection_queue_size: parse_env("S5_CONNECTION_QUEUE_SIZE", 0),
+            listeners: Vec::new(),
+            host_key_rotation: HostKeyRotationConfig {
+                new_key_path: opt_env("S5_HOST_KEY_NEW_PATH").map(PathBuf::from),
+                retire_after_days: parse_env("S5_HOST_KEY_RETIRE_AFTER_DAYS", 30),
+            },
+            maintenance_message: opt_env("S5_MAINTENANCE_MESSAGE").unwrap_or_else(|| {
+                "Server is under maintenance. Please try again later.".to_string()
+            }),
+            socket_handover: parse_bool_env("S5_SOCKET_HANDOVER", false),
         },
         shell: ShellConfig {
             hostname: opt_env("S5_SHELL_HOSTNAME").unwrap_or_else(|| "s5-proxy".to_string()),
@@ -85,6 +113,18 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
             ),
             udp_relay_timeout: parse_env("S5_UDP_RELAY_TIMEOUT", 300),
             max_udp_sessions_per_user: parse_env("S5_MAX_UDP_SESSIONS_PER_USER", 0),
+            max_unauthenticated_connections: parse_env("S5_MAX_UNAUTHENTICATED_CONNECTIONS", 256),
+            max_unauthenticated_per_ip: parse_env("S5_MAX_UNAUTHENTICATED_PER_IP", 64),
+            overload_policy: opt_env("S5_OVERLOAD_POLICY")
+                .map(|s| parse_overload_policy(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            max_buffered_bytes: parse_env("S5_MAX_BUFFERED_BYTES", 0),
+            max_connection_bytes: parse_env("S5_MAX_CONNECTION_BYTES", 0),
+            upload_alert_bytes: parse_env("S5_UPLOAD_ALERT_BYTES", 0),
+            download_alert_bytes: parse_env("S5_DOWNLOAD_ALERT_BYTES", 0),
+            max_channels_per_connection: parse_env("S5_MAX_CHANNELS_PER_CONNECTION", 10),
+            quota_reset_state_path: opt_env("S5_QUOTA_RESET_STATE_PATH").map(PathBuf::from),
         },
         security: SecurityConfig {
             allowed_source_ips: opt_env("S5_ALLOWED_SOURCE_IPS")
@@ -102,6 +142,8 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
             ban_window: parse_env("S5_BAN_WINDOW", 300),
             ban_duration: parse_env("S5_BAN_DURATION", 900),
             ban_whitelist: parse_csv_env("S5_BAN_WHITELIST"),
+            lock_after_failures: parse_env("S5_LOCK_AFTER_FAILURES", 0),
+            lock_duration: parse_env("S5_LOCK_DURATION", 900),
             ip_guard_enabled: parse_bool_env("S5_IP_GUARD_ENABLED", true),
             totp_required_for: parse_csv_env("S5_TOTP_REQUIRED_FOR"),
             max_new_connections_per_ip_per_minute: parse_env(
@@ -114,9 +156,37 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
             argon2_memory_cost: parse_env("S5_ARGON2_MEMORY_COST", 19456),
             argon2_time_cost: parse_env("S5_ARGON2_TIME_COST", 2),
             argon2_parallelism: parse_env("S5_ARGON2_PARALLELISM", 1),
+            password_hash_algorithm: opt_env("S5_PASSWORD_HASH_ALGORITHM")
+                .map(|s| parse_password_hash_algorithm(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            rehash_on_login: parse_bool_env("S5_REHASH_ON_LOGIN", true),
             rate_limit_cleanup_interval: parse_env("S5_RATE_LIMIT_CLEANUP_INTERVAL", 60),
             rate_limit_max_ips: parse_env("S5_RATE_LIMIT_MAX_IPS", 100_000),
             rate_limit_max_users: parse_env("S5_RATE_LIMIT_MAX_USERS", 10_000),
+            tarpit_enabled: parse_bool_env("S5_TARPIT_ENABLED", false),
+            tarpit_max_connections: parse_env("S5_TARPIT_MAX_CONNECTIONS", 256),
+            tarpit_interval: parse_env("S5_TARPIT_INTERVAL", 10),
+            tarpit_max_duration: parse_env("S5_TARPIT_MAX_DURATION", 3600),
+            auth_backend: opt_env("S5_AUTH_BACKEND")
+                .map(|s| parse_auth_backend(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            external_auth: parse_external_auth_env()?,
+            jump_ports: parse_ports_env("S5_JUMP_PORTS")?.unwrap_or_else(|| vec![22]),
+            blocked_ports: parse_ports_env("S5_BLOCKED_PORTS")?
+                .unwrap_or_else(crate::config::types::default_blocked_ports),
+            password_policy: PasswordPolicyConfig {
+                min_length: parse_env("S5_PASSWORD_MIN_LENGTH", 8),
+                min_classes: parse_env("S5_PASSWORD_MIN_CLASSES", 1),
+                deny_common: parse_bool_env("S5_PASSWORD_DENY_COMMON", true),
+                max_age_days: parse_env("S5_PASSWORD_MAX_AGE_DAYS", 0),
+            },
+            credential_spraying: CredentialSprayingConfig {
+                enabled: parse_bool_env("S5_CREDENTIAL_SPRAYING_ENABLED", true),
+                min_usernames: parse_env("S5_CREDENTIAL_SPRAYING_MIN_USERNAMES", 10),
+                window_secs: parse_env("S5_CREDENTIAL_SPRAYING_WINDOW", 3600),
+            },
         },
         logging: LoggingConfig {
             level: opt_env("S5_LOG_LEVEL")
@@ -131,20 +201,78 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
             audit_max_size_mb: parse_env("S5_AUDIT_MAX_SIZE_MB", 100),
             audit_max_files: parse_env("S5_AUDIT_MAX_FILES", 5),
             connection_flow_logs: parse_bool_env("S5_CONNECTION_FLOW_LOGS", false),
+            event_log: parse_bool_env("S5_LOG_EVENT_LOG", false),
+            audit_chain: parse_bool_env("S5_AUDIT_CHAIN", false),
+            audit_checkpoint_interval: parse_env("S5_AUDIT_CHECKPOINT_INTERVAL", 300),
+            audit_signing_key: resolve_env_or_file("S5_AUDIT_SIGNING_KEY")?,
+            flows: FlowLogConfig {
+                enabled: parse_bool_env("S5_FLOWS_ENABLED", false),
+                path: opt_env("S5_FLOWS_PATH")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("flows.db")),
+                retention_days: parse_env("S5_FLOWS_RETENTION_DAYS", 30),
+                payload_metadata: parse_bool_env("S5_FLOWS_PAYLOAD_METADATA", false),
+                payload_metadata_max_len: parse_env("S5_FLOWS_PAYLOAD_METADATA_MAX_LEN", 256),
+            },
+            ipfix: IpfixConfig {
+                enabled: parse_bool_env("S5_IPFIX_ENABLED", false),
+                collector: opt_env("S5_IPFIX_COLLECTOR").unwrap_or_default(),
+                observation_domain_id: parse_env("S5_IPFIX_OBSERVATION_DOMAIN_ID", 1),
+                enterprise_number: parse_env("S5_IPFIX_ENTERPRISE_NUMBER", 32473),
+                template_refresh_secs: parse_env("S5_IPFIX_TEMPLATE_REFRESH", 600),
+            },
+            capture: CaptureConfig {
+                enabled: parse_bool_env("S5_CAPTURE_ENABLED", false),
+                directory: opt_env("S5_CAPTURE_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("captures")),
+                max_duration_secs: parse_env("S5_CAPTURE_MAX_DURATION", 300),
+                max_size_mb: parse_env("S5_CAPTURE_MAX_SIZE_MB", 100),
+            },
+            audit: AuditLogConfig {
+                archive: AuditArchiveConfig {
+                    enabled: parse_bool_env("S5_AUDIT_ARCHIVE_ENABLED", false),
+                    endpoint: opt_env("S5_AUDIT_ARCHIVE_ENDPOINT").unwrap_or_default(),
+                    bucket: opt_env("S5_AUDIT_ARCHIVE_BUCKET").unwrap_or_default(),
+                    region: opt_env("S5_AUDIT_ARCHIVE_REGION")
+                        .unwrap_or_else(|| "us-east-1".to_string()),
+                    prefix: opt_env("S5_AUDIT_ARCHIVE_PREFIX")
+                        .unwrap_or_else(|| "audit/".to_string()),
+                    access_key_id: opt_env("S5_AUDIT_ARCHIVE_ACCESS_KEY_ID").unwrap_or_default(),
+                    secret_access_key: resolve_env_or_file("S5_AUDIT_ARCHIVE_SECRET_ACCESS_KEY")?
+                        .unwrap_or_default(),
+                    path_style: parse_bool_env("S5_AUDIT_ARCHIVE_PATH_STYLE", true),
+                    retry_interval_secs: parse_env("S5_AUDIT_ARCHIVE_RETRY_INTERVAL", 60),
+                    timeout_secs: parse_env("S5_AUDIT_ARCHIVE_TIMEOUT", 300),
+                },
+            },
         },
         metrics: MetricsConfig {
             enabled: parse_bool_env("S5_METRICS_ENABLED", false),
             listen: opt_env("S5_METRICS_LISTEN").unwrap_or_else(|| "127.0.0.1:9090".to_string()),
             max_metric_labels: parse_env("S5_MAX_METRIC_LABELS", 100),
+            user_labels: parse_bool_env("S5_METRICS_USER_LABELS", false),
         },
         api: ApiConfig {
             enabled: parse_bool_env("S5_API_ENABLED", false),
             listen: opt_env("S5_API_LISTEN").unwrap_or_else(|| "127.0.0.1:9091".to_string()),
+            unix_socket: opt_env("S5_API_UNIX_SOCKET").map(PathBuf::from),
+            unix_socket_mode: opt_env("S5_API_UNIX_SOCKET_MODE")
+                .unwrap_or_else(|| "0660".to_string()),
             token: resolve_env_or_file("S5_API_TOKEN")?.unwrap_or_default(),
+            rate_limit_per_minute: parse_env("S5_API_RATE_LIMIT_PER_MINUTE", 600),
+            auth_failure_delay_ms: parse_env("S5_API_AUTH_FAILURE_DELAY_MS", 250),
+            allowed_origins: parse_csv_env("S5_API_ALLOWED_ORIGINS"),
+            session_idle_timeout: parse_env("S5_API_SESSION_IDLE_TIMEOUT", 1800),
+            accounts: Vec::new(),
+            config_history: parse_env("S5_API_CONFIG_HISTORY", 10),
+            ssh_websocket: parse_bool_env("S5_API_SSH_WEBSOCKET", false),
+            trusted_proxies: parse_cidr_csv_env("S5_API_TRUSTED_PROXIES")?,
         },
         geoip: GeoIpConfig {
             enabled: parse_bool_env("S5_GEOIP_ENABLED", false),
             database_path: opt_env("S5_GEOIP_DATABASE_PATH").map(PathBuf::from),
+            asn_database_path: opt_env("S5_GEOIP_ASN_DATABASE_PATH").map(PathBuf::from),
             allowed_countries: parse_csv_env("S5_GEOIP_ALLOWED_COUNTRIES"),
             denied_countries: parse_csv_env("S5_GEOIP_DENIED_COUNTRIES"),
             fail_closed: parse_bool_env("S5_GEOIP_FAIL_CLOSED", false),
@@ -158,13 +286,151 @@ pub fn build_config_from_env() -> anyhow::Result<AppConfig> {
                 .unwrap_or(AclPolicyConfig::Allow),
             allow: parse_csv_env("S5_GLOBAL_ACL_ALLOW"),
             deny: parse_csv_env("S5_GLOBAL_ACL_DENY"),
+            inspect_server_names: parse_bool_env("S5_GLOBAL_ACL_INSPECT_SERVER_NAMES", false),
         },
         users,
         groups: Vec::new(),
+        tenants: Vec::new(),
         motd: MotdConfig::default(),
         alerting: AlertingConfig::default(),
         maintenance_windows: Vec::new(),
+        quota_plans: Default::default(),
         connection_pool: ConnectionPoolConfig::default(),
+        threat_intel: ThreatIntelConfig {
+            refresh_interval: parse_env("S5_THREAT_INTEL_REFRESH", 60),
+            crowdsec: match (
+                opt_env("S5_CROWDSEC_URL"),
+                resolve_env_or_file("S5_CROWDSEC_API_KEY")?,
+            ) {
+                (Some(url), Some(api_key)) => Some(CrowdSecConfig { url, api_key }),
+                _ => None,
+            },
+            denylists: parse_csv_env("S5_DENYLIST_URLS")
+                .into_iter()
+                .enumerate()
+                .map(|(i, url)| DenylistConfig {
+                    name: format!("denylist-{}", i),
+                    url,
+                })
+                .collect(),
+        },
+        ip_intel: IpIntelConfig {
+            deny: parse_source_conditions(&parse_csv_env("S5_IP_INTEL_DENY"))?,
+            path: opt_env("S5_IP_INTEL_PATH").map(PathBuf::from),
+            ..IpIntelConfig::default()
+        },
+        honeypot: HoneypotConfig {
+            credentials: parse_honeypot_env("S5_HONEYPOT_CREDENTIALS"),
+            ban: parse_bool_env("S5_HONEYPOT_BAN", true),
+            ban_duration: parse_env("S5_HONEYPOT_BAN_DURATION", 86_400),
+        },
+        login_anomaly: LoginAnomalyConfig {
+            enabled: parse_bool_env("S5_LOGIN_ANOMALY_ENABLED", false),
+            history_path: opt_env("S5_LOGIN_ANOMALY_HISTORY_PATH").map(PathBuf::from),
+            learning_logins: parse_env("S5_LOGIN_ANOMALY_LEARNING_LOGINS", 5),
+            new_country: parse_bool_env("S5_LOGIN_ANOMALY_NEW_COUNTRY", true),
+            new_asn: parse_bool_env("S5_LOGIN_ANOMALY_NEW_ASN", true),
+            unusual_hours: parse_bool_env("S5_LOGIN_ANOMALY_UNUSUAL_HOURS", true),
+            hour_tolerance: parse_env("S5_LOGIN_ANOMALY_HOUR_TOLERANCE", 1),
+        },
+        impossible_travel: ImpossibleTravelConfig {
+            enabled: parse_bool_env("S5_IMPOSSIBLE_TRAVEL_ENABLED", false),
+            action: opt_env("S5_IMPOSSIBLE_TRAVEL_ACTION")
+                .map(|s| parse_impossible_travel_action(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            max_speed_kmh: parse_env("S5_IMPOSSIBLE_TRAVEL_MAX_SPEED_KMH", 1000),
+            min_distance_km: parse_env("S5_IMPOSSIBLE_TRAVEL_MIN_DISTANCE_KM", 500),
+            window_secs: parse_env("S5_IMPOSSIBLE_TRAVEL_WINDOW", 3600),
+        },
+        invitations: Default::default(),
+        authorized_keys_fetch: Default::default(),
+        key_enrollment: KeyEnrollmentConfig {
+            enabled: parse_bool_env("S5_KEY_ENROLLMENT_ENABLED", false),
+            wait_secs: parse_env("S5_KEY_ENROLLMENT_WAIT_SECS", 60),
+            pending_hours: parse_env("S5_KEY_ENROLLMENT_PENDING_HOURS", 24),
+            max_pending_per_user: parse_env("S5_KEY_ENROLLMENT_MAX_PENDING_PER_USER", 3),
+        },
+        notifications: build_notifications_from_env()?,
+        reports: ReportsConfig {
+            usage_history_path: opt_env("S5_REPORTS_USAGE_HISTORY_PATH").map(PathBuf::from),
+            usage_history_months: parse_env("S5_REPORTS_USAGE_HISTORY_MONTHS", 13),
+            monthly_email: parse_bool_env("S5_REPORTS_MONTHLY_EMAIL", false),
+            email_to: parse_csv_env("S5_REPORTS_EMAIL_TO"),
+        },
+        cluster: ClusterConfig {
+            enabled: parse_bool_env("S5_CLUSTER_ENABLED", false),
+            backend: opt_env("S5_CLUSTER_BACKEND")
+                .map(|b| parse_cluster_backend(&b))
+                .transpose()?
+                .unwrap_or_default(),
+            url: opt_env("S5_CLUSTER_URL").unwrap_or_else(|| ClusterConfig::default().url),
+            username: opt_env("S5_CLUSTER_USERNAME"),
+            password: resolve_env_or_file("S5_CLUSTER_PASSWORD")?,
+            node_id: opt_env("S5_CLUSTER_NODE_ID").unwrap_or_default(),
+            key_prefix: opt_env("S5_CLUSTER_KEY_PREFIX")
+                .unwrap_or_else(|| ClusterConfig::default().key_prefix),
+            sync_interval_secs: parse_env("S5_CLUSTER_SYNC_INTERVAL", 5),
+            listen: opt_env("S5_CLUSTER_LISTEN").unwrap_or_else(|| ClusterConfig::default().listen),
+            peers: parse_csv_env("S5_CLUSTER_PEERS"),
+            secret: resolve_env_or_file("S5_CLUSTER_SECRET")?,
+            observer: parse_bool_env("S5_CLUSTER_OBSERVER", false),
+            ..Default::default()
+        },
+        sandbox: SandboxConfig {
+            enabled: parse_bool_env("S5_SANDBOX_ENABLED", false),
+            user: opt_env("S5_SANDBOX_USER"),
+            group: opt_env("S5_SANDBOX_GROUP"),
+            seccomp: parse_bool_env("S5_SANDBOX_SECCOMP", false),
+        },
+        vpn: VpnConfig {
+            enabled: parse_bool_env("S5_VPN_ENABLED", false),
+            pool: parse_env("S5_VPN_POOL", VpnConfig::default().pool),
+            mtu: parse_env("S5_VPN_MTU", VpnConfig::default().mtu),
+        },
+        subsystems: Vec::new(),
+        proxy: ProxyConfig {
+            address_family: opt_env("S5_ADDRESS_FAMILY")
+                .map(|s| parse_address_family(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            dns_cache: DnsCacheConfig {
+                min_ttl: parse_env("S5_DNS_CACHE_MIN_TTL", 0),
+                max_ttl: parse_env("S5_DNS_CACHE_MAX_TTL", 3600),
+                max_entries: None,
+                eviction: opt_env("S5_DNS_CACHE_EVICTION")
+                    .map(|s| parse_dns_eviction(&s))
+                    .transpose()?
+                    .unwrap_or_default(),
+                latency_ordering: parse_bool_env("S5_DNS_CACHE_LATENCY_ORDERING", true),
+                latency_ttl: parse_env("S5_DNS_CACHE_LATENCY_TTL", 600),
+            },
+            hosts: parse_hosts_env("S5_PROXY_HOSTS")?,
+            circuit_breaker: CircuitBreakerConfig {
+                enabled: parse_bool_env("S5_CIRCUIT_BREAKER_ENABLED", false),
+                failure_threshold: parse_env("S5_CIRCUIT_BREAKER_FAILURE_THRESHOLD", 5),
+                window_secs: parse_env("S5_CIRCUIT_BREAKER_WINDOW_SECS", 60),
+                open_secs: parse_env("S5_CIRCUIT_BREAKER_OPEN_SECS", 30),
+            },
+            tcp: TcpConfig {
+                keepalive_time_secs: parse_env("S5_TCP_KEEPALIVE_TIME_SECS", 60),
+                keepalive_interval_secs: parse_env("S5_TCP_KEEPALIVE_INTERVAL_SECS", 15),
+                keepalive_retries: opt_env("S5_TCP_KEEPALIVE_RETRIES").and_then(|v| v.parse().ok()),
+                user_timeout_ms: parse_env("S5_TCP_USER_TIMEOUT_MS", 0),
+                fast_open: parse_bool_env("S5_TCP_FAST_OPEN", false),
+            },
+            buffer_pool: BufferPoolConfig {
+                buffer_size: parse_env("S5_BUFFER_POOL_BUFFER_SIZE", 8192),
+                max_buffers: parse_env("S5_BUFFER_POOL_MAX_BUFFERS", 1024),
+            },
+            // Classes need a config file
+            priority: PriorityConfig::default(),
+        },
+        // Rules need a config file
+        policy: Default::default(),
+        scripting: None,
+        masque: None,
+        gssapi: None,
     };
 
     // Clear sensitive env vars from the process environment after reading them.
@@ -186,7 +452,7 @@ fn build_user_config(
         username,
         password_hash,
         authorized_keys,
-        allow_forwarding: parse_bool_env(&format!("{prefix}ALLOW_FORWARDING"), true),
+        allow_forwarding: Some(parse_bool_env(&format!("{prefix}ALLOW_FORWARDING"), true)),
         allow_shell: parse_bool_env(&format!("{prefix}ALLOW_SHELL"), true),
         max_new_connections_per_minute: parse_env(
             &format!("{prefix}MAX_NEW_CONNECTIONS_PER_MINUTE"),
@@ -199,6 +465,12 @@ fn build_user_config(
         ),
         source_ips: parse_cidr_csv_env(&format!("{prefix}SOURCE_IPS"))?,
         expires_at: opt_env(&format!("{prefix}EXPIRES_AT")),
+        disabled: false,
+        require_security_key: false,
+        allow_vpn: false,
+        vpn_address: None,
+        tags: Vec::new(),
+        authorized_keys_url: None,
         upstream_proxy: opt_env(&format!("{prefix}UPSTREAM_PROXY")),
         acl: UserAclConfig {
             default_policy: opt_env(&format!("{prefix}ACL_DEFAULT_POLICY"))
@@ -213,8 +485,10 @@ fn build_user_config(
         group: opt_env(&format!("{prefix}GROUP")),
         role: UserRole::default(),
         shell_permissions: None,
+        terminal: None,
         motd: None,
         quotas: None,
+        quota_plan: None,
         time_access: None,
         auth_methods: None,
         idle_warning_secs: None,
@@ -225,6 +499,15 @@ fn build_user_config(
         max_connections: opt_env(&format!("{prefix}MAX_CONNECTIONS"))
             .map(|v| v.parse().unwrap_or(0)),
         rate_limits: build_rate_limits_from_env(&format!("{prefix}RATE_LIMIT")),
+        api_token_hash: None,
+        api_tokens: Vec::new(),
+        ip_guard_exemptions: None,
+        jump_targets: None,
+        unix_sockets: None,
+        allowed_hassh: None,
+        subsystems: None,
+        impossible_travel: None,
+        password_changed_at: None,
     })
 }
 
@@ -318,7 +601,8 @@ fn build_rate_limits_from_env(prefix: &str) -> Option<RateLimitsConfig> {
 
 /// Apply environment variable overrides to an existing config (hybrid mode).
 /// Only overrides values for which an env var is set. Supports _FILE convention.
-pub fn apply_env_overrides(config: &mut AppConfig) {
+/// Fails when a structured override (e.g. the SMTP settings) is invalid.
+pub fn apply_env_overrides(config: &mut AppConfig) -> anyhow::Result<()> {
     // Server overrides
     if let Some(v) = opt_env("S5_SSH_LISTEN") {
         config.server.ssh_listen = v;
@@ -329,12 +613,27 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
     if let Some(v) = opt_env("S5_HOST_KEY_PATH") {
         config.server.host_key_path = PathBuf::from(v);
     }
+    if let Some(v) = opt_env("S5_HOST_KEY_NEW_PATH") {
+        config.server.host_key_rotation.new_key_path = Some(PathBuf::from(v));
+    }
+    if std::env::var("S5_HOST_KEY_RETIRE_AFTER_DAYS").is_ok() {
+        config.server.host_key_rotation.retire_after_days = parse_env(
+            "S5_HOST_KEY_RETIRE_AFTER_DAYS",
+            config.server.host_key_rotation.retire_after_days,
+        );
+    }
     if let Some(v) = opt_env("S5_BANNER") {
         config.server.banner = v;
     }
+    if let Some(v) = opt_env("S5_MAINTENANCE_MESSAGE") {
+        config.server.maintenance_message = v;
+    }
     if std::env::var("S5_PROXY_PROTOCOL").is_ok() {
         config.server.proxy_protocol = parse_bool_env("S5_PROXY_PROTOCOL", false);
     }
+    if std::env::var("S5_SOCKET_HANDOVER").is_ok() {
+        config.server.socket_handover = parse_bool_env("S5_SOCKET_HANDOVER", false);
+    }
 
     // SOCKS5 handshake timeout override
     if std::env::var("S5_SOCKS5_HANDSHAKE_TIMEOUT").is_ok() {
@@ -355,6 +654,368 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
             config.logging.format = format;
         }
     }
+    if std::env::var("S5_LOG_EVENT_LOG").is_ok() {
+        config.logging.event_log = parse_bool_env("S5_LOG_EVENT_LOG", config.logging.event_log);
+    }
+    if std::env::var("S5_AUDIT_CHAIN").is_ok() {
+        config.logging.audit_chain = parse_bool_env("S5_AUDIT_CHAIN", config.logging.audit_chain);
+    }
+    if std::env::var("S5_AUDIT_CHECKPOINT_INTERVAL").is_ok() {
+        config.logging.audit_checkpoint_interval = parse_env(
+            "S5_AUDIT_CHECKPOINT_INTERVAL",
+            config.logging.audit_checkpoint_interval,
+        );
+    }
+    if let Ok(Some(v)) = resolve_env_or_file("S5_AUDIT_SIGNING_KEY") {
+        config.logging.audit_signing_key = Some(v);
+    }
+    if std::env::var("S5_FLOWS_ENABLED").is_ok() {
+        config.logging.flows.enabled =
+            parse_bool_env("S5_FLOWS_ENABLED", config.logging.flows.enabled);
+    }
+    if let Some(v) = opt_env("S5_FLOWS_PATH") {
+        config.logging.flows.path = PathBuf::from(v);
+    }
+    if std::env::var("S5_FLOWS_RETENTION_DAYS").is_ok() {
+        config.logging.flows.retention_days = parse_env(
+            "S5_FLOWS_RETENTION_DAYS",
+            config.logging.flows.retention_days,
+        );
+    }
+    if std::env::var("S5_FLOWS_PAYLOAD_METADATA").is_ok() {
+        config.logging.flows.payload_metadata = parse_bool_env(
+            "S5_FLOWS_PAYLOAD_METADATA",
+            config.logging.flows.payload_metadata,
+        );
+    }
+    if std::env::var("S5_FLOWS_PAYLOAD_METADATA_MAX_LEN").is_ok() {
+        config.logging.flows.payload_metadata_max_len = parse_env(
+            "S5_FLOWS_PAYLOAD_METADATA_MAX_LEN",
+            config.logging.flows.payload_metadata_max_len,
+        );
+    }
+    if std::env::var("S5_IPFIX_ENABLED").is_ok() {
+        config.logging.ipfix.enabled =
+            parse_bool_env("S5_IPFIX_ENABLED", config.logging.ipfix.enabled);
+    }
+    if let Some(v) = opt_env("S5_IPFIX_COLLECTOR") {
+        config.logging.ipfix.collector = v;
+    }
+    if std::env::var("S5_IPFIX_OBSERVATION_DOMAIN_ID").is_ok() {
+        config.logging.ipfix.observation_domain_id = parse_env(
+            "S5_IPFIX_OBSERVATION_DOMAIN_ID",
+            config.logging.ipfix.observation_domain_id,
+        );
+    }
+    if std::env::var("S5_IPFIX_ENTERPRISE_NUMBER").is_ok() {
+        config.logging.ipfix.enterprise_number = parse_env(
+            "S5_IPFIX_ENTERPRISE_NUMBER",
+            config.logging.ipfix.enterprise_number,
+        );
+    }
+    if std::env::var("S5_IPFIX_TEMPLATE_REFRESH").is_ok() {
+        config.logging.ipfix.template_refresh_secs = parse_env(
+            "S5_IPFIX_TEMPLATE_REFRESH",
+            config.logging.ipfix.template_refresh_secs,
+        );
+    }
+    if std::env::var("S5_CAPTURE_ENABLED").is_ok() {
+        config.logging.capture.enabled =
+            parse_bool_env("S5_CAPTURE_ENABLED", config.logging.capture.enabled);
+    }
+    if let Some(v) = opt_env("S5_CAPTURE_DIR") {
+        config.logging.capture.directory = PathBuf::from(v);
+    }
+    if std::env::var("S5_CAPTURE_MAX_DURATION").is_ok() {
+        config.logging.capture.max_duration_secs = parse_env(
+            "S5_CAPTURE_MAX_DURATION",
+            config.logging.capture.max_duration_secs,
+        );
+    }
+    if std::env::var("S5_CAPTURE_MAX_SIZE_MB").is_ok() {
+        config.logging.capture.max_size_mb =
+            parse_env("S5_CAPTURE_MAX_SIZE_MB", config.logging.capture.max_size_mb);
+    }
+    let archive = &mut config.logging.audit.archive;
+    archive.enabled = parse_bool_env("S5_AUDIT_ARCHIVE_ENABLED", archive.enabled);
+    if let Some(v) = opt_env("S5_AUDIT_ARCHIVE_ENDPOINT") {
+        archive.endpoint = v;
+    }
+    if let Some(v) = opt_env("S5_AUDIT_ARCHIVE_BUCKET") {
+        archive.bucket = v;
+    }
+    if let Some(v) = opt_env("S5_AUDIT_ARCHIVE_REGION") {
+        archive.region = v;
+    }
+    if let Some(v) = opt_env("S5_AUDIT_ARCHIVE_PREFIX") {
+        archive.prefix = v;
+    }
+    if let Some(v) = opt_env("S5_AUDIT_ARCHIVE_ACCESS_KEY_ID") {
+        archive.access_key_id = v;
+    }
+    if let Ok(Some(v)) = resolve_env_or_file("S5_AUDIT_ARCHIVE_SECRET_ACCESS_KEY") {
+        archive.secret_access_key = v;
+    }
+    archive.path_style = parse_bool_env("S5_AUDIT_ARCHIVE_PATH_STYLE", archive.path_style);
+    archive.retry_interval_secs = parse_env(
+        "S5_AUDIT_ARCHIVE_RETRY_INTERVAL",
+        archive.retry_interval_secs,
+    );
+    archive.timeout_secs = parse_env("S5_AUDIT_ARCHIVE_TIMEOUT", archive.timeout_secs);
+
+    // Threat intel overrides
+    if std::env::var("S5_THREAT_INTEL_REFRESH").is_ok() {
+        config.threat_intel.refresh_interval = parse_env(
+            "S5_THREAT_INTEL_REFRESH",
+            config.threat_intel.refresh_interval,
+        );
+    }
+    if let (Some(url), Ok(Some(api_key))) = (
+        opt_env("S5_CROWDSEC_URL"),
+        resolve_env_or_file("S5_CROWDSEC_API_KEY"),
+    ) {
+        config.threat_intel.crowdsec = Some(CrowdSecConfig { url, api_key });
+    }
+    if std::env::var("S5_DENYLIST_URLS").is_ok() {
+        config.threat_intel.denylists = parse_csv_env("S5_DENYLIST_URLS")
+            .into_iter()
+            .enumerate()
+            .map(|(i, url)| DenylistConfig {
+                name: format!("denylist-{}", i),
+                url,
+            })
+            .collect();
+    }
+
+    // IP intel overrides
+    if std::env::var("S5_IP_INTEL_DENY").is_ok() {
+        if let Ok(deny) = parse_source_conditions(&parse_csv_env("S5_IP_INTEL_DENY")) {
+            config.ip_intel.deny = deny;
+        }
+    }
+    if let Some(v) = opt_env("S5_IP_INTEL_PATH") {
+        config.ip_intel.path = Some(PathBuf::from(v));
+    }
+
+    // Honeypot overrides
+    if std::env::var("S5_HONEYPOT_CREDENTIALS").is_ok() {
+        config.honeypot.credentials = parse_honeypot_env("S5_HONEYPOT_CREDENTIALS");
+    }
+    if std::env::var("S5_HONEYPOT_BAN").is_ok() {
+        config.honeypot.ban = parse_bool_env("S5_HONEYPOT_BAN", true);
+    }
+    if std::env::var("S5_HONEYPOT_BAN_DURATION").is_ok() {
+        config.honeypot.ban_duration =
+            parse_env("S5_HONEYPOT_BAN_DURATION", config.honeypot.ban_duration);
+    }
+
+    // Login anomaly overrides
+    let anomaly = &mut config.login_anomaly;
+    anomaly.enabled = parse_bool_env("S5_LOGIN_ANOMALY_ENABLED", anomaly.enabled);
+    if let Some(path) = opt_env("S5_LOGIN_ANOMALY_HISTORY_PATH") {
+        anomaly.history_path = Some(PathBuf::from(path));
+    }
+    anomaly.learning_logins =
+        parse_env("S5_LOGIN_ANOMALY_LEARNING_LOGINS", anomaly.learning_logins);
+    anomaly.new_country = parse_bool_env("S5_LOGIN_ANOMALY_NEW_COUNTRY", anomaly.new_country);
+    anomaly.new_asn = parse_bool_env("S5_LOGIN_ANOMALY_NEW_ASN", anomaly.new_asn);
+    anomaly.unusual_hours = parse_bool_env("S5_LOGIN_ANOMALY_UNUSUAL_HOURS", anomaly.unusual_hours);
+    anomaly.hour_tolerance = parse_env("S5_LOGIN_ANOMALY_HOUR_TOLERANCE", anomaly.hour_tolerance);
+    if let Some(path) = opt_env("S5_GEOIP_ASN_DATABASE_PATH") {
+        config.geoip.asn_database_path = Some(PathBuf::from(path));
+    }
+
+    // Impossible travel overrides
+    let travel = &mut config.impossible_travel;
+    travel.enabled = parse_bool_env("S5_IMPOSSIBLE_TRAVEL_ENABLED", travel.enabled);
+    if let Some(action) = opt_env("S5_IMPOSSIBLE_TRAVEL_ACTION") {
+        travel.action = parse_impossible_travel_action(&action)?;
+    }
+    travel.max_speed_kmh = parse_env("S5_IMPOSSIBLE_TRAVEL_MAX_SPEED_KMH", travel.max_speed_kmh);
+    travel.min_distance_km = parse_env(
+        "S5_IMPOSSIBLE_TRAVEL_MIN_DISTANCE_KM",
+        travel.min_distance_km,
+    );
+    travel.window_secs = parse_env("S5_IMPOSSIBLE_TRAVEL_WINDOW", travel.window_secs);
+
+    // Key enrollment overrides
+    let enrollment = &mut config.key_enrollment;
+    enrollment.enabled = parse_bool_env("S5_KEY_ENROLLMENT_ENABLED", enrollment.enabled);
+    enrollment.wait_secs = parse_env("S5_KEY_ENROLLMENT_WAIT_SECS", enrollment.wait_secs);
+    enrollment.pending_hours =
+        parse_env("S5_KEY_ENROLLMENT_PENDING_HOURS", enrollment.pending_hours);
+    enrollment.max_pending_per_user = parse_env(
+        "S5_KEY_ENROLLMENT_MAX_PENDING_PER_USER",
+        enrollment.max_pending_per_user,
+    );
+
+    // Notification overrides
+    if let Some(smtp) = parse_smtp_env()? {
+        config.notifications.smtp = Some(smtp);
+    }
+    for sink in parse_notification_sinks_env()? {
+        config.notifications.sinks.retain(|s| s.name != sink.name);
+        config.notifications.sinks.push(sink);
+    }
+    if std::env::var("S5_NOTIFICATION_EVENTS").is_ok() {
+        config.notifications.rules =
+            parse_notification_rules_env("S5_NOTIFICATION_EVENTS", &config.notifications)?;
+    }
+    if std::env::var("S5_NOTIFICATION_BATCH_WINDOW").is_ok() {
+        config.notifications.batch_window_secs = parse_env(
+            "S5_NOTIFICATION_BATCH_WINDOW",
+            config.notifications.batch_window_secs,
+        );
+    }
+    if std::env::var("S5_NOTIFICATION_DEDUP_WINDOW").is_ok() {
+        config.notifications.dedup_window_secs = parse_env(
+            "S5_NOTIFICATION_DEDUP_WINDOW",
+            config.notifications.dedup_window_secs,
+        );
+    }
+    if std::env::var("S5_NOTIFICATION_MAX_EVENTS").is_ok() {
+        config.notifications.max_events_per_email = parse_env(
+            "S5_NOTIFICATION_MAX_EVENTS",
+            config.notifications.max_events_per_email,
+        );
+    }
+    if std::env::var("S5_NOTIFICATION_AUTH_FAILURE_THRESHOLD").is_ok() {
+        config.notifications.auth_failure_threshold = parse_env(
+            "S5_NOTIFICATION_AUTH_FAILURE_THRESHOLD",
+            config.notifications.auth_failure_threshold,
+        );
+    }
+    if std::env::var("S5_NOTIFICATION_AUTH_FAILURE_WINDOW").is_ok() {
+        config.notifications.auth_failure_window_secs = parse_env(
+            "S5_NOTIFICATION_AUTH_FAILURE_WINDOW",
+            config.notifications.auth_failure_window_secs,
+        );
+    }
+
+    // Usage report overrides
+    let reports = &mut config.reports;
+    if let Some(path) = opt_env("S5_REPORTS_USAGE_HISTORY_PATH") {
+        reports.usage_history_path = Some(PathBuf::from(path));
+    }
+    reports.usage_history_months = parse_env(
+        "S5_REPORTS_USAGE_HISTORY_MONTHS",
+        reports.usage_history_months,
+    );
+    reports.monthly_email = parse_bool_env("S5_REPORTS_MONTHLY_EMAIL", reports.monthly_email);
+    if std::env::var("S5_REPORTS_EMAIL_TO").is_ok() {
+        reports.email_to = parse_csv_env("S5_REPORTS_EMAIL_TO");
+    }
+
+    // Cluster overrides
+    let cluster = &mut config.cluster;
+    cluster.enabled = parse_bool_env("S5_CLUSTER_ENABLED", cluster.enabled);
+    if let Some(backend) = opt_env("S5_CLUSTER_BACKEND") {
+        cluster.backend = parse_cluster_backend(&backend)?;
+    }
+    if let Some(url) = opt_env("S5_CLUSTER_URL") {
+        cluster.url = url;
+    }
+    if let Some(username) = opt_env("S5_CLUSTER_USERNAME") {
+        cluster.username = Some(username);
+    }
+    if let Ok(Some(password)) = resolve_env_or_file("S5_CLUSTER_PASSWORD") {
+        cluster.password = Some(password);
+    }
+    if let Some(node_id) = opt_env("S5_CLUSTER_NODE_ID") {
+        cluster.node_id = node_id;
+    }
+    if let Some(prefix) = opt_env("S5_CLUSTER_KEY_PREFIX") {
+        cluster.key_prefix = prefix;
+    }
+    cluster.sync_interval_secs = parse_env("S5_CLUSTER_SYNC_INTERVAL", cluster.sync_interval_secs);
+    if let Some(listen) = opt_env("S5_CLUSTER_LISTEN") {
+        cluster.listen = listen;
+    }
+    if std::env::var("S5_CLUSTER_PEERS").is_ok() {
+        cluster.peers = parse_csv_env("S5_CLUSTER_PEERS");
+    }
+    if let Ok(Some(secret)) = resolve_env_or_file("S5_CLUSTER_SECRET") {
+        cluster.secret = Some(secret);
+    }
+    cluster.observer = parse_bool_env("S5_CLUSTER_OBSERVER", cluster.observer);
+
+    // Sandbox overrides
+    let sandbox = &mut config.sandbox;
+    sandbox.enabled = parse_bool_env("S5_SANDBOX_ENABLED", sandbox.enabled);
+    if let Some(user) = opt_env("S5_SANDBOX_USER") {
+        sandbox.user = Some(user);
+    }
+    if let Some(group) = opt_env("S5_SANDBOX_GROUP") {
+        sandbox.group = Some(group);
+    }
+    sandbox.seccomp = parse_bool_env("S5_SANDBOX_SECCOMP", sandbox.seccomp);
+
+    // Proxy overrides
+    if let Some(family) = opt_env("S5_ADDRESS_FAMILY") {
+        config.proxy.address_family = parse_address_family(&family)?;
+    }
+    if std::env::var("S5_PROXY_HOSTS").is_ok() {
+        config.proxy.hosts = parse_hosts_env("S5_PROXY_HOSTS")?;
+    }
+    let breaker = &mut config.proxy.circuit_breaker;
+    if std::env::var("S5_CIRCUIT_BREAKER_ENABLED").is_ok() {
+        breaker.enabled = parse_bool_env("S5_CIRCUIT_BREAKER_ENABLED", breaker.enabled);
+    }
+    if std::env::var("S5_CIRCUIT_BREAKER_FAILURE_THRESHOLD").is_ok() {
+        breaker.failure_threshold = parse_env(
+            "S5_CIRCUIT_BREAKER_FAILURE_THRESHOLD",
+            breaker.failure_threshold,
+        );
+    }
+    if std::env::var("S5_CIRCUIT_BREAKER_WINDOW_SECS").is_ok() {
+        breaker.window_secs = parse_env("S5_CIRCUIT_BREAKER_WINDOW_SECS", breaker.window_secs);
+    }
+    if std::env::var("S5_CIRCUIT_BREAKER_OPEN_SECS").is_ok() {
+        breaker.open_secs = parse_env("S5_CIRCUIT_BREAKER_OPEN_SECS", breaker.open_secs);
+    }
+    let tcp = &mut config.proxy.tcp;
+    if std::env::var("S5_TCP_KEEPALIVE_TIME_SECS").is_ok() {
+        tcp.keepalive_time_secs = parse_env("S5_TCP_KEEPALIVE_TIME_SECS", tcp.keepalive_time_secs);
+    }
+    if std::env::var("S5_TCP_KEEPALIVE_INTERVAL_SECS").is_ok() {
+        tcp.keepalive_interval_secs = parse_env(
+            "S5_TCP_KEEPALIVE_INTERVAL_SECS",
+            tcp.keepalive_interval_secs,
+        );
+    }
+    if let Some(retries) = opt_env("S5_TCP_KEEPALIVE_RETRIES") {
+        tcp.keepalive_retries = retries.parse().ok();
+    }
+    if std::env::var("S5_TCP_USER_TIMEOUT_MS").is_ok() {
+        tcp.user_timeout_ms = parse_env("S5_TCP_USER_TIMEOUT_MS", tcp.user_timeout_ms);
+    }
+    if std::env::var("S5_TCP_FAST_OPEN").is_ok() {
+        tcp.fast_open = parse_bool_env("S5_TCP_FAST_OPEN", tcp.fast_open);
+    }
+    let pool = &mut config.proxy.buffer_pool;
+    if std::env::var("S5_BUFFER_POOL_BUFFER_SIZE").is_ok() {
+        pool.buffer_size = parse_env("S5_BUFFER_POOL_BUFFER_SIZE", pool.buffer_size);
+    }
+    if std::env::var("S5_BUFFER_POOL_MAX_BUFFERS").is_ok() {
+        pool.max_buffers = parse_env("S5_BUFFER_POOL_MAX_BUFFERS", pool.max_buffers);
+    }
+    let dns_cache = &mut config.proxy.dns_cache;
+    if std::env::var("S5_DNS_CACHE_MIN_TTL").is_ok() {
+        dns_cache.min_ttl = parse_env("S5_DNS_CACHE_MIN_TTL", dns_cache.min_ttl);
+    }
+    if std::env::var("S5_DNS_CACHE_MAX_TTL").is_ok() {
+        dns_cache.max_ttl = parse_env("S5_DNS_CACHE_MAX_TTL", dns_cache.max_ttl);
+    }
+    if let Some(eviction) = opt_env("S5_DNS_CACHE_EVICTION") {
+        dns_cache.eviction = parse_dns_eviction(&eviction)?;
+    }
+    if std::env::var("S5_DNS_CACHE_LATENCY_ORDERING").is_ok() {
+        dns_cache.latency_ordering =
+            parse_bool_env("S5_DNS_CACHE_LATENCY_ORDERING", dns_cache.latency_ordering);
+    }
+    if std::env::var("S5_DNS_CACHE_LATENCY_TTL").is_ok() {
+        dns_cache.latency_ttl = parse_env("S5_DNS_CACHE_LATENCY_TTL", dns_cache.latency_ttl);
+    }
 
     // Limits overrides
     if std::env::var("S5_MAX_CONNECTIONS").is_ok() {
@@ -390,6 +1051,52 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
             config.limits.max_new_connections_per_minute,
         );
     }
+    if std::env::var("S5_MAX_UNAUTHENTICATED_CONNECTIONS").is_ok() {
+        config.limits.max_unauthenticated_connections = parse_env(
+            "S5_MAX_UNAUTHENTICATED_CONNECTIONS",
+            config.limits.max_unauthenticated_connections,
+        );
+    }
+    if std::env::var("S5_MAX_UNAUTHENTICATED_PER_IP").is_ok() {
+        config.limits.max_unauthenticated_per_ip = parse_env(
+            "S5_MAX_UNAUTHENTICATED_PER_IP",
+            config.limits.max_unauthenticated_per_ip,
+        );
+    }
+    if std::env::var("S5_MAX_BUFFERED_BYTES").is_ok() {
+        config.limits.max_buffered_bytes =
+            parse_env("S5_MAX_BUFFERED_BYTES", config.limits.max_buffered_bytes);
+    }
+    if std::env::var("S5_MAX_CONNECTION_BYTES").is_ok() {
+        config.limits.max_connection_bytes = parse_env(
+            "S5_MAX_CONNECTION_BYTES",
+            config.limits.max_connection_bytes,
+        );
+    }
+    if std::env::var("S5_UPLOAD_ALERT_BYTES").is_ok() {
+        config.limits.upload_alert_bytes =
+            parse_env("S5_UPLOAD_ALERT_BYTES", config.limits.upload_alert_bytes);
+    }
+    if std::env::var("S5_DOWNLOAD_ALERT_BYTES").is_ok() {
+        config.limits.download_alert_bytes = parse_env(
+            "S5_DOWNLOAD_ALERT_BYTES",
+            config.limits.download_alert_bytes,
+        );
+    }
+    if std::env::var("S5_MAX_CHANNELS_PER_CONNECTION").is_ok() {
+        config.limits.max_channels_per_connection = parse_env(
+            "S5_MAX_CHANNELS_PER_CONNECTION",
+            config.limits.max_channels_per_connection,
+        );
+    }
+    if let Some(path) = opt_env("S5_QUOTA_RESET_STATE_PATH") {
+        config.limits.quota_reset_state_path = Some(PathBuf::from(path));
+    }
+    if let Some(v) = opt_env("S5_OVERLOAD_POLICY") {
+        if let Ok(policy) = parse_overload_policy(&v) {
+            config.limits.overload_policy = policy;
+        }
+    }
 
     // Security overrides
     if std::env::var("S5_BAN_ENABLED").is_ok() {
@@ -417,6 +1124,64 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
             config.security.rate_limit_max_users,
         );
     }
+    if std::env::var("S5_TARPIT_ENABLED").is_ok() {
+        config.security.tarpit_enabled = parse_bool_env("S5_TARPIT_ENABLED", false);
+    }
+    if std::env::var("S5_TARPIT_MAX_CONNECTIONS").is_ok() {
+        config.security.tarpit_max_connections = parse_env(
+            "S5_TARPIT_MAX_CONNECTIONS",
+            config.security.tarpit_max_connections,
+        );
+    }
+    if std::env::var("S5_TARPIT_INTERVAL").is_ok() {
+        config.security.tarpit_interval =
+            parse_env("S5_TARPIT_INTERVAL", config.security.tarpit_interval);
+    }
+    if std::env::var("S5_TARPIT_MAX_DURATION").is_ok() {
+        config.security.tarpit_max_duration = parse_env(
+            "S5_TARPIT_MAX_DURATION",
+            config.security.tarpit_max_duration,
+        );
+    }
+
+    if let Some(backend) = opt_env("S5_AUTH_BACKEND") {
+        config.security.auth_backend = parse_auth_backend(&backend)?;
+    }
+    if let Some(external_auth) = parse_external_auth_env()? {
+        config.security.external_auth = Some(external_auth);
+    }
+    if let Some(ports) = parse_ports_env("S5_JUMP_PORTS")? {
+        config.security.jump_ports = ports;
+    }
+    if let Some(ports) = parse_ports_env("S5_BLOCKED_PORTS")? {
+        config.security.blocked_ports = ports;
+    }
+    let policy = &mut config.security.password_policy;
+    if std::env::var("S5_PASSWORD_MIN_LENGTH").is_ok() {
+        policy.min_length = parse_env("S5_PASSWORD_MIN_LENGTH", policy.min_length);
+    }
+    if std::env::var("S5_PASSWORD_MIN_CLASSES").is_ok() {
+        policy.min_classes = parse_env("S5_PASSWORD_MIN_CLASSES", policy.min_classes);
+    }
+    if std::env::var("S5_PASSWORD_DENY_COMMON").is_ok() {
+        policy.deny_common = parse_bool_env("S5_PASSWORD_DENY_COMMON", true);
+    }
+    if std::env::var("S5_PASSWORD_MAX_AGE_DAYS").is_ok() {
+        policy.max_age_days = parse_env("S5_PASSWORD_MAX_AGE_DAYS", policy.max_age_days);
+    }
+    let spraying = &mut config.security.credential_spraying;
+    if std::env::var("S5_CREDENTIAL_SPRAYING_ENABLED").is_ok() {
+        spraying.enabled = parse_bool_env("S5_CREDENTIAL_SPRAYING_ENABLED", true);
+    }
+    if std::env::var("S5_CREDENTIAL_SPRAYING_MIN_USERNAMES").is_ok() {
+        spraying.min_usernames = parse_env(
+            "S5_CREDENTIAL_SPRAYING_MIN_USERNAMES",
+            spraying.min_usernames,
+        );
+    }
+    if std::env::var("S5_CREDENTIAL_SPRAYING_WINDOW").is_ok() {
+        spraying.window_secs = parse_env("S5_CREDENTIAL_SPRAYING_WINDOW", spraying.window_secs);
+    }
 
     // Argon2 parameter overrides
     if std::env::var("S5_ARGON2_MEMORY_COST").is_ok() {
@@ -431,6 +1196,12 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
         config.security.argon2_parallelism =
             parse_env("S5_ARGON2_PARALLELISM", config.security.argon2_parallelism);
     }
+    if let Some(algorithm) = opt_env("S5_PASSWORD_HASH_ALGORITHM") {
+        config.security.password_hash_algorithm = parse_password_hash_algorithm(&algorithm)?;
+    }
+    if std::env::var("S5_REHASH_ON_LOGIN").is_ok() {
+        config.security.rehash_on_login = parse_bool_env("S5_REHASH_ON_LOGIN", true);
+    }
 
     // API overrides (useful for injecting tokens without putting them in config files)
     // Supports _FILE convention for Docker/K8s secrets
@@ -443,6 +1214,42 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
     if let Some(v) = opt_env("S5_API_LISTEN") {
         config.api.listen = v;
     }
+    if let Some(v) = opt_env("S5_API_UNIX_SOCKET") {
+        config.api.unix_socket = Some(PathBuf::from(v));
+    }
+    if let Some(v) = opt_env("S5_API_UNIX_SOCKET_MODE") {
+        config.api.unix_socket_mode = v;
+    }
+    if std::env::var("S5_API_RATE_LIMIT_PER_MINUTE").is_ok() {
+        config.api.rate_limit_per_minute = parse_env(
+            "S5_API_RATE_LIMIT_PER_MINUTE",
+            config.api.rate_limit_per_minute,
+        );
+    }
+    if std::env::var("S5_API_AUTH_FAILURE_DELAY_MS").is_ok() {
+        config.api.auth_failure_delay_ms = parse_env(
+            "S5_API_AUTH_FAILURE_DELAY_MS",
+            config.api.auth_failure_delay_ms,
+        );
+    }
+    if std::env::var("S5_API_ALLOWED_ORIGINS").is_ok() {
+        config.api.allowed_origins = parse_csv_env("S5_API_ALLOWED_ORIGINS");
+    }
+    if std::env::var("S5_API_SESSION_IDLE_TIMEOUT").is_ok() {
+        config.api.session_idle_timeout = parse_env(
+            "S5_API_SESSION_IDLE_TIMEOUT",
+            config.api.session_idle_timeout,
+        );
+    }
+    if std::env::var("S5_API_CONFIG_HISTORY").is_ok() {
+        config.api.config_history = parse_env("S5_API_CONFIG_HISTORY", config.api.config_history);
+    }
+    if std::env::var("S5_API_SSH_WEBSOCKET").is_ok() {
+        config.api.ssh_websocket = parse_bool_env("S5_API_SSH_WEBSOCKET", config.api.ssh_websocket);
+    }
+    if std::env::var("S5_API_TRUSTED_PROXIES").is_ok() {
+        config.api.trusted_proxies = parse_cidr_csv_env("S5_API_TRUSTED_PROXIES")?;
+    }
 
     // Metrics overrides
     if std::env::var("S5_METRICS_ENABLED").is_ok() {
@@ -464,11 +1271,17 @@ pub fn apply_env_overrides(config: &mut AppConfig) {
     if std::env::var("S5_GLOBAL_ACL_DENY").is_ok() {
         config.acl.deny = parse_csv_env("S5_GLOBAL_ACL_DENY");
     }
+    if std::env::var("S5_GLOBAL_ACL_INSPECT_SERVER_NAMES").is_ok() {
+        config.acl.inspect_server_names =
+            parse_bool_env("S5_GLOBAL_ACL_INSPECT_SERVER_NAMES", false);
+    }
+
+    Ok(())
 }
 
 /// Clear sensitive environment variables from the process after they have been read.
 /// This limits exposure via /proc/pid/environ or similar process inspection.
-/// Covers: PASSWORD_HASH, API_TOKEN, TOTP_SECRET and their _FILE and indexed variants.
+/// Covers: PASSWORD_HASH, API_TOKEN, TOTP_SECRET, AUDIT_SIGNING_KEY and their _FILE and indexed variants.
 fn clear_sensitive_env_vars() {
     let sensitive_suffixes = [
         "PASSWORD_HASH",
@@ -477,6 +1290,24 @@ fn clear_sensitive_env_vars() {
         "API_TOKEN_FILE",
         "TOTP_SECRET",
         "TOTP_SECRET_FILE",
+        "AUDIT_SIGNING_KEY",
+        "AUDIT_SIGNING_KEY_FILE",
+        "CROWDSEC_API_KEY",
+        "CROWDSEC_API_KEY_FILE",
+        "SMTP_PASSWORD",
+        "SMTP_PASSWORD_FILE",
+        "NOTIFICATION_SLACK_URL",
+        "NOTIFICATION_SLACK_URL_FILE",
+        "NOTIFICATION_DISCORD_URL",
+        "NOTIFICATION_DISCORD_URL_FILE",
+        "EXTERNAL_AUTH_SECRET",
+        "EXTERNAL_AUTH_SECRET_FILE",
+        "AUDIT_ARCHIVE_SECRET_ACCESS_KEY",
+        "AUDIT_ARCHIVE_SECRET_ACCESS_KEY_FILE",
+        "CLUSTER_PASSWORD",
+        "CLUSTER_PASSWORD_FILE",
+        "CLUSTER_SECRET",
+        "CLUSTER_SECRET_FILE",
     ];
 
     // Clear single-user flat vars
@@ -562,6 +1393,23 @@ fn parse_csv_env(key: &str) -> Vec<String> {
         .unwrap_or_default()
 }
 
+/// Parse `user:password,user2` into decoy credentials (no password = any password).
+fn parse_honeypot_env(key: &str) -> Vec<HoneypotCredential> {
+    parse_csv_env(key)
+        .into_iter()
+        .map(|entry| match entry.split_once(':') {
+            Some((username, password)) => HoneypotCredential {
+                username: username.to_string(),
+                password: Some(password.to_string()),
+            },
+            None => HoneypotCredential {
+                username: entry,
+                password: None,
+            },
+        })
+        .collect()
+}
+
 fn parse_cidr_csv_env(key: &str) -> anyhow::Result<Vec<ipnet::IpNet>> {
     opt_env(key)
         .map(|s| {
@@ -575,6 +1423,254 @@ fn parse_cidr_csv_env(key: &str) -> anyhow::Result<Vec<ipnet::IpNet>> {
         .map(|v| v.unwrap_or_default())
 }
 
+/// `name=ip` pairs, comma-separated; repeat a name for several addresses.
+fn parse_hosts_env(key: &str) -> anyhow::Result<HashMap<String, Vec<std::net::IpAddr>>> {
+    let mut hosts: HashMap<String, Vec<std::net::IpAddr>> = HashMap::new();
+    for entry in parse_csv_env(key) {
+        let (name, ip) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("invalid {key} entry '{entry}' (expected name=ip)"))?;
+        let ip = ip
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid {key} entry '{entry}': {e}"))?;
+        hosts.entry(name.trim().to_string()).or_default().push(ip);
+    }
+    Ok(hosts)
+}
+
+fn parse_address_family(s: &str) -> anyhow::Result<AddressFamily> {
+    match s.to_ascii_lowercase().as_str() {
+        "any" => Ok(AddressFamily::Any),
+        "prefer_ipv6" => Ok(AddressFamily::PreferIpv6),
+        "prefer_ipv4" => Ok(AddressFamily::PreferIpv4),
+        "ipv4_only" => Ok(AddressFamily::Ipv4Only),
+        "ipv6_only" => Ok(AddressFamily::Ipv6Only),
+        _ => anyhow::bail!(
+            "invalid address family: '{s}' (expected 'any', 'prefer_ipv6', 'prefer_ipv4', \
+             'ipv4_only' or 'ipv6_only')"
+        ),
+    }
+}
+
+fn parse_cluster_backend(s: &str) -> anyhow::Result<ClusterBackend> {
+    match s.to_ascii_lowercase().as_str() {
+        "redis" => Ok(ClusterBackend::Redis),
+        "gossip" => Ok(ClusterBackend::Gossip),
+        _ => anyhow::bail!("invalid cluster backend: '{s}' (expected 'redis' or 'gossip')"),
+    }
+}
+
+fn parse_dns_eviction(s: &str) -> anyhow::Result<DnsEvictionPolicy> {
+    match s.to_ascii_lowercase().as_str() {
+        "lru" => Ok(DnsEvictionPolicy::Lru),
+        "lfu" => Ok(DnsEvictionPolicy::Lfu),
+        "fifo" => Ok(DnsEvictionPolicy::Fifo),
+        _ => anyhow::bail!("invalid DNS cache eviction: '{s}' (expected 'lru', 'lfu' or 'fifo')"),
+    }
+}
+
+fn parse_source_conditions(values: &[String]) -> anyhow::Result<Vec<SourceCondition>> {
+    values
+        .iter()
+        .map(|s| match s.to_ascii_lowercase().as_str() {
+            "source_is_tor" => Ok(SourceCondition::SourceIsTor),
+            "source_is_datacenter" => Ok(SourceCondition::SourceIsDatacenter),
+            _ => anyhow::bail!(
+                "invalid IP intel condition: '{s}' \
+                 (expected 'source_is_tor' or 'source_is_datacenter')"
+            ),
+        })
+        .collect()
+}
+
+fn parse_overload_policy(s: &str) -> anyhow::Result<OverloadPolicy> {
+    match s.to_ascii_lowercase().as_str() {
+        "reject-new" => Ok(OverloadPolicy::RejectNew),
+        "drop-oldest-preauth" => Ok(OverloadPolicy::DropOldestPreauth),
+        _ => anyhow::bail!(
+            "invalid overload policy: '{s}' (expected 'reject-new' or 'drop-oldest-preauth')"
+        ),
+    }
+}
+
+fn parse_auth_backend(s: &str) -> anyhow::Result<AuthBackend> {
+    match s.to_ascii_lowercase().as_str() {
+        "local" => Ok(AuthBackend::Local),
+        "external" => Ok(AuthBackend::External),
+        _ => anyhow::bail!("invalid auth backend: '{s}' (expected 'local' or 'external')"),
+    }
+}
+
+fn parse_password_hash_algorithm(s: &str) -> anyhow::Result<PasswordHashAlgorithm> {
+    match s.to_ascii_lowercase().as_str() {
+        "argon2id" => Ok(PasswordHashAlgorithm::Argon2id),
+        "bcrypt" => Ok(PasswordHashAlgorithm::Bcrypt),
+        "scrypt" => Ok(PasswordHashAlgorithm::Scrypt),
+        _ => anyhow::bail!(
+            "invalid password hash algorithm: '{s}' (expected 'argon2id', 'bcrypt' or 'scrypt')"
+        ),
+    }
+}
+
+fn parse_impossible_travel_action(s: &str) -> anyhow::Result<ImpossibleTravelAction> {
+    match s.to_ascii_lowercase().as_str() {
+        "off" => Ok(ImpossibleTravelAction::Off),
+        "flag" => Ok(ImpossibleTravelAction::Flag),
+        "block" => Ok(ImpossibleTravelAction::Block),
+        _ => anyhow::bail!(
+            "invalid impossible travel action: '{s}' (expected 'off', 'flag' or 'block')"
+        ),
+    }
+}
+
+/// `[security.external_auth]` from `S5_EXTERNAL_AUTH_*`, if a command or URL is set.
+/// The command is split on whitespace (no shell quoting).
+fn parse_external_auth_env() -> anyhow::Result<Option<ExternalAuthConfig>> {
+    let command: Vec<String> = opt_env("S5_EXTERNAL_AUTH_COMMAND")
+        .map(|c| c.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    let url = opt_env("S5_EXTERNAL_AUTH_URL");
+    if command.is_empty() && url.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(ExternalAuthConfig {
+        command,
+        url,
+        secret: resolve_env_or_file("S5_EXTERNAL_AUTH_SECRET")?,
+        timeout_secs: parse_env("S5_EXTERNAL_AUTH_TIMEOUT", 5),
+        allow_private_ips: parse_bool_env("S5_EXTERNAL_AUTH_ALLOW_PRIVATE_IPS", false),
+    }))
+}
+
+/// A comma-separated port list (`S5_JUMP_PORTS`, `S5_BLOCKED_PORTS`), if
+/// set; `none` is the empty list.
+fn parse_ports_env(var: &str) -> anyhow::Result<Option<Vec<u16>>> {
+    match opt_env(var) {
+        None => return Ok(None),
+        Some(v) if v.trim().eq_ignore_ascii_case("none") => return Ok(Some(Vec::new())),
+        Some(_) => {}
+    }
+    parse_csv_env(var)
+        .iter()
+        .map(|p| p.parse())
+        .collect::<Result<Vec<u16>, _>>()
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("invalid {var}: {e}"))
+}
+
+/// Build the SMTP settings from `S5_SMTP_*` (None when `S5_SMTP_HOST` is unset).
+fn parse_smtp_env() -> anyhow::Result<Option<SmtpConfig>> {
+    let Some(host) = opt_env("S5_SMTP_HOST") else {
+        return Ok(None);
+    };
+    Ok(Some(SmtpConfig {
+        host,
+        port: parse_env("S5_SMTP_PORT", 587),
+        tls: opt_env("S5_SMTP_TLS")
+            .map(|s| parse_smtp_tls(&s))
+            .transpose()?
+            .unwrap_or_default(),
+        username: opt_env("S5_SMTP_USERNAME"),
+        password: resolve_env_or_file("S5_SMTP_PASSWORD")?,
+        from: opt_env("S5_SMTP_FROM").unwrap_or_default(),
+        to: parse_csv_env("S5_SMTP_TO"),
+        timeout_secs: parse_env("S5_SMTP_TIMEOUT", 10),
+    }))
+}
+
+fn parse_smtp_tls(s: &str) -> anyhow::Result<SmtpTlsMode> {
+    match s.to_ascii_lowercase().as_str() {
+        "starttls" => Ok(SmtpTlsMode::Starttls),
+        "tls" => Ok(SmtpTlsMode::Tls),
+        "none" => Ok(SmtpTlsMode::None),
+        _ => anyhow::bail!("invalid SMTP TLS mode: '{s}' (expected 'starttls', 'tls' or 'none')"),
+    }
+}
+
+fn build_notifications_from_env() -> anyhow::Result<NotificationsConfig> {
+    let mut notifications = NotificationsConfig {
+        smtp: parse_smtp_env()?,
+        sinks: parse_notification_sinks_env()?,
+        rules: Vec::new(),
+        batch_window_secs: parse_env("S5_NOTIFICATION_BATCH_WINDOW", 60),
+        dedup_window_secs: parse_env("S5_NOTIFICATION_DEDUP_WINDOW", 3600),
+        max_events_per_email: parse_env("S5_NOTIFICATION_MAX_EVENTS", 50),
+        auth_failure_threshold: parse_env("S5_NOTIFICATION_AUTH_FAILURE_THRESHOLD", 20),
+        auth_failure_window_secs: parse_env("S5_NOTIFICATION_AUTH_FAILURE_WINDOW", 60),
+    };
+    notifications.rules = parse_notification_rules_env("S5_NOTIFICATION_EVENTS", &notifications)?;
+    Ok(notifications)
+}
+
+/// Slack and Discord sinks from `S5_NOTIFICATION_{SLACK,DISCORD}_URL`, named
+/// `slack` and `discord`.
+fn parse_notification_sinks_env() -> anyhow::Result<Vec<NotificationSinkConfig>> {
+    let mut sinks = Vec::new();
+    for (key, name, kind) in [
+        (
+            "S5_NOTIFICATION_SLACK_URL",
+            "slack",
+            NotificationSinkKind::Slack,
+        ),
+        (
+            "S5_NOTIFICATION_DISCORD_URL",
+            "discord",
+            NotificationSinkKind::Discord,
+        ),
+    ] {
+        if let Some(url) = resolve_env_or_file(key)? {
+            sinks.push(NotificationSinkConfig {
+                name: name.to_string(),
+                kind,
+                url,
+                room_id: None,
+                access_token: None,
+                secret: None,
+                allow_private_ips: false,
+            });
+        }
+    }
+    Ok(sinks)
+}
+
+/// Parse `ban,quota_exceeded,...` into one rule per event (all users),
+/// delivered to email (when SMTP is set) and every configured sink.
+fn parse_notification_rules_env(
+    key: &str,
+    notifications: &NotificationsConfig,
+) -> anyhow::Result<Vec<NotificationRule>> {
+    let mut sinks: Vec<String> = notifications.sinks.iter().map(|s| s.name.clone()).collect();
+    if notifications.smtp.is_some() {
+        sinks.insert(0, EMAIL_SINK.to_string());
+    }
+    parse_csv_env(key)
+        .into_iter()
+        .map(|name| {
+            let event = match name.to_ascii_lowercase().as_str() {
+                "ban" => NotificationEvent::Ban,
+                "quota_exceeded" => NotificationEvent::QuotaExceeded,
+                "new_country" => NotificationEvent::NewCountry,
+                "auth_failure_spike" => NotificationEvent::AuthFailureSpike,
+                "login_anomaly" => NotificationEvent::LoginAnomaly,
+                "key_enrollment" => NotificationEvent::KeyEnrollment,
+                "key_expiring" => NotificationEvent::KeyExpiring,
+                _ => anyhow::bail!(
+                    "invalid notification event in {key}: '{name}' (expected 'ban', \
+                     'quota_exceeded', 'new_country', 'auth_failure_spike', 'login_anomaly', \
+                     'key_enrollment' or 'key_expiring')"
+                ),
+            };
+            Ok(NotificationRule {
+                event,
+                users: Vec::new(),
+                to: Vec::new(),
+                sinks: sinks.clone(),
+            })
+        })
+        .collect()
+}
+
 fn parse_acl_policy(s: &str) -> anyhow::Result<AclPolicyConfig> {
     match s.to_ascii_lowercase().as_str() {
         "allow" => Ok(AclPolicyConfig::Allow),
@@ -781,7 +1877,7 @@ username = "test"
 password_hash = "argon2id-fake"
 "##;
                 let mut config: AppConfig = toml::from_str(toml_str).unwrap();
-                apply_env_overrides(&mut config);
+                apply_env_overrides(&mut config).unwrap();
 
                 assert_eq!(config.logging.level, LogLevel::Debug);
                 assert_eq!(config.api.token, "secret-from-env");
@@ -790,6 +1886,19 @@ password_hash = "argon2id-fake"
         );
     }
 
+    #[test]
+    fn test_apply_env_overrides_rejects_invalid_eviction() {
+        with_env_vars(&[("S5_DNS_CACHE_EVICTION", "random")], || {
+            let mut config: AppConfig = toml::from_str(
+                "[server]\nssh_listen = \"0.0.0.0:2222\"\n\n\
+                 [[users]]\nusername = \"test\"\npassword_hash = \"argon2id-fake\"\n",
+            )
+            .unwrap();
+            let err = apply_env_overrides(&mut config).unwrap_err();
+            assert!(err.to_string().contains("random"));
+        });
+    }
+
     #[test]
     fn test_parse_csv_env_empty() {
         with_env_vars(&[], || {
@@ -863,7 +1972,7 @@ password_hash = "argon2id-fake"
                 assert!(config.users[0].allow_shell);
 
                 assert_eq!(config.users[1].username, "bob");
-                assert!(!config.users[1].allow_forwarding);
+                assert_eq!(config.users[1].allow_forwarding, Some(false));
                 assert!(!config.users[1].allow_shell);
             },
         );
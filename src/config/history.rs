@@ -0,0 +1,117 @@
+//! Recently applied configurations, kept for rollback (`api.config_history`).
+//!
+//! The configuration s5 starts with and every successful reload (API,
+//! SIGHUP or rollback) is recorded with its TOML source. Listing them with
+//! `GET /api/config/history` shows what was applied and when, and
+//! `POST /api/config/rollback/{id}` writes an earlier one back to the config
+//! file and applies it, so a bad hot-reload can be undone without a shell on
+//! the host.
+//!
+//! Snapshots live in memory: the oldest are dropped past
+//! `api.config_history` entries, and all of them on restart. A reload that
+//! leaves the file unchanged is not recorded again.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One applied configuration.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSnapshot {
+    pub id: u64,
+    pub applied_at: DateTime<Utc>,
+    /// `startup`, `api` (`POST /api/reload`), `signal` (SIGHUP) or `rollback`
+    pub source: String,
+    pub users_count: usize,
+    /// Hex SHA-256 of the TOML source
+    pub sha256: String,
+    /// TOML source; holds secrets, so it is never serialized
+    #[serde(skip)]
+    pub content: String,
+}
+
+/// Bounded list of applied configurations, oldest first.
+pub struct ConfigHistory {
+    keep: AtomicUsize,
+    next_id: AtomicU64,
+    snapshots: Mutex<VecDeque<ConfigSnapshot>>,
+}
+
+impl ConfigHistory {
+    /// Keep the last `keep` configurations (0 = record nothing).
+    pub fn new(keep: usize) -> Self {
+        Self {
+            keep: AtomicUsize::new(keep),
+            next_id: AtomicU64::new(1),
+            snapshots: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Apply a reloaded `api.config_history`, dropping the oldest snapshots
+    /// past the new size.
+    pub fn set_keep(&self, keep: usize) {
+        self.keep.store(keep, Ordering::Relaxed);
+        let mut snapshots = self.lock();
+        while snapshots.len() > keep {
+            snapshots.pop_front();
+        }
+    }
+
+    /// Record `content` as the configuration now applied. Returns the new
+    /// snapshot, or None if history is disabled or `content` is the same as
+    /// the current one.
+    pub fn record(
+        &self,
+        content: &str,
+        source: &str,
+        users_count: usize,
+    ) -> Option<ConfigSnapshot> {
+        let keep = self.keep.load(Ordering::Relaxed);
+        if keep == 0 {
+            return None;
+        }
+        let sha256 = sha256_hex(content);
+        let mut snapshots = self.lock();
+        if snapshots.back().is_some_and(|s| s.sha256 == sha256) {
+            return None;
+        }
+        let snapshot = ConfigSnapshot {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            applied_at: Utc::now(),
+            source: source.to_string(),
+            users_count,
+            sha256,
+            content: content.to_string(),
+        };
+        snapshots.push_back(snapshot.clone());
+        while snapshots.len() > keep {
+            snapshots.pop_front();
+        }
+        Some(snapshot)
+    }
+
+    /// Snapshots, newest (the current configuration) first.
+    pub fn list(&self) -> Vec<ConfigSnapshot> {
+        self.lock().iter().rev().cloned().collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<ConfigSnapshot> {
+        self.lock().iter().find(|s| s.id == id).cloned()
+    }
+
+    /// Id of the configuration applied last.
+    pub fn current_id(&self) -> Option<u64> {
+        self.lock().back().map(|s| s.id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ConfigSnapshot>> {
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn sha256_hex(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
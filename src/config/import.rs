@@ -0,0 +1,282 @@
+//! Conversion of OpenSSH-style user files into `[[users]]` entries
+//! (`s5 import`).
+//!
+//! Two layouts are read, alone or together:
+//!
+//! - an htpasswd file (`user:hash` per line). Only Argon2, bcrypt and
+//!   scrypt hashes can be verified by s5; users with MD5-crypt or SHA hashes
+//!   are imported without a password and reported, so they need
+//!   `s5 hash-password` or their keys.
+//! - an authorized_keys directory, holding one file per user
+//!   (`<dir>/alice`, `<dir>/alice.pub`) or home directories
+//!   (`<dir>/alice/.ssh/authorized_keys`, as in `/home`).
+//!
+//! Users found in both are merged into a single entry.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Table};
+
+/// One user to add to the configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedUser {
+    pub username: String,
+    pub password_hash: Option<String>,
+    pub authorized_keys: Vec<String>,
+}
+
+/// Users read from the source files, sorted by username, and what could not
+/// be carried over.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub users: Vec<ImportedUser>,
+    pub warnings: Vec<String>,
+}
+
+impl Import {
+    /// Read an htpasswd file and/or an authorized_keys directory.
+    pub fn read(passwd_file: Option<&Path>, authorized_keys_dir: Option<&Path>) -> Result<Self> {
+        let mut import = Import::default();
+        let mut users: BTreeMap<String, ImportedUser> = BTreeMap::new();
+
+        if let Some(path) = passwd_file {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("reading passwd file: {}", path.display()))?;
+            for (username, hash) in parse_htpasswd(&content, &mut import.warnings) {
+                users.entry(username.clone()).or_default().password_hash = hash;
+            }
+        }
+
+        if let Some(dir) = authorized_keys_dir {
+            for (username, keys) in read_authorized_keys_dir(dir, &mut import.warnings)? {
+                users.entry(username).or_default().authorized_keys = keys;
+            }
+        }
+
+        for (username, mut user) in users {
+            if user.password_hash.is_none() && user.authorized_keys.is_empty() {
+                import
+                    .warnings
+                    .push(format!("{}: no usable password or key, skipped", username));
+                continue;
+            }
+            user.username = username;
+            import.users.push(user);
+        }
+        Ok(import)
+    }
+}
+
+/// Parse htpasswd `content` into `(username, password_hash)` pairs. Hashes
+/// s5 cannot verify are dropped (None) with a warning.
+pub fn parse_htpasswd(content: &str, warnings: &mut Vec<String>) -> Vec<(String, Option<String>)> {
+    let mut users = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((username, hash)) = line.split_once(':') else {
+            warnings.push(format!(
+                "passwd line {}: expected user:hash, skipped",
+                n + 1
+            ));
+            continue;
+        };
+        let username = username.trim();
+        if username.is_empty() {
+            warnings.push(format!("passwd line {}: empty username, skipped", n + 1));
+            continue;
+        }
+        // Some tools append extra `:`-separated fields after the hash
+        let hash = hash.split(':').next().unwrap_or_default().trim();
+        let hash = if crate::auth::password::hash_algorithm(hash).is_some() {
+            Some(hash.to_string())
+        } else {
+            warnings.push(format!(
+                "{}: {} hash cannot be verified by s5, imported without a password",
+                username,
+                hash_kind(hash)
+            ));
+            None
+        };
+        users.push((username.to_string(), hash));
+    }
+    users
+}
+
+fn hash_kind(hash: &str) -> &'static str {
+    if hash.starts_with("$apr1$") || hash.starts_with("$1$") {
+        "MD5"
+    } else if hash.starts_with("$5$") || hash.starts_with("$6$") || hash.starts_with("{SHA}") {
+        "SHA"
+    } else {
+        "unsupported"
+    }
+}
+
+/// Parse authorized_keys `content` into `type base64 [comment]` lines.
+/// Options in front of a key (`command=`, `from=`, ...) are not supported by
+/// s5: the key is kept and the dropped restriction reported.
+pub fn parse_authorized_keys(
+    content: &str,
+    username: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(start) = tokens.iter().position(|t| is_key_type(t)) else {
+            warnings.push(format!(
+                "{}: line {}: no key found, skipped",
+                username,
+                n + 1
+            ));
+            continue;
+        };
+        let key_line = tokens[start..].join(" ");
+        if let Err(e) = crate::auth::pubkey::parse_authorized_key(&key_line) {
+            warnings.push(format!("{}: line {}: {}, skipped", username, n + 1, e));
+            continue;
+        }
+        if start > 0 {
+            warnings.push(format!(
+                "{}: line {}: key options dropped ({})",
+                username,
+                n + 1,
+                tokens[..start].join(" ")
+            ));
+        }
+        if !keys.contains(&key_line) {
+            keys.push(key_line);
+        }
+    }
+    keys
+}
+
+fn is_key_type(token: &str) -> bool {
+    token.starts_with("ssh-")
+        || token.starts_with("ecdsa-sha2-")
+        || token.starts_with("sk-ssh-")
+        || token.starts_with("sk-ecdsa-")
+}
+
+/// Read `<dir>/<user>[.pub]` files and `<dir>/<user>/.ssh/authorized_keys`
+/// (or `<dir>/<user>/authorized_keys`). Users without valid keys are left
+/// out.
+pub fn read_authorized_keys_dir(
+    dir: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("reading authorized_keys directory: {}", dir.display()))?;
+    let mut users: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let (username, file) = if path.is_dir() {
+            let nested = path.join(".ssh").join("authorized_keys");
+            let flat = path.join("authorized_keys");
+            match [nested, flat].into_iter().find(|p| p.is_file()) {
+                Some(file) => (name, file),
+                None => continue,
+            }
+        } else {
+            (name.strip_suffix(".pub").unwrap_or(&name).to_string(), path)
+        };
+        let content = match std::fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) => {
+                warnings.push(format!("{}: {}, skipped", file.display(), e));
+                continue;
+            }
+        };
+        let keys = parse_authorized_keys(&content, &username, warnings);
+        let user_keys = users.entry(username).or_default();
+        for key in keys {
+            if !user_keys.contains(&key) {
+                user_keys.push(key);
+            }
+        }
+    }
+    users.retain(|_, keys| !keys.is_empty());
+    Ok(users)
+}
+
+/// Render `users` as `[[users]]` TOML entries.
+pub fn to_toml(users: &[ImportedUser]) -> String {
+    let mut doc = DocumentMut::new();
+    doc["users"] = toml_edit::Item::ArrayOfTables(users_tables(users));
+    doc.to_string()
+}
+
+/// Append `users` to the configuration `content`, leaving users it already
+/// defines untouched. Returns the edited document, validated, and the names
+/// of the users added.
+pub fn append_users(content: &str, users: &[ImportedUser]) -> Result<(String, Vec<String>)> {
+    let mut doc: DocumentMut = content.parse().context("parsing TOML configuration")?;
+    let existing: Vec<String> = doc
+        .get("users")
+        .and_then(|item| item.as_array_of_tables())
+        .map(|tables| {
+            tables
+                .iter()
+                .filter_map(|t| t.get("username").and_then(|v| v.as_str()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let new: Vec<ImportedUser> = users
+        .iter()
+        .filter(|u| !existing.contains(&u.username))
+        .cloned()
+        .collect();
+    if new.is_empty() {
+        return Ok((content.to_string(), Vec::new()));
+    }
+
+    match doc.get_mut("users") {
+        Some(item) => {
+            let tables = item
+                .as_array_of_tables_mut()
+                .context("`users` is not an array of [[users]] tables")?;
+            for table in users_tables(&new).iter() {
+                tables.push(table.clone());
+            }
+        }
+        None => doc["users"] = toml_edit::Item::ArrayOfTables(users_tables(&new)),
+    }
+
+    let updated = doc.to_string();
+    super::parse_config(&updated).context("updated config failed validation")?;
+    Ok((updated, new.into_iter().map(|u| u.username).collect()))
+}
+
+fn users_tables(users: &[ImportedUser]) -> ArrayOfTables {
+    let mut tables = ArrayOfTables::new();
+    for user in users {
+        let mut table = Table::new();
+        table["username"] = value(user.username.as_str());
+        if let Some(ref hash) = user.password_hash {
+            table["password_hash"] = value(hash.as_str());
+        }
+        if !user.authorized_keys.is_empty() {
+            let mut keys = Array::new();
+            for key in &user.authorized_keys {
+                keys.push(key.as_str());
+            }
+            table["authorized_keys"] = value(keys);
+        }
+        tables.push(table);
+    }
+    tables
+}
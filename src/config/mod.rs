@@ -1,18 +1,45 @@
 pub mod acl;
 pub mod env;
+pub mod history;
+pub mod import;
+pub mod persist;
 pub mod presets;
 pub mod redact;
 pub mod types;
 
 use anyhow::{Context, Result};
 use std::path::Path;
-use types::AppConfig;
+use types::{AppConfig, AuthBackend, NotificationEvent, NotificationSinkKind, EMAIL_SINK};
 
 /// Maximum config file size (1 MB)
 const MAX_CONFIG_SIZE: u64 = 1_048_576;
 
 /// Load and validate configuration from a TOML file
 pub fn load_config(path: &Path) -> Result<AppConfig> {
+    load_config_with_source(path).map(|(config, _)| config)
+}
+
+/// Load the configuration the way the server does at startup: the file with
+/// environment overrides applied, or the environment alone when the file
+/// does not exist and `S5_*` variables describe a config.
+pub fn load_effective_config(path: &Path) -> Result<AppConfig> {
+    if path.exists() {
+        let mut config = load_config(path)?;
+        env::apply_env_overrides(&mut config)?;
+        Ok(config)
+    } else if env::can_build_from_env() {
+        let config = env::build_config_from_env()?;
+        validate_config(&config)?;
+        Ok(config)
+    } else {
+        // Produces the "file not found" error
+        load_config(path)
+    }
+}
+
+/// Like [`load_config`], also returning the TOML source (recorded in the
+/// config history).
+pub fn load_config_with_source(path: &Path) -> Result<(AppConfig, String)> {
     let metadata = std::fs::metadata(path)
         .with_context(|| format!("reading config metadata: {}", path.display()))?;
     if metadata.len() > MAX_CONFIG_SIZE {
@@ -28,7 +55,8 @@ pub fn load_config(path: &Path) -> Result<AppConfig> {
 
     let content = std::fs::read_to_string(path)
         .with_context(|| format!("reading config: {}", path.display()))?;
-    parse_config(&content)
+    let config = parse_config(&content)?;
+    Ok((config, content))
 }
 
 /// On Unix, warn if the config file is readable by group or others,
@@ -80,13 +108,48 @@ pub fn parse_config_validate(config: &AppConfig) -> Result<()> {
 /// Validate configuration values
 fn validate_config(config: &AppConfig) -> Result<()> {
     validate_server(config)?;
+    validate_listeners(config)?;
     validate_limits(config)?;
     validate_socks5_handshake_timeout(config)?;
     validate_socks5_tls(config)?;
+    validate_masque(config)?;
+    validate_gssapi(config)?;
     validate_global_acl(config)?;
+    validate_groups(config)?;
     validate_users(config)?;
+    validate_tenants(config)?;
+    validate_quota_plans(config)?;
+    validate_jump_ports(config)?;
+    validate_blocked_ports(config)?;
+    validate_dns_cache(config)?;
+    validate_proxy_hosts(config)?;
+    validate_circuit_breaker(config)?;
+    validate_proxy_tcp(config)?;
+    validate_buffer_pool(config)?;
+    validate_priority(config)?;
+    validate_policy(config)?;
+    validate_scripting(config)?;
+    validate_external_auth(config)?;
+    validate_password_policy(config)?;
+    validate_password_hashing(config)?;
+    validate_credential_spraying(config)?;
     validate_api(config)?;
     validate_webhooks(config)?;
+    validate_logging(config)?;
+    validate_threat_intel(config)?;
+    validate_ip_intel(config)?;
+    validate_honeypot(config)?;
+    validate_login_anomaly(config)?;
+    validate_impossible_travel(config)?;
+    validate_key_enrollment(config)?;
+    validate_invitations(config)?;
+    validate_authorized_keys_fetch(config)?;
+    validate_notifications(config)?;
+    validate_reports(config)?;
+    validate_cluster(config)?;
+    validate_sandbox(config)?;
+    validate_vpn(config)?;
+    validate_subsystems(config)?;
     Ok(())
 }
 
@@ -121,6 +184,72 @@ fn validate_server(config: &AppConfig) -> Result<()> {
             config.server.server_id
         );
     }
+    let packet = config.server.ssh_max_packet_size;
+    if !(4096..=256 * 1024).contains(&packet) {
+        anyhow::bail!("server.ssh_max_packet_size must be between 4096 and 262144 bytes");
+    }
+    if config.server.ssh_window_size < packet {
+        anyhow::bail!("server.ssh_window_size must be >= server.ssh_max_packet_size");
+    }
+    let (max, per_user) = (
+        config.server.max_connections,
+        config.server.max_connections_per_user,
+    );
+    if max > 0 && per_user > max {
+        anyhow::bail!("server.max_connections_per_user must be <= server.max_connections");
+    }
+    if config.server.connection_queue_timeout_ms > 300_000 {
+        anyhow::bail!("server.connection_queue_timeout_ms must be <= 300000");
+    }
+    let rotation = &config.server.host_key_rotation;
+    if let Some(path) = &rotation.new_key_path {
+        if path == &config.server.host_key_path {
+            anyhow::bail!("server.host_key_rotation.new_key_path must differ from host_key_path");
+        }
+        if rotation.retire_after_days == 0 {
+            anyhow::bail!("server.host_key_rotation.retire_after_days must be > 0");
+        }
+    }
+    if config.server.socket_handover {
+        if !cfg!(target_os = "linux") {
+            anyhow::bail!("server.socket_handover is only supported on Linux");
+        }
+        if config.api.unix_socket.is_some() {
+            anyhow::bail!("server.socket_handover cannot hand over api.unix_socket");
+        }
+    }
+    Ok(())
+}
+
+fn validate_listeners(config: &AppConfig) -> Result<()> {
+    let mut names = std::collections::HashSet::new();
+    let mut addrs = std::collections::HashSet::from([config.server.ssh_listen.as_str()]);
+    for listener in &config.server.listeners {
+        let name = &listener.name;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!(
+                "server.listeners: invalid name '{}' (letters, digits, '-' and '_' only)",
+                name
+            );
+        }
+        if name == "main" || !names.insert(name.as_str()) {
+            anyhow::bail!("server.listeners: duplicate listener name '{}'", name);
+        }
+        if listener.listen.is_empty() {
+            anyhow::bail!("server.listeners '{}': listen must not be empty", name);
+        }
+        if !addrs.insert(listener.listen.as_str()) {
+            anyhow::bail!(
+                "server.listeners '{}': address {} is already used by another listener",
+                name,
+                listener.listen
+            );
+        }
+    }
     Ok(())
 }
 
@@ -128,14 +257,202 @@ fn validate_limits(config: &AppConfig) -> Result<()> {
     if config.limits.connection_timeout == 0 {
         anyhow::bail!("limits.connection_timeout must be > 0");
     }
+    let (total, per_ip) = (
+        config.limits.max_unauthenticated_connections,
+        config.limits.max_unauthenticated_per_ip,
+    );
+    if total > 0 && per_ip > total {
+        anyhow::bail!(
+            "limits.max_unauthenticated_per_ip ({}) must not exceed limits.max_unauthenticated_connections ({})",
+            per_ip,
+            total
+        );
+    }
+    // Each relay direction holds up to one buffer at a time
+    let min_buffered = 2 * config.proxy.buffer_pool.buffer_size as u64;
+    let max_buffered = config.limits.max_buffered_bytes;
+    if max_buffered > 0 && max_buffered < min_buffered {
+        anyhow::bail!(
+            "limits.max_buffered_bytes must be 0 or >= 2 x proxy.buffer_pool.buffer_size ({})",
+            min_buffered
+        );
+    }
     if config.security.ban_enabled && config.security.ban_threshold < 1 {
         anyhow::bail!("security.ban_threshold must be >= 1");
     }
+    if config.security.lock_after_failures > 0 && config.security.lock_duration == 0 {
+        anyhow::bail!("security.lock_duration must be > 0 when lock_after_failures is set");
+    }
+    if config.security.tarpit_enabled {
+        if config.security.tarpit_max_connections == 0 {
+            anyhow::bail!("security.tarpit_max_connections must be > 0");
+        }
+        if config.security.tarpit_interval == 0 {
+            anyhow::bail!("security.tarpit_interval must be > 0");
+        }
+    }
+    Ok(())
+}
+
+fn validate_groups(config: &AppConfig) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for group in &config.groups {
+        if group.name.is_empty() {
+            anyhow::bail!("group entry has empty name");
+        }
+        if !seen.insert(group.name.as_str()) {
+            anyhow::bail!("duplicate group name: {}", group.name);
+        }
+
+        for rule in &group.acl.allow {
+            acl::AclRule::parse(rule)
+                .with_context(|| format!("group '{}' ACL allow rule: {}", group.name, rule))?;
+        }
+        for rule in &group.acl.deny {
+            acl::AclRule::parse(rule)
+                .with_context(|| format!("group '{}' ACL deny rule: {}", group.name, rule))?;
+        }
+        for rule in group.jump_targets.iter().flatten() {
+            validate_jump_target(rule)
+                .with_context(|| format!("group '{}' jump target: {}", group.name, rule))?;
+        }
+        for path in group.unix_sockets.iter().flatten() {
+            crate::proxy::streamlocal::validate_pattern(path)
+                .map_err(|e| anyhow::anyhow!("group '{}' unix_sockets: {}", group.name, e))?;
+        }
+        for path in group.fs_allow.iter().flatten() {
+            if !std::path::Path::new(path).is_absolute() {
+                anyhow::bail!(
+                    "group '{}' fs_allow: '{}' must be an absolute path",
+                    group.name,
+                    path
+                );
+            }
+        }
+        validate_terminal(group.terminal.as_ref(), &format!("group '{}'", group.name))?;
+        for value in group.allowed_hassh.iter().flatten() {
+            if !crate::ssh::handshake::is_valid_hassh(value) {
+                anyhow::bail!(
+                    "group '{}' allowed_hassh: '{}' is not a HASSH (32 hex digits)",
+                    group.name,
+                    value
+                );
+            }
+        }
+        for manager in &group.managers {
+            if !config.users.iter().any(|u| &u.username == manager) {
+                anyhow::bail!(
+                    "group '{}' manager '{}' is not a configured user",
+                    group.name,
+                    manager
+                );
+            }
+        }
+    }
+
+    // Walk each `inherits` chain: parents must exist, no cycles, bounded depth
+    for group in &config.groups {
+        let mut chain = vec![group.name.as_str()];
+        let mut parent = group.inherits.as_deref();
+        while let Some(name) = parent {
+            if !seen.contains(name) {
+                anyhow::bail!(
+                    "group '{}' inherits from unknown group '{}'",
+                    chain[chain.len() - 1],
+                    name
+                );
+            }
+            if chain.contains(&name) {
+                chain.push(name);
+                anyhow::bail!("group inheritance cycle: {}", chain.join(" -> "));
+            }
+            chain.push(name);
+            if chain.len() > types::MAX_GROUP_INHERITANCE_DEPTH {
+                anyhow::bail!(
+                    "group '{}' inheritance chain exceeds {} levels",
+                    group.name,
+                    types::MAX_GROUP_INHERITANCE_DEPTH
+                );
+            }
+            parent = config
+                .groups
+                .iter()
+                .find(|g| g.name == name)
+                .and_then(|g| g.inherits.as_deref());
+        }
+    }
+    Ok(())
+}
+
+fn validate_tenants(config: &AppConfig) -> Result<()> {
+    let mut names = std::collections::HashSet::new();
+    let mut tokens = std::collections::HashSet::from([config.api.token.as_str()]);
+    for tenant in &config.tenants {
+        let name = &tenant.name;
+        // Lowercase, so the suffix compares like a domain; no ':' (SSE tickets)
+        if name.is_empty()
+            || name.starts_with(['.', '-'])
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-')
+        {
+            anyhow::bail!(
+                "tenants: invalid name '{}' (lowercase letters, digits, '.' and '-' only)",
+                name
+            );
+        }
+        if !names.insert(name.as_str()) {
+            anyhow::bail!("tenants: duplicate tenant '{}'", name);
+        }
+        if let Some(token) = &tenant.api_token {
+            if token.len() < 16 {
+                anyhow::bail!(
+                    "tenants '{}': api_token is too short ({} chars, minimum 16)",
+                    name,
+                    token.len()
+                );
+            }
+            if !tokens.insert(token.as_str()) {
+                anyhow::bail!("tenants '{}': api_token is already used", name);
+            }
+        }
+    }
+    for listener in &config.server.listeners {
+        if let Some(tenant) = &listener.tenant {
+            if !names.contains(tenant.as_str()) {
+                anyhow::bail!(
+                    "server.listeners '{}': unknown tenant '{}'",
+                    listener.name,
+                    tenant
+                );
+            }
+        }
+    }
+    for account in &config.api.accounts {
+        let Some(tenant) = &account.tenant else {
+            continue;
+        };
+        if !names.contains(tenant.as_str()) {
+            anyhow::bail!(
+                "api.accounts '{}': unknown tenant '{}'",
+                account.username,
+                tenant
+            );
+        }
+        if account.role == types::DashboardRole::Admin {
+            anyhow::bail!(
+                "api.accounts '{}': tenant accounts cannot be admin",
+                account.username
+            );
+        }
+    }
     Ok(())
 }
 
 fn validate_users(config: &AppConfig) -> Result<()> {
-    if config.users.is_empty() {
+    // With external auth, [[users]] entries are optional per-user profiles
+    let external = config.security.auth_backend == AuthBackend::External;
+    if config.users.is_empty() && !external {
         anyhow::bail!("at least one user is required");
     }
 
@@ -144,12 +461,29 @@ fn validate_users(config: &AppConfig) -> Result<()> {
         if user.username.is_empty() {
             anyhow::bail!("user entry has empty username");
         }
-        if user.password_hash.is_none() && user.authorized_keys.is_empty() {
+        if user.password_hash.is_none()
+            && user.authorized_keys.is_empty()
+            && user.authorized_keys_url.is_none()
+            && !external
+        {
             anyhow::bail!(
-                "user '{}' must have at least a password_hash or authorized_keys",
+                "user '{}' must have at least a password_hash, authorized_keys or \
+                 authorized_keys_url",
                 user.username
             );
         }
+        if let Some(ref url) = user.authorized_keys_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!(
+                    "user '{}': authorized_keys_url must be an http:// or https:// URL",
+                    user.username
+                );
+            }
+        }
+        for line in &user.authorized_keys {
+            crate::auth::pubkey::key_options(line)
+                .with_context(|| format!("user '{}' authorized_keys", user.username))?;
+        }
         if !seen.insert(&user.username) {
             anyhow::bail!("duplicate username: {}", user.username);
         }
@@ -162,6 +496,360 @@ fn validate_users(config: &AppConfig) -> Result<()> {
             acl::AclRule::parse(rule)
                 .with_context(|| format!("user '{}' ACL deny rule: {}", user.username, rule))?;
         }
+        for rule in user.jump_targets.iter().flatten() {
+            validate_jump_target(rule)
+                .with_context(|| format!("user '{}' jump target: {}", user.username, rule))?;
+        }
+        for path in user.unix_sockets.iter().flatten() {
+            crate::proxy::streamlocal::validate_pattern(path)
+                .map_err(|e| anyhow::anyhow!("user '{}' unix_sockets: {}", user.username, e))?;
+        }
+        validate_terminal(user.terminal.as_ref(), &format!("user '{}'", user.username))?;
+        for value in user.allowed_hassh.iter().flatten() {
+            if !crate::ssh::handshake::is_valid_hassh(value) {
+                anyhow::bail!(
+                    "user '{}' allowed_hassh: '{}' is not a HASSH (32 hex digits)",
+                    user.username,
+                    value
+                );
+            }
+        }
+        let mut token_ids = std::collections::HashSet::new();
+        for token in &user.api_tokens {
+            if token.id.is_empty() || !token_ids.insert(&token.id) {
+                anyhow::bail!(
+                    "user '{}' api_tokens: empty or duplicate id '{}'",
+                    user.username,
+                    token.id
+                );
+            }
+            if token.sha256.len() != 64 || !token.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                anyhow::bail!(
+                    "user '{}' api_tokens '{}': sha256 must be 64 hex digits",
+                    user.username,
+                    token.id
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check a group's or user's `terminal` policy; `owner` names it in errors.
+/// Jump targets follow the ACL rule format, except that the port is
+/// required: `host` alone would allow hops to any port.
+fn validate_jump_target(rule: &str) -> Result<()> {
+    acl::AclRule::parse(rule)?;
+    let explicit_port = match rule.strip_prefix('[') {
+        Some(rest) => rest.contains("]:"),
+        // A bare IPv6 address has colons but no port
+        None => rule
+            .rsplit_once(':')
+            .is_some_and(|(host, _)| !host.contains(':') || host.contains('/')),
+    };
+    if !explicit_port {
+        anyhow::bail!("a port is required (e.g. '{}:22')", rule);
+    }
+    Ok(())
+}
+
+fn validate_terminal(terminal: Option<&types::TerminalConfig>, owner: &str) -> Result<()> {
+    let Some(terminal) = terminal else {
+        return Ok(());
+    };
+    for pattern in terminal.accept_env.iter().flatten() {
+        crate::ssh::terminal::validate_env_pattern(pattern)
+            .map_err(|e| anyhow::anyhow!("{} terminal.accept_env: {}", owner, e))?;
+    }
+    if let Some(term) = &terminal.default_term {
+        crate::ssh::terminal::validate_term(term)
+            .map_err(|e| anyhow::anyhow!("{} terminal.default_term: {}", owner, e))?;
+    }
+    Ok(())
+}
+
+fn validate_quota_plans(config: &AppConfig) -> Result<()> {
+    for (name, plan) in &config.quota_plans {
+        if name.trim().is_empty() {
+            anyhow::bail!("quota_plans entry has empty name");
+        }
+        crate::quota::plan::parse_timezone(&plan.reset_timezone)
+            .map_err(|e| anyhow::anyhow!("quota plan '{}': {}", name, e))?;
+        if let Some(ref schedule) = plan.reset_schedule {
+            crate::quota::plan::ResetSchedule::parse(schedule, &plan.reset_timezone)
+                .map_err(|e| anyhow::anyhow!("quota plan '{}': {}", name, e))?;
+        }
+    }
+
+    let check = |owner: String, quotas: bool, plan: Option<&String>| -> Result<()> {
+        let Some(plan) = plan else {
+            return Ok(());
+        };
+        if quotas {
+            anyhow::bail!("{} sets both quotas and quota_plan", owner);
+        }
+        if !config.quota_plans.contains_key(plan) {
+            anyhow::bail!("{} uses unknown quota plan '{}'", owner, plan);
+        }
+        Ok(())
+    };
+    for group in &config.groups {
+        check(
+            format!("group '{}'", group.name),
+            group.quotas.is_some(),
+            group.quota_plan.as_ref(),
+        )?;
+    }
+    for user in &config.users {
+        check(
+            format!("user '{}'", user.username),
+            user.quotas.is_some(),
+            user.quota_plan.as_ref(),
+        )?;
+    }
+    Ok(())
+}
+
+fn validate_jump_ports(config: &AppConfig) -> Result<()> {
+    if config.security.jump_ports.contains(&0) {
+        anyhow::bail!("security.jump_ports must not contain port 0");
+    }
+    Ok(())
+}
+
+fn validate_blocked_ports(config: &AppConfig) -> Result<()> {
+    if config.security.blocked_ports.contains(&0) {
+        anyhow::bail!("security.blocked_ports must not contain port 0");
+    }
+    for group in &config.groups {
+        if group.blocked_ports.as_ref().is_some_and(|p| p.contains(&0)) {
+            anyhow::bail!(
+                "group '{}': blocked_ports must not contain port 0",
+                group.name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_dns_cache(config: &AppConfig) -> Result<()> {
+    let dns = &config.proxy.dns_cache;
+    if dns.max_ttl == 0 {
+        anyhow::bail!(
+            "proxy.dns_cache.max_ttl must be > 0 (server.dns_cache_ttl = 0 disables the cache)"
+        );
+    }
+    if dns.min_ttl > dns.max_ttl {
+        anyhow::bail!(
+            "proxy.dns_cache.min_ttl ({}) must not exceed max_ttl ({})",
+            dns.min_ttl,
+            dns.max_ttl
+        );
+    }
+    if dns.max_entries == Some(0) {
+        anyhow::bail!("proxy.dns_cache.max_entries must be > 0");
+    }
+    if dns.latency_ordering && dns.latency_ttl == 0 {
+        anyhow::bail!("proxy.dns_cache.latency_ttl must be > 0 (disable latency_ordering instead)");
+    }
+    Ok(())
+}
+
+fn validate_proxy_hosts(config: &AppConfig) -> Result<()> {
+    for (host, addrs) in &config.proxy.hosts {
+        if host.is_empty()
+            || host.len() > 253
+            || host.contains(|c: char| c.is_whitespace() || c == ':' || c == '/')
+        {
+            anyhow::bail!("proxy.hosts: invalid hostname '{}'", host);
+        }
+        if addrs.is_empty() {
+            anyhow::bail!("proxy.hosts: '{}' has no address", host);
+        }
+    }
+    Ok(())
+}
+
+fn validate_circuit_breaker(config: &AppConfig) -> Result<()> {
+    let breaker = &config.proxy.circuit_breaker;
+    if !breaker.enabled {
+        return Ok(());
+    }
+    if breaker.failure_threshold == 0 {
+        anyhow::bail!("proxy.circuit_breaker.failure_threshold must be > 0");
+    }
+    if breaker.window_secs == 0 || breaker.open_secs == 0 {
+        anyhow::bail!("proxy.circuit_breaker.window_secs and open_secs must be > 0");
+    }
+    Ok(())
+}
+
+fn validate_proxy_tcp(config: &AppConfig) -> Result<()> {
+    let tcp = &config.proxy.tcp;
+    if tcp.keepalive_time_secs > 0 && tcp.keepalive_interval_secs == 0 {
+        anyhow::bail!("proxy.tcp.keepalive_interval_secs must be > 0 when keepalive is enabled");
+    }
+    if tcp.keepalive_retries == Some(0) {
+        anyhow::bail!("proxy.tcp.keepalive_retries must be > 0 (omit it for the OS default)");
+    }
+    if tcp.user_timeout_ms > u64::from(u32::MAX) {
+        anyhow::bail!("proxy.tcp.user_timeout_ms must be <= {}", u32::MAX);
+    }
+    Ok(())
+}
+
+fn validate_buffer_pool(config: &AppConfig) -> Result<()> {
+    let size = config.proxy.buffer_pool.buffer_size;
+    if !(1024..=1024 * 1024).contains(&size) {
+        anyhow::bail!("proxy.buffer_pool.buffer_size must be between 1024 and 1048576 bytes");
+    }
+    Ok(())
+}
+
+fn validate_priority(config: &AppConfig) -> Result<()> {
+    let priority = &config.proxy.priority;
+    if priority.max_inflight == 0 {
+        anyhow::bail!("proxy.priority.max_inflight must be > 0");
+    }
+    if !(1..=1000).contains(&priority.default_weight) {
+        anyhow::bail!("proxy.priority.default_weight must be between 1 and 1000");
+    }
+    let mut names = std::collections::HashSet::new();
+    for class in &priority.classes {
+        if class.name.is_empty() || class.name == crate::proxy::priority::DEFAULT_CLASS {
+            anyhow::bail!(
+                "proxy.priority.classes: name must be set and not '{}'",
+                crate::proxy::priority::DEFAULT_CLASS
+            );
+        }
+        if !names.insert(class.name.as_str()) {
+            anyhow::bail!("proxy.priority.classes: duplicate class '{}'", class.name);
+        }
+        if !(1..=1000).contains(&class.weight) {
+            anyhow::bail!(
+                "proxy.priority class '{}': weight must be between 1 and 1000",
+                class.name
+            );
+        }
+        if class.destinations.is_empty() && class.tags.is_empty() {
+            anyhow::bail!(
+                "proxy.priority class '{}' needs destinations or tags",
+                class.name
+            );
+        }
+    }
+    crate::proxy::priority::Scheduler::from_config(priority)?;
+    Ok(())
+}
+
+fn validate_policy(config: &AppConfig) -> Result<()> {
+    let policy = &config.policy;
+    for dest in &policy.destinations {
+        if dest.tag.is_empty() {
+            anyhow::bail!("policy.destinations: tag must be set");
+        }
+        if dest.targets.is_empty() {
+            anyhow::bail!("policy destination tag '{}' needs targets", dest.tag);
+        }
+    }
+    if policy.time_windows.iter().any(|w| w.tag.is_empty()) {
+        anyhow::bail!("policy.time_windows: tag must be set");
+    }
+    crate::proxy::policy::Policy::from_config(policy)?;
+    if let Some(ref plugin) = policy.plugin {
+        if plugin.fuel == 0 || plugin.timeout_ms == 0 {
+            anyhow::bail!("policy.plugin: fuel and timeout_ms must be greater than 0");
+        }
+        if cfg!(not(feature = "wasm-plugins")) {
+            anyhow::bail!(
+                "policy.plugin: this s5 was built without WASM plugin support \
+                 (rebuild with `--features wasm-plugins`)"
+            );
+        }
+        #[cfg(feature = "wasm-plugins")]
+        crate::proxy::plugin::Plugin::load(plugin).context("policy.plugin")?;
+    }
+    Ok(())
+}
+
+fn validate_scripting(config: &AppConfig) -> Result<()> {
+    let Some(ref scripting) = config.scripting else {
+        return Ok(());
+    };
+    if scripting.timeout_ms == 0 || scripting.memory_limit_kb == 0 {
+        anyhow::bail!("scripting: timeout_ms and memory_limit_kb must be greater than 0");
+    }
+    if cfg!(not(feature = "lua-hooks")) {
+        anyhow::bail!(
+            "scripting: this s5 was built without Lua hooks support \
+             (rebuild with `--features lua-hooks`)"
+        );
+    }
+    #[cfg(feature = "lua-hooks")]
+    crate::scripting::Hooks::load(scripting).context("scripting")?;
+    Ok(())
+}
+
+fn validate_external_auth(config: &AppConfig) -> Result<()> {
+    let Some(ref ext) = config.security.external_auth else {
+        if config.security.auth_backend == AuthBackend::External {
+            anyhow::bail!("security.auth_backend = \"external\" requires [security.external_auth]");
+        }
+        return Ok(());
+    };
+    match (ext.command.is_empty(), &ext.url) {
+        (false, None) => {
+            if ext.secret.is_some() {
+                anyhow::bail!("security.external_auth.secret only applies to url");
+            }
+        }
+        (true, Some(url)) => {
+            validate_outbound_url("security.external_auth", url, ext.allow_private_ips)?
+        }
+        _ => anyhow::bail!("security.external_auth needs exactly one of command and url"),
+    }
+    if ext.timeout_secs == 0 || ext.timeout_secs > 60 {
+        anyhow::bail!("security.external_auth.timeout_secs must be between 1 and 60");
+    }
+    Ok(())
+}
+
+fn validate_password_hashing(config: &AppConfig) -> Result<()> {
+    let security = &config.security;
+    argon2::Params::new(
+        security.argon2_memory_cost,
+        security.argon2_time_cost,
+        security.argon2_parallelism,
+        None,
+    )
+    .map_err(|e| anyhow::anyhow!("security.argon2_* parameters are invalid: {e}"))?;
+    Ok(())
+}
+
+fn validate_credential_spraying(config: &AppConfig) -> Result<()> {
+    let spraying = &config.security.credential_spraying;
+    if !spraying.enabled {
+        return Ok(());
+    }
+    if spraying.min_usernames < 2 {
+        anyhow::bail!("security.credential_spraying.min_usernames must be at least 2");
+    }
+    if spraying.window_secs == 0 {
+        anyhow::bail!("security.credential_spraying.window_secs must be greater than 0");
+    }
+    Ok(())
+}
+
+fn validate_password_policy(config: &AppConfig) -> Result<()> {
+    let policy = &config.security.password_policy;
+    if policy.min_length < 8 || policy.min_length > crate::auth::self_service::MAX_PASSWORD_LENGTH {
+        anyhow::bail!(
+            "security.password_policy.min_length must be between 8 and {}",
+            crate::auth::self_service::MAX_PASSWORD_LENGTH
+        );
+    }
+    if !(1..=4).contains(&policy.min_classes) {
+        anyhow::bail!("security.password_policy.min_classes must be between 1 and 4");
     }
     Ok(())
 }
@@ -176,60 +864,688 @@ fn validate_api(config: &AppConfig) -> Result<()> {
             config.api.token.len()
         );
     }
+    let mut account_names = std::collections::HashSet::new();
+    for account in &config.api.accounts {
+        if account.username.is_empty() {
+            anyhow::bail!("api.accounts: username must not be empty");
+        }
+        if !account_names.insert(account.username.as_str()) {
+            anyhow::bail!("api.accounts: duplicate username '{}'", account.username);
+        }
+        if !account.password_hash.starts_with("$argon2") {
+            anyhow::bail!(
+                "api.accounts '{}': password_hash must be an argon2 hash (use `s5 hash-password`)",
+                account.username
+            );
+        }
+    }
+    if config.api.session_idle_timeout == 0 {
+        anyhow::bail!("api.session_idle_timeout must be greater than 0");
+    }
+    if config.api.config_history > 100 {
+        anyhow::bail!("api.config_history must be at most 100");
+    }
+    if let Some(path) = &config.api.unix_socket {
+        if cfg!(not(unix)) {
+            anyhow::bail!("api.unix_socket is only supported on Unix");
+        }
+        if !path.is_absolute() {
+            anyhow::bail!("api.unix_socket must be an absolute path");
+        }
+        if config.api.ssh_websocket {
+            anyhow::bail!(
+                "api.ssh_websocket needs a TCP api.listen (tunnels need a client address)"
+            );
+        }
+    }
+    if config.api.unix_socket_mode_bits().is_none() {
+        anyhow::bail!(
+            "api.unix_socket_mode '{}' must be an octal mode between 0000 and 0777",
+            config.api.unix_socket_mode
+        );
+    }
+    for origin in &config.api.allowed_origins {
+        if crate::api::cors::normalize_origin(origin).is_none() {
+            anyhow::bail!(
+                "api.allowed_origins entry '{}' must be an http(s) origin like https://dash.example.com (no path, no wildcard)",
+                origin
+            );
+        }
+    }
     Ok(())
 }
 
 fn validate_webhooks(config: &AppConfig) -> Result<()> {
     for (i, webhook) in config.webhooks.iter().enumerate() {
-        let parsed = url::Url::parse(&webhook.url)
-            .with_context(|| format!("webhook[{}] invalid URL: {}", i, webhook.url))?;
+        validate_outbound_url(
+            &format!("webhook[{}]", i),
+            &webhook.url,
+            webhook.allow_private_ips,
+        )?;
+    }
+    Ok(())
+}
+
+/// Check an outbound HTTP(S) URL; unless `allow_private_ips` is set, reject
+/// localhost and literal private/internal IPs (hostnames are checked again
+/// after DNS resolution at delivery time).
+fn validate_outbound_url(label: &str, url: &str, allow_private_ips: bool) -> Result<()> {
+    let parsed = url::Url::parse(url).with_context(|| format!("{} invalid URL: {}", label, url))?;
+
+    let scheme = parsed.scheme();
+    if scheme != "http" && scheme != "https" {
+        anyhow::bail!("{} URL must use http or https scheme: {}", label, url);
+    }
+
+    // P0-1: Check for private/internal IPs unless allow_private_ips is set
+    if allow_private_ips {
+        return Ok(());
+    }
+    if let Some(host) = parsed.host_str() {
+        // Check obvious localhost names
+        if host == "localhost" || host == "0.0.0.0" {
+            anyhow::bail!(
+                "{} URL must not point to localhost (set allow_private_ips=true to override): {}",
+                label,
+                url
+            );
+        }
+        // Check direct IP addresses, bracketed for IPv6
+        let trimmed = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = trimmed.parse::<std::net::IpAddr>() {
+            if crate::proxy::ip_guard::is_dangerous_ip(&ip) {
+                let range = crate::proxy::ip_guard::classify_dangerous_ip(&ip).unwrap_or("private");
+                anyhow::bail!(
+                    "{} URL points to {} IP {} (set allow_private_ips=true to override): {}",
+                    label,
+                    range,
+                    ip,
+                    url
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reject a feed URL that is not http(s).
+fn check_feed_url(field: &str, url: &str) -> Result<()> {
+    let parsed = url::Url::parse(url).with_context(|| format!("{} invalid URL: {}", field, url))?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        anyhow::bail!("{} must use http or https: {}", field, url);
+    }
+    Ok(())
+}
+
+fn validate_threat_intel(config: &AppConfig) -> Result<()> {
+    let ti = &config.threat_intel;
+    if let Some(ref crowdsec) = ti.crowdsec {
+        check_feed_url("threat_intel.crowdsec.url", &crowdsec.url)?;
+        if crowdsec.api_key.is_empty() {
+            anyhow::bail!("threat_intel.crowdsec.api_key must not be empty");
+        }
+    }
+    let mut names = std::collections::HashSet::new();
+    for (i, list) in ti.denylists.iter().enumerate() {
+        if list.name.is_empty() || list.name == crate::security::threat_intel::CROWDSEC_FEED {
+            anyhow::bail!(
+                "threat_intel.denylists[{}].name must be non-empty and not '{}'",
+                i,
+                crate::security::threat_intel::CROWDSEC_FEED
+            );
+        }
+        if !names.insert(list.name.as_str()) {
+            anyhow::bail!("duplicate threat_intel.denylists name '{}'", list.name);
+        }
+        check_feed_url(&format!("threat_intel.denylists[{}].url", i), &list.url)?;
+    }
+    if ti.is_enabled() && ti.refresh_interval < 10 {
+        anyhow::bail!(
+            "threat_intel.refresh_interval must be >= 10 (got {})",
+            ti.refresh_interval
+        );
+    }
+    Ok(())
+}
+
+fn validate_ip_intel(config: &AppConfig) -> Result<()> {
+    let intel = &config.ip_intel;
+    for (i, url) in intel.tor_urls.iter().enumerate() {
+        check_feed_url(&format!("ip_intel.tor_urls[{}]", i), url)?;
+    }
+    for (i, url) in intel.datacenter_urls.iter().enumerate() {
+        check_feed_url(&format!("ip_intel.datacenter_urls[{}]", i), url)?;
+    }
+    if intel
+        .path
+        .as_ref()
+        .is_some_and(|p| p.as_os_str().is_empty())
+    {
+        anyhow::bail!("ip_intel.path must not be empty");
+    }
+    crate::security::ip_intel::check_deny(intel)
+}
+
+fn validate_honeypot(config: &AppConfig) -> Result<()> {
+    let honeypot = &config.honeypot;
+    let mut names = std::collections::HashSet::new();
+    for cred in &honeypot.credentials {
+        if cred.username.is_empty() {
+            anyhow::bail!("honeypot credential has empty username");
+        }
+        if !names.insert(cred.username.as_str()) {
+            anyhow::bail!("duplicate honeypot username '{}'", cred.username);
+        }
+        if config.users.iter().any(|u| u.username == cred.username) {
+            anyhow::bail!(
+                "honeypot username '{}' is also a configured user",
+                cred.username
+            );
+        }
+        if cred.password.as_deref() == Some("") {
+            anyhow::bail!(
+                "honeypot password for '{}' must not be empty (omit it to accept any password)",
+                cred.username
+            );
+        }
+    }
+    if honeypot.ban && honeypot.ban_duration == 0 && !honeypot.credentials.is_empty() {
+        anyhow::bail!("honeypot.ban_duration must be > 0 when honeypot.ban is enabled");
+    }
+    Ok(())
+}
+
+fn validate_login_anomaly(config: &AppConfig) -> Result<()> {
+    let anomaly = &config.login_anomaly;
+    if !anomaly.enabled {
+        return Ok(());
+    }
+    if anomaly.hour_tolerance > 11 {
+        anyhow::bail!(
+            "login_anomaly.hour_tolerance must be <= 11 (got {})",
+            anomaly.hour_tolerance
+        );
+    }
+    if anomaly
+        .history_path
+        .as_ref()
+        .is_some_and(|p| p.as_os_str().is_empty())
+    {
+        anyhow::bail!("login_anomaly.history_path must not be empty");
+    }
+    Ok(())
+}
+
+fn validate_impossible_travel(config: &AppConfig) -> Result<()> {
+    let travel = &config.impossible_travel;
+    if !travel.enabled {
+        return Ok(());
+    }
+    if !config.geoip.enabled || config.geoip.database_path.is_none() {
+        anyhow::bail!(
+            "impossible_travel needs geoip.enabled and a City database in geoip.database_path"
+        );
+    }
+    if travel.max_speed_kmh == 0 {
+        anyhow::bail!("impossible_travel.max_speed_kmh must be greater than 0");
+    }
+    if travel.window_secs == 0 {
+        anyhow::bail!("impossible_travel.window_secs must be greater than 0");
+    }
+    Ok(())
+}
+
+fn validate_key_enrollment(config: &AppConfig) -> Result<()> {
+    let enrollment = &config.key_enrollment;
+    if !enrollment.enabled {
+        return Ok(());
+    }
+    // A held login must be decided before the auth deadline drops it
+    if enrollment.wait_secs >= config.server.ssh_auth_timeout {
+        anyhow::bail!(
+            "key_enrollment.wait_secs ({}) must be lower than server.ssh_auth_timeout ({})",
+            enrollment.wait_secs,
+            config.server.ssh_auth_timeout
+        );
+    }
+    if enrollment.pending_hours == 0 || enrollment.pending_hours > 8_760 {
+        anyhow::bail!(
+            "key_enrollment.pending_hours must be between 1 and 8760 (got {})",
+            enrollment.pending_hours
+        );
+    }
+    if enrollment.max_pending_per_user == 0 {
+        anyhow::bail!("key_enrollment.max_pending_per_user must be > 0");
+    }
+    Ok(())
+}
+
+fn validate_authorized_keys_fetch(config: &AppConfig) -> Result<()> {
+    let fetch = &config.authorized_keys_fetch;
+    if fetch.timeout_secs == 0 || fetch.timeout_secs > 60 {
+        anyhow::bail!(
+            "authorized_keys_fetch.timeout_secs must be between 1 and 60 (got {})",
+            fetch.timeout_secs
+        );
+    }
+    Ok(())
+}
 
-        let scheme = parsed.scheme();
-        if scheme != "http" && scheme != "https" {
+fn validate_invitations(config: &AppConfig) -> Result<()> {
+    let invitations = &config.invitations;
+    if !invitations.enabled {
+        return Ok(());
+    }
+    if invitations.ttl_hours == 0 || invitations.ttl_hours > 8_760 {
+        anyhow::bail!(
+            "invitations.ttl_hours must be between 1 and 8760 (got {})",
+            invitations.ttl_hours
+        );
+    }
+    if invitations.max_pending == 0 {
+        anyhow::bail!("invitations.max_pending must be > 0");
+    }
+    if let Some(ref url) = invitations.public_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            anyhow::bail!("invitations.public_url must be an http:// or https:// URL");
+        }
+    }
+    Ok(())
+}
+
+/// Addresses end up in SMTP commands and mail headers
+fn check_address(field: &str, addr: &str) -> Result<()> {
+    let valid = addr
+        .split_once('@')
+        .is_some_and(|(l, d)| !l.is_empty() && !d.is_empty());
+    if !valid
+        || addr
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || c == '<' || c == '>')
+    {
+        anyhow::bail!("{} is not a valid email address: '{}'", field, addr);
+    }
+    Ok(())
+}
+
+fn validate_notifications(config: &AppConfig) -> Result<()> {
+    let n = &config.notifications;
+    if let Some(ref smtp) = n.smtp {
+        if smtp.host.is_empty() || smtp.port == 0 {
+            anyhow::bail!("notifications.smtp.host and port must be set");
+        }
+        if smtp.username.is_some() != smtp.password.is_some() {
+            anyhow::bail!("notifications.smtp.username and password must be set together");
+        }
+        if smtp.timeout_secs == 0 {
+            anyhow::bail!("notifications.smtp.timeout_secs must be > 0");
+        }
+        check_address("notifications.smtp.from", &smtp.from)?;
+        for addr in &smtp.to {
+            check_address("notifications.smtp.to", addr)?;
+        }
+    }
+
+    let mut names = std::collections::HashSet::new();
+    for (i, sink) in n.sinks.iter().enumerate() {
+        if sink.name.is_empty() || sink.name == EMAIL_SINK {
             anyhow::bail!(
-                "webhook[{}] URL must use http or https scheme: {}",
+                "notifications.sinks[{}].name must be non-empty and not '{}'",
                 i,
-                webhook.url
+                EMAIL_SINK
+            );
+        }
+        if !names.insert(sink.name.as_str()) {
+            anyhow::bail!("duplicate notifications.sinks name '{}'", sink.name);
+        }
+        validate_outbound_url(
+            &format!("notifications.sinks[{}]", i),
+            &sink.url,
+            sink.allow_private_ips,
+        )?;
+        let matrix = sink.kind == NotificationSinkKind::Matrix;
+        if matrix && (sink.room_id.is_none() || sink.access_token.is_none()) {
+            anyhow::bail!(
+                "notifications.sinks[{}]: matrix requires room_id and access_token",
+                i
             );
         }
+        if sink.secret.is_some() && sink.kind != NotificationSinkKind::Webhook {
+            anyhow::bail!(
+                "notifications.sinks[{}]: secret is only supported by webhook sinks",
+                i
+            );
+        }
+    }
 
-        // P0-1: Check for private/internal IPs unless allow_private_ips is set
-        if !webhook.allow_private_ips {
-            if let Some(host) = parsed.host_str() {
-                // Check obvious localhost names
-                if host == "localhost" || host == "0.0.0.0" {
+    for (i, rule) in n.rules.iter().enumerate() {
+        for sink in rule.sink_names() {
+            if sink == EMAIL_SINK {
+                if n.smtp.is_none() {
                     anyhow::bail!(
-                        "webhook[{}] URL must not point to localhost (set allow_private_ips=true to override): {}",
-                        i, webhook.url
+                        "notifications.rules[{}] sends email but [notifications.smtp] is not set",
+                        i
                     );
                 }
-                // Check if host is a direct IP address
-                if let Ok(ip) = host.parse::<std::net::IpAddr>() {
-                    if crate::proxy::ip_guard::is_dangerous_ip(&ip) {
-                        let range =
-                            crate::proxy::ip_guard::classify_dangerous_ip(&ip).unwrap_or("private");
-                        anyhow::bail!(
-                            "webhook[{}] URL points to {} IP {} (set allow_private_ips=true to override): {}",
-                            i, range, ip, webhook.url
-                        );
-                    }
-                }
-                // Check bracketed IPv6
-                let trimmed = host.trim_start_matches('[').trim_end_matches(']');
-                if trimmed != host {
-                    if let Ok(ip) = trimmed.parse::<std::net::IpAddr>() {
-                        if crate::proxy::ip_guard::is_dangerous_ip(&ip) {
-                            let range = crate::proxy::ip_guard::classify_dangerous_ip(&ip)
-                                .unwrap_or("private");
-                            anyhow::bail!(
-                                "webhook[{}] URL points to {} IP {} (set allow_private_ips=true to override): {}",
-                                i, range, ip, webhook.url
-                            );
-                        }
-                    }
-                }
+            } else if !names.contains(sink) {
+                anyhow::bail!("notifications.rules[{}]: unknown sink '{}'", i, sink);
             }
         }
+        for addr in &rule.to {
+            check_address(&format!("notifications.rules[{}].to", i), addr)?;
+        }
+        let emails = rule.sink_names().contains(&EMAIL_SINK);
+        let default_to = n.smtp.as_ref().is_none_or(|s| s.to.is_empty());
+        if emails && rule.to.is_empty() && default_to {
+            anyhow::bail!(
+                "notifications.rules[{}] has no recipients (set rules.to or smtp.to)",
+                i
+            );
+        }
+        if rule.event == NotificationEvent::NewCountry
+            && !(config.geoip.enabled && config.geoip.database_path.is_some())
+        {
+            anyhow::bail!(
+                "notifications.rules[{}]: new_country requires geoip with a database_path",
+                i
+            );
+        }
+    }
+    if n.batch_window_secs == 0 {
+        anyhow::bail!("notifications.batch_window_secs must be > 0");
+    }
+    if n.max_events_per_email == 0 {
+        anyhow::bail!("notifications.max_events_per_email must be > 0");
+    }
+    if n.auth_failure_threshold == 0 || n.auth_failure_window_secs == 0 {
+        anyhow::bail!(
+            "notifications.auth_failure_threshold and auth_failure_window_secs must be > 0"
+        );
+    }
+    Ok(())
+}
+
+fn validate_reports(config: &AppConfig) -> Result<()> {
+    let reports = &config.reports;
+    if reports.usage_history_months == 0 {
+        anyhow::bail!("reports.usage_history_months must be > 0");
+    }
+    for addr in &reports.email_to {
+        check_address("reports.email_to", addr)?;
+    }
+    if reports.monthly_email {
+        let Some(ref smtp) = config.notifications.smtp else {
+            anyhow::bail!("reports.monthly_email requires [notifications.smtp]");
+        };
+        if reports.email_to.is_empty() && smtp.to.is_empty() {
+            anyhow::bail!("reports.monthly_email needs reports.email_to or notifications.smtp.to");
+        }
+    }
+    Ok(())
+}
+
+fn validate_cluster(config: &AppConfig) -> Result<()> {
+    let cluster = &config.cluster;
+    if !cluster.enabled {
+        if cluster.observer {
+            anyhow::bail!("cluster.observer requires cluster.enabled = true");
+        }
+        return Ok(());
+    }
+    if cluster.observer && !config.api.enabled {
+        anyhow::bail!("cluster.observer requires [api] enabled = true (it only serves the API)");
+    }
+    match cluster.backend {
+        types::ClusterBackend::Redis => {
+            crate::cluster::redis::RedisAddr::parse(&cluster.url)
+                .map_err(|e| anyhow::anyhow!("cluster.url: {}", e))?;
+        }
+        types::ClusterBackend::Gossip => validate_gossip(cluster)?,
+    }
+    if cluster.sync_interval_secs == 0 || cluster.timeout_secs == 0 {
+        anyhow::bail!("cluster.sync_interval_secs and cluster.timeout_secs must be > 0");
+    }
+    if cluster.key_prefix.is_empty() || cluster.key_prefix.contains(char::is_whitespace) {
+        anyhow::bail!("cluster.key_prefix must be non-empty, without spaces");
+    }
+    if cluster.node_id.contains(char::is_whitespace) {
+        anyhow::bail!("cluster.node_id must not contain spaces");
+    }
+    if cluster.username.is_some() && cluster.password.is_none() {
+        anyhow::bail!("cluster.username requires cluster.password");
+    }
+    Ok(())
+}
+
+fn validate_gossip(cluster: &types::ClusterConfig) -> Result<()> {
+    cluster
+        .listen
+        .parse::<std::net::SocketAddr>()
+        .map_err(|e| anyhow::anyhow!("cluster.listen '{}': {}", cluster.listen, e))?;
+    if cluster.peers.is_empty() {
+        anyhow::bail!("cluster.backend = \"gossip\" needs at least one entry in cluster.peers");
+    }
+    for peer in &cluster.peers {
+        let valid = peer.rsplit_once(':').is_some_and(|(host, port)| {
+            !host.is_empty() && !host.contains(['/', '@', ' ']) && port.parse::<u16>().is_ok()
+        });
+        if !valid {
+            anyhow::bail!("cluster.peers: '{}' is not host:port", peer);
+        }
+    }
+    match cluster.secret.as_deref() {
+        Some(secret) if secret.len() >= 16 => Ok(()),
+        _ => anyhow::bail!(
+            "cluster.backend = \"gossip\" requires cluster.secret (at least 16 characters)"
+        ),
+    }
+}
+
+fn validate_sandbox(config: &AppConfig) -> Result<()> {
+    let sandbox = &config.sandbox;
+    if !sandbox.enabled {
+        return Ok(());
+    }
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("sandbox is only supported on Linux");
+    }
+    if sandbox.user.as_deref().is_some_and(str::is_empty) {
+        anyhow::bail!("sandbox.user must not be empty");
+    }
+    if sandbox.group.is_some() && sandbox.user.is_none() {
+        anyhow::bail!("sandbox.group requires sandbox.user");
+    }
+    if sandbox.seccomp && !cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+        anyhow::bail!("sandbox.seccomp is only supported on x86_64 and aarch64");
+    }
+    Ok(())
+}
+
+fn validate_vpn(config: &AppConfig) -> Result<()> {
+    let vpn = &config.vpn;
+    if !vpn.enabled {
+        return Ok(());
+    }
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("vpn is only supported on Linux");
+    }
+    if vpn.pool.prefix_len() > 30 {
+        anyhow::bail!("vpn.pool must hold at least 2 addresses (a /30 or larger)");
+    }
+    if vpn.mtu < 576 {
+        anyhow::bail!("vpn.mtu must be at least 576");
+    }
+    // A packet and its address family are relayed in one read
+    if usize::from(vpn.mtu) + 4 > config.proxy.buffer_pool.buffer_size {
+        anyhow::bail!(
+            "vpn.mtu {} needs proxy.buffer_pool.buffer_size of at least {}",
+            vpn.mtu,
+            usize::from(vpn.mtu) + 4
+        );
+    }
+    if config.sandbox.enabled && config.sandbox.user.is_some() {
+        anyhow::bail!("vpn needs CAP_NET_ADMIN, which sandbox.user drops");
+    }
+    let mut addresses = std::collections::HashSet::new();
+    for user in &config.users {
+        let Some(address) = user.vpn_address else {
+            continue;
+        };
+        if !crate::proxy::tun::is_client_address(&vpn.pool, address) {
+            anyhow::bail!(
+                "user '{}' vpn_address {} is not a client address of vpn.pool {}",
+                user.username,
+                address,
+                vpn.pool
+            );
+        }
+        if !addresses.insert(address) {
+            anyhow::bail!(
+                "user '{}' vpn_address {} is already taken",
+                user.username,
+                address
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_subsystems(config: &AppConfig) -> Result<()> {
+    let mut names = std::collections::HashSet::new();
+    for subsystem in &config.subsystems {
+        crate::ssh::subsystem::validate(subsystem)
+            .map_err(|e| anyhow::anyhow!("subsystem '{}': {}", subsystem.name, e))?;
+        if !names.insert(subsystem.name.as_str()) {
+            anyhow::bail!("duplicate subsystem name: '{}'", subsystem.name);
+        }
+    }
+    let owners = config
+        .groups
+        .iter()
+        .map(|g| (format!("group '{}'", g.name), &g.subsystems))
+        .chain(
+            config
+                .users
+                .iter()
+                .map(|u| (format!("user '{}'", u.username), &u.subsystems)),
+        );
+    for (owner, allowed) in owners {
+        if let Some(name) = allowed
+            .iter()
+            .flatten()
+            .find(|n| !names.contains(n.as_str()))
+        {
+            anyhow::bail!(
+                "{} subsystems: '{}' is not a [[subsystems]] entry",
+                owner,
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_audit_archive(logging: &types::LoggingConfig) -> Result<()> {
+    let archive = &logging.audit.archive;
+    if !archive.enabled {
+        return Ok(());
+    }
+    if logging.audit_log_path.is_none() || logging.audit_max_size_mb == 0 {
+        anyhow::bail!(
+            "logging.audit.archive requires logging.audit_log_path and audit_max_size_mb > 0 \
+             (only rotated files are archived)"
+        );
+    }
+    let endpoint = url::Url::parse(&archive.endpoint).map_err(|e| {
+        anyhow::anyhow!(
+            "logging.audit.archive.endpoint is not a valid URL ('{}'): {e}",
+            archive.endpoint
+        )
+    })?;
+    if !matches!(endpoint.scheme(), "http" | "https")
+        || endpoint.host_str().is_none()
+        || endpoint.path() != "/"
+        || endpoint.query().is_some()
+    {
+        anyhow::bail!(
+            "logging.audit.archive.endpoint must be an http(s) URL without a path (got '{}')",
+            archive.endpoint
+        );
+    }
+    if archive.bucket.is_empty() {
+        anyhow::bail!("logging.audit.archive.bucket must not be empty");
+    }
+    if archive.region.is_empty() {
+        anyhow::bail!("logging.audit.archive.region must not be empty");
+    }
+    if archive.access_key_id.is_empty() || archive.secret_access_key.is_empty() {
+        anyhow::bail!("logging.audit.archive requires access_key_id and secret_access_key");
+    }
+    if archive.retry_interval_secs == 0 || archive.timeout_secs == 0 {
+        anyhow::bail!("logging.audit.archive.retry_interval_secs and timeout_secs must be > 0");
+    }
+    Ok(())
+}
+
+fn validate_logging(config: &AppConfig) -> Result<()> {
+    let logging = &config.logging;
+    if logging.event_log && !cfg!(windows) {
+        anyhow::bail!("logging.event_log is only supported on Windows");
+    }
+    if logging.audit_chain && logging.audit_log_path.is_none() {
+        anyhow::bail!("logging.audit_chain requires logging.audit_log_path");
+    }
+    if let Some(ref key) = logging.audit_signing_key {
+        if !logging.audit_chain {
+            anyhow::bail!("logging.audit_signing_key requires logging.audit_chain = true");
+        }
+        if key.len() < 16 {
+            anyhow::bail!("logging.audit_signing_key must be at least 16 characters");
+        }
+    }
+    validate_audit_archive(logging)?;
+    if logging.flows.enabled && logging.flows.path.as_os_str().is_empty() {
+        anyhow::bail!("logging.flows.path must not be empty when flows are enabled");
+    }
+    let max_len = logging.flows.payload_metadata_max_len;
+    if !(1..=types::MAX_PAYLOAD_METADATA_LEN).contains(&max_len) {
+        anyhow::bail!(
+            "logging.flows.payload_metadata_max_len must be between 1 and {} (got {})",
+            types::MAX_PAYLOAD_METADATA_LEN,
+            max_len
+        );
+    }
+    let ipfix = &logging.ipfix;
+    if ipfix.enabled {
+        let port = ipfix
+            .collector
+            .rsplit_once(':')
+            .and_then(|(host, port)| (!host.is_empty()).then_some(port))
+            .and_then(|port| port.parse::<u16>().ok());
+        if port.is_none_or(|p| p == 0) {
+            anyhow::bail!(
+                "logging.ipfix.collector must be host:port (got '{}')",
+                ipfix.collector
+            );
+        }
+        if ipfix.enterprise_number == 0 {
+            anyhow::bail!("logging.ipfix.enterprise_number must be > 0");
+        }
+        if ipfix.template_refresh_secs == 0 {
+            anyhow::bail!("logging.ipfix.template_refresh_secs must be > 0");
+        }
+    }
+    let capture = &logging.capture;
+    if capture.enabled {
+        if capture.directory.as_os_str().is_empty() {
+            anyhow::bail!("logging.capture.directory must not be empty when capture is enabled");
+        }
+        if capture.max_duration_secs == 0 || capture.max_duration_secs > 3600 {
+            anyhow::bail!("logging.capture.max_duration_secs must be between 1 and 3600");
+        }
+        if capture.max_size_mb == 0 {
+            anyhow::bail!("logging.capture.max_size_mb must be > 0");
+        }
     }
     Ok(())
 }
@@ -270,6 +1586,64 @@ fn validate_socks5_tls(config: &AppConfig) -> Result<()> {
     Ok(())
 }
 
+fn validate_masque(config: &AppConfig) -> Result<()> {
+    let Some(ref masque) = config.masque else {
+        return Ok(());
+    };
+    if masque.listen.parse::<std::net::SocketAddr>().is_err() {
+        anyhow::bail!(
+            "masque.listen must be an IP address and port (got '{}')",
+            masque.listen
+        );
+    }
+    if !masque.tls_cert.exists() {
+        anyhow::bail!("masque.tls_cert not found: {}", masque.tls_cert.display());
+    }
+    if !masque.tls_key.exists() {
+        anyhow::bail!("masque.tls_key not found: {}", masque.tls_key.display());
+    }
+    if cfg!(not(feature = "masque")) {
+        anyhow::bail!(
+            "masque: this s5 was built without HTTP/3 support \
+             (rebuild with `--features masque`)"
+        );
+    }
+    Ok(())
+}
+
+fn validate_gssapi(config: &AppConfig) -> Result<()> {
+    let Some(ref gssapi) = config.gssapi else {
+        return Ok(());
+    };
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("gssapi is only supported on Linux");
+    }
+    if let Some(ref keytab) = gssapi.keytab {
+        if !keytab.exists() {
+            anyhow::bail!("gssapi.keytab not found: {}", keytab.display());
+        }
+    }
+    for (principal, user) in &gssapi.principals {
+        if user.is_empty() {
+            anyhow::bail!("gssapi.principals: '{}' maps to an empty user", principal);
+        }
+    }
+    for rule in &gssapi.rules {
+        if rule.pattern.is_empty() || rule.user.is_empty() {
+            anyhow::bail!("gssapi.rules: pattern and user must not be empty");
+        }
+    }
+    if cfg!(not(feature = "gssapi")) {
+        anyhow::bail!(
+            "gssapi: this s5 was built without Kerberos support \
+             (rebuild with `--features gssapi`)"
+        );
+    }
+    #[cfg(feature = "gssapi")]
+    crate::auth::gssapi::PrincipalMap::new(gssapi).context("gssapi")?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
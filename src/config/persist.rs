@@ -0,0 +1,429 @@
+//! Write-back of runtime changes into the on-disk TOML configuration.
+//!
+//! Edits go through `toml_edit` so that comments, ordering and formatting
+//! written by the operator are preserved; only the touched value changes.
+//! Every write goes through [`update_config`], which holds one process-wide
+//! lock from reading the file to renaming the new one into place, so
+//! concurrent writers never lose each other's edits. Edits made to the file
+//! outside s5 in the meantime are caught by hashing it again before the
+//! write.
+
+use super::types::PersonalTokenConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use thiserror::Error;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table};
+
+/// Held across the read → edit → rename of every config write.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Why [`update_config`] failed.
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    /// The config file could not be read
+    #[error(transparent)]
+    Read(anyhow::Error),
+    /// The edit was refused, or its result does not validate
+    #[error(transparent)]
+    Edit(anyhow::Error),
+    /// The new content could not be written
+    #[error(transparent)]
+    Write(anyhow::Error),
+    /// The file was changed outside s5 during the edit; nothing was written
+    #[error("config changed on disk during the edit: {}", .0.display())]
+    Changed(PathBuf),
+}
+
+/// Read the config file at `path`, apply `edit` to its content and write
+/// the result back atomically, all under the process-wide write lock.
+/// `edit` returns the new content, or None to leave the file untouched.
+/// Returns what was written.
+///
+/// The lock only orders writers in this process, so the file is hashed again
+/// right before the write: if it no longer matches what `edit` saw, someone
+/// edited it by hand in between and [`UpdateError::Changed`] is returned
+/// rather than overwriting their change.
+pub fn update_config(
+    path: &Path,
+    edit: impl FnOnce(&str) -> Result<Option<String>>,
+) -> std::result::Result<Option<String>, UpdateError> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("reading config: {}", path.display()))
+            .map_err(UpdateError::Read)
+    };
+    let content = read(path)?;
+    let read_hash = Sha256::digest(content.as_bytes());
+    let Some(updated) = edit(&content).map_err(UpdateError::Edit)? else {
+        return Ok(None);
+    };
+    if Sha256::digest(read(path)?.as_bytes()) != read_hash {
+        return Err(UpdateError::Changed(path.to_path_buf()));
+    }
+    write_atomic(path, &updated).map_err(UpdateError::Write)?;
+    Ok(Some(updated))
+}
+
+/// Group-level policy editable at runtime (`PUT /api/groups/{name}/policy`).
+/// `None` leaves the field unset, so members inherit it from the parent
+/// group or the global defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupPolicy {
+    #[serde(default)]
+    pub max_bandwidth_kbps: Option<u64>,
+    #[serde(default)]
+    pub max_aggregate_bandwidth_kbps: Option<u64>,
+    #[serde(default)]
+    pub max_connections_per_user: Option<u32>,
+    #[serde(default)]
+    pub allow_forwarding: Option<bool>,
+    #[serde(default)]
+    pub allow_shell: Option<bool>,
+}
+
+impl GroupPolicy {
+    /// The policy fields set on `group` itself (not inherited).
+    pub fn of(group: &super::types::GroupConfig) -> Self {
+        Self {
+            max_bandwidth_kbps: group.max_bandwidth_kbps,
+            max_aggregate_bandwidth_kbps: group.max_aggregate_bandwidth_kbps,
+            max_connections_per_user: group.max_connections_per_user,
+            allow_forwarding: group.allow_forwarding,
+            allow_shell: group.allow_shell,
+        }
+    }
+}
+
+/// Replace the `password_hash` of the `[[users]]` entry named `username`.
+///
+/// The file is rewritten atomically (temp file + rename) so a crash mid-write
+/// never leaves a truncated config behind. The new content is re-parsed and
+/// validated before the rename, so a write can never produce a config that
+/// the next reload would reject.
+pub fn set_user_password_hash(path: &Path, username: &str, password_hash: &str) -> Result<()> {
+    update_config(path, |content| {
+        set_user_field(content, username, "password_hash", password_hash).map(Some)
+    })?;
+    Ok(())
+}
+
+/// Set `password_hash` and `password_changed_at` on the `[[users]]` entry
+/// named `username` (self-service password change), written like
+/// [`set_user_password_hash`].
+pub fn set_user_password(
+    path: &Path,
+    username: &str,
+    password_hash: &str,
+    changed_at: DateTime<Utc>,
+) -> Result<()> {
+    let changed_at = changed_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+    update_config(path, |content| {
+        edit_user(content, username, |entry| {
+            set_str(entry, "password_hash", password_hash);
+            set_str(entry, "password_changed_at", &changed_at);
+        })
+        .map(Some)
+    })?;
+    Ok(())
+}
+
+/// Replace `username`'s `password_hash` with `new_hash` if it is still
+/// `old_hash` (rehash on login). Returns false, leaving the file untouched,
+/// when the entry holds another hash.
+pub fn upgrade_user_password_hash(
+    path: &Path,
+    username: &str,
+    old_hash: &str,
+    new_hash: &str,
+) -> Result<bool> {
+    let written = update_config(path, |content| {
+        let mut current = None;
+        let updated = edit_user(content, username, |entry| {
+            current = entry
+                .get("password_hash")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            if current.as_deref() == Some(old_hash) {
+                set_str(entry, "password_hash", new_hash);
+            }
+        })?;
+        Ok((current.as_deref() == Some(old_hash)).then_some(updated))
+    })?;
+    Ok(written.is_some())
+}
+
+/// Append `key_line` to the `authorized_keys` of the `[[users]]` entry named
+/// `username` (approved key enrollment), written like
+/// [`set_user_password_hash`]. A key already listed is not added twice.
+pub fn add_user_authorized_key(path: &Path, username: &str, key_line: &str) -> Result<()> {
+    update_config(path, |content| {
+        add_authorized_key(content, username, key_line).map(Some)
+    })?;
+    Ok(())
+}
+
+/// Replace the `api_tokens` of the `[[users]]` entry named `username`
+/// (personal tokens created or revoked on the self-service page), written
+/// like [`set_user_password_hash`].
+pub fn set_user_api_tokens(
+    path: &Path,
+    username: &str,
+    tokens: &[PersonalTokenConfig],
+) -> Result<()> {
+    update_config(path, |content| {
+        apply_user_api_tokens(content, username, tokens).map(Some)
+    })?;
+    Ok(())
+}
+
+/// Append imported users to the config file (`s5 import --write`), written
+/// like [`set_user_password_hash`]. Users the file already defines are left
+/// untouched; returns the names of the users added.
+pub fn add_users(path: &Path, users: &[super::import::ImportedUser]) -> Result<Vec<String>> {
+    let mut added = Vec::new();
+    update_config(path, |content| {
+        let (updated, names) = super::import::append_users(content, users)?;
+        added = names;
+        Ok((!added.is_empty()).then_some(updated))
+    })?;
+    Ok(added)
+}
+
+/// Set the policy of the `[[groups]]` entry named `name` (dashboard group
+/// editor), written like [`set_user_password_hash`]. Returns the new file
+/// content.
+pub fn set_group_policy(path: &Path, name: &str, policy: &GroupPolicy) -> Result<String> {
+    let updated = update_config(path, |content| {
+        apply_group_policy(content, name, policy).map(Some)
+    })?;
+    Ok(updated.expect("edit always writes"))
+}
+
+/// Write `policy` into the `[[groups]]` entry named `name` and return the
+/// edited document, validated. Unset fields are removed from the entry; the
+/// entry is appended when the group is only named by its users.
+pub fn apply_group_policy(content: &str, name: &str, policy: &GroupPolicy) -> Result<String> {
+    // TOML integers are signed
+    let kbps = |field: &str, v: Option<u64>| {
+        v.map(i64::try_from)
+            .transpose()
+            .with_context(|| format!("{field} is out of range"))
+    };
+    let bandwidth = kbps("max_bandwidth_kbps", policy.max_bandwidth_kbps)?;
+    let aggregate = kbps(
+        "max_aggregate_bandwidth_kbps",
+        policy.max_aggregate_bandwidth_kbps,
+    )?;
+
+    let mut doc: DocumentMut = content.parse().context("parsing TOML configuration")?;
+    if doc.get("groups").is_none() {
+        doc["groups"] = Item::ArrayOfTables(ArrayOfTables::new());
+    }
+    let groups = doc["groups"]
+        .as_array_of_tables_mut()
+        .context("`groups` is not an array of tables")?;
+    let existing = groups.iter().position(|table| {
+        table
+            .get("name")
+            .and_then(|v| v.as_str())
+            .is_some_and(|n| n == name)
+    });
+    let entry = match existing {
+        Some(index) => groups.get_mut(index).expect("index from position"),
+        None => {
+            let mut table = Table::new();
+            table["name"] = value(name);
+            groups.push(table);
+            groups.get_mut(groups.len() - 1).expect("just pushed")
+        }
+    };
+
+    set_or_remove(entry, "max_bandwidth_kbps", bandwidth);
+    set_or_remove(entry, "max_aggregate_bandwidth_kbps", aggregate);
+    set_or_remove(
+        entry,
+        "max_connections_per_user",
+        policy.max_connections_per_user.map(i64::from),
+    );
+    set_or_remove(entry, "allow_forwarding", policy.allow_forwarding);
+    set_or_remove(entry, "allow_shell", policy.allow_shell);
+
+    let updated = doc.to_string();
+    super::parse_config(&updated).context("updated config failed validation")?;
+    Ok(updated)
+}
+
+fn set_or_remove<V: Into<toml_edit::Value>>(entry: &mut Table, key: &str, new: Option<V>) {
+    match new {
+        Some(v) => entry[key] = value(v),
+        None => {
+            entry.remove(key);
+        }
+    }
+}
+
+/// Replace the whole config file with `content` (config rollback), written
+/// like [`set_user_password_hash`]: refused unless `content` parses and
+/// validates.
+pub fn replace_config(path: &Path, content: &str) -> Result<()> {
+    super::parse_config(content).context("refusing to write an invalid config")?;
+    update_config(path, |_| Ok(Some(content.to_string())))?;
+    Ok(())
+}
+
+/// Set a string field on the `[[users]]` entry named `username` and return
+/// the edited document.
+pub fn set_user_field(content: &str, username: &str, key: &str, new_value: &str) -> Result<String> {
+    edit_user(content, username, |entry| set_str(entry, key, new_value))
+}
+
+/// Set `key` to `new_value`, keeping the whitespace and trailing comment
+/// around the value it replaces.
+fn set_str(entry: &mut Table, key: &str, new_value: &str) {
+    let mut item = value(new_value);
+    if let (Some(old), Some(new)) = (entry.get(key).and_then(Item::as_value), item.as_value_mut()) {
+        *new.decor_mut() = old.decor().clone();
+    }
+    entry[key] = item;
+}
+
+/// Append `key_line` to `username`'s `authorized_keys` array (created if
+/// missing) and return the edited document.
+pub fn add_authorized_key(content: &str, username: &str, key_line: &str) -> Result<String> {
+    edit_user(content, username, |entry| {
+        match entry
+            .get_mut("authorized_keys")
+            .and_then(|item| item.as_array_mut())
+        {
+            Some(keys) => {
+                if !keys.iter().any(|k| k.as_str() == Some(key_line)) {
+                    keys.push(key_line);
+                }
+            }
+            None => {
+                let mut keys = Array::new();
+                keys.push(key_line);
+                entry["authorized_keys"] = value(keys);
+            }
+        }
+    })
+}
+
+/// Write `tokens` as `username`'s `api_tokens` (an array of inline tables,
+/// removed when empty) and return the edited document.
+pub fn apply_user_api_tokens(
+    content: &str,
+    username: &str,
+    tokens: &[PersonalTokenConfig],
+) -> Result<String> {
+    edit_user(content, username, |entry| {
+        if tokens.is_empty() {
+            entry.remove("api_tokens");
+            return;
+        }
+        let mut list = Array::new();
+        for token in tokens {
+            let mut t = InlineTable::new();
+            t.insert("id", token.id.as_str().into());
+            t.insert("name", token.name.as_str().into());
+            t.insert("sha256", token.sha256.as_str().into());
+            if let Some(ref created_at) = token.created_at {
+                t.insert("created_at", created_at.as_str().into());
+            }
+            list.push(t);
+        }
+        for item in list.iter_mut() {
+            item.decor_mut().set_prefix("\n    ");
+        }
+        list.set_trailing("\n");
+        list.set_trailing_comma(true);
+        entry["api_tokens"] = value(list);
+    })
+}
+
+/// Set or clear `username`'s `disabled` flag (removed when false) and return
+/// the edited document.
+pub fn apply_user_disabled(content: &str, username: &str, disabled: bool) -> Result<String> {
+    edit_user(content, username, |entry| {
+        if disabled {
+            entry["disabled"] = value(true);
+        } else {
+            entry.remove("disabled");
+        }
+    })
+}
+
+/// Apply `edit` to the `[[users]]` entry named `username` and return the
+/// edited document, validated.
+fn edit_user(content: &str, username: &str, edit: impl FnOnce(&mut Table)) -> Result<String> {
+    let mut doc: DocumentMut = content.parse().context("parsing TOML configuration")?;
+    let users = doc
+        .get_mut("users")
+        .and_then(|item| item.as_array_of_tables_mut())
+        .context("config has no [[users]] entries")?;
+
+    let entry = users
+        .iter_mut()
+        .find(|table| {
+            table
+                .get("username")
+                .and_then(|v| v.as_str())
+                .is_some_and(|name| name == username)
+        })
+        .with_context(|| format!("user '{}' not found in config file", username))?;
+    edit(entry);
+
+    let updated = doc.to_string();
+    super::parse_config(&updated).context("updated config failed validation")?;
+    Ok(updated)
+}
+
+/// Write `content` to `path` via a sibling temp file and rename, keeping the
+/// original file's permissions (configs hold secrets and are usually 0600).
+/// The temp file is named after the process and a random suffix, so it is
+/// never shared with another writer.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("not a file path: {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.{:016x}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        // Never expose the secrets to other users, even for the brief
+        // window before the original permissions are copied over.
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp_path)
+        .with_context(|| format!("creating temp config: {}", tmp_path.display()))?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("writing temp config: {}", tmp_path.display()))?;
+    drop(file);
+
+    if let Ok(meta) = std::fs::metadata(path) {
+        let _ = std::fs::set_permissions(&tmp_path, meta.permissions());
+    }
+
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e).with_context(|| format!("replacing config: {}", path.display()));
+    }
+    Ok(())
+}
@@ -1,7 +1,10 @@
-use crate::config::types::AppConfig;
+use crate::config::types::{AppConfig, NotificationSinkKind};
 
 /// Redact sensitive fields in a config for safe display.
-/// Replaces password_hash, api.token, totp_secret, webhook secrets with "***".
+/// Replaces password_hash, api_token_hash, api_tokens digests, api.token, totp_secret,
+/// webhook secrets, the audit signing key and archive secret, the CrowdSec API key, the
+/// SMTP password, notification sink credentials, the external auth secret and the cluster
+/// password and secret with "***".
 pub fn redact_config(cfg: &AppConfig) -> AppConfig {
     let mut redacted = cfg.clone();
 
@@ -9,6 +12,9 @@ pub fn redact_config(cfg: &AppConfig) -> AppConfig {
     if !redacted.api.token.is_empty() {
         redacted.api.token = "***".to_string();
     }
+    for account in &mut redacted.api.accounts {
+        account.password_hash = "***".to_string();
+    }
 
     // Redact user sensitive fields
     for user in &mut redacted.users {
@@ -18,6 +24,62 @@ pub fn redact_config(cfg: &AppConfig) -> AppConfig {
         if user.totp_secret.is_some() {
             user.totp_secret = Some("***".to_string());
         }
+        if user.api_token_hash.is_some() {
+            user.api_token_hash = Some("***".to_string());
+        }
+        for token in &mut user.api_tokens {
+            token.sha256 = "***".to_string();
+        }
+    }
+
+    if redacted.logging.audit_signing_key.is_some() {
+        redacted.logging.audit_signing_key = Some("***".to_string());
+    }
+    let archive = &mut redacted.logging.audit.archive;
+    if !archive.secret_access_key.is_empty() {
+        archive.secret_access_key = "***".to_string();
+    }
+
+    if let Some(ref mut external) = redacted.security.external_auth {
+        if external.secret.is_some() {
+            external.secret = Some("***".to_string());
+        }
+    }
+
+    if let Some(ref mut crowdsec) = redacted.threat_intel.crowdsec {
+        crowdsec.api_key = "***".to_string();
+    }
+
+    if let Some(ref mut smtp) = redacted.notifications.smtp {
+        if smtp.password.is_some() {
+            smtp.password = Some("***".to_string());
+        }
+    }
+
+    if redacted.cluster.password.is_some() {
+        redacted.cluster.password = Some("***".to_string());
+    }
+    if redacted.cluster.secret.is_some() {
+        redacted.cluster.secret = Some("***".to_string());
+    }
+
+    for sink in &mut redacted.notifications.sinks {
+        if sink.access_token.is_some() {
+            sink.access_token = Some("***".to_string());
+        }
+        if sink.secret.is_some() {
+            sink.secret = Some("***".to_string());
+        }
+        // Slack and Discord webhook URLs carry their token in the path
+        if matches!(
+            sink.kind,
+            NotificationSinkKind::Slack | NotificationSinkKind::Discord
+        ) {
+            sink.url = match url::Url::parse(&sink.url) {
+                Ok(u) => format!("{}://{}/***", u.scheme(), u.host_str().unwrap_or("")),
+                Err(_) => "***".to_string(),
+            };
+        }
     }
 
     // Redact webhook secrets
@@ -139,6 +201,6 @@ allow_forwarding = true
         let redacted = redact_config(&config);
         assert_eq!(redacted.server.ssh_listen, "0.0.0.0:2222");
         assert_eq!(redacted.users[0].username, "alice");
-        assert!(redacted.users[0].allow_forwarding);
+        assert_eq!(redacted.users[0].allow_forwarding, Some(true));
     }
 }
@@ -1,8 +1,9 @@
 use chrono::{Datelike, Timelike};
-use ipnet::IpNet;
+use ipnet::{IpNet, Ipv4Net};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
 
 /// Log level enum (replaces stringly-typed field)
@@ -107,14 +108,64 @@ pub struct AppConfig {
     pub users: Vec<UserConfig>,
     #[serde(default)]
     pub groups: Vec<GroupConfig>,
+    /// Isolated user namespaces, selected by listener or username suffix
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
     #[serde(default)]
     pub motd: MotdConfig,
     #[serde(default)]
     pub alerting: AlertingConfig,
     #[serde(default)]
     pub maintenance_windows: Vec<MaintenanceWindowConfig>,
+    /// Named quota plans, assigned with `quota_plan` on users or groups
+    #[serde(default)]
+    pub quota_plans: BTreeMap<String, QuotaPlanConfig>,
     #[serde(default)]
     pub connection_pool: ConnectionPoolConfig,
+    #[serde(default)]
+    pub threat_intel: ThreatIntelConfig,
+    #[serde(default)]
+    pub ip_intel: IpIntelConfig,
+    #[serde(default)]
+    pub honeypot: HoneypotConfig,
+    #[serde(default)]
+    pub login_anomaly: LoginAnomalyConfig,
+    #[serde(default)]
+    pub impossible_travel: ImpossibleTravelConfig,
+    #[serde(default)]
+    pub key_enrollment: KeyEnrollmentConfig,
+    #[serde(default)]
+    pub invitations: InvitationConfig,
+    #[serde(default)]
+    pub authorized_keys_fetch: AuthorizedKeysFetchConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub reports: ReportsConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+    /// Layer-3 tunnels (`ssh -w`) to a TUN interface per session
+    #[serde(default)]
+    pub vpn: VpnConfig,
+    /// Custom SSH subsystems (`ssh -s host <name>`), allowed per user or group
+    #[serde(default)]
+    pub subsystems: Vec<SubsystemConfig>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Tag-based rules checked on each channel open, before the ACL
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    /// Lua hooks on logins, channel opens and session closes
+    #[serde(default)]
+    pub scripting: Option<ScriptingConfig>,
+    /// CONNECT and CONNECT-UDP proxying over HTTP/3
+    #[serde(default)]
+    pub masque: Option<MasqueConfig>,
+    /// Kerberos logins over SSH (`gssapi-with-mic`)
+    #[serde(default)]
+    pub gssapi: Option<GssapiConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -148,6 +199,10 @@ pub struct ServerConfig {
     /// Maximum DNS cache entries (default 1000).
     #[serde(default = "default_dns_cache_max_entries")]
     pub dns_cache_max_entries: u32,
+    /// Number of most-used hostnames whose DNS cache entries are refreshed
+    /// in the background shortly before they expire (0 = disabled).
+    #[serde(default)]
+    pub dns_prefetch_hosts: u32,
     /// Smart retry on connect: number of retries (0 = disabled).
     #[serde(default)]
     pub connect_retry: u32,
@@ -168,6 +223,99 @@ pub struct ServerConfig {
     /// Default: 120 seconds. Range: 10-600.
     #[serde(default = "default_ssh_auth_timeout")]
     pub ssh_auth_timeout: u64,
+    /// SSH channel window in bytes: data the client may have in flight per
+    /// channel before it must acknowledge.
+    #[serde(default = "default_ssh_window_size")]
+    pub ssh_window_size: u32,
+    /// Largest SSH channel data packet in bytes.
+    #[serde(default = "default_ssh_max_packet_size")]
+    pub ssh_max_packet_size: u32,
+    /// A forwarded channel whose client has not opened its window for this
+    /// many milliseconds counts as stalled (0 = not tracked).
+    #[serde(default = "default_ssh_stall_threshold_ms")]
+    pub ssh_stall_threshold_ms: u64,
+    /// Connected clients allowed at once, SSH and SOCKS5 together (0 = unlimited).
+    #[serde(default)]
+    pub max_connections: u32,
+    /// Connected clients allowed at once per authenticated user (0 = unlimited).
+    #[serde(default)]
+    pub max_connections_per_user: u32,
+    /// How long a client past `max_connections` waits for a free slot before
+    /// being refused, in milliseconds (0 = refuse at once).
+    #[serde(default)]
+    pub connection_queue_timeout_ms: u64,
+    /// Clients allowed to wait for a slot at once (0 = as many as
+    /// `max_connections`); beyond it, new clients are refused at once.
+    #[serde(default)]
+    pub connection_queue_size: u32,
+    /// Additional SSH listeners, each with its own policy (`[[server.listeners]]`).
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Serve a new host key next to `host_key_path` and retire the old one
+    /// (`[server.host_key_rotation]`).
+    #[serde(default)]
+    pub host_key_rotation: HostKeyRotationConfig,
+    /// Disconnect message for non-admin SSH logins while maintenance mode is
+    /// on (an active `[[maintenance_windows]]` entry uses its own `message`).
+    #[serde(default = "default_server_maintenance_message")]
+    pub maintenance_message: String,
+    /// On `SIGUSR2`, start the binary again with the listening sockets and
+    /// drain this process once the new one serves (Linux only).
+    #[serde(default)]
+    pub socket_handover: bool,
+}
+
+/// Host key rotation. While both keys are served, clients that know the old
+/// key keep negotiating it; once `retire_after_days` have passed since the
+/// new key file was written, only the new key is served.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HostKeyRotationConfig {
+    /// New host key (generated if absent). Unset = no rotation.
+    #[serde(default)]
+    pub new_key_path: Option<PathBuf>,
+    /// Days the old key is still served after the new key appears
+    #[serde(default = "default_host_key_retire_after_days")]
+    pub retire_after_days: u64,
+}
+
+impl Default for HostKeyRotationConfig {
+    fn default() -> Self {
+        Self {
+            new_key_path: None,
+            retire_after_days: default_host_key_retire_after_days(),
+        }
+    }
+}
+
+fn default_host_key_retire_after_days() -> u64 {
+    30
+}
+
+/// An SSH listener besides `ssh_listen`, with its own host key and security
+/// profile. Unset fields follow the server-wide settings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    /// Name shown in logs and metrics
+    pub name: String,
+    /// Bind address
+    pub listen: String,
+    /// Host key for this listener (default: `server.host_key_path`)
+    #[serde(default)]
+    pub host_key_path: Option<PathBuf>,
+    /// Overrides `security.ip_guard_enabled` for tunnels opened through this listener
+    #[serde(default)]
+    pub ip_guard_enabled: Option<bool>,
+    /// Source networks accepted on this listener, on top of
+    /// `security.allowed_source_ips` (empty = any)
+    #[serde(default)]
+    pub allowed_source_ips: Vec<IpNet>,
+    /// Overrides `security.tarpit_enabled` for banned clients on this listener
+    #[serde(default)]
+    pub tarpit_enabled: Option<bool>,
+    /// `[[tenants]]` entry whose users log in here without their suffix;
+    /// users of other tenants and of the default namespace are refused
+    #[serde(default)]
+    pub tenant: Option<String>,
 }
 
 fn default_dns_cache_ttl() -> i64 {
@@ -190,6 +338,10 @@ fn default_banner() -> String {
     "Welcome to s5".to_string()
 }
 
+fn default_server_maintenance_message() -> String {
+    "Server is under maintenance. Please try again later.".to_string()
+}
+
 fn default_shutdown_timeout() -> u64 {
     30
 }
@@ -210,6 +362,18 @@ fn default_ssh_auth_timeout() -> u64 {
     120
 }
 
+fn default_ssh_window_size() -> u32 {
+    2 * 1024 * 1024
+}
+
+fn default_ssh_max_packet_size() -> u32 {
+    32 * 1024
+}
+
+fn default_ssh_stall_threshold_ms() -> u64 {
+    5000
+}
+
 /// MOTD template configuration (global, overridable by group/user)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MotdConfig {
@@ -318,10 +482,80 @@ impl Default for ShellPermissions {
     }
 }
 
+/// Session environment and PTY policy (`terminal` on a group or user).
+/// Unset fields fall back to the group, then to the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TerminalConfig {
+    /// Variable names clients may set with `env` requests (`*` and `?`
+    /// wildcards, e.g. "LC_*"). Default: none.
+    #[serde(default)]
+    pub accept_env: Option<Vec<String>>,
+    /// TERM when the client requests no PTY or names no terminal
+    /// (default "xterm-256color")
+    #[serde(default)]
+    pub default_term: Option<String>,
+    /// Allow PTY allocation (default true)
+    #[serde(default)]
+    pub allow_pty: Option<bool>,
+    /// Largest window width in columns; bigger sizes are clamped (0 = no limit)
+    #[serde(default)]
+    pub max_cols: Option<u32>,
+    /// Largest window height in rows; bigger sizes are clamped (0 = no limit)
+    #[serde(default)]
+    pub max_rows: Option<u32>,
+}
+
+impl TerminalConfig {
+    /// Overlay this policy on `parent`: fields set here win.
+    pub fn overlay_on(&self, parent: &TerminalConfig) -> TerminalConfig {
+        TerminalConfig {
+            accept_env: self
+                .accept_env
+                .clone()
+                .or_else(|| parent.accept_env.clone()),
+            default_term: self
+                .default_term
+                .clone()
+                .or_else(|| parent.default_term.clone()),
+            allow_pty: self.allow_pty.or(parent.allow_pty),
+            max_cols: self.max_cols.or(parent.max_cols),
+            max_rows: self.max_rows.or(parent.max_rows),
+        }
+    }
+}
+
+/// An isolated user namespace (`[[tenants]]`). Its users are the
+/// `[[users]]` entries named `<user>@<tenant>`; a client selects the tenant
+/// by logging in with that suffix or through a listener bound to it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TenantConfig {
+    /// Tenant name, also the username suffix (`alice@acme`)
+    pub name: String,
+    /// API token scoped to this tenant (operator role, tenant users only)
+    #[serde(default)]
+    pub api_token: Option<String>,
+}
+
+impl fmt::Debug for TenantConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantConfig")
+            .field("name", &self.name)
+            .field("api_token", &self.api_token.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
 /// Group configuration (global → group → user inheritance)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GroupConfig {
     pub name: String,
+    /// Parent group name. Fields unset here are taken from the parent chain.
+    #[serde(default)]
+    pub inherits: Option<String>,
+    /// Users who may view and act on this group's members through
+    /// `/api/self/managed` (not inherited by child groups)
+    #[serde(default)]
+    pub managers: Vec<String>,
     #[serde(default)]
     pub acl: UserAclConfig,
     #[serde(default)]
@@ -334,14 +568,39 @@ pub struct GroupConfig {
     pub max_new_connections_per_minute: Option<u32>,
     #[serde(default)]
     pub allow_forwarding: Option<bool>,
+    /// Private/reserved destination ranges members may reach despite ip_guard
+    #[serde(default)]
+    pub ip_guard_exemptions: Option<Vec<IpNet>>,
+    /// Hosts members may reach on a jump port (`ssh -J`), in ACL rule syntax
+    #[serde(default)]
+    pub jump_targets: Option<Vec<String>>,
+    /// Unix socket paths members may forward to (`ssh -L port:/path`)
+    #[serde(default)]
+    pub unix_sockets: Option<Vec<String>>,
+    /// HASSH fingerprints of the SSH clients members may log in with
+    #[serde(default)]
+    pub allowed_hassh: Option<Vec<String>>,
+    /// Host paths the commands started for members may access; set =
+    /// commands are confined with Landlock (Linux)
+    #[serde(default)]
+    pub fs_allow: Option<Vec<String>>,
+    /// `[[subsystems]]` members may open
+    #[serde(default)]
+    pub subsystems: Option<Vec<String>>,
     #[serde(default)]
     pub allow_shell: Option<bool>,
     #[serde(default)]
     pub shell_permissions: Option<ShellPermissions>,
+    /// Session environment and PTY policy
+    #[serde(default)]
+    pub terminal: Option<TerminalConfig>,
     #[serde(default)]
     pub motd: Option<MotdConfig>,
     #[serde(default)]
     pub quotas: Option<QuotaConfig>,
+    /// Name of a `[quota_plans]` entry (instead of `quotas`)
+    #[serde(default)]
+    pub quota_plan: Option<String>,
     #[serde(default)]
     pub time_access: Option<TimeAccessConfig>,
     #[serde(default)]
@@ -359,6 +618,168 @@ pub struct GroupConfig {
     /// Multi-window rate limits for new connections (overrides server defaults)
     #[serde(default)]
     pub rate_limits: Option<RateLimitsConfig>,
+    /// First line of plaintext streams in members' flow records
+    /// (overrides `logging.flows.payload_metadata`)
+    #[serde(default)]
+    pub payload_metadata: Option<bool>,
+    /// Destination ports members may not reach (replaces
+    /// `security.blocked_ports`)
+    #[serde(default)]
+    pub blocked_ports: Option<Vec<u16>>,
+    /// Labels added to members' own `tags`
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Maximum length of a group `inherits` chain (the group itself included).
+pub const MAX_GROUP_INHERITANCE_DEPTH: usize = 8;
+
+impl GroupConfig {
+    /// Overlay this group on top of its resolved `parent`.
+    ///
+    /// Every field set on this group wins; unset fields fall back to the parent.
+    /// ACL allow/deny rules accumulate (parent first) unless this group sets
+    /// `acl.inherit = false`, which drops the parent's rules.
+    pub fn overlay_on(&self, parent: &GroupConfig) -> GroupConfig {
+        let acl = if self.acl.inherit {
+            UserAclConfig {
+                default_policy: self.acl.default_policy.or(parent.acl.default_policy),
+                allow: parent
+                    .acl
+                    .allow
+                    .iter()
+                    .chain(&self.acl.allow)
+                    .cloned()
+                    .collect(),
+                deny: parent
+                    .acl
+                    .deny
+                    .iter()
+                    .chain(&self.acl.deny)
+                    .cloned()
+                    .collect(),
+                inherit: true,
+            }
+        } else {
+            UserAclConfig {
+                inherit: true,
+                ..self.acl.clone()
+            }
+        };
+
+        GroupConfig {
+            name: self.name.clone(),
+            inherits: self.inherits.clone(),
+            managers: self.managers.clone(),
+            acl,
+            max_connections_per_user: self
+                .max_connections_per_user
+                .or(parent.max_connections_per_user),
+            max_bandwidth_kbps: self.max_bandwidth_kbps.or(parent.max_bandwidth_kbps),
+            max_aggregate_bandwidth_kbps: self
+                .max_aggregate_bandwidth_kbps
+                .or(parent.max_aggregate_bandwidth_kbps),
+            max_new_connections_per_minute: self
+                .max_new_connections_per_minute
+                .or(parent.max_new_connections_per_minute),
+            allow_forwarding: self.allow_forwarding.or(parent.allow_forwarding),
+            ip_guard_exemptions: self
+                .ip_guard_exemptions
+                .clone()
+                .or_else(|| parent.ip_guard_exemptions.clone()),
+            jump_targets: self
+                .jump_targets
+                .clone()
+                .or_else(|| parent.jump_targets.clone()),
+            unix_sockets: self
+                .unix_sockets
+                .clone()
+                .or_else(|| parent.unix_sockets.clone()),
+            allowed_hassh: self
+                .allowed_hassh
+                .clone()
+                .or_else(|| parent.allowed_hassh.clone()),
+            fs_allow: self.fs_allow.clone().or_else(|| parent.fs_allow.clone()),
+            subsystems: self
+                .subsystems
+                .clone()
+                .or_else(|| parent.subsystems.clone()),
+            allow_shell: self.allow_shell.or(parent.allow_shell),
+            shell_permissions: self
+                .shell_permissions
+                .clone()
+                .or_else(|| parent.shell_permissions.clone()),
+            terminal: match (&self.terminal, &parent.terminal) {
+                (Some(own), Some(inherited)) => Some(own.overlay_on(inherited)),
+                (own, inherited) => own.clone().or_else(|| inherited.clone()),
+            },
+            motd: self.motd.clone().or_else(|| parent.motd.clone()),
+            // Quotas and plan are one setting: either one here replaces both
+            quotas: if self.quotas.is_some() || self.quota_plan.is_some() {
+                self.quotas.clone()
+            } else {
+                parent.quotas.clone()
+            },
+            quota_plan: if self.quotas.is_some() || self.quota_plan.is_some() {
+                self.quota_plan.clone()
+            } else {
+                parent.quota_plan.clone()
+            },
+            time_access: self
+                .time_access
+                .clone()
+                .or_else(|| parent.time_access.clone()),
+            auth_methods: self
+                .auth_methods
+                .clone()
+                .or_else(|| parent.auth_methods.clone()),
+            idle_warning_secs: self.idle_warning_secs.or(parent.idle_warning_secs),
+            role: self.role.or(parent.role),
+            colors: self.colors.or(parent.colors),
+            connect_retry: self.connect_retry.or(parent.connect_retry),
+            connect_retry_delay_ms: self
+                .connect_retry_delay_ms
+                .or(parent.connect_retry_delay_ms),
+            rate_limits: self
+                .rate_limits
+                .clone()
+                .or_else(|| parent.rate_limits.clone()),
+            payload_metadata: self.payload_metadata.or(parent.payload_metadata),
+            blocked_ports: self
+                .blocked_ports
+                .clone()
+                .or_else(|| parent.blocked_ports.clone()),
+            tags: self.tags.clone().or_else(|| parent.tags.clone()),
+        }
+    }
+}
+
+/// Look up a group by name and flatten its `inherits` chain into a single
+/// effective `GroupConfig`. Returns `None` if the group does not exist.
+///
+/// Missing parents, cycles and over-deep chains are rejected at config load;
+/// here the walk simply stops at the first such link.
+pub fn resolve_group(name: &str, groups: &[GroupConfig]) -> Option<GroupConfig> {
+    let mut chain: Vec<&GroupConfig> = Vec::new();
+    let mut next = Some(name);
+    while let Some(current) = next {
+        if chain.len() >= MAX_GROUP_INHERITANCE_DEPTH || chain.iter().any(|g| g.name == current) {
+            break;
+        }
+        let Some(group) = groups.iter().find(|g| g.name == current) else {
+            break;
+        };
+        chain.push(group);
+        next = group.inherits.as_deref();
+    }
+
+    let (root, descendants) = chain.split_last()?;
+    Some(
+        descendants
+            .iter()
+            .rev()
+            .fold((*root).clone(), |acc, child| child.overlay_on(&acc)),
+    )
 }
 
 /// Time-based access restrictions
@@ -427,6 +848,32 @@ pub struct QuotaConfig {
     pub total_bandwidth_bytes: u64,
 }
 
+/// A named quota plan (`[quota_plans.<name>]`): the `[users.quotas]` limits,
+/// plus an optional schedule on which members' counters are reset.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QuotaPlanConfig {
+    #[serde(flatten)]
+    pub quotas: QuotaConfig,
+    /// When to reset members' counters: "daily HH:MM", "<day> HH:MM"
+    /// (e.g. "mon 00:00") or "monthly <1-31> HH:MM" (`None` = never)
+    #[serde(default)]
+    pub reset_schedule: Option<String>,
+    /// Time zone of `reset_schedule`: "UTC" or a fixed offset like "+02:00"
+    #[serde(default = "default_timezone")]
+    pub reset_timezone: String,
+    /// Counters the scheduled reset clears (empty = all)
+    #[serde(default)]
+    pub reset_counters: Vec<crate::quota::QuotaCounter>,
+}
+
+impl QuotaPlanConfig {
+    /// The parsed `reset_schedule`, if set and valid (checked at load).
+    pub fn schedule(&self) -> Option<crate::quota::plan::ResetSchedule> {
+        let schedule = self.reset_schedule.as_deref()?;
+        crate::quota::plan::ResetSchedule::parse(schedule, &self.reset_timezone).ok()
+    }
+}
+
 /// Alerting rules configuration
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct AlertingConfig {
@@ -700,163 +1147,1701 @@ impl Default for ConnectionPoolConfig {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct LimitsConfig {
-    #[serde(default = "default_max_connections")]
-    pub max_connections: u32,
-    /// Max concurrent connections per user (0 = unlimited). Default: 0.
+/// Proxy engine tuning (`[proxy]`)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProxyConfig {
+    /// Address family used for outbound connections
     #[serde(default)]
-    pub max_connections_per_user: u32,
-    #[serde(default = "default_connection_timeout")]
-    pub connection_timeout: u64,
-    #[serde(default = "default_idle_timeout")]
-    pub idle_timeout: u64,
-    #[serde(default = "default_max_auth_attempts")]
-    pub max_auth_attempts: u32,
-    /// SOCKS5 handshake timeout in seconds (default 30, min 5, max 120).
-    #[serde(default = "default_socks5_handshake_timeout")]
-    pub socks5_handshake_timeout: u64,
-    /// Idle warning: seconds before disconnect to warn user (0 = no warning).
+    pub address_family: AddressFamily,
     #[serde(default)]
-    pub idle_warning_secs: u64,
-    /// Server-wide bandwidth cap in Mbps (0 = unlimited).
+    pub dns_cache: DnsCacheConfig,
+    /// Fixed addresses per hostname, used instead of DNS (`[proxy.hosts]`)
     #[serde(default)]
-    pub max_bandwidth_mbps: u64,
-    /// Server-level max new connections per second (0 = unlimited).
+    pub hosts: HashMap<String, Vec<IpAddr>>,
     #[serde(default)]
-    pub max_new_connections_per_second: u32,
-    /// Server-level max new connections per minute (0 = unlimited).
+    pub circuit_breaker: CircuitBreakerConfig,
     #[serde(default)]
-    pub max_new_connections_per_minute: u32,
-    /// UDP relay idle timeout in seconds (default 300, range 30-3600)
-    #[serde(default = "default_udp_relay_timeout")]
-    pub udp_relay_timeout: u64,
-    /// Maximum concurrent UDP relay sessions per user (0 = unlimited)
+    pub tcp: TcpConfig,
     #[serde(default)]
-    pub max_udp_sessions_per_user: u32,
+    pub buffer_pool: BufferPoolConfig,
+    #[serde(default)]
+    pub priority: PriorityConfig,
 }
 
-impl Default for LimitsConfig {
-    fn default() -> Self {
-        Self {
-            max_connections: default_max_connections(),
-            max_connections_per_user: 0,
-            connection_timeout: default_connection_timeout(),
-            idle_timeout: default_idle_timeout(),
-            max_auth_attempts: default_max_auth_attempts(),
-            socks5_handshake_timeout: default_socks5_handshake_timeout(),
-            idle_warning_secs: 0,
-            max_bandwidth_mbps: 0,
-            max_new_connections_per_second: 0,
-            max_new_connections_per_minute: 0,
-            udp_relay_timeout: default_udp_relay_timeout(),
-            max_udp_sessions_per_user: 0,
-        }
-    }
+/// Fail fast to destinations that keep failing (`[proxy.circuit_breaker]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Failed connects to one `host:port` within `window_secs` that open the circuit
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Window in seconds over which failures are counted
+    #[serde(default = "default_circuit_window_secs")]
+    pub window_secs: u64,
+    /// Seconds connects fail fast before one probe is let through
+    #[serde(default = "default_circuit_open_secs")]
+    pub open_secs: u64,
 }
 
-fn default_socks5_handshake_timeout() -> u64 {
-    30
+fn default_circuit_failure_threshold() -> u32 {
+    5
 }
 
-fn default_udp_relay_timeout() -> u64 {
-    300
+fn default_circuit_window_secs() -> u64 {
+    60
 }
 
-fn default_max_connections() -> u32 {
-    1000
-}
-fn default_connection_timeout() -> u64 {
-    300
-}
-fn default_idle_timeout() -> u64 {
-    0
+fn default_circuit_open_secs() -> u64 {
+    30
 }
-fn default_max_auth_attempts() -> u32 {
-    3
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: default_circuit_failure_threshold(),
+            window_secs: default_circuit_window_secs(),
+            open_secs: default_circuit_open_secs(),
+        }
+    }
 }
 
+/// Socket options of outbound TCP connections (`[proxy.tcp]`)
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct SecurityConfig {
-    #[serde(default)]
-    pub allowed_source_ips: Vec<IpNet>,
-    #[serde(default = "default_true")]
-    pub ban_enabled: bool,
-    #[serde(default = "default_ban_threshold")]
-    pub ban_threshold: u32,
-    #[serde(default = "default_ban_window")]
-    pub ban_window: u64,
-    #[serde(default = "default_ban_duration")]
-    pub ban_duration: u64,
+pub struct TcpConfig {
+    /// Idle seconds before the first keepalive probe (0 = keepalive off)
+    #[serde(default = "default_tcp_keepalive_time_secs")]
+    pub keepalive_time_secs: u64,
+    /// Seconds between keepalive probes
+    #[serde(default = "default_tcp_keepalive_interval_secs")]
+    pub keepalive_interval_secs: u64,
+    /// Unanswered probes before the connection is dropped (unset = OS default)
     #[serde(default)]
-    pub ban_whitelist: Vec<String>,
-    #[serde(default = "default_true")]
-    pub ip_guard_enabled: bool,
-    #[serde(default)]
-    pub totp_required_for: Vec<String>,
-    /// Maximum new connections per IP per minute (pre-auth). 0 = unlimited.
-    #[serde(default)]
-    pub max_new_connections_per_ip_per_minute: u32,
-    /// IP reputation scoring: enable dynamic scoring
+    pub keepalive_retries: Option<u32>,
+    /// TCP_USER_TIMEOUT in milliseconds, Linux only (0 = OS default)
     #[serde(default)]
-    pub ip_reputation_enabled: bool,
-    /// IP reputation: auto-ban threshold score (0 = disabled)
-    #[serde(default = "default_ip_reputation_threshold")]
-    pub ip_reputation_ban_threshold: u32,
-    /// Trusted CA keys for SSH certificate authentication
+    pub user_timeout_ms: u64,
+    /// TCP Fast Open on outbound connects, Linux only
     #[serde(default)]
-    pub trusted_user_ca_keys: Vec<String>,
-    /// Argon2id memory cost in KiB (default 19456 = 19 MiB, OWASP recommendation)
-    #[serde(default = "default_argon2_memory_cost")]
-    pub argon2_memory_cost: u32,
-    /// Argon2id time cost / iterations (default 2)
-    #[serde(default = "default_argon2_time_cost")]
-    pub argon2_time_cost: u32,
-    /// Argon2id parallelism / lanes (default 1)
-    #[serde(default = "default_argon2_parallelism")]
-    pub argon2_parallelism: u32,
-    /// Rate limiter cleanup interval in seconds (default 60).
-    /// A background task prunes stale entries at this interval.
-    #[serde(default = "default_rate_limit_cleanup_interval")]
-    pub rate_limit_cleanup_interval: u64,
-    /// Maximum number of tracked IPs in the rate limiter (default 100_000).
-    /// When exceeded, oldest entries are evicted.
-    #[serde(default = "default_rate_limit_max_ips")]
-    pub rate_limit_max_ips: usize,
-    /// Maximum number of tracked usernames in the rate limiter (default 10_000).
-    /// When exceeded, oldest entries are evicted.
-    #[serde(default = "default_rate_limit_max_users")]
-    pub rate_limit_max_users: usize,
+    pub fast_open: bool,
 }
 
-fn default_ip_reputation_threshold() -> u32 {
-    100
+fn default_tcp_keepalive_time_secs() -> u64 {
+    60
 }
 
-fn default_argon2_memory_cost() -> u32 {
-    19456
+fn default_tcp_keepalive_interval_secs() -> u64 {
+    15
 }
 
-fn default_argon2_time_cost() -> u32 {
-    2
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            keepalive_time_secs: default_tcp_keepalive_time_secs(),
+            keepalive_interval_secs: default_tcp_keepalive_interval_secs(),
+            keepalive_retries: None,
+            user_timeout_ms: 0,
+            fast_open: false,
+        }
+    }
 }
 
-fn default_argon2_parallelism() -> u32 {
-    1
+/// Relay buffers shared between tunnels (`[proxy.buffer_pool]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BufferPoolConfig {
+    /// Size in bytes of each relay read buffer
+    #[serde(default = "default_relay_buffer_size")]
+    pub buffer_size: usize,
+    /// Idle buffers kept for reuse (0 = no pooling)
+    #[serde(default = "default_buffer_pool_max_buffers")]
+    pub max_buffers: usize,
 }
 
-fn default_rate_limit_cleanup_interval() -> u64 {
-    60
+fn default_relay_buffer_size() -> usize {
+    8192
 }
 
-fn default_rate_limit_max_ips() -> usize {
-    100_000
+fn default_buffer_pool_max_buffers() -> usize {
+    1024
 }
 
-fn default_rate_limit_max_users() -> usize {
+impl Default for BufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: default_relay_buffer_size(),
+            max_buffers: default_buffer_pool_max_buffers(),
+        }
+    }
+}
+
+/// Priority classes of relayed sessions (`[proxy.priority]`); scheduling
+/// is off without classes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PriorityConfig {
+    /// Relay writes in progress at once; further chunks wait and go out by
+    /// class weight
+    #[serde(default = "default_priority_max_inflight")]
+    pub max_inflight: usize,
+    /// Milliseconds a write to a slow peer keeps its slot
+    #[serde(default = "default_priority_hold_ms")]
+    pub hold_ms: u64,
+    /// Weight of sessions no class matches
+    #[serde(default = "default_priority_weight")]
+    pub default_weight: u32,
+    /// Checked in order; the first match wins (`[[proxy.priority.classes]]`)
+    #[serde(default)]
+    pub classes: Vec<PriorityClassConfig>,
+}
+
+fn default_priority_max_inflight() -> usize {
+    16
+}
+
+fn default_priority_hold_ms() -> u64 {
+    50
+}
+
+fn default_priority_weight() -> u32 {
+    1
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            max_inflight: default_priority_max_inflight(),
+            hold_ms: default_priority_hold_ms(),
+            default_weight: default_priority_weight(),
+            classes: Vec::new(),
+        }
+    }
+}
+
+/// One priority class (`[[proxy.priority.classes]]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriorityClassConfig {
+    pub name: String,
+    /// Share of writes against other classes when they compete (1-1000)
+    #[serde(default = "default_priority_weight")]
+    pub weight: u32,
+    /// Destinations in ACL rule syntax, e.g. "*:22" or "10.0.0.0/8:3389"
+    #[serde(default)]
+    pub destinations: Vec<String>,
+    /// Users with one of these `tags`
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Tag-based channel policy (`[policy]`); off without rules or plugin
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Checked in order; the first matching rule allows or denies the
+    /// channel, e.g. "allow user:tag(dev) dest:tag(staging) port:443"
+    #[serde(default)]
+    pub rules: Vec<String>,
+    /// Tagged destinations (`[[policy.destinations]]`)
+    #[serde(default)]
+    pub destinations: Vec<PolicyDestinationConfig>,
+    /// Tagged time windows (`[[policy.time_windows]]`)
+    #[serde(default)]
+    pub time_windows: Vec<PolicyTimeWindowConfig>,
+    /// WASM module deciding the channels no rule matches (`[policy.plugin]`)
+    #[serde(default)]
+    pub plugin: Option<PolicyPluginConfig>,
+}
+
+/// A WASM policy plugin (`[policy.plugin]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyPluginConfig {
+    /// Compiled WebAssembly module (`.wasm`)
+    pub path: PathBuf,
+    /// WebAssembly instructions one decision may run
+    #[serde(default = "default_policy_plugin_fuel")]
+    pub fuel: u64,
+    /// Wall-clock limit of one decision
+    #[serde(default = "default_policy_plugin_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_policy_plugin_fuel() -> u64 {
+    10_000_000
+}
+
+fn default_policy_plugin_timeout_ms() -> u64 {
+    50
+}
+
+/// Destinations named by `dest:tag(<tag>)`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyDestinationConfig {
+    pub tag: String,
+    /// In ACL rule syntax, e.g. "*.staging.example.com" or "10.20.0.0/16:443"
+    pub targets: Vec<String>,
+}
+
+/// A time window named by `time:tag(<tag>)`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyTimeWindowConfig {
+    pub tag: String,
+    /// "HH:MM-HH:MM", past midnight when the end comes first (None = all day)
+    #[serde(default)]
+    pub hours: Option<String>,
+    /// "mon" to "sun" (empty = every day)
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// "UTC" or a fixed offset like "+02:00"
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+/// Lua hook script (`[scripting]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptingConfig {
+    /// Script defining `on_auth`, `on_channel_open` and/or `on_session_close`
+    pub path: PathBuf,
+    /// Wall-clock limit of one hook call
+    #[serde(default = "default_scripting_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Memory the script may allocate, in KiB
+    #[serde(default = "default_scripting_memory_limit_kb")]
+    pub memory_limit_kb: usize,
+}
+
+fn default_scripting_timeout_ms() -> u64 {
+    50
+}
+
+fn default_scripting_memory_limit_kb() -> usize {
+    16 * 1024
+}
+
+/// HTTP/3 proxy listener (`[masque]`): CONNECT to TCP destinations and
+/// CONNECT-UDP (RFC 9298) to UDP ones, authenticated like SOCKS5 with a
+/// `Proxy-Authorization: Basic` header
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MasqueConfig {
+    /// UDP address of the QUIC listener
+    pub listen: String,
+    /// TLS certificate chain (PEM)
+    pub tls_cert: PathBuf,
+    /// TLS private key (PEM)
+    pub tls_key: PathBuf,
+}
+
+/// Kerberos logins over SSH (`[gssapi]`): the `gssapi-with-mic` method,
+/// with each client principal mapped to the s5 user it may log in as
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GssapiConfig {
+    /// Keytab of the `host/` service principal (default: the Kerberos
+    /// library's, usually `/etc/krb5.keytab`)
+    #[serde(default)]
+    pub keytab: Option<PathBuf>,
+    /// Principals mapped to a user as they are (`"alice@EXAMPLE.COM" = "alice"`),
+    /// checked before `rules`
+    #[serde(default)]
+    pub principals: HashMap<String, String>,
+    /// Pattern rules tried in order when `principals` has no entry
+    #[serde(default)]
+    pub rules: Vec<GssapiRule>,
+}
+
+/// A `[[gssapi.rules]]` entry: principals matching `pattern` log in as
+/// `user`, where `$1`, `$name`... stand for the pattern's groups
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GssapiRule {
+    /// Regular expression matched against the whole principal
+    pub pattern: String,
+    /// User name, with the pattern's groups expanded
+    pub user: String,
+}
+
+/// Which resolved addresses outbound connections try, and in what order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    /// Resolver order
+    #[default]
+    Any,
+    /// IPv6 addresses first, then IPv4
+    PreferIpv6,
+    /// IPv4 addresses first, then IPv6
+    PreferIpv4,
+    /// IPv4 addresses only
+    Ipv4Only,
+    /// IPv6 addresses only
+    Ipv6Only,
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Any => write!(f, "any"),
+            AddressFamily::PreferIpv6 => write!(f, "prefer_ipv6"),
+            AddressFamily::PreferIpv4 => write!(f, "prefer_ipv4"),
+            AddressFamily::Ipv4Only => write!(f, "ipv4_only"),
+            AddressFamily::Ipv6Only => write!(f, "ipv6_only"),
+        }
+    }
+}
+
+/// DNS cache bounds (`[proxy.dns_cache]`); the TTL mode stays in
+/// `server.dns_cache_ttl`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DnsCacheConfig {
+    /// Shortest time in seconds a lookup is cached
+    #[serde(default)]
+    pub min_ttl: u64,
+    /// Longest time in seconds a lookup is cached
+    #[serde(default = "default_dns_cache_max_ttl")]
+    pub max_ttl: u64,
+    /// Maximum entries; overrides `server.dns_cache_max_entries` when set
+    #[serde(default)]
+    pub max_entries: Option<u32>,
+    /// Entry dropped when the cache is full (after expired ones)
+    #[serde(default)]
+    pub eviction: DnsEvictionPolicy,
+    /// Try the addresses of a destination fastest first, from recent
+    /// connect times (false = resolver order)
+    #[serde(default = "default_true")]
+    pub latency_ordering: bool,
+    /// Seconds a measured connect time or failure is remembered
+    #[serde(default = "default_dns_latency_ttl")]
+    pub latency_ttl: u64,
+}
+
+fn default_dns_cache_max_ttl() -> u64 {
+    3600
+}
+
+fn default_dns_latency_ttl() -> u64 {
+    600
+}
+
+impl Default for DnsCacheConfig {
+    fn default() -> Self {
+        Self {
+            min_ttl: 0,
+            max_ttl: default_dns_cache_max_ttl(),
+            max_entries: None,
+            eviction: DnsEvictionPolicy::default(),
+            latency_ordering: true,
+            latency_ttl: default_dns_latency_ttl(),
+        }
+    }
+}
+
+/// Which DNS cache entry makes room for a new one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsEvictionPolicy {
+    /// Least recently looked up
+    #[default]
+    Lru,
+    /// Least frequently looked up
+    Lfu,
+    /// Oldest fetch
+    Fifo,
+}
+
+impl fmt::Display for DnsEvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsEvictionPolicy::Lru => write!(f, "lru"),
+            DnsEvictionPolicy::Lfu => write!(f, "lfu"),
+            DnsEvictionPolicy::Fifo => write!(f, "fifo"),
+        }
+    }
+}
+
+/// External threat intelligence merged into the ban engine (`[threat_intel]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ThreatIntelConfig {
+    /// Seconds between feed refreshes
+    #[serde(default = "default_threat_intel_refresh")]
+    pub refresh_interval: u64,
+    /// CrowdSec Local API, polled in bouncer stream mode
+    #[serde(default)]
+    pub crowdsec: Option<CrowdSecConfig>,
+    /// Plain-text IP/CIDR lists, one entry per line
+    #[serde(default)]
+    pub denylists: Vec<DenylistConfig>,
+}
+
+fn default_threat_intel_refresh() -> u64 {
+    60
+}
+
+impl Default for ThreatIntelConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval: default_threat_intel_refresh(),
+            crowdsec: None,
+            denylists: Vec::new(),
+        }
+    }
+}
+
+impl ThreatIntelConfig {
+    /// True if at least one feed is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.crowdsec.is_some() || !self.denylists.is_empty()
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CrowdSecConfig {
+    /// LAPI base URL, e.g. `http://127.0.0.1:8080`
+    pub url: String,
+    /// Bouncer API key from `cscli bouncers add`
+    pub api_key: String,
+}
+
+impl fmt::Debug for CrowdSecConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrowdSecConfig")
+            .field("url", &self.url)
+            .field("api_key", &"***")
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DenylistConfig {
+    /// Feed name used in logs and metric labels
+    pub name: String,
+    /// http(s) URL of the list
+    pub url: String,
+}
+
+/// Offline Tor exit and datacenter ranges (`[ip_intel]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IpIntelConfig {
+    /// Source conditions refused before authentication
+    #[serde(default)]
+    pub deny: Vec<SourceCondition>,
+    /// Dataset written by `s5 update-intel` (unset = embedded ranges only)
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Tor exit lists, one IP per line
+    #[serde(default = "default_ip_intel_tor_urls")]
+    pub tor_urls: Vec<String>,
+    /// Datacenter and hosting range lists, one IP or CIDR per line
+    #[serde(default = "default_ip_intel_datacenter_urls")]
+    pub datacenter_urls: Vec<String>,
+}
+
+fn default_ip_intel_tor_urls() -> Vec<String> {
+    vec!["https://check.torproject.org/torbulkexitlist".to_string()]
+}
+
+fn default_ip_intel_datacenter_urls() -> Vec<String> {
+    vec![
+        "https://raw.githubusercontent.com/X4BNet/lists_vpn/main/output/datacenter/ipv4.txt"
+            .to_string(),
+    ]
+}
+
+impl Default for IpIntelConfig {
+    fn default() -> Self {
+        Self {
+            deny: Vec::new(),
+            path: None,
+            tor_urls: default_ip_intel_tor_urls(),
+            datacenter_urls: default_ip_intel_datacenter_urls(),
+        }
+    }
+}
+
+/// What `[ip_intel]` knows about a source address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceCondition {
+    /// A current Tor exit relay
+    SourceIsTor,
+    /// A cloud provider or hosting network
+    SourceIsDatacenter,
+}
+
+impl fmt::Display for SourceCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceCondition::SourceIsTor => write!(f, "source_is_tor"),
+            SourceCondition::SourceIsDatacenter => write!(f, "source_is_datacenter"),
+        }
+    }
+}
+
+/// Decoy logins that open a sandboxed session and flag the source (`[honeypot]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HoneypotConfig {
+    #[serde(default)]
+    pub credentials: Vec<HoneypotCredential>,
+    /// Ban the source IP when a decoy login is used
+    #[serde(default = "default_true")]
+    pub ban: bool,
+    /// Ban duration in seconds (default 86400)
+    #[serde(default = "default_honeypot_ban_duration")]
+    pub ban_duration: u64,
+}
+
+fn default_honeypot_ban_duration() -> u64 {
+    86_400
+}
+
+impl Default for HoneypotConfig {
+    fn default() -> Self {
+        Self {
+            credentials: Vec::new(),
+            ban: true,
+            ban_duration: default_honeypot_ban_duration(),
+        }
+    }
+}
+
+/// Flag logins that break a user's habits (`[login_anomaly]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoginAnomalyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// JSON file keeping each user's login history across restarts
+    /// (None = in memory only)
+    #[serde(default)]
+    pub history_path: Option<PathBuf>,
+    /// Logins on record before a user's history is trusted (default 5)
+    #[serde(default = "default_login_anomaly_learning_logins")]
+    pub learning_logins: u32,
+    /// Flag a country the user never logged in from (needs `geoip.database_path`)
+    #[serde(default = "default_true")]
+    pub new_country: bool,
+    /// Flag an ASN the user never logged in from (needs `geoip.asn_database_path`)
+    #[serde(default = "default_true")]
+    pub new_asn: bool,
+    /// Flag a login more than `hour_tolerance` hours (UTC) away from any
+    /// hour the user logged in at before
+    #[serde(default = "default_true")]
+    pub unusual_hours: bool,
+    /// Hours around a known login hour that still count as usual (default 1)
+    #[serde(default = "default_login_anomaly_hour_tolerance")]
+    pub hour_tolerance: u32,
+}
+
+fn default_login_anomaly_learning_logins() -> u32 {
+    5
+}
+
+fn default_login_anomaly_hour_tolerance() -> u32 {
+    1
+}
+
+impl Default for LoginAnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            history_path: None,
+            learning_logins: default_login_anomaly_learning_logins(),
+            new_country: true,
+            new_asn: true,
+            unusual_hours: true,
+            hour_tolerance: default_login_anomaly_hour_tolerance(),
+        }
+    }
+}
+
+/// What to do with an impossible-travel login
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImpossibleTravelAction {
+    /// Don't check
+    Off,
+    /// Allow the login and raise an `auth.impossible_travel` audit event
+    #[default]
+    Flag,
+    /// Refuse the login as well
+    Block,
+}
+
+/// Logins for one user from places too far apart to travel between in the
+/// time elapsed (`[impossible_travel]`). Needs `geoip.database_path` to point
+/// to a City database (e.g. GeoLite2-City).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImpossibleTravelConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Default action; users can override it with `impossible_travel`
+    #[serde(default)]
+    pub action: ImpossibleTravelAction,
+    /// Fastest plausible travel speed in km/h (default 1000, about an airliner)
+    #[serde(default = "default_impossible_travel_max_speed_kmh")]
+    pub max_speed_kmh: u32,
+    /// Distances below this many km are never flagged, to absorb GeoIP
+    /// inaccuracy (default 500)
+    #[serde(default = "default_impossible_travel_min_distance_km")]
+    pub min_distance_km: u32,
+    /// Seconds a login is compared with later ones (default 3600)
+    #[serde(default = "default_impossible_travel_window_secs")]
+    pub window_secs: u64,
+}
+
+fn default_impossible_travel_max_speed_kmh() -> u32 {
+    1000
+}
+
+fn default_impossible_travel_min_distance_km() -> u32 {
+    500
+}
+
+fn default_impossible_travel_window_secs() -> u64 {
+    3600
+}
+
+impl Default for ImpossibleTravelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            action: ImpossibleTravelAction::default(),
+            max_speed_kmh: default_impossible_travel_max_speed_kmh(),
+            min_distance_km: default_impossible_travel_min_distance_km(),
+            window_secs: default_impossible_travel_window_secs(),
+        }
+    }
+}
+
+/// Admin approval of public keys a user presents for the first time
+/// (`[key_enrollment]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyEnrollmentConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds an SSH login with a new key is held waiting for a decision
+    /// (0 = reject at once; the key is accepted on the next login once
+    /// approved). Must stay below `server.ssh_auth_timeout`.
+    #[serde(default = "default_key_enrollment_wait_secs")]
+    pub wait_secs: u64,
+    /// Hours a request is kept, decided or not (default 24, max 8760)
+    #[serde(default = "default_key_enrollment_pending_hours")]
+    pub pending_hours: u64,
+    /// Pending requests kept per user; further new keys are rejected (default 3)
+    #[serde(default = "default_key_enrollment_max_pending_per_user")]
+    pub max_pending_per_user: usize,
+}
+
+fn default_key_enrollment_wait_secs() -> u64 {
+    60
+}
+
+fn default_key_enrollment_pending_hours() -> u64 {
+    24
+}
+
+fn default_key_enrollment_max_pending_per_user() -> usize {
+    3
+}
+
+impl Default for KeyEnrollmentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wait_secs: default_key_enrollment_wait_secs(),
+            pending_hours: default_key_enrollment_pending_hours(),
+            max_pending_per_user: default_key_enrollment_max_pending_per_user(),
+        }
+    }
+}
+
+/// Single-use onboarding links made by admins (`[invitations]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InvitationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Hours a link stays valid unless the admin sets another TTL (default
+    /// 72, max 8760)
+    #[serde(default = "default_invitation_ttl_hours")]
+    pub ttl_hours: u64,
+    /// Invitations waiting at once; further ones are refused (default 100)
+    #[serde(default = "default_invitation_max_pending")]
+    pub max_pending: usize,
+    /// Base of the links handed out, e.g. "https://s5.example.com:9091"
+    /// (None = links relative to the API address)
+    #[serde(default)]
+    pub public_url: Option<String>,
+}
+
+fn default_invitation_ttl_hours() -> u64 {
+    72
+}
+
+fn default_invitation_max_pending() -> usize {
+    100
+}
+
+impl Default for InvitationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_hours: default_invitation_ttl_hours(),
+            max_pending: default_invitation_max_pending(),
+            public_url: None,
+        }
+    }
+}
+
+/// Fetching of per-user `authorized_keys_url` (`[authorized_keys_fetch]`)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuthorizedKeysFetchConfig {
+    /// Seconds fetched keys are used before the URL is asked again, with
+    /// the cached ETag (default 300; 0 = on every login)
+    #[serde(default = "default_keys_fetch_refresh_secs")]
+    pub refresh_secs: u64,
+    /// Request timeout (default 5, max 60)
+    #[serde(default = "default_keys_fetch_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Seconds cached keys keep working while the URL is unreachable, from
+    /// the last successful fetch (default 86400; 0 = not at all)
+    #[serde(default = "default_keys_fetch_max_stale_secs")]
+    pub max_stale_secs: u64,
+}
+
+fn default_keys_fetch_refresh_secs() -> u64 {
+    300
+}
+
+fn default_keys_fetch_timeout_secs() -> u64 {
+    5
+}
+
+fn default_keys_fetch_max_stale_secs() -> u64 {
+    86_400
+}
+
+impl Default for AuthorizedKeysFetchConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: default_keys_fetch_refresh_secs(),
+            timeout_secs: default_keys_fetch_timeout_secs(),
+            max_stale_secs: default_keys_fetch_max_stale_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HoneypotCredential {
+    pub username: String,
+    /// Decoy password; None = any password is accepted for this username
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Email notifications for security events (`[notifications]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+    /// Chat and HTTP destinations, referenced by name from `rules.sinks`
+    #[serde(default)]
+    pub sinks: Vec<NotificationSinkConfig>,
+    #[serde(default)]
+    pub rules: Vec<NotificationRule>,
+    /// Seconds to collect events before sending one digest email (default 60)
+    #[serde(default = "default_notification_batch_window")]
+    pub batch_window_secs: u64,
+    /// Seconds during which a repeat of the same event is dropped (default 3600)
+    #[serde(default = "default_notification_dedup_window")]
+    pub dedup_window_secs: u64,
+    /// Events listed in one email; the rest are only counted (default 50)
+    #[serde(default = "default_notification_max_events")]
+    pub max_events_per_email: usize,
+    /// Auth failures (server-wide) that make an `auth_failure_spike` (default 20)
+    #[serde(default = "default_notification_auth_failure_threshold")]
+    pub auth_failure_threshold: u32,
+    /// Window in seconds for `auth_failure_threshold` (default 60)
+    #[serde(default = "default_notification_auth_failure_window")]
+    pub auth_failure_window_secs: u64,
+}
+
+fn default_notification_batch_window() -> u64 {
+    60
+}
+
+fn default_notification_dedup_window() -> u64 {
+    3600
+}
+
+fn default_notification_max_events() -> usize {
+    50
+}
+
+fn default_notification_auth_failure_threshold() -> u32 {
+    20
+}
+
+fn default_notification_auth_failure_window() -> u64 {
+    60
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            smtp: None,
+            sinks: Vec::new(),
+            rules: Vec::new(),
+            batch_window_secs: default_notification_batch_window(),
+            dedup_window_secs: default_notification_dedup_window(),
+            max_events_per_email: default_notification_max_events(),
+            auth_failure_threshold: default_notification_auth_failure_threshold(),
+            auth_failure_window_secs: default_notification_auth_failure_window(),
+        }
+    }
+}
+
+impl NotificationsConfig {
+    /// True if at least one rule and one destination are configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty() && (self.smtp.is_some() || !self.sinks.is_empty())
+    }
+}
+
+/// Monthly per-user usage reports (`[reports]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReportsConfig {
+    /// JSON file keeping the monthly totals across restarts
+    /// (None = in memory only)
+    #[serde(default)]
+    pub usage_history_path: Option<PathBuf>,
+    /// Months kept, the current one included (default 13)
+    #[serde(default = "default_usage_history_months")]
+    pub usage_history_months: u32,
+    /// Email last month's report on the 1st (needs `[notifications.smtp]`)
+    #[serde(default)]
+    pub monthly_email: bool,
+    /// Report recipients (empty = `notifications.smtp.to`)
+    #[serde(default)]
+    pub email_to: Vec<String>,
+}
+
+fn default_usage_history_months() -> u32 {
+    13
+}
+
+impl Default for ReportsConfig {
+    fn default() -> Self {
+        Self {
+            usage_history_path: None,
+            usage_history_months: default_usage_history_months(),
+            monthly_email: false,
+            email_to: Vec::new(),
+        }
+    }
+}
+
+/// Shared state backend of a cluster
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterBackend {
+    /// A Redis server shared by every node
+    #[default]
+    Redis,
+    /// Direct exchanges between the nodes in `peers`
+    Gossip,
+}
+
+/// Several instances sharing bans, quota counters and session lists
+/// (`[cluster]`)
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ClusterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub backend: ClusterBackend,
+    /// `redis://host:port/db` or `rediss://` for TLS, without credentials
+    #[serde(default = "default_cluster_url")]
+    pub url: String,
+    /// Redis ACL user (None = `default`)
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Name of this instance, unique in the cluster (empty = `server.server_id`)
+    #[serde(default)]
+    pub node_id: String,
+    /// Prefix of every key written (default `"s5:"`)
+    #[serde(default = "default_cluster_key_prefix")]
+    pub key_prefix: String,
+    /// Seconds between syncs with the backend (default 5)
+    #[serde(default = "default_cluster_sync_interval")]
+    pub sync_interval_secs: u64,
+    /// Connect and per-command timeout in seconds (default 5)
+    #[serde(default = "default_cluster_timeout")]
+    pub timeout_secs: u64,
+    /// Gossip: address the other nodes connect to (default `"0.0.0.0:7946"`)
+    #[serde(default = "default_cluster_listen")]
+    pub listen: String,
+    /// Gossip: `host:port` of every other node
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// Gossip: key signing every exchange, the same on every node
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Serve only the read-only API and dashboard on the shared state: no
+    /// SSH or SOCKS5 listener, nothing written to the backend
+    #[serde(default)]
+    pub observer: bool,
+    #[serde(default = "default_true")]
+    pub share_bans: bool,
+    #[serde(default = "default_true")]
+    pub share_quotas: bool,
+    #[serde(default = "default_true")]
+    pub share_sessions: bool,
+}
+
+fn default_cluster_url() -> String {
+    "redis://127.0.0.1:6379".to_string()
+}
+
+fn default_cluster_key_prefix() -> String {
+    "s5:".to_string()
+}
+
+fn default_cluster_sync_interval() -> u64 {
+    5
+}
+
+fn default_cluster_timeout() -> u64 {
+    5
+}
+
+fn default_cluster_listen() -> String {
+    "0.0.0.0:7946".to_string()
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: ClusterBackend::default(),
+            url: default_cluster_url(),
+            username: None,
+            password: None,
+            node_id: String::new(),
+            key_prefix: default_cluster_key_prefix(),
+            sync_interval_secs: default_cluster_sync_interval(),
+            timeout_secs: default_cluster_timeout(),
+            listen: default_cluster_listen(),
+            peers: Vec::new(),
+            secret: None,
+            observer: false,
+            share_bans: true,
+            share_quotas: true,
+            share_sessions: true,
+        }
+    }
+}
+
+impl fmt::Debug for ClusterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterConfig")
+            .field("enabled", &self.enabled)
+            .field("backend", &self.backend)
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("node_id", &self.node_id)
+            .field("key_prefix", &self.key_prefix)
+            .field("sync_interval_secs", &self.sync_interval_secs)
+            .field("listen", &self.listen)
+            .field("peers", &self.peers)
+            .field("secret", &self.secret.as_ref().map(|_| "***"))
+            .field("observer", &self.observer)
+            .finish()
+    }
+}
+
+/// Privilege drop and syscall filter applied once the listeners are bound
+/// and the host keys read (`[sandbox]`, Linux only)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SandboxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Switch to this user (requires starting as root)
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Switch to this group (default: the user's primary group)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Refuse the syscalls s5 never makes (kernel modules, mounts, ptrace...)
+    #[serde(default)]
+    pub seccomp: bool,
+}
+
+/// Layer-3 tunnels (`[vpn]`, Linux only): an OpenSSH `tun@openssh.com`
+/// channel (`ssh -w any -o Tunnel=point-to-point`) of a user with
+/// `allow_vpn` gets its own TUN interface, routed to one address of `pool`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VpnConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Tunnel addresses: the first one is the server end of every tunnel,
+    /// clients get the others (`vpn_address` or the first free one)
+    #[serde(default = "default_vpn_pool")]
+    pub pool: Ipv4Net,
+    /// MTU of the TUN interfaces
+    #[serde(default = "default_vpn_mtu")]
+    pub mtu: u16,
+}
+
+fn default_vpn_pool() -> Ipv4Net {
+    Ipv4Net::new(Ipv4Addr::new(10, 99, 0, 0), 24).expect("valid prefix")
+}
+
+fn default_vpn_mtu() -> u16 {
+    1420
+}
+
+impl Default for VpnConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pool: default_vpn_pool(),
+            mtu: default_vpn_mtu(),
+        }
+    }
+}
+
+/// Custom SSH subsystem (`[[subsystems]]`): the channel is relayed to a
+/// command's stdin/stdout or to a Unix socket. Exactly one backend is set.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubsystemConfig {
+    /// Name requested by the client (`ssh -s host <name>`)
+    pub name: String,
+    /// Program (absolute path) and arguments, started for each channel
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Unix socket connected for each channel
+    #[serde(default)]
+    pub socket: Option<String>,
+}
+
+/// Sink name that routes a rule to `[notifications.smtp]`.
+pub const EMAIL_SINK: &str = "email";
+
+/// Message format of a notification sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationSinkKind {
+    /// JSON POST with `subject`, `text` and `events` (optionally HMAC-signed)
+    Webhook,
+    /// Slack incoming webhook
+    Slack,
+    /// Discord channel webhook
+    Discord,
+    /// Matrix client-server API (`m.room.message`)
+    Matrix,
+}
+
+impl fmt::Display for NotificationSinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Webhook => write!(f, "webhook"),
+            Self::Slack => write!(f, "slack"),
+            Self::Discord => write!(f, "discord"),
+            Self::Matrix => write!(f, "matrix"),
+        }
+    }
+}
+
+/// A chat or HTTP destination (`[[notifications.sinks]]`)
+#[derive(Clone, Deserialize, Serialize)]
+pub struct NotificationSinkConfig {
+    /// Name referenced by `rules.sinks`
+    pub name: String,
+    pub kind: NotificationSinkKind,
+    /// Webhook URL, or the homeserver base URL for Matrix
+    pub url: String,
+    /// Matrix room ID, e.g. `!abcdef:example.org`
+    #[serde(default)]
+    pub room_id: Option<String>,
+    /// Matrix access token of the posting user
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// HMAC-SHA256 secret for the `X-Signature-256` header (`webhook` only)
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Allow delivery to private/internal IPs (for local relays)
+    #[serde(default)]
+    pub allow_private_ips: bool,
+}
+
+impl fmt::Debug for NotificationSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Slack and Discord webhook URLs embed their token
+        f.debug_struct("NotificationSinkConfig")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("url", &"***")
+            .field("room_id", &self.room_id)
+            .field("access_token", &self.access_token.as_ref().map(|_| "***"))
+            .field("secret", &self.secret.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTlsMode {
+    /// Plain connection upgraded with STARTTLS (port 587)
+    #[default]
+    Starttls,
+    /// TLS from the first byte (port 465)
+    Tls,
+    /// No encryption (local relays only)
+    None,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub tls: SmtpTlsMode,
+    /// Login for AUTH PLAIN (None = no authentication)
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Envelope and header sender
+    pub from: String,
+    /// Default recipients (rules may override)
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// Connect and per-command timeout in seconds (default 10)
+    #[serde(default = "default_smtp_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_timeout() -> u64 {
+    10
+}
+
+impl fmt::Debug for SmtpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+/// Security event that can trigger an email
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// An IP was banned (auth failures, honeypot, threat intel)
+    Ban,
+    /// A user hit a bandwidth or connection quota
+    QuotaExceeded,
+    /// A user logged in from a country not seen before for them
+    NewCountry,
+    /// Server-wide auth failures crossed `auth_failure_threshold`
+    AuthFailureSpike,
+    /// A login broke the user's habits (`[login_anomaly]`)
+    LoginAnomaly,
+    /// A user presented a new public key awaiting approval (`[key_enrollment]`)
+    KeyEnrollment,
+    /// An authorized key's `expiry-time` is less than 14 days away
+    KeyExpiring,
+}
+
+impl fmt::Display for NotificationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ban => write!(f, "ban"),
+            Self::QuotaExceeded => write!(f, "quota_exceeded"),
+            Self::NewCountry => write!(f, "new_country"),
+            Self::AuthFailureSpike => write!(f, "auth_failure_spike"),
+            Self::LoginAnomaly => write!(f, "login_anomaly"),
+            Self::KeyEnrollment => write!(f, "key_enrollment"),
+            Self::KeyExpiring => write!(f, "key_expiring"),
+        }
+    }
+}
+
+/// A single notification rule
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationRule {
+    pub event: NotificationEvent,
+    /// Users to apply to (empty = all; ignored for `ban`, which has no user)
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Email recipients for this rule (empty = `smtp.to`)
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// Sink names to deliver to, `email` meaning SMTP (empty = `["email"]`)
+    #[serde(default)]
+    pub sinks: Vec<String>,
+}
+
+impl NotificationRule {
+    /// Sinks this rule delivers to, defaulting to email.
+    pub fn sink_names(&self) -> Vec<&str> {
+        if self.sinks.is_empty() {
+            vec![EMAIL_SINK]
+        } else {
+            self.sinks.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LimitsConfig {
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Max concurrent connections per user (0 = unlimited). Default: 0.
+    #[serde(default)]
+    pub max_connections_per_user: u32,
+    #[serde(default = "default_connection_timeout")]
+    pub connection_timeout: u64,
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout: u64,
+    #[serde(default = "default_max_auth_attempts")]
+    pub max_auth_attempts: u32,
+    /// SOCKS5 handshake timeout in seconds (default 30, min 5, max 120).
+    #[serde(default = "default_socks5_handshake_timeout")]
+    pub socks5_handshake_timeout: u64,
+    /// Idle warning: seconds before disconnect to warn user (0 = no warning).
+    #[serde(default)]
+    pub idle_warning_secs: u64,
+    /// Server-wide bandwidth cap in Mbps (0 = unlimited).
+    #[serde(default)]
+    pub max_bandwidth_mbps: u64,
+    /// Server-level max new connections per second (0 = unlimited).
+    #[serde(default)]
+    pub max_new_connections_per_second: u32,
+    /// Server-level max new connections per minute (0 = unlimited).
+    #[serde(default)]
+    pub max_new_connections_per_minute: u32,
+    /// UDP relay idle timeout in seconds (default 300, range 30-3600)
+    #[serde(default = "default_udp_relay_timeout")]
+    pub udp_relay_timeout: u64,
+    /// Maximum concurrent UDP relay sessions per user (0 = unlimited)
+    #[serde(default)]
+    pub max_udp_sessions_per_user: u32,
+    /// Maximum SSH connections that have not completed authentication (0 = unlimited)
+    #[serde(default = "default_max_unauthenticated_connections")]
+    pub max_unauthenticated_connections: usize,
+    /// Maximum unauthenticated SSH connections from one source IP (0 = unlimited)
+    #[serde(default = "default_max_unauthenticated_per_ip")]
+    pub max_unauthenticated_per_ip: usize,
+    /// What gives way once `max_unauthenticated_connections` is reached
+    #[serde(default)]
+    pub overload_policy: OverloadPolicy,
+    /// Bytes a session may hold in memory before it is closed (0 = unlimited)
+    #[serde(default)]
+    pub max_buffered_bytes: u64,
+    /// Bytes one session may relay, both directions together (0 = unlimited)
+    #[serde(default)]
+    pub max_connection_bytes: u64,
+    /// Raise a `session.transfer_alert` past this many bytes uploaded by one session (0 = off)
+    #[serde(default)]
+    pub upload_alert_bytes: u64,
+    /// Same for bytes downloaded by one session (0 = off)
+    #[serde(default)]
+    pub download_alert_bytes: u64,
+    /// Channels one SSH connection may hold open: sessions (shell, exec,
+    /// subsystems) and forwardings together (0 = unlimited)
+    #[serde(default = "default_max_channels_per_connection")]
+    pub max_channels_per_connection: usize,
+    /// JSON file recording when `[quota_plans]` resets were last applied,
+    /// so resets due while the server was down run at startup
+    /// (None = in memory only)
+    #[serde(default)]
+    pub quota_reset_state_path: Option<PathBuf>,
+}
+
+/// Load shedding once the pre-authentication limit is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverloadPolicy {
+    /// Refuse the new connection
+    #[default]
+    RejectNew,
+    /// Close the longest-waiting unauthenticated SSH connection to make room
+    DropOldestPreauth,
+}
+
+impl fmt::Display for OverloadPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverloadPolicy::RejectNew => write!(f, "reject-new"),
+            OverloadPolicy::DropOldestPreauth => write!(f, "drop-oldest-preauth"),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: default_max_connections(),
+            max_connections_per_user: 0,
+            connection_timeout: default_connection_timeout(),
+            idle_timeout: default_idle_timeout(),
+            max_auth_attempts: default_max_auth_attempts(),
+            socks5_handshake_timeout: default_socks5_handshake_timeout(),
+            idle_warning_secs: 0,
+            max_bandwidth_mbps: 0,
+            max_new_connections_per_second: 0,
+            max_new_connections_per_minute: 0,
+            udp_relay_timeout: default_udp_relay_timeout(),
+            max_udp_sessions_per_user: 0,
+            max_unauthenticated_connections: default_max_unauthenticated_connections(),
+            max_unauthenticated_per_ip: default_max_unauthenticated_per_ip(),
+            overload_policy: OverloadPolicy::default(),
+            max_buffered_bytes: 0,
+            max_connection_bytes: 0,
+            upload_alert_bytes: 0,
+            download_alert_bytes: 0,
+            max_channels_per_connection: default_max_channels_per_connection(),
+            quota_reset_state_path: None,
+        }
+    }
+}
+
+fn default_socks5_handshake_timeout() -> u64 {
+    30
+}
+
+fn default_udp_relay_timeout() -> u64 {
+    300
+}
+
+fn default_max_unauthenticated_connections() -> usize {
+    256
+}
+
+fn default_max_unauthenticated_per_ip() -> usize {
+    64
+}
+
+fn default_max_channels_per_connection() -> usize {
+    10
+}
+
+fn default_max_connections() -> u32 {
+    1000
+}
+fn default_connection_timeout() -> u64 {
+    300
+}
+fn default_idle_timeout() -> u64 {
+    0
+}
+fn default_max_auth_attempts() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub allowed_source_ips: Vec<IpNet>,
+    #[serde(default = "default_true")]
+    pub ban_enabled: bool,
+    #[serde(default = "default_ban_threshold")]
+    pub ban_threshold: u32,
+    #[serde(default = "default_ban_window")]
+    pub ban_window: u64,
+    #[serde(default = "default_ban_duration")]
+    pub ban_duration: u64,
+    #[serde(default)]
+    pub ban_whitelist: Vec<String>,
+    /// Lock an account after this many consecutive failed password logins,
+    /// from any source. 0 = disabled.
+    #[serde(default)]
+    pub lock_after_failures: u32,
+    /// How long an account stays locked (seconds)
+    #[serde(default = "default_lock_duration")]
+    pub lock_duration: u64,
+    #[serde(default = "default_true")]
+    pub ip_guard_enabled: bool,
+    #[serde(default)]
+    pub totp_required_for: Vec<String>,
+    /// Maximum new connections per IP per minute (pre-auth). 0 = unlimited.
+    #[serde(default)]
+    pub max_new_connections_per_ip_per_minute: u32,
+    /// IP reputation scoring: enable dynamic scoring
+    #[serde(default)]
+    pub ip_reputation_enabled: bool,
+    /// IP reputation: auto-ban threshold score (0 = disabled)
+    #[serde(default = "default_ip_reputation_threshold")]
+    pub ip_reputation_ban_threshold: u32,
+    /// Trusted CA keys for SSH certificate authentication
+    #[serde(default)]
+    pub trusted_user_ca_keys: Vec<String>,
+    /// Argon2id memory cost in KiB (default 19456 = 19 MiB, OWASP recommendation)
+    #[serde(default = "default_argon2_memory_cost")]
+    pub argon2_memory_cost: u32,
+    /// Argon2id time cost / iterations (default 2)
+    #[serde(default = "default_argon2_time_cost")]
+    pub argon2_time_cost: u32,
+    /// Argon2id parallelism / lanes (default 1)
+    #[serde(default = "default_argon2_parallelism")]
+    pub argon2_parallelism: u32,
+    /// Algorithm for new password hashes (default argon2id)
+    #[serde(default)]
+    pub password_hash_algorithm: PasswordHashAlgorithm,
+    /// After a successful password login, re-hash a stored hash that uses
+    /// another algorithm or other Argon2id parameters (default true)
+    #[serde(default = "default_true")]
+    pub rehash_on_login: bool,
+    /// Rate limiter cleanup interval in seconds (default 60).
+    /// A background task prunes stale entries at this interval.
+    #[serde(default = "default_rate_limit_cleanup_interval")]
+    pub rate_limit_cleanup_interval: u64,
+    /// Maximum number of tracked IPs in the rate limiter (default 100_000).
+    /// When exceeded, oldest entries are evicted.
+    #[serde(default = "default_rate_limit_max_ips")]
+    pub rate_limit_max_ips: usize,
+    /// Maximum number of tracked usernames in the rate limiter (default 10_000).
+    /// When exceeded, oldest entries are evicted.
+    #[serde(default = "default_rate_limit_max_users")]
+    pub rate_limit_max_users: usize,
+    /// Hold banned SSH clients in a tarpit instead of closing them (default false)
+    #[serde(default)]
+    pub tarpit_enabled: bool,
+    /// Maximum concurrently tarpitted sockets; banned clients beyond this are
+    /// closed (default 256)
+    #[serde(default = "default_tarpit_max_connections")]
+    pub tarpit_max_connections: usize,
+    /// Seconds between tarpit lines (default 10)
+    #[serde(default = "default_tarpit_interval")]
+    pub tarpit_interval: u64,
+    /// Seconds before a tarpitted socket is closed; 0 = until the client gives
+    /// up (default 3600)
+    #[serde(default = "default_tarpit_max_duration")]
+    pub tarpit_max_duration: u64,
+    /// Where passwords are verified (default: local `password_hash`)
+    #[serde(default)]
+    pub auth_backend: AuthBackend,
+    /// Command or HTTP endpoint for `auth_backend = "external"`
+    #[serde(default)]
+    pub external_auth: Option<ExternalAuthConfig>,
+    /// Destination ports of SSH hops (`ssh -J`): `direct-tcpip` channels to
+    /// these ports follow the users' `jump_targets` (default [22])
+    #[serde(default = "default_jump_ports")]
+    pub jump_ports: Vec<u16>,
+    /// Destination ports no tunnel may reach, whatever the ACL says (SMTP
+    /// and RDP by default); groups replace the list with `blocked_ports`
+    #[serde(default = "default_blocked_ports")]
+    pub blocked_ports: Vec<u16>,
+    /// Rules for passwords set through the API, the shell `passwd` command
+    /// and `s5 hash-password`
+    #[serde(default)]
+    pub password_policy: PasswordPolicyConfig,
+    /// Ban sources that fail logins as many different usernames
+    #[serde(default)]
+    pub credential_spraying: CredentialSprayingConfig,
+}
+
+/// Password verification backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthBackend {
+    /// Argon2 hashes from `[[users]]`
+    #[default]
+    Local,
+    /// Ask `[security.external_auth]` for every password login
+    External,
+}
+
+/// Password hash format, recognized by its prefix (`$argon2id$`, `$2b$`,
+/// `$scrypt$`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordHashAlgorithm {
+    #[default]
+    Argon2id,
+    Bcrypt,
+    Scrypt,
+}
+
+/// External password check (`[security.external_auth]`). Exactly one of
+/// `command` and `url` must be set.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ExternalAuthConfig {
+    /// Program and arguments, run without a shell; credentials are written
+    /// to its stdin as JSON
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Endpoint that receives the credentials as a JSON POST
+    #[serde(default)]
+    pub url: Option<String>,
+    /// HMAC-SHA256 secret for the `X-Signature-256` header (`url` only)
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Seconds before a pending check counts as a denial (default 5)
+    #[serde(default = "default_external_auth_timeout")]
+    pub timeout_secs: u64,
+    /// Allow `url` to point to private/internal IPs (e.g. a local sidecar)
+    #[serde(default)]
+    pub allow_private_ips: bool,
+}
+
+fn default_external_auth_timeout() -> u64 {
+    5
+}
+
+impl fmt::Debug for ExternalAuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalAuthConfig")
+            .field("command", &self.command)
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(|_| "***"))
+            .field("timeout_secs", &self.timeout_secs)
+            .field("allow_private_ips", &self.allow_private_ips)
+            .finish()
+    }
+}
+
+/// Password policy (`[security.password_policy]`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PasswordPolicyConfig {
+    /// Minimum length in characters (default 8)
+    #[serde(default = "default_password_min_length")]
+    pub min_length: usize,
+    /// How many of lowercase, uppercase, digits and symbols a password must
+    /// mix (1-4, default 1)
+    #[serde(default = "default_password_min_classes")]
+    pub min_classes: u8,
+    /// Refuse passwords from the built-in list of common passwords
+    /// (default true)
+    #[serde(default = "default_true")]
+    pub deny_common: bool,
+    /// Days after which a password counts as expired and is flagged in the
+    /// API and dashboard; 0 = never (default 0)
+    #[serde(default)]
+    pub max_age_days: u32,
+}
+
+fn default_password_min_length() -> usize {
+    8
+}
+
+fn default_password_min_classes() -> u8 {
+    1
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: default_password_min_length(),
+            min_classes: default_password_min_classes(),
+            deny_common: true,
+            max_age_days: 0,
+        }
+    }
+}
+
+/// Credential spraying detection (`[security.credential_spraying]`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CredentialSprayingConfig {
+    /// Detect spraying and ban the source (default true)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Distinct usernames one IP must fail as within `window_secs`
+    /// (default 10)
+    #[serde(default = "default_spraying_min_usernames")]
+    pub min_usernames: u32,
+    /// Window over which usernames are counted, in seconds (default 3600)
+    #[serde(default = "default_spraying_window")]
+    pub window_secs: u64,
+}
+
+fn default_spraying_min_usernames() -> u32 {
+    10
+}
+
+fn default_spraying_window() -> u64 {
+    3600
+}
+
+impl Default for CredentialSprayingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_usernames: default_spraying_min_usernames(),
+            window_secs: default_spraying_window(),
+        }
+    }
+}
+
+fn default_ip_reputation_threshold() -> u32 {
+    100
+}
+
+fn default_argon2_memory_cost() -> u32 {
+    19456
+}
+
+fn default_argon2_time_cost() -> u32 {
+    2
+}
+
+fn default_argon2_parallelism() -> u32 {
+    1
+}
+
+fn default_rate_limit_cleanup_interval() -> u64 {
+    60
+}
+
+fn default_rate_limit_max_ips() -> usize {
+    100_000
+}
+
+fn default_rate_limit_max_users() -> usize {
     10_000
 }
 
+fn default_tarpit_max_connections() -> usize {
+    256
+}
+
+fn default_tarpit_interval() -> u64 {
+    10
+}
+
+fn default_tarpit_max_duration() -> u64 {
+    3600
+}
+
+fn default_jump_ports() -> Vec<u16> {
+    vec![22]
+}
+
+/// SMTP (25, 465, 587) and RDP (3389): the usual sources of abuse reports.
+pub fn default_blocked_ports() -> Vec<u16> {
+    vec![25, 465, 587, 3389]
+}
+
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
@@ -866,6 +2851,8 @@ impl Default for SecurityConfig {
             ban_window: default_ban_window(),
             ban_duration: default_ban_duration(),
             ban_whitelist: Vec::new(),
+            lock_after_failures: 0,
+            lock_duration: default_lock_duration(),
             ip_guard_enabled: true,
             totp_required_for: Vec::new(),
             max_new_connections_per_ip_per_minute: 0,
@@ -875,9 +2862,21 @@ impl Default for SecurityConfig {
             argon2_memory_cost: default_argon2_memory_cost(),
             argon2_time_cost: default_argon2_time_cost(),
             argon2_parallelism: default_argon2_parallelism(),
+            password_hash_algorithm: PasswordHashAlgorithm::default(),
+            rehash_on_login: true,
             rate_limit_cleanup_interval: default_rate_limit_cleanup_interval(),
             rate_limit_max_ips: default_rate_limit_max_ips(),
             rate_limit_max_users: default_rate_limit_max_users(),
+            tarpit_enabled: false,
+            tarpit_max_connections: default_tarpit_max_connections(),
+            tarpit_interval: default_tarpit_interval(),
+            tarpit_max_duration: default_tarpit_max_duration(),
+            auth_backend: AuthBackend::default(),
+            external_auth: None,
+            jump_ports: default_jump_ports(),
+            blocked_ports: default_blocked_ports(),
+            password_policy: PasswordPolicyConfig::default(),
+            credential_spraying: CredentialSprayingConfig::default(),
         }
     }
 }
@@ -894,8 +2893,11 @@ fn default_ban_window() -> u64 {
 fn default_ban_duration() -> u64 {
     900
 }
+fn default_lock_duration() -> u64 {
+    900
+}
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
     pub level: LogLevel,
@@ -909,6 +2911,54 @@ pub struct LoggingConfig {
     /// Enable connection flow logs (detailed per-step timing)
     #[serde(default)]
     pub connection_flow_logs: bool,
+    /// Also send log events to the Windows Event Log (source `s5`)
+    #[serde(default)]
+    pub event_log: bool,
+    /// Chain-hash audit records (`seq` + `prev_hash`) for tamper evidence
+    #[serde(default)]
+    pub audit_chain: bool,
+    /// Seconds between `audit.checkpoint` records (0 = only on shutdown)
+    #[serde(default = "default_audit_checkpoint_interval")]
+    pub audit_checkpoint_interval: u64,
+    /// HMAC key signing audit checkpoints (unsigned if unset)
+    #[serde(default)]
+    pub audit_signing_key: Option<String>,
+    /// Per-connection flow records in SQLite (`[logging.flows]`)
+    #[serde(default)]
+    pub flows: FlowLogConfig,
+    /// IPFIX export of forwarded connections (`[logging.ipfix]`)
+    #[serde(default)]
+    pub ipfix: IpfixConfig,
+    /// Admin-triggered pcapng captures of live sessions (`[logging.capture]`)
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    /// Audit file handling (`[logging.audit]`)
+    #[serde(default)]
+    pub audit: AuditLogConfig,
+}
+
+impl fmt::Debug for LoggingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoggingConfig")
+            .field("level", &self.level)
+            .field("format", &self.format)
+            .field("audit_log_path", &self.audit_log_path)
+            .field("audit_max_size_mb", &self.audit_max_size_mb)
+            .field("audit_max_files", &self.audit_max_files)
+            .field("connection_flow_logs", &self.connection_flow_logs)
+            .field("event_log", &self.event_log)
+            .field("audit_chain", &self.audit_chain)
+            .field("audit_checkpoint_interval", &self.audit_checkpoint_interval)
+            .field(
+                "audit_signing_key",
+                &self.audit_signing_key.as_ref().map(|_| "***"),
+            )
+            .field("flows", &self.flows)
+            .field("ipfix", &self.ipfix)
+            .field("capture", &self.capture)
+            .field("audit", &self.audit)
+            .finish()
+    }
 }
 
 impl Default for LoggingConfig {
@@ -920,6 +2970,14 @@ impl Default for LoggingConfig {
             audit_max_size_mb: default_audit_max_size_mb(),
             audit_max_files: default_audit_max_files(),
             connection_flow_logs: false,
+            event_log: false,
+            audit_chain: false,
+            audit_checkpoint_interval: default_audit_checkpoint_interval(),
+            audit_signing_key: None,
+            flows: FlowLogConfig::default(),
+            ipfix: IpfixConfig::default(),
+            capture: CaptureConfig::default(),
+            audit: AuditLogConfig::default(),
         }
     }
 }
@@ -940,6 +2998,233 @@ fn default_audit_max_files() -> u32 {
     5
 }
 
+fn default_audit_checkpoint_interval() -> u64 {
+    300
+}
+
+/// Connection flow export to SQLite
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlowLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// SQLite database file (created if missing)
+    #[serde(default = "default_flows_path")]
+    pub path: PathBuf,
+    /// Days to keep flow records (0 = keep forever)
+    #[serde(default = "default_flows_retention_days")]
+    pub retention_days: u32,
+    /// Record the first line of plaintext streams (HTTP request line, SMTP
+    /// `EHLO`); groups override it with `payload_metadata`
+    #[serde(default)]
+    pub payload_metadata: bool,
+    /// Longest first line kept, in bytes
+    #[serde(default = "default_flows_payload_metadata_max_len")]
+    pub payload_metadata_max_len: usize,
+}
+
+fn default_flows_path() -> PathBuf {
+    PathBuf::from("flows.db")
+}
+
+fn default_flows_retention_days() -> u32 {
+    30
+}
+
+/// Upper bound of `logging.flows.payload_metadata_max_len`.
+pub const MAX_PAYLOAD_METADATA_LEN: usize = 1024;
+
+fn default_flows_payload_metadata_max_len() -> usize {
+    256
+}
+
+impl Default for FlowLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_flows_path(),
+            retention_days: default_flows_retention_days(),
+            payload_metadata: false,
+            payload_metadata_max_len: default_flows_payload_metadata_max_len(),
+        }
+    }
+}
+
+/// Audit file handling (`[logging.audit]`)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AuditLogConfig {
+    /// Upload of rotated audit files to object storage (`[logging.audit.archive]`)
+    #[serde(default)]
+    pub archive: AuditArchiveConfig,
+}
+
+/// Upload of rotated audit files to an S3-compatible bucket
+/// (`[logging.audit.archive]`)
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AuditArchiveConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Service URL, e.g. `https://s3.eu-west-3.amazonaws.com` or
+    /// `http://minio:9000`
+    #[serde(default)]
+    pub endpoint: String,
+    #[serde(default)]
+    pub bucket: String,
+    /// Region used to sign requests (default "us-east-1")
+    #[serde(default = "default_audit_archive_region")]
+    pub region: String,
+    /// Prepended to object names (default "audit/")
+    #[serde(default = "default_audit_archive_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub access_key_id: String,
+    #[serde(default)]
+    pub secret_access_key: String,
+    /// Address the bucket as `endpoint/bucket/...` instead of
+    /// `bucket.endpoint/...` (default true, as most S3-compatible stores expect)
+    #[serde(default = "default_true")]
+    pub path_style: bool,
+    /// Seconds before a failed upload is retried (default 60)
+    #[serde(default = "default_audit_archive_retry_interval")]
+    pub retry_interval_secs: u64,
+    /// Seconds an upload may take (default 300)
+    #[serde(default = "default_audit_archive_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_audit_archive_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_audit_archive_prefix() -> String {
+    "audit/".to_string()
+}
+
+fn default_audit_archive_retry_interval() -> u64 {
+    60
+}
+
+fn default_audit_archive_timeout() -> u64 {
+    300
+}
+
+impl Default for AuditArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: default_audit_archive_region(),
+            prefix: default_audit_archive_prefix(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            path_style: true,
+            retry_interval_secs: default_audit_archive_retry_interval(),
+            timeout_secs: default_audit_archive_timeout(),
+        }
+    }
+}
+
+impl fmt::Debug for AuditArchiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditArchiveConfig")
+            .field("enabled", &self.enabled)
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"***")
+            .field("path_style", &self.path_style)
+            .field("retry_interval_secs", &self.retry_interval_secs)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+/// IPFIX (NetFlow v10) flow export over UDP
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IpfixConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Collector address as `host:port` (usually port 4739)
+    #[serde(default)]
+    pub collector: String,
+    #[serde(default = "default_ipfix_observation_domain_id")]
+    pub observation_domain_id: u32,
+    /// Private enterprise number of the username and other s5-specific fields
+    #[serde(default = "default_ipfix_enterprise_number")]
+    pub enterprise_number: u32,
+    /// Seconds between template retransmissions
+    #[serde(default = "default_ipfix_template_refresh")]
+    pub template_refresh_secs: u64,
+}
+
+fn default_ipfix_observation_domain_id() -> u32 {
+    1
+}
+
+/// 32473 is reserved for documentation (RFC 5612); set your own PEN in production.
+fn default_ipfix_enterprise_number() -> u32 {
+    32473
+}
+
+fn default_ipfix_template_refresh() -> u64 {
+    600
+}
+
+impl Default for IpfixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            collector: String::new(),
+            observation_domain_id: default_ipfix_observation_domain_id(),
+            enterprise_number: default_ipfix_enterprise_number(),
+            template_refresh_secs: default_ipfix_template_refresh(),
+        }
+    }
+}
+
+/// Per-session traffic capture to pcapng files, started from the API
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaptureConfig {
+    /// Allow `POST /api/sessions/{id}/capture` (off by default: captures hold
+    /// decrypted traffic)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory the capture files are written to (created if missing)
+    #[serde(default = "default_capture_directory")]
+    pub directory: PathBuf,
+    /// Longest capture an admin may request, in seconds
+    #[serde(default = "default_capture_max_duration")]
+    pub max_duration_secs: u64,
+    /// Size at which a capture file is closed, in MiB
+    #[serde(default = "default_capture_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+fn default_capture_directory() -> PathBuf {
+    PathBuf::from("captures")
+}
+
+fn default_capture_max_duration() -> u64 {
+    300
+}
+
+fn default_capture_max_size_mb() -> u64 {
+    100
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: default_capture_directory(),
+            max_duration_secs: default_capture_max_duration(),
+            max_size_mb: default_capture_max_size_mb(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MetricsConfig {
     #[serde(default)]
@@ -949,6 +3234,10 @@ pub struct MetricsConfig {
     /// Maximum distinct label values before aggregating under "_other" (default 100).
     #[serde(default = "default_max_metric_labels")]
     pub max_metric_labels: u32,
+    /// Label per-user metrics with the username (opt-in). When false, all
+    /// users are aggregated under a single "_all" label.
+    #[serde(default)]
+    pub user_labels: bool,
 }
 
 impl Default for MetricsConfig {
@@ -957,6 +3246,7 @@ impl Default for MetricsConfig {
             enabled: false,
             listen: default_metrics_listen(),
             max_metric_labels: default_max_metric_labels(),
+            user_labels: false,
         }
     }
 }
@@ -975,8 +3265,105 @@ pub struct ApiConfig {
     pub enabled: bool,
     #[serde(default = "default_api_listen")]
     pub listen: String,
+    /// Serve the API on this Unix socket instead of `listen` (no TCP port).
+    #[serde(default)]
+    pub unix_socket: Option<PathBuf>,
+    /// Octal file mode applied to `unix_socket`, e.g. "0660".
+    #[serde(default = "default_api_unix_socket_mode")]
+    pub unix_socket_mode: String,
     #[serde(default)]
     pub token: String,
+    /// Requests per minute allowed from one IP on `/api/*` (0 = unlimited).
+    #[serde(default = "default_api_rate_limit_per_minute")]
+    pub rate_limit_per_minute: u32,
+    /// Base delay before answering an invalid token; doubles with each
+    /// consecutive failure from the same IP, capped at 5s (0 = no delay).
+    #[serde(default = "default_api_auth_failure_delay_ms")]
+    pub auth_failure_delay_ms: u64,
+    /// Origins (`scheme://host[:port]`) allowed to call the API cross-origin.
+    /// Empty = same-origin only.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Dashboard login sessions expire after this many idle seconds.
+    #[serde(default = "default_api_session_idle_timeout")]
+    pub session_idle_timeout: u64,
+    /// Dashboard accounts (separate from SSH users), each with a role.
+    #[serde(default)]
+    pub accounts: Vec<DashboardAccount>,
+    /// Applied configurations kept for `/api/config/rollback` (0 = none).
+    #[serde(default = "default_api_config_history")]
+    pub config_history: usize,
+    /// Accept SSH tunneled over WebSocket on `GET /ssh`.
+    #[serde(default)]
+    pub ssh_websocket: bool,
+    /// Reverse proxies in front of the API: the client address of a `/ssh`
+    /// tunnel from one of them is read from `X-Forwarded-For`.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+/// Dashboard/API role, ordered by privilege (viewer < operator < admin).
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DashboardRole {
+    /// Read-only: status, users, connections, bans, quotas, live stream.
+    #[default]
+    Viewer,
+    /// Day-to-day actions: kick sessions, lift bans, toggle maintenance, broadcast, reset quotas.
+    Operator,
+    /// Everything, including config reload and state restore.
+    Admin,
+}
+
+impl DashboardRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "viewer" => Some(Self::Viewer),
+            "operator" => Some(Self::Operator),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DashboardRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A dashboard login account (`[[api.accounts]]`).
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DashboardAccount {
+    pub username: String,
+    /// Argon2id hash (`s5 hash-password`).
+    pub password_hash: String,
+    #[serde(default)]
+    pub role: DashboardRole,
+    /// `[[tenants]]` entry the account is limited to (None = whole server)
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+impl fmt::Debug for DashboardAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DashboardAccount")
+            .field("username", &self.username)
+            .field("password_hash", &"***")
+            .field("role", &self.role)
+            .field("tenant", &self.tenant)
+            .finish()
+    }
 }
 
 impl fmt::Debug for ApiConfig {
@@ -984,6 +3371,8 @@ impl fmt::Debug for ApiConfig {
         f.debug_struct("ApiConfig")
             .field("enabled", &self.enabled)
             .field("listen", &self.listen)
+            .field("unix_socket", &self.unix_socket)
+            .field("unix_socket_mode", &self.unix_socket_mode)
             .field(
                 "token",
                 &if self.token.is_empty() {
@@ -992,6 +3381,14 @@ impl fmt::Debug for ApiConfig {
                     "***"
                 },
             )
+            .field("rate_limit_per_minute", &self.rate_limit_per_minute)
+            .field("auth_failure_delay_ms", &self.auth_failure_delay_ms)
+            .field("allowed_origins", &self.allowed_origins)
+            .field("session_idle_timeout", &self.session_idle_timeout)
+            .field("accounts", &self.accounts)
+            .field("config_history", &self.config_history)
+            .field("ssh_websocket", &self.ssh_websocket)
+            .field("trusted_proxies", &self.trusted_proxies)
             .finish()
     }
 }
@@ -1001,7 +3398,17 @@ impl Default for ApiConfig {
         Self {
             enabled: false,
             listen: default_api_listen(),
+            unix_socket: None,
+            unix_socket_mode: default_api_unix_socket_mode(),
             token: String::new(),
+            rate_limit_per_minute: default_api_rate_limit_per_minute(),
+            auth_failure_delay_ms: default_api_auth_failure_delay_ms(),
+            allowed_origins: Vec::new(),
+            session_idle_timeout: default_api_session_idle_timeout(),
+            accounts: Vec::new(),
+            config_history: default_api_config_history(),
+            ssh_websocket: false,
+            trusted_proxies: Vec::new(),
         }
     }
 }
@@ -1010,12 +3417,46 @@ fn default_api_listen() -> String {
     "127.0.0.1:9091".to_string()
 }
 
+fn default_api_unix_socket_mode() -> String {
+    "0660".to_string()
+}
+
+impl ApiConfig {
+    /// `unix_socket_mode` as permission bits, if it is valid octal up to 0777.
+    pub fn unix_socket_mode_bits(&self) -> Option<u32> {
+        let mode = &self.unix_socket_mode;
+        let digits = mode.strip_prefix("0o").unwrap_or(mode);
+        u32::from_str_radix(digits, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o777)
+    }
+}
+
+fn default_api_rate_limit_per_minute() -> u32 {
+    600
+}
+
+fn default_api_auth_failure_delay_ms() -> u64 {
+    250
+}
+
+fn default_api_session_idle_timeout() -> u64 {
+    1800
+}
+
+fn default_api_config_history() -> usize {
+    10
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct GeoIpConfig {
     #[serde(default)]
     pub enabled: bool,
     #[serde(default)]
     pub database_path: Option<PathBuf>,
+    /// GeoLite2-ASN (or compatible) database, used by `[login_anomaly]`
+    #[serde(default)]
+    pub asn_database_path: Option<PathBuf>,
     #[serde(default)]
     pub allowed_countries: Vec<String>,
     #[serde(default)]
@@ -1140,8 +3581,21 @@ pub struct UserConfig {
     pub password_hash: Option<String>,
     #[serde(default)]
     pub authorized_keys: Vec<String>,
-    #[serde(default = "default_true")]
-    pub allow_forwarding: bool,
+    /// URL publishing more authorized_keys lines, e.g.
+    /// "https://github.com/<user>.keys" (`[authorized_keys_fetch]`)
+    #[serde(default)]
+    pub authorized_keys_url: Option<String>,
+    /// Public key logins only with FIDO2 security keys (`sk-ssh-ed25519`,
+    /// `sk-ecdsa-sha2-nistp256`)
+    #[serde(default)]
+    pub require_security_key: bool,
+    /// Labels matched by `[[proxy.priority.classes]]` `tags` and `[policy]`
+    /// `user:tag(..)` rules (besides the group's `tags`)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Forwarding permission (overrides group; `None` = group value, else `true`)
+    #[serde(default)]
+    pub allow_forwarding: Option<bool>,
     #[serde(default = "default_true")]
     pub allow_shell: bool,
     #[serde(default)]
@@ -1150,7 +3604,42 @@ pub struct UserConfig {
     pub max_bandwidth_kbps: u64,
     #[serde(default)]
     pub source_ips: Vec<IpNet>,
+    /// Private/reserved destination ranges reachable despite ip_guard
+    /// (replaces the group's list when set)
+    #[serde(default)]
+    pub ip_guard_exemptions: Option<Vec<IpNet>>,
+    /// Hosts reachable on a jump port (`ssh -J`), in ACL rule syntax
+    /// (replaces the group's list when set)
+    #[serde(default)]
+    pub jump_targets: Option<Vec<String>>,
+    /// Unix socket paths on the server this user may forward to
+    /// (replaces the group's list when set)
+    #[serde(default)]
+    pub unix_sockets: Option<Vec<String>>,
+    /// HASSH fingerprints of the SSH clients this user may log in with
+    /// (replaces the group's list when set)
+    #[serde(default)]
+    pub allowed_hassh: Option<Vec<String>>,
+    /// `[[subsystems]]` this user may open (replaces the group's list when set)
+    #[serde(default)]
+    pub subsystems: Option<Vec<String>>,
+    /// Layer-3 tunnels (`ssh -w`) when `[vpn]` is enabled
+    #[serde(default)]
+    pub allow_vpn: bool,
+    /// Tunnel address of this user in `vpn.pool` (default: the first free one)
+    #[serde(default)]
+    pub vpn_address: Option<Ipv4Addr>,
+    /// Per-user `[impossible_travel]` action ("off", "flag" or "block")
+    #[serde(default)]
+    pub impossible_travel: Option<ImpossibleTravelAction>,
     pub expires_at: Option<String>,
+    /// Refuse every login, e.g. set by a group manager; kept in the config
+    #[serde(default)]
+    pub disabled: bool,
+    /// When the password was last set (RFC 3339), for
+    /// `security.password_policy.max_age_days`. Written on self-service changes.
+    #[serde(default)]
+    pub password_changed_at: Option<String>,
     pub upstream_proxy: Option<String>,
     #[serde(default)]
     pub acl: UserAclConfig,
@@ -1168,12 +3657,18 @@ pub struct UserConfig {
     /// Shell command permissions (overrides group/global)
     #[serde(default)]
     pub shell_permissions: Option<ShellPermissions>,
+    /// Session environment and PTY policy (merged over the group's, field by field)
+    #[serde(default)]
+    pub terminal: Option<TerminalConfig>,
     /// Per-user MOTD override
     #[serde(default)]
     pub motd: Option<MotdConfig>,
     /// Per-user quotas
     #[serde(default)]
     pub quotas: Option<QuotaConfig>,
+    /// Name of a `[quota_plans]` entry (instead of `quotas`)
+    #[serde(default)]
+    pub quota_plan: Option<String>,
     /// Time-based access restrictions
     #[serde(default)]
     pub time_access: Option<TimeAccessConfig>,
@@ -1201,6 +3696,41 @@ pub struct UserConfig {
     /// Multi-window rate limits for new connections (overrides group/server defaults)
     #[serde(default)]
     pub rate_limits: Option<RateLimitsConfig>,
+    /// Argon2 hash of a personal API token for the self-service endpoints
+    /// (`/api/self/*`), generated with `s5 hash-password`.
+    #[serde(default)]
+    pub api_token_hash: Option<String>,
+    /// Read-only personal API tokens the user created on the self-service
+    /// page (`/dashboard/me`)
+    #[serde(default)]
+    pub api_tokens: Vec<PersonalTokenConfig>,
+}
+
+/// Read-only personal API token (`[[users]] api_tokens`): lets scripts read
+/// the user's own quota and sessions. Only the SHA-256 of the secret is kept.
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PersonalTokenConfig {
+    pub id: String,
+    /// Label chosen by the user
+    #[serde(default)]
+    pub name: String,
+    /// Hex SHA-256 of the token
+    pub sha256: String,
+    /// RFC 3339
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl fmt::Debug for PersonalTokenConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersonalTokenConfig")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("sha256", &"***")
+            .field("created_at", &self.created_at)
+            .finish()
+    }
 }
 
 impl fmt::Debug for UserConfig {
@@ -1212,6 +3742,9 @@ impl fmt::Debug for UserConfig {
                 "authorized_keys",
                 &format!("[{} keys]", self.authorized_keys.len()),
             )
+            .field("authorized_keys_url", &self.authorized_keys_url)
+            .field("require_security_key", &self.require_security_key)
+            .field("tags", &self.tags)
             .field("allow_forwarding", &self.allow_forwarding)
             .field("allow_shell", &self.allow_shell)
             .field(
@@ -1220,11 +3753,27 @@ impl fmt::Debug for UserConfig {
             )
             .field("max_bandwidth_kbps", &self.max_bandwidth_kbps)
             .field("source_ips", &self.source_ips)
+            .field("ip_guard_exemptions", &self.ip_guard_exemptions)
+            .field("jump_targets", &self.jump_targets)
+            .field("unix_sockets", &self.unix_sockets)
+            .field("allowed_hassh", &self.allowed_hassh)
+            .field("subsystems", &self.subsystems)
+            .field("allow_vpn", &self.allow_vpn)
+            .field("vpn_address", &self.vpn_address)
+            .field("impossible_travel", &self.impossible_travel)
             .field("expires_at", &self.expires_at)
+            .field("disabled", &self.disabled)
+            .field("password_changed_at", &self.password_changed_at)
             .field("upstream_proxy", &self.upstream_proxy)
             .field("acl", &self.acl)
             .field("group", &self.group)
+            .field("quota_plan", &self.quota_plan)
             .field("role", &self.role)
+            .field(
+                "api_token_hash",
+                &self.api_token_hash.as_ref().map(|_| "***"),
+            )
+            .field("api_tokens", &self.api_tokens)
             .finish()
     }
 }
@@ -1239,6 +3788,9 @@ pub struct GlobalAclConfig {
     pub allow: Vec<String>,
     #[serde(default)]
     pub deny: Vec<String>,
+    /// Also check the server name sent inside each tunnel (TLS SNI, HTTP `Host`)
+    #[serde(default)]
+    pub inspect_server_names: bool,
 }
 
 impl Default for GlobalAclConfig {
@@ -1247,6 +3799,7 @@ impl Default for GlobalAclConfig {
             default_policy: default_acl_policy(),
             allow: Vec::new(),
             deny: Vec::new(),
+            inspect_server_names: false,
         }
     }
 }
@@ -1,15 +1,27 @@
 use crate::alerting::AlertEngine;
+use crate::audit::events::AuditEvent;
 use crate::audit::AuditLogger;
+use crate::auth::tenant::tenant_of;
 use crate::auth::AuthService;
-use crate::config::types::AppConfig;
+use crate::config::types::{AppConfig, ImpossibleTravelAction, UserRole};
+use crate::flows::ipfix::IpfixExporter;
+use crate::flows::{FlowLog, FlowRecord};
+use crate::metrics::collectors::ProtocolLabel;
 use crate::metrics::MetricsRegistry;
 use crate::proxy::ProxyEngine;
 use crate::quota::QuotaTracker;
+#[cfg(feature = "lua-hooks")]
+use crate::scripting::{Hook, Outcome};
+use crate::security::normalize::normalize_ip;
 use crate::security::SecurityManager;
 use crate::webhooks::WebhookDispatcher;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::RwLock;
+use tracing::warn;
 
 /// Shared application context, replacing scattered Arc parameters
 pub struct AppContext {
@@ -22,5 +34,365 @@ pub struct AppContext {
     pub quota_tracker: Arc<QuotaTracker>,
     pub webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
     pub alert_engine: Option<Arc<AlertEngine>>,
+    /// Per-connection flow export (`[logging.flows]`), if enabled
+    pub flow_log: Option<Arc<FlowLog>>,
+    /// IPFIX flow exporter (`[logging.ipfix]`), if enabled
+    pub ipfix: Option<Arc<IpfixExporter>>,
+    /// Maintenance mode flag, shared with the API, the maintenance window
+    /// scheduler and SIGUSR1
+    pub maintenance: Arc<AtomicBool>,
     pub start_time: Instant,
+    /// Path of the loaded config file, used to persist runtime changes
+    /// (e.g. self-service password rotation). `None` in env-var/demo mode.
+    pub config_path: Option<PathBuf>,
+}
+
+impl AppContext {
+    /// True if finished connections are recorded anywhere (SQLite or IPFIX).
+    pub fn records_flows(&self) -> bool {
+        self.flow_log.is_some() || self.ipfix.is_some()
+    }
+
+    /// Hand a finished connection to the flow store and the IPFIX exporter.
+    pub fn record_flow(&self, flow: FlowRecord) {
+        if let Some(ref exporter) = self.ipfix {
+            exporter.record(&flow);
+        }
+        if let Some(ref log) = self.flow_log {
+            log.record(flow);
+        }
+    }
+
+    /// While maintenance mode is on, the message a new SSH login of
+    /// `username` is refused with: the active `[[maintenance_windows]]`
+    /// entry's, else `server.maintenance_message`. Admins (`role = "admin"`)
+    /// are let in so they can work on the server.
+    pub async fn maintenance_rejection(&self, username: &str) -> Option<String> {
+        if !self.maintenance.load(Ordering::Relaxed) {
+            return None;
+        }
+        let admin = self
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(username)
+            .is_some_and(|u| u.role == UserRole::Admin);
+        if admin {
+            return None;
+        }
+        let now = chrono::Utc::now();
+        let message = self
+            .config
+            .maintenance_windows
+            .iter()
+            .find(|w| w.is_active(&now))
+            .map_or(&self.config.server.maintenance_message, |w| &w.message);
+        Some(message.clone())
+    }
+
+    /// A decoy login was used: ban the source (if configured), raise a
+    /// `honeypot.triggered` audit event and count it. The caller then serves
+    /// a sandboxed session.
+    pub async fn honeypot_triggered(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+    ) {
+        let ban = {
+            let security = self.security.read().await;
+            let ban = security.honeypot().ban_duration();
+            if let Some(duration) = ban {
+                let ip = normalize_ip(source.ip());
+                security.ban_manager().ban(ip, duration);
+                self.audit.log_ban_created(&ip, duration.as_secs());
+            }
+            ban
+        };
+        warn!(
+            conn_id = %cid,
+            user = %username,
+            ip = %source.ip(),
+            protocol = %protocol,
+            banned = ban.is_some(),
+            "Honeypot credential used"
+        );
+        self.audit.log_honeypot_triggered(
+            username,
+            source,
+            protocol,
+            cid,
+            ban.map(|d| d.as_secs()),
+        );
+        self.metrics
+            .honeypot_triggers_total
+            .get_or_create(&ProtocolLabel {
+                protocol: protocol.to_string(),
+            })
+            .inc();
+    }
+
+    /// Record a failed login as `username` from `source`: counts towards the
+    /// auto-ban of the source for the user's tenant, and towards credential
+    /// spraying detection (`[security.credential_spraying]`), which bans the
+    /// source server-wide once it has failed as enough distinct usernames.
+    pub async fn record_login_failure(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+    ) {
+        let (usernames, window, ban) = {
+            let security = self.security.read().await;
+            let tenant = tenant_of(&self.config.tenants, username);
+            security.record_auth_failure(&source.ip(), tenant);
+            let Some(usernames) = security.record_username_failure(&source.ip(), username) else {
+                return;
+            };
+            let bans = security.ban_manager();
+            let ban = bans.is_enabled().then(|| bans.ban_duration());
+            if let Some(duration) = ban {
+                let ip = normalize_ip(source.ip());
+                bans.ban(ip, duration);
+                self.audit.log_ban_created(&ip, duration.as_secs());
+            }
+            (usernames, security.spraying().window(), ban)
+        };
+        warn!(
+            conn_id = %cid,
+            ip = %source.ip(),
+            protocol = %protocol,
+            usernames = usernames.len(),
+            banned = ban.is_some(),
+            "Credential spraying detected"
+        );
+        self.audit.log_credential_spraying(
+            source,
+            protocol,
+            cid,
+            usernames,
+            window.as_secs(),
+            ban.map(|d| d.as_secs()),
+        );
+        self.metrics
+            .credential_spraying_detected_total
+            .get_or_create(&ProtocolLabel {
+                protocol: protocol.to_string(),
+            })
+            .inc();
+    }
+
+    /// Count a failed password login against `username`'s account
+    /// (`security.lock_after_failures`), whatever the source, and lock the
+    /// account once the threshold is reached.
+    pub async fn record_account_failure(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+    ) {
+        let (failures, duration) = {
+            let security = self.security.read().await;
+            let lockout = security.account_lockout();
+            let Some(failures) = lockout.record_failure(username) else {
+                return;
+            };
+            (failures, lockout.lock_duration())
+        };
+        warn!(
+            conn_id = %cid,
+            user = %username,
+            ip = %source.ip(),
+            protocol = %protocol,
+            failures = failures,
+            lock_secs = duration.as_secs(),
+            "Account locked after repeated failed logins"
+        );
+        self.audit.log_account_locked(
+            username,
+            source,
+            protocol,
+            cid,
+            failures,
+            duration.as_secs(),
+        );
+        self.metrics.account_lockouts_total.inc();
+    }
+
+    /// Whether `username` is locked out (`security.lock_after_failures`).
+    /// Called once the credentials checked out: a locked account refuses the
+    /// login, which the caller then records as a failure.
+    pub async fn is_account_locked(&self, username: &str, source: &SocketAddr, cid: &str) -> bool {
+        let locked = self
+            .security
+            .read()
+            .await
+            .account_lockout()
+            .is_locked(username);
+        if locked {
+            warn!(
+                conn_id = %cid,
+                user = %username,
+                ip = %source.ip(),
+                "Login refused: account locked"
+            );
+        }
+        locked
+    }
+
+    /// Run the `on_auth` hook (`[scripting]`) on a login whose credentials
+    /// checked out, given its `auth.success` event. Returns the event with
+    /// the hook's labels, or None when the hook refuses the login (or
+    /// fails), which the caller then records as a failure.
+    #[cfg(feature = "lua-hooks")]
+    pub async fn run_auth_hook(&self, event: AuditEvent) -> Option<AuditEvent> {
+        let Some(hooks) = self.proxy_engine.hooks() else {
+            return Some(event);
+        };
+        let reason = match hooks.run(Hook::Auth, &event).await {
+            Ok(Outcome::Continue(labels)) => return Some(event.with_labels(labels)),
+            Ok(Outcome::Refuse(reason)) => reason.unwrap_or_default(),
+            Err(e) => format!("hook error: {}", e),
+        };
+        if let AuditEvent::AuthSuccess {
+            username,
+            source_ip,
+            correlation_id,
+            ..
+        } = &event
+        {
+            warn!(
+                conn_id = correlation_id.as_deref().unwrap_or("-"),
+                user = %username,
+                ip = %source_ip,
+                reason = %reason,
+                "Login refused by the on_auth hook"
+            );
+        }
+        None
+    }
+
+    /// Without the `lua-hooks` feature there is no hook: the login goes on.
+    #[cfg(not(feature = "lua-hooks"))]
+    pub async fn run_auth_hook(&self, event: AuditEvent) -> Option<AuditEvent> {
+        Some(event)
+    }
+
+    /// Compare a login whose credentials checked out with the user's recent
+    /// logins (`[impossible_travel]`) and raise an `auth.impossible_travel`
+    /// audit event when it is too far away. Returns false when the login
+    /// must be refused (action `block`, for the user or by default), which
+    /// the caller then records as a failure.
+    pub async fn check_impossible_travel(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+    ) -> bool {
+        let user_action = self
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(username)
+            .and_then(|u| u.impossible_travel);
+        let (travel, block) = {
+            let security = self.security.read().await;
+            let Some(detector) = security.impossible_travel() else {
+                return true;
+            };
+            let action = user_action.unwrap_or(detector.action());
+            if action == ImpossibleTravelAction::Off {
+                return true;
+            }
+            let block = action == ImpossibleTravelAction::Block;
+            (detector.check(username, &source.ip(), block), block)
+        };
+        let Some(travel) = travel else {
+            return true;
+        };
+        warn!(
+            conn_id = %cid,
+            user = %username,
+            ip = %source.ip(),
+            protocol = %protocol,
+            previous_ip = %travel.previous_ip,
+            distance_km = travel.distance_km,
+            elapsed_secs = travel.elapsed_secs,
+            blocked = block,
+            "Impossible travel between logins"
+        );
+        self.audit
+            .log_impossible_travel(username, source, protocol, cid, &travel, block);
+        self.metrics
+            .impossible_travel_detected_total
+            .get_or_create(&ProtocolLabel {
+                protocol: protocol.to_string(),
+            })
+            .inc();
+        !block
+    }
+
+    /// Compare a successful login with the user's history
+    /// (`[login_anomaly]`) and raise an `auth.anomaly` audit event when it
+    /// breaks their habits. The login proceeds either way.
+    pub async fn check_login_anomaly(
+        &self,
+        username: &str,
+        source: &SocketAddr,
+        protocol: &str,
+        cid: &str,
+    ) {
+        let anomaly = {
+            let security = self.security.read().await;
+            let Some(detector) = security.login_anomaly() else {
+                return;
+            };
+            detector.check(username, &source.ip(), chrono::Utc::now())
+        };
+        let Some(anomaly) = anomaly else {
+            return;
+        };
+        warn!(
+            conn_id = %cid,
+            user = %username,
+            ip = %source.ip(),
+            protocol = %protocol,
+            reasons = %anomaly.reasons.join(","),
+            "Unusual login for user"
+        );
+        self.audit
+            .log_login_anomaly(username, source, protocol, cid, &anomaly);
+    }
+
+    /// Upgrade `username`'s outdated password hash in the background after a
+    /// successful login with `password` (`security.rehash_on_login`). The
+    /// caller checks [`AuthService::needs_rehash`] first.
+    pub fn spawn_password_rehash(&self, username: &str, password: &str) {
+        let auth = self.auth_service.clone();
+        let config_path = self.config_path.clone();
+        let username = username.to_string();
+        let password = zeroize::Zeroizing::new(password.to_string());
+        tokio::spawn(async move {
+            let result = crate::auth::rehash::upgrade_password_hash(
+                &auth,
+                config_path.as_deref(),
+                &username,
+                &password,
+            )
+            .await;
+            if let Err(e) = result {
+                warn!(
+                    user = %username,
+                    error = %format!("{e:#}"),
+                    "Failed to upgrade password hash"
+                );
+            }
+        });
+    }
 }
@@ -0,0 +1,401 @@
+//! `s5 ctl`: operator commands against a running server's management API.
+//!
+//! The API address and token come from `--api-addr`/`--token` (or
+//! `S5_API_ADDR`/`S5_API_TOKEN`) and fall back to the `[api]` section of the
+//! config file, so on the server host `s5 ctl status` needs no arguments.
+
+use crate::cli::CtlCommand;
+use crate::config::types::ApiConfig;
+use crate::utils::format_bytes_used;
+use anyhow::{bail, Context, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Where the API is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiTarget {
+    /// Base URL, without a trailing slash
+    Http(String),
+    /// Unix socket path (`api.unix_socket`)
+    Unix(PathBuf),
+}
+
+impl ApiTarget {
+    /// Parse `--api-addr`: `unix:/path`, `http(s)://host:port` or a bare `host:port`.
+    pub fn parse(addr: &str) -> Result<Self> {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            if path.is_empty() {
+                bail!("API address 'unix:' needs a socket path");
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        let url = if addr.contains("://") {
+            addr.to_string()
+        } else {
+            format!("http://{}", addr)
+        };
+        let parsed =
+            url::Url::parse(&url).with_context(|| format!("invalid API address '{}'", addr))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("API address '{}' must be http(s):// or unix:", addr);
+        }
+        Ok(Self::Http(url.trim_end_matches('/').to_string()))
+    }
+
+    /// How a client on the server host reaches `[api]`: the Unix socket when
+    /// set, otherwise `listen` with a wildcard host replaced by loopback.
+    pub fn from_config(api: &ApiConfig) -> Self {
+        if let Some(path) = &api.unix_socket {
+            return Self::Unix(path.clone());
+        }
+        let listen = match api.listen.rsplit_once(':') {
+            Some(("0.0.0.0", port)) => format!("127.0.0.1:{}", port),
+            Some(("[::]", port)) => format!("[::1]:{}", port),
+            _ => api.listen.clone(),
+        };
+        Self::Http(format!("http://{}", listen))
+    }
+}
+
+/// Bearer-authenticated client for the JSON API.
+pub struct CtlClient {
+    target: ApiTarget,
+    token: String,
+}
+
+impl CtlClient {
+    pub fn new(target: ApiTarget, token: impl Into<String>) -> Self {
+        Self {
+            target,
+            token: token.into(),
+        }
+    }
+
+    /// Send a request and return the `data` of the response envelope, or the
+    /// API's error message.
+    pub async fn call(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value> {
+        let (status, text) = match &self.target {
+            ApiTarget::Http(base) => self.call_http(base, method, path, body).await?,
+            ApiTarget::Unix(socket) => self.call_unix(socket, method, path, body).await?,
+        };
+        let envelope: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+        if !(200..300).contains(&status) {
+            let message = envelope
+                .get("error")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| text.trim().to_string());
+            bail!("HTTP {}: {}", status, message);
+        }
+        Ok(envelope.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn call_http(
+        &self,
+        base: &str,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<(u16, String)> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let mut req = reqwest::Client::new()
+            .request(method, format!("{}{}", base, path))
+            .bearer_auth(&self.token);
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let resp = req
+            .send()
+            .await
+            .with_context(|| format!("connecting to {}", base))?;
+        let status = resp.status().as_u16();
+        Ok((status, resp.text().await?))
+    }
+
+    #[cfg(unix)]
+    async fn call_unix(
+        &self,
+        socket: &Path,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<(u16, String)> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        // HTTP/1.0: the server closes the connection after a non-chunked response
+        let request = format!(
+            "{} {} HTTP/1.0\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            self.token,
+            body.len(),
+            body
+        );
+        let mut stream = tokio::net::UnixStream::connect(socket)
+            .await
+            .with_context(|| format!("connecting to {}", socket.display()))?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        parse_http_response(&response)
+    }
+
+    #[cfg(not(unix))]
+    async fn call_unix(
+        &self,
+        _socket: &Path,
+        _method: &str,
+        _path: &str,
+        _body: Option<Value>,
+    ) -> Result<(u16, String)> {
+        bail!("Unix sockets are not supported on this platform")
+    }
+}
+
+/// Status code and body of a raw HTTP/1.x response.
+pub fn parse_http_response(raw: &[u8]) -> Result<(u16, String)> {
+    let text = String::from_utf8_lossy(raw);
+    let (head, body) = text
+        .split_once("\r\n\r\n")
+        .context("malformed HTTP response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("malformed HTTP status line")?;
+    Ok((status, body.to_string()))
+}
+
+/// Run one action and return the response data.
+pub async fn execute(client: &CtlClient, action: &CtlCommand) -> Result<Value> {
+    match action {
+        CtlCommand::Status => client.call("GET", "/api/status", None).await,
+        CtlCommand::Sessions { user } => {
+            let mut data = client.call("GET", "/api/sessions", None).await?;
+            if let (Some(user), Some(sessions)) = (user, data.as_array_mut()) {
+                sessions.retain(|s| s["username"] == user.as_str());
+            }
+            Ok(data)
+        }
+        CtlCommand::Kick { username, message } => {
+            let path = format!(
+                "/api/kick/{}",
+                utf8_percent_encode(username, NON_ALPHANUMERIC)
+            );
+            let body = message.as_ref().map(|m| json!({ "message": m }));
+            client.call("POST", &path, body).await
+        }
+        CtlCommand::Ban { ip, duration } => {
+            let body = json!({ "ip": ip, "duration_secs": duration });
+            client.call("POST", "/api/bans", Some(body)).await
+        }
+        CtlCommand::Unban { ip } => {
+            let path = format!("/api/bans/{}", utf8_percent_encode(ip, NON_ALPHANUMERIC));
+            client.call("DELETE", &path, None).await
+        }
+        CtlCommand::Bans => client.call("GET", "/api/bans", None).await,
+        CtlCommand::Unlock { username } => {
+            let path = format!(
+                "/api/users/{}/lock",
+                utf8_percent_encode(username, NON_ALPHANUMERIC)
+            );
+            client.call("DELETE", &path, None).await
+        }
+        CtlCommand::Reload => client.call("POST", "/api/reload", None).await,
+        CtlCommand::LogLevel { level, reset } => match level {
+            Some(level) => {
+                let body = json!({ "level": level });
+                client.call("PUT", "/api/logging/level", Some(body)).await
+            }
+            None if *reset => client.call("DELETE", "/api/logging/level", None).await,
+            None => client.call("GET", "/api/logging/level", None).await,
+        },
+    }
+}
+
+/// Human-readable output for an action's response data.
+pub fn render(action: &CtlCommand, data: &Value) -> String {
+    let mut out = String::new();
+    match action {
+        CtlCommand::Status => {
+            let caps = &data["connection_caps"];
+            let _ = writeln!(out, "Status:       {}", str_of(&data["status"]));
+            let _ = writeln!(
+                out,
+                "Uptime:       {}",
+                format_secs(u64_of(&data["uptime_secs"]))
+            );
+            let _ = write!(
+                out,
+                "Clients:      {} (ssh {}, socks5 {})",
+                u64_of(&caps["active"]),
+                u64_of(&caps["ssh"]),
+                u64_of(&caps["socks5"])
+            );
+            match u64_of(&caps["max"]) {
+                0 => out.push('\n'),
+                max => {
+                    let _ = writeln!(out, ", max {}, {} queued", max, u64_of(&caps["queued"]));
+                }
+            }
+            let _ = writeln!(out, "Connections:  {}", u64_of(&data["active_connections"]));
+            let _ = writeln!(out, "Users:        {}", u64_of(&data["total_users"]));
+            let _ = writeln!(
+                out,
+                "Maintenance:  {}",
+                if data["maintenance"] == true {
+                    "on"
+                } else {
+                    "off"
+                }
+            );
+        }
+        CtlCommand::Sessions { .. } => {
+            let sessions = data.as_array().map(Vec::as_slice).unwrap_or_default();
+            if sessions.is_empty() {
+                return "No active sessions.\n".to_string();
+            }
+            let _ = writeln!(
+                out,
+                "{:<12} {:<16} {:<32} {:<16} {:<6} {:>9} {:>10} {:>10}",
+                "SESSION", "USER", "TARGET", "SOURCE", "PROTO", "AGE", "UP", "DOWN"
+            );
+            for s in sessions {
+                let target = format!(
+                    "{}:{}",
+                    str_of(&s["target_host"]),
+                    u64_of(&s["target_port"])
+                );
+                let _ = writeln!(
+                    out,
+                    "{:<12} {:<16} {:<32} {:<16} {:<6} {:>9} {:>10} {:>10}",
+                    str_of(&s["session_id"]),
+                    str_of(&s["username"]),
+                    target,
+                    str_of(&s["source_ip"]),
+                    str_of(&s["protocol"]),
+                    format_secs(u64_of(&s["duration_secs"])),
+                    format_bytes_used(u64_of(&s["bytes_up"])),
+                    format_bytes_used(u64_of(&s["bytes_down"]))
+                );
+            }
+        }
+        CtlCommand::Kick { username, .. } => {
+            if data["kicked"] == true {
+                let _ = writeln!(
+                    out,
+                    "Kicked {} ({} sessions closed)",
+                    username,
+                    u64_of(&data["sessions_closed"])
+                );
+            } else {
+                let _ = writeln!(out, "{} has no active connections", username);
+            }
+        }
+        CtlCommand::Ban { ip, .. } => {
+            let _ = writeln!(
+                out,
+                "Banned {} for {}",
+                ip,
+                format_secs(u64_of(&data["remaining_secs"]))
+            );
+        }
+        CtlCommand::Unban { ip } => {
+            let _ = writeln!(out, "Unbanned {}", ip);
+        }
+        CtlCommand::Bans => {
+            let bans = data.as_array().map(Vec::as_slice).unwrap_or_default();
+            if bans.is_empty() {
+                return "No banned IPs.\n".to_string();
+            }
+            let _ = writeln!(out, "{:<40} {:<16} {:>10}", "IP", "TENANT", "REMAINING");
+            for ban in bans {
+                let _ = writeln!(
+                    out,
+                    "{:<40} {:<16} {:>10}",
+                    str_of(&ban["ip"]),
+                    ban["tenant"].as_str().unwrap_or("-"),
+                    format_secs(u64_of(&ban["remaining_secs"]))
+                );
+            }
+        }
+        CtlCommand::Unlock { username } => {
+            let _ = writeln!(out, "Unlocked {}", username);
+        }
+        CtlCommand::Reload => {
+            let _ = writeln!(
+                out,
+                "Configuration reloaded ({} users)",
+                u64_of(&data["users_count"])
+            );
+        }
+        CtlCommand::LogLevel { .. } => {
+            let _ = writeln!(out, "Log level:    {}", str_of(&data["level"]));
+            let _ = writeln!(out, "Startup:      {}", str_of(&data["default"]));
+        }
+    }
+    out
+}
+
+/// `s5 ctl`: resolve the API address and token, run the action, print the result.
+pub fn run_cli(
+    config_path: &Path,
+    api_addr: Option<&str>,
+    token: Option<&str>,
+    json_output: bool,
+    action: &CtlCommand,
+) -> Result<()> {
+    let api = match (api_addr, token) {
+        (Some(_), Some(_)) => ApiConfig::default(),
+        _ => {
+            crate::config::load_config(config_path)
+                .with_context(|| {
+                    format!(
+                        "reading [api] from {} (or pass --api-addr and --token)",
+                        config_path.display()
+                    )
+                })?
+                .api
+        }
+    };
+    let target = match api_addr {
+        Some(addr) => ApiTarget::parse(addr)?,
+        None => ApiTarget::from_config(&api),
+    };
+    let token = token.map(str::to_string).unwrap_or(api.token);
+    if token.is_empty() {
+        bail!("no API token: pass --token, set S5_API_TOKEN or api.token");
+    }
+
+    let client = CtlClient::new(target, token);
+    let rt = tokio::runtime::Runtime::new()?;
+    let data = rt.block_on(execute(&client, action))?;
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&data)?);
+    } else {
+        print!("{}", render(action, &data));
+    }
+    Ok(())
+}
+
+fn str_of(v: &Value) -> &str {
+    v.as_str().unwrap_or("-")
+}
+
+fn u64_of(v: &Value) -> u64 {
+    v.as_u64().unwrap_or(0)
+}
+
+/// `3725` -> `1h2m5s`
+fn format_secs(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match (h, m) {
+        (0, 0) => format!("{}s", s),
+        (0, _) => format!("{}m{}s", m, s),
+        _ => format!("{}h{}m{}s", h, m, s),
+    }
+}
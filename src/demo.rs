@@ -1,13 +1,14 @@
 use crate::audit::events::AuditEvent;
 use crate::config::types::{
-    AclPolicyConfig, ApiConfig, AppConfig, GlobalAclConfig, GroupConfig, LoggingConfig,
-    QuotaConfig, SecurityConfig, ServerConfig, ShellConfig, UserAclConfig, UserConfig, UserRole,
+    AclPolicyConfig, ApiConfig, AppConfig, GlobalAclConfig, GroupConfig, HostKeyRotationConfig,
+    LoggingConfig, QuotaConfig, SecurityConfig, ServerConfig, ShellConfig, UserAclConfig,
+    UserConfig, UserRole,
 };
 use crate::context::AppContext;
 use crate::proxy::LiveSession;
 
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
@@ -23,7 +24,7 @@ pub fn build_demo_config(
         server: ServerConfig {
             ssh_listen: format!("127.0.0.1:{}", ssh_port),
             socks5_listen: Some(format!("127.0.0.1:{}", socks5_port)),
-            host_key_path: std::path::PathBuf::from("/tmp/s5-demo-host-key"),
+            host_key_path: crate::paths::temp_file("s5-demo-host-key"),
             server_id: "SSH-2.0-s5-demo".to_string(),
             banner: "Welcome to s5 demo".to_string(),
             motd_path: None,
@@ -35,12 +36,24 @@ pub fn build_demo_config(
             socks5_tls_key: None,
             dns_cache_ttl: -1,
             dns_cache_max_entries: 1000,
+            dns_prefetch_hosts: 0,
             connect_retry: 0,
             connect_retry_delay_ms: 1000,
             bookmarks_path: None,
             ssh_keepalive_interval_secs: 15,
             ssh_keepalive_max: 3,
             ssh_auth_timeout: 120,
+            ssh_window_size: 2 * 1024 * 1024,
+            ssh_max_packet_size: 32 * 1024,
+            ssh_stall_threshold_ms: 5000,
+            max_connections: 0,
+            max_connections_per_user: 0,
+            connection_queue_timeout_ms: 0,
+            connection_queue_size: 0,
+            listeners: Vec::new(),
+            host_key_rotation: HostKeyRotationConfig::default(),
+            maintenance_message: "Server is under maintenance. Please try again later.".to_string(),
+            socket_handover: false,
         },
         shell: ShellConfig {
             hostname: "s5-demo".to_string(),
@@ -58,6 +71,7 @@ pub fn build_demo_config(
             enabled: true,
             listen: format!("127.0.0.1:{}", api_port),
             token: "demo".to_string(),
+            ..ApiConfig::default()
         },
         geoip: Default::default(),
         upstream_proxy: None,
@@ -71,6 +85,7 @@ pub fn build_demo_config(
                 "example.com:*".to_string(),
             ],
             deny: vec!["*.internal:*".to_string(), "10.0.0.0/8:*".to_string()],
+            inspect_server_names: false,
         },
         users: vec![
             // alice: admin with shell+fwd and generous quotas
@@ -78,12 +93,18 @@ pub fn build_demo_config(
                 username: "alice".to_string(),
                 password_hash: Some(password_hash.to_string()),
                 authorized_keys: Vec::new(),
-                allow_forwarding: true,
+                allow_forwarding: Some(true),
                 allow_shell: true,
                 max_new_connections_per_minute: 0,
                 max_bandwidth_kbps: 0,
                 source_ips: Vec::new(),
                 expires_at: None,
+                disabled: false,
+                require_security_key: false,
+                allow_vpn: false,
+                vpn_address: None,
+                tags: Vec::new(),
+                authorized_keys_url: None,
                 upstream_proxy: None,
                 acl: UserAclConfig::default(),
                 totp_secret: None,
@@ -92,6 +113,7 @@ pub fn build_demo_config(
                 group: Some("developers".to_string()),
                 role: UserRole::Admin,
                 shell_permissions: None,
+                terminal: None,
                 motd: None,
                 quotas: Some(QuotaConfig {
                     daily_bandwidth_bytes: 5_368_709_120,    // 5 GB
@@ -101,6 +123,7 @@ pub fn build_demo_config(
                     monthly_connection_limit: 1000,
                     total_bandwidth_bytes: 0,
                 }),
+                quota_plan: None,
                 time_access: None,
                 auth_methods: None,
                 idle_warning_secs: None,
@@ -110,18 +133,33 @@ pub fn build_demo_config(
                 aliases: HashMap::new(),
                 max_connections: None,
                 rate_limits: None,
+                api_token_hash: None,
+                api_tokens: Vec::new(),
+                ip_guard_exemptions: None,
+                jump_targets: None,
+                unix_sockets: None,
+                allowed_hassh: None,
+                subsystems: None,
+                impossible_travel: None,
+                password_changed_at: None,
             },
             // bob: forwarding only, moderate quotas
             UserConfig {
                 username: "bob".to_string(),
                 password_hash: Some(password_hash.to_string()),
                 authorized_keys: Vec::new(),
-                allow_forwarding: true,
+                allow_forwarding: Some(true),
                 allow_shell: false,
                 max_new_connections_per_minute: 0,
                 max_bandwidth_kbps: 0,
                 source_ips: Vec::new(),
                 expires_at: None,
+                disabled: false,
+                require_security_key: false,
+                allow_vpn: false,
+                vpn_address: None,
+                tags: Vec::new(),
+                authorized_keys_url: None,
                 upstream_proxy: None,
                 acl: UserAclConfig::default(),
                 totp_secret: None,
@@ -130,6 +168,7 @@ pub fn build_demo_config(
                 group: Some("developers".to_string()),
                 role: UserRole::User,
                 shell_permissions: None,
+                terminal: None,
                 motd: None,
                 quotas: Some(QuotaConfig {
                     daily_bandwidth_bytes: 2_147_483_648,    // 2 GB
@@ -139,6 +178,7 @@ pub fn build_demo_config(
                     monthly_connection_limit: 500,
                     total_bandwidth_bytes: 0,
                 }),
+                quota_plan: None,
                 time_access: None,
                 auth_methods: None,
                 idle_warning_secs: None,
@@ -148,18 +188,33 @@ pub fn build_demo_config(
                 aliases: HashMap::new(),
                 max_connections: None,
                 rate_limits: None,
+                api_token_hash: None,
+                api_tokens: Vec::new(),
+                ip_guard_exemptions: None,
+                jump_targets: None,
+                unix_sockets: None,
+                allowed_hassh: None,
+                subsystems: None,
+                impossible_travel: None,
+                password_changed_at: None,
             },
             // charlie: shell+fwd, tight quotas
             UserConfig {
                 username: "charlie".to_string(),
                 password_hash: Some(password_hash.to_string()),
                 authorized_keys: Vec::new(),
-                allow_forwarding: true,
+                allow_forwarding: Some(true),
                 allow_shell: true,
                 max_new_connections_per_minute: 0,
                 max_bandwidth_kbps: 0,
                 source_ips: Vec::new(),
                 expires_at: None,
+                disabled: false,
+                require_security_key: false,
+                allow_vpn: false,
+                vpn_address: None,
+                tags: Vec::new(),
+                authorized_keys_url: None,
                 upstream_proxy: None,
                 acl: UserAclConfig::default(),
                 totp_secret: None,
@@ -168,6 +223,7 @@ pub fn build_demo_config(
                 group: None,
                 role: UserRole::User,
                 shell_permissions: None,
+                terminal: None,
                 motd: None,
                 quotas: Some(QuotaConfig {
                     daily_bandwidth_bytes: 536_870_912,     // 500 MB
@@ -177,6 +233,7 @@ pub fn build_demo_config(
                     monthly_connection_limit: 200,
                     total_bandwidth_bytes: 0,
                 }),
+                quota_plan: None,
                 time_access: None,
                 auth_methods: None,
                 idle_warning_secs: None,
@@ -186,10 +243,21 @@ pub fn build_demo_config(
                 aliases: HashMap::new(),
                 max_connections: None,
                 rate_limits: None,
+                api_token_hash: None,
+                api_tokens: Vec::new(),
+                ip_guard_exemptions: None,
+                jump_targets: None,
+                unix_sockets: None,
+                allowed_hassh: None,
+                subsystems: None,
+                impossible_travel: None,
+                password_changed_at: None,
             },
         ],
         groups: vec![GroupConfig {
             name: "developers".to_string(),
+            inherits: None,
+            managers: Vec::new(),
             acl: UserAclConfig::default(),
             max_connections_per_user: None,
             max_bandwidth_kbps: Some(10240),
@@ -198,8 +266,10 @@ pub fn build_demo_config(
             allow_forwarding: Some(true),
             allow_shell: Some(true),
             shell_permissions: None,
+            terminal: None,
             motd: None,
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: None,
             idle_warning_secs: None,
@@ -208,11 +278,41 @@ pub fn build_demo_config(
             connect_retry: None,
             connect_retry_delay_ms: None,
             rate_limits: None,
+            ip_guard_exemptions: None,
+            jump_targets: None,
+            unix_sockets: None,
+            allowed_hassh: None,
+            fs_allow: None,
+            subsystems: None,
+            payload_metadata: None,
+            blocked_ports: None,
+            tags: None,
         }],
         motd: Default::default(),
         alerting: Default::default(),
         maintenance_windows: Vec::new(),
+        quota_plans: Default::default(),
         connection_pool: Default::default(),
+        threat_intel: Default::default(),
+        ip_intel: Default::default(),
+        tenants: Default::default(),
+        honeypot: Default::default(),
+        login_anomaly: Default::default(),
+        impossible_travel: Default::default(),
+        invitations: Default::default(),
+        key_enrollment: Default::default(),
+        authorized_keys_fetch: Default::default(),
+        notifications: Default::default(),
+        reports: Default::default(),
+        cluster: Default::default(),
+        sandbox: Default::default(),
+        vpn: Default::default(),
+        subsystems: Vec::new(),
+        proxy: Default::default(),
+        policy: Default::default(),
+        scripting: None,
+        masque: None,
+        gssapi: None,
     }
 }
 
@@ -281,9 +381,10 @@ pub async fn inject_demo_data(ctx: &AppContext) -> Vec<Arc<LiveSession>> {
     // --- Banned IP ---
     {
         let security = ctx.security.write().await;
-        security
-            .ban_manager()
-            .ban("192.168.99.1".parse().unwrap(), Duration::from_secs(1800));
+        security.ban_manager().ban(
+            "192.168.99.1".parse::<IpAddr>().unwrap(),
+            Duration::from_secs(1800),
+        );
     }
 
     // --- Audit events ---
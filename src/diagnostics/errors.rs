@@ -0,0 +1,99 @@
+//! Recent warnings and errors, kept in memory for diagnostic bundles.
+//!
+//! [`RecentErrorsLayer`] sits next to the log output layers and copies every
+//! `WARN` and `ERROR` event that passes the log filter into a bounded ring,
+//! so `GET /api/debug/bundle` can include what went wrong lately without
+//! access to the log files.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Records kept; the oldest is dropped first.
+pub const RECENT_ERRORS_CAPACITY: usize = 200;
+
+/// Longest message kept, in characters.
+const MAX_MESSAGE_CHARS: usize = 2048;
+
+static RECENT: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+
+/// One captured log event.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    /// Message followed by the event's other fields as `name=value`
+    pub message: String,
+}
+
+/// Tracing layer feeding [`recent_errors`].
+pub struct RecentErrorsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentErrorsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if *meta.level() > Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        message.push_str(&visitor.fields);
+        record(meta.level().as_str(), meta.target(), &message);
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Add a record to the ring.
+pub fn record(level: &str, target: &str, message: &str) {
+    let record = LogRecord {
+        timestamp: Utc::now(),
+        level: level.to_string(),
+        target: target.to_string(),
+        message: message.chars().take(MAX_MESSAGE_CHARS).collect(),
+    };
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() >= RECENT_ERRORS_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(record);
+}
+
+/// Captured warnings and errors, oldest first.
+pub fn recent_errors() -> Vec<LogRecord> {
+    RECENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
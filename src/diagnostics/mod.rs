@@ -0,0 +1,421 @@
+//! Self-diagnostics: `s5 doctor` and `GET /api/debug/bundle`.
+//!
+//! [`run_checks`] inspects a configuration for problems the validator cannot
+//! see because they depend on the host: missing key and database files,
+//! log directories that do not exist, listeners sharing a port, an API
+//! exposed to the network. `s5 doctor` runs the checks offline against the
+//! config file; the debug bundle runs them in the live server and packs the
+//! result with the redacted config, version, recent errors, session counts
+//! and runtime stats into a tarball for bug reports.
+
+pub mod errors;
+
+use crate::config::types::AppConfig;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        }
+    }
+}
+
+/// One diagnostic check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run every check against `config`.
+pub fn run_checks(config: &AppConfig) -> Vec<Check> {
+    let mut checks = vec![Check::new(
+        "config",
+        CheckStatus::Ok,
+        format!("valid, {} users", config.users.len()),
+    )];
+
+    let server = &config.server;
+    checks.push(host_key_check("host_key", &server.host_key_path));
+    for listener in &server.listeners {
+        if let Some(ref path) = listener.host_key_path {
+            checks.push(host_key_check(&format!("host_key.{}", listener.name), path));
+        }
+    }
+    if let (Some(cert), Some(key)) = (&server.socks5_tls_cert, &server.socks5_tls_key) {
+        checks.push(file_check("socks5_tls_cert", cert));
+        checks.push(file_check("socks5_tls_key", key));
+    }
+    checks.push(listeners_check(config));
+    if let Some(check) = api_exposure_check(config) {
+        checks.push(check);
+    }
+
+    let logging = &config.logging;
+    if let Some(ref path) = logging.audit_log_path {
+        checks.push(writable_file_check("audit_log", path));
+    }
+    if logging.flows.enabled {
+        checks.push(writable_file_check("flows", &logging.flows.path));
+    }
+    if logging.capture.enabled {
+        checks.push(directory_check("capture", &logging.capture.directory));
+    }
+
+    if config.geoip.enabled {
+        if let Some(ref path) = config.geoip.database_path {
+            checks.push(file_check("geoip", path));
+        }
+        if let Some(ref path) = config.geoip.asn_database_path {
+            checks.push(file_check("geoip_asn", path));
+        }
+    }
+    checks
+}
+
+/// Warn when the config file at `path` is readable by group or others.
+pub fn config_file_check(path: &Path) -> Option<Check> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path).ok()?.permissions().mode();
+        (mode & 0o077 != 0).then(|| {
+            Check::new(
+                "config_permissions",
+                CheckStatus::Warn,
+                format!(
+                    "{} has mode {:04o}; restrict it to 0600, it holds secrets",
+                    path.display(),
+                    mode & 0o7777
+                ),
+            )
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+fn file_check(name: &str, path: &Path) -> Check {
+    match std::fs::File::open(path) {
+        Ok(_) => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!("{} readable", path.display()),
+        ),
+        Err(e) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("{}: {}", path.display(), e),
+        ),
+    }
+}
+
+/// A missing host key is generated at startup, if its directory exists.
+fn host_key_check(name: &str, path: &Path) -> Check {
+    if path.exists() {
+        return file_check(name, path);
+    }
+    match parent_dir(path) {
+        Some(dir) if dir.is_dir() => Check::new(
+            name,
+            CheckStatus::Warn,
+            format!("{} missing, a new key will be generated", path.display()),
+        ),
+        _ => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "{} missing and its directory does not exist",
+                path.display()
+            ),
+        ),
+    }
+}
+
+/// A file the server appends to or creates.
+fn writable_file_check(name: &str, path: &Path) -> Check {
+    if path.exists() {
+        return match std::fs::OpenOptions::new().append(true).open(path) {
+            Ok(_) => Check::new(
+                name,
+                CheckStatus::Ok,
+                format!("{} writable", path.display()),
+            ),
+            Err(e) => Check::new(
+                name,
+                CheckStatus::Fail,
+                format!("{}: {}", path.display(), e),
+            ),
+        };
+    }
+    match parent_dir(path) {
+        Some(dir) if dir.is_dir() => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!("{} will be created", path.display()),
+        ),
+        _ => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("directory of {} does not exist", path.display()),
+        ),
+    }
+}
+
+/// A directory the server creates if missing.
+fn directory_check(name: &str, dir: &Path) -> Check {
+    if dir.is_dir() {
+        return Check::new(name, CheckStatus::Ok, format!("{} exists", dir.display()));
+    }
+    match parent_dir(dir) {
+        Some(parent) if parent.is_dir() => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!("{} will be created", dir.display()),
+        ),
+        _ => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("parent of {} does not exist", dir.display()),
+        ),
+    }
+}
+
+/// Directory of `path`; `.` for a bare file name.
+fn parent_dir(path: &Path) -> Option<&Path> {
+    match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Some(Path::new(".")),
+        other => other,
+    }
+}
+
+/// TCP listeners that would fight over the same port.
+fn listeners_check(config: &AppConfig) -> Check {
+    let mut listeners = vec![("ssh".to_string(), config.server.ssh_listen.as_str())];
+    for listener in &config.server.listeners {
+        listeners.push((format!("listener {}", listener.name), &listener.listen));
+    }
+    if let Some(ref socks) = config.server.socks5_listen {
+        listeners.push(("socks5".to_string(), socks));
+    }
+    if config.api.enabled && config.api.unix_socket.is_none() {
+        listeners.push(("api".to_string(), &config.api.listen));
+    }
+    if config.metrics.enabled {
+        listeners.push(("metrics".to_string(), &config.metrics.listen));
+    }
+
+    let mut clashes = Vec::new();
+    for (i, (name_a, addr_a)) in listeners.iter().enumerate() {
+        for (name_b, addr_b) in &listeners[i + 1..] {
+            if same_port(addr_a, addr_b) {
+                clashes.push(format!("{name_a} ({addr_a}) and {name_b} ({addr_b})"));
+            }
+        }
+    }
+    if clashes.is_empty() {
+        Check::new(
+            "listeners",
+            CheckStatus::Ok,
+            format!("{} TCP listeners, no shared ports", listeners.len()),
+        )
+    } else {
+        Check::new(
+            "listeners",
+            CheckStatus::Fail,
+            format!("same port: {}", clashes.join("; ")),
+        )
+    }
+}
+
+fn same_port(a: &str, b: &str) -> bool {
+    match (a.parse::<SocketAddr>(), b.parse::<SocketAddr>()) {
+        (Ok(a), Ok(b)) => {
+            a.port() == b.port()
+                && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+        }
+        _ => a == b,
+    }
+}
+
+/// The management API listening beyond loopback.
+fn api_exposure_check(config: &AppConfig) -> Option<Check> {
+    let api = &config.api;
+    if !api.enabled || api.unix_socket.is_some() {
+        return None;
+    }
+    let exposed = api
+        .listen
+        .parse::<SocketAddr>()
+        .map(|addr| !addr.ip().is_loopback())
+        .unwrap_or(true);
+    Some(if exposed {
+        Check::new(
+            "api",
+            CheckStatus::Warn,
+            format!(
+                "management API listens on {}, reachable from the network; \
+                 bind it to loopback or api.unix_socket, or restrict access",
+                api.listen
+            ),
+        )
+    } else {
+        Check::new(
+            "api",
+            CheckStatus::Ok,
+            format!("listening on {}", api.listen),
+        )
+    })
+}
+
+/// Human-readable report of `checks`, one line each plus a summary.
+pub fn render_checks(checks: &[Check]) -> String {
+    let mut out = String::new();
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in checks {
+        let _ = writeln!(
+            out,
+            "  {:<5} {:<width$}  {}",
+            check.status.as_str(),
+            check.name,
+            check.detail
+        );
+    }
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let _ = writeln!(
+        out,
+        "{} ok, {} warnings, {} failures",
+        count(CheckStatus::Ok),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    );
+    out
+}
+
+/// `s5 doctor`: load the config as the server would and print the checks.
+/// Fails when the config does not load or any check fails.
+pub fn doctor_cli(config_path: &Path, json: bool) -> anyhow::Result<()> {
+    let mut checks = Vec::new();
+    match crate::config::load_effective_config(config_path) {
+        Ok(config) => {
+            checks.extend(config_file_check(config_path));
+            checks.extend(run_checks(&config));
+        }
+        Err(e) => checks.push(Check::new("config", CheckStatus::Fail, format!("{e:#}"))),
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        println!(
+            "s5 {} doctor: {}",
+            env!("CARGO_PKG_VERSION"),
+            config_path.display()
+        );
+        print!("{}", render_checks(&checks));
+    }
+    let failures = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    if failures > 0 {
+        anyhow::bail!("{} check(s) failed", failures);
+    }
+    Ok(())
+}
+
+/// Build and platform details for the bundle.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub pid: u32,
+    pub generated_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    /// Log filter in effect
+    pub log_level: Option<String>,
+}
+
+impl VersionInfo {
+    pub fn current(uptime_secs: u64) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            pid: std::process::id(),
+            generated_at: Utc::now(),
+            uptime_secs,
+            log_level: crate::log_filter::level().map(|l| l.level),
+        }
+    }
+}
+
+/// Tokio runtime counters.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeStats {
+    pub flavor: String,
+    pub workers: usize,
+    pub alive_tasks: usize,
+    /// Tasks waiting in the shared injection queue
+    pub global_queue_depth: usize,
+}
+
+/// Stats of the runtime the caller runs on, if any.
+pub fn runtime_stats() -> Option<RuntimeStats> {
+    let handle = tokio::runtime::Handle::try_current().ok()?;
+    let metrics = handle.metrics();
+    Some(RuntimeStats {
+        flavor: format!("{:?}", handle.runtime_flavor()),
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+    })
+}
+
+/// Pack `files` into a gzipped tarball under the directory `root`.
+pub fn tar_gz(root: &str, files: &[(&str, Vec<u8>)]) -> std::io::Result<Vec<u8>> {
+    let mtime = Utc::now().timestamp().max(0) as u64;
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        tar.append_data(&mut header, format!("{root}/{name}"), contents.as_slice())?;
+    }
+    tar.into_inner()?.finish()
+}
@@ -0,0 +1,382 @@
+//! IPFIX (RFC 7011) export of finished connections (`[logging.ipfix]`).
+//!
+//! Each [`FlowRecord`] becomes one biflow data record sent over UDP to the
+//! configured collector. Standard information elements carry the addresses,
+//! destination port, timestamps and byte counts (client-to-target bytes as
+//! `octetDeltaCount`, target-to-client bytes as the RFC 5103 reverse element);
+//! the username, destination hostname, s5 protocol and close reason are
+//! enterprise-specific string fields under `enterprise_number`.
+//!
+//! Four templates cover the IPv4/IPv6 combinations of source and destination.
+//! Templates are sent before the first data record and again every
+//! `template_refresh_secs`, as UDP collectors require.
+
+use super::{FlowRecord, CLOSE_NORMAL};
+use crate::config::types::IpfixConfig;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// IPFIX protocol version.
+pub const IPFIX_VERSION: u16 = 10;
+/// Set ID of template sets.
+pub const TEMPLATE_SET_ID: u16 = 2;
+/// First template ID (IPv4 source, IPv4 destination).
+pub const TEMPLATE_ID_BASE: u16 = 256;
+/// IANA private enterprise number of RFC 5103 reverse information elements.
+pub const REVERSE_PEN: u32 = 29305;
+
+/// Enterprise-specific element IDs under the configured enterprise number.
+pub const FIELD_USERNAME: u16 = 1;
+pub const FIELD_DEST_HOST: u16 = 2;
+pub const FIELD_PROTOCOL: u16 = 3;
+pub const FIELD_CLOSE_REASON: u16 = 4;
+
+/// `flowEndReason` values (IANA registry).
+pub const END_OF_FLOW_DETECTED: u8 = 0x03;
+pub const FORCED_END: u8 = 0x04;
+
+/// Keep messages below a typical path MTU.
+const MAX_MESSAGE_LEN: usize = 1400;
+const MESSAGE_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+const VARIABLE_LENGTH: u16 = 65535;
+/// Longest string written into an enterprise field.
+const MAX_STRING_LEN: usize = 512;
+const IPFIX_CHANNEL_CAPACITY: usize = 10_000;
+const MAX_BATCH: usize = 64;
+
+const IE_OCTET_DELTA_COUNT: u16 = 1;
+const IE_PROTOCOL_IDENTIFIER: u16 = 4;
+const IE_DESTINATION_TRANSPORT_PORT: u16 = 11;
+const IE_SOURCE_IPV4_ADDRESS: u16 = 8;
+const IE_DESTINATION_IPV4_ADDRESS: u16 = 12;
+const IE_SOURCE_IPV6_ADDRESS: u16 = 27;
+const IE_DESTINATION_IPV6_ADDRESS: u16 = 28;
+const IE_FLOW_END_REASON: u16 = 136;
+const IE_FLOW_START_MILLISECONDS: u16 = 152;
+const IE_FLOW_END_MILLISECONDS: u16 = 153;
+const PROTOCOL_TCP: u8 = 6;
+
+/// Template ID for a source/destination address family pair.
+pub fn template_id(src_v6: bool, dst_v6: bool) -> u16 {
+    TEMPLATE_ID_BASE + u16::from(src_v6) * 2 + u16::from(dst_v6)
+}
+
+/// Builds IPFIX messages for one observation domain.
+pub struct IpfixEncoder {
+    observation_domain_id: u32,
+    enterprise_number: u32,
+    /// Data records exported so far (message sequence number, mod 2^32)
+    sequence: u32,
+}
+
+impl IpfixEncoder {
+    pub fn new(observation_domain_id: u32, enterprise_number: u32) -> Self {
+        Self {
+            observation_domain_id,
+            enterprise_number,
+            sequence: 0,
+        }
+    }
+
+    /// Data records exported so far.
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// One message carrying all four templates.
+    pub fn template_message(&self, export_time: u32) -> Vec<u8> {
+        let mut set = Vec::new();
+        for (src_v6, dst_v6) in [(false, false), (false, true), (true, false), (true, true)] {
+            self.write_template(&mut set, src_v6, dst_v6);
+        }
+        let mut msg = self.message_header(export_time);
+        push_set(&mut msg, TEMPLATE_SET_ID, &set);
+        finish_message(msg)
+    }
+
+    /// Data messages for `records`, each below the MTU budget. Advances the
+    /// sequence number by the number of records encoded.
+    pub fn data_messages(&mut self, records: &[FlowRecord], export_time: u32) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        let mut msg = self.message_header(export_time);
+        // (template ID, encoded records) of the set being filled
+        let mut set: Option<(u16, Vec<u8>)> = None;
+
+        for record in records {
+            let (tid, encoded) = self.encode_record(record);
+            let set_len = set.as_ref().map_or(0, |(_, b)| SET_HEADER_LEN + b.len());
+            let extra = if set.as_ref().is_some_and(|(id, _)| *id == tid) {
+                encoded.len()
+            } else {
+                SET_HEADER_LEN + encoded.len()
+            };
+            if msg.len() + set_len + extra > MAX_MESSAGE_LEN
+                && msg.len() + set_len > MESSAGE_HEADER_LEN
+            {
+                if let Some((id, body)) = set.take() {
+                    push_set(&mut msg, id, &body);
+                }
+                messages.push(finish_message(msg));
+                msg = self.message_header(export_time);
+            }
+            match set {
+                Some((id, ref mut body)) if id == tid => body.extend_from_slice(&encoded),
+                _ => {
+                    if let Some((id, body)) = set.take() {
+                        push_set(&mut msg, id, &body);
+                    }
+                    set = Some((tid, encoded));
+                }
+            }
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+        if let Some((id, body)) = set.take() {
+            push_set(&mut msg, id, &body);
+        }
+        if msg.len() > MESSAGE_HEADER_LEN {
+            messages.push(finish_message(msg));
+        }
+        messages
+    }
+
+    fn message_header(&self, export_time: u32) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(MAX_MESSAGE_LEN);
+        msg.extend_from_slice(&IPFIX_VERSION.to_be_bytes());
+        msg.extend_from_slice(&0u16.to_be_bytes()); // length, patched in finish_message
+        msg.extend_from_slice(&export_time.to_be_bytes());
+        msg.extend_from_slice(&self.sequence.to_be_bytes());
+        msg.extend_from_slice(&self.observation_domain_id.to_be_bytes());
+        msg
+    }
+
+    fn write_template(&self, out: &mut Vec<u8>, src_v6: bool, dst_v6: bool) {
+        let mut fields: Vec<(u16, u16, Option<u32>)> = vec![
+            if src_v6 {
+                (IE_SOURCE_IPV6_ADDRESS, 16, None)
+            } else {
+                (IE_SOURCE_IPV4_ADDRESS, 4, None)
+            },
+            if dst_v6 {
+                (IE_DESTINATION_IPV6_ADDRESS, 16, None)
+            } else {
+                (IE_DESTINATION_IPV4_ADDRESS, 4, None)
+            },
+            (IE_DESTINATION_TRANSPORT_PORT, 2, None),
+            (IE_PROTOCOL_IDENTIFIER, 1, None),
+            (IE_FLOW_START_MILLISECONDS, 8, None),
+            (IE_FLOW_END_MILLISECONDS, 8, None),
+            (IE_OCTET_DELTA_COUNT, 8, None),
+            (IE_OCTET_DELTA_COUNT, 8, Some(REVERSE_PEN)),
+            (IE_FLOW_END_REASON, 1, None),
+        ];
+        for id in [
+            FIELD_USERNAME,
+            FIELD_DEST_HOST,
+            FIELD_PROTOCOL,
+            FIELD_CLOSE_REASON,
+        ] {
+            fields.push((id, VARIABLE_LENGTH, Some(self.enterprise_number)));
+        }
+
+        out.extend_from_slice(&template_id(src_v6, dst_v6).to_be_bytes());
+        out.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        for (id, len, pen) in fields {
+            match pen {
+                Some(pen) => {
+                    out.extend_from_slice(&(id | 0x8000).to_be_bytes());
+                    out.extend_from_slice(&len.to_be_bytes());
+                    out.extend_from_slice(&pen.to_be_bytes());
+                }
+                None => {
+                    out.extend_from_slice(&id.to_be_bytes());
+                    out.extend_from_slice(&len.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    fn encode_record(&self, r: &FlowRecord) -> (u16, Vec<u8>) {
+        let src: IpAddr = r
+            .source_ip
+            .parse()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        // Connections that failed before resolving export an unspecified address
+        let dst: IpAddr = r
+            .dest_ip
+            .as_deref()
+            .and_then(|ip| ip.parse().ok())
+            .unwrap_or(if src.is_ipv6() {
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+            } else {
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            });
+
+        let mut out = Vec::with_capacity(96);
+        push_ip(&mut out, src);
+        push_ip(&mut out, dst);
+        out.extend_from_slice(&r.dest_port.to_be_bytes());
+        out.push(PROTOCOL_TCP);
+        let start = r.started_at.timestamp_millis().max(0) as u64;
+        out.extend_from_slice(&start.to_be_bytes());
+        out.extend_from_slice(&start.saturating_add(r.duration_ms).to_be_bytes());
+        out.extend_from_slice(&r.bytes_up.to_be_bytes());
+        out.extend_from_slice(&r.bytes_down.to_be_bytes());
+        out.push(if r.close_reason == CLOSE_NORMAL {
+            END_OF_FLOW_DETECTED
+        } else {
+            FORCED_END
+        });
+        for s in [&r.username, &r.dest_host, &r.protocol, &r.close_reason] {
+            push_string(&mut out, s);
+        }
+        (template_id(src.is_ipv6(), dst.is_ipv6()), out)
+    }
+}
+
+fn push_ip(out: &mut Vec<u8>, ip: IpAddr) {
+    match ip {
+        IpAddr::V4(v4) => out.extend_from_slice(&v4.octets()),
+        IpAddr::V6(v6) => out.extend_from_slice(&v6.octets()),
+    }
+}
+
+/// Variable-length string (RFC 7011 section 7): 1-byte length below 255,
+/// otherwise 255 followed by a 2-byte length.
+fn push_string(out: &mut Vec<u8>, s: &str) {
+    let mut end = s.len().min(MAX_STRING_LEN);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let bytes = &s.as_bytes()[..end];
+    if bytes.len() < 255 {
+        out.push(bytes.len() as u8);
+    } else {
+        out.push(255);
+        out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    }
+    out.extend_from_slice(bytes);
+}
+
+fn push_set(msg: &mut Vec<u8>, set_id: u16, body: &[u8]) {
+    msg.extend_from_slice(&set_id.to_be_bytes());
+    msg.extend_from_slice(&((SET_HEADER_LEN + body.len()) as u16).to_be_bytes());
+    msg.extend_from_slice(body);
+}
+
+fn finish_message(mut msg: Vec<u8>) -> Vec<u8> {
+    let len = msg.len() as u16;
+    msg[2..4].copy_from_slice(&len.to_be_bytes());
+    msg
+}
+
+fn export_time() -> u32 {
+    chrono::Utc::now().timestamp().clamp(0, i64::from(u32::MAX)) as u32
+}
+
+/// Asynchronous IPFIX exporter sending to one UDP collector.
+pub struct IpfixExporter {
+    sender: mpsc::Sender<FlowRecord>,
+    dropped: AtomicU64,
+}
+
+impl IpfixExporter {
+    /// Start the export task. The collector address is resolved in the task,
+    /// so an unreachable collector does not prevent startup.
+    pub fn start(config: &IpfixConfig) -> Arc<Self> {
+        let (sender, receiver) = mpsc::channel(IPFIX_CHANNEL_CAPACITY);
+        let encoder = IpfixEncoder::new(config.observation_domain_id, config.enterprise_number);
+        tokio::spawn(ipfix_export_task(
+            receiver,
+            encoder,
+            config.collector.clone(),
+            Duration::from_secs(config.template_refresh_secs),
+        ));
+        Arc::new(Self {
+            sender,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Queue a flow for export (dropped if the exporter is backlogged).
+    pub fn record(&self, flow: &FlowRecord) {
+        if self.sender.try_send(flow.clone()).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % 100 == 1 {
+                warn!(total_dropped = dropped, "IPFIX records being dropped");
+            }
+        }
+    }
+
+    /// Number of flow records dropped due to channel overflow.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+async fn connect_collector(collector: &str) -> std::io::Result<UdpSocket> {
+    let addr = tokio::net::lookup_host(collector)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::other("collector address did not resolve"))?;
+    let bind = if addr.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+    Ok(socket)
+}
+
+async fn ipfix_export_task(
+    mut receiver: mpsc::Receiver<FlowRecord>,
+    mut encoder: IpfixEncoder,
+    collector: String,
+    template_refresh: Duration,
+) {
+    let mut socket: Option<UdpSocket> = None;
+    let mut templates_sent: Option<Instant> = None;
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+
+    while receiver.recv_many(&mut batch, MAX_BATCH).await > 0 {
+        let records = std::mem::take(&mut batch);
+        if socket.is_none() {
+            match connect_collector(&collector).await {
+                Ok(s) => {
+                    info!(collector = %collector, "IPFIX exporter connected");
+                    socket = Some(s);
+                    templates_sent = None;
+                }
+                Err(e) => {
+                    warn!(collector = %collector, error = %e, dropped = records.len(), "IPFIX collector unavailable");
+                    continue;
+                }
+            }
+        }
+        let Some(ref sock) = socket else { continue };
+
+        let now = export_time();
+        let mut messages = Vec::new();
+        if templates_sent.is_none_or(|t| t.elapsed() >= template_refresh) {
+            messages.push(encoder.template_message(now));
+            templates_sent = Some(Instant::now());
+        }
+        messages.extend(encoder.data_messages(&records, now));
+
+        for msg in &messages {
+            if let Err(e) = sock.send(msg).await {
+                warn!(collector = %collector, error = %e, "IPFIX send failed");
+                // Re-resolve and resend templates on the next batch
+                socket = None;
+                break;
+            }
+        }
+        debug!(count = records.len(), "IPFIX flow records exported");
+    }
+}
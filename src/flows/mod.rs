@@ -0,0 +1,448 @@
+//! Per-connection flow records (`[logging.flows]`).
+//!
+//! Every forwarded connection (SSH `direct-tcpip` or SOCKS5 CONNECT) produces
+//! one row in a SQLite database when it closes, including connections that
+//! failed to establish. Rows older than `retention_days` are pruned hourly.
+//! Writes are batched on a background task; [`FlowLog::record`] never blocks
+//! the relay path.
+
+pub mod ipfix;
+
+use crate::config::types::FlowLogConfig;
+use crate::proxy::classify::Classification;
+use crate::proxy::close::CloseReason;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
+
+const FLOW_CHANNEL_CAPACITY: usize = 10_000;
+/// Maximum rows written per transaction.
+const MAX_BATCH: usize = 256;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Default and maximum rows returned by a query.
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// Close reason of a connection that ended normally.
+pub const CLOSE_NORMAL: &str = "closed";
+
+/// One forwarded connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlowRecord {
+    /// Row ID (None until stored)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub started_at: DateTime<Utc>,
+    pub username: String,
+    /// "ssh", "socks5", "masque" or "masque-udp"
+    pub protocol: String,
+    pub source_ip: String,
+    pub dest_host: String,
+    pub dest_ip: Option<String>,
+    pub dest_port: u16,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub duration_ms: u64,
+    /// [`CLOSE_NORMAL`] or a metrics error type (`acl_denied`, `relay_error`, ...)
+    pub close_reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// `tls`, `http`, `ssh` or `unknown`, from the first bytes the client sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_protocol: Option<String>,
+    /// TLS SNI or HTTP `Host` of the stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    /// HTTP request line or SMTP greeting (`logging.flows.payload_metadata`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_line: Option<String>,
+}
+
+impl FlowRecord {
+    /// A connection that started `elapsed` ago and is closing now, with no
+    /// bytes transferred and [`CLOSE_NORMAL`] as reason.
+    pub fn closing(
+        protocol: &str,
+        username: &str,
+        source_ip: &str,
+        dest_host: &str,
+        dest_port: u16,
+        elapsed: Duration,
+    ) -> Self {
+        let duration = chrono::Duration::from_std(elapsed).unwrap_or_default();
+        Self {
+            id: None,
+            started_at: Utc::now() - duration,
+            username: username.to_string(),
+            protocol: protocol.to_string(),
+            source_ip: source_ip.to_string(),
+            dest_host: dest_host.to_string(),
+            dest_ip: None,
+            dest_port,
+            bytes_up: 0,
+            bytes_down: 0,
+            duration_ms: elapsed.as_millis() as u64,
+            close_reason: CLOSE_NORMAL.to_string(),
+            correlation_id: None,
+            app_protocol: None,
+            server_name: None,
+            first_line: None,
+        }
+    }
+
+    /// Record what the client sent first, if the relay saw any data.
+    pub fn with_traffic(self, traffic: Option<&Classification>) -> Self {
+        match traffic {
+            Some(c) => Self {
+                app_protocol: Some(c.protocol.as_str().to_string()),
+                server_name: c.server_name.clone(),
+                first_line: c.first_line.clone(),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// A relay the ACL ended after it started (`acl.inspect_server_names`)
+    /// is recorded as `acl_denied`; other relays closed normally.
+    pub fn with_relay_close(self, reason: CloseReason) -> Self {
+        match reason {
+            CloseReason::AclDenied => Self {
+                close_reason: reason.as_str().to_string(),
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Filters for [`FlowStore::query`]. All set filters must match.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct FlowQuery {
+    pub user: Option<String>,
+    pub source_ip: Option<String>,
+    /// Matches the destination hostname or resolved IP
+    pub dest: Option<String>,
+    pub port: Option<u16>,
+    pub close_reason: Option<String>,
+    /// Connection correlation ID (`conn_id` in logs and audit events)
+    pub correlation_id: Option<String>,
+    /// `tls`, `http`, `ssh` or `unknown`
+    pub app_protocol: Option<String>,
+    /// Only flows started at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Only flows started before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+    /// Only rows with an ID below this (paging, newest first)
+    #[serde(rename = "before")]
+    pub before_id: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl FlowQuery {
+    pub fn effective_limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT)
+    }
+}
+
+/// SQLite-backed flow table.
+pub struct FlowStore {
+    conn: Mutex<Connection>,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS flows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL,
+    username TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    source_ip TEXT NOT NULL,
+    dest_host TEXT NOT NULL,
+    dest_ip TEXT,
+    dest_port INTEGER NOT NULL,
+    bytes_up INTEGER NOT NULL,
+    bytes_down INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    close_reason TEXT NOT NULL,
+    correlation_id TEXT,
+    app_protocol TEXT,
+    server_name TEXT,
+    first_line TEXT
+);
+CREATE INDEX IF NOT EXISTS flows_started_at ON flows(started_at);
+CREATE INDEX IF NOT EXISTS flows_username ON flows(username, started_at);
+";
+
+/// Columns added after the first release, for databases created before them.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("app_protocol", "TEXT"),
+    ("server_name", "TEXT"),
+    ("first_line", "TEXT"),
+];
+
+fn add_missing_columns(conn: &Connection) -> rusqlite::Result<()> {
+    let existing: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('flows')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (name, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == name) {
+            conn.execute_batch(&format!("ALTER TABLE flows ADD COLUMN {name} {kind}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn to_millis(t: &DateTime<Utc>) -> i64 {
+    t.timestamp_millis()
+}
+
+fn from_millis(ms: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(ms).single().unwrap_or_default()
+}
+
+fn to_i64(v: u64) -> i64 {
+    i64::try_from(v).unwrap_or(i64::MAX)
+}
+
+impl FlowStore {
+    /// Open (or create) the database at `path`.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::init(conn)
+    }
+
+    /// In-memory database (tests, demo).
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        add_missing_columns(&conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Insert records in one transaction.
+    pub fn insert(&self, records: &[FlowRecord]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO flows (started_at, username, protocol, source_ip, dest_host, \
+                 dest_ip, dest_port, bytes_up, bytes_down, duration_ms, close_reason, \
+                 correlation_id, app_protocol, server_name, first_line) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            )?;
+            for r in records {
+                stmt.execute(rusqlite::params![
+                    to_millis(&r.started_at),
+                    r.username,
+                    r.protocol,
+                    r.source_ip,
+                    r.dest_host,
+                    r.dest_ip,
+                    r.dest_port,
+                    to_i64(r.bytes_up),
+                    to_i64(r.bytes_down),
+                    to_i64(r.duration_ms),
+                    r.close_reason,
+                    r.correlation_id,
+                    r.app_protocol,
+                    r.server_name,
+                    r.first_line,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Matching flows, newest first.
+    pub fn query(&self, q: &FlowQuery) -> rusqlite::Result<Vec<FlowRecord>> {
+        let mut sql = String::from(
+            "SELECT id, started_at, username, protocol, source_ip, dest_host, dest_ip, \
+             dest_port, bytes_up, bytes_down, duration_ms, close_reason, correlation_id, \
+             app_protocol, server_name, first_line FROM flows WHERE 1=1",
+        );
+        let mut args: Vec<rusqlite::types::Value> = Vec::new();
+        let mut push = |clause: &str, values: &[rusqlite::types::Value]| {
+            sql.push_str(clause);
+            args.extend_from_slice(values);
+        };
+        if let Some(ref v) = q.user {
+            push(" AND username = ?", &[v.clone().into()]);
+        }
+        if let Some(ref v) = q.source_ip {
+            push(" AND source_ip = ?", &[v.clone().into()]);
+        }
+        if let Some(ref v) = q.dest {
+            push(
+                " AND (dest_host = ? OR dest_ip = ?)",
+                &[v.clone().into(), v.clone().into()],
+            );
+        }
+        if let Some(v) = q.port {
+            push(" AND dest_port = ?", &[i64::from(v).into()]);
+        }
+        if let Some(ref v) = q.close_reason {
+            push(" AND close_reason = ?", &[v.clone().into()]);
+        }
+        if let Some(ref v) = q.correlation_id {
+            push(" AND correlation_id = ?", &[v.clone().into()]);
+        }
+        if let Some(ref v) = q.app_protocol {
+            push(" AND app_protocol = ?", &[v.clone().into()]);
+        }
+        if let Some(ref v) = q.since {
+            push(" AND started_at >= ?", &[to_millis(v).into()]);
+        }
+        if let Some(ref v) = q.until {
+            push(" AND started_at < ?", &[to_millis(v).into()]);
+        }
+        if let Some(v) = q.before_id {
+            push(" AND id < ?", &[v.into()]);
+        }
+        push(
+            " ORDER BY id DESC LIMIT ?",
+            &[(q.effective_limit() as i64).into()],
+        );
+
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args), |row| {
+            Ok(FlowRecord {
+                id: Some(row.get(0)?),
+                started_at: from_millis(row.get(1)?),
+                username: row.get(2)?,
+                protocol: row.get(3)?,
+                source_ip: row.get(4)?,
+                dest_host: row.get(5)?,
+                dest_ip: row.get(6)?,
+                dest_port: row.get(7)?,
+                bytes_up: row.get::<_, i64>(8)?.max(0) as u64,
+                bytes_down: row.get::<_, i64>(9)?.max(0) as u64,
+                duration_ms: row.get::<_, i64>(10)?.max(0) as u64,
+                close_reason: row.get(11)?,
+                correlation_id: row.get(12)?,
+                app_protocol: row.get(13)?,
+                server_name: row.get(14)?,
+                first_line: row.get(15)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Delete flows that started before `cutoff`. Returns the number removed.
+    pub fn prune_before(&self, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.execute(
+            "DELETE FROM flows WHERE started_at < ?1",
+            [to_millis(&cutoff)],
+        )
+    }
+
+    pub fn count(&self) -> rusqlite::Result<u64> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.query_row("SELECT COUNT(*) FROM flows", [], |row| row.get::<_, i64>(0))
+            .map(|n| n.max(0) as u64)
+    }
+}
+
+/// Asynchronous flow recorder in front of a [`FlowStore`].
+pub struct FlowLog {
+    sender: mpsc::Sender<FlowRecord>,
+    store: Arc<FlowStore>,
+    dropped: AtomicU64,
+}
+
+impl FlowLog {
+    /// Open the configured database and start the writer and retention tasks.
+    pub fn start(config: &FlowLogConfig) -> anyhow::Result<Arc<Self>> {
+        let store =
+            Arc::new(FlowStore::open(&config.path).map_err(|e| {
+                anyhow::anyhow!("opening flow log {}: {}", config.path.display(), e)
+            })?);
+        Ok(Self::with_store(store, config.retention_days))
+    }
+
+    /// Start the background tasks on an existing store.
+    pub fn with_store(store: Arc<FlowStore>, retention_days: u32) -> Arc<Self> {
+        let (sender, receiver) = mpsc::channel(FLOW_CHANNEL_CAPACITY);
+        tokio::spawn(flow_writer_task(receiver, store.clone()));
+        if retention_days > 0 {
+            tokio::spawn(flow_retention_task(store.clone(), retention_days));
+        }
+        Arc::new(Self {
+            sender,
+            store,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Queue a flow for storage (dropped if the writer is backlogged).
+    pub fn record(&self, flow: FlowRecord) {
+        if self.sender.try_send(flow).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % 100 == 1 {
+                warn!(total_dropped = dropped, "Flow records being dropped");
+            }
+        }
+    }
+
+    /// Number of flow records dropped due to channel overflow.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Query stored flows off the async runtime.
+    pub async fn query(&self, q: FlowQuery) -> anyhow::Result<Vec<FlowRecord>> {
+        let store = self.store.clone();
+        let rows = tokio::task::spawn_blocking(move || store.query(&q)).await??;
+        Ok(rows)
+    }
+}
+
+async fn flow_writer_task(mut receiver: mpsc::Receiver<FlowRecord>, store: Arc<FlowStore>) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    while receiver.recv_many(&mut batch, MAX_BATCH).await > 0 {
+        let records = std::mem::take(&mut batch);
+        let count = records.len();
+        let store = store.clone();
+        match tokio::task::spawn_blocking(move || store.insert(&records)).await {
+            Ok(Ok(())) => debug!(count, "Flow records written"),
+            Ok(Err(e)) => error!(error = %e, count, "Failed to write flow records"),
+            Err(e) => error!(error = %e, "Flow writer task panicked"),
+        }
+    }
+}
+
+async fn flow_retention_task(store: Arc<FlowStore>, retention_days: u32) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(retention_days));
+        let store = store.clone();
+        match tokio::task::spawn_blocking(move || store.prune_before(cutoff)).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(n)) => debug!(removed = n, "Pruned expired flow records"),
+            Ok(Err(e)) => error!(error = %e, "Failed to prune flow records"),
+            Err(e) => error!(error = %e, "Flow retention task panicked"),
+        }
+    }
+}
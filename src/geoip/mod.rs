@@ -43,6 +43,11 @@ impl GeoIpService {
         }
     }
 
+    /// True if the database was opened.
+    pub fn has_database(&self) -> bool {
+        self.reader.is_some()
+    }
+
     /// Check if an IP is allowed by GeoIP rules. Returns true if no GeoIP filtering is active.
     pub fn is_allowed(&self, ip: &IpAddr) -> bool {
         if self.reader.is_none() {
@@ -53,7 +58,7 @@ impl GeoIpService {
             return true;
         }
 
-        let country_code = match self.lookup_country(ip) {
+        let country_code = match self.country(ip) {
             Some(c) => c,
             None => return !self.fail_closed,
         };
@@ -69,10 +74,29 @@ impl GeoIpService {
         true
     }
 
-    fn lookup_country(&self, ip: &IpAddr) -> Option<String> {
+    /// ISO 3166-1 alpha-2 country code of `ip`, if the database knows it.
+    pub fn country(&self, ip: &IpAddr) -> Option<String> {
         let reader = self.reader.as_ref()?;
         let lookup = reader.lookup(*ip).ok()?;
         let result: maxminddb::geoip2::Country = lookup.decode().ok()??;
         result.country.iso_code.map(|s| s.to_string())
     }
+
+    /// Approximate (latitude, longitude) of `ip`, if the database is a City
+    /// database that knows it.
+    pub fn location(&self, ip: &IpAddr) -> Option<(f64, f64)> {
+        let reader = self.reader.as_ref()?;
+        let lookup = reader.lookup(*ip).ok()?;
+        let result: maxminddb::geoip2::City = lookup.decode().ok()??;
+        Some((result.location.latitude?, result.location.longitude?))
+    }
+
+    /// Autonomous system number of `ip`, if the database is an ASN database
+    /// that knows it.
+    pub fn asn(&self, ip: &IpAddr) -> Option<u32> {
+        let reader = self.reader.as_ref()?;
+        let lookup = reader.lookup(*ip).ok()?;
+        let result: maxminddb::geoip2::Asn = lookup.decode().ok()??;
+        result.autonomous_system_number
+    }
 }
@@ -0,0 +1,357 @@
+//! Listening socket handover (`server.socket_handover`).
+//!
+//! Every TCP listener (SSH, SOCKS5, API, metrics, gossip) is bound through
+//! [`Listeners::bind`], which first looks for a socket passed in by the
+//! parent process, the way systemd passes them: descriptors 3 and up,
+//! counted by `LISTEN_FDS` and named by `LISTEN_FDNAMES`. A passed socket is
+//! matched by name, else by local address, so systemd socket activation and
+//! the systemd FD store work too.
+//!
+//! With handover enabled, `SIGUSR2` upgrades the binary in place: s5 starts
+//! its executable again (the new binary once it has been replaced on disk)
+//! with the same arguments and its listening sockets, waits until the new
+//! process is serving, then stops accepting and drains its sessions as on
+//! `SIGTERM`. Both processes share the kernel accept queues, so no client is
+//! refused in between, and sessions already open stay on the old process
+//! until they end or `server.shutdown_timeout` runs out. If the new process
+//! exits or is not ready within [`READY_TIMEOUT`], it is killed and the old
+//! one keeps serving.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long a new process may take to start serving
+pub const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// First descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+pub const LISTEN_FDS_START: i32 = 3;
+
+/// Descriptor the new process writes to once it is serving
+const READY_FD_ENV: &str = "S5_HANDOVER_READY_FD";
+
+static GLOBAL: OnceLock<Listeners> = OnceLock::new();
+
+/// The process-wide listeners, with the sockets passed in the environment.
+pub fn global() -> &'static Listeners {
+    GLOBAL.get_or_init(Listeners::from_env)
+}
+
+/// Names and descriptors passed by `LISTEN_PID`, `LISTEN_FDS` and
+/// `LISTEN_FDNAMES`, for process `pid`. Nothing when `LISTEN_PID` names
+/// another process; unnamed descriptors are called `unknown`.
+pub fn listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    names: Option<&str>,
+    pid: u32,
+) -> Vec<(String, i32)> {
+    if listen_pid.is_some_and(|p| p.trim().parse::<u32>().ok() != Some(pid)) {
+        return Vec::new();
+    }
+    let Some(count) = listen_fds.and_then(|n| n.trim().parse::<i32>().ok()) else {
+        return Vec::new();
+    };
+    let mut names = names.unwrap_or_default().split(':');
+    (0..count.max(0))
+        .map(|i| {
+            let name = names.next().filter(|n| !n.is_empty()).unwrap_or("unknown");
+            (name.to_string(), LISTEN_FDS_START + i)
+        })
+        .collect()
+}
+
+/// Listening sockets received from the parent process and handed to the
+/// next one.
+pub struct Listeners {
+    /// Passed in and not claimed yet
+    inherited: Mutex<Vec<(String, std::net::TcpListener)>>,
+    /// Copies of the sockets in use, recorded when handover is enabled
+    bound: Mutex<Vec<(String, std::net::TcpListener)>>,
+    /// Names of the listeners up so far
+    up: Mutex<Vec<String>>,
+    enabled: AtomicBool,
+    /// Write end of the parent's readiness pipe
+    ready: Mutex<Option<std::fs::File>>,
+    upgrading: AtomicBool,
+}
+
+impl Listeners {
+    pub fn new(inherited: Vec<(String, std::net::TcpListener)>) -> Self {
+        Self {
+            inherited: Mutex::new(inherited),
+            bound: Mutex::new(Vec::new()),
+            up: Mutex::new(Vec::new()),
+            enabled: AtomicBool::new(false),
+            ready: Mutex::new(None),
+            upgrading: AtomicBool::new(false),
+        }
+    }
+
+    /// Take the sockets passed in the environment and clear it, so they
+    /// are not passed on to anything else.
+    #[cfg(unix)]
+    pub fn from_env() -> Self {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        let var = |key: &str| std::env::var(key).ok();
+        let fds = listen_fds(
+            var("LISTEN_PID").as_deref(),
+            var("LISTEN_FDS").as_deref(),
+            var("LISTEN_FDNAMES").as_deref(),
+            std::process::id(),
+        );
+        let ready = var(READY_FD_ENV).and_then(|fd| fd.parse::<i32>().ok());
+        for key in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES", READY_FD_ENV] {
+            // SAFETY: read once at startup, before anything else reads them
+            unsafe {
+                std::env::remove_var(key);
+            }
+        }
+
+        let inherited = fds
+            .into_iter()
+            .map(|(name, fd)| {
+                // SAFETY: the parent passed this descriptor for us to own
+                let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                let _ = socket2::SockRef::from(&fd).set_cloexec(true);
+                (name, std::net::TcpListener::from(fd))
+            })
+            .collect::<Vec<_>>();
+        if !inherited.is_empty() {
+            let names: Vec<&str> = inherited.iter().map(|(n, _)| n.as_str()).collect();
+            info!(sockets = ?names, "Listening sockets passed by the parent process");
+        }
+        let listeners = Self::new(inherited);
+        if let Some(fd) = ready {
+            // SAFETY: the parent passed the write end of its pipe
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let _ = socket2::SockRef::from(&fd).set_cloexec(true);
+            *listeners.ready.lock().unwrap() = Some(std::fs::File::from(fd));
+        }
+        listeners
+    }
+
+    /// Nothing is passed between processes outside Unix.
+    #[cfg(not(unix))]
+    pub fn from_env() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// Record the sockets bound from now on, for `SIGUSR2` upgrades.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Listen on `addr`: with the passed socket named `name` or bound to
+    /// `addr` if there is one, else with a new socket.
+    pub async fn bind(&self, name: &str, addr: &str) -> std::io::Result<tokio::net::TcpListener> {
+        let listener = match self.take(name, addr).await {
+            Some(listener) => {
+                info!(name = %name, addr = %addr, "Using passed listening socket");
+                listener
+            }
+            None => tokio::net::TcpListener::bind(addr).await?.into_std()?,
+        };
+        if self.enabled() {
+            let copy = listener.try_clone()?;
+            self.bound.lock().unwrap().push((name.to_string(), copy));
+        }
+        listener.set_nonblocking(true)?;
+        self.mark_bound(name);
+        tokio::net::TcpListener::from_std(listener)
+    }
+
+    /// Record that listener `name` is up, when bound outside [`Self::bind`].
+    pub fn mark_bound(&self, name: &str) {
+        self.up.lock().unwrap().push(name.to_string());
+    }
+
+    /// Whether listener `name` is up.
+    pub fn is_bound(&self, name: &str) -> bool {
+        self.up.lock().unwrap().iter().any(|n| n == name)
+    }
+
+    async fn take(&self, name: &str, addr: &str) -> Option<std::net::TcpListener> {
+        if self.inherited.lock().unwrap().is_empty() {
+            return None;
+        }
+        let wanted: Vec<SocketAddr> = tokio::net::lookup_host(addr)
+            .await
+            .map(|addrs| addrs.collect())
+            .unwrap_or_default();
+        let mut inherited = self.inherited.lock().unwrap();
+        let index = inherited.iter().position(|(n, _)| n == name).or_else(|| {
+            inherited
+                .iter()
+                .position(|(_, l)| l.local_addr().is_ok_and(|local| wanted.contains(&local)))
+        })?;
+        Some(inherited.remove(index).1)
+    }
+
+    /// Names of the passed sockets no listener claimed.
+    pub fn unclaimed(&self) -> Vec<String> {
+        let inherited = self.inherited.lock().unwrap();
+        inherited.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Tell the parent process (after an upgrade) and systemd (`READY=1`)
+    /// that we are serving, and close the passed sockets nobody claimed.
+    pub fn notify_ready(&self) {
+        let unclaimed = std::mem::take(&mut *self.inherited.lock().unwrap());
+        if !unclaimed.is_empty() {
+            let names: Vec<&str> = unclaimed.iter().map(|(n, _)| n.as_str()).collect();
+            warn!(sockets = ?names, "Closing passed listening sockets no listener uses");
+        }
+        if let Some(mut ready) = self.ready.lock().unwrap().take() {
+            use std::io::Write;
+            if let Err(e) = ready.write_all(b"1") {
+                warn!(error = %e, "Failed to notify the parent process");
+            }
+        }
+        sd_notify("READY=1");
+    }
+
+    /// Start the executable again with our listening sockets and wait until
+    /// it is serving. Returns its PID; the caller then drains this process.
+    pub async fn upgrade(&self) -> anyhow::Result<u32> {
+        anyhow::ensure!(self.enabled(), "server.socket_handover is disabled");
+        anyhow::ensure!(
+            !self.upgrading.swap(true, Ordering::SeqCst),
+            "an upgrade is already in progress"
+        );
+        let result = self.spawn_successor().await;
+        if result.is_err() {
+            self.upgrading.store(false, Ordering::SeqCst);
+        }
+        result
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn spawn_successor(&self) -> anyhow::Result<u32> {
+        anyhow::bail!("socket handover is only supported on Linux")
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn spawn_successor(&self) -> anyhow::Result<u32> {
+        use anyhow::Context;
+        use std::io::Read;
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+        use std::os::unix::process::CommandExt;
+
+        let (mut reader, writer) = std::io::pipe()?;
+        let (names, mut fds) = {
+            let bound = self.bound.lock().unwrap();
+            let names: Vec<String> = bound.iter().map(|(n, _)| n.clone()).collect();
+            let fds: Vec<i32> = bound.iter().map(|(_, l)| l.as_raw_fd()).collect();
+            (names, fds)
+        };
+        fds.push(writer.as_raw_fd());
+
+        // Copy every descriptor above its target slot first, so that moving
+        // them into 3.. in the child never overwrites one not moved yet
+        let first_free = LISTEN_FDS_START + fds.len() as i32;
+        let high = fds
+            .iter()
+            .map(|&fd| {
+                // SAFETY: fcntl on a descriptor we own; the result is new
+                let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, first_free) };
+                if copy < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // SAFETY: a fresh descriptor nothing else owns
+                Ok(unsafe { OwnedFd::from_raw_fd(copy) })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let raw: Vec<i32> = high.iter().map(|fd| fd.as_raw_fd()).collect();
+
+        let exe = current_exe()?;
+        let mut command = std::process::Command::new(&exe);
+        command
+            .args(std::env::args_os().skip(1))
+            .env("LISTEN_FDS", names.len().to_string())
+            .env("LISTEN_FDNAMES", names.join(":"))
+            .env(
+                READY_FD_ENV,
+                (LISTEN_FDS_START + names.len() as i32).to_string(),
+            );
+        // SAFETY: only dup2 (async-signal-safe) runs between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                for (slot, &fd) in (LISTEN_FDS_START..).zip(raw.iter()) {
+                    if libc::dup2(fd, slot) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("starting {}", exe.display()))?;
+        drop(high);
+        drop(writer);
+        let pid = child.id();
+        info!(pid, exe = %exe.display(), sockets = ?names, "Started new process for upgrade");
+
+        let wait = tokio::task::spawn_blocking(move || {
+            let mut byte = [0u8; 1];
+            reader.read(&mut byte).map(|n| n == 1)
+        });
+        let ready = matches!(
+            tokio::time::timeout(READY_TIMEOUT, wait).await,
+            Ok(Ok(Ok(true)))
+        );
+        if !ready {
+            let _ = child.kill();
+            let status = tokio::task::spawn_blocking(move || child.wait()).await??;
+            anyhow::bail!("new process {} did not become ready ({})", pid, status);
+        }
+        sd_notify(&format!("MAINPID={}", pid));
+        Ok(pid)
+    }
+}
+
+/// Our executable; on Linux it reads ` (deleted)` once replaced on disk,
+/// and the new binary is at the original path.
+#[cfg(target_os = "linux")]
+fn current_exe() -> std::io::Result<std::path::PathBuf> {
+    let exe = std::env::current_exe()?;
+    Ok(
+        match exe.to_str().and_then(|p| p.strip_suffix(" (deleted)")) {
+            Some(path) => std::path::PathBuf::from(path),
+            None => exe,
+        },
+    )
+}
+
+/// Send `state` to the systemd notification socket, if there is one.
+#[cfg(target_os = "linux")]
+fn sd_notify(state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(&path),
+    };
+    let sent = addr.and_then(|addr| {
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &addr)
+    });
+    if let Err(e) = sent {
+        warn!(error = %e, state = %state, "Failed to notify systemd");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sd_notify(_state: &str) {}
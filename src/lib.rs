@@ -3,16 +3,31 @@ pub mod api;
 pub mod audit;
 pub mod auth;
 pub mod cli;
+pub mod cluster;
 pub mod config;
 pub mod context;
+pub mod ctl;
 pub mod demo;
+pub mod diagnostics;
+pub mod flows;
 pub mod geoip;
+pub mod handover;
+pub mod log_filter;
+#[cfg(feature = "masque")]
+pub mod masque;
 pub mod metrics;
 pub mod motd;
+pub mod notifications;
+pub mod paths;
 pub mod proxy;
 pub mod quota;
+pub mod reports;
+pub mod sandbox;
+#[cfg(feature = "lua-hooks")]
+pub mod scripting;
 pub mod security;
 pub mod server;
+pub mod service;
 pub mod shell;
 pub mod socks;
 pub mod ssh;
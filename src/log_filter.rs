@@ -0,0 +1,96 @@
+//! Runtime-adjustable log filter.
+//!
+//! The process-wide `EnvFilter` is installed behind a reload handle so the
+//! level and per-module directives (`info,s5::proxy=trace`) can be changed
+//! from `PUT /api/logging/level` without a restart. The filter given at
+//! startup is kept as the default to return to. Changes are not persisted:
+//! the next start uses `logging.level` (or `--log-level`) again.
+
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::registry::Registry;
+use tracing_subscriber::reload;
+
+/// Filter layer to install first on the tracing registry.
+pub type FilterLayer = reload::Layer<EnvFilter, Registry>;
+
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives applied at startup
+    default: String,
+    /// Directives currently applied
+    current: Mutex<String>,
+}
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Current and startup directives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLevel {
+    pub level: String,
+    pub default: String,
+}
+
+/// Errors from [`set_level`].
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("runtime log level changes are not available")]
+    NotInstalled,
+    #[error("invalid filter directives: {0}")]
+    Invalid(String),
+}
+
+/// Build the filter layer from `directives` (falling back to `info` if they
+/// do not parse) and register its handle. Only the first call registers.
+pub fn layer(directives: &str) -> FilterLayer {
+    let (directives, filter) = match EnvFilter::try_new(directives) {
+        Ok(filter) => (directives.to_string(), filter),
+        Err(_) => ("info".to_string(), EnvFilter::new("info")),
+    };
+    let (layer, handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER.set(LogFilter {
+        handle,
+        default: directives.clone(),
+        current: Mutex::new(directives),
+    });
+    layer
+}
+
+/// Directives in effect, or None before logging is set up.
+pub fn level() -> Option<LogLevel> {
+    let filter = LOG_FILTER.get()?;
+    let level = filter
+        .current
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    Some(LogLevel {
+        level,
+        default: filter.default.clone(),
+    })
+}
+
+/// Replace the filter with `directives`. Returns the previous directives.
+pub fn set_level(directives: &str) -> Result<String, LogFilterError> {
+    let filter = LOG_FILTER.get().ok_or(LogFilterError::NotInstalled)?;
+    let directives = directives.trim();
+    if directives.is_empty() {
+        return Err(LogFilterError::Invalid("empty filter".to_string()));
+    }
+    let new_filter =
+        EnvFilter::try_new(directives).map_err(|e| LogFilterError::Invalid(e.to_string()))?;
+    let mut current = filter.current.lock().unwrap_or_else(|e| e.into_inner());
+    filter
+        .handle
+        .reload(new_filter)
+        .map_err(|_| LogFilterError::NotInstalled)?;
+    Ok(std::mem::replace(&mut *current, directives.to_string()))
+}
+
+/// Return to the startup directives. Returns the previous directives.
+pub fn reset_level() -> Result<String, LogFilterError> {
+    let filter = LOG_FILTER.get().ok_or(LogFilterError::NotInstalled)?;
+    set_level(&filter.default)
+}
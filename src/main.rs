@@ -2,31 +2,81 @@ use anyhow::Result;
 use clap::Parser;
 use tracing::{error, info};
 
-use s5::cli::{Cli, Command};
+use s5::cli::{Cli, Command, ServiceCommand};
 use s5::config;
 use std::collections::HashMap;
 
 use s5::config::types::{
-    AlertingConfig, AppConfig, ConnectionPoolConfig, GlobalAclConfig, LogFormat, LoggingConfig,
-    MotdConfig, SecurityConfig, ServerConfig, ShellConfig, UserAclConfig, UserConfig, UserRole,
+    AlertingConfig, AppConfig, ConnectionPoolConfig, GlobalAclConfig, HostKeyRotationConfig,
+    LogFormat, LoggingConfig, MotdConfig, SecurityConfig, ServerConfig, ShellConfig, UserAclConfig,
+    UserConfig, UserRole,
 };
 
-fn setup_logging(level: &str, format: LogFormat) {
-    use tracing_subscriber::EnvFilter;
+fn setup_logging(level: &str, format: LogFormat, event_source: Option<&str>) -> Result<()> {
+    use tracing_subscriber::prelude::*;
 
-    let filter = EnvFilter::try_new(level).unwrap_or_else(|_| EnvFilter::new("info"));
+    // Reloadable, so `PUT /api/logging/level` can change it at runtime
+    let filter = s5::log_filter::layer(level);
+    let event_log = event_source.map(s5::service::event_log_layer).transpose()?;
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(event_log)
+        .with(s5::diagnostics::errors::RecentErrorsLayer);
 
     match format {
-        LogFormat::Json => {
-            tracing_subscriber::fmt()
-                .json()
-                .with_env_filter(filter)
-                .init();
-        }
-        LogFormat::Pretty => {
-            tracing_subscriber::fmt().with_env_filter(filter).init();
-        }
+        LogFormat::Json => registry
+            .with(tracing_subscriber::fmt::layer().json())
+            .init(),
+        LogFormat::Pretty => registry.with(tracing_subscriber::fmt::layer()).init(),
     }
+    Ok(())
+}
+
+/// `s5 service run`. The Service Control Manager starts us in `System32`,
+/// so the config path is made absolute and relative paths in the config
+/// (host key, flow database, ...) resolve under the state directory.
+fn run_service(cli: &Cli, name: &str) -> Result<()> {
+    let config_path = std::path::absolute(&cli.config)?;
+    let state_dir = s5::paths::state_dir();
+    std::fs::create_dir_all(&state_dir)?;
+    std::env::set_current_dir(&state_dir)?;
+
+    let loaded = config::load_config(&config_path).and_then(|mut cfg| {
+        config::env::apply_env_overrides(&mut cfg)?;
+        Ok(cfg)
+    });
+    let app_config = match loaded {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            // Nobody sees stderr here; report through the Event Log
+            setup_logging("info", LogFormat::Pretty, Some(name))?;
+            error!(error = %e, path = %config_path.display(), "Invalid configuration");
+            return Err(e);
+        }
+    };
+    let log_level = cli
+        .log_level
+        .clone()
+        .unwrap_or_else(|| app_config.logging.level.to_string());
+    setup_logging(&log_level, app_config.logging.format, Some(name))?;
+
+    s5::service::run(
+        name,
+        Box::new(move |shutdown| {
+            info!(
+                version = env!("CARGO_PKG_VERSION"),
+                ssh_listen = %app_config.server.ssh_listen,
+                state_dir = %state_dir.display(),
+                "Starting s5 proxy server (Windows service)"
+            );
+            let rt = tokio::runtime::Runtime::new()?;
+            rt.block_on(s5::server::run_until(
+                app_config,
+                Some(config_path),
+                shutdown,
+            ))
+        }),
+    )
 }
 
 fn main() -> Result<()> {
@@ -34,7 +84,12 @@ fn main() -> Result<()> {
 
     match &cli.command {
         Some(Command::HashPassword { password }) => {
-            return s5::auth::password::hash_password_cli(password.as_deref());
+            let security = if cli.config.exists() {
+                config::load_config(&cli.config)?.security
+            } else {
+                Default::default()
+            };
+            return s5::auth::password::hash_password_cli(password.as_deref(), &security);
         }
         Some(Command::CheckConfig) => {
             let cfg = config::load_config(&cli.config)?;
@@ -109,7 +164,7 @@ fn main() -> Result<()> {
                 eprintln!();
             }
 
-            setup_logging("info", app_config.logging.format);
+            setup_logging("info", app_config.logging.format, None)?;
             info!(
                 version = env!("CARGO_PKG_VERSION"),
                 ssh_listen = %app_config.server.ssh_listen,
@@ -218,7 +273,7 @@ fn main() -> Result<()> {
             println!("    Port {}", port);
             println!("    User {}", user);
             println!("    StrictHostKeyChecking no");
-            println!("    UserKnownHostsFile /dev/null");
+            println!("    UserKnownHostsFile {}", s5::paths::NULL_DEVICE);
             if let Some(dyn_port) = dynamic_forward {
                 println!("    DynamicForward {}", dyn_port);
             }
@@ -265,17 +320,7 @@ fn main() -> Result<()> {
         }
         Some(Command::ShowConfig { format }) => {
             // Load config the same way as the server does
-            let app_config = if cli.config.exists() {
-                let mut cfg = config::load_config(&cli.config)?;
-                config::env::apply_env_overrides(&mut cfg);
-                cfg
-            } else if config::env::can_build_from_env() {
-                let cfg = config::env::build_config_from_env()?;
-                config::parse_config_validate(&cfg)?;
-                cfg
-            } else {
-                config::load_config(&cli.config)?
-            };
+            let app_config = config::load_effective_config(&cli.config)?;
 
             let redacted = config::redact::redact_config(&app_config);
 
@@ -294,6 +339,54 @@ fn main() -> Result<()> {
             }
             return Ok(());
         }
+        Some(Command::Doctor { json }) => {
+            return s5::diagnostics::doctor_cli(&cli.config, *json);
+        }
+        Some(Command::VerifyAudit { files, key }) => {
+            return s5::audit::chain::verify_files_cli(files, key.as_deref());
+        }
+        Some(Command::UpdateIntel { output }) => {
+            let ip_intel = if cli.config.exists() {
+                config::load_config(&cli.config)?.ip_intel
+            } else {
+                Default::default()
+            };
+            return s5::security::ip_intel::update_intel_cli(&ip_intel, output.as_deref());
+        }
+        Some(Command::Import {
+            passwd_file,
+            authorized_keys_dir,
+            write,
+        }) => {
+            if passwd_file.is_none() && authorized_keys_dir.is_none() {
+                anyhow::bail!("nothing to import: pass --passwd-file and/or --authorized-keys-dir");
+            }
+            let import = config::import::Import::read(
+                passwd_file.as_deref(),
+                authorized_keys_dir.as_deref(),
+            )?;
+            for warning in &import.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            if import.users.is_empty() {
+                anyhow::bail!("no users to import");
+            }
+
+            if *write {
+                let added = config::persist::add_users(&cli.config, &import.users)?;
+                let existing = import.users.len() - added.len();
+                eprintln!(
+                    "Added {} user(s) to {} ({} already defined)",
+                    added.len(),
+                    cli.config.display(),
+                    existing
+                );
+            } else {
+                print!("{}", config::import::to_toml(&import.users));
+                eprintln!("Imported {} user(s)", import.users.len());
+            }
+            return Ok(());
+        }
         Some(Command::Backup {
             output,
             api_addr,
@@ -354,6 +447,40 @@ fn main() -> Result<()> {
             })?;
             return Ok(());
         }
+        Some(Command::Service { action }) => {
+            return match action {
+                ServiceCommand::Install { name } => {
+                    s5::service::install(name, &cli.config)?;
+                    println!(
+                        "Service {} installed (config: {})",
+                        name,
+                        cli.config.display()
+                    );
+                    println!("Start it with: sc.exe start {}", name);
+                    Ok(())
+                }
+                ServiceCommand::Uninstall { name } => {
+                    s5::service::uninstall(name)?;
+                    println!("Service {} removed", name);
+                    Ok(())
+                }
+                ServiceCommand::Run { name } => run_service(&cli, name),
+            };
+        }
+        Some(Command::Ctl {
+            api_addr,
+            token,
+            json,
+            action,
+        }) => {
+            return s5::ctl::run_cli(
+                &cli.config,
+                api_addr.as_deref(),
+                token.as_deref(),
+                *json,
+                action,
+            );
+        }
         Some(Command::Demo {
             ssh_port,
             socks5_port,
@@ -378,7 +505,7 @@ fn main() -> Result<()> {
             );
             eprintln!();
 
-            setup_logging("info", demo_config.logging.format);
+            setup_logging("info", demo_config.logging.format, None)?;
             info!(
                 version = env!("CARGO_PKG_VERSION"),
                 ssh_port = ssh_port,
@@ -415,7 +542,7 @@ fn main() -> Result<()> {
     let (app_config, config_path) = if cli.config.exists() {
         let mut cfg = config::load_config(&cli.config)?;
         // Apply env var overrides (hybrid mode)
-        config::env::apply_env_overrides(&mut cfg);
+        config::env::apply_env_overrides(&mut cfg)?;
         (cfg, Some(cli.config.clone()))
     } else if config::env::can_build_from_env() {
         let cfg = config::env::build_config_from_env()?;
@@ -434,7 +561,11 @@ fn main() -> Result<()> {
         .as_deref()
         .map(|s| s.to_string())
         .unwrap_or_else(|| app_config.logging.level.to_string());
-    setup_logging(&log_level, app_config.logging.format);
+    let event_source = app_config
+        .logging
+        .event_log
+        .then_some(s5::service::DEFAULT_EVENT_SOURCE);
+    setup_logging(&log_level, app_config.logging.format, event_source)?;
 
     info!(
         version = env!("CARGO_PKG_VERSION"),
@@ -476,12 +607,24 @@ fn build_quick_config(
             socks5_tls_key: None,
             dns_cache_ttl: -1,
             dns_cache_max_entries: 1000,
+            dns_prefetch_hosts: 0,
             connect_retry: 0,
             connect_retry_delay_ms: 1000,
             bookmarks_path: None,
             ssh_keepalive_interval_secs: 15,
             ssh_keepalive_max: 3,
             ssh_auth_timeout: 120,
+            ssh_window_size: 2 * 1024 * 1024,
+            ssh_max_packet_size: 32 * 1024,
+            ssh_stall_threshold_ms: 5000,
+            max_connections: 0,
+            max_connections_per_user: 0,
+            connection_queue_timeout_ms: 0,
+            connection_queue_size: 0,
+            listeners: Vec::new(),
+            host_key_rotation: HostKeyRotationConfig::default(),
+            maintenance_message: "Server is under maintenance. Please try again later.".to_string(),
+            socket_handover: false,
         },
         shell: ShellConfig::default(),
         limits: Default::default(),
@@ -497,12 +640,18 @@ fn build_quick_config(
             username,
             password_hash: Some(password_hash),
             authorized_keys: Vec::new(),
-            allow_forwarding: true,
+            allow_forwarding: Some(true),
             allow_shell: true,
             max_new_connections_per_minute: 0,
             max_bandwidth_kbps: 0,
             source_ips: Vec::new(),
             expires_at: None,
+            disabled: false,
+            require_security_key: false,
+            allow_vpn: false,
+            vpn_address: None,
+            tags: Vec::new(),
+            authorized_keys_url: None,
             upstream_proxy: None,
             acl: UserAclConfig::default(),
             totp_secret: None,
@@ -511,8 +660,10 @@ fn build_quick_config(
             group: None,
             role: UserRole::User,
             shell_permissions: None,
+            terminal: None,
             motd: None,
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: None,
             idle_warning_secs: None,
@@ -522,12 +673,42 @@ fn build_quick_config(
             aliases: HashMap::new(),
             max_connections: None,
             rate_limits: None,
+            api_token_hash: None,
+            api_tokens: Vec::new(),
+            ip_guard_exemptions: None,
+            jump_targets: None,
+            unix_sockets: None,
+            allowed_hassh: None,
+            subsystems: None,
+            impossible_travel: None,
+            password_changed_at: None,
         }],
         groups: Vec::new(),
+        tenants: Default::default(),
         motd: MotdConfig::default(),
         alerting: AlertingConfig::default(),
         maintenance_windows: Vec::new(),
+        quota_plans: Default::default(),
         connection_pool: ConnectionPoolConfig::default(),
+        threat_intel: Default::default(),
+        ip_intel: Default::default(),
+        honeypot: Default::default(),
+        login_anomaly: Default::default(),
+        impossible_travel: Default::default(),
+        invitations: Default::default(),
+        key_enrollment: Default::default(),
+        authorized_keys_fetch: Default::default(),
+        notifications: Default::default(),
+        reports: Default::default(),
+        cluster: Default::default(),
+        sandbox: Default::default(),
+        vpn: Default::default(),
+        subsystems: Vec::new(),
+        proxy: Default::default(),
+        policy: Default::default(),
+        scripting: None,
+        masque: None,
+        gssapi: None,
     }
 }
 
@@ -0,0 +1,587 @@
+//! One MASQUE request: CONNECT to a TCP destination, or CONNECT-UDP
+//! (RFC 9298) to a UDP one. The request authenticates with a
+//! `Proxy-Authorization: Basic` header and goes through the same checks as
+//! a SOCKS5 login and CONNECT.
+
+use super::udp::UdpTunnel;
+use crate::audit::events::AuditEvent;
+use crate::auth::login::{self, PasswordAttempt, PasswordOutcome, Refusal};
+use crate::auth::user::User;
+use crate::context::AppContext;
+use crate::flows::FlowRecord;
+use crate::proxy::forwarder::{RelayConfig, ServerNameCheck};
+use crate::proxy::ConnectionGuard;
+use anyhow::Result;
+use base64::Engine;
+use bytes::{Buf, Bytes};
+use http::{header, Method, Request, Response, StatusCode};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// The request stream of an HTTP/3 request.
+pub type Stream = h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
+
+/// Path prefix of CONNECT-UDP targets (RFC 9298, 3: the default URI template
+/// `https://$PROXY/.well-known/masque/udp/{target_host}/{target_port}/`).
+pub const UDP_PATH_PREFIX: &str = "/.well-known/masque/udp/";
+
+/// Buffer between the request stream and the relay.
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// What a request asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// CONNECT: a TCP stream to host:port
+    Tcp { host: String, port: u16 },
+    /// CONNECT-UDP: UDP datagrams to host:port
+    Udp { host: String, port: u16 },
+}
+
+impl Target {
+    fn host_port(&self) -> (&str, u16) {
+        match self {
+            Self::Tcp { host, port } | Self::Udp { host, port } => (host, *port),
+        }
+    }
+
+    /// Protocol of the session and flow record.
+    fn protocol(&self) -> &'static str {
+        match self {
+            Self::Tcp { .. } => "masque",
+            Self::Udp { .. } => "masque-udp",
+        }
+    }
+}
+
+/// The target of a request, or the status refusing it.
+pub fn parse_target<B>(req: &Request<B>) -> Result<Target, StatusCode> {
+    if req.method() != Method::CONNECT {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let target = match req.extensions().get::<h3::ext::Protocol>() {
+        None => {
+            // Classic CONNECT: the target is the authority, which has a port
+            let authority = req.uri().authority().ok_or(StatusCode::BAD_REQUEST)?;
+            let port = authority.port_u16().ok_or(StatusCode::BAD_REQUEST)?;
+            let host = authority.host();
+            let host = host
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+                .unwrap_or(host);
+            Target::Tcp {
+                host: host.to_string(),
+                port,
+            }
+        }
+        Some(protocol) if *protocol == h3::ext::Protocol::CONNECT_UDP => {
+            let (host, port) = parse_udp_path(req.uri().path()).ok_or(StatusCode::BAD_REQUEST)?;
+            Target::Udp { host, port }
+        }
+        Some(_) => return Err(StatusCode::NOT_IMPLEMENTED),
+    };
+    let (host, port) = target.host_port();
+    if port == 0
+        || (host.parse::<IpAddr>().is_err()
+            && crate::socks::protocol::validate_domain(host).is_err())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(target)
+}
+
+/// Target host and port of a CONNECT-UDP path,
+/// `/.well-known/masque/udp/{host}/{port}/`. The host is percent-decoded
+/// (IPv6 addresses come with their colons escaped).
+pub fn parse_udp_path(path: &str) -> Option<(String, u16)> {
+    let rest = path.strip_prefix(UDP_PATH_PREFIX)?;
+    let rest = rest.strip_suffix('/').unwrap_or(rest);
+    let (host, port) = rest.split_once('/')?;
+    let host = percent_encoding::percent_decode_str(host)
+        .decode_utf8()
+        .ok()?
+        .into_owned();
+    if host.is_empty() || host.contains('/') {
+        return None;
+    }
+    Some((host, port.parse().ok()?))
+}
+
+/// Username and password of a `Proxy-Authorization: Basic` header.
+pub fn credentials(headers: &http::HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::PROXY_AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(token.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Answer a request that is refused.
+async fn respond(stream: &mut Stream, status: StatusCode) -> Result<()> {
+    let mut resp = Response::builder().status(status);
+    if status == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        resp = resp.header(header::PROXY_AUTHENTICATE, "Basic realm=\"s5\"");
+    }
+    stream.send_response(resp.body(())?).await?;
+    stream.finish().await?;
+    Ok(())
+}
+
+/// Handle one request of a MASQUE connection.
+pub async fn handle_request(
+    req: Request<()>,
+    mut stream: Stream,
+    ctx: Arc<AppContext>,
+    peer_addr: SocketAddr,
+    conn_id: String,
+) -> Result<()> {
+    let target = match parse_target(&req) {
+        Ok(target) => target,
+        Err(status) => {
+            debug!(conn_id = %conn_id, method = %req.method(), uri = %req.uri(), "MASQUE request refused");
+            return respond(&mut stream, status).await;
+        }
+    };
+    let Some((username, password)) = credentials(req.headers()) else {
+        return respond(&mut stream, StatusCode::PROXY_AUTHENTICATION_REQUIRED).await;
+    };
+
+    let login = match authorize(&ctx, username, &password, &peer_addr, &conn_id).await? {
+        Ok(login) => login,
+        Err(status) => return respond(&mut stream, status).await,
+    };
+    let (host, port) = target.host_port();
+    debug!(conn_id = %conn_id, user = %login.username, target = %format!("{}:{}", host, port), protocol = target.protocol(), "MASQUE request");
+
+    let source_ip = peer_addr.ip().to_string();
+    let user = &login.user;
+    let connect_start = Instant::now();
+    let hangup = CancellationToken::new();
+    let connected = match target {
+        Target::Tcp { .. } => {
+            let upstream_proxy =
+                crate::proxy::ProxyEngine::resolve_upstream_proxy(user, &ctx.config);
+            ctx.proxy_engine
+                .connect_for_socks(
+                    &login.username,
+                    host,
+                    port,
+                    &user.acl,
+                    &user.ip_guard_exemptions,
+                    user.blocked_ports.as_deref(),
+                    user.group.as_deref(),
+                    &user.tags,
+                    &source_ip,
+                    user.max_connections,
+                    upstream_proxy.as_ref(),
+                    crate::proxy::retry::RetryPolicy::for_user(user),
+                    &conn_id,
+                )
+                .await
+                .map(|(tcp, resolved_addr, guard)| {
+                    let server_names =
+                        ctx.proxy_engine
+                            .server_name_check(&user.acl, port, resolved_addr);
+                    (Dest::Tcp(tcp), resolved_addr, guard, server_names)
+                })
+        }
+        Target::Udp { .. } => {
+            if crate::proxy::ProxyEngine::resolve_upstream_proxy(user, &ctx.config).is_some() {
+                warn!(conn_id = %conn_id, user = %login.username, "MASQUE CONNECT-UDP refused: user goes through an upstream proxy");
+                return respond(&mut stream, StatusCode::FORBIDDEN).await;
+            }
+            ctx.proxy_engine
+                .connect_udp(
+                    &login.username,
+                    host,
+                    port,
+                    &user.acl,
+                    &user.ip_guard_exemptions,
+                    user.blocked_ports.as_deref(),
+                    user.group.as_deref(),
+                    &user.tags,
+                    &source_ip,
+                    user.max_connections,
+                    &conn_id,
+                )
+                .await
+                .map(|(socket, resolved_addr, guard)| {
+                    let tunnel = UdpTunnel::new(socket, hangup.clone());
+                    (Dest::Udp(tunnel), resolved_addr, guard, None)
+                })
+        }
+    };
+    let (dest, resolved_addr, guard, server_names) = match connected {
+        Ok(connected) => connected,
+        Err(e) => {
+            let error_type = crate::socks::handler::classify_connect_error(&e);
+            warn!(conn_id = %conn_id, user = %login.username, target = %format!("{}:{}", host, port), error = %e, error_type = %error_type, "MASQUE connect failed");
+            ctx.metrics.record_error(error_type);
+            record_flow(&ctx, || FlowRecord {
+                close_reason: error_type.to_string(),
+                correlation_id: Some(conn_id.clone()),
+                ..FlowRecord::closing(
+                    target.protocol(),
+                    &login.username,
+                    &source_ip,
+                    host,
+                    port,
+                    connect_start.elapsed(),
+                )
+            });
+            return respond(&mut stream, connect_error_status(&e)).await;
+        }
+    };
+
+    stream
+        .send_response(Response::builder().status(StatusCode::OK).body(())?)
+        .await?;
+    relay(
+        &ctx,
+        &login,
+        &target,
+        stream,
+        dest,
+        Connected {
+            resolved_addr,
+            server_names,
+            _guard: guard,
+        },
+        hangup,
+        &peer_addr,
+        &conn_id,
+    )
+    .await
+}
+
+/// The status refusing a request whose target could not be reached.
+fn connect_error_status(err: &anyhow::Error) -> StatusCode {
+    let msg = err.to_string();
+    if msg.contains("ACL denied") || msg.contains("connection limit") {
+        StatusCode::FORBIDDEN
+    } else if msg.contains("circuit open") {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else if err
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+    {
+        StatusCode::GATEWAY_TIMEOUT
+    } else if err
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+    {
+        StatusCode::FORBIDDEN
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
+}
+
+/// An authenticated user allowed to open a tunnel.
+struct Login {
+    username: String,
+    user: Arc<User>,
+    _user_slot: crate::proxy::client_caps::UserSlot,
+    /// Counted in the usage reports when the tunnel closes
+    _login: crate::reports::Login,
+}
+
+/// Authenticate a request and run the checks of a SOCKS5 login: bans,
+/// external auth, password and TOTP, lockout, impossible travel, the auth
+/// hook, source IPs, access hours, client caps, rate limits and quotas.
+/// `Err` is the status refusing the request.
+async fn authorize(
+    ctx: &Arc<AppContext>,
+    username: String,
+    password: &str,
+    peer_addr: &SocketAddr,
+    conn_id: &str,
+) -> Result<Result<Login, StatusCode>> {
+    if let Err(reason) = ctx.security.read().await.pre_auth_check(&peer_addr.ip()) {
+        warn!(conn_id = %conn_id, ip = %peer_addr.ip(), reason = %reason, "MASQUE request rejected");
+        let metric_reason = if reason == "banned IP" {
+            "banned"
+        } else if reason.contains("rate") {
+            "rate_limited"
+        } else {
+            "acl_denied"
+        };
+        ctx.metrics.record_connection_rejected(metric_reason);
+        return Ok(Err(if metric_reason == "rate_limited" {
+            StatusCode::TOO_MANY_REQUESTS
+        } else {
+            StatusCode::FORBIDDEN
+        }));
+    }
+
+    // Decoy login: accept, then refuse the tunnel as unreachable
+    if ctx
+        .security
+        .read()
+        .await
+        .honeypot()
+        .matches(&username, password)
+    {
+        ctx.honeypot_triggered(&username, peer_addr, "masque", conn_id)
+            .await;
+        return Ok(Err(StatusCode::BAD_GATEWAY));
+    }
+
+    // Banned from the user's tenant: refused without counting a failure
+    let tenant = crate::auth::tenant::tenant_of(&ctx.config.tenants, &username);
+    if tenant.is_some()
+        && ctx
+            .security
+            .read()
+            .await
+            .is_banned_for(&peer_addr.ip(), tenant)
+    {
+        warn!(conn_id = %conn_id, user = %username, ip = %peer_addr.ip(), tenant = ?tenant, "MASQUE login refused: IP banned from the tenant");
+        ctx.metrics.record_connection_rejected("banned");
+        return Ok(Err(StatusCode::FORBIDDEN));
+    }
+
+    let attempt = PasswordAttempt {
+        username: &username,
+        password,
+        totp_code: None,
+        peer_addr,
+        protocol: "masque",
+        audit_method: "masque",
+        method: "password",
+        hassh: None,
+        conn_id,
+    };
+    let user = match login::authenticate_password(ctx, &attempt).await {
+        PasswordOutcome::Accepted { user, .. } => user,
+        PasswordOutcome::Rejected { with_totp } => {
+            login::record_password_failure(ctx, &attempt, with_totp).await;
+            return Ok(Err(StatusCode::PROXY_AUTHENTICATION_REQUIRED));
+        }
+    };
+
+    let user = user.ok_or_else(|| anyhow::anyhow!("user disappeared after auth"))?;
+    let admission = match login::admit_proxy_login(ctx, &user, peer_addr, "masque", conn_id).await {
+        Ok(admission) => admission,
+        Err(Refusal::ClientCap | Refusal::RateLimited | Refusal::Quota) => {
+            return Ok(Err(StatusCode::TOO_MANY_REQUESTS))
+        }
+        Err(Refusal::SourceIp | Refusal::AccessHours | Refusal::Forwarding) => {
+            return Ok(Err(StatusCode::FORBIDDEN))
+        }
+    };
+
+    Ok(Ok(Login {
+        username,
+        user,
+        _user_slot: admission.user_slot,
+        _login: admission.login,
+    }))
+}
+
+/// Destination side of a tunnel.
+enum Dest {
+    Tcp(tokio::net::TcpStream),
+    Udp(UdpTunnel),
+}
+
+/// What the connect step leaves to the relay.
+struct Connected {
+    resolved_addr: SocketAddr,
+    server_names: Option<ServerNameCheck>,
+    _guard: ConnectionGuard,
+}
+
+/// Relay a tunnel until either side is done, then log it like a SOCKS5
+/// relay.
+#[allow(clippy::too_many_arguments)]
+async fn relay(
+    ctx: &Arc<AppContext>,
+    login: &Login,
+    target: &Target,
+    stream: Stream,
+    dest: Dest,
+    connected: Connected,
+    hangup: CancellationToken,
+    peer_addr: &SocketAddr,
+    conn_id: &str,
+) -> Result<()> {
+    let (host, port) = target.host_port();
+    let user = &login.user;
+    let resolved_ip = connected.resolved_addr.ip();
+    let session = ctx.proxy_engine.register_session_cid(
+        &login.username,
+        host,
+        port,
+        &peer_addr.ip().to_string(),
+        target.protocol(),
+        conn_id,
+    );
+    ctx.proxy_engine.prioritize(
+        &session,
+        Some(resolved_ip).filter(|ip| !ip.is_unspecified()),
+        &user.tags,
+    );
+    if matches!(target, Target::Tcp { .. }) {
+        ctx.proxy_engine
+            .sample_first_line(&session, user.payload_metadata);
+    }
+    let relay_cfg = RelayConfig {
+        idle_timeout: Duration::from_secs(ctx.config.limits.idle_timeout),
+        context: format!("{}@{}:{}", login.username, host, port),
+        per_conn_bandwidth_kbps: user.max_bandwidth_kbps,
+        aggregate_bandwidth_kbps: user.max_aggregate_bandwidth_kbps,
+        quota_tracker: Some(ctx.quota_tracker.clone()),
+        username: Some(login.username.clone()),
+        quotas: user.quotas.clone(),
+        audit: Some(ctx.audit.clone()),
+        session: Some(session.clone()),
+        stall_watch: None,
+        client_window: None,
+        server_names: connected.server_names,
+    };
+
+    let client = pipe(stream, hangup);
+    let relay_start = Instant::now();
+    let outcome = match dest {
+        Dest::Tcp(tcp) => crate::proxy::forwarder::relay_with_reason(client, tcp, relay_cfg).await,
+        Dest::Udp(tunnel) => {
+            crate::proxy::forwarder::relay_with_reason(client, tunnel, relay_cfg).await
+        }
+    };
+    let flow = |elapsed: Duration| FlowRecord {
+        dest_ip: Some(resolved_ip.to_string()),
+        correlation_id: Some(conn_id.to_string()),
+        ..FlowRecord::closing(
+            target.protocol(),
+            &login.username,
+            &peer_addr.ip().to_string(),
+            host,
+            port,
+            elapsed,
+        )
+    };
+    let outcome = outcome.inspect_err(|_| {
+        record_flow(ctx, || FlowRecord {
+            close_reason: crate::metrics::error_types::RELAY_ERROR.to_string(),
+            ..flow(relay_start.elapsed())
+        })
+    })?;
+    let duration_ms = relay_start.elapsed().as_millis() as u64;
+    ctx.proxy_engine.close_session(&session, outcome.reason);
+
+    let (bytes_up, bytes_down) = (outcome.bytes_up, outcome.bytes_down);
+    info!(
+        conn_id = %conn_id,
+        user = %login.username,
+        target = %format!("{}:{}", host, port),
+        resolved_ip = %resolved_ip,
+        bytes_up = bytes_up,
+        bytes_down = bytes_down,
+        duration_ms = duration_ms,
+        close_reason = %outcome.reason,
+        "MASQUE relay completed"
+    );
+    let event = AuditEvent::proxy_complete_with_cid(
+        &login.username,
+        host,
+        port,
+        bytes_up,
+        bytes_down,
+        duration_ms,
+        peer_addr,
+        Some(resolved_ip.to_string()),
+        conn_id,
+    )
+    .with_close_reason(outcome.reason);
+    ctx.audit
+        .log_event(ctx.proxy_engine.label_session_close(event).await);
+    ctx.metrics
+        .record_bytes_transferred(&login.username, bytes_up + bytes_down);
+    ctx.metrics.record_typed_connection_duration_cid(
+        &login.username,
+        "masque",
+        duration_ms as f64 / 1000.0,
+        conn_id,
+    );
+    record_flow(ctx, || {
+        FlowRecord {
+            bytes_up,
+            bytes_down,
+            ..flow(Duration::from_millis(duration_ms))
+        }
+        .with_traffic(outcome.traffic.as_ref())
+        .with_relay_close(outcome.reason)
+    });
+    Ok(())
+}
+
+/// The request stream as the client side of a relay: DATA frames are
+/// copied in and out through an in-memory pipe. `hangup` is cancelled once
+/// the client is done sending; the client's side is stopped once the relay
+/// has let go of the pipe.
+fn pipe(stream: Stream, hangup: CancellationToken) -> impl AsyncRead + AsyncWrite + Send + Unpin {
+    let (client, relay_side) = tokio::io::duplex(PIPE_SIZE);
+    let (mut from_relay, mut to_relay) = tokio::io::split(client);
+    let (mut send, mut recv) = stream.split();
+    let relay_done = CancellationToken::new();
+
+    let done = relay_done.clone();
+    tokio::spawn(async move {
+        'recv: loop {
+            let data = tokio::select! {
+                data = recv.recv_data() => data,
+                _ = done.cancelled() => {
+                    recv.stop_sending(h3::error::Code::H3_NO_ERROR);
+                    break;
+                }
+            };
+            let Ok(Some(mut data)) = data else {
+                break;
+            };
+            while data.has_remaining() {
+                let chunk = data.chunk();
+                if to_relay.write_all(chunk).await.is_err() {
+                    break 'recv;
+                }
+                let len = chunk.len();
+                data.advance(len);
+            }
+        }
+        let _ = to_relay.shutdown().await;
+        hangup.cancel();
+    });
+
+    tokio::spawn(async move {
+        let mut buf = vec![0; PIPE_SIZE];
+        while let Ok(n) = from_relay.read(&mut buf).await {
+            if n == 0
+                || send
+                    .send_data(Bytes::copy_from_slice(&buf[..n]))
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+        let _ = send.finish().await;
+        relay_done.cancel();
+    });
+
+    relay_side
+}
+
+/// Record a flow if `[logging.flows]` or `[logging.ipfix]` is enabled.
+fn record_flow(ctx: &AppContext, flow: impl FnOnce() -> FlowRecord) {
+    if ctx.records_flows() {
+        ctx.record_flow(flow());
+    }
+}
@@ -0,0 +1,178 @@
+//! MASQUE proxying over HTTP/3 (`[masque]`, feature `masque`): a QUIC
+//! listener taking CONNECT requests to TCP destinations and CONNECT-UDP
+//! requests (RFC 9298) to UDP ones. Each request is handled like a SOCKS5
+//! session, see [`handler`].
+
+pub mod handler;
+pub mod udp;
+
+use crate::config::types::MasqueConfig;
+use crate::context::AppContext;
+use crate::proxy::client_caps::Listener;
+use crate::utils::generate_correlation_id;
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_rustls::rustls;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+/// ALPN of HTTP/3.
+const ALPN_H3: &[u8] = b"h3";
+
+/// Build the QUIC server config from `[masque]`'s certificate and key.
+fn server_config(config: &MasqueConfig) -> Result<quinn::ServerConfig> {
+    use std::io::BufReader;
+    let cert_file = std::fs::File::open(&config.tls_cert)
+        .map_err(|e| anyhow::anyhow!("reading TLS cert {}: {}", config.tls_cert.display(), e))?;
+    let key_file = std::fs::File::open(&config.tls_key)
+        .map_err(|e| anyhow::anyhow!("reading TLS key {}: {}", config.tls_key.display(), e))?;
+
+    let certs: Vec<_> = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<_, _>>()
+        .map_err(|e| anyhow::anyhow!("parsing TLS certs: {}", e))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificates found in {}", config.tls_cert.display());
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .map_err(|e| anyhow::anyhow!("parsing TLS key: {}", e))?
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {}", config.tls_key.display()))?;
+
+    // QUIC needs TLS 1.3
+    let mut tls = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| anyhow::anyhow!("TLS config error: {}", e))?;
+    tls.alpn_protocols = vec![ALPN_H3.to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(tls)
+        .map_err(|e| anyhow::anyhow!("QUIC TLS config error: {}", e))?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+/// Start the MASQUE listener with graceful shutdown support.
+pub async fn start_masque_server(
+    config: &MasqueConfig,
+    ctx: Arc<AppContext>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let listen: SocketAddr = config.listen.parse()?;
+    let endpoint = quinn::Endpoint::server(server_config(config)?, listen)?;
+    info!(addr = %listen, "MASQUE server listening (HTTP/3)");
+
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => break,
+            },
+            _ = shutdown.cancelled() => {
+                info!("MASQUE server shutting down (no new connections)");
+                break;
+            }
+        };
+
+        let peer_addr = incoming.remote_address();
+        if let Err(reason) = ctx.security.read().await.pre_auth_check(&peer_addr.ip()) {
+            warn!(ip = %peer_addr.ip(), reason = %reason, "MASQUE connection rejected");
+            ctx.metrics
+                .record_connection_rejected(if reason == "banned IP" {
+                    "banned"
+                } else if reason.contains("rate") {
+                    "rate_limited"
+                } else {
+                    "acl_denied"
+                });
+            incoming.refuse();
+            continue;
+        }
+
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let _client_slot = match ctx
+                .proxy_engine
+                .client_caps()
+                .acquire(Listener::Masque)
+                .await
+            {
+                Ok(slot) => slot,
+                Err(rejection) => {
+                    warn!("MASQUE client connection cap reached, refusing connection");
+                    ctx.metrics
+                        .record_connection_rejected(rejection.metric_reason());
+                    incoming.refuse();
+                    return;
+                }
+            };
+            let conn_id = generate_correlation_id();
+            let span = info_span!("masque", conn_id = %conn_id, peer = %peer_addr.ip());
+            if let Err(e) = handle_connection(incoming, ctx, peer_addr, &conn_id)
+                .instrument(span)
+                .await
+            {
+                debug!(conn_id = %conn_id, error = %e, "MASQUE connection error");
+            }
+        });
+    }
+
+    endpoint.close(0u32.into(), b"shutting down");
+    Ok(())
+}
+
+/// Serve the requests of one QUIC connection, each on its own task.
+async fn handle_connection(
+    incoming: quinn::Incoming,
+    ctx: Arc<AppContext>,
+    peer_addr: SocketAddr,
+    conn_id: &str,
+) -> Result<()> {
+    let conn = incoming.await?;
+    debug!(conn_id = %conn_id, peer = %peer_addr, "New MASQUE connection");
+    ctx.audit
+        .log_connection_new_cid(&peer_addr, "masque", conn_id);
+
+    let result = async {
+        let mut h3_conn = h3::server::builder()
+            .enable_extended_connect(true)
+            .build::<_, bytes::Bytes>(h3_quinn::Connection::new(conn))
+            .await?;
+        while let Some(resolver) = h3_conn.accept().await? {
+            let ctx = ctx.clone();
+            let conn_id = conn_id.to_string();
+            tokio::spawn(
+                async move {
+                    let (req, stream) = match resolver.resolve_request().await {
+                        Ok(request) => request,
+                        Err(e) => {
+                            debug!(conn_id = %conn_id, error = %e, "MASQUE request error");
+                            return;
+                        }
+                    };
+                    if let Err(e) =
+                        handler::handle_request(req, stream, ctx, peer_addr, conn_id.clone()).await
+                    {
+                        error!(conn_id = %conn_id, error = %e, "MASQUE request error");
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    ctx.audit
+        .log_connection_closed_cid(&peer_addr, "masque", conn_id);
+    match result {
+        Err(e)
+            if e.downcast_ref::<h3::error::ConnectionError>()
+                .is_some_and(|e| e.is_h3_no_error()) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,185 @@
+//! UDP payloads of a CONNECT-UDP request (RFC 9298).
+//!
+//! Payloads travel on the request stream as DATAGRAM capsules (RFC 9297):
+//! a varint capsule type (`0x00`), a varint length, then the HTTP datagram,
+//! a varint context ID followed by the UDP payload for context 0. Capsules
+//! of other types and datagrams of other contexts are skipped, as the RFCs
+//! require. s5 does not advertise QUIC datagrams, so clients send every
+//! payload this way.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UdpSocket;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+/// Capsule type of an HTTP datagram.
+pub const DATAGRAM_CAPSULE: u64 = 0x00;
+
+/// Largest UDP payload relayed.
+pub const MAX_PAYLOAD_SIZE: usize = 65527;
+
+/// Largest capsule accepted: a datagram capsule of the largest payload,
+/// with room for a long context ID.
+const MAX_CAPSULE_SIZE: usize = MAX_PAYLOAD_SIZE + 16;
+
+/// Append `value` as a QUIC variable-length integer (RFC 9000, 16).
+pub fn encode_varint(value: u64, out: &mut Vec<u8>) {
+    match value {
+        0..=0x3f => out.push(value as u8),
+        0x40..=0x3fff => out.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3fff_ffff => out.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => out.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+/// Read a varint at the start of `data`: its value and length. None when
+/// `data` stops before its end.
+pub fn decode_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(..len)?;
+    let value = bytes[1..].iter().fold(u64::from(first & 0x3f), |value, b| {
+        value << 8 | u64::from(*b)
+    });
+    Some((value, len))
+}
+
+/// Append `payload` as the DATAGRAM capsule of a UDP payload (context 0).
+pub fn encode_datagram(payload: &[u8], out: &mut Vec<u8>) {
+    encode_varint(DATAGRAM_CAPSULE, out);
+    encode_varint(payload.len() as u64 + 1, out);
+    encode_varint(0, out);
+    out.extend_from_slice(payload);
+}
+
+/// Splits the request stream into the UDP payloads it carries; capsules
+/// read in several parts are put back together.
+#[derive(Debug, Default)]
+pub struct CapsuleDecoder {
+    buf: Vec<u8>,
+}
+
+impl CapsuleDecoder {
+    /// Add `data` read from the stream; the UDP payloads it completes.
+    pub fn push(&mut self, data: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        self.buf.extend_from_slice(data);
+        let mut payloads = Vec::new();
+        let mut pos = 0;
+        loop {
+            let rest = &self.buf[pos..];
+            let Some((kind, kind_len)) = decode_varint(rest) else {
+                break;
+            };
+            let Some((len, len_len)) = decode_varint(&rest[kind_len..]) else {
+                break;
+            };
+            let header = kind_len + len_len;
+            if len > MAX_CAPSULE_SIZE as u64 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("capsule of {len} bytes"),
+                ));
+            }
+            let Some(value) = rest.get(header..header + len as usize) else {
+                break;
+            };
+            if kind == DATAGRAM_CAPSULE {
+                match decode_varint(value) {
+                    Some((0, id_len)) => payloads.push(value[id_len..].to_vec()),
+                    Some(_) => {}
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "datagram capsule without a context ID",
+                        ))
+                    }
+                }
+            }
+            pos += header + len as usize;
+        }
+        self.buf.drain(..pos);
+        Ok(payloads)
+    }
+}
+
+/// A UDP socket connected to the target, as the destination side of a
+/// relay: reads return what the target sends as DATAGRAM capsules, writes
+/// take capsules and send their payloads. Reads end once `hangup` is
+/// cancelled, when the client's side of the request stream is done.
+pub struct UdpTunnel {
+    socket: UdpSocket,
+    capsules: CapsuleDecoder,
+    pending: Vec<u8>,
+    pending_pos: usize,
+    datagram: Box<[u8]>,
+    hangup: Pin<Box<WaitForCancellationFutureOwned>>,
+}
+
+impl UdpTunnel {
+    pub fn new(socket: UdpSocket, hangup: CancellationToken) -> Self {
+        Self {
+            socket,
+            capsules: CapsuleDecoder::default(),
+            pending: Vec::new(),
+            pending_pos: 0,
+            datagram: vec![0; MAX_PAYLOAD_SIZE].into_boxed_slice(),
+            hangup: Box::pin(hangup.cancelled_owned()),
+        }
+    }
+}
+
+impl AsyncRead for UdpTunnel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.pending_pos == this.pending.len() {
+            if this.hangup.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Ok(()));
+            }
+            let mut datagram = ReadBuf::new(&mut this.datagram);
+            match ready!(this.socket.poll_recv(cx, &mut datagram)) {
+                Ok(()) => {
+                    this.pending.clear();
+                    this.pending_pos = 0;
+                    encode_datagram(datagram.filled(), &mut this.pending);
+                }
+                // An ICMP error for an earlier send: the target is not there (yet)
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        let len = buf.remaining().min(this.pending.len() - this.pending_pos);
+        buf.put_slice(&this.pending[this.pending_pos..this.pending_pos + len]);
+        this.pending_pos += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for UdpTunnel {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        for payload in this.capsules.push(buf)? {
+            // Like a full socket buffer, a refused send drops the datagram
+            let _ = this.socket.try_send(&payload);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
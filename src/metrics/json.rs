@@ -0,0 +1,154 @@
+//! JSON snapshot of the metrics registry (`GET /api/metrics`), for
+//! consumers that ingest JSON rather than scrape Prometheus.
+//!
+//! The registry is encoded in the OpenMetrics text format, as `/metrics`
+//! serves it, and each family is turned into an object with its samples, so
+//! the JSON always carries exactly the series a scrape would. Exemplars are
+//! left out.
+
+use super::MetricsRegistry;
+use chrono::{DateTime, Utc};
+use prometheus_client::encoding::text::encode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Every metric family at one point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub metrics: Vec<MetricFamily>,
+}
+
+/// One registered metric.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricFamily {
+    pub name: String,
+    /// `counter`, `gauge`, `histogram`, `info`...
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub help: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    pub samples: Vec<Sample>,
+}
+
+/// One series value. Histograms have one sample per bucket (`_bucket`, with
+/// an `le` label) plus `_sum` and `_count`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    /// `+Inf`, `-Inf` and `NaN` are serialized as `null`
+    pub value: f64,
+}
+
+/// Take a snapshot of every metric in `metrics`.
+pub fn snapshot(metrics: &MetricsRegistry) -> Result<MetricsSnapshot, std::fmt::Error> {
+    let mut text = String::new();
+    encode(&mut text, &metrics.registry)?;
+    Ok(MetricsSnapshot {
+        timestamp: Utc::now(),
+        metrics: parse_openmetrics(&text),
+    })
+}
+
+/// Parse OpenMetrics exposition text into metric families. Malformed sample
+/// lines are skipped.
+pub fn parse_openmetrics(text: &str) -> Vec<MetricFamily> {
+    let mut families: Vec<MetricFamily> = Vec::new();
+    for line in text.lines() {
+        if let Some(meta) = line.strip_prefix("# ") {
+            let mut parts = meta.splitn(3, ' ');
+            let (Some(keyword), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let rest = parts.next().unwrap_or_default();
+            if !matches!(keyword, "HELP" | "TYPE" | "UNIT") {
+                continue;
+            }
+            if families.last().is_none_or(|f| f.name != name) {
+                families.push(MetricFamily {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+            }
+            let family = families.last_mut().expect("pushed above");
+            match keyword {
+                "HELP" => family.help = unescape(rest),
+                "TYPE" => family.kind = rest.to_string(),
+                _ => family.unit = Some(rest.to_string()).filter(|u| !u.is_empty()),
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let (Some(sample), Some(family)) = (parse_sample(line), families.last_mut()) {
+            family.samples.push(sample);
+        }
+    }
+    families
+}
+
+/// `name{label="value",...} value [timestamp] [# exemplar]`
+fn parse_sample(line: &str) -> Option<Sample> {
+    let name_end = line.find(['{', ' '])?;
+    let name = &line[..name_end];
+    let mut labels = BTreeMap::new();
+    let mut rest = &line[name_end..];
+
+    if let Some(mut s) = rest.strip_prefix('{') {
+        loop {
+            if let Some(after) = s.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+            let (key, after_key) = s.split_once("=\"")?;
+            let (value, after_value) = quoted(after_key)?;
+            labels.insert(key.trim_start_matches(',').to_string(), value);
+            s = after_value;
+        }
+    }
+
+    let value = rest.split_whitespace().next()?.parse().ok()?;
+    Some(Sample {
+        name: name.to_string(),
+        labels,
+        value,
+    })
+}
+
+/// Read an escaped label value up to its closing quote; returns the value
+/// and what follows the quote.
+fn quoted(s: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &s[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                other => value.push(other),
+            },
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
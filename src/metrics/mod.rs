@@ -1,4 +1,5 @@
 pub mod collectors;
+pub mod json;
 
 /// Well-known error type constants for metrics
 pub mod error_types {
@@ -7,6 +8,7 @@ pub mod error_types {
     pub const CONNECTION_REFUSED: &str = "connection_refused";
     pub const CONNECTION_TIMEOUT: &str = "connection_timeout";
     pub const DNS_FAILURE: &str = "dns_failure";
+    pub const CIRCUIT_OPEN: &str = "circuit_open";
     pub const QUOTA_EXCEEDED: &str = "quota_exceeded";
     pub const RATE_LIMITED: &str = "rate_limited";
     pub const PROTOCOL_ERROR: &str = "protocol_error";
@@ -14,18 +16,62 @@ pub mod error_types {
     pub const INTERNAL_ERROR: &str = "internal_error";
 }
 
+/// Reason label values for `s5_connections_shed_total`
+pub mod shed_reasons {
+    /// The wait queue for `server.max_connections` was full (or disabled)
+    pub const QUEUE_FULL: &str = "queue_full";
+    /// A queued client found no free slot in `server.connection_queue_timeout_ms`
+    pub const QUEUE_TIMEOUT: &str = "queue_timeout";
+    /// A new SSH connection was refused at `limits.max_unauthenticated_connections`
+    pub const PRE_AUTH_FULL: &str = "pre_auth_full";
+    /// The oldest unauthenticated SSH connection was closed to admit a new one
+    pub const OLDEST_PRE_AUTH: &str = "oldest_pre_auth";
+}
+
+/// Outcome label values for the latency histograms
+pub mod outcomes {
+    pub const SUCCESS: &str = "success";
+    pub const FAILURE: &str = "failure";
+    pub const TIMEOUT: &str = "timeout";
+    pub const DENIED: &str = "denied";
+
+    /// Map a connect-path error to an outcome label.
+    pub fn from_error(err: &anyhow::Error) -> &'static str {
+        let msg = err.to_string();
+        if msg.contains("ACL denied") || msg.contains("connection limit") {
+            DENIED
+        } else if err.downcast_ref::<tokio::time::error::Elapsed>().is_some()
+            || err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+        {
+            TIMEOUT
+        } else {
+            FAILURE
+        }
+    }
+}
+
 use collectors::{
-    AuthMethodLabel, AuthMethodUserLabel, ConnectionTypeUserLabel, ErrorTypeLabel,
-    HttpDurationLabel, HttpRequestLabel, ReasonLabel, UserLabel, UserTypeLabel, UserWindowLabel,
+    AppProtocolDirectionLabel, AuthMethodLabel, AuthMethodOutcomeLabel, AuthMethodUserLabel,
+    ConnectionTypeUserLabel, CorrelationLabel, ErrorTypeLabel, FeedLabel, HttpDurationLabel,
+    HttpRequestLabel, OutcomeLabel, ProtocolLabel, ProtocolReasonLabel, ReasonLabel, UserLabel,
+    UserTypeLabel, UserWindowLabel, WorkerLabel,
 };
 use dashmap::DashSet;
 use prometheus_client::metrics::counter::{Atomic as CounterAtomic, Counter};
+use prometheus_client::metrics::exemplar::HistogramWithExemplars;
 use prometheus_client::metrics::family::{Family, MetricConstructor};
 use prometheus_client::metrics::gauge::Gauge;
 use prometheus_client::metrics::histogram::Histogram;
 use prometheus_client::registry::Registry;
 use std::sync::atomic::AtomicU64;
 
+/// User label of every user when per-user labels are disabled
+pub const AGGREGATE_USER_LABEL: &str = "_all";
+/// User label of the users beyond the `max_metric_labels` cap
+pub const OTHER_USER_LABEL: &str = "_other";
+
 /// Constructor for connection duration histograms with predefined buckets.
 #[derive(Clone)]
 pub struct DurationHistogramBuilder;
@@ -49,15 +95,35 @@ impl MetricConstructor<Histogram> for HttpDurationHistogramBuilder {
 }
 
 /// Constructor for connection duration histograms with network-optimized buckets.
-/// Covers short-lived (sub-second) to long-lived (1 hour) connections.
+/// Covers short-lived (sub-second) to long-lived (1 hour) connections. Each
+/// bucket keeps the correlation ID of its latest sample as an exemplar.
 #[derive(Clone)]
 pub struct ConnectionDurationHistogramBuilder;
 
-impl MetricConstructor<Histogram> for ConnectionDurationHistogramBuilder {
-    fn new_metric(&self) -> Histogram {
+impl MetricConstructor<HistogramWithExemplars<CorrelationLabel>>
+    for ConnectionDurationHistogramBuilder
+{
+    fn new_metric(&self) -> HistogramWithExemplars<CorrelationLabel> {
         // Buckets: 0.1s, 0.5s, 1s, 5s, 10s, 30s, 60s, 300s (5m), 600s (10m), 1800s (30m), 3600s (1h)
+        HistogramWithExemplars::new(
+            [
+                0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0,
+            ]
+            .into_iter(),
+        )
+    }
+}
+
+/// Constructor for connection-setup latency histograms (DNS, TCP connect,
+/// SSH auth, channel setup). Sub-millisecond to 30s.
+#[derive(Clone)]
+pub struct LatencyHistogramBuilder;
+
+impl MetricConstructor<Histogram> for LatencyHistogramBuilder {
+    fn new_metric(&self) -> Histogram {
+        // Buckets: 1ms, 2.5ms, 5ms, 10ms, 25ms, 50ms, 100ms, 250ms, 500ms, 1s, 2.5s, 5s, 10s, 30s
         Histogram::new([
-            0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0,
+            0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
         ])
     }
 }
@@ -72,15 +138,59 @@ pub struct MetricsRegistry {
     pub auth_successes_total: Family<AuthMethodUserLabel, Counter>,
     pub errors_total: Family<ErrorTypeLabel, Counter>,
     pub banned_ips_current: Gauge,
+    /// Connections rejected because the source IP is on a threat-intel feed
+    pub threat_intel_blocked_total: Family<FeedLabel, Counter>,
+    /// Active entries (IPs and ranges) per threat-intel feed
+    pub threat_intel_entries: Family<FeedLabel, Gauge>,
+    /// Failed threat-intel feed refreshes
+    pub threat_intel_refresh_failures_total: Family<FeedLabel, Counter>,
+    /// Sockets currently held in the SSH tarpit
+    pub tarpit_active: Gauge,
+    /// Banned connections sent to the SSH tarpit
+    pub tarpit_connections_total: Counter,
+    /// Banned connections closed because the tarpit was full
+    pub tarpit_rejected_total: Counter,
+    /// SSH connections accepted but not yet authenticated
+    pub ssh_unauthenticated_connections: Gauge,
+    /// Connected clients per listener (`server.max_connections`)
+    pub client_connections: Family<ProtocolLabel, Gauge>,
+    /// Clients waiting for a free connection slot
+    pub client_connections_queued: Gauge,
+    /// Forwarded SSH channels currently waiting on the client's window
+    pub ssh_channels_stalled: Gauge,
+    /// Times a forwarded SSH channel stalled on the client's window
+    pub ssh_channel_stalls_total: Counter,
+    /// Logins with a decoy credential, per protocol
+    pub honeypot_triggers_total: Family<ProtocolLabel, Counter>,
+    /// Sources caught failing logins as many usernames, per protocol
+    pub credential_spraying_detected_total: Family<ProtocolLabel, Counter>,
+    /// Accounts locked after repeated failed logins
+    pub account_lockouts_total: Counter,
+    /// Logins from too far away to have travelled since a previous one, per protocol
+    pub impossible_travel_detected_total: Family<ProtocolLabel, Counter>,
+    /// Relayed sessions closed, per protocol and close reason
+    pub sessions_closed_total: Family<ProtocolReasonLabel, Counter>,
+    /// Relayed bytes per application protocol (tls, http, ssh, unknown) and direction
+    pub app_protocol_bytes_total: Family<AppProtocolDirectionLabel, Counter>,
     pub audit_events_dropped: Counter,
+    /// Seconds the oldest rotated audit file has been waiting for upload
+    pub audit_archive_lag_seconds: Gauge,
+    pub audit_archive_uploads_total: Counter,
+    pub audit_archive_failures_total: Counter,
     pub cardinality_capped_total: Counter,
     pub quota_bandwidth_used_bytes: Family<UserWindowLabel, Counter<f64, AtomicU64>>,
     pub quota_connections_used: Family<UserWindowLabel, Counter>,
     pub quota_exceeded_total: Family<UserTypeLabel, Counter>,
     pub connection_duration_seconds: Family<UserLabel, Histogram, DurationHistogramBuilder>,
-    pub connection_duration_by_type_seconds:
-        Family<ConnectionTypeUserLabel, Histogram, ConnectionDurationHistogramBuilder>,
+    pub connection_duration_by_type_seconds: Family<
+        ConnectionTypeUserLabel,
+        HistogramWithExemplars<CorrelationLabel>,
+        ConnectionDurationHistogramBuilder,
+    >,
     pub connections_rejected_total: Family<ReasonLabel, Counter>,
+    /// Connections shed under overload, by reason (queue full or timed out,
+    /// pre-auth limit reached, oldest pre-auth connection dropped)
+    pub connections_shed_total: Family<ReasonLabel, Counter>,
     pub http_requests_total: Family<HttpRequestLabel, Counter>,
     pub http_request_duration_seconds:
         Family<HttpDurationLabel, Histogram, HttpDurationHistogramBuilder>,
@@ -88,13 +198,52 @@ pub struct MetricsRegistry {
     pub dns_cache_hits_total: Counter,
     /// DNS cache miss counter (incremented in connector::connect_with_cache).
     pub dns_cache_misses_total: Counter,
+    /// DNS cache entries dropped, by reason (`capacity` or `expired`).
+    pub dns_cache_evictions_total: Family<ReasonLabel, Counter>,
+    /// Background DNS cache refreshes of hot hostnames, by outcome.
+    pub dns_prefetch_total: Family<OutcomeLabel, Counter>,
+    /// Upstream DNS lookup time on cache misses, by outcome.
+    pub dns_resolution_duration_seconds: Family<OutcomeLabel, Histogram, LatencyHistogramBuilder>,
+    /// Destination circuits opened by the circuit breaker.
+    pub circuit_breaker_opened_total: Counter,
+    /// Connects refused at once because the destination's circuit is open.
+    pub circuit_breaker_rejected_total: Counter,
+    /// Outbound connect retries after a refused or timed-out attempt, by reason.
+    pub connect_retries_total: Family<ReasonLabel, Counter>,
+    /// TCP connect time to the target (all resolved addresses tried), by outcome.
+    pub tcp_connect_duration_seconds: Family<OutcomeLabel, Histogram, LatencyHistogramBuilder>,
+    /// Credential verification time per SSH auth attempt, by method and outcome.
+    pub ssh_auth_duration_seconds:
+        Family<AuthMethodOutcomeLabel, Histogram, LatencyHistogramBuilder>,
+    /// Time from a forwarding request to a connected target (ACL + DNS + connect), by outcome.
+    pub channel_setup_duration_seconds: Family<OutcomeLabel, Histogram, LatencyHistogramBuilder>,
     /// Process resident memory in bytes (updated periodically)
     pub process_resident_memory_bytes: Gauge,
     /// Process open file descriptors (updated periodically)
     pub process_open_fds: Gauge,
+    /// Tokio worker threads (updated periodically)
+    pub tokio_workers: Gauge,
+    /// Tasks alive on the Tokio runtime (updated periodically)
+    pub tokio_alive_tasks: Gauge,
+    /// Tasks waiting in the runtime's shared injection queue (updated periodically)
+    pub tokio_global_queue_depth: Gauge,
+    /// Time each worker spent running tasks (updated periodically)
+    pub tokio_worker_busy_seconds_total: Family<WorkerLabel, Counter<f64, AtomicU64>>,
+    /// Times each worker parked for lack of work (updated periodically)
+    pub tokio_worker_park_total: Family<WorkerLabel, Counter>,
+    /// Relay direction tasks currently running (updated periodically)
+    pub forwarding_tasks_active: Gauge,
+    /// Relay direction tasks spawned since startup (updated periodically)
+    pub forwarding_tasks_spawned_total: Counter,
+    /// Bytes buffered by all live sessions (updated periodically)
+    pub session_buffered_bytes: Gauge,
+    /// Connections opened per user (lifetime counter)
+    pub user_connections_total: Family<UserLabel, Counter>,
     /// Track known label values for cardinality cap
     known_users: DashSet<String>,
     max_labels: u32,
+    /// Whether user labels carry the username (false = everything under "_all")
+    user_labels: bool,
 }
 
 impl MetricsRegistry {
@@ -102,6 +251,13 @@ impl MetricsRegistry {
         Self::with_max_labels(100)
     }
 
+    /// Build a registry from `[metrics]` config (`max_metric_labels`, `user_labels`).
+    pub fn from_config(config: &crate::config::types::MetricsConfig) -> Self {
+        let mut metrics = Self::with_max_labels(config.max_metric_labels);
+        metrics.user_labels = config.user_labels;
+        metrics
+    }
+
     pub fn with_max_labels(max_labels: u32) -> Self {
         let mut registry = Registry::default();
 
@@ -119,6 +275,13 @@ impl MetricsRegistry {
             connections_total.clone(),
         );
 
+        let user_connections_total = Family::<UserLabel, Counter>::default();
+        registry.register(
+            "s5_user_connections_total",
+            "Total connections opened per user",
+            user_connections_total.clone(),
+        );
+
         let bytes_transferred = Family::<UserLabel, Counter<f64, AtomicU64>>::default();
         registry.register(
             "s5_bytes_transferred",
@@ -154,6 +317,125 @@ impl MetricsRegistry {
             banned_ips_current.clone(),
         );
 
+        let threat_intel_blocked_total = Family::<FeedLabel, Counter>::default();
+        registry.register(
+            "s5_threat_intel_blocked_total",
+            "Connections rejected by a threat-intel feed",
+            threat_intel_blocked_total.clone(),
+        );
+
+        let threat_intel_entries = Family::<FeedLabel, Gauge>::default();
+        registry.register(
+            "s5_threat_intel_entries",
+            "Active threat-intel entries (IPs and ranges) per feed",
+            threat_intel_entries.clone(),
+        );
+
+        let threat_intel_refresh_failures_total = Family::<FeedLabel, Counter>::default();
+        registry.register(
+            "s5_threat_intel_refresh_failures_total",
+            "Failed threat-intel feed refreshes",
+            threat_intel_refresh_failures_total.clone(),
+        );
+
+        let tarpit_active = Gauge::default();
+        registry.register(
+            "s5_tarpit_active",
+            "Sockets currently held in the SSH tarpit",
+            tarpit_active.clone(),
+        );
+
+        let tarpit_connections_total = Counter::default();
+        registry.register(
+            "s5_tarpit_connections_total",
+            "Banned connections sent to the SSH tarpit",
+            tarpit_connections_total.clone(),
+        );
+
+        let tarpit_rejected_total = Counter::default();
+        registry.register(
+            "s5_tarpit_rejected_total",
+            "Banned connections closed because the tarpit was full",
+            tarpit_rejected_total.clone(),
+        );
+
+        let ssh_unauthenticated_connections = Gauge::default();
+        registry.register(
+            "s5_ssh_unauthenticated_connections",
+            "SSH connections accepted but not yet authenticated",
+            ssh_unauthenticated_connections.clone(),
+        );
+
+        let client_connections = Family::<ProtocolLabel, Gauge>::default();
+        registry.register(
+            "s5_client_connections",
+            "Connected SSH and SOCKS5 clients, by listener",
+            client_connections.clone(),
+        );
+
+        let client_connections_queued = Gauge::default();
+        registry.register(
+            "s5_client_connections_queued",
+            "Clients waiting for a free slot under server.max_connections",
+            client_connections_queued.clone(),
+        );
+
+        let ssh_channels_stalled = Gauge::default();
+        registry.register(
+            "s5_ssh_channels_stalled",
+            "Forwarded SSH channels stalled on the client's window (server.ssh_stall_threshold_ms)",
+            ssh_channels_stalled.clone(),
+        );
+
+        let ssh_channel_stalls_total = Counter::default();
+        registry.register(
+            "s5_ssh_channel_stalls_total",
+            "Forwarded SSH channel writes that stalled on the client's window",
+            ssh_channel_stalls_total.clone(),
+        );
+
+        let honeypot_triggers_total = Family::<ProtocolLabel, Counter>::default();
+        registry.register(
+            "s5_honeypot_triggers_total",
+            "Logins with a decoy honeypot credential",
+            honeypot_triggers_total.clone(),
+        );
+
+        let credential_spraying_detected_total = Family::<ProtocolLabel, Counter>::default();
+        registry.register(
+            "s5_credential_spraying_detected_total",
+            "Sources detected failing logins as many different usernames",
+            credential_spraying_detected_total.clone(),
+        );
+
+        let account_lockouts_total = Counter::default();
+        registry.register(
+            "s5_account_lockouts_total",
+            "Accounts locked after repeated failed logins",
+            account_lockouts_total.clone(),
+        );
+
+        let impossible_travel_detected_total = Family::<ProtocolLabel, Counter>::default();
+        registry.register(
+            "s5_impossible_travel_detected_total",
+            "Logins too far from a recent login by the same user to have travelled in between",
+            impossible_travel_detected_total.clone(),
+        );
+
+        let sessions_closed_total = Family::<ProtocolReasonLabel, Counter>::default();
+        registry.register(
+            "s5_sessions_closed_total",
+            "Relayed sessions closed, by protocol and close reason",
+            sessions_closed_total.clone(),
+        );
+
+        let app_protocol_bytes_total = Family::<AppProtocolDirectionLabel, Counter>::default();
+        registry.register(
+            "s5_app_protocol_bytes_total",
+            "Bytes relayed by closed sessions, by application protocol and direction",
+            app_protocol_bytes_total.clone(),
+        );
+
         let audit_events_dropped = Counter::default();
         registry.register(
             "s5_audit_events_dropped_total",
@@ -161,6 +443,27 @@ impl MetricsRegistry {
             audit_events_dropped.clone(),
         );
 
+        let audit_archive_lag_seconds = Gauge::default();
+        registry.register(
+            "s5_audit_archive_lag_seconds",
+            "Age of the oldest rotated audit file not yet archived (0 = none)",
+            audit_archive_lag_seconds.clone(),
+        );
+
+        let audit_archive_uploads_total = Counter::default();
+        registry.register(
+            "s5_audit_archive_uploads_total",
+            "Rotated audit files uploaded to object storage",
+            audit_archive_uploads_total.clone(),
+        );
+
+        let audit_archive_failures_total = Counter::default();
+        registry.register(
+            "s5_audit_archive_failures_total",
+            "Failed uploads of rotated audit files",
+            audit_archive_failures_total.clone(),
+        );
+
         let cardinality_capped_total = Counter::default();
         registry.register(
             "s5_metrics_cardinality_capped_total",
@@ -200,13 +503,12 @@ impl MetricsRegistry {
             connection_duration_seconds.clone(),
         );
 
-        let connection_duration_by_type_seconds = Family::<
-            ConnectionTypeUserLabel,
-            Histogram,
-            ConnectionDurationHistogramBuilder,
-        >::new_with_constructor(
-            ConnectionDurationHistogramBuilder
-        );
+        let connection_duration_by_type_seconds =
+            Family::<
+                ConnectionTypeUserLabel,
+                HistogramWithExemplars<CorrelationLabel>,
+                ConnectionDurationHistogramBuilder,
+            >::new_with_constructor(ConnectionDurationHistogramBuilder);
         registry.register(
             "s5_connection_duration_by_type_seconds",
             "Connection duration in seconds by connection type (ssh/socks5)",
@@ -220,6 +522,13 @@ impl MetricsRegistry {
             connections_rejected_total.clone(),
         );
 
+        let connections_shed_total = Family::<ReasonLabel, Counter>::default();
+        registry.register(
+            "s5_connections_shed",
+            "Connections shed under overload, by reason",
+            connections_shed_total.clone(),
+        );
+
         let http_requests_total = Family::<HttpRequestLabel, Counter>::default();
         registry.register(
             "s5_http_requests_total",
@@ -254,6 +563,82 @@ impl MetricsRegistry {
             dns_cache_misses_total.clone(),
         );
 
+        let dns_cache_evictions_total = Family::<ReasonLabel, Counter>::default();
+        registry.register(
+            "s5_dns_cache_evictions_total",
+            "DNS cache entries dropped, by reason (capacity or expired)",
+            dns_cache_evictions_total.clone(),
+        );
+
+        let circuit_breaker_opened_total = Counter::default();
+        registry.register(
+            "s5_circuit_breaker_opened_total",
+            "Destination circuits opened after repeated connect failures",
+            circuit_breaker_opened_total.clone(),
+        );
+
+        let circuit_breaker_rejected_total = Counter::default();
+        registry.register(
+            "s5_circuit_breaker_rejected_total",
+            "Connects failed fast because the destination circuit is open",
+            circuit_breaker_rejected_total.clone(),
+        );
+
+        let connect_retries_total = Family::<ReasonLabel, Counter>::default();
+        registry.register(
+            "s5_connect_retries_total",
+            "Outbound connect retries after a transient failure, by reason (refused or timeout)",
+            connect_retries_total.clone(),
+        );
+
+        let dns_prefetch_total = Family::<OutcomeLabel, Counter>::default();
+        registry.register(
+            "s5_dns_prefetch_total",
+            "DNS cache entries of hot hostnames refreshed before expiry, by outcome",
+            dns_prefetch_total.clone(),
+        );
+
+        let dns_resolution_duration_seconds =
+            Family::<OutcomeLabel, Histogram, LatencyHistogramBuilder>::new_with_constructor(
+                LatencyHistogramBuilder,
+            );
+        registry.register(
+            "s5_dns_resolution_duration_seconds",
+            "Upstream DNS resolution time in seconds (cache misses) by outcome",
+            dns_resolution_duration_seconds.clone(),
+        );
+
+        let tcp_connect_duration_seconds =
+            Family::<OutcomeLabel, Histogram, LatencyHistogramBuilder>::new_with_constructor(
+                LatencyHistogramBuilder,
+            );
+        registry.register(
+            "s5_tcp_connect_duration_seconds",
+            "TCP connect time to the target in seconds by outcome",
+            tcp_connect_duration_seconds.clone(),
+        );
+
+        let ssh_auth_duration_seconds = Family::<
+            AuthMethodOutcomeLabel,
+            Histogram,
+            LatencyHistogramBuilder,
+        >::new_with_constructor(LatencyHistogramBuilder);
+        registry.register(
+            "s5_ssh_auth_duration_seconds",
+            "SSH credential verification time in seconds by method and outcome",
+            ssh_auth_duration_seconds.clone(),
+        );
+
+        let channel_setup_duration_seconds =
+            Family::<OutcomeLabel, Histogram, LatencyHistogramBuilder>::new_with_constructor(
+                LatencyHistogramBuilder,
+            );
+        registry.register(
+            "s5_channel_setup_duration_seconds",
+            "Total forwarding channel setup time in seconds (ACL, DNS, connect) by outcome",
+            channel_setup_duration_seconds.clone(),
+        );
+
         let process_resident_memory_bytes = Gauge::default();
         registry.register(
             "process_resident_memory_bytes",
@@ -268,6 +653,63 @@ impl MetricsRegistry {
             process_open_fds.clone(),
         );
 
+        let tokio_workers = Gauge::default();
+        registry.register(
+            "s5_tokio_workers",
+            "Number of Tokio runtime worker threads",
+            tokio_workers.clone(),
+        );
+
+        let tokio_alive_tasks = Gauge::default();
+        registry.register(
+            "s5_tokio_alive_tasks",
+            "Number of tasks alive on the Tokio runtime",
+            tokio_alive_tasks.clone(),
+        );
+
+        let tokio_global_queue_depth = Gauge::default();
+        registry.register(
+            "s5_tokio_global_queue_depth",
+            "Tasks waiting in the Tokio runtime's shared injection queue",
+            tokio_global_queue_depth.clone(),
+        );
+
+        let tokio_worker_busy_seconds_total =
+            Family::<WorkerLabel, Counter<f64, AtomicU64>>::default();
+        registry.register(
+            "s5_tokio_worker_busy_seconds",
+            "Time each Tokio worker spent running tasks in seconds",
+            tokio_worker_busy_seconds_total.clone(),
+        );
+
+        let tokio_worker_park_total = Family::<WorkerLabel, Counter>::default();
+        registry.register(
+            "s5_tokio_worker_park",
+            "Times each Tokio worker parked because it ran out of work",
+            tokio_worker_park_total.clone(),
+        );
+
+        let forwarding_tasks_active = Gauge::default();
+        registry.register(
+            "s5_forwarding_tasks_active",
+            "Relay direction tasks currently running (two per forwarded connection)",
+            forwarding_tasks_active.clone(),
+        );
+
+        let forwarding_tasks_spawned_total = Counter::default();
+        registry.register(
+            "s5_forwarding_tasks_spawned",
+            "Relay direction tasks spawned since startup",
+            forwarding_tasks_spawned_total.clone(),
+        );
+
+        let session_buffered_bytes = Gauge::default();
+        registry.register(
+            "s5_session_buffered_bytes",
+            "Bytes held in memory by all live sessions (relay chunks, capture queues)",
+            session_buffered_bytes.clone(),
+        );
+
         Self {
             registry,
             connections_active,
@@ -277,7 +719,27 @@ impl MetricsRegistry {
             auth_successes_total,
             errors_total,
             banned_ips_current,
+            threat_intel_blocked_total,
+            threat_intel_entries,
+            threat_intel_refresh_failures_total,
+            tarpit_active,
+            tarpit_connections_total,
+            tarpit_rejected_total,
+            ssh_unauthenticated_connections,
+            client_connections,
+            client_connections_queued,
+            ssh_channels_stalled,
+            ssh_channel_stalls_total,
+            honeypot_triggers_total,
+            credential_spraying_detected_total,
+            account_lockouts_total,
+            impossible_travel_detected_total,
+            sessions_closed_total,
+            app_protocol_bytes_total,
             audit_events_dropped,
+            audit_archive_lag_seconds,
+            audit_archive_uploads_total,
+            audit_archive_failures_total,
             cardinality_capped_total,
             quota_bandwidth_used_bytes,
             quota_connections_used,
@@ -285,20 +747,44 @@ impl MetricsRegistry {
             connection_duration_seconds,
             connection_duration_by_type_seconds,
             connections_rejected_total,
+            connections_shed_total,
             http_requests_total,
             http_request_duration_seconds,
             dns_cache_hits_total,
             dns_cache_misses_total,
+            dns_cache_evictions_total,
+            dns_prefetch_total,
+            circuit_breaker_opened_total,
+            circuit_breaker_rejected_total,
+            connect_retries_total,
+            dns_resolution_duration_seconds,
+            tcp_connect_duration_seconds,
+            ssh_auth_duration_seconds,
+            channel_setup_duration_seconds,
             process_resident_memory_bytes,
             process_open_fds,
+            tokio_workers,
+            tokio_alive_tasks,
+            tokio_global_queue_depth,
+            tokio_worker_busy_seconds_total,
+            tokio_worker_park_total,
+            forwarding_tasks_active,
+            forwarding_tasks_spawned_total,
+            session_buffered_bytes,
+            user_connections_total,
             known_users: DashSet::new(),
             max_labels,
+            user_labels: true,
         }
     }
 
     /// Resolve a username label, capping cardinality at max_labels.
-    /// Returns "_other" if the cap is exceeded for a previously unseen user.
+    /// Returns "_other" if the cap is exceeded for a previously unseen user,
+    /// or "_all" when per-user labels are disabled.
     fn resolve_label(&self, username: &str) -> String {
+        if !self.user_labels {
+            return AGGREGATE_USER_LABEL.to_string();
+        }
         if self.known_users.contains(username) {
             return username.to_string();
         }
@@ -309,7 +795,28 @@ impl MetricsRegistry {
         }
         // Cardinality cap exceeded
         self.cardinality_capped_total.inc();
-        "_other".to_string()
+        OTHER_USER_LABEL.to_string()
+    }
+
+    /// Count a newly opened connection for `username` (active gauge + lifetime
+    /// counter). Returns the label to pass to [`Self::record_user_connection_close`],
+    /// so the gauge is decremented on the same series even after a prune.
+    pub fn record_user_connection_open(&self, username: &str) -> String {
+        let label = self.resolve_label(username);
+        let user_label = UserLabel {
+            user: label.clone(),
+        };
+        self.connections_active.get_or_create(&user_label).inc();
+        self.user_connections_total.get_or_create(&user_label).inc();
+        label
+    }
+
+    pub fn record_user_connection_close(&self, label: &str) {
+        self.connections_active
+            .get_or_create(&UserLabel {
+                user: label.to_string(),
+            })
+            .dec();
     }
 
     pub fn record_auth_success(&self, username: &str, method: &str) {
@@ -338,6 +845,30 @@ impl MetricsRegistry {
             .inc();
     }
 
+    /// Count a closed relay session (`reason` is a [`CloseReason`] string).
+    ///
+    /// [`CloseReason`]: crate::proxy::close::CloseReason
+    pub fn record_session_closed(&self, protocol: &str, reason: &str) {
+        self.sessions_closed_total
+            .get_or_create(&ProtocolReasonLabel {
+                protocol: protocol.to_string(),
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
+    /// Add a closed session's bytes to its application protocol.
+    pub fn record_app_protocol_bytes(&self, app_protocol: &str, bytes_up: u64, bytes_down: u64) {
+        for (direction, bytes) in [("up", bytes_up), ("down", bytes_down)] {
+            self.app_protocol_bytes_total
+                .get_or_create(&AppProtocolDirectionLabel {
+                    app_protocol: app_protocol.to_string(),
+                    direction: direction.to_string(),
+                })
+                .inc_by(bytes);
+        }
+    }
+
     pub fn record_bytes_transferred(&self, username: &str, bytes: u64) {
         let label = self.resolve_label(username);
         self.bytes_transferred
@@ -381,6 +912,31 @@ impl MetricsRegistry {
         username: &str,
         conn_type: &str,
         duration_secs: f64,
+    ) {
+        self.observe_typed_connection_duration(username, conn_type, duration_secs, None);
+    }
+
+    /// Like [`record_typed_connection_duration`](Self::record_typed_connection_duration),
+    /// attaching the connection's correlation ID as an exemplar.
+    pub fn record_typed_connection_duration_cid(
+        &self,
+        username: &str,
+        conn_type: &str,
+        duration_secs: f64,
+        cid: &str,
+    ) {
+        let exemplar = CorrelationLabel {
+            correlation_id: cid.to_string(),
+        };
+        self.observe_typed_connection_duration(username, conn_type, duration_secs, Some(exemplar));
+    }
+
+    fn observe_typed_connection_duration(
+        &self,
+        username: &str,
+        conn_type: &str,
+        duration_secs: f64,
+        exemplar: Option<CorrelationLabel>,
     ) {
         let label = self.resolve_label(username);
         // Record into the type-labeled histogram
@@ -389,7 +945,7 @@ impl MetricsRegistry {
                 conn_type: conn_type.to_string(),
                 user: label.clone(),
             })
-            .observe(duration_secs);
+            .observe(duration_secs, exemplar, None);
         // Also record into the existing user-only histogram for backward compatibility
         self.connection_duration_seconds
             .get_or_create(&UserLabel { user: label })
@@ -414,6 +970,15 @@ impl MetricsRegistry {
             .inc();
     }
 
+    /// Count a connection shed under overload (see [`shed_reasons`]).
+    pub fn record_connection_shed(&self, reason: &str) {
+        self.connections_shed_total
+            .get_or_create(&ReasonLabel {
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
     pub fn record_http_request(&self, method: &str, path: &str, status: u16) {
         // Pre-format status to avoid itoa allocation each time
         let status_str = match status {
@@ -444,6 +1009,63 @@ impl MetricsRegistry {
             })
             .observe(duration_secs);
     }
+
+    pub fn record_dns_resolution(&self, outcome: &str, duration_secs: f64) {
+        self.dns_resolution_duration_seconds
+            .get_or_create(&OutcomeLabel {
+                outcome: outcome.to_string(),
+            })
+            .observe(duration_secs);
+    }
+
+    pub fn record_dns_cache_evictions(&self, reason: &str, count: u64) {
+        self.dns_cache_evictions_total
+            .get_or_create(&ReasonLabel {
+                reason: reason.to_string(),
+            })
+            .inc_by(count);
+    }
+
+    pub fn record_connect_retry(&self, reason: &str) {
+        self.connect_retries_total
+            .get_or_create(&ReasonLabel {
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_dns_prefetch(&self, outcome: &str) {
+        self.dns_prefetch_total
+            .get_or_create(&OutcomeLabel {
+                outcome: outcome.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_tcp_connect(&self, outcome: &str, duration_secs: f64) {
+        self.tcp_connect_duration_seconds
+            .get_or_create(&OutcomeLabel {
+                outcome: outcome.to_string(),
+            })
+            .observe(duration_secs);
+    }
+
+    pub fn record_ssh_auth(&self, method: &str, outcome: &str, duration_secs: f64) {
+        self.ssh_auth_duration_seconds
+            .get_or_create(&AuthMethodOutcomeLabel {
+                method: method.to_string(),
+                outcome: outcome.to_string(),
+            })
+            .observe(duration_secs);
+    }
+
+    pub fn record_channel_setup(&self, outcome: &str, duration_secs: f64) {
+        self.channel_setup_duration_seconds
+            .get_or_create(&OutcomeLabel {
+                outcome: outcome.to_string(),
+            })
+            .observe(duration_secs);
+    }
 }
 
 impl MetricsRegistry {
@@ -466,6 +1088,43 @@ impl MetricsRegistry {
         }
     }
 
+    /// Update Tokio runtime metrics (workers, alive tasks, queue depth,
+    /// per-worker busy time and parks), the relay task counts and the bytes
+    /// buffered by sessions.
+    pub fn update_runtime_metrics(&self, handle: &tokio::runtime::Handle) {
+        let runtime = handle.metrics();
+        self.tokio_workers.set(runtime.num_workers() as i64);
+        self.tokio_alive_tasks.set(runtime.num_alive_tasks() as i64);
+        self.tokio_global_queue_depth
+            .set(runtime.global_queue_depth() as i64);
+
+        // The runtime reports totals since startup; the counters follow them
+        for worker in 0..runtime.num_workers() {
+            let label = WorkerLabel {
+                worker: worker.to_string(),
+            };
+            let busy = runtime.worker_total_busy_duration(worker).as_secs_f64();
+            let counter = self.tokio_worker_busy_seconds_total.get_or_create(&label);
+            let seen = counter.get();
+            if busy > seen {
+                counter.inc_by(busy - seen);
+            }
+            let parks = runtime.worker_park_count(worker);
+            let counter = self.tokio_worker_park_total.get_or_create(&label);
+            counter.inc_by(parks.saturating_sub(counter.get()));
+        }
+
+        let tasks = crate::proxy::forwarder::forwarding_tasks();
+        self.forwarding_tasks_active.set(tasks.active as i64);
+        self.forwarding_tasks_spawned_total.inc_by(
+            tasks
+                .spawned
+                .saturating_sub(self.forwarding_tasks_spawned_total.get()),
+        );
+        self.session_buffered_bytes
+            .set(crate::proxy::memory::total_buffered() as i64);
+    }
+
     /// Remove stale users from the known_users set.
     /// Call after config reload to prevent unbounded growth.
     pub fn prune_known_users(&self, active_usernames: &[String]) {
@@ -0,0 +1,527 @@
+//! Notifications for security events (`[notifications]`).
+//!
+//! The audit writer hands every event to [`Notifier::observe`]. Events that
+//! match a rule are queued for each of the rule's sinks: email over SMTP or a
+//! chat/HTTP sink (see [`sinks`]). A repeat of the same event (same IP
+//! banned, same user and quota, same user and country) within
+//! `dedup_window_secs` is only counted. Every `batch_window_secs` the queue
+//! is sent as one digest per destination, so a ban storm produces a single
+//! message rather than one per IP.
+
+pub mod sinks;
+pub mod smtp;
+
+use crate::audit::events::AuditEvent;
+use crate::config::types::{
+    AppConfig, NotificationEvent, NotificationsConfig, SmtpConfig, EMAIL_SINK,
+};
+use crate::geoip::GeoIpService;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// A security event that matched at least one rule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Identity used for deduplication, e.g. `ban:203.0.113.7`
+    pub key: String,
+    pub summary: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Where a digest goes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Route {
+    /// Email to these recipients (sorted)
+    Email(Vec<String>),
+    /// Chat/HTTP sink, by name
+    Sink(String),
+}
+
+/// One message ready to send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub route: Route,
+    pub subject: String,
+    /// Plain-text listing of the events
+    pub body: String,
+    pub events: Vec<Notification>,
+    /// Repeats dropped by deduplication
+    pub suppressed: u64,
+}
+
+#[derive(Default)]
+struct BatchState {
+    /// Queued notifications by destination
+    pending: BTreeMap<Route, Vec<Notification>>,
+    /// Repeats dropped since the last flush, by destination
+    suppressed: BTreeMap<Route, u64>,
+    /// When each key was last queued
+    last_queued: HashMap<String, Instant>,
+}
+
+/// Rule matching, deduplication and batching (no I/O).
+pub struct NotificationBatcher {
+    config: NotificationsConfig,
+    default_to: Vec<String>,
+    server_id: String,
+    state: Mutex<BatchState>,
+}
+
+impl NotificationBatcher {
+    pub fn new(config: NotificationsConfig, server_id: &str) -> Self {
+        let default_to = config
+            .smtp
+            .as_ref()
+            .map(|s| s.to.clone())
+            .unwrap_or_default();
+        Self {
+            config,
+            default_to,
+            server_id: server_id.to_string(),
+            state: Mutex::new(BatchState::default()),
+        }
+    }
+
+    /// Whether any rule wants this kind of event.
+    pub fn wants(&self, event: NotificationEvent) -> bool {
+        self.config.rules.iter().any(|r| r.event == event)
+    }
+
+    /// Destinations of every rule matching `n`. Email recipients of all
+    /// matching rules are merged into a single route.
+    fn routes(&self, n: &Notification) -> BTreeSet<Route> {
+        let mut routes = BTreeSet::new();
+        let mut to = BTreeSet::new();
+        for rule in &self.config.rules {
+            if rule.event != n.event {
+                continue;
+            }
+            if !rule.users.is_empty() {
+                match n.username {
+                    Some(ref user) if rule.users.contains(user) => {}
+                    _ => continue,
+                }
+            }
+            for sink in rule.sink_names() {
+                if sink != EMAIL_SINK {
+                    routes.insert(Route::Sink(sink.to_string()));
+                } else if rule.to.is_empty() {
+                    to.extend(self.default_to.iter().cloned());
+                } else {
+                    to.extend(rule.to.iter().cloned());
+                }
+            }
+        }
+        if !to.is_empty() {
+            routes.insert(Route::Email(to.into_iter().collect()));
+        }
+        routes
+    }
+
+    /// Queue a notification. Returns false if no rule matched or it repeats
+    /// an event queued less than `dedup_window_secs` ago.
+    pub fn push(&self, n: Notification, now: Instant) -> bool {
+        let routes = self.routes(&n);
+        if routes.is_empty() {
+            return false;
+        }
+        let window = Duration::from_secs(self.config.dedup_window_secs);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&at) = state.last_queued.get(&n.key) {
+            if now.saturating_duration_since(at) < window {
+                for route in routes {
+                    *state.suppressed.entry(route).or_default() += 1;
+                }
+                return false;
+            }
+        }
+        state.last_queued.insert(n.key.clone(), now);
+        for route in routes {
+            state.pending.entry(route).or_default().push(n.clone());
+        }
+        true
+    }
+
+    /// Take everything queued and build one digest per destination.
+    pub fn flush(&self, now: Instant) -> Vec<Digest> {
+        let window = Duration::from_secs(self.config.dedup_window_secs);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .last_queued
+            .retain(|_, at| now.saturating_duration_since(*at) < window);
+        let pending = std::mem::take(&mut state.pending);
+        let mut suppressed = std::mem::take(&mut state.suppressed);
+        drop(state);
+
+        pending
+            .into_iter()
+            .map(|(route, events)| {
+                let repeats = suppressed.remove(&route).unwrap_or(0);
+                self.digest(route, events, repeats)
+            })
+            .collect()
+    }
+
+    fn digest(&self, route: Route, events: Vec<Notification>, repeats: u64) -> Digest {
+        let subject = match events.as_slice() {
+            [only] => format!("[s5 {}] {}", self.server_id, only.summary),
+            _ => format!("[s5 {}] {} security events", self.server_id, events.len()),
+        };
+        let mut body = format!("Security events on s5 server '{}':\n\n", self.server_id);
+        let shown = self.config.max_events_per_email;
+        for n in events.iter().take(shown) {
+            body.push_str(&format!(
+                "{}  {:<18}  {}\n",
+                n.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                n.event.to_string(),
+                n.summary
+            ));
+        }
+        if events.len() > shown {
+            body.push_str(&format!("... and {} more\n", events.len() - shown));
+        }
+        if repeats > 0 {
+            body.push_str(&format!(
+                "\n{} repeated event(s) suppressed (dedup window {}s)\n",
+                repeats, self.config.dedup_window_secs
+            ));
+        }
+        Digest {
+            route,
+            subject,
+            body,
+            events,
+            suppressed: repeats,
+        }
+    }
+}
+
+/// Countries each user has logged in from since startup.
+#[derive(Default)]
+pub struct CountryTracker {
+    seen: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl CountryTracker {
+    /// Record a login. Returns the previously seen countries (sorted) when
+    /// `country` is new for a user who already had logins on record; a
+    /// user's first login only sets the baseline.
+    pub fn record(&self, username: &str, country: &str) -> Option<Vec<String>> {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let countries = seen.entry(username.to_string()).or_default();
+        let first = countries.is_empty();
+        if !countries.insert(country.to_string()) || first {
+            return None;
+        }
+        let mut previous: Vec<String> = countries
+            .iter()
+            .filter(|c| c.as_str() != country)
+            .cloned()
+            .collect();
+        previous.sort();
+        Some(previous)
+    }
+}
+
+/// Server-wide auth failures over a sliding window.
+pub struct FailureWindow {
+    window: Duration,
+    times: Mutex<VecDeque<Instant>>,
+}
+
+impl FailureWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            times: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record a failure at `now`; returns the count within the window.
+    pub fn record(&self, now: Instant) -> usize {
+        let mut times = self.times.lock().unwrap_or_else(|e| e.into_inner());
+        while times
+            .front()
+            .is_some_and(|t| now.saturating_duration_since(*t) >= self.window)
+        {
+            times.pop_front();
+        }
+        times.push_back(now);
+        times.len()
+    }
+}
+
+/// Turns audit events into batched notifications.
+pub struct Notifier {
+    batcher: NotificationBatcher,
+    smtp: Option<SmtpConfig>,
+    sinks: HashMap<String, sinks::Sink>,
+    batch_window: Duration,
+    geoip: Option<GeoIpService>,
+    countries: CountryTracker,
+    auth_failures: FailureWindow,
+    auth_failure_threshold: usize,
+}
+
+impl Notifier {
+    pub fn new(config: &AppConfig) -> Self {
+        let n = &config.notifications;
+        // Lookup only: the country allow/deny lists are not applied here
+        let geoip = n
+            .rules
+            .iter()
+            .any(|r| r.event == NotificationEvent::NewCountry)
+            .then(|| {
+                GeoIpService::new(
+                    config.geoip.enabled,
+                    config.geoip.database_path.as_deref(),
+                    Vec::new(),
+                    Vec::new(),
+                    false,
+                )
+            });
+        Self {
+            batcher: NotificationBatcher::new(n.clone(), &config.server.server_id),
+            smtp: n.smtp.clone(),
+            sinks: n
+                .sinks
+                .iter()
+                .map(|s| (s.name.clone(), sinks::Sink::new(s.clone())))
+                .collect(),
+            batch_window: Duration::from_secs(n.batch_window_secs.max(1)),
+            geoip,
+            countries: CountryTracker::default(),
+            auth_failures: FailureWindow::new(Duration::from_secs(n.auth_failure_window_secs)),
+            auth_failure_threshold: n.auth_failure_threshold as usize,
+        }
+    }
+
+    /// Queue the notification for `event`, if a rule matches.
+    pub fn observe(&self, event: &AuditEvent) {
+        if let Some(n) = self.notification_for(event) {
+            debug!(key = %n.key, "Security event queued for notification");
+            self.batcher.push(n, Instant::now());
+        }
+    }
+
+    fn notification_for(&self, event: &AuditEvent) -> Option<Notification> {
+        match event {
+            AuditEvent::BanCreated {
+                timestamp,
+                ip,
+                duration_secs,
+                tenant,
+            } if self.batcher.wants(NotificationEvent::Ban) => Some(Notification {
+                event: NotificationEvent::Ban,
+                username: None,
+                key: format!("ban:{}", ip),
+                summary: match tenant {
+                    Some(tenant) => {
+                        format!(
+                            "IP {} banned from tenant {} for {}s",
+                            ip, tenant, duration_secs
+                        )
+                    }
+                    None => format!("IP {} banned for {}s", ip, duration_secs),
+                },
+                timestamp: *timestamp,
+            }),
+            AuditEvent::QuotaExceeded {
+                timestamp,
+                username,
+                quota_type,
+                current_usage,
+                limit,
+                ..
+            } if self.batcher.wants(NotificationEvent::QuotaExceeded) => Some(Notification {
+                event: NotificationEvent::QuotaExceeded,
+                username: Some(username.clone()),
+                key: format!("quota:{}:{}", username, quota_type),
+                summary: format!(
+                    "User {} exhausted the {} quota ({}/{})",
+                    username, quota_type, current_usage, limit
+                ),
+                timestamp: *timestamp,
+            }),
+            AuditEvent::AuthSuccess {
+                timestamp,
+                username,
+                source_ip,
+                ..
+            } if self.batcher.wants(NotificationEvent::NewCountry) => {
+                let ip: IpAddr = source_ip.parse().ok()?;
+                let country = self.geoip.as_ref()?.country(&ip)?;
+                let previous = self.countries.record(username, &country)?;
+                Some(Notification {
+                    event: NotificationEvent::NewCountry,
+                    username: Some(username.clone()),
+                    key: format!("new_country:{}:{}", username, country),
+                    summary: format!(
+                        "User {} logged in from new country {} ({}; before: {})",
+                        username,
+                        country,
+                        source_ip,
+                        previous.join(", ")
+                    ),
+                    timestamp: *timestamp,
+                })
+            }
+            AuditEvent::LoginAnomaly {
+                timestamp,
+                username,
+                source_ip,
+                reasons,
+                country,
+                asn,
+                hour,
+                ..
+            } if self.batcher.wants(NotificationEvent::LoginAnomaly) => {
+                let mut details = vec![format!("{:02}:00 UTC", hour)];
+                details.extend(country.clone());
+                details.extend(asn.map(|a| format!("AS{}", a)));
+                Some(Notification {
+                    event: NotificationEvent::LoginAnomaly,
+                    username: Some(username.clone()),
+                    key: format!(
+                        "login_anomaly:{}:{}:{}",
+                        username,
+                        reasons.join(","),
+                        details[1..].join(",")
+                    ),
+                    summary: format!(
+                        "Unusual login for user {} from {} ({}; {})",
+                        username,
+                        source_ip,
+                        reasons.join(", "),
+                        details.join(", ")
+                    ),
+                    timestamp: *timestamp,
+                })
+            }
+            AuditEvent::KeyEnrollmentRequested {
+                timestamp,
+                request_id,
+                username,
+                source_ip,
+                fingerprint,
+                ..
+            } if self.batcher.wants(NotificationEvent::KeyEnrollment) => Some(Notification {
+                event: NotificationEvent::KeyEnrollment,
+                username: Some(username.clone()),
+                key: format!("key_enrollment:{}", request_id),
+                summary: format!(
+                    "User {} presented new key {} from {}, awaiting approval (request {})",
+                    username, fingerprint, source_ip, request_id
+                ),
+                timestamp: *timestamp,
+            }),
+            AuditEvent::KeyExpiring {
+                timestamp,
+                username,
+                fingerprint,
+                expires_at,
+                days_left,
+                ..
+            } if self.batcher.wants(NotificationEvent::KeyExpiring) => Some(Notification {
+                event: NotificationEvent::KeyExpiring,
+                username: Some(username.clone()),
+                // One reminder per key per day left
+                key: format!("key_expiring:{}:{}:{}", username, fingerprint, days_left),
+                summary: format!(
+                    "Public key {} of user {} expires on {} ({} days left)",
+                    fingerprint,
+                    username,
+                    expires_at.format("%Y-%m-%d %H:%M UTC"),
+                    days_left
+                ),
+                timestamp: *timestamp,
+            }),
+            AuditEvent::AuthFailure {
+                timestamp,
+                username,
+                source_ip,
+                ..
+            } if self.batcher.wants(NotificationEvent::AuthFailureSpike) => {
+                let count = self.auth_failures.record(Instant::now());
+                if count < self.auth_failure_threshold {
+                    return None;
+                }
+                // Server-wide: one key, so deduplication throttles the spike
+                Some(Notification {
+                    event: NotificationEvent::AuthFailureSpike,
+                    username: None,
+                    key: "auth_failure_spike".to_string(),
+                    summary: format!(
+                        "{} authentication failures in {}s (latest: {} from {})",
+                        count,
+                        self.auth_failures.window.as_secs(),
+                        username,
+                        source_ip
+                    ),
+                    timestamp: *timestamp,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Build the digests queued so far (see [`NotificationBatcher::flush`]).
+    pub fn flush(&self) -> Vec<Digest> {
+        self.batcher.flush(Instant::now())
+    }
+
+    async fn deliver(&self, digest: &Digest) -> anyhow::Result<()> {
+        match digest.route {
+            Route::Email(ref to) => match self.smtp {
+                Some(ref smtp) => smtp::send(smtp, to, digest).await,
+                None => anyhow::bail!("no SMTP server configured"),
+            },
+            Route::Sink(ref name) => match self.sinks.get(name) {
+                Some(sink) => sink.send(digest).await,
+                None => anyhow::bail!("unknown sink '{}'", name),
+            },
+        }
+    }
+
+    /// Send digests every batch window until `shutdown`, then flush once more.
+    /// Failed deliveries are logged and dropped.
+    pub async fn run(self: Arc<Self>, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + self.batch_window,
+            self.batch_window,
+        );
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            let stopping = tokio::select! {
+                _ = shutdown.cancelled() => true,
+                _ = interval.tick() => false,
+            };
+            for digest in self.flush() {
+                match self.deliver(&digest).await {
+                    Ok(()) => debug!(
+                        route = ?digest.route,
+                        subject = %digest.subject,
+                        "Notification sent"
+                    ),
+                    Err(e) => warn!(
+                        route = ?digest.route,
+                        error = %e,
+                        "Failed to send notification"
+                    ),
+                }
+            }
+            if stopping {
+                break;
+            }
+        }
+    }
+}
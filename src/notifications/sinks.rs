@@ -0,0 +1,172 @@
+//! Chat and HTTP sinks for notification digests (`[[notifications.sinks]]`).
+//!
+//! Each kind gets the message shape its service expects: Slack and Discord
+//! incoming webhooks, a Matrix `m.room.message` sent with the client-server
+//! API, or a generic JSON webhook carrying the structured events. Delivery
+//! uses the same DNS rebinding protection as `[[webhooks]]`.
+
+use super::Digest;
+use crate::config::types::{NotificationSinkConfig, NotificationSinkKind};
+use crate::webhooks;
+use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::json;
+
+/// Discord rejects message content longer than this many characters.
+const DISCORD_MAX_CONTENT: usize = 2000;
+
+/// Slack truncates `text` beyond this; keep well below it.
+const SLACK_MAX_TEXT: usize = 39_000;
+
+/// HTTP request that delivers one digest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkRequest {
+    pub method: reqwest::Method,
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    /// JSON body, sent as-is (signatures cover these exact bytes)
+    pub body: String,
+}
+
+/// A configured notification sink.
+pub struct Sink {
+    config: NotificationSinkConfig,
+    client: reqwest::Client,
+}
+
+/// Cut `text` to at most `max` characters, marking the cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Escape the characters Slack treats as control sequences.
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn html_escape(text: &str) -> String {
+    slack_escape(text).replace('"', "&quot;")
+}
+
+impl Sink {
+    pub fn new(config: NotificationSinkConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .timeout(std::time::Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self { config, client }
+    }
+
+    /// Build the request for `digest` (no I/O).
+    pub fn request(&self, digest: &Digest) -> Result<SinkRequest> {
+        let body = digest.body.trim_end();
+        let (method, url, payload) = match self.config.kind {
+            NotificationSinkKind::Webhook => (
+                reqwest::Method::POST,
+                self.config.url.clone(),
+                json!({
+                    "source": "s5",
+                    "subject": digest.subject,
+                    "text": digest.body,
+                    "events": digest.events,
+                    "suppressed": digest.suppressed,
+                }),
+            ),
+            NotificationSinkKind::Slack => {
+                let text = format!(
+                    "*{}*\n```{}```",
+                    slack_escape(&digest.subject),
+                    slack_escape(body)
+                );
+                (
+                    reqwest::Method::POST,
+                    self.config.url.clone(),
+                    json!({ "text": truncate(&text, SLACK_MAX_TEXT) }),
+                )
+            }
+            NotificationSinkKind::Discord => {
+                let head = format!("**{}**\n```\n", digest.subject);
+                let room = DISCORD_MAX_CONTENT.saturating_sub(head.chars().count() + 4);
+                let content = format!("{}{}\n```", head, truncate(body, room));
+                (
+                    reqwest::Method::POST,
+                    self.config.url.clone(),
+                    // Usernames in events must never ping @everyone
+                    json!({
+                        "content": truncate(&content, DISCORD_MAX_CONTENT),
+                        "allowed_mentions": { "parse": [] },
+                    }),
+                )
+            }
+            NotificationSinkKind::Matrix => {
+                let room_id = self.config.room_id.as_deref().unwrap_or_default();
+                let url = format!(
+                    "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                    self.config.url.trim_end_matches('/'),
+                    utf8_percent_encode(room_id, NON_ALPHANUMERIC),
+                    uuid::Uuid::new_v4().simple()
+                );
+                (
+                    reqwest::Method::PUT,
+                    url,
+                    json!({
+                        "msgtype": "m.text",
+                        "body": format!("{}\n\n{}", digest.subject, body),
+                        "format": "org.matrix.custom.html",
+                        "formatted_body": format!(
+                            "<b>{}</b><pre>{}</pre>",
+                            html_escape(&digest.subject),
+                            html_escape(body)
+                        ),
+                    }),
+                )
+            }
+        };
+
+        let body = serde_json::to_string(&payload)?;
+        let mut headers = vec![("Content-Type", "application/json".to_string())];
+        if let Some(ref token) = self.config.access_token {
+            headers.push(("Authorization", format!("Bearer {}", token)));
+        }
+        if let Some(ref secret) = self.config.secret {
+            headers.push(("X-Signature-256", webhooks::signature(secret, &body)?));
+        }
+        Ok(SinkRequest {
+            method,
+            url,
+            headers,
+            body,
+        })
+    }
+
+    /// Deliver `digest` (single attempt).
+    pub async fn send(&self, digest: &Digest) -> Result<()> {
+        let request = self.request(digest)?;
+        let client =
+            webhooks::guarded_client(&self.client, &request.url, self.config.allow_private_ips)
+                .await?;
+        let mut builder = client.request(request.method, &request.url);
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder.body(request.body).send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "{} sink '{}' returned status {}",
+                self.config.kind,
+                self.config.name,
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
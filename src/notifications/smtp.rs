@@ -0,0 +1,244 @@
+//! Minimal SMTP submission client (RFC 5321) for notification emails.
+//!
+//! Supports STARTTLS (RFC 3207), implicit TLS and AUTH PLAIN (RFC 4954).
+//! Server certificates are checked against the Mozilla root store.
+
+use super::Digest;
+use crate::config::types::{SmtpConfig, SmtpTlsMode};
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+/// Name sent in EHLO.
+const EHLO_NAME: &str = "localhost";
+
+/// Longest reply line accepted (RFC 5321 section 4.5.3.1.5 allows 512).
+const MAX_REPLY_LINE: usize = 4096;
+
+/// Send one digest to `to` using `config`.
+pub async fn send(config: &SmtpConfig, to: &[String], mail: &Digest) -> Result<()> {
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let tcp = tokio::time::timeout(
+        timeout,
+        TcpStream::connect((config.host.as_str(), config.port)),
+    )
+    .await
+    .context("SMTP connect timed out")?
+    .with_context(|| format!("connecting to {}:{}", config.host, config.port))?;
+    let message = format_message(config, to, mail, chrono::Utc::now());
+
+    match config.tls {
+        SmtpTlsMode::None => {
+            let mut conn = SmtpConnection::open(tcp, timeout).await?;
+            conn.deliver(config, to, &message).await
+        }
+        SmtpTlsMode::Tls => {
+            let tls = tls_connect(&config.host, tcp).await?;
+            let mut conn = SmtpConnection::open(tls, timeout).await?;
+            conn.deliver(config, to, &message).await
+        }
+        SmtpTlsMode::Starttls => {
+            let mut conn = SmtpConnection::open(tcp, timeout).await?;
+            conn.ehlo().await?;
+            conn.command("STARTTLS", 220).await?;
+            let tls = tls_connect(&config.host, conn.into_inner()).await?;
+            // The session restarts from scratch after the TLS handshake
+            let mut conn = SmtpConnection::resume(tls, timeout);
+            conn.deliver(config, to, &message).await
+        }
+    }
+}
+
+async fn tls_connect(
+    host: &str,
+    tcp: TcpStream,
+) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())
+        .with_context(|| format!("invalid SMTP server name: {}", host))?;
+    tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .connect(name, tcp)
+        .await
+        .context("SMTP TLS handshake")
+}
+
+/// One SMTP session over an established stream.
+pub struct SmtpConnection<S> {
+    stream: BufReader<S>,
+    timeout: Duration,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpConnection<S> {
+    /// Wait for the server greeting.
+    pub async fn open(stream: S, timeout: Duration) -> Result<Self> {
+        let mut conn = Self::resume(stream, timeout);
+        conn.expect(220).await?;
+        Ok(conn)
+    }
+
+    /// Continue on a stream whose greeting was already consumed.
+    pub fn resume(stream: S, timeout: Duration) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            timeout,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Read a (possibly multi-line) reply: code and text lines.
+    async fn reply(&mut self) -> Result<(u16, Vec<String>)> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let n = tokio::time::timeout(self.timeout, self.stream.read_line(&mut line))
+                .await
+                .context("SMTP reply timed out")??;
+            if n == 0 {
+                anyhow::bail!("SMTP server closed the connection");
+            }
+            // "250-text" continues the reply, "250 text" ends it
+            let code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+            let (Some(code), Some(text)) = (code, line.get(4..)) else {
+                anyhow::bail!("malformed SMTP reply: {:?}", line.trim_end());
+            };
+            if line.len() > MAX_REPLY_LINE {
+                anyhow::bail!("SMTP reply line too long");
+            }
+            let last = line.as_bytes()[3] != b'-';
+            lines.push(text.trim_end().to_string());
+            if last {
+                return Ok((code, lines));
+            }
+        }
+    }
+
+    /// Read a reply and fail unless it is in the same class as `code`.
+    async fn expect(&mut self, code: u16) -> Result<Vec<String>> {
+        let (got, lines) = self.reply().await?;
+        if got / 100 != code / 100 {
+            anyhow::bail!("SMTP server replied {} {}", got, lines.join(" "));
+        }
+        Ok(lines)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        tokio::time::timeout(self.timeout, self.stream.get_mut().write_all(data))
+            .await
+            .context("SMTP write timed out")??;
+        Ok(())
+    }
+
+    async fn command(&mut self, line: &str, code: u16) -> Result<Vec<String>> {
+        self.write(format!("{}\r\n", line).as_bytes()).await?;
+        self.expect(code)
+            .await
+            .with_context(|| format!("after {}", line.split(' ').next().unwrap_or(line)))
+    }
+
+    async fn ehlo(&mut self) -> Result<Vec<String>> {
+        self.command(&format!("EHLO {}", EHLO_NAME), 250).await
+    }
+
+    /// EHLO, AUTH, envelope and message, then QUIT.
+    pub async fn deliver(
+        &mut self,
+        config: &SmtpConfig,
+        to: &[String],
+        message: &str,
+    ) -> Result<()> {
+        self.ehlo().await?;
+        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+            let token =
+                base64::engine::general_purpose::STANDARD.encode(format!("\0{}\0{}", user, pass));
+            self.command(&format!("AUTH PLAIN {}", token), 235)
+                .await
+                .context("SMTP authentication failed")?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", config.from), 250)
+            .await?;
+        for rcpt in to {
+            self.command(&format!("RCPT TO:<{}>", rcpt), 250).await?;
+        }
+        self.command("DATA", 354).await?;
+        self.write(dot_stuff(message).as_bytes()).await?;
+        self.write(b".\r\n").await?;
+        self.expect(250).await.context("after DATA")?;
+        // The message is accepted; a failed QUIT does not matter
+        let _ = self.command("QUIT", 221).await;
+        Ok(())
+    }
+}
+
+/// Escape lines starting with a dot (RFC 5321 section 4.5.2).
+fn dot_stuff(message: &str) -> String {
+    let mut out = String::with_capacity(message.len() + 16);
+    for line in message.split_inclusive("\r\n") {
+        if line.starts_with('.') {
+            out.push('.');
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Encode a header value as an RFC 2047 word if it is not plain ASCII.
+fn encode_header(value: &str) -> String {
+    let value: String = value.chars().filter(|c| !c.is_control()).collect();
+    if value.is_ascii() {
+        value
+    } else {
+        let b64 = base64::engine::general_purpose::STANDARD.encode(value.as_bytes());
+        format!("=?utf-8?B?{}?=", b64)
+    }
+}
+
+/// Build the RFC 5322 message for `mail` (CRLF line endings).
+pub fn format_message(
+    config: &SmtpConfig,
+    to: &[String],
+    mail: &Digest,
+    date: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let domain = config.from.rsplit('@').next().unwrap_or("localhost");
+    let mut headers = vec![
+        format!("From: <{}>", config.from),
+        format!(
+            "To: {}",
+            to.iter()
+                .map(|a| format!("<{}>", a))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        format!("Subject: {}", encode_header(&mail.subject)),
+        format!("Date: {}", date.to_rfc2822()),
+        format!("Message-ID: <{}@{}>", uuid::Uuid::new_v4(), domain),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Auto-Submitted: auto-generated".to_string(),
+    ];
+    let body = if mail.body.is_ascii() {
+        headers.push("Content-Transfer-Encoding: 7bit".to_string());
+        mail.body.lines().collect::<Vec<_>>().join("\r\n")
+    } else {
+        headers.push("Content-Transfer-Encoding: base64".to_string());
+        let b64 = base64::engine::general_purpose::STANDARD.encode(mail.body.as_bytes());
+        b64.as_bytes()
+            .chunks(76)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    };
+    format!("{}\r\n\r\n{}\r\n", headers.join("\r\n"), body)
+}
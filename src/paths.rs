@@ -0,0 +1,41 @@
+//! Platform-aware locations of the files s5 writes.
+//!
+//! Relative paths in the config (`host_key`, `flows.db`, ...) resolve against
+//! the working directory. Under a service manager that is the state
+//! directory: systemd sets it with `WorkingDirectory=`, and the Windows
+//! service switches to it at startup, since services start in `System32`.
+
+use std::path::PathBuf;
+
+/// Environment variable overriding [`state_dir`].
+pub const STATE_DIR_ENV: &str = "S5_STATE_DIR";
+
+/// Null device, for client config snippets generated on this host.
+pub const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+/// Directory for persistent state: `S5_STATE_DIR`, else `/var/lib/s5` on
+/// Unix and `%ProgramData%\s5` on Windows.
+pub fn state_dir() -> PathBuf {
+    match std::env::var_os(STATE_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => default_state_dir(),
+    }
+}
+
+#[cfg(windows)]
+fn default_state_dir() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join("s5")
+}
+
+#[cfg(not(windows))]
+fn default_state_dir() -> PathBuf {
+    PathBuf::from("/var/lib/s5")
+}
+
+/// Scratch file that need not survive a reboot (e.g. the demo host key).
+pub fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(name)
+}
@@ -80,3 +80,50 @@ pub fn check_and_log(
         }
     }
 }
+
+/// `reason` of the `acl.deny` audit event for a denied server name.
+pub const SERVER_NAME_CHECK: &str = "server name check";
+
+/// `reason` of the `acl.deny` audit event for a TLS or HTTP head without a
+/// readable server name, under an ACL with hostname rules.
+pub const SERVER_NAME_MISSING: &str = "server name missing";
+
+/// Check the server name a client sent inside its tunnel (TLS SNI or HTTP
+/// `Host`) as if it had been the requested host. The address actually
+/// connected to stands in for its resolved IP, so CIDR rules still apply.
+pub fn check_server_name_and_log(
+    acl: &ParsedAcl,
+    username: &str,
+    server_name: &str,
+    port: u16,
+    connected_ip: Option<IpAddr>,
+) -> AclDecision {
+    let host = without_port(server_name);
+    let (policy, matched_rule) = acl.check_verbose(host, port, connected_ip);
+    let allowed = policy == crate::config::acl::AclPolicy::Allow;
+    if !allowed {
+        warn!(
+            user = %username,
+            target = %format!("{}:{}", host, port),
+            connected_ip = ?connected_ip.map(|ip| ip.to_string()),
+            matched_rule = ?matched_rule,
+            reason = SERVER_NAME_CHECK,
+            "ACL: denied (server name)"
+        );
+    }
+    AclDecision {
+        allowed,
+        matched_rule,
+    }
+}
+
+/// Host part of an HTTP `Host` value (`name`, `name:port` or `[v6]:port`).
+fn without_port(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.parse::<u16>().is_ok() => name,
+        _ => host,
+    }
+}
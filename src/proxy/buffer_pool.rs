@@ -0,0 +1,99 @@
+//! Shared relay buffers (`[proxy.buffer_pool]`).
+//!
+//! Each relay direction borrows a buffer for its lifetime instead of
+//! allocating its own. On drop the buffer goes back to the pool, unless
+//! `max_buffers` are already idle there, so a burst of tunnels does not pin
+//! its peak memory forever. Only the `n` bytes of each read are ever written
+//! out, so a reused buffer never leaks a previous tunnel's data.
+
+use crate::config::types::BufferPoolConfig;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, OnceLock};
+
+static GLOBAL: OnceLock<BufferPool> = OnceLock::new();
+
+/// Size the process-wide pool from config. Only the first call (or the first
+/// [`global`] use) takes effect; returns `false` if the pool already existed.
+pub fn init(config: &BufferPoolConfig) -> bool {
+    GLOBAL.set(BufferPool::from_config(config)).is_ok()
+}
+
+/// The process-wide pool used by the relay.
+pub fn global() -> &'static BufferPool {
+    GLOBAL.get_or_init(|| BufferPool::from_config(&BufferPoolConfig::default()))
+}
+
+/// Free list of equally sized byte buffers.
+pub struct BufferPool {
+    buffer_size: usize,
+    max_buffers: usize,
+    free: Mutex<Vec<Box<[u8]>>>,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize, max_buffers: usize) -> Self {
+        Self {
+            buffer_size,
+            max_buffers,
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn from_config(config: &BufferPoolConfig) -> Self {
+        Self::new(config.buffer_size, config.max_buffers)
+    }
+
+    /// Borrow a buffer, allocating one when none is idle.
+    pub fn get(&self) -> PooledBuffer<'_> {
+        let reused = self.free.lock().unwrap().pop();
+        let buf = reused.unwrap_or_else(|| vec![0u8; self.buffer_size].into_boxed_slice());
+        PooledBuffer {
+            buf: Some(buf),
+            pool: self,
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Buffers currently idle in the pool.
+    pub fn idle(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    fn put(&self, buf: Box<[u8]>) {
+        let mut free = self.free.lock().unwrap();
+        if free.len() < self.max_buffers {
+            free.push(buf);
+        }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`], returned to it on drop.
+pub struct PooledBuffer<'a> {
+    buf: Option<Box<[u8]>>,
+    pool: &'a BufferPool,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf.as_deref().unwrap_or_default()
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf.as_deref_mut().unwrap_or_default()
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.put(buf);
+        }
+    }
+}
@@ -0,0 +1,769 @@
+//! Admin-triggered traffic capture of one live session (`[logging.capture]`).
+//!
+//! `POST /api/sessions/{id}/capture` attaches a [`SessionCapture`] to the
+//! session. The relay then hands it every chunk it forwards, already
+//! decrypted, and the capture writes each one to a pcapng file as a synthetic
+//! TCP segment between the client and the destination, so Wireshark can
+//! dissect the application protocol carried through the tunnel. In `headers`
+//! mode only the IP/TCP headers are kept: sizes and timing, no payload.
+//!
+//! A capture ends when its duration runs out, the file reaches
+//! `max_size_mb`, the session closes or an admin stops it. Both ends are
+//! recorded as critical audit events (`capture.started`, `capture.stopped`).
+
+use super::memory::{MemoryCharge, SessionMemory};
+use super::LiveSession;
+use crate::audit::events::AuditEvent;
+use crate::audit::AuditLogger;
+use crate::config::types::CaptureConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Notify};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+/// Packets queued for the writer before new ones are dropped.
+const CAPTURE_CHANNEL_CAPACITY: usize = 1024;
+
+/// Largest payload per synthetic segment (fits the 16-bit IP length).
+const MAX_SEGMENT: usize = 65_000;
+
+/// pcapng link type for packets starting with an IPv4 or IPv6 header.
+const LINKTYPE_RAW: u16 = 101;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// What a capture keeps of each forwarded chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    /// Headers and payload
+    #[default]
+    Full,
+    /// IP/TCP headers only (sizes and timing)
+    Headers,
+}
+
+impl CaptureMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Headers => "headers",
+        }
+    }
+}
+
+/// Why a capture ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureEnd {
+    /// The requested duration ran out
+    Expired,
+    /// The file reached `max_size_mb`
+    SizeLimit,
+    /// The captured session closed
+    SessionClosed,
+    /// Stopped through the API
+    Stopped,
+    /// The capture file could not be written
+    WriteError,
+}
+
+impl CaptureEnd {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Expired => "expired",
+            Self::SizeLimit => "size_limit",
+            Self::SessionClosed => "session_closed",
+            Self::Stopped => "stopped",
+            Self::WriteError => "write_error",
+        }
+    }
+}
+
+/// Reasons a capture cannot be started.
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("session capture is disabled (logging.capture.enabled)")]
+    Disabled,
+    #[error("session not found")]
+    SessionNotFound,
+    #[error("duration must be between 1 and {0} seconds")]
+    InvalidDuration(u64),
+    #[error("a capture is already running on this session")]
+    AlreadyRunning,
+    #[error("failed to create capture file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A capture as started.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureInfo {
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    pub username: String,
+    pub target_host: String,
+    pub target_port: u16,
+    pub path: PathBuf,
+    pub mode: CaptureMode,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub started_by: String,
+}
+
+/// A finished capture.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureSummary {
+    #[serde(flatten)]
+    pub info: CaptureInfo,
+    pub ended_at: DateTime<Utc>,
+    pub reason: CaptureEnd,
+    /// Admin who stopped the capture (reason `stopped`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_by: Option<String>,
+    /// Forwarded chunks captured
+    pub packets: u64,
+    /// Forwarded payload bytes, counted in `headers` mode too
+    pub payload_bytes: u64,
+    pub file_bytes: u64,
+    /// Chunks lost because the writer fell behind
+    pub dropped: u64,
+}
+
+/// Packets for the writer, counted against the session's memory budget
+/// until written.
+type QueuedPackets = (Vec<u8>, MemoryCharge);
+
+/// A running capture, fed by the relay of its session.
+pub struct SessionCapture {
+    info: CaptureInfo,
+    sender: mpsc::Sender<QueuedPackets>,
+    memory: Arc<SessionMemory>,
+    flow: Mutex<TcpFlow>,
+    stop: CancellationToken,
+    stop_request: Mutex<Option<(CaptureEnd, Option<String>)>>,
+    summary: Mutex<Option<CaptureSummary>>,
+    done: Notify,
+    packets: AtomicU64,
+    payload_bytes: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SessionCapture {
+    /// Create the capture file for `session` and start the writer task.
+    /// `duration` must already be checked against the config.
+    fn start(
+        session: &LiveSession,
+        mode: CaptureMode,
+        duration: Duration,
+        config: &CaptureConfig,
+        started_by: &str,
+        audit: Option<Arc<AuditLogger>>,
+    ) -> std::io::Result<Arc<Self>> {
+        let started_at = Utc::now();
+        let stem = format!(
+            "{}-{}",
+            session.session_id,
+            started_at.format("%Y%m%dT%H%M%S")
+        );
+        let (mut file, path) = create_capture_file(&config.directory, &stem)?;
+
+        let mut flow = TcpFlow::new(session);
+        let mut header = section_header();
+        header.extend(interface_description(
+            &session.session_id,
+            &format!(
+                "{}@{} -> {}:{}",
+                session.username, session.source_ip, session.target_host, session.target_port
+            ),
+        ));
+        header.extend(flow.handshake(mode, timestamp_micros()));
+        std::io::Write::write_all(&mut file, &header)?;
+        let file = tokio::fs::File::from_std(file);
+
+        let info = CaptureInfo {
+            session_id: session.session_id.clone(),
+            correlation_id: session.correlation_id.clone(),
+            username: session.username.clone(),
+            target_host: session.target_host.clone(),
+            target_port: session.target_port,
+            path,
+            mode,
+            started_at,
+            expires_at: started_at
+                + chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::zero()),
+            started_by: started_by.to_string(),
+        };
+        let (sender, receiver) = mpsc::channel(CAPTURE_CHANNEL_CAPACITY);
+        let capture = Arc::new(Self {
+            info,
+            sender,
+            memory: session.memory.clone(),
+            flow: Mutex::new(flow),
+            stop: CancellationToken::new(),
+            stop_request: Mutex::new(None),
+            summary: Mutex::new(None),
+            done: Notify::new(),
+            packets: AtomicU64::new(0),
+            payload_bytes: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+
+        warn!(
+            session = %capture.info.session_id,
+            user = %capture.info.username,
+            path = %capture.info.path.display(),
+            mode = capture.info.mode.as_str(),
+            duration_secs = duration.as_secs(),
+            by = %started_by,
+            "Session capture started"
+        );
+        if let Some(ref audit) = audit {
+            audit.log_event(AuditEvent::capture_started(
+                &capture.info,
+                duration.as_secs(),
+            ));
+        }
+        tokio::spawn(capture_writer_task(
+            capture.clone(),
+            file,
+            receiver,
+            header.len() as u64,
+            config.max_size_mb.saturating_mul(1024 * 1024),
+            tokio::time::Instant::now() + duration,
+            audit,
+        ));
+        Ok(capture)
+    }
+
+    pub fn info(&self) -> &CaptureInfo {
+        &self.info
+    }
+
+    /// Whether the capture has ended (its file may still be closing).
+    pub fn is_finished(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    /// Queue one forwarded chunk (`upload`: client to destination).
+    pub fn record(&self, upload: bool, data: &[u8]) {
+        if self.is_finished() {
+            return;
+        }
+        let packets = self
+            .flow
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .segments(upload, data, self.info.mode, timestamp_micros());
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.payload_bytes
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        let charge = self.memory.charge(packets.len());
+        if self.sender.try_send((packets, charge)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// End the capture; the first reason given wins.
+    pub fn stop(&self, reason: CaptureEnd, by: Option<&str>) {
+        {
+            let mut request = self.stop_request.lock().unwrap_or_else(|e| e.into_inner());
+            if request.is_none() && !self.is_finished() {
+                *request = Some((reason, by.map(str::to_string)));
+            }
+        }
+        self.stop.cancel();
+    }
+
+    /// Wait until the capture file is closed and return the summary.
+    pub async fn finished(&self) -> CaptureSummary {
+        loop {
+            let notified = self.done.notified();
+            if let Some(summary) = self.summary() {
+                return summary;
+            }
+            notified.await;
+        }
+    }
+
+    /// Summary of the capture once its file is closed.
+    pub fn summary(&self) -> Option<CaptureSummary> {
+        self.summary
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// The capture attached to a live session, if any.
+#[derive(Default)]
+pub struct CaptureSlot {
+    /// Fast path for the relay: no lock while nothing is captured
+    active: AtomicBool,
+    capture: Mutex<Option<Arc<SessionCapture>>>,
+}
+
+impl CaptureSlot {
+    /// Start capturing `session` (the owner of this slot) unless a capture
+    /// is already running on it.
+    pub fn start(
+        &self,
+        session: &LiveSession,
+        mode: CaptureMode,
+        duration: Duration,
+        config: &CaptureConfig,
+        started_by: &str,
+        audit: Option<Arc<AuditLogger>>,
+    ) -> Result<Arc<SessionCapture>, CaptureError> {
+        let mut slot = self.capture.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.as_ref().is_some_and(|c| !c.is_finished()) {
+            return Err(CaptureError::AlreadyRunning);
+        }
+        let capture = SessionCapture::start(session, mode, duration, config, started_by, audit)?;
+        *slot = Some(capture.clone());
+        self.active.store(true, Ordering::Release);
+        Ok(capture)
+    }
+
+    /// The running capture, if any.
+    pub fn current(&self) -> Option<Arc<SessionCapture>> {
+        if !self.active.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut slot = self.capture.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.as_ref().is_some_and(|c| c.is_finished()) {
+            *slot = None;
+            self.active.store(false, Ordering::Release);
+        }
+        slot.clone()
+    }
+
+    /// Detach the capture, running or not.
+    pub fn take(&self) -> Option<Arc<SessionCapture>> {
+        self.active.store(false, Ordering::Release);
+        self.capture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Hand a forwarded chunk to the running capture, if any.
+    pub fn record(&self, upload: bool, data: &[u8]) {
+        if let Some(capture) = self.current() {
+            capture.record(upload, data);
+        }
+    }
+}
+
+/// Check a requested duration against `max_duration_secs`; None picks the
+/// shorter of one minute and the maximum.
+pub fn capture_duration(
+    requested: Option<u64>,
+    config: &CaptureConfig,
+) -> Result<Duration, CaptureError> {
+    let max = config.max_duration_secs;
+    match requested {
+        None => Ok(Duration::from_secs(max.min(60))),
+        Some(secs) if secs == 0 || secs > max => Err(CaptureError::InvalidDuration(max)),
+        Some(secs) => Ok(Duration::from_secs(secs)),
+    }
+}
+
+/// Create `<stem>.pcapng` in `directory`, or `<stem>-<n>.pcapng` if a
+/// capture of the same session started within the same second.
+fn create_capture_file(
+    directory: &std::path::Path,
+    stem: &str,
+) -> std::io::Result<(std::fs::File, PathBuf)> {
+    let mut dir = std::fs::DirBuilder::new();
+    dir.recursive(true);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        // Captures hold decrypted traffic: owner only
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+        dir.mode(0o700);
+        options.mode(0o600);
+    }
+    dir.create(directory)?;
+    let mut n = 0;
+    loop {
+        let name = match n {
+            0 => format!("{stem}.pcapng"),
+            _ => format!("{stem}-{n}.pcapng"),
+        };
+        let path = directory.join(name);
+        match options.open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && n < 100 => n += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Write queued packets until the capture ends, then close the file and
+/// report the summary.
+async fn capture_writer_task(
+    capture: Arc<SessionCapture>,
+    file: tokio::fs::File,
+    mut receiver: mpsc::Receiver<QueuedPackets>,
+    mut file_bytes: u64,
+    max_bytes: u64,
+    deadline: tokio::time::Instant,
+    audit: Option<Arc<AuditLogger>>,
+) {
+    let mut writer = tokio::io::BufWriter::new(file);
+    let ended = loop {
+        tokio::select! {
+            _ = capture.stop.cancelled() => break None,
+            _ = tokio::time::sleep_until(deadline) => break Some(CaptureEnd::Expired),
+            packets = receiver.recv() => {
+                let Some((packets, _charge)) = packets else {
+                    break Some(CaptureEnd::SessionClosed);
+                };
+                if file_bytes + packets.len() as u64 > max_bytes {
+                    break Some(CaptureEnd::SizeLimit);
+                }
+                if let Err(e) = writer.write_all(&packets).await {
+                    error!(path = %capture.info.path.display(), error = %e, "Capture write failed");
+                    break Some(CaptureEnd::WriteError);
+                }
+                file_bytes += packets.len() as u64;
+            }
+        }
+    };
+    let (reason, stopped_by) = match ended {
+        Some(reason) => {
+            capture.stop(reason, None);
+            (reason, None)
+        }
+        None => capture
+            .stop_request
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or((CaptureEnd::Stopped, None)),
+    };
+    if reason != CaptureEnd::WriteError {
+        // Keep what was relayed before the stop
+        while let Ok((packets, _charge)) = receiver.try_recv() {
+            if file_bytes + packets.len() as u64 > max_bytes
+                || writer.write_all(&packets).await.is_err()
+            {
+                break;
+            }
+            file_bytes += packets.len() as u64;
+        }
+    }
+    if let Err(e) = writer.flush().await {
+        error!(path = %capture.info.path.display(), error = %e, "Capture flush failed");
+    }
+
+    let summary = CaptureSummary {
+        info: capture.info.clone(),
+        ended_at: Utc::now(),
+        reason,
+        stopped_by,
+        packets: capture.packets.load(Ordering::Relaxed),
+        payload_bytes: capture.payload_bytes.load(Ordering::Relaxed),
+        file_bytes,
+        dropped: capture.dropped.load(Ordering::Relaxed),
+    };
+    warn!(
+        session = %summary.info.session_id,
+        path = %summary.info.path.display(),
+        reason = reason.as_str(),
+        packets = summary.packets,
+        file_bytes = summary.file_bytes,
+        dropped = summary.dropped,
+        "Session capture stopped"
+    );
+    if let Some(ref audit) = audit {
+        audit.log_event(AuditEvent::capture_stopped(&summary));
+    }
+    *capture.summary.lock().unwrap_or_else(|e| e.into_inner()) = Some(summary);
+    capture.done.notify_waiters();
+}
+
+fn timestamp_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+// ---------------------------------------------------------------------------
+// Synthetic TCP flow
+// ---------------------------------------------------------------------------
+
+/// Addresses and sequence numbers of the TCP connection the capture shows.
+///
+/// The client port is not kept by the proxy, so one is derived from the
+/// session ID; a destination given as a hostname is shown as 192.0.2.1
+/// (TEST-NET-1), with the name in the interface description.
+struct TcpFlow {
+    client: IpAddr,
+    server: IpAddr,
+    client_port: u16,
+    server_port: u16,
+    client_seq: u32,
+    server_seq: u32,
+}
+
+impl TcpFlow {
+    fn new(session: &LiveSession) -> Self {
+        let client =
+            parse_ip(&session.source_ip).unwrap_or(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1)));
+        let server =
+            parse_ip(&session.target_host).unwrap_or(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+        // Both ends must share an IP version
+        let (client, server) = match (client, server) {
+            (IpAddr::V4(_), IpAddr::V4(_)) => (client, server),
+            _ => (IpAddr::V6(to_v6(client)), IpAddr::V6(to_v6(server))),
+        };
+        let id: u16 = session
+            .session_id
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .parse::<u64>()
+            .map(|n| (n % 16384) as u16)
+            .unwrap_or_default();
+        Self {
+            client,
+            server,
+            client_port: 49152 + id,
+            server_port: session.target_port,
+            client_seq: 1,
+            server_seq: 1,
+        }
+    }
+
+    /// SYN, SYN-ACK, ACK opening the flow, so dissectors see a whole stream.
+    fn handshake(&mut self, mode: CaptureMode, ts: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (upload, flags) in [(true, TCP_SYN), (false, TCP_SYN | TCP_ACK), (true, TCP_ACK)] {
+            out.extend(self.packet(upload, flags, &[], mode, ts));
+        }
+        out
+    }
+
+    /// Enhanced Packet Blocks carrying `data` in one or more segments.
+    fn segments(&mut self, upload: bool, data: &[u8], mode: CaptureMode, ts: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(MAX_SEGMENT) {
+            out.extend(self.packet(upload, TCP_PSH | TCP_ACK, chunk, mode, ts));
+        }
+        out
+    }
+
+    /// One Enhanced Packet Block; advances the sender's sequence number.
+    fn packet(
+        &mut self,
+        upload: bool,
+        flags: u8,
+        payload: &[u8],
+        mode: CaptureMode,
+        ts: u64,
+    ) -> Vec<u8> {
+        let (src, dst, sport, dport, seq, ack) = if upload {
+            (
+                self.client,
+                self.server,
+                self.client_port,
+                self.server_port,
+                self.client_seq,
+                self.server_seq,
+            )
+        } else {
+            (
+                self.server,
+                self.client,
+                self.server_port,
+                self.client_port,
+                self.server_seq,
+                self.client_seq,
+            )
+        };
+        let ack = if flags & TCP_ACK != 0 { ack } else { 0 };
+        let packet = ip_tcp_packet(src, dst, sport, dport, seq, ack, flags, payload);
+
+        let consumed = payload.len() as u32 + u32::from(flags & (TCP_SYN | TCP_FIN) != 0);
+        if upload {
+            self.client_seq = self.client_seq.wrapping_add(consumed);
+        } else {
+            self.server_seq = self.server_seq.wrapping_add(consumed);
+        }
+
+        let captured = match mode {
+            CaptureMode::Full => packet.len(),
+            CaptureMode::Headers => packet.len() - payload.len(),
+        };
+        enhanced_packet(ts, &packet[..captured], packet.len())
+    }
+}
+
+fn parse_ip(s: &str) -> Option<IpAddr> {
+    s.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+        .or_else(|| s.parse::<std::net::SocketAddr>().ok().map(|a| a.ip()))
+}
+
+fn to_v6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+/// IPv4 or IPv6 header, TCP header and payload, with checksums.
+#[allow(clippy::too_many_arguments)]
+fn ip_tcp_packet(
+    src: IpAddr,
+    dst: IpAddr,
+    sport: u16,
+    dport: u16,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let tcp_len = 20 + payload.len();
+    let mut tcp = Vec::with_capacity(tcp_len);
+    tcp.extend(sport.to_be_bytes());
+    tcp.extend(dport.to_be_bytes());
+    tcp.extend(seq.to_be_bytes());
+    tcp.extend(ack.to_be_bytes());
+    tcp.push(5 << 4); // data offset: 5 words, no options
+    tcp.push(flags);
+    tcp.extend(u16::MAX.to_be_bytes()); // window
+    tcp.extend([0, 0]); // checksum, set below
+    tcp.extend([0, 0]); // urgent pointer
+
+    let (mut packet, pseudo) = match (src, dst) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            let mut ip = Vec::with_capacity(20 + tcp_len);
+            ip.extend([0x45, 0]);
+            ip.extend(((20 + tcp_len) as u16).to_be_bytes());
+            ip.extend([0, 0, 0x40, 0, 64, 6, 0, 0]); // id, DF, TTL, TCP, checksum
+            ip.extend(s.octets());
+            ip.extend(d.octets());
+            let checksum = internet_checksum(&[&ip]);
+            ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+            let mut pseudo = Vec::with_capacity(12);
+            pseudo.extend(s.octets());
+            pseudo.extend(d.octets());
+            pseudo.extend([0, 6]);
+            pseudo.extend((tcp_len as u16).to_be_bytes());
+            (ip, pseudo)
+        }
+        _ => {
+            let (s, d) = (to_v6(src), to_v6(dst));
+            let mut ip = Vec::with_capacity(40 + tcp_len);
+            ip.extend([0x60, 0, 0, 0]);
+            ip.extend((tcp_len as u16).to_be_bytes());
+            ip.extend([6, 64]); // next header TCP, hop limit
+            ip.extend(s.octets());
+            ip.extend(d.octets());
+
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.extend(s.octets());
+            pseudo.extend(d.octets());
+            pseudo.extend((tcp_len as u32).to_be_bytes());
+            pseudo.extend([0, 0, 0, 6]);
+            (ip, pseudo)
+        }
+    };
+    let checksum = internet_checksum(&[&pseudo, &tcp, payload]);
+    tcp[16..18].copy_from_slice(&checksum.to_be_bytes());
+    packet.extend(tcp);
+    packet.extend(payload);
+    packet
+}
+
+/// RFC 1071 checksum over `parts`; only the last part may have an odd
+/// length.
+fn internet_checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum = 0u64;
+    for part in parts {
+        for word in part.chunks(2) {
+            let low = word.get(1).copied().unwrap_or(0);
+            sum += u64::from(u16::from_be_bytes([word[0], low]));
+        }
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// ---------------------------------------------------------------------------
+// pcapng blocks (little-endian)
+// ---------------------------------------------------------------------------
+
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let padded = body.len().div_ceil(4) * 4;
+    let total = (12 + padded) as u32;
+    let mut out = Vec::with_capacity(total as usize);
+    out.extend(block_type.to_le_bytes());
+    out.extend(total.to_le_bytes());
+    out.extend(body);
+    out.resize(8 + padded, 0);
+    out.extend(total.to_le_bytes());
+    out
+}
+
+fn option(out: &mut Vec<u8>, code: u16, value: &[u8]) {
+    out.extend(code.to_le_bytes());
+    out.extend((value.len() as u16).to_le_bytes());
+    out.extend(value);
+    out.resize(out.len().div_ceil(4) * 4, 0);
+}
+
+fn section_header() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(0x1A2B_3C4Du32.to_le_bytes()); // byte-order magic
+    body.extend(1u16.to_le_bytes()); // major version
+    body.extend(0u16.to_le_bytes()); // minor version
+    body.extend((-1i64).to_le_bytes()); // section length: unspecified
+    let app = format!("s5 {}", env!("CARGO_PKG_VERSION"));
+    option(&mut body, 4, app.as_bytes()); // shb_userappl
+    option(&mut body, 0, &[]);
+    block(0x0A0D_0D0A, &body)
+}
+
+fn interface_description(name: &str, description: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(LINKTYPE_RAW.to_le_bytes());
+    body.extend(0u16.to_le_bytes()); // reserved
+    body.extend(0u32.to_le_bytes()); // snaplen: unlimited
+    option(&mut body, 2, name.as_bytes()); // if_name
+    option(&mut body, 3, description.as_bytes()); // if_description
+    option(&mut body, 9, &[6]); // if_tsresol: microseconds
+    option(&mut body, 0, &[]);
+    block(1, &body)
+}
+
+fn enhanced_packet(ts_micros: u64, data: &[u8], original_len: usize) -> Vec<u8> {
+    let mut body = Vec::with_capacity(20 + data.len());
+    body.extend(0u32.to_le_bytes()); // interface 0
+    body.extend(((ts_micros >> 32) as u32).to_le_bytes());
+    body.extend((ts_micros as u32).to_le_bytes());
+    body.extend((data.len() as u32).to_le_bytes());
+    body.extend((original_len as u32).to_le_bytes());
+    body.extend(data);
+    block(6, &body)
+}
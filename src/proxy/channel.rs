@@ -0,0 +1,125 @@
+//! SSH channels as relay streams that show the relay their window.
+//!
+//! `Channel::into_stream` hides the client's window inside its writer: the
+//! relay only finds a client slow once a write blocks, with a chunk already
+//! read from the destination. [`WindowedChannel`] keeps the channel's write
+//! half, so the relay asks for the room left first and reads no more than
+//! that from the destination (see [`ClientWindow`]).
+
+use crate::proxy::forwarder::ClientWindow;
+use russh::server::Msg;
+use russh::{Channel, ChannelMsg, ChannelReadHalf, ChannelWriteHalf};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::sync::ReusableBoxFuture;
+
+impl ClientWindow for ChannelWriteHalf<Msg> {
+    fn room(&self) -> Pin<Box<dyn Future<Output = usize> + Send + '_>> {
+        Box::pin(self.wait_writable_packet_size())
+    }
+}
+
+/// The next data the client sends on the channel; None at its EOF or close.
+async fn next_data(mut read_half: ChannelReadHalf) -> (ChannelReadHalf, Option<Vec<u8>>) {
+    loop {
+        match read_half.wait().await {
+            Some(ChannelMsg::Data { data }) if !data.is_empty() => {
+                return (read_half, Some(data.to_vec()))
+            }
+            Some(ChannelMsg::Eof) | None => return (read_half, None),
+            Some(_) => {}
+        }
+    }
+}
+
+/// A direct-tcpip or streamlocal channel relayed as a stream, its window
+/// open to the relay through [`WindowedChannel::window`]. Closes the channel
+/// when dropped, like the stream of `Channel::into_stream`.
+pub struct WindowedChannel {
+    incoming: ReusableBoxFuture<'static, (ChannelReadHalf, Option<Vec<u8>>)>,
+    /// The client sent its EOF or closed the channel
+    eof: bool,
+    /// Data received and not read yet, from `offset`
+    pending: Vec<u8>,
+    offset: usize,
+    writer: Pin<Box<dyn AsyncWrite + Send + Sync>>,
+    write_half: Arc<ChannelWriteHalf<Msg>>,
+}
+
+impl WindowedChannel {
+    pub fn new(channel: Channel<Msg>) -> Self {
+        let (read_half, write_half) = channel.split();
+        Self {
+            incoming: ReusableBoxFuture::new(next_data(read_half)),
+            eof: false,
+            pending: Vec::new(),
+            offset: 0,
+            writer: Box::pin(write_half.make_writer()),
+            write_half: Arc::new(write_half),
+        }
+    }
+
+    /// The client's window, for [`RelayConfig::client_window`](crate::proxy::forwarder::RelayConfig::client_window).
+    pub fn window(&self) -> Arc<dyn ClientWindow> {
+        self.write_half.clone()
+    }
+}
+
+impl AsyncRead for WindowedChannel {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.offset == this.pending.len() {
+            if this.eof {
+                return Poll::Ready(Ok(()));
+            }
+            let (read_half, data) = ready!(this.incoming.poll(cx));
+            let Some(data) = data else {
+                this.eof = true;
+                return Poll::Ready(Ok(()));
+            };
+            this.pending = data;
+            this.offset = 0;
+            this.incoming.set(next_data(read_half));
+        }
+        let rest = &this.pending[this.offset..];
+        let n = rest.len().min(buf.remaining());
+        buf.put_slice(&rest[..n]);
+        this.offset += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for WindowedChannel {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.writer.as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.writer.as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.writer.as_mut().poll_shutdown(cx)
+    }
+}
+
+impl Drop for WindowedChannel {
+    fn drop(&mut self) {
+        let write_half = Arc::clone(&self.write_half);
+        tokio::spawn(async move {
+            let _ = write_half.close().await;
+        });
+    }
+}
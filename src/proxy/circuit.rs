@@ -0,0 +1,132 @@
+//! Per-destination circuit breaker (`[proxy.circuit_breaker]`).
+//!
+//! After `failure_threshold` failed connects to the same `host:port` within
+//! `window_secs`, with no success in between, the circuit opens: connects to
+//! that destination fail at once for `open_secs` instead of each client
+//! waiting out the connect timeout. Then a single probe connect is let through
+//! (half-open): its success closes the circuit, its failure opens it again.
+
+use crate::config::types::CircuitBreakerConfig;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// Destinations tracked at most; stale closed circuits are dropped beyond it.
+const MAX_CIRCUITS: usize = 10_000;
+
+#[derive(Debug)]
+struct Circuit {
+    failures: u32,
+    window_start: Instant,
+    /// Set while the circuit is open
+    open_until: Option<Instant>,
+    /// A half-open probe is in flight
+    probing: bool,
+}
+
+/// Failure tracking for every destination.
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: DashMap<String, Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: DashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Whether a connect to `key` (`host:port`) may go ahead. `Err` holds the
+    /// time left before the next probe (zero while a probe is in flight).
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let Some(mut circuit) = self.circuits.get_mut(key) else {
+            return Ok(());
+        };
+        let Some(open_until) = circuit.open_until else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if now < open_until {
+            return Err(open_until - now);
+        }
+        if circuit.probing {
+            return Err(Duration::ZERO);
+        }
+        circuit.probing = true;
+        Ok(())
+    }
+
+    /// A connect to `key` succeeded: close its circuit.
+    pub fn record_success(&self, key: &str) {
+        if self.config.enabled {
+            self.circuits.remove(key);
+        }
+    }
+
+    /// A connect to `key` failed. Returns `true` if this opened the circuit.
+    pub fn record_failure(&self, key: &str) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window_secs);
+        if self.circuits.len() >= MAX_CIRCUITS && !self.circuits.contains_key(key) {
+            self.circuits.retain(|_, c| {
+                c.open_until.is_some() || now.duration_since(c.window_start) <= window
+            });
+        }
+
+        let mut circuit = self.circuits.entry(key.to_string()).or_insert(Circuit {
+            failures: 0,
+            window_start: now,
+            open_until: None,
+            probing: false,
+        });
+        if circuit.open_until.is_none() && now.duration_since(circuit.window_start) > window {
+            circuit.failures = 0;
+            circuit.window_start = now;
+        }
+        circuit.failures += 1;
+
+        let reopen = circuit.probing;
+        if reopen || circuit.failures >= self.config.failure_threshold {
+            circuit.open_until = Some(now + Duration::from_secs(self.config.open_secs));
+            circuit.probing = false;
+            circuit.failures = 0;
+            return true;
+        }
+        false
+    }
+
+    /// A connect to `key` ended with an error that says nothing about the
+    /// target (policy, DNS without an answer): a pending probe may be retried.
+    pub fn release(&self, key: &str) {
+        if let Some(mut circuit) = self.circuits.get_mut(key) {
+            circuit.probing = false;
+        }
+    }
+
+    /// Destinations whose circuit is currently open.
+    pub fn open_count(&self) -> usize {
+        let now = Instant::now();
+        self.circuits
+            .iter()
+            .filter(|c| c.open_until.is_some_and(|t| now < t))
+            .count()
+    }
+}
+
+/// Whether a connect-path error means the target itself failed: an I/O error
+/// (refused, reset, unreachable, timed out) or a DNS timeout.
+pub fn is_target_failure(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some()
+        || err.downcast_ref::<tokio::time::error::Elapsed>().is_some()
+}
@@ -0,0 +1,299 @@
+//! Traffic classification of forwarded streams.
+//!
+//! The first chunk a client sends through a tunnel is inspected once, without
+//! decrypting anything: a TLS ClientHello gives its SNI, a plain HTTP request
+//! its `Host` header, and an SSH identification string marks an SSH hop.
+//! Everything else is `unknown`. Server name inspection
+//! ([`crate::proxy::inspect`]) instead waits for the whole ClientHello or
+//! request head, measured by [`head_len`]. The result lands in flow records and the
+//! per-protocol byte counters.
+//!
+//! For users whose group opted in (`logging.flows.payload_metadata`), the
+//! first line of a plaintext stream is kept too: the HTTP request line or the
+//! SMTP `EHLO`/`HELO` command, cut to `payload_metadata_max_len` bytes.
+
+/// Longest server name kept (DNS names are at most 253 characters).
+const MAX_SERVER_NAME_LEN: usize = 255;
+
+/// TLS record content type of a handshake message.
+const TLS_HANDSHAKE: u8 = 0x16;
+const TLS_CLIENT_HELLO: u8 = 0x01;
+const TLS_EXT_SERVER_NAME: u16 = 0x0000;
+const TLS_SNI_HOST_NAME: u8 = 0x00;
+
+const HTTP_METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+/// Greetings an SMTP (or LMTP) client opens with, once the server's banner
+/// is in.
+const SMTP_GREETINGS: &[&[u8]] = &[b"EHLO ", b"HELO ", b"LHLO "];
+
+/// Application protocol seen at the start of a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppProtocol {
+    Tls,
+    Http,
+    Ssh,
+    Unknown,
+}
+
+impl AppProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tls => "tls",
+            Self::Http => "http",
+            Self::Ssh => "ssh",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for AppProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Protocol of a stream and the server it names (TLS SNI or HTTP `Host`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classification {
+    pub protocol: AppProtocol,
+    pub server_name: Option<String>,
+    /// First line of a plaintext stream, when sampled ([`first_line`])
+    pub first_line: Option<String>,
+}
+
+/// Classify a stream from the first bytes its client sent.
+pub fn classify(first: &[u8]) -> Classification {
+    let (protocol, server_name) = if is_tls_handshake(first) {
+        let name = match client_hello(first) {
+            Hello::Complete { message, .. } => hello_server_name(&message),
+            // A hello cut short may still have its SNI near the start
+            Hello::Partial | Hello::Invalid => first.get(5..).and_then(hello_server_name),
+        };
+        (AppProtocol::Tls, name)
+    } else if first.starts_with(b"SSH-") {
+        (AppProtocol::Ssh, None)
+    } else if is_http_request(first) {
+        (AppProtocol::Http, http_host(first))
+    } else {
+        (AppProtocol::Unknown, None)
+    };
+    Classification {
+        protocol,
+        server_name,
+        first_line: None,
+    }
+}
+
+/// The first line of a plaintext stream classified as `protocol`, at most
+/// `max_len` bytes: an HTTP request line, or an SMTP greeting
+/// (`EHLO client.example.com`). None for other streams and for lines with
+/// control or non-ASCII bytes.
+pub fn first_line(first: &[u8], protocol: AppProtocol, max_len: usize) -> Option<String> {
+    let plaintext = match protocol {
+        AppProtocol::Http => true,
+        AppProtocol::Unknown => SMTP_GREETINGS.iter().any(|greeting| {
+            first
+                .get(..greeting.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(greeting))
+        }),
+        AppProtocol::Tls | AppProtocol::Ssh => false,
+    };
+    if !plaintext {
+        return None;
+    }
+    let line = first.split(|&b| b == b'\n').next()?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = &line[..line.len().min(max_len)];
+    let printable = !line.is_empty() && line.iter().all(|&b| b == b' ' || b.is_ascii_graphic());
+    printable.then(|| String::from_utf8_lossy(line).into_owned())
+}
+
+/// Length of the head of a stream that names its server: a TLS ClientHello
+/// (however many records it spans) or an HTTP request head. Some(0) for
+/// streams with no such head; None while more bytes are needed to tell.
+pub fn head_len(data: &[u8]) -> Option<usize> {
+    if data.first() == Some(&TLS_HANDSHAKE) {
+        if data.len() < 3 {
+            return None;
+        }
+        if is_tls_handshake(data) {
+            return match client_hello(data) {
+                Hello::Complete { end, .. } => Some(end),
+                Hello::Partial => None,
+                Hello::Invalid => Some(0),
+            };
+        }
+    }
+    if is_http_request(data) {
+        let end = data
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map(|i| i + 4)
+            .or_else(|| data.windows(2).position(|w| w == b"\n\n").map(|i| i + 2));
+        return end;
+    }
+    // Too short to tell an HTTP method from another protocol
+    let method_prefix = HTTP_METHODS
+        .iter()
+        .any(|method| data.len() <= method.len() && method.as_bytes().starts_with(data));
+    if method_prefix {
+        return None;
+    }
+    Some(0)
+}
+
+fn is_tls_handshake(data: &[u8]) -> bool {
+    matches!(data, [TLS_HANDSHAKE, 0x03, minor, ..] if *minor <= 0x04)
+}
+
+/// A ClientHello as read from the handshake records at the start of a stream.
+enum Hello {
+    /// The message and the stream offset of the end of its last record
+    Complete {
+        message: Vec<u8>,
+        end: usize,
+    },
+    Partial,
+    /// Not a ClientHello, or records of another type in the middle
+    Invalid,
+}
+
+/// Reassemble the ClientHello from the handshake records it spans.
+fn client_hello(data: &[u8]) -> Hello {
+    let mut message = Vec::new();
+    let mut pos = 0;
+    loop {
+        let Some(header) = data.get(pos..pos + 5) else {
+            return Hello::Partial;
+        };
+        if !is_tls_handshake(header) {
+            return Hello::Invalid;
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let Some(fragment) = data.get(pos + 5..pos + 5 + len) else {
+            return Hello::Partial;
+        };
+        message.extend_from_slice(fragment);
+        pos += 5 + len;
+        if message.first().is_some_and(|&t| t != TLS_CLIENT_HELLO) {
+            return Hello::Invalid;
+        }
+        if message.len() >= 4 {
+            let body = u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+            if message.len() >= 4 + body {
+                message.truncate(4 + body);
+                return Hello::Complete { message, end: pos };
+            }
+        }
+        if len == 0 {
+            return Hello::Invalid;
+        }
+    }
+}
+
+/// SNI of a ClientHello handshake message. None when it is truncated or has
+/// no SNI.
+fn hello_server_name(message: &[u8]) -> Option<String> {
+    let mut r = Reader(message);
+    if r.u8()? != TLS_CLIENT_HELLO {
+        return None;
+    }
+    r.skip(3)?; // handshake length
+    r.skip(2 + 32)?; // client version, random
+    let session_id = r.u8()? as usize;
+    r.skip(session_id)?;
+    let ciphers = r.u16()? as usize;
+    r.skip(ciphers)?;
+    let compression = r.u8()? as usize;
+    r.skip(compression)?;
+    // A hello split over several reads still has its SNI near the start
+    let len = r.u16()? as usize;
+    let mut extensions = Reader(r.take_upto(len));
+    while let Some(kind) = extensions.u16() {
+        let len = extensions.u16()? as usize;
+        let body = extensions.take(len)?;
+        if kind != TLS_EXT_SERVER_NAME {
+            continue;
+        }
+        let mut list = Reader(body);
+        let len = list.u16()? as usize;
+        let mut names = Reader(list.take(len)?);
+        while let Some(name_type) = names.u8() {
+            let len = names.u16()? as usize;
+            let name = names.take(len)?;
+            if name_type == TLS_SNI_HOST_NAME {
+                return server_name(name);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+fn is_http_request(data: &[u8]) -> bool {
+    HTTP_METHODS.iter().any(|method| {
+        data.strip_prefix(method.as_bytes())
+            .is_some_and(|rest| rest.first() == Some(&b' '))
+    })
+}
+
+/// `Host` header of a request whose headers start in `data`.
+fn http_host(data: &[u8]) -> Option<String> {
+    let head = data
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map_or(data, |end| &data[..end]);
+    head.split(|&b| b == b'\n').skip(1).find_map(|line| {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let colon = line.iter().position(|&b| b == b':')?;
+        let (name, value) = line.split_at(colon);
+        if !name.eq_ignore_ascii_case(b"host") {
+            return None;
+        }
+        server_name(value[1..].trim_ascii())
+    })
+}
+
+/// A printable host name, lowercased; None for anything else.
+fn server_name(raw: &[u8]) -> Option<String> {
+    let valid = !raw.is_empty()
+        && raw.len() <= MAX_SERVER_NAME_LEN
+        && raw.iter().all(|b| b.is_ascii_graphic());
+    valid.then(|| String::from_utf8_lossy(raw).to_ascii_lowercase())
+}
+
+/// Bounds-checked cursor over a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    /// Up to `n` bytes, fewer if the data ends first.
+    fn take_upto(&mut self, n: usize) -> &'a [u8] {
+        let (head, tail) = self.0.split_at(n.min(self.0.len()));
+        self.0 = tail;
+        head
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
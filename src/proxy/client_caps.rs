@@ -0,0 +1,281 @@
+//! Client connection caps (`server.max_connections`,
+//! `server.max_connections_per_user`).
+//!
+//! Every accepted SSH, SOCKS5 or MASQUE (QUIC) client holds a [`ClientSlot`] for its whole
+//! lifetime. Past `max_connections`, a new client waits up to
+//! `connection_queue_timeout_ms` for a slot to free up (at most
+//! `connection_queue_size` clients wait at once, `max_connections` by
+//! default) and is refused after that. Once
+//! authenticated, a client also takes a [`UserSlot`]; a user already at
+//! `max_connections_per_user` is refused at once. These cap client
+//! connections, unlike `limits.max_connections*`, which cap the proxied
+//! connections opened through them.
+
+use crate::config::types::AppConfig;
+use crate::metrics::collectors::{ProtocolLabel, ReasonLabel};
+use crate::metrics::{shed_reasons, MetricsRegistry};
+use dashmap::DashMap;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Why a client was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapRejection {
+    /// `server.max_connections` reached (after queueing, if enabled)
+    Global,
+    /// `server.max_connections_per_user` reached for this user
+    PerUser,
+}
+
+impl CapRejection {
+    /// `reason` label in `s5_connections_rejected_total`.
+    pub fn metric_reason(&self) -> &'static str {
+        match self {
+            Self::Global => "connection_cap",
+            Self::PerUser => "user_connection_cap",
+        }
+    }
+}
+
+/// Listener a client connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listener {
+    Ssh,
+    Socks5,
+    Masque,
+}
+
+impl Listener {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ssh => "ssh",
+            Self::Socks5 => "socks5",
+            Self::Masque => "masque",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Current use of the caps, for `/api/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapsSnapshot {
+    /// Connected clients, all listeners
+    pub active: u32,
+    /// `server.max_connections` (0 = unlimited)
+    pub max: u32,
+    /// Clients waiting for a slot
+    pub queued: u32,
+    /// Clients allowed to wait at once
+    pub queue_size: u32,
+    /// `active / max`, 0 when unlimited
+    pub saturation: f64,
+    pub ssh: u32,
+    pub socks5: u32,
+    /// QUIC connections to the `[masque]` listener
+    pub masque: u32,
+    /// `server.max_connections_per_user` (0 = unlimited)
+    pub max_per_user: u32,
+}
+
+/// Counts connected clients, globally, per listener and per user.
+pub struct ClientCaps {
+    /// 0 = unlimited
+    max_total: u32,
+    /// 0 = unlimited
+    max_per_user: u32,
+    queue_timeout: Duration,
+    /// Clients allowed to wait at once
+    queue_size: u32,
+    slots: Option<Arc<Semaphore>>,
+    queued: AtomicU32,
+    active: [AtomicU32; 3],
+    per_user: DashMap<String, u32>,
+    active_gauge: Family<ProtocolLabel, Gauge>,
+    queued_gauge: Gauge,
+    shed: Family<ReasonLabel, Counter>,
+}
+
+impl ClientCaps {
+    pub fn new(max_total: u32, max_per_user: u32, queue_timeout: Duration) -> Self {
+        Self {
+            max_total,
+            max_per_user,
+            queue_timeout,
+            queue_size: max_total,
+            slots: (max_total > 0).then(|| Arc::new(Semaphore::new(max_total as usize))),
+            queued: AtomicU32::new(0),
+            active: [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)],
+            per_user: DashMap::new(),
+            active_gauge: Family::default(),
+            queued_gauge: Gauge::default(),
+            shed: Family::default(),
+        }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(
+            config.server.max_connections,
+            config.server.max_connections_per_user,
+            Duration::from_millis(config.server.connection_queue_timeout_ms),
+        )
+        .with_queue_size(config.server.connection_queue_size)
+    }
+
+    /// Bound the wait queue to `size` clients (0 = as many as the cap).
+    pub fn with_queue_size(mut self, size: u32) -> Self {
+        self.queue_size = if size == 0 { self.max_total } else { size };
+        self
+    }
+
+    /// Report to `s5_client_connections`, `s5_client_connections_queued`
+    /// and `s5_connections_shed_total`.
+    pub fn with_metrics(mut self, metrics: &MetricsRegistry) -> Self {
+        self.active_gauge = metrics.client_connections.clone();
+        self.queued_gauge = metrics.client_connections_queued.clone();
+        self.shed = metrics.connections_shed_total.clone();
+        self
+    }
+
+    /// Take a slot for a new client on `listener`, waiting in the queue when
+    /// the server is full and queueing is enabled.
+    pub async fn acquire(self: &Arc<Self>, listener: Listener) -> Result<ClientSlot, CapRejection> {
+        let permit = match &self.slots {
+            None => None,
+            Some(slots) => Some(self.acquire_permit(slots).await?),
+        };
+        self.active[listener.index()].fetch_add(1, Ordering::Relaxed);
+        self.listener_gauge(listener).inc();
+        Ok(ClientSlot {
+            caps: self.clone(),
+            listener,
+            _permit: permit,
+        })
+    }
+
+    async fn acquire_permit(
+        &self,
+        slots: &Arc<Semaphore>,
+    ) -> Result<OwnedSemaphorePermit, CapRejection> {
+        if let Ok(permit) = slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        if self.queue_timeout.is_zero() {
+            self.record_shed(shed_reasons::QUEUE_FULL);
+            return Err(CapRejection::Global);
+        }
+        // Beyond the queue size, refuse at once
+        let position = self.queued.fetch_add(1, Ordering::AcqRel);
+        if position >= self.queue_size {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+            self.record_shed(shed_reasons::QUEUE_FULL);
+            return Err(CapRejection::Global);
+        }
+        self.queued_gauge.inc();
+        let waited = tokio::time::timeout(self.queue_timeout, slots.clone().acquire_owned()).await;
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+        self.queued_gauge.dec();
+        match waited {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                self.record_shed(shed_reasons::QUEUE_TIMEOUT);
+                Err(CapRejection::Global)
+            }
+        }
+    }
+
+    fn record_shed(&self, reason: &str) {
+        self.shed
+            .get_or_create(&ReasonLabel {
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
+    /// Take a per-user slot for an authenticated client.
+    pub fn try_acquire_user(self: &Arc<Self>, username: &str) -> Result<UserSlot, CapRejection> {
+        let mut count = self.per_user.entry(username.to_string()).or_insert(0);
+        if self.max_per_user > 0 && *count >= self.max_per_user {
+            return Err(CapRejection::PerUser);
+        }
+        *count += 1;
+        Ok(UserSlot {
+            caps: self.clone(),
+            username: username.to_string(),
+        })
+    }
+
+    /// Connected clients of `username`.
+    pub fn user_connections(&self, username: &str) -> u32 {
+        self.per_user.get(username).map(|c| *c).unwrap_or(0)
+    }
+
+    pub fn snapshot(&self) -> CapsSnapshot {
+        let ssh = self.active[Listener::Ssh.index()].load(Ordering::Relaxed);
+        let socks5 = self.active[Listener::Socks5.index()].load(Ordering::Relaxed);
+        let masque = self.active[Listener::Masque.index()].load(Ordering::Relaxed);
+        let active = ssh + socks5 + masque;
+        CapsSnapshot {
+            active,
+            max: self.max_total,
+            queued: self.queued.load(Ordering::Relaxed),
+            queue_size: self.queue_size,
+            saturation: if self.max_total > 0 {
+                f64::from(active) / f64::from(self.max_total)
+            } else {
+                0.0
+            },
+            ssh,
+            socks5,
+            masque,
+            max_per_user: self.max_per_user,
+        }
+    }
+
+    fn listener_gauge(&self, listener: Listener) -> Gauge {
+        self.active_gauge
+            .get_or_create(&ProtocolLabel {
+                protocol: listener.as_str().to_string(),
+            })
+            .clone()
+    }
+}
+
+/// A connected client's share of `server.max_connections`; freed on drop.
+pub struct ClientSlot {
+    caps: Arc<ClientCaps>,
+    listener: Listener,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.caps.active[self.listener.index()].fetch_sub(1, Ordering::Relaxed);
+        self.caps.listener_gauge(self.listener).dec();
+    }
+}
+
+/// An authenticated client's share of `server.max_connections_per_user`.
+pub struct UserSlot {
+    caps: Arc<ClientCaps>,
+    username: String,
+}
+
+impl Drop for UserSlot {
+    fn drop(&mut self) {
+        self.caps
+            .per_user
+            .remove_if_mut(&self.username, |_, count| {
+                *count = count.saturating_sub(1);
+                *count == 0
+            });
+    }
+}
@@ -0,0 +1,175 @@
+//! Why a relayed session ended, and the recent-history ring of closed sessions.
+//!
+//! The relay classifies each direction's end (EOF, I/O error, idle timeout,
+//! quota, memory budget, kill) and keeps the most telling one: a kill, quota
+//! or memory budget hit beats an error, an error beats an EOF, and an idle
+//! timeout is only reported when nothing else happened. The reason goes to
+//! the `proxy.complete` audit event, `/api/sessions/history` and
+//! `s5_sessions_closed_total`.
+
+use super::SessionSnapshot;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use tokio_util::sync::CancellationToken;
+
+/// Closed sessions kept for `/api/sessions/history`.
+pub const HISTORY_CAPACITY: usize = 1000;
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// The client closed its side
+    ClientEof,
+    /// The destination closed its side
+    UpstreamEof,
+    /// No data for `limits.idle_timeout`
+    IdleTimeout,
+    /// A bandwidth or connection quota was exhausted
+    QuotaExceeded,
+    /// The session buffered more than `limits.max_buffered_bytes`
+    MemoryLimit,
+    /// The session relayed `limits.max_connection_bytes`
+    TransferLimit,
+    /// The client's IP was banned while the session was open
+    Ban,
+    /// Closed by an administrator (kick or session kill)
+    AdminKill,
+    /// The server name sent inside the tunnel is denied by the ACL
+    AclDenied,
+    /// The destination reset the connection
+    UpstreamReset,
+    /// Any other I/O error
+    Error,
+}
+
+impl CloseReason {
+    pub const ALL: [CloseReason; 11] = [
+        Self::ClientEof,
+        Self::UpstreamEof,
+        Self::IdleTimeout,
+        Self::QuotaExceeded,
+        Self::MemoryLimit,
+        Self::TransferLimit,
+        Self::Ban,
+        Self::AdminKill,
+        Self::AclDenied,
+        Self::UpstreamReset,
+        Self::Error,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ClientEof => "client_eof",
+            Self::UpstreamEof => "upstream_eof",
+            Self::IdleTimeout => "idle_timeout",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::MemoryLimit => "memory_limit",
+            Self::TransferLimit => "transfer_limit",
+            Self::Ban => "ban",
+            Self::AdminKill => "admin_kill",
+            Self::AclDenied => "acl_denied",
+            Self::UpstreamReset => "upstream_reset",
+            Self::Error => "error",
+        }
+    }
+
+    /// How well this reason explains the close (higher wins).
+    fn weight(&self) -> u8 {
+        match self {
+            Self::AdminKill | Self::Ban | Self::AclDenied => 4,
+            Self::QuotaExceeded | Self::MemoryLimit | Self::TransferLimit => 3,
+            Self::UpstreamReset | Self::Error => 2,
+            Self::ClientEof | Self::UpstreamEof => 1,
+            Self::IdleTimeout => 0,
+        }
+    }
+
+    /// Reason of a relay whose directions ended with `first` then `second`.
+    pub fn combine(first: CloseReason, second: CloseReason) -> CloseReason {
+        if second.weight() > first.weight() {
+            second
+        } else {
+            first
+        }
+    }
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lets the API (or the ban sweep) stop a live session.
+#[derive(Debug, Default)]
+pub struct KillSwitch {
+    token: CancellationToken,
+    reason: OnceLock<CloseReason>,
+}
+
+impl KillSwitch {
+    /// Stop the session; the first reason given sticks. Returns `false` if
+    /// the session was already killed.
+    pub fn kill(&self, reason: CloseReason) -> bool {
+        let first = self.reason.set(reason).is_ok();
+        self.token.cancel();
+        first
+    }
+
+    /// Reason given to [`kill`](Self::kill), if the session was killed.
+    pub fn reason(&self) -> Option<CloseReason> {
+        self.reason.get().copied()
+    }
+
+    /// Resolves once the session is killed.
+    pub async fn killed(&self) -> CloseReason {
+        self.token.cancelled().await;
+        self.reason().unwrap_or(CloseReason::AdminKill)
+    }
+}
+
+/// A session that has ended.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClosedSession {
+    #[serde(flatten)]
+    pub session: SessionSnapshot,
+    pub ended_at: DateTime<Utc>,
+    pub close_reason: CloseReason,
+}
+
+/// The last [`HISTORY_CAPACITY`] closed sessions, oldest first.
+#[derive(Default)]
+pub struct SessionHistory {
+    closed: Mutex<VecDeque<ClosedSession>>,
+}
+
+impl SessionHistory {
+    pub fn push(&self, session: ClosedSession) {
+        let mut closed = self.closed.lock().unwrap();
+        if closed.len() >= HISTORY_CAPACITY {
+            closed.pop_front();
+        }
+        closed.push_back(session);
+    }
+
+    /// Closed sessions matching `filter`, newest first, at most `limit`.
+    pub fn recent(
+        &self,
+        limit: usize,
+        filter: impl Fn(&ClosedSession) -> bool,
+    ) -> Vec<ClosedSession> {
+        self.closed
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|c| filter(c))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
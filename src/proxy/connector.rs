@@ -1,12 +1,45 @@
 use super::dns_cache::DnsCache;
 use super::ip_guard;
-use crate::metrics::MetricsRegistry;
+use super::retry::{self, RetryPolicy};
+use crate::config::types::{AddressFamily, TcpConfig};
+use crate::metrics::{outcomes, MetricsRegistry};
 use anyhow::{Context, Result};
-use std::net::SocketAddr;
-use std::time::Duration;
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tracing::{debug, warn};
 
+/// Every resolved address of a destination was blocked by ip_guard.
+#[derive(Debug, thiserror::Error)]
+#[error("all resolved addresses for {host} are blocked by ip_guard")]
+pub struct IpGuardBlocked {
+    pub host: String,
+    /// First blocked address and its range
+    pub ip: IpAddr,
+    pub range: &'static str,
+}
+
+/// The destination port is in `security.blocked_ports` (or the group's
+/// `blocked_ports`). Reads as an ACL denial in logs and metrics.
+#[derive(Debug, thiserror::Error)]
+#[error("ACL denied: destination port {port} is blocked")]
+pub struct PortBlocked {
+    pub port: u16,
+}
+
+/// `reason` of the `acl.deny` audit event for a blocked port.
+pub const PORT_BLOCKED_REASON: &str = "blocked port";
+
+/// Refuse `port` when it is one of `blocked`, before anything is resolved
+/// or connected.
+pub fn check_port(port: u16, blocked: &[u16]) -> std::result::Result<(), PortBlocked> {
+    if blocked.contains(&port) {
+        return Err(PortBlocked { port });
+    }
+    Ok(())
+}
+
 /// Resolve hostname and check all addresses against ip_guard.
 /// Returns only safe addresses (H-6: prevents port scanning oracle).
 pub async fn resolve_and_check(
@@ -15,6 +48,60 @@ pub async fn resolve_and_check(
     timeout_secs: u64,
     ip_guard_enabled: bool,
 ) -> Result<Vec<SocketAddr>> {
+    resolve_and_check_with_exemptions(
+        host,
+        port,
+        timeout_secs,
+        ip_guard_enabled,
+        &[],
+        AddressFamily::Any,
+    )
+    .await
+}
+
+/// [`resolve_and_check`] with per-user ip_guard exemptions: resolved addresses
+/// inside one of `ip_guard_exemptions` are kept even if private/reserved.
+/// The safe addresses are then ordered or filtered per `family`.
+pub async fn resolve_and_check_with_exemptions(
+    host: &str,
+    port: u16,
+    timeout_secs: u64,
+    ip_guard_enabled: bool,
+    ip_guard_exemptions: &[IpNet],
+    family: AddressFamily,
+) -> Result<Vec<SocketAddr>> {
+    let addrs = lookup(host, port, timeout_secs).await?;
+    let addrs = filter_guarded(host, addrs, ip_guard_enabled, ip_guard_exemptions)?;
+    apply_address_family(host, addrs, family)
+}
+
+/// Order (`prefer_*`) or filter (`*_only`) addresses by family, keeping the
+/// resolver order within a family; fails if no address of an `*_only`
+/// family remains.
+pub fn apply_address_family(
+    host: &str,
+    mut addrs: Vec<SocketAddr>,
+    family: AddressFamily,
+) -> Result<Vec<SocketAddr>> {
+    match family {
+        AddressFamily::Any => {}
+        AddressFamily::PreferIpv6 => addrs.sort_by_key(SocketAddr::is_ipv4),
+        AddressFamily::PreferIpv4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+        AddressFamily::Ipv4Only => addrs.retain(SocketAddr::is_ipv4),
+        AddressFamily::Ipv6Only => addrs.retain(SocketAddr::is_ipv6),
+    }
+    if addrs.is_empty() {
+        anyhow::bail!(
+            "no address for {} allowed by address_family {}",
+            host,
+            family
+        );
+    }
+    Ok(addrs)
+}
+
+/// Plain DNS lookup with timeout (no ip_guard filtering).
+async fn lookup(host: &str, port: u16, timeout_secs: u64) -> Result<Vec<SocketAddr>> {
     let addr_str = if host.contains(':') {
         format!("[{}]:{}", host, port)
     } else {
@@ -32,21 +119,33 @@ pub async fn resolve_and_check(
     if addrs.is_empty() {
         anyhow::bail!("no addresses found for {}", addr_str);
     }
+    Ok(addrs)
+}
 
+/// Drop resolved addresses blocked by ip_guard; fails if none remain.
+fn filter_guarded(
+    host: &str,
+    addrs: Vec<SocketAddr>,
+    ip_guard_enabled: bool,
+    ip_guard_exemptions: &[IpNet],
+) -> Result<Vec<SocketAddr>> {
     if !ip_guard_enabled {
         return Ok(addrs);
     }
 
+    let mut blocked = None;
     let safe_addrs: Vec<SocketAddr> = addrs
         .into_iter()
         .filter(|addr| {
-            if let Some(range_name) = ip_guard::classify_dangerous_ip(&addr.ip()) {
+            if let Some(range_name) = ip_guard::classify_guarded_ip(&addr.ip(), ip_guard_exemptions)
+            {
                 warn!(
                     target_host = %host,
                     resolved_ip = %addr.ip(),
                     range = %range_name,
                     "Blocked connection to {} IP (anti-SSRF)", range_name
                 );
+                blocked.get_or_insert((addr.ip(), range_name));
                 false
             } else {
                 true
@@ -55,10 +154,15 @@ pub async fn resolve_and_check(
         .collect();
 
     if safe_addrs.is_empty() {
-        anyhow::bail!(
-            "all resolved addresses for {} are blocked by ip_guard",
-            host
-        );
+        if let Some((ip, range)) = blocked {
+            return Err(IpGuardBlocked {
+                host: host.to_string(),
+                ip,
+                range,
+            }
+            .into());
+        }
+        anyhow::bail!("no addresses found for {}", host);
     }
 
     Ok(safe_addrs)
@@ -89,7 +193,7 @@ pub async fn connect(
         match tokio::time::timeout(timeout_duration, TcpStream::connect(addr)).await {
             Ok(Ok(stream)) => {
                 debug!(target_addr = %addr, "TCP connected");
-                configure_tcp_socket(&stream);
+                configure_tcp_socket(&stream, &TcpConfig::default());
                 return Ok((stream, *addr));
             }
             Ok(Err(e)) => {
@@ -112,11 +216,24 @@ pub async fn connect(
 }
 
 /// P3-3: DNS resolve + TCP connect with DNS cache support.
+///
+/// Cache hits are re-validated against the plain ip_guard, so an exempted
+/// private address is always re-resolved rather than served from the cache
+/// (the cache is shared between users with different exemptions). The cache
+/// keeps every family; `family` is applied to each lookup result.
+/// `[proxy.hosts]` entries are used instead of DNS, still subject to ip_guard.
+/// Refused and timed-out connects are retried per `retry`; sockets get the
+/// `[proxy.tcp]` options in `tcp`.
+#[allow(clippy::too_many_arguments)]
 pub async fn connect_with_cache(
     host: &str,
     port: u16,
     timeout_secs: u64,
     ip_guard_enabled: bool,
+    ip_guard_exemptions: &[IpNet],
+    family: AddressFamily,
+    tcp: &TcpConfig,
+    retry: RetryPolicy,
     dns_cache: &DnsCache,
     metrics: Option<&MetricsRegistry>,
 ) -> Result<(TcpStream, SocketAddr)> {
@@ -124,6 +241,24 @@ pub async fn connect_with_cache(
         anyhow::bail!("port 0 is not allowed");
     }
 
+    // [proxy.hosts] entries win over the cache and DNS
+    if let Some(pinned) = dns_cache.static_lookup(host, port) {
+        debug!(target_host = %host, pinned = ?pinned, "Static hosts entry");
+        let addrs = filter_guarded(host, pinned, ip_guard_enabled, ip_guard_exemptions)?;
+        let addrs = attempt_order(host, addrs, family, dns_cache)?;
+        return connect_to_addrs_timed(
+            &addrs,
+            timeout_secs,
+            host,
+            port,
+            tcp,
+            retry,
+            dns_cache,
+            metrics,
+        )
+        .await;
+    }
+
     // Build cache key on the stack to avoid heap allocation in hot path
     let mut cache_key = String::with_capacity(host.len() + 6);
     cache_key.push_str(host);
@@ -139,38 +274,213 @@ pub async fn connect_with_cache(
         if let Some(m) = metrics {
             m.dns_cache_hits_total.inc();
         }
-        return connect_to_addrs(&cached_addrs, timeout_secs, host, port).await;
+        let addrs = attempt_order(host, cached_addrs, family, dns_cache)?;
+        return connect_to_addrs_timed(
+            &addrs,
+            timeout_secs,
+            host,
+            port,
+            tcp,
+            retry,
+            dns_cache,
+            metrics,
+        )
+        .await;
     }
 
     // Cache miss — resolve normally
     if let Some(m) = metrics {
         m.dns_cache_misses_total.inc();
     }
-    let addrs = resolve_and_check(host, port, timeout_secs, ip_guard_enabled).await?;
+    let dns_start = Instant::now();
+    let lookup_result = lookup(host, port, timeout_secs).await;
+    if let Some(m) = metrics {
+        m.record_dns_resolution(
+            outcome_of(&lookup_result),
+            dns_start.elapsed().as_secs_f64(),
+        );
+    }
+    let addrs = filter_guarded(host, lookup_result?, ip_guard_enabled, ip_guard_exemptions)?;
 
     debug!(target_host = %host, resolved = ?addrs, "Resolved target (ip_guard filtered)");
 
     // Store in cache (use default TTL since we don't have native TTL from tokio::net::lookup_host)
     dns_cache.insert(&cache_key, addrs.clone(), None);
 
-    connect_to_addrs(&addrs, timeout_secs, host, port).await
+    let addrs = attempt_order(host, addrs, family, dns_cache)?;
+    connect_to_addrs_timed(
+        &addrs,
+        timeout_secs,
+        host,
+        port,
+        tcp,
+        retry,
+        dns_cache,
+        metrics,
+    )
+    .await
+}
+
+/// The addresses to try, in order: fastest recent connects first
+/// (`proxy.dns_cache.latency_ordering`), then `proxy.address_family`, whose
+/// stable sort keeps the latency order within each family.
+fn attempt_order(
+    host: &str,
+    mut addrs: Vec<SocketAddr>,
+    family: AddressFamily,
+    dns_cache: &DnsCache,
+) -> Result<Vec<SocketAddr>> {
+    dns_cache.order_by_latency(&mut addrs);
+    apply_address_family(host, addrs, family)
+}
+
+/// Refresh the `top_n` hottest DNS cache entries that are about to expire,
+/// so popular destinations never pay for a lookup on the connect path.
+/// Addresses blocked by ip_guard are dropped (exempted ones are re-resolved
+/// on use, as for cache hits); failed lookups let the entry expire.
+/// Returns the number of entries refreshed.
+pub async fn prefetch_hot(
+    dns_cache: &DnsCache,
+    top_n: usize,
+    timeout_secs: u64,
+    ip_guard_enabled: bool,
+    metrics: Option<&MetricsRegistry>,
+) -> usize {
+    let mut refreshed = 0;
+    for key in dns_cache.prefetch_candidates(top_n) {
+        let Some((host, port)) = key
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        else {
+            continue;
+        };
+        let result = lookup(host, port, timeout_secs).await.map(|addrs| {
+            addrs
+                .into_iter()
+                .filter(|a| !ip_guard_enabled || !ip_guard::is_dangerous_ip(&a.ip()))
+                .collect::<Vec<_>>()
+        });
+        if let Some(m) = metrics {
+            m.record_dns_prefetch(outcome_of(&result));
+        }
+        match result {
+            Ok(addrs) if !addrs.is_empty() => {
+                debug!(target_host = %host, resolved = ?addrs, "DNS cache entry prefetched");
+                dns_cache.refresh(&key, addrs, None);
+                refreshed += 1;
+            }
+            Ok(_) => debug!(target_host = %host, "DNS prefetch: every address blocked"),
+            Err(e) => debug!(target_host = %host, error = %e, "DNS prefetch failed"),
+        }
+    }
+    refreshed
+}
+
+/// [`connect_to_addrs`], recording the total connect time (retries
+/// included) into `s5_tcp_connect_duration_seconds`.
+#[allow(clippy::too_many_arguments)]
+async fn connect_to_addrs_timed(
+    addrs: &[SocketAddr],
+    timeout_secs: u64,
+    host: &str,
+    port: u16,
+    tcp: &TcpConfig,
+    retry: RetryPolicy,
+    dns_cache: &DnsCache,
+    metrics: Option<&MetricsRegistry>,
+) -> Result<(TcpStream, SocketAddr)> {
+    let start = Instant::now();
+    let result = connect_to_addrs(
+        addrs,
+        timeout_secs,
+        host,
+        port,
+        tcp,
+        retry,
+        dns_cache,
+        metrics,
+    )
+    .await;
+    if let Some(m) = metrics {
+        m.record_tcp_connect(outcome_of(&result), start.elapsed().as_secs_f64());
+    }
+    result
+}
+
+fn outcome_of<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => outcomes::SUCCESS,
+        Err(e) => outcomes::from_error(e),
+    }
 }
 
 /// Connect to a list of already-resolved addresses.
+///
+/// When every address fails and the last failure is transient (refused or
+/// timed out), the whole list is tried again after a jittered exponential
+/// backoff, up to `retry.max_retries` times. Each retry is counted in
+/// `s5_connect_retries_total`.
+#[allow(clippy::too_many_arguments)]
 async fn connect_to_addrs(
     addrs: &[SocketAddr],
     timeout_secs: u64,
     host: &str,
     port: u16,
+    tcp: &TcpConfig,
+    retry: RetryPolicy,
+    dns_cache: &DnsCache,
+    metrics: Option<&MetricsRegistry>,
 ) -> Result<(TcpStream, SocketAddr)> {
+    let mut attempt = 0;
+    loop {
+        let e = match try_addrs(addrs, timeout_secs, tcp, dns_cache).await {
+            Ok(connected) => return Ok(connected),
+            Err(Some(e)) => e,
+            Err(None) => anyhow::bail!("failed to connect to {}:{}", host, port),
+        };
+        if !retry::is_transient(&e) || attempt >= retry.max_retries {
+            return Err(anyhow::anyhow!(e));
+        }
+        attempt += 1;
+        let delay = retry.backoff(attempt);
+        debug!(
+            target_host = %host,
+            attempt = attempt,
+            delay_ms = delay.as_millis() as u64,
+            error = %e,
+            "Connect failed, retrying after backoff"
+        );
+        if let Some(m) = metrics {
+            m.record_connect_retry(retry_reason(&e));
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// One pass over `addrs`; the error is the last address's failure. Each
+/// attempt's outcome is remembered for the next ordering.
+async fn try_addrs(
+    addrs: &[SocketAddr],
+    timeout_secs: u64,
+    tcp: &TcpConfig,
+    dns_cache: &DnsCache,
+) -> std::result::Result<(TcpStream, SocketAddr), Option<std::io::Error>> {
     let timeout_duration = std::time::Duration::from_secs(timeout_secs);
     let mut last_err = None;
 
     for addr in addrs {
-        match tokio::time::timeout(timeout_duration, TcpStream::connect(addr)).await {
+        let start = Instant::now();
+        let connected = tokio::time::timeout(timeout_duration, open_stream(*addr, tcp)).await;
+        let elapsed = start.elapsed();
+        dns_cache.record_connect(*addr, matches!(connected, Ok(Ok(_))).then_some(elapsed));
+        match connected {
             Ok(Ok(stream)) => {
-                debug!(target_addr = %addr, "TCP connected");
-                configure_tcp_socket(&stream);
+                debug!(
+                    target_addr = %addr,
+                    connect_ms = elapsed.as_millis() as u64,
+                    "TCP connected"
+                );
+                configure_tcp_socket(&stream, tcp);
                 return Ok((stream, *addr));
             }
             Ok(Err(e)) => {
@@ -187,21 +497,29 @@ async fn connect_to_addrs(
         }
     }
 
-    Err(last_err
-        .map(|e| anyhow::anyhow!(e))
-        .unwrap_or_else(|| anyhow::anyhow!("failed to connect to {}:{}", host, port)))
+    Err(last_err)
+}
+
+/// `reason` label of a retried connect error.
+fn retry_reason(e: &std::io::Error) -> &'static str {
+    match e.kind() {
+        std::io::ErrorKind::ConnectionRefused => "refused",
+        _ => "timeout",
+    }
 }
 
 /// Connect to a target host:port via an upstream SOCKS5 proxy.
 ///
 /// Performs the full SOCKS5 client handshake (greeting, optional auth, CONNECT),
 /// then returns the tunnelled TCP stream. DNS resolution of the target is delegated
-/// to the upstream proxy (ATYP_DOMAIN).
+/// to the upstream proxy (ATYP_DOMAIN). The connection to the proxy gets the
+/// `[proxy.tcp]` keepalive and user timeout options.
 pub async fn connect_via_socks5(
     proxy: &crate::config::types::ParsedUpstreamProxy,
     target_host: &str,
     target_port: u16,
     timeout: Duration,
+    tcp: &TcpConfig,
 ) -> Result<TcpStream> {
     use crate::socks::protocol::{
         ATYP_DOMAIN, ATYP_IPV4, ATYP_IPV6, AUTH_NONE, AUTH_PASSWORD, CMD_CONNECT, REPLY_SUCCESS,
@@ -216,7 +534,7 @@ pub async fn connect_via_socks5(
         .map_err(|_| anyhow::anyhow!("timeout connecting to upstream proxy {}", proxy_addr))?
         .with_context(|| format!("failed to connect to upstream proxy {}", proxy_addr))?;
 
-    configure_tcp_socket(&stream);
+    configure_tcp_socket(&stream, tcp);
 
     let (reader, writer) = stream.into_split();
     let mut reader = tokio::io::BufReader::new(reader);
@@ -380,13 +698,77 @@ pub async fn connect_via_socks5(
     Ok(stream)
 }
 
-/// Set TCP keepalive and nodelay on a connected stream.
-fn configure_tcp_socket(stream: &TcpStream) {
+/// Open a TCP connection to `addr`, with TCP Fast Open when `tcp.fast_open`.
+async fn open_stream(addr: SocketAddr, tcp: &TcpConfig) -> std::io::Result<TcpStream> {
+    if tcp.fast_open {
+        return connect_fast_open(addr).await;
+    }
+    TcpStream::connect(addr).await
+}
+
+/// Connect with `TCP_FASTOPEN_CONNECT`: the kernel sends the first write in
+/// the SYN once it holds a cookie for the destination, and falls back to a
+/// regular handshake otherwise (or when `net.ipv4.tcp_fastopen` lacks bit 1).
+#[cfg(target_os = "linux")]
+async fn connect_fast_open(addr: SocketAddr) -> std::io::Result<TcpStream> {
+    use std::os::fd::AsRawFd;
+    let socket = if addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    let enable: libc::c_int = 1;
+    // SAFETY: the fd is a live socket owned by `socket`, and the option value
+    // points to a c_int of the given length.
+    let rc = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN_CONNECT,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        debug!(
+            target_addr = %addr,
+            error = %std::io::Error::last_os_error(),
+            "TCP_FASTOPEN_CONNECT unavailable, regular connect"
+        );
+    }
+    socket.connect(addr).await
+}
+
+/// TCP Fast Open is only wired up on Linux; elsewhere connect normally.
+#[cfg(not(target_os = "linux"))]
+async fn connect_fast_open(addr: SocketAddr) -> std::io::Result<TcpStream> {
+    TcpStream::connect(addr).await
+}
+
+/// Set the `[proxy.tcp]` keepalive and user timeout, and nodelay, on a
+/// connected stream. Options the platform lacks are skipped.
+fn configure_tcp_socket(stream: &TcpStream, tcp: &TcpConfig) {
     use socket2::SockRef;
     let sock = SockRef::from(stream);
-    let ka = socket2::TcpKeepalive::new()
-        .with_time(Duration::from_secs(60))
-        .with_interval(Duration::from_secs(15));
-    let _ = sock.set_tcp_keepalive(&ka);
+    if tcp.keepalive_time_secs > 0 {
+        let ka = socket2::TcpKeepalive::new()
+            .with_time(Duration::from_secs(tcp.keepalive_time_secs))
+            .with_interval(Duration::from_secs(tcp.keepalive_interval_secs));
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+        let ka = match tcp.keepalive_retries {
+            Some(retries) => ka.with_retries(retries),
+            None => ka,
+        };
+        if let Err(e) = sock.set_tcp_keepalive(&ka) {
+            debug!(error = %e, "Failed to set TCP keepalive");
+        }
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if tcp.user_timeout_ms > 0 {
+        let timeout = Duration::from_millis(tcp.user_timeout_ms);
+        if let Err(e) = sock.set_tcp_user_timeout(Some(timeout)) {
+            debug!(error = %e, "Failed to set TCP_USER_TIMEOUT");
+        }
+    }
     let _ = stream.set_nodelay(true);
 }
@@ -1,26 +1,72 @@
+use crate::config::types::{AppConfig, DnsEvictionPolicy};
+use crate::metrics::MetricsRegistry;
 use crate::proxy::ip_guard;
 use dashmap::DashMap;
-use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::debug;
 
+/// Addresses whose connect latency is remembered at most; past it, stale
+/// measurements are dropped and new addresses are not tracked.
+pub const MAX_TRACKED_ADDRS: usize = 4096;
+
+/// Weight of a new connect time in the smoothed latency.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+/// Recent connects to one address (`proxy.dns_cache.latency_ordering`)
+#[derive(Debug, Clone, Copy)]
+struct AddrLatency {
+    /// Smoothed connect time (None until a connect succeeds)
+    rtt: Option<Duration>,
+    /// Failed connects since the last success
+    failures: u32,
+    updated_at: Instant,
+}
+
 /// DNS cache entry
 struct CacheEntry {
     addrs: Vec<SocketAddr>,
     inserted_at: Instant,
     last_accessed: Instant,
     ttl: Duration,
+    /// Lookups served or stored under this key (prefetch ranking)
+    uses: u64,
+    /// Looked up since the last (pre)fetch: still worth refreshing
+    used_since_refresh: bool,
+}
+
+impl CacheEntry {
+    /// Time before expiry at which a hot entry is prefetched.
+    fn prefetch_margin(&self) -> Duration {
+        (self.ttl / 10).clamp(Duration::from_secs(1), Duration::from_secs(10))
+    }
 }
 
-/// DNS cache with configurable TTL and ip_guard re-validation.
+/// DNS cache with configurable TTL and ip_guard re-validation, fronted by
+/// the `[proxy.hosts]` static entries.
 pub struct DnsCache {
     cache: DashMap<String, CacheEntry>,
     /// -1 = follow native DNS TTL, 0 = disabled, N = N seconds custom TTL.
     ttl_mode: i64,
     max_entries: u32,
+    /// Bounds applied to every TTL (`proxy.dns_cache.min_ttl` / `max_ttl`)
+    min_ttl: Duration,
+    max_ttl: Duration,
+    eviction: DnsEvictionPolicy,
     pub hits: AtomicU64,
     pub misses: AtomicU64,
+    /// Entries dropped to make room (expired entries not included)
+    pub evictions: AtomicU64,
+    metrics: Option<Arc<MetricsRegistry>>,
+    /// `[proxy.hosts]`, keyed by normalized hostname
+    static_hosts: HashMap<String, Vec<IpAddr>>,
+    /// Recent connect latency per address, to try the fastest first
+    latencies: DashMap<SocketAddr, AddrLatency>,
+    /// How long a measurement counts (zero = addresses keep resolver order)
+    latency_ttl: Duration,
 }
 
 impl DnsCache {
@@ -29,8 +75,85 @@ impl DnsCache {
             cache: DashMap::new(),
             ttl_mode,
             max_entries,
+            min_ttl: Duration::ZERO,
+            max_ttl: Duration::from_secs(3600),
+            eviction: DnsEvictionPolicy::Lru,
             hits: AtomicU64::new(0),
             misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            metrics: None,
+            static_hosts: HashMap::new(),
+            latencies: DashMap::new(),
+            latency_ttl: Duration::from_secs(600),
+        }
+    }
+
+    /// Cache for `server.dns_cache_ttl` and the `[proxy.dns_cache]` bounds.
+    pub fn from_config(config: &AppConfig) -> Self {
+        let dns = &config.proxy.dns_cache;
+        let max_entries = dns
+            .max_entries
+            .unwrap_or(config.server.dns_cache_max_entries);
+        Self::new(config.server.dns_cache_ttl, max_entries)
+            .with_ttl_bounds(
+                Duration::from_secs(dns.min_ttl),
+                Duration::from_secs(dns.max_ttl),
+            )
+            .with_eviction(dns.eviction)
+            .with_static_hosts(&config.proxy.hosts)
+            .with_latency_ordering(dns.latency_ordering, Duration::from_secs(dns.latency_ttl))
+    }
+
+    /// Clamp every cached TTL to `min..=max`.
+    pub fn with_ttl_bounds(mut self, min: Duration, max: Duration) -> Self {
+        self.min_ttl = min;
+        self.max_ttl = max.max(min);
+        self
+    }
+
+    /// Entry dropped when the cache is full.
+    pub fn with_eviction(mut self, eviction: DnsEvictionPolicy) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    /// Fixed addresses answered instead of DNS, whether or not caching is
+    /// enabled. Names match case-insensitively, with or without a final dot.
+    pub fn with_static_hosts(mut self, hosts: &HashMap<String, Vec<IpAddr>>) -> Self {
+        self.static_hosts = hosts
+            .iter()
+            .map(|(name, ips)| (normalize_host(name), ips.clone()))
+            .collect();
+        self
+    }
+
+    /// Order connect attempts by the latency measured in the last `ttl`
+    /// (`enabled = false` keeps the resolver's order).
+    pub fn with_latency_ordering(mut self, enabled: bool, ttl: Duration) -> Self {
+        self.latency_ttl = if enabled { ttl } else { Duration::ZERO };
+        self
+    }
+
+    /// Addresses pinned for `host` in `[proxy.hosts]`, if any.
+    pub fn static_lookup(&self, host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        if self.static_hosts.is_empty() {
+            return None;
+        }
+        let ips = self.static_hosts.get(&normalize_host(host))?;
+        Some(ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect())
+    }
+
+    /// Report evictions in `s5_dns_cache_evictions_total`.
+    pub fn set_metrics(&mut self, metrics: Arc<MetricsRegistry>) {
+        self.metrics = Some(metrics);
+    }
+
+    fn record_evictions(&self, reason: &str, count: u64) {
+        if count == 0 {
+            return;
+        }
+        if let Some(ref m) = self.metrics {
+            m.record_dns_cache_evictions(reason, count);
         }
     }
 
@@ -54,6 +177,7 @@ impl DnsCache {
             drop(entry);
             self.cache.remove(key);
             self.misses.fetch_add(1, Ordering::Relaxed);
+            self.record_evictions("expired", 1);
             return None;
         }
 
@@ -78,6 +202,8 @@ impl DnsCache {
 
         // Update last_accessed for LRU tracking
         entry.last_accessed = now;
+        entry.uses += 1;
+        entry.used_since_refresh = true;
 
         self.hits.fetch_add(1, Ordering::Relaxed);
         Some(addrs)
@@ -89,16 +215,17 @@ impl DnsCache {
             return;
         }
 
-        // Enforce max entries: evict expired first, then LRU if still full
-        if self.cache.len() >= self.max_entries as usize {
+        // Enforce max entries: evict expired first, then per policy if still full
+        if self.cache.len() >= self.max_entries as usize && !self.cache.contains_key(key) {
             self.cleanup_expired();
             if self.cache.len() >= self.max_entries as usize {
-                self.evict_lru();
+                self.evict_one();
             }
         }
 
         let ttl = self.resolve_ttl(native_ttl);
         let now = Instant::now();
+        let uses = self.cache.get(key).map_or(0, |e| e.uses);
 
         self.cache.insert(
             key.to_string(),
@@ -107,39 +234,165 @@ impl DnsCache {
                 inserted_at: now,
                 last_accessed: now,
                 ttl,
+                uses: uses + 1,
+                used_since_refresh: true,
             },
         );
     }
 
-    /// Determine the TTL to use based on config mode and native DNS TTL.
+    /// Replace the addresses of a prefetched entry, restarting its TTL. The
+    /// use count is kept; a key evicted in the meantime is not re-added.
+    pub fn refresh(&self, key: &str, addrs: Vec<SocketAddr>, native_ttl: Option<Duration>) {
+        let ttl = self.resolve_ttl(native_ttl);
+        if let Some(mut entry) = self.cache.get_mut(key) {
+            entry.addrs = addrs;
+            entry.inserted_at = Instant::now();
+            entry.ttl = ttl;
+            entry.used_since_refresh = false;
+        }
+    }
+
+    /// Keys (`host:port`) to prefetch: among the `top_n` most-used entries
+    /// looked up since their last fetch, those about to expire.
+    pub fn prefetch_candidates(&self, top_n: usize) -> Vec<String> {
+        let now = Instant::now();
+        let mut hot: Vec<(u64, String, bool)> = self
+            .cache
+            .iter()
+            .filter(|e| e.value().used_since_refresh)
+            .map(|e| {
+                let entry = e.value();
+                let due =
+                    now.duration_since(entry.inserted_at) + entry.prefetch_margin() >= entry.ttl;
+                (entry.uses, e.key().clone(), due)
+            })
+            .collect();
+        hot.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        hot.into_iter()
+            .take(top_n)
+            .filter(|(_, _, due)| *due)
+            .map(|(_, key, _)| key)
+            .collect()
+    }
+
+    /// Determine the TTL to use based on config mode and native DNS TTL,
+    /// clamped to the configured bounds.
     fn resolve_ttl(&self, native_ttl: Option<Duration>) -> Duration {
-        if self.ttl_mode < 0 {
+        let ttl = if self.ttl_mode < 0 {
             // Follow native DNS TTL, default to 60s if not available
             native_ttl.unwrap_or(Duration::from_secs(60))
         } else {
             Duration::from_secs(self.ttl_mode as u64)
-        }
+        };
+        ttl.clamp(self.min_ttl, self.max_ttl)
     }
 
-    /// Evict the least-recently-accessed entry from the cache.
-    fn evict_lru(&self) {
-        let oldest = self
+    /// Evict one entry according to the eviction policy.
+    fn evict_one(&self) {
+        let victim = self
             .cache
             .iter()
-            .min_by_key(|entry| entry.value().last_accessed)
+            .min_by_key(|entry| {
+                let e = entry.value();
+                match self.eviction {
+                    DnsEvictionPolicy::Lru => (0, e.last_accessed),
+                    DnsEvictionPolicy::Lfu => (e.uses, e.last_accessed),
+                    DnsEvictionPolicy::Fifo => (0, e.inserted_at),
+                }
+            })
             .map(|entry| entry.key().clone());
 
-        if let Some(key) = oldest {
-            debug!(key = %key, "DNS cache full, evicting LRU entry");
-            self.cache.remove(&key);
+        if let Some(key) = victim {
+            debug!(key = %key, policy = %self.eviction, "DNS cache full, evicting entry");
+            if self.cache.remove(&key).is_some() {
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                self.record_evictions("capacity", 1);
+            }
         }
     }
 
+    /// Whether connect attempts are ordered by measured latency.
+    pub fn orders_by_latency(&self) -> bool {
+        !self.latency_ttl.is_zero()
+    }
+
+    /// Remember how a connect to `addr` went: its duration when it
+    /// succeeded, None when it failed or timed out.
+    pub fn record_connect(&self, addr: SocketAddr, elapsed: Option<Duration>) {
+        if !self.orders_by_latency() {
+            return;
+        }
+        let now = Instant::now();
+        if self.latencies.len() >= MAX_TRACKED_ADDRS && !self.latencies.contains_key(&addr) {
+            self.latencies
+                .retain(|_, l| now.duration_since(l.updated_at) < self.latency_ttl);
+            if self.latencies.len() >= MAX_TRACKED_ADDRS {
+                return;
+            }
+        }
+        let mut entry = self.latencies.entry(addr).or_insert(AddrLatency {
+            rtt: None,
+            failures: 0,
+            updated_at: now,
+        });
+        if now.duration_since(entry.updated_at) >= self.latency_ttl {
+            entry.rtt = None;
+            entry.failures = 0;
+        }
+        match elapsed {
+            Some(elapsed) => {
+                entry.rtt = Some(match entry.rtt {
+                    Some(rtt) => {
+                        rtt.mul_f64(1.0 - LATENCY_SMOOTHING) + elapsed.mul_f64(LATENCY_SMOOTHING)
+                    }
+                    None => elapsed,
+                });
+                entry.failures = 0;
+            }
+            None => entry.failures = entry.failures.saturating_add(1),
+        }
+        entry.updated_at = now;
+    }
+
+    /// Smoothed connect time to `addr` measured in the last
+    /// `latency_ttl`, if any.
+    pub fn connect_latency(&self, addr: &SocketAddr) -> Option<Duration> {
+        self.fresh_latency(addr).and_then(|l| l.rtt)
+    }
+
+    fn fresh_latency(&self, addr: &SocketAddr) -> Option<AddrLatency> {
+        let latency = *self.latencies.get(addr)?;
+        (latency.updated_at.elapsed() < self.latency_ttl).then_some(latency)
+    }
+
+    /// Sort `addrs` for connect attempts: addresses that last connected,
+    /// fastest first, then unmeasured ones in resolver order, then those
+    /// whose last connect failed, fewest failures first.
+    pub fn order_by_latency(&self, addrs: &mut [SocketAddr]) {
+        if !self.orders_by_latency() || addrs.len() < 2 || self.latencies.is_empty() {
+            return;
+        }
+        addrs.sort_by_cached_key(|addr| match self.fresh_latency(addr) {
+            Some(l) if l.failures > 0 => (2, Duration::ZERO, l.failures),
+            Some(AddrLatency { rtt: Some(rtt), .. }) => (0, rtt, 0),
+            _ => (1, Duration::ZERO, 0),
+        });
+    }
+
     /// Remove expired entries.
     pub fn cleanup_expired(&self) {
         let now = Instant::now();
-        self.cache
-            .retain(|_, entry| now.duration_since(entry.inserted_at) <= entry.ttl);
+        let mut expired = 0;
+        self.cache.retain(|_, entry| {
+            let live = now.duration_since(entry.inserted_at) <= entry.ttl;
+            expired += u64::from(!live);
+            live
+        });
+        self.record_evictions("expired", expired);
+        if self.orders_by_latency() {
+            self.latencies
+                .retain(|_, l| now.duration_since(l.updated_at) < self.latency_ttl);
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -157,6 +410,10 @@ impl Default for DnsCache {
     }
 }
 
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,19 +1,66 @@
+use crate::audit::events::AuditEvent;
 use crate::audit::AuditLogger;
+use crate::config::acl::{AclRule, ParsedAcl};
+use crate::metrics::MetricsRegistry;
+use crate::proxy::acl;
+use crate::proxy::buffer_pool;
+use crate::proxy::classify::{classify, first_line, AppProtocol, Classification};
+use crate::proxy::close::CloseReason;
+use crate::proxy::inspect::{Inspector, UpgradeWatch};
+use crate::proxy::memory::MemoryCharge;
+use crate::proxy::priority;
 use crate::proxy::LiveSession;
 use crate::quota::{QuotaConfig, QuotaTracker, UserBandwidthState};
 use anyhow::Result;
-use std::sync::atomic::Ordering;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tracing::{debug, info};
-
-/// Buffer size for relay read operations (8 KiB)
-const RELAY_BUFFER_SIZE: usize = 8192;
+use tracing::{debug, info, warn};
 
 /// Conversion factor from kilobits/s to bytes/s: 1 kbps = 1000 bits/s = 125 bytes/s
 const KILOBITS_TO_BYTES_PER_SEC: f64 = 1000.0 / 8.0;
 
+static FORWARDING_TASKS_ACTIVE: AtomicU64 = AtomicU64::new(0);
+static FORWARDING_TASKS_SPAWNED: AtomicU64 = AtomicU64::new(0);
+
+/// Relay direction tasks, for the runtime metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForwardingTasks {
+    pub active: u64,
+    pub spawned: u64,
+}
+
+/// Relay direction tasks running now and spawned since startup.
+pub fn forwarding_tasks() -> ForwardingTasks {
+    ForwardingTasks {
+        active: FORWARDING_TASKS_ACTIVE.load(Ordering::Relaxed),
+        spawned: FORWARDING_TASKS_SPAWNED.load(Ordering::Relaxed),
+    }
+}
+
+/// Counts one relay direction task; leaves the active count on drop, so
+/// aborted and panicking tasks are released too.
+struct ForwardingTask;
+
+impl ForwardingTask {
+    fn start() -> Self {
+        FORWARDING_TASKS_SPAWNED.fetch_add(1, Ordering::Relaxed);
+        FORWARDING_TASKS_ACTIVE.fetch_add(1, Ordering::Relaxed);
+        ForwardingTask
+    }
+}
+
+impl Drop for ForwardingTask {
+    fn drop(&mut self) {
+        FORWARDING_TASKS_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// Configuration for a relay session, consolidating all throttle/quota parameters.
 pub struct RelayConfig {
     pub idle_timeout: Duration,
@@ -25,6 +72,124 @@ pub struct RelayConfig {
     pub quotas: Option<QuotaConfig>,
     pub audit: Option<Arc<AuditLogger>>,
     pub session: Option<Arc<LiveSession>>,
+    /// Track waits on the client's SSH channel window
+    pub stall_watch: Option<StallWatch>,
+    /// The client's SSH channel window, which the destination reads wait on
+    pub client_window: Option<Arc<dyn ClientWindow>>,
+    /// Check the server name in the client's first bytes (`acl.inspect_server_names`)
+    pub server_names: Option<ServerNameCheck>,
+}
+
+/// Checks the server name a client sends inside its tunnel (TLS SNI or HTTP
+/// `Host`) against its ACL before those bytes reach the destination. A denied
+/// name closes the session with `acl_denied`: the client cannot reach a
+/// forbidden host by connecting to an allowed address.
+#[derive(Clone)]
+pub struct ServerNameCheck {
+    pub acl: Arc<ParsedAcl>,
+    /// Destination port, matched by the rules' port part
+    pub port: u16,
+    /// Address connected to, for CIDR rules (None through an upstream proxy)
+    pub connected_ip: Option<IpAddr>,
+}
+
+impl ServerNameCheck {
+    /// Whether the ACL has hostname rules, so that a TLS or HTTP head
+    /// without a readable name is refused rather than let through.
+    pub fn requires_name(&self) -> bool {
+        self.acl
+            .allow_rules
+            .iter()
+            .chain(&self.acl.deny_rules)
+            .any(|rule| matches!(rule, AclRule::HostPattern { .. }))
+    }
+}
+
+/// The room left in the client's SSH channel window.
+///
+/// With one, the download waits for room before reading from the destination
+/// and reads no more than that, so what a slow client has not taken stays in
+/// the destination's socket, where TCP pushes back, rather than in a chunk
+/// the relay holds against the session's memory budget.
+pub trait ClientWindow: Send + Sync {
+    /// Bytes one write can send (at most the maximum packet size), once
+    /// the client has made room for any: pending while the window is full.
+    fn room(&self) -> Pin<Box<dyn Future<Output = usize> + Send + '_>>;
+}
+
+/// Flags a relay waiting on a full SSH channel window.
+///
+/// With a [`ClientWindow`] the download waits for room before it reads from
+/// the destination; without one its writes to the client wait as the window
+/// opens, one relay buffer read ahead. Either way a slow client throttles
+/// the destination reads; this watch makes those waits visible in
+/// `s5_ssh_channels_stalled`.
+#[derive(Clone)]
+pub struct StallWatch {
+    /// A write pending this long counts as a stall
+    pub threshold: Duration,
+    pub metrics: Option<Arc<MetricsRegistry>>,
+}
+
+impl StallWatch {
+    fn enter(&self) -> Stalled<'_> {
+        if let Some(ref m) = self.metrics {
+            m.ssh_channels_stalled.inc();
+            m.ssh_channel_stalls_total.inc();
+        }
+        Stalled(self)
+    }
+}
+
+/// A stalled write in progress; leaves the stalled gauge on drop.
+struct Stalled<'a>(&'a StallWatch);
+
+impl Drop for Stalled<'_> {
+    fn drop(&mut self) {
+        if let Some(ref m) = self.0.metrics {
+            m.ssh_channels_stalled.dec();
+        }
+    }
+}
+
+/// Write all of `data`, counting the wait as a stall once it outlasts the
+/// watch threshold.
+async fn write_watched<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+    watch: Option<&StallWatch>,
+    context: &str,
+) -> std::io::Result<()> {
+    let write = tokio::io::AsyncWriteExt::write_all(writer, data);
+    let Some(watch) = watch else {
+        return write.await;
+    };
+    tokio::pin!(write);
+    if let Ok(result) = tokio::time::timeout(watch.threshold, &mut write).await {
+        return result;
+    }
+    let _stalled = watch.enter();
+    debug!(context = %context, "SSH channel stalled on the client window");
+    write.await
+}
+
+/// Wait until the client's window has room and return it, counting the wait
+/// as a stall once it outlasts the watch threshold.
+async fn wait_for_room(
+    window: &dyn ClientWindow,
+    watch: Option<&StallWatch>,
+    context: &str,
+) -> usize {
+    let mut room = window.room();
+    let Some(watch) = watch else {
+        return room.await;
+    };
+    if let Ok(room) = tokio::time::timeout(watch.threshold, &mut room).await {
+        return room;
+    }
+    let _stalled = watch.enter();
+    debug!(context = %context, "SSH channel stalled on the client window");
+    room.await
 }
 
 /// Parameters for one direction of a relay, owned by the spawned task.
@@ -42,40 +207,343 @@ struct DirectionParams {
     direction_is_upload: bool,
     /// Pre-fetched user bandwidth state to avoid DashMap lookup per chunk.
     cached_user_state: Option<Arc<UserBandwidthState>>,
+    stall_watch: Option<StallWatch>,
+    client_window: Option<Arc<dyn ClientWindow>>,
+    server_names: Option<ServerNameCheck>,
+    /// The destination's answers to upgrades, read on download for the
+    /// upload's inspector
+    upgrade_watch: Option<Arc<UpgradeWatch>>,
+}
+
+/// Run `fut` unless the session is killed first.
+async fn unless_killed<T>(
+    session: Option<&LiveSession>,
+    fut: impl std::future::Future<Output = T>,
+) -> Result<T, CloseReason> {
+    match session {
+        Some(session) => tokio::select! {
+            biased;
+            reason = session.kill.killed() => Err(reason),
+            value = fut => Ok(value),
+        },
+        None => Ok(fut.await),
+    }
+}
+
+/// Reason for an I/O error on the destination side.
+fn upstream_error(e: &std::io::Error) -> CloseReason {
+    match e.kind() {
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+            CloseReason::UpstreamReset
+        }
+        _ => CloseReason::Error,
+    }
+}
+
+/// End a session that went past `limits.max_buffered_bytes`: stop the other
+/// direction too and record why.
+fn memory_exceeded(
+    session: &LiveSession,
+    charge: &MemoryCharge,
+    params: &DirectionParams,
+) -> CloseReason {
+    let buffered = charge.buffered();
+    if session.kill.kill(CloseReason::MemoryLimit) {
+        warn!(
+            context = %params.context,
+            session = %session.session_id,
+            buffered_bytes = buffered,
+            max_buffered_bytes = session.memory.budget(),
+            "Session memory budget exceeded, closing"
+        );
+        if let Some(ref audit) = params.audit {
+            audit.log_event(AuditEvent::session_memory_exceeded(session, buffered));
+        }
+    }
+    CloseReason::MemoryLimit
+}
+
+/// End a session a chunk would take past `limits.max_connection_bytes`:
+/// stop the other direction too and record why.
+fn transfer_exceeded(session: &LiveSession, params: &DirectionParams) -> CloseReason {
+    if session.kill.kill(CloseReason::TransferLimit) {
+        warn!(
+            context = %params.context,
+            session = %session.session_id,
+            bytes_up = session.bytes_up.load(Ordering::Relaxed),
+            bytes_down = session.bytes_down.load(Ordering::Relaxed),
+            max_connection_bytes = session.transfer.max_bytes,
+            "Session transfer limit reached, closing"
+        );
+        if let Some(ref audit) = params.audit {
+            audit.log_event(AuditEvent::session_transfer_exceeded(session));
+        }
+    }
+    CloseReason::TransferLimit
+}
+
+/// Count `n` relayed bytes on the session and raise a transfer alert the
+/// first time its direction goes past `limits.upload_alert_bytes` or
+/// `limits.download_alert_bytes`.
+fn count_bytes(session: &LiveSession, upload: bool, n: u64, params: &DirectionParams) {
+    let counter = if upload {
+        &session.bytes_up
+    } else {
+        &session.bytes_down
+    };
+    let before = counter.fetch_add(n, Ordering::Relaxed);
+    let Some(threshold) = session.transfer.crossed_alert(upload, before, before + n) else {
+        return;
+    };
+    warn!(
+        context = %params.context,
+        session = %session.session_id,
+        direction = if upload { "upload" } else { "download" },
+        bytes = before + n,
+        threshold = threshold,
+        "Session transfer alert threshold crossed"
+    );
+    if let Some(ref audit) = params.audit {
+        audit.log_event(AuditEvent::session_transfer_alert(
+            session,
+            upload,
+            before + n,
+            threshold,
+        ));
+    }
+}
+
+/// Classify the session from the first head (or chunk) its client sent.
+fn record_traffic(session: &LiveSession, head: &[u8], traffic: &Classification) {
+    session.traffic.get_or_init(|| {
+        let mut traffic = traffic.clone();
+        if let Some(&max_len) = session.first_line_len.get() {
+            traffic.first_line = first_line(head, traffic.protocol, max_len);
+        }
+        traffic
+    });
+}
+
+/// Whether a head the client sent (see [`Inspector`]) may go on; the first
+/// one classifies the session. A denied or missing server name stops the
+/// other direction too.
+fn head_allowed(
+    session: &LiveSession,
+    head: &[u8],
+    traffic: &Classification,
+    first: bool,
+    params: &DirectionParams,
+) -> bool {
+    if first {
+        record_traffic(session, head, traffic);
+    }
+    let Some(check) = &params.server_names else {
+        return true;
+    };
+    let (name, decision, reason) = match &traffic.server_name {
+        Some(name) => {
+            let decision = acl::check_server_name_and_log(
+                &check.acl,
+                &session.username,
+                name,
+                check.port,
+                check.connected_ip,
+            );
+            (name.as_str(), decision, acl::SERVER_NAME_CHECK)
+        }
+        // Later heads are HTTP requests, which must name their host
+        None if check.requires_name()
+            && (!first || matches!(traffic.protocol, AppProtocol::Tls | AppProtocol::Http)) =>
+        {
+            warn!(
+                user = %session.username,
+                protocol = %traffic.protocol,
+                port = check.port,
+                reason = acl::SERVER_NAME_MISSING,
+                "ACL: denied (no server name)"
+            );
+            let decision = acl::AclDecision {
+                allowed: false,
+                matched_rule: None,
+            };
+            (
+                session.target_host.as_str(),
+                decision,
+                acl::SERVER_NAME_MISSING,
+            )
+        }
+        None => return true,
+    };
+    if decision.allowed {
+        return true;
+    }
+    if session.kill.kill(CloseReason::AclDenied) {
+        if let Some(ref audit) = params.audit {
+            let (user, source) = (&session.username, &session.source_ip);
+            let ip = check.connected_ip.map(|ip| ip.to_string());
+            let rule = decision.matched_rule;
+            match session.correlation_id.as_deref() {
+                Some(cid) => {
+                    audit.log_acl_deny_cid(user, name, check.port, ip, source, rule, reason, cid)
+                }
+                None => audit.log_acl_deny(user, name, check.port, ip, source, rule, reason),
+            }
+        }
+    }
+    false
 }
 
 /// Relay data in one direction: reader → writer, with idle timeout, throttling, and quota enforcement.
+/// Returns the bytes relayed and why the direction ended.
 async fn relay_one_direction<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
     mut reader: R,
     mut writer: W,
     params: DirectionParams,
-) -> u64 {
+) -> (u64, CloseReason) {
     let mut total = 0u64;
-    let mut buf = vec![0u8; RELAY_BUFFER_SIZE];
+    let mut buf = buffer_pool::global().get();
+    let session = params.session.as_deref();
+    // Upload reads from the client and writes to the destination
+    let upload = params.direction_is_upload;
+    let mut first_chunk = upload;
+    // With server names checked, client bytes go through once their head is allowed
+    let mut inspector = match (&params.upgrade_watch, session, &params.server_names) {
+        (Some(watch), Some(_), Some(_)) if upload => {
+            Some(Inspector::with_upgrade_watch(Arc::clone(watch)))
+        }
+        _ => None,
+    };
     loop {
-        match tokio::time::timeout(
-            params.timeout,
-            tokio::io::AsyncReadExt::read(&mut reader, &mut buf),
-        )
-        .await
-        {
-            Ok(Ok(0)) => break,
-            Ok(Ok(n)) => {
-                if tokio::io::AsyncWriteExt::write_all(&mut writer, &buf[..n])
-                    .await
-                    .is_err()
-                {
-                    break;
+        // Bytes held for an upgrade go on once the destination answers
+        let held = inspector.as_ref().and_then(Inspector::held_for_upgrade);
+        let full = inspector.as_ref().is_some_and(Inspector::hold_is_full);
+        // Read no more than the client's window takes; a window that stays
+        // full as long as the idle timeout ends the relay like silence would
+        let room = match &params.client_window {
+            Some(window) => {
+                let watch = params.stall_watch.as_ref();
+                let wait = wait_for_room(window.as_ref(), watch, &params.context);
+                match unless_killed(session, tokio::time::timeout(params.timeout, wait)).await {
+                    Ok(Ok(room)) => room.min(buf.len()),
+                    Ok(Err(_)) => {
+                        debug!(context = %params.context, direction = params.direction, "Relay idle timeout");
+                        return (total, CloseReason::IdleTimeout);
+                    }
+                    Err(reason) => return (total, reason),
+                }
+            }
+            None => buf.len(),
+        };
+        let read = tokio::time::timeout(params.timeout, async {
+            let read = tokio::io::AsyncReadExt::read(&mut reader, &mut buf[..room]);
+            match held {
+                Some(watch) if full => {
+                    watch.decided().await;
+                    Ok(None)
+                }
+                Some(watch) => tokio::select! {
+                    () = watch.decided() => Ok(None),
+                    read = read => read.map(Some),
+                },
+                None => read.await.map(Some),
+            }
+        });
+        match unless_killed(session, read).await {
+            Err(reason) => return (total, reason),
+            Ok(Ok(Ok(Some(0)))) if upload => {
+                // A head cut short by the client's EOF is judged as it stands
+                if let (Some(inspector), Some(session)) = (inspector.as_mut(), session) {
+                    let mut allow = |head: &[u8], traffic: &Classification, first: bool| {
+                        head_allowed(session, head, traffic, first, &params)
+                    };
+                    let Some(rest) = inspector.finish(&mut allow) else {
+                        return (total, CloseReason::AclDenied);
+                    };
+                    let write = write_watched(
+                        &mut writer,
+                        &rest,
+                        params.stall_watch.as_ref(),
+                        &params.context,
+                    );
+                    if !rest.is_empty() && write.await.is_ok() {
+                        total += rest.len() as u64;
+                        count_bytes(session, upload, rest.len() as u64, &params);
+                        session.capture.record(upload, &rest);
+                    }
+                }
+                return (total, CloseReason::ClientEof);
+            }
+            Ok(Ok(Ok(Some(0)))) => return (total, CloseReason::UpstreamEof),
+            Ok(Ok(Ok(read))) => {
+                let n = read.unwrap_or(0);
+                let inspected;
+                let chunk = match (inspector.as_mut(), session) {
+                    (Some(inspector), Some(session)) => {
+                        let mut allow = |head: &[u8], traffic: &Classification, first: bool| {
+                            head_allowed(session, head, traffic, first, &params)
+                        };
+                        match inspector.feed(&buf[..n], &mut allow) {
+                            None => return (total, CloseReason::AclDenied),
+                            Some(bytes) if bytes.is_empty() => continue,
+                            Some(bytes) => {
+                                inspected = bytes;
+                                &inspected[..]
+                            }
+                        }
+                    }
+                    _ => &buf[..n],
+                };
+                if let Some(watch) = &params.upgrade_watch {
+                    if !upload {
+                        watch.observe(chunk);
+                    }
+                }
+                let n = chunk.len();
+                // The chunk counts against the session's memory budget until written
+                let held = session.map(|s| s.memory.charge(n));
+                if let (Some(session), Some(charge)) = (session, &held) {
+                    if charge.over_budget() {
+                        return (total, memory_exceeded(session, charge, &params));
+                    }
+                    if std::mem::take(&mut first_chunk) && inspector.is_none() {
+                        record_traffic(session, chunk, &classify(chunk));
+                    }
+                    let moved = session.bytes_up.load(Ordering::Relaxed)
+                        + session.bytes_down.load(Ordering::Relaxed);
+                    if session.transfer.would_exceed(moved, n as u64) {
+                        return (total, transfer_exceeded(session, &params));
+                    }
+                }
+                // Wait for a `[proxy.priority]` write slot
+                let slot = match session.and_then(|s| s.priority.get()) {
+                    Some(flow) => match unless_killed(session, flow.slot(n)).await {
+                        Ok(slot) => Some(slot),
+                        Err(reason) => return (total, reason),
+                    },
+                    None => None,
+                };
+                let write = priority::holding(
+                    slot,
+                    write_watched(
+                        &mut writer,
+                        chunk,
+                        params.stall_watch.as_ref(),
+                        &params.context,
+                    ),
+                );
+                match unless_killed(session, write).await {
+                    Err(reason) => return (total, reason),
+                    Ok(Err(e)) if upload => return (total, upstream_error(&e)),
+                    Ok(Err(_)) => return (total, CloseReason::Error),
+                    Ok(Ok(())) => {}
                 }
+                drop(held);
                 total += n as u64;
 
                 // Update live session byte counters
-                if let Some(ref session) = params.session {
-                    if params.direction_is_upload {
-                        session.bytes_up.fetch_add(n as u64, Ordering::Relaxed);
-                    } else {
-                        session.bytes_down.fetch_add(n as u64, Ordering::Relaxed);
-                    }
+                if let Some(session) = session {
+                    count_bytes(session, upload, n as u64, &params);
+                    session.capture.record(upload, chunk);
                 }
 
                 let delay = if let (Some(qt), Some(cached_state)) =
@@ -94,9 +562,14 @@ async fn relay_one_direction<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
                             if let (Some(ref audit), Some(ref username)) =
                                 (&params.audit, &params.username)
                             {
-                                audit.log_quota_exceeded(username, &reason, 0, 0);
+                                match session.and_then(|s| s.correlation_id.as_deref()) {
+                                    Some(cid) => {
+                                        audit.log_quota_exceeded_cid(username, &reason, 0, 0, cid)
+                                    }
+                                    None => audit.log_quota_exceeded(username, &reason, 0, 0),
+                                }
                             }
-                            break;
+                            return (total, CloseReason::QuotaExceeded);
                         }
                     }
                 } else if params.per_conn_bw > 0 {
@@ -112,17 +585,29 @@ async fn relay_one_direction<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
                 };
 
                 if !delay.is_zero() {
-                    tokio::time::sleep(delay).await;
+                    if let Err(reason) = unless_killed(session, tokio::time::sleep(delay)).await {
+                        return (total, reason);
+                    }
                 }
             }
-            Ok(Err(_)) => break,
-            Err(_) => {
+            Ok(Ok(Err(_))) if upload => return (total, CloseReason::Error),
+            Ok(Ok(Err(e))) => return (total, upstream_error(&e)),
+            Ok(Err(_)) => {
                 debug!(context = %params.context, direction = params.direction, "Relay idle timeout");
-                break;
+                return (total, CloseReason::IdleTimeout);
             }
         }
     }
-    total
+}
+
+/// Bytes moved by a finished relay and why it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayOutcome {
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub reason: CloseReason,
+    /// What the client sent first (None without a live session or upload)
+    pub traffic: Option<Classification>,
 }
 
 /// Bidirectional relay between two streams with idle timeout, bandwidth throttling, and quota enforcement.
@@ -132,6 +617,22 @@ where
     A: AsyncRead + AsyncWrite + Unpin + Send + 'static,
     B: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 {
+    let outcome = relay_with_reason(stream_a, stream_b, config).await?;
+    Ok((outcome.bytes_up, outcome.bytes_down))
+}
+
+/// [`relay`] that also reports why the relay ended. A is the client side,
+/// B the destination.
+pub async fn relay_with_reason<A, B>(
+    stream_a: A,
+    stream_b: B,
+    config: RelayConfig,
+) -> Result<RelayOutcome>
+where
+    A: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    B: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let session = config.session.clone();
     let effective_timeout = if config.idle_timeout.is_zero() {
         Duration::from_secs(365 * 24 * 3600)
     } else {
@@ -145,12 +646,17 @@ where
     // Wrap quotas in Arc once, shared between both relay directions to avoid cloning
     let shared_quotas = config.quotas.map(Arc::new);
 
-    // Pre-fetch user bandwidth state to avoid DashMap lookup per 8KiB chunk
+    // Pre-fetch user bandwidth state to avoid DashMap lookup per chunk
     let cached_user_state = match (&config.quota_tracker, &config.username) {
         (Some(qt), Some(username)) => Some(qt.get_user(username)),
         _ => None,
     };
 
+    let upgrade_watch = config
+        .server_names
+        .is_some()
+        .then(|| Arc::new(UpgradeWatch::default()));
+
     let ab_params = DirectionParams {
         timeout: effective_timeout,
         context: config.context.clone(),
@@ -164,6 +670,11 @@ where
         session: config.session.clone(),
         direction_is_upload: true,
         cached_user_state: cached_user_state.clone(),
+        stall_watch: None,
+        client_window: None,
+        // Only the client's bytes carry the server name
+        server_names: config.server_names,
+        upgrade_watch: upgrade_watch.clone(),
     };
 
     let ba_params = DirectionParams {
@@ -179,36 +690,63 @@ where
         session: config.session,
         direction_is_upload: false,
         cached_user_state,
+        // Only writes to the client wait on its channel window
+        stall_watch: config.stall_watch,
+        client_window: config.client_window,
+        server_names: None,
+        upgrade_watch,
     };
 
-    let a_to_b = tokio::spawn(relay_one_direction(a_read, b_write, ab_params));
-    let b_to_a = tokio::spawn(relay_one_direction(b_read, a_write, ba_params));
+    // Each direction reports when it ended, so the first end can explain the close
+    let ab_task = ForwardingTask::start();
+    let a_to_b = tokio::spawn(async move {
+        let _task = ab_task;
+        let (bytes, reason) = relay_one_direction(a_read, b_write, ab_params).await;
+        (bytes, reason, Instant::now())
+    });
+    let ba_task = ForwardingTask::start();
+    let b_to_a = tokio::spawn(async move {
+        let _task = ba_task;
+        let (bytes, reason) = relay_one_direction(b_read, a_write, ba_params).await;
+        (bytes, reason, Instant::now())
+    });
 
     let (ab_result, ba_result) = tokio::join!(a_to_b, b_to_a);
 
-    let bytes_up = match ab_result {
+    let (bytes_up, up_reason, up_end) = match ab_result {
         Ok(v) => v,
         Err(e) => {
             tracing::error!(error = %e, context = %config.context, "Relay panic (a->b)");
-            0
+            (0, CloseReason::Error, Instant::now())
         }
     };
-    let bytes_down = match ba_result {
+    let (bytes_down, down_reason, down_end) = match ba_result {
         Ok(v) => v,
         Err(e) => {
             tracing::error!(error = %e, context = %config.context, "Relay panic (b->a)");
-            0
+            (0, CloseReason::Error, Instant::now())
         }
     };
+    let reason = if up_end <= down_end {
+        CloseReason::combine(up_reason, down_reason)
+    } else {
+        CloseReason::combine(down_reason, up_reason)
+    };
     let duration_ms = start.elapsed().as_millis() as u64;
 
     info!(
         bytes_up = bytes_up,
         bytes_down = bytes_down,
         duration_ms = duration_ms,
+        reason = %reason,
         context = %config.context,
         "Relay completed"
     );
 
-    Ok((bytes_up, bytes_down))
+    Ok(RelayOutcome {
+        bytes_up,
+        bytes_down,
+        reason,
+        traffic: session.and_then(|s| s.traffic.get().cloned()),
+    })
 }
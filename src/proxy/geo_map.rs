@@ -0,0 +1,88 @@
+//! Source locations of the active sessions, for the dashboard world map.
+//!
+//! Sessions are placed with the GeoIP City database (`geoip.database_path`)
+//! and grouped on a grid of [`CELL_DEGREES`] cells, so many sessions from
+//! one region show as a single marker. Each cluster sits at the mean
+//! position of its sessions.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Grid cell size, in degrees of latitude and longitude.
+pub const CELL_DEGREES: f64 = 2.0;
+
+/// One located session.
+#[derive(Debug, Clone)]
+pub struct SessionPoint {
+    pub location: (f64, f64),
+    pub country: Option<String>,
+    pub username: String,
+}
+
+/// Sessions from one grid cell.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeoCluster {
+    pub lat: f64,
+    pub lon: f64,
+    pub sessions: usize,
+    /// Distinct usernames, sorted
+    pub users: Vec<String>,
+    /// Distinct ISO country codes, sorted
+    pub countries: Vec<String>,
+}
+
+/// Clustered session locations, largest cluster first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SessionMap {
+    pub clusters: Vec<GeoCluster>,
+    /// Sessions the database could not place
+    pub unlocated: usize,
+}
+
+#[derive(Default)]
+struct Cell {
+    lat_sum: f64,
+    lon_sum: f64,
+    sessions: usize,
+    users: BTreeSet<String>,
+    countries: BTreeSet<String>,
+}
+
+impl SessionMap {
+    pub fn build(points: impl IntoIterator<Item = SessionPoint>, unlocated: usize) -> Self {
+        let mut cells: BTreeMap<(i32, i32), Cell> = BTreeMap::new();
+        for point in points {
+            let (lat, lon) = point.location;
+            let key = (
+                (lat / CELL_DEGREES).floor() as i32,
+                (lon / CELL_DEGREES).floor() as i32,
+            );
+            let cell = cells.entry(key).or_default();
+            cell.lat_sum += lat;
+            cell.lon_sum += lon;
+            cell.sessions += 1;
+            cell.users.insert(point.username);
+            cell.countries.extend(point.country);
+        }
+        let mut clusters: Vec<GeoCluster> = cells
+            .into_values()
+            .map(|cell| GeoCluster {
+                lat: cell.lat_sum / cell.sessions as f64,
+                lon: cell.lon_sum / cell.sessions as f64,
+                sessions: cell.sessions,
+                users: cell.users.into_iter().collect(),
+                countries: cell.countries.into_iter().collect(),
+            })
+            .collect();
+        clusters.sort_by_key(|c| std::cmp::Reverse(c.sessions));
+        Self {
+            clusters,
+            unlocated,
+        }
+    }
+
+    /// Sessions placed on the map.
+    pub fn located(&self) -> usize {
+        self.clusters.iter().map(|c| c.sessions).sum()
+    }
+}
@@ -0,0 +1,484 @@
+//! Server name inspection of a tunnel's client stream
+//! (`acl.inspect_server_names`).
+//!
+//! The client's bytes are held back until the head naming the server is
+//! complete: a TLS ClientHello, reassembled across records, or an HTTP
+//! request head, up to [`MAX_HEAD_LEN`] bytes. Plain HTTP is then followed
+//! request by request through its `Content-Length` and chunked bodies, so
+//! every request of a kept-alive connection is checked before it is
+//! forwarded. A request whose framing is ambiguous (a malformed, repeated
+//! or conflicting `Content-Length`, `Transfer-Encoding` with
+//! `Content-Length`) is judged as a nameless head, like a malformed chunked
+//! body. After an `Upgrade` or `CONNECT` request the client's bytes are held
+//! until the destination answers (see [`UpgradeWatch`]): the stream turns
+//! opaque on a `101` (a 2xx for `CONNECT`), otherwise requests go on being
+//! checked. It also turns opaque after a TLS hello or any other protocol.
+
+use crate::proxy::classify::{classify, head_len, AppProtocol, Classification};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::Notify;
+
+/// Most bytes held back waiting for a head; a head still incomplete past
+/// this is judged as it stands.
+pub const MAX_HEAD_LEN: usize = 32 * 1024;
+
+/// Longest chunk-size or trailer line followed in a chunked body.
+const MAX_CHUNK_LINE: usize = 4096;
+
+/// Where the inspected stream stands.
+enum State {
+    /// Holding a head: the stream's first, or the next HTTP request's
+    Head {
+        first: bool,
+    },
+    /// Inside an HTTP body, with this many bytes left
+    Body(u64),
+    Chunked(Chunked),
+    /// Waiting for the destination's answer to an upgrade or `CONNECT`
+    Upgrade,
+    /// Nothing more to inspect
+    Opaque,
+}
+
+/// How the request a head opens is framed.
+struct Framing {
+    /// What follows the head
+    body: State,
+    /// Whether the connection may switch protocols after the request, and
+    /// on which answer
+    switch: Option<Switch>,
+}
+
+/// What switches a connection to another protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Switch {
+    /// `101 Switching Protocols` to an `Upgrade` request
+    Upgrade,
+    /// A 2xx to a `CONNECT`
+    Connect,
+}
+
+/// The destination's answer to an upgrade or `CONNECT` request, read by the
+/// relay's other direction and passed to the [`Inspector`] holding the
+/// client's bytes meanwhile. An answer that cannot be read as the response
+/// to that request (a pipelined earlier response, a malformed status line)
+/// keeps the stream inspected.
+#[derive(Default)]
+pub struct UpgradeWatch {
+    state: Mutex<Answer>,
+    decided: Notify,
+}
+
+#[derive(Default)]
+enum Answer {
+    /// No request waiting for an answer
+    #[default]
+    Idle,
+    /// Reading the destination's response head
+    Awaiting { switch: Switch, head: Vec<u8> },
+    /// Whether the connection switched protocols
+    Decided(bool),
+}
+
+impl UpgradeWatch {
+    /// Look at bytes the destination sends, before they reach the client.
+    pub fn observe(&self, data: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let Answer::Awaiting { switch, head } = &mut *state else {
+            return;
+        };
+        head.extend_from_slice(data);
+        let switched = loop {
+            if !b"HTTP/".starts_with(&head[..head.len().min(5)]) {
+                break false;
+            }
+            let Some(end) = head.windows(4).position(|w| w == b"\r\n\r\n") else {
+                if head.len() < MAX_HEAD_LEN {
+                    return;
+                }
+                break false;
+            };
+            let status = head
+                .split(|&b| b == b' ')
+                .nth(1)
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| code.trim().parse::<u16>().ok());
+            match (status, *switch) {
+                (Some(101), Switch::Upgrade) => break true,
+                (Some(200..=299), Switch::Connect) => break true,
+                // Interim responses come before the final one
+                (Some(100..=199), _) => {
+                    head.drain(..end + 4);
+                }
+                _ => break false,
+            }
+        };
+        *state = Answer::Decided(switched);
+        drop(state);
+        self.decided.notify_one();
+    }
+
+    /// Start waiting for the answer to a request that may switch protocols.
+    fn expect(&self, switch: Switch) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = Answer::Awaiting {
+            switch,
+            head: Vec::new(),
+        };
+    }
+
+    /// The answer, once read; the watch is then idle again.
+    fn take(&self) -> Option<bool> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let Answer::Decided(switched) = *state else {
+            return None;
+        };
+        *state = Answer::Idle;
+        Some(switched)
+    }
+
+    fn is_decided(&self) -> bool {
+        matches!(
+            *self.state.lock().unwrap_or_else(PoisonError::into_inner),
+            Answer::Decided(_)
+        )
+    }
+
+    /// Wait until the answer has been read.
+    pub async fn decided(&self) {
+        while !self.is_decided() {
+            self.decided.notified().await;
+        }
+    }
+}
+
+/// Position in a chunked HTTP body.
+enum Chunked {
+    /// Reading a chunk-size line
+    Size(Vec<u8>),
+    /// Inside a chunk, with this many bytes left before its CRLF
+    Data(u64),
+    /// The CRLF closing a chunk, with this many bytes left
+    DataEnd(u8),
+    /// Reading the trailer section after the last chunk
+    Trailer(Vec<u8>),
+}
+
+impl Chunked {
+    /// Consume the body's bytes at the start of `data`. Returns how many
+    /// belong to it and whether it ended there; None when malformed.
+    fn consume(&mut self, data: &[u8]) -> Option<(usize, bool)> {
+        let mut pos = 0;
+        while pos < data.len() {
+            match self {
+                Chunked::Data(left) => {
+                    let n = (*left).min((data.len() - pos) as u64) as usize;
+                    pos += n;
+                    *left -= n as u64;
+                    if *left == 0 {
+                        *self = Chunked::DataEnd(2);
+                    }
+                }
+                Chunked::DataEnd(left) => {
+                    pos += 1;
+                    *left -= 1;
+                    if *left == 0 {
+                        *self = Chunked::Size(Vec::new());
+                    }
+                }
+                Chunked::Trailer(line) => {
+                    let byte = data[pos];
+                    pos += 1;
+                    if let Some(text) = line_byte(line, byte)? {
+                        if text.is_empty() {
+                            return Some((pos, true));
+                        }
+                    }
+                }
+                Chunked::Size(line) => {
+                    let byte = data[pos];
+                    pos += 1;
+                    if let Some(text) = line_byte(line, byte)? {
+                        let size = std::str::from_utf8(&text).ok()?;
+                        let size = size.split(';').next().unwrap_or_default().trim();
+                        *self = match u64::from_str_radix(size, 16).ok()? {
+                            0 => Chunked::Trailer(Vec::new()),
+                            n => Chunked::Data(n),
+                        };
+                    }
+                }
+            }
+        }
+        Some((pos, false))
+    }
+}
+
+/// Add `byte` to a chunk-size or trailer line. Returns the line, without
+/// its CRLF, once complete; None when it runs too long.
+fn line_byte(line: &mut Vec<u8>, byte: u8) -> Option<Option<Vec<u8>>> {
+    if byte != b'\n' {
+        line.push(byte);
+        return (line.len() <= MAX_CHUNK_LINE).then_some(None);
+    }
+    let mut text = std::mem::take(line);
+    if text.last() == Some(&b'\r') {
+        text.pop();
+    }
+    Some(Some(text))
+}
+
+/// Follows a tunnel's client stream, releasing its bytes once every head in
+/// them has been allowed.
+pub struct Inspector {
+    state: State,
+    /// Bytes of the head being held, or of the new protocol until the
+    /// destination has answered
+    pending: Vec<u8>,
+    /// What may switch protocols once the current request's body is sent
+    switch: Option<Switch>,
+    upgrade: Arc<UpgradeWatch>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self::with_upgrade_watch(Arc::default())
+    }
+
+    /// An inspector told of the destination's answers through `upgrade`.
+    pub fn with_upgrade_watch(upgrade: Arc<UpgradeWatch>) -> Self {
+        Self {
+            state: State::Head { first: true },
+            pending: Vec::new(),
+            switch: None,
+            upgrade,
+        }
+    }
+
+    /// The watch to wait on while client bytes are held for an answer.
+    pub fn held_for_upgrade(&self) -> Option<Arc<UpgradeWatch>> {
+        (matches!(self.state, State::Upgrade) && !self.pending.is_empty())
+            .then(|| Arc::clone(&self.upgrade))
+    }
+
+    /// Whether as much is held for an answer as a head may take; the client
+    /// is not read further until the answer.
+    pub fn hold_is_full(&self) -> bool {
+        matches!(self.state, State::Upgrade) && self.pending.len() >= MAX_HEAD_LEN
+    }
+
+    /// What follows a request once its body is sent.
+    fn request_done(&mut self) -> State {
+        match self.switch.take() {
+            Some(_) => State::Upgrade,
+            None => State::Head { first: false },
+        }
+    }
+
+    /// Take the client's next bytes. `allow` judges each complete head:
+    /// its bytes, classification, and whether it opens the stream. Returns
+    /// the bytes cleared for the destination (possibly none yet), or None
+    /// once a head is refused: the bytes of the same read cleared before it
+    /// are dropped with it. An HTTP body that cannot be followed is judged
+    /// as a nameless, unknown head. Empty `data` releases bytes held for an
+    /// upgrade once the destination has answered.
+    pub fn feed(
+        &mut self,
+        data: &[u8],
+        allow: &mut dyn FnMut(&[u8], &Classification, bool) -> bool,
+    ) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut input = Cow::Borrowed(data);
+        if matches!(self.state, State::Upgrade) {
+            match self.upgrade.take() {
+                None => {
+                    self.pending.extend_from_slice(data);
+                    return Some(out);
+                }
+                Some(switched) => {
+                    self.state = match switched {
+                        true => State::Opaque,
+                        false => State::Head { first: false },
+                    };
+                    let mut held = std::mem::take(&mut self.pending);
+                    held.extend_from_slice(data);
+                    input = Cow::Owned(held);
+                }
+            }
+        }
+        let mut pos = 0;
+        while pos < input.len() {
+            let rest = &input[pos..];
+            match &mut self.state {
+                State::Opaque => {
+                    out.extend_from_slice(rest);
+                    pos = input.len();
+                }
+                State::Upgrade => {
+                    self.pending.extend_from_slice(rest);
+                    pos = input.len();
+                }
+                State::Body(left) => {
+                    let n = (*left).min(rest.len() as u64) as usize;
+                    out.extend_from_slice(&rest[..n]);
+                    pos += n;
+                    *left -= n as u64;
+                    if *left == 0 {
+                        self.state = self.request_done();
+                    }
+                }
+                State::Chunked(body) => match body.consume(rest) {
+                    Some((n, done)) => {
+                        out.extend_from_slice(&rest[..n]);
+                        pos += n;
+                        if done {
+                            self.state = self.request_done();
+                        }
+                    }
+                    None => {
+                        if !allow(rest, &UNFOLLOWED, false) {
+                            return None;
+                        }
+                        self.state = State::Opaque;
+                    }
+                },
+                State::Head { first } => {
+                    let first = *first;
+                    self.pending.extend_from_slice(rest);
+                    let end = match head_len(&self.pending) {
+                        Some(0) => self.pending.len(),
+                        Some(end) => end,
+                        None if self.pending.len() >= MAX_HEAD_LEN => self.pending.len(),
+                        None => return Some(out),
+                    };
+                    let mut held = std::mem::take(&mut self.pending);
+                    let after = held.split_off(end);
+                    let traffic = classify(&held);
+                    if !allow(&held, &traffic, first) {
+                        return None;
+                    }
+                    self.state = match traffic.protocol {
+                        AppProtocol::Http => match request_framing(&held) {
+                            Some(framing) => {
+                                if let Some(switch) = framing.switch {
+                                    self.upgrade.expect(switch);
+                                    self.switch = Some(switch);
+                                }
+                                match framing.body {
+                                    State::Head { .. } => self.request_done(),
+                                    body => body,
+                                }
+                            }
+                            // Where the request ends is anyone's guess
+                            None if allow(&held, &UNFOLLOWED, false) => State::Opaque,
+                            None => return None,
+                        },
+                        _ => State::Opaque,
+                    };
+                    out.extend_from_slice(&held);
+                    input = Cow::Owned(after);
+                    pos = 0;
+                }
+            }
+        }
+        Some(out)
+    }
+
+    /// Judge a head still held when the client stops sending, as it
+    /// stands. Returns its bytes, or None when refused.
+    pub fn finish(
+        &mut self,
+        allow: &mut dyn FnMut(&[u8], &Classification, bool) -> bool,
+    ) -> Option<Vec<u8>> {
+        // Unless the destination switched, the held bytes are more requests
+        if matches!(self.state, State::Upgrade) {
+            self.state = match self.upgrade.take() {
+                Some(true) => State::Opaque,
+                _ => State::Head { first: false },
+            };
+            let held = std::mem::take(&mut self.pending);
+            let mut out = self.feed(&held, allow)?;
+            out.extend(self.finish(allow)?);
+            return Some(out);
+        }
+        let held = std::mem::take(&mut self.pending);
+        let State::Head { first } = self.state else {
+            return Some(held);
+        };
+        if held.is_empty() {
+            return Some(held);
+        }
+        self.state = State::Opaque;
+        allow(&held, &classify(&held), first).then_some(held)
+    }
+}
+
+/// How a traffic that cannot be followed is judged: a nameless head.
+const UNFOLLOWED: Classification = Classification {
+    protocol: AppProtocol::Unknown,
+    server_name: None,
+    first_line: None,
+};
+
+/// How the request opened by an HTTP `head` is framed. None when a
+/// destination could read it differently: a malformed, repeated or
+/// list-valued `Content-Length`, `Transfer-Encoding` with `Content-Length`
+/// or not ending in `chunked`, a folded line or a space in a header name.
+fn request_framing(head: &[u8]) -> Option<Framing> {
+    let mut lines = head.split(|&b| b == b'\n');
+    let request_line = lines.next().unwrap_or_default();
+    let connect = request_line.starts_with(b"CONNECT ");
+    let mut length = None;
+    let mut transfer_encoding: Option<String> = None;
+    let mut upgrade = false;
+    for line in lines {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            return None;
+        }
+        let colon = line.iter().position(|&b| b == b':')?;
+        let (name, value) = line.split_at(colon);
+        if name.is_empty() || name.iter().any(u8::is_ascii_whitespace) {
+            return None;
+        }
+        let value = std::str::from_utf8(&value[1..]).ok()?.trim();
+        if name.eq_ignore_ascii_case(b"upgrade") {
+            upgrade = true;
+        } else if name.eq_ignore_ascii_case(b"transfer-encoding") {
+            let codings = transfer_encoding.get_or_insert_with(String::new);
+            codings.push(',');
+            codings.push_str(&value.to_ascii_lowercase());
+        } else if name.eq_ignore_ascii_case(b"content-length") {
+            if length.is_some() || value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            length = Some(value.parse::<u64>().ok()?);
+        }
+    }
+    let body = match (transfer_encoding, length) {
+        (Some(_), Some(_)) => return None,
+        (Some(codings), None) => {
+            let last = codings.rsplit(',').map(str::trim).find(|c| !c.is_empty());
+            if last != Some("chunked") {
+                return None;
+            }
+            State::Chunked(Chunked::Size(Vec::new()))
+        }
+        (None, Some(n)) if n > 0 => State::Body(n),
+        (None, _) => State::Head { first: false },
+    };
+    let switch = if connect {
+        Some(Switch::Connect)
+    } else {
+        upgrade.then_some(Switch::Upgrade)
+    };
+    Some(Framing { body, switch })
+}
@@ -1,5 +1,10 @@
+use ipnet::IpNet;
 use std::net::IpAddr;
 
+/// `reason` of the `acl.deny` audit event raised when ip_guard blocks a
+/// destination.
+pub const ACL_DENY_REASON: &str = "ip_guard";
+
 /// Classify a dangerous IP address by its range name.
 /// Returns `Some("range-name")` if the IP is private/reserved/dangerous, `None` if public.
 pub fn classify_dangerous_ip(ip: &IpAddr) -> Option<&'static str> {
@@ -74,6 +79,18 @@ pub fn is_dangerous_ip(ip: &IpAddr) -> bool {
     classify_dangerous_ip(ip).is_some()
 }
 
+/// Like [`classify_dangerous_ip`], but addresses inside one of `exemptions`
+/// (per-user/group `ip_guard_exemptions`) are treated as safe.
+pub fn classify_guarded_ip(ip: &IpAddr, exemptions: &[IpNet]) -> Option<&'static str> {
+    let range = classify_dangerous_ip(ip)?;
+    let canonical = ip.to_canonical();
+    if exemptions.iter().any(|net| net.contains(&canonical)) {
+        None
+    } else {
+        Some(range)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -260,4 +277,25 @@ mod tests {
         // 2002:0808:0808:: embeds 8.8.8.8 (public - allowed)
         assert!(!is_dangerous_ip(&"2002:0808:0808::1".parse().unwrap()));
     }
+
+    #[test]
+    fn test_exemption_allows_private_range() {
+        let exemptions: Vec<IpNet> = vec!["10.1.0.0/16".parse().unwrap()];
+        assert_eq!(
+            classify_guarded_ip(&"10.1.2.3".parse().unwrap(), &exemptions),
+            None
+        );
+        assert_eq!(
+            classify_guarded_ip(&"::ffff:10.1.2.3".parse().unwrap(), &exemptions),
+            None
+        );
+        assert_eq!(
+            classify_guarded_ip(&"10.2.0.1".parse().unwrap(), &exemptions),
+            Some("private-10")
+        );
+        assert_eq!(
+            classify_guarded_ip(&"127.0.0.1".parse().unwrap(), &exemptions),
+            Some("loopback")
+        );
+    }
 }
@@ -0,0 +1,56 @@
+//! SSH jump host policy (`ssh -J` / ProxyJump).
+//!
+//! A `direct-tcpip` channel to one of `security.jump_ports` is an SSH hop.
+//! When the user has `jump_targets`, the hop may only reach those targets
+//! (the user's ACL deny rules still apply), and a permitted target is reachable
+//! even in private/reserved ranges: internal hosts are what a bastion is for.
+//! Without `jump_targets`, hops follow the regular ACL and ip_guard.
+
+use crate::auth::user::User;
+use crate::config::acl::{AclPolicy, AclRule, ParsedAcl};
+use ipnet::IpNet;
+
+/// Effective policy for one SSH hop.
+#[derive(Debug, Clone)]
+pub struct JumpPolicy {
+    /// Allows the user's jump targets only (user deny rules first)
+    pub acl: ParsedAcl,
+    /// The user's exemptions plus the ranges the jump targets cover
+    pub ip_guard_exemptions: Vec<IpNet>,
+}
+
+impl JumpPolicy {
+    /// Policy for a hop from `user` to `host:port`, or `None` when the user
+    /// has no `jump_targets` (the regular ACL applies).
+    pub fn for_target(user: &User, host: &str, port: u16) -> Option<Self> {
+        let targets = user.jump_targets.as_ref()?;
+        let mut ip_guard_exemptions = user.ip_guard_exemptions.clone();
+        for rule in targets {
+            match rule {
+                AclRule::Cidr { network, port: pm } if pm.matches(port) => {
+                    ip_guard_exemptions.push(*network);
+                }
+                // A named target may resolve anywhere; the name is what was allowed
+                AclRule::HostPattern { .. } if rule.matches(host, port, None) => {
+                    ip_guard_exemptions.extend(all_addresses());
+                }
+                _ => {}
+            }
+        }
+        Some(Self {
+            acl: ParsedAcl {
+                default_policy: AclPolicy::Deny,
+                allow_rules: targets.clone(),
+                deny_rules: user.acl.deny_rules.clone(),
+            },
+            ip_guard_exemptions,
+        })
+    }
+}
+
+fn all_addresses() -> [IpNet; 2] {
+    [
+        IpNet::V4(ipnet::Ipv4Net::default()),
+        IpNet::V6(ipnet::Ipv6Net::default()),
+    ]
+}
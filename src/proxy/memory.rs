@@ -0,0 +1,96 @@
+//! Per-session memory accounting (`limits.max_buffered_bytes`).
+//!
+//! Every live session carries a [`SessionMemory`] that counts the bytes it
+//! holds: relay chunks read from one side and not yet written to the other
+//! (a write to a slow SSH client holds its chunk until the window opens) and
+//! packets queued for a running traffic capture. Each holder takes a
+//! [`MemoryCharge`] that gives the bytes back on drop. When a relay chunk
+//! takes a session past its budget the relay ends it with
+//! [`CloseReason::MemoryLimit`](super::close::CloseReason::MemoryLimit).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Bytes held by all sessions together, for `s5_session_buffered_bytes`.
+static TOTAL_BUFFERED: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes buffered by all live sessions.
+pub fn total_buffered() -> u64 {
+    TOTAL_BUFFERED.load(Ordering::Relaxed)
+}
+
+/// Bytes one session holds in memory, against its budget.
+#[derive(Debug, Default)]
+pub struct SessionMemory {
+    /// 0 = unlimited
+    budget: u64,
+    buffered: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl SessionMemory {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            budget,
+            ..Self::default()
+        }
+    }
+
+    /// Account `bytes` held until the returned charge is dropped.
+    pub fn charge(self: &Arc<Self>, bytes: usize) -> MemoryCharge {
+        let bytes = bytes as u64;
+        let buffered = self.buffered.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(buffered, Ordering::Relaxed);
+        TOTAL_BUFFERED.fetch_add(bytes, Ordering::Relaxed);
+        MemoryCharge {
+            memory: self.clone(),
+            bytes,
+            buffered,
+        }
+    }
+
+    /// `limits.max_buffered_bytes` for this session (0 = unlimited).
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Bytes held now.
+    pub fn buffered(&self) -> u64 {
+        self.buffered.load(Ordering::Relaxed)
+    }
+
+    /// Most bytes held at once.
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes accounted to a session; given back on drop.
+#[derive(Debug)]
+pub struct MemoryCharge {
+    memory: Arc<SessionMemory>,
+    bytes: u64,
+    /// Session total right after this charge
+    buffered: u64,
+}
+
+impl MemoryCharge {
+    /// Session total right after this charge was taken.
+    pub fn buffered(&self) -> u64 {
+        self.buffered
+    }
+
+    /// Whether this charge took the session past its budget.
+    pub fn over_budget(&self) -> bool {
+        self.memory.budget > 0 && self.buffered > self.memory.budget
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        self.memory
+            .buffered
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+        TOTAL_BUFFERED.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
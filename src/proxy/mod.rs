@@ -1,34 +1,63 @@
 pub mod acl;
+pub mod buffer_pool;
+pub mod capture;
+pub mod channel;
+pub mod circuit;
+pub mod classify;
+pub mod client_caps;
+pub mod close;
 pub mod connector;
 pub mod dns_cache;
 pub mod forwarder;
+pub mod geo_map;
+pub mod inspect;
 pub mod ip_guard;
+pub mod jump;
+pub mod memory;
+#[cfg(feature = "wasm-plugins")]
+pub mod plugin;
+pub mod policy;
 pub mod pool;
+pub mod priority;
 pub mod retry;
+pub mod streamlocal;
+pub mod throughput;
+pub mod transfer;
+pub mod tun;
 
+use crate::audit::events::AuditEvent;
 use crate::audit::AuditLogger;
 use crate::auth::user::User;
 use crate::config::acl::ParsedAcl;
 use crate::config::types::{AppConfig, ParsedUpstreamProxy, QuotaConfig};
 use crate::metrics::MetricsRegistry;
 use crate::quota::QuotaTracker;
+use crate::ssh::handshake::{HandshakeInfo, NegotiatedAlgorithms};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use serde::Serialize;
-use std::net::SocketAddr;
+use ipnet::IpNet;
+use retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{
     AtomicU32, AtomicU64,
     Ordering::{self, AcqRel, Acquire},
 };
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{info, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Minimum spacing between two transfer rate samples of a session.
+const RATE_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Serializable snapshot of an active session (for API responses).
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionSnapshot {
     pub session_id: String,
+    /// Connection correlation ID shared with logs, audit events and flows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
     pub username: String,
     pub target_host: String,
     pub target_port: u16,
@@ -42,6 +71,8 @@ pub struct SessionSnapshot {
 /// Live session state tracked at runtime with atomic byte counters.
 pub struct LiveSession {
     pub session_id: String,
+    /// Connection correlation ID shared with logs, audit events and flows
+    pub correlation_id: Option<String>,
     pub username: String,
     pub target_host: String,
     pub target_port: u16,
@@ -50,6 +81,22 @@ pub struct LiveSession {
     pub bytes_up: AtomicU64,
     pub bytes_down: AtomicU64,
     pub protocol: String,
+    /// Stops the relay (admin kill, ban)
+    pub kill: close::KillSwitch,
+    /// Traffic capture started by an admin (`[logging.capture]`)
+    pub capture: capture::CaptureSlot,
+    /// Bytes buffered for this session (`limits.max_buffered_bytes`)
+    pub memory: Arc<memory::SessionMemory>,
+    /// Set from the first bytes the client sends
+    pub traffic: std::sync::OnceLock<classify::Classification>,
+    /// Byte cap and alert thresholds (`limits.max_connection_bytes`, ...)
+    pub transfer: transfer::TransferLimits,
+    /// Class and write scheduling (`[proxy.priority]`), set once the
+    /// destination is connected
+    pub priority: std::sync::OnceLock<priority::Flow>,
+    /// Longest first line of the stream kept for its flow record, when the
+    /// user's group samples it (`logging.flows.payload_metadata`)
+    pub first_line_len: std::sync::OnceLock<usize>,
 }
 
 impl LiveSession {
@@ -57,6 +104,7 @@ impl LiveSession {
     pub fn snapshot(&self) -> SessionSnapshot {
         SessionSnapshot {
             session_id: self.session_id.clone(),
+            correlation_id: self.correlation_id.clone(),
             username: self.username.clone(),
             target_host: self.target_host.clone(),
             target_port: self.target_port,
@@ -69,15 +117,74 @@ impl LiveSession {
     }
 }
 
+/// Serializable view of an SSH connection (for the session detail API).
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionSnapshot {
+    pub correlation_id: String,
+    pub source_ip: String,
+    pub started_at: DateTime<Utc>,
+    pub client_version: Option<String>,
+    pub hassh: Option<String>,
+    pub algorithms: Option<NegotiatedAlgorithms>,
+    pub auth_method: Option<String>,
+    /// Session (shell/exec) channels plus port-forwarding channels
+    pub open_channels: u32,
+    /// Shell, exec and subsystem channels
+    pub session_channels: u32,
+    /// Port-forwarding channels with a live session
+    pub forwarding_channels: u32,
+}
+
+/// Live state of an SSH connection, shared by the accept loop (handshake),
+/// the SSH handler (auth, channels) and the API.
+pub struct LiveConnection {
+    pub correlation_id: String,
+    pub source_ip: String,
+    pub started_at: DateTime<Utc>,
+    pub handshake: Arc<std::sync::Mutex<HandshakeInfo>>,
+    pub auth_method: std::sync::Mutex<Option<String>>,
+    /// Open session (shell/exec) channels
+    pub session_channels: AtomicU32,
+}
+
+impl LiveConnection {
+    pub fn set_auth_method(&self, method: &str) {
+        *self.auth_method.lock().unwrap_or_else(|e| e.into_inner()) = Some(method.to_string());
+    }
+
+    /// HASSH of the client, once its KEXINIT has been seen.
+    pub fn hassh(&self) -> Option<String> {
+        self.handshake
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .hassh
+            .clone()
+    }
+}
+
+/// Last byte counts seen for a session, used to derive its transfer rate.
+struct RateSample {
+    at: Instant,
+    bytes_up: u64,
+    bytes_down: u64,
+    up_per_sec: f64,
+    down_per_sec: f64,
+}
+
 /// RAII guard for connection counting
 pub struct ConnectionGuard {
     global_counter: Arc<AtomicU32>,
     user_counters: Arc<DashMap<String, AtomicU32>>,
     username: String,
+    /// Metrics registry and the user label the connection was counted under
+    metrics: Option<(Arc<MetricsRegistry>, String)>,
 }
 
 impl Drop for ConnectionGuard {
     fn drop(&mut self) {
+        if let Some((metrics, label)) = self.metrics.take() {
+            metrics.record_user_connection_close(&label);
+        }
         self.global_counter.fetch_sub(1, Ordering::Relaxed);
         if let Some(counter) = self.user_counters.get(&self.username) {
             let prev = counter.fetch_sub(1, Ordering::AcqRel);
@@ -104,8 +211,16 @@ pub struct SshRelayRequest<'a> {
     pub channel: russh::Channel<russh::server::Msg>,
     /// Parsed ACL rules for this user.
     pub user_acl: &'a ParsedAcl,
+    /// Private/reserved ranges this user may reach despite ip_guard.
+    pub ip_guard_exemptions: &'a [IpNet],
+    /// Listener override of `security.ip_guard_enabled` (`[[server.listeners]]`).
+    pub ip_guard_enabled: Option<bool>,
+    /// Group override of `security.blocked_ports`.
+    pub blocked_ports: Option<&'a [u16]>,
     /// Source IP address of the client.
     pub source_ip: &'a str,
+    /// Correlation ID of the SSH connection carrying this channel.
+    pub correlation_id: &'a str,
     /// Per-connection bandwidth limit in kbps (0 = unlimited).
     pub bandwidth_limit_kbps: u64,
     /// Maximum concurrent connections for this user (0 = unlimited).
@@ -118,6 +233,72 @@ pub struct SshRelayRequest<'a> {
     pub quotas: Option<QuotaConfig>,
     /// Optional upstream SOCKS5 proxy for chaining.
     pub upstream_proxy: Option<ParsedUpstreamProxy>,
+    /// User tags, for `[proxy.priority]` classes and `[policy]` rules.
+    pub tags: &'a [String],
+    /// Group of the user, for `[policy]` rules.
+    pub group: Option<&'a str>,
+    /// Group override of `logging.flows.payload_metadata`.
+    pub payload_metadata: Option<bool>,
+    /// Retries of refused or timed-out connects to the target.
+    pub connect_retry: RetryPolicy,
+    /// SSH hop to a jump port (`ssh -J`): logged as an `ssh.jump` audit event.
+    pub jump: bool,
+}
+
+/// Parameters for relaying an SSH `direct-streamlocal` channel to a Unix socket.
+pub struct UnixRelayRequest<'a> {
+    /// Username of the connected user.
+    pub username: &'a str,
+    /// Socket path, already checked against the user's `unix_sockets`.
+    pub socket_path: &'a str,
+    /// SSH channel to relay through.
+    pub channel: russh::Channel<russh::server::Msg>,
+    /// Source IP address of the client.
+    pub source_ip: &'a str,
+    /// Correlation ID of the SSH connection carrying this channel.
+    pub correlation_id: &'a str,
+    /// Per-connection bandwidth limit in kbps (0 = unlimited).
+    pub bandwidth_limit_kbps: u64,
+    /// Maximum concurrent connections for this user (0 = unlimited).
+    pub max_per_user: u32,
+    /// Aggregate bandwidth limit across all connections in kbps (0 = unlimited).
+    pub aggregate_bandwidth_kbps: u64,
+    /// Optional quota tracker for bandwidth/connection accounting.
+    pub quota_tracker: Option<Arc<QuotaTracker>>,
+    /// Optional per-user quota configuration.
+    pub quotas: Option<QuotaConfig>,
+    /// User tags, for `[proxy.priority]` classes.
+    pub tags: &'a [String],
+}
+
+/// Parameters for relaying an SSH `tun@openssh.com` channel to its TUN
+/// interface.
+#[cfg(target_os = "linux")]
+pub struct TunRelayRequest<'a> {
+    /// Username of the connected user.
+    pub username: &'a str,
+    /// SSH channel to relay through.
+    pub channel: russh::Channel<russh::server::Msg>,
+    /// Interface created for this channel.
+    pub device: tun::Device,
+    /// Filter of the packets the client sends.
+    pub filter: tun::PacketFilter,
+    /// Source IP address of the client.
+    pub source_ip: &'a str,
+    /// Correlation ID of the SSH connection carrying this channel.
+    pub correlation_id: &'a str,
+    /// Per-connection bandwidth limit in kbps (0 = unlimited).
+    pub bandwidth_limit_kbps: u64,
+    /// Maximum concurrent connections for this user (0 = unlimited).
+    pub max_per_user: u32,
+    /// Aggregate bandwidth limit across all connections in kbps (0 = unlimited).
+    pub aggregate_bandwidth_kbps: u64,
+    /// Optional quota tracker for bandwidth/connection accounting.
+    pub quota_tracker: Option<Arc<QuotaTracker>>,
+    /// Optional per-user quota configuration.
+    pub quotas: Option<QuotaConfig>,
+    /// User tags, for `[proxy.priority]` classes.
+    pub tags: &'a [String],
 }
 
 /// Shared proxy engine - used by both SSH direct-tcpip and SOCKS5
@@ -128,16 +309,82 @@ pub struct ProxyEngine {
     global_connections: Arc<AtomicU32>,
     user_connections: Arc<DashMap<String, AtomicU32>>,
     dns_cache: dns_cache::DnsCache,
+    circuit_breaker: circuit::CircuitBreaker,
+    client_caps: Arc<client_caps::ClientCaps>,
     active_sessions: DashMap<String, Arc<LiveSession>>,
     session_counter: AtomicU64,
+    rate_samples: DashMap<String, RateSample>,
+    connections: DashMap<String, Arc<LiveConnection>>,
+    throughput: throughput::ThroughputHistory,
+    history: close::SessionHistory,
+    usage: Arc<crate::reports::UsageHistory>,
+    /// City database for the dashboard map (startup-only)
+    geoip: Option<crate::geoip::GeoIpService>,
+    /// Write scheduling by class (None = no `[proxy.priority]` classes, startup-only)
+    priority: Option<Arc<priority::Scheduler>>,
+    /// Tag rules checked on each channel open (None = no `[policy]` rules, startup-only)
+    policy: Option<policy::Policy>,
+    /// WASM module deciding the channels no rule matches (None = no `[policy.plugin]`, startup-only)
+    #[cfg(feature = "wasm-plugins")]
+    plugin: Option<Arc<plugin::Plugin>>,
+    /// Lua hooks (None = no `[scripting]`, startup-only)
+    #[cfg(feature = "lua-hooks")]
+    hooks: Option<Arc<crate::scripting::Hooks>>,
+    /// Tunnel addresses (None = `[vpn]` disabled, startup-only)
+    vpn_pool: Option<tun::AddressPool>,
 }
 
 impl ProxyEngine {
     pub fn new(config: Arc<AppConfig>, audit: Arc<AuditLogger>) -> Self {
-        let dns_cache = dns_cache::DnsCache::new(
-            config.server.dns_cache_ttl,
-            config.server.dns_cache_max_entries,
-        );
+        let dns_cache = dns_cache::DnsCache::from_config(&config);
+        let circuit_breaker = circuit::CircuitBreaker::new(config.proxy.circuit_breaker.clone());
+        let client_caps = Arc::new(client_caps::ClientCaps::from_config(&config));
+        let usage = Arc::new(crate::reports::UsageHistory::new(&config.reports));
+        // Lookup only: the country allow/deny lists are not applied here
+        let geoip = config
+            .geoip
+            .database_path
+            .as_deref()
+            .filter(|_| config.geoip.enabled)
+            .map(|path| {
+                crate::geoip::GeoIpService::new(true, Some(path), Vec::new(), Vec::new(), false)
+            })
+            .filter(crate::geoip::GeoIpService::has_database);
+        // The classes were checked when the config was validated
+        let priority = priority::Scheduler::from_config(&config.proxy.priority).unwrap_or_default();
+        let policy = policy::Policy::from_config(&config.policy).unwrap_or_default();
+        // Loaded once already when the config was validated
+        #[cfg(feature = "wasm-plugins")]
+        let plugin = config
+            .policy
+            .plugin
+            .as_ref()
+            .and_then(|p| match plugin::Plugin::load(p) {
+                Ok(plugin) => Some(Arc::new(plugin)),
+                Err(e) => {
+                    tracing::error!(path = %p.path.display(), error = %e, "Policy plugin not loaded");
+                    None
+                }
+            });
+        // Loaded once already when the config was validated
+        #[cfg(feature = "lua-hooks")]
+        let hooks =
+            config
+                .scripting
+                .as_ref()
+                .and_then(|s| match crate::scripting::Hooks::load(s) {
+                    Ok(hooks) => Some(Arc::new(hooks)),
+                    Err(e) => {
+                        tracing::error!(path = %s.path.display(), error = %e, "Scripting hooks not loaded");
+                        None
+                    }
+                });
+        let vpn_pool = config.vpn.enabled.then(|| {
+            tun::AddressPool::new(
+                config.vpn.pool,
+                config.users.iter().filter_map(|u| u.vpn_address),
+            )
+        });
         Self {
             config,
             audit,
@@ -145,16 +392,94 @@ impl ProxyEngine {
             global_connections: Arc::new(AtomicU32::new(0)),
             user_connections: Arc::new(DashMap::new()),
             dns_cache,
+            circuit_breaker,
+            client_caps,
             active_sessions: DashMap::new(),
             session_counter: AtomicU64::new(0),
+            rate_samples: DashMap::new(),
+            connections: DashMap::new(),
+            throughput: throughput::ThroughputHistory::default(),
+            history: close::SessionHistory::default(),
+            usage,
+            geoip,
+            priority,
+            policy,
+            #[cfg(feature = "wasm-plugins")]
+            plugin,
+            #[cfg(feature = "lua-hooks")]
+            hooks,
+            vpn_pool,
         }
     }
 
+    /// The `[vpn]` tunnel addresses, if enabled.
+    pub fn vpn_pool(&self) -> Option<&tun::AddressPool> {
+        self.vpn_pool.as_ref()
+    }
+
+    /// The `[scripting]` hooks, if any.
+    #[cfg(feature = "lua-hooks")]
+    pub fn hooks(&self) -> Option<&Arc<crate::scripting::Hooks>> {
+        self.hooks.as_ref()
+    }
+
+    /// Run the `on_session_close` hook on the `proxy.complete` event of a
+    /// finished tunnel and add the labels it returns. A failing hook adds
+    /// none.
+    #[cfg(feature = "lua-hooks")]
+    pub async fn label_session_close(&self, event: AuditEvent) -> AuditEvent {
+        let Some(hooks) = &self.hooks else {
+            return event;
+        };
+        match hooks
+            .run(crate::scripting::Hook::SessionClose, &event)
+            .await
+        {
+            Ok(crate::scripting::Outcome::Continue(labels)) => event.with_labels(labels),
+            Ok(crate::scripting::Outcome::Refuse(_)) => event,
+            Err(e) => {
+                warn!(error = %e, "on_session_close hook failed");
+                event
+            }
+        }
+    }
+
+    /// Without the `lua-hooks` feature the event is left as it is.
+    #[cfg(not(feature = "lua-hooks"))]
+    pub async fn label_session_close(&self, event: AuditEvent) -> AuditEvent {
+        event
+    }
+
+    /// Refresh hot DNS cache entries about to expire (`server.dns_prefetch_hosts`).
+    /// Returns the number of entries refreshed.
+    pub async fn prefetch_dns(&self) -> usize {
+        let top_n = self.config.server.dns_prefetch_hosts as usize;
+        if top_n == 0 || !self.dns_cache.is_enabled() {
+            return 0;
+        }
+        connector::prefetch_hot(
+            &self.dns_cache,
+            top_n,
+            self.config.limits.connection_timeout,
+            self.config.security.ip_guard_enabled,
+            self.metrics.as_deref(),
+        )
+        .await
+    }
+
     /// Set the metrics registry reference for lifetime connection counting.
     pub fn set_metrics(&mut self, metrics: Arc<MetricsRegistry>) {
+        self.dns_cache.set_metrics(metrics.clone());
+        self.client_caps =
+            Arc::new(client_caps::ClientCaps::from_config(&self.config).with_metrics(&metrics));
         self.metrics = Some(metrics);
     }
 
+    /// Client connection caps (`server.max_connections*`).
+    pub fn client_caps(&self) -> &Arc<client_caps::ClientCaps> {
+        &self.client_caps
+    }
+
     /// Try to acquire a connection slot. Returns a guard that auto-decrements on drop.
     /// `max_per_user` overrides the global `limits.max_connections_per_user` for this user.
     /// A value of 0 means unlimited (no per-user cap).
@@ -198,10 +523,16 @@ impl ProxyEngine {
             user_entry.fetch_add(1, Ordering::AcqRel);
         }
 
+        let metrics = self.metrics.as_ref().map(|m| {
+            let label = m.record_user_connection_open(username);
+            (m.clone(), label)
+        });
+
         Ok(ConnectionGuard {
             global_counter: self.global_connections.clone(),
             user_counters: self.user_connections.clone(),
             username: username.to_string(),
+            metrics,
         })
     }
 
@@ -241,6 +572,8 @@ impl ProxyEngine {
     /// When `upstream_proxy` is `Some`, the connection is established through the
     /// upstream SOCKS5 proxy. In that case the ACL post-check (CIDR by resolved IP)
     /// is skipped because DNS resolution happens on the upstream proxy side.
+    ///
+    /// The total time is recorded in `s5_channel_setup_duration_seconds`.
     #[allow(clippy::too_many_arguments)]
     async fn connect_checked(
         &self,
@@ -248,24 +581,79 @@ impl ProxyEngine {
         host: &str,
         port: u16,
         user_acl: &ParsedAcl,
+        ip_guard_exemptions: &[IpNet],
+        ip_guard_enabled: Option<bool>,
+        blocked_ports: Option<&[u16]>,
+        group: Option<&str>,
+        tags: &[String],
         source_ip: &str,
         max_per_user: u32,
         upstream_proxy: Option<&ParsedUpstreamProxy>,
+        connect_retry: RetryPolicy,
+        correlation_id: &str,
     ) -> Result<(tokio::net::TcpStream, SocketAddr, ConnectionGuard)> {
-        // Pre-check ACL with hostname only (before connect, prevents port scanning)
-        let pre_decision = acl::pre_check_hostname_and_log(user_acl, username, host, port);
-        if !pre_decision.allowed {
-            self.audit.log_acl_deny(
+        let start = std::time::Instant::now();
+        let result = self
+            .connect_checked_inner(
                 username,
                 host,
                 port,
-                None,
+                user_acl,
+                ip_guard_exemptions,
+                ip_guard_enabled,
+                blocked_ports,
+                group,
+                tags,
                 source_ip,
-                pre_decision.matched_rule,
-                "hostname pre-check",
-            );
-            anyhow::bail!("ACL denied: {}:{}", host, port);
+                max_per_user,
+                upstream_proxy,
+                connect_retry,
+                correlation_id,
+            )
+            .await;
+        if let Some(ref metrics) = self.metrics {
+            let outcome = match &result {
+                Ok(_) => crate::metrics::outcomes::SUCCESS,
+                Err(e) => crate::metrics::outcomes::from_error(e),
+            };
+            metrics.record_channel_setup(outcome, start.elapsed().as_secs_f64());
         }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn connect_checked_inner(
+        &self,
+        username: &str,
+        host: &str,
+        port: u16,
+        user_acl: &ParsedAcl,
+        ip_guard_exemptions: &[IpNet],
+        ip_guard_enabled: Option<bool>,
+        blocked_ports: Option<&[u16]>,
+        group: Option<&str>,
+        tags: &[String],
+        source_ip: &str,
+        max_per_user: u32,
+        upstream_proxy: Option<&ParsedUpstreamProxy>,
+        connect_retry: RetryPolicy,
+        correlation_id: &str,
+    ) -> Result<(tokio::net::TcpStream, SocketAddr, ConnectionGuard)> {
+        let subject = policy::Subject {
+            username,
+            group,
+            tags,
+        };
+        self.check_destination(
+            &subject,
+            host,
+            port,
+            user_acl,
+            blocked_ports,
+            source_ip,
+            correlation_id,
+        )
+        .await?;
 
         // Acquire connection slot (RAII)
         let guard = self.acquire_connection(username, max_per_user)?;
@@ -273,7 +661,9 @@ impl ProxyEngine {
         if let Some(proxy) = upstream_proxy {
             // Connect via upstream SOCKS5 proxy — DNS resolution delegated to proxy
             let timeout = Duration::from_secs(self.config.limits.connection_timeout);
-            let tcp_stream = connector::connect_via_socks5(proxy, host, port, timeout).await?;
+            let tcp_stream =
+                connector::connect_via_socks5(proxy, host, port, timeout, &self.config.proxy.tcp)
+                    .await?;
 
             // Use sentinel address for logs/metrics (real IP unknown when proxied)
             let sentinel_addr: SocketAddr = ([0, 0, 0, 0], 0).into();
@@ -289,68 +679,438 @@ impl ProxyEngine {
             Ok((tcp_stream, sentinel_addr, guard))
         } else {
             // Direct connection (existing path)
-            let ip_guard_enabled = self.config.security.ip_guard_enabled;
-            let (tcp_stream, resolved_addr) = connector::connect_with_cache(
+            let ip_guard_enabled =
+                ip_guard_enabled.unwrap_or(self.config.security.ip_guard_enabled);
+            let circuit_key = format!("{}:{}", host.to_ascii_lowercase(), port);
+            if let Err(retry_in) = self.circuit_breaker.check(&circuit_key) {
+                if let Some(ref m) = self.metrics {
+                    m.circuit_breaker_rejected_total.inc();
+                }
+                anyhow::bail!(
+                    "circuit open for {}: target failing, next attempt in {}s",
+                    circuit_key,
+                    retry_in.as_secs().max(1)
+                );
+            }
+            let result = connector::connect_with_cache(
                 host,
                 port,
                 self.config.limits.connection_timeout,
                 ip_guard_enabled,
+                ip_guard_exemptions,
+                self.config.proxy.address_family,
+                &self.config.proxy.tcp,
+                connect_retry,
                 &self.dns_cache,
                 self.metrics.as_deref(),
             )
-            .await?;
-
-            // Post-check ACL with resolved IP (for CIDR rules)
-            let post_decision =
-                acl::check_and_log(user_acl, username, host, port, Some(resolved_addr.ip()));
-            if !post_decision.allowed {
-                self.audit.log_acl_deny(
+            .await;
+            match &result {
+                Ok(_) => self.circuit_breaker.record_success(&circuit_key),
+                Err(e) if circuit::is_target_failure(e) => {
+                    if self.circuit_breaker.record_failure(&circuit_key) {
+                        warn!(
+                            target = %circuit_key,
+                            open_secs = self.config.proxy.circuit_breaker.open_secs,
+                            "Circuit opened for failing destination"
+                        );
+                        if let Some(ref m) = self.metrics {
+                            m.circuit_breaker_opened_total.inc();
+                        }
+                    }
+                }
+                Err(_) => self.circuit_breaker.release(&circuit_key),
+            }
+            if let Some(blocked) = result
+                .as_ref()
+                .err()
+                .and_then(|e| e.downcast_ref::<connector::IpGuardBlocked>())
+            {
+                self.audit.log_acl_deny_cid(
                     username,
                     host,
                     port,
-                    Some(resolved_addr.ip().to_string()),
+                    Some(blocked.ip.to_string()),
                     source_ip,
-                    post_decision.matched_rule,
-                    "post-check",
+                    Some(blocked.range.to_string()),
+                    ip_guard::ACL_DENY_REASON,
+                    correlation_id,
                 );
-                anyhow::bail!("ACL denied: {}:{}", host, port);
             }
-
+            let (tcp_stream, resolved_addr) = result?;
+            self.check_resolved(
+                &subject,
+                host,
+                port,
+                user_acl,
+                resolved_addr,
+                source_ip,
+                correlation_id,
+            )
+            .await?;
             Ok((tcp_stream, resolved_addr, guard))
         }
     }
 
+    /// Checks of a destination before anything is resolved: blocked ports,
+    /// the channel hook, policy rules, then the ACL on the hostname (which
+    /// keeps denied targets from being probed).
+    #[allow(clippy::too_many_arguments)]
+    async fn check_destination(
+        &self,
+        subject: &policy::Subject<'_>,
+        host: &str,
+        port: u16,
+        user_acl: &ParsedAcl,
+        blocked_ports: Option<&[u16]>,
+        source_ip: &str,
+        correlation_id: &str,
+    ) -> Result<()> {
+        let username = subject.username;
+        // Blocked ports are refused whatever the ACL allows
+        let blocked_ports = blocked_ports.unwrap_or(&self.config.security.blocked_ports);
+        if let Err(blocked) = connector::check_port(port, blocked_ports) {
+            self.audit.log_acl_deny_cid(
+                username,
+                host,
+                port,
+                None,
+                source_ip,
+                None,
+                connector::PORT_BLOCKED_REASON,
+                correlation_id,
+            );
+            return Err(blocked.into());
+        }
+
+        self.check_channel_hook(subject, host, port, source_ip, correlation_id)
+            .await?;
+
+        // Policy rules go first; the ACL decides the channels they leave open
+        if self
+            .check_policy(subject, host, port, None, source_ip, correlation_id)
+            .await?
+        {
+            return Ok(());
+        }
+
+        // Pre-check ACL with hostname only (before connect, prevents port scanning)
+        let pre_decision = acl::pre_check_hostname_and_log(user_acl, username, host, port);
+        if !pre_decision.allowed {
+            self.audit.log_acl_deny_cid(
+                username,
+                host,
+                port,
+                None,
+                source_ip,
+                pre_decision.matched_rule,
+                "hostname pre-check",
+                correlation_id,
+            );
+            anyhow::bail!("ACL denied: {}:{}", host, port);
+        }
+        Ok(())
+    }
+
+    /// Checks of a destination once its address is known: policy rules
+    /// with the resolved IP (CIDR targets), then the ACL.
+    #[allow(clippy::too_many_arguments)]
+    async fn check_resolved(
+        &self,
+        subject: &policy::Subject<'_>,
+        host: &str,
+        port: u16,
+        user_acl: &ParsedAcl,
+        resolved_addr: SocketAddr,
+        source_ip: &str,
+        correlation_id: &str,
+    ) -> Result<()> {
+        let resolved_ip = Some(resolved_addr.ip());
+        if self
+            .check_policy(subject, host, port, resolved_ip, source_ip, correlation_id)
+            .await?
+        {
+            return Ok(());
+        }
+
+        // Post-check ACL with resolved IP (for CIDR rules)
+        let post_decision = acl::check_and_log(user_acl, subject.username, host, port, resolved_ip);
+        if !post_decision.allowed {
+            self.audit.log_acl_deny_cid(
+                subject.username,
+                host,
+                port,
+                Some(resolved_addr.ip().to_string()),
+                source_ip,
+                post_decision.matched_rule,
+                "post-check",
+                correlation_id,
+            );
+            anyhow::bail!("ACL denied: {}:{}", host, port);
+        }
+        Ok(())
+    }
+
+    /// Stall tracking for SSH channel relays (`server.ssh_stall_threshold_ms`).
+    pub fn stall_watch(&self) -> Option<forwarder::StallWatch> {
+        let threshold_ms = self.config.server.ssh_stall_threshold_ms;
+        (threshold_ms > 0).then(|| forwarder::StallWatch {
+            threshold: Duration::from_millis(threshold_ms),
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// Check a channel of `subject` to `host:port` (`ip` once resolved)
+    /// against `[policy]`, then its plugin: true when allowed, false when
+    /// neither decides (the ACL does), an error once a denial is audited.
+    async fn check_policy(
+        &self,
+        subject: &policy::Subject<'_>,
+        host: &str,
+        port: u16,
+        ip: Option<IpAddr>,
+        source_ip: &str,
+        correlation_id: &str,
+    ) -> Result<bool> {
+        let (allowed, matched_rule, reason) = match self
+            .policy
+            .as_ref()
+            .and_then(|p| p.decide(subject, host, port, ip, Utc::now()))
+        {
+            Some(decision) => (
+                decision.effect == policy::Effect::Allow,
+                decision.rule.to_string(),
+                policy::ACL_DENY_REASON,
+            ),
+            #[cfg(feature = "wasm-plugins")]
+            None => match self.ask_plugin(subject, host, port, ip, source_ip).await {
+                Some((allowed, reason)) => (allowed, reason, plugin::ACL_DENY_REASON),
+                None => return Ok(false),
+            },
+            #[cfg(not(feature = "wasm-plugins"))]
+            None => return Ok(false),
+        };
+        if allowed {
+            debug!(
+                user = %subject.username,
+                target = %format!("{}:{}", host, port),
+                matched_rule = %matched_rule,
+                "Policy: allowed"
+            );
+            return Ok(true);
+        }
+        warn!(
+            user = %subject.username,
+            target = %format!("{}:{}", host, port),
+            matched_rule = %matched_rule,
+            "Policy: denied"
+        );
+        self.audit.log_acl_deny_cid(
+            subject.username,
+            host,
+            port,
+            ip.map(|ip| ip.to_string()),
+            source_ip,
+            Some(matched_rule),
+            reason,
+            correlation_id,
+        );
+        anyhow::bail!("ACL denied: {}:{}", host, port);
+    }
+
+    /// Run the `on_channel_open` hook (`[scripting]`) on a channel of
+    /// `subject` to `host:port`: an error once a refusal is audited. A
+    /// failing hook refuses the channel.
+    #[cfg(feature = "lua-hooks")]
+    async fn check_channel_hook(
+        &self,
+        subject: &policy::Subject<'_>,
+        host: &str,
+        port: u16,
+        source_ip: &str,
+        correlation_id: &str,
+    ) -> Result<()> {
+        let Some(hooks) = &self.hooks else {
+            return Ok(());
+        };
+        let event = crate::scripting::ChannelOpen {
+            username: subject.username,
+            group: subject.group,
+            tags: subject.tags,
+            source_ip,
+            target_host: host,
+            target_port: port,
+            correlation_id,
+        };
+        let reason = match hooks.run(crate::scripting::Hook::ChannelOpen, &event).await {
+            Ok(crate::scripting::Outcome::Continue(_)) => return Ok(()),
+            Ok(crate::scripting::Outcome::Refuse(reason)) => reason,
+            Err(e) => Some(format!("hook error: {}", e)),
+        };
+        warn!(
+            user = %subject.username,
+            target = %format!("{}:{}", host, port),
+            reason = reason.as_deref().unwrap_or("-"),
+            "on_channel_open hook refused the channel"
+        );
+        self.audit.log_acl_deny_cid(
+            subject.username,
+            host,
+            port,
+            None,
+            source_ip,
+            reason,
+            crate::scripting::ACL_DENY_REASON,
+            correlation_id,
+        );
+        anyhow::bail!("ACL denied: {}:{}", host, port);
+    }
+
+    /// Without the `lua-hooks` feature every channel goes on.
+    #[cfg(not(feature = "lua-hooks"))]
+    async fn check_channel_hook(
+        &self,
+        _subject: &policy::Subject<'_>,
+        _host: &str,
+        _port: u16,
+        _source_ip: &str,
+        _correlation_id: &str,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Run the `[policy.plugin]` module on a channel: whether it allows it
+    /// and the reply's reason, or None on a pass. A module that fails
+    /// denies the channel.
+    #[cfg(feature = "wasm-plugins")]
+    async fn ask_plugin(
+        &self,
+        subject: &policy::Subject<'_>,
+        host: &str,
+        port: u16,
+        ip: Option<IpAddr>,
+        source_ip: &str,
+    ) -> Option<(bool, String)> {
+        let plugin = self.plugin.clone()?;
+        let request = plugin::PluginRequest {
+            user: subject.username.to_string(),
+            group: subject.group.map(str::to_string),
+            tags: subject.tags.to_vec(),
+            source_ip: source_ip.to_string(),
+            host: host.to_string(),
+            port,
+            ip,
+            time: Utc::now(),
+        };
+        let reply = tokio::task::spawn_blocking(move || plugin.decide(&request))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r);
+        match reply {
+            Ok(reply) => {
+                let reason = reply.reason.unwrap_or_else(|| "plugin".to_string());
+                match reply.decision {
+                    plugin::Verdict::Allow => Some((true, reason)),
+                    plugin::Verdict::Deny => Some((false, reason)),
+                    plugin::Verdict::Pass => None,
+                }
+            }
+            Err(e) => {
+                warn!(
+                    user = %subject.username,
+                    target = %format!("{}:{}", host, port),
+                    error = %e,
+                    "Policy plugin failed"
+                );
+                Some((false, format!("plugin error: {}", e)))
+            }
+        }
+    }
+
+    /// Server name check for a relay to `port` at `connected`, when
+    /// `acl.inspect_server_names` is on.
+    pub fn server_name_check(
+        &self,
+        user_acl: &ParsedAcl,
+        port: u16,
+        connected: SocketAddr,
+    ) -> Option<forwarder::ServerNameCheck> {
+        self.config
+            .acl
+            .inspect_server_names
+            .then(|| forwarder::ServerNameCheck {
+                acl: Arc::new(user_acl.clone()),
+                port,
+                // The sentinel address means the upstream proxy resolved the target
+                connected_ip: Some(connected.ip()).filter(|ip| !ip.is_unspecified()),
+            })
+    }
+
     /// Connect to a target and relay data through an SSH channel.
-    /// Returns (bytes_up, bytes_down, resolved_addr).
+    /// Returns the relay outcome (bytes and close reason) and the resolved address.
     pub async fn connect_and_relay(
         &self,
         req: SshRelayRequest<'_>,
-    ) -> Result<(u64, u64, SocketAddr)> {
+    ) -> Result<(forwarder::RelayOutcome, SocketAddr)> {
         let (tcp_stream, resolved_addr, _guard) = self
             .connect_checked(
                 req.username,
                 req.host,
                 req.port,
                 req.user_acl,
+                req.ip_guard_exemptions,
+                req.ip_guard_enabled,
+                req.blocked_ports,
+                req.group,
+                req.tags,
                 req.source_ip,
                 req.max_per_user,
                 req.upstream_proxy.as_ref(),
+                req.connect_retry,
+                req.correlation_id,
             )
             .await?;
 
+        if req.jump {
+            // Record where the hop went; the sentinel address means the upstream
+            // proxy resolved the target
+            let resolved_ip = Some(resolved_addr.ip()).filter(|ip| !ip.is_unspecified());
+            let (_, matched_rule) = req.user_acl.check_verbose(req.host, req.port, resolved_ip);
+            self.audit.log_ssh_jump_cid(
+                req.username,
+                req.host,
+                req.port,
+                resolved_ip.map(|ip| ip.to_string()),
+                req.source_ip,
+                matched_rule,
+                req.correlation_id,
+            );
+        }
+
         // Register the live session for tracking
-        let session = self.register_session(req.username, req.host, req.port, req.source_ip, "ssh");
+        let session = self.register_session_cid(
+            req.username,
+            req.host,
+            req.port,
+            req.source_ip,
+            "ssh",
+            req.correlation_id,
+        );
+        let resolved_ip = Some(resolved_addr.ip()).filter(|ip| !ip.is_unspecified());
+        self.prioritize(&session, resolved_ip, req.tags);
+        self.sample_first_line(&session, req.payload_metadata);
 
         info!(
             user = %req.username,
             target = %format!("{}:{}", req.host, req.port),
             resolved_ip = %resolved_addr.ip(),
             session_id = %session.session_id,
+            conn_id = %req.correlation_id,
             "Relay started"
         );
 
-        // Convert SSH channel to stream and relay
-        let channel_stream = req.channel.into_stream();
+        // Relay the channel, its window pacing the reads from the target
+        let channel = channel::WindowedChannel::new(req.channel);
         let relay_cfg = forwarder::RelayConfig {
             idle_timeout: Duration::from_secs(self.config.limits.idle_timeout),
             context: format!("{}@{}:{}", req.username, req.host, req.port),
@@ -361,14 +1121,111 @@ impl ProxyEngine {
             quotas: req.quotas,
             audit: Some(self.audit.clone()),
             session: Some(session.clone()),
+            stall_watch: self.stall_watch(),
+            client_window: Some(channel.window()),
+            server_names: self.server_name_check(req.user_acl, req.port, resolved_addr),
         };
-        let (bytes_up, bytes_down) =
-            forwarder::relay(channel_stream, tcp_stream, relay_cfg).await?;
+        let outcome = forwarder::relay_with_reason(channel, tcp_stream, relay_cfg).await?;
 
         // Unregister the session after relay completes
-        self.unregister_session(&session.session_id);
+        self.close_session(&session, outcome.reason);
+
+        Ok((outcome, resolved_addr))
+    }
+
+    /// Connect to a Unix socket and relay it through an SSH channel. Counts
+    /// against the same connection limits as TCP tunnels; the session shows
+    /// the socket path as its host, with port 0.
+    #[cfg(unix)]
+    pub async fn relay_unix(&self, req: UnixRelayRequest<'_>) -> Result<forwarder::RelayOutcome> {
+        let _guard = self.acquire_connection(req.username, req.max_per_user)?;
+        let timeout = Duration::from_secs(self.config.limits.connection_timeout);
+        let socket =
+            tokio::time::timeout(timeout, tokio::net::UnixStream::connect(req.socket_path))
+                .await??;
+
+        let session = self.register_session_cid(
+            req.username,
+            req.socket_path,
+            0,
+            req.source_ip,
+            "ssh",
+            req.correlation_id,
+        );
+        self.prioritize(&session, None, req.tags);
+        info!(
+            user = %req.username,
+            socket = %req.socket_path,
+            session_id = %session.session_id,
+            conn_id = %req.correlation_id,
+            "Unix socket relay started"
+        );
+
+        let channel = channel::WindowedChannel::new(req.channel);
+        let relay_cfg = forwarder::RelayConfig {
+            idle_timeout: Duration::from_secs(self.config.limits.idle_timeout),
+            context: format!("{}@unix:{}", req.username, req.socket_path),
+            per_conn_bandwidth_kbps: req.bandwidth_limit_kbps,
+            aggregate_bandwidth_kbps: req.aggregate_bandwidth_kbps,
+            quota_tracker: req.quota_tracker,
+            username: Some(req.username.to_string()),
+            quotas: req.quotas,
+            audit: Some(self.audit.clone()),
+            session: Some(session.clone()),
+            stall_watch: self.stall_watch(),
+            client_window: Some(channel.window()),
+            server_names: None,
+        };
+        let outcome = forwarder::relay_with_reason(channel, socket, relay_cfg).await?;
+        self.close_session(&session, outcome.reason);
+        Ok(outcome)
+    }
+
+    /// Relay a `tun@openssh.com` channel to its interface until either side
+    /// closes, the client's packets filtered by `req.filter`.
+    #[cfg(target_os = "linux")]
+    pub async fn relay_tun(&self, req: TunRelayRequest<'_>) -> Result<forwarder::RelayOutcome> {
+        let _guard = self.acquire_connection(req.username, req.max_per_user)?;
+        let client = req.filter.client.to_string();
+        let interface = req.device.name().to_string();
+        let session = self.register_session_cid(
+            req.username,
+            &client,
+            0,
+            req.source_ip,
+            "vpn",
+            req.correlation_id,
+        );
+        self.prioritize(&session, None, req.tags);
+        info!(
+            user = %req.username,
+            interface = %interface,
+            client = %client,
+            session_id = %session.session_id,
+            conn_id = %req.correlation_id,
+            "VPN tunnel started"
+        );
 
-        Ok((bytes_up, bytes_down, resolved_addr))
+        let relay_cfg = forwarder::RelayConfig {
+            idle_timeout: Duration::from_secs(self.config.limits.idle_timeout),
+            context: format!("{}@{}", req.username, interface),
+            per_conn_bandwidth_kbps: req.bandwidth_limit_kbps,
+            aggregate_bandwidth_kbps: req.aggregate_bandwidth_kbps,
+            quota_tracker: req.quota_tracker,
+            username: Some(req.username.to_string()),
+            quotas: req.quotas,
+            audit: Some(self.audit.clone()),
+            session: Some(session.clone()),
+            stall_watch: self.stall_watch(),
+            // Packets are never split to fit the window
+            client_window: None,
+            server_names: None,
+        };
+        let (channel, tunnel) =
+            tun::TunStream::new(req.device, req.filter, req.channel.into_datagram_stream())?;
+        let outcome = forwarder::relay_with_reason(channel, tunnel, relay_cfg).await?;
+        self.close_session(&session, outcome.reason);
+        Ok(outcome)
     }
 
     /// Connect to a target for SOCKS5 (returns the TCP stream directly).
@@ -380,22 +1237,121 @@ impl ProxyEngine {
         host: &str,
         port: u16,
         user_acl: &ParsedAcl,
+        ip_guard_exemptions: &[IpNet],
+        blocked_ports: Option<&[u16]>,
+        group: Option<&str>,
+        tags: &[String],
         source_ip: &str,
         max_per_user: u32,
         upstream_proxy: Option<&ParsedUpstreamProxy>,
+        connect_retry: RetryPolicy,
+        correlation_id: &str,
     ) -> Result<(tokio::net::TcpStream, std::net::SocketAddr, ConnectionGuard)> {
         self.connect_checked(
             username,
             host,
             port,
             user_acl,
+            ip_guard_exemptions,
+            None,
+            blocked_ports,
+            group,
+            tags,
             source_ip,
             max_per_user,
             upstream_proxy,
+            connect_retry,
+            correlation_id,
         )
         .await
     }
 
+    /// Open a UDP socket connected to a target (MASQUE CONNECT-UDP), with
+    /// the checks of a TCP connect: blocked ports, policy, ACL before and
+    /// after resolving, ip_guard and the connection limits. UDP is always
+    /// sent directly: callers refuse users with an upstream proxy.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn connect_udp(
+        &self,
+        username: &str,
+        host: &str,
+        port: u16,
+        user_acl: &ParsedAcl,
+        ip_guard_exemptions: &[IpNet],
+        blocked_ports: Option<&[u16]>,
+        group: Option<&str>,
+        tags: &[String],
+        source_ip: &str,
+        max_per_user: u32,
+        correlation_id: &str,
+    ) -> Result<(tokio::net::UdpSocket, SocketAddr, ConnectionGuard)> {
+        let subject = policy::Subject {
+            username,
+            group,
+            tags,
+        };
+        self.check_destination(
+            &subject,
+            host,
+            port,
+            user_acl,
+            blocked_ports,
+            source_ip,
+            correlation_id,
+        )
+        .await?;
+        let guard = self.acquire_connection(username, max_per_user)?;
+
+        let result = connector::resolve_and_check_with_exemptions(
+            host,
+            port,
+            self.config.limits.connection_timeout,
+            self.config.security.ip_guard_enabled,
+            ip_guard_exemptions,
+            self.config.proxy.address_family,
+        )
+        .await;
+        if let Some(blocked) = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<connector::IpGuardBlocked>())
+        {
+            self.audit.log_acl_deny_cid(
+                username,
+                host,
+                port,
+                Some(blocked.ip.to_string()),
+                source_ip,
+                Some(blocked.range.to_string()),
+                ip_guard::ACL_DENY_REASON,
+                correlation_id,
+            );
+        }
+        let resolved_addr = result?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no addresses found for {}", host))?;
+        self.check_resolved(
+            &subject,
+            host,
+            port,
+            user_acl,
+            resolved_addr,
+            source_ip,
+            correlation_id,
+        )
+        .await?;
+
+        let local: SocketAddr = if resolved_addr.is_ipv4() {
+            (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = tokio::net::UdpSocket::bind(local).await?;
+        socket.connect(resolved_addr).await?;
+        Ok((socket, resolved_addr, guard))
+    }
+
     pub fn active_connections(&self) -> u32 {
         self.global_connections.load(Ordering::Relaxed)
     }
@@ -428,11 +1384,52 @@ impl ProxyEngine {
         target_port: u16,
         source_ip: &str,
         protocol: &str,
+    ) -> Arc<LiveSession> {
+        self.insert_session(
+            username,
+            target_host,
+            target_port,
+            source_ip,
+            protocol,
+            None,
+        )
+    }
+
+    /// Like [`register_session`](Self::register_session), tagging the session
+    /// with the connection's correlation ID.
+    pub fn register_session_cid(
+        &self,
+        username: &str,
+        target_host: &str,
+        target_port: u16,
+        source_ip: &str,
+        protocol: &str,
+        correlation_id: &str,
+    ) -> Arc<LiveSession> {
+        self.insert_session(
+            username,
+            target_host,
+            target_port,
+            source_ip,
+            protocol,
+            Some(correlation_id.to_string()),
+        )
+    }
+
+    fn insert_session(
+        &self,
+        username: &str,
+        target_host: &str,
+        target_port: u16,
+        source_ip: &str,
+        protocol: &str,
+        correlation_id: Option<String>,
     ) -> Arc<LiveSession> {
         let id = self.session_counter.fetch_add(1, Ordering::Relaxed);
         let session_id = format!("s{}", id);
         let session = Arc::new(LiveSession {
             session_id: session_id.clone(),
+            correlation_id,
             username: username.to_string(),
             target_host: target_host.to_string(),
             target_port,
@@ -441,6 +1438,15 @@ impl ProxyEngine {
             bytes_up: AtomicU64::new(0),
             bytes_down: AtomicU64::new(0),
             protocol: protocol.to_string(),
+            kill: close::KillSwitch::default(),
+            capture: capture::CaptureSlot::default(),
+            memory: Arc::new(memory::SessionMemory::new(
+                self.config.limits.max_buffered_bytes,
+            )),
+            traffic: std::sync::OnceLock::new(),
+            transfer: transfer::TransferLimits::new(&self.config.limits),
+            priority: std::sync::OnceLock::new(),
+            first_line_len: std::sync::OnceLock::new(),
         });
         self.active_sessions.insert(session_id, session.clone());
 
@@ -452,9 +1458,161 @@ impl ProxyEngine {
         session
     }
 
+    /// Put a session in its `[proxy.priority]` class, from its destination
+    /// (`ip` once resolved) and the user's `tags`. No-op without classes.
+    pub fn prioritize(&self, session: &LiveSession, ip: Option<IpAddr>, tags: &[String]) {
+        if let Some(ref scheduler) = self.priority {
+            let flow = scheduler.flow(&session.target_host, session.target_port, ip, tags);
+            let _ = session.priority.set(flow);
+        }
+    }
+
+    /// Keep the first line of a session's stream for its flow record
+    /// (`logging.flows.payload_metadata`, or the `payload_metadata` of the
+    /// user's group). No-op unless `[logging.flows]` is enabled.
+    pub fn sample_first_line(&self, session: &LiveSession, group_override: Option<bool>) {
+        let flows = &self.config.logging.flows;
+        if flows.enabled && group_override.unwrap_or(flows.payload_metadata) {
+            let _ = session.first_line_len.set(flows.payload_metadata_max_len);
+        }
+    }
+
+    /// The `[proxy.priority]` scheduler (None without classes).
+    pub fn priority_scheduler(&self) -> Option<&Arc<priority::Scheduler>> {
+        self.priority.as_ref()
+    }
+
+    /// Priority class of a live session (None without `[proxy.priority]`
+    /// classes).
+    pub fn session_priority(&self, session_id: &str) -> Option<String> {
+        let session = self.active_sessions.get(session_id)?;
+        session.priority.get().map(|flow| flow.class().to_string())
+    }
+
     /// Unregister a session by ID.
     pub fn unregister_session(&self, session_id: &str) {
-        self.active_sessions.remove(session_id);
+        if let Some((_, session)) = self.active_sessions.remove(session_id) {
+            if let Some(capture) = session.capture.take() {
+                capture.stop(capture::CaptureEnd::SessionClosed, None);
+            }
+        }
+        self.rate_samples.remove(session_id);
+    }
+
+    /// Unregister a finished session, keep it in the history and count its
+    /// close reason in `s5_sessions_closed_total`.
+    pub fn close_session(&self, session: &LiveSession, reason: close::CloseReason) {
+        self.unregister_session(&session.session_id);
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_session_closed(&session.protocol, reason.as_str());
+            let app_protocol = session
+                .traffic
+                .get()
+                .map_or(classify::AppProtocol::Unknown, |c| c.protocol);
+            metrics.record_app_protocol_bytes(
+                app_protocol.as_str(),
+                session.bytes_up.load(Ordering::Relaxed),
+                session.bytes_down.load(Ordering::Relaxed),
+            );
+        }
+        let closed = close::ClosedSession {
+            session: session.snapshot(),
+            ended_at: Utc::now(),
+            close_reason: reason,
+        };
+        self.usage.record_session(&closed);
+        self.history.push(closed);
+    }
+
+    /// Stop one live session. Returns `false` if there is no such session.
+    pub fn kill_session(&self, session_id: &str, reason: close::CloseReason) -> bool {
+        match self.active_sessions.get(session_id) {
+            Some(session) => {
+                session.kill.kill(reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop every live session matching `filter`; returns how many were stopped.
+    pub fn kill_sessions(
+        &self,
+        reason: close::CloseReason,
+        filter: impl Fn(&LiveSession) -> bool,
+    ) -> usize {
+        self.active_sessions
+            .iter()
+            .filter(|e| filter(e.value()))
+            .filter(|e| e.value().kill.kill(reason))
+            .count()
+    }
+
+    /// Start capturing live session `session_id` to a pcapng file
+    /// (`[logging.capture]`). `duration_secs` defaults to one minute.
+    pub fn start_capture(
+        &self,
+        session_id: &str,
+        mode: capture::CaptureMode,
+        duration_secs: Option<u64>,
+        started_by: &str,
+    ) -> Result<capture::CaptureInfo, capture::CaptureError> {
+        let config = &self.config.logging.capture;
+        if !config.enabled {
+            return Err(capture::CaptureError::Disabled);
+        }
+        let duration = capture::capture_duration(duration_secs, config)?;
+        let session = self
+            .active_sessions
+            .get(session_id)
+            .map(|e| e.value().clone())
+            .ok_or(capture::CaptureError::SessionNotFound)?;
+        let started = session.capture.start(
+            &session,
+            mode,
+            duration,
+            config,
+            started_by,
+            Some(self.audit.clone()),
+        )?;
+        Ok(started.info().clone())
+    }
+
+    /// Stop the capture running on `session_id` and wait for its file to be
+    /// closed. Returns None if nothing is being captured.
+    pub async fn stop_capture(
+        &self,
+        session_id: &str,
+        stopped_by: &str,
+    ) -> Option<capture::CaptureSummary> {
+        let running = self
+            .active_sessions
+            .get(session_id)
+            .and_then(|e| e.value().capture.current())?;
+        running.stop(capture::CaptureEnd::Stopped, Some(stopped_by));
+        Some(running.finished().await)
+    }
+
+    /// The capture running on `session_id`, if any.
+    pub fn session_capture(&self, session_id: &str) -> Option<capture::CaptureInfo> {
+        self.active_sessions
+            .get(session_id)
+            .and_then(|e| e.value().capture.current())
+            .map(|c| c.info().clone())
+    }
+
+    /// Recently closed sessions matching `filter`, newest first.
+    pub fn closed_sessions(
+        &self,
+        limit: usize,
+        filter: impl Fn(&close::ClosedSession) -> bool,
+    ) -> Vec<close::ClosedSession> {
+        self.history.recent(limit, filter)
+    }
+
+    /// Monthly per-user usage totals (`/api/reports/usage`).
+    pub fn usage_history(&self) -> &Arc<crate::reports::UsageHistory> {
+        &self.usage
     }
 
     /// Get snapshots of all active sessions.
@@ -465,6 +1623,27 @@ impl ProxyEngine {
             .collect()
     }
 
+    /// Source locations of the active sessions, clustered for the dashboard
+    /// map. None without a loaded GeoIP database.
+    pub fn session_map(&self) -> Option<geo_map::SessionMap> {
+        let geoip = self.geoip.as_ref()?;
+        let mut points = Vec::new();
+        let mut unlocated = 0;
+        for entry in self.active_sessions.iter() {
+            let session = entry.value();
+            let ip = session.source_ip.parse().ok();
+            match ip.and_then(|ip| geoip.location(&ip).map(|loc| (ip, loc))) {
+                Some((ip, location)) => points.push(geo_map::SessionPoint {
+                    location,
+                    country: geoip.country(&ip),
+                    username: session.username.clone(),
+                }),
+                None => unlocated += 1,
+            }
+        }
+        Some(geo_map::SessionMap::build(points, unlocated))
+    }
+
     /// Get snapshots of sessions for a specific user.
     pub fn get_user_sessions(&self, username: &str) -> Vec<SessionSnapshot> {
         self.active_sessions
@@ -473,4 +1652,138 @@ impl ProxyEngine {
             .map(|e| e.value().snapshot())
             .collect()
     }
+
+    /// Get snapshots of the sessions opened by one connection.
+    pub fn get_correlated_sessions(&self, correlation_id: &str) -> Vec<SessionSnapshot> {
+        self.active_sessions
+            .iter()
+            .filter(|e| e.value().correlation_id.as_deref() == Some(correlation_id))
+            .map(|e| e.value().snapshot())
+            .collect()
+    }
+
+    /// Get the snapshot of one session by ID.
+    pub fn get_session(&self, session_id: &str) -> Option<SessionSnapshot> {
+        self.active_sessions.get(session_id).map(|e| e.snapshot())
+    }
+
+    /// Current transfer rate of a session as (up, down) bytes per second.
+    ///
+    /// Measured between two calls at least 500 ms apart; the first call
+    /// returns the average since the session started.
+    pub fn session_rate(&self, session_id: &str) -> Option<(f64, f64)> {
+        let snap = self.get_session(session_id)?;
+        let now = Instant::now();
+        let mut sample = self
+            .rate_samples
+            .entry(session_id.to_string())
+            .or_insert_with(|| {
+                let age = Utc::now().signed_duration_since(snap.started_at);
+                let secs = (age.num_milliseconds().max(1) as f64) / 1000.0;
+                RateSample {
+                    at: now,
+                    bytes_up: snap.bytes_up,
+                    bytes_down: snap.bytes_down,
+                    up_per_sec: snap.bytes_up as f64 / secs,
+                    down_per_sec: snap.bytes_down as f64 / secs,
+                }
+            });
+        let elapsed = now.duration_since(sample.at);
+        if elapsed >= RATE_SAMPLE_INTERVAL {
+            let secs = elapsed.as_secs_f64();
+            sample.up_per_sec = snap.bytes_up.saturating_sub(sample.bytes_up) as f64 / secs;
+            sample.down_per_sec = snap.bytes_down.saturating_sub(sample.bytes_down) as f64 / secs;
+            sample.at = now;
+            sample.bytes_up = snap.bytes_up;
+            sample.bytes_down = snap.bytes_down;
+        }
+        Some((sample.up_per_sec, sample.down_per_sec))
+    }
+
+    /// Track a new SSH connection under its correlation ID.
+    pub fn register_connection(
+        &self,
+        correlation_id: &str,
+        source_ip: &str,
+    ) -> Arc<LiveConnection> {
+        let connection = Arc::new(LiveConnection {
+            correlation_id: correlation_id.to_string(),
+            source_ip: source_ip.to_string(),
+            started_at: Utc::now(),
+            handshake: Arc::new(std::sync::Mutex::new(HandshakeInfo::default())),
+            auth_method: std::sync::Mutex::new(None),
+            session_channels: AtomicU32::new(0),
+        });
+        self.connections
+            .insert(correlation_id.to_string(), connection.clone());
+        connection
+    }
+
+    /// Stop tracking an SSH connection.
+    pub fn unregister_connection(&self, correlation_id: &str) {
+        self.connections.remove(correlation_id);
+    }
+
+    /// Snapshot of an SSH connection, counting its forwarding channels from
+    /// the live sessions that share its correlation ID.
+    pub fn get_connection(&self, correlation_id: &str) -> Option<ConnectionSnapshot> {
+        let conn = self.connections.get(correlation_id)?.clone();
+        let handshake = conn
+            .handshake
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let auth_method = conn
+            .auth_method
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let forwarding = self
+            .active_sessions
+            .iter()
+            .filter(|e| e.value().correlation_id.as_deref() == Some(correlation_id))
+            .count() as u32;
+        let session_channels = conn.session_channels.load(Ordering::Relaxed);
+        Some(ConnectionSnapshot {
+            correlation_id: conn.correlation_id.clone(),
+            source_ip: conn.source_ip.clone(),
+            started_at: conn.started_at,
+            client_version: handshake.client_version,
+            hassh: handshake.hassh,
+            algorithms: handshake.algorithms,
+            auth_method,
+            open_channels: session_channels + forwarding,
+            session_channels,
+            forwarding_channels: forwarding,
+        })
+    }
+
+    /// Take one aggregate throughput sample over all live sessions.
+    /// Called once per second by the server's sampler task.
+    pub fn sample_throughput(&self) -> throughput::ThroughputSample {
+        let counters: Vec<(String, u64, u64)> = self
+            .active_sessions
+            .iter()
+            .map(|e| {
+                let s = e.value();
+                (
+                    s.session_id.clone(),
+                    s.bytes_up.load(Ordering::Relaxed),
+                    s.bytes_down.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+        self.throughput
+            .record(counters, Instant::now(), Utc::now().timestamp())
+    }
+
+    /// Aggregate throughput of the last minute, oldest first.
+    pub fn throughput_history(&self) -> Vec<throughput::ThroughputSample> {
+        self.throughput.samples()
+    }
+
+    /// Most recent aggregate throughput sample.
+    pub fn latest_throughput(&self) -> Option<throughput::ThroughputSample> {
+        self.throughput.latest()
+    }
 }
@@ -0,0 +1,154 @@
+//! WASM policy plugins (`[policy.plugin]`).
+//!
+//! A WebAssembly module decides the channels no `[policy]` rule matches,
+//! before the ACL. It gets no imports, so it can only compute: each call
+//! runs in a fresh instance under a fuel budget and a wall-clock deadline,
+//! and a module that traps, runs out of either or answers nonsense denies
+//! the channel.
+//!
+//! The module exports `memory`, `alloc(len: i32) -> i32` and
+//! `decide(ptr: i32, len: i32) -> i64`. `decide` reads a JSON
+//! [`PluginRequest`] of `len` bytes at `ptr` (a buffer from `alloc`) and
+//! returns where its JSON [`PluginReply`] is, as `ptr << 32 | len`.
+
+use crate::config::types::PolicyPluginConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+use wasmtime::{Engine, Instance, Module, Store, Trap};
+
+/// `reason` of the `acl.deny` audit events of channels the plugin denies.
+pub const ACL_DENY_REASON: &str = "policy plugin";
+
+/// Epoch tick of the deadline clock.
+const TICK: Duration = Duration::from_millis(1);
+
+/// Longest reply read back from the module.
+const MAX_REPLY_LEN: usize = 64 * 1024;
+
+/// A channel open, as the module sees it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginRequest {
+    pub user: String,
+    pub group: Option<String>,
+    /// Own and group tags
+    pub tags: Vec<String>,
+    pub source_ip: String,
+    pub host: String,
+    pub port: u16,
+    /// The resolved destination; null on the check before connecting
+    pub ip: Option<IpAddr>,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Allow,
+    Deny,
+    /// Leave the channel to the ACL
+    Pass,
+}
+
+/// The module's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PluginReply {
+    pub decision: Verdict,
+    /// Recorded as the `matched_rule` of audit events
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A compiled policy module.
+pub struct Plugin {
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    /// Deadline of a call, in ticks
+    deadline: u64,
+}
+
+impl Plugin {
+    /// Compile the module of `config` and check its exports. A thread
+    /// ticks the deadline clock for as long as the plugin lives.
+    pub fn load(config: &PolicyPluginConfig) -> Result<Self> {
+        let mut wasm = wasmtime::Config::new();
+        wasm.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&wasm)?;
+        let bytes = std::fs::read(&config.path)
+            .with_context(|| format!("reading {}", config.path.display()))?;
+        let module = Module::from_binary(&engine, &bytes)
+            .with_context(|| format!("compiling {}", config.path.display()))?;
+        if let Some(import) = module.imports().next() {
+            anyhow::bail!(
+                "module imports {}::{}; policy plugins get no imports",
+                import.module(),
+                import.name()
+            );
+        }
+        for name in ["memory", "alloc", "decide"] {
+            if module.get_export(name).is_none() {
+                anyhow::bail!("module does not export '{}'", name);
+            }
+        }
+
+        let clock = engine.weak();
+        std::thread::Builder::new()
+            .name("s5-policy-plugin".to_string())
+            .spawn(move || {
+                while let Some(engine) = clock.upgrade() {
+                    engine.increment_epoch();
+                    drop(engine);
+                    std::thread::sleep(TICK);
+                }
+            })?;
+        let deadline = config.timeout_ms.max(1) * 1000 / TICK.as_micros() as u64;
+        Ok(Self {
+            engine,
+            module,
+            fuel: config.fuel,
+            deadline,
+        })
+    }
+
+    /// Run the module on `request`. Blocks for up to `timeout_ms`.
+    pub fn decide(&self, request: &PluginRequest) -> Result<PluginReply> {
+        let input = serde_json::to_vec(request)?;
+        let len = i32::try_from(input.len())?;
+        let mut store = Store::new(&self.engine, ());
+        store.set_fuel(self.fuel)?;
+        store.set_epoch_deadline(self.deadline);
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(describe)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("'memory' is not a memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let decide = instance.get_typed_func::<(i32, i32), i64>(&mut store, "decide")?;
+
+        let ptr = alloc.call(&mut store, len).map_err(describe)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, &input)
+            .context("alloc returned a buffer outside memory")?;
+        let reply = decide.call(&mut store, (ptr, len)).map_err(describe)? as u64;
+        let (start, len) = ((reply >> 32) as usize, (reply & 0xffff_ffff) as usize);
+        if len > MAX_REPLY_LEN {
+            anyhow::bail!("reply of {} bytes", len);
+        }
+        let reply = memory
+            .data(&store)
+            .get(start..start + len)
+            .context("reply outside memory")?;
+        serde_json::from_slice(reply).context("malformed reply")
+    }
+}
+
+/// Name the limits a call ran into.
+fn describe(e: anyhow::Error) -> anyhow::Error {
+    match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => anyhow::anyhow!("out of fuel"),
+        Some(Trap::Interrupt) => anyhow::anyhow!("timed out"),
+        _ => e,
+    }
+}
@@ -0,0 +1,269 @@
+//! Tag-based channel policy (`[policy]`).
+//!
+//! Users (through their own and their group's `tags`), destinations
+//! (`[[policy.destinations]]`) and time windows (`[[policy.time_windows]]`)
+//! carry tags, and rules refer to them:
+//!
+//! ```text
+//! allow user:tag(dev) dest:tag(staging) port:443
+//! deny dest:tag(prod) time:tag(weekend)
+//! ```
+//!
+//! Each channel open (SSH forwarding, SOCKS5 CONNECT) is checked against
+//! the rules in order; the first rule whose selectors all match allows or
+//! denies it. When no rule matches, the ACL decides as before. Rules are
+//! checked once before connecting and again with the resolved address, so
+//! a `dest` tag holding CIDR ranges applies to hostnames too.
+
+use crate::config::acl::{AclRule, PortMatch};
+use crate::config::types::{PolicyConfig, PolicyTimeWindowConfig};
+use crate::quota::plan::Zone;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// `reason` of the `acl.deny` audit events of channels a rule denies.
+pub const ACL_DENY_REASON: &str = "policy";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// The user opening a channel.
+#[derive(Debug, Clone, Copy)]
+pub struct Subject<'a> {
+    pub username: &'a str,
+    pub group: Option<&'a str>,
+    /// Own and group tags
+    pub tags: &'a [String],
+}
+
+/// The first rule matching a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision<'a> {
+    pub effect: Effect,
+    /// The rule as configured (`matched_rule` of audit events)
+    pub rule: &'a str,
+}
+
+/// A parsed `[[policy.time_windows]]` entry.
+#[derive(Debug, Clone)]
+struct Window {
+    /// Empty = every day
+    days: Vec<Weekday>,
+    /// Start and end; past midnight when the end comes first
+    hours: Option<(NaiveTime, NaiveTime)>,
+    zone: Zone,
+}
+
+impl Window {
+    fn parse(window: &PolicyTimeWindowConfig) -> Result<Self> {
+        let days = window
+            .days
+            .iter()
+            .map(|day| {
+                day.parse::<Weekday>()
+                    .map_err(|_| anyhow::anyhow!("invalid day '{}'", day))
+            })
+            .collect::<Result<_>>()?;
+        let hours = window
+            .hours
+            .as_deref()
+            .map(|hours| {
+                let invalid =
+                    || anyhow::anyhow!("invalid hours '{}' (expected HH:MM-HH:MM)", hours);
+                let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+                let time = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M");
+                Ok::<_, anyhow::Error>((
+                    time(start).map_err(|_| invalid())?,
+                    time(end).map_err(|_| invalid())?,
+                ))
+            })
+            .transpose()?;
+        let zone =
+            crate::quota::plan::parse_timezone(&window.timezone).map_err(anyhow::Error::msg)?;
+        Ok(Self { days, hours, zone })
+    }
+
+    fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local = self.zone.local(now);
+        if !self.days.is_empty() && !self.days.contains(&local.weekday()) {
+            return false;
+        }
+        match self.hours {
+            None => true,
+            Some((start, end)) if start <= end => (start..end).contains(&local.time()),
+            Some((start, end)) => local.time() >= start || local.time() < end,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    User(String),
+    UserTag(String),
+    Group(String),
+    Dest(AclRule),
+    /// Targets of a destination tag
+    DestTag(Vec<AclRule>),
+    Port(PortMatch),
+    /// Windows of a time tag
+    Time(Vec<Window>),
+}
+
+impl Selector {
+    fn matches(
+        &self,
+        subject: &Subject<'_>,
+        host: &str,
+        port: u16,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match self {
+            Selector::User(name) => subject.username == name.as_str(),
+            Selector::UserTag(tag) => subject.tags.contains(tag),
+            Selector::Group(name) => subject.group == Some(name.as_str()),
+            Selector::Dest(rule) => rule.matches(host, port, ip),
+            Selector::DestTag(rules) => rules.iter().any(|r| r.matches(host, port, ip)),
+            Selector::Port(ports) => ports.matches(port),
+            Selector::Time(windows) => windows.iter().any(|w| w.contains(now)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    effect: Effect,
+    selectors: Vec<Selector>,
+    text: String,
+}
+
+/// The rules of `[policy]`, with their tags resolved.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// The policy of `[policy]`; None without rules. Every tagged
+    /// destination and time window is checked, used or not.
+    pub fn from_config(config: &PolicyConfig) -> Result<Option<Self>> {
+        let mut tags = Tags::default();
+        for dest in &config.destinations {
+            let targets = dest
+                .targets
+                .iter()
+                .map(|t| {
+                    AclRule::parse(t).with_context(|| format!("destination tag '{}'", dest.tag))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            tags.destinations
+                .entry(dest.tag.as_str())
+                .or_default()
+                .extend(targets);
+        }
+        for window in &config.time_windows {
+            let parsed =
+                Window::parse(window).with_context(|| format!("time tag '{}'", window.tag))?;
+            tags.windows
+                .entry(window.tag.as_str())
+                .or_default()
+                .push(parsed);
+        }
+        if config.rules.is_empty() {
+            return Ok(None);
+        }
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| parse_rule(rule, &tags).with_context(|| format!("policy rule '{}'", rule)))
+            .collect::<Result<_>>()?;
+        Ok(Some(Self { rules }))
+    }
+
+    /// The first rule matching a channel of `subject` to `host:port` (`ip`
+    /// once resolved) at `now`; None leaves the channel to the ACL.
+    pub fn decide(
+        &self,
+        subject: &Subject<'_>,
+        host: &str,
+        port: u16,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Option<Decision<'_>> {
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.selectors
+                    .iter()
+                    .all(|s| s.matches(subject, host, port, ip, now))
+            })
+            .map(|rule| Decision {
+                effect: rule.effect,
+                rule: &rule.text,
+            })
+    }
+}
+
+/// Tagged destinations and time windows, by tag.
+#[derive(Default)]
+struct Tags<'a> {
+    destinations: HashMap<&'a str, Vec<AclRule>>,
+    windows: HashMap<&'a str, Vec<Window>>,
+}
+
+/// Parse `allow|deny [kind:value ...]`; a rule without selectors matches
+/// every channel.
+fn parse_rule(rule: &str, tags: &Tags<'_>) -> Result<Rule> {
+    let mut words = rule.split_whitespace();
+    let effect = match words.next() {
+        Some("allow") => Effect::Allow,
+        Some("deny") => Effect::Deny,
+        _ => anyhow::bail!("must start with 'allow' or 'deny'"),
+    };
+    let selectors = words
+        .map(|word| parse_selector(word, tags))
+        .collect::<Result<_>>()?;
+    Ok(Rule {
+        effect,
+        selectors,
+        text: rule.split_whitespace().collect::<Vec<_>>().join(" "),
+    })
+}
+
+fn parse_selector(word: &str, tags: &Tags<'_>) -> Result<Selector> {
+    let Some((kind, value)) = word.split_once(':') else {
+        anyhow::bail!("invalid selector '{}' (expected kind:value)", word);
+    };
+    if value.is_empty() {
+        anyhow::bail!("selector '{}' has no value", word);
+    }
+    let tag = value
+        .strip_prefix("tag(")
+        .and_then(|t| t.strip_suffix(')'))
+        .filter(|t| !t.is_empty());
+    Ok(match (kind, tag) {
+        ("user", Some(tag)) => Selector::UserTag(tag.to_string()),
+        ("user", None) => Selector::User(value.to_string()),
+        ("group", None) => Selector::Group(value.to_string()),
+        ("dest", Some(tag)) => match tags.destinations.get(tag) {
+            Some(targets) => Selector::DestTag(targets.clone()),
+            None => anyhow::bail!("unknown destination tag '{}'", tag),
+        },
+        ("dest", None) => Selector::Dest(AclRule::parse(value)?),
+        ("port", None) => Selector::Port(PortMatch::parse(value)?),
+        ("time", Some(tag)) => match tags.windows.get(tag) {
+            Some(windows) => Selector::Time(windows.clone()),
+            None => anyhow::bail!("unknown time tag '{}'", tag),
+        },
+        _ => anyhow::bail!(
+            "invalid selector '{}' (expected user:<name>, user:tag(..), group:<name>, \
+             dest:<rule>, dest:tag(..), port:<ports> or time:tag(..))",
+            word
+        ),
+    })
+}
@@ -0,0 +1,262 @@
+//! Priority classes for relayed sessions (`[proxy.priority]`).
+//!
+//! A session goes to the first class whose `destinations` match its target
+//! or whose `tags` share one with the user's `tags`; other sessions weigh
+//! `default_weight`. Before writing a chunk, a relay takes a slot from the
+//! [`Scheduler`]. While fewer than `max_inflight` writes are in progress the
+//! chunk goes straight out. Past that, waiting chunks are served by weighted
+//! fair queuing: a chunk finishes, in virtual time, its size divided by its
+//! class weight after the previous chunk of its session, and the earliest
+//! finish goes next. A bulk transfer keeps its share of the link, and the
+//! few bytes of an interactive channel do not queue behind its chunks.
+//!
+//! A write that waits on a slow peer (a full SSH channel window) keeps its
+//! slot for `hold_ms` at most, so one stalled client cannot hold up the
+//! others.
+
+use crate::config::acl::AclRule;
+use crate::config::types::PriorityConfig;
+use anyhow::{Context, Result};
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Class name of sessions no class matches.
+pub const DEFAULT_CLASS: &str = "default";
+
+/// Virtual time units per byte at weight 1, so integer division keeps
+/// precision for weights up to 1000.
+const WEIGHT_SCALE: u64 = 1000;
+
+struct Class {
+    name: String,
+    weight: u32,
+    destinations: Vec<AclRule>,
+    tags: Vec<String>,
+}
+
+/// A chunk waiting for a write slot.
+struct Waiter {
+    start: u64,
+    finish: u64,
+    /// Arrival order, to break ties
+    seq: u64,
+    grant: oneshot::Sender<Slot>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (self.finish, self.seq).cmp(&(other.finish, other.seq))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    in_flight: usize,
+    /// Start tag of the chunk sent last
+    virtual_time: u64,
+    waiting: BinaryHeap<Reverse<Waiter>>,
+    seq: u64,
+}
+
+/// Hands out write slots to relays by class weight.
+pub struct Scheduler {
+    classes: Vec<Class>,
+    default_weight: u32,
+    max_inflight: usize,
+    hold: Duration,
+    state: Mutex<State>,
+}
+
+impl Scheduler {
+    /// The scheduler for `[proxy.priority]`; None without classes.
+    pub fn from_config(config: &PriorityConfig) -> Result<Option<Arc<Self>>> {
+        if config.classes.is_empty() {
+            return Ok(None);
+        }
+        let classes = config
+            .classes
+            .iter()
+            .map(|class| {
+                let destinations = class
+                    .destinations
+                    .iter()
+                    .map(|rule| {
+                        AclRule::parse(rule)
+                            .with_context(|| format!("priority class '{}': {}", class.name, rule))
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(Class {
+                    name: class.name.clone(),
+                    weight: class.weight,
+                    destinations,
+                    tags: class.tags.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(Arc::new(Self {
+            classes,
+            default_weight: config.default_weight,
+            max_inflight: config.max_inflight.max(1),
+            hold: Duration::from_millis(config.hold_ms),
+            state: Mutex::new(State::default()),
+        })))
+    }
+
+    /// The flow of a session to `host:port` (`ip` once resolved) opened by a
+    /// user with `tags`.
+    pub fn flow(
+        self: &Arc<Self>,
+        host: &str,
+        port: u16,
+        ip: Option<IpAddr>,
+        tags: &[String],
+    ) -> Flow {
+        let class = self.classes.iter().position(|class| {
+            class.destinations.iter().any(|r| r.matches(host, port, ip))
+                || class.tags.iter().any(|t| tags.contains(t))
+        });
+        let weight = class.map_or(self.default_weight, |i| self.classes[i].weight);
+        Flow {
+            scheduler: self.clone(),
+            class,
+            weight: u64::from(weight.max(1)),
+            finish: AtomicU64::new(0),
+        }
+    }
+
+    /// Chunks waiting for a slot now.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiting.len()
+    }
+
+    /// Writes holding a slot now.
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// Give a slot back: to the waiting chunk that finishes first, or to
+    /// the free pool.
+    fn release(self: &Arc<Self>) {
+        loop {
+            let waiter = {
+                let mut state = self.lock();
+                match state.waiting.pop() {
+                    Some(Reverse(waiter)) => {
+                        state.virtual_time = state.virtual_time.max(waiter.start);
+                        waiter
+                    }
+                    None => {
+                        state.in_flight = state.in_flight.saturating_sub(1);
+                        return;
+                    }
+                }
+            };
+            match waiter.grant.send(Slot(Some(self.clone()))) {
+                Ok(()) => return,
+                // The relay stopped waiting: pass the slot on
+                Err(mut slot) => slot.0 = None,
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The scheduling state of one session.
+pub struct Flow {
+    scheduler: Arc<Scheduler>,
+    /// Index in the classes (None = default)
+    class: Option<usize>,
+    weight: u64,
+    /// Finish tag of the session's last chunk
+    finish: AtomicU64,
+}
+
+impl Flow {
+    /// Class name, [`DEFAULT_CLASS`] when none matched.
+    pub fn class(&self) -> &str {
+        self.class
+            .map_or(DEFAULT_CLASS, |i| self.scheduler.classes[i].name.as_str())
+    }
+
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    /// Wait for a slot to write a `bytes` chunk.
+    pub async fn slot(&self, bytes: usize) -> Slot {
+        let waiting = {
+            let mut state = self.scheduler.lock();
+            let start = state.virtual_time.max(self.finish.load(Ordering::Relaxed));
+            let cost = (bytes as u64).saturating_mul(WEIGHT_SCALE) / self.weight;
+            let finish = start.saturating_add(cost.max(1));
+            self.finish.store(finish, Ordering::Relaxed);
+            if state.waiting.is_empty() && state.in_flight < self.scheduler.max_inflight {
+                state.in_flight += 1;
+                state.virtual_time = start;
+                return Slot(Some(self.scheduler.clone()));
+            }
+            let (grant, waiting) = oneshot::channel();
+            state.seq += 1;
+            let seq = state.seq;
+            state.waiting.push(Reverse(Waiter {
+                start,
+                finish,
+                seq,
+                grant,
+            }));
+            waiting
+        };
+        // The sender only goes away with a slot in hand
+        waiting.await.unwrap_or(Slot(None))
+    }
+}
+
+/// Permission to write one chunk; given back on drop.
+pub struct Slot(Option<Arc<Scheduler>>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.0.take() {
+            scheduler.release();
+        }
+    }
+}
+
+/// Run `write` holding `slot`, giving the slot back once the write has
+/// taken `hold_ms` (the peer is not reading).
+pub async fn holding<T>(slot: Option<Slot>, write: impl Future<Output = T>) -> T {
+    let Some(hold) = slot.as_ref().and_then(|s| s.0.as_ref()).map(|s| s.hold) else {
+        return write.await;
+    };
+    tokio::pin!(write);
+    match tokio::time::timeout(hold, &mut write).await {
+        Ok(value) => value,
+        Err(_) => {
+            drop(slot);
+            write.await
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::auth::user::User;
+use rand::Rng;
 use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::net::TcpStream;
@@ -6,6 +8,56 @@ use tracing::{debug, warn};
 /// Maximum backoff cap: 10 seconds.
 const MAX_BACKOFF_MS: u64 = 10_000;
 
+/// Outbound connect retries (`connect_retry`, `connect_retry_delay_ms`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 = no retry)
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for each further one
+    pub base_delay_ms: u64,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay_ms: u64) -> Self {
+        Self {
+            max_retries,
+            base_delay_ms,
+        }
+    }
+
+    /// The user's effective policy (user > group > server).
+    pub fn for_user(user: &User) -> Self {
+        Self::new(user.connect_retry, user.connect_retry_delay_ms)
+    }
+
+    /// Exponential backoff before retry `retry` (1-based), capped at
+    /// [`MAX_BACKOFF_MS`], without jitter.
+    pub fn max_backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64 << retry.saturating_sub(1).min(32);
+        Duration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(MAX_BACKOFF_MS),
+        )
+    }
+
+    /// [`max_backoff`](Self::max_backoff) with equal jitter: a random delay
+    /// between half and all of it, so clients failing together retry apart.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let max = self.max_backoff(retry).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(max / 2..=max))
+    }
+}
+
+/// Connect errors worth retrying: refused (the service may be restarting)
+/// and timed out. Anything else fails at once.
+pub fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::TimedOut
+    )
+}
+
 /// Attempt to connect to `host:port` with exponential-backoff retries.
 ///
 /// - On the first attempt failure, waits `delay_ms` before retrying.
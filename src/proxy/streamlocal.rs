@@ -0,0 +1,53 @@
+//! Unix socket targets (`direct-streamlocal@openssh.com`, `ssh -L 5432:/run/db.sock`).
+//!
+//! A user may only open the socket paths listed in their `unix_sockets`. An
+//! entry is an absolute path, matched exactly, or a directory followed by
+//! `/*`, matching the sockets directly inside it. Requested paths must be
+//! absolute and free of `.`/`..` components, so a pattern cannot be escaped
+//! by walking out of its directory.
+
+use std::path::{Component, Path};
+
+/// Check an allowlist entry. Returns a description of the problem.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let path = pattern.strip_suffix("/*").unwrap_or(pattern);
+    if !is_clean_absolute(path) {
+        return Err(format!(
+            "'{}' must be an absolute path without '.' or '..' components",
+            pattern
+        ));
+    }
+    if path.contains('*') {
+        return Err(format!(
+            "'{}': '*' is only allowed as a final '/*'",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `path` is listed in `allowed`.
+pub fn is_allowed(allowed: &[String], path: &str) -> bool {
+    if !is_clean_absolute(path) {
+        return false;
+    }
+    allowed
+        .iter()
+        .any(|pattern| match pattern.strip_suffix("/*") {
+            Some(dir) => Path::new(path)
+                .parent()
+                .is_some_and(|parent| parent == Path::new(dir)),
+            None => pattern == path,
+        })
+}
+
+fn is_clean_absolute(path: &str) -> bool {
+    // `components()` silently drops inner `.` and repeated `/`, so check the text too
+    !path.contains('\0')
+        && !path.contains("/./")
+        && !path.ends_with("/.")
+        && Path::new(path).is_absolute()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::RootDir | Component::Normal(_)))
+}
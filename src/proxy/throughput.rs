@@ -0,0 +1,109 @@
+//! Aggregate throughput history for the dashboard sparkline.
+//!
+//! A sampler calls [`ThroughputHistory::record`] once per second with the
+//! byte counters of every live session. The difference with the previous
+//! call, summed over sessions, gives the server-wide rate for that second;
+//! the last [`HISTORY_SECONDS`] samples are kept in a ring buffer.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Samples kept (one per second).
+pub const HISTORY_SECONDS: usize = 60;
+
+/// Server-wide transfer rate over one sampling interval.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ThroughputSample {
+    /// Unix timestamp (seconds) of the end of the interval
+    pub timestamp: i64,
+    /// Client to target, bytes per second
+    pub up_bps: u64,
+    /// Target to client, bytes per second
+    pub down_bps: u64,
+}
+
+#[derive(Default)]
+struct State {
+    samples: VecDeque<ThroughputSample>,
+    /// Counters seen at the previous sample, by session ID
+    last: HashMap<String, (u64, u64)>,
+    last_at: Option<Instant>,
+}
+
+/// Fixed-size ring buffer of [`ThroughputSample`]s.
+pub struct ThroughputHistory {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+impl Default for ThroughputHistory {
+    fn default() -> Self {
+        Self::new(HISTORY_SECONDS)
+    }
+}
+
+impl ThroughputHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Record one sample from `(session_id, bytes_up, bytes_down)` counters.
+    ///
+    /// Sessions first seen now count in full, sessions gone since the last
+    /// call are dropped. The first call only sets the baseline and records
+    /// a zero sample, so traffic from before the sampler started is not
+    /// reported as a spike.
+    pub fn record<I>(&self, counters: I, now: Instant, timestamp: i64) -> ThroughputSample
+    where
+        I: IntoIterator<Item = (String, u64, u64)>,
+    {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut seen = HashMap::with_capacity(state.last.len());
+        let (mut up, mut down) = (0u64, 0u64);
+        for (id, bytes_up, bytes_down) in counters {
+            let (prev_up, prev_down) = state.last.get(&id).copied().unwrap_or((0, 0));
+            up += bytes_up.saturating_sub(prev_up);
+            down += bytes_down.saturating_sub(prev_down);
+            seen.insert(id, (bytes_up, bytes_down));
+        }
+        state.last = seen;
+
+        let sample = match state.last_at.replace(now) {
+            Some(prev) => {
+                let secs = now.duration_since(prev).as_secs_f64().max(0.001);
+                ThroughputSample {
+                    timestamp,
+                    up_bps: (up as f64 / secs).round() as u64,
+                    down_bps: (down as f64 / secs).round() as u64,
+                }
+            }
+            None => ThroughputSample {
+                timestamp,
+                up_bps: 0,
+                down_bps: 0,
+            },
+        };
+        if state.samples.len() == self.capacity {
+            state.samples.pop_front();
+        }
+        state.samples.push_back(sample);
+        sample
+    }
+
+    /// Samples oldest first.
+    pub fn samples(&self) -> Vec<ThroughputSample> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.samples.iter().copied().collect()
+    }
+
+    /// Most recent sample, if any.
+    pub fn latest(&self) -> Option<ThroughputSample> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.samples.back().copied()
+    }
+}
@@ -0,0 +1,45 @@
+//! Per-connection transfer guardrails against bulk exfiltration.
+//!
+//! `limits.max_connection_bytes` caps what one relayed session may move in
+//! both directions together: the relay ends the session with
+//! [`CloseReason::TransferLimit`](super::close::CloseReason::TransferLimit)
+//! before a chunk would take it past. `limits.upload_alert_bytes` and
+//! `limits.download_alert_bytes` leave the session open but raise a critical
+//! `session.transfer_alert` audit event the first time one direction goes
+//! past its threshold, so a single large upload stands out for review.
+
+use crate::config::types::LimitsConfig;
+
+/// Transfer limits of one session (0 = off).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferLimits {
+    pub max_bytes: u64,
+    pub upload_alert: u64,
+    pub download_alert: u64,
+}
+
+impl TransferLimits {
+    pub fn new(limits: &LimitsConfig) -> Self {
+        Self {
+            max_bytes: limits.max_connection_bytes,
+            upload_alert: limits.upload_alert_bytes,
+            download_alert: limits.download_alert_bytes,
+        }
+    }
+
+    /// Whether moving `chunk` more bytes after `moved` goes past the cap.
+    pub fn would_exceed(&self, moved: u64, chunk: u64) -> bool {
+        self.max_bytes > 0 && moved.saturating_add(chunk) > self.max_bytes
+    }
+
+    /// The alert threshold a direction crossed going from `before` to
+    /// `after` bytes, if any. Counters only grow, so each fires once.
+    pub fn crossed_alert(&self, upload: bool, before: u64, after: u64) -> Option<u64> {
+        let threshold = if upload {
+            self.upload_alert
+        } else {
+            self.download_alert
+        };
+        (threshold > 0 && before <= threshold && after > threshold).then_some(threshold)
+    }
+}
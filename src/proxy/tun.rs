@@ -0,0 +1,575 @@
+//! Layer-3 tunnels (`tun@openssh.com` channels, `ssh -w`, `[vpn]`).
+//!
+//! Each channel gets its own TUN interface (Linux): the server end is the
+//! first address of `vpn.pool`, the client end an address leased from it
+//! (`vpn_address` or the first free one). The kernel routes what the client
+//! sends; IP forwarding and NAT of the pool are left to the admin.
+//!
+//! A packet from the client is dropped unless its source is the leased
+//! address and the user's ACL, ip_guard and blocked ports let its
+//! destination through. TCP and UDP are matched on their destination port,
+//! other protocols (ICMP...) as port 0. Decisions are cached per
+//! destination, so a denied one is audited once per tunnel.
+//!
+//! Channel data follows OpenSSH: each data message is one packet, the
+//! `u32` address family (OpenBSD values) then the IP packet.
+
+use crate::audit::AuditLogger;
+use crate::config::acl::{AclPolicy, ParsedAcl};
+use crate::proxy::{connector, ip_guard};
+use ipnet::{IpNet, Ipv4Net};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+
+/// `mode` of a layer 3 (point-to-point) `tun@openssh.com` channel; layer 2
+/// (ethernet, `2`) is refused.
+pub const TUN_MODE_POINTOPOINT: u32 = 1;
+
+/// Largest IP packet carried.
+pub const MAX_PACKET_SIZE: usize = 65535;
+
+/// Address families in the channel data (OpenBSD values, whatever the OS).
+const OPENBSD_AF_INET: u32 = 2;
+const OPENBSD_AF_INET6: u32 = 24;
+
+/// Destinations whose decision is remembered per tunnel.
+const MAX_CACHED_DECISIONS: usize = 4096;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// `reason` of the `acl.deny` audit event of a packet the ACL refuses.
+pub const ACL_DENY_REASON: &str = "vpn";
+
+/// Addresses of an IPv4 packet, and its destination port for TCP and UDP
+/// (0 otherwise).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketInfo {
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub protocol: u8,
+    pub port: u16,
+}
+
+/// Parse the header of an IPv4 packet. None for IPv6 and malformed packets.
+pub fn parse_packet(packet: &[u8]) -> Option<PacketInfo> {
+    if packet.len() < 20 || packet[0] >> 4 != 4 {
+        return None;
+    }
+    let header_len = usize::from(packet[0] & 0x0f) * 4;
+    if header_len < 20 || packet.len() < header_len {
+        return None;
+    }
+    let protocol = packet[9];
+    let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    let destination = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+    // Only the first fragment carries the ports; others are matched as port 0
+    let first_fragment = u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff == 0;
+    let port = match protocol {
+        IPPROTO_TCP | IPPROTO_UDP if first_fragment => packet
+            .get(header_len + 2..header_len + 4)
+            .map_or(0, |p| u16::from_be_bytes([p[0], p[1]])),
+        _ => 0,
+    };
+    Some(PacketInfo {
+        source,
+        destination,
+        protocol,
+        port,
+    })
+}
+
+/// Append `packet` to `out` as the data of one channel message.
+pub fn encode_packet(packet: &[u8], out: &mut Vec<u8>) {
+    let af = if packet.first().is_some_and(|b| b >> 4 == 6) {
+        OPENBSD_AF_INET6
+    } else {
+        OPENBSD_AF_INET
+    };
+    out.extend_from_slice(&af.to_be_bytes());
+    out.extend_from_slice(packet);
+}
+
+/// Splits channel data into the IP packets it carries. Packets are
+/// delimited by the length in their IP header, so a message read in
+/// several parts is put back together.
+#[derive(Debug, Default)]
+pub struct PacketDecoder {
+    buf: Vec<u8>,
+}
+
+impl PacketDecoder {
+    /// Add `data` received on the channel; the packets it completes.
+    pub fn push(&mut self, data: &[u8]) -> std::io::Result<Vec<Vec<u8>>> {
+        self.buf.extend_from_slice(data);
+        let mut packets = Vec::new();
+        let mut pos = 0;
+        while let Some(header) = self.buf.get(pos..pos + 10) {
+            let af = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let len = match (af, header[4] >> 4) {
+                (OPENBSD_AF_INET, 4) => usize::from(u16::from_be_bytes([header[6], header[7]])),
+                (OPENBSD_AF_INET6, 6) => {
+                    40 + usize::from(u16::from_be_bytes([header[8], header[9]]))
+                }
+                _ => 0,
+            };
+            if len < 20 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("malformed tunnel packet (address family {af})"),
+                ));
+            }
+            let Some(packet) = self.buf.get(pos + 4..pos + 4 + len) else {
+                break;
+            };
+            packets.push(packet.to_vec());
+            pos += 4 + len;
+        }
+        self.buf.drain(..pos);
+        Ok(packets)
+    }
+}
+
+/// Whether `address` can be the client end of a tunnel in `pool`: one of
+/// its hosts, other than the first (the server end).
+pub fn is_client_address(pool: &Ipv4Net, address: Ipv4Addr) -> bool {
+    pool.contains(&address)
+        && address != pool.network()
+        && address != pool.broadcast()
+        && pool.hosts().next() != Some(address)
+}
+
+/// Tunnel addresses of `vpn.pool` (startup-only).
+pub struct AddressPool {
+    network: Ipv4Net,
+    /// Users' `vpn_address`, never handed out to others
+    reserved: HashSet<Ipv4Addr>,
+    leased: Arc<Mutex<HashSet<Ipv4Addr>>>,
+}
+
+impl AddressPool {
+    pub fn new(network: Ipv4Net, reserved: impl IntoIterator<Item = Ipv4Addr>) -> Self {
+        Self {
+            network,
+            reserved: reserved.into_iter().collect(),
+            leased: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Server end of every tunnel: the first address of the pool.
+    pub fn server_address(&self) -> Ipv4Addr {
+        self.network.hosts().next().unwrap_or(self.network.addr())
+    }
+
+    /// Lease `fixed`, or the first free address that is not reserved. None
+    /// when it is taken (or outside the pool), or the pool is exhausted.
+    pub fn lease(&self, fixed: Option<Ipv4Addr>) -> Option<Lease> {
+        let server = self.server_address();
+        let mut leased = self.leased.lock().unwrap_or_else(|e| e.into_inner());
+        let address = match fixed {
+            Some(address) => Some(address)
+                .filter(|a| is_client_address(&self.network, *a) && !leased.contains(a))?,
+            None => self
+                .network
+                .hosts()
+                .find(|a| *a != server && !self.reserved.contains(a) && !leased.contains(a))?,
+        };
+        leased.insert(address);
+        Some(Lease {
+            address,
+            leased: self.leased.clone(),
+        })
+    }
+}
+
+/// Client address of a tunnel, returned to the pool when dropped.
+pub struct Lease {
+    address: Ipv4Addr,
+    leased: Arc<Mutex<HashSet<Ipv4Addr>>>,
+}
+
+impl Lease {
+    pub fn address(&self) -> Ipv4Addr {
+        self.address
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.leased
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.address);
+    }
+}
+
+/// Decides which packets of a tunnel reach the network.
+pub struct PacketFilter {
+    pub username: String,
+    /// Leased address, the only source accepted
+    pub client: Ipv4Addr,
+    pub acl: ParsedAcl,
+    pub ip_guard_enabled: bool,
+    pub ip_guard_exemptions: Vec<IpNet>,
+    /// Refused TCP and UDP ports
+    pub blocked_ports: Vec<u16>,
+    pub audit: Option<Arc<AuditLogger>>,
+    pub source_ip: String,
+    pub correlation_id: String,
+    decisions: HashMap<(Ipv4Addr, u8, u16), bool>,
+}
+
+impl PacketFilter {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        username: &str,
+        client: Ipv4Addr,
+        acl: ParsedAcl,
+        ip_guard_enabled: bool,
+        ip_guard_exemptions: Vec<IpNet>,
+        blocked_ports: Vec<u16>,
+        audit: Option<Arc<AuditLogger>>,
+        source_ip: &str,
+        correlation_id: &str,
+    ) -> Self {
+        Self {
+            username: username.to_string(),
+            client,
+            acl,
+            ip_guard_enabled,
+            ip_guard_exemptions,
+            blocked_ports,
+            audit,
+            source_ip: source_ip.to_string(),
+            correlation_id: correlation_id.to_string(),
+            decisions: HashMap::new(),
+        }
+    }
+
+    /// Whether `packet`, sent by the client, may be routed.
+    pub fn allows(&mut self, packet: &[u8]) -> bool {
+        let Some(info) = parse_packet(packet) else {
+            return false;
+        };
+        if info.source != self.client {
+            return false;
+        }
+        let key = (info.destination, info.protocol, info.port);
+        if let Some(&allowed) = self.decisions.get(&key) {
+            return allowed;
+        }
+        let allowed = self.decide(&info);
+        if self.decisions.len() >= MAX_CACHED_DECISIONS {
+            self.decisions.clear();
+        }
+        self.decisions.insert(key, allowed);
+        allowed
+    }
+
+    fn decide(&self, info: &PacketInfo) -> bool {
+        let ip = IpAddr::V4(info.destination);
+        let host = info.destination.to_string();
+        let port = info.port;
+        let refusal = if matches!(info.protocol, IPPROTO_TCP | IPPROTO_UDP)
+            && connector::check_port(port, &self.blocked_ports).is_err()
+        {
+            Some((None, connector::PORT_BLOCKED_REASON))
+        } else if let Some(range) = self
+            .ip_guard_enabled
+            .then(|| ip_guard::classify_guarded_ip(&ip, &self.ip_guard_exemptions))
+            .flatten()
+        {
+            Some((Some(range.to_string()), ip_guard::ACL_DENY_REASON))
+        } else {
+            match self.acl.check_verbose(&host, port, Some(ip)) {
+                (AclPolicy::Allow, _) => None,
+                (AclPolicy::Deny, matched_rule) => Some((matched_rule, ACL_DENY_REASON)),
+            }
+        };
+        let Some((matched_rule, reason)) = refusal else {
+            return true;
+        };
+        tracing::warn!(
+            conn_id = %self.correlation_id,
+            user = %self.username,
+            destination = %host,
+            protocol = info.protocol,
+            port = port,
+            reason = %reason,
+            "VPN packet dropped"
+        );
+        if let Some(audit) = &self.audit {
+            audit.log_acl_deny_cid(
+                &self.username,
+                &host,
+                port,
+                Some(host.clone()),
+                &self.source_ip,
+                matched_rule,
+                reason,
+                &self.correlation_id,
+            );
+        }
+        false
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use device::{ChannelEnd, Device, TunStream};
+
+#[cfg(target_os = "linux")]
+mod device {
+    use super::{encode_packet, PacketDecoder, PacketFilter, MAX_PACKET_SIZE};
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::net::Ipv4Addr;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{ready, Context, Poll, Waker};
+    use tokio::io::unix::AsyncFd;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    /// A TUN interface, removed when dropped.
+    pub struct Device {
+        file: File,
+        name: String,
+    }
+
+    impl Device {
+        /// Create an `s5tunN` interface from `server` to `client` and bring
+        /// it up (needs CAP_NET_ADMIN).
+        pub fn open(server: Ipv4Addr, client: Ipv4Addr, mtu: u16) -> io::Result<Self> {
+            let fd = unsafe {
+                libc::open(
+                    c"/dev/net/tun".as_ptr(),
+                    libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+
+            let mut req = ifreq("s5tun%d");
+            req.ifr_ifru.ifru_flags = (libc::IFF_TUN | libc::IFF_NO_PI) as libc::c_short;
+            ioctl(file.as_raw_fd(), libc::TUNSETIFF as _, &mut req)?;
+            let name = unsafe { std::ffi::CStr::from_ptr(req.ifr_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned();
+
+            let socket = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+            if socket < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = unsafe { OwnedFd::from_raw_fd(socket) };
+            let fd = socket.as_raw_fd();
+            let mut req = ifreq(&name);
+            req.ifr_ifru.ifru_addr = sockaddr(server);
+            ioctl(fd, libc::SIOCSIFADDR as _, &mut req)?;
+            req.ifr_ifru.ifru_dstaddr = sockaddr(client);
+            ioctl(fd, libc::SIOCSIFDSTADDR as _, &mut req)?;
+            req.ifr_ifru.ifru_mtu = libc::c_int::from(mtu);
+            ioctl(fd, libc::SIOCSIFMTU as _, &mut req)?;
+            ioctl(fd, libc::SIOCGIFFLAGS as _, &mut req)?;
+            unsafe {
+                req.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+            }
+            ioctl(fd, libc::SIOCSIFFLAGS as _, &mut req)?;
+            Ok(Self { file, name })
+        }
+
+        /// Interface name (`s5tun0`...).
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    fn ifreq(name: &str) -> libc::ifreq {
+        let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+        for (dst, src) in req
+            .ifr_name
+            .iter_mut()
+            .zip(name.bytes().take(libc::IFNAMSIZ - 1))
+        {
+            *dst = src as libc::c_char;
+        }
+        req
+    }
+
+    fn sockaddr(ip: Ipv4Addr) -> libc::sockaddr {
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: 0,
+            sin_addr: libc::in_addr {
+                s_addr: u32::from(ip).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        unsafe { std::mem::transmute::<libc::sockaddr_in, libc::sockaddr>(addr) }
+    }
+
+    fn ioctl(fd: libc::c_int, request: libc::Ioctl, req: &mut libc::ifreq) -> io::Result<()> {
+        if unsafe { libc::ioctl(fd, request, req as *mut libc::ifreq) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// A tunnel as a byte stream of channel data, for the relay: writes are
+    /// the client's packets, filtered then sent to the interface; reads are
+    /// the packets routed to the client, one per read (a relay buffer holds
+    /// `vpn.mtu` + 4 bytes), until the channel ends.
+    pub struct TunStream {
+        device: AsyncFd<File>,
+        filter: PacketFilter,
+        packets: PacketDecoder,
+        /// Packet not yet read by the relay, with its address family
+        pending: Vec<u8>,
+        pending_pos: usize,
+        packet: Box<[u8]>,
+        hangup: Arc<Hangup>,
+    }
+
+    /// Set once the channel reaches EOF: the interface has no end of its
+    /// own, so this ends the tunnel's reads.
+    #[derive(Default)]
+    struct Hangup {
+        done: AtomicBool,
+        reader: Mutex<Option<Waker>>,
+    }
+
+    impl Hangup {
+        fn close(&self) {
+            self.done.store(true, Ordering::Release);
+            if let Some(waker) = self.reader.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// The channel side of a tunnel: its EOF ends the [`TunStream`] reads.
+    pub struct ChannelEnd<S> {
+        inner: S,
+        hangup: Arc<Hangup>,
+    }
+
+    impl TunStream {
+        /// The tunnel of `device`, with `channel` wrapped to end it.
+        pub fn new<S>(
+            device: Device,
+            filter: PacketFilter,
+            channel: S,
+        ) -> io::Result<(ChannelEnd<S>, Self)> {
+            let hangup = Arc::new(Hangup::default());
+            let tunnel = Self {
+                device: AsyncFd::new(device.file)?,
+                filter,
+                packets: PacketDecoder::default(),
+                pending: Vec::new(),
+                pending_pos: 0,
+                packet: vec![0; MAX_PACKET_SIZE].into_boxed_slice(),
+                hangup: hangup.clone(),
+            };
+            Ok((
+                ChannelEnd {
+                    inner: channel,
+                    hangup,
+                },
+                tunnel,
+            ))
+        }
+    }
+
+    impl AsyncRead for TunStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            while this.pending_pos == this.pending.len() {
+                *this.hangup.reader.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(cx.waker().clone());
+                if this.hangup.done.load(Ordering::Acquire) {
+                    return Poll::Ready(Ok(()));
+                }
+                let mut guard = ready!(this.device.poll_read_ready(cx))?;
+                match guard.try_io(|device| device.get_ref().read(&mut this.packet)) {
+                    Ok(Ok(len)) => {
+                        this.pending.clear();
+                        this.pending_pos = 0;
+                        encode_packet(&this.packet[..len], &mut this.pending);
+                    }
+                    Ok(Err(e)) => return Poll::Ready(Err(e)),
+                    Err(_would_block) => continue,
+                }
+            }
+            let len = buf.remaining().min(this.pending.len() - this.pending_pos);
+            buf.put_slice(&this.pending[this.pending_pos..this.pending_pos + len]);
+            this.pending_pos += len;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<S: AsyncRead + Unpin> AsyncRead for ChannelEnd<S> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            let filled = buf.filled().len();
+            let result = ready!(Pin::new(&mut this.inner).poll_read(cx, buf));
+            if result.is_err() || (buf.filled().len() == filled && buf.remaining() > 0) {
+                this.hangup.close();
+            }
+            Poll::Ready(result)
+        }
+    }
+
+    impl<S: AsyncWrite + Unpin> AsyncWrite for ChannelEnd<S> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+        }
+    }
+
+    impl AsyncWrite for TunStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            for packet in this.packets.push(buf)? {
+                if this.filter.allows(&packet) {
+                    // Like a full interface queue, a refused write drops the packet
+                    let _ = this.device.get_ref().write(&packet);
+                }
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
@@ -1,10 +1,12 @@
 pub mod bandwidth;
+pub mod plan;
 pub mod rolling_window;
 
 pub use crate::config::types::QuotaConfig;
 use crate::config::types::{LimitsConfig, RateLimitsConfig};
 use dashmap::DashMap;
 use rolling_window::RollingWindow;
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -43,6 +45,14 @@ pub struct UserBandwidthState {
     total_bytes: AtomicU64,
     /// Last activity timestamp (for cleanup).
     last_activity: AtomicU64,
+    /// One-off allowances on top of the configured limits (`PATCH
+    /// /api/users/{name}/quota`); the daily and monthly ones lapse with
+    /// their counter.
+    extra_daily_bytes: AtomicU64,
+    extra_monthly_bytes: AtomicU64,
+    extra_total_bytes: AtomicU64,
+    extra_daily_connections: AtomicU32,
+    extra_monthly_connections: AtomicU32,
 }
 
 impl UserBandwidthState {
@@ -62,6 +72,11 @@ impl UserBandwidthState {
             conn_per_hour: RollingWindow::new(3600, 60),
             total_bytes: AtomicU64::new(0),
             last_activity: AtomicU64::new(now),
+            extra_daily_bytes: AtomicU64::new(0),
+            extra_monthly_bytes: AtomicU64::new(0),
+            extra_total_bytes: AtomicU64::new(0),
+            extra_daily_connections: AtomicU32::new(0),
+            extra_monthly_connections: AtomicU32::new(0),
         }
     }
 
@@ -74,6 +89,8 @@ impl UserBandwidthState {
         if now >= daily {
             self.daily_bytes.store(0, Ordering::Relaxed);
             self.daily_connections.store(0, Ordering::Relaxed);
+            self.extra_daily_bytes.store(0, Ordering::Relaxed);
+            self.extra_daily_connections.store(0, Ordering::Relaxed);
             self.daily_reset
                 .store(next_day_boundary(now), Ordering::Release);
         }
@@ -83,10 +100,58 @@ impl UserBandwidthState {
         if now >= monthly {
             self.monthly_bytes.store(0, Ordering::Relaxed);
             self.monthly_connections.store(0, Ordering::Relaxed);
+            self.extra_monthly_bytes.store(0, Ordering::Relaxed);
+            self.extra_monthly_connections.store(0, Ordering::Relaxed);
             self.monthly_reset
                 .store(next_month_boundary(now), Ordering::Release);
         }
     }
+
+    /// The bandwidth quota `q` this user has used up, if any. Granted extras
+    /// raise the daily, monthly and total limits.
+    fn bandwidth_exceeded(&self, q: &QuotaConfig) -> Option<&'static str> {
+        let over = |used: &AtomicU64, limit: u64, extra: &AtomicU64| {
+            limit > 0
+                && used.load(Ordering::Relaxed)
+                    >= limit.saturating_add(extra.load(Ordering::Relaxed))
+        };
+        if over(
+            &self.total_bytes,
+            q.total_bandwidth_bytes,
+            &self.extra_total_bytes,
+        ) {
+            return Some("total bandwidth quota exceeded");
+        }
+        if over(
+            &self.daily_bytes,
+            q.daily_bandwidth_bytes,
+            &self.extra_daily_bytes,
+        ) {
+            return Some("daily bandwidth quota exceeded");
+        }
+        if over(
+            &self.monthly_bytes,
+            q.monthly_bandwidth_bytes,
+            &self.extra_monthly_bytes,
+        ) {
+            return Some("monthly bandwidth quota exceeded");
+        }
+        if q.bandwidth_per_hour_bytes > 0 && self.hour_window.sum() >= q.bandwidth_per_hour_bytes {
+            return Some("hourly bandwidth quota exceeded");
+        }
+        None
+    }
+
+    /// Current one-off allowances.
+    fn grant(&self) -> QuotaGrant {
+        QuotaGrant {
+            daily_bytes: self.extra_daily_bytes.load(Ordering::Relaxed),
+            monthly_bytes: self.extra_monthly_bytes.load(Ordering::Relaxed),
+            total_bytes: self.extra_total_bytes.load(Ordering::Relaxed),
+            daily_connections: self.extra_daily_connections.load(Ordering::Relaxed),
+            monthly_connections: self.extra_monthly_connections.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Usage snapshot for a user (returned by get_user_usage).
@@ -99,6 +164,74 @@ pub struct UserQuotaUsage {
     pub current_rate_bps: u64,
     pub hourly_bytes: u64,
     pub total_bytes: u64,
+    /// One-off allowances on top of the configured limits
+    pub granted: QuotaGrant,
+}
+
+impl UserQuotaUsage {
+    /// The cumulative counters, without rates and grants.
+    pub fn counters(&self) -> UsageCounters {
+        UsageCounters {
+            daily_bytes: self.daily_bytes,
+            daily_connections: self.daily_connections,
+            monthly_bytes: self.monthly_bytes,
+            monthly_connections: self.monthly_connections,
+            total_bytes: self.total_bytes,
+        }
+    }
+}
+
+/// A user's cumulative counters (shared between cluster nodes).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageCounters {
+    pub daily_bytes: u64,
+    pub daily_connections: u32,
+    pub monthly_bytes: u64,
+    pub monthly_connections: u32,
+    pub total_bytes: u64,
+}
+
+/// One-off extra allowance on top of a user's configured quotas. Daily and
+/// monthly extras lapse at that counter's next day or month boundary; none
+/// of them survive a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaGrant {
+    pub daily_bytes: u64,
+    pub monthly_bytes: u64,
+    pub total_bytes: u64,
+    pub daily_connections: u32,
+    pub monthly_connections: u32,
+}
+
+impl QuotaGrant {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A usage counter that can be reset on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaCounter {
+    HourlyBytes,
+    DailyBytes,
+    MonthlyBytes,
+    TotalBytes,
+    DailyConnections,
+    MonthlyConnections,
+}
+
+impl QuotaCounter {
+    /// Every counter: [`QuotaTracker::reset_user`]'s plus the hourly window.
+    pub const ALL: [QuotaCounter; 6] = [
+        Self::HourlyBytes,
+        Self::DailyBytes,
+        Self::MonthlyBytes,
+        Self::TotalBytes,
+        Self::DailyConnections,
+        Self::MonthlyConnections,
+    ];
 }
 
 /// Central quota and rate-limiting tracker.
@@ -206,14 +339,23 @@ impl QuotaTracker {
 
         // Check cumulative quotas before accepting
         if let Some(q) = quotas {
-            if q.daily_connection_limit > 0
-                && state.daily_connections.load(Ordering::Relaxed) >= q.daily_connection_limit
-            {
+            let over = |used: &AtomicU32, limit: u32, extra: &AtomicU32| {
+                limit > 0
+                    && used.load(Ordering::Relaxed)
+                        >= limit.saturating_add(extra.load(Ordering::Relaxed))
+            };
+            if over(
+                &state.daily_connections,
+                q.daily_connection_limit,
+                &state.extra_daily_connections,
+            ) {
                 return Err("daily connection quota exceeded".to_string());
             }
-            if q.monthly_connection_limit > 0
-                && state.monthly_connections.load(Ordering::Relaxed) >= q.monthly_connection_limit
-            {
+            if over(
+                &state.monthly_connections,
+                q.monthly_connection_limit,
+                &state.extra_monthly_connections,
+            ) {
                 return Err("monthly connection quota exceeded".to_string());
             }
         }
@@ -250,25 +392,10 @@ impl QuotaTracker {
         let state = self.get_user(username);
         state.lazy_reset();
 
-        if q.total_bandwidth_bytes > 0
-            && state.total_bytes.load(Ordering::Relaxed) >= q.total_bandwidth_bytes
-        {
-            return Err("total bandwidth quota exceeded".to_string());
-        }
-        if q.daily_bandwidth_bytes > 0
-            && state.daily_bytes.load(Ordering::Relaxed) >= q.daily_bandwidth_bytes
-        {
-            return Err("daily bandwidth quota exceeded".to_string());
-        }
-        if q.monthly_bandwidth_bytes > 0
-            && state.monthly_bytes.load(Ordering::Relaxed) >= q.monthly_bandwidth_bytes
-        {
-            return Err("monthly bandwidth quota exceeded".to_string());
+        match state.bandwidth_exceeded(q) {
+            Some(reason) => Err(reason.to_string()),
+            None => Ok(()),
         }
-        if q.bandwidth_per_hour_bytes > 0 && state.hour_window.sum() >= q.bandwidth_per_hour_bytes {
-            return Err("hourly bandwidth quota exceeded".to_string());
-        }
-        Ok(())
     }
 
     /// Record bytes transferred for a user. Returns Ok(delay) or QuotaExceeded.
@@ -297,27 +424,8 @@ impl QuotaTracker {
         state.total_bytes.fetch_add(bytes, Ordering::Relaxed);
 
         // Check cumulative quotas
-        if let Some(q) = quotas {
-            if q.total_bandwidth_bytes > 0
-                && state.total_bytes.load(Ordering::Relaxed) >= q.total_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("total bandwidth quota exceeded".to_string());
-            }
-            if q.daily_bandwidth_bytes > 0
-                && state.daily_bytes.load(Ordering::Relaxed) >= q.daily_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("daily bandwidth quota exceeded".to_string());
-            }
-            if q.monthly_bandwidth_bytes > 0
-                && state.monthly_bytes.load(Ordering::Relaxed) >= q.monthly_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("monthly bandwidth quota exceeded".to_string());
-            }
-            if q.bandwidth_per_hour_bytes > 0
-                && state.hour_window.sum() >= q.bandwidth_per_hour_bytes
-            {
-                return QuotaResult::Exceeded("hourly bandwidth quota exceeded".to_string());
-            }
+        if let Some(reason) = quotas.and_then(|q| state.bandwidth_exceeded(q)) {
+            return QuotaResult::Exceeded(reason.to_string());
         }
 
         // Compute throttle delay
@@ -359,27 +467,8 @@ impl QuotaTracker {
         state.monthly_bytes.fetch_add(bytes, Ordering::Relaxed);
         state.total_bytes.fetch_add(bytes, Ordering::Relaxed);
 
-        if let Some(q) = quotas {
-            if q.total_bandwidth_bytes > 0
-                && state.total_bytes.load(Ordering::Relaxed) >= q.total_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("total bandwidth quota exceeded".to_string());
-            }
-            if q.daily_bandwidth_bytes > 0
-                && state.daily_bytes.load(Ordering::Relaxed) >= q.daily_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("daily bandwidth quota exceeded".to_string());
-            }
-            if q.monthly_bandwidth_bytes > 0
-                && state.monthly_bytes.load(Ordering::Relaxed) >= q.monthly_bandwidth_bytes
-            {
-                return QuotaResult::Exceeded("monthly bandwidth quota exceeded".to_string());
-            }
-            if q.bandwidth_per_hour_bytes > 0
-                && state.hour_window.sum() >= q.bandwidth_per_hour_bytes
-            {
-                return QuotaResult::Exceeded("hourly bandwidth quota exceeded".to_string());
-            }
+        if let Some(reason) = quotas.and_then(|q| state.bandwidth_exceeded(q)) {
+            return QuotaResult::Exceeded(reason.to_string());
         }
 
         let aggregate_limit_bps = aggregate_limit_kbps * 1000 / 8;
@@ -411,6 +500,7 @@ impl QuotaTracker {
             current_rate_bps: state.second_window.sum(),
             hourly_bytes: state.hour_window.sum(),
             total_bytes: state.total_bytes.load(Ordering::Relaxed),
+            granted: state.grant(),
         }
     }
 
@@ -441,6 +531,51 @@ impl QuotaTracker {
         }
     }
 
+    /// Reset some of a user's counters (admin action), e.g. after a
+    /// false-positive burn. Granted extras are kept.
+    pub fn reset_counters(&self, username: &str, counters: &[QuotaCounter]) {
+        let Some(state) = self.user_state.get(username) else {
+            return;
+        };
+        for counter in counters {
+            match counter {
+                QuotaCounter::HourlyBytes => state.hour_window.clear(),
+                QuotaCounter::DailyBytes => state.daily_bytes.store(0, Ordering::Relaxed),
+                QuotaCounter::MonthlyBytes => state.monthly_bytes.store(0, Ordering::Relaxed),
+                QuotaCounter::TotalBytes => state.total_bytes.store(0, Ordering::Relaxed),
+                QuotaCounter::DailyConnections => {
+                    state.daily_connections.store(0, Ordering::Relaxed)
+                }
+                QuotaCounter::MonthlyConnections => {
+                    state.monthly_connections.store(0, Ordering::Relaxed)
+                }
+            }
+        }
+    }
+
+    /// Add `grant` to a user's one-off allowances (admin action) and return
+    /// the allowances now in effect.
+    pub fn grant_user(&self, username: &str, grant: &QuotaGrant) -> QuotaGrant {
+        let state = self.get_user(username);
+        state.lazy_reset();
+        let add64 = |a: &AtomicU64, n: u64| {
+            let _ = a.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_add(n))
+            });
+        };
+        let add32 = |a: &AtomicU32, n: u32| {
+            let _ = a.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_add(n))
+            });
+        };
+        add64(&state.extra_daily_bytes, grant.daily_bytes);
+        add64(&state.extra_monthly_bytes, grant.monthly_bytes);
+        add64(&state.extra_total_bytes, grant.total_bytes);
+        add32(&state.extra_daily_connections, grant.daily_connections);
+        add32(&state.extra_monthly_connections, grant.monthly_connections);
+        state.grant()
+    }
+
     /// Restore user usage from a backup (admin action).
     pub fn restore_user_usage(
         &self,
@@ -463,6 +598,35 @@ impl QuotaTracker {
         state.total_bytes.store(total_bytes, Ordering::Relaxed);
     }
 
+    /// Move a user's counters from `from`, read earlier, to `to` (cluster
+    /// sync). Traffic recorded since `from` was read is kept on top.
+    pub fn rebase_user_usage(&self, username: &str, from: &UsageCounters, to: &UsageCounters) {
+        let state = self.get_user(username);
+        let rebase64 = |a: &AtomicU64, from: u64, to: u64| {
+            let _ = a.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_sub(from).saturating_add(to))
+            });
+        };
+        let rebase32 = |a: &AtomicU32, from: u32, to: u32| {
+            let _ = a.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_sub(from).saturating_add(to))
+            });
+        };
+        rebase64(&state.daily_bytes, from.daily_bytes, to.daily_bytes);
+        rebase32(
+            &state.daily_connections,
+            from.daily_connections,
+            to.daily_connections,
+        );
+        rebase64(&state.monthly_bytes, from.monthly_bytes, to.monthly_bytes);
+        rebase32(
+            &state.monthly_connections,
+            from.monthly_connections,
+            to.monthly_connections,
+        );
+        rebase64(&state.total_bytes, from.total_bytes, to.total_bytes);
+    }
+
     /// Get all usernames with tracked state.
     pub fn tracked_users(&self) -> Vec<String> {
         self.user_state.iter().map(|e| e.key().clone()).collect()
@@ -0,0 +1,301 @@
+//! Named quota plans (`[quota_plans]`) and their scheduled resets.
+//!
+//! A plan is a `[users.quotas]` block with a name, assigned with
+//! `quota_plan = "<name>"` on users or groups. A plan with a
+//! `reset_schedule` zeroes its members' counters at that time, e.g. on the
+//! customer's billing day instead of the first of the month. Time zones are
+//! IANA names (`"Europe/Paris"`), whose daylight saving rules apply to each
+//! date, or fixed UTC offsets (`"+02:00"`).
+
+use super::{QuotaCounter, QuotaTracker};
+use crate::audit::AuditLogger;
+use crate::auth::AuthService;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How often the scheduler looks for due resets.
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Which days a reset falls on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recurrence {
+    Daily,
+    Weekly(Weekday),
+    /// Day of month; months without that day reset on their last day
+    Monthly(u32),
+}
+
+/// A parsed `reset_schedule` in its `reset_timezone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetSchedule {
+    recurrence: Recurrence,
+    time: NaiveTime,
+    zone: Zone,
+}
+
+/// A time zone setting: an IANA zone or a fixed UTC offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Zone {
+    /// Wall-clock time at `t`.
+    pub fn local(&self, t: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Fixed(offset) => t.with_timezone(offset).naive_local(),
+            Zone::Named(tz) => t.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// The instant the clocks show `local`. The first one when the hour is
+    /// repeated (end of DST); when it is skipped (start of DST), the instant
+    /// the same time after the shift, e.g. 03:30 for 02:30.
+    pub fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let resolve = |local: NaiveDateTime| match self {
+            Zone::Fixed(offset) => earliest(offset.from_local_datetime(&local)),
+            Zone::Named(tz) => earliest(tz.from_local_datetime(&local)),
+        };
+        resolve(local).or_else(|| resolve(local + Duration::hours(1)))
+    }
+}
+
+fn earliest<T: TimeZone>(result: LocalResult<DateTime<T>>) -> Option<DateTime<Utc>> {
+    result.earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// Parse a timezone: `"UTC"`, `"Z"`, an IANA name such as `"Europe/Paris"` or
+/// an offset such as `"+05:30"`.
+pub fn parse_timezone(tz: &str) -> Result<Zone, String> {
+    let tz = tz.trim();
+    if tz.eq_ignore_ascii_case("utc") || tz.eq_ignore_ascii_case("z") {
+        return Ok(Zone::Fixed(
+            FixedOffset::east_opt(0).expect("zero offset is valid"),
+        ));
+    }
+    let invalid = || {
+        format!(
+            "invalid timezone '{}' (expected UTC, an IANA name like Europe/Paris \
+             or an offset like +02:00)",
+            tz
+        )
+    };
+    let (sign, rest) = match tz.as_bytes().first() {
+        Some(b'+') => (1, &tz[1..]),
+        Some(b'-') => (-1, &tz[1..]),
+        _ => return tz.parse::<Tz>().map(Zone::Named).map_err(|_| invalid()),
+    };
+    let (h, m) = rest.split_once(':').ok_or_else(invalid)?;
+    let h: i32 = h.parse().map_err(|_| invalid())?;
+    let m: i32 = m.parse().map_err(|_| invalid())?;
+    if h > 14 || m > 59 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+        .map(Zone::Fixed)
+        .ok_or_else(invalid)
+}
+
+impl ResetSchedule {
+    /// Parse `schedule` (`"daily 06:00"`, `"mon 00:00"`, `"monthly 15 00:00"`)
+    /// in `timezone`.
+    pub fn parse(schedule: &str, timezone: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid reset_schedule '{}' (expected \"daily HH:MM\", \"<day> HH:MM\" \
+                 or \"monthly <1-31> HH:MM\")",
+                schedule
+            )
+        };
+        let parts: Vec<&str> = schedule.split_whitespace().collect();
+        let (recurrence, time) = match parts.as_slice() {
+            [day, time] => {
+                let recurrence = if day.eq_ignore_ascii_case("daily") {
+                    Recurrence::Daily
+                } else {
+                    Recurrence::Weekly(day.parse::<Weekday>().map_err(|_| invalid())?)
+                };
+                (recurrence, *time)
+            }
+            [monthly, day, time] if monthly.eq_ignore_ascii_case("monthly") => {
+                let day: u32 = day.parse().map_err(|_| invalid())?;
+                if !(1..=31).contains(&day) {
+                    return Err(invalid());
+                }
+                (Recurrence::Monthly(day), *time)
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            recurrence,
+            time: NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid())?,
+            zone: parse_timezone(timezone)?,
+        })
+    }
+
+    fn falls_on(&self, date: NaiveDate) -> bool {
+        match self.recurrence {
+            Recurrence::Daily => true,
+            Recurrence::Weekly(day) => date.weekday() == day,
+            Recurrence::Monthly(day) => date.day() == day.min(last_day_of_month(date)),
+        }
+    }
+
+    /// First reset strictly after `t`.
+    pub fn next_after(&self, t: DateTime<Utc>) -> DateTime<Utc> {
+        let mut date = self.zone.local(t).date();
+        // A monthly reset is at most 31 days away
+        for _ in 0..=32 {
+            if self.falls_on(date) {
+                if let Some(at) = self.zone.to_utc(date.and_time(self.time)) {
+                    if at > t {
+                        return at;
+                    }
+                }
+            }
+            date = date.succ_opt().unwrap_or(date);
+        }
+        t + Duration::days(1)
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> u32 {
+    let (y, m) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(y, m, 1)
+        .and_then(|d| d.pred_opt())
+        .map_or(28, |d| d.day())
+}
+
+/// Reset the counters of members of every plan whose schedule came due in
+/// `(since, now]`. Returns how many users were reset.
+pub fn run_due_resets(
+    auth: &AuthService,
+    tracker: &QuotaTracker,
+    audit: Option<&AuditLogger>,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> usize {
+    let mut reset = 0;
+    for (name, plan) in auth.quota_plans() {
+        let Some(schedule) = plan.schedule() else {
+            continue;
+        };
+        if schedule.next_after(since) > now {
+            continue;
+        }
+        let counters = if plan.reset_counters.is_empty() {
+            QuotaCounter::ALL.to_vec()
+        } else {
+            plan.reset_counters.clone()
+        };
+        let by = format!("plan:{}", name);
+        let store = auth.user_store();
+        let mut members = 0;
+        for username in store.usernames() {
+            let on_plan = store
+                .get(&username)
+                .is_some_and(|u| u.quota_plan.as_deref() == Some(name.as_str()));
+            if !on_plan {
+                continue;
+            }
+            tracker.reset_counters(&username, &counters);
+            if let Some(audit) = audit {
+                audit.log_event(crate::audit::events::AuditEvent::quota_reset(
+                    &username, &counters, &by,
+                ));
+            }
+            members += 1;
+        }
+        info!(plan = %name, users = members, "Scheduled quota reset");
+        reset += members;
+    }
+    reset
+}
+
+/// The `limits.quota_reset_state_path` file.
+#[derive(Debug, Serialize, Deserialize)]
+struct ResetState {
+    /// Resets due up to this time have been run
+    applied_until: DateTime<Utc>,
+}
+
+/// When resets were last applied, from `path`. None if it is missing or
+/// unreadable (logged).
+pub fn load_applied_until(path: &Path) -> Option<DateTime<Utc>> {
+    match std::fs::read(path) {
+        Ok(bytes) => match serde_json::from_slice::<ResetState>(&bytes) {
+            Ok(state) => Some(state.applied_until),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Ignoring unreadable quota reset state");
+                None
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read quota reset state");
+            None
+        }
+    }
+}
+
+/// Write through a temporary file so a crash never leaves half a state.
+pub fn save_applied_until(path: &Path, applied_until: DateTime<Utc>) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(&ResetState { applied_until })?)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Run [`run_due_resets`] every [`CHECK_INTERVAL_SECS`] until `shutdown`.
+/// Plans are read on each check, so reloads apply without a restart.
+///
+/// With a `state_path`, the first check starts from the time saved there,
+/// so resets that fell due while the server was down run at startup, and
+/// each check saves its time.
+pub fn spawn_scheduler(
+    auth: Arc<RwLock<AuthService>>,
+    tracker: Arc<QuotaTracker>,
+    audit: Arc<AuditLogger>,
+    state_path: Option<PathBuf>,
+    shutdown: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(CHECK_INTERVAL_SECS));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut since = state_path
+            .as_deref()
+            .and_then(load_applied_until)
+            .unwrap_or_else(Utc::now);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                // The first tick is immediate: overdue resets run at startup
+                _ = interval.tick() => {
+                    let now = Utc::now();
+                    let auth = auth.read().await;
+                    run_due_resets(&auth, &tracker, Some(&audit), since, now);
+                    since = now;
+                    if let Some(path) = state_path.as_deref() {
+                        if let Err(e) = save_applied_until(path, now) {
+                            warn!(path = %path.display(), error = %e, "Failed to save quota reset state");
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
@@ -51,6 +51,14 @@ impl RollingWindow {
         self.cached_sum.load(Ordering::Relaxed)
     }
 
+    /// Forget everything recorded so far.
+    pub fn clear(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.cached_sum.store(0, Ordering::Relaxed);
+    }
+
     /// Advance the window: zero out any buckets that have become stale since last advance.
     /// Uses CAS on `last_advance` to ensure only one thread advances at a time.
     /// After clearing stale buckets, recomputes `cached_sum` from all buckets to
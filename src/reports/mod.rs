@@ -0,0 +1,457 @@
+//! Monthly usage reports (`GET /api/reports/usage`, `[reports]`).
+//!
+//! Every closed relay session adds its bytes and one connection to its
+//! user's totals for the month (UTC) it ended in; every SSH or SOCKS5 login
+//! adds one login and its duration, from authentication to disconnect, so
+//! a client with several channels open is billed its time once. A login
+//! that spans a month boundary has its time split between the months. The
+//! totals are kept in memory and, with `usage_history_path`, written to a
+//! JSON file every minute and on shutdown, so months survive restarts. With
+//! `monthly_email`, the previous month's report goes out by SMTP once a new
+//! month starts, for rebilling.
+
+use crate::config::types::{AppConfig, ReportsConfig, SmtpConfig};
+use crate::notifications::{smtp, Digest, Route};
+use crate::proxy::close::ClosedSession;
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How often the history is saved and the monthly email considered.
+const TICK_SECS: u64 = 60;
+
+/// Wait after a failed report email before trying again.
+const EMAIL_RETRY_SECS: u64 = 3600;
+
+/// Usage of one user over one month.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    /// Relay sessions (SOCKS5 connects and SSH channels)
+    pub connections: u64,
+    /// Authenticated SSH and SOCKS5 clients
+    #[serde(default)]
+    pub logins: u64,
+    /// Summed login durations, from authentication to disconnect
+    pub session_secs: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: &UsageTotals) {
+        self.bytes_up = self.bytes_up.saturating_add(other.bytes_up);
+        self.bytes_down = self.bytes_down.saturating_add(other.bytes_down);
+        self.connections = self.connections.saturating_add(other.connections);
+        self.logins = self.logins.saturating_add(other.logins);
+        self.session_secs = self.session_secs.saturating_add(other.session_secs);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    /// `YYYY-MM` -> username -> totals
+    months: BTreeMap<String, BTreeMap<String, UsageTotals>>,
+    /// Last month whose report was emailed
+    #[serde(default)]
+    last_emailed: Option<String>,
+}
+
+struct Inner {
+    file: HistoryFile,
+    dirty: bool,
+}
+
+/// Per-user monthly usage totals.
+pub struct UsageHistory {
+    path: Option<PathBuf>,
+    months_kept: usize,
+    inner: std::sync::Mutex<Inner>,
+}
+
+/// `YYYY-MM` of `at`.
+pub fn month_of(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m").to_string()
+}
+
+/// Check a `YYYY-MM` month.
+pub fn parse_month(month: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .ok()
+        .filter(|d| month_of_date(*d) == month)
+}
+
+fn month_of_date(date: NaiveDate) -> String {
+    format!("{:04}-{:02}", date.year(), date.month())
+}
+
+/// Start of the month after the one containing `at`.
+fn next_month_start(at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let first = at.date_naive().with_day(1)?;
+    Some(
+        first
+            .checked_add_months(Months::new(1))?
+            .and_time(NaiveTime::MIN)
+            .and_utc(),
+    )
+}
+
+/// The month before the one containing `at`.
+pub fn previous_month(at: DateTime<Utc>) -> String {
+    let first = at.date_naive().with_day(1).unwrap_or(at.date_naive());
+    month_of_date(first.checked_sub_months(Months::new(1)).unwrap_or(first))
+}
+
+impl UsageHistory {
+    /// Load `usage_history_path`, if set. A missing file starts an empty
+    /// history; an unreadable one is logged and replaced on the next save.
+    pub fn new(config: &ReportsConfig) -> Self {
+        let file = config
+            .usage_history_path
+            .as_deref()
+            .map(load_history)
+            .unwrap_or_default();
+        Self {
+            path: config.usage_history_path.clone(),
+            months_kept: config.usage_history_months.max(1) as usize,
+            inner: std::sync::Mutex::new(Inner { file, dirty: false }),
+        }
+    }
+
+    /// Count a closed relay session: its bytes and one connection.
+    pub fn record_session(&self, closed: &ClosedSession) {
+        let session = &closed.session;
+        self.record(
+            &session.username,
+            closed.ended_at,
+            &UsageTotals {
+                bytes_up: session.bytes_up,
+                bytes_down: session.bytes_down,
+                connections: 1,
+                ..UsageTotals::default()
+            },
+        );
+    }
+
+    /// Start timing a login of `username`, just authenticated.
+    pub fn start_login(self: &Arc<Self>, username: &str) -> Login {
+        Login {
+            usage: self.clone(),
+            username: username.to_string(),
+            started_at: Utc::now(),
+        }
+    }
+
+    /// Count a login that lasted from `started_at` to `ended_at`: one login
+    /// in the month it ended, and its time in the months it covered.
+    pub fn record_login(&self, username: &str, started_at: DateTime<Utc>, ended_at: DateTime<Utc>) {
+        self.record(
+            username,
+            ended_at,
+            &UsageTotals {
+                logins: 1,
+                ..UsageTotals::default()
+            },
+        );
+        let mut from = started_at;
+        while from < ended_at {
+            let to = next_month_start(from).map_or(ended_at, |next| next.min(ended_at));
+            self.record(
+                username,
+                from,
+                &UsageTotals {
+                    session_secs: (to - from).num_seconds().max(0) as u64,
+                    ..UsageTotals::default()
+                },
+            );
+            from = to;
+        }
+    }
+
+    /// Add `usage` to `username`'s totals for the month of `at`.
+    pub fn record(&self, username: &str, at: DateTime<Utc>, usage: &UsageTotals) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let months = &mut inner.file.months;
+        months
+            .entry(month_of(at))
+            .or_default()
+            .entry(username.to_string())
+            .or_default()
+            .add(usage);
+        while months.len() > self.months_kept {
+            months.pop_first();
+        }
+        inner.dirty = true;
+    }
+
+    /// Per-user totals of `month` (`YYYY-MM`), by username.
+    pub fn month(&self, month: &str) -> BTreeMap<String, UsageTotals> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.file.months.get(month).cloned().unwrap_or_default()
+    }
+
+    /// Months on record, oldest first.
+    pub fn months(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.file.months.keys().cloned().collect()
+    }
+
+    /// Report of `month`.
+    pub fn report(&self, month: &str) -> UsageReport {
+        UsageReport::new(month, &self.month(month))
+    }
+
+    /// Write the history to `usage_history_path` if it changed since the
+    /// last save.
+    pub fn save(&self) {
+        let Some(path) = self.path.as_deref() else {
+            return;
+        };
+        // Serialize under the lock, write outside it: sessions keep closing
+        let json = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if !inner.dirty {
+                return;
+            }
+            inner.dirty = false;
+            serde_json::to_vec(&inner.file)
+        };
+        if let Err(e) = json
+            .map_err(std::io::Error::from)
+            .and_then(|j| write_file(path, &j))
+        {
+            warn!(path = %path.display(), error = %e, "Failed to save usage history");
+            self.inner.lock().unwrap_or_else(|e| e.into_inner()).dirty = true;
+        }
+    }
+
+    fn last_emailed(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.file.last_emailed.clone()
+    }
+
+    fn set_last_emailed(&self, month: &str) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.file.last_emailed = Some(month.to_string());
+        inner.dirty = true;
+    }
+}
+
+/// An authenticated SSH or SOCKS5 client; counts its login and duration
+/// in the usage history when dropped, at disconnect.
+pub struct Login {
+    usage: Arc<UsageHistory>,
+    username: String,
+    started_at: DateTime<Utc>,
+}
+
+impl Drop for Login {
+    fn drop(&mut self) {
+        self.usage
+            .record_login(&self.username, self.started_at, Utc::now());
+    }
+}
+
+fn load_history(path: &Path) -> HistoryFile {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(path = %path.display(), error = %e, "Ignoring unreadable usage history");
+            HistoryFile::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read usage history");
+            HistoryFile::default()
+        }
+    }
+}
+
+/// Write through a temporary file so a crash never leaves half a history.
+fn write_file(path: &Path, json: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// One row of a [`UsageReport`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserUsage {
+    pub username: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    pub bytes_total: u64,
+    pub session_hours: f64,
+}
+
+impl UserUsage {
+    fn new(username: &str, totals: UsageTotals) -> Self {
+        Self {
+            username: username.to_string(),
+            totals,
+            bytes_total: totals.bytes_up.saturating_add(totals.bytes_down),
+            session_hours: (totals.session_secs as f64 / 36.0).round() / 100.0,
+        }
+    }
+}
+
+/// Usage of every user over one month, sorted by username.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub month: String,
+    pub users: Vec<UserUsage>,
+    /// All users together
+    pub total: UserUsage,
+}
+
+impl UsageReport {
+    pub fn new(month: &str, users: &BTreeMap<String, UsageTotals>) -> Self {
+        let mut total = UsageTotals::default();
+        for usage in users.values() {
+            total.add(usage);
+        }
+        Self {
+            month: month.to_string(),
+            users: users
+                .iter()
+                .map(|(name, usage)| UserUsage::new(name, *usage))
+                .collect(),
+            total: UserUsage::new("", total),
+        }
+    }
+
+    /// One line per user, then a `TOTAL` line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "month,username,bytes_up,bytes_down,bytes_total,connections,logins,session_hours\n",
+        );
+        let rows = self.users.iter().map(|u| (csv_field(&u.username), u));
+        let total = std::iter::once(("TOTAL".to_string(), &self.total));
+        for (name, u) in rows.chain(total) {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{:.2}",
+                self.month,
+                name,
+                u.totals.bytes_up,
+                u.totals.bytes_down,
+                u.bytes_total,
+                u.totals.connections,
+                u.totals.logins,
+                u.session_hours
+            );
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field when needed, and defuse values a spreadsheet would
+/// run as a formula.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// The monthly report email for `report`.
+pub fn report_email(report: &UsageReport, to: &[String], server_id: &str) -> Digest {
+    let mut recipients = to.to_vec();
+    recipients.sort();
+    Digest {
+        route: Route::Email(recipients),
+        subject: format!("[{}] Usage report {}", server_id, report.month),
+        body: format!(
+            "Usage per user for {} (UTC), {} users.\n\n{}",
+            report.month,
+            report.users.len(),
+            report.to_csv()
+        ),
+        events: Vec::new(),
+        suppressed: 0,
+    }
+}
+
+/// Save `history` every minute until `shutdown` (and once more then), and
+/// email the previous month's report when `[reports] monthly_email` is on.
+/// Startup-only: recipients are not hot-reloaded.
+pub fn spawn(history: Arc<UsageHistory>, config: &AppConfig, shutdown: CancellationToken) {
+    let reports = &config.reports;
+    let email = reports
+        .monthly_email
+        .then(|| config.notifications.smtp.clone())
+        .flatten()
+        .map(|smtp| {
+            let to = if reports.email_to.is_empty() {
+                smtp.to.clone()
+            } else {
+                reports.email_to.clone()
+            };
+            (smtp, to)
+        });
+    let server_id = config.server.server_id.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut retry_at = tokio::time::Instant::now();
+        loop {
+            let stopping = tokio::select! {
+                _ = shutdown.cancelled() => true,
+                _ = interval.tick() => false,
+            };
+            if let Some((ref smtp, ref to)) = email {
+                if !stopping
+                    && tokio::time::Instant::now() >= retry_at
+                    && !send_due_report(&history, smtp, to, &server_id, Utc::now()).await
+                {
+                    retry_at = tokio::time::Instant::now() + Duration::from_secs(EMAIL_RETRY_SECS);
+                }
+            }
+            let h = history.clone();
+            let _ = tokio::task::spawn_blocking(move || h.save()).await;
+            if stopping {
+                break;
+            }
+        }
+    });
+}
+
+/// Email last month's report unless it was sent already or has no usage.
+/// Returns `false` if sending failed.
+async fn send_due_report(
+    history: &UsageHistory,
+    smtp: &SmtpConfig,
+    to: &[String],
+    server_id: &str,
+    now: DateTime<Utc>,
+) -> bool {
+    let month = previous_month(now);
+    if history.last_emailed().as_deref() >= Some(month.as_str()) {
+        return true;
+    }
+    let report = history.report(&month);
+    if report.users.is_empty() {
+        return true;
+    }
+    match smtp::send(smtp, to, &report_email(&report, to, server_id)).await {
+        Ok(()) => {
+            info!(month = %month, users = report.users.len(), "Usage report emailed");
+            history.set_last_emailed(&month);
+            true
+        }
+        Err(e) => {
+            warn!(month = %month, error = %e, "Failed to email usage report");
+            false
+        }
+    }
+}
@@ -0,0 +1,544 @@
+//! Process sandbox (`[sandbox]`, Linux only).
+//!
+//! Applied once the listeners are bound and the host keys read, so neither
+//! needs privileges afterwards:
+//!
+//! - `user` / `group`: switch IDs (and supplementary groups) from root. The
+//!   kernel then clears the permitted, effective and ambient capabilities
+//!   of every thread; s5 checks that none is left and refuses to run
+//!   otherwise.
+//! - `seccomp`: `no_new_privs` plus a filter, synchronized to every thread,
+//!   that fails the syscalls s5 never makes (kernel modules, mounts, ptrace,
+//!   BPF, keyrings, namespaces, clock changes...) with `EPERM`.
+//!
+//! [`status`] reads the result back from the kernel (`/proc/self/status`)
+//! for `GET /api/status`, as evidence rather than as configured intent.
+//!
+//! [`FsConfinement`] is narrower: a Landlock ruleset applied to one host
+//! command started for a member of a group with `fs_allow`, in the child
+//! just before it runs.
+
+use crate::config::types::SandboxConfig;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// What [`apply`] did, for [`status`]
+static APPLIED: OnceLock<usize> = OnceLock::new();
+
+/// Sandbox state of the process, as the kernel reports it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxStatus {
+    /// `[sandbox]` was applied at startup
+    pub applied: bool,
+    /// Effective user ID
+    pub uid: Option<u32>,
+    /// Effective group ID
+    pub gid: Option<u32>,
+    /// Effective capabilities of any thread (hex mask, all zeros = none)
+    pub capabilities: Option<String>,
+    /// Capability bounding set (hex mask)
+    pub capability_bounding_set: Option<String>,
+    pub no_new_privs: Option<bool>,
+    /// `disabled`, `strict` or `filter`
+    pub seccomp: Option<String>,
+    /// Syscalls refused by the `sandbox.seccomp` filter
+    pub denied_syscalls: usize,
+}
+
+/// Parse `/proc/<pid>/status`. Missing fields stay `None`.
+pub fn parse_proc_status(text: &str) -> SandboxStatus {
+    let mut status = SandboxStatus::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        // Uid and Gid list the real, effective, saved and filesystem IDs
+        let effective = || value.split_whitespace().nth(1)?.parse().ok();
+        match key {
+            "Uid" => status.uid = effective(),
+            "Gid" => status.gid = effective(),
+            "CapEff" => status.capabilities = Some(value.to_string()),
+            "CapBnd" => status.capability_bounding_set = Some(value.to_string()),
+            "NoNewPrivs" => status.no_new_privs = Some(value == "1"),
+            "Seccomp" => {
+                let mode = match value {
+                    "0" => "disabled",
+                    "1" => "strict",
+                    "2" => "filter",
+                    other => other,
+                };
+                status.seccomp = Some(mode.to_string());
+            }
+            _ => {}
+        }
+    }
+    status
+}
+
+/// The sandbox state of this process.
+pub fn status() -> SandboxStatus {
+    let mut status = std::fs::read_to_string("/proc/self/status")
+        .map(|text| parse_proc_status(&text))
+        .unwrap_or_default();
+    if let Some(held) = thread_capabilities() {
+        status.capabilities = Some(format!("{:016x}", held));
+    }
+    if let Some(&denied) = APPLIED.get() {
+        status.applied = true;
+        status.denied_syscalls = denied;
+    }
+    status
+}
+
+/// Effective capabilities of all our threads, combined. Capabilities are
+/// per thread, and `/proc/self/status` only shows the main one.
+fn thread_capabilities() -> Option<u64> {
+    let mut held = 0u64;
+    for task in std::fs::read_dir("/proc/self/task").ok()?.flatten() {
+        let Ok(text) = std::fs::read_to_string(task.path().join("status")) else {
+            continue;
+        };
+        let caps = parse_proc_status(&text).capabilities?;
+        held |= u64::from_str_radix(&caps, 16).ok()?;
+    }
+    Some(held)
+}
+
+/// Switch user and install the syscall filter, per `config`.
+#[cfg(target_os = "linux")]
+pub fn apply(config: &SandboxConfig) -> anyhow::Result<()> {
+    use tracing::info;
+
+    if let Some(user) = &config.user {
+        let (uid, gid) = lookup(user, config.group.as_deref())?;
+        // SAFETY: plain ID getters and setters; glibc and musl apply the
+        // setters to every thread of the process
+        unsafe {
+            // Already the user when started by a socket handover
+            if libc::geteuid() != uid {
+                anyhow::ensure!(
+                    libc::geteuid() == 0,
+                    "sandbox.user requires starting as root"
+                );
+                if libc::setgroups(1, &gid) != 0 {
+                    return Err(os_error("setgroups"));
+                }
+                if libc::setgid(gid) != 0 {
+                    return Err(os_error("setgid"));
+                }
+                if libc::setuid(uid) != 0 {
+                    return Err(os_error("setuid"));
+                }
+            }
+        }
+        if let Some(held) = thread_capabilities().filter(|&held| held != 0) {
+            anyhow::bail!("capabilities {:016x} still held after switching user", held);
+        }
+        info!(user = %user, uid, gid, "Switched user, no capabilities left");
+    }
+
+    let denied = if config.seccomp {
+        let syscalls = denied_syscalls();
+        install_filter(&seccomp_filter(&syscalls))?;
+        info!(denied = syscalls.len(), "Seccomp filter installed");
+        syscalls.len()
+    } else {
+        0
+    };
+    let _ = APPLIED.set(denied);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_config: &SandboxConfig) -> anyhow::Result<()> {
+    anyhow::bail!("sandbox is only supported on Linux")
+}
+
+#[cfg(target_os = "linux")]
+fn os_error(call: &str) -> anyhow::Error {
+    anyhow::anyhow!("{} failed: {}", call, std::io::Error::last_os_error())
+}
+
+/// UID and GID of `user`, and of `group` instead of its primary group.
+#[cfg(target_os = "linux")]
+fn lookup(user: &str, group: Option<&str>) -> anyhow::Result<(u32, u32)> {
+    use std::ffi::CString;
+
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let name = CString::new(user)?;
+    // SAFETY: zeroed C structs filled in by the reentrant lookups, with
+    // `buf` holding their strings
+    let (uid, mut gid) = unsafe {
+        let mut pwd: libc::passwd = std::mem::zeroed();
+        let mut found = std::ptr::null_mut();
+        let rc = libc::getpwnam_r(
+            name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        );
+        anyhow::ensure!(
+            rc == 0 && !found.is_null(),
+            "sandbox.user '{}' not found",
+            user
+        );
+        (pwd.pw_uid, pwd.pw_gid)
+    };
+    if let Some(group) = group {
+        let name = CString::new(group)?;
+        // SAFETY: as above
+        gid = unsafe {
+            let mut grp: libc::group = std::mem::zeroed();
+            let mut found = std::ptr::null_mut();
+            let rc = libc::getgrnam_r(
+                name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut found,
+            );
+            anyhow::ensure!(
+                rc == 0 && !found.is_null(),
+                "sandbox.group '{}' not found",
+                group
+            );
+            grp.gr_gid
+        };
+    }
+    Ok((uid, gid))
+}
+
+/// Syscalls the filter fails with `EPERM`.
+#[cfg(target_os = "linux")]
+pub fn denied_syscalls() -> Vec<libc::c_long> {
+    let common = [
+        // Kernel and system administration
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_acct,
+        libc::SYS_quotactl,
+        libc::SYS_syslog,
+        // Filesystems and namespaces
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_setns,
+        libc::SYS_unshare,
+        libc::SYS_open_by_handle_at,
+        // Other processes' memory
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        // Kernel attack surface s5 has no use for
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        // System clock
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_adjtimex,
+    ];
+    #[cfg(target_arch = "x86_64")]
+    let arch = [libc::SYS_iopl, libc::SYS_ioperm, libc::SYS_modify_ldt];
+    #[cfg(not(target_arch = "x86_64"))]
+    let arch: [libc::c_long; 0] = [];
+    common.into_iter().chain(arch).collect()
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod bpf {
+    /// `BPF_LD | BPF_W | BPF_ABS`
+    pub const LD_W_ABS: u16 = 0x20;
+    /// `BPF_JMP | BPF_JEQ | BPF_K`
+    pub const JMP_JEQ_K: u16 = 0x15;
+    /// `BPF_JMP | BPF_JGE | BPF_K`
+    #[cfg(target_arch = "x86_64")]
+    pub const JMP_JGE_K: u16 = 0x35;
+    /// `BPF_RET | BPF_K`
+    pub const RET_K: u16 = 0x06;
+    /// `seccomp_data` offsets
+    pub const NR: u32 = 0;
+    pub const ARCH: u32 = 4;
+    pub const RET_KILL_PROCESS: u32 = 0x8000_0000;
+    pub const RET_ERRNO: u32 = 0x0005_0000;
+    pub const RET_ALLOW: u32 = 0x7fff_0000;
+    #[cfg(target_arch = "x86_64")]
+    pub const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    pub const AUDIT_ARCH: u32 = 0xc000_00b7;
+    /// x32 syscalls on x86_64
+    #[cfg(target_arch = "x86_64")]
+    pub const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+}
+
+/// Classic BPF program failing `syscalls` with `EPERM`. Other architectures
+/// (32-bit compat calls) kill the process, and so would x32 calls.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn seccomp_filter(syscalls: &[libc::c_long]) -> Vec<libc::sock_filter> {
+    let insn = |code, jt, jf, k| libc::sock_filter { code, jt, jf, k };
+    let deny = bpf::RET_ERRNO | libc::EPERM as u32;
+    let mut program = vec![
+        insn(bpf::LD_W_ABS, 0, 0, bpf::ARCH),
+        insn(bpf::JMP_JEQ_K, 1, 0, bpf::AUDIT_ARCH),
+        insn(bpf::RET_K, 0, 0, bpf::RET_KILL_PROCESS),
+        insn(bpf::LD_W_ABS, 0, 0, bpf::NR),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    program.extend([
+        insn(bpf::JMP_JGE_K, 0, 1, bpf::X32_SYSCALL_BIT),
+        insn(bpf::RET_K, 0, 0, bpf::RET_KILL_PROCESS),
+    ]);
+    for &nr in syscalls {
+        program.push(insn(bpf::JMP_JEQ_K, 0, 1, nr as u32));
+        program.push(insn(bpf::RET_K, 0, 0, deny));
+    }
+    program.push(insn(bpf::RET_K, 0, 0, bpf::RET_ALLOW));
+    program
+}
+
+#[cfg(all(
+    target_os = "linux",
+    not(any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+pub fn seccomp_filter(_syscalls: &[libc::c_long]) -> Vec<libc::sock_filter> {
+    Vec::new()
+}
+
+/// Set `no_new_privs` and load `program` into every thread.
+#[cfg(target_os = "linux")]
+fn install_filter(program: &[libc::sock_filter]) -> anyhow::Result<()> {
+    const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+    const SECCOMP_FILTER_FLAG_TSYNC: libc::c_ulong = 1;
+
+    anyhow::ensure!(
+        !program.is_empty(),
+        "no seccomp filter for this architecture"
+    );
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+    // SAFETY: `prog` points to `program`, which outlives both calls; the
+    // kernel copies the filter
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(os_error("prctl(PR_SET_NO_NEW_PRIVS)"));
+        }
+        let rc = libc::syscall(
+            libc::SYS_seccomp,
+            SECCOMP_SET_MODE_FILTER,
+            SECCOMP_FILTER_FLAG_TSYNC,
+            &prog as *const libc::sock_fprog,
+        );
+        // A positive value is a thread that could not be synchronized
+        if rc != 0 {
+            return Err(if rc < 0 {
+                os_error("seccomp")
+            } else {
+                anyhow::anyhow!("seccomp: thread {} could not be synchronized", rc)
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Directories confined commands may read and execute from, so that they
+/// can start (programs, shared libraries). Missing ones are skipped.
+pub const FS_SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64"];
+
+#[cfg(target_os = "linux")]
+mod landlock {
+    pub const CREATE_RULESET_VERSION: u32 = 1;
+    pub const RULE_PATH_BENEATH: libc::c_int = 1;
+    pub const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    pub const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    pub const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    pub const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    /// Every right of ABI 1 (`EXECUTE` to `MAKE_SYM`)
+    pub const ACCESS_FS_V1: u64 = (1 << 13) - 1;
+    pub const ACCESS_FS_REFER: u64 = 1 << 13;
+    pub const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+    pub const ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+    /// Rights that apply to a file rather than a directory
+    pub const ACCESS_FILE: u64 = ACCESS_FS_EXECUTE
+        | ACCESS_FS_WRITE_FILE
+        | ACCESS_FS_READ_FILE
+        | ACCESS_FS_TRUNCATE
+        | ACCESS_FS_IOCTL_DEV;
+
+    /// `struct landlock_ruleset_attr` as of ABI 1
+    #[repr(C)]
+    pub struct RulesetAttr {
+        pub handled_access_fs: u64,
+    }
+
+    /// `struct landlock_path_beneath_attr`
+    #[repr(C, packed)]
+    pub struct PathBeneathAttr {
+        pub allowed_access: u64,
+        pub parent_fd: i32,
+    }
+}
+
+/// Landlock ruleset for a session's host command (`[[groups]] fs_allow`):
+/// every access beneath the allowed paths, read and execute beneath
+/// [`FS_SYSTEM_DIRS`], nothing elsewhere. Built in the parent, applied in
+/// the child by [`FsConfinement::restrict_self`].
+#[cfg(target_os = "linux")]
+pub struct FsConfinement {
+    handled: u64,
+    /// Paths opened with `O_PATH`, and the rights granted beneath each
+    rules: Vec<(std::os::fd::OwnedFd, u64)>,
+}
+
+#[cfg(target_os = "linux")]
+impl FsConfinement {
+    /// Open `allowed` and the system directories. Fails when the kernel
+    /// has no Landlock or an allowed path cannot be opened.
+    pub fn new(allowed: &[String]) -> std::io::Result<Self> {
+        use landlock::*;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        // SAFETY: a null attribute with the version flag only queries the ABI
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Err(std::io::Error::other(format!(
+                "Landlock is not available: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        let mut handled = ACCESS_FS_V1;
+        if abi >= 2 {
+            handled |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= ACCESS_FS_TRUNCATE;
+        }
+        if abi >= 5 {
+            handled |= ACCESS_FS_IOCTL_DEV;
+        }
+
+        let open = |path: &str| {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH)
+                .open(path)
+        };
+        let read_exec = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+        let mut rules = Vec::new();
+        let mut add = |file: std::fs::File, access: u64| -> std::io::Result<()> {
+            let access = if file.metadata()?.is_dir() {
+                access
+            } else {
+                access & ACCESS_FILE
+            };
+            rules.push((file.into(), access & handled));
+            Ok(())
+        };
+        for dir in FS_SYSTEM_DIRS {
+            match open(dir) {
+                Ok(file) => add(file, read_exec)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for path in allowed {
+            let file = open(path).map_err(|e| {
+                std::io::Error::new(e.kind(), format!("fs_allow '{}': {}", path, e))
+            })?;
+            add(file, handled)?;
+        }
+        Ok(Self { handled, rules })
+    }
+
+    /// Restrict the calling process (and what it executes) to the ruleset.
+    /// Only makes system calls, so it may run between `fork` and `exec`.
+    pub fn restrict_self(&self) -> std::io::Result<()> {
+        use landlock::*;
+        use std::os::fd::AsRawFd;
+
+        let attr = RulesetAttr {
+            handled_access_fs: self.handled,
+        };
+        // SAFETY: the attributes outlive the calls, which copy them; the
+        // ruleset descriptor is closed on every path
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let ruleset = libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            );
+            if ruleset < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let ruleset = ruleset as libc::c_int;
+            let mut result = Ok(());
+            for (fd, access) in &self.rules {
+                let rule = PathBeneathAttr {
+                    allowed_access: *access,
+                    parent_fd: fd.as_raw_fd(),
+                };
+                let rc = libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset,
+                    RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0u32,
+                );
+                if rc != 0 {
+                    result = Err(std::io::Error::last_os_error());
+                    break;
+                }
+            }
+            if result.is_ok() && libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0
+            {
+                result = Err(std::io::Error::last_os_error());
+            }
+            libc::close(ruleset);
+            result
+        }
+    }
+}
+
+/// Landlock is Linux only: confined commands cannot be started elsewhere.
+#[cfg(not(target_os = "linux"))]
+pub struct FsConfinement;
+
+#[cfg(not(target_os = "linux"))]
+impl FsConfinement {
+    pub fn new(_allowed: &[String]) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "fs_allow needs Landlock (Linux)",
+        ))
+    }
+}
@@ -0,0 +1,264 @@
+//! Lua hooks (`[scripting]`).
+//!
+//! One script, loaded at startup, may define `on_auth`, `on_channel_open`
+//! and `on_session_close`. Each is called with a table describing the
+//! event and may refuse it (`return false, "reason"`; not a session close)
+//! or return a table of labels added to its audit event.
+//!
+//! The script gets the `string`, `table`, `math` and `utf8` libraries and a
+//! `log` function: no files, processes, network, module loading or error
+//! catching. It runs under a memory limit and each call is stopped after
+//! `timeout_ms`, checked between instructions. Globals persist between
+//! calls, which run one at a time.
+
+use crate::config::types::ScriptingConfig;
+use anyhow::{Context, Result};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Value};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// `reason` of the `acl.deny` audit events of channels `on_channel_open` refuses.
+pub const ACL_DENY_REASON: &str = "script";
+
+/// Labels added to an audit event.
+pub type Labels = BTreeMap<String, String>;
+
+/// Most labels kept from one hook call; longer values are dropped.
+const MAX_LABELS: usize = 32;
+const MAX_LABEL_LEN: usize = 1024;
+
+/// Instructions between two deadline checks.
+const DEADLINE_CHECK_EVERY: u32 = 1000;
+
+/// Globals of the base library removed from the script's reach.
+const REMOVED_GLOBALS: &[&str] = &[
+    "dofile",
+    "loadfile",
+    "load",
+    "require",
+    "collectgarbage",
+    "pcall",
+    "xpcall",
+    "print",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// A login whose credentials checked out, with its `auth.success` event
+    Auth,
+    /// A tunnel about to connect, before `[policy]` and the ACL
+    ChannelOpen,
+    /// A finished tunnel, with its `proxy.complete` event
+    SessionClose,
+}
+
+impl Hook {
+    const ALL: [Hook; 3] = [Hook::Auth, Hook::ChannelOpen, Hook::SessionClose];
+
+    /// The global function the script defines.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::Auth => "on_auth",
+            Hook::ChannelOpen => "on_channel_open",
+            Hook::SessionClose => "on_session_close",
+        }
+    }
+}
+
+/// What a hook answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Go on, adding these labels to the audit event
+    Continue(Labels),
+    /// Refuse, with the script's reason if it gave one
+    Refuse(Option<String>),
+}
+
+/// A tunnel about to connect, as `on_channel_open` sees it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelOpen<'a> {
+    pub username: &'a str,
+    pub group: Option<&'a str>,
+    /// Own and group tags
+    pub tags: &'a [String],
+    pub source_ip: &'a str,
+    pub target_host: &'a str,
+    pub target_port: u16,
+    pub correlation_id: &'a str,
+}
+
+/// The loaded script.
+pub struct Hooks {
+    lua: Mutex<Lua>,
+    timeout: Duration,
+    defined: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Run the script of `config` in a fresh sandbox, noting which hooks
+    /// it defines.
+    pub fn load(config: &ScriptingConfig) -> Result<Self> {
+        let source = std::fs::read_to_string(&config.path)
+            .with_context(|| format!("reading {}", config.path.display()))?;
+        let lua = Lua::new_with(
+            StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8,
+            LuaOptions::default(),
+        )?;
+        lua.set_memory_limit(config.memory_limit_kb.saturating_mul(1024))?;
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        let defined = {
+            let globals = lua.globals();
+            for name in REMOVED_GLOBALS {
+                globals.set(*name, Value::Nil)?;
+            }
+            globals.set(
+                "log",
+                lua.create_function(|_, message: String| {
+                    info!(target: "s5::scripting", "{}", message);
+                    Ok(())
+                })?,
+            )?;
+            let name = config.path.display().to_string();
+            with_deadline(&lua, timeout, || lua.load(&source).set_name(name).exec())?;
+            Hook::ALL
+                .into_iter()
+                .filter(|hook| matches!(globals.get(hook.name()), Ok(Value::Function(_))))
+                .collect()
+        };
+        Ok(Self {
+            lua: Mutex::new(lua),
+            timeout,
+            defined,
+        })
+    }
+
+    /// Whether the script defines `hook`.
+    pub fn defines(&self, hook: Hook) -> bool {
+        self.defined.contains(&hook)
+    }
+
+    /// Call `hook` with `event` as a table. Blocks for up to `timeout_ms`
+    /// (plus the calls in progress); a hook the script does not define
+    /// continues without labels.
+    pub fn call(&self, hook: Hook, event: &impl Serialize) -> Result<Outcome> {
+        if !self.defines(hook) {
+            return Ok(Outcome::Continue(Labels::new()));
+        }
+        let event = serde_json::to_value(event)?;
+        let lua = self.lua.lock().unwrap_or_else(PoisonError::into_inner);
+        let function: Function = lua.globals().get(hook.name())?;
+        let event = to_lua(&lua, &event)?;
+        let values = with_deadline(&lua, self.timeout, || function.call::<_, MultiValue>(event))?;
+        outcome(values).with_context(|| hook.name())
+    }
+
+    /// [`Hooks::call`] off the async runtime.
+    pub async fn run(self: &Arc<Self>, hook: Hook, event: &impl Serialize) -> Result<Outcome> {
+        if !self.defines(hook) {
+            return Ok(Outcome::Continue(Labels::new()));
+        }
+        let event = serde_json::to_value(event)?;
+        let hooks = Arc::clone(self);
+        tokio::task::spawn_blocking(move || hooks.call(hook, &event)).await?
+    }
+}
+
+/// Run `f` on `lua`, stopping it once `timeout` has passed.
+fn with_deadline<R>(
+    lua: &Lua,
+    timeout: Duration,
+    f: impl FnOnce() -> mlua::Result<R>,
+) -> Result<R> {
+    let deadline = Instant::now() + timeout;
+    let expired = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&expired);
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(DEADLINE_CHECK_EVERY),
+        move |_, _| {
+            if Instant::now() < deadline {
+                return Ok(());
+            }
+            flag.store(true, Ordering::Relaxed);
+            Err(mlua::Error::RuntimeError("timed out".to_string()))
+        },
+    );
+    let result = f();
+    lua.remove_hook();
+    match result {
+        Ok(value) => Ok(value),
+        Err(_) if expired.load(Ordering::Relaxed) => anyhow::bail!("timed out"),
+        Err(mlua::Error::MemoryError(_)) => anyhow::bail!("out of memory"),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Convert a JSON event into a Lua value.
+fn to_lua<'lua>(lua: &'lua Lua, value: &serde_json::Value) -> mlua::Result<Value<'lua>> {
+    use serde_json::Value as Json;
+    Ok(match value {
+        Json::Null => Value::Nil,
+        Json::Bool(b) => Value::Boolean(*b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Value::String(lua.create_string(s)?),
+        Json::Array(items) => Value::Table(
+            lua.create_sequence_from(
+                items
+                    .iter()
+                    .map(|item| to_lua(lua, item))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        ),
+        Json::Object(fields) => {
+            let table = lua.create_table()?;
+            for (key, field) in fields {
+                table.set(key.as_str(), to_lua(lua, field)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
+/// Read a hook's return values: nothing or `true` continues, `false` and
+/// an optional reason refuses, a table continues with its string, number
+/// and boolean fields as labels.
+fn outcome(values: MultiValue<'_>) -> Result<Outcome> {
+    let mut values = values.into_iter();
+    match values.next().unwrap_or(Value::Nil) {
+        Value::Nil | Value::Boolean(true) => Ok(Outcome::Continue(Labels::new())),
+        Value::Boolean(false) => {
+            let reason = match values.next() {
+                Some(Value::String(reason)) => Some(reason.to_str()?.to_string()),
+                _ => None,
+            };
+            Ok(Outcome::Refuse(reason))
+        }
+        Value::Table(table) => {
+            let mut labels = Labels::new();
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                let Value::String(key) = key else {
+                    continue;
+                };
+                let value = match value {
+                    Value::String(s) => s.to_str()?.to_string(),
+                    Value::Integer(i) => i.to_string(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Boolean(b) => b.to_string(),
+                    _ => continue,
+                };
+                if labels.len() < MAX_LABELS && value.len() <= MAX_LABEL_LEN {
+                    labels.insert(key.to_str()?.to_string(), value);
+                }
+            }
+            Ok(Outcome::Continue(labels))
+        }
+        other => anyhow::bail!("returned a {}", other.type_name()),
+    }
+}
@@ -1,11 +1,78 @@
+use super::threat_intel::ThreatIntel;
 use crate::audit::AuditLogger;
 use dashmap::DashMap;
 use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
+/// What a ban blocks: a source IP, server-wide or for the logins of one
+/// `[[tenants]]` entry. Written `ip` or `ip@tenant` (cluster, backups).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BanKey {
+    pub ip: IpAddr,
+    /// None = server-wide
+    pub tenant: Option<String>,
+}
+
+impl BanKey {
+    pub fn new(ip: IpAddr, tenant: Option<&str>) -> Self {
+        Self {
+            ip,
+            tenant: tenant.map(str::to_string),
+        }
+    }
+}
+
+impl From<IpAddr> for BanKey {
+    fn from(ip: IpAddr) -> Self {
+        Self::new(ip, None)
+    }
+}
+
+impl From<&IpAddr> for BanKey {
+    fn from(ip: &IpAddr) -> Self {
+        Self::new(*ip, None)
+    }
+}
+
+impl fmt::Display for BanKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tenant {
+            Some(tenant) => write!(f, "{}@{}", self.ip, tenant),
+            None => write!(f, "{}", self.ip),
+        }
+    }
+}
+
+impl FromStr for BanKey {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('@') {
+            Some((ip, tenant)) => Ok(Self::new(ip.parse()?, Some(tenant))),
+            None => Ok(Self::new(s.parse()?, None)),
+        }
+    }
+}
+
+impl Serialize for BanKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BanKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// Parse a whitelist entry as either an IpNet (CIDR) or a single IpAddr.
 fn parse_whitelist(entries: &[String]) -> Vec<IpNet> {
     entries
@@ -24,12 +91,14 @@ fn parse_whitelist(entries: &[String]) -> Vec<IpNet> {
         .collect()
 }
 
-/// Auto-ban manager (fail2ban-like)
+/// Auto-ban manager (fail2ban-like). Failures and bans are kept per tenant:
+/// failed logins to a tenant's users ban the source for that tenant only,
+/// other bans apply server-wide.
 pub struct BanManager {
-    /// Track auth failures per IP: IP -> list of failure timestamps
-    failures: DashMap<IpAddr, Vec<Instant>>,
-    /// Currently banned IPs: IP -> ban expiry
-    bans: DashMap<IpAddr, Instant>,
+    /// Track auth failures per IP and tenant: key -> list of failure timestamps
+    failures: DashMap<BanKey, Vec<Instant>>,
+    /// Currently banned IPs: key -> ban expiry
+    bans: DashMap<BanKey, Instant>,
     /// Number of failures before ban
     threshold: u32,
     /// Window in which failures are counted
@@ -42,6 +111,9 @@ pub struct BanManager {
     enabled: bool,
     /// Optional audit logger for ban events
     audit: Option<Arc<AuditLogger>>,
+    /// External decisions (CrowdSec, denylists), checked even when local
+    /// banning is disabled
+    threat_intel: Option<Arc<ThreatIntel>>,
 }
 
 impl BanManager {
@@ -61,6 +133,7 @@ impl BanManager {
             whitelist: parse_whitelist(&whitelist),
             enabled,
             audit: None,
+            threat_intel: None,
         }
     }
 
@@ -86,20 +159,27 @@ impl BanManager {
         self.audit = Some(audit);
     }
 
-    /// Record an auth failure. May trigger a ban.
-    pub fn record_failure(&self, ip: &IpAddr) {
+    /// Merge external threat-intel decisions into [`is_banned`](Self::is_banned).
+    pub fn set_threat_intel(&mut self, threat_intel: Arc<ThreatIntel>) {
+        self.threat_intel = Some(threat_intel);
+    }
+
+    /// Record an auth failure, for a login to a user of `tenant` (None =
+    /// outside tenants). May trigger a ban of the source for that tenant.
+    pub fn record_failure(&self, ip: &IpAddr, tenant: Option<&str>) {
         if !self.enabled || self.is_whitelisted(ip) {
             return;
         }
+        let key = BanKey::new(*ip, tenant);
 
         // M-4: Capacity check to prevent memory exhaustion
-        if !self.failures.contains_key(ip) && self.failures.len() >= 100_000 {
+        if !self.failures.contains_key(&key) && self.failures.len() >= 100_000 {
             warn!("Ban manager failure map capacity exceeded, rejecting new IP tracking");
             return;
         }
 
         let now = Instant::now();
-        let mut failures = self.failures.entry(*ip).or_default();
+        let mut failures = self.failures.entry(key.clone()).or_default();
 
         // Remove old failures outside the window
         failures.retain(|t| now.duration_since(*t) < self.window);
@@ -114,76 +194,117 @@ impl BanManager {
         if failures.len() >= self.threshold as usize {
             // Ban the IP
             let expiry = now + self.duration;
-            self.bans.insert(*ip, expiry);
             failures.clear();
-            warn!(ip = %ip, duration_secs = self.duration.as_secs(), "IP banned");
+            drop(failures);
+            warn!(ip = %ip, tenant = ?tenant, duration_secs = self.duration.as_secs(), "IP banned");
             if let Some(ref audit) = self.audit {
-                audit.log_ban_created(ip, self.duration.as_secs());
+                audit.log_ban_created_for(ip, tenant, self.duration.as_secs());
             }
+            self.bans.insert(key, expiry);
         }
     }
 
-    /// Check if an IP is currently banned
+    /// Check if an IP is currently banned server-wide
     pub fn is_banned(&self, ip: &IpAddr) -> bool {
-        if !self.enabled || self.is_whitelisted(ip) {
+        if self.is_whitelisted(ip) {
             return false;
         }
+        if self.enabled && self.is_locally_banned(&BanKey::from(ip)) {
+            return true;
+        }
+        self.threat_intel.as_ref().is_some_and(|ti| ti.check(ip))
+    }
+
+    /// Check if an IP may not log in to the users of `tenant` (None =
+    /// outside tenants): banned server-wide or for that tenant.
+    pub fn is_banned_for(&self, ip: &IpAddr, tenant: Option<&str>) -> bool {
+        if self.is_banned(ip) {
+            return true;
+        }
+        tenant.is_some()
+            && self.enabled
+            && !self.is_whitelisted(ip)
+            && self.is_locally_banned(&BanKey::new(*ip, tenant))
+    }
 
+    fn is_locally_banned(&self, key: &BanKey) -> bool {
         // Atomically remove expired bans (no TOCTOU between get and remove)
         if self
             .bans
-            .remove_if(ip, |_, expiry| Instant::now() >= *expiry)
+            .remove_if(key, |_, expiry| Instant::now() >= *expiry)
             .is_some()
         {
-            info!(ip = %ip, "IP ban expired");
+            info!(ip = %key.ip, tenant = ?key.tenant, "IP ban expired");
             if let Some(ref audit) = self.audit {
-                audit.log_ban_expired(ip);
+                audit.log_ban_expired_for(&key.ip, key.tenant.as_deref());
             }
             return false;
         }
 
-        self.bans.contains_key(ip)
+        self.bans.contains_key(key)
+    }
+
+    /// Manually ban an IP, server-wide or (with a [`BanKey`]) for a tenant
+    pub fn ban(&self, key: impl Into<BanKey>, duration: Duration) {
+        let key = key.into();
+        info!(ip = %key.ip, tenant = ?key.tenant, duration_secs = duration.as_secs(), "IP manually banned");
+        self.bans.insert(key, Instant::now() + duration);
     }
 
-    /// Manually ban an IP
-    pub fn ban(&self, ip: IpAddr, duration: Duration) {
-        self.bans.insert(ip, Instant::now() + duration);
-        info!(ip = %ip, duration_secs = duration.as_secs(), "IP manually banned");
+    /// Enforce a ban made on another cluster node. Not audited: the node
+    /// that banned logged it.
+    pub fn import_ban(&self, key: impl Into<BanKey>, duration: Duration) {
+        let key = key.into();
+        debug!(ip = %key.ip, tenant = ?key.tenant, duration_secs = duration.as_secs(), "IP banned by another node");
+        self.bans.insert(key, Instant::now() + duration);
     }
 
-    /// Manually unban an IP
-    pub fn unban(&self, ip: &IpAddr) -> bool {
-        let removed = self.bans.remove(ip).is_some();
+    /// Manually unban an IP, server-wide or (with a [`BanKey`]) for a tenant
+    pub fn unban(&self, key: impl Into<BanKey>) -> bool {
+        let key = key.into();
+        let removed = self.bans.remove(&key).is_some();
         if removed {
-            info!(ip = %ip, "IP manually unbanned");
+            info!(ip = %key.ip, tenant = ?key.tenant, "IP manually unbanned");
         }
         removed
     }
 
-    /// Get all currently banned IPs
-    pub fn banned_ips(&self) -> Vec<(IpAddr, Instant)> {
+    /// Get all currently banned IPs, server-wide and per tenant (local bans
+    /// only; threat-intel entries are not listed)
+    pub fn banned_ips(&self) -> Vec<(BanKey, Instant)> {
         let now = Instant::now();
         self.bans
             .iter()
             .filter(|entry| now < *entry.value())
-            .map(|entry| (*entry.key(), *entry.value()))
+            .map(|entry| (entry.key().clone(), *entry.value()))
             .collect()
     }
 
-    fn is_whitelisted(&self, ip: &IpAddr) -> bool {
+    /// Whether `ip` is in `security.ban_whitelist` (never banned)
+    pub fn is_whitelisted(&self, ip: &IpAddr) -> bool {
         self.whitelist.iter().any(|net| net.contains(ip))
     }
 
+    /// Whether local bans are enforced (`security.ban_enabled`)
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Duration of an automatic ban (`security.ban_duration`)
+    pub fn ban_duration(&self) -> Duration {
+        self.duration
+    }
+
     /// Remove stale entries from the failures map (IPs with no recent failures).
     /// Called periodically by the cleanup task.
     pub fn cleanup_stale_failures(&self) {
         let now = Instant::now();
-        self.failures.retain(|_ip, failures| {
+        self.failures.retain(|_key, failures| {
             failures.retain(|t| now.duration_since(*t) < self.window);
             !failures.is_empty()
         });
         // L-5: Also clean up expired bans
-        self.bans.retain(|_ip, expiry| now < *expiry);
+        self.bans.retain(|_key, expiry| now < *expiry);
     }
 }
 
@@ -197,6 +318,11 @@ pub fn spawn_cleanup_task(security: Arc<tokio::sync::RwLock<super::SecurityManag
             let sec = security.read().await;
             sec.ban_manager().cleanup_stale_failures();
             sec.cleanup_rate_limiters(Duration::from_secs(600));
+            sec.spraying().cleanup_stale();
+            sec.account_lockout().cleanup_stale();
+            if let Some(detector) = sec.impossible_travel() {
+                detector.cleanup_stale();
+            }
             debug!("Security cleanup completed (bans + rate limiters)");
         }
     });
@@ -212,10 +338,10 @@ mod tests {
         let ip: IpAddr = "1.2.3.4".parse().unwrap();
 
         assert!(!mgr.is_banned(&ip));
-        mgr.record_failure(&ip);
-        mgr.record_failure(&ip);
+        mgr.record_failure(&ip, None);
+        mgr.record_failure(&ip, None);
         assert!(!mgr.is_banned(&ip));
-        mgr.record_failure(&ip);
+        mgr.record_failure(&ip, None);
         assert!(mgr.is_banned(&ip));
     }
 
@@ -224,8 +350,8 @@ mod tests {
         let mgr = BanManager::new(true, 1, 300, 60, vec!["127.0.0.1".to_string()]);
         let ip: IpAddr = "127.0.0.1".parse().unwrap();
 
-        mgr.record_failure(&ip);
-        mgr.record_failure(&ip);
+        mgr.record_failure(&ip, None);
+        mgr.record_failure(&ip, None);
         assert!(!mgr.is_banned(&ip));
     }
 
@@ -234,9 +360,37 @@ mod tests {
         let mgr = BanManager::new(false, 1, 300, 60, vec![]);
         let ip: IpAddr = "1.2.3.4".parse().unwrap();
 
-        mgr.record_failure(&ip);
-        mgr.record_failure(&ip);
+        mgr.record_failure(&ip, None);
+        mgr.record_failure(&ip, None);
+        assert!(!mgr.is_banned(&ip));
+    }
+
+    #[test]
+    fn test_tenant_bans_stay_in_their_tenant() {
+        let mgr = BanManager::new(true, 2, 300, 60, vec![]);
+        let ip: IpAddr = "1.2.3.4".parse().unwrap();
+
+        mgr.record_failure(&ip, Some("acme"));
+        mgr.record_failure(&ip, Some("acme"));
+        assert!(mgr.is_banned_for(&ip, Some("acme")));
+        assert!(!mgr.is_banned_for(&ip, Some("globex")));
         assert!(!mgr.is_banned(&ip));
+        assert_eq!(mgr.banned_ips()[0].0.to_string(), "1.2.3.4@acme");
+
+        // A server-wide ban covers every tenant
+        mgr.ban(ip, Duration::from_secs(60));
+        assert!(mgr.is_banned_for(&ip, Some("globex")));
+        assert!(mgr.unban(BanKey::new(ip, Some("acme"))));
+        assert!(mgr.unban(ip));
+        assert!(!mgr.is_banned_for(&ip, Some("acme")));
+    }
+
+    #[test]
+    fn test_ban_key_round_trips() {
+        for key in ["10.0.0.1", "10.0.0.1@acme", "2001:db8::1@acme"] {
+            assert_eq!(key.parse::<BanKey>().unwrap().to_string(), key);
+        }
+        assert!("acme@10.0.0.1".parse::<BanKey>().is_err());
     }
 
     #[test]
@@ -247,7 +401,7 @@ mod tests {
         assert!(!mgr.is_banned(&ip));
         mgr.ban(ip, Duration::from_secs(60));
         assert!(mgr.is_banned(&ip));
-        mgr.unban(&ip);
+        mgr.unban(ip);
         assert!(!mgr.is_banned(&ip));
     }
 }
@@ -0,0 +1,44 @@
+//! Decoy credentials (`[honeypot]`).
+//!
+//! A login with a decoy username/password "succeeds" into a sandboxed session:
+//! the SSH shell has only the basic virtual commands, and every forwarding or
+//! SOCKS5 CONNECT request is refused. The source is flagged with a
+//! `honeypot.triggered` audit event and, by default, banned.
+
+use crate::config::types::{HoneypotConfig, HoneypotCredential};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+pub struct Honeypot {
+    credentials: Vec<HoneypotCredential>,
+    ban_duration: Option<Duration>,
+}
+
+impl Honeypot {
+    pub fn new(config: &HoneypotConfig) -> Self {
+        Self {
+            credentials: config.credentials.clone(),
+            ban_duration: config.ban.then(|| Duration::from_secs(config.ban_duration)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    /// True if `username`/`password` is a decoy login.
+    pub fn matches(&self, username: &str, password: &str) -> bool {
+        self.credentials.iter().any(|cred| {
+            cred.username == username
+                && cred.password.as_deref().is_none_or(|expected| {
+                    expected.len() == password.len()
+                        && bool::from(expected.as_bytes().ct_eq(password.as_bytes()))
+                })
+        })
+    }
+
+    /// How long to ban a source that used a decoy login (None = no ban).
+    pub fn ban_duration(&self) -> Option<Duration> {
+        self.ban_duration
+    }
+}
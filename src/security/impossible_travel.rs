@@ -0,0 +1,188 @@
+//! Impossible travel (`[impossible_travel]`).
+//!
+//! A user logging in twice from places further apart than anyone could
+//! travel in the time between is most likely sharing credentials, or has
+//! had them stolen. Each successful login is placed with the GeoIP City
+//! database and compared with the user's other logins of the last
+//! `window_secs`: a login from another IP at least `min_distance_km` away
+//! that would need more than `max_speed_kmh` to reach is reported as an
+//! [`ImpossibleTravel`]. The caller then flags or blocks it, per user.
+//!
+//! Only logins that got past the credential check are compared, so a
+//! failed attempt cannot poison a user's record, and a blocked login is
+//! not recorded, so it cannot lock the legitimate user out in turn.
+
+use crate::config::types::{AppConfig, ImpossibleTravelAction, ImpossibleTravelConfig};
+use crate::geoip::GeoIpService;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Maximum users tracked at once.
+const MAX_TRACKED_USERS: usize = 100_000;
+
+/// Recent logins kept per user.
+const MAX_LOGINS_PER_USER: usize = 16;
+
+/// Mean Earth radius, in km.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone)]
+struct Login {
+    ip: IpAddr,
+    /// (latitude, longitude)
+    location: (f64, f64),
+    country: Option<String>,
+    at: Instant,
+}
+
+/// A login too far from one of the user's recent logins.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImpossibleTravel {
+    pub previous_ip: IpAddr,
+    pub previous_country: Option<String>,
+    pub country: Option<String>,
+    pub distance_km: u64,
+    pub elapsed_secs: u64,
+    /// Speed needed to cover the distance in time (elapsed time rounded up
+    /// to one second)
+    pub speed_kmh: u64,
+}
+
+/// Great-circle distance in km between two (latitude, longitude) points.
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// Recent located logins per user.
+pub struct ImpossibleTravelDetector {
+    config: ImpossibleTravelConfig,
+    geoip: Option<GeoIpService>,
+    logins: DashMap<String, VecDeque<Login>>,
+}
+
+impl ImpossibleTravelDetector {
+    pub fn new(config: &AppConfig) -> Self {
+        let mut detector = Self {
+            config: config.impossible_travel.clone(),
+            geoip: None,
+            logins: DashMap::new(),
+        };
+        detector.configure(config);
+        detector
+    }
+
+    /// Apply a reloaded config. Recent logins are kept.
+    pub fn configure(&mut self, config: &AppConfig) {
+        let geoip = &config.geoip;
+        self.geoip = geoip.database_path.as_deref().map(|path| {
+            GeoIpService::new(geoip.enabled, Some(path), Vec::new(), Vec::new(), false)
+        });
+        self.config = config.impossible_travel.clone();
+    }
+
+    /// Default action, for users without their own `impossible_travel`
+    pub fn action(&self) -> ImpossibleTravelAction {
+        self.config.action
+    }
+
+    /// Window over which logins are compared (`window_secs`)
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.config.window_secs)
+    }
+
+    /// Check a successful login from `ip` and record it, unless it is found
+    /// impossible and `block` is set. IPs the database cannot place are
+    /// neither checked nor recorded.
+    pub fn check(&self, username: &str, ip: &IpAddr, block: bool) -> Option<ImpossibleTravel> {
+        let geoip = self.geoip.as_ref()?;
+        let location = geoip.location(ip)?;
+        let country = geoip.country(ip);
+        self.observe(username, ip, location, country, Instant::now(), block)
+    }
+
+    /// Same as [`check`](Self::check), with the location already resolved.
+    pub fn observe(
+        &self,
+        username: &str,
+        ip: &IpAddr,
+        location: (f64, f64),
+        country: Option<String>,
+        now: Instant,
+        block: bool,
+    ) -> Option<ImpossibleTravel> {
+        if !self.logins.contains_key(username) && self.logins.len() >= MAX_TRACKED_USERS {
+            warn!("Impossible travel map capacity exceeded, rejecting new user tracking");
+            return None;
+        }
+        let window = self.window();
+        let mut logins = self.logins.entry(username.to_string()).or_default();
+        logins.retain(|login| now.saturating_duration_since(login.at) < window);
+        // An IP on record stays where it was first placed, so a GeoIP
+        // database update cannot make it travel
+        let location = logins
+            .iter()
+            .find(|login| login.ip == *ip)
+            .map_or(location, |login| login.location);
+
+        let found = logins
+            .iter()
+            .filter(|login| login.ip != *ip)
+            .filter_map(|login| {
+                let distance = distance_km(login.location, location);
+                let elapsed = now.saturating_duration_since(login.at).as_secs();
+                let speed = distance * 3600.0 / elapsed.max(1) as f64;
+                (distance >= f64::from(self.config.min_distance_km)
+                    && speed > f64::from(self.config.max_speed_kmh))
+                .then(|| ImpossibleTravel {
+                    previous_ip: login.ip,
+                    previous_country: login.country.clone(),
+                    country: country.clone(),
+                    distance_km: distance.round() as u64,
+                    elapsed_secs: elapsed,
+                    speed_kmh: speed.round() as u64,
+                })
+            })
+            .max_by_key(|travel| travel.speed_kmh);
+
+        if found.is_none() || !block {
+            logins.retain(|login| login.ip != *ip);
+            if logins.len() >= MAX_LOGINS_PER_USER {
+                logins.pop_front();
+            }
+            logins.push_back(Login {
+                ip: *ip,
+                location,
+                country,
+                at: now,
+            });
+        }
+        found
+    }
+
+    /// Forget logins older than the window.
+    pub fn cleanup_stale(&self) {
+        let now = Instant::now();
+        let window = self.window();
+        self.logins.retain(|_, logins| {
+            logins.retain(|login| now.saturating_duration_since(login.at) < window);
+            !logins.is_empty()
+        });
+    }
+
+    /// Number of users tracked
+    pub fn len(&self) -> usize {
+        self.logins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.logins.is_empty()
+    }
+}
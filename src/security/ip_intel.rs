@@ -0,0 +1,413 @@
+//! Offline IP intelligence: Tor exits and datacenter ranges (`[ip_intel]`).
+//!
+//! A dataset is compiled into the binary (`assets/ip-intel.txt`), so
+//! `ip_intel.deny` can refuse `source_is_tor` or `source_is_datacenter`
+//! clients without GeoIP databases or network access. `s5 update-intel` and
+//! `POST /api/security/ip-intel/refresh` download `tor_urls` and
+//! `datacenter_urls` into `ip_intel.path`, which replaces the embedded ranges
+//! whenever it can be read. A failed refresh keeps the current ranges.
+//!
+//! A `deny` condition the ranges cannot match (`source_is_tor` with the
+//! embedded dataset, which ships no Tor exits) fails config validation, and a
+//! download that would leave one without ranges is refused.
+//!
+//! Dataset format: one `<category> <ip-or-cidr>` per line, category `tor` or
+//! `datacenter`; `#` starts a comment.
+
+use super::threat_intel::parse_denylist;
+use crate::config::types::{IpIntelConfig, SourceCondition};
+use ipnet::IpNet;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Ranges compiled into the binary.
+pub const EMBEDDED: &str = include_str!("../../assets/ip-intel.txt");
+
+/// Largest list body accepted from one URL.
+const MAX_LIST_BYTES: usize = 32 * 1024 * 1024;
+
+/// Sorted, merged address ranges with binary-search lookup.
+#[derive(Debug, Default, Clone)]
+struct RangeSet {
+    v4: Vec<(u32, u32)>,
+    v6: Vec<(u128, u128)>,
+}
+
+impl RangeSet {
+    fn from_nets(nets: &[IpNet]) -> Self {
+        let mut set = Self::default();
+        for net in nets {
+            match net {
+                IpNet::V4(n) => set.v4.push((n.network().into(), n.broadcast().into())),
+                IpNet::V6(n) => set.v6.push((n.network().into(), n.broadcast().into())),
+            }
+        }
+        merge(&mut set.v4);
+        merge(&mut set.v6);
+        set
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(v4) => lookup(&self.v4, u32::from(*v4)),
+            IpAddr::V6(v6) => lookup(&self.v6, u128::from(*v6)),
+        }
+    }
+}
+
+/// Sort `ranges` and fold overlapping ones together.
+fn merge<T: Ord + Copy>(ranges: &mut Vec<(T, T)>) {
+    ranges.sort_unstable();
+    let mut merged: Vec<(T, T)> = Vec::with_capacity(ranges.len());
+    for &(start, end) in ranges.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    *ranges = merged;
+}
+
+fn lookup<T: Ord + Copy>(ranges: &[(T, T)], addr: T) -> bool {
+    let idx = ranges.partition_point(|&(start, _)| start <= addr);
+    idx > 0 && addr <= ranges[idx - 1].1
+}
+
+/// Parsed Tor exit and datacenter ranges.
+#[derive(Debug, Default, Clone)]
+pub struct Dataset {
+    tor: RangeSet,
+    datacenter: RangeSet,
+    tor_entries: usize,
+    datacenter_entries: usize,
+}
+
+impl Dataset {
+    pub fn new(tor: &[IpNet], datacenter: &[IpNet]) -> Self {
+        Self {
+            tor: RangeSet::from_nets(tor),
+            datacenter: RangeSet::from_nets(datacenter),
+            tor_entries: tor.len(),
+            datacenter_entries: datacenter.len(),
+        }
+    }
+
+    /// Parse the dataset format. Returns the dataset and the number of
+    /// non-empty lines that were not a known category and an IP or CIDR.
+    pub fn parse(text: &str) -> (Self, usize) {
+        let mut tor = Vec::new();
+        let mut datacenter = Vec::new();
+        let mut invalid = 0;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (category, value) = (fields.next(), fields.next());
+            let net = value.and_then(|v| {
+                v.parse::<IpNet>()
+                    .ok()
+                    .or_else(|| v.parse::<IpAddr>().ok().map(IpNet::from))
+            });
+            match (category, net) {
+                (Some("tor"), Some(net)) => tor.push(net),
+                (Some("datacenter"), Some(net)) => datacenter.push(net),
+                _ => invalid += 1,
+            }
+        }
+        (Self::new(&tor, &datacenter), invalid)
+    }
+
+    /// Dataset file content for `tor` and `datacenter`.
+    pub fn render(tor: &[IpNet], datacenter: &[IpNet]) -> String {
+        let mut out = format!(
+            "# s5 IP intelligence dataset, written by s5 {} on {}\n",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+        );
+        for net in tor {
+            out.push_str(&format!("tor {}\n", net.trunc()));
+        }
+        for net in datacenter {
+            out.push_str(&format!("datacenter {}\n", net.trunc()));
+        }
+        out
+    }
+
+    pub fn is_tor(&self, ip: &IpAddr) -> bool {
+        self.tor.contains(ip)
+    }
+
+    pub fn is_datacenter(&self, ip: &IpAddr) -> bool {
+        self.datacenter.contains(ip)
+    }
+
+    pub fn matches(&self, ip: &IpAddr, condition: SourceCondition) -> bool {
+        match condition {
+            SourceCondition::SourceIsTor => self.is_tor(ip),
+            SourceCondition::SourceIsDatacenter => self.is_datacenter(ip),
+        }
+    }
+
+    /// Tor entries, as listed (before merging)
+    pub fn tor_len(&self) -> usize {
+        self.tor_entries
+    }
+
+    /// Datacenter entries, as listed (before merging)
+    pub fn datacenter_len(&self) -> usize {
+        self.datacenter_entries
+    }
+}
+
+/// Where the current ranges came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetSource {
+    Embedded,
+    File,
+    Refresh,
+}
+
+/// Entry counts of the current ranges.
+#[derive(Debug, Clone, Serialize)]
+pub struct IpIntelStatus {
+    pub source: DatasetSource,
+    pub tor_entries: usize,
+    pub datacenter_entries: usize,
+    pub deny: Vec<SourceCondition>,
+}
+
+struct State {
+    dataset: Arc<Dataset>,
+    source: DatasetSource,
+    config: IpIntelConfig,
+}
+
+/// Current ranges, shared by the connection checks and the refresh paths.
+pub struct IpIntel {
+    state: RwLock<State>,
+}
+
+impl IpIntel {
+    /// Ranges from `config.path`, or the embedded ones if it is unset or
+    /// cannot be read.
+    pub fn new(config: &IpIntelConfig) -> Self {
+        let (dataset, source) = load(config.path.as_deref());
+        Self {
+            state: RwLock::new(State {
+                dataset: Arc::new(dataset),
+                source,
+                config: config.clone(),
+            }),
+        }
+    }
+
+    /// Apply a reloaded `[ip_intel]` and re-read `ip_intel.path`, so a
+    /// dataset refreshed out of process is picked up on SIGHUP.
+    pub fn configure(&self, config: &IpIntelConfig) {
+        let (dataset, source) = load(config.path.as_deref());
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        // Keep ranges refreshed in memory when no file replaces them
+        if source == DatasetSource::File || state.source != DatasetSource::Refresh {
+            state.dataset = Arc::new(dataset);
+            state.source = source;
+        }
+        state.config = config.clone();
+    }
+
+    pub fn dataset(&self) -> Arc<Dataset> {
+        self.state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .dataset
+            .clone()
+    }
+
+    /// The first `ip_intel.deny` condition `ip` meets, if any.
+    pub fn denied(&self, ip: &IpAddr) -> Option<SourceCondition> {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state
+            .config
+            .deny
+            .iter()
+            .copied()
+            .find(|c| state.dataset.matches(ip, *c))
+    }
+
+    pub fn status(&self) -> IpIntelStatus {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        IpIntelStatus {
+            source: state.source,
+            tor_entries: state.dataset.tor_len(),
+            datacenter_entries: state.dataset.datacenter_len(),
+            deny: state.config.deny.clone(),
+        }
+    }
+
+    /// Download fresh ranges, write them to `ip_intel.path` when set, and
+    /// use them from now on.
+    pub async fn refresh(&self) -> anyhow::Result<IpIntelStatus> {
+        let config = self
+            .state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .config
+            .clone();
+        let text = fetch(&config).await?;
+        let (dataset, _) = Dataset::parse(&text);
+        check_matched(&config.deny, &dataset, "downloaded ranges")?;
+        if let Some(ref path) = config.path {
+            write_dataset(path, &text)?;
+        }
+        info!(
+            tor = dataset.tor_len(),
+            datacenter = dataset.datacenter_len(),
+            "IP intel ranges refreshed"
+        );
+        {
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            state.dataset = Arc::new(dataset);
+            state.source = DatasetSource::Refresh;
+        }
+        Ok(self.status())
+    }
+}
+
+/// Fail unless `dataset` has ranges for every `deny` condition.
+fn check_matched(deny: &[SourceCondition], dataset: &Dataset, what: &str) -> anyhow::Result<()> {
+    for condition in deny {
+        let entries = match condition {
+            SourceCondition::SourceIsTor => dataset.tor_len(),
+            SourceCondition::SourceIsDatacenter => dataset.datacenter_len(),
+        };
+        if entries == 0 {
+            anyhow::bail!(
+                "ip_intel.deny has {} but the {} have no such ranges",
+                condition,
+                what
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Refuse `deny` conditions the ranges `config` loads cannot match, so a
+/// rule that would never fire stops startup and reloads instead.
+pub fn check_deny(config: &IpIntelConfig) -> anyhow::Result<()> {
+    if config.deny.is_empty() {
+        return Ok(());
+    }
+    let (dataset, source) = load(config.path.as_deref());
+    let what = match source {
+        DatasetSource::File => "ranges in ip_intel.path",
+        _ => "embedded ranges",
+    };
+    check_matched(&config.deny, &dataset, what).map_err(|e| {
+        anyhow::anyhow!(
+            "{}; run `s5 update-intel` to download them into ip_intel.path, \
+             and schedule it (Tor exits change hourly)",
+            e
+        )
+    })
+}
+
+/// Read the dataset at `path`, falling back to the embedded one.
+fn load(path: Option<&Path>) -> (Dataset, DatasetSource) {
+    if let Some(path) = path {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let (dataset, invalid) = Dataset::parse(&text);
+                if invalid > 0 {
+                    warn!(path = %path.display(), invalid, "Skipped invalid IP intel lines");
+                }
+                return (dataset, DatasetSource::File);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(path = %path.display(), "No IP intel dataset yet, using embedded ranges");
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to read IP intel dataset");
+            }
+        }
+    }
+    (Dataset::parse(EMBEDDED).0, DatasetSource::Embedded)
+}
+
+/// Write `text` next to `path` and rename it into place.
+pub fn write_dataset(path: &Path, text: &str) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, text).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))?;
+    Ok(())
+}
+
+async fn fetch_list(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<IpNet>> {
+    let resp = client.get(url).send().await?.error_for_status()?;
+    if resp
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_LIST_BYTES)
+    {
+        anyhow::bail!("{} larger than {} bytes", url, MAX_LIST_BYTES);
+    }
+    let body = resp.bytes().await?;
+    if body.len() > MAX_LIST_BYTES {
+        anyhow::bail!("{} larger than {} bytes", url, MAX_LIST_BYTES);
+    }
+    let (nets, invalid) = parse_denylist(&String::from_utf8_lossy(&body));
+    if invalid > 0 {
+        warn!(url, invalid, "Skipped invalid IP intel lines");
+    }
+    Ok(nets)
+}
+
+/// Download every configured list and return them as a dataset file. Fails
+/// if any list fails, so a partial download never replaces good ranges.
+pub async fn fetch(config: &IpIntelConfig) -> anyhow::Result<String> {
+    if config.tor_urls.is_empty() && config.datacenter_urls.is_empty() {
+        anyhow::bail!("no ip_intel.tor_urls or ip_intel.datacenter_urls to download");
+    }
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(60))
+        .user_agent(concat!("s5/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let mut tor = Vec::new();
+    for url in &config.tor_urls {
+        tor.extend(fetch_list(&client, url).await?);
+    }
+    let mut datacenter = Vec::new();
+    for url in &config.datacenter_urls {
+        datacenter.extend(fetch_list(&client, url).await?);
+    }
+    Ok(Dataset::render(&tor, &datacenter))
+}
+
+/// `s5 update-intel`: download the ranges into `output`, `ip_intel.path`,
+/// or stdout.
+pub fn update_intel_cli(config: &IpIntelConfig, output: Option<&Path>) -> anyhow::Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let text = rt.block_on(fetch(config))?;
+    let (dataset, _) = Dataset::parse(&text);
+    check_matched(&config.deny, &dataset, "downloaded ranges")?;
+    match output.or(config.path.as_deref()) {
+        Some(path) => {
+            write_dataset(path, &text)?;
+            eprintln!(
+                "Wrote {} Tor and {} datacenter entries to {}",
+                dataset.tor_len(),
+                dataset.datacenter_len(),
+                path.display()
+            );
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
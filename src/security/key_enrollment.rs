@@ -0,0 +1,213 @@
+//! Admin approval of public keys a user presents for the first time
+//! (`[key_enrollment]`).
+//!
+//! When a configured user signs in with a key that is not in their
+//! `authorized_keys`, russh has already checked the signature, so the client
+//! holds the private key. Instead of refusing it outright, the key is queued
+//! as a [`KeyEnrollmentRequest`] and the SSH login is held for `wait_secs`.
+//! An admin approves or rejects it with `/api/key-enrollments`. An approved
+//! key is added to the user's `authorized_keys`, so the held login (or the
+//! next one) goes through. A rejected key is refused without a new request
+//! until the rejection expires.
+//!
+//! Requests live in memory: decided or not, they are dropped
+//! `pending_hours` after they were made, and on restart.
+
+use crate::config::types::{AppConfig, KeyEnrollmentConfig};
+use chrono::{DateTime, Duration, Utc};
+use russh::keys::{PublicKey, PublicKeyBase64};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// State of an enrollment request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnrollmentStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// One entry of `GET /api/key-enrollments`.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyEnrollmentRequest {
+    pub id: String,
+    pub username: String,
+    /// OpenSSH-style `SHA256:<base64>` fingerprint
+    pub fingerprint: String,
+    /// `<algorithm> <base64>`, as added to `authorized_keys` on approval
+    pub public_key: String,
+    pub source_ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hassh: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub status: EnrollmentStatus,
+    /// API principal that approved or rejected the key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+}
+
+/// Outcome of presenting an unknown key, see [`KeyEnrollment::request`].
+pub enum Enrollment {
+    /// Queued now: tell the admins, then wait for the decision
+    New(KeyEnrollmentRequest, watch::Receiver<EnrollmentStatus>),
+    /// Already queued by an earlier login: wait for the same decision
+    Pending(watch::Receiver<EnrollmentStatus>),
+    /// Rejected earlier, or the user already has `max_pending_per_user`
+    /// requests waiting
+    Refused,
+}
+
+struct Entry {
+    request: KeyEnrollmentRequest,
+    decision: watch::Sender<EnrollmentStatus>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Queue of keys waiting for (or holding) an admin decision.
+pub struct KeyEnrollment {
+    config: KeyEnrollmentConfig,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl KeyEnrollment {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            config: config.key_enrollment.clone(),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Apply a reloaded config. Queued requests are kept.
+    pub fn configure(&mut self, config: &AppConfig) {
+        self.config = config.key_enrollment.clone();
+    }
+
+    /// How long an SSH login is held waiting for a decision.
+    pub fn wait(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.config.wait_secs)
+    }
+
+    /// Queue `key` for `username`, or join the request already made for it.
+    pub fn request(
+        &self,
+        username: &str,
+        key: &PublicKey,
+        source: IpAddr,
+        hassh: Option<String>,
+    ) -> Enrollment {
+        let now = Utc::now();
+        let fingerprint = key_fingerprint(key);
+        let mut entries = self.lock(now);
+        if let Some(entry) = entries
+            .iter()
+            .find(|e| e.request.username == username && e.request.fingerprint == fingerprint)
+        {
+            return match entry.request.status {
+                EnrollmentStatus::Rejected => Enrollment::Refused,
+                // Approved but not in authorized_keys (yet): the decision
+                // is already sent, the caller re-checks the key
+                _ => Enrollment::Pending(entry.decision.subscribe()),
+            };
+        }
+        let pending = entries
+            .iter()
+            .filter(|e| {
+                e.request.username == username && e.request.status == EnrollmentStatus::Pending
+            })
+            .count();
+        if pending >= self.config.max_pending_per_user {
+            return Enrollment::Refused;
+        }
+
+        let request = KeyEnrollmentRequest {
+            id: crate::utils::generate_correlation_id(),
+            username: username.to_string(),
+            fingerprint,
+            public_key: format!("{} {}", key.algorithm().as_str(), key.public_key_base64()),
+            source_ip: source.to_string(),
+            hassh,
+            requested_at: now,
+            status: EnrollmentStatus::Pending,
+            decided_by: None,
+        };
+        let (decision, rx) = watch::channel(EnrollmentStatus::Pending);
+        entries.push(Entry {
+            request: request.clone(),
+            decision,
+            expires_at: now + self.pending_for(),
+        });
+        Enrollment::New(request, rx)
+    }
+
+    /// All requests not yet expired, oldest first.
+    pub fn list(&self) -> Vec<KeyEnrollmentRequest> {
+        self.lock(Utc::now())
+            .iter()
+            .map(|e| e.request.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<KeyEnrollmentRequest> {
+        self.lock(Utc::now())
+            .iter()
+            .find(|e| e.request.id == id)
+            .map(|e| e.request.clone())
+    }
+
+    /// Record the decision on a pending request and wake the logins held on
+    /// it. Returns the updated request, or None if `id` is unknown or was
+    /// already decided.
+    pub fn decide(&self, id: &str, approved: bool, by: &str) -> Option<KeyEnrollmentRequest> {
+        let mut entries = self.lock(Utc::now());
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.request.id == id && e.request.status == EnrollmentStatus::Pending)?;
+        let status = if approved {
+            EnrollmentStatus::Approved
+        } else {
+            EnrollmentStatus::Rejected
+        };
+        entry.request.status = status;
+        entry.request.decided_by = Some(by.to_string());
+        entry.decision.send_replace(status);
+        Some(entry.request.clone())
+    }
+
+    /// Drop requests made more than `pending_hours` before `now`. Logins
+    /// still held on an undecided one are released as rejected.
+    pub fn expire(&self, now: DateTime<Utc>) {
+        drop(self.lock(now));
+    }
+
+    fn pending_for(&self) -> Duration {
+        // Validation caps pending_hours at a year
+        Duration::hours(self.config.pending_hours.min(8_760) as i64)
+    }
+
+    /// Lock the queue with expired entries removed.
+    fn lock(&self, now: DateTime<Utc>) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|e| {
+            let live = e.expires_at > now;
+            if !live && e.request.status == EnrollmentStatus::Pending {
+                e.decision.send_replace(EnrollmentStatus::Rejected);
+            }
+            live
+        });
+        entries
+    }
+}
+
+/// OpenSSH-style `SHA256:<base64>` fingerprint of a public key.
+pub fn key_fingerprint(key: &PublicKey) -> String {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+    let hash = Sha256::digest(key.public_key_bytes());
+    format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash)
+    )
+}
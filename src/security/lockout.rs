@@ -0,0 +1,175 @@
+//! Per-account lockout (`security.lock_after_failures`).
+//!
+//! IP bans do not stop a brute force spread over many source addresses. This
+//! counts consecutive failed password logins per username, whatever the
+//! source, and locks the account for `lock_duration` once the count reaches
+//! `lock_after_failures`. A locked account refuses every login, even with the
+//! right credentials, until the lock expires or an operator lifts it.
+
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Maximum usernames tracked at once (unknown usernames are tracked too, so
+/// a lockout reveals nothing about which accounts exist).
+const MAX_TRACKED_ACCOUNTS: usize = 100_000;
+
+/// Usernames are cut to this many characters before being stored.
+const MAX_USERNAME_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct AccountState {
+    /// Consecutive failures since the last success or lock
+    failures: u32,
+    last_failure: Instant,
+    /// Lock expiry, while locked
+    locked_until: Option<Instant>,
+}
+
+/// Failure counts and locks per username
+pub struct AccountLockout {
+    /// Failures before locking; 0 = disabled
+    threshold: u32,
+    /// Lock length, also the time after which failures are forgotten
+    duration: Duration,
+    accounts: DashMap<String, AccountState>,
+}
+
+fn account_key(username: &str) -> String {
+    username.chars().take(MAX_USERNAME_CHARS).collect()
+}
+
+impl AccountLockout {
+    pub fn new(lock_after_failures: u32, lock_duration_secs: u64) -> Self {
+        Self {
+            threshold: lock_after_failures,
+            duration: Duration::from_secs(lock_duration_secs),
+            accounts: DashMap::new(),
+        }
+    }
+
+    /// Apply reloaded settings. Current locks are kept unless lockout is
+    /// turned off.
+    pub fn configure(&mut self, lock_after_failures: u32, lock_duration_secs: u64) {
+        self.threshold = lock_after_failures;
+        self.duration = Duration::from_secs(lock_duration_secs);
+        if !self.is_enabled() {
+            self.accounts.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    /// Lock length (`security.lock_duration`)
+    pub fn lock_duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Record a failed login as `username`. Returns the failure count when
+    /// this failure locks the account. Failures while locked are ignored and
+    /// do not extend the lock.
+    pub fn record_failure(&self, username: &str) -> Option<u32> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let key = account_key(username);
+        if !self.accounts.contains_key(&key) && self.accounts.len() >= MAX_TRACKED_ACCOUNTS {
+            warn!("Account lockout map capacity exceeded, rejecting new account tracking");
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut state = self.accounts.entry(key).or_insert(AccountState {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        match state.locked_until {
+            Some(until) if now < until => return None,
+            Some(_) => state.locked_until = None,
+            None => {}
+        }
+        if now.duration_since(state.last_failure) >= self.duration {
+            state.failures = 0;
+        }
+        state.failures += 1;
+        state.last_failure = now;
+        if state.failures < self.threshold {
+            return None;
+        }
+        let failures = state.failures;
+        state.failures = 0;
+        state.locked_until = Some(now + self.duration);
+        Some(failures)
+    }
+
+    /// A successful login clears the failure count of an unlocked account.
+    pub fn record_success(&self, username: &str) {
+        self.accounts.remove_if(&account_key(username), |_, state| {
+            state.locked_until.is_none()
+        });
+    }
+
+    /// Whether `username` is locked right now
+    pub fn is_locked(&self, username: &str) -> bool {
+        self.remaining(username).is_some()
+    }
+
+    /// Time left on `username`'s lock, if locked
+    pub fn remaining(&self, username: &str) -> Option<Duration> {
+        let now = Instant::now();
+        self.accounts
+            .get(&account_key(username))
+            .and_then(|state| state.locked_until)
+            .filter(|until| now < *until)
+            .map(|until| until - now)
+    }
+
+    /// Lift `username`'s lock and clear its failures. Returns whether it was
+    /// locked.
+    pub fn unlock(&self, username: &str) -> bool {
+        let now = Instant::now();
+        let unlocked = self
+            .accounts
+            .remove(&account_key(username))
+            .is_some_and(|(_, state)| state.locked_until.is_some_and(|until| now < until));
+        if unlocked {
+            info!(user = %username, "Account manually unlocked");
+        }
+        unlocked
+    }
+
+    /// Locked accounts with their lock expiry
+    pub fn locked_accounts(&self) -> Vec<(String, Instant)> {
+        let now = Instant::now();
+        self.accounts
+            .iter()
+            .filter_map(|entry| {
+                entry
+                    .locked_until
+                    .filter(|until| now < *until)
+                    .map(|until| (entry.key().clone(), until))
+            })
+            .collect()
+    }
+
+    /// Forget expired locks and failures older than `lock_duration`.
+    pub fn cleanup_stale(&self) {
+        let now = Instant::now();
+        self.accounts.retain(|_, state| match state.locked_until {
+            Some(until) => now < until,
+            None => now.duration_since(state.last_failure) < self.duration,
+        });
+    }
+
+    /// Number of usernames tracked
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
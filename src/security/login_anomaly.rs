@@ -0,0 +1,181 @@
+//! Logins that break a user's habits (`[login_anomaly]`).
+//!
+//! Each successful login (SSH or SOCKS5) is compared with the user's
+//! history: the countries and autonomous systems they logged in from and
+//! the UTC hours they logged in at. Once `learning_logins` logins are on
+//! record, a new country, a new ASN or an hour more than `hour_tolerance`
+//! away from every known one is reported as a [`LoginAnomaly`]. The login
+//! is allowed and added to the history either way, so a new habit is only
+//! flagged once.
+//!
+//! The history is kept in memory and, with `history_path`, rewritten to a
+//! JSON file after every login so baselines survive restarts.
+
+use crate::config::types::{AppConfig, LoginAnomalyConfig};
+use crate::geoip::GeoIpService;
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Reason reported for a country not seen for the user.
+pub const NEW_COUNTRY: &str = "new_country";
+/// Reason reported for an autonomous system not seen for the user.
+pub const NEW_ASN: &str = "new_asn";
+/// Reason reported for a login far from the user's usual hours.
+pub const UNUSUAL_HOUR: &str = "unusual_hour";
+
+/// Logins on record for one user.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UserLogins {
+    logins: u64,
+    countries: BTreeSet<String>,
+    asns: BTreeSet<u32>,
+    /// Logins per UTC hour of day
+    hours: [u64; 24],
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LoginHistory {
+    users: BTreeMap<String, UserLogins>,
+}
+
+/// A login that did not match the user's history.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoginAnomaly {
+    /// [`NEW_COUNTRY`], [`NEW_ASN`] and/or [`UNUSUAL_HOUR`]
+    pub reasons: Vec<&'static str>,
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    /// UTC hour of the login
+    pub hour: u32,
+    /// Logins on record before this one
+    pub known_logins: u64,
+}
+
+/// Per-user login history and the checks run against it.
+pub struct LoginAnomalyDetector {
+    config: LoginAnomalyConfig,
+    countries: Option<GeoIpService>,
+    asns: Option<GeoIpService>,
+    history: Mutex<LoginHistory>,
+}
+
+impl LoginAnomalyDetector {
+    /// Open the GeoIP databases and load `history_path`, if set. A missing
+    /// history file starts an empty history; an unreadable one is logged
+    /// and replaced on the next login.
+    pub fn new(config: &AppConfig) -> Self {
+        let history = config
+            .login_anomaly
+            .history_path
+            .as_deref()
+            .map(load_history)
+            .unwrap_or_default();
+        let mut detector = Self {
+            config: config.login_anomaly.clone(),
+            countries: None,
+            asns: None,
+            history: Mutex::new(history),
+        };
+        detector.configure(config);
+        detector
+    }
+
+    /// Apply a reloaded config. The history in memory is kept.
+    pub fn configure(&mut self, config: &AppConfig) {
+        let geoip = &config.geoip;
+        let open = |path: Option<&Path>| {
+            GeoIpService::new(geoip.enabled, path, Vec::new(), Vec::new(), false)
+        };
+        let anomaly = &config.login_anomaly;
+        self.countries = (anomaly.new_country && geoip.database_path.is_some())
+            .then(|| open(geoip.database_path.as_deref()));
+        self.asns = (anomaly.new_asn && geoip.asn_database_path.is_some())
+            .then(|| open(geoip.asn_database_path.as_deref()));
+        self.config = anomaly.clone();
+    }
+
+    /// Check a successful login from `ip` at `at` and add it to the history.
+    pub fn check(&self, username: &str, ip: &IpAddr, at: DateTime<Utc>) -> Option<LoginAnomaly> {
+        let country = self.countries.as_ref().and_then(|g| g.country(ip));
+        let asn = self.asns.as_ref().and_then(|g| g.asn(ip));
+        self.observe(username, country.as_deref(), asn, at)
+    }
+
+    /// Same as [`check`](Self::check), with the location already resolved.
+    pub fn observe(
+        &self,
+        username: &str,
+        country: Option<&str>,
+        asn: Option<u32>,
+        at: DateTime<Utc>,
+    ) -> Option<LoginAnomaly> {
+        let hour = at.hour();
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let user = history.users.entry(username.to_string()).or_default();
+        let trusted = user.logins >= u64::from(self.config.learning_logins);
+
+        let mut reasons = Vec::new();
+        if trusted {
+            if self.config.new_country && country.is_some_and(|c| !user.countries.contains(c)) {
+                reasons.push(NEW_COUNTRY);
+            }
+            if self.config.new_asn && asn.is_some_and(|a| !user.asns.contains(&a)) {
+                reasons.push(NEW_ASN);
+            }
+            if self.config.unusual_hours && !self.usual_hour(user, hour) {
+                reasons.push(UNUSUAL_HOUR);
+            }
+        }
+        let anomaly = (!reasons.is_empty()).then(|| LoginAnomaly {
+            reasons,
+            country: country.map(str::to_string),
+            asn,
+            hour,
+            known_logins: user.logins,
+        });
+
+        user.logins += 1;
+        user.countries.extend(country.map(str::to_string));
+        user.asns.extend(asn);
+        user.hours[hour as usize] += 1;
+        if let Some(path) = self.config.history_path.as_deref() {
+            if let Err(e) = save_history(path, &history) {
+                warn!(path = %path.display(), error = %e, "Failed to save login history");
+            }
+        }
+        anomaly
+    }
+
+    /// Whether some past login happened within `hour_tolerance` hours of
+    /// `hour`, wrapping around midnight.
+    fn usual_hour(&self, user: &UserLogins, hour: u32) -> bool {
+        let tolerance = self.config.hour_tolerance.min(12) as i64;
+        (-tolerance..=tolerance).any(|d| user.hours[(hour as i64 + d).rem_euclid(24) as usize] > 0)
+    }
+}
+
+fn load_history(path: &Path) -> LoginHistory {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(path = %path.display(), error = %e, "Ignoring unreadable login history");
+            LoginHistory::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => LoginHistory::default(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read login history");
+            LoginHistory::default()
+        }
+    }
+}
+
+/// Write through a temporary file so a crash never leaves half a history.
+fn save_history(path: &Path, history: &LoginHistory) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(history)?)?;
+    std::fs::rename(&tmp, path)
+}
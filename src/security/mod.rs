@@ -1,16 +1,33 @@
 pub mod ban;
+pub mod honeypot;
+pub mod impossible_travel;
 pub mod ip_filter;
+pub mod ip_intel;
 pub mod ip_reputation;
+pub mod key_enrollment;
+pub mod lockout;
+pub mod login_anomaly;
 pub mod normalize;
 pub mod rate_limit;
+pub mod spraying;
+pub mod tarpit;
+pub mod threat_intel;
+pub mod timeline;
 
 use crate::audit::AuditLogger;
-use crate::config::types::AppConfig;
+use crate::config::types::{AppConfig, SourceCondition};
 use ban::BanManager;
+use honeypot::Honeypot;
+use impossible_travel::ImpossibleTravelDetector;
+use ip_intel::IpIntel;
 use ip_reputation::IpReputationManager;
 use ipnet::IpNet;
+use key_enrollment::KeyEnrollment;
+use lockout::AccountLockout;
+use login_anomaly::LoginAnomalyDetector;
 use normalize::normalize_ip;
 use rate_limit::{IpRateLimiter, UserRateLimiter};
+use spraying::SprayingDetector;
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -40,6 +57,19 @@ pub struct SecurityManager {
     global_allowed_ips: Vec<IpNet>,
     /// L-4: Ban whitelist supports CIDR ranges
     ban_whitelist: Vec<IpNet>,
+    /// `[ip_intel]` ranges, shared with the refresh API
+    ip_intel: Arc<IpIntel>,
+    honeypot: Honeypot,
+    /// `[login_anomaly]`, when enabled
+    login_anomaly: Option<LoginAnomalyDetector>,
+    /// `[impossible_travel]`, when enabled
+    impossible_travel: Option<ImpossibleTravelDetector>,
+    /// `[key_enrollment]`, when enabled
+    key_enrollment: Option<KeyEnrollment>,
+    /// `[security.credential_spraying]`
+    spraying: SprayingDetector,
+    /// `security.lock_after_failures`
+    account_lockout: AccountLockout,
 }
 
 impl SecurityManager {
@@ -67,6 +97,25 @@ impl SecurityManager {
             ip_reputation,
             global_allowed_ips: config.security.allowed_source_ips.clone(),
             ban_whitelist: parse_ban_whitelist(&config.security.ban_whitelist),
+            ip_intel: Arc::new(IpIntel::new(&config.ip_intel)),
+            honeypot: Honeypot::new(&config.honeypot),
+            login_anomaly: config
+                .login_anomaly
+                .enabled
+                .then(|| LoginAnomalyDetector::new(config)),
+            impossible_travel: config
+                .impossible_travel
+                .enabled
+                .then(|| ImpossibleTravelDetector::new(config)),
+            key_enrollment: config
+                .key_enrollment
+                .enabled
+                .then(|| KeyEnrollment::new(config)),
+            spraying: SprayingDetector::new(&config.security.credential_spraying),
+            account_lockout: AccountLockout::new(
+                config.security.lock_after_failures,
+                config.security.lock_duration,
+            ),
         }
     }
 
@@ -91,6 +140,45 @@ impl SecurityManager {
         );
         self.global_allowed_ips = config.security.allowed_source_ips.clone();
         self.ban_whitelist = parse_ban_whitelist(&config.security.ban_whitelist);
+        self.ip_intel.configure(&config.ip_intel);
+        self.honeypot = Honeypot::new(&config.honeypot);
+        // Keep the login history when the detector stays enabled
+        self.login_anomaly = match (self.login_anomaly.take(), config.login_anomaly.enabled) {
+            (Some(mut detector), true) => {
+                detector.configure(config);
+                Some(detector)
+            }
+            (None, true) => Some(LoginAnomalyDetector::new(config)),
+            (_, false) => None,
+        };
+        // Recent logins survive a reload, not disabling the feature
+        self.impossible_travel = match (
+            self.impossible_travel.take(),
+            config.impossible_travel.enabled,
+        ) {
+            (Some(mut detector), true) => {
+                detector.configure(config);
+                Some(detector)
+            }
+            (None, true) => Some(ImpossibleTravelDetector::new(config)),
+            (_, false) => None,
+        };
+        // Queued requests survive a reload, not disabling the feature
+        self.key_enrollment = match (self.key_enrollment.take(), config.key_enrollment.enabled) {
+            (Some(mut queue), true) => {
+                queue.configure(config);
+                Some(queue)
+            }
+            (None, true) => Some(KeyEnrollment::new(config)),
+            (_, false) => None,
+        };
+        self.spraying
+            .configure(&config.security.credential_spraying);
+        // Current locks survive a reload
+        self.account_lockout.configure(
+            config.security.lock_after_failures,
+            config.security.lock_duration,
+        );
     }
 
     pub fn is_banned(&self, ip: &IpAddr) -> bool {
@@ -98,9 +186,27 @@ impl SecurityManager {
         self.ban_manager.is_banned(&ip)
     }
 
-    pub fn record_auth_failure(&self, ip: &IpAddr) {
+    /// Banned server-wide or for the logins of `tenant`.
+    pub fn is_banned_for(&self, ip: &IpAddr, tenant: Option<&str>) -> bool {
+        let ip = normalize_ip(*ip);
+        self.ban_manager.is_banned_for(&ip, tenant)
+    }
+
+    /// Record a failed login to a user of `tenant` (None = outside tenants).
+    pub fn record_auth_failure(&self, ip: &IpAddr, tenant: Option<&str>) {
         let ip = normalize_ip(*ip);
-        self.ban_manager.record_failure(&ip);
+        self.ban_manager.record_failure(&ip, tenant);
+    }
+
+    /// Record a failed login as `username` with the credential spraying
+    /// detector. Returns the usernames tried when `ip` is found spraying.
+    /// Sources in `ban_whitelist` are not tracked.
+    pub fn record_username_failure(&self, ip: &IpAddr, username: &str) -> Option<Vec<String>> {
+        let ip = normalize_ip(*ip);
+        if self.ban_whitelist.iter().any(|net| net.contains(&ip)) {
+            return None;
+        }
+        self.spraying.record_failure(&ip, username)
     }
 
     pub fn check_source_ip(&self, ip: &IpAddr) -> bool {
@@ -108,22 +214,29 @@ impl SecurityManager {
         ip_filter::is_allowed(&ip, &self.global_allowed_ips)
     }
 
-    /// Combined pre-authentication check: IP rate limit + IP allowlist + ban status.
+    /// Combined pre-authentication check: IP rate limit + IP allowlist +
+    /// `ip_intel.deny` + ban status.
     /// Returns Ok(()) if the IP is allowed, or Err with a reason string.
     pub fn pre_auth_check(&self, ip: &IpAddr) -> Result<(), &'static str> {
         // P2-1: Per-IP rate limiting (before other checks)
         let normalized = normalize_ip(*ip);
-        if !self
+        let whitelisted = self
             .ban_whitelist
             .iter()
-            .any(|net| net.contains(&normalized))
-            && !self.ip_rate_limiter.check(&normalized)
-        {
+            .any(|net| net.contains(&normalized));
+        if !whitelisted && !self.ip_rate_limiter.check(&normalized) {
             return Err("IP rate limit exceeded");
         }
         if !self.check_source_ip(ip) {
             return Err("disallowed source IP");
         }
+        if !whitelisted {
+            match self.ip_intel.denied(&normalized) {
+                Some(SourceCondition::SourceIsTor) => return Err("Tor exit source"),
+                Some(SourceCondition::SourceIsDatacenter) => return Err("datacenter source"),
+                None => {}
+            }
+        }
         if self.is_banned(ip) {
             return Err("banned IP");
         }
@@ -142,6 +255,30 @@ impl SecurityManager {
         self.ban_manager.set_audit(audit);
     }
 
+    pub fn set_threat_intel(&mut self, threat_intel: Arc<threat_intel::ThreatIntel>) {
+        self.ban_manager.set_threat_intel(threat_intel);
+    }
+
+    pub fn ip_intel(&self) -> &Arc<IpIntel> {
+        &self.ip_intel
+    }
+
+    pub fn honeypot(&self) -> &Honeypot {
+        &self.honeypot
+    }
+
+    pub fn login_anomaly(&self) -> Option<&LoginAnomalyDetector> {
+        self.login_anomaly.as_ref()
+    }
+
+    pub fn impossible_travel(&self) -> Option<&ImpossibleTravelDetector> {
+        self.impossible_travel.as_ref()
+    }
+
+    pub fn key_enrollment(&self) -> Option<&KeyEnrollment> {
+        self.key_enrollment.as_ref()
+    }
+
     pub fn ban_manager(&self) -> &BanManager {
         &self.ban_manager
     }
@@ -150,6 +287,14 @@ impl SecurityManager {
         &self.ip_reputation
     }
 
+    pub fn spraying(&self) -> &SprayingDetector {
+        &self.spraying
+    }
+
+    pub fn account_lockout(&self) -> &AccountLockout {
+        &self.account_lockout
+    }
+
     /// Clean up stale rate limiter entries
     pub fn cleanup_rate_limiters(&self, max_age: Duration) {
         self.rate_limiter.cleanup_stale(max_age);
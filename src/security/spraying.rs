@@ -0,0 +1,114 @@
+//! Credential spraying detection (`[security.credential_spraying]`).
+//!
+//! Spraying tries a few common passwords against many accounts from one
+//! source, slowly enough that no account, and no `ban_window`, sees enough
+//! failures to trip the auto-ban. This detector counts instead the distinct
+//! usernames each source IP failed to log in as over a longer window, and
+//! reports the IP once it reaches `min_usernames`.
+
+use crate::config::types::CredentialSprayingConfig;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Maximum source IPs tracked at once (same cap as the ban manager).
+const MAX_TRACKED_IPS: usize = 100_000;
+
+/// Usernames are cut to this many characters before being stored.
+const MAX_USERNAME_CHARS: usize = 64;
+
+/// Distinct usernames per source IP, with the time of the last failure.
+pub struct SprayingDetector {
+    enabled: bool,
+    min_usernames: usize,
+    window: Duration,
+    failures: DashMap<IpAddr, HashMap<String, Instant>>,
+}
+
+impl SprayingDetector {
+    pub fn new(config: &CredentialSprayingConfig) -> Self {
+        let mut detector = Self {
+            enabled: false,
+            min_usernames: 0,
+            window: Duration::ZERO,
+            failures: DashMap::new(),
+        };
+        detector.configure(config);
+        detector
+    }
+
+    /// Apply a reloaded configuration, keeping the failures on record.
+    pub fn configure(&mut self, config: &CredentialSprayingConfig) {
+        self.enabled = config.enabled;
+        self.min_usernames = config.min_usernames as usize;
+        self.window = Duration::from_secs(config.window_secs);
+        if !self.enabled {
+            self.failures.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Window over which usernames are counted (`window_secs`)
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Record a failed login as `username` from `ip`. Returns the usernames
+    /// tried, sorted, when this failure brings the IP to `min_usernames`;
+    /// the IP's record then starts over.
+    pub fn record_failure(&self, ip: &IpAddr, username: &str) -> Option<Vec<String>> {
+        self.record_failure_at(ip, username, Instant::now())
+    }
+
+    /// [`record_failure`](Self::record_failure) at a given time.
+    pub fn record_failure_at(
+        &self,
+        ip: &IpAddr,
+        username: &str,
+        now: Instant,
+    ) -> Option<Vec<String>> {
+        if !self.enabled {
+            return None;
+        }
+        if !self.failures.contains_key(ip) && self.failures.len() >= MAX_TRACKED_IPS {
+            warn!("Credential spraying map capacity exceeded, rejecting new IP tracking");
+            return None;
+        }
+
+        let mut usernames = self.failures.entry(*ip).or_default();
+        usernames.retain(|_, last| now.saturating_duration_since(*last) < self.window);
+        let username: String = username.chars().take(MAX_USERNAME_CHARS).collect();
+        usernames.insert(username, now);
+        if usernames.len() < self.min_usernames {
+            return None;
+        }
+        let mut tried: Vec<String> = usernames.drain().map(|(name, _)| name).collect();
+        drop(usernames);
+        self.failures.remove(ip);
+        tried.sort();
+        Some(tried)
+    }
+
+    /// Forget IPs with no failure left in the window.
+    pub fn cleanup_stale(&self) {
+        let now = Instant::now();
+        self.failures.retain(|_, usernames| {
+            usernames.retain(|_, last| now.saturating_duration_since(*last) < self.window);
+            !usernames.is_empty()
+        });
+    }
+
+    /// Number of source IPs tracked
+    pub fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
@@ -0,0 +1,141 @@
+//! SSH tarpit for banned clients (`security.tarpit_*`).
+//!
+//! RFC 4253 section 4.2 lets a server send other lines before its
+//! identification string. The tarpit accepts the socket and writes one short
+//! random line every `tarpit_interval` seconds, never the `SSH-` banner, so
+//! scanners wait (endlessh-style) while costing a few bytes per tick.
+
+use crate::config::types::SecurityConfig;
+use crate::metrics::MetricsRegistry;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::gauge::Gauge;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// Longest pre-banner line sent, CRLF excluded.
+pub const MAX_LINE_LEN: usize = 32;
+
+/// Random pre-banner line: alphanumeric (so it never starts with `SSH-`),
+/// CRLF-terminated.
+pub fn tarpit_line(rng: &mut impl Rng) -> Vec<u8> {
+    let len = rng.gen_range(3..=MAX_LINE_LEN);
+    let mut line: Vec<u8> = (0..len).map(|_| rng.sample(Alphanumeric)).collect();
+    line.extend_from_slice(b"\r\n");
+    line
+}
+
+/// Bounded pool of tarpitted sockets.
+pub struct Tarpit {
+    slots: Arc<Semaphore>,
+    interval: Duration,
+    /// None = hold until the client disconnects
+    max_duration: Option<Duration>,
+    active: Gauge,
+    trapped_total: Counter,
+    rejected_total: Counter,
+}
+
+impl Tarpit {
+    pub fn new(max_connections: usize, interval: Duration, max_duration: Option<Duration>) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_connections)),
+            interval,
+            max_duration,
+            active: Gauge::default(),
+            trapped_total: Counter::default(),
+            rejected_total: Counter::default(),
+        }
+    }
+
+    pub fn from_config(config: &SecurityConfig) -> Self {
+        Self::new(
+            config.tarpit_max_connections,
+            Duration::from_secs(config.tarpit_interval),
+            (config.tarpit_max_duration > 0)
+                .then(|| Duration::from_secs(config.tarpit_max_duration)),
+        )
+    }
+
+    /// Report to the `s5_tarpit_*` metrics.
+    pub fn with_metrics(mut self, metrics: &MetricsRegistry) -> Self {
+        self.active = metrics.tarpit_active.clone();
+        self.trapped_total = metrics.tarpit_connections_total.clone();
+        self.rejected_total = metrics.tarpit_rejected_total.clone();
+        self
+    }
+
+    /// Sockets currently held.
+    pub fn active(&self) -> usize {
+        self.active.get().max(0) as usize
+    }
+
+    /// Hold `stream` in the tarpit. Returns false (dropping the stream) when
+    /// every slot is taken.
+    pub fn try_trap<S>(&self, stream: S, shutdown: CancellationToken) -> bool
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        let Ok(permit) = self.slots.clone().try_acquire_owned() else {
+            self.rejected_total.inc();
+            return false;
+        };
+        self.trapped_total.inc();
+        self.active.inc();
+        let active = self.active.clone();
+        let interval = self.interval;
+        let max_duration = self.max_duration;
+        tokio::spawn(async move {
+            let _permit = permit;
+            let started = Instant::now();
+            let lines = drip(stream, interval, max_duration, shutdown).await;
+            active.dec();
+            debug!(
+                held_secs = started.elapsed().as_secs(),
+                lines, "Tarpitted connection released"
+            );
+        });
+        true
+    }
+}
+
+/// Write a line every `interval` until the peer goes away, `max_duration`
+/// elapses or `shutdown` fires. Returns the number of lines written.
+async fn drip<S>(
+    mut stream: S,
+    interval: Duration,
+    max_duration: Option<Duration>,
+    shutdown: CancellationToken,
+) -> u64
+where
+    S: AsyncWrite + Unpin,
+{
+    let deadline = async {
+        match max_duration {
+            Some(d) => tokio::time::sleep(d).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
+    let mut ticker = tokio::time::interval(interval);
+    let mut lines = 0;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = &mut deadline => break,
+            _ = ticker.tick() => {}
+        }
+        let line = tarpit_line(&mut rand::thread_rng());
+        if stream.write_all(&line).await.is_err() || stream.flush().await.is_err() {
+            break;
+        }
+        lines += 1;
+    }
+    let _ = stream.shutdown().await;
+    lines
+}
@@ -0,0 +1,441 @@
+//! External threat intelligence merged into the ban engine (`[threat_intel]`).
+//!
+//! Feeds are pulled on `refresh_interval`:
+//! - CrowdSec LAPI in bouncer stream mode (`/v1/decisions/stream`): the first
+//!   pull loads all active `ban` decisions, later pulls apply new and deleted
+//!   ones. Decisions expire after their CrowdSec duration.
+//! - Plain-text denylists, one IP or CIDR per line (`#` and `;` start a
+//!   comment, as in Spamhaus DROP). Each refresh replaces the whole list.
+//!
+//! [`BanManager::is_banned`](super::ban::BanManager::is_banned) consults the
+//! merged feeds, so blocked IPs are refused wherever local bans are. A failed
+//! refresh keeps the previous entries.
+
+use crate::config::types::{CrowdSecConfig, DenylistConfig, ThreatIntelConfig};
+use crate::metrics::collectors::FeedLabel;
+use crate::metrics::MetricsRegistry;
+use ipnet::IpNet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Feed name of CrowdSec decisions.
+pub const CROWDSEC_FEED: &str = "crowdsec";
+
+/// Largest denylist body accepted.
+const MAX_DENYLIST_BYTES: usize = 32 * 1024 * 1024;
+
+/// One blocked IP or range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub net: IpNet,
+    /// None = until the feed drops it
+    pub expires: Option<Instant>,
+}
+
+#[derive(Default)]
+struct FeedEntries {
+    /// Single addresses (host-length prefixes)
+    ips: HashMap<IpAddr, Option<Instant>>,
+    ranges: Vec<(IpNet, Option<Instant>)>,
+}
+
+impl FeedEntries {
+    fn insert(&mut self, d: Decision) {
+        if d.net.prefix_len() == d.net.max_prefix_len() {
+            self.ips.insert(d.net.addr(), d.expires);
+        } else {
+            self.ranges.retain(|(n, _)| *n != d.net);
+            self.ranges.push((d.net, d.expires));
+        }
+    }
+
+    fn remove(&mut self, net: &IpNet) {
+        if net.prefix_len() == net.max_prefix_len() {
+            self.ips.remove(&net.addr());
+        } else {
+            self.ranges.retain(|(n, _)| n != net);
+        }
+    }
+
+    fn contains(&self, ip: &IpAddr, now: Instant) -> bool {
+        let live = |expires: &Option<Instant>| expires.is_none_or(|e| now < e);
+        self.ips.get(ip).is_some_and(live)
+            || self
+                .ranges
+                .iter()
+                .any(|(net, expires)| live(expires) && net.contains(ip))
+    }
+
+    fn purge_expired(&mut self, now: Instant) {
+        self.ips.retain(|_, e| e.is_none_or(|e| now < e));
+        self.ranges.retain(|(_, e)| e.is_none_or(|e| now < e));
+    }
+
+    fn len(&self) -> usize {
+        self.ips.len() + self.ranges.len()
+    }
+}
+
+/// Merged decisions of all configured feeds.
+pub struct ThreatIntel {
+    feeds: RwLock<HashMap<String, FeedEntries>>,
+    blocked_total: Family<FeedLabel, Counter>,
+    entries: Family<FeedLabel, Gauge>,
+    refresh_failures: Family<FeedLabel, Counter>,
+}
+
+impl Default for ThreatIntel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThreatIntel {
+    /// Store without exported metrics.
+    pub fn new() -> Self {
+        Self {
+            feeds: RwLock::new(HashMap::new()),
+            blocked_total: Family::default(),
+            entries: Family::default(),
+            refresh_failures: Family::default(),
+        }
+    }
+
+    /// Store reporting to the `s5_threat_intel_*` metrics.
+    pub fn with_metrics(metrics: &MetricsRegistry) -> Self {
+        Self {
+            feeds: RwLock::new(HashMap::new()),
+            blocked_total: metrics.threat_intel_blocked_total.clone(),
+            entries: metrics.threat_intel_entries.clone(),
+            refresh_failures: metrics.threat_intel_refresh_failures_total.clone(),
+        }
+    }
+
+    /// Name of the first feed blocking `ip`, if any.
+    pub fn lookup(&self, ip: &IpAddr) -> Option<String> {
+        let now = Instant::now();
+        let feeds = self.feeds.read().unwrap_or_else(|e| e.into_inner());
+        feeds
+            .iter()
+            .find(|(_, entries)| entries.contains(ip, now))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// True if `ip` is blocked; counts the hit against the blocking feed.
+    pub fn check(&self, ip: &IpAddr) -> bool {
+        match self.lookup(ip) {
+            Some(feed) => {
+                debug!(ip = %ip, feed = %feed, "Connection blocked by threat intel");
+                self.blocked_total.get_or_create(&FeedLabel { feed }).inc();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace every entry of `feed`.
+    pub fn replace_feed(&self, feed: &str, decisions: Vec<Decision>) {
+        let mut entries = FeedEntries::default();
+        for d in decisions {
+            entries.insert(d);
+        }
+        self.update_feed(feed, |current| *current = entries);
+    }
+
+    /// Apply an incremental update: remove `deleted`, then add `new`.
+    pub fn apply_delta(&self, feed: &str, new: Vec<Decision>, deleted: &[IpNet]) {
+        self.update_feed(feed, |entries| {
+            for net in deleted {
+                entries.remove(net);
+            }
+            for d in new {
+                entries.insert(d);
+            }
+        });
+    }
+
+    /// Active entries of `feed` (0 if unknown).
+    pub fn feed_len(&self, feed: &str) -> usize {
+        let feeds = self.feeds.read().unwrap_or_else(|e| e.into_inner());
+        feeds.get(feed).map_or(0, FeedEntries::len)
+    }
+
+    /// Drop expired decisions from every feed.
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+        for (name, entries) in feeds.iter_mut() {
+            entries.purge_expired(now);
+            self.set_entries_gauge(name, entries.len());
+        }
+    }
+
+    fn update_feed(&self, feed: &str, f: impl FnOnce(&mut FeedEntries)) {
+        let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+        let entries = feeds.entry(feed.to_string()).or_default();
+        f(entries);
+        entries.purge_expired(Instant::now());
+        self.set_entries_gauge(feed, entries.len());
+    }
+
+    fn set_entries_gauge(&self, feed: &str, len: usize) {
+        self.entries
+            .get_or_create(&FeedLabel {
+                feed: feed.to_string(),
+            })
+            .set(len as i64);
+    }
+
+    fn record_refresh_failure(&self, feed: &str) {
+        self.refresh_failures
+            .get_or_create(&FeedLabel {
+                feed: feed.to_string(),
+            })
+            .inc();
+    }
+}
+
+/// Parse an IP or CIDR (a bare IP becomes a host-length prefix).
+fn parse_net(s: &str) -> Option<IpNet> {
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// Parse a plain-text denylist. Returns the entries and the number of
+/// non-empty lines that were not an IP or CIDR.
+pub fn parse_denylist(text: &str) -> (Vec<IpNet>, usize) {
+    let mut nets = Vec::new();
+    let mut invalid = 0;
+    for line in text.lines() {
+        let line = line.split(['#', ';']).next().unwrap_or("").trim();
+        let Some(token) = line.split_whitespace().next() else {
+            continue;
+        };
+        match parse_net(token) {
+            Some(net) => nets.push(net),
+            None => invalid += 1,
+        }
+    }
+    (nets, invalid)
+}
+
+/// Parse a Go duration string as used by CrowdSec (`"3h59m58.5s"`, `"500ms"`).
+/// Negative durations yield zero.
+pub fn parse_go_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix('-') {
+        return parse_go_duration(rest).map(|_| Duration::ZERO);
+    }
+    if s == "0" {
+        return Some(Duration::ZERO);
+    }
+    let mut total = 0f64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..num_end].parse().ok()?;
+        rest = &rest[num_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return None,
+        };
+        total += value * seconds;
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[derive(Deserialize)]
+struct CrowdSecStream {
+    #[serde(default)]
+    new: Option<Vec<CrowdSecDecision>>,
+    #[serde(default)]
+    deleted: Option<Vec<CrowdSecDecision>>,
+}
+
+#[derive(Deserialize)]
+struct CrowdSecDecision {
+    scope: String,
+    value: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    duration: Option<String>,
+}
+
+impl CrowdSecDecision {
+    /// Blocked network of an IP/range `ban` decision; other types and scopes
+    /// (captcha, country, AS) are ignored.
+    fn ban_net(&self) -> Option<IpNet> {
+        let scope = self.scope.to_ascii_lowercase();
+        if !self.kind.eq_ignore_ascii_case("ban") || (scope != "ip" && scope != "range") {
+            return None;
+        }
+        parse_net(&self.value)
+    }
+}
+
+/// Parse a `/v1/decisions/stream` response into (new bans, deleted networks).
+pub fn parse_crowdsec_stream(
+    body: &str,
+    now: Instant,
+) -> anyhow::Result<(Vec<Decision>, Vec<IpNet>)> {
+    let stream: CrowdSecStream = serde_json::from_str(body)?;
+    let new = stream
+        .new
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|d| {
+            let net = d.ban_net()?;
+            let expires = d
+                .duration
+                .as_deref()
+                .and_then(parse_go_duration)
+                .map(|dur| now + dur);
+            Some(Decision { net, expires })
+        })
+        .collect();
+    let deleted = stream
+        .deleted
+        .unwrap_or_default()
+        .iter()
+        .filter_map(CrowdSecDecision::ban_net)
+        .collect();
+    Ok((new, deleted))
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("s5-bouncer/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+async fn pull_crowdsec(
+    client: &reqwest::Client,
+    ti: &ThreatIntel,
+    config: &CrowdSecConfig,
+    startup: bool,
+) -> anyhow::Result<()> {
+    let url = format!(
+        "{}/v1/decisions/stream?startup={}",
+        config.url.trim_end_matches('/'),
+        startup
+    );
+    let body = client
+        .get(&url)
+        .header("X-Api-Key", &config.api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let (new, deleted) = parse_crowdsec_stream(&body, Instant::now())?;
+    let (added, removed) = (new.len(), deleted.len());
+    if startup {
+        ti.replace_feed(CROWDSEC_FEED, new);
+    } else {
+        ti.apply_delta(CROWDSEC_FEED, new, &deleted);
+    }
+    if startup || added > 0 || removed > 0 {
+        info!(
+            added,
+            removed,
+            active = ti.feed_len(CROWDSEC_FEED),
+            "CrowdSec decisions updated"
+        );
+    }
+    Ok(())
+}
+
+async fn pull_denylist(
+    client: &reqwest::Client,
+    ti: &ThreatIntel,
+    list: &DenylistConfig,
+) -> anyhow::Result<()> {
+    let resp = client.get(&list.url).send().await?.error_for_status()?;
+    if resp
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_DENYLIST_BYTES)
+    {
+        anyhow::bail!("denylist larger than {} bytes", MAX_DENYLIST_BYTES);
+    }
+    let body = resp.bytes().await?;
+    if body.len() > MAX_DENYLIST_BYTES {
+        anyhow::bail!("denylist larger than {} bytes", MAX_DENYLIST_BYTES);
+    }
+    let (nets, invalid) = parse_denylist(&String::from_utf8_lossy(&body));
+    if invalid > 0 {
+        warn!(feed = %list.name, invalid, "Skipped invalid denylist lines");
+    }
+    let previous = ti.feed_len(&list.name);
+    ti.replace_feed(
+        &list.name,
+        nets.into_iter()
+            .map(|net| Decision { net, expires: None })
+            .collect(),
+    );
+    let current = ti.feed_len(&list.name);
+    if current != previous {
+        info!(feed = %list.name, entries = current, "Denylist updated");
+    }
+    Ok(())
+}
+
+/// Spawn the feed refresh loop. Runs until `shutdown` is cancelled.
+pub fn spawn_refresh_task(
+    ti: Arc<ThreatIntel>,
+    config: ThreatIntelConfig,
+    shutdown: CancellationToken,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = http_client();
+        let mut crowdsec_startup = true;
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.refresh_interval.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            if let Some(ref crowdsec) = config.crowdsec {
+                match pull_crowdsec(&client, &ti, crowdsec, crowdsec_startup).await {
+                    Ok(()) => crowdsec_startup = false,
+                    Err(e) => {
+                        warn!(url = %crowdsec.url, error = %e, "CrowdSec pull failed");
+                        ti.record_refresh_failure(CROWDSEC_FEED);
+                    }
+                }
+            }
+            for list in &config.denylists {
+                if let Err(e) = pull_denylist(&client, &ti, list).await {
+                    warn!(feed = %list.name, error = %e, "Denylist refresh failed");
+                    ti.record_refresh_failure(&list.name);
+                }
+            }
+            ti.purge_expired();
+        }
+    })
+}
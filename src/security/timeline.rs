@@ -0,0 +1,186 @@
+//! Security event history for the dashboard timeline
+//! (`GET /api/security/timeline`).
+//!
+//! The audit logger hands every event to [`SecurityTimeline::record`]. Auth
+//! failures, bans and ip_guard blocks are counted in one-minute buckets over
+//! the last 24 hours, and the events themselves are kept (the latest
+//! [`MAX_EVENTS`]) so a point on the chart opens the matching audit entries.
+//! The history lives in memory and starts over on restart.
+
+use crate::audit::events::AuditEvent;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+/// Width of a stored bucket.
+pub const BUCKET_SECS: i64 = 60;
+/// How far back buckets and events are kept.
+pub const RETENTION_SECS: i64 = 24 * 3600;
+/// Events kept for drill-down, all kinds together.
+pub const MAX_EVENTS: usize = 5000;
+/// Most buckets returned by [`SecurityTimeline::buckets`]; wider windows
+/// get wider buckets.
+pub const MAX_POINTS: i64 = 120;
+
+/// What a timeline point counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityKind {
+    /// `auth.failure`
+    AuthFailure,
+    /// `ban.created`
+    Ban,
+    /// `acl.deny` with reason `ip_guard`
+    IpGuard,
+}
+
+impl SecurityKind {
+    pub const ALL: [SecurityKind; 3] = [Self::AuthFailure, Self::Ban, Self::IpGuard];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::AuthFailure => "auth_failure",
+            Self::Ban => "ban",
+            Self::IpGuard => "ip_guard",
+        }
+    }
+
+    /// The kind of `event`, if it belongs on the timeline.
+    pub fn of(event: &AuditEvent) -> Option<Self> {
+        match event {
+            AuditEvent::AuthFailure { .. } => Some(Self::AuthFailure),
+            AuditEvent::BanCreated { .. } => Some(Self::Ban),
+            AuditEvent::AclDeny { reason, .. }
+                if reason == crate::proxy::ip_guard::ACL_DENY_REASON =>
+            {
+                Some(Self::IpGuard)
+            }
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Event counts of one time slice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineBucket {
+    pub start: DateTime<Utc>,
+    pub auth_failure: u64,
+    pub ban: u64,
+    pub ip_guard: u64,
+}
+
+impl TimelineBucket {
+    fn new(start: i64, counts: [u64; 3]) -> Self {
+        Self {
+            start: Utc.timestamp_opt(start, 0).single().unwrap_or_default(),
+            auth_failure: counts[SecurityKind::AuthFailure.index()],
+            ban: counts[SecurityKind::Ban.index()],
+            ip_guard: counts[SecurityKind::IpGuard.index()],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Bucket start (Unix seconds) -> counts per kind
+    buckets: BTreeMap<i64, [u64; 3]>,
+    /// Oldest first
+    events: VecDeque<(DateTime<Utc>, SecurityKind, AuditEvent)>,
+}
+
+/// Counts and recent entries of security audit events.
+#[derive(Default)]
+pub struct SecurityTimeline {
+    inner: Mutex<Inner>,
+}
+
+impl SecurityTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `event` now if it belongs on the timeline.
+    pub fn record(&self, event: &AuditEvent) {
+        if let Some(kind) = SecurityKind::of(event) {
+            self.record_at(kind, Utc::now(), event);
+        }
+    }
+
+    /// Record `event` as a `kind` event seen `at`.
+    pub fn record_at(&self, kind: SecurityKind, at: DateTime<Utc>, event: &AuditEvent) {
+        let start = at.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.buckets.entry(start).or_default()[kind.index()] += 1;
+        inner.events.push_back((at, kind, event.clone()));
+
+        // Prune: nothing older than the retention, at most MAX_EVENTS entries
+        let cutoff = at.timestamp() - RETENTION_SECS;
+        inner.buckets = inner.buckets.split_off(&cutoff);
+        while inner.events.len() > MAX_EVENTS
+            || inner
+                .events
+                .front()
+                .is_some_and(|(t, _, _)| t.timestamp() < cutoff)
+        {
+            inner.events.pop_front();
+        }
+    }
+
+    /// Step used for a `window_secs` chart: a whole number of minutes giving
+    /// at most [`MAX_POINTS`] buckets.
+    pub fn step_for(window_secs: i64) -> i64 {
+        let per_point = (window_secs + MAX_POINTS - 1) / MAX_POINTS;
+        ((per_point + BUCKET_SECS - 1) / BUCKET_SECS).max(1) * BUCKET_SECS
+    }
+
+    /// Counts over the `window_secs` before `now`, oldest first, in buckets
+    /// of `step_secs` (a multiple of [`BUCKET_SECS`]). Empty buckets are
+    /// included so the chart has a point per step.
+    pub fn buckets(
+        &self,
+        now: DateTime<Utc>,
+        window_secs: i64,
+        step_secs: i64,
+    ) -> Vec<TimelineBucket> {
+        let step = (step_secs / BUCKET_SECS).max(1) * BUCKET_SECS;
+        let end = now.timestamp().div_euclid(step) * step + step;
+        let first = end - (window_secs.clamp(step, RETENTION_SECS) + step - 1) / step * step;
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        (first..end)
+            .step_by(step as usize)
+            .map(|start| {
+                let mut counts = [0u64; 3];
+                for (_, c) in inner.buckets.range(start..start + step) {
+                    for (total, n) in counts.iter_mut().zip(c) {
+                        *total += n;
+                    }
+                }
+                TimelineBucket::new(start, counts)
+            })
+            .collect()
+    }
+
+    /// Events recorded in `[from, to)`, newest first, optionally of one kind.
+    pub fn events(
+        &self,
+        kind: Option<SecurityKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<AuditEvent> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .events
+            .iter()
+            .rev()
+            .filter(|(at, k, _)| *at >= from && *at < to && kind.is_none_or(|kind| kind == *k))
+            .take(limit)
+            .map(|(_, _, event)| event.clone())
+            .collect()
+    }
+}
@@ -1,15 +1,21 @@
 use crate::alerting::AlertEngine;
 use crate::api;
+use crate::audit::archive::{ArchiveMetrics, AuditArchiver};
+use crate::audit::events::AuditEvent;
 use crate::audit::AuditLogger;
+use crate::auth::pubkey::KEY_EXPIRY_REMINDER_DAYS;
 use crate::auth::AuthService;
 use crate::config;
-use crate::config::types::AppConfig;
+use crate::config::history::ConfigHistory;
+use crate::config::types::{AppConfig, DashboardAccount};
 use crate::context::AppContext;
 use crate::metrics::MetricsRegistry;
+use crate::proxy::close::CloseReason;
 use crate::proxy::ProxyEngine;
 use crate::quota::QuotaTracker;
 use crate::security::SecurityManager;
 use crate::ssh::handler::SshHandler;
+use crate::ssh::host_keys::HostKeyRing;
 use crate::ssh::keys;
 use crate::webhooks::WebhookDispatcher;
 
@@ -20,7 +26,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn, Instrument};
+use tracing::{debug, error, info, warn, Instrument};
 
 /// Main server orchestrator (no config path — no reload support)
 pub async fn run(config: AppConfig) -> Result<()> {
@@ -45,6 +51,30 @@ pub async fn run_with_post_init<F, Fut>(
     config_path: Option<PathBuf>,
     hook: F,
 ) -> Result<()>
+where
+    F: FnOnce(Arc<AppContext>) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    supervise(config, config_path, CancellationToken::new(), hook).await
+}
+
+/// Like `run_with_config_path`, but also shuts down when `shutdown` is
+/// cancelled. Used by the Windows service, whose stop request arrives
+/// through the service control handler rather than a signal.
+pub async fn run_until(
+    config: AppConfig,
+    config_path: Option<PathBuf>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    supervise(config, config_path, shutdown, |_| async {}).await
+}
+
+async fn supervise<F, Fut>(
+    config: AppConfig,
+    config_path: Option<PathBuf>,
+    shutdown: CancellationToken,
+    hook: F,
+) -> Result<()>
 where
     F: FnOnce(Arc<AppContext>) -> Fut,
     Fut: std::future::Future<Output = ()>,
@@ -58,30 +88,89 @@ where
         Some(Arc::new(WebhookDispatcher::new(config.webhooks.clone())))
     };
 
+    // Email notifications (startup-only, not hot-reloaded)
+    let notifier = config
+        .notifications
+        .is_enabled()
+        .then(|| Arc::new(crate::notifications::Notifier::new(&config)));
+
+    // Upload of rotated audit files (startup-only, not hot-reloaded)
+    let audit_archive = config
+        .logging
+        .audit_log_path
+        .as_deref()
+        .filter(|_| config.logging.audit.archive.enabled)
+        .map(|path| AuditArchiver::new(&config.logging.audit.archive, path));
+
     // Initialize shared services (these survive reloads)
-    let audit = Arc::new(AuditLogger::new(
+    let audit = Arc::new(AuditLogger::with_chain(
         config.logging.audit_log_path.clone(),
         config.logging.audit_max_size_mb * 1024 * 1024,
         config.logging.audit_max_files,
         webhook_dispatcher.clone(),
+        crate::audit::chain::AuditChainOptions {
+            enabled: config.logging.audit_chain,
+            checkpoint_interval: std::time::Duration::from_secs(
+                config.logging.audit_checkpoint_interval,
+            ),
+            signing_key: config.logging.audit_signing_key.clone(),
+        },
+        notifier.clone(),
+        audit_archive.clone(),
     ));
-    let metrics = Arc::new(MetricsRegistry::with_max_labels(
-        config.metrics.max_metric_labels,
-    ));
+    // Relay buffer pool (startup-only, not hot-reloaded)
+    crate::proxy::buffer_pool::init(&config.proxy.buffer_pool);
+    // Take the sockets passed by a previous process or systemd
+    crate::handover::global().set_enabled(config.server.socket_handover);
+    let metrics = Arc::new(MetricsRegistry::from_config(&config.metrics));
     let auth_service = Arc::new(RwLock::new(AuthService::new(&config)?));
     let mut proxy_engine = ProxyEngine::new(config.clone(), audit.clone());
     proxy_engine.set_metrics(metrics.clone());
     let proxy_engine = Arc::new(proxy_engine);
+    // External threat-intel feeds (startup-only, not hot-reloaded)
+    let threat_intel = config.threat_intel.is_enabled().then(|| {
+        Arc::new(crate::security::threat_intel::ThreatIntel::with_metrics(
+            &metrics,
+        ))
+    });
     let security = {
         let mut sm = SecurityManager::new(&config);
         sm.set_audit(audit.clone());
+        if let Some(ref ti) = threat_intel {
+            sm.set_threat_intel(ti.clone());
+        }
         Arc::new(RwLock::new(sm))
     };
 
     let quota_tracker = Arc::new(QuotaTracker::new(&config.limits));
 
+    // Per-connection flow export (survives reloads)
+    let flow_log = if config.logging.flows.enabled {
+        let log = crate::flows::FlowLog::start(&config.logging.flows)?;
+        info!(path = %config.logging.flows.path.display(), "Flow log enabled");
+        Some(log)
+    } else {
+        None
+    };
+    let ipfix = if config.logging.ipfix.enabled {
+        info!(collector = %config.logging.ipfix.collector, "IPFIX export enabled");
+        Some(crate::flows::ipfix::IpfixExporter::start(
+            &config.logging.ipfix,
+        ))
+    } else {
+        None
+    };
+
     // Wire the audit dropped counter to the Prometheus metric
     audit.set_dropped_metric(metrics.audit_events_dropped.clone());
+    if let Some(ref archiver) = audit_archive {
+        archiver.set_metrics(ArchiveMetrics {
+            lag_seconds: metrics.audit_archive_lag_seconds.clone(),
+            uploads: metrics.audit_archive_uploads_total.clone(),
+            failures: metrics.audit_archive_failures_total.clone(),
+        });
+        archiver.spawn();
+    }
 
     let alert_engine = if config.alerting.enabled {
         Some(Arc::new(AlertEngine::new(
@@ -98,13 +187,27 @@ where
     // Channel for reload signals (from SIGHUP handler or API)
     let (reload_tx, mut reload_rx) = tokio::sync::mpsc::channel::<()>(1);
 
-    // Global shutdown token
-    let shutdown = CancellationToken::new();
+    // Readiness flags reported by the API `/readyz` probe
+    let readiness = Arc::new(api::Readiness::new());
 
     // Load or generate host key
-    let host_key = keys::load_or_generate_host_key(&config.server.host_key_path)?;
+    let host_keys = Arc::new(HostKeyRing::load(&config.server)?);
     info!(path = %config.server.host_key_path.display(), "Host key loaded");
 
+    // Applied configurations for `/api/config/rollback` (needs a config file)
+    let config_history = config_path.as_deref().map(|path| {
+        let history = Arc::new(ConfigHistory::new(config.api.config_history));
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                history.record(&content, "startup", config.users.len());
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to read config for history")
+            }
+        }
+        history
+    });
+
     // Spawn periodic ban cleanup task (bans + failure records)
     crate::security::ban::spawn_cleanup_task(security.clone());
 
@@ -119,14 +222,16 @@ where
         },
     );
 
-    // Spawn periodic system metrics updater (every 15s)
+    // Spawn periodic system and runtime metrics updater (every 15s)
     {
         let metrics_ref = metrics.clone();
+        let runtime = tokio::runtime::Handle::current();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
             loop {
                 interval.tick().await;
                 metrics_ref.update_system_metrics();
+                metrics_ref.update_runtime_metrics(&runtime);
             }
         });
     }
@@ -156,6 +261,34 @@ where
         services_shutdown.clone(),
     );
 
+    // Save monthly usage totals and email the monthly report
+    crate::reports::spawn(
+        proxy_engine.usage_history().clone(),
+        &config,
+        services_shutdown.clone(),
+    );
+
+    // Share bans, quota counters and sessions with the other nodes
+    let cluster = crate::cluster::Cluster::new(&config);
+    if let Some(ref cluster) = cluster {
+        cluster.listen(services_shutdown.clone()).await?;
+        cluster.spawn(
+            security.clone(),
+            quota_tracker.clone(),
+            proxy_engine.clone(),
+            services_shutdown.clone(),
+        );
+    }
+
+    // Scheduled resets of `[quota_plans]` members
+    crate::quota::plan::spawn_scheduler(
+        auth_service.clone(),
+        quota_tracker.clone(),
+        audit.clone(),
+        config.limits.quota_reset_state_path.clone(),
+        services_shutdown.clone(),
+    );
+
     // Shared context for SSH and SOCKS5
     let app_ctx = Arc::new(AppContext {
         config: config.clone(),
@@ -167,12 +300,92 @@ where
         quota_tracker: quota_tracker.clone(),
         webhook_dispatcher: webhook_dispatcher.clone(),
         alert_engine: alert_engine.clone(),
+        flow_log: flow_log.clone(),
+        ipfix,
+        maintenance: maintenance.clone(),
         start_time: std::time::Instant::now(),
+        config_path: config_path.clone(),
     });
 
     // Run post-init hook (e.g. inject demo data)
     hook(app_ctx.clone()).await;
 
+    // Sample aggregate throughput every second for the dashboard sparkline
+    {
+        let engine = proxy_engine.clone();
+        let shutdown_for_sampler = services_shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown_for_sampler.cancelled() => break,
+                    _ = interval.tick() => {
+                        engine.sample_throughput();
+                    }
+                }
+            }
+        });
+    }
+
+    // Keep DNS cache entries of the hottest destinations fresh
+    if config.server.dns_prefetch_hosts > 0 {
+        let engine = proxy_engine.clone();
+        let shutdown_for_prefetch = services_shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown_for_prefetch.cancelled() => break,
+                    _ = interval.tick() => {
+                        let refreshed = engine.prefetch_dns().await;
+                        if refreshed > 0 {
+                            debug!(entries = refreshed, "DNS cache prefetch");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Close relayed sessions whose client IP has since been banned
+    {
+        let engine = proxy_engine.clone();
+        let security = security.clone();
+        let shutdown_for_bans = services_shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown_for_bans.cancelled() => break,
+                    _ = interval.tick() => {
+                        let security = security.read().await;
+                        let closed = engine.kill_sessions(CloseReason::Ban, |s| {
+                            s.source_ip
+                                .parse()
+                                .is_ok_and(|ip| security.is_banned(&ip))
+                        });
+                        if closed > 0 {
+                            info!(sessions = closed, "Closed sessions of banned IPs");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Send batched notification emails
+    if let Some(ref notifier) = notifier {
+        info!(
+            rules = config.notifications.rules.len(),
+            batch_window_secs = config.notifications.batch_window_secs,
+            "Email notifications enabled"
+        );
+        tokio::spawn(notifier.clone().run(services_shutdown.clone()));
+    }
+
     // Spawn periodic alert evaluation task
     if let Some(ref engine) = alert_engine {
         let engine = engine.clone();
@@ -194,16 +407,65 @@ where
         });
     }
 
-    // SOCKS5 server
-    let _socks_handle = spawn_socks5_server(
-        &config.server.socks5_listen,
-        app_ctx.clone(),
+    // Daily key.expiring reminders for authorized keys near their expiry-time
+    spawn_key_expiry_reminders(
+        auth_service.clone(),
+        audit.clone(),
         services_shutdown.clone(),
     );
 
+    // Threat-intel refresh task
+    if let Some(ref ti) = threat_intel {
+        info!(
+            crowdsec = config.threat_intel.crowdsec.is_some(),
+            denylists = config.threat_intel.denylists.len(),
+            "Threat intel feeds enabled"
+        );
+        crate::security::threat_intel::spawn_refresh_task(
+            ti.clone(),
+            config.threat_intel.clone(),
+            services_shutdown.clone(),
+        );
+    }
+
+    // A read-only observer serves the API alone
+    let observer = cluster.as_ref().is_some_and(|c| c.is_observer());
+    if observer {
+        readiness.set_observer();
+        info!("Observer mode: SSH and SOCKS5 listeners disabled, API read-only");
+    }
+
+    // SOCKS5 server
+    let _socks_handle = if observer {
+        None
+    } else {
+        spawn_socks5_server(
+            &config.server.socks5_listen,
+            app_ctx.clone(),
+            services_shutdown.clone(),
+        )
+    };
+
+    // MASQUE (HTTP/3) server
+    #[cfg(feature = "masque")]
+    let _masque_handle = if observer {
+        None
+    } else {
+        spawn_masque_server(&config.masque, app_ctx.clone(), services_shutdown.clone())
+    };
+
+    // SSH over WebSocket: the API hands `/ssh` tunnels to the main SSH listener
+    let (ssh_tunnel_tx, ssh_tunnel_rx) =
+        if config.api.enabled && config.api.ssh_websocket && !observer {
+            let (tx, rx) = api::ssh_tunnel::channel();
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
+
     // API server
     let api_listen = if config.api.enabled {
-        Some(config.api.listen.clone())
+        Some(api_bind(&config.api))
     } else {
         None
     };
@@ -220,16 +482,72 @@ where
         config_path: config_path.clone(),
         quota_tracker: quota_tracker.clone(),
         webhook_dispatcher: webhook_dispatcher.clone(),
+        readiness: readiness.clone(),
+        api_guard: Arc::new(api::guard::ApiGuard::new(&config.api)),
+        allowed_origins: config
+            .api
+            .allowed_origins
+            .iter()
+            .filter_map(|o| api::cors::normalize_origin(o))
+            .collect(),
+        session_idle_timeout: std::time::Duration::from_secs(config.api.session_idle_timeout),
+        dashboard_accounts: config.api.accounts.clone(),
+        tenants: config.tenants.clone(),
+        flow_log: flow_log.clone(),
+        host_keys: host_keys.clone(),
+        config_history: config_history.clone(),
+        cluster: cluster.clone(),
+        ssh_tunnel: ssh_tunnel_tx,
+        trusted_proxies: config.api.trusted_proxies.clone(),
         shutdown: services_shutdown.clone(),
     });
 
-    // SSH server
-    let _ssh_handle = spawn_ssh_server(
-        &config.server.ssh_listen,
-        host_key.clone(),
-        &config,
-        app_ctx.clone(),
-    );
+    // SSH servers: `server.ssh_listen` plus `[[server.listeners]]`
+    let _ssh_handles = if observer {
+        Vec::new()
+    } else {
+        spawn_ssh_servers(
+            ssh_listeners(&config, &host_keys, &app_ctx, ssh_tunnel_rx)?,
+            app_ctx.clone(),
+            readiness.clone(),
+            services_shutdown.clone(),
+        )
+    };
+
+    // Drop privileges once every listener is bound and the host keys read
+    if config.sandbox.enabled {
+        let names = listener_names(&config, observer);
+        let bound = async {
+            while !names.iter().all(|n| crate::handover::global().is_bound(n)) {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        };
+        if tokio::time::timeout(crate::handover::READY_TIMEOUT, bound)
+            .await
+            .is_err()
+        {
+            anyhow::bail!("listeners not bound in time, sandbox not applied");
+        }
+        crate::sandbox::apply(&config.sandbox)?;
+    }
+
+    // Tell the parent process (after an upgrade) and systemd once serving
+    {
+        let readiness = readiness.clone();
+        tokio::spawn(async move {
+            let bound = async {
+                while !observer && !readiness.ssh_listener_bound() {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+            };
+            if tokio::time::timeout(crate::handover::READY_TIMEOUT, bound)
+                .await
+                .is_ok()
+            {
+                crate::handover::global().notify_ready();
+            }
+        });
+    }
 
     // Signal handler
     let signal_params = SignalHandlerParams {
@@ -240,6 +558,9 @@ where
         audit: audit.clone(),
         quota_tracker: quota_tracker.clone(),
         metrics: metrics.clone(),
+        readiness: readiness.clone(),
+        host_keys: host_keys.clone(),
+        config_history,
         shutdown: shutdown.clone(),
         reload_tx,
     };
@@ -247,13 +568,12 @@ where
         handle_signals(signal_params).await;
     });
 
-    info!(addr = %config.server.ssh_listen, "SSH server listening");
-
     // Supervisor loop: wait for reload signals or shutdown
     loop {
         tokio::select! {
             _ = shutdown.cancelled() => {
                 info!(timeout = shutdown_timeout, "Initiating graceful shutdown");
+                readiness.set_draining();
                 maintenance.store(true, Ordering::Relaxed);
                 services_shutdown.cancel();
 
@@ -305,21 +625,105 @@ where
     }
 }
 
-/// Spawn the SSH server task
-fn spawn_ssh_server(
-    listen_addr: &str,
-    host_key: russh::keys::PrivateKey,
+/// One SSH listener: `server.ssh_listen` or a `[[server.listeners]]` entry.
+struct SshListener {
+    /// `main` for `server.ssh_listen`
+    name: String,
+    listen: String,
+    ssh_config: Arc<russh::server::Config>,
+    /// None for `server.ssh_listen`: server-wide settings apply
+    policy: Option<Arc<config::types::ListenerConfig>>,
+    tarpit: Option<Arc<crate::security::tarpit::Tarpit>>,
+    /// Set when the listener serves `server.host_key_path` (and its rotation)
+    host_keys: Option<Arc<HostKeyRing>>,
+    /// `HostKeyRing` version `ssh_config` was built from
+    keys_version: u64,
+    /// `/ssh` WebSocket tunnels from the API (main listener only)
+    tunnels: Option<api::ssh_tunnel::TunnelReceiver>,
+}
+
+/// Build every SSH listener. The pre-auth caps and the tarpit are shared, so
+/// extra listeners do not multiply them.
+fn ssh_listeners(
     config: &AppConfig,
-    ctx: Arc<AppContext>,
-) -> tokio::task::JoinHandle<()> {
-    let config = Arc::new(config.clone());
-    let listen = listen_addr.to_string();
+    host_keys: &Arc<HostKeyRing>,
+    ctx: &Arc<AppContext>,
+    tunnels: Option<api::ssh_tunnel::TunnelReceiver>,
+) -> Result<Vec<SshListener>> {
+    let tarpit_anywhere = config.security.tarpit_enabled
+        || config
+            .server
+            .listeners
+            .iter()
+            .any(|l| l.tarpit_enabled == Some(true));
+    let tarpit = tarpit_anywhere.then(|| {
+        info!(
+            max_connections = config.security.tarpit_max_connections,
+            "SSH tarpit enabled for banned IPs"
+        );
+        Arc::new(
+            crate::security::tarpit::Tarpit::from_config(&config.security)
+                .with_metrics(&ctx.metrics),
+        )
+    });
 
-    let mut ssh_config = russh::server::Config::default();
-    ssh_config.keys.push(host_key);
-    ssh_config.server_id = russh::SshId::Standard(config.server.server_id.clone());
-    ssh_config.auth_rejection_time = std::time::Duration::from_secs(1);
-    ssh_config.auth_rejection_time_initial = Some(std::time::Duration::from_secs(0));
+    let (keys_version, shared_keys) = host_keys.served(std::time::SystemTime::now());
+    let mut listeners = vec![SshListener {
+        name: "main".to_string(),
+        listen: config.server.ssh_listen.clone(),
+        ssh_config: ssh_server_config(config, shared_keys.clone()),
+        policy: None,
+        tarpit: tarpit.clone().filter(|_| config.security.tarpit_enabled),
+        host_keys: Some(host_keys.clone()),
+        keys_version,
+        tunnels,
+    }];
+    for policy in &config.server.listeners {
+        // A listener with its own key is outside the rotation
+        let (keys, ring) = match &policy.host_key_path {
+            Some(path) => {
+                let key = keys::load_or_generate_host_key(path)?;
+                info!(listener = %policy.name, path = %path.display(), "Host key loaded");
+                (vec![key], None)
+            }
+            None => (shared_keys.clone(), Some(host_keys.clone())),
+        };
+        let tarpit_enabled = policy
+            .tarpit_enabled
+            .unwrap_or(config.security.tarpit_enabled);
+        listeners.push(SshListener {
+            name: policy.name.clone(),
+            listen: policy.listen.clone(),
+            ssh_config: ssh_server_config(config, keys),
+            policy: Some(Arc::new(policy.clone())),
+            tarpit: tarpit.clone().filter(|_| tarpit_enabled),
+            host_keys: ring,
+            keys_version,
+            tunnels: None,
+        });
+    }
+    Ok(listeners)
+}
+
+/// russh settings for one listener, serving `host_keys` (preferred first).
+fn ssh_server_config(
+    config: &AppConfig,
+    host_keys: Vec<russh::keys::PrivateKey>,
+) -> Arc<russh::server::Config> {
+    let mut ssh_config = russh::server::Config {
+        keys: host_keys,
+        server_id: russh::SshId::Standard(config.server.server_id.clone()),
+        auth_rejection_time: std::time::Duration::from_secs(1),
+        auth_rejection_time_initial: Some(std::time::Duration::from_secs(0)),
+        // Per-channel flow control: the relay only reads from a destination
+        // once the client's window has taken the previous chunk
+        window_size: config.server.ssh_window_size,
+        maximum_packet_size: config.server.ssh_max_packet_size,
+        // UpdateHostKeys: clients learn a rotated key while the old one is
+        // still served, and forget the old one once it is retired
+        update_host_keys: true,
+        ..Default::default()
+    };
 
     // SSH keepalive: server sends keepalive@openssh.com global requests to detect
     // dead clients and prevent ghost sessions. If the client does not respond within
@@ -331,14 +735,266 @@ fn spawn_ssh_server(
         ssh_config.keepalive_max = config.server.ssh_keepalive_max as usize;
     }
 
-    let ssh_config = Arc::new(ssh_config);
+    // Kerberos logins: offered only with `[gssapi]`
+    if config.gssapi.is_some() {
+        ssh_config.methods.push(russh::MethodKind::GssapiWithMic);
+    }
 
-    tokio::spawn(async move {
-        let mut server = SshServer { ctx };
-        if let Err(e) = server.run_on_address(ssh_config, &listen as &str).await {
-            error!(error = %e, "SSH server error");
+    Arc::new(ssh_config)
+}
+
+/// Names (as in [`crate::handover`]) of the listeners started by the
+/// spawned server tasks.
+fn listener_names(config: &AppConfig, observer: bool) -> Vec<String> {
+    let mut names = Vec::new();
+    if !observer {
+        names.push("ssh".to_string());
+        names.extend(
+            config
+                .server
+                .listeners
+                .iter()
+                .map(|l| format!("ssh-{}", l.name)),
+        );
+        if config.server.socks5_listen.is_some() {
+            names.push("socks5".to_string());
         }
-    })
+    }
+    if config.api.enabled {
+        names.push("api".to_string());
+    }
+    if config.metrics.enabled {
+        names.push("metrics".to_string());
+    }
+    names
+}
+
+/// Spawn the SSH server tasks, one per listener. `/readyz` follows the main
+/// listener.
+fn spawn_ssh_servers(
+    listeners: Vec<SshListener>,
+    ctx: Arc<AppContext>,
+    readiness: Arc<api::Readiness>,
+    shutdown: CancellationToken,
+) -> Vec<tokio::task::JoinHandle<()>> {
+    use crate::ssh::pre_auth::PreAuthLimiter;
+
+    let limits = &ctx.config.limits;
+    let pre_auth = Arc::new(
+        PreAuthLimiter::new(
+            limits.max_unauthenticated_connections,
+            limits.max_unauthenticated_per_ip,
+        )
+        .with_policy(limits.overload_policy)
+        .with_metrics(&ctx.metrics),
+    );
+
+    listeners
+        .into_iter()
+        .map(|listener| {
+            let ctx = ctx.clone();
+            let pre_auth = pre_auth.clone();
+            let readiness = listener.policy.is_none().then(|| readiness.clone());
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                // Bind explicitly so `/readyz` can report whether the listener is up
+                let name = match &listener.policy {
+                    None => "ssh".to_string(),
+                    Some(_) => format!("ssh-{}", listener.name),
+                };
+                let tcp = match crate::handover::global()
+                    .bind(&name, &listener.listen)
+                    .await
+                {
+                    Ok(l) => l,
+                    Err(e) => {
+                        error!(
+                            listener = %listener.name,
+                            addr = %listener.listen,
+                            error = %e,
+                            "SSH server failed to bind"
+                        );
+                        return;
+                    }
+                };
+                if let Some(readiness) = &readiness {
+                    readiness.set_ssh_listener_bound(true);
+                }
+                info!(listener = %listener.name, addr = %listener.listen, "SSH server listening");
+                run_ssh_accept_loop(tcp, listener, ctx, pre_auth, shutdown).await;
+                if let Some(readiness) = &readiness {
+                    readiness.set_ssh_listener_bound(false);
+                }
+            })
+        })
+        .collect()
+}
+
+/// SSH accept loop. Banned peers are diverted to the tarpit (when enabled)
+/// before the SSH handshake. Everyone else must hold a pre-auth slot and
+/// authenticate within `server.ssh_auth_timeout`, or the socket is dropped.
+/// WebSocket tunnels from the API join the loop as if accepted here.
+async fn run_ssh_accept_loop(
+    tcp: tokio::net::TcpListener,
+    mut listener: SshListener,
+    ctx: Arc<AppContext>,
+    pre_auth: Arc<crate::ssh::pre_auth::PreAuthLimiter>,
+    shutdown: CancellationToken,
+) {
+    use crate::proxy::client_caps::Listener;
+    use crate::security::normalize::normalize_ip;
+    use crate::ssh::handshake::HandshakeTap;
+    use crate::ssh::pre_auth::{PreAuthDeadline, PreAuthRejection};
+    use crate::ssh::reject;
+    use tokio_util::either::Either;
+
+    let auth_timeout =
+        std::time::Duration::from_secs(ctx.config.server.ssh_auth_timeout.clamp(10, 600));
+    let mut server = SshServer { ctx: ctx.clone() };
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = tcp.accept() => match accepted {
+                Ok((stream, peer)) => (Either::Left(stream), peer),
+                Err(e) => {
+                    error!(error = %e, "SSH accept error");
+                    continue;
+                }
+            },
+            Some(tunnel) = next_tunnel(&mut listener.tunnels) => {
+                (Either::Right(tunnel.stream), tunnel.peer)
+            }
+        };
+
+        if let Some(policy) = &listener.policy {
+            if !crate::security::ip_filter::is_allowed(&peer.ip(), &policy.allowed_source_ips) {
+                debug!(listener = %listener.name, peer = %peer, "Source IP not allowed here");
+                ctx.metrics.record_connection_rejected("acl_denied");
+                continue;
+            }
+        }
+
+        if let Some(tarpit) = &listener.tarpit {
+            if ctx.security.read().await.is_banned(&peer.ip()) {
+                if tarpit.try_trap(stream, shutdown.clone()) {
+                    debug!(peer = %peer, "Banned SSH client sent to tarpit");
+                } else {
+                    debug!(peer = %peer, "Tarpit full, closing banned SSH client");
+                }
+                continue;
+            }
+        }
+
+        let slot = match pre_auth.try_acquire(normalize_ip(peer.ip())) {
+            Ok(slot) => slot,
+            Err(reason) => {
+                let scope = match reason {
+                    PreAuthRejection::Global => "global",
+                    PreAuthRejection::PerIp => "per_ip",
+                };
+                debug!(peer = %peer, scope, "Too many unauthenticated SSH connections, closing");
+                ctx.metrics.record_connection_rejected("pre_auth_limit");
+                continue;
+            }
+        };
+
+        let mut handler = server.new_client(Some(peer));
+        let authenticated = slot.flag();
+        let shed = slot.shed_signal();
+        handler.set_pre_auth_slot(slot);
+        if let Some(policy) = &listener.policy {
+            handler.set_listener(policy.clone());
+        }
+        let conn_id = handler.conn_id().to_string();
+        let connection = ctx
+            .proxy_engine
+            .register_connection(&conn_id, &peer.ip().to_string());
+        let handshake = connection.handshake.clone();
+        handler.set_connection(connection);
+        ctx.audit.log_connection_new_cid(&peer, "ssh", &conn_id);
+        let span = tracing::info_span!(
+            "ssh",
+            conn_id = %conn_id,
+            peer = %peer.ip(),
+            listener = %listener.name,
+            transport = match &stream {
+                Either::Left(_) => "tcp",
+                Either::Right(_) => "websocket",
+            }
+        );
+        if let Some(ring) = &listener.host_keys {
+            let now = std::time::SystemTime::now();
+            if let Some(keys) = ring.served_if_changed(&mut listener.keys_version, now) {
+                info!(listener = %listener.name, keys = keys.len(), "SSH host keys updated");
+                listener.ssh_config = ssh_server_config(&ctx.config, keys);
+            }
+        }
+        let ssh_config = listener.ssh_config.clone();
+        let ctx = ctx.clone();
+        let session_task = async move {
+            if let (true, Either::Left(socket)) = (ssh_config.nodelay, &stream) {
+                let _ = socket.set_nodelay(true);
+            }
+            let _client_slot = match ctx.proxy_engine.client_caps().acquire(Listener::Ssh).await {
+                Ok(slot) => slot,
+                Err(rejection) => {
+                    debug!(peer = %peer, "Too many connections, refusing SSH client");
+                    ctx.metrics
+                        .record_connection_rejected(rejection.metric_reason());
+                    reject::refuse(
+                        stream,
+                        &ctx.config.server.server_id,
+                        reject::DISCONNECT_TOO_MANY_CONNECTIONS,
+                        "too many connections",
+                    )
+                    .await;
+                    ctx.proxy_engine.unregister_connection(&conn_id);
+                    ctx.audit.log_connection_closed_cid(&peer, "ssh", &conn_id);
+                    return;
+                }
+            };
+            let started = std::time::Instant::now();
+            let stream = HandshakeTap::new(stream, handshake);
+            let stream = PreAuthDeadline::new(stream, auth_timeout, authenticated.clone());
+            let session = async {
+                match russh::server::run_stream(ssh_config, stream, handler).await {
+                    Ok(session) => {
+                        if let Err(e) = session.await {
+                            debug!(peer = %peer, error = %e, "SSH session ended with error");
+                        }
+                    }
+                    Err(e) => debug!(peer = %peer, error = %e, "SSH connection setup failed"),
+                }
+            };
+            tokio::select! {
+                _ = session => {}
+                _ = shed.cancelled() => {
+                    debug!(peer = %peer, "Unauthenticated SSH connection shed under load");
+                }
+            }
+            if !authenticated.load(std::sync::atomic::Ordering::Acquire)
+                && started.elapsed() >= auth_timeout
+            {
+                debug!(peer = %peer, "SSH client did not authenticate in time, connection closed");
+                ctx.metrics.record_connection_rejected("auth_timeout");
+            }
+            ctx.proxy_engine.unregister_connection(&conn_id);
+            ctx.audit.log_connection_closed_cid(&peer, "ssh", &conn_id);
+        };
+        tokio::spawn(session_task.instrument(span));
+    }
+}
+
+/// Next `/ssh` WebSocket tunnel; pending forever without (or after) the API.
+async fn next_tunnel(
+    tunnels: &mut Option<api::ssh_tunnel::TunnelReceiver>,
+) -> Option<api::ssh_tunnel::Tunnel> {
+    if let Some(rx) = tunnels {
+        if let Some(tunnel) = rx.recv().await {
+            return Some(tunnel);
+        }
+        *tunnels = None;
+    }
+    std::future::pending().await
 }
 
 /// Spawn the SOCKS5 server task (if configured)
@@ -362,6 +1018,27 @@ fn spawn_socks5_server(
     ))
 }
 
+/// Spawn the MASQUE server task (if configured)
+#[cfg(feature = "masque")]
+fn spawn_masque_server(
+    config: &Option<crate::config::types::MasqueConfig>,
+    ctx: Arc<AppContext>,
+    shutdown: CancellationToken,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let config = config.clone()?;
+
+    let trace_id = uuid::Uuid::new_v4().to_string();
+    let span = tracing::info_span!("masque_server", trace_id = %trace_id, addr = %config.listen);
+    Some(tokio::spawn(
+        async move {
+            if let Err(e) = crate::masque::start_masque_server(&config, ctx, shutdown).await {
+                error!(error = %e, "MASQUE server error");
+            }
+        }
+        .instrument(span),
+    ))
+}
+
 /// Spawn the metrics server task (if configured)
 fn spawn_metrics_server(
     listen_addr: &Option<String>,
@@ -415,9 +1092,38 @@ fn spawn_maintenance_scheduler(
     });
 }
 
+fn spawn_key_expiry_reminders(
+    auth_service: Arc<RwLock<AuthService>>,
+    audit: Arc<AuditLogger>,
+    shutdown: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(24 * 3600));
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {
+                    let now = chrono::Utc::now();
+                    let within = chrono::Duration::days(KEY_EXPIRY_REMINDER_DAYS);
+                    let keys = auth_service.read().await.expiring_keys(now, within);
+                    for key in keys.iter().filter(|k| !k.expired) {
+                        warn!(
+                            user = %key.username,
+                            fingerprint = %key.fingerprint,
+                            expires_at = %key.expires_at,
+                            "Authorized key expires soon"
+                        );
+                        audit.log_event(AuditEvent::key_expiring(key, now));
+                    }
+                }
+            }
+        }
+    });
+}
+
 /// Parameters for spawning the API server, replacing 12+ individual arguments.
 struct ApiServerParams {
-    listen_addr: Option<String>,
+    listen_addr: Option<api::ApiBind>,
     auth_service: Arc<RwLock<AuthService>>,
     proxy_engine: Arc<ProxyEngine>,
     security: Arc<RwLock<SecurityManager>>,
@@ -429,13 +1135,37 @@ struct ApiServerParams {
     config_path: Option<PathBuf>,
     quota_tracker: Arc<QuotaTracker>,
     webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+    readiness: Arc<api::Readiness>,
+    api_guard: Arc<api::guard::ApiGuard>,
+    allowed_origins: Vec<String>,
+    session_idle_timeout: std::time::Duration,
+    dashboard_accounts: Vec<DashboardAccount>,
+    tenants: Vec<crate::config::types::TenantConfig>,
+    flow_log: Option<Arc<crate::flows::FlowLog>>,
+    host_keys: Arc<HostKeyRing>,
+    config_history: Option<Arc<ConfigHistory>>,
+    cluster: Option<Arc<crate::cluster::Cluster>>,
+    ssh_tunnel: Option<api::ssh_tunnel::TunnelSender>,
+    trusted_proxies: Vec<ipnet::IpNet>,
     shutdown: CancellationToken,
 }
 
+/// `api.unix_socket` replaces the TCP listener when set.
+fn api_bind(api: &crate::config::types::ApiConfig) -> api::ApiBind {
+    #[cfg(unix)]
+    if let Some(path) = &api.unix_socket {
+        return api::ApiBind::Unix {
+            path: path.clone(),
+            // Checked by config validation
+            mode: api.unix_socket_mode_bits().unwrap_or(0o660),
+        };
+    }
+    api::ApiBind::Tcp(api.listen.clone())
+}
+
 /// Spawn the API server task (if configured)
 fn spawn_api_server(params: ApiServerParams) -> Option<tokio::task::JoinHandle<()>> {
-    let listen = params.listen_addr.as_ref()?;
-    let listen = listen.clone();
+    let listen = params.listen_addr.clone()?;
 
     let (broadcast_tx, _broadcast_rx) = tokio::sync::broadcast::channel(256);
     let state = api::AppState {
@@ -452,13 +1182,27 @@ fn spawn_api_server(params: ApiServerParams) -> Option<tokio::task::JoinHandle<(
         ssh_listen_addr: Some(params.ssh_listen_addr),
         quota_tracker: Some(params.quota_tracker),
         webhook_dispatcher: params.webhook_dispatcher,
+        readiness: Some(params.readiness),
+        api_guard: Some(params.api_guard),
+        allowed_origins: Arc::new(params.allowed_origins),
+        sessions: Arc::new(api::session::SessionStore::new(params.session_idle_timeout)),
+        user_sessions: Arc::new(api::session::SessionStore::new(params.session_idle_timeout)),
+        dashboard_accounts: Arc::new(params.dashboard_accounts),
+        tenants: Arc::new(params.tenants),
+        invitations: Default::default(),
+        flow_log: params.flow_log,
+        host_keys: Some(params.host_keys),
+        config_history: params.config_history,
+        cluster: params.cluster,
+        ssh_tunnel: params.ssh_tunnel,
+        trusted_proxies: Arc::new(params.trusted_proxies),
     };
 
     // Spawn background task to clean up expired SSE tickets every 60s
     api::spawn_ticket_cleanup_task(params.shutdown.clone());
 
     Some(tokio::spawn(async move {
-        if let Err(e) = api::start_api_server(&listen, state, params.shutdown).await {
+        if let Err(e) = api::serve_api(listen, state, params.shutdown).await {
             error!(error = %e, "API server error");
         }
     }))
@@ -473,6 +1217,9 @@ struct SignalHandlerParams {
     audit: Arc<AuditLogger>,
     quota_tracker: Arc<QuotaTracker>,
     metrics: Arc<MetricsRegistry>,
+    readiness: Arc<api::Readiness>,
+    host_keys: Arc<HostKeyRing>,
+    config_history: Option<Arc<ConfigHistory>>,
     shutdown: CancellationToken,
     reload_tx: tokio::sync::mpsc::Sender<()>,
 }
@@ -505,6 +1252,9 @@ async fn handle_signals(params: SignalHandlerParams) {
         audit,
         quota_tracker,
         metrics,
+        readiness,
+        host_keys,
+        config_history,
         shutdown,
         reload_tx,
     } = params;
@@ -530,6 +1280,13 @@ async fn handle_signals(params: SignalHandlerParams) {
             return;
         }
     };
+    let mut sigusr2 = match signal(SignalKind::user_defined2()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!(error = %e, "Failed to install SIGUSR2 handler");
+            return;
+        }
+    };
 
     loop {
         tokio::select! {
@@ -540,13 +1297,14 @@ async fn handle_signals(params: SignalHandlerParams) {
             }
             _ = sighup.recv() => {
                 info!("SIGHUP received, reloading configuration");
-                match config::load_config(&config_path) {
-                    Ok(new_config) => {
+                match config::load_config_with_source(&config_path) {
+                    Ok((new_config, content)) => {
                         let users_count = new_config.users.len();
                         match auth_service.write().await.reload(&new_config) {
                             Ok(()) => info!(users = users_count, "Auth service reloaded"),
                             Err(e) => {
                                 error!(error = %e, "Failed to reload auth service");
+                                readiness.set_config_valid(false);
                                 audit.log_config_reload(0, false, Some(e.to_string()));
                                 continue;
                             }
@@ -555,6 +1313,10 @@ async fn handle_signals(params: SignalHandlerParams) {
                         security.write().await.reload(&new_config);
                         info!("Security manager reloaded");
 
+                        if let Err(e) = host_keys.reload(&new_config.server) {
+                            error!(error = %e, "Failed to reload host keys, keeping current ones");
+                        }
+
                         quota_tracker.update_config(&new_config.limits);
                         info!("Quota tracker limits updated");
 
@@ -562,6 +1324,12 @@ async fn handle_signals(params: SignalHandlerParams) {
                         let usernames: Vec<String> = new_config.users.iter().map(|u| u.username.clone()).collect();
                         metrics.prune_known_users(&usernames);
 
+                        if let Some(ref history) = config_history {
+                            history.set_keep(new_config.api.config_history);
+                            history.record(&content, "signal", users_count);
+                        }
+
+                        readiness.set_config_valid(true);
                         audit.log_config_reload(users_count, true, None);
                         info!("Configuration reloaded successfully");
 
@@ -570,10 +1338,24 @@ async fn handle_signals(params: SignalHandlerParams) {
                     }
                     Err(e) => {
                         error!(error = %e, "Failed to reload configuration");
+                        readiness.set_config_valid(false);
                         audit.log_config_reload(0, false, Some(e.to_string()));
                     }
                 }
             }
+            _ = sigusr2.recv() => {
+                info!("SIGUSR2 received, handing the listening sockets to a new process");
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    match crate::handover::global().upgrade().await {
+                        Ok(pid) => {
+                            info!(pid, "New process is serving, draining this one");
+                            shutdown.cancel();
+                        }
+                        Err(e) => error!(error = %e, "Upgrade failed, still serving"),
+                    }
+                });
+            }
             _ = sigusr1.recv() => {
                 let current = maintenance.load(Ordering::Relaxed);
                 maintenance.store(!current, Ordering::Relaxed);
@@ -588,7 +1370,14 @@ async fn handle_signals(params: SignalHandlerParams) {
     }
 }
 
+/// No SIGHUP/SIGUSR1 here: reload goes through the API, and Ctrl+C shuts
+/// down gracefully.
 #[cfg(not(unix))]
-async fn handle_signals(_params: SignalHandlerParams) {
-    std::future::pending::<()>().await;
+async fn handle_signals(params: SignalHandlerParams) {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!(error = %e, "Failed to install Ctrl+C handler");
+        return;
+    }
+    info!("Ctrl+C received, initiating graceful shutdown");
+    params.shutdown.cancel();
 }
@@ -0,0 +1,113 @@
+//! `tracing` layer writing to the Windows Application log.
+//!
+//! Events are reported against message id 1-3 of `EventCreate.exe`, whose
+//! message table is a bare `%1`, so Event Viewer shows the text as-is
+//! without a dedicated message DLL. The source is registered by
+//! `s5 service install`; an unregistered source still logs, with a
+//! "description cannot be found" preamble.
+
+use super::wide;
+use anyhow::Result;
+use std::fmt::Write as _;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+    EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+};
+
+/// Handle to a registered event source. Debug and trace events are dropped;
+/// the Event Log is for operators, not for tracing traffic.
+pub struct EventLogLayer {
+    handle: HANDLE,
+}
+
+// SAFETY: event source handles may be used from any thread; ReportEventW is
+// thread-safe.
+unsafe impl Send for EventLogLayer {}
+unsafe impl Sync for EventLogLayer {}
+
+/// Open the Event Log under `source`.
+pub fn event_log_layer(source: &str) -> Result<EventLogLayer> {
+    let name = wide(source);
+    // SAFETY: `name` is a NUL-terminated UTF-16 string that outlives the call.
+    let handle = unsafe { RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+    if handle.is_null() {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(EventLogLayer { handle })
+}
+
+impl Drop for EventLogLayer {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from RegisterEventSourceW and is closed once.
+        unsafe {
+            DeregisterEventSource(self.handle);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let (kind, id) = match *event.metadata().level() {
+            Level::ERROR => (EVENTLOG_ERROR_TYPE, 1),
+            Level::WARN => (EVENTLOG_WARNING_TYPE, 2),
+            Level::INFO => (EVENTLOG_INFORMATION_TYPE, 3),
+            _ => return,
+        };
+        let mut text = MessageVisitor::default();
+        event.record(&mut text);
+        let text = wide(&text.finish(event.metadata().target()));
+        let strings = [text.as_ptr()];
+        // SAFETY: one valid NUL-terminated string, no SID and no raw data.
+        unsafe {
+            ReportEventW(
+                self.handle,
+                kind,
+                0,
+                id,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            );
+        }
+    }
+}
+
+/// Renders an event as `message key=value ...`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self, target: &str) -> String {
+        let mut out = format!("{target}: {}", self.message);
+        out.push_str(&self.fields);
+        out
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
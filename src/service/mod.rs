@@ -0,0 +1,61 @@
+//! Windows service support (`s5 service`) and the Event Log backend.
+//!
+//! On other platforms the same entry points exist but fail with a pointer
+//! to the systemd unit in `contrib/`, so `main` needs no `cfg` of its own.
+
+#[cfg(windows)]
+mod eventlog;
+#[cfg(windows)]
+mod windows;
+
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(windows)]
+pub use self::eventlog::{event_log_layer, EventLogLayer};
+#[cfg(windows)]
+pub use self::windows::{install, run, uninstall};
+
+#[cfg(not(windows))]
+pub use self::unsupported::{event_log_layer, install, run, uninstall, EventLogLayer};
+
+/// Event Log source used when `logging.event_log` is set outside the service.
+pub const DEFAULT_EVENT_SOURCE: &str = "s5";
+
+/// Body of the service: runs the server until the token is cancelled.
+pub type ServiceMain = Box<dyn FnOnce(CancellationToken) -> Result<()> + Send>;
+
+/// NUL-terminated UTF-16 for Win32 `W` functions.
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(not(windows))]
+mod unsupported {
+    use super::ServiceMain;
+    use anyhow::{bail, Result};
+    use std::path::Path;
+
+    const UNSUPPORTED: &str =
+        "Windows services are not supported on this platform; use contrib/s5.service (systemd)";
+
+    /// Stand-in so callers can name the layer type on every platform.
+    pub type EventLogLayer = tracing_subscriber::layer::Identity;
+
+    pub fn event_log_layer(_source: &str) -> Result<EventLogLayer> {
+        bail!("the Windows Event Log is not available on this platform")
+    }
+
+    pub fn install(_name: &str, _config_path: &Path) -> Result<()> {
+        bail!(UNSUPPORTED)
+    }
+
+    pub fn uninstall(_name: &str) -> Result<()> {
+        bail!(UNSUPPORTED)
+    }
+
+    pub fn run(_name: &str, _main: ServiceMain) -> Result<()> {
+        bail!(UNSUPPORTED)
+    }
+}
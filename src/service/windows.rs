@@ -0,0 +1,216 @@
+//! Service Control Manager integration.
+
+use super::{wide, ServiceMain};
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+use windows_sys::Win32::System::Registry::{
+    RegCloseKey, RegCreateKeyExW, RegDeleteKeyW, RegSetValueExW, HKEY, HKEY_LOCAL_MACHINE,
+    KEY_WRITE, REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+};
+
+const EVENT_LOG_KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog\Application";
+
+/// Generic `%1` message table shipped with Windows.
+const EVENT_MESSAGE_FILE: &str = r"%SystemRoot%\System32\EventCreate.exe";
+
+/// Service name and body, parked here for the SCM callback.
+static SERVICE: Mutex<Option<(String, ServiceMain)>> = Mutex::new(None);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Register the service (auto start, running `s5 --config <config_path>
+/// service run`) and its Event Log source.
+pub fn install(name: &str, config_path: &Path) -> Result<()> {
+    let config_path = std::path::absolute(config_path)?;
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .context("cannot open the Service Control Manager (run as Administrator)")?;
+    let info = ServiceInfo {
+        name: OsString::from(name),
+        display_name: OsString::from(format!("s5 SSH/SOCKS5 proxy ({name})")),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![
+            OsString::from("--config"),
+            config_path.into_os_string(),
+            OsString::from("service"),
+            OsString::from("run"),
+            OsString::from("--name"),
+            OsString::from(name),
+        ],
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .with_context(|| format!("cannot create service {name}"))?;
+    service.set_description("SSH server with SOCKS5 proxy and port forwarding")?;
+    register_event_source(name)?;
+    Ok(())
+}
+
+/// Stop and delete the service, then remove its Event Log source.
+pub fn uninstall(name: &str) -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .context("cannot open the Service Control Manager (run as Administrator)")?;
+    let service = manager
+        .open_service(
+            name,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )
+        .with_context(|| format!("cannot open service {name}"))?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    // Deletion completes once the last handle is closed
+    service.delete()?;
+    drop(service);
+    deregister_event_source(name)
+}
+
+/// Hand the process to the Service Control Manager and run `main` until
+/// the service is stopped. Blocks until then.
+pub fn run(name: &str, main: ServiceMain) -> Result<()> {
+    *SERVICE.lock().unwrap_or_else(|e| e.into_inner()) = Some((name.to_string(), main));
+    service_dispatcher::start(name, ffi_service_main)
+        .context("not started by the Service Control Manager (use `s5 service install`)")?;
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let Some((name, main)) = SERVICE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    if let Err(e) = run_service(&name, main) {
+        error!(error = %e, service = %name, "Windows service failed");
+    }
+}
+
+fn run_service(name: &str, main: ServiceMain) -> Result<()> {
+    let shutdown = CancellationToken::new();
+    let stop = shutdown.clone();
+    let status = service_control_handler::register(name, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            stop.cancel();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    let report = |state: ServiceState, exit_code: ServiceExitCode| {
+        status.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            } else {
+                ServiceControlAccept::empty()
+            },
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+
+    report(ServiceState::Running, ServiceExitCode::Win32(0))?;
+    info!(service = %name, "Windows service running");
+    let result = main(shutdown);
+    let exit_code = match &result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(_) => ServiceExitCode::ServiceSpecific(1),
+    };
+    report(ServiceState::Stopped, exit_code)?;
+    result
+}
+
+/// Point `Application\<name>` at the generic message file so Event Viewer
+/// renders our text without a "description cannot be found" preamble.
+fn register_event_source(name: &str) -> Result<()> {
+    let subkey = wide(&format!(r"{EVENT_LOG_KEY}\{name}"));
+    let mut key: HKEY = std::ptr::null_mut();
+    // SAFETY: valid NUL-terminated strings and out-pointer; the key is closed below.
+    let rc = unsafe {
+        RegCreateKeyExW(
+            HKEY_LOCAL_MACHINE,
+            subkey.as_ptr(),
+            0,
+            std::ptr::null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            std::ptr::null(),
+            &mut key,
+            std::ptr::null_mut(),
+        )
+    };
+    if rc != ERROR_SUCCESS {
+        return Err(std::io::Error::from_raw_os_error(rc as i32))
+            .context("cannot register the Event Log source");
+    }
+
+    let message_file = wide(EVENT_MESSAGE_FILE);
+    let types_supported: u32 = 0x7; // error | warning | information
+    let message_file_name = wide("EventMessageFile");
+    let types_supported_name = wide("TypesSupported");
+    // SAFETY: `key` is open for writing; data pointers and byte lengths
+    // describe live buffers.
+    let rc = unsafe {
+        let rc = RegSetValueExW(
+            key,
+            message_file_name.as_ptr(),
+            0,
+            REG_EXPAND_SZ,
+            message_file.as_ptr().cast(),
+            (message_file.len() * 2) as u32,
+        );
+        if rc == ERROR_SUCCESS {
+            RegSetValueExW(
+                key,
+                types_supported_name.as_ptr(),
+                0,
+                REG_DWORD,
+                (&types_supported as *const u32).cast(),
+                4,
+            )
+        } else {
+            rc
+        }
+    };
+    // SAFETY: closes the key opened above.
+    unsafe { RegCloseKey(key) };
+    if rc != ERROR_SUCCESS {
+        return Err(std::io::Error::from_raw_os_error(rc as i32))
+            .context("cannot register the Event Log source");
+    }
+    Ok(())
+}
+
+fn deregister_event_source(name: &str) -> Result<()> {
+    let subkey = wide(&format!(r"{EVENT_LOG_KEY}\{name}"));
+    // SAFETY: valid NUL-terminated key path.
+    let rc = unsafe { RegDeleteKeyW(HKEY_LOCAL_MACHINE, subkey.as_ptr()) };
+    if rc != ERROR_SUCCESS && rc != ERROR_FILE_NOT_FOUND {
+        return Err(std::io::Error::from_raw_os_error(rc as i32))
+            .context("cannot remove the Event Log source");
+    }
+    Ok(())
+}
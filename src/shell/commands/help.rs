@@ -18,6 +18,7 @@ pub fn run(ctx: Option<&ShellContext>) -> CommandResult {
          echo <text>        Print text\r\n\
          env                Print environment variables\r\n\
          clear              Clear screen\r\n\
+         passwd             Change your password\r\n\
          help               Show this help\r\n\
          exit               Close connection\r\n",
     );
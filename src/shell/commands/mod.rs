@@ -6,6 +6,7 @@ pub mod colors;
 pub mod exit;
 pub mod help;
 pub mod ls;
+pub mod passwd;
 pub mod ping_cmd;
 pub mod pwd;
 pub mod resolve_cmd;
@@ -22,6 +23,8 @@ use crate::shell::parser;
 pub struct CommandResult {
     pub output: String,
     pub exit_requested: bool,
+    /// The session should start the interactive password change prompt
+    pub passwd_requested: bool,
 }
 
 impl CommandResult {
@@ -29,6 +32,7 @@ impl CommandResult {
         Self {
             output: text.into(),
             exit_requested: false,
+            passwd_requested: false,
         }
     }
 
@@ -36,6 +40,7 @@ impl CommandResult {
         Self {
             output: "logout\r\n".to_string(),
             exit_requested: true,
+            passwd_requested: false,
         }
     }
 
@@ -43,6 +48,15 @@ impl CommandResult {
         Self {
             output: String::new(),
             exit_requested: false,
+            passwd_requested: false,
+        }
+    }
+
+    pub fn passwd() -> Self {
+        Self {
+            output: String::new(),
+            exit_requested: false,
+            passwd_requested: true,
         }
     }
 }
@@ -81,6 +95,36 @@ pub fn execute(
     execute_inner(cmd, args, fs, username, hostname, ctx)
 }
 
+/// `env`: the fixed variables, then the session's own (`TERM`, accepted
+/// client variables), which replace fixed ones of the same name.
+fn env(username: &str, hostname: &str, ctx: Option<&ShellContext>) -> CommandResult {
+    let mut vars = vec![
+        ("HOME".to_string(), format!("/home/{}", username)),
+        ("USER".to_string(), username.to_string()),
+        ("SHELL".to_string(), "/bin/sh".to_string()),
+        ("HOSTNAME".to_string(), hostname.to_string()),
+        (
+            "TERM".to_string(),
+            crate::ssh::terminal::DEFAULT_TERM.to_string(),
+        ),
+        (
+            "PATH".to_string(),
+            "/usr/local/bin:/usr/bin:/bin".to_string(),
+        ),
+    ];
+    for (name, value) in ctx.iter().flat_map(|c| &c.env) {
+        match vars.iter_mut().find(|(n, _)| n == name) {
+            Some(var) => var.1 = value.clone(),
+            None => vars.push((name.clone(), value.clone())),
+        }
+    }
+    CommandResult::output(
+        vars.iter()
+            .map(|(name, value)| format!("{}={}\r\n", name, value))
+            .collect::<String>(),
+    )
+}
+
 /// Inner execute that handles all command dispatch without alias expansion.
 fn execute_inner(
     cmd: &str,
@@ -100,6 +144,7 @@ fn execute_inner(
         "uname" => uname::run(args, hostname),
         "help" => help::run(ctx.as_deref()),
         "exit" | "logout" => exit::run(),
+        "passwd" => passwd::run(args, username),
         "echo" => {
             let text = args.join(" ");
             CommandResult::output(format!("{}\r\n", text))
@@ -110,10 +155,7 @@ fn execute_inner(
         )),
         "hostname" => CommandResult::output(format!("{}\r\n", hostname)),
         "clear" => CommandResult::output("\x1b[2J\x1b[H".to_string()),
-        "env" | "printenv" => CommandResult::output(format!(
-            "HOME=/home/{}\r\nUSER={}\r\nSHELL=/bin/sh\r\nHOSTNAME={}\r\nTERM=xterm-256color\r\nPATH=/usr/local/bin:/usr/bin:/bin\r\n",
-            username, username, hostname
-        )),
+        "env" | "printenv" => env(username, hostname, ctx.as_deref()),
         "" => CommandResult::empty(),
 
         // Extended commands (require ShellContext)
@@ -180,6 +222,7 @@ pub mod test_helpers {
             proxy_engine: None,
             quota_tracker: None,
             quota_config: None,
+            env: Vec::new(),
         }
     }
 }
@@ -0,0 +1,39 @@
+use crate::shell::commands::CommandResult;
+
+/// Start the interactive password change. The prompts themselves are driven
+/// by the shell session, which owns the terminal and the auth service handle.
+pub fn run(args: &[String], username: &str) -> CommandResult {
+    match args.first() {
+        Some(target) if target != username => CommandResult::output(format!(
+            "passwd: you may not change the password for {}\r\n",
+            target
+        )),
+        _ if args.len() > 1 => CommandResult::output("Usage: passwd\r\n".to_string()),
+        _ => CommandResult::passwd(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwd_requests_prompt() {
+        let result = run(&[], "alice");
+        assert!(result.passwd_requested);
+        assert!(result.output.is_empty());
+    }
+
+    #[test]
+    fn passwd_own_username_allowed() {
+        let result = run(&["alice".to_string()], "alice");
+        assert!(result.passwd_requested);
+    }
+
+    #[test]
+    fn passwd_other_user_denied() {
+        let result = run(&["bob".to_string()], "alice");
+        assert!(!result.passwd_requested);
+        assert!(result.output.contains("may not change"));
+    }
+}
@@ -35,6 +35,8 @@ pub struct ShellContext {
     pub quota_tracker: Option<Arc<QuotaTracker>>,
     /// Configured quota limits for this user (from config).
     pub quota_config: Option<QuotaConfig>,
+    /// Session environment: `TERM` and the variables accepted from the client.
+    pub env: Vec<(String, String)>,
 }
 
 impl ShellContext {
@@ -5,11 +5,41 @@ pub mod filesystem;
 pub mod parser;
 pub mod terminal;
 
+use crate::audit::AuditLogger;
+use crate::auth::self_service;
+use crate::auth::AuthService;
+use crate::ssh::terminal::{TerminalPolicy, MAX_ENV_VARS};
 use anyhow::Result;
 use context::ShellContext;
 use executor::CommandExecutor;
 use russh::CryptoVec;
+use std::path::PathBuf;
+use std::sync::Arc;
 use terminal::TerminalState;
+use tokio::sync::RwLock;
+use zeroize::Zeroizing;
+
+/// Services needed by the interactive `passwd` command.
+#[derive(Clone)]
+pub struct PasswordChangeHandle {
+    pub auth_service: Arc<RwLock<AuthService>>,
+    /// Config file the new hash is written back to (None = in-memory only)
+    pub config_path: Option<PathBuf>,
+    pub audit: Arc<AuditLogger>,
+    pub source_ip: String,
+}
+
+/// Progress through the `passwd` prompts. Entered secrets are zeroized on drop.
+enum PasswdStage {
+    Current,
+    New {
+        current: Zeroizing<String>,
+    },
+    Confirm {
+        current: Zeroizing<String>,
+        new: Zeroizing<String>,
+    },
+}
 
 /// A shell session attached to an SSH channel
 pub struct ShellSession {
@@ -21,6 +51,14 @@ pub struct ShellSession {
     closed: bool,
     /// Pre-rendered MOTD to send on shell_request
     motd: Option<String>,
+    /// Handle for self-service password changes (None = `passwd` unavailable)
+    password_change: Option<PasswordChangeHandle>,
+    /// Active `passwd` prompt, if any
+    passwd_stage: Option<PasswdStage>,
+    /// Environment and PTY policy of the user
+    terminal_policy: TerminalPolicy,
+    /// False for users limited to subsystems (`allow_shell = false`)
+    shell_allowed: bool,
 }
 
 impl ShellSession {
@@ -35,9 +73,33 @@ impl ShellSession {
             _channel: channel,
             closed: false,
             motd: None,
+            password_change: None,
+            passwd_stage: None,
+            terminal_policy: TerminalPolicy::default(),
+            shell_allowed: true,
         }
     }
 
+    /// Refuse shell, exec and input on this channel, leaving it only for a
+    /// subsystem request.
+    pub fn set_shell_allowed(&mut self, allowed: bool) {
+        self.shell_allowed = allowed;
+    }
+
+    pub fn shell_allowed(&self) -> bool {
+        self.shell_allowed
+    }
+
+    /// Give up the shell and hand its channel to a subsystem.
+    pub fn into_channel(self) -> russh::Channel<russh::server::Msg> {
+        self._channel
+    }
+
+    /// Enable the `passwd` command for this session.
+    pub fn set_password_change(&mut self, handle: PasswordChangeHandle) {
+        self.password_change = Some(handle);
+    }
+
     /// Set the shell context for extended commands (show, test, ping, etc.).
     pub fn set_context(&mut self, ctx: ShellContext) {
         self.terminal.autocomplete = ctx.colors; // colors implies full UX
@@ -54,10 +116,37 @@ impl ShellSession {
         self.motd.take()
     }
 
+    /// Apply the user's environment and PTY policy to this session.
+    pub fn set_terminal_policy(&mut self, policy: TerminalPolicy) {
+        self.terminal_policy = policy;
+    }
+
+    pub fn terminal_policy(&self) -> &TerminalPolicy {
+        &self.terminal_policy
+    }
+
+    /// Set the window size, clamped to the policy's `max_cols`/`max_rows`.
     pub fn set_terminal_size(&mut self, cols: u32, rows: u32) {
+        let (cols, rows) = self.terminal_policy.clamp_size(cols, rows);
         self.terminal.set_size(cols, rows);
     }
 
+    /// Set a session environment variable, replacing an earlier value.
+    /// Returns false without a shell context or once `MAX_ENV_VARS` are set.
+    pub fn set_env(&mut self, name: &str, value: &str) -> bool {
+        let Some(ctx) = self.executor.context.as_mut() else {
+            return false;
+        };
+        match ctx.env.iter().position(|(n, _)| n == name) {
+            Some(i) => ctx.env[i].1 = value.to_string(),
+            None if ctx.env.len() < MAX_ENV_VARS => {
+                ctx.env.push((name.to_string(), value.to_string()))
+            }
+            None => return false,
+        }
+        true
+    }
+
     /// Send the shell prompt
     pub async fn send_prompt(
         &mut self,
@@ -90,6 +179,12 @@ impl ShellSession {
 
             // If we got a completed line, execute it
             if let Some(line) = completed_line {
+                if self.passwd_stage.is_some() {
+                    let output = self.advance_passwd(Zeroizing::new(line)).await;
+                    let _ = session.data(channel_id, CryptoVec::from_slice(output.as_bytes()));
+                    continue;
+                }
+
                 if line.is_empty() {
                     // Just a newline, show prompt again
                     let prompt = self.executor.prompt();
@@ -110,6 +205,12 @@ impl ShellSession {
                     return Ok(());
                 }
 
+                if result.passwd_requested {
+                    let output = self.start_passwd().await;
+                    let _ = session.data(channel_id, CryptoVec::from_slice(output.as_bytes()));
+                    continue;
+                }
+
                 // Show prompt for next command
                 let prompt = self.executor.prompt();
                 let _ = session.data(channel_id, CryptoVec::from_slice(prompt.as_bytes()));
@@ -118,4 +219,98 @@ impl ShellSession {
 
         Ok(())
     }
+
+    /// Begin the `passwd` prompt sequence. Returns the text to send.
+    async fn start_passwd(&mut self) -> String {
+        let Some(handle) = &self.password_change else {
+            return format!(
+                "passwd: password changes are not available\r\n{}",
+                self.executor.prompt()
+            );
+        };
+        let has_password = handle
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(&self.executor.username)
+            .is_some_and(|u| u.password_hash.is_some());
+        if !has_password {
+            return format!(
+                "passwd: no password is set for this account\r\n{}",
+                self.executor.prompt()
+            );
+        }
+
+        self.terminal.set_masked(true);
+        self.passwd_stage = Some(PasswdStage::Current);
+        format!(
+            "Changing password for {}.\r\nCurrent password: ",
+            self.executor.username
+        )
+    }
+
+    /// Feed one masked line into the `passwd` prompt sequence.
+    /// An empty line (including Ctrl+C / Ctrl+D) cancels.
+    async fn advance_passwd(&mut self, line: Zeroizing<String>) -> String {
+        let Some(stage) = self.passwd_stage.take() else {
+            return String::new();
+        };
+        if line.is_empty() {
+            return self.finish_passwd("passwd: password unchanged\r\n");
+        }
+
+        match stage {
+            PasswdStage::Current => {
+                self.passwd_stage = Some(PasswdStage::New { current: line });
+                "New password: ".to_string()
+            }
+            PasswdStage::New { current } => {
+                self.passwd_stage = Some(PasswdStage::Confirm { current, new: line });
+                "Retype new password: ".to_string()
+            }
+            PasswdStage::Confirm { current, new } => {
+                if *new != *line {
+                    return self.finish_passwd(
+                        "passwd: passwords do not match\r\npasswd: password unchanged\r\n",
+                    );
+                }
+                let Some(handle) = self.password_change.clone() else {
+                    return self.finish_passwd("passwd: password changes are not available\r\n");
+                };
+                let username = self.executor.username.clone();
+                let result = self_service::change_password(
+                    &handle.auth_service,
+                    handle.config_path.as_deref(),
+                    &username,
+                    &current,
+                    &new,
+                )
+                .await;
+                handle.audit.log_password_changed(
+                    &username,
+                    &handle.source_ip,
+                    "ssh",
+                    result.is_ok(),
+                    result.as_ref().err().map(|e| e.to_string()),
+                );
+                match result {
+                    Ok(()) => self.finish_passwd("passwd: password updated successfully\r\n"),
+                    Err(e) if e.is_client_error() => self
+                        .finish_passwd(&format!("passwd: {}\r\npasswd: password unchanged\r\n", e)),
+                    Err(e) => {
+                        tracing::error!(user = %username, error = %e, "Password change failed");
+                        self.finish_passwd("passwd: internal error, password unchanged\r\n")
+                    }
+                }
+            }
+        }
+    }
+
+    /// Leave secret entry mode and return `message` followed by the prompt.
+    fn finish_passwd(&mut self, message: &str) -> String {
+        self.passwd_stage = None;
+        self.terminal.set_masked(false);
+        format!("{}{}", message, self.executor.prompt())
+    }
 }
@@ -3,7 +3,8 @@ const MAX_LINE_LENGTH: usize = 4096;
 /// Known commands for tab completion
 const COMPLETABLE_COMMANDS: &[&str] = &[
     "alias", "bookmark", "cat", "cd", "clear", "echo", "env", "exit", "help", "hostname", "id",
-    "logout", "ls", "ping", "printenv", "pwd", "resolve", "show", "test", "uname", "whoami",
+    "logout", "ls", "passwd", "ping", "printenv", "pwd", "resolve", "show", "test", "uname",
+    "whoami",
 ];
 
 /// Known show subcommands for tab completion
@@ -54,6 +55,8 @@ pub struct TerminalState {
     saved_line: Vec<u8>,
     /// ANSI escape sequence parsing state
     esc_state: EscapeState,
+    /// Secret entry mode (password prompts): no echo, no history, no completion
+    masked: bool,
 }
 
 const MAX_HISTORY: usize = 100;
@@ -70,6 +73,7 @@ impl Default for TerminalState {
             history_index: None,
             saved_line: Vec::new(),
             esc_state: EscapeState::Normal,
+            masked: false,
         }
     }
 }
@@ -79,8 +83,26 @@ impl TerminalState {
         Self::default()
     }
 
+    /// Enable or disable secret entry mode. Switching modes discards any
+    /// partially typed line so a secret never leaks into the next command.
+    pub fn set_masked(&mut self, masked: bool) {
+        self.masked = masked;
+        self.line_buffer.clear();
+        self.cursor_pos = 0;
+        self.esc_state = EscapeState::Normal;
+    }
+
+    /// Whether secret entry mode is active.
+    pub fn is_masked(&self) -> bool {
+        self.masked
+    }
+
     /// Process a byte of input, returns (echo_bytes, completed_line)
     pub fn process_byte(&mut self, byte: u8) -> (Vec<u8>, Option<String>) {
+        if self.masked {
+            return self.process_masked_byte(byte);
+        }
+
         // Handle ESC sequence state machine
         if self.esc_state == EscapeState::GotEsc {
             self.esc_state = EscapeState::Normal;
@@ -207,6 +229,52 @@ impl TerminalState {
         }
     }
 
+    /// Secret entry: only Enter echoes anything. Ctrl+C and Ctrl+D complete
+    /// with an empty line so callers can treat them as "cancel".
+    fn process_masked_byte(&mut self, byte: u8) -> (Vec<u8>, Option<String>) {
+        if self.esc_state != EscapeState::Normal {
+            // Swallow escape sequences (arrow keys) while masked
+            self.esc_state = match (self.esc_state, byte) {
+                (EscapeState::GotEsc, b'[') => EscapeState::GotCsi,
+                _ => EscapeState::Normal,
+            };
+            return (Vec::new(), None);
+        }
+        match byte {
+            b'\r' | b'\n' => {
+                let line = String::from_utf8_lossy(&self.line_buffer).to_string();
+                self.line_buffer.clear();
+                self.cursor_pos = 0;
+                (b"\r\n".to_vec(), Some(line))
+            }
+            0x03 | 0x04 => {
+                self.line_buffer.clear();
+                self.cursor_pos = 0;
+                (b"\r\n".to_vec(), Some(String::new()))
+            }
+            0x7f | 0x08 => {
+                self.line_buffer.pop();
+                self.cursor_pos = self.line_buffer.len();
+                (Vec::new(), None)
+            }
+            0x15 => {
+                self.line_buffer.clear();
+                self.cursor_pos = 0;
+                (Vec::new(), None)
+            }
+            0x1b => {
+                self.esc_state = EscapeState::GotEsc;
+                (Vec::new(), None)
+            }
+            _ if byte >= 0x20 && self.line_buffer.len() < MAX_LINE_LENGTH => {
+                self.line_buffer.push(byte);
+                self.cursor_pos = self.line_buffer.len();
+                (Vec::new(), None)
+            }
+            _ => (Vec::new(), None),
+        }
+    }
+
     /// Replace the current line buffer with new content, returning echo bytes
     /// that clear the old line and display the new one.
     fn replace_line(&mut self, new_content: &[u8]) -> Vec<u8> {
@@ -692,4 +760,50 @@ mod tests {
         let (_, line) = term.process_byte(b'\r');
         assert_eq!(line, Some("second".to_string()));
     }
+
+    #[test]
+    fn test_masked_input_not_echoed_or_recorded() {
+        let mut term = TerminalState::new();
+        term.set_masked(true);
+        for &b in b"s3cret!" {
+            let (echo, line) = term.process_byte(b);
+            assert!(echo.is_empty());
+            assert!(line.is_none());
+        }
+        let (echo, line) = term.process_byte(b'\r');
+        assert_eq!(echo, b"\r\n");
+        assert_eq!(line, Some("s3cret!".to_string()));
+        assert!(term.history.is_empty());
+    }
+
+    #[test]
+    fn test_masked_ctrl_d_cancels_instead_of_exit() {
+        let mut term = TerminalState::new();
+        term.set_masked(true);
+        let (_, line) = term.process_byte(0x04);
+        assert_eq!(line, Some(String::new()));
+    }
+
+    #[test]
+    fn test_masked_backspace_and_arrows() {
+        let mut term = TerminalState::new();
+        term.set_masked(true);
+        term.process_byte(b'a');
+        term.process_byte(b'b');
+        let (echo, _) = term.process_byte(0x7f);
+        assert!(echo.is_empty());
+        assert!(press_up(&mut term).is_empty());
+        let (_, line) = term.process_byte(b'\r');
+        assert_eq!(line, Some("a".to_string()));
+    }
+
+    #[test]
+    fn test_unmasking_discards_partial_secret() {
+        let mut term = TerminalState::new();
+        term.set_masked(true);
+        term.process_byte(b'x');
+        term.set_masked(false);
+        assert!(!term.is_masked());
+        assert_eq!(term.current_line(), "");
+    }
 }
@@ -1,4 +1,8 @@
+use crate::audit::events::AuditEvent;
+use crate::auth::login::{self, PasswordAttempt, PasswordOutcome, Refusal};
 use crate::context::AppContext;
+use crate::flows::FlowRecord;
+use crate::proxy::forwarder::RelayOutcome;
 use crate::socks::{auth as socks_auth, protocol, socks5_handshake_timeout};
 use crate::utils::generate_correlation_id;
 use anyhow::Result;
@@ -6,7 +10,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use tracing::{debug, info, info_span, warn, Instrument};
+use tracing::{debug, info_span, warn, Instrument};
 
 /// Handle a single SOCKS5 connection
 pub async fn handle_connection(mut stream: TcpStream, ctx: Arc<AppContext>) -> Result<()> {
@@ -29,13 +33,21 @@ pub async fn handle_connection(mut stream: TcpStream, ctx: Arc<AppContext>) -> R
         match handshake_result {
             Ok(Ok(Some(relay_info))) => {
                 // Register session for tracking
-                let session = ctx.proxy_engine.register_session(
+                let session = ctx.proxy_engine.register_session_cid(
                     &relay_info.username,
                     &relay_info.host,
                     relay_info.port,
                     &peer_addr.ip().to_string(),
                     "socks5",
+                    &conn_id,
+                );
+                ctx.proxy_engine.prioritize(
+                    &session,
+                    Some(relay_info.resolved_addr.ip()).filter(|ip| !ip.is_unspecified()),
+                    &relay_info.tags,
                 );
+                ctx.proxy_engine
+                    .sample_first_line(&session, relay_info.payload_metadata);
                 // Relay phase - uses its own idle timeout, no handshake timeout
                 let relay_cfg = relay_info.to_relay_config(
                     ctx.config.limits.idle_timeout,
@@ -45,18 +57,25 @@ pub async fn handle_connection(mut stream: TcpStream, ctx: Arc<AppContext>) -> R
                 );
                 let rlog = relay_info.log_info();
                 let relay_start = Instant::now();
-                let (bytes_up, bytes_down) =
-                    crate::proxy::forwarder::relay(stream, relay_info.target_stream, relay_cfg)
-                        .await?;
+                let outcome = crate::proxy::forwarder::relay_with_reason(
+                    stream,
+                    relay_info.target_stream,
+                    relay_cfg,
+                )
+                .await
+                .inspect_err(|_| {
+                    record_flow(&ctx, || {
+                        rlog.flow_error(&peer_addr, relay_start.elapsed(), &conn_id)
+                    })
+                })?;
                 let duration_ms = relay_start.elapsed().as_millis() as u64;
 
                 // Unregister session after relay completes
-                ctx.proxy_engine.unregister_session(&session.session_id);
+                ctx.proxy_engine.close_session(&session, outcome.reason);
 
                 log_relay_complete(
                     &rlog,
-                    bytes_up,
-                    bytes_down,
+                    &outcome,
                     duration_ms,
                     &peer_addr,
                     &ctx,
@@ -93,7 +112,15 @@ struct RelayInfo {
     bandwidth_limit_kbps: u64,
     aggregate_bandwidth_kbps: u64,
     quotas: Option<crate::config::types::QuotaConfig>,
+    server_names: Option<crate::proxy::forwarder::ServerNameCheck>,
+    /// User tags, for `[proxy.priority]` classes
+    tags: Vec<String>,
+    /// Group override of `logging.flows.payload_metadata`
+    payload_metadata: Option<bool>,
     _guard: crate::proxy::ConnectionGuard,
+    _user_slot: crate::proxy::client_caps::UserSlot,
+    /// Counted in the usage reports when the client disconnects
+    _login: crate::reports::Login,
 }
 
 impl RelayInfo {
@@ -114,6 +141,9 @@ impl RelayInfo {
             quotas: self.quotas.clone(),
             audit,
             session,
+            stall_watch: None,
+            client_window: None,
+            server_names: self.server_names.clone(),
         }
     }
 }
@@ -137,18 +167,60 @@ impl RelayInfo {
     }
 }
 
+impl RelayLogInfo {
+    fn flow(
+        &self,
+        peer_addr: &std::net::SocketAddr,
+        elapsed: Duration,
+        conn_id: &str,
+    ) -> FlowRecord {
+        FlowRecord {
+            dest_ip: Some(self.resolved_addr.ip().to_string()),
+            correlation_id: Some(conn_id.to_string()),
+            ..FlowRecord::closing(
+                "socks5",
+                &self.username,
+                &peer_addr.ip().to_string(),
+                &self.host,
+                self.port,
+                elapsed,
+            )
+        }
+    }
+
+    fn flow_error(
+        &self,
+        peer_addr: &std::net::SocketAddr,
+        elapsed: Duration,
+        conn_id: &str,
+    ) -> FlowRecord {
+        FlowRecord {
+            close_reason: crate::metrics::error_types::RELAY_ERROR.to_string(),
+            ..self.flow(peer_addr, elapsed, conn_id)
+        }
+    }
+}
+
+/// Record a flow if `[logging.flows]` or `[logging.ipfix]` is enabled (the
+/// record is only built then).
+fn record_flow(ctx: &AppContext, flow: impl FnOnce() -> FlowRecord) {
+    if ctx.records_flows() {
+        ctx.record_flow(flow());
+    }
+}
+
 /// Log relay completion: info log + audit + metrics
 #[allow(clippy::too_many_arguments)]
 async fn log_relay_complete(
     info: &RelayLogInfo,
-    bytes_up: u64,
-    bytes_down: u64,
+    outcome: &RelayOutcome,
     duration_ms: u64,
     peer_addr: &std::net::SocketAddr,
     ctx: &AppContext,
     protocol_label: &str,
     conn_id: &str,
 ) {
+    let (bytes_up, bytes_down) = (outcome.bytes_up, outcome.bytes_down);
     tracing::info!(
         conn_id = %conn_id,
         user = %info.username,
@@ -157,28 +229,40 @@ async fn log_relay_complete(
         bytes_up = bytes_up,
         bytes_down = bytes_down,
         duration_ms = duration_ms,
+        close_reason = %outcome.reason,
         "{} relay completed", protocol_label
     );
+    let event = AuditEvent::proxy_complete_with_cid(
+        &info.username,
+        &info.host,
+        info.port,
+        bytes_up,
+        bytes_down,
+        duration_ms,
+        peer_addr,
+        Some(info.resolved_addr.ip().to_string()),
+        conn_id,
+    )
+    .with_close_reason(outcome.reason);
     ctx.audit
-        .log_proxy_complete_cid(
-            &info.username,
-            &info.host,
-            info.port,
-            bytes_up,
-            bytes_down,
-            duration_ms,
-            peer_addr,
-            Some(info.resolved_addr.ip().to_string()),
-            conn_id,
-        )
-        .await;
+        .log_event(ctx.proxy_engine.label_session_close(event).await);
     ctx.metrics
         .record_bytes_transferred(&info.username, bytes_up + bytes_down);
-    ctx.metrics.record_typed_connection_duration(
+    ctx.metrics.record_typed_connection_duration_cid(
         &info.username,
         "socks5",
         duration_ms as f64 / 1000.0,
+        conn_id,
     );
+    record_flow(ctx, || {
+        FlowRecord {
+            bytes_up,
+            bytes_down,
+            ..info.flow(peer_addr, Duration::from_millis(duration_ms), conn_id)
+        }
+        .with_traffic(outcome.traffic.as_ref())
+        .with_relay_close(outcome.reason)
+    });
 }
 
 /// P3-1: Handle a TLS-wrapped SOCKS5 connection.
@@ -211,13 +295,21 @@ pub async fn handle_tls_connection(
         match handshake_result {
             Ok(Ok(Some(relay_info))) => {
                 // Register session for tracking
-                let session = ctx.proxy_engine.register_session(
+                let session = ctx.proxy_engine.register_session_cid(
                     &relay_info.username,
                     &relay_info.host,
                     relay_info.port,
                     &peer_addr.ip().to_string(),
                     "socks5-tls",
+                    &conn_id,
                 );
+                ctx.proxy_engine.prioritize(
+                    &session,
+                    Some(relay_info.resolved_addr.ip()).filter(|ip| !ip.is_unspecified()),
+                    &relay_info.tags,
+                );
+                ctx.proxy_engine
+                    .sample_first_line(&session, relay_info.payload_metadata);
                 let relay_cfg = relay_info.to_relay_config(
                     ctx.config.limits.idle_timeout,
                     ctx.quota_tracker.clone(),
@@ -226,17 +318,25 @@ pub async fn handle_tls_connection(
                 );
                 let rlog = relay_info.log_info();
                 let relay_start = Instant::now();
-                let (bytes_up, bytes_down) =
-                    crate::proxy::forwarder::relay(rw, relay_info.target_stream, relay_cfg).await?;
+                let outcome = crate::proxy::forwarder::relay_with_reason(
+                    rw,
+                    relay_info.target_stream,
+                    relay_cfg,
+                )
+                .await
+                .inspect_err(|_| {
+                    record_flow(&ctx, || {
+                        rlog.flow_error(&peer_addr, relay_start.elapsed(), &conn_id)
+                    })
+                })?;
                 let duration_ms = relay_start.elapsed().as_millis() as u64;
 
                 // Unregister session after relay completes
-                ctx.proxy_engine.unregister_session(&session.session_id);
+                ctx.proxy_engine.close_session(&session, outcome.reason);
 
                 log_relay_complete(
                     &rlog,
-                    bytes_up,
-                    bytes_down,
+                    &outcome,
                     duration_ms,
                     &peer_addr,
                     &ctx,
@@ -295,170 +395,85 @@ async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
 
     let creds = socks_auth::read_credentials(stream).await?;
 
-    let totp_required = ctx
-        .config
-        .security
-        .totp_required_for
-        .contains(&"socks5".to_string());
-
-    // Phase 2 (post-auth): Single auth_service read for TOTP check, password verify, and user lookup
-    let (password_ok, totp_ok, totp_code, user_opt) = {
-        let auth = ctx.auth_service.read().await;
-
-        // AUTH-001: Determine TOTP extraction based on user config
-        let (actual_password, totp_code) = if totp_required {
-            let user_has_totp = auth
-                .user_store()
-                .get(&creds.username)
-                .map(|u| u.totp_enabled && u.totp_secret.is_some())
-                .unwrap_or(false);
-
-            if user_has_totp {
-                extract_totp_from_password(&creds.password)
-            } else {
-                (creds.password.to_string(), None)
-            }
-        } else {
-            (creds.password.to_string(), None)
-        };
-
-        let pass_ok = auth.auth_password(&creds.username, &actual_password);
-        let t_ok = if pass_ok {
-            match &totp_code {
-                Some(code) => auth.verify_totp(&creds.username, code),
-                None => true,
-            }
-        } else {
-            false
-        };
-        let user = auth.user_store().get(&creds.username).cloned();
-        (pass_ok, t_ok, totp_code, user)
-    };
-
-    if !password_ok || !totp_ok {
-        let audit_method = if totp_code.is_some() && password_ok {
-            "socks5+totp"
-        } else {
-            "socks5"
-        };
-        let metric_method = if totp_code.is_some() && password_ok {
-            "totp"
-        } else {
-            "password"
-        };
-        warn!(conn_id = %conn_id, user = %creds.username, ip = %peer_addr, "SOCKS5 auth failed");
-        socks_auth::send_auth_result(stream, false).await?;
-        // Consolidated security read: record auth failure
-        {
-            let sec = ctx.security.read().await;
-            sec.record_auth_failure(&peer_addr.ip());
-        }
-        ctx.audit
-            .log_auth_failure_cid(&creds.username, peer_addr, audit_method, conn_id)
-            .await;
-        ctx.metrics.record_auth_failure(metric_method);
-        ctx.metrics
-            .record_error(crate::metrics::error_types::AUTH_FAILURE);
-        ctx.metrics.record_connection_rejected("auth_failed");
-        return Ok(None);
-    }
-
-    socks_auth::send_auth_result(stream, true).await?;
-    info!(conn_id = %conn_id, user = %creds.username, ip = %peer_addr, "SOCKS5 auth success");
-    ctx.audit
-        .log_auth_success_cid(&creds.username, peer_addr, "socks5", conn_id)
-        .await;
-    let socks5_method = if totp_required && totp_code.is_some() {
-        "password+totp"
-    } else {
-        "password"
-    };
-    ctx.audit.log_session_authenticated_cid(
-        &creds.username,
-        peer_addr,
-        "socks5",
-        socks5_method,
-        conn_id,
-    );
-    ctx.metrics
-        .record_auth_success(&creds.username, socks5_method);
-
-    // User was already fetched in Phase 2 auth read — no additional lock acquisition
-    let user = user_opt.ok_or_else(|| anyhow::anyhow!("user disappeared after auth"))?;
-
-    if !user.is_source_ip_allowed(&peer_addr.ip()) {
-        warn!(conn_id = %conn_id, user = %creds.username, ip = %peer_addr.ip(), "SOCKS5 connection from IP not in user's allowed source_ips");
-        return Ok(None);
-    }
-
-    // Time-based access check
-    if !user.check_time_access() {
-        warn!(conn_id = %conn_id, user = %creds.username, ip = %peer_addr.ip(), "SOCKS5 connection denied: outside allowed access hours/days");
-        ctx.metrics.record_connection_rejected("time_access_denied");
-        return Ok(None);
-    }
-
-    // Post-auth rate limit check (security read)
-    if !ctx
+    // Decoy login: accept, then refuse every CONNECT as unreachable
+    if ctx
         .security
         .read()
         .await
-        .check_rate_limit(&creds.username, user.max_new_connections_per_minute)
+        .honeypot()
+        .matches(&creds.username, &creds.password)
     {
-        warn!(conn_id = %conn_id, user = %creds.username, limit = user.max_new_connections_per_minute, "SOCKS5 rate limit exceeded (legacy)");
-        ctx.audit.log_rate_limit_exceeded_cid(
-            &creds.username,
-            peer_addr,
-            "legacy_per_minute",
-            conn_id,
-        );
-        ctx.metrics.record_connection_rejected("rate_limited");
-        return Ok(None);
-    }
-
-    // Multi-window rate limiting (QuotaTracker)
-    if let Err(reason) = ctx.quota_tracker.check_connection_rate(
-        &creds.username,
-        &user.rate_limits,
-        &ctx.config.limits,
-    ) {
-        warn!(conn_id = %conn_id, user = %creds.username, reason = %reason, "SOCKS5 quota rate limit exceeded");
-        ctx.audit
-            .log_rate_limit_exceeded_cid(&creds.username, peer_addr, &reason, conn_id);
-        ctx.metrics.record_connection_rejected("rate_limited");
+        socks_auth::send_auth_result(stream, true).await?;
+        ctx.honeypot_triggered(&creds.username, peer_addr, "socks5", conn_id)
+            .await;
+        if let Ok(target) = protocol::read_connect_request(stream).await {
+            warn!(
+                conn_id = %conn_id,
+                ip = %peer_addr.ip(),
+                target = %format!("{}:{}", target.host_string(), target.port()),
+                "Honeypot SOCKS5 CONNECT refused"
+            );
+            let _ = protocol::send_reply(
+                stream,
+                protocol::REPLY_HOST_UNREACHABLE,
+                &protocol::TargetAddr::Ipv4([0; 4], 0),
+            )
+            .await;
+        }
         return Ok(None);
     }
 
-    // Record connection in quota tracker (checks daily/monthly quotas)
-    if let Err(reason) = ctx
-        .quota_tracker
-        .record_connection(&creds.username, user.quotas.as_ref())
+    // Banned from the user's tenant: refused without counting a failure
+    let tenant = crate::auth::tenant::tenant_of(&ctx.config.tenants, &creds.username);
+    if tenant.is_some()
+        && ctx
+            .security
+            .read()
+            .await
+            .is_banned_for(&peer_addr.ip(), tenant)
     {
-        warn!(conn_id = %conn_id, user = %creds.username, reason = %reason, "SOCKS5 connection quota exceeded");
-        ctx.audit.log_quota_exceeded(&creds.username, &reason, 0, 0);
-        ctx.metrics
-            .record_error(crate::metrics::error_types::QUOTA_EXCEEDED);
-        ctx.metrics.record_connection_rejected("quota_exceeded");
+        warn!(conn_id = %conn_id, user = %creds.username, ip = %peer_addr.ip(), tenant = ?tenant, "SOCKS5 login refused: IP banned from the tenant");
+        socks_auth::send_auth_result(stream, false).await?;
+        ctx.metrics.record_connection_rejected("banned");
         return Ok(None);
     }
 
-    // Pre-check bandwidth quotas before starting relay
-    if let Err(reason) = ctx
-        .quota_tracker
-        .check_bandwidth_quota(&creds.username, user.quotas.as_ref())
-    {
-        warn!(conn_id = %conn_id, user = %creds.username, reason = %reason, "SOCKS5 bandwidth quota already exhausted");
-        ctx.audit.log_quota_exceeded(&creds.username, &reason, 0, 0);
-        ctx.metrics
-            .record_error(crate::metrics::error_types::QUOTA_EXCEEDED);
-        ctx.metrics.record_connection_rejected("quota_exceeded");
-        return Ok(None);
-    }
+    let attempt = PasswordAttempt {
+        username: &creds.username,
+        password: &creds.password,
+        totp_code: None,
+        peer_addr,
+        protocol: "socks5",
+        audit_method: "socks5",
+        method: "password",
+        hassh: None,
+        conn_id,
+    };
+    let user_opt = match login::authenticate_password(ctx, &attempt).await {
+        PasswordOutcome::Accepted { user, .. } => user,
+        PasswordOutcome::Rejected { with_totp } => {
+            socks_auth::send_auth_result(stream, false).await?;
+            login::record_password_failure(ctx, &attempt, with_totp).await;
+            return Ok(None);
+        }
+    };
+    socks_auth::send_auth_result(stream, true).await?;
 
-    if !user.allow_forwarding {
-        warn!(conn_id = %conn_id, user = %creds.username, "SOCKS5 forwarding denied");
-        return Ok(None);
-    }
+    let user = user_opt.ok_or_else(|| anyhow::anyhow!("user disappeared after auth"))?;
+    let admission = match login::admit_proxy_login(ctx, &user, peer_addr, "socks5", conn_id).await {
+        Ok(admission) => admission,
+        Err(Refusal::ClientCap) => {
+            protocol::read_connect_request(stream).await?;
+            protocol::send_reply(
+                stream,
+                protocol::REPLY_NOT_ALLOWED,
+                &protocol::TargetAddr::Ipv4([0; 4], 0),
+            )
+            .await?;
+            return Ok(None);
+        }
+        Err(_) => return Ok(None),
+    };
 
     let target = protocol::read_connect_request(stream).await?;
     let host = target.host_string();
@@ -471,6 +486,7 @@ async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
     // Resolve upstream proxy (user-level > global-level)
     let upstream_proxy = crate::proxy::ProxyEngine::resolve_upstream_proxy(&user, &ctx.config);
 
+    let connect_start = Instant::now();
     match ctx
         .proxy_engine
         .connect_for_socks(
@@ -478,9 +494,15 @@ async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
             &host,
             port,
             &user.acl,
+            &user.ip_guard_exemptions,
+            user.blocked_ports.as_deref(),
+            user.group.as_deref(),
+            &user.tags,
             &source_ip_str,
             user.max_connections,
             upstream_proxy.as_ref(),
+            crate::proxy::retry::RetryPolicy::for_user(&user),
+            conn_id,
         )
         .await
     {
@@ -504,13 +526,32 @@ async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
                 bandwidth_limit_kbps: user.max_bandwidth_kbps,
                 aggregate_bandwidth_kbps: user.max_aggregate_bandwidth_kbps,
                 quotas: user.quotas.clone(),
+                server_names: ctx
+                    .proxy_engine
+                    .server_name_check(&user.acl, port, resolved_addr),
+                tags: user.tags.clone(),
+                payload_metadata: user.payload_metadata,
                 _guard: guard,
+                _user_slot: admission.user_slot,
+                _login: admission.login,
             }))
         }
         Err(e) => {
             let error_type = classify_connect_error(&e);
             warn!(conn_id = %conn_id, user = %creds.username, target = %format!("{}:{}", host, port), error = %e, error_type = %error_type, "SOCKS5 connect failed");
             ctx.metrics.record_error(error_type);
+            record_flow(ctx, || FlowRecord {
+                close_reason: error_type.to_string(),
+                correlation_id: Some(conn_id.to_string()),
+                ..FlowRecord::closing(
+                    "socks5",
+                    &creds.username,
+                    &source_ip_str,
+                    &host,
+                    port,
+                    connect_start.elapsed(),
+                )
+            });
             let reply_code = classify_error_reply(&e);
             protocol::send_reply(stream, reply_code, &protocol::TargetAddr::Ipv4([0; 4], 0))
                 .await?;
@@ -523,13 +564,15 @@ async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
 pub use crate::auth::password::extract_totp_from_password;
 
 /// Classify a connect/proxy error into a metric error_type label.
-fn classify_connect_error(err: &anyhow::Error) -> &'static str {
+pub(crate) fn classify_connect_error(err: &anyhow::Error) -> &'static str {
     use crate::metrics::error_types;
     let msg = err.to_string();
     if msg.contains("ACL denied") {
         error_types::ACL_DENIED
     } else if msg.contains("connection limit") {
         error_types::CONNECTION_REFUSED
+    } else if msg.contains("circuit open") {
+        error_types::CIRCUIT_OPEN
     } else if msg.contains("DNS") || msg.contains("dns") || msg.contains("lookup") {
         error_types::DNS_FAILURE
     } else if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
@@ -551,6 +594,8 @@ fn classify_error_reply(err: &anyhow::Error) -> u8 {
     let msg = err.to_string();
     if msg.contains("ACL denied") || msg.contains("connection limit") {
         protocol::REPLY_NOT_ALLOWED
+    } else if msg.contains("circuit open") {
+        protocol::REPLY_HOST_UNREACHABLE
     } else if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
         match io_err.kind() {
             std::io::ErrorKind::ConnectionRefused => protocol::REPLY_CONNECTION_REFUSED,
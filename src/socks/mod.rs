@@ -5,14 +5,22 @@ pub mod udp_relay;
 
 use crate::config::types::AppConfig;
 use crate::context::AppContext;
+use crate::proxy::client_caps::Listener;
 use anyhow::Result;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpListener;
 use tokio::sync::Semaphore;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// Answer a plain SOCKS5 client refused at accept time: no acceptable
+/// method is the one refusal a client understands before authenticating.
+async fn refuse(mut stream: tokio::net::TcpStream, timeout: Duration) {
+    if let Ok(Ok(_)) = tokio::time::timeout(timeout, protocol::read_greeting(&mut stream)).await {
+        let _ = protocol::send_method_selection(&mut stream, protocol::AUTH_NO_ACCEPTABLE).await;
+    }
+}
+
 /// Build the handshake timeout from config.
 pub(crate) fn socks5_handshake_timeout(config: &AppConfig) -> Duration {
     Duration::from_secs(config.limits.socks5_handshake_timeout)
@@ -61,7 +69,9 @@ pub async fn start_socks5_server(
 ) -> Result<()> {
     let tls_acceptor = load_tls_config(&ctx.config)?.map(tokio_rustls::TlsAcceptor::from);
 
-    let listener = TcpListener::bind(listen_addr).await?;
+    let listener = crate::handover::global()
+        .bind("socks5", listen_addr)
+        .await?;
 
     if tls_acceptor.is_some() {
         info!(addr = %listen_addr, "SOCKS5 server listening (TLS enabled)");
@@ -104,6 +114,23 @@ pub async fn start_socks5_server(
 
         tokio::spawn(async move {
             let _permit = permit;
+            let _client_slot = match ctx
+                .proxy_engine
+                .client_caps()
+                .acquire(Listener::Socks5)
+                .await
+            {
+                Ok(slot) => slot,
+                Err(rejection) => {
+                    warn!("SOCKS5 client connection cap reached, refusing connection");
+                    ctx.metrics
+                        .record_connection_rejected(rejection.metric_reason());
+                    if tls.is_none() {
+                        refuse(stream, socks5_handshake_timeout(&ctx.config)).await;
+                    }
+                    return;
+                }
+            };
 
             if let Some(acceptor) = tls {
                 // P3-1: TLS-wrapped SOCKS5
@@ -1,13 +1,24 @@
+use crate::audit::events::AuditEvent;
+use crate::auth::login::{self, PasswordAttempt, PasswordOutcome};
 use crate::auth::user::User;
+use crate::auth::{pubkey, tenant};
+use crate::config::types::ListenerConfig;
 use crate::context::AppContext;
+use crate::flows::FlowRecord;
 use crate::motd;
-use crate::proxy::SshRelayRequest;
+use crate::proxy::client_caps::UserSlot;
+use crate::proxy::{LiveConnection, SshRelayRequest};
+use crate::reports::Login;
+use crate::security::key_enrollment::{self, Enrollment, EnrollmentStatus};
 use crate::shell::context::ShellContext;
 use crate::shell::executor::CommandExecutor;
-use crate::shell::ShellSession;
+use crate::shell::{PasswordChangeHandle, ShellSession};
+use crate::ssh::pre_auth::PreAuthSlot;
 use crate::ssh::session::ClientSession;
 use crate::utils::generate_correlation_id;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -16,9 +27,20 @@ use russh::CryptoVec;
 use tokio::sync::Mutex;
 use tracing::{debug, info, info_span, warn, Instrument};
 
-/// Maximum number of shell channels per SSH connection to prevent resource exhaustion.
+/// Default `limits.max_channels_per_connection`: channels (sessions and
+/// forwardings) one SSH connection may hold open.
 pub const MAX_CHANNELS_PER_CONNECTION: usize = 10;
 
+/// Held by a forwarding channel's relay task; gives its channel back to the
+/// connection when the relay ends.
+struct ChannelSlot(Arc<AtomicUsize>);
+
+impl Drop for ChannelSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// Per-connection SSH handler
 pub struct SshHandler {
     ctx: Arc<AppContext>,
@@ -26,8 +48,30 @@ pub struct SshHandler {
     conn_id: String,
     session_state: ClientSession,
     shells: DashMap<russh::ChannelId, Arc<Mutex<ShellSession>>>,
+    /// Session channels handed to a `[[subsystems]]` backend, by subsystem name
+    subsystem_channels: DashMap<russh::ChannelId, String>,
+    /// Forwarding channels (`direct-tcpip`, `direct-streamlocal`) still relaying
+    forwarding_channels: Arc<AtomicUsize>,
     total_auth_attempts: u32,
     connected_at: Instant,
+    /// Held until authentication completes (unauthenticated connection caps)
+    pre_auth: Option<PreAuthSlot>,
+    /// Registry entry backing the session detail API
+    connection: Option<Arc<LiveConnection>>,
+    /// Share of `server.max_connections_per_user`, taken once authenticated
+    user_slot: Option<UserSlot>,
+    /// Login counted in the usage reports at disconnect
+    login: Option<Login>,
+    /// Policy of the `[[server.listeners]]` entry the client connected to
+    /// (None for `server.ssh_listen`)
+    listener: Option<Arc<ListenerConfig>>,
+    /// A login was already held for a key enrollment decision
+    enrollment_held: bool,
+    /// Authenticator flags of the security key signature being checked
+    security_key_flags: Option<u8>,
+    /// Security context of the `gssapi-with-mic` attempt under way
+    #[cfg(feature = "gssapi")]
+    gssapi: Option<crate::auth::gssapi::Acceptor>,
 }
 
 impl SshHandler {
@@ -39,9 +83,303 @@ impl SshHandler {
             conn_id,
             session_state: ClientSession::new(),
             shells: DashMap::new(),
+            subsystem_channels: DashMap::new(),
+            forwarding_channels: Arc::new(AtomicUsize::new(0)),
             total_auth_attempts: 0,
             connected_at: Instant::now(),
+            pre_auth: None,
+            connection: None,
+            user_slot: None,
+            login: None,
+            listener: None,
+            enrollment_held: false,
+            security_key_flags: None,
+            #[cfg(feature = "gssapi")]
+            gssapi: None,
+        }
+    }
+
+    /// Attach the connection's pre-auth slot, released on successful login.
+    pub fn set_pre_auth_slot(&mut self, slot: PreAuthSlot) {
+        self.pre_auth = Some(slot);
+    }
+
+    /// Attach the policy of the listener the client connected to.
+    pub fn set_listener(&mut self, listener: Arc<ListenerConfig>) {
+        self.listener = Some(listener);
+    }
+
+    /// The account `user` logs in as on this listener (`[[tenants]]`), or
+    /// None, logged and audited, if the listener serves another tenant or
+    /// the client's IP is banned from the account's tenant.
+    async fn tenant_login(&self, user: &str, method: &str) -> Option<String> {
+        let listener_tenant = self.listener.as_ref().and_then(|l| l.tenant.as_deref());
+        let login = tenant::login_name(&self.ctx.config.tenants, listener_tenant, user);
+        if let Some(ref login) = login {
+            let login_tenant = tenant::tenant_of(&self.ctx.config.tenants, login);
+            if login_tenant.is_some()
+                && self
+                    .ctx
+                    .security
+                    .read()
+                    .await
+                    .is_banned_for(&self.peer_addr.ip(), login_tenant)
+            {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = %login,
+                    ip = %self.peer_addr,
+                    tenant = ?login_tenant,
+                    "Login refused: IP banned from the tenant"
+                );
+                self.ctx.metrics.record_connection_rejected("banned");
+                return None;
+            }
+        }
+        if login.is_none() {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %user,
+                ip = %self.peer_addr,
+                tenant = ?listener_tenant,
+                "Login refused: user outside the listener's tenant"
+            );
+            self.ctx.audit.log_event(AuditEvent::auth_failure_with_cid(
+                user,
+                &self.peer_addr,
+                method,
+                &self.conn_id,
+            ));
+            self.ctx.metrics.record_connection_rejected("acl_denied");
+        }
+        login
+    }
+
+    /// Attach the connection's registry entry (see `ProxyEngine::register_connection`).
+    pub fn set_connection(&mut self, connection: Arc<LiveConnection>) {
+        self.connection = Some(connection);
+    }
+
+    /// Mark the session authenticated and give back the pre-auth slot.
+    fn complete_auth(&mut self, user: &str, method: &str) {
+        self.session_state.username = Some(user.to_string());
+        self.session_state.authenticated = true;
+        self.session_state.auth_method = method.to_string();
+        if let Some(mut slot) = self.pre_auth.take() {
+            slot.authenticated();
+        }
+        if let Some(connection) = &self.connection {
+            connection.set_auth_method(method);
+        }
+    }
+
+    /// HASSH of the client, once its KEXINIT has been seen.
+    fn client_hassh(&self) -> Option<String> {
+        self.connection.as_ref().and_then(|c| c.hassh())
+    }
+
+    /// Check the client against the user's `allowed_hassh` pins, after the
+    /// credentials were accepted: a mismatch points at credentials reused
+    /// from another SSH client.
+    async fn is_hassh_allowed(&self, user: &str) -> bool {
+        let hassh = self.client_hassh();
+        let allowed = self
+            .ctx
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(user)
+            .is_none_or(|u| u.is_hassh_allowed(hassh.as_deref()));
+        if !allowed {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %user,
+                ip = %self.peer_addr,
+                hassh = hassh.as_deref().unwrap_or("-"),
+                "Valid credentials from an SSH client not in the user's allowed_hassh"
+            );
+        }
+        allowed
+    }
+
+    /// FIDO2 security keys: check the authenticator `flags` of the login
+    /// signature, which must show a touch, and user verification too when
+    /// the user has `require_security_key`.
+    async fn is_security_key_signature_allowed(
+        &self,
+        user: &str,
+        key: &russh::keys::PublicKey,
+        flags: Option<u8>,
+    ) -> bool {
+        if !pubkey::is_security_key(key.algorithm().as_str()) {
+            return true;
+        }
+        let allowed = self
+            .ctx
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(user)
+            .is_some_and(|u| flags.is_some_and(|f| u.accepts_security_key_flags(f)));
+        if !allowed {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %user,
+                ip = %self.peer_addr,
+                flags = flags.unwrap_or(0),
+                "Security key signature refused: not touched, or not user-verified with require_security_key set"
+            );
+        }
+        allowed
+    }
+
+    /// Check `key` against the user's `authorized_keys_url` list (fetched
+    /// or cached), outside the auth service lock.
+    async fn fetched_key_matches(&self, user: &str, key: &russh::keys::PublicKey) -> bool {
+        let remote = {
+            let auth = self.ctx.auth_service.read().await;
+            let allowed = auth
+                .user_store()
+                .get(user)
+                .is_some_and(|u| u.accepts_key_type(key.algorithm().as_str()));
+            allowed.then(|| auth.remote_keys(user)).flatten()
+        };
+        let Some((fetcher, url)) = remote else {
+            return false;
+        };
+        let matched = fetcher.authorizes(&url, key).await;
+        if matched {
+            debug!(
+                conn_id = %self.conn_id,
+                user = %user,
+                "Public key found at authorized_keys_url"
+            );
+        }
+        matched
+    }
+
+    /// `[key_enrollment]`: queue an unknown key of a configured user for
+    /// admin approval and hold the login until it is decided or `wait_secs`
+    /// pass. Only the first new key of a connection is held, so a client
+    /// offering several keys does not wait once per key. True if the key
+    /// was approved in time.
+    async fn await_key_enrollment(&mut self, user: &str, key: &russh::keys::PublicKey) -> bool {
+        let known = self
+            .ctx
+            .auth_service
+            .read()
+            .await
+            .user_store()
+            .get(user)
+            // A configured key is not new: it was refused for its expiry-time
+            // or key type
+            .is_some_and(|u| {
+                u.can_log_in()
+                    && u.accepts_key_type(key.algorithm().as_str())
+                    && !u.parsed_authorized_keys.contains(key)
+            });
+        if !known {
+            return false;
+        }
+        let hassh = self.client_hassh();
+        let (enrollment, wait) = {
+            let security = self.ctx.security.read().await;
+            let Some(queue) = security.key_enrollment() else {
+                return false;
+            };
+            (
+                queue.request(user, key, self.peer_addr.ip(), hassh),
+                queue.wait(),
+            )
+        };
+        let mut decision = match enrollment {
+            Enrollment::New(request, rx) => {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = %user,
+                    ip = %self.peer_addr,
+                    fingerprint = %request.fingerprint,
+                    request_id = %request.id,
+                    "New public key awaiting approval"
+                );
+                self.ctx
+                    .audit
+                    .log_event(AuditEvent::key_enrollment_requested_with_cid(
+                        &request,
+                        &self.conn_id,
+                    ));
+                rx
+            }
+            Enrollment::Pending(rx) => rx,
+            Enrollment::Refused => {
+                info!(
+                    conn_id = %self.conn_id,
+                    user = %user,
+                    ip = %self.peer_addr,
+                    "New public key refused (rejected, or too many pending for the user)"
+                );
+                return false;
+            }
+        };
+        let wait = if self.enrollment_held {
+            std::time::Duration::ZERO
+        } else {
+            wait
+        };
+        self.enrollment_held = true;
+        let pending = decision.wait_for(|status| *status != EnrollmentStatus::Pending);
+        let approved = match tokio::time::timeout(wait, pending).await {
+            Ok(Ok(status)) => *status == EnrollmentStatus::Approved,
+            _ => false,
+        };
+        // Approval adds the key to the user store before waking us
+        approved && self.ctx.auth_service.read().await.auth_publickey(user, key)
+    }
+
+    /// Publish the number of open session channels to the registry entry.
+    /// Session channels open on this connection (shells and subsystems).
+    fn open_channels(&self) -> usize {
+        self.shells.len() + self.subsystem_channels.len()
+    }
+
+    fn sync_channel_count(&self) {
+        if let Some(connection) = &self.connection {
+            connection
+                .session_channels
+                .store(self.open_channels() as u32, Ordering::Relaxed);
+        }
+    }
+
+    /// True if `limits.max_channels_per_connection` leaves room for one
+    /// more channel of any kind.
+    fn channel_available(&self) -> bool {
+        let max = self.ctx.config.limits.max_channels_per_connection;
+        max == 0 || self.open_channels() + self.forwarding_channels.load(Ordering::Relaxed) < max
+    }
+
+    /// Refuse a `kind` channel once the connection holds
+    /// `limits.max_channels_per_connection` (several multiplexed sessions
+    /// under OpenSSH `ControlMaster`, for instance).
+    fn channel_limit_reached(&self, kind: &str) -> bool {
+        if self.channel_available() {
+            return false;
         }
+        warn!(
+            conn_id = %self.conn_id,
+            user = ?self.session_state.username,
+            kind = %kind,
+            max = self.ctx.config.limits.max_channels_per_connection,
+            "Channel refused: max_channels_per_connection reached"
+        );
+        true
+    }
+
+    /// Count a new forwarding channel until the returned slot is dropped.
+    fn forwarding_slot(&self) -> ChannelSlot {
+        self.forwarding_channels.fetch_add(1, Ordering::Relaxed);
+        ChannelSlot(self.forwarding_channels.clone())
     }
 
     /// Check if the SSH auth timeout has been exceeded (slow-client DoS protection).
@@ -64,6 +402,17 @@ impl SshHandler {
         if !self.session_state.authenticated {
             return Ok(None);
         }
+        if self.session_state.honeypot {
+            warn!(
+                conn_id = %self.conn_id,
+                user = ?self.session_state.username,
+                ip = %self.peer_addr.ip(),
+                host = %host_to_connect,
+                port = port_to_connect,
+                "Honeypot session forwarding attempt denied"
+            );
+            return Ok(None);
+        }
 
         let username = match &self.session_state.username {
             Some(u) => u.clone(),
@@ -166,7 +515,9 @@ impl SshHandler {
                 reason = %reason,
                 "SSH direct-tcpip connection quota exceeded"
             );
-            self.ctx.audit.log_quota_exceeded(&username, &reason, 0, 0);
+            self.ctx
+                .audit
+                .log_quota_exceeded_cid(&username, &reason, 0, 0, &self.conn_id);
             self.ctx
                 .metrics
                 .record_error(crate::metrics::error_types::QUOTA_EXCEEDED);
@@ -188,7 +539,9 @@ impl SshHandler {
                 reason = %reason,
                 "SSH direct-tcpip bandwidth quota already exhausted"
             );
-            self.ctx.audit.log_quota_exceeded(&username, &reason, 0, 0);
+            self.ctx
+                .audit
+                .log_quota_exceeded_cid(&username, &reason, 0, 0, &self.conn_id);
             self.ctx
                 .metrics
                 .record_error(crate::metrics::error_types::QUOTA_EXCEEDED);
@@ -219,6 +572,35 @@ impl SshHandler {
         Ok(Some((user, username, port)))
     }
 
+    /// Methods a rejected client may try next.
+    fn retry_methods(&self) -> russh::MethodSet {
+        let mut methods = russh::MethodSet::from(
+            [
+                russh::MethodKind::Password,
+                russh::MethodKind::PublicKey,
+                russh::MethodKind::KeyboardInteractive,
+            ]
+            .as_slice(),
+        );
+        if self.ctx.config.gssapi.is_some() {
+            methods.push(russh::MethodKind::GssapiWithMic);
+        }
+        methods
+    }
+
+    /// Record credential verification time into `s5_ssh_auth_duration_seconds`.
+    fn record_auth_duration(&self, method: &str, success: bool, started: Instant) {
+        use crate::metrics::outcomes;
+        let outcome = if success {
+            outcomes::SUCCESS
+        } else {
+            outcomes::FAILURE
+        };
+        self.ctx
+            .metrics
+            .record_ssh_auth(method, outcome, started.elapsed().as_secs_f64());
+    }
+
     /// Record an auth failure: log, audit, metrics, ban, check max attempts
     async fn record_auth_failure(
         &mut self,
@@ -234,10 +616,10 @@ impl SshHandler {
             method = %method,
             "Auth failed"
         );
-        self.ctx
-            .audit
-            .log_auth_failure_cid(username, &self.peer_addr, method, &self.conn_id)
-            .await;
+        self.ctx.audit.log_event(
+            AuditEvent::auth_failure_with_cid(username, &self.peer_addr, method, &self.conn_id)
+                .with_hassh(self.client_hassh()),
+        );
         let metric_method = if method == "publickey" {
             "pubkey"
         } else {
@@ -249,10 +631,15 @@ impl SshHandler {
             .record_error(crate::metrics::error_types::AUTH_FAILURE);
         self.ctx.metrics.record_connection_rejected("auth_failed");
         self.ctx
-            .security
-            .read()
-            .await
-            .record_auth_failure(&self.peer_addr.ip());
+            .record_login_failure(username, &self.peer_addr, "ssh", &self.conn_id)
+            .await;
+        // Rejected keys and Kerberos tickets are not guesses; only password
+        // failures lock the account
+        if !matches!(method, "publickey" | "gssapi-with-mic") {
+            self.ctx
+                .record_account_failure(username, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+        }
 
         if attempts >= self.ctx.config.limits.max_auth_attempts {
             return russh::server::Auth::Reject {
@@ -262,12 +649,59 @@ impl SshHandler {
         }
 
         russh::server::Auth::Reject {
-            proceed_with_methods: Some(russh::MethodSet::from(
-                [russh::MethodKind::Password, russh::MethodKind::PublicKey].as_slice(),
-            )),
+            proceed_with_methods: Some(self.retry_methods()),
             partial_success: false,
         }
     }
+
+    /// Check a password (and TOTP code, when required) for `user`, shared by
+    /// the `password` and `keyboard-interactive` methods. Without
+    /// `totp_code`, the code is taken from the end of the password.
+    async fn verify_password_login(
+        &mut self,
+        user: &str,
+        password: &str,
+        totp_code: Option<String>,
+        method: &str,
+    ) -> russh::server::Auth {
+        if self
+            .ctx
+            .security
+            .read()
+            .await
+            .honeypot()
+            .matches(user, password)
+        {
+            self.complete_auth(user, method);
+            self.session_state.honeypot = true;
+            self.ctx
+                .honeypot_triggered(user, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+            return russh::server::Auth::Accept;
+        }
+
+        let attempt = PasswordAttempt {
+            username: user,
+            password,
+            totp_code,
+            peer_addr: &self.peer_addr,
+            protocol: "ssh",
+            audit_method: method,
+            method,
+            hassh: self.client_hassh(),
+            conn_id: &self.conn_id,
+        };
+        match login::authenticate_password(&self.ctx, &attempt).await {
+            PasswordOutcome::Accepted { method, .. } => {
+                self.complete_auth(user, &method);
+                russh::server::Auth::Accept
+            }
+            PasswordOutcome::Rejected { .. } => {
+                self.record_auth_failure(user, method, self.total_auth_attempts)
+                    .await
+            }
+        }
+    }
 }
 
 /// Test helper methods for inspecting SshHandler internal state.
@@ -298,6 +732,11 @@ impl SshHandler {
         self.session_state.username.as_deref()
     }
 
+    /// Test helper: check if the session logged in with a decoy credential
+    pub fn is_honeypot(&self) -> bool {
+        self.session_state.honeypot
+    }
+
     /// Test helper: get the auth method
     pub fn auth_method(&self) -> &str {
         &self.session_state.auth_method
@@ -337,9 +776,10 @@ impl SshHandler {
         self.session_state.ssh_key_fingerprint = Some(fp.to_string());
     }
 
-    /// Test helper: check if a new channel would be accepted based on current shell count
+    /// Test helper: check if a new channel would be accepted based on the
+    /// channels open on this connection
     pub fn would_accept_new_channel(&self) -> bool {
-        self.shells.len() < MAX_CHANNELS_PER_CONNECTION
+        self.channel_available()
     }
 
     /// Test helper: get access to the record_auth_failure method
@@ -389,6 +829,19 @@ impl russh::server::Handler for SshHandler {
             None => return Ok(false),
         };
 
+        // Decoy login: bare virtual shell, no context, MOTD or passwd
+        if self.session_state.honeypot {
+            if !self.channel_available() {
+                return Ok(false);
+            }
+            let channel_id = channel.id();
+            let shell =
+                ShellSession::new(username, self.ctx.config.shell.hostname.clone(), channel);
+            self.shells.insert(channel_id, Arc::new(Mutex::new(shell)));
+            self.sync_channel_count();
+            return Ok(true);
+        }
+
         let user = match self
             .ctx
             .auth_service
@@ -402,19 +855,14 @@ impl russh::server::Handler for SshHandler {
             None => return Ok(false),
         };
 
-        if !user.allow_shell {
+        // Users limited to subsystems still need the session channel
+        if !user.allow_shell && user.subsystems.is_empty() {
             warn!(conn_id = %self.conn_id, user = %username, "Shell access denied by config");
             return Ok(false);
         }
 
         // Enforce per-connection channel limit to prevent resource exhaustion
-        if self.shells.len() >= MAX_CHANNELS_PER_CONNECTION {
-            warn!(
-                user = %username,
-                conn_id = %self.conn_id,
-                max = MAX_CHANNELS_PER_CONNECTION,
-                "Max shell channels per connection exceeded"
-            );
+        if self.channel_limit_reached("session") {
             return Ok(false);
         }
 
@@ -444,8 +892,18 @@ impl russh::server::Handler for SshHandler {
             proxy_engine: Some(self.ctx.proxy_engine.clone()),
             quota_tracker: Some(self.ctx.quota_tracker.clone()),
             quota_config: user.quotas.clone(),
+            env: Vec::new(),
         };
         shell.set_context(shell_ctx);
+        shell.set_env("TERM", &user.terminal.default_term);
+        shell.set_terminal_policy(user.terminal.clone());
+        shell.set_shell_allowed(user.allow_shell);
+        shell.set_password_change(PasswordChangeHandle {
+            auth_service: self.ctx.auth_service.clone(),
+            config_path: self.ctx.config_path.clone(),
+            audit: self.ctx.audit.clone(),
+            source_ip: self.peer_addr.ip().to_string(),
+        });
 
         // Render MOTD
         let (motd_enabled, motd_template, motd_colors) = motd::resolve_motd_config(
@@ -481,6 +939,7 @@ impl russh::server::Handler for SshHandler {
         }
 
         self.shells.insert(channel_id, Arc::new(Mutex::new(shell)));
+        self.sync_channel_count();
         Ok(true)
     }
 
@@ -520,80 +979,29 @@ impl russh::server::Handler for SshHandler {
             });
         }
 
-        // Determine if TOTP is required for SSH
-        let totp_required = self
-            .ctx
-            .config
-            .security
-            .totp_required_for
-            .contains(&"ssh".to_string());
-
-        // AUTH-001: Consolidated auth reads to reduce RwLock contention
-        // Single auth_service read for TOTP check, password verify, and user_has_totp flag
-        let (auth_result, user_has_totp) = {
-            let auth = self.ctx.auth_service.read().await;
-
-            if totp_required {
-                let has_totp = auth
-                    .user_store()
-                    .get(user)
-                    .map(|u| u.totp_enabled && u.totp_secret.is_some())
-                    .unwrap_or(false);
-
-                if has_totp {
-                    // Secure TOTP extraction with delimiter + suffix support
-                    let (actual_pass, totp_code) =
-                        crate::auth::password::extract_totp_from_password(password);
-                    let pass_ok = auth.auth_password(user, &actual_pass);
-                    let result = pass_ok
-                        && match totp_code {
-                            Some(code) => auth.verify_totp(user, &code),
-                            None => false,
-                        };
-                    (result, has_totp)
-                } else {
-                    (auth.auth_password(user, password), false)
-                }
-            } else {
-                (auth.auth_password(user, password), false)
-            }
+        let Some(user) = self.tenant_login(user, "password").await else {
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
         };
-
-        if auth_result {
-            info!(conn_id = %self.conn_id, user = %user, ip = %self.peer_addr, "Password auth success");
-            self.session_state.username = Some(user.to_string());
-            self.session_state.authenticated = true;
-            self.session_state.auth_method = if totp_required && user_has_totp {
-                "password+totp".to_string()
-            } else {
-                "password".to_string()
-            };
-            self.ctx
-                .audit
-                .log_auth_success_cid(user, &self.peer_addr, "password", &self.conn_id)
-                .await;
-            self.ctx.audit.log_session_authenticated_cid(
-                user,
-                &self.peer_addr,
-                "ssh",
-                &self.session_state.auth_method,
-                &self.conn_id,
-            );
-            self.ctx.metrics.record_auth_success(user, "password");
-            Ok(russh::server::Auth::Accept)
-        } else {
-            Ok(self
-                .record_auth_failure(user, "password", self.total_auth_attempts)
-                .await)
-        }
+        Ok(self
+            .verify_password_login(&user, password, None, "password")
+            .await)
     }
 
-    async fn auth_publickey(
-        &mut self,
+    /// `keyboard-interactive`, for clients that offer nothing else: one round
+    /// of prompts (password, plus a verification code when
+    /// `totp_required_for` has `ssh`), checked like `auth_password`.
+    async fn auth_keyboard_interactive<'a>(
+        &'a mut self,
         user: &str,
-        public_key: &russh::keys::PublicKey,
+        _submethods: &str,
+        response: Option<russh::server::Response<'a>>,
     ) -> Result<russh::server::Auth, Self::Error> {
-        self.total_auth_attempts += 1;
+        if response.is_some() {
+            self.total_auth_attempts += 1;
+        }
 
         if self.is_auth_timed_out() {
             warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), "SSH auth timeout exceeded");
@@ -611,7 +1019,12 @@ impl russh::server::Handler for SshHandler {
             .await
             .pre_auth_check(&self.peer_addr.ip())
         {
-            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), reason = %reason, "SSH pubkey auth rejected");
+            warn!(
+                conn_id = %self.conn_id,
+                ip = %self.peer_addr.ip(),
+                reason = %reason,
+                "SSH keyboard-interactive auth rejected"
+            );
             let metric_reason = if reason == "banned IP" {
                 "banned"
             } else {
@@ -624,53 +1037,50 @@ impl russh::server::Handler for SshHandler {
             });
         }
 
-        if self
+        let totp_prompt = self
             .ctx
-            .auth_service
-            .read()
-            .await
-            .auth_publickey(user, public_key)
-        {
-            info!(conn_id = %self.conn_id, user = %user, ip = %self.peer_addr, "Public key auth success");
-            self.session_state.username = Some(user.to_string());
-            self.session_state.authenticated = true;
-            self.session_state.auth_method = "publickey".to_string();
-            // Compute SSH key fingerprint (SHA256 of base64-decoded public key bytes)
-            let fingerprint = {
-                use base64::Engine;
-                use russh::keys::PublicKeyBase64;
-                use sha2::{Digest, Sha256};
-                let key_b64 = public_key.public_key_base64();
-                let key_bytes = base64::engine::general_purpose::STANDARD
-                    .decode(&key_b64)
-                    .unwrap_or_default();
-                let hash = Sha256::digest(&key_bytes);
-                let b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash);
-                format!("SHA256:{}", b64)
-            };
-            self.session_state.ssh_key_fingerprint = Some(fingerprint);
-            self.ctx
-                .audit
-                .log_auth_success_cid(user, &self.peer_addr, "publickey", &self.conn_id)
-                .await;
-            self.ctx.audit.log_session_authenticated_cid(
-                user,
-                &self.peer_addr,
-                "ssh",
-                "publickey",
-                &self.conn_id,
-            );
-            self.ctx.metrics.record_auth_success(user, "pubkey");
-            Ok(russh::server::Auth::Accept)
-        } else {
-            Ok(self
-                .record_auth_failure(user, "publickey", self.total_auth_attempts)
-                .await)
-        }
+            .config
+            .security
+            .totp_required_for
+            .contains(&"ssh".to_string());
+        let Some(response) = response else {
+            // Same prompts for every user, so they reveal nothing about the account
+            let mut prompts = vec![(Cow::Borrowed("Password: "), false)];
+            if totp_prompt {
+                prompts.push((Cow::Borrowed("Verification code: "), true));
+            }
+            return Ok(russh::server::Auth::Partial {
+                name: Cow::Borrowed(""),
+                instructions: Cow::Borrowed(""),
+                prompts: Cow::Owned(prompts),
+            });
+        };
+
+        let mut answers =
+            response.map(|answer| String::from_utf8_lossy(answer.as_ref()).into_owned());
+        let password = answers.next().unwrap_or_default();
+        let totp_code = answers
+            .next()
+            .map(|code| code.trim().to_string())
+            .filter(|code| totp_prompt && !code.is_empty());
+        let Some(user) = self.tenant_login(user, "keyboard-interactive").await else {
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        };
+        Ok(self
+            .verify_password_login(&user, &password, totp_code, "keyboard-interactive")
+            .await)
     }
 
-    async fn auth_none(&mut self, user: &str) -> Result<russh::server::Auth, Self::Error> {
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &russh::keys::PublicKey,
+    ) -> Result<russh::server::Auth, Self::Error> {
         self.total_auth_attempts += 1;
+        let security_key_flags = self.security_key_flags.take();
 
         if self.is_auth_timed_out() {
             warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), "SSH auth timeout exceeded");
@@ -681,7 +1091,6 @@ impl russh::server::Handler for SshHandler {
             });
         }
 
-        // P1-1: Pre-auth ban check — reject banned IPs immediately (0 auth attempts)
         if let Err(reason) = self
             .ctx
             .security
@@ -689,7 +1098,7 @@ impl russh::server::Handler for SshHandler {
             .await
             .pre_auth_check(&self.peer_addr.ip())
         {
-            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), reason = %reason, "SSH auth_none rejected (pre-auth)");
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), reason = %reason, "SSH pubkey auth rejected");
             let metric_reason = if reason == "banned IP" {
                 "banned"
             } else {
@@ -702,15 +1111,372 @@ impl russh::server::Handler for SshHandler {
             });
         }
 
-        info!(conn_id = %self.conn_id, user = %user, ip = %self.peer_addr, "auth_none attempt (rejected)");
-        Ok(russh::server::Auth::Reject {
-            proceed_with_methods: Some(russh::MethodSet::from(
-                [russh::MethodKind::Password, russh::MethodKind::PublicKey].as_slice(),
-            )),
-            partial_success: false,
+        let Some(login) = self.tenant_login(user, "publickey").await else {
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        };
+        let user = login.as_str();
+
+        let verify_start = Instant::now();
+        let auth_result = self
+            .ctx
+            .auth_service
+            .read()
+            .await
+            .auth_publickey(user, public_key);
+        self.record_auth_duration("publickey", auth_result, verify_start);
+        let auth_result = auth_result || self.fetched_key_matches(user, public_key).await;
+        let auth_result = auth_result || self.await_key_enrollment(user, public_key).await;
+        let auth_result = auth_result
+            && self
+                .is_security_key_signature_allowed(user, public_key, security_key_flags)
+                .await;
+        let auth_result = auth_result && self.is_hassh_allowed(user).await;
+        let auth_result = auth_result
+            && !self
+                .ctx
+                .is_account_locked(user, &self.peer_addr, &self.conn_id)
+                .await;
+        let auth_result = auth_result
+            && self
+                .ctx
+                .check_impossible_travel(user, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+        let success =
+            AuditEvent::auth_success_with_cid(user, &self.peer_addr, "publickey", &self.conn_id)
+                .with_hassh(self.client_hassh());
+        let success = if auth_result {
+            self.ctx.run_auth_hook(success).await
+        } else {
+            None
+        };
+
+        if let Some(success) = success {
+            info!(conn_id = %self.conn_id, user = %user, ip = %self.peer_addr, "Public key auth success");
+            self.complete_auth(user, "publickey");
+            self.session_state.ssh_key_fingerprint =
+                Some(key_enrollment::key_fingerprint(public_key));
+            self.ctx.audit.log_event(success);
+            self.ctx.audit.log_session_authenticated_cid(
+                user,
+                &self.peer_addr,
+                "ssh",
+                "publickey",
+                &self.conn_id,
+            );
+            self.ctx.metrics.record_auth_success(user, "pubkey");
+            self.ctx
+                .security
+                .read()
+                .await
+                .account_lockout()
+                .record_success(user);
+            self.ctx
+                .check_login_anomaly(user, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+            Ok(russh::server::Auth::Accept)
+        } else {
+            Ok(self
+                .record_auth_failure(user, "publickey", self.total_auth_attempts)
+                .await)
+        }
+    }
+
+    /// Keep the flags of a security key signature for the `auth_publickey`
+    /// call that follows.
+    async fn auth_security_key_flags(
+        &mut self,
+        _user: &str,
+        flags: u8,
+    ) -> Result<russh::server::Auth, Self::Error> {
+        self.security_key_flags = Some(flags);
+        Ok(russh::server::Auth::Accept)
+    }
+
+    /// Start a Kerberos login (`[gssapi]`) when the client offers the
+    /// Kerberos V5 mechanism.
+    #[cfg(feature = "gssapi")]
+    async fn auth_gssapi_mechanism(
+        &mut self,
+        user: &str,
+        mechanisms: &[Vec<u8>],
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        use crate::auth::gssapi::KRB5_MECHANISM;
+        self.total_auth_attempts += 1;
+        self.gssapi = None;
+
+        if self.is_auth_timed_out() {
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), "SSH auth timeout exceeded");
+            self.ctx.metrics.record_connection_rejected("auth_timeout");
+            return Ok(None);
+        }
+
+        if let Err(reason) = self
+            .ctx
+            .security
+            .read()
+            .await
+            .pre_auth_check(&self.peer_addr.ip())
+        {
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), reason = %reason, "SSH gssapi auth rejected");
+            let metric_reason = if reason == "banned IP" {
+                "banned"
+            } else {
+                "acl_denied"
+            };
+            self.ctx.metrics.record_connection_rejected(metric_reason);
+            return Ok(None);
+        }
+
+        let Some(gssapi) = self.ctx.auth_service.read().await.gssapi() else {
+            return Ok(None);
+        };
+        if !mechanisms.iter().any(|m| m == KRB5_MECHANISM) {
+            debug!(conn_id = %self.conn_id, user = %user, "gssapi-with-mic without the Kerberos mechanism");
+            return Ok(None);
+        }
+        match gssapi.acceptor() {
+            Ok(acceptor) => {
+                self.gssapi = Some(acceptor);
+                Ok(Some(KRB5_MECHANISM.to_vec()))
+            }
+            Err(e) => {
+                warn!(conn_id = %self.conn_id, error = %e, "GSS-API unavailable");
+                Ok(None)
+            }
+        }
+    }
+
+    #[cfg(feature = "gssapi")]
+    async fn auth_gssapi_token(
+        &mut self,
+        user: &str,
+        token: &[u8],
+    ) -> Result<russh::server::GssapiStep, Self::Error> {
+        use russh::server::GssapiStep;
+        let Some(ref mut acceptor) = self.gssapi else {
+            return Ok(GssapiStep::Reject(None));
+        };
+        let step = acceptor.accept(token);
+        if let GssapiStep::Reject(_) = step {
+            self.gssapi = None;
+            self.record_auth_failure(user, "gssapi-with-mic", self.total_auth_attempts)
+                .await;
+        }
+        Ok(step)
+    }
+
+    /// Finish a Kerberos login: check the MIC, then let the client in if
+    /// its principal maps to `user` (`[gssapi]`).
+    #[cfg(feature = "gssapi")]
+    async fn auth_gssapi_mic(
+        &mut self,
+        user: &str,
+        mic_data: &[u8],
+        mic: &[u8],
+    ) -> Result<russh::server::Auth, Self::Error> {
+        let Some(mut acceptor) = self.gssapi.take() else {
+            return Ok(russh::server::Auth::reject());
+        };
+
+        if self.is_auth_timed_out() {
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), "SSH auth timeout exceeded");
+            self.ctx.metrics.record_connection_rejected("auth_timeout");
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        }
+
+        let Some(login) = self.tenant_login(user, "gssapi-with-mic").await else {
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        };
+        let user = login.as_str();
+
+        let verify_start = Instant::now();
+        let principal = acceptor
+            .verify_mic(mic_data, mic)
+            .then(|| acceptor.principal())
+            .flatten();
+        let auth_result = {
+            let auth_service = self.ctx.auth_service.read().await;
+            let mapped = principal.and_then(|principal| {
+                auth_service
+                    .gssapi()
+                    .and_then(|gssapi| gssapi.principals().user(principal))
+            });
+            if let Some(principal) = principal.filter(|_| mapped.as_deref() != Some(user)) {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = %user,
+                    principal = %principal,
+                    "Kerberos principal not mapped to this user"
+                );
+            }
+            mapped.as_deref() == Some(user)
+                && auth_service
+                    .user_store()
+                    .get(user)
+                    .is_some_and(|u| u.can_log_in())
+        };
+        self.record_auth_duration("gssapi-with-mic", auth_result, verify_start);
+        let auth_result = auth_result && self.is_hassh_allowed(user).await;
+        let auth_result = auth_result
+            && !self
+                .ctx
+                .is_account_locked(user, &self.peer_addr, &self.conn_id)
+                .await;
+        let auth_result = auth_result
+            && self
+                .ctx
+                .check_impossible_travel(user, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+        let success = AuditEvent::auth_success_with_cid(
+            user,
+            &self.peer_addr,
+            "gssapi-with-mic",
+            &self.conn_id,
+        )
+        .with_hassh(self.client_hassh());
+        let success = if auth_result {
+            self.ctx.run_auth_hook(success).await
+        } else {
+            None
+        };
+
+        if let Some(success) = success {
+            info!(
+                conn_id = %self.conn_id,
+                user = %user,
+                ip = %self.peer_addr,
+                principal = principal.unwrap_or_default(),
+                "Kerberos auth success"
+            );
+            self.complete_auth(user, "gssapi-with-mic");
+            self.ctx.audit.log_event(success);
+            self.ctx.audit.log_session_authenticated_cid(
+                user,
+                &self.peer_addr,
+                "ssh",
+                "gssapi-with-mic",
+                &self.conn_id,
+            );
+            self.ctx
+                .metrics
+                .record_auth_success(user, "gssapi-with-mic");
+            self.ctx
+                .security
+                .read()
+                .await
+                .account_lockout()
+                .record_success(user);
+            self.ctx
+                .check_login_anomaly(user, &self.peer_addr, "ssh", &self.conn_id)
+                .await;
+            Ok(russh::server::Auth::Accept)
+        } else {
+            Ok(self
+                .record_auth_failure(user, "gssapi-with-mic", self.total_auth_attempts)
+                .await)
+        }
+    }
+
+    async fn auth_none(&mut self, user: &str) -> Result<russh::server::Auth, Self::Error> {
+        self.total_auth_attempts += 1;
+
+        if self.is_auth_timed_out() {
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), "SSH auth timeout exceeded");
+            self.ctx.metrics.record_connection_rejected("auth_timeout");
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        }
+
+        // P1-1: Pre-auth ban check — reject banned IPs immediately (0 auth attempts)
+        if let Err(reason) = self
+            .ctx
+            .security
+            .read()
+            .await
+            .pre_auth_check(&self.peer_addr.ip())
+        {
+            warn!(conn_id = %self.conn_id, ip = %self.peer_addr.ip(), reason = %reason, "SSH auth_none rejected (pre-auth)");
+            let metric_reason = if reason == "banned IP" {
+                "banned"
+            } else {
+                "acl_denied"
+            };
+            self.ctx.metrics.record_connection_rejected(metric_reason);
+            return Ok(russh::server::Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        }
+
+        info!(conn_id = %self.conn_id, user = %user, ip = %self.peer_addr, "auth_none attempt (rejected)");
+        Ok(russh::server::Auth::Reject {
+            proceed_with_methods: Some(self.retry_methods()),
+            partial_success: false,
         })
     }
 
+    async fn auth_succeeded(
+        &mut self,
+        session: &mut russh::server::Session,
+    ) -> Result<(), Self::Error> {
+        if self.session_state.honeypot {
+            return Ok(());
+        }
+        let Some(username) = self.session_state.username.clone() else {
+            return Ok(());
+        };
+        if let Some(message) = self.ctx.maintenance_rejection(&username).await {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %username,
+                ip = %self.peer_addr.ip(),
+                "Maintenance mode, disconnecting non-admin login"
+            );
+            self.ctx.metrics.record_connection_rejected("maintenance");
+            let _ = session.disconnect(russh::Disconnect::ServiceNotAvailable, &message, "");
+            return Ok(());
+        }
+        match self
+            .ctx
+            .proxy_engine
+            .client_caps()
+            .try_acquire_user(&username)
+        {
+            Ok(slot) => {
+                self.user_slot = Some(slot);
+                self.login = Some(self.ctx.proxy_engine.usage_history().start_login(&username));
+            }
+            Err(rejection) => {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = %username,
+                    ip = %self.peer_addr.ip(),
+                    "Too many connections for user, disconnecting"
+                );
+                self.ctx
+                    .metrics
+                    .record_connection_rejected(rejection.metric_reason());
+                let _ = session.disconnect(
+                    russh::Disconnect::TooManyConnections,
+                    &format!("too many connections for user {username}"),
+                    "",
+                );
+            }
+        }
+        Ok(())
+    }
+
     async fn channel_open_direct_tcpip(
         &mut self,
         channel: russh::Channel<russh::server::Msg>,
@@ -727,6 +1493,9 @@ impl russh::server::Handler for SshHandler {
             Some(v) => v,
             None => return Ok(false),
         };
+        if self.channel_limit_reached("direct-tcpip") {
+            return Ok(false);
+        }
         let host = host_to_connect.to_string();
 
         debug!(
@@ -741,6 +1510,7 @@ impl russh::server::Handler for SshHandler {
         let audit = self.ctx.audit.clone();
         let metrics = self.ctx.metrics.clone();
         let quota_tracker = self.ctx.quota_tracker.clone();
+        let flow_ctx = self.ctx.clone();
         let peer = self.peer_addr;
         let source_ip_str = peer.ip().to_string();
         let user_quotas = user.quotas.clone();
@@ -750,27 +1520,73 @@ impl russh::server::Handler for SshHandler {
         let upstream_proxy =
             crate::proxy::ProxyEngine::resolve_upstream_proxy(&user, &self.ctx.config);
 
+        // SSH hop (`ssh -J`): restricted to the user's jump targets, if any
+        let jump = self.ctx.config.security.jump_ports.contains(&port);
+        let jump_policy = jump
+            .then(|| crate::proxy::jump::JumpPolicy::for_target(&user, &host, port))
+            .flatten();
+
+        let ip_guard_enabled = self.listener.as_ref().and_then(|l| l.ip_guard_enabled);
         let conn_id = self.conn_id.clone();
         let relay_span = info_span!("ssh-relay", conn_id = %conn_id, user = %username, target = %format!("{}:{}", host, port));
+        let slot = self.forwarding_slot();
         tokio::spawn(
             async move {
+                let _slot = slot;
                 let start = Instant::now();
                 let relay_req = SshRelayRequest {
                     username: &username,
                     host: &host,
                     port,
                     channel,
-                    user_acl: &user.acl,
+                    user_acl: jump_policy.as_ref().map_or(&user.acl, |p| &p.acl),
+                    ip_guard_exemptions: jump_policy
+                        .as_ref()
+                        .map_or(&user.ip_guard_exemptions, |p| &p.ip_guard_exemptions),
+                    ip_guard_enabled,
+                    blocked_ports: user.blocked_ports.as_deref(),
                     source_ip: &source_ip_str,
+                    correlation_id: &conn_id,
                     bandwidth_limit_kbps: user.max_bandwidth_kbps,
                     max_per_user: user.max_connections,
                     aggregate_bandwidth_kbps: aggregate_bw,
                     quota_tracker: Some(quota_tracker),
                     quotas: user_quotas,
                     upstream_proxy,
+                    connect_retry: crate::proxy::retry::RetryPolicy::for_user(&user),
+                    jump,
+                    tags: &user.tags,
+                    group: user.group.as_deref(),
+                    payload_metadata: user.payload_metadata,
                 };
-                match proxy.connect_and_relay(relay_req).await {
-                    Ok((bytes_up, bytes_down, resolved_addr)) => {
+                let relay_result = proxy.connect_and_relay(relay_req).await;
+                let flow = flow_ctx.records_flows().then(|| {
+                    let mut flow = FlowRecord::closing(
+                        "ssh",
+                        &username,
+                        &source_ip_str,
+                        &host,
+                        port,
+                        start.elapsed(),
+                    );
+                    flow.correlation_id = Some(conn_id.clone());
+                    flow
+                });
+                match relay_result {
+                    Ok((outcome, resolved_addr)) => {
+                        let (bytes_up, bytes_down) = (outcome.bytes_up, outcome.bytes_down);
+                        if let Some(flow) = flow {
+                            flow_ctx.record_flow(
+                                FlowRecord {
+                                    dest_ip: Some(resolved_addr.ip().to_string()),
+                                    bytes_up,
+                                    bytes_down,
+                                    ..flow
+                                }
+                                .with_traffic(outcome.traffic.as_ref())
+                                .with_relay_close(outcome.reason),
+                            );
+                        }
                         let duration_ms = start.elapsed().as_millis() as u64;
                         info!(
                             conn_id = %conn_id,
@@ -780,26 +1596,28 @@ impl russh::server::Handler for SshHandler {
                             bytes_up = bytes_up,
                             bytes_down = bytes_down,
                             duration_ms = duration_ms,
+                            close_reason = %outcome.reason,
                             "Forwarding completed"
                         );
-                        audit
-                            .log_proxy_complete_cid(
-                                &username,
-                                &host,
-                                port,
-                                bytes_up,
-                                bytes_down,
-                                duration_ms,
-                                &peer,
-                                Some(resolved_addr.ip().to_string()),
-                                &conn_id,
-                            )
-                            .await;
+                        let event = AuditEvent::proxy_complete_with_cid(
+                            &username,
+                            &host,
+                            port,
+                            bytes_up,
+                            bytes_down,
+                            duration_ms,
+                            &peer,
+                            Some(resolved_addr.ip().to_string()),
+                            &conn_id,
+                        )
+                        .with_close_reason(outcome.reason);
+                        audit.log_event(proxy.label_session_close(event).await);
                         metrics.record_bytes_transferred(&username, bytes_up + bytes_down);
-                        metrics.record_typed_connection_duration(
+                        metrics.record_typed_connection_duration_cid(
                             &username,
                             "ssh",
                             duration_ms as f64 / 1000.0,
+                            &conn_id,
                         );
                     }
                     Err(e) => {
@@ -813,6 +1631,303 @@ impl russh::server::Handler for SshHandler {
                             "Forwarding failed"
                         );
                         metrics.record_error(error_type);
+                        if let Some(flow) = flow {
+                            flow_ctx.record_flow(FlowRecord {
+                                close_reason: error_type.to_string(),
+                                ..flow
+                            });
+                        }
+                    }
+                }
+            }
+            .instrument(relay_span),
+        );
+
+        Ok(true)
+    }
+
+    /// `ssh -L port:/path/to.sock`: forward to a Unix socket on this host,
+    /// limited to the user's `unix_sockets`.
+    #[cfg(unix)]
+    async fn channel_open_direct_streamlocal(
+        &mut self,
+        channel: russh::Channel<russh::server::Msg>,
+        socket_path: &str,
+        _session: &mut russh::server::Session,
+    ) -> Result<bool, Self::Error> {
+        let (user, username, _) = match self.validate_forwarding_request(socket_path, 0).await? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let source_ip_str = self.peer_addr.ip().to_string();
+        if !crate::proxy::streamlocal::is_allowed(&user.unix_sockets, socket_path) {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %username,
+                socket = %socket_path,
+                "Unix socket forwarding denied: path not in unix_sockets"
+            );
+            self.ctx.audit.log_acl_deny_cid(
+                &username,
+                socket_path,
+                0,
+                None,
+                &source_ip_str,
+                None,
+                "unix socket not allowed",
+                &self.conn_id,
+            );
+            self.ctx
+                .metrics
+                .record_error(crate::metrics::error_types::ACL_DENIED);
+            return Ok(false);
+        }
+
+        if self.channel_limit_reached("direct-streamlocal") {
+            return Ok(false);
+        }
+
+        debug!(
+            conn_id = %self.conn_id,
+            user = %username,
+            socket = %socket_path,
+            "direct-streamlocal channel open"
+        );
+
+        let proxy = self.ctx.proxy_engine.clone();
+        let audit = self.ctx.audit.clone();
+        let metrics = self.ctx.metrics.clone();
+        let quota_tracker = self.ctx.quota_tracker.clone();
+        let peer = self.peer_addr;
+        let socket_path = socket_path.to_string();
+        let conn_id = self.conn_id.clone();
+        let relay_span =
+            info_span!("ssh-relay", conn_id = %conn_id, user = %username, socket = %socket_path);
+        let slot = self.forwarding_slot();
+        tokio::spawn(
+            async move {
+                let _slot = slot;
+                let start = Instant::now();
+                let relay_req = crate::proxy::UnixRelayRequest {
+                    username: &username,
+                    socket_path: &socket_path,
+                    channel,
+                    source_ip: &source_ip_str,
+                    correlation_id: &conn_id,
+                    bandwidth_limit_kbps: user.max_bandwidth_kbps,
+                    max_per_user: user.max_connections,
+                    aggregate_bandwidth_kbps: user.max_aggregate_bandwidth_kbps,
+                    quota_tracker: Some(quota_tracker),
+                    quotas: user.quotas.clone(),
+                    tags: &user.tags,
+                };
+                match proxy.relay_unix(relay_req).await {
+                    Ok(outcome) => {
+                        let duration_ms = start.elapsed().as_millis() as u64;
+                        info!(
+                            conn_id = %conn_id,
+                            user = %username,
+                            socket = %socket_path,
+                            bytes_up = outcome.bytes_up,
+                            bytes_down = outcome.bytes_down,
+                            duration_ms = duration_ms,
+                            close_reason = %outcome.reason,
+                            "Unix socket forwarding completed"
+                        );
+                        let event = AuditEvent::proxy_complete_with_cid(
+                            &username,
+                            &socket_path,
+                            0,
+                            outcome.bytes_up,
+                            outcome.bytes_down,
+                            duration_ms,
+                            &peer,
+                            None,
+                            &conn_id,
+                        )
+                        .with_close_reason(outcome.reason);
+                        audit.log_event(proxy.label_session_close(event).await);
+                        metrics.record_bytes_transferred(
+                            &username,
+                            outcome.bytes_up + outcome.bytes_down,
+                        );
+                    }
+                    Err(e) => {
+                        let error_type = classify_relay_error(&e);
+                        warn!(
+                            conn_id = %conn_id,
+                            user = %username,
+                            socket = %socket_path,
+                            error = %e,
+                            error_type = %error_type,
+                            "Unix socket forwarding failed"
+                        );
+                        metrics.record_error(error_type);
+                    }
+                }
+            }
+            .instrument(relay_span),
+        );
+
+        Ok(true)
+    }
+
+    /// `ssh -w any -o Tunnel=point-to-point`: a layer-3 tunnel to a TUN
+    /// interface of its own, for users with `allow_vpn` when `[vpn]` is
+    /// enabled.
+    #[cfg(target_os = "linux")]
+    async fn channel_open_tun(
+        &mut self,
+        channel: russh::Channel<russh::server::Msg>,
+        mode: u32,
+        _unit: u32,
+        _session: &mut russh::server::Session,
+    ) -> Result<bool, Self::Error> {
+        use crate::proxy::tun;
+
+        let (user, username, _) = match self.validate_forwarding_request("tun", 0).await? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let Some(pool) = self.ctx.proxy_engine.vpn_pool() else {
+            warn!(conn_id = %self.conn_id, user = %username, "VPN tunnel refused: [vpn] is disabled");
+            return Ok(false);
+        };
+        if !user.allow_vpn {
+            warn!(conn_id = %self.conn_id, user = %username, "VPN tunnel denied by config");
+            return Ok(false);
+        }
+        if mode != tun::TUN_MODE_POINTOPOINT {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %username,
+                mode = mode,
+                "VPN tunnel refused: only point-to-point (layer 3) tunnels are supported"
+            );
+            return Ok(false);
+        }
+        if self.channel_limit_reached("tun") {
+            return Ok(false);
+        }
+        let Some(lease) = pool.lease(user.vpn_address) else {
+            warn!(
+                conn_id = %self.conn_id,
+                user = %username,
+                vpn_address = ?user.vpn_address,
+                "VPN tunnel refused: no free address in vpn.pool"
+            );
+            return Ok(false);
+        };
+        let client = lease.address();
+        let device = match tun::Device::open(pool.server_address(), client, self.ctx.config.vpn.mtu)
+        {
+            Ok(device) => device,
+            Err(e) => {
+                warn!(conn_id = %self.conn_id, user = %username, error = %e, "VPN tunnel refused: TUN interface not created");
+                return Ok(false);
+            }
+        };
+
+        info!(
+            conn_id = %self.conn_id,
+            user = %username,
+            interface = %device.name(),
+            server = %pool.server_address(),
+            client = %client,
+            "tun channel open"
+        );
+
+        let ip_guard_enabled = self
+            .listener
+            .as_ref()
+            .and_then(|l| l.ip_guard_enabled)
+            .unwrap_or(self.ctx.config.security.ip_guard_enabled);
+        let source_ip_str = self.peer_addr.ip().to_string();
+        let filter = tun::PacketFilter::new(
+            &username,
+            client,
+            user.acl.clone(),
+            ip_guard_enabled,
+            user.ip_guard_exemptions.clone(),
+            user.blocked_ports
+                .clone()
+                .unwrap_or_else(|| self.ctx.config.security.blocked_ports.clone()),
+            Some(self.ctx.audit.clone()),
+            &source_ip_str,
+            &self.conn_id,
+        );
+
+        let proxy = self.ctx.proxy_engine.clone();
+        let audit = self.ctx.audit.clone();
+        let metrics = self.ctx.metrics.clone();
+        let quota_tracker = self.ctx.quota_tracker.clone();
+        let peer = self.peer_addr;
+        let conn_id = self.conn_id.clone();
+        let relay_span =
+            info_span!("ssh-relay", conn_id = %conn_id, user = %username, tun = %client);
+        let slot = self.forwarding_slot();
+        tokio::spawn(
+            async move {
+                let _slot = slot;
+                let _lease = lease;
+                let start = Instant::now();
+                let relay_req = crate::proxy::TunRelayRequest {
+                    username: &username,
+                    channel,
+                    device,
+                    filter,
+                    source_ip: &source_ip_str,
+                    correlation_id: &conn_id,
+                    bandwidth_limit_kbps: user.max_bandwidth_kbps,
+                    max_per_user: user.max_connections,
+                    aggregate_bandwidth_kbps: user.max_aggregate_bandwidth_kbps,
+                    quota_tracker: Some(quota_tracker),
+                    quotas: user.quotas.clone(),
+                    tags: &user.tags,
+                };
+                let client = client.to_string();
+                match proxy.relay_tun(relay_req).await {
+                    Ok(outcome) => {
+                        let duration_ms = start.elapsed().as_millis() as u64;
+                        info!(
+                            conn_id = %conn_id,
+                            user = %username,
+                            client = %client,
+                            bytes_up = outcome.bytes_up,
+                            bytes_down = outcome.bytes_down,
+                            duration_ms = duration_ms,
+                            close_reason = %outcome.reason,
+                            "VPN tunnel completed"
+                        );
+                        let event = AuditEvent::proxy_complete_with_cid(
+                            &username,
+                            &client,
+                            0,
+                            outcome.bytes_up,
+                            outcome.bytes_down,
+                            duration_ms,
+                            &peer,
+                            None,
+                            &conn_id,
+                        )
+                        .with_close_reason(outcome.reason);
+                        audit.log_event(proxy.label_session_close(event).await);
+                        metrics.record_bytes_transferred(
+                            &username,
+                            outcome.bytes_up + outcome.bytes_down,
+                        );
+                    }
+                    Err(e) => {
+                        let error_type = classify_relay_error(&e);
+                        warn!(
+                            conn_id = %conn_id,
+                            user = %username,
+                            client = %client,
+                            error = %e,
+                            error_type = %error_type,
+                            "VPN tunnel failed"
+                        );
+                        metrics.record_error(error_type);
                     }
                 }
             }
@@ -835,7 +1950,22 @@ impl russh::server::Handler for SshHandler {
 
         if let Some(shell) = self.shells.get(&channel) {
             let mut shell = shell.lock().await;
-            shell.handle_input(data, session, channel).await?;
+            if shell.shell_allowed() {
+                shell.handle_input(data, session, channel).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: russh::ChannelId,
+        _session: &mut russh::server::Session,
+    ) -> Result<(), Self::Error> {
+        let shell = self.shells.remove(&channel);
+        let subsystem = self.subsystem_channels.remove(&channel);
+        if shell.is_some() || subsystem.is_some() {
+            self.sync_channel_count();
         }
         Ok(())
     }
@@ -852,6 +1982,15 @@ impl russh::server::Handler for SshHandler {
 
         if let Some(shell) = self.shells.get(&channel) {
             let mut shell = shell.lock().await;
+            if !shell.shell_allowed() {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = ?self.session_state.username,
+                    "Shell access denied by config"
+                );
+                let _ = session.channel_failure(channel);
+                return Ok(());
+            }
             // Send MOTD before the first prompt
             if let Some(motd) = shell.take_motd() {
                 let _ = session.data(channel, CryptoVec::from_slice(motd.as_bytes()));
@@ -862,10 +2001,12 @@ impl russh::server::Handler for SshHandler {
         Ok(())
     }
 
+    /// Allocate the PTY unless the user's terminal policy forbids it. The
+    /// client's TERM replaces `default_term`; the size is clamped.
     async fn pty_request(
         &mut self,
         channel: russh::ChannelId,
-        _term: &str,
+        term: &str,
         col_width: u32,
         row_height: u32,
         _pix_width: u32,
@@ -875,12 +2016,56 @@ impl russh::server::Handler for SshHandler {
     ) -> Result<(), Self::Error> {
         if let Some(shell) = self.shells.get(&channel) {
             let mut shell = shell.lock().await;
+            if !shell.terminal_policy().allow_pty {
+                debug!(
+                    conn_id = %self.conn_id,
+                    user = ?self.session_state.username,
+                    "PTY request denied by terminal policy"
+                );
+                let _ = session.channel_failure(channel);
+                return Ok(());
+            }
+            let term = shell.terminal_policy().term(term);
+            shell.set_env("TERM", &term);
             shell.set_terminal_size(col_width, row_height);
         }
         let _ = session.channel_success(channel);
         Ok(())
     }
 
+    /// Accept a client environment variable only if `terminal.accept_env`
+    /// lists it (`ssh -o SendEnv=...`).
+    async fn env_request(
+        &mut self,
+        channel: russh::ChannelId,
+        variable_name: &str,
+        variable_value: &str,
+        session: &mut russh::server::Session,
+    ) -> Result<(), Self::Error> {
+        let accepted = match self.shells.get(&channel) {
+            Some(shell) => {
+                let mut shell = shell.lock().await;
+                shell
+                    .terminal_policy()
+                    .accepts_env(variable_name, variable_value)
+                    && shell.set_env(variable_name, variable_value)
+            }
+            None => false,
+        };
+        if accepted {
+            let _ = session.channel_success(channel);
+        } else {
+            debug!(
+                conn_id = %self.conn_id,
+                user = ?self.session_state.username,
+                variable = %variable_name.escape_debug(),
+                "Environment variable refused by terminal policy"
+            );
+            let _ = session.channel_failure(channel);
+        }
+        Ok(())
+    }
+
     async fn window_change_request(
         &mut self,
         channel: russh::ChannelId,
@@ -911,9 +2096,22 @@ impl russh::server::Handler for SshHandler {
             return Ok(());
         }
 
+        if let Some(shell) = self.shells.get(&channel) {
+            if !shell.lock().await.shell_allowed() {
+                warn!(
+                    conn_id = %self.conn_id,
+                    user = ?self.session_state.username,
+                    "Exec denied by config (allow_shell = false)"
+                );
+                let _ = session.channel_failure(channel);
+                return Ok(());
+            }
+        }
+
         // M-12: Limit exec data size to prevent abuse
         if data.len() > 4096 {
             warn!(
+                conn_id = %self.conn_id,
                 user = ?self.session_state.username,
                 data_len = data.len(),
                 "exec_request data too large, rejecting"
@@ -923,7 +2121,15 @@ impl russh::server::Handler for SshHandler {
         }
 
         let command = String::from_utf8_lossy(data).to_string();
-        debug!(channel = ?channel, command = %command, "exec_request received");
+        debug!(conn_id = %self.conn_id, channel = ?channel, command = %command, "exec_request received");
+        if self.session_state.honeypot {
+            warn!(
+                conn_id = %self.conn_id,
+                ip = %self.peer_addr.ip(),
+                command = %command,
+                "Honeypot session exec"
+            );
+        }
 
         let username = match &self.session_state.username {
             Some(u) => u.clone(),
@@ -940,6 +2146,13 @@ impl russh::server::Handler for SshHandler {
         if !result.output.is_empty() {
             let _ = session.data(channel, CryptoVec::from_slice(result.output.as_bytes()));
         }
+        if result.passwd_requested {
+            // The prompts need an interactive terminal with masked input
+            let _ = session.data(
+                channel,
+                CryptoVec::from_slice(b"passwd: an interactive shell is required\r\n"),
+            );
+        }
 
         // Send exit status 0 and close channel
         let _ = session.exit_status_request(channel, 0);
@@ -947,21 +2160,134 @@ impl russh::server::Handler for SshHandler {
         Ok(())
     }
 
-    /// Reject SFTP/SCP subsystem requests explicitly.
+    /// Start a `[[subsystems]]` entry the user may open; SFTP/SCP and
+    /// unknown subsystems are refused.
     async fn subsystem_request(
         &mut self,
         channel: russh::ChannelId,
         name: &str,
         session: &mut russh::server::Session,
     ) -> Result<(), Self::Error> {
-        warn!(
+        let subsystem = self
+            .ctx
+            .config
+            .subsystems
+            .iter()
+            .find(|s| s.name == name)
+            .cloned();
+        let user = match &self.session_state.username {
+            Some(username) if self.session_state.authenticated && !self.session_state.honeypot => {
+                self.ctx
+                    .auth_service
+                    .read()
+                    .await
+                    .user_store()
+                    .get(username)
+                    .cloned()
+            }
+            _ => None,
+        };
+        let (subsystem, user) = match (subsystem, user) {
+            (Some(subsystem), Some(user))
+                if crate::ssh::subsystem::is_allowed(&user.subsystems, name) =>
+            {
+                (subsystem, user)
+            }
+            _ => {
+                warn!(
+                    conn_id = %self.conn_id,
+                    subsystem = %name.escape_debug(),
+                    user = ?self.session_state.username,
+                    ip = %self.peer_addr,
+                    "Subsystem denied (not registered or not allowed)"
+                );
+                let _ = session.channel_failure(channel);
+                return Ok(());
+            }
+        };
+
+        // The channel leaves the virtual shell for the subsystem
+        let shell = self
+            .shells
+            .remove(&channel)
+            .and_then(|(_, shell)| Arc::try_unwrap(shell).ok());
+        let Some(shell) = shell else {
+            self.sync_channel_count();
+            let _ = session.channel_failure(channel);
+            return Ok(());
+        };
+        let source_ip = self.peer_addr.ip().to_string();
+        let timeout = std::time::Duration::from_secs(self.ctx.config.limits.connection_timeout);
+        let backend = match crate::ssh::subsystem::open(
+            &subsystem,
+            user.fs_allow.as_deref(),
+            &user.username,
+            &source_ip,
+            timeout,
+        )
+        .await
+        {
+            Ok(backend) => backend,
+            Err(e) => {
+                warn!(
+                    conn_id = %self.conn_id,
+                    subsystem = %name,
+                    user = %user.username,
+                    error = %e,
+                    "Subsystem backend unavailable"
+                );
+                self.sync_channel_count();
+                let _ = session.channel_failure(channel);
+                return Ok(());
+            }
+        };
+        self.subsystem_channels.insert(channel, name.to_string());
+        self.sync_channel_count();
+        let _ = session.channel_success(channel);
+        info!(
             conn_id = %self.conn_id,
             subsystem = %name,
-            user = ?self.session_state.username,
-            ip = %self.peer_addr,
-            "Subsystem denied (SFTP/SCP not allowed)"
+            user = %user.username,
+            "Subsystem started"
         );
-        let _ = session.channel_failure(channel);
+
+        let stream = shell.into_inner().into_channel().into_stream();
+        let handle = session.handle();
+        let metrics = self.ctx.metrics.clone();
+        let conn_id = self.conn_id.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let exit_status = match crate::ssh::subsystem::relay(stream, backend).await {
+                Ok(outcome) => {
+                    info!(
+                        conn_id = %conn_id,
+                        subsystem = %name,
+                        user = %user.username,
+                        bytes_up = outcome.bytes_up,
+                        bytes_down = outcome.bytes_down,
+                        exit_status = outcome.exit_status,
+                        "Subsystem completed"
+                    );
+                    metrics.record_bytes_transferred(
+                        &user.username,
+                        outcome.bytes_up + outcome.bytes_down,
+                    );
+                    outcome.exit_status
+                }
+                Err(e) => {
+                    warn!(
+                        conn_id = %conn_id,
+                        subsystem = %name,
+                        user = %user.username,
+                        error = %e,
+                        "Subsystem relay failed"
+                    );
+                    255
+                }
+            };
+            let _ = handle.exit_status_request(channel, exit_status).await;
+            let _ = handle.close(channel).await;
+        });
         Ok(())
     }
 
@@ -0,0 +1,311 @@
+//! Passive observer for the cleartext part of the SSH handshake.
+//!
+//! The identification strings and the first `SSH_MSG_KEXINIT` of each side
+//! are sent in the clear (RFC 4253 sections 4.2 and 7.1). [`HandshakeTap`]
+//! copies those bytes as they cross the socket and, once both KEXINITs are
+//! seen, applies the RFC 4253 negotiation rules to record which algorithms
+//! the session uses. Later traffic is passed through untouched.
+//!
+//! The client KEXINIT also yields its [HASSH](https://github.com/salesforce/hassh)
+//! fingerprint, which identifies the client implementation rather than the
+//! user: the same credentials presented by a different SSH stack produce a
+//! different value.
+
+use serde::Serialize;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// `SSH_MSG_KEXINIT`
+const MSG_KEXINIT: u8 = 20;
+
+/// Give up if the handshake has not produced a KEXINIT within this many bytes.
+const MAX_OBSERVED_BYTES: usize = 64 * 1024;
+
+/// MAC reported for AEAD ciphers, which carry their own integrity tag.
+pub const IMPLICIT_MAC: &str = "<implicit>";
+
+/// Algorithm name-lists from one side's `SSH_MSG_KEXINIT`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KexInit {
+    pub kex: Vec<String>,
+    pub host_key: Vec<String>,
+    pub cipher_client_to_server: Vec<String>,
+    pub cipher_server_to_client: Vec<String>,
+    pub mac_client_to_server: Vec<String>,
+    pub mac_server_to_client: Vec<String>,
+    pub compression_client_to_server: Vec<String>,
+    pub compression_server_to_client: Vec<String>,
+}
+
+/// Algorithms agreed for the session. `None` means the two sides had no
+/// algorithm in common (the handshake fails in that case).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NegotiatedAlgorithms {
+    pub kex: Option<String>,
+    pub host_key: Option<String>,
+    pub cipher_client_to_server: Option<String>,
+    pub cipher_server_to_client: Option<String>,
+    pub mac_client_to_server: Option<String>,
+    pub mac_server_to_client: Option<String>,
+    pub compression_client_to_server: Option<String>,
+    pub compression_server_to_client: Option<String>,
+}
+
+/// What was learned from the handshake so far.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HandshakeInfo {
+    /// Client identification string, e.g. `SSH-2.0-OpenSSH_9.6`
+    pub client_version: Option<String>,
+    /// HASSH of the client KEXINIT (see [`hassh`])
+    pub hassh: Option<String>,
+    pub algorithms: Option<NegotiatedAlgorithms>,
+}
+
+/// Parse an `SSH_MSG_KEXINIT` payload (message byte included).
+pub fn parse_kexinit(payload: &[u8]) -> Option<KexInit> {
+    if payload.first() != Some(&MSG_KEXINIT) {
+        return None;
+    }
+    // Message byte + 16-byte cookie, then ten name-lists
+    let mut rest = payload.get(17..)?;
+    let mut lists = Vec::with_capacity(10);
+    for _ in 0..10 {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let raw = rest.get(4..4 + len)?;
+        let text = std::str::from_utf8(raw).ok()?;
+        lists.push(
+            text.split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        );
+        rest = &rest[4 + len..];
+    }
+    let mut lists = lists.into_iter();
+    let mut next = || lists.next().unwrap_or_default();
+    Some(KexInit {
+        kex: next(),
+        host_key: next(),
+        cipher_client_to_server: next(),
+        cipher_server_to_client: next(),
+        mac_client_to_server: next(),
+        mac_server_to_client: next(),
+        compression_client_to_server: next(),
+        compression_server_to_client: next(),
+    })
+}
+
+/// HASSH fingerprint of a client KEXINIT: MD5 (lowercase hex) of its
+/// key exchange, client-to-server cipher, MAC and compression lists,
+/// `;`-separated.
+pub fn hassh(client: &KexInit) -> String {
+    let algorithms = [
+        &client.kex,
+        &client.cipher_client_to_server,
+        &client.mac_client_to_server,
+        &client.compression_client_to_server,
+    ]
+    .map(|list| list.join(","))
+    .join(";");
+    format!("{:x}", md5::compute(algorithms))
+}
+
+/// Whether `value` looks like a HASSH (32 hex digits).
+pub fn is_valid_hassh(value: &str) -> bool {
+    value.len() == 32 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// First client algorithm the server also supports (RFC 4253 section 7.1).
+fn pick(client: &[String], server: &[String]) -> Option<String> {
+    client.iter().find(|a| server.contains(a)).cloned()
+}
+
+fn is_aead(cipher: &Option<String>) -> bool {
+    cipher
+        .as_deref()
+        .is_some_and(|c| c == "chacha20-poly1305@openssh.com" || c.ends_with("-gcm@openssh.com"))
+}
+
+/// Apply the RFC 4253 negotiation rules to both KEXINITs.
+pub fn negotiate(client: &KexInit, server: &KexInit) -> NegotiatedAlgorithms {
+    let cipher_c2s = pick(
+        &client.cipher_client_to_server,
+        &server.cipher_client_to_server,
+    );
+    let cipher_s2c = pick(
+        &client.cipher_server_to_client,
+        &server.cipher_server_to_client,
+    );
+    let mac = |cipher: &Option<String>, c: &[String], s: &[String]| {
+        if is_aead(cipher) {
+            Some(IMPLICIT_MAC.to_string())
+        } else {
+            pick(c, s)
+        }
+    };
+    NegotiatedAlgorithms {
+        kex: pick(&client.kex, &server.kex),
+        host_key: pick(&client.host_key, &server.host_key),
+        mac_client_to_server: mac(
+            &cipher_c2s,
+            &client.mac_client_to_server,
+            &server.mac_client_to_server,
+        ),
+        mac_server_to_client: mac(
+            &cipher_s2c,
+            &client.mac_server_to_client,
+            &server.mac_server_to_client,
+        ),
+        cipher_client_to_server: cipher_c2s,
+        cipher_server_to_client: cipher_s2c,
+        compression_client_to_server: pick(
+            &client.compression_client_to_server,
+            &server.compression_client_to_server,
+        ),
+        compression_server_to_client: pick(
+            &client.compression_server_to_client,
+            &server.compression_server_to_client,
+        ),
+    }
+}
+
+/// Reassembles one direction of the stream up to its first KEXINIT.
+#[derive(Default)]
+struct Direction {
+    buf: Vec<u8>,
+    version: Option<String>,
+    kexinit: Option<KexInit>,
+    failed: bool,
+}
+
+impl Direction {
+    fn done(&self) -> bool {
+        self.kexinit.is_some() || self.failed
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        if self.done() {
+            return;
+        }
+        self.buf.extend_from_slice(data);
+        if self.buf.len() > MAX_OBSERVED_BYTES {
+            self.failed = true;
+            self.buf = Vec::new();
+            return;
+        }
+        // Identification string, possibly preceded by other lines
+        while self.version.is_none() {
+            let Some(end) = self.buf.iter().position(|&b| b == b'\n') else {
+                return;
+            };
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            if line.starts_with(b"SSH-") {
+                let text = String::from_utf8_lossy(&line);
+                self.version = Some(text.trim_end().to_string());
+            }
+        }
+        // First binary packet: uint32 length, byte padding, payload
+        let Some(header) = self.buf.get(..5) else {
+            return;
+        };
+        let packet_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let padding = header[4] as usize;
+        if packet_len < padding + 1 || packet_len > MAX_OBSERVED_BYTES {
+            self.failed = true;
+            return;
+        }
+        let Some(payload) = self.buf.get(5..4 + packet_len - padding) else {
+            return;
+        };
+        match parse_kexinit(payload) {
+            Some(kexinit) => self.kexinit = Some(kexinit),
+            None => self.failed = true,
+        }
+        self.buf = Vec::new();
+    }
+}
+
+/// Stream wrapper that records the client version and negotiated
+/// algorithms into a shared [`HandshakeInfo`].
+pub struct HandshakeTap<S> {
+    inner: S,
+    client: Direction,
+    server: Direction,
+    info: Arc<Mutex<HandshakeInfo>>,
+    observing: bool,
+}
+
+impl<S> HandshakeTap<S> {
+    pub fn new(inner: S, info: Arc<Mutex<HandshakeInfo>>) -> Self {
+        Self {
+            inner,
+            client: Direction::default(),
+            server: Direction::default(),
+            info,
+            observing: true,
+        }
+    }
+
+    fn publish(&mut self) {
+        let mut info = self.info.lock().unwrap_or_else(|e| e.into_inner());
+        if info.client_version.is_none() {
+            info.client_version = self.client.version.clone();
+        }
+        if info.hassh.is_none() {
+            info.hassh = self.client.kexinit.as_ref().map(hassh);
+        }
+        if !(self.client.done() && self.server.done()) {
+            return;
+        }
+        if let (Some(c), Some(s)) = (&self.client.kexinit, &self.server.kexinit) {
+            info.algorithms = Some(negotiate(c, s));
+        }
+        self.observing = false;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HandshakeTap<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if self.observing && matches!(poll, Poll::Ready(Ok(()))) {
+            let this = &mut *self;
+            this.client.feed(&buf.filled()[before..]);
+            this.publish();
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HandshakeTap<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if self.observing {
+            if let Poll::Ready(Ok(n)) = poll {
+                let this = &mut *self;
+                this.server.feed(&buf[..n]);
+                this.publish();
+            }
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,233 @@
+//! Host keys served by the SSH listeners, and their rotation
+//! (`[server.host_key_rotation]`).
+//!
+//! During rotation both keys are offered, the current one first. russh signs
+//! with the first key of the negotiated algorithm and OpenSSH clients prefer
+//! algorithms they already hold in known_hosts, so existing clients keep
+//! verifying the key they know. The old key is dropped `retire_after_days`
+//! after the new key file was written, without a restart: listeners pick up
+//! the new key set on their next accept.
+//!
+//! Every served key is announced to authenticated clients through OpenSSH's
+//! UpdateHostKeys (`hostkeys-00@openssh.com`, see `vendor/PATCHES.md`):
+//! clients that connect during the overlap add the new key to known_hosts,
+//! and drop the old one once it is no longer announced. `GET /api/host-keys`
+//! lists both keys for clients that do not take part.
+
+use crate::config::types::ServerConfig;
+use crate::ssh::keys;
+use anyhow::Result;
+use russh::keys::{PrivateKey, PublicKeyBase64};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// The current host key and, during rotation, its successor.
+pub struct HostKeyRing {
+    keys: RwLock<Keys>,
+}
+
+struct Keys {
+    current: HostKey,
+    new: Option<HostKey>,
+    retire_after: Duration,
+    /// Bumped whenever a reload changes the keys
+    generation: u64,
+}
+
+struct HostKey {
+    key: PrivateKey,
+    path: PathBuf,
+    /// Modification time of the key file; rotation counts from here
+    added: SystemTime,
+}
+
+/// One entry of `GET /api/host-keys`.
+#[derive(Debug, Serialize)]
+pub struct HostKeyInfo {
+    /// `current` (`host_key_path`) or `new` (`host_key_rotation.new_key_path`)
+    pub role: &'static str,
+    pub path: String,
+    pub algorithm: String,
+    pub fingerprint: String,
+    /// `<algorithm> <base64>`: prefix with the host name for a known_hosts line
+    pub public_key: String,
+    /// Offered to clients right now
+    pub served: bool,
+}
+
+/// Response of `GET /api/host-keys`.
+#[derive(Debug, Serialize)]
+pub struct HostKeyStatus {
+    pub keys: Vec<HostKeyInfo>,
+    /// When the current key stops being served (Unix seconds), during rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retire_at: Option<u64>,
+    pub retired: bool,
+}
+
+impl HostKeyRing {
+    /// Load (or generate) `host_key_path` and the rotation key, if any.
+    pub fn load(server: &ServerConfig) -> Result<Self> {
+        let keys = Keys::load(server, 0)?;
+        keys.log_rotation();
+        Ok(Self {
+            keys: RwLock::new(keys),
+        })
+    }
+
+    /// Re-read the key files after a config reload. Listeners switch to the
+    /// new set on their next accept; established sessions are unaffected.
+    pub fn reload(&self, server: &ServerConfig) -> Result<()> {
+        let mut keys = self.keys.write().unwrap_or_else(|e| e.into_inner());
+        let loaded = Keys::load(server, keys.generation)?;
+        if !loaded.same_as(&keys) {
+            let generation = keys.generation + 1;
+            *keys = Keys {
+                generation,
+                ..loaded
+            };
+            keys.log_rotation();
+        }
+        Ok(())
+    }
+
+    /// Keys to offer at `now`, plus a version that changes whenever the
+    /// set does (reload or retirement of the old key).
+    pub fn served(&self, now: SystemTime) -> (u64, Vec<PrivateKey>) {
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        (keys.version(now), keys.served(now))
+    }
+
+    /// The served keys, if they changed since version `seen` (updated).
+    pub fn served_if_changed(&self, seen: &mut u64, now: SystemTime) -> Option<Vec<PrivateKey>> {
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let version = keys.version(now);
+        if version == *seen {
+            return None;
+        }
+        *seen = version;
+        Some(keys.served(now))
+    }
+
+    pub fn status(&self, now: SystemTime) -> HostKeyStatus {
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let retired = keys.retired(now);
+        let mut list = vec![key_info("current", &keys.current, !retired)];
+        if let Some(new) = &keys.new {
+            list.push(key_info("new", new, true));
+        }
+        HostKeyStatus {
+            keys: list,
+            retire_at: keys
+                .retire_at()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            retired,
+        }
+    }
+}
+
+impl Keys {
+    fn load(server: &ServerConfig, generation: u64) -> Result<Self> {
+        let rotation = &server.host_key_rotation;
+        Ok(Self {
+            current: HostKey::load(server.host_key_path.clone())?,
+            new: rotation
+                .new_key_path
+                .clone()
+                .map(HostKey::load)
+                .transpose()?,
+            retire_after: Duration::from_secs(rotation.retire_after_days.saturating_mul(86_400)),
+            generation,
+        })
+    }
+
+    fn same_as(&self, other: &Keys) -> bool {
+        let same_key = |a: &HostKey, b: &HostKey| {
+            a.key.public_key() == b.key.public_key() && a.added == b.added
+        };
+        let same_new = match (&self.new, &other.new) {
+            (Some(a), Some(b)) => same_key(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_key(&self.current, &other.current)
+            && same_new
+            && self.retire_after == other.retire_after
+    }
+
+    fn retire_at(&self) -> Option<SystemTime> {
+        self.new.as_ref().map(|new| new.added + self.retire_after)
+    }
+
+    fn retired(&self, now: SystemTime) -> bool {
+        self.retire_at().is_some_and(|at| now >= at)
+    }
+
+    fn version(&self, now: SystemTime) -> u64 {
+        self.generation * 2 + u64::from(self.retired(now))
+    }
+
+    fn served(&self, now: SystemTime) -> Vec<PrivateKey> {
+        match &self.new {
+            Some(new) if self.retired(now) => vec![new.key.clone()],
+            Some(new) => vec![self.current.key.clone(), new.key.clone()],
+            None => vec![self.current.key.clone()],
+        }
+    }
+
+    fn log_rotation(&self) {
+        let Some(new) = &self.new else {
+            return;
+        };
+        let retire_at = self.retire_at().map(rfc3339).unwrap_or_default();
+        info!(
+            current = %self.current.path.display(),
+            new = %new.path.display(),
+            new_fingerprint = %fingerprint(&new.key),
+            retire_at = %retire_at,
+            "Host key rotation in progress"
+        );
+    }
+}
+
+impl HostKey {
+    fn load(path: PathBuf) -> Result<Self> {
+        let key = keys::load_or_generate_host_key(&path)?;
+        let added = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        Ok(Self { key, path, added })
+    }
+}
+
+fn key_info(role: &'static str, key: &HostKey, served: bool) -> HostKeyInfo {
+    let public = key.key.public_key();
+    let algorithm = public.algorithm().as_str().to_string();
+    HostKeyInfo {
+        role,
+        path: key.path.display().to_string(),
+        public_key: format!("{} {}", algorithm, public.public_key_base64()),
+        algorithm,
+        fingerprint: fingerprint(&key.key),
+        served,
+    }
+}
+
+/// OpenSSH-style `SHA256:<base64>` fingerprint of the public half.
+pub fn fingerprint(key: &PrivateKey) -> String {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+    let hash = Sha256::digest(key.public_key().public_key_bytes());
+    format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash)
+    )
+}
+
+fn rfc3339(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()
+}
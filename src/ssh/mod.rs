@@ -1,3 +1,9 @@
 pub mod handler;
+pub mod handshake;
+pub mod host_keys;
 pub mod keys;
+pub mod pre_auth;
+pub mod reject;
 pub mod session;
+pub mod subsystem;
+pub mod terminal;
@@ -0,0 +1,293 @@
+//! Pre-authentication limits for SSH connections (slowloris protection).
+//!
+//! Every accepted socket holds a [`PreAuthSlot`] until the client
+//! authenticates or disconnects, so `limits.max_unauthenticated_connections`
+//! and `limits.max_unauthenticated_per_ip` bound how many half-open SSH
+//! handshakes can be parked on the server. Once the global limit is reached,
+//! `limits.overload_policy` decides what gives way: the new connection
+//! (`reject-new`) or the longest-waiting unauthenticated one
+//! (`drop-oldest-preauth`), whose [`PreAuthSlot::shed_signal`] fires so its
+//! session closes. [`PreAuthDeadline`] wraps the socket and fails reads once
+//! `server.ssh_auth_timeout` elapses without a successful login, whatever
+//! stage the handshake is stuck in.
+
+use crate::config::types::OverloadPolicy;
+use crate::metrics::collectors::ReasonLabel;
+use crate::metrics::{shed_reasons, MetricsRegistry};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+use tokio_util::sync::CancellationToken;
+
+/// Why a connection was refused a pre-auth slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreAuthRejection {
+    /// `max_unauthenticated_connections` reached
+    Global,
+    /// `max_unauthenticated_per_ip` reached for this source
+    PerIp,
+}
+
+/// An unauthenticated connection, in [`Counts::pending`].
+struct Pending {
+    ip: IpAddr,
+    shed: CancellationToken,
+}
+
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+    /// Held slots by acquisition order, oldest first
+    pending: BTreeMap<u64, Pending>,
+    next_id: u64,
+}
+
+impl Counts {
+    /// Forget slot `id`; false if it was already released or shed.
+    fn remove(&mut self, id: u64) -> bool {
+        let Some(pending) = self.pending.remove(&id) else {
+            return false;
+        };
+        if let Some(n) = self.per_ip.get_mut(&pending.ip) {
+            *n -= 1;
+            if *n == 0 {
+                self.per_ip.remove(&pending.ip);
+            }
+        }
+        self.total = self.total.saturating_sub(1);
+        true
+    }
+}
+
+/// Counts unauthenticated SSH connections, globally and per source IP.
+pub struct PreAuthLimiter {
+    /// 0 = unlimited
+    max_total: usize,
+    /// 0 = unlimited
+    max_per_ip: usize,
+    policy: OverloadPolicy,
+    counts: Mutex<Counts>,
+    in_flight: Gauge,
+    shed: Family<ReasonLabel, Counter>,
+}
+
+impl PreAuthLimiter {
+    pub fn new(max_total: usize, max_per_ip: usize) -> Self {
+        Self {
+            max_total,
+            max_per_ip,
+            policy: OverloadPolicy::default(),
+            counts: Mutex::new(Counts::default()),
+            in_flight: Gauge::default(),
+            shed: Family::default(),
+        }
+    }
+
+    /// What gives way once `max_total` is reached.
+    pub fn with_policy(mut self, policy: OverloadPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Report to the `s5_ssh_unauthenticated_connections` gauge and
+    /// `s5_connections_shed_total`.
+    pub fn with_metrics(mut self, metrics: &MetricsRegistry) -> Self {
+        self.in_flight = metrics.ssh_unauthenticated_connections.clone();
+        self.shed = metrics.connections_shed_total.clone();
+        self
+    }
+
+    /// Reserve a slot for a new connection from `ip`. At the global limit,
+    /// `drop-oldest-preauth` sheds the longest-waiting connection instead of
+    /// refusing this one; the per-IP limit always refuses.
+    pub fn try_acquire(self: &Arc<Self>, ip: IpAddr) -> Result<PreAuthSlot, PreAuthRejection> {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let full = self.max_total > 0 && counts.total >= self.max_total;
+        if full && self.policy == OverloadPolicy::RejectNew {
+            self.record_shed(shed_reasons::PRE_AUTH_FULL);
+            return Err(PreAuthRejection::Global);
+        }
+        let for_ip = counts.per_ip.get(&ip).copied().unwrap_or(0);
+        if self.max_per_ip > 0 && for_ip >= self.max_per_ip {
+            return Err(PreAuthRejection::PerIp);
+        }
+        if full {
+            let Some((&oldest, _)) = counts.pending.first_key_value() else {
+                return Err(PreAuthRejection::Global);
+            };
+            let shed = counts.pending[&oldest].shed.clone();
+            counts.remove(oldest);
+            self.in_flight.dec();
+            shed.cancel();
+            self.record_shed(shed_reasons::OLDEST_PRE_AUTH);
+        }
+
+        let id = counts.next_id;
+        counts.next_id += 1;
+        let shed = CancellationToken::new();
+        counts.pending.insert(
+            id,
+            Pending {
+                ip,
+                shed: shed.clone(),
+            },
+        );
+        *counts.per_ip.entry(ip).or_insert(0) += 1;
+        counts.total += 1;
+        self.in_flight.inc();
+        Ok(PreAuthSlot {
+            limiter: Some(self.clone()),
+            id,
+            authenticated: Arc::new(AtomicBool::new(false)),
+            shed,
+        })
+    }
+
+    /// Unauthenticated connections currently held.
+    pub fn in_flight(&self) -> usize {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner()).total
+    }
+
+    /// Unauthenticated connections currently held for `ip`.
+    pub fn in_flight_for(&self, ip: &IpAddr) -> usize {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .per_ip
+            .get(ip)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn release(&self, id: u64) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        // A shed slot was already given back
+        if counts.remove(id) {
+            self.in_flight.dec();
+        }
+    }
+
+    fn record_shed(&self, reason: &str) {
+        self.shed
+            .get_or_create(&ReasonLabel {
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+}
+
+/// A connection's hold on the pre-auth limits. The slot is given back when
+/// the client authenticates ([`PreAuthSlot::authenticated`]) or on drop.
+pub struct PreAuthSlot {
+    limiter: Option<Arc<PreAuthLimiter>>,
+    id: u64,
+    authenticated: Arc<AtomicBool>,
+    shed: CancellationToken,
+}
+
+impl PreAuthSlot {
+    /// Flag shared with [`PreAuthDeadline`]; set once the client logs in.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.authenticated.clone()
+    }
+
+    /// Cancelled when the connection is shed to admit a newer one; the
+    /// session should close. Never fires once authenticated.
+    pub fn shed_signal(&self) -> CancellationToken {
+        self.shed.clone()
+    }
+
+    /// Mark the connection as authenticated: lifts the deadline and frees
+    /// the slot for other clients.
+    pub fn authenticated(&mut self) {
+        self.authenticated.store(true, Ordering::Release);
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release(self.id);
+        }
+    }
+}
+
+impl Drop for PreAuthSlot {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release(self.id);
+        }
+    }
+}
+
+/// Stream wrapper that fails reads with [`io::ErrorKind::TimedOut`] once the
+/// deadline passes and the shared flag is still unset.
+pub struct PreAuthDeadline<S> {
+    inner: S,
+    deadline: Pin<Box<Sleep>>,
+    authenticated: Arc<AtomicBool>,
+}
+
+impl<S> PreAuthDeadline<S> {
+    pub fn new(inner: S, timeout: Duration, authenticated: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+            authenticated,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for PreAuthDeadline<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.authenticated.load(Ordering::Acquire) && self.deadline.as_mut().poll(cx).is_ready()
+        {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "SSH authentication not completed in time",
+            )));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for PreAuthDeadline<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
@@ -0,0 +1,62 @@
+//! Refusing an SSH client before the handshake.
+//!
+//! A client turned away at accept time (`server.max_connections`) still gets
+//! the server identification and an `SSH_MSG_DISCONNECT` with a reason code,
+//! so `ssh` prints "Received disconnect ... Too many connections" instead of
+//! a bare "Connection closed". Before key exchange, packets are unencrypted
+//! and carry no MAC (RFC 4253 §6), so the packet is built by hand.
+
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SSH_MSG_DISCONNECT: u8 = 1;
+
+/// `SSH_DISCONNECT_TOO_MANY_CONNECTIONS` (RFC 4253 §11.1)
+pub const DISCONNECT_TOO_MANY_CONNECTIONS: u32 = 12;
+
+/// How long to drain the client's data after refusing, so the socket closes
+/// cleanly instead of with a reset that could discard the message.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An unencrypted `SSH_MSG_DISCONNECT` binary packet.
+pub fn disconnect_packet(reason: u32, description: &str) -> Vec<u8> {
+    let mut payload = vec![SSH_MSG_DISCONNECT];
+    payload.extend_from_slice(&reason.to_be_bytes());
+    payload.extend_from_slice(&(description.len() as u32).to_be_bytes());
+    payload.extend_from_slice(description.as_bytes());
+    // Empty language tag
+    payload.extend_from_slice(&0u32.to_be_bytes());
+
+    // packet_length + padding_length + payload + padding: a multiple of 8,
+    // with at least 4 bytes of padding
+    let unpadded = 4 + 1 + payload.len();
+    let mut padding = 8 - unpadded % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+    let packet_length = (1 + payload.len() + padding) as u32;
+
+    let mut packet = Vec::with_capacity(4 + packet_length as usize);
+    packet.extend_from_slice(&packet_length.to_be_bytes());
+    packet.push(padding as u8);
+    packet.extend_from_slice(&payload);
+    packet.resize(packet.len() + padding, 0);
+    packet
+}
+
+/// Send the identification line and a disconnect message, then close.
+pub async fn refuse<S>(mut stream: S, server_id: &str, reason: u32, description: &str)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut out = format!("{server_id}\r\n").into_bytes();
+    out.extend_from_slice(&disconnect_packet(reason, description));
+    if stream.write_all(&out).await.is_err() || stream.shutdown().await.is_err() {
+        return;
+    }
+    let mut sink = [0u8; 1024];
+    let _ = tokio::time::timeout(DRAIN_TIMEOUT, async {
+        while matches!(stream.read(&mut sink).await, Ok(n) if n > 0) {}
+    })
+    .await;
+}
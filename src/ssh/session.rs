@@ -5,6 +5,8 @@ pub struct ClientSession {
     pub authenticated: bool,
     pub auth_method: String,
     pub ssh_key_fingerprint: Option<String>,
+    /// Logged in with a decoy credential: sandboxed shell, no forwarding
+    pub honeypot: bool,
 }
 
 impl ClientSession {
@@ -0,0 +1,217 @@
+//! Custom SSH subsystems (`[[subsystems]]`, `ssh -s host netconf`).
+//!
+//! A subsystem takes over a session channel: its bytes are relayed to a
+//! command started for the channel (stdin/stdout) or to a Unix socket, until
+//! the backend closes its side. Users open only the subsystems listed in
+//! their (or their group's) `subsystems`. SFTP cannot be registered: sessions
+//! have no host filesystem to serve.
+
+use crate::config::types::SubsystemConfig;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+/// How long a command may keep running once it closed its stdout.
+const EXIT_GRACE: Duration = Duration::from_secs(5);
+
+/// Open backend of a subsystem channel.
+pub enum Backend {
+    Command(tokio::process::Child),
+    #[cfg(unix)]
+    Socket(tokio::net::UnixStream),
+}
+
+/// Bytes relayed and the exit status reported to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub exit_status: u32,
+}
+
+/// Check a `[[subsystems]]` entry. Returns a description of the problem.
+pub fn validate(subsystem: &SubsystemConfig) -> Result<(), String> {
+    let name = &subsystem.name;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_graphic()) {
+        return Err("name must be non-empty printable ASCII without spaces".to_string());
+    }
+    if name == "sftp" {
+        return Err("SFTP is not supported (sessions have no host filesystem)".to_string());
+    }
+    match (&subsystem.command, &subsystem.socket) {
+        (Some(command), None) => match command.first() {
+            Some(program) if Path::new(program).is_absolute() => Ok(()),
+            _ => Err("command must start with an absolute program path".to_string()),
+        },
+        (None, Some(socket)) if Path::new(socket).is_absolute() => Ok(()),
+        (None, Some(_)) => Err("socket must be an absolute path".to_string()),
+        _ => Err("exactly one of command or socket must be set".to_string()),
+    }
+}
+
+/// Whether `name` is listed in the user's `allowed` subsystems.
+pub fn is_allowed(allowed: &[String], name: &str) -> bool {
+    allowed.iter().any(|a| a == name)
+}
+
+/// Start the command or connect the socket of `subsystem`. A command gets a
+/// clean environment with only `S5_USER` and `S5_SOURCE_IP`, and with
+/// `fs_allow` (the user's group's) runs under Landlock: it is not started
+/// when the confinement cannot be applied.
+pub async fn open(
+    subsystem: &SubsystemConfig,
+    fs_allow: Option<&[String]>,
+    username: &str,
+    source_ip: &str,
+    timeout: Duration,
+) -> std::io::Result<Backend> {
+    if let Some(command) = &subsystem.command {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| std::io::Error::other("empty subsystem command"))?;
+        let confinement = fs_allow
+            .map(crate::sandbox::FsConfinement::new)
+            .transpose()?;
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args)
+            .env_clear()
+            .env("S5_USER", username)
+            .env("S5_SOURCE_IP", source_ip)
+            .current_dir("/")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(target_os = "linux")]
+        if let Some(confinement) = confinement {
+            // SAFETY: `restrict_self` only makes system calls
+            unsafe {
+                cmd.pre_exec(move || confinement.restrict_self());
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = confinement;
+        let mut child = cmd.spawn()?;
+        if let Some(stderr) = child.stderr.take() {
+            let name = subsystem.name.clone();
+            tokio::spawn(log_stderr(name, stderr));
+        }
+        return Ok(Backend::Command(child));
+    }
+    #[cfg(unix)]
+    if let Some(socket) = &subsystem.socket {
+        let stream = tokio::time::timeout(timeout, tokio::net::UnixStream::connect(socket))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        return Ok(Backend::Socket(stream));
+    }
+    let _ = timeout;
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Relay `client` (the SSH channel) to `backend` until the backend closes
+/// its output. EOF from the client is passed on as a half-close, so a
+/// command still writes its last reply.
+pub async fn relay<S>(client: S, backend: Backend) -> std::io::Result<Outcome>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (client_rx, client_tx) = tokio::io::split(client);
+    match backend {
+        Backend::Command(mut child) => {
+            let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+                return Err(std::io::Error::other("subsystem command without stdio"));
+            };
+            let (bytes_up, bytes_down) = pump(client_rx, stdin, stdout, client_tx).await?;
+            let status = match tokio::time::timeout(EXIT_GRACE, child.wait()).await {
+                Ok(status) => status?.code().map_or(255, |code| code as u32),
+                Err(_) => {
+                    let _ = child.kill().await;
+                    255
+                }
+            };
+            Ok(Outcome {
+                bytes_up,
+                bytes_down,
+                exit_status: status,
+            })
+        }
+        #[cfg(unix)]
+        Backend::Socket(stream) => {
+            let (socket_rx, socket_tx) = stream.into_split();
+            let (bytes_up, bytes_down) = pump(client_rx, socket_tx, socket_rx, client_tx).await?;
+            Ok(Outcome {
+                bytes_up,
+                bytes_down,
+                exit_status: 0,
+            })
+        }
+    }
+}
+
+/// Copy both ways; done when the backend's output ends.
+async fn pump<CR, BW, BR, CW>(
+    client_rx: CR,
+    backend_tx: BW,
+    backend_rx: BR,
+    client_tx: CW,
+) -> std::io::Result<(u64, u64)>
+where
+    CR: AsyncRead + Unpin,
+    BW: AsyncWrite + Unpin,
+    BR: AsyncRead + Unpin,
+    CW: AsyncWrite + Unpin,
+{
+    let up = AtomicU64::new(0);
+    let down = AtomicU64::new(0);
+    {
+        let upstream = copy_counted(client_rx, backend_tx, &up);
+        let downstream = copy_counted(backend_rx, client_tx, &down);
+        tokio::pin!(upstream, downstream);
+        let mut upstream_done = false;
+        loop {
+            tokio::select! {
+                result = &mut upstream, if !upstream_done => {
+                    // A backend that stopped reading may still answer
+                    if let Err(e) = result {
+                        debug!(error = %e, "Subsystem input closed");
+                    }
+                    upstream_done = true;
+                }
+                result = &mut downstream => {
+                    result?;
+                    break;
+                }
+            }
+        }
+    }
+    Ok((up.into_inner(), down.into_inner()))
+}
+
+/// Copy until EOF, then shut `writer` down (half-close).
+async fn copy_counted<R, W>(mut reader: R, mut writer: W, count: &AtomicU64) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return writer.shutdown().await;
+        }
+        writer.write_all(&buf[..n]).await?;
+        count.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+async fn log_stderr(name: String, stderr: tokio::process::ChildStderr) {
+    use tokio::io::AsyncBufReadExt;
+    let mut lines = tokio::io::BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!(subsystem = %name, line = %line.escape_debug(), "Subsystem stderr");
+    }
+}
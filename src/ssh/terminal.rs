@@ -0,0 +1,143 @@
+//! Session environment and PTY policy (`terminal` on a group or user).
+//!
+//! Clients may only set the environment variables matched by `accept_env`;
+//! everything else is refused, like OpenSSH's `AcceptEnv`. `TERM` comes from
+//! the PTY request, or `default_term` without one. Window sizes beyond
+//! `max_cols`/`max_rows` are clamped rather than refused, since a resize
+//! cannot be answered with a failure.
+
+use crate::config::types::TerminalConfig;
+
+/// TERM when neither the client nor the config names a terminal.
+pub const DEFAULT_TERM: &str = "xterm-256color";
+
+/// Most variables a client may set on one channel.
+pub const MAX_ENV_VARS: usize = 32;
+
+/// Longest accepted variable value, in bytes.
+pub const MAX_ENV_VALUE_LEN: usize = 1024;
+
+/// Longest accepted TERM name, in bytes.
+const MAX_TERM_LEN: usize = 64;
+
+/// Resolved terminal policy of a user (user > group > defaults).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalPolicy {
+    pub accept_env: Vec<String>,
+    pub default_term: String,
+    pub allow_pty: bool,
+    /// 0 = no limit
+    pub max_cols: u32,
+    /// 0 = no limit
+    pub max_rows: u32,
+}
+
+impl Default for TerminalPolicy {
+    fn default() -> Self {
+        Self {
+            accept_env: Vec::new(),
+            default_term: DEFAULT_TERM.to_string(),
+            allow_pty: true,
+            max_cols: 0,
+            max_rows: 0,
+        }
+    }
+}
+
+impl TerminalPolicy {
+    /// Resolve the user's `terminal` over the group's, field by field.
+    pub fn resolve(user: Option<&TerminalConfig>, group: Option<&TerminalConfig>) -> Self {
+        let merged = match (user, group) {
+            (Some(user), Some(group)) => user.overlay_on(group),
+            (Some(cfg), None) | (None, Some(cfg)) => cfg.clone(),
+            (None, None) => TerminalConfig::default(),
+        };
+        let defaults = Self::default();
+        Self {
+            accept_env: merged.accept_env.unwrap_or(defaults.accept_env),
+            default_term: merged.default_term.unwrap_or(defaults.default_term),
+            allow_pty: merged.allow_pty.unwrap_or(defaults.allow_pty),
+            max_cols: merged.max_cols.unwrap_or(defaults.max_cols),
+            max_rows: merged.max_rows.unwrap_or(defaults.max_rows),
+        }
+    }
+
+    /// Whether a client may set `name` to `value`.
+    pub fn accepts_env(&self, name: &str, value: &str) -> bool {
+        is_valid_env_name(name)
+            && value.len() <= MAX_ENV_VALUE_LEN
+            && !value.chars().any(char::is_control)
+            && self
+                .accept_env
+                .iter()
+                .any(|pattern| wildcard_match(pattern.as_bytes(), name.as_bytes()))
+    }
+
+    /// TERM for the session: the client's terminal name when it is usable.
+    pub fn term(&self, requested: &str) -> String {
+        if is_valid_term(requested) {
+            requested.to_string()
+        } else {
+            self.default_term.clone()
+        }
+    }
+
+    /// Window size after applying `max_cols`/`max_rows`.
+    pub fn clamp_size(&self, cols: u32, rows: u32) -> (u32, u32) {
+        let clamp = |value: u32, max: u32| if max > 0 { value.min(max) } else { value };
+        (clamp(cols, self.max_cols), clamp(rows, self.max_rows))
+    }
+}
+
+/// Check an `accept_env` entry. Returns a description of the problem.
+pub fn validate_env_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.is_empty()
+        || !pattern
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '*' | '?'))
+    {
+        return Err(format!(
+            "'{}' must be a variable name, optionally with '*' or '?' wildcards",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+/// Check a `default_term` value. Returns a description of the problem.
+pub fn validate_term(term: &str) -> Result<(), String> {
+    if !is_valid_term(term) {
+        return Err(format!(
+            "'{}' must be 1-{} letters, digits, '-', '_', '.' or '+'",
+            term, MAX_TERM_LEN
+        ));
+    }
+    Ok(())
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_valid_term(term: &str) -> bool {
+    !term.is_empty()
+        && term.len() <= MAX_TERM_LEN
+        && term
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+}
+
+/// Glob match with `*` (any run) and `?` (one character).
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some((&c, rest)) => text
+            .split_first()
+            .is_some_and(|(&t, tail)| (c == b'?' || c == t) && wildcard_match(rest, tail)),
+    }
+}
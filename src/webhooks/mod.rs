@@ -65,35 +65,14 @@ impl WebhookDispatcher {
                     }
                 };
 
-                // P2-4: DNS rebinding protection — resolve, check, and pin IP
-                let pinned_ip = match check_webhook_dns(&url, allow_private_ips).await {
-                    Ok(pin) => pin,
+                let pinned_client = match guarded_client(&client, &url, allow_private_ips).await {
+                    Ok(c) => c,
                     Err(e) => {
                         warn!(url = %url, error = %e, "Webhook DNS rebinding check failed");
                         return;
                     }
                 };
 
-                // If we have a pinned IP, build a per-request client that resolves
-                // the hostname to the validated IP, preventing DNS rebinding TOCTOU.
-                let pinned_client = if let Some((ref host, addr)) = pinned_ip {
-                    match reqwest::Client::builder()
-                        .connect_timeout(std::time::Duration::from_secs(5))
-                        .timeout(std::time::Duration::from_secs(10))
-                        .redirect(reqwest::redirect::Policy::none())
-                        .resolve(host, addr)
-                        .build()
-                    {
-                        Ok(c) => c,
-                        Err(e) => {
-                            warn!(url = %url, error = %e, "Failed to build pinned HTTP client");
-                            return;
-                        }
-                    }
-                } else {
-                    client.clone()
-                };
-
                 // Retry loop with exponential backoff
                 let mut attempt = 0u32;
                 loop {
@@ -120,6 +99,40 @@ impl WebhookDispatcher {
     }
 }
 
+/// P2-4: DNS rebinding protection — resolve, check, and pin IP.
+///
+/// If we have a pinned IP, returns a per-request client that resolves the
+/// hostname to the validated IP, preventing DNS rebinding TOCTOU; otherwise
+/// a clone of `client`.
+pub(crate) async fn guarded_client(
+    client: &reqwest::Client,
+    url: &str,
+    allow_private_ips: bool,
+) -> anyhow::Result<reqwest::Client> {
+    match check_webhook_dns(url, allow_private_ips).await? {
+        Some((host, addr)) => Ok(reqwest::Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .timeout(std::time::Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(&host, addr)
+            .build()?),
+        None => Ok(client.clone()),
+    }
+}
+
+/// `X-Signature-256` header value: HMAC-SHA256 of `body` keyed by `secret`.
+pub(crate) fn signature(secret: &str, body: &str) -> anyhow::Result<String> {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body.as_bytes());
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
 /// P2-4: Resolve webhook URL hostname and verify all IPs against ip_guard.
 /// Returns the first validated IP address for connection pinning (TOCTOU prevention).
 async fn check_webhook_dns(
@@ -183,13 +196,7 @@ async fn send_webhook(
     let mut request = client.post(url).header("Content-Type", "application/json");
 
     if let Some(secret) = secret {
-        use hmac::{Hmac, Mac};
-        use sha2::Sha256;
-
-        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
-        mac.update(body.as_bytes());
-        let signature = hex::encode(mac.finalize().into_bytes());
-        request = request.header("X-Signature-256", format!("sha256={}", signature));
+        request = request.header("X-Signature-256", signature(secret, &body)?);
     }
 
     let response = request.body(body).send().await?;
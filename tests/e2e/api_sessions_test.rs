@@ -34,6 +34,20 @@ async fn start_api_with_engine(config: s5::config::types::AppConfig) -> (u16, Ar
         ssh_listen_addr: None,
         quota_tracker: None,
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let _task = tokio::spawn(async move {
@@ -249,3 +263,73 @@ async fn test_sessions_list_with_registered_session() {
         "sessions should be empty after unregister"
     );
 }
+
+// ---------------------------------------------------------------------------
+// Test 5: GET /api/sessions/:id returns the session detail with its
+//         connection's destinations and SSH handshake data
+// ---------------------------------------------------------------------------
+#[tokio::test]
+async fn test_session_detail_by_id() {
+    let port = free_port().await;
+    let hash = hash_pass("pass");
+    let config = api_config(port, "test-token", &hash);
+    let (port, engine) = start_api_with_engine(config).await;
+
+    let conn = engine.register_connection("c0ffee01", "192.168.1.42");
+    conn.set_auth_method("publickey");
+    let first = engine.register_session_cid(
+        "alice",
+        "example.com",
+        443,
+        "192.168.1.42",
+        "ssh",
+        "c0ffee01",
+    );
+    engine.register_session_cid(
+        "alice",
+        "example.org",
+        22,
+        "192.168.1.42",
+        "ssh",
+        "c0ffee01",
+    );
+    let socks = engine.register_session("bob", "example.net", 80, "192.168.1.43", "socks5");
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{}/api/sessions/{}",
+            port, first.session_id
+        ))
+        .header("Authorization", "Bearer test-token")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let d = &body["data"];
+    assert_eq!(d["session_id"], first.session_id);
+    assert_eq!(d["correlation_id"], "c0ffee01");
+    assert_eq!(d["destinations"].as_array().unwrap().len(), 2);
+    assert_eq!(d["ssh"]["auth_method"], "publickey");
+    assert_eq!(d["ssh"]["open_channels"], 2);
+    assert!(d["rate"]["up_bytes_per_sec"].as_f64().is_some());
+
+    // SOCKS5 sessions have no SSH connection behind them
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{}/api/sessions/{}",
+            port, socks.session_id
+        ))
+        .header("Authorization", "Bearer test-token")
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["data"]["ssh"].is_null());
+    assert_eq!(
+        body["data"]["destinations"][0]["target_host"],
+        "example.net"
+    );
+}
@@ -69,6 +69,10 @@ async fn start_socks5_with_audit(config: AppConfig, audit_path: std::path::PathB
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     });
 
     let task = tokio::spawn(async move {
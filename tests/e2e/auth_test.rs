@@ -178,3 +178,98 @@ async fn test_ssh_auth_retry_then_success() {
         "correct password should succeed after a failure"
     );
 }
+
+// ---------------------------------------------------------------------------
+// Test 6: Security key signatures need a touch, and a PIN with
+// require_security_key
+// ---------------------------------------------------------------------------
+
+/// Signs like a FIDO2 token holding an `sk-ssh-ed25519` key, reporting
+/// `flags` (0x01 touched, 0x04 user verified).
+struct SecurityKey {
+    signing: ed25519_dalek::SigningKey,
+    flags: u8,
+}
+
+impl SecurityKey {
+    fn public(&self) -> russh::keys::PublicKey {
+        use russh::keys::ssh_key::public::{Ed25519PublicKey, KeyData, SkEd25519};
+        let point = Ed25519PublicKey(self.signing.verifying_key().to_bytes());
+        KeyData::SkEd25519(SkEd25519::new(point, "ssh:")).into()
+    }
+}
+
+impl russh::Signer for SecurityKey {
+    type Error = russh::SendError;
+
+    async fn auth_publickey_sign(
+        &mut self,
+        _key: &russh::keys::PublicKey,
+        _hash_alg: Option<russh::keys::HashAlg>,
+        mut to_sign: russh::CryptoVec,
+    ) -> Result<russh::CryptoVec, Self::Error> {
+        use ed25519_dalek::Signer as _;
+        use sha2::{Digest, Sha256};
+
+        let counter = 7u32.to_be_bytes();
+        let mut signed = Sha256::digest(b"ssh:").to_vec();
+        signed.push(self.flags);
+        signed.extend(counter);
+        signed.extend(Sha256::digest(&to_sign[..]));
+        let signature = self.signing.sign(&signed).to_bytes();
+
+        let mut blob = Vec::new();
+        for field in [b"sk-ssh-ed25519@openssh.com".as_slice(), &signature] {
+            blob.extend((field.len() as u32).to_be_bytes());
+            blob.extend(field);
+        }
+        blob.push(self.flags);
+        blob.extend(counter);
+        to_sign.extend(&(blob.len() as u32).to_be_bytes());
+        to_sign.extend(&blob);
+        Ok(to_sign)
+    }
+}
+
+/// Log in as `testuser` with a security key reporting `flags`.
+async fn security_key_login(require_security_key: bool, flags: u8) -> bool {
+    let port = free_port().await;
+    let mut key = SecurityKey {
+        signing: ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng),
+        flags,
+    };
+    let public = key.public();
+    let mut config = ssh_config(port, &hash_pass("unused"));
+    config.users[0].authorized_keys = vec![public.to_openssh().unwrap()];
+    config.users[0].require_security_key = require_security_key;
+    let server = start_ssh(config).await;
+
+    let client_config = Arc::new(russh::client::Config::default());
+    let mut handle = russh::client::connect(
+        client_config,
+        format!("127.0.0.1:{}", server.port),
+        TestClientHandler,
+    )
+    .await
+    .unwrap();
+    handle
+        .authenticate_publickey_with("testuser", public, None, &mut key)
+        .await
+        .unwrap()
+        .success()
+}
+
+#[tokio::test]
+async fn test_ssh_security_key_flags() {
+    assert!(security_key_login(false, 0x01).await, "touched");
+    assert!(!security_key_login(false, 0x00).await, "not touched");
+    assert!(security_key_login(true, 0x05).await, "touched and verified");
+    assert!(
+        !security_key_login(true, 0x01).await,
+        "require_security_key needs user verification"
+    );
+    assert!(
+        !security_key_login(true, 0x04).await,
+        "verified, not touched"
+    );
+}
@@ -71,6 +71,10 @@ async fn start_socks5(config: AppConfig) -> TestServer {
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     });
 
     let task = tokio::spawn(async move {
@@ -61,6 +61,20 @@ async fn start_api_with_quota(config: s5::config::types::AppConfig) -> (u16, Arc
         ssh_listen_addr: None,
         quota_tracker: Some(quota_tracker.clone()),
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let _task = tokio::spawn(async move {
@@ -119,22 +119,30 @@ async fn wait_api_ready(api_port: u16, token: &str) {
     panic!("API server not ready on port {} after 2s", api_port);
 }
 
-/// Open the dashboard in a new browser tab.
-/// The token is passed as a query parameter so the JS can extract it
-/// and use it for Bearer auth on API calls (WS/SSE ticket negotiation).
+/// Open the dashboard in a new browser tab, signing in via the login form.
 async fn open_dashboard(
     browser: &chromiumoxide::Browser,
     api_port: u16,
     token: &str,
 ) -> chromiumoxide::Page {
     let page = browser
-        .new_page(format!(
-            "http://127.0.0.1:{}/dashboard?token={}",
-            api_port, token
-        ))
+        .new_page(format!("http://127.0.0.1:{}/dashboard/login", api_port))
         .await
         .expect("open new page");
 
+    // Sign in through the login form; the session cookie carries auth from here on
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    page.evaluate(format!(
+        "document.getElementById('token').value = '{}'; \
+         document.getElementById('loginForm').requestSubmit(); true",
+        token
+    ))
+    .await
+    .expect("submit login form");
+    page.wait_for_navigation()
+        .await
+        .expect("redirect to dashboard");
+
     // Wait for page DOM + initial scripts to load
     tokio::time::sleep(Duration::from_millis(1000)).await;
     page
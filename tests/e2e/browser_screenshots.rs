@@ -279,9 +279,10 @@ async fn populate_screenshot_data(server: &TestApiServerWithState) -> Vec<Arc<Li
     // --- Ban an IP ---
     {
         let security = server.security.write().await;
-        security
-            .ban_manager()
-            .ban("192.168.99.1".parse().unwrap(), Duration::from_secs(1800));
+        security.ban_manager().ban(
+            "192.168.99.1".parse::<std::net::IpAddr>().unwrap(),
+            Duration::from_secs(1800),
+        );
     }
 
     sessions
@@ -293,13 +294,23 @@ async fn open_dashboard(
     token: &str,
 ) -> chromiumoxide::Page {
     let page = browser
-        .new_page(format!(
-            "http://127.0.0.1:{}/dashboard?token={}",
-            api_port, token
-        ))
+        .new_page(format!("http://127.0.0.1:{}/dashboard/login", api_port))
         .await
         .expect("open new page");
 
+    // Sign in through the login form; the session cookie carries auth from here on
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    page.evaluate(format!(
+        "document.getElementById('token').value = '{}'; \
+         document.getElementById('loginForm').requestSubmit(); true",
+        token
+    ))
+    .await
+    .expect("submit login form");
+    page.wait_for_navigation()
+        .await
+        .expect("redirect to dashboard");
+
     // Set viewport to 1280x800 for deterministic screenshots via CDP
     let set_metrics =
         chromiumoxide::cdp::browser_protocol::emulation::SetDeviceMetricsOverrideParams::builder()
@@ -152,3 +152,126 @@ async fn test_forwarding_denied_user() {
         "forwarding should be denied for nofwd user"
     );
 }
+
+// ---------------------------------------------------------------------------
+// Test 4: VPN tunnel (tun@openssh.com) routes the client's packets
+// ---------------------------------------------------------------------------
+
+/// IPv4 ICMP echo request from `source` to `destination`.
+fn icmp_echo(source: [u8; 4], destination: [u8; 4]) -> Vec<u8> {
+    fn checksum(data: &[u8]) -> u16 {
+        let sum: u32 = data
+            .chunks(2)
+            .map(|c| u32::from(u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)])))
+            .sum();
+        let sum = (sum & 0xffff) + (sum >> 16);
+        !((sum & 0xffff) + (sum >> 16)) as u16
+    }
+    let mut icmp = vec![8, 0, 0, 0, 0x12, 0x34, 0, 1];
+    icmp.extend_from_slice(b"s5 vpn e2e");
+    let sum = checksum(&icmp);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 1, 0, 0];
+    packet[2..4].copy_from_slice(&((20 + icmp.len()) as u16).to_be_bytes());
+    packet.extend_from_slice(&source);
+    packet.extend_from_slice(&destination);
+    let sum = checksum(&packet);
+    packet[10..12].copy_from_slice(&sum.to_be_bytes());
+    packet.extend_from_slice(&icmp);
+    packet
+}
+
+/// Send `packet` through the tunnel; the ICMP echo reply routed back, if any.
+async fn ping_through(
+    stream: &mut (impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin),
+    packet: &[u8],
+) -> Option<s5::proxy::tun::PacketInfo> {
+    let mut message = Vec::new();
+    s5::proxy::tun::encode_packet(packet, &mut message);
+    stream.write_all(&message).await.unwrap();
+
+    let mut buf = vec![0u8; 4096];
+    let read = async {
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "tunnel closed");
+            // Each message carries exactly one packet, as OpenSSH expects
+            let packets = s5::proxy::tun::PacketDecoder::default()
+                .push(&buf[..n])
+                .unwrap();
+            assert_eq!(packets.len(), 1);
+            // ICMP echo reply
+            if packets[0].get(20) == Some(&0) {
+                return s5::proxy::tun::parse_packet(&packets[0]);
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_millis(1500), read)
+        .await
+        .ok()
+        .flatten()
+}
+
+async fn login(ssh_port: u16, user: &str, pass: &str) -> russh::client::Handle<TestClientHandler> {
+    let client_config = Arc::new(russh::client::Config::default());
+    let mut handle = russh::client::connect(
+        client_config,
+        format!("127.0.0.1:{}", ssh_port),
+        TestClientHandler,
+    )
+    .await
+    .unwrap();
+    assert!(handle
+        .authenticate_password(user, pass)
+        .await
+        .unwrap()
+        .success());
+    handle
+}
+
+#[tokio::test]
+async fn test_vpn_tunnel() {
+    // Creating TUN interfaces needs CAP_NET_ADMIN
+    if s5::proxy::tun::Device::open([10, 99, 77, 1].into(), [10, 99, 77, 2].into(), 1420).is_err() {
+        eprintln!("TUN interfaces not available, skipping test");
+        return;
+    }
+
+    let ssh_port = free_port().await;
+    let hash1 = hash_pass("pass1");
+    let hash2 = hash_pass("pass2");
+    let mut config = ssh_config_multi_user(ssh_port, &hash1, &hash2);
+    config.vpn.enabled = true;
+    config.vpn.pool = "10.99.77.0/29".parse().unwrap();
+    config.users[0].allow_vpn = true;
+    config.users[0].vpn_address = Some([10, 99, 77, 5].into());
+    let _server = start_ssh(config).await;
+
+    // Only users with allow_vpn get a tunnel, only in point-to-point mode
+    let handle = login(ssh_port, "nofwd", "pass2").await;
+    assert!(handle.channel_open_tun(1, 0x7fff_ffff).await.is_err());
+    let handle = login(ssh_port, "testuser", "pass1").await;
+    assert!(handle.channel_open_tun(2, 0x7fff_ffff).await.is_err());
+
+    let channel = handle.channel_open_tun(1, 0x7fff_ffff).await.unwrap();
+    let mut stream = channel.into_datagram_stream();
+    let (server, client) = ([10, 99, 77, 1], [10, 99, 77, 5]);
+
+    // The server end answers pings from the client's address...
+    let reply = ping_through(&mut stream, &icmp_echo(client, server))
+        .await
+        .expect("no echo reply");
+    assert_eq!(reply.source, std::net::Ipv4Addr::from(server));
+    assert_eq!(reply.destination, std::net::Ipv4Addr::from(client));
+
+    // ...but not from a spoofed one
+    assert!(
+        ping_through(&mut stream, &icmp_echo([10, 99, 77, 6], server))
+            .await
+            .is_none()
+    );
+
+    // The second tunnel of the user would need the same fixed address
+    assert!(handle.channel_open_tun(1, 0x7fff_ffff).await.is_err());
+}
@@ -91,6 +91,10 @@ pub async fn start_ssh(config: AppConfig) -> TestSshServer {
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     });
 
     let key_pair =
@@ -129,6 +133,10 @@ pub async fn start_socks5(config: AppConfig) -> TestSocksServer {
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     });
 
     let task = tokio::spawn(async move {
@@ -175,6 +183,20 @@ pub async fn start_api(config: AppConfig) -> TestApiServer {
         ssh_listen_addr: None,
         quota_tracker: None,
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let task = tokio::spawn(async move {
@@ -212,6 +234,20 @@ pub async fn start_api_with_state(config: AppConfig) -> TestApiServerWithState {
         ssh_listen_addr: None,
         quota_tracker: Some(quota_tracker.clone()),
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let task = tokio::spawn(async move {
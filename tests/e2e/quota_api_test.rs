@@ -62,6 +62,20 @@ async fn start_api_with_quota(config: s5::config::types::AppConfig) -> (u16, Arc
         ssh_listen_addr: None,
         quota_tracker: Some(quota_tracker.clone()),
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let _task = tokio::spawn(async move {
@@ -457,3 +471,124 @@ password_hash = "{hash}"
         "should have quota exceeded metric"
     );
 }
+
+// ---------------------------------------------------------------------------
+// Test 11: POST /api/users/:name/quota/reset clears the named counters
+// ---------------------------------------------------------------------------
+#[tokio::test]
+async fn test_user_quota_counter_reset() {
+    let port = free_port().await;
+    let hash = hash_pass("pass");
+    let (port, qt) = start_api_with_quota(make_config(port, &hash)).await;
+
+    qt.record_connection("alice", None).unwrap();
+    match qt.record_bytes("alice", 4096, 0, 0, None) {
+        QuotaResult::Ok(_) => {}
+        QuotaResult::Exceeded(r) => panic!("unexpected: {r}"),
+    }
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{port}/api/users/alice/quota/reset"
+        ))
+        .header("Authorization", "Bearer quota-test-token")
+        .json(&serde_json::json!({ "counters": ["daily_bytes"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["daily_bytes"], 0);
+    assert_eq!(data["monthly_bytes"], 4096);
+    assert_eq!(data["daily_connections"], 1);
+
+    // No body: every counter
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{port}/api/users/alice/quota/reset"
+        ))
+        .header("Authorization", "Bearer quota-test-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["monthly_bytes"], 0);
+    assert_eq!(body["data"]["daily_connections"], 0);
+
+    // A typo is refused rather than resetting everything
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{port}/api/users/alice/quota/reset"
+        ))
+        .header("Authorization", "Bearer quota-test-token")
+        .json(&serde_json::json!({ "counters": ["daily_byte"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{port}/api/users/nobody/quota/reset"
+        ))
+        .header("Authorization", "Bearer quota-test-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+// ---------------------------------------------------------------------------
+// Test 12: PATCH /api/users/:name/quota grants extra allowance
+// ---------------------------------------------------------------------------
+#[tokio::test]
+async fn test_user_quota_grant() {
+    let port = free_port().await;
+    let hash = hash_pass("pass");
+    let (port, qt) = start_api_with_quota(make_config(port, &hash)).await;
+    let quota = QuotaConfig {
+        daily_bandwidth_bytes: 1000,
+        ..Default::default()
+    };
+    match qt.record_bytes("alice", 1000, 0, 0, Some(&quota)) {
+        QuotaResult::Exceeded(_) => {}
+        QuotaResult::Ok(_) => panic!("should exceed"),
+    }
+
+    let client = reqwest::Client::new();
+    let url = format!("http://127.0.0.1:{port}/api/users/alice/quota");
+    let resp = client
+        .patch(&url)
+        .header("Authorization", "Bearer quota-test-token")
+        .json(&serde_json::json!({ "daily_bytes": 5000 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["granted"]["daily_bytes"], 5000);
+    assert!(qt.check_bandwidth_quota("alice", Some(&quota)).is_ok());
+
+    // Nothing to grant, unknown field, unknown user
+    for (url, body, status) in [
+        (&url, serde_json::json!({}), 400),
+        (&url, serde_json::json!({ "hourly_bytes": 1 }), 422),
+        (
+            &format!("http://127.0.0.1:{port}/api/users/nobody/quota"),
+            serde_json::json!({ "daily_bytes": 1 }),
+            404,
+        ),
+    ] {
+        let resp = client
+            .patch(url)
+            .header("Authorization", "Bearer quota-test-token")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{body}");
+    }
+}
@@ -62,6 +62,20 @@ allow_shell = true
         ssh_listen_addr: None,
         quota_tracker: None,
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let api_addr = format!("127.0.0.1:{api_port}");
@@ -139,6 +153,20 @@ allow_shell = true
         ssh_listen_addr: None,
         quota_tracker: None,
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let api_addr = format!("127.0.0.1:{api_port}");
@@ -215,6 +243,20 @@ allow_shell = true
         ssh_listen_addr: None,
         quota_tracker: None,
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let api_addr = format!("127.0.0.1:{api_port}");
@@ -79,6 +79,20 @@ async fn start_api_with_quota(config: s5::config::types::AppConfig) -> (u16, Arc
         ssh_listen_addr: None,
         quota_tracker: Some(quota_tracker.clone()),
         webhook_dispatcher: None,
+        readiness: None,
+        api_guard: None,
+        allowed_origins: Default::default(),
+        sessions: Default::default(),
+        user_sessions: Default::default(),
+        dashboard_accounts: Default::default(),
+        tenants: Default::default(),
+        invitations: Default::default(),
+        flow_log: None,
+        host_keys: None,
+        config_history: None,
+        cluster: None,
+        ssh_tunnel: None,
+        trusted_proxies: Default::default(),
     };
 
     let _task = tokio::spawn(async move {
@@ -244,6 +258,11 @@ async fn test_sse_payload_contains_sessions() {
         sessions["sessions"].as_array().unwrap().is_empty(),
         "sessions array should be empty initially"
     );
+    // Throughput sparkline history rides along with the sessions
+    assert!(
+        payload["throughput"].is_array(),
+        "SSE payload should contain a 'throughput' array"
+    );
 }
 
 // ---------------------------------------------------------------------------
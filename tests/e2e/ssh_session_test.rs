@@ -97,6 +97,10 @@ async fn start_ssh_server(config: AppConfig) -> (u16, tokio::task::JoinHandle<()
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     });
 
     let key_pair =
@@ -224,3 +228,75 @@ async fn test_ssh_shell_session() {
         output
     );
 }
+
+// ---------------------------------------------------------------------------
+// Test 4: Host keys announced after login (UpdateHostKeys)
+// ---------------------------------------------------------------------------
+
+/// Client handler keeping the host keys the server announces.
+struct HostKeysClient {
+    announced: tokio::sync::mpsc::UnboundedSender<Vec<russh::keys::PublicKey>>,
+}
+
+impl russh::client::Handler for HostKeysClient {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        _server_public_key: &russh::keys::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    async fn openssh_ext_host_keys_announced(
+        &mut self,
+        keys: Vec<russh::keys::PublicKey>,
+        _session: &mut russh::client::Session,
+    ) -> Result<(), Self::Error> {
+        let _ = self.announced.send(keys);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_ssh_host_keys_announced_during_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = free_port().await;
+    let hash = password::hash_password("rotate").unwrap();
+    let mut config = make_ssh_config(port, &hash);
+    config.server.host_key_path = dir.path().join("host_key");
+    config.server.host_key_rotation.new_key_path = Some(dir.path().join("host_key.new"));
+    let served = s5::ssh::host_keys::HostKeyRing::load(&config.server)
+        .unwrap()
+        .served(std::time::SystemTime::now())
+        .1;
+    assert_eq!(served.len(), 2);
+
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let server = tokio::spawn(s5::server::run_until(config, None, shutdown.clone()));
+    sleep(Duration::from_millis(300)).await;
+
+    let (tx, mut announced) = tokio::sync::mpsc::unbounded_channel();
+    let mut handle = russh::client::connect(
+        Arc::new(russh::client::Config::default()),
+        format!("127.0.0.1:{port}"),
+        HostKeysClient { announced: tx },
+    )
+    .await
+    .unwrap();
+    assert!(handle
+        .authenticate_password("testuser", "rotate")
+        .await
+        .unwrap()
+        .success());
+
+    let keys = tokio::time::timeout(Duration::from_secs(3), announced.recv())
+        .await
+        .expect("no hostkeys-00@openssh.com request")
+        .unwrap();
+    let expected: Vec<_> = served.iter().map(|k| k.public_key().clone()).collect();
+    assert_eq!(keys, expected, "current key first, then the new one");
+
+    shutdown.cancel();
+    let _ = server.await;
+}
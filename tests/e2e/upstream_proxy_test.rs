@@ -3,7 +3,7 @@
 //! Uses a mock SOCKS5 server to test `connect_via_socks5()` without requiring
 //! an external SOCKS5 proxy.
 
-use s5::config::types::ParsedUpstreamProxy;
+use s5::config::types::{ParsedUpstreamProxy, TcpConfig};
 use s5::proxy::connector::connect_via_socks5;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -222,9 +222,15 @@ async fn test_connect_via_socks5_no_auth() {
         password: None,
     };
 
-    let mut stream = connect_via_socks5(&proxy, "example.com", 80, Duration::from_secs(5))
-        .await
-        .expect("should connect via proxy");
+    let mut stream = connect_via_socks5(
+        &proxy,
+        "example.com",
+        80,
+        Duration::from_secs(5),
+        &TcpConfig::default(),
+    )
+    .await
+    .expect("should connect via proxy");
 
     // Test that the tunnel works (mock echoes data back)
     stream.write_all(b"hello").await.unwrap();
@@ -244,9 +250,15 @@ async fn test_connect_via_socks5_with_auth() {
         password: Some("proxypass".to_string()),
     };
 
-    let mut stream = connect_via_socks5(&proxy, "target.example.com", 443, Duration::from_secs(5))
-        .await
-        .expect("should connect via proxy with auth");
+    let mut stream = connect_via_socks5(
+        &proxy,
+        "target.example.com",
+        443,
+        Duration::from_secs(5),
+        &TcpConfig::default(),
+    )
+    .await
+    .expect("should connect via proxy with auth");
 
     // Verify tunnel works
     stream.write_all(b"ping").await.unwrap();
@@ -266,7 +278,14 @@ async fn test_connect_via_socks5_auth_rejected() {
         password: None,
     };
 
-    let result = connect_via_socks5(&proxy, "example.com", 80, Duration::from_secs(5)).await;
+    let result = connect_via_socks5(
+        &proxy,
+        "example.com",
+        80,
+        Duration::from_secs(5),
+        &TcpConfig::default(),
+    )
+    .await;
 
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
@@ -288,7 +307,14 @@ async fn test_connect_via_socks5_connect_failure() {
         password: None,
     };
 
-    let result = connect_via_socks5(&proxy, "example.com", 80, Duration::from_secs(5)).await;
+    let result = connect_via_socks5(
+        &proxy,
+        "example.com",
+        80,
+        Duration::from_secs(5),
+        &TcpConfig::default(),
+    )
+    .await;
 
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
@@ -310,7 +336,14 @@ async fn test_connect_via_socks5_wrong_auth_credentials() {
         password: Some("wrongpass".to_string()),
     };
 
-    let result = connect_via_socks5(&proxy, "example.com", 80, Duration::from_secs(5)).await;
+    let result = connect_via_socks5(
+        &proxy,
+        "example.com",
+        80,
+        Duration::from_secs(5),
+        &TcpConfig::default(),
+    )
+    .await;
 
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
@@ -331,7 +364,14 @@ async fn test_connect_via_socks5_timeout() {
         password: None,
     };
 
-    let result = connect_via_socks5(&proxy, "example.com", 80, Duration::from_secs(1)).await;
+    let result = connect_via_socks5(
+        &proxy,
+        "example.com",
+        80,
+        Duration::from_secs(1),
+        &TcpConfig::default(),
+    )
+    .await;
 
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
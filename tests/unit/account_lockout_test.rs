@@ -0,0 +1,100 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::security::lockout::AccountLockout;
+use s5::security::SecurityManager;
+
+// ---------------------------------------------------------------------------
+// Lockout
+// ---------------------------------------------------------------------------
+
+#[test]
+fn locks_at_threshold() {
+    let lockout = AccountLockout::new(3, 900);
+    assert_eq!(lockout.record_failure("alice"), None);
+    assert_eq!(lockout.record_failure("alice"), None);
+    assert!(!lockout.is_locked("alice"));
+    assert_eq!(lockout.record_failure("alice"), Some(3));
+    assert!(lockout.is_locked("alice"));
+    let remaining = lockout.remaining("alice").unwrap();
+    assert!(remaining.as_secs() >= 899 && remaining.as_secs() <= 900);
+    // Other accounts are unaffected
+    assert!(!lockout.is_locked("bob"));
+}
+
+#[test]
+fn failures_while_locked_do_not_extend_the_lock() {
+    let lockout = AccountLockout::new(1, 900);
+    assert_eq!(lockout.record_failure("alice"), Some(1));
+    let before = lockout.locked_accounts();
+    assert_eq!(lockout.record_failure("alice"), None);
+    assert_eq!(lockout.locked_accounts(), before);
+}
+
+#[test]
+fn success_clears_failures_but_not_a_lock() {
+    let lockout = AccountLockout::new(2, 900);
+    lockout.record_failure("alice");
+    lockout.record_success("alice");
+    assert_eq!(lockout.record_failure("alice"), None);
+    assert_eq!(lockout.record_failure("alice"), Some(2));
+    lockout.record_success("alice");
+    assert!(lockout.is_locked("alice"));
+}
+
+#[test]
+fn unlock_lifts_the_lock() {
+    let lockout = AccountLockout::new(1, 900);
+    assert!(!lockout.unlock("alice"));
+    lockout.record_failure("alice");
+    assert_eq!(lockout.locked_accounts().len(), 1);
+    assert!(lockout.unlock("alice"));
+    assert!(!lockout.is_locked("alice"));
+    assert!(lockout.is_empty());
+    assert!(!lockout.unlock("alice"));
+}
+
+#[test]
+fn disabled_by_default() {
+    let lockout = AccountLockout::new(0, 900);
+    assert!(!lockout.is_enabled());
+    for _ in 0..100 {
+        assert_eq!(lockout.record_failure("alice"), None);
+    }
+    assert!(lockout.is_empty());
+}
+
+#[test]
+fn reload_keeps_locks_unless_disabled() {
+    let mut config = parse_app_config("[security]\nlock_after_failures = 1", "").unwrap();
+    let mut security = SecurityManager::new(&config);
+    security.account_lockout().record_failure("alice");
+
+    config.security.lock_duration = 60;
+    security.reload(&config);
+    assert!(security.account_lockout().is_locked("alice"));
+    assert_eq!(security.account_lockout().lock_duration().as_secs(), 60);
+
+    config.security.lock_after_failures = 0;
+    security.reload(&config);
+    assert!(!security.account_lockout().is_locked("alice"));
+}
+
+// ---------------------------------------------------------------------------
+// Audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn audit_events() {
+    let source = "203.0.113.5:40000".parse().unwrap();
+    let locked = AuditEvent::account_locked_with_cid("alice", &source, "socks5", "c1", 5, 900);
+    assert_eq!(locked.event_type(), "user.locked");
+    assert!(locked.is_critical());
+    let json = serde_json::to_value(&locked).unwrap();
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["failures"], 5);
+    assert_eq!(json["lock_secs"], 900);
+
+    let unlocked = AuditEvent::account_unlocked("alice");
+    assert_eq!(unlocked.event_type(), "user.unlocked");
+    assert!(unlocked.is_critical());
+}
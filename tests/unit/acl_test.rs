@@ -76,6 +76,7 @@ fn test_global_acl_deny_inherited_by_user() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["169.254.169.254:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig::default(); // inherit = true, no overrides
 
@@ -94,6 +95,7 @@ fn test_global_acl_user_adds_rules() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["169.254.169.254:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None, // inherit global Allow
@@ -118,6 +120,7 @@ fn test_global_acl_user_overrides_default_policy() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Deny),
@@ -139,6 +142,7 @@ fn test_global_acl_inherit_false_ignores_global() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec!["*.example.com:443".to_string()],
         deny: vec!["169.254.169.254:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Allow),
@@ -161,6 +165,7 @@ fn test_global_acl_inherit_false_defaults_to_allow() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None, // no override, should fallback to Allow
@@ -180,6 +185,7 @@ fn test_global_acl_allow_rules_merge() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec!["*.global.com:443".to_string()],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None, // inherit Deny
@@ -0,0 +1,89 @@
+use axum::http::{HeaderMap, HeaderValue};
+use s5::api::cors::{csrf_token_valid, normalize_origin, origin_allowed, CSRF_HEADER};
+
+#[test]
+fn normalize_origin_accepts_bare_origins() {
+    assert_eq!(
+        normalize_origin("https://dash.example.com").as_deref(),
+        Some("https://dash.example.com")
+    );
+    assert_eq!(
+        normalize_origin("https://dash.example.com/").as_deref(),
+        Some("https://dash.example.com")
+    );
+    assert_eq!(
+        normalize_origin("https://dash.example.com:443").as_deref(),
+        Some("https://dash.example.com"),
+        "default port is dropped like browsers do"
+    );
+    assert_eq!(
+        normalize_origin("http://10.0.0.5:8080").as_deref(),
+        Some("http://10.0.0.5:8080")
+    );
+}
+
+#[test]
+fn normalize_origin_rejects_non_origins() {
+    for bad in [
+        "*",
+        "dash.example.com",
+        "ftp://dash.example.com",
+        "https://dash.example.com/app",
+        "https://dash.example.com/?x=1",
+        "https://user@dash.example.com",
+    ] {
+        assert!(
+            normalize_origin(bad).is_none(),
+            "{} should be rejected",
+            bad
+        );
+    }
+}
+
+#[test]
+fn origin_allowed_same_origin_and_configured() {
+    let allowed = vec!["https://dash.example.com".to_string()];
+    assert!(origin_allowed(
+        "http://127.0.0.1:9091",
+        Some("127.0.0.1:9091"),
+        &[]
+    ));
+    assert!(origin_allowed(
+        "https://dash.example.com",
+        Some("127.0.0.1:9091"),
+        &allowed
+    ));
+    assert!(!origin_allowed(
+        "https://evil.example.com",
+        Some("127.0.0.1:9091"),
+        &allowed
+    ));
+    assert!(!origin_allowed("null", Some("127.0.0.1:9091"), &allowed));
+    assert!(!origin_allowed("https://dash.example.com", None, &[]));
+}
+
+fn headers(cookie: Option<&str>, token: Option<&str>) -> HeaderMap {
+    let mut h = HeaderMap::new();
+    if let Some(c) = cookie {
+        h.insert("cookie", HeaderValue::from_str(c).unwrap());
+    }
+    if let Some(t) = token {
+        h.insert(CSRF_HEADER, HeaderValue::from_str(t).unwrap());
+    }
+    h
+}
+
+#[test]
+fn csrf_token_requires_matching_cookie_and_header() {
+    assert!(csrf_token_valid(&headers(
+        Some("theme=dark; s5_csrf=abc123"),
+        Some("abc123")
+    )));
+    assert!(!csrf_token_valid(&headers(
+        Some("s5_csrf=abc123"),
+        Some("abc124")
+    )));
+    assert!(!csrf_token_valid(&headers(Some("s5_csrf=abc123"), None)));
+    assert!(!csrf_token_valid(&headers(None, Some("abc123"))));
+    assert!(!csrf_token_valid(&headers(Some("s5_csrf="), Some(""))));
+}
@@ -0,0 +1,83 @@
+use s5::api::guard::{ApiGuard, MAX_FAILURE_DELAY};
+use s5::config::types::ApiConfig;
+use std::net::IpAddr;
+use std::time::Duration;
+
+fn guard(rate_limit_per_minute: u32, auth_failure_delay_ms: u64) -> ApiGuard {
+    ApiGuard::new(&ApiConfig {
+        rate_limit_per_minute,
+        auth_failure_delay_ms,
+        ..ApiConfig::default()
+    })
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn api_config_defaults_enable_protection() {
+    let cfg = ApiConfig::default();
+    assert_eq!(cfg.rate_limit_per_minute, 600);
+    assert_eq!(cfg.auth_failure_delay_ms, 250);
+}
+
+#[test]
+fn failure_delay_doubles_and_caps() {
+    let g = guard(0, 100);
+    assert_eq!(g.delay_for(0), Duration::ZERO);
+    assert_eq!(g.delay_for(1), Duration::from_millis(100));
+    assert_eq!(g.delay_for(2), Duration::from_millis(200));
+    assert_eq!(g.delay_for(3), Duration::from_millis(400));
+    assert_eq!(g.delay_for(10), MAX_FAILURE_DELAY);
+    assert_eq!(g.delay_for(u32::MAX), MAX_FAILURE_DELAY);
+}
+
+#[test]
+fn zero_base_delay_disables_stalling() {
+    let g = guard(0, 0);
+    assert_eq!(g.record_failure(&ip("10.0.0.1")), Duration::ZERO);
+    assert_eq!(g.record_failure(&ip("10.0.0.1")), Duration::ZERO);
+}
+
+#[test]
+fn consecutive_failures_are_per_ip_and_reset_on_success() {
+    let g = guard(0, 10);
+    let a = ip("10.0.0.1");
+    let b = ip("10.0.0.2");
+
+    assert_eq!(g.record_failure(&a), Duration::from_millis(10));
+    assert_eq!(g.record_failure(&a), Duration::from_millis(20));
+    assert_eq!(g.record_failure(&b), Duration::from_millis(10));
+    assert_eq!(g.consecutive_failures(&a), 2);
+
+    g.record_success(&a);
+    assert_eq!(g.consecutive_failures(&a), 0);
+    assert_eq!(g.record_failure(&a), Duration::from_millis(10));
+    assert_eq!(g.consecutive_failures(&b), 1);
+}
+
+#[test]
+fn rate_limit_rejects_after_budget() {
+    let g = guard(2, 0);
+    let a = ip("192.0.2.1");
+    assert!(g.check_rate(&a));
+    assert!(g.check_rate(&a));
+    assert!(
+        !g.check_rate(&a),
+        "third request within a minute must be rejected"
+    );
+    assert!(
+        g.check_rate(&ip("192.0.2.2")),
+        "other IPs keep their own budget"
+    );
+}
+
+#[test]
+fn rate_limit_zero_is_unlimited() {
+    let g = guard(0, 0);
+    let a = ip("192.0.2.1");
+    for _ in 0..1000 {
+        assert!(g.check_rate(&a));
+    }
+}
@@ -0,0 +1,77 @@
+use s5::api::rbac::Principal;
+use s5::api::session::SessionStore;
+use s5::config::types::DashboardRole;
+use std::time::Duration;
+
+fn admin() -> Principal {
+    Principal::api_token()
+}
+
+#[test]
+fn created_session_is_valid_until_removed() {
+    let store = SessionStore::new(Duration::from_secs(60));
+    let id = store.create(admin());
+    assert_eq!(id.len(), 64);
+    assert!(store.touch(&id).is_some());
+    assert!(store.remove(&id));
+    assert!(store.touch(&id).is_none());
+    assert!(!store.remove(&id));
+}
+
+#[test]
+fn unknown_session_is_rejected() {
+    let store = SessionStore::default();
+    assert!(store.touch("deadbeef").is_none());
+    assert!(store.touch("").is_none());
+}
+
+#[test]
+fn session_ids_are_unique() {
+    let store = SessionStore::default();
+    let a = store.create(admin());
+    let b = store.create(admin());
+    assert_ne!(a, b);
+    assert_eq!(store.len(), 2);
+}
+
+#[test]
+fn idle_session_expires_and_is_dropped() {
+    let store = SessionStore::new(Duration::from_millis(30));
+    let id = store.create(admin());
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(store.touch(&id).is_none(), "idle session must expire");
+    assert!(store.is_empty(), "expired session is removed on touch");
+}
+
+#[test]
+fn activity_extends_idle_window() {
+    let store = SessionStore::new(Duration::from_millis(80));
+    let id = store.create(admin());
+    for _ in 0..4 {
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(
+            store.touch(&id).is_some(),
+            "touch within the idle window keeps it alive"
+        );
+    }
+}
+
+#[test]
+fn prune_drops_only_expired_sessions() {
+    let store = SessionStore::new(Duration::from_millis(30));
+    let _old = store.create(admin());
+    std::thread::sleep(Duration::from_millis(60));
+    let fresh = store.create(admin());
+    store.prune();
+    assert_eq!(store.len(), 1);
+    assert!(store.touch(&fresh).is_some());
+}
+
+#[test]
+fn session_carries_its_principal() {
+    let store = SessionStore::default();
+    let id = store.create(Principal::new("alice", DashboardRole::Viewer));
+    let p = store.touch(&id).expect("live session");
+    assert_eq!(p.name, "alice");
+    assert_eq!(p.role, DashboardRole::Viewer);
+}
@@ -0,0 +1,265 @@
+use chrono::{TimeZone, Utc};
+use s5::audit::archive::{self, ArchiveMetrics, AuditArchiver};
+use s5::audit::chain::AuditChainOptions;
+use s5::audit::AuditLogger;
+use s5::config::parse_config;
+use s5::config::types::AuditArchiveConfig;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+/// (path, headers, body) of each request received
+type Received = Arc<Mutex<Vec<(String, axum::http::HeaderMap, Vec<u8>)>>>;
+
+fn archive_config(endpoint: &str) -> AuditArchiveConfig {
+    AuditArchiveConfig {
+        enabled: true,
+        endpoint: endpoint.to_string(),
+        bucket: "audit-logs".to_string(),
+        region: "eu-west-3".to_string(),
+        prefix: "s5/".to_string(),
+        access_key_id: "AKIDEXAMPLE".to_string(),
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+        ..Default::default()
+    }
+}
+
+/// Local object store answering every PUT with `status`.
+async fn object_store(status: Arc<AtomicU16>) -> (String, Received) {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let recv = received.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().fallback(
+            move |uri: axum::http::Uri, headers: axum::http::HeaderMap, body: axum::body::Bytes| {
+                let recv = recv.clone();
+                let status = status.clone();
+                async move {
+                    recv.lock()
+                        .await
+                        .push((uri.path().to_string(), headers, body.to_vec()));
+                    axum::http::StatusCode::from_u16(status.load(Ordering::Relaxed)).unwrap()
+                }
+            },
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    sleep(Duration::from_millis(50)).await;
+    (format!("http://127.0.0.1:{port}"), received)
+}
+
+// ---------------------------------------------------------------------------
+// Request signing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn object_url_path_and_virtual_hosted_style() {
+    let mut config = archive_config("https://s3.eu-west-3.amazonaws.com");
+    let url = archive::object_url(&config, "s5/audit.json.20261016T101500.000Z").unwrap();
+    assert_eq!(
+        url.as_str(),
+        "https://s3.eu-west-3.amazonaws.com/audit-logs/s5/audit.json.20261016T101500.000Z"
+    );
+
+    config.path_style = false;
+    let url = archive::object_url(&config, "s5/a b+c").unwrap();
+    assert_eq!(
+        url.as_str(),
+        "https://audit-logs.s3.eu-west-3.amazonaws.com/s5/a%20b%2Bc"
+    );
+}
+
+#[test]
+fn sigv4_signature() {
+    let config = archive_config("http://127.0.0.1:9000");
+    let url = archive::object_url(&config, "s5/audit.json.20261016T101500.000Z").unwrap();
+    let payload_hash = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+    let now = Utc.with_ymd_and_hms(2026, 10, 16, 10, 15, 0).unwrap();
+
+    let headers = archive::sign_put(&config, &url, payload_hash, now);
+    assert_eq!(headers[0], ("x-amz-date", "20261016T101500Z".to_string()));
+    assert_eq!(
+        headers[1],
+        ("x-amz-content-sha256", payload_hash.to_string())
+    );
+    assert_eq!(
+        headers[2].1,
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20261016/eu-west-3/s3/aws4_request, \
+         SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+         Signature=2d54af4ebcb1f8386b233aabd28a6f3db7ff9fe278cb23aa9bf51610f1cd2545"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Spool and upload
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn staged_file_uploaded_and_removed() {
+    let (endpoint, received) = object_store(Arc::new(AtomicU16::new(200))).await;
+    let dir = TempDir::new().unwrap();
+    let audit_path = dir.path().join("audit.json");
+    let rotated = dir.path().join("audit.json.1");
+    std::fs::write(&rotated, "hello\n").unwrap();
+
+    let archiver = AuditArchiver::new(&archive_config(&endpoint), &audit_path);
+    assert_eq!(archiver.spool(), archive::spool_dir(&audit_path));
+    archiver.stage(&rotated).await;
+    // Rotation may shift the original away; the spooled link stays
+    std::fs::remove_file(&rotated).unwrap();
+    let pending = archiver.pending().await;
+    assert_eq!(pending.len(), 1);
+    let key = archiver.object_key(&pending[0]);
+    assert!(key.starts_with("s5/audit.json.20"), "{key}");
+
+    assert_eq!(archiver.flush().await.unwrap(), 1);
+    assert!(archiver.pending().await.is_empty());
+
+    let reqs = received.lock().await;
+    assert_eq!(reqs.len(), 1);
+    let (path, headers, body) = &reqs[0];
+    assert_eq!(path, &format!("/audit-logs/{key}"));
+    assert_eq!(body, b"hello\n");
+    assert_eq!(
+        headers["x-amz-content-sha256"],
+        "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+    );
+    assert!(headers["authorization"]
+        .to_str()
+        .unwrap()
+        .starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+}
+
+#[tokio::test]
+async fn failed_upload_kept_for_retry() {
+    let status = Arc::new(AtomicU16::new(503));
+    let (endpoint, received) = object_store(status.clone()).await;
+    let dir = TempDir::new().unwrap();
+    let audit_path = dir.path().join("audit.json");
+    for (i, content) in ["first\n", "second\n"].iter().enumerate() {
+        let rotated = dir.path().join(format!("audit.json.{}", i + 1));
+        std::fs::write(&rotated, content).unwrap();
+    }
+
+    let archiver = AuditArchiver::new(&archive_config(&endpoint), &audit_path);
+    let metrics = ArchiveMetrics {
+        lag_seconds: Default::default(),
+        uploads: Default::default(),
+        failures: Default::default(),
+    };
+    let (lag, uploads, failures) = (
+        metrics.lag_seconds.clone(),
+        metrics.uploads.clone(),
+        metrics.failures.clone(),
+    );
+    archiver.set_metrics(metrics);
+    archiver.stage(&dir.path().join("audit.json.1")).await;
+    archiver.stage(&dir.path().join("audit.json.2")).await;
+
+    let err = archiver.flush().await.unwrap_err();
+    assert!(format!("{err:#}").contains("503"), "{err:#}");
+    // Stopped at the first failure, nothing removed
+    assert_eq!(received.lock().await.len(), 1);
+    assert_eq!(archiver.pending().await.len(), 2);
+    assert_eq!(failures.get(), 1);
+
+    status.store(200, Ordering::Relaxed);
+    assert_eq!(archiver.flush().await.unwrap(), 2);
+    assert_eq!(uploads.get(), 2);
+    assert_eq!(lag.get(), 0);
+    // Oldest first
+    let reqs = received.lock().await;
+    assert_eq!(reqs[1].2, b"first\n");
+    assert_eq!(reqs[2].2, b"second\n");
+}
+
+#[tokio::test]
+async fn rotated_audit_files_are_archived() {
+    let (endpoint, received) = object_store(Arc::new(AtomicU16::new(200))).await;
+    let dir = TempDir::new().unwrap();
+    let audit_path = dir.path().join("audit.json");
+    let archiver = AuditArchiver::new(&archive_config(&endpoint), &audit_path);
+    archiver.spawn();
+    let logger = AuditLogger::with_chain(
+        Some(audit_path.clone()),
+        200,
+        3,
+        None,
+        AuditChainOptions::default(),
+        None,
+        Some(archiver.clone()),
+    );
+
+    let source: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+    for i in 0..10 {
+        logger
+            .log_auth_success(&format!("user{i}"), &source, "password")
+            .await;
+    }
+    sleep(Duration::from_millis(800)).await;
+
+    let reqs = received.lock().await;
+    assert!(!reqs.is_empty(), "rotated files should have been uploaded");
+    let uploaded: String = reqs
+        .iter()
+        .map(|(_, _, body)| String::from_utf8_lossy(body).into_owned())
+        .collect();
+    assert!(uploaded.contains("\"user0\""));
+    assert!(archiver.pending().await.is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+fn config(logging: &str, archive: &str) -> anyhow::Result<s5::config::types::AppConfig> {
+    parse_config(&format!(
+        "[server]\nssh_listen = \"0.0.0.0:2222\"\n\n[logging]\n{logging}\n\n\
+         [logging.audit.archive]\n{archive}\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"x\"\n"
+    ))
+}
+
+#[test]
+fn archive_config_defaults_and_validation() {
+    let archive = config("", "").unwrap().logging.audit.archive;
+    assert!(!archive.enabled);
+    assert_eq!(archive.region, "us-east-1");
+    assert_eq!(archive.prefix, "audit/");
+    assert!(archive.path_style);
+    assert_eq!(archive.retry_interval_secs, 60);
+
+    let logging = "audit_log_path = \"/var/log/s5/audit.json\"";
+    let valid = "enabled = true\nendpoint = \"https://s3.example.com\"\nbucket = \"b\"\n\
+                 access_key_id = \"AK\"\nsecret_access_key = \"SK\"";
+    let parsed = config(logging, valid).unwrap();
+    assert!(!format!("{:?}", parsed.logging.audit.archive).contains("\"SK\""));
+    assert_eq!(
+        s5::config::redact::redact_config(&parsed)
+            .logging
+            .audit
+            .archive
+            .secret_access_key,
+        "***"
+    );
+
+    // Only rotated files are archived
+    assert!(config("", valid).is_err());
+    assert!(config(&format!("{logging}\naudit_max_size_mb = 0"), valid).is_err());
+    assert!(config(
+        logging,
+        &valid.replace("https://s3.example.com", "s3.example.com")
+    )
+    .is_err());
+    assert!(config(
+        logging,
+        &valid.replace("https://s3.example.com", "https://s3.example.com/path")
+    )
+    .is_err());
+    assert!(config(logging, &valid.replace("bucket = \"b\"", "")).is_err());
+    assert!(config(logging, &valid.replace("secret_access_key = \"SK\"", "")).is_err());
+}
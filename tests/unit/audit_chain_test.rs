@@ -0,0 +1,149 @@
+use s5::audit::chain::{
+    verify_lines, AuditChain, AuditChainOptions, ChainError, CHECKPOINT_EVENT, GENESIS_HASH,
+};
+use s5::audit::AuditLogger;
+use std::net::SocketAddr;
+use tempfile::TempDir;
+use tokio::time::{sleep, Duration};
+
+const KEY: &str = "audit-signing-key-0123";
+
+fn record(n: u64) -> serde_json::Value {
+    serde_json::json!({ "event_type": "auth.success", "username": format!("u{}", n) })
+}
+
+/// Three records, a checkpoint, then one more record.
+fn sample_chain(key: Option<&str>) -> Vec<String> {
+    let mut chain = AuditChain::new(key);
+    let mut lines: Vec<String> = (0..3).map(|n| chain.seal(record(n))).collect();
+    lines.push(chain.checkpoint());
+    lines.push(chain.seal(record(3)));
+    lines
+}
+
+#[test]
+fn first_record_links_to_genesis() {
+    let mut chain = AuditChain::new(None);
+    let line = chain.seal(record(0));
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["seq"], 0);
+    assert_eq!(v["prev_hash"], GENESIS_HASH);
+    assert_eq!(v["username"], "u0");
+    assert_eq!(chain.next_seq(), 1);
+    assert_eq!(chain.head_hash(), s5::audit::chain::line_hash(&line));
+}
+
+#[test]
+fn intact_chain_verifies() {
+    let lines = sample_chain(Some(KEY));
+    let report = verify_lines(lines.iter().map(String::as_str), Some(KEY)).unwrap();
+    assert_eq!(report.records, 5);
+    assert_eq!(report.checkpoints, 1);
+    assert_eq!(report.first_seq, Some(0));
+    assert_eq!(report.last_seq, Some(4));
+    assert_eq!(report.unsealed_tail, 1);
+
+    let cp: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+    assert_eq!(cp["event_type"], CHECKPOINT_EVENT);
+    assert_eq!(cp["head_seq"], 2);
+    assert!(cp["signature"].is_string());
+}
+
+#[test]
+fn edited_record_breaks_the_chain() {
+    let mut lines = sample_chain(None);
+    lines[1] = lines[1].replace("u1", "u9");
+    assert_eq!(
+        verify_lines(lines.iter().map(String::as_str), None),
+        Err(ChainError::HashMismatch { line: 3 })
+    );
+}
+
+#[test]
+fn removed_record_is_a_seq_gap() {
+    let mut lines = sample_chain(None);
+    lines.remove(1);
+    assert_eq!(
+        verify_lines(lines.iter().map(String::as_str), None),
+        Err(ChainError::SeqGap {
+            line: 2,
+            expected: 1,
+            found: 2
+        })
+    );
+}
+
+#[test]
+fn rewritten_tail_fails_signature_check() {
+    // Rebuild the chain from scratch with an attacker-chosen key
+    let forged = sample_chain(Some("attacker-key-000000"));
+    assert!(verify_lines(forged.iter().map(String::as_str), None).is_ok());
+    assert_eq!(
+        verify_lines(forged.iter().map(String::as_str), Some(KEY)),
+        Err(ChainError::BadSignature { line: 4 })
+    );
+
+    let unsigned = sample_chain(None);
+    assert_eq!(
+        verify_lines(unsigned.iter().map(String::as_str), Some(KEY)),
+        Err(ChainError::BadSignature { line: 4 })
+    );
+}
+
+#[test]
+fn resume_continues_after_last_line() {
+    let lines = sample_chain(None);
+    let mut resumed = AuditChain::resume(lines.last().unwrap(), None);
+    assert_eq!(resumed.next_seq(), 5);
+    let mut all = lines.clone();
+    all.push(resumed.seal(record(5)));
+    assert!(verify_lines(all.iter().map(String::as_str), None).is_ok());
+
+    // Unchained line: start over
+    let fresh = AuditChain::resume(r#"{"event_type":"auth.success"}"#, None);
+    assert_eq!(fresh.next_seq(), 0);
+}
+
+#[test]
+fn unchained_line_is_malformed() {
+    let mut lines = sample_chain(None);
+    lines.insert(2, r#"{"event_type":"auth.success"}"#.to_string());
+    assert_eq!(
+        verify_lines(lines.iter().map(String::as_str), None),
+        Err(ChainError::Malformed { line: 3 })
+    );
+}
+
+#[tokio::test]
+async fn logger_writes_chained_records_and_checkpoints() {
+    let temp_dir = TempDir::new().unwrap();
+    let audit_path = temp_dir.path().join("audit.json");
+
+    let logger = AuditLogger::with_chain(
+        Some(audit_path.clone()),
+        0,
+        0,
+        None,
+        AuditChainOptions {
+            enabled: true,
+            checkpoint_interval: Duration::from_millis(100),
+            signing_key: Some(KEY.to_string()),
+        },
+        None,
+        None,
+    );
+    let source: SocketAddr = "192.168.1.100:12345".parse().unwrap();
+    logger.log_auth_success("alice", &source, "password").await;
+    logger.log_auth_failure("bob", &source, "password").await;
+    sleep(Duration::from_millis(250)).await;
+
+    let content = tokio::fs::read_to_string(&audit_path).await.unwrap();
+    let report = verify_lines(content.lines(), Some(KEY)).unwrap();
+    assert_eq!(report.first_seq, Some(0));
+    assert_eq!(
+        report.checkpoints, 1,
+        "no new checkpoint without new records"
+    );
+    assert_eq!(report.records, 3);
+    assert_eq!(report.unsealed_tail, 0);
+}
@@ -247,6 +247,20 @@ fn serde_maintenance_toggled_contains_all_fields() {
     assert!(json["timestamp"].is_string());
 }
 
+#[test]
+fn serde_password_changed_contains_all_fields() {
+    let event = AuditEvent::password_changed("alice", "10.0.0.1", "ssh", true, None);
+    let json = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json["event_type"], "user.password_changed");
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["source_ip"], "10.0.0.1");
+    assert_eq!(json["via"], "ssh");
+    assert_eq!(json["success"], true);
+    assert!(json.get("error").is_none());
+    assert!(event.is_critical());
+}
+
 // ===========================================================================
 // Correlation ID: all *_with_cid constructors
 // ===========================================================================
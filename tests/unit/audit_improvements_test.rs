@@ -163,6 +163,9 @@ async fn relay_handles_zero_traffic_gracefully() {
         quotas: None,
         audit: None,
         session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     };
 
     // Both ends are immediately dropped (_relay_*), so relay sees EOF
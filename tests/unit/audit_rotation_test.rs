@@ -309,6 +309,24 @@ async fn cid_rate_limit_exceeded_includes_correlation_id() {
     assert_eq!(parsed["correlation_id"], "cid-rate-005");
 }
 
+#[tokio::test]
+async fn cid_quota_exceeded_includes_correlation_id() {
+    let temp_dir = TempDir::new().unwrap();
+    let audit_path = temp_dir.path().join("cid_quota.log");
+
+    let logger = AuditLogger::new(Some(audit_path.clone()), 0, 0, None);
+
+    logger.log_quota_exceeded_cid("alice", "daily_bandwidth", 0, 0, "cid-quota-006");
+
+    sleep(Duration::from_millis(100)).await;
+
+    let content = tokio::fs::read_to_string(&audit_path).await.unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+
+    assert_eq!(parsed["event_type"], "quota.exceeded");
+    assert_eq!(parsed["correlation_id"], "cid-quota-006");
+}
+
 // ===========================================================================
 // Ban CID events
 // ===========================================================================
@@ -0,0 +1,96 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::proxy::connector::{self, PortBlocked};
+use s5::proxy::retry::RetryPolicy;
+use s5::proxy::ProxyEngine;
+use s5::ssh::handler::classify_relay_error;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// `[security]` with `security`, `groups`, alice in group `mail`, and bob.
+fn config(security: &str, groups: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!("[security]\nip_guard_enabled = false\n{security}\n\n{groups}"),
+        &format!(
+            "group = \"mail\"\n\n[[users]]\nusername = \"bob\"\npassword_hash = \"{FAKE_HASH}\""
+        ),
+    )
+}
+
+async fn connect(
+    engine: &ProxyEngine,
+    auth: &AuthService,
+    user: &str,
+    port: u16,
+) -> anyhow::Result<()> {
+    let user = auth.user_store().get(user).unwrap();
+    engine
+        .connect_for_socks(
+            &user.username,
+            "127.0.0.1",
+            port,
+            &user.acl,
+            &[],
+            user.blocked_ports.as_deref(),
+            None,
+            &[],
+            "203.0.113.9",
+            0,
+            None,
+            RetryPolicy::default(),
+            "c1",
+        )
+        .await
+        .map(|_| ())
+}
+
+#[test]
+fn smtp_and_rdp_are_blocked_by_default() {
+    let cfg = config("", "").unwrap();
+    assert_eq!(cfg.security.blocked_ports, [25, 465, 587, 3389]);
+    assert!(connector::check_port(443, &cfg.security.blocked_ports).is_ok());
+    let err = connector::check_port(25, &cfg.security.blocked_ports).unwrap_err();
+    assert_eq!(err.port, 25);
+    assert!(connector::check_port(25, &[]).is_ok());
+}
+
+#[test]
+fn config_validation() {
+    assert!(config("blocked_ports = []", "").is_ok());
+    assert!(config("blocked_ports = [0]", "").is_err());
+    assert!(config("", "[[groups]]\nname = \"mail\"\nblocked_ports = [0]").is_err());
+    assert!(config("", "[[groups]]\nname = \"mail\"\nblocked_ports = [3389]").is_ok());
+}
+
+#[test]
+fn groups_replace_the_list() {
+    let groups = "[[groups]]\nname = \"relays\"\nblocked_ports = [3389]\n\n\
+                  [[groups]]\nname = \"mail\"\ninherits = \"relays\"\n";
+    let auth = AuthService::new(&config("", groups).unwrap()).unwrap();
+    let users = auth.user_store();
+    assert_eq!(
+        users.get("alice").unwrap().blocked_ports.as_deref(),
+        Some(&[3389][..])
+    );
+    assert_eq!(users.get("bob").unwrap().blocked_ports, None);
+}
+
+#[tokio::test]
+async fn blocked_ports_are_refused_before_connecting() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let groups = "[[groups]]\nname = \"mail\"\nblocked_ports = []\n";
+    let cfg = config(&format!("blocked_ports = [{port}]"), groups).unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+
+    let err = connect(&engine, &auth, "bob", port).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<PortBlocked>().unwrap().port, port);
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+    assert_eq!(engine.active_connections(), 0);
+
+    // The group's empty list lets its members through
+    connect(&engine, &auth, "alice", port).await.unwrap();
+}
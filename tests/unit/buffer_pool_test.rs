@@ -0,0 +1,119 @@
+use crate::test_support::parse_app_config;
+use s5::config::types::AppConfig;
+use s5::proxy::buffer_pool::{self, BufferPool};
+use s5::proxy::forwarder::{self, RelayConfig};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn config_with_pool(pool: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[proxy.buffer_pool]\n{pool}"), "")
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn buffer_pool_defaults() {
+    let pool = config_with_pool("").unwrap().proxy.buffer_pool;
+    assert_eq!(pool.buffer_size, 8192);
+    assert_eq!(pool.max_buffers, 1024);
+}
+
+#[test]
+fn buffer_pool_config_validation() {
+    let config = config_with_pool("buffer_size = 16384\nmax_buffers = 0").unwrap();
+    assert_eq!(config.proxy.buffer_pool.buffer_size, 16384);
+    assert_eq!(config.proxy.buffer_pool.max_buffers, 0);
+
+    assert!(config_with_pool("buffer_size = 512").is_err());
+    assert!(config_with_pool("buffer_size = 2097152").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Pool
+// ---------------------------------------------------------------------------
+
+#[test]
+fn buffers_are_reused() {
+    let pool = BufferPool::new(4096, 4);
+    let ptr = {
+        let mut buf = pool.get();
+        assert_eq!(buf.len(), 4096);
+        buf[0] = 7;
+        buf.as_ptr()
+    };
+    assert_eq!(pool.idle(), 1);
+
+    let buf = pool.get();
+    assert_eq!(buf.as_ptr(), ptr);
+    assert_eq!(pool.idle(), 0);
+}
+
+#[test]
+fn idle_buffers_are_capped() {
+    let pool = BufferPool::new(1024, 2);
+    let bufs: Vec<_> = (0..5).map(|_| pool.get()).collect();
+    assert_eq!(pool.idle(), 0);
+    drop(bufs);
+    assert_eq!(pool.idle(), 2);
+
+    let unpooled = BufferPool::new(1024, 0);
+    drop(unpooled.get());
+    assert_eq!(unpooled.idle(), 0);
+}
+
+#[test]
+fn concurrent_borrowers_get_distinct_buffers() {
+    let pool = BufferPool::new(1024, 64);
+    std::thread::scope(|s| {
+        for i in 0..8u8 {
+            let pool = &pool;
+            s.spawn(move || {
+                for _ in 0..100 {
+                    let mut buf = pool.get();
+                    buf.fill(i);
+                    assert!(buf.iter().all(|b| *b == i));
+                }
+            });
+        }
+    });
+    assert!(pool.idle() <= 8);
+}
+
+#[tokio::test]
+async fn relay_returns_buffers_to_global_pool() {
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let config = RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "test@pool:80".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit: None,
+        session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    };
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        config,
+    ));
+
+    client.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    server.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+    drop(client);
+    drop(server);
+    handle.await.unwrap().unwrap();
+
+    // Other tests share the global pool: only check the relay gave back buffers
+    assert!(buffer_pool::global().idle() >= 1);
+    assert_eq!(buffer_pool::global().buffer_size(), 8192);
+}
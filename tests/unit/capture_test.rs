@@ -0,0 +1,272 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::config::parse_config;
+use s5::proxy::capture::{capture_duration, CaptureEnd, CaptureError, CaptureMode, CaptureSummary};
+use s5::proxy::ProxyEngine;
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn config_toml(capture: &str) -> String {
+    format!(
+        "[server]\nssh_listen = \"0.0.0.0:2222\"\n\n[logging.capture]\n{capture}\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"{FAKE_HASH}\"\n"
+    )
+}
+
+fn engine(dir: &Path) -> ProxyEngine {
+    let capture = format!(
+        "enabled = true\ndirectory = \"{}\"\nmax_duration_secs = 30",
+        dir.display()
+    );
+    let config = parse_config(&config_toml(&capture)).unwrap();
+    ProxyEngine::new(Arc::new(config), Arc::new(AuditLogger::new_noop()))
+}
+
+/// `(block type, body)` of every pcapng block in `bytes`.
+fn blocks(bytes: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut blocks = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let block_type = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+        let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        assert_eq!(len % 4, 0);
+        assert_eq!(rest[len - 4..len], rest[4..8], "trailing block length");
+        blocks.push((block_type, rest[8..len - 4].to_vec()));
+        rest = &rest[len..];
+    }
+    blocks
+}
+
+/// `(captured, original length)` of each Enhanced Packet Block.
+fn packets(bytes: &[u8]) -> Vec<(Vec<u8>, usize)> {
+    blocks(bytes)
+        .into_iter()
+        .filter(|(t, _)| *t == 6)
+        .map(|(_, body)| {
+            let caplen = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+            let origlen = u32::from_le_bytes(body[16..20].try_into().unwrap()) as usize;
+            (body[20..20 + caplen].to_vec(), origlen)
+        })
+        .collect()
+}
+
+async fn capture_session(mode: CaptureMode) -> (tempfile::TempDir, CaptureSummary) {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(dir.path());
+    let session = engine.register_session("alice", "93.184.216.34", 80, "10.0.0.5", "ssh");
+    let info = engine
+        .start_capture(&session.session_id, mode, Some(10), "ops")
+        .unwrap();
+    assert!(info.path.starts_with(dir.path()));
+    assert_eq!(
+        engine.session_capture(&session.session_id).unwrap().mode,
+        mode
+    );
+
+    session.capture.record(true, b"GET / HTTP/1.1\r\n\r\n");
+    session.capture.record(false, b"HTTP/1.1 200 OK\r\n\r\n");
+    let summary = engine
+        .stop_capture(&session.session_id, "ops")
+        .await
+        .unwrap();
+    assert!(engine.session_capture(&session.session_id).is_none());
+    (dir, summary)
+}
+
+// ---------------------------------------------------------------------------
+// pcapng output
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn full_capture_writes_decrypted_payloads() {
+    let (_dir, summary) = capture_session(CaptureMode::Full).await;
+    assert_eq!(summary.reason, CaptureEnd::Stopped);
+    assert_eq!(summary.stopped_by.as_deref(), Some("ops"));
+    assert_eq!(summary.packets, 2);
+    assert_eq!(summary.payload_bytes, 37);
+    assert_eq!(summary.dropped, 0);
+
+    let bytes = std::fs::read(&summary.info.path).unwrap();
+    assert_eq!(bytes.len() as u64, summary.file_bytes);
+    let blocks = blocks(&bytes);
+    assert_eq!(blocks[0].0, 0x0A0D_0D0A);
+    assert_eq!(blocks[0].1[0..4], 0x1A2B_3C4Du32.to_le_bytes());
+    assert_eq!(blocks[1].0, 1);
+    assert_eq!(blocks[1].1[0..2], 101u16.to_le_bytes(), "LINKTYPE_RAW");
+
+    // SYN, SYN-ACK, ACK, then one segment per chunk
+    let packets = packets(&bytes);
+    assert_eq!(packets.len(), 5);
+    let (request, len) = &packets[3];
+    assert_eq!(request.len(), *len);
+    assert_eq!(request[0] >> 4, 4, "IPv4");
+    assert_eq!(request[12..16], [10, 0, 0, 5]);
+    assert_eq!(request[16..20], [93, 184, 216, 34]);
+    assert_eq!(request[22..24], 80u16.to_be_bytes());
+    assert!(request.ends_with(b"GET / HTTP/1.1\r\n\r\n"));
+    let (response, _) = &packets[4];
+    assert_eq!(response[12..16], [93, 184, 216, 34]);
+    assert!(response.ends_with(b"HTTP/1.1 200 OK\r\n\r\n"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&summary.info.path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn headers_mode_keeps_no_payload() {
+    let (_dir, summary) = capture_session(CaptureMode::Headers).await;
+    let bytes = std::fs::read(&summary.info.path).unwrap();
+    let packets = packets(&bytes);
+    let (request, len) = &packets[3];
+    assert_eq!(request.len(), 40, "IPv4 + TCP headers only");
+    assert_eq!(*len, 40 + 18);
+    assert!(!bytes.windows(3).any(|w| w == b"GET"));
+}
+
+// ---------------------------------------------------------------------------
+// Lifecycle
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn session_close_ends_capture() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(dir.path());
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.5", "socks5");
+    engine
+        .start_capture(&session.session_id, CaptureMode::Full, None, "ops")
+        .unwrap();
+    let running = session.capture.current().unwrap();
+
+    engine.unregister_session(&session.session_id);
+    let summary = running.finished().await;
+    assert_eq!(summary.reason, CaptureEnd::SessionClosed);
+    assert!(summary.stopped_by.is_none());
+    assert!(session.capture.current().is_none());
+}
+
+#[tokio::test]
+async fn capture_expires_after_duration() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(dir.path());
+    let session = engine.register_session("alice", "10.1.1.1", 22, "10.0.0.5", "ssh");
+    engine
+        .start_capture(&session.session_id, CaptureMode::Full, Some(1), "ops")
+        .unwrap();
+    let running = session.capture.current().unwrap();
+
+    let summary = tokio::time::timeout(Duration::from_secs(5), running.finished())
+        .await
+        .expect("capture should expire");
+    assert_eq!(summary.reason, CaptureEnd::Expired);
+    // Chunks relayed afterwards are not recorded
+    session.capture.record(true, b"late");
+    assert_eq!(running.summary().unwrap().packets, 0);
+}
+
+#[tokio::test]
+async fn start_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(dir.path());
+    let session = engine.register_session("alice", "10.1.1.1", 22, "10.0.0.5", "ssh");
+    let id = session.session_id.as_str();
+
+    assert!(matches!(
+        engine.start_capture("nope", CaptureMode::Full, None, "ops"),
+        Err(CaptureError::SessionNotFound)
+    ));
+    assert!(matches!(
+        engine.start_capture(id, CaptureMode::Full, Some(31), "ops"),
+        Err(CaptureError::InvalidDuration(30))
+    ));
+    engine
+        .start_capture(id, CaptureMode::Full, None, "ops")
+        .unwrap();
+    assert!(matches!(
+        engine.start_capture(id, CaptureMode::Headers, None, "ops"),
+        Err(CaptureError::AlreadyRunning)
+    ));
+
+    // A new capture may start once the previous one is stopped
+    engine.stop_capture(id, "ops").await.unwrap();
+    assert!(engine.stop_capture(id, "ops").await.is_none());
+    engine
+        .start_capture(id, CaptureMode::Headers, Some(5), "ops")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn disabled_by_default() {
+    let config = parse_config(&config_toml("")).unwrap();
+    assert!(!config.logging.capture.enabled);
+    let engine = ProxyEngine::new(Arc::new(config), Arc::new(AuditLogger::new_noop()));
+    let session = engine.register_session("alice", "10.1.1.1", 22, "10.0.0.5", "ssh");
+    assert!(matches!(
+        engine.start_capture(&session.session_id, CaptureMode::Full, None, "ops"),
+        Err(CaptureError::Disabled)
+    ));
+}
+
+// ---------------------------------------------------------------------------
+// Configuration and audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn duration_defaults_and_bounds() {
+    let mut config = parse_config(&config_toml("")).unwrap().logging.capture;
+    assert_eq!(config.max_duration_secs, 300);
+    assert_eq!(config.max_size_mb, 100);
+    assert_eq!(
+        capture_duration(None, &config).unwrap(),
+        Duration::from_secs(60)
+    );
+    assert!(capture_duration(Some(0), &config).is_err());
+    assert!(capture_duration(Some(301), &config).is_err());
+
+    config.max_duration_secs = 20;
+    assert_eq!(
+        capture_duration(None, &config).unwrap(),
+        Duration::from_secs(20)
+    );
+}
+
+#[test]
+fn config_validation() {
+    let with = |extra: &str| parse_config(&config_toml(&format!("enabled = true\n{extra}")));
+    assert!(with("").is_ok());
+    assert!(with("max_duration_secs = 0").is_err());
+    assert!(with("max_duration_secs = 3601").is_err());
+    assert!(with("max_size_mb = 0").is_err());
+    assert!(with("directory = \"\"").is_err());
+    // Limits are only checked when capture is on
+    assert!(parse_config(&config_toml("max_size_mb = 0")).is_ok());
+}
+
+#[tokio::test]
+async fn audit_events_are_critical() {
+    let (_dir, summary) = capture_session(CaptureMode::Headers).await;
+    let started = AuditEvent::capture_started(&summary.info, 10);
+    assert_eq!(started.event_type(), "capture.started");
+    assert!(started.is_critical());
+    let json = serde_json::to_value(&started).unwrap();
+    assert_eq!(json["mode"], "headers");
+    assert_eq!(json["started_by"], "ops");
+
+    let stopped = AuditEvent::capture_stopped(&summary);
+    assert_eq!(stopped.event_type(), "capture.stopped");
+    assert!(stopped.is_critical());
+    let json = serde_json::to_value(&stopped).unwrap();
+    assert_eq!(json["reason"], "stopped");
+    assert_eq!(json["stopped_by"], "ops");
+    assert_eq!(json["packets"], 2);
+}
@@ -0,0 +1,203 @@
+use crate::test_support::parse_app_config;
+use prometheus_client::encoding::text::encode;
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::acl::ParsedAcl;
+use s5::config::types::{AppConfig, CircuitBreakerConfig};
+use s5::metrics::MetricsRegistry;
+use s5::proxy::circuit::{is_target_failure, CircuitBreaker};
+use s5::proxy::retry::RetryPolicy;
+use s5::proxy::ProxyEngine;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+const KEY: &str = "down.example:443";
+
+fn breaker(failure_threshold: u32, open_secs: u64) -> CircuitBreaker {
+    CircuitBreaker::new(CircuitBreakerConfig {
+        enabled: true,
+        failure_threshold,
+        window_secs: 60,
+        open_secs,
+    })
+}
+
+fn config_with(section: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!("[security]\nip_guard_enabled = false\n\n[proxy.circuit_breaker]\n{section}"),
+        "",
+    )
+}
+
+// ---------------------------------------------------------------------------
+// State machine
+// ---------------------------------------------------------------------------
+
+#[test]
+fn circuit_opens_after_threshold() {
+    let cb = breaker(3, 30);
+    assert!(!cb.record_failure(KEY));
+    assert!(!cb.record_failure(KEY));
+    assert!(cb.check(KEY).is_ok());
+    assert!(cb.record_failure(KEY));
+
+    let retry_in = cb.check(KEY).unwrap_err();
+    assert!(retry_in > Duration::from_secs(25), "{retry_in:?}");
+    assert_eq!(cb.open_count(), 1);
+    // Other destinations are unaffected
+    assert!(cb.check("up.example:443").is_ok());
+}
+
+#[test]
+fn success_resets_failure_count() {
+    let cb = breaker(2, 30);
+    assert!(!cb.record_failure(KEY));
+    cb.record_success(KEY);
+    assert!(!cb.record_failure(KEY));
+    assert!(cb.check(KEY).is_ok());
+}
+
+#[test]
+fn half_open_lets_one_probe_through() {
+    let cb = breaker(1, 1);
+    assert!(cb.record_failure(KEY));
+    assert!(cb.check(KEY).is_err());
+    std::thread::sleep(Duration::from_millis(1100));
+
+    // One probe, the others keep failing fast
+    assert!(cb.check(KEY).is_ok());
+    assert_eq!(cb.check(KEY), Err(Duration::ZERO));
+
+    // A failed probe opens the circuit again
+    assert!(cb.record_failure(KEY));
+    assert!(cb.check(KEY).unwrap_err() > Duration::from_millis(500));
+}
+
+#[test]
+fn successful_probe_closes_circuit() {
+    let cb = breaker(1, 1);
+    cb.record_failure(KEY);
+    std::thread::sleep(Duration::from_millis(1100));
+    assert!(cb.check(KEY).is_ok());
+    cb.record_success(KEY);
+    assert!(cb.check(KEY).is_ok());
+    assert!(cb.check(KEY).is_ok());
+    assert_eq!(cb.open_count(), 0);
+}
+
+#[test]
+fn released_probe_can_be_retried() {
+    let cb = breaker(1, 1);
+    cb.record_failure(KEY);
+    std::thread::sleep(Duration::from_millis(1100));
+    assert!(cb.check(KEY).is_ok());
+    cb.release(KEY);
+    assert!(cb.check(KEY).is_ok());
+}
+
+#[test]
+fn disabled_breaker_never_opens() {
+    let cb = CircuitBreaker::new(CircuitBreakerConfig::default());
+    assert!(!cb.is_enabled());
+    for _ in 0..20 {
+        assert!(!cb.record_failure(KEY));
+    }
+    assert!(cb.check(KEY).is_ok());
+}
+
+#[test]
+fn only_target_errors_count() {
+    let refused = anyhow::anyhow!(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+    assert!(is_target_failure(&refused));
+    assert!(!is_target_failure(&anyhow::anyhow!("ACL denied: x:1")));
+    assert!(!is_target_failure(&anyhow::anyhow!(
+        "all resolved addresses for x are blocked by ip_guard"
+    )));
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn circuit_breaker_config() {
+    let config = config_with("").unwrap();
+    assert!(!config.proxy.circuit_breaker.enabled);
+    assert_eq!(config.proxy.circuit_breaker.failure_threshold, 5);
+
+    let config = config_with("enabled = true\nfailure_threshold = 2\nopen_secs = 10").unwrap();
+    assert_eq!(config.proxy.circuit_breaker.open_secs, 10);
+    assert_eq!(config.proxy.circuit_breaker.window_secs, 60);
+
+    assert!(config_with("enabled = true\nfailure_threshold = 0").is_err());
+    assert!(config_with("enabled = true\nopen_secs = 0").is_err());
+    // Not validated while disabled
+    assert!(config_with("failure_threshold = 0").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Engine
+// ---------------------------------------------------------------------------
+
+async fn connect(engine: &ProxyEngine, acl: &ParsedAcl, port: u16) -> anyhow::Result<()> {
+    engine
+        .connect_for_socks(
+            "alice",
+            "127.0.0.1",
+            port,
+            acl,
+            &[],
+            None,
+            None,
+            &[],
+            "203.0.113.9",
+            0,
+            None,
+            RetryPolicy::default(),
+            "c1",
+        )
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn engine_fails_fast_once_circuit_is_open() {
+    let config = Arc::new(config_with("enabled = true\nfailure_threshold = 2").unwrap());
+    let audit = Arc::new(AuditLogger::new(None, 0, 0, None));
+    let mut engine = ProxyEngine::new(config.clone(), audit);
+    let metrics = Arc::new(MetricsRegistry::new());
+    engine.set_metrics(metrics.clone());
+    let auth = AuthService::new(&config).unwrap();
+    let alice = auth.user_store().get("alice").unwrap().clone();
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    for _ in 0..2 {
+        let err = connect(&engine, &alice.acl, port).await.unwrap_err();
+        assert!(!err.to_string().contains("circuit open"), "{err}");
+    }
+    let start = Instant::now();
+    let err = connect(&engine, &alice.acl, port).await.unwrap_err();
+    assert!(err.to_string().contains("circuit open"), "{err}");
+    assert!(start.elapsed() < Duration::from_millis(100));
+    // The connection slot is released
+    assert_eq!(engine.active_connections(), 0);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    for name in [
+        "s5_circuit_breaker_opened_total",
+        "s5_circuit_breaker_rejected_total",
+    ] {
+        let line = buffer
+            .lines()
+            .find(|l| l.starts_with(name))
+            .unwrap_or_else(|| panic!("{name} missing"));
+        assert!(line.ends_with(" 1"), "{line}");
+    }
+}
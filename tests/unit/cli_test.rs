@@ -1,5 +1,5 @@
 use clap::Parser;
-use s5::cli::{Cli, Command};
+use s5::cli::{Cli, Command, CtlCommand, ServiceCommand};
 
 // ---------------------------------------------------------------------------
 // Test 1: Default config path is "config.toml"
@@ -365,3 +365,117 @@ fn init_short_output_flag() {
         _ => panic!("expected Init command"),
     }
 }
+
+// ---------------------------------------------------------------------------
+// Test 16: verify-audit takes files oldest first and an optional key
+// ---------------------------------------------------------------------------
+#[test]
+fn verify_audit_files_and_key() {
+    let cli = Cli::try_parse_from([
+        "s5",
+        "verify-audit",
+        "audit.json.1",
+        "audit.json",
+        "--key",
+        "0123456789abcdef",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::VerifyAudit { files, key }) => {
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].to_str().unwrap(), "audit.json.1");
+            assert_eq!(key.as_deref(), Some("0123456789abcdef"));
+        }
+        _ => panic!("expected VerifyAudit command"),
+    }
+    assert!(Cli::try_parse_from(["s5", "verify-audit"]).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 17: ctl takes connection flags before the action
+// ---------------------------------------------------------------------------
+#[test]
+fn ctl_actions_parse() {
+    let cli = Cli::try_parse_from([
+        "s5",
+        "ctl",
+        "--api-addr",
+        "unix:/run/s5/api.sock",
+        "--json",
+        "ban",
+        "203.0.113.7",
+        "--duration",
+        "600",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Ctl {
+            api_addr,
+            json,
+            action,
+            ..
+        }) => {
+            assert_eq!(api_addr.as_deref(), Some("unix:/run/s5/api.sock"));
+            assert!(json);
+            match action {
+                CtlCommand::Ban { ip, duration } => {
+                    assert_eq!(ip, "203.0.113.7");
+                    assert_eq!(duration, Some(600));
+                }
+                other => panic!("expected ban, got {:?}", other),
+            }
+        }
+        _ => panic!("expected Ctl command"),
+    }
+    for action in ["status", "sessions", "bans", "reload"] {
+        assert!(
+            Cli::try_parse_from(["s5", "ctl", action]).is_ok(),
+            "{action}"
+        );
+    }
+    assert!(Cli::try_parse_from(["s5", "ctl", "kick"]).is_err());
+    assert!(Cli::try_parse_from(["s5", "ctl"]).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 18: service install/uninstall take a name; run is hidden but parses
+// ---------------------------------------------------------------------------
+#[test]
+fn service_actions_parse() {
+    let cli = Cli::try_parse_from(["s5", "service", "install", "--name", "s5-edge"]).unwrap();
+    match cli.command {
+        Some(Command::Service {
+            action: ServiceCommand::Install { name },
+        }) => assert_eq!(name, "s5-edge"),
+        other => panic!("expected service install, got {:?}", other),
+    }
+    let cli = Cli::try_parse_from(["s5", "-c", "C:/s5/config.toml", "service", "run"]).unwrap();
+    assert_eq!(cli.config, std::path::PathBuf::from("C:/s5/config.toml"));
+    match cli.command {
+        Some(Command::Service {
+            action: ServiceCommand::Run { name },
+        }) => assert_eq!(name, "s5"),
+        other => panic!("expected service run, got {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["s5", "service", "uninstall"]).is_ok());
+    assert!(Cli::try_parse_from(["s5", "service"]).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 19: update-intel writes to stdout unless given an output file
+// ---------------------------------------------------------------------------
+#[test]
+fn update_intel_output_is_optional() {
+    let cli = Cli::try_parse_from(["s5", "update-intel", "-o", "ip-intel.txt"]).unwrap();
+    match cli.command {
+        Some(Command::UpdateIntel { output }) => {
+            assert_eq!(output.unwrap().to_str().unwrap(), "ip-intel.txt");
+        }
+        _ => panic!("expected UpdateIntel command"),
+    }
+    let cli = Cli::try_parse_from(["s5", "update-intel"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::UpdateIntel { output: None })
+    ));
+}
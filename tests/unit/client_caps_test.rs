@@ -0,0 +1,262 @@
+use crate::test_support::parse_app_config;
+use prometheus_client::encoding::text::encode;
+use s5::config::types::AppConfig;
+use s5::metrics::MetricsRegistry;
+use s5::proxy::client_caps::{CapRejection, ClientCaps, Listener};
+use s5::ssh::reject::{disconnect_packet, refuse, DISCONNECT_TOO_MANY_CONNECTIONS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+fn caps(max: u32, per_user: u32, queue_ms: u64) -> Arc<ClientCaps> {
+    Arc::new(ClientCaps::new(
+        max,
+        per_user,
+        Duration::from_millis(queue_ms),
+    ))
+}
+
+/// `extra` keys in `[server]`.
+fn config_with_server(extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(extra, "")
+}
+
+// ---------------------------------------------------------------------------
+// Global cap
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unlimited_by_default() {
+    let config = config_with_server("").unwrap();
+    assert_eq!(config.server.max_connections, 0);
+    assert_eq!(config.server.max_connections_per_user, 0);
+    assert_eq!(config.server.connection_queue_timeout_ms, 0);
+    assert_eq!(config.server.connection_queue_size, 0);
+
+    let caps = Arc::new(ClientCaps::from_config(&config));
+    let mut slots = Vec::new();
+    for _ in 0..50 {
+        slots.push(caps.acquire(Listener::Ssh).await.unwrap());
+    }
+    let snap = caps.snapshot();
+    assert_eq!(snap.active, 50);
+    assert_eq!(snap.saturation, 0.0);
+}
+
+#[tokio::test]
+async fn global_cap_refuses_without_queue() {
+    let caps = caps(2, 0, 0);
+    let _a = caps.acquire(Listener::Ssh).await.unwrap();
+    let b = caps.acquire(Listener::Socks5).await.unwrap();
+    assert_eq!(
+        caps.acquire(Listener::Ssh).await.err(),
+        Some(CapRejection::Global)
+    );
+
+    drop(b);
+    assert!(caps.acquire(Listener::Socks5).await.is_ok());
+}
+
+#[tokio::test]
+async fn queued_client_gets_freed_slot() {
+    let caps = caps(1, 0, 2000);
+    let first = caps.acquire(Listener::Ssh).await.unwrap();
+
+    let waiter = {
+        let caps = caps.clone();
+        tokio::spawn(async move { caps.acquire(Listener::Socks5).await.is_ok() })
+    };
+    while caps.snapshot().queued == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    drop(first);
+
+    assert!(waiter.await.unwrap());
+    assert_eq!(caps.snapshot().queued, 0);
+}
+
+#[tokio::test]
+async fn queued_client_refused_after_timeout() {
+    let caps = caps(1, 0, 50);
+    let _first = caps.acquire(Listener::Ssh).await.unwrap();
+
+    let start = std::time::Instant::now();
+    assert_eq!(
+        caps.acquire(Listener::Ssh).await.err(),
+        Some(CapRejection::Global)
+    );
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(caps.snapshot().queued, 0);
+}
+
+#[tokio::test]
+async fn full_queue_refuses_at_once() {
+    // One slot, so at most one client waits
+    let caps = caps(1, 0, 5000);
+    let _first = caps.acquire(Listener::Ssh).await.unwrap();
+    let _waiter = {
+        let caps = caps.clone();
+        tokio::spawn(async move { caps.acquire(Listener::Ssh).await.is_ok() })
+    };
+    while caps.snapshot().queued == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let start = std::time::Instant::now();
+    assert!(caps.acquire(Listener::Ssh).await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn queue_size_bounds_waiting_clients() {
+    let metrics = MetricsRegistry::new();
+    let caps = Arc::new(
+        ClientCaps::new(1, 0, Duration::from_millis(100))
+            .with_queue_size(2)
+            .with_metrics(&metrics),
+    );
+    assert_eq!(caps.snapshot().queue_size, 2);
+    let _first = caps.acquire(Listener::Ssh).await.unwrap();
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            let caps = caps.clone();
+            tokio::spawn(async move { caps.acquire(Listener::Ssh).await.is_ok() })
+        })
+        .collect();
+    while caps.snapshot().queued < 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // A third waiter does not fit
+    let start = std::time::Instant::now();
+    assert!(caps.acquire(Listener::Ssh).await.is_err());
+    assert!(start.elapsed() < Duration::from_millis(100));
+    for waiter in waiters {
+        assert!(!waiter.await.unwrap());
+    }
+
+    let mut out = String::new();
+    encode(&mut out, &metrics.registry).unwrap();
+    assert!(
+        out.contains("s5_connections_shed_total{reason=\"queue_full\"} 1"),
+        "{out}"
+    );
+    assert!(out.contains("s5_connections_shed_total{reason=\"queue_timeout\"} 2"));
+}
+
+// ---------------------------------------------------------------------------
+// Per-user cap
+// ---------------------------------------------------------------------------
+
+#[test]
+fn per_user_cap_counts_each_user() {
+    let caps = caps(0, 2, 0);
+    let a1 = caps.try_acquire_user("alice").unwrap();
+    let _a2 = caps.try_acquire_user("alice").unwrap();
+    assert_eq!(
+        caps.try_acquire_user("alice").err(),
+        Some(CapRejection::PerUser)
+    );
+    assert!(caps.try_acquire_user("bob").is_ok());
+    assert_eq!(caps.user_connections("alice"), 2);
+
+    drop(a1);
+    assert_eq!(caps.user_connections("alice"), 1);
+    assert!(caps.try_acquire_user("alice").is_ok());
+}
+
+#[test]
+fn user_entry_dropped_with_last_slot() {
+    let caps = caps(0, 0, 0);
+    let slot = caps.try_acquire_user("alice").unwrap();
+    drop(slot);
+    assert_eq!(caps.user_connections("alice"), 0);
+}
+
+// ---------------------------------------------------------------------------
+// Snapshot, metrics and config
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn snapshot_reports_saturation_per_listener() {
+    let caps = caps(4, 1, 0);
+    let _a = caps.acquire(Listener::Ssh).await.unwrap();
+    let _b = caps.acquire(Listener::Ssh).await.unwrap();
+    let _c = caps.acquire(Listener::Socks5).await.unwrap();
+
+    let snap = caps.snapshot();
+    assert_eq!((snap.ssh, snap.socks5, snap.active), (2, 1, 3));
+    assert_eq!(snap.max, 4);
+    assert_eq!(snap.max_per_user, 1);
+    assert!((snap.saturation - 0.75).abs() < f64::EPSILON);
+}
+
+#[tokio::test]
+async fn gauges_follow_slots() {
+    let metrics = MetricsRegistry::new();
+    let caps = Arc::new(ClientCaps::new(0, 0, Duration::ZERO).with_metrics(&metrics));
+    let slot = caps.acquire(Listener::Socks5).await.unwrap();
+
+    let mut out = String::new();
+    encode(&mut out, &metrics.registry).unwrap();
+    assert!(
+        out.lines()
+            .any(|l| l.starts_with("s5_client_connections{protocol=\"socks5\"} 1")),
+        "{out}"
+    );
+
+    drop(slot);
+    let mut out = String::new();
+    encode(&mut out, &metrics.registry).unwrap();
+    assert!(out
+        .lines()
+        .any(|l| l.starts_with("s5_client_connections{protocol=\"socks5\"} 0")));
+}
+
+#[test]
+fn invalid_caps_rejected() {
+    assert!(config_with_server("max_connections = 10\nmax_connections_per_user = 20").is_err());
+    assert!(config_with_server("connection_queue_timeout_ms = 600000").is_err());
+    // Per-user cap alone is fine
+    assert!(config_with_server("max_connections_per_user = 20").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// SSH refusal
+// ---------------------------------------------------------------------------
+
+#[test]
+fn disconnect_packet_is_well_formed() {
+    let packet = disconnect_packet(DISCONNECT_TOO_MANY_CONNECTIONS, "too many connections");
+    let packet_length = u32::from_be_bytes(packet[..4].try_into().unwrap()) as usize;
+    assert_eq!(packet.len(), 4 + packet_length);
+    assert_eq!(packet.len() % 8, 0);
+
+    let padding = packet[4] as usize;
+    assert!(padding >= 4);
+    let payload = &packet[5..packet.len() - padding];
+    assert_eq!(payload[0], 1, "SSH_MSG_DISCONNECT");
+    assert_eq!(u32::from_be_bytes(payload[1..5].try_into().unwrap()), 12);
+    let desc_len = u32::from_be_bytes(payload[5..9].try_into().unwrap()) as usize;
+    assert_eq!(&payload[9..9 + desc_len], b"too many connections");
+    assert_eq!(
+        &payload[9 + desc_len..],
+        &[0, 0, 0, 0],
+        "empty language tag"
+    );
+}
+
+#[tokio::test]
+async fn refuse_sends_identification_then_disconnect() {
+    let (server, mut client) = tokio::io::duplex(4096);
+    let task = tokio::spawn(refuse(server, "SSH-2.0-s5", 12, "too many connections"));
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).await.unwrap();
+    drop(client);
+    task.await.unwrap();
+
+    let expected_packet = disconnect_packet(12, "too many connections");
+    assert!(received.starts_with(b"SSH-2.0-s5\r\n"));
+    assert_eq!(&received[b"SSH-2.0-s5\r\n".len()..], &expected_packet[..]);
+}
@@ -0,0 +1,738 @@
+use s5::audit::AuditLogger;
+use s5::cluster::gossip::{BanRecord, GossipState, SharedCounter};
+use s5::cluster::redis::{self, RedisAddr, RedisConnection, Reply};
+use s5::cluster::{BanMap, BanSync, Cluster, CounterUpdate, SyncState};
+use s5::config::parse_config;
+use s5::config::types::{AppConfig, LimitsConfig};
+use s5::proxy::ProxyEngine;
+use s5::quota::{QuotaCounter, QuotaResult, QuotaTracker, UsageCounters};
+use s5::security::ban::BanKey;
+use s5::security::SecurityManager;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+const CONFIG: &str = r#"[server]
+ssh_listen = "0.0.0.0:2222"
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+"#;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn key(s: &str) -> BanKey {
+    s.parse().unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Redis client
+// ---------------------------------------------------------------------------
+
+#[test]
+fn redis_urls() {
+    let addr = RedisAddr::parse("redis://10.0.0.5").unwrap();
+    assert_eq!(
+        addr,
+        RedisAddr {
+            host: "10.0.0.5".to_string(),
+            port: 6379,
+            tls: false,
+            db: 0,
+        }
+    );
+    let addr = RedisAddr::parse("rediss://cache.example.com:6380/2").unwrap();
+    assert!(addr.tls);
+    assert_eq!((addr.port, addr.db), (6380, 2));
+    assert_eq!(RedisAddr::parse("redis://[::1]:7000").unwrap().host, "::1");
+
+    for url in [
+        "http://10.0.0.5",
+        "redis://:secret@10.0.0.5",
+        "redis://10.0.0.5/db",
+        "redis:///0",
+        "10.0.0.5:6379",
+    ] {
+        assert!(RedisAddr::parse(url).is_err(), "{url:?} should be refused");
+    }
+}
+
+#[tokio::test]
+async fn pipelined_replies_stay_in_order() {
+    let (client, server) = tokio::io::duplex(4096);
+    let server = tokio::spawn(async move {
+        let mut server = BufReader::new(server);
+        let mut request = vec![0u8; redis::encode(&redis::cmd(&["PING"])).len() * 5];
+        server.read_exact(&mut request).await.unwrap();
+        server
+            .get_mut()
+            .write_all(
+                b"+PONG\r\n:42\r\n$-1\r\n*2\r\n$3\r\nk\r\n\r\n$1\r\nv\r\n-ERR wrong type\r\n",
+            )
+            .await
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let mut conn = RedisConnection::new(client, Duration::from_secs(5));
+    let ping = redis::cmd(&["PING"]);
+    let replies = conn.pipeline(&vec![ping; 5]).await.unwrap();
+    assert_eq!(
+        replies,
+        [
+            Reply::Text("PONG".to_string()),
+            Reply::Int(42),
+            Reply::Nil,
+            Reply::Array(vec![
+                Reply::Text("k\r\n".to_string()),
+                Reply::Text("v".to_string())
+            ]),
+            Reply::Error("ERR wrong type".to_string()),
+        ]
+    );
+    assert_eq!(server.await.unwrap(), "*1\r\n$4\r\nPING\r\n".repeat(5));
+}
+
+#[tokio::test]
+async fn error_reply_fails_a_query() {
+    let (client, mut server) = tokio::io::duplex(1024);
+    tokio::spawn(async move {
+        server
+            .write_all(b"-WRONGPASS invalid password\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0u8; 256];
+        let _ = server.read(&mut buf).await;
+    });
+    let mut conn = RedisConnection::new(client, Duration::from_secs(5));
+    let err = conn.query(redis::cmd(&["AUTH", "nope"])).await.unwrap_err();
+    assert!(err.to_string().contains("WRONGPASS"), "{err}");
+}
+
+#[tokio::test]
+async fn empty_reply_line_is_an_error() {
+    let (client, mut server) = tokio::io::duplex(1024);
+    tokio::spawn(async move {
+        server.write_all(b"\r\n").await.unwrap();
+        let mut buf = [0u8; 256];
+        let _ = server.read(&mut buf).await;
+    });
+    let mut conn = RedisConnection::new(client, Duration::from_secs(5));
+    let err = conn.query(redis::cmd(&["PING"])).await.unwrap_err();
+    assert!(err.to_string().contains("empty Redis reply line"), "{err}");
+}
+
+// ---------------------------------------------------------------------------
+// Merging
+// ---------------------------------------------------------------------------
+
+#[test]
+fn bans_reconcile_both_ways() {
+    let now = 1_000_000;
+    let mut sync = BanSync::default();
+    let local: BanMap = [(key("10.0.0.1"), now + 600)].into();
+    let shared: BanMap = [(key("10.0.0.2"), now + 300), (key("10.0.0.3"), now - 1)].into();
+
+    let changes = sync.reconcile(&local, &shared, now);
+    assert_eq!(changes.publish, [(key("10.0.0.1"), now + 600)]);
+    assert_eq!(changes.apply, [(key("10.0.0.2"), now + 300)]);
+    // Expired in the shared hash
+    assert_eq!(changes.withdraw, [key("10.0.0.3")]);
+    assert!(changes.lift.is_empty());
+    sync.commit(&shared, &changes);
+
+    // 10.0.0.1 lifted elsewhere, 10.0.0.2 lifted here
+    let local: BanMap = [(key("10.0.0.1"), now + 600)].into();
+    let shared: BanMap = BanMap::new();
+    let changes = sync.reconcile(&local, &shared, now);
+    assert_eq!(changes.lift, [key("10.0.0.1")]);
+    assert!(changes.publish.is_empty());
+
+    let local = BanMap::new();
+    let shared: BanMap = [(key("10.0.0.2"), now + 300)].into();
+    let mut sync = BanSync::default();
+    sync.commit(&shared, &Default::default());
+    let changes = sync.reconcile(&local, &shared, now);
+    assert_eq!(changes.withdraw, [key("10.0.0.2")]);
+
+    // Banned again for longer on one side
+    let local: BanMap = [(key("10.0.0.2"), now + 900)].into();
+    let changes = sync.reconcile(&local, &shared, now);
+    assert_eq!(changes.publish, [(key("10.0.0.2"), now + 900)]);
+    let local: BanMap = [(key("10.0.0.2"), now + 100)].into();
+    let changes = sync.reconcile(&local, &shared, now);
+    assert_eq!(changes.apply, [(key("10.0.0.2"), now + 300)]);
+}
+
+#[test]
+fn counters_add_growth_or_overwrite_resets() {
+    assert_eq!(CounterUpdate::new(150, 100), CounterUpdate::Add(50));
+    assert_eq!(CounterUpdate::new(100, 100), CounterUpdate::Add(0));
+    assert_eq!(CounterUpdate::new(0, 100), CounterUpdate::Set(0));
+}
+
+#[test]
+fn rebase_keeps_traffic_recorded_meanwhile() {
+    let tracker = QuotaTracker::new(&LimitsConfig::default());
+    assert!(matches!(
+        tracker.record_bytes("alice", 100, 0, 0, None),
+        QuotaResult::Ok(_)
+    ));
+    let read = tracker.get_user_usage("alice").counters();
+    // Relayed while the sync was in flight
+    assert!(matches!(
+        tracker.record_bytes("alice", 5, 0, 0, None),
+        QuotaResult::Ok(_)
+    ));
+    let shared = UsageCounters {
+        daily_bytes: 1000,
+        monthly_bytes: 2000,
+        total_bytes: 3000,
+        daily_connections: 7,
+        monthly_connections: 8,
+    };
+    tracker.rebase_user_usage("alice", &read, &shared);
+    let usage = tracker.get_user_usage("alice");
+    assert_eq!(usage.daily_bytes, 1005);
+    assert_eq!(usage.monthly_bytes, 2005);
+    assert_eq!(usage.total_bytes, 3005);
+    assert_eq!(usage.daily_connections, 7);
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn cluster_config_validated_and_redacted() {
+    let cases = [
+        ("url = \"redis://:pw@10.0.0.5\"", "cluster.url"),
+        ("url = \"memcached://10.0.0.5\"", "unsupported scheme"),
+        ("sync_interval_secs = 0", "sync_interval_secs"),
+        ("key_prefix = \"\"", "key_prefix"),
+        ("username = \"s5\"", "requires cluster.password"),
+    ];
+    for (line, expected) in cases {
+        let config = format!("{CONFIG}\n[cluster]\nenabled = true\n{line}\n");
+        let err = parse_config(&config).unwrap_err();
+        assert!(format!("{err:#}").contains(expected), "{line}: {err:#}");
+    }
+
+    // Not checked while disabled
+    parse_config(&format!("{CONFIG}\n[cluster]\nurl = \"nope\"\n")).unwrap();
+
+    let config = parse_config(&format!(
+        "{CONFIG}\n[cluster]\nenabled = true\nurl = \"redis://10.0.0.5\"\n\
+         username = \"s5\"\npassword = \"hunter2\"\n"
+    ))
+    .unwrap();
+    assert_eq!(config.cluster.key_prefix, "s5:");
+    assert_eq!(config.cluster.sync_interval_secs, 5);
+    assert!(!format!("{:?}", config.cluster).contains("hunter2"));
+    let redacted = s5::config::redact::redact_config(&config);
+    assert_eq!(redacted.cluster.password.as_deref(), Some("***"));
+}
+
+// ---------------------------------------------------------------------------
+// Sync between two nodes
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FakeRedis {
+    hashes: HashMap<String, HashMap<String, String>>,
+    strings: HashMap<String, String>,
+    sets: HashMap<String, BTreeSet<String>>,
+}
+
+impl FakeRedis {
+    fn run(&mut self, args: &[String]) -> String {
+        let bulk = |s: &str| format!("${}\r\n{}\r\n", s.len(), s);
+        let array = |items: Vec<String>| format!("*{}\r\n{}", items.len(), items.concat());
+        match args[0].as_str() {
+            "HGETALL" => {
+                let hash = self.hashes.get(&args[1]).cloned().unwrap_or_default();
+                array(hash.iter().flat_map(|(k, v)| [bulk(k), bulk(v)]).collect())
+            }
+            "HGET" => match self.hashes.get(&args[1]).and_then(|h| h.get(&args[2])) {
+                Some(v) => bulk(v),
+                None => "$-1\r\n".to_string(),
+            },
+            "HSET" => {
+                let hash = self.hashes.entry(args[1].clone()).or_default();
+                hash.insert(args[2].clone(), args[3].clone());
+                ":1\r\n".to_string()
+            }
+            "HDEL" => {
+                let hash = self.hashes.entry(args[1].clone()).or_default();
+                format!(":{}\r\n", hash.remove(&args[2]).is_some() as u8)
+            }
+            "HINCRBY" => {
+                let hash = self.hashes.entry(args[1].clone()).or_default();
+                let value = hash
+                    .entry(args[2].clone())
+                    .or_insert_with(|| "0".to_string());
+                let n = value.parse::<i64>().unwrap() + args[3].parse::<i64>().unwrap();
+                *value = n.to_string();
+                format!(":{}\r\n", n)
+            }
+            "SET" => {
+                self.strings.insert(args[1].clone(), args[2].clone());
+                "+OK\r\n".to_string()
+            }
+            "DEL" => {
+                format!(":{}\r\n", self.strings.remove(&args[1]).is_some() as u8)
+            }
+            "MGET" => array(
+                args[1..]
+                    .iter()
+                    .map(|k| match self.strings.get(k) {
+                        Some(v) => bulk(v),
+                        None => "$-1\r\n".to_string(),
+                    })
+                    .collect(),
+            ),
+            "SADD" | "SREM" => {
+                let set = self.sets.entry(args[1].clone()).or_default();
+                for member in &args[2..] {
+                    if args[0] == "SADD" {
+                        set.insert(member.clone());
+                    } else {
+                        set.remove(member);
+                    }
+                }
+                ":1\r\n".to_string()
+            }
+            "SMEMBERS" => {
+                let set = self.sets.get(&args[1]).cloned().unwrap_or_default();
+                array(set.iter().map(|m| bulk(m)).collect())
+            }
+            "EXPIRE" => ":1\r\n".to_string(),
+            other => format!("-ERR unknown command {}\r\n", other),
+        }
+    }
+}
+
+/// A Redis stand-in on a local port, enough for the cluster sync.
+async fn start_fake_redis() -> (u16, Arc<Mutex<FakeRedis>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let db = Arc::new(Mutex::new(FakeRedis::default()));
+    let shared = db.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let db = shared.clone();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                        break;
+                    }
+                    let n: usize = line.trim_end()[1..].parse().unwrap();
+                    let mut args = Vec::with_capacity(n);
+                    for _ in 0..n {
+                        line.clear();
+                        stream.read_line(&mut line).await.unwrap();
+                        let len: usize = line.trim_end()[1..].parse().unwrap();
+                        let mut data = vec![0u8; len + 2];
+                        stream.read_exact(&mut data).await.unwrap();
+                        data.truncate(len);
+                        args.push(String::from_utf8(data).unwrap());
+                    }
+                    let reply = db.lock().unwrap().run(&args);
+                    stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                }
+            });
+        }
+    });
+    (port, db)
+}
+
+struct Node {
+    cluster: Arc<Cluster>,
+    security: RwLock<SecurityManager>,
+    tracker: QuotaTracker,
+    engine: ProxyEngine,
+    state: SyncState,
+}
+
+impl Node {
+    /// A node with `[cluster] enabled = true` and `cluster` (more fields).
+    fn new(cluster: &str) -> Self {
+        let config: AppConfig =
+            parse_config(&format!("{CONFIG}\n[cluster]\nenabled = true\n{cluster}\n")).unwrap();
+        Self {
+            cluster: Cluster::new(&config).unwrap(),
+            security: RwLock::new(SecurityManager::new(&config)),
+            tracker: QuotaTracker::new(&config.limits),
+            engine: ProxyEngine::new(Arc::new(config), Arc::new(AuditLogger::new_noop())),
+            state: SyncState::default(),
+        }
+    }
+
+    async fn sync(&mut self) {
+        self.cluster
+            .sync_once(&self.security, &self.tracker, &self.engine, &mut self.state)
+            .await
+            .unwrap();
+    }
+
+    fn send(&self, bytes: u64) {
+        assert!(matches!(
+            self.tracker.record_bytes("alice", bytes, 0, 0, None),
+            QuotaResult::Ok(_)
+        ));
+    }
+
+    fn daily_bytes(&self) -> u64 {
+        self.tracker.get_user_usage("alice").daily_bytes
+    }
+}
+
+#[tokio::test]
+async fn nodes_share_bans_quotas_and_sessions() {
+    let (port, db) = start_fake_redis().await;
+    let node =
+        |node_id: &str| format!("url = \"redis://127.0.0.1:{port}\"\nnode_id = \"{node_id}\"");
+    let mut a = Node::new(&node("a"));
+    let mut b = Node::new(&node("b"));
+    let banned = ip("203.0.113.9");
+
+    a.security
+        .read()
+        .await
+        .ban_manager()
+        .ban(banned, Duration::from_secs(600));
+    a.send(1000);
+    let _session = a
+        .engine
+        .register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    a.sync().await;
+
+    a.security
+        .read()
+        .await
+        .ban_manager()
+        .ban(key("198.51.100.7@acme"), Duration::from_secs(600));
+    a.sync().await;
+
+    b.send(10);
+    b.sync().await;
+    assert!(b.security.read().await.is_banned(&banned));
+    // A tenant's ban stays the tenant's on the other nodes
+    let tenant_banned = ip("198.51.100.7");
+    assert!(b
+        .security
+        .read()
+        .await
+        .is_banned_for(&tenant_banned, Some("acme")));
+    assert!(!b.security.read().await.is_banned(&tenant_banned));
+    assert!(db.lock().unwrap().hashes["s5:bans"].contains_key("198.51.100.7@acme"));
+    assert_eq!(b.daily_bytes(), 1010);
+    a.sync().await;
+    assert_eq!(a.daily_bytes(), 1010);
+
+    // Lifted on one node, lifted everywhere
+    b.security.read().await.ban_manager().unban(banned);
+    b.sync().await;
+    a.sync().await;
+    assert!(!a.security.read().await.is_banned(&banned));
+
+    // A reset on one node resets the cluster
+    a.tracker.reset_counters("alice", &QuotaCounter::ALL);
+    a.sync().await;
+    b.send(1);
+    b.sync().await;
+    assert_eq!(b.daily_bytes(), 1);
+    assert_eq!(
+        db.lock().unwrap().hashes["s5:quota:alice:total"]["bytes"],
+        "1"
+    );
+
+    let nodes = a.cluster.nodes().await.unwrap();
+    let sessions: Vec<_> = nodes
+        .iter()
+        .map(|n| (n.node_id.as_str(), n.sessions.len()))
+        .collect();
+    assert_eq!(sessions, [("a", 1), ("b", 0)]);
+
+    // A node that stopped syncing drops out
+    db.lock().unwrap().strings.remove("s5:node:b");
+    assert_eq!(b.cluster.nodes().await.unwrap().len(), 1);
+    assert!(!db.lock().unwrap().sets["s5:nodes"].contains("b"));
+}
+
+#[tokio::test]
+async fn observer_follows_without_writing() {
+    let (port, db) = start_fake_redis().await;
+    let mut a = Node::new(&format!(
+        "url = \"redis://127.0.0.1:{port}\"\nnode_id = \"a\""
+    ));
+    let mut observer = Node::new(&format!(
+        "url = \"redis://127.0.0.1:{port}\"\nnode_id = \"o\"\nobserver = true\n\n\
+         [api]\nenabled = true\ntoken = \"0123456789abcdef\""
+    ));
+    assert!(observer.cluster.is_observer());
+    let banned = ip("203.0.113.9");
+    a.security
+        .read()
+        .await
+        .ban_manager()
+        .ban(banned, Duration::from_secs(600));
+    a.send(1000);
+    a.sync().await;
+
+    let before = db.lock().unwrap().clone();
+    observer.sync().await;
+    observer.sync().await;
+    assert_eq!(*db.lock().unwrap(), before);
+    assert!(observer.security.read().await.is_banned(&banned));
+    // alice is followed without having relayed anything here
+    assert_eq!(observer.daily_bytes(), 1000);
+    let nodes = observer.cluster.nodes().await.unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].node_id, "a");
+}
+
+#[test]
+fn observer_needs_cluster_and_api() {
+    let cases = [
+        ("[cluster]\nobserver = true\n", "requires cluster.enabled"),
+        (
+            "[cluster]\nenabled = true\nobserver = true\n",
+            "requires [api] enabled",
+        ),
+    ];
+    for (section, expected) in cases {
+        let err = parse_config(&format!("{CONFIG}\n{section}")).unwrap_err();
+        assert!(format!("{err:#}").contains(expected), "{section}: {err:#}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Gossip
+// ---------------------------------------------------------------------------
+
+const SECRET: &str = "0123456789abcdef-gossip";
+
+fn counter(epoch: u64, nodes: &[(&str, u64)]) -> SharedCounter {
+    SharedCounter {
+        epoch,
+        nodes: nodes.iter().map(|(n, v)| (n.to_string(), *v)).collect(),
+        expires_at: None,
+    }
+}
+
+fn ban(expires_at: u64, updated_at: u64, lifted: bool) -> BanRecord {
+    BanRecord {
+        expires_at,
+        updated_at,
+        lifted,
+    }
+}
+
+#[test]
+fn gossip_states_converge_in_any_order() {
+    let now = 1_000_000;
+    let mut a = GossipState::default();
+    a.counters.insert(
+        "quota:alice:total/bytes".into(),
+        counter(0, &[("a", 100), ("b", 5)]),
+    );
+    a.counters
+        .insert("quota:bob:total/bytes".into(), counter(1, &[("a", 7)]));
+    a.bans.insert(key("10.0.0.1"), ban(now + 600, 1000, false));
+    a.bans.insert(key("10.0.0.2"), ban(now + 600, 1000, false));
+
+    let mut b = GossipState::default();
+    b.counters.insert(
+        "quota:alice:total/bytes".into(),
+        counter(0, &[("a", 90), ("b", 20)]),
+    );
+    // Reset on b before a's last update
+    b.counters
+        .insert("quota:bob:total/bytes".into(), counter(2, &[("b", 1)]));
+    // Lifted on b after a's ban, banned again on a later
+    b.bans.insert(key("10.0.0.1"), ban(now + 600, 2000, true));
+    b.bans.insert(key("10.0.0.2"), ban(now + 300, 500, false));
+    a.bans.insert(key("10.0.0.3"), ban(now + 900, 3000, false));
+    b.bans.insert(key("10.0.0.3"), ban(now + 600, 2500, true));
+
+    let mut ab = a.clone();
+    ab.merge(&b);
+    let mut ba = b.clone();
+    ba.merge(&a);
+    assert_eq!(ab, ba);
+    let mut again = ab.clone();
+    again.merge(&a);
+    again.merge(&b);
+    assert_eq!(again, ab);
+
+    assert_eq!(ab.counters["quota:alice:total/bytes"].value(), 120);
+    assert_eq!(ab.counters["quota:bob:total/bytes"].value(), 1);
+    let live: BTreeMap<_, _> = ab.live_bans(now).into_iter().collect();
+    assert_eq!(
+        live,
+        BTreeMap::from([(key("10.0.0.2"), now + 600), (key("10.0.0.3"), now + 900)])
+    );
+
+    // Expired bans and counters are dropped
+    ab.counters
+        .get_mut("quota:bob:total/bytes")
+        .unwrap()
+        .expires_at = Some(now);
+    ab.prune(now + 601);
+    assert_eq!(ab.bans.keys().collect::<Vec<_>>(), [&key("10.0.0.3")]);
+    assert!(!ab.counters.contains_key("quota:bob:total/bytes"));
+}
+
+#[test]
+fn gossip_config_validated() {
+    let gossip = format!("backend = \"gossip\"\nsecret = \"{SECRET}\"\n");
+    let cases = [
+        (gossip.clone(), "cluster.peers"),
+        (format!("{gossip}peers = [\"10.0.0.2\"]"), "not host:port"),
+        (
+            format!("{gossip}peers = [\"http://10.0.0.2:7946/x\"]"),
+            "not host:port",
+        ),
+        (
+            "backend = \"gossip\"\npeers = [\"10.0.0.2:7946\"]\nsecret = \"short\"".to_string(),
+            "cluster.secret",
+        ),
+        (
+            format!("{gossip}peers = [\"10.0.0.2:7946\"]\nlisten = \"7946\""),
+            "cluster.listen",
+        ),
+    ];
+    for (cluster, expected) in cases {
+        let config = format!("{CONFIG}\n[cluster]\nenabled = true\n{cluster}\n");
+        let err = parse_config(&config).unwrap_err();
+        assert!(format!("{err:#}").contains(expected), "{cluster}: {err:#}");
+    }
+
+    let config = parse_config(&format!(
+        "{CONFIG}\n[cluster]\nenabled = true\n{gossip}peers = [\"s5-b.internal:7946\"]\n"
+    ))
+    .unwrap();
+    assert_eq!(config.cluster.listen, "0.0.0.0:7946");
+    assert!(!format!("{:?}", config.cluster).contains(SECRET));
+    let redacted = s5::config::redact::redact_config(&config);
+    assert_eq!(redacted.cluster.secret.as_deref(), Some("***"));
+}
+
+#[test]
+fn gossip_refuses_unsigned_messages() {
+    use hmac::{Hmac, Mac};
+
+    let node = Node::new(&format!(
+        "backend = \"gossip\"\nnode_id = \"a\"\npeers = [\"127.0.0.1:1\"]\nsecret = \"{SECRET}\""
+    ));
+    let gossip = node.cluster.gossip().unwrap();
+    let sign = |body: &str| {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    };
+    let mut state = GossipState::default();
+    state.bans.insert(key("10.0.0.9"), ban(u64::MAX, 1, false));
+    let body = serde_json::json!({ "node_id": "b", "state": state }).to_string();
+
+    assert!(gossip.receive(&body, "sha256=00").is_err());
+    assert!(gossip
+        .receive(&body.replace("10.0.0.9", "10.0.0.8"), &sign(&body))
+        .is_err());
+    let own = body.replace("\"b\"", "\"a\"");
+    assert!(gossip.receive(&own, &sign(&own)).is_err());
+    assert!(gossip.with_state(|s| s.bans.is_empty()));
+
+    let (reply, signature) = gossip.receive(&body, &sign(&body)).unwrap();
+    assert_eq!(signature, sign(&reply));
+    assert!(gossip.with_state(|s| s.bans.contains_key(&key("10.0.0.9"))));
+}
+
+#[tokio::test]
+async fn peers_gossip_bans_quotas_and_sessions() {
+    let ports = [free_port(), free_port()];
+    let shutdown = CancellationToken::new();
+    let gossip = |me: usize| {
+        format!(
+            "backend = \"gossip\"\nnode_id = \"{}\"\nlisten = \"127.0.0.1:{}\"\n\
+             peers = [\"127.0.0.1:{}\"]\nsecret = \"{SECRET}\"",
+            ["a", "b"][me],
+            ports[me],
+            ports[1 - me]
+        )
+    };
+    let mut a = Node::new(&gossip(0));
+    let mut b = Node::new(&gossip(1));
+    a.cluster.listen(shutdown.clone()).await.unwrap();
+    b.cluster.listen(shutdown.clone()).await.unwrap();
+    let banned = ip("203.0.113.9");
+
+    a.security
+        .read()
+        .await
+        .ban_manager()
+        .ban(banned, Duration::from_secs(600));
+    a.send(1000);
+    let _session = a
+        .engine
+        .register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    a.sync().await;
+
+    b.send(10);
+    b.sync().await;
+    assert!(b.security.read().await.is_banned(&banned));
+    assert_eq!(b.daily_bytes(), 1010);
+    a.sync().await;
+    assert_eq!(a.daily_bytes(), 1010);
+
+    b.security.read().await.ban_manager().unban(banned);
+    b.sync().await;
+    a.sync().await;
+    assert!(!a.security.read().await.is_banned(&banned));
+
+    a.tracker.reset_counters("alice", &QuotaCounter::ALL);
+    a.sync().await;
+    b.send(1);
+    b.sync().await;
+    assert_eq!(b.daily_bytes(), 1);
+
+    let nodes = b.cluster.nodes().await.unwrap();
+    let sessions: Vec<_> = nodes
+        .iter()
+        .map(|n| (n.node_id.as_str(), n.sessions.len()))
+        .collect();
+    assert_eq!(sessions, [("a", 1), ("b", 0)]);
+
+    // A peer that is down fails the sync, without losing local changes
+    shutdown.cancel();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    a.send(5);
+    let err = a
+        .cluster
+        .sync_once(&a.security, &a.tracker, &a.engine, &mut a.state)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("gossip with 127.0.0.1"),
+        "{err:#}"
+    );
+    assert_eq!(a.daily_bytes(), 6);
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
@@ -0,0 +1,147 @@
+use s5::audit::events::AuditEvent;
+use s5::config::history::ConfigHistory;
+use s5::config::parse_config;
+use s5::config::persist;
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn config_toml(users: &[&str]) -> String {
+    let mut toml = "[server]\nssh_listen = \"0.0.0.0:2222\"\n".to_string();
+    for user in users {
+        toml.push_str(&format!(
+            "\n[[users]]\nusername = \"{user}\"\npassword_hash = \"{FAKE_HASH}\"\n"
+        ));
+    }
+    toml
+}
+
+// ---------------------------------------------------------------------------
+// History
+// ---------------------------------------------------------------------------
+
+#[test]
+fn records_applied_configs_newest_first() {
+    let history = ConfigHistory::new(10);
+    let first = history
+        .record(&config_toml(&["alice"]), "startup", 1)
+        .unwrap();
+    let second = history
+        .record(&config_toml(&["alice", "bob"]), "api", 2)
+        .unwrap();
+    assert!(second.id > first.id);
+    assert_eq!(first.sha256.len(), 64);
+    assert_ne!(first.sha256, second.sha256);
+
+    let list = history.list();
+    assert_eq!(
+        list.iter().map(|s| s.source.as_str()).collect::<Vec<_>>(),
+        vec!["api", "startup"]
+    );
+    assert_eq!(history.current_id(), Some(second.id));
+    assert_eq!(
+        history.get(first.id).unwrap().content,
+        config_toml(&["alice"])
+    );
+    assert!(history.get(999).is_none());
+}
+
+#[test]
+fn unchanged_reload_not_recorded_again() {
+    let history = ConfigHistory::new(10);
+    let content = config_toml(&["alice"]);
+    assert!(history.record(&content, "startup", 1).is_some());
+    assert!(history.record(&content, "signal", 1).is_none());
+    assert_eq!(history.list().len(), 1);
+
+    // Going back to an earlier config is a new entry
+    history.record(&config_toml(&["bob"]), "api", 1).unwrap();
+    assert!(history.record(&content, "rollback", 1).is_some());
+    assert_eq!(history.list().len(), 3);
+}
+
+#[test]
+fn oldest_snapshots_dropped_past_keep() {
+    let history = ConfigHistory::new(2);
+    let ids: Vec<u64> = ["a", "b", "c"]
+        .iter()
+        .map(|u| history.record(&config_toml(&[*u]), "api", 1).unwrap().id)
+        .collect();
+    let kept: Vec<u64> = history.list().iter().map(|s| s.id).collect();
+    assert_eq!(kept, vec![ids[2], ids[1]]);
+
+    history.set_keep(1);
+    assert_eq!(history.list().len(), 1);
+    assert_eq!(history.current_id(), Some(ids[2]));
+
+    history.set_keep(0);
+    assert!(history.list().is_empty());
+    assert!(history.record(&config_toml(&["d"]), "api", 1).is_none());
+}
+
+#[test]
+fn snapshot_source_never_serialized() {
+    let history = ConfigHistory::new(10);
+    let snapshot = history
+        .record(&config_toml(&["alice"]), "startup", 1)
+        .unwrap();
+    let json = serde_json::to_value(&snapshot).unwrap();
+    assert!(json.get("content").is_none());
+    assert!(!json.to_string().contains(FAKE_HASH));
+    assert_eq!(json["source"], "startup");
+    assert_eq!(json["users_count"], 1);
+}
+
+// ---------------------------------------------------------------------------
+// Rollback write, configuration and audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn replace_config_refuses_invalid_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("s5.toml");
+    std::fs::write(&path, config_toml(&["alice"])).unwrap();
+
+    persist::replace_config(&path, &config_toml(&["bob"])).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        config_toml(&["bob"])
+    );
+
+    assert!(persist::replace_config(&path, "[server]\nnot toml").is_err());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        config_toml(&["bob"])
+    );
+}
+
+#[test]
+fn config_defaults_and_validation() {
+    let c = parse_config(&config_toml(&["alice"])).unwrap();
+    assert_eq!(c.api.config_history, 10);
+
+    let with = |n: usize| {
+        let toml = config_toml(&["alice"]).replace(
+            "[server]",
+            &format!("[api]\nconfig_history = {n}\n\n[server]"),
+        );
+        parse_config(&toml)
+    };
+    assert_eq!(with(0).unwrap().api.config_history, 0);
+    assert!(with(100).is_ok());
+    assert!(with(101).is_err());
+}
+
+#[test]
+fn rollback_event() {
+    let history = ConfigHistory::new(10);
+    let snapshot = history
+        .record(&config_toml(&["alice"]), "startup", 1)
+        .unwrap();
+    let event = AuditEvent::config_rollback(&snapshot, "ops");
+    assert_eq!(event.event_type(), "config.rollback");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["snapshot_id"], snapshot.id);
+    assert_eq!(json["sha256"], snapshot.sha256.as_str());
+    assert_eq!(json["rolled_back_by"], "ops");
+}
@@ -16,6 +16,7 @@ fn user_no_inherit_ignores_global_deny_rules() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["evil.com:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None, // falls back to Allow when inherit=false
@@ -35,6 +36,7 @@ fn user_no_inherit_ignores_group_deny_rules() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Deny),
@@ -62,6 +64,7 @@ fn user_no_inherit_without_policy_defaults_to_allow() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None, // no explicit policy
@@ -85,6 +88,7 @@ fn user_policy_overrides_group_policy() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Deny),
@@ -109,6 +113,7 @@ fn group_policy_overrides_global_when_user_has_none() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Deny),
@@ -133,6 +138,7 @@ fn global_policy_used_when_both_group_and_user_are_none() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: None,
@@ -161,6 +167,7 @@ fn deny_rules_from_all_three_levels_are_merged() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["10.0.0.0/8:*".to_string()],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: None,
@@ -203,6 +210,7 @@ fn allow_rules_from_all_three_levels_are_merged() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec!["*.google.com:443".to_string()],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: None,
@@ -237,6 +245,7 @@ fn global_deny_overrides_user_allow_for_same_host() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec![],
         deny: vec!["evil.com:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None,
@@ -256,6 +265,7 @@ fn user_deny_overrides_global_allow_for_same_host() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec!["good.com:*".to_string()],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: None,
@@ -279,6 +289,7 @@ fn no_group_merges_only_global_and_user() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["169.254.169.254:*".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig {
         default_policy: Some(AclPolicyConfig::Deny),
@@ -314,6 +325,7 @@ fn all_empty_rules_fall_back_to_global_allow() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: None,
@@ -338,6 +350,7 @@ fn all_empty_rules_fall_back_to_global_deny() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec![],
         deny: vec![],
+        inspect_server_names: false,
     };
     let group = UserAclConfig {
         default_policy: None,
@@ -366,6 +379,7 @@ fn invalid_global_deny_rule_returns_error() {
         default_policy: AclPolicyConfig::Allow,
         allow: vec![],
         deny: vec!["host:not-a-port".to_string()],
+        inspect_server_names: false,
     };
     let user = UserAclConfig::default();
     let result = ParsedAcl::from_config_merged(&global, &user);
@@ -447,6 +461,7 @@ fn merged_acl_respects_port_range_in_allow_rule() {
         default_policy: AclPolicyConfig::Deny,
         allow: vec!["*.example.com:80-443".to_string()],
         deny: vec![],
+        inspect_server_names: false,
     };
     let user = UserAclConfig::default();
 
@@ -49,7 +49,8 @@ password_hash = "{FAKE_HASH}"
     );
     let cfg = config::parse_config(&toml).unwrap();
     let user = &cfg.users[0];
-    assert!(user.allow_forwarding);
+    // Unset: inherits from the group, or allowed when there is none
+    assert_eq!(user.allow_forwarding, None);
     assert!(user.allow_shell);
     assert_eq!(user.max_new_connections_per_minute, 0);
     assert_eq!(user.max_bandwidth_kbps, 0);
@@ -1,3 +1,4 @@
+use crate::test_support::parse_app_config;
 use s5::config::parse_config;
 
 const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
@@ -345,3 +346,254 @@ password_hash = "{FAKE_HASH}"
     let config = s5::config::load_config(&path).unwrap();
     assert_eq!(config.users[0].username, "fileuser");
 }
+
+// ---------------------------------------------------------------------------
+// Test 16: SSH channel window and packet size bounds
+// ---------------------------------------------------------------------------
+#[test]
+fn ssh_window_and_packet_size_validation() {
+    let config_with = |server: &str| {
+        parse_config(&format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+{server}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        ))
+    };
+    let config = config_with("").unwrap();
+    assert_eq!(config.server.ssh_window_size, 2 * 1024 * 1024);
+    assert_eq!(config.server.ssh_max_packet_size, 32 * 1024);
+    assert_eq!(config.server.ssh_stall_threshold_ms, 5000);
+
+    let config = config_with("ssh_window_size = 262144\nssh_max_packet_size = 16384").unwrap();
+    assert_eq!(config.server.ssh_window_size, 262144);
+    assert!(config_with("ssh_max_packet_size = 1024").is_err());
+    assert!(config_with("ssh_max_packet_size = 1048576").is_err());
+    assert!(config_with("ssh_window_size = 8192").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 17: logging.event_log is Windows-only
+// ---------------------------------------------------------------------------
+#[test]
+fn event_log_rejected_off_windows() {
+    let toml = format!(
+        r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[logging]
+event_log = true
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+    );
+    let result = parse_config(&toml);
+    if cfg!(windows) {
+        assert!(result.unwrap().logging.event_log);
+    } else {
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("only supported on Windows"), "{err}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Test 18: [scripting] needs the lua-hooks feature
+// ---------------------------------------------------------------------------
+#[test]
+fn scripting_rejected_without_lua_hooks() {
+    let toml = format!(
+        r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[scripting]
+path = "/nonexistent/hooks.lua"
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+    );
+    let err = format!("{:#}", parse_config(&toml).unwrap_err());
+    if cfg!(feature = "lua-hooks") {
+        // Built in: the script itself is loaded
+        assert!(err.starts_with("scripting: "), "{err}");
+        assert!(!err.contains("--features"), "{err}");
+    } else {
+        assert!(err.contains("--features lua-hooks"), "{err}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Test 19: credential spraying thresholds (only checked while enabled)
+// ---------------------------------------------------------------------------
+#[test]
+fn credential_spraying_validation() {
+    let section =
+        |body: &str| parse_app_config(&format!("[security.credential_spraying]\n{body}"), "");
+    assert!(section("min_usernames = 5\nwindow_secs = 600").is_ok());
+    assert!(section("min_usernames = 1").is_err());
+    assert!(section("window_secs = 0").is_err());
+    assert!(section("enabled = false\nmin_usernames = 0").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Test 20: account lockout needs a lock duration once enabled
+// ---------------------------------------------------------------------------
+#[test]
+fn account_lockout_validation() {
+    let security = |body: &str| parse_app_config(&format!("[security]\n{body}"), "");
+    assert!(security("lock_after_failures = 5\nlock_duration = 60").is_ok());
+    assert!(security("lock_after_failures = 5\nlock_duration = 0").is_err());
+    assert!(security("lock_duration = 0").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Test 21: login anomaly hour tolerance and history path
+// ---------------------------------------------------------------------------
+#[test]
+fn login_anomaly_validation() {
+    let section =
+        |body: &str| parse_app_config(&format!("[login_anomaly]\nenabled = true\n{body}"), "");
+    assert!(section("hour_tolerance = 3").is_ok());
+    assert!(section("hour_tolerance = 12").is_err());
+    assert!(section("history_path = \"\"").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 22: key enrollment holds end before the auth timeout
+// ---------------------------------------------------------------------------
+#[test]
+fn key_enrollment_validation() {
+    let section =
+        |body: &str| parse_app_config(&format!("[key_enrollment]\nenabled = true\n{body}"), "");
+    // The held login must be decided before the auth timeout (120 s)
+    assert!(section("wait_secs = 120").is_err());
+    assert!(section("wait_secs = 0").is_ok());
+    assert!(section("pending_hours = 0").is_err());
+    assert!(section("pending_hours = 9000").is_err());
+    assert!(section("max_pending_per_user = 0").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 23: impossible travel needs a City database and sane limits
+// ---------------------------------------------------------------------------
+#[test]
+fn impossible_travel_validation() {
+    let section = |body: &str| {
+        parse_app_config(
+            &format!(
+                "[geoip]\nenabled = true\ndatabase_path = \"/nonexistent/GeoLite2-City.mmdb\"\n\n\
+                 [impossible_travel]\nenabled = true\n{body}"
+            ),
+            "",
+        )
+    };
+    assert!(section("action = \"block\"\nmax_speed_kmh = 900").is_ok());
+    assert!(section("max_speed_kmh = 0").is_err());
+    assert!(section("window_secs = 0").is_err());
+    assert!(section("action = \"deny\"").is_err());
+    assert!(parse_app_config("[impossible_travel]\nenabled = true", "").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 24: proxy.priority classes and limits
+// ---------------------------------------------------------------------------
+#[test]
+fn priority_class_validation() {
+    let class = |body: &str| parse_app_config(&format!("[[proxy.priority.classes]]\n{body}"), "");
+    assert!(class("name = \"ssh\"\ndestinations = [\"*:22\"]").is_ok());
+    // No destinations nor tags
+    assert!(class("name = \"ssh\"").is_err());
+    assert!(class("name = \"ssh\"\nweight = 0\ndestinations = [\"*:22\"]").is_err());
+    assert!(class("name = \"ssh\"\nweight = 1001\ndestinations = [\"*:22\"]").is_err());
+    assert!(class("name = \"default\"\ndestinations = [\"*:22\"]").is_err());
+    assert!(class("name = \"ssh\"\ndestinations = [\"*:99999\"]").is_err());
+    assert!(class("name = \"ssh\"\ntags = [\"ops\"]\nnice = 5").is_err());
+    assert!(parse_app_config(
+        "[[proxy.priority.classes]]\nname = \"bulk\"\ntags = [\"backup\"]\n\n\
+         [[proxy.priority.classes]]\nname = \"bulk\"\ntags = [\"ops\"]",
+        "",
+    )
+    .is_err());
+    assert!(parse_app_config("[proxy.priority]\nmax_inflight = 0", "").is_err());
+    assert!(parse_app_config("[proxy.priority]\ndefault_weight = 0", "").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Test 25: [masque] needs an address, its TLS files and the masque feature
+// ---------------------------------------------------------------------------
+#[test]
+fn masque_validation() {
+    let dir = tempfile::tempdir().unwrap();
+    let cert = dir.path().join("cert.pem");
+    let key = dir.path().join("key.pem");
+    std::fs::write(&cert, "").unwrap();
+    std::fs::write(&key, "").unwrap();
+    let masque = |listen: &str, cert: &std::path::Path| {
+        parse_app_config(
+            &format!(
+                "[masque]\nlisten = \"{listen}\"\ntls_cert = \"{}\"\ntls_key = \"{}\"",
+                cert.display(),
+                key.display()
+            ),
+            "",
+        )
+    };
+    assert!(masque("localhost:443", &cert).is_err());
+    let err = masque("0.0.0.0:443", &dir.path().join("missing.pem"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("masque.tls_cert not found"), "{err}");
+    let result = masque("0.0.0.0:443", &cert);
+    if cfg!(feature = "masque") {
+        assert!(result.is_ok());
+    } else {
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--features masque"));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Test 26: [gssapi] needs an existing keytab, valid rules and the gssapi feature
+// ---------------------------------------------------------------------------
+#[test]
+fn gssapi_validation() {
+    let dir = tempfile::tempdir().unwrap();
+    let keytab = dir.path().join("krb5.keytab");
+    std::fs::write(&keytab, "").unwrap();
+    let gssapi = |section: &str| parse_app_config(&format!("[gssapi]\n{section}"), "");
+    let err = gssapi(&format!(
+        "keytab = \"{}\"",
+        dir.path().join("missing.keytab").display()
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("gssapi.keytab not found"), "{err}");
+    assert!(gssapi("[[gssapi.rules]]\npattern = \"\"\nuser = \"$1\"").is_err());
+    assert!(gssapi("[gssapi.principals]\n\"alice@EXAMPLE.COM\" = \"\"").is_err());
+    let result = gssapi(&format!(
+        "keytab = \"{}\"\n[gssapi.principals]\n\"alice@EXAMPLE.COM\" = \"alice\"\n\
+         [[gssapi.rules]]\npattern = \"(.*)@EXAMPLE\\\\.COM\"\nuser = \"$1\"",
+        keytab.display()
+    ));
+    if cfg!(feature = "gssapi") {
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert!(gssapi("[[gssapi.rules]]\npattern = \"(\"\nuser = \"bob\"").is_err());
+    } else {
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--features gssapi"));
+    }
+}
@@ -1,4 +1,7 @@
+use s5::config::types::{AddressFamily, TcpConfig};
 use s5::proxy::connector;
+use s5::proxy::retry::RetryPolicy;
+use std::net::SocketAddr;
 
 // ===========================================================================
 // Port 0 rejection (M-9)
@@ -31,7 +34,19 @@ async fn connect_port_zero_rejected_with_ip_guard() {
 #[tokio::test]
 async fn connect_with_cache_port_zero_rejected() {
     let dns_cache = s5::proxy::dns_cache::DnsCache::new(-1, 1000);
-    let result = connector::connect_with_cache("example.com", 0, 5, false, &dns_cache, None).await;
+    let result = connector::connect_with_cache(
+        "example.com",
+        0,
+        5,
+        false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &dns_cache,
+        None,
+    )
+    .await;
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
     assert!(
@@ -233,6 +248,36 @@ async fn resolve_and_check_ipv6_format_with_brackets() {
     );
 }
 
+#[tokio::test]
+async fn resolve_and_check_loopback_allowed_by_exemption() {
+    let exemptions: Vec<ipnet::IpNet> = vec!["127.0.0.0/8".parse().unwrap()];
+    let result = connector::resolve_and_check_with_exemptions(
+        "127.0.0.1",
+        80,
+        5,
+        true,
+        &exemptions,
+        AddressFamily::Any,
+    )
+    .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn resolve_and_check_exemption_does_not_cover_other_ranges() {
+    let exemptions: Vec<ipnet::IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+    let result = connector::resolve_and_check_with_exemptions(
+        "127.0.0.1",
+        80,
+        5,
+        true,
+        &exemptions,
+        AddressFamily::Any,
+    )
+    .await;
+    assert!(result.is_err());
+}
+
 // ===========================================================================
 // connect_with_cache
 // ===========================================================================
@@ -240,7 +285,19 @@ async fn resolve_and_check_ipv6_format_with_brackets() {
 #[tokio::test]
 async fn connect_with_cache_loopback_blocked_by_ip_guard() {
     let dns_cache = s5::proxy::dns_cache::DnsCache::new(-1, 1000);
-    let result = connector::connect_with_cache("127.0.0.1", 80, 5, true, &dns_cache, None).await;
+    let result = connector::connect_with_cache(
+        "127.0.0.1",
+        80,
+        5,
+        true,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &dns_cache,
+        None,
+    )
+    .await;
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
     assert!(
@@ -258,6 +315,10 @@ async fn connect_with_cache_invalid_hostname_fails() {
         80,
         2,
         false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
         &dns_cache,
         None,
     )
@@ -282,3 +343,138 @@ async fn connect_port_65535() {
     let result = connector::connect("198.51.100.1", 65535, 1, false).await;
     assert!(result.is_err());
 }
+
+// ===========================================================================
+// Address family policy
+// ===========================================================================
+
+fn mixed_addrs() -> Vec<SocketAddr> {
+    [
+        "192.0.2.1:80",
+        "[2001:db8::1]:80",
+        "192.0.2.2:80",
+        "[2001:db8::2]:80",
+    ]
+    .iter()
+    .map(|a| a.parse().unwrap())
+    .collect()
+}
+
+fn names(addrs: &[SocketAddr]) -> Vec<String> {
+    addrs.iter().map(|a| a.ip().to_string()).collect()
+}
+
+#[test]
+fn address_family_any_keeps_resolver_order() {
+    let addrs = connector::apply_address_family("h", mixed_addrs(), AddressFamily::Any).unwrap();
+    assert_eq!(addrs, mixed_addrs());
+}
+
+#[test]
+fn address_family_prefer_orders_within_family() {
+    let v6 = connector::apply_address_family("h", mixed_addrs(), AddressFamily::PreferIpv6);
+    assert_eq!(
+        names(&v6.unwrap()),
+        ["2001:db8::1", "2001:db8::2", "192.0.2.1", "192.0.2.2"]
+    );
+    let v4 = connector::apply_address_family("h", mixed_addrs(), AddressFamily::PreferIpv4);
+    assert_eq!(
+        names(&v4.unwrap()),
+        ["192.0.2.1", "192.0.2.2", "2001:db8::1", "2001:db8::2"]
+    );
+}
+
+#[test]
+fn address_family_only_filters() {
+    let v4 = connector::apply_address_family("h", mixed_addrs(), AddressFamily::Ipv4Only);
+    assert_eq!(names(&v4.unwrap()), ["192.0.2.1", "192.0.2.2"]);
+    let v6 = connector::apply_address_family("h", mixed_addrs(), AddressFamily::Ipv6Only);
+    assert_eq!(names(&v6.unwrap()), ["2001:db8::1", "2001:db8::2"]);
+
+    let v4_host = vec!["192.0.2.1:80".parse().unwrap()];
+    let err = connector::apply_address_family("v4.example", v4_host, AddressFamily::Ipv6Only)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("v4.example") && err.contains("ipv6_only"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn resolve_and_check_applies_address_family() {
+    let result = connector::resolve_and_check_with_exemptions(
+        "127.0.0.1",
+        80,
+        5,
+        false,
+        &[],
+        AddressFamily::Ipv6Only,
+    )
+    .await;
+    assert!(result.is_err());
+    let result = connector::resolve_and_check_with_exemptions(
+        "127.0.0.1",
+        80,
+        5,
+        false,
+        &[],
+        AddressFamily::Ipv4Only,
+    )
+    .await;
+    assert_eq!(result.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn connect_with_cache_filters_cached_addresses() {
+    let dns_cache = s5::proxy::dns_cache::DnsCache::new(-1, 1000);
+    dns_cache.insert("v4.example:80", vec!["192.0.2.1:80".parse().unwrap()], None);
+    let result = connector::connect_with_cache(
+        "v4.example",
+        80,
+        2,
+        false,
+        &[],
+        AddressFamily::Ipv6Only,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &dns_cache,
+        None,
+    )
+    .await;
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("address_family"), "{err}");
+    // The cache still holds the IPv4 answer for other policies
+    assert!(dns_cache.get("v4.example:80", false).is_some());
+}
+
+#[test]
+fn address_family_config_values() {
+    let config = s5::config::parse_config(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:2222"
+
+[proxy]
+address_family = "ipv4_only"
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+"##,
+    )
+    .unwrap();
+    assert_eq!(config.proxy.address_family, AddressFamily::Ipv4Only);
+    assert_eq!(AddressFamily::default(), AddressFamily::Any);
+    for family in [
+        "any",
+        "prefer_ipv6",
+        "prefer_ipv4",
+        "ipv4_only",
+        "ipv6_only",
+    ] {
+        let parsed: AddressFamily = serde_json::from_value(family.into()).unwrap();
+        assert_eq!(parsed.to_string(), family);
+    }
+    assert!(serde_json::from_value::<AddressFamily>("ipv5".into()).is_err());
+}
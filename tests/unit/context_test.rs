@@ -97,6 +97,7 @@ fn api_config_debug_redacts_token() {
         enabled: true,
         listen: "0.0.0.0:9091".to_string(),
         token: "super-secret-api-token".to_string(),
+        ..ApiConfig::default()
     };
 
     let debug = format!("{:?}", api);
@@ -0,0 +1,164 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::config::types::CredentialSprayingConfig;
+use s5::security::spraying::SprayingDetector;
+use s5::security::SecurityManager;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+fn detector(min_usernames: u32, window_secs: u64) -> SprayingDetector {
+    SprayingDetector::new(&CredentialSprayingConfig {
+        enabled: true,
+        min_usernames,
+        window_secs,
+    })
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Detector
+// ---------------------------------------------------------------------------
+
+#[test]
+fn detected_at_min_usernames() {
+    let detector = detector(3, 3600);
+    let source = ip("203.0.113.5");
+    assert!(detector.record_failure(&source, "carol").is_none());
+    assert!(detector.record_failure(&source, "alice").is_none());
+    assert_eq!(
+        detector.record_failure(&source, "bob"),
+        Some(vec![
+            "alice".to_string(),
+            "bob".to_string(),
+            "carol".to_string()
+        ])
+    );
+    // The record starts over once reported
+    assert!(detector.record_failure(&source, "dave").is_none());
+}
+
+#[test]
+fn repeated_username_counts_once() {
+    let detector = detector(3, 3600);
+    let source = ip("203.0.113.5");
+    for _ in 0..10 {
+        assert!(detector.record_failure(&source, "alice").is_none());
+    }
+    assert!(detector.record_failure(&source, "bob").is_none());
+    assert!(detector.record_failure(&source, "carol").is_some());
+}
+
+#[test]
+fn sources_tracked_separately() {
+    let detector = detector(2, 3600);
+    assert!(detector
+        .record_failure(&ip("203.0.113.5"), "alice")
+        .is_none());
+    assert!(detector.record_failure(&ip("203.0.113.6"), "bob").is_none());
+    assert_eq!(detector.len(), 2);
+    assert!(detector
+        .record_failure(&ip("203.0.113.6"), "carol")
+        .is_some());
+}
+
+#[test]
+fn usernames_expire_after_window() {
+    let detector = detector(3, 60);
+    let source = ip("203.0.113.5");
+    let start = Instant::now();
+    assert!(detector
+        .record_failure_at(&source, "alice", start)
+        .is_none());
+    assert!(detector
+        .record_failure_at(&source, "bob", start + Duration::from_secs(30))
+        .is_none());
+    // alice is out of the window by now
+    assert!(detector
+        .record_failure_at(&source, "carol", start + Duration::from_secs(61))
+        .is_none());
+    assert!(detector
+        .record_failure_at(&source, "dave", start + Duration::from_secs(62))
+        .is_some());
+}
+
+#[test]
+fn long_usernames_are_truncated() {
+    let detector = detector(2, 3600);
+    let source = ip("203.0.113.5");
+    assert!(detector.record_failure(&source, &"a".repeat(500)).is_none());
+    let tried = detector.record_failure(&source, "bob").unwrap();
+    assert_eq!(tried[0].len(), 64);
+}
+
+#[test]
+fn disabled_detector_records_nothing() {
+    let mut detector = detector(2, 3600);
+    let source = ip("203.0.113.5");
+    assert!(detector.record_failure(&source, "alice").is_none());
+    detector.configure(&CredentialSprayingConfig {
+        enabled: false,
+        ..Default::default()
+    });
+    assert!(detector.is_empty());
+    assert!(!detector.is_enabled());
+    assert!(detector.record_failure(&source, "bob").is_none());
+    assert!(detector.record_failure(&source, "carol").is_none());
+    assert!(detector.is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Security manager
+// ---------------------------------------------------------------------------
+
+#[test]
+fn security_manager_normalizes_and_skips_whitelist() {
+    let config = parse_app_config(
+        "[security]\nban_whitelist = [\"10.0.0.0/8\"]\n\n\
+         [security.credential_spraying]\nmin_usernames = 2",
+        "",
+    )
+    .unwrap();
+    let security = SecurityManager::new(&config);
+
+    // IPv4-mapped IPv6 counts as the same source
+    assert!(security
+        .record_username_failure(&ip("203.0.113.5"), "alice")
+        .is_none());
+    assert!(security
+        .record_username_failure(&ip("::ffff:203.0.113.5"), "bob")
+        .is_some());
+
+    assert!(security
+        .record_username_failure(&ip("10.1.2.3"), "alice")
+        .is_none());
+    assert!(security
+        .record_username_failure(&ip("10.1.2.3"), "bob")
+        .is_none());
+    assert!(security.spraying().is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn audit_event_is_critical() {
+    let source = "203.0.113.5:40000".parse().unwrap();
+    let event = AuditEvent::credential_spraying_with_cid(
+        &source,
+        "ssh",
+        "conn-1",
+        vec!["alice".to_string(), "bob".to_string()],
+        3600,
+        Some(900),
+    );
+    assert_eq!(event.event_type(), "auth.spraying");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["source_ip"], "203.0.113.5");
+    assert_eq!(json["usernames"][1], "bob");
+    assert_eq!(json["ban_secs"], 900);
+}
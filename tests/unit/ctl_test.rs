@@ -0,0 +1,288 @@
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::IntoResponse;
+use s5::cli::CtlCommand;
+use s5::config::types::ApiConfig;
+use s5::ctl::{execute, parse_http_response, render, ApiTarget, CtlClient};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+const TOKEN: &str = "ctl-test-token-123";
+
+/// Echo the request back in the API envelope; reject a wrong token like the API.
+async fn echo(method: Method, uri: Uri, headers: HeaderMap, body: String) -> impl IntoResponse {
+    let auth = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if auth != format!("Bearer {TOKEN}") {
+        return (StatusCode::UNAUTHORIZED, "unauthorized").into_response();
+    }
+    if uri.path() == "/api/missing" {
+        return (
+            StatusCode::NOT_FOUND,
+            axum::Json(json!({ "success": false, "error": "IP not banned" })),
+        )
+            .into_response();
+    }
+    let body: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    axum::Json(json!({
+        "success": true,
+        "data": { "method": method.as_str(), "path": uri.path(), "body": body }
+    }))
+    .into_response()
+}
+
+fn echo_router() -> axum::Router {
+    axum::Router::new().fallback(echo)
+}
+
+async fn tcp_client(token: &str) -> CtlClient {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, echo_router()).await });
+    CtlClient::new(ApiTarget::parse(&addr.to_string()).unwrap(), token)
+}
+
+// ---------------------------------------------------------------------------
+// Address resolution
+// ---------------------------------------------------------------------------
+
+#[test]
+fn api_addr_forms() {
+    assert_eq!(
+        ApiTarget::parse("unix:/run/s5/api.sock").unwrap(),
+        ApiTarget::Unix(PathBuf::from("/run/s5/api.sock"))
+    );
+    assert_eq!(
+        ApiTarget::parse("127.0.0.1:9091").unwrap(),
+        ApiTarget::Http("http://127.0.0.1:9091".to_string())
+    );
+    assert_eq!(
+        ApiTarget::parse("https://s5.example.com/").unwrap(),
+        ApiTarget::Http("https://s5.example.com".to_string())
+    );
+    assert!(ApiTarget::parse("unix:").is_err());
+    assert!(ApiTarget::parse("ftp://host").is_err());
+}
+
+#[test]
+fn target_from_config_prefers_socket_and_loopback() {
+    let mut api = ApiConfig {
+        listen: "0.0.0.0:9091".to_string(),
+        ..ApiConfig::default()
+    };
+    assert_eq!(
+        ApiTarget::from_config(&api),
+        ApiTarget::Http("http://127.0.0.1:9091".to_string())
+    );
+    api.listen = "[::]:9091".to_string();
+    assert_eq!(
+        ApiTarget::from_config(&api),
+        ApiTarget::Http("http://[::1]:9091".to_string())
+    );
+    api.unix_socket = Some(PathBuf::from("/run/s5/api.sock"));
+    assert_eq!(
+        ApiTarget::from_config(&api),
+        ApiTarget::Unix(PathBuf::from("/run/s5/api.sock"))
+    );
+}
+
+#[test]
+fn raw_http_response_parsed() {
+    let (status, body) =
+        parse_http_response(b"HTTP/1.0 403 Forbidden\r\ncontent-length: 9\r\n\r\nforbidden")
+            .unwrap();
+    assert_eq!(status, 403);
+    assert_eq!(body, "forbidden");
+    assert!(parse_http_response(b"garbage").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Requests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn actions_map_to_api_calls() {
+    let client = tcp_client(TOKEN).await;
+    let cases = [
+        (CtlCommand::Status, "GET", "/api/status"),
+        (CtlCommand::Bans, "GET", "/api/bans"),
+        (CtlCommand::Reload, "POST", "/api/reload"),
+        (
+            CtlCommand::Kick {
+                username: "alice".to_string(),
+                message: None,
+            },
+            "POST",
+            "/api/kick/alice",
+        ),
+        (
+            CtlCommand::Unban {
+                ip: "2001:db8::1".to_string(),
+            },
+            "DELETE",
+            "/api/bans/2001%3Adb8%3A%3A1",
+        ),
+        (
+            CtlCommand::Unlock {
+                username: "alice".to_string(),
+            },
+            "DELETE",
+            "/api/users/alice/lock",
+        ),
+        (
+            CtlCommand::LogLevel {
+                level: None,
+                reset: false,
+            },
+            "GET",
+            "/api/logging/level",
+        ),
+        (
+            CtlCommand::LogLevel {
+                level: None,
+                reset: true,
+            },
+            "DELETE",
+            "/api/logging/level",
+        ),
+    ];
+    for (action, method, path) in cases {
+        let data = execute(&client, &action).await.unwrap();
+        assert_eq!(data["method"], method, "{action:?}");
+        assert_eq!(data["path"], path, "{action:?}");
+    }
+
+    let ban = CtlCommand::Ban {
+        ip: "203.0.113.7".to_string(),
+        duration: Some(600),
+    };
+    let data = execute(&client, &ban).await.unwrap();
+    assert_eq!(data["path"], "/api/bans");
+    assert_eq!(
+        data["body"],
+        json!({ "ip": "203.0.113.7", "duration_secs": 600 })
+    );
+
+    let level = CtlCommand::LogLevel {
+        level: Some("info,s5::proxy=trace".to_string()),
+        reset: false,
+    };
+    let data = execute(&client, &level).await.unwrap();
+    assert_eq!(data["method"], "PUT");
+    assert_eq!(data["path"], "/api/logging/level");
+    assert_eq!(data["body"], json!({ "level": "info,s5::proxy=trace" }));
+}
+
+#[tokio::test]
+async fn api_errors_surface_status_and_message() {
+    let client = tcp_client("wrong-token").await;
+    let err = execute(&client, &CtlCommand::Status).await.unwrap_err();
+    assert_eq!(err.to_string(), "HTTP 401: unauthorized");
+
+    let client = tcp_client(TOKEN).await;
+    let err = client.call("GET", "/api/missing", None).await.unwrap_err();
+    assert_eq!(err.to_string(), "HTTP 404: IP not banned");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn requests_over_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.sock");
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let server = {
+        let path = path.clone();
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            s5::api::unix_socket::serve(&path, 0o600, echo_router(), cancel).await
+        })
+    };
+    for _ in 0..40 {
+        if path.exists() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+    }
+
+    let client = CtlClient::new(ApiTarget::Unix(path.clone()), TOKEN);
+    let data = client
+        .call("POST", "/api/bans", Some(json!({ "ip": "198.51.100.1" })))
+        .await
+        .unwrap();
+    assert_eq!(data["method"], "POST");
+    assert_eq!(data["body"]["ip"], "198.51.100.1");
+
+    cancel.cancel();
+    server.await.unwrap().unwrap();
+}
+
+// ---------------------------------------------------------------------------
+// Output
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sessions_rendered_as_table() {
+    let action = CtlCommand::Sessions { user: None };
+    assert_eq!(render(&action, &json!([])), "No active sessions.\n");
+
+    let out = render(
+        &action,
+        &json!([{
+            "session_id": "s12",
+            "username": "alice",
+            "target_host": "db.internal",
+            "target_port": 5432,
+            "source_ip": "203.0.113.7",
+            "protocol": "ssh",
+            "duration_secs": 3725,
+            "bytes_up": 2048,
+            "bytes_down": 0
+        }]),
+    );
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().starts_with("SESSION"));
+    let row = lines.next().unwrap();
+    for field in [
+        "s12",
+        "alice",
+        "db.internal:5432",
+        "1h2m5s",
+        "2.0 KB",
+        "0 B",
+    ] {
+        assert!(row.contains(field), "{field} missing from {row}");
+    }
+}
+
+#[test]
+fn status_and_results_rendered() {
+    let status = render(
+        &CtlCommand::Status,
+        &json!({
+            "status": "ok",
+            "uptime_secs": 90,
+            "active_connections": 3,
+            "total_users": 5,
+            "maintenance": false,
+            "connection_caps": { "active": 2, "ssh": 2, "socks5": 0, "max": 10, "queued": 1 }
+        }),
+    );
+    assert!(status.contains("Status:       ok"));
+    assert!(status.contains("Uptime:       1m30s"));
+    assert!(status.contains("2 (ssh 2, socks5 0), max 10, 1 queued"));
+    assert!(status.contains("Maintenance:  off"));
+
+    let kick = CtlCommand::Kick {
+        username: "bob".to_string(),
+        message: None,
+    };
+    assert_eq!(
+        render(&kick, &json!({ "kicked": true, "sessions_closed": 2 })),
+        "Kicked bob (2 sessions closed)\n"
+    );
+    assert_eq!(
+        render(&kick, &json!({ "kicked": false, "sessions_closed": 0 })),
+        "bob has no active connections\n"
+    );
+}
@@ -79,6 +79,7 @@ fn demo_shell_context() -> (ShellContext, Arc<ProxyEngine>, Arc<QuotaTracker>) {
         proxy_engine: Some(pe.clone()),
         quota_tracker: Some(qt.clone()),
         quota_config: alice_quota,
+        env: Vec::new(),
     };
 
     (ctx, pe, qt)
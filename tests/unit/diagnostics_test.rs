@@ -0,0 +1,199 @@
+use crate::test_support::{app_config_toml, parse_app_config};
+use s5::config::types::AppConfig;
+use s5::diagnostics::errors::{recent_errors, RecentErrorsLayer};
+use s5::diagnostics::{
+    config_file_check, doctor_cli, render_checks, run_checks, runtime_stats, tar_gz, Check,
+    CheckStatus,
+};
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+use tracing_subscriber::prelude::*;
+
+/// `server` keys in `[server]` and `extra` sections, with SSH listening on
+/// every address.
+fn config(server: &str, extra: &str) -> AppConfig {
+    let mut config = parse_app_config(&format!("{server}\n\n{extra}"), "").unwrap();
+    config.server.ssh_listen = "0.0.0.0:2222".to_string();
+    config
+}
+
+fn server(dir: &Path) -> String {
+    format!("host_key_path = \"{}\"", dir.join("host_key").display())
+}
+
+fn check<'a>(checks: &'a [Check], name: &str) -> &'a Check {
+    checks
+        .iter()
+        .find(|c| c.name == name)
+        .unwrap_or_else(|| panic!("no {name} check in {checks:?}"))
+}
+
+// ---------------------------------------------------------------------------
+// Checks
+// ---------------------------------------------------------------------------
+
+#[test]
+fn host_key_and_log_paths() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing");
+    let cfg = config(
+        &server(dir.path()),
+        &format!(
+            "[logging]\naudit_log_path = \"{}\"\n\n[logging.flows]\nenabled = true\npath = \"{}\"",
+            dir.path().join("audit.json").display(),
+            missing.join("flows.db").display()
+        ),
+    );
+    let checks = run_checks(&cfg);
+    assert_eq!(check(&checks, "config").status, CheckStatus::Ok);
+    // Generated at startup
+    assert_eq!(check(&checks, "host_key").status, CheckStatus::Warn);
+    assert_eq!(check(&checks, "audit_log").status, CheckStatus::Ok);
+    assert_eq!(check(&checks, "flows").status, CheckStatus::Fail);
+
+    std::fs::write(dir.path().join("host_key"), "key").unwrap();
+    let checks = run_checks(&cfg);
+    assert_eq!(check(&checks, "host_key").status, CheckStatus::Ok);
+
+    let cfg = config(&server(&missing), "");
+    assert_eq!(
+        check(&run_checks(&cfg), "host_key").status,
+        CheckStatus::Fail
+    );
+}
+
+#[test]
+fn listeners_sharing_a_port() {
+    let dir = TempDir::new().unwrap();
+    let base = server(dir.path());
+    let cfg = config(&format!("{base}\nsocks5_listen = \"127.0.0.1:1080\""), "");
+    assert_eq!(
+        check(&run_checks(&cfg), "listeners").status,
+        CheckStatus::Ok
+    );
+
+    // The wildcard SSH address covers 127.0.0.1
+    let cfg = config(&format!("{base}\nsocks5_listen = \"127.0.0.1:2222\""), "");
+    let checks = run_checks(&cfg);
+    let listeners = check(&checks, "listeners");
+    assert_eq!(listeners.status, CheckStatus::Fail);
+    assert!(listeners.detail.contains("socks5"), "{}", listeners.detail);
+}
+
+#[test]
+fn api_exposure_warned() {
+    let dir = TempDir::new().unwrap();
+    let base = server(dir.path());
+    let api = "[api]\nenabled = true\ntoken = \"0123456789abcdef0123456789abcdef\"\n";
+    let cfg = config(&base, &format!("{api}listen = \"127.0.0.1:9091\""));
+    assert_eq!(check(&run_checks(&cfg), "api").status, CheckStatus::Ok);
+
+    let cfg = config(&base, &format!("{api}listen = \"0.0.0.0:9091\""));
+    assert_eq!(check(&run_checks(&cfg), "api").status, CheckStatus::Warn);
+
+    let cfg = config(&base, "");
+    assert!(run_checks(&cfg).iter().all(|c| c.name != "api"));
+}
+
+#[cfg(unix)]
+#[test]
+fn config_file_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let warning = config_file_check(&path).unwrap();
+    assert_eq!(warning.status, CheckStatus::Warn);
+    assert!(warning.detail.contains("0644"));
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    assert!(config_file_check(&path).is_none());
+}
+
+#[test]
+fn report_and_doctor_exit_status() {
+    let dir = TempDir::new().unwrap();
+    let report = render_checks(&run_checks(&config(&server(dir.path()), "")));
+    assert!(report.contains("  warn  host_key"), "{report}");
+    assert!(
+        report.ends_with("2 ok, 1 warnings, 0 failures\n"),
+        "{report}"
+    );
+
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, app_config_toml(&server(dir.path()), "")).unwrap();
+    assert!(doctor_cli(&path, true).is_ok());
+
+    let missing = dir.path().join("missing");
+    std::fs::write(&path, app_config_toml(&server(&missing), "")).unwrap();
+    let err = doctor_cli(&path, false).unwrap_err();
+    assert_eq!(err.to_string(), "1 check(s) failed");
+}
+
+// ---------------------------------------------------------------------------
+// Bundle contents
+// ---------------------------------------------------------------------------
+
+#[test]
+fn warnings_and_errors_captured() {
+    let subscriber = tracing_subscriber::registry().with(RecentErrorsLayer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: "diagnostics_test", "not kept");
+        tracing::warn!(target: "diagnostics_test", user = "alice", port = 22, "Upstream slow");
+        tracing::error!(target: "diagnostics_test", "Relay failed");
+    });
+    let ours: Vec<_> = recent_errors()
+        .into_iter()
+        .filter(|r| r.target == "diagnostics_test")
+        .collect();
+    assert_eq!(ours.len(), 2);
+    assert_eq!(ours[0].level, "WARN");
+    assert_eq!(ours[0].message, "Upstream slow user=alice port=22");
+    assert_eq!(ours[1].level, "ERROR");
+    assert_eq!(ours[1].message, "Relay failed");
+}
+
+#[test]
+fn tarball_holds_files_under_root() {
+    let gz = tar_gz(
+        "s5-debug-test",
+        &[
+            ("version.json", b"{}".to_vec()),
+            ("config.toml", b"[server]\n".to_vec()),
+        ],
+    )
+    .unwrap();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(gz.as_slice()));
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        let path = entry.path().unwrap().display().to_string();
+        entries.push((path, contents));
+    }
+    assert_eq!(
+        entries,
+        vec![
+            ("s5-debug-test/version.json".to_string(), "{}".to_string()),
+            (
+                "s5-debug-test/config.toml".to_string(),
+                "[server]\n".to_string()
+            ),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn runtime_stats_reported() {
+    let stats = runtime_stats().unwrap();
+    assert_eq!(stats.workers, 2);
+    assert_eq!(stats.flavor, "MultiThread");
+}
+
+#[test]
+fn no_runtime_stats_outside_tokio() {
+    assert!(runtime_stats().is_none());
+}
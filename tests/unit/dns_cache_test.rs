@@ -1,3 +1,5 @@
+use s5::config::parse_config;
+use s5::config::types::{AppConfig, DnsEvictionPolicy};
 use s5::proxy::dns_cache::DnsCache;
 use std::net::SocketAddr;
 use std::sync::atomic::Ordering;
@@ -367,3 +369,312 @@ async fn concurrent_access() {
     // At minimum some lookups happened (readers did 10*100*2 = 2000 lookups)
     assert!(total_lookups > 0 || !cache.is_empty());
 }
+
+// -- 18. prefetch_candidates_rank_by_use --------------------------------------
+
+#[test]
+fn prefetch_candidates_rank_by_use() {
+    // 1s TTL: every entry is within the 1s prefetch margin
+    let cache = DnsCache::new(1, 100);
+    cache.insert("a.com:443", public_addrs(), None);
+    cache.insert("b.com:443", public_addrs(), None);
+    cache.insert("c.com:443", public_addrs(), None);
+    let _ = cache.get("b.com:443", false);
+    let _ = cache.get("b.com:443", false);
+    let _ = cache.get("c.com:443", false);
+
+    assert_eq!(cache.prefetch_candidates(2), vec!["b.com:443", "c.com:443"]);
+    assert!(cache.prefetch_candidates(0).is_empty());
+}
+
+// -- 19. refreshed_entries_wait_for_next_use ----------------------------------
+
+#[test]
+fn refreshed_entries_wait_for_next_use() {
+    let cache = DnsCache::new(1, 100);
+    cache.insert("a.com:443", public_addrs(), None);
+    cache.insert("b.com:443", public_addrs(), None);
+    let _ = cache.get("b.com:443", false);
+
+    cache.refresh("b.com:443", vec![addr("9.9.9.9:443")], None);
+    assert_eq!(cache.prefetch_candidates(10), vec!["a.com:443"]);
+
+    // The next use makes it hot again, with its use count intact
+    assert_eq!(
+        cache.get("b.com:443", false),
+        Some(vec![addr("9.9.9.9:443")])
+    );
+    assert_eq!(cache.prefetch_candidates(1), vec!["b.com:443"]);
+
+    // Evicted keys are not re-added by a refresh
+    cache.refresh("gone.com:443", public_addrs(), None);
+    assert_eq!(cache.len(), 2);
+}
+
+// -- 20. fresh_entries_are_not_prefetched -------------------------------------
+
+#[test]
+fn fresh_entries_are_not_prefetched() {
+    let cache = DnsCache::new(60, 100);
+    cache.insert("a.com:443", public_addrs(), None);
+    let _ = cache.get("a.com:443", false);
+    assert!(cache.prefetch_candidates(10).is_empty());
+}
+
+// -- 21. prefetch_hot_re_resolves ---------------------------------------------
+
+#[tokio::test]
+async fn prefetch_hot_re_resolves() {
+    use s5::proxy::connector::prefetch_hot;
+
+    let cache = DnsCache::new(1, 100);
+    cache.insert("localhost:80", vec![addr("192.0.2.1:80")], None);
+
+    // Loopback is blocked by ip_guard: the entry is left alone
+    assert_eq!(prefetch_hot(&cache, 10, 5, true, None).await, 0);
+    assert_eq!(
+        cache.get("localhost:80", false),
+        Some(vec![addr("192.0.2.1:80")])
+    );
+
+    assert_eq!(prefetch_hot(&cache, 10, 5, false, None).await, 1);
+    let addrs = cache.get("localhost:80", false).unwrap();
+    assert!(addrs.iter().all(|a| a.ip().is_loopback()), "{addrs:?}");
+}
+
+// -- 22. ttl_bounds_clamp_every_mode ------------------------------------------
+
+#[test]
+fn ttl_bounds_clamp_every_mode() {
+    // Native TTL of 0s raised to min_ttl: still cached after a short wait
+    let cache =
+        DnsCache::new(-1, 100).with_ttl_bounds(Duration::from_secs(30), Duration::from_secs(60));
+    cache.insert("short.com:443", public_addrs(), Some(Duration::ZERO));
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(cache.get("short.com:443", false).is_some());
+
+    // Custom TTL lowered to max_ttl: gone once max_ttl has passed
+    let cache = DnsCache::new(3600, 100).with_ttl_bounds(Duration::ZERO, Duration::from_millis(10));
+    cache.insert("long.com:443", public_addrs(), None);
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(cache.get("long.com:443", false).is_none());
+}
+
+// -- 23. lfu_evicts_least_used ------------------------------------------------
+
+#[test]
+fn lfu_evicts_least_used() {
+    let cache = DnsCache::new(60, 2).with_eviction(DnsEvictionPolicy::Lfu);
+    cache.insert("a.com:443", public_addrs(), None);
+    cache.insert("b.com:443", public_addrs(), None);
+    let _ = cache.get("a.com:443", false);
+    let _ = cache.get("a.com:443", false);
+    std::thread::sleep(Duration::from_millis(2));
+    // b.com becomes the most recently used, but is still the least used
+    let _ = cache.get("b.com:443", false);
+
+    cache.insert("c.com:443", public_addrs(), None);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("a.com:443", false).is_some());
+    assert!(cache.get("b.com:443", false).is_none());
+    assert_eq!(cache.evictions.load(Ordering::Relaxed), 1);
+}
+
+// -- 24. fifo_evicts_oldest_insert --------------------------------------------
+
+#[test]
+fn fifo_evicts_oldest_insert() {
+    let cache = DnsCache::new(60, 2).with_eviction(DnsEvictionPolicy::Fifo);
+    cache.insert("a.com:443", public_addrs(), None);
+    std::thread::sleep(Duration::from_millis(2));
+    cache.insert("b.com:443", public_addrs(), None);
+    std::thread::sleep(Duration::from_millis(2));
+    // Recent use does not save the oldest entry
+    let _ = cache.get("a.com:443", false);
+
+    cache.insert("c.com:443", public_addrs(), None);
+    assert!(cache.get("a.com:443", false).is_none());
+    assert!(cache.get("b.com:443", false).is_some());
+    assert!(cache.get("c.com:443", false).is_some());
+
+    // Re-inserting a cached key never evicts
+    cache.insert("c.com:443", public_addrs(), None);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.evictions.load(Ordering::Relaxed), 1);
+}
+
+// -- 25. from_config_reads_proxy_dns_cache ------------------------------------
+
+fn dns_config(section: &str) -> anyhow::Result<AppConfig> {
+    parse_config(&format!(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:2222"
+dns_cache_max_entries = 5
+
+[proxy.dns_cache]
+{section}
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+    ))
+}
+
+#[test]
+fn from_config_reads_proxy_dns_cache() {
+    let config = dns_config("").unwrap();
+    assert_eq!(config.proxy.dns_cache.min_ttl, 0);
+    assert_eq!(config.proxy.dns_cache.max_ttl, 3600);
+    assert_eq!(config.proxy.dns_cache.eviction, DnsEvictionPolicy::Lru);
+
+    // server.dns_cache_max_entries applies without an override
+    let cache = DnsCache::from_config(&config);
+    for i in 0..10 {
+        cache.insert(&format!("h{i}.com:443"), public_addrs(), None);
+    }
+    assert_eq!(cache.len(), 5);
+
+    let config = dns_config("max_entries = 2\neviction = \"fifo\"\nmin_ttl = 5").unwrap();
+    assert_eq!(config.proxy.dns_cache.eviction, DnsEvictionPolicy::Fifo);
+    let cache = DnsCache::from_config(&config);
+    for i in 0..10 {
+        cache.insert(&format!("h{i}.com:443"), public_addrs(), None);
+    }
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.evictions.load(Ordering::Relaxed), 8);
+}
+
+// -- 26. invalid_dns_cache_bounds_rejected ------------------------------------
+
+#[test]
+fn invalid_dns_cache_bounds_rejected() {
+    assert!(dns_config("min_ttl = 120\nmax_ttl = 60").is_err());
+    assert!(dns_config("max_ttl = 0").is_err());
+    assert!(dns_config("max_entries = 0").is_err());
+    assert!(dns_config("eviction = \"random\"").is_err());
+    assert!(dns_config("min_ttl = 60\nmax_ttl = 60").is_ok());
+}
+
+// -- 27. evictions_are_reported_in_metrics ------------------------------------
+
+#[test]
+fn evictions_are_reported_in_metrics() {
+    use prometheus_client::encoding::text::encode;
+    use s5::metrics::MetricsRegistry;
+    use std::sync::Arc;
+
+    let metrics = Arc::new(MetricsRegistry::new());
+    let mut cache = DnsCache::new(60, 1);
+    cache.set_metrics(metrics.clone());
+    cache.insert("a.com:443", public_addrs(), None);
+    cache.insert("b.com:443", public_addrs(), None);
+    cache.insert("c.com:443", public_addrs(), None);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    let line = buffer
+        .lines()
+        .find(|l| l.starts_with("s5_dns_cache_evictions_total") && l.contains("capacity"))
+        .expect("capacity eviction series present");
+    assert!(line.ends_with(" 2"), "{line}");
+}
+
+// -- 28. connect_attempts_follow_measured_latency -----------------------------
+
+#[test]
+fn connect_attempts_follow_measured_latency() {
+    let cache = DnsCache::new(60, 100);
+    let (slow, fast, failed, unknown) = (
+        addr("93.184.216.1:443"),
+        addr("93.184.216.2:443"),
+        addr("93.184.216.3:443"),
+        addr("93.184.216.4:443"),
+    );
+    cache.record_connect(slow, Some(Duration::from_millis(200)));
+    cache.record_connect(fast, Some(Duration::from_millis(20)));
+    cache.record_connect(failed, None);
+
+    let mut addrs = vec![failed, unknown, slow, fast];
+    cache.order_by_latency(&mut addrs);
+    assert_eq!(addrs, [fast, slow, unknown, failed]);
+
+    // A success clears the failures; a failure demotes a fast address
+    cache.record_connect(failed, Some(Duration::from_millis(50)));
+    cache.record_connect(fast, None);
+    let mut addrs = vec![unknown, slow, fast, failed];
+    cache.order_by_latency(&mut addrs);
+    assert_eq!(addrs, [failed, slow, unknown, fast]);
+}
+
+// -- 29. connect_latency_is_smoothed ------------------------------------------
+
+#[test]
+fn connect_latency_is_smoothed() {
+    let cache = DnsCache::new(60, 100);
+    let target = addr("93.184.216.34:443");
+    assert_eq!(cache.connect_latency(&target), None);
+    cache.record_connect(target, Some(Duration::from_millis(100)));
+    assert_eq!(
+        cache.connect_latency(&target),
+        Some(Duration::from_millis(100))
+    );
+    // One slow connect moves the estimate part of the way
+    cache.record_connect(target, Some(Duration::from_millis(200)));
+    let rtt = cache.connect_latency(&target).unwrap();
+    assert!(rtt > Duration::from_millis(120) && rtt < Duration::from_millis(140));
+}
+
+// -- 30. latency_measurements_expire ------------------------------------------
+
+#[test]
+fn latency_measurements_expire() {
+    let cache = DnsCache::new(60, 100).with_latency_ordering(true, Duration::from_millis(50));
+    let (slow, fast) = (addr("93.184.216.1:443"), addr("93.184.216.2:443"));
+    cache.record_connect(fast, Some(Duration::from_millis(10)));
+    cache.record_connect(slow, None);
+    std::thread::sleep(Duration::from_millis(80));
+
+    assert_eq!(cache.connect_latency(&fast), None);
+    let mut addrs = vec![slow, fast];
+    cache.order_by_latency(&mut addrs);
+    assert_eq!(addrs, [slow, fast]);
+
+    // A stale failure count does not carry over
+    cache.record_connect(slow, Some(Duration::from_millis(30)));
+    cache.order_by_latency(&mut addrs);
+    assert_eq!(addrs, [slow, fast]);
+}
+
+// -- 31. latency_ordering_can_be_disabled -------------------------------------
+
+#[test]
+fn latency_ordering_can_be_disabled() {
+    let cache = DnsCache::new(60, 100).with_latency_ordering(false, Duration::from_secs(600));
+    assert!(!cache.orders_by_latency());
+    let (first, second) = (addr("93.184.216.1:443"), addr("93.184.216.2:443"));
+    cache.record_connect(first, None);
+    cache.record_connect(second, Some(Duration::from_millis(5)));
+    assert_eq!(cache.connect_latency(&second), None);
+
+    let mut addrs = vec![first, second];
+    cache.order_by_latency(&mut addrs);
+    assert_eq!(addrs, [first, second]);
+}
+
+// -- 32. latency_ordering_config ----------------------------------------------
+
+#[test]
+fn latency_ordering_config() {
+    let config = dns_config("").unwrap();
+    assert!(config.proxy.dns_cache.latency_ordering);
+    assert_eq!(config.proxy.dns_cache.latency_ttl, 600);
+    assert!(DnsCache::from_config(&config).orders_by_latency());
+
+    let config = dns_config("latency_ordering = false\nlatency_ttl = 0").unwrap();
+    assert!(!DnsCache::from_config(&config).orders_by_latency());
+
+    assert!(dns_config("latency_ttl = 0").is_err());
+    assert!(dns_config("latency_ttl = 30").is_ok());
+}
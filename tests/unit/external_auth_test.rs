@@ -0,0 +1,240 @@
+use crate::test_support::{app_config_toml, parse_app_config};
+use s5::auth::external::{authenticate, ExternalAuthResponse};
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::redact::redact_config;
+use s5::config::types::{AppConfig, AuthBackend};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+/// Allows `good` (adding bob to group `staff`), denies everything else.
+const HOOK: &str = r#"read line
+case "$line" in
+  *'"password":"good"'*) echo '{"group":"staff"}' ;;
+  *) exit 1 ;;
+esac"#;
+
+/// `[security]` with `security`, and group `staff`.
+fn security_toml(security: &str) -> String {
+    format!(
+        "[security]\nban_enabled = false\n{security}\n\n\
+         [[groups]]\nname = \"staff\"\nmax_connections_per_user = 7\n"
+    )
+}
+
+fn config_with(security: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&security_toml(security), "")
+}
+
+/// Same as [`config_with`], without any `[[users]]` entry.
+fn config_without_users(security: &str) -> anyhow::Result<AppConfig> {
+    let toml = app_config_toml(&security_toml(security), "");
+    let (toml, _users) = toml.split_once("[[users]]").unwrap();
+    parse_config(toml)
+}
+
+fn hook_config(hook: &str) -> AppConfig {
+    let security = format!(
+        "auth_backend = \"external\"\n[security.external_auth]\ncommand = [\"sh\", \"-c\", {:?}]\n",
+        hook
+    );
+    config_without_users(&security).unwrap()
+}
+
+fn peer() -> SocketAddr {
+    "198.51.100.20:50000".parse().unwrap()
+}
+
+#[test]
+fn external_auth_config_validation() {
+    let config = config_with("").unwrap();
+    assert_eq!(config.security.auth_backend, AuthBackend::Local);
+    assert!(config.security.external_auth.is_none());
+
+    // No users needed with an external backend
+    let config = hook_config("exit 0");
+    assert_eq!(config.security.auth_backend, AuthBackend::External);
+    assert_eq!(
+        config.security.external_auth.as_ref().unwrap().timeout_secs,
+        5
+    );
+
+    // Backend without a hook
+    assert!(config_without_users("auth_backend = \"external\"").is_err());
+    // Local backend still needs users
+    assert!(config_without_users("").is_err());
+    // Both command and url
+    let both = "auth_backend = \"external\"\n[security.external_auth]\n\
+                command = [\"/bin/true\"]\nurl = \"https://auth.example.com/check\"\n";
+    assert!(config_with(both).is_err());
+    // Secret without url
+    let secret = "auth_backend = \"external\"\n[security.external_auth]\n\
+                  command = [\"/bin/true\"]\nsecret = \"s\"\n";
+    assert!(config_with(secret).is_err());
+    // Loopback endpoint needs allow_private_ips
+    let local = "auth_backend = \"external\"\n[security.external_auth]\n\
+                 url = \"http://127.0.0.1:9000/check\"\n";
+    assert!(config_with(local).is_err());
+    let local = format!("{local}allow_private_ips = true\n");
+    assert!(config_with(&local).is_ok());
+}
+
+#[test]
+fn external_auth_secret_is_redacted() {
+    let security = "auth_backend = \"external\"\n[security.external_auth]\n\
+                    url = \"https://auth.example.com/check\"\nsecret = \"hunter2\"\n";
+    let config = config_with(security).unwrap();
+    let redacted = redact_config(&config);
+    let external = redacted.security.external_auth.unwrap();
+    assert_eq!(external.secret.as_deref(), Some("***"));
+    assert!(!format!("{:?}", config.security).contains("hunter2"));
+}
+
+#[test]
+fn hook_answer_parsing() {
+    let blank = ExternalAuthResponse::parse(b"\n").unwrap();
+    assert!(blank.allow);
+    assert!(blank.group.is_none());
+
+    let denied = ExternalAuthResponse::parse(br#"{"allow": false}"#).unwrap();
+    assert!(!denied.allow);
+
+    let answer = ExternalAuthResponse::parse(
+        br#"{"group": "staff", "quotas": {"daily_bandwidth_bytes": 1024}, "extra": 1}"#,
+    )
+    .unwrap();
+    assert!(answer.allow);
+    assert_eq!(answer.group.as_deref(), Some("staff"));
+    assert_eq!(answer.quotas.unwrap().daily_bandwidth_bytes, 1024);
+
+    assert!(ExternalAuthResponse::parse(b"yes").is_err());
+    assert!(ExternalAuthResponse::parse(&vec![b' '; 70_000]).is_err());
+}
+
+#[tokio::test]
+async fn local_backend_skips_the_hook() {
+    let config = config_with("").unwrap();
+    let auth = RwLock::new(AuthService::new(&config).unwrap());
+    assert!(auth.read().await.external().is_none());
+    assert_eq!(
+        authenticate(&auth, "alice", "x", &peer(), "ssh").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn command_hook_allows_and_sets_group() {
+    let config = hook_config(HOOK);
+    let auth = RwLock::new(AuthService::new(&config).unwrap());
+    assert!(auth.read().await.user_store().is_empty());
+
+    assert_eq!(
+        authenticate(&auth, "bob", "bad", &peer(), "ssh").await,
+        Some(false)
+    );
+    assert!(auth.read().await.user_store().get("bob").is_none());
+
+    assert_eq!(
+        authenticate(&auth, "bob", "good", &peer(), "socks5").await,
+        Some(true)
+    );
+    let store = auth.read().await.user_store().clone();
+    let bob = store.get("bob").unwrap();
+    assert_eq!(bob.group.as_deref(), Some("staff"));
+    assert_eq!(bob.max_connections, 7);
+    assert!(bob.password_hash.is_none());
+
+    // Users from the hook survive a reload
+    auth.write().await.reload(&config).unwrap();
+    assert!(auth.read().await.user_store().get("bob").is_some());
+}
+
+#[tokio::test]
+async fn command_hook_errors_deny() {
+    // Unknown group
+    let auth = RwLock::new(AuthService::new(&hook_config("echo '{\"group\":\"nope\"}'")).unwrap());
+    assert_eq!(
+        authenticate(&auth, "bob", "x", &peer(), "ssh").await,
+        Some(false)
+    );
+
+    // Garbage output
+    let auth = RwLock::new(AuthService::new(&hook_config("echo ok")).unwrap());
+    assert_eq!(
+        authenticate(&auth, "bob", "x", &peer(), "ssh").await,
+        Some(false)
+    );
+
+    // Too slow
+    let mut config = hook_config("sleep 5");
+    config.security.external_auth.as_mut().unwrap().timeout_secs = 1;
+    let auth = RwLock::new(AuthService::new(&config).unwrap());
+    assert_eq!(
+        authenticate(&auth, "bob", "x", &peer(), "ssh").await,
+        Some(false)
+    );
+}
+
+#[tokio::test]
+async fn http_hook_receives_signed_credentials() {
+    let received = Arc::new(Mutex::new(Vec::<(String, Option<String>)>::new()));
+    let received_clone = received.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().route(
+            "/check",
+            axum::routing::post(move |headers: axum::http::HeaderMap, body: String| {
+                let recv = received_clone.clone();
+                async move {
+                    let sig = headers
+                        .get("X-Signature-256")
+                        .map(|v| v.to_str().unwrap().to_string());
+                    let allowed = body.contains("\"password\":\"good\"");
+                    recv.lock().await.push((body, sig));
+                    if allowed {
+                        (
+                            axum::http::StatusCode::OK,
+                            r#"{"quotas":{"daily_connection_limit":3}}"#,
+                        )
+                    } else {
+                        (axum::http::StatusCode::FORBIDDEN, "")
+                    }
+                }
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let security = format!(
+        "auth_backend = \"external\"\n[security.external_auth]\n\
+         url = \"http://127.0.0.1:{port}/check\"\nsecret = \"s3cret\"\nallow_private_ips = true\n"
+    );
+    let config = parse_app_config(&security_toml(&security), "allow_shell = false").unwrap();
+    let auth = RwLock::new(AuthService::new(&config).unwrap());
+
+    assert_eq!(
+        authenticate(&auth, "alice", "bad", &peer(), "ssh").await,
+        Some(false)
+    );
+    assert_eq!(
+        authenticate(&auth, "alice", "good", &peer(), "ssh").await,
+        Some(true)
+    );
+
+    // The [[users]] entry is the profile, the hook adds quotas
+    let store = auth.read().await.user_store().clone();
+    let alice = store.get("alice").unwrap();
+    assert!(!alice.allow_shell);
+    assert_eq!(alice.quotas.as_ref().unwrap().daily_connection_limit, 3);
+
+    let received = received.lock().await;
+    assert_eq!(received.len(), 2);
+    let (body, sig) = &received[1];
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["source_ip"], "198.51.100.20");
+    assert_eq!(json["protocol"], "ssh");
+    assert!(sig.as_deref().unwrap().starts_with("sha256="));
+}
@@ -0,0 +1,239 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use s5::config::types::FlowLogConfig;
+use s5::flows::{FlowLog, FlowQuery, FlowRecord, FlowStore, CLOSE_NORMAL, MAX_QUERY_LIMIT};
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn flow(user: &str, host: &str, port: u16) -> FlowRecord {
+    FlowRecord {
+        dest_ip: Some("203.0.113.7".to_string()),
+        bytes_up: 100,
+        bytes_down: 2000,
+        correlation_id: Some(format!("cid-{}", user)),
+        ..FlowRecord::closing(
+            "socks5",
+            user,
+            "198.51.100.1",
+            host,
+            port,
+            Duration::from_millis(1500),
+        )
+    }
+}
+
+fn populated_store() -> FlowStore {
+    let store = FlowStore::open_in_memory().unwrap();
+    let mut denied = flow("bob", "blocked.test", 25);
+    denied.close_reason = "acl_denied".to_string();
+    denied.dest_ip = None;
+    store
+        .insert(&[
+            flow("alice", "example.com", 443),
+            flow("alice", "example.org", 80),
+            flow("bob", "example.com", 443),
+            denied,
+        ])
+        .unwrap();
+    store
+}
+
+#[test]
+fn closing_sets_duration_and_start_time() {
+    let before = Utc::now();
+    let f = FlowRecord::closing(
+        "ssh",
+        "alice",
+        "10.0.0.1",
+        "db.internal",
+        5432,
+        Duration::from_secs(10),
+    );
+    assert_eq!(f.duration_ms, 10_000);
+    assert_eq!(f.close_reason, CLOSE_NORMAL);
+    assert_eq!(f.bytes_up, 0);
+    assert!(f.started_at <= before - ChronoDuration::seconds(9));
+    assert!(f.id.is_none());
+}
+
+#[test]
+fn insert_and_query_round_trip() {
+    let store = populated_store();
+    assert_eq!(store.count().unwrap(), 4);
+
+    let rows = store.query(&FlowQuery::default()).unwrap();
+    assert_eq!(rows.len(), 4);
+    // Newest first
+    assert_eq!(rows[0].username, "bob");
+    assert_eq!(rows[0].close_reason, "acl_denied");
+    assert!(rows[0].dest_ip.is_none());
+
+    let last = &rows[3];
+    assert_eq!(last.username, "alice");
+    assert_eq!(last.dest_host, "example.com");
+    assert_eq!(last.dest_ip.as_deref(), Some("203.0.113.7"));
+    assert_eq!(last.dest_port, 443);
+    assert_eq!(last.bytes_up, 100);
+    assert_eq!(last.bytes_down, 2000);
+    assert_eq!(last.duration_ms, 1500);
+    assert_eq!(last.protocol, "socks5");
+    assert_eq!(last.correlation_id.as_deref(), Some("cid-alice"));
+    assert!(last.id.is_some());
+}
+
+#[test]
+fn query_filters_combine() {
+    let store = populated_store();
+    let q = |q: FlowQuery| store.query(&q).unwrap();
+
+    let alice = q(FlowQuery {
+        user: Some("alice".into()),
+        ..Default::default()
+    });
+    assert_eq!(alice.len(), 2);
+
+    let https = q(FlowQuery {
+        port: Some(443),
+        ..Default::default()
+    });
+    assert_eq!(https.len(), 2);
+
+    let alice_com = q(FlowQuery {
+        user: Some("alice".into()),
+        dest: Some("example.com".into()),
+        ..Default::default()
+    });
+    assert_eq!(alice_com.len(), 1);
+
+    // `dest` also matches the resolved IP
+    let by_ip = q(FlowQuery {
+        dest: Some("203.0.113.7".into()),
+        ..Default::default()
+    });
+    assert_eq!(by_ip.len(), 3);
+
+    let denied = q(FlowQuery {
+        close_reason: Some("acl_denied".into()),
+        ..Default::default()
+    });
+    assert_eq!(denied.len(), 1);
+    assert_eq!(denied[0].dest_host, "blocked.test");
+
+    let by_cid = q(FlowQuery {
+        correlation_id: Some("cid-alice".into()),
+        ..Default::default()
+    });
+    assert_eq!(by_cid.len(), 2);
+    assert!(by_cid.iter().all(|f| f.username == "alice"));
+
+    let none = q(FlowQuery {
+        source_ip: Some("192.0.2.99".into()),
+        ..Default::default()
+    });
+    assert!(none.is_empty());
+}
+
+#[test]
+fn query_time_window_and_paging() {
+    let store = populated_store();
+
+    let future = store
+        .query(&FlowQuery {
+            since: Some(Utc::now() + ChronoDuration::hours(1)),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(future.is_empty());
+
+    let past = store
+        .query(&FlowQuery {
+            until: Some(Utc::now() + ChronoDuration::hours(1)),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(past.len(), 4);
+
+    let page1 = store
+        .query(&FlowQuery {
+            limit: Some(3),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(page1.len(), 3);
+    let page2 = store
+        .query(&FlowQuery {
+            limit: Some(3),
+            before_id: page1.last().unwrap().id,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(page2.len(), 1);
+    assert_eq!(page2[0].dest_host, "example.com");
+    assert_eq!(page2[0].username, "alice");
+}
+
+#[test]
+fn query_limit_is_clamped() {
+    let q = FlowQuery {
+        limit: Some(1_000_000),
+        ..Default::default()
+    };
+    assert_eq!(q.effective_limit(), MAX_QUERY_LIMIT);
+    let q = FlowQuery {
+        limit: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(q.effective_limit(), 1);
+}
+
+#[test]
+fn prune_removes_old_flows() {
+    let store = FlowStore::open_in_memory().unwrap();
+    let mut old = flow("alice", "old.test", 80);
+    old.started_at = Utc::now() - ChronoDuration::days(40);
+    store.insert(&[old, flow("alice", "new.test", 80)]).unwrap();
+
+    let removed = store
+        .prune_before(Utc::now() - ChronoDuration::days(30))
+        .unwrap();
+    assert_eq!(removed, 1);
+    let rows = store.query(&FlowQuery::default()).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].dest_host, "new.test");
+}
+
+#[test]
+fn store_persists_to_disk() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sub").join("flows.db");
+    FlowStore::open(&path)
+        .unwrap()
+        .insert(&[flow("alice", "example.com", 443)])
+        .unwrap();
+    let reopened = FlowStore::open(&path).unwrap();
+    assert_eq!(reopened.count().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn flow_log_records_asynchronously() {
+    let dir = TempDir::new().unwrap();
+    let config = FlowLogConfig {
+        enabled: true,
+        path: dir.path().join("flows.db"),
+        retention_days: 30,
+        ..Default::default()
+    };
+    let log = FlowLog::start(&config).unwrap();
+    log.record(flow("alice", "example.com", 443));
+    log.record(flow("bob", "example.net", 22));
+
+    let mut rows = Vec::new();
+    for _ in 0..50 {
+        rows = log.query(FlowQuery::default()).await.unwrap();
+        if rows.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(rows.len(), 2);
+    assert_eq!(log.dropped_count(), 0);
+}
@@ -13,6 +13,9 @@ fn test_relay_config(idle_timeout: Duration, context: &str) -> RelayConfig {
         quotas: None,
         audit: None,
         session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     }
 }
 
@@ -138,3 +141,33 @@ async fn test_byte_counting() {
     assert_eq!(bytes_up, 10); // client → server
     assert_eq!(bytes_down, 5); // server → client
 }
+
+#[tokio::test]
+async fn test_forwarding_tasks_counted() {
+    let (mut client_rw, relay_client) = tokio::io::duplex(4096);
+    let (mut server_rw, relay_server) = tokio::io::duplex(4096);
+    let before = forwarder::forwarding_tasks();
+
+    let relay_handle = tokio::spawn(async move {
+        forwarder::relay(
+            relay_client,
+            relay_server,
+            test_relay_config(Duration::from_secs(5), "test@tasks:80"),
+        )
+        .await
+        .unwrap()
+    });
+
+    // Once data flows both direction tasks are running
+    client_rw.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 64];
+    let _ = server_rw.read(&mut buf).await.unwrap();
+    let during = forwarder::forwarding_tasks();
+    assert!(during.spawned >= before.spawned + 2);
+    assert!(during.active >= 2);
+
+    drop(client_rw);
+    drop(server_rw);
+    relay_handle.await.unwrap();
+    assert!(forwarder::forwarding_tasks().spawned >= during.spawned);
+}
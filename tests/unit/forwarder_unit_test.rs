@@ -13,6 +13,9 @@ fn test_relay_config(idle_timeout: Duration, context: &str) -> RelayConfig {
         quotas: None,
         audit: None,
         session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     }
 }
 
@@ -276,6 +279,9 @@ async fn relay_with_bandwidth_limit_still_transfers_data() {
         quotas: None,
         audit: None,
         session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     };
 
     let handle = tokio::spawn(async move {
@@ -311,6 +317,7 @@ async fn relay_updates_session_byte_counters() {
 
     let session = Arc::new(LiveSession {
         session_id: "test-s0".to_string(),
+        correlation_id: None,
         username: "alice".to_string(),
         target_host: "host".to_string(),
         target_port: 80,
@@ -319,6 +326,13 @@ async fn relay_updates_session_byte_counters() {
         bytes_up: AtomicU64::new(0),
         bytes_down: AtomicU64::new(0),
         protocol: "ssh".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Default::default(),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
     });
 
     let config = RelayConfig {
@@ -331,6 +345,9 @@ async fn relay_updates_session_byte_counters() {
         quotas: None,
         audit: None,
         session: Some(session.clone()),
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     };
 
     let handle = tokio::spawn(async move {
@@ -396,6 +413,9 @@ fn relay_config_with_username() {
         quotas: None,
         audit: None,
         session: None,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
     };
 
     assert_eq!(config.username.as_deref(), Some("alice"));
@@ -443,3 +463,143 @@ async fn relay_multiple_writes_accumulate_bytes() {
     assert_eq!(up, 10); // 5 * 2 bytes
     assert_eq!(down, 9); // 3 * 3 bytes
 }
+
+// ---------------------------------------------------------------------------
+// Stalled client (SSH channel window)
+// ---------------------------------------------------------------------------
+
+fn metric_value(metrics: &s5::metrics::MetricsRegistry, name: &str) -> String {
+    let mut buffer = String::new();
+    prometheus_client::encoding::text::encode(&mut buffer, &metrics.registry).unwrap();
+    buffer
+        .lines()
+        .find(|l| l.starts_with(name))
+        .and_then(|l| l.rsplit(' ').next())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[tokio::test]
+async fn slow_client_throttles_upstream_and_counts_stall() {
+    let metrics = std::sync::Arc::new(s5::metrics::MetricsRegistry::new());
+    // The client side holds 64 bytes, like an exhausted channel window
+    let (mut client, relay_client) = tokio::io::duplex(64);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let mut config = test_relay_config(Duration::from_secs(5), "test@stall:80");
+    config.stall_watch = Some(forwarder::StallWatch {
+        threshold: Duration::from_millis(50),
+        metrics: Some(metrics.clone()),
+    });
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        config,
+    ));
+
+    let payload = vec![7u8; 64 * 1024];
+    let upstream = tokio::spawn(async move {
+        server.write_all(&payload).await.unwrap();
+        server
+    });
+
+    // The upstream cannot get ahead of the client
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!upstream.is_finished());
+    assert_eq!(metric_value(&metrics, "s5_ssh_channels_stalled"), "1");
+    assert_eq!(metric_value(&metrics, "s5_ssh_channel_stalls_total"), "1");
+
+    // Once the client reads, the relay drains and the stall ends
+    let mut received = vec![0u8; 64 * 1024];
+    client.read_exact(&mut received).await.unwrap();
+    let server = upstream.await.unwrap();
+    assert_eq!(metric_value(&metrics, "s5_ssh_channels_stalled"), "0");
+
+    drop(client);
+    drop(server);
+    let outcome = relay.await.unwrap().unwrap();
+    assert_eq!(outcome.bytes_down, 64 * 1024);
+}
+
+/// A client window the test grants room in, each look taking all of it,
+/// until it opens for good. Looks wait on `adjusted` while it is full.
+#[derive(Default)]
+struct TestWindow {
+    granted: std::sync::atomic::AtomicUsize,
+    open: std::sync::atomic::AtomicBool,
+    adjusted: tokio::sync::Notify,
+}
+
+impl TestWindow {
+    fn grant(&self, room: usize) {
+        self.granted
+            .store(room, std::sync::atomic::Ordering::Relaxed);
+        self.adjusted.notify_one();
+    }
+
+    fn open(&self) {
+        self.open.store(true, std::sync::atomic::Ordering::Relaxed);
+        self.adjusted.notify_one();
+    }
+}
+
+impl forwarder::ClientWindow for TestWindow {
+    fn room(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = usize> + Send + '_>> {
+        Box::pin(async move {
+            loop {
+                let room = if self.open.load(std::sync::atomic::Ordering::Relaxed) {
+                    32 * 1024
+                } else {
+                    self.granted.swap(0, std::sync::atomic::Ordering::Relaxed)
+                };
+                if room > 0 {
+                    return room;
+                }
+                self.adjusted.notified().await;
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn full_client_window_stops_upstream_reads() {
+    let metrics = std::sync::Arc::new(s5::metrics::MetricsRegistry::new());
+    let window = std::sync::Arc::new(TestWindow::default());
+    let (mut client, relay_client) = tokio::io::duplex(64 * 1024);
+    let (mut server, relay_server) = tokio::io::duplex(64 * 1024);
+    let mut config = test_relay_config(Duration::from_secs(5), "test@window:80");
+    config.stall_watch = Some(forwarder::StallWatch {
+        threshold: Duration::from_millis(50),
+        metrics: Some(metrics.clone()),
+    });
+    config.client_window = Some(window.clone());
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        config,
+    ));
+
+    // Nothing is read from the destination while the window is full
+    server.write_all(b"hello world").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let mut received = [0u8; 11];
+    let early = tokio::time::timeout(Duration::from_millis(50), client.read(&mut received)).await;
+    assert!(early.is_err());
+    assert_eq!(metric_value(&metrics, "s5_ssh_channels_stalled"), "1");
+    assert_eq!(metric_value(&metrics, "s5_ssh_channel_stalls_total"), "1");
+
+    // Once it opens, the relay reads no more than the room granted
+    window.grant(4);
+    client.read_exact(&mut received[..4]).await.unwrap();
+    assert_eq!(&received[..4], b"hell");
+    let more = tokio::time::timeout(Duration::from_millis(100), client.read(&mut received[4..]));
+    assert!(more.await.is_err());
+    window.open();
+    client.read_exact(&mut received[4..]).await.unwrap();
+    assert_eq!(&received, b"hello world");
+
+    drop(client);
+    drop(server);
+    let outcome = relay.await.unwrap().unwrap();
+    assert_eq!(outcome.bytes_down, 11);
+    assert_eq!(metric_value(&metrics, "s5_ssh_channels_stalled"), "0");
+}
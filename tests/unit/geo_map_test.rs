@@ -0,0 +1,92 @@
+use crate::test_support::parse_app_config;
+use s5::audit::AuditLogger;
+use s5::proxy::geo_map::{SessionMap, SessionPoint, CELL_DEGREES};
+use s5::proxy::ProxyEngine;
+use std::sync::Arc;
+
+fn point(lat: f64, lon: f64, country: &str, username: &str) -> SessionPoint {
+    SessionPoint {
+        location: (lat, lon),
+        country: Some(country.to_string()),
+        username: username.to_string(),
+    }
+}
+
+#[test]
+fn nearby_sessions_share_a_cluster() {
+    let map = SessionMap::build(
+        vec![
+            point(48.85, 2.35, "FR", "alice"),
+            point(48.87, 2.33, "FR", "bob"),
+            point(48.86, 2.34, "FR", "alice"),
+            point(40.71, -74.0, "US", "carol"),
+        ],
+        2,
+    );
+    assert_eq!(map.clusters.len(), 2);
+    assert_eq!(map.unlocated, 2);
+    assert_eq!(map.located(), 4);
+
+    // Largest first, at the mean position
+    let paris = &map.clusters[0];
+    assert_eq!(paris.sessions, 3);
+    assert!((paris.lat - 48.86).abs() < 1e-9);
+    assert!((paris.lon - 2.34).abs() < 1e-9);
+    assert_eq!(paris.users, vec!["alice", "bob"]);
+    assert_eq!(paris.countries, vec!["FR"]);
+    assert_eq!(map.clusters[1].users, vec!["carol"]);
+}
+
+#[test]
+fn cells_split_on_grid_boundaries() {
+    // Same cell, even across countries
+    let map = SessionMap::build(
+        vec![point(0.1, 0.1, "GH", "a"), point(1.9, 1.9, "TG", "b")],
+        0,
+    );
+    assert_eq!(map.clusters.len(), 1);
+    assert_eq!(map.clusters[0].countries, vec!["GH", "TG"]);
+
+    // Either side of the equator and the prime meridian
+    let map = SessionMap::build(
+        vec![
+            point(0.1, 0.1, "GH", "a"),
+            point(-0.1, 0.1, "GH", "a"),
+            point(0.1, -0.1, "GH", "a"),
+            point(CELL_DEGREES + 0.1, 0.1, "GH", "a"),
+        ],
+        0,
+    );
+    assert_eq!(map.clusters.len(), 4);
+}
+
+#[test]
+fn unknown_country_left_out() {
+    let map = SessionMap::build(
+        vec![SessionPoint {
+            location: (10.0, 10.0),
+            country: None,
+            username: "alice".to_string(),
+        }],
+        0,
+    );
+    assert!(map.clusters[0].countries.is_empty());
+    let json = serde_json::to_value(&map).unwrap();
+    assert_eq!(json["clusters"][0]["sessions"], 1);
+    assert_eq!(json["unlocated"], 0);
+}
+
+#[tokio::test]
+async fn no_map_without_geoip_database() {
+    let engine = |geoip: &str| {
+        let config = parse_app_config(geoip, "").unwrap();
+        ProxyEngine::new(
+            Arc::new(config),
+            Arc::new(AuditLogger::new(None, 0, 0, None)),
+        )
+    };
+    assert!(engine("").session_map().is_none());
+    let missing = engine("[geoip]\nenabled = true\ndatabase_path = \"/nonexistent/City.mmdb\"");
+    missing.register_session("alice", "example.com", 443, "8.8.8.8", "ssh");
+    assert!(missing.session_map().is_none());
+}
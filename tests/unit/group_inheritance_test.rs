@@ -0,0 +1,235 @@
+use crate::test_support::parse_app_config;
+use s5::auth::AuthService;
+use s5::config::acl::AclPolicy;
+use s5::config::types::{resolve_group, AclPolicyConfig, AppConfig, GroupConfig, UserRole};
+
+/// alice in group "devs", with `groups` defined.
+fn config_with_groups(groups: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(groups, "group = \"devs\"")
+}
+
+const CHAIN: &str = r##"
+[[groups]]
+name = "base"
+max_bandwidth_kbps = 1000
+max_connections_per_user = 5
+allow_shell = false
+
+[groups.acl]
+default_policy = "deny"
+allow = ["*.internal:443"]
+
+[[groups]]
+name = "devs"
+inherits = "base"
+max_bandwidth_kbps = 5000
+role = "admin"
+
+[groups.acl]
+allow = ["github.com:22"]
+"##;
+
+// ---------------------------------------------------------------------------
+// Resolution
+// ---------------------------------------------------------------------------
+
+#[test]
+fn child_overrides_and_inherits_unset_fields() {
+    let config = config_with_groups(CHAIN).unwrap();
+    let devs = resolve_group("devs", &config.groups).unwrap();
+
+    assert_eq!(devs.name, "devs");
+    assert_eq!(devs.max_bandwidth_kbps, Some(5000));
+    assert_eq!(devs.max_connections_per_user, Some(5));
+    assert_eq!(devs.allow_shell, Some(false));
+    assert_eq!(devs.role, Some(UserRole::Admin));
+    assert_eq!(devs.acl.default_policy, Some(AclPolicyConfig::Deny));
+    assert_eq!(devs.acl.allow, vec!["*.internal:443", "github.com:22"]);
+}
+
+#[test]
+fn acl_inherit_false_drops_parent_rules() {
+    let groups = CHAIN.replace(
+        "allow = [\"github.com:22\"]",
+        "allow = [\"github.com:22\"]\ninherit = false",
+    );
+    let config = config_with_groups(&groups).unwrap();
+    let devs = resolve_group("devs", &config.groups).unwrap();
+
+    assert_eq!(devs.acl.allow, vec!["github.com:22"]);
+    assert_eq!(devs.acl.default_policy, None);
+    // Non-ACL fields are still inherited
+    assert_eq!(devs.max_connections_per_user, Some(5));
+}
+
+#[test]
+fn three_level_chain_resolves_nearest_first() {
+    let groups = format!(
+        r##"{CHAIN}
+[[groups]]
+name = "seniors"
+inherits = "devs"
+max_connections_per_user = 20
+"##
+    );
+    let config = config_with_groups(&groups).unwrap();
+    let seniors = resolve_group("seniors", &config.groups).unwrap();
+
+    assert_eq!(seniors.max_connections_per_user, Some(20));
+    assert_eq!(seniors.max_bandwidth_kbps, Some(5000));
+    assert_eq!(seniors.allow_shell, Some(false));
+}
+
+#[test]
+fn resolve_unknown_group_is_none() {
+    let groups: Vec<GroupConfig> = Vec::new();
+    assert!(resolve_group("missing", &groups).is_none());
+}
+
+#[test]
+fn user_receives_inherited_group_settings() {
+    let config = config_with_groups(CHAIN).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let alice = auth.user_store().get("alice").unwrap();
+
+    assert_eq!(alice.max_bandwidth_kbps, 5000);
+    assert_eq!(alice.max_connections, 5);
+    assert!(!alice.allow_shell);
+    assert_eq!(alice.acl.check("db.internal", 443, None), AclPolicy::Allow);
+    assert_eq!(alice.acl.check("example.com", 80, None), AclPolicy::Deny);
+}
+
+// ---------------------------------------------------------------------------
+// Validation
+// ---------------------------------------------------------------------------
+
+#[test]
+fn unknown_parent_rejected() {
+    let groups = r##"
+[[groups]]
+name = "devs"
+inherits = "nope"
+"##;
+    let err = config_with_groups(groups).unwrap_err();
+    assert!(
+        err.to_string().contains("unknown group 'nope'"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn inheritance_cycle_rejected() {
+    let groups = r##"
+[[groups]]
+name = "devs"
+inherits = "ops"
+
+[[groups]]
+name = "ops"
+inherits = "devs"
+"##;
+    let err = config_with_groups(groups).unwrap_err();
+    assert!(err.to_string().contains("cycle"), "unexpected error: {err}");
+}
+
+#[test]
+fn self_inheritance_rejected() {
+    let groups = r##"
+[[groups]]
+name = "devs"
+inherits = "devs"
+"##;
+    let err = config_with_groups(groups).unwrap_err();
+    assert!(
+        err.to_string().contains("devs -> devs"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn overly_deep_chain_rejected() {
+    let mut groups = String::from("[[groups]]\nname = \"devs\"\ninherits = \"g1\"\n");
+    for i in 1..=10 {
+        groups.push_str(&format!("\n[[groups]]\nname = \"g{i}\"\n"));
+        if i < 10 {
+            groups.push_str(&format!("inherits = \"g{}\"\n", i + 1));
+        }
+    }
+    let err = config_with_groups(&groups).unwrap_err();
+    assert!(
+        err.to_string().contains("exceeds"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn duplicate_group_name_rejected() {
+    let groups = r##"
+[[groups]]
+name = "devs"
+
+[[groups]]
+name = "devs"
+"##;
+    let err = config_with_groups(groups).unwrap_err();
+    assert!(
+        err.to_string().contains("duplicate group"),
+        "unexpected error: {err}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Group-level forwarding and ip_guard policies
+// ---------------------------------------------------------------------------
+
+const POLICY_GROUPS: &str = r##"
+[[groups]]
+name = "base"
+allow_forwarding = false
+ip_guard_exemptions = ["10.20.0.0/16"]
+
+[[groups]]
+name = "devs"
+inherits = "base"
+"##;
+
+fn user_with(extra: &str) -> std::sync::Arc<s5::auth::user::User> {
+    let config = parse_app_config(POLICY_GROUPS, &format!("group = \"devs\"\n{extra}")).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    auth.user_store().get("alice").unwrap().clone()
+}
+
+#[test]
+fn user_inherits_group_forwarding_and_exemptions() {
+    let alice = user_with("");
+    assert!(!alice.allow_forwarding);
+    assert_eq!(
+        alice.ip_guard_exemptions,
+        vec!["10.20.0.0/16".parse::<ipnet::IpNet>().unwrap()]
+    );
+}
+
+#[test]
+fn user_overrides_group_forwarding() {
+    let alice = user_with("allow_forwarding = true");
+    assert!(alice.allow_forwarding);
+}
+
+#[test]
+fn user_exemptions_replace_group_list() {
+    let alice = user_with("ip_guard_exemptions = []");
+    assert!(alice.ip_guard_exemptions.is_empty());
+
+    let alice = user_with("ip_guard_exemptions = [\"192.168.5.0/24\"]");
+    assert_eq!(
+        alice.ip_guard_exemptions,
+        vec!["192.168.5.0/24".parse::<ipnet::IpNet>().unwrap()]
+    );
+}
+
+#[test]
+fn forwarding_defaults_to_allowed_without_group() {
+    let config = parse_app_config("", "").unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    assert!(auth.user_store().get("alice").unwrap().allow_forwarding);
+}
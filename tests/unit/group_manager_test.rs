@@ -0,0 +1,93 @@
+use s5::audit::events::AuditEvent;
+use s5::auth::password;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::persist;
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn config(extra: &str) -> anyhow::Result<s5::config::types::AppConfig> {
+    parse_config(&format!(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+         [[groups]]\nname = \"staff\"\nmanagers = [\"lead\"]\n\n\
+         [[groups]]\nname = \"interns\"\ninherits = \"staff\"\n\n\
+         [[users]]\nusername = \"lead\"\npassword_hash = \"{FAKE_HASH}\"\ngroup = \"staff\"\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"{FAKE_HASH}\"\ngroup = \"staff\"\n\n\
+         [[users]]\nusername = \"ivan\"\npassword_hash = \"{FAKE_HASH}\"\ngroup = \"interns\"\n\n\
+         [[users]]\nusername = \"bob\"\npassword_hash = \"{FAKE_HASH}\"\n\n{extra}\n"
+    ))
+}
+
+#[test]
+fn managers_reach_direct_members_only() {
+    let auth = AuthService::new(&config("").unwrap()).unwrap();
+    let groups: Vec<&str> = auth
+        .managed_groups("lead")
+        .iter()
+        .map(|g| g.name.as_str())
+        .collect();
+    assert_eq!(groups, vec!["staff"]);
+    assert!(auth.managed_groups("alice").is_empty());
+
+    assert!(auth.manages("lead", "alice"));
+    // Child groups, users without a group and unknown users are out of reach
+    assert!(!auth.manages("lead", "ivan"));
+    assert!(!auth.manages("lead", "bob"));
+    assert!(!auth.manages("lead", "nobody"));
+    assert!(!auth.manages("alice", "lead"));
+}
+
+#[test]
+fn managers_must_be_configured_users() {
+    let err = config("[[groups]]\nname = \"ops\"\nmanagers = [\"mallory\"]").unwrap_err();
+    assert!(err.to_string().contains("mallory"), "{err}");
+}
+
+#[test]
+fn disabled_user_cannot_log_in() {
+    let hash = password::hash_password("alice-password").unwrap();
+    let token_hash = password::hash_password("alice-token").unwrap();
+    let toml = |disabled: bool| {
+        format!(
+            "[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+             [[users]]\nusername = \"alice\"\npassword_hash = \"{hash}\"\n\
+             api_token_hash = \"{token_hash}\"\ndisabled = {disabled}\n"
+        )
+    };
+
+    let auth = AuthService::new(&parse_config(&toml(false)).unwrap()).unwrap();
+    assert!(auth.auth_password("alice", "alice-password"));
+    assert!(auth.auth_api_token("alice", "alice-token"));
+
+    let auth = AuthService::new(&parse_config(&toml(true)).unwrap()).unwrap();
+    assert!(!auth.user_store().get("alice").unwrap().can_log_in());
+    assert!(!auth.auth_password("alice", "alice-password"));
+    assert!(!auth.auth_api_token("alice", "alice-token"));
+}
+
+#[test]
+fn disabled_flag_round_trips_through_config_file() {
+    let content = "# kept\n[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+                   [[users]]\nusername = \"alice\"\npassword_hash = \"x\"\n\n\
+                   [[users]]\nusername = \"bob\"\npassword_hash = \"x\"\n";
+    let disabled = persist::apply_user_disabled(content, "bob", true).unwrap();
+    assert!(disabled.starts_with("# kept\n"));
+    let cfg = parse_config(&disabled).unwrap();
+    assert!(!cfg.users[0].disabled);
+    assert!(cfg.users[1].disabled);
+
+    // Re-enabling removes the key
+    let enabled = persist::apply_user_disabled(&disabled, "bob", false).unwrap();
+    assert!(!enabled.contains("disabled"));
+    assert!(persist::apply_user_disabled(content, "mallory", true).is_err());
+}
+
+#[test]
+fn disable_is_audited() {
+    let event = AuditEvent::account_disabled("alice", true, "lead");
+    assert_eq!(event.event_type(), "user.disabled");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["disabled"], true);
+    assert_eq!(json["updated_by"], "lead");
+}
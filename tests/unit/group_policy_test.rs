@@ -0,0 +1,150 @@
+use s5::audit::events::AuditEvent;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::persist::{self, GroupPolicy};
+
+const CONFIG: &str = r#"[server]
+ssh_listen = "0.0.0.0:2222"
+
+# Office staff
+[[groups]]
+name = "staff"
+max_bandwidth_kbps = 512 # per connection
+allow_shell = false
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+group = "staff"
+
+[[users]]
+username = "bob"
+password_hash = "argon2id-fakehash-for-testing"
+group = "contractors"
+"#;
+
+fn group<'a>(
+    config: &'a s5::config::types::AppConfig,
+    name: &str,
+) -> &'a s5::config::types::GroupConfig {
+    config.groups.iter().find(|g| g.name == name).unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Config write
+// ---------------------------------------------------------------------------
+
+#[test]
+fn policy_replaces_fields_and_keeps_comments() {
+    let policy = GroupPolicy {
+        max_aggregate_bandwidth_kbps: Some(2048),
+        max_connections_per_user: Some(4),
+        allow_forwarding: Some(true),
+        ..Default::default()
+    };
+    let updated = persist::apply_group_policy(CONFIG, "staff", &policy).unwrap();
+    assert!(updated.contains("# Office staff"));
+    assert!(!updated.contains("max_bandwidth_kbps = 512"));
+    assert!(!updated.contains("allow_shell"));
+
+    let config = parse_config(&updated).unwrap();
+    let staff = group(&config, "staff");
+    assert_eq!(GroupPolicy::of(staff), policy);
+    assert_eq!(config.groups.len(), 1);
+}
+
+#[test]
+fn group_named_only_by_users_gets_an_entry() {
+    let policy = GroupPolicy {
+        allow_shell: Some(false),
+        ..Default::default()
+    };
+    let updated = persist::apply_group_policy(CONFIG, "contractors", &policy).unwrap();
+    let config = parse_config(&updated).unwrap();
+    assert_eq!(config.groups.len(), 2);
+    assert_eq!(GroupPolicy::of(group(&config, "contractors")), policy);
+    // Untouched
+    assert_eq!(group(&config, "staff").max_bandwidth_kbps, Some(512));
+
+    // No [[groups]] at all yet
+    let users_only = "[server]\nssh_listen = \"0.0.0.0:2222\"\n\n\
+                      [[users]]\nusername = \"bob\"\npassword_hash = \"x\"\n\
+                      group = \"contractors\"\n";
+    let updated = persist::apply_group_policy(users_only, "contractors", &policy).unwrap();
+    assert_eq!(parse_config(&updated).unwrap().groups.len(), 1);
+}
+
+#[test]
+fn invalid_policy_refused() {
+    let huge = GroupPolicy {
+        max_bandwidth_kbps: Some(u64::MAX),
+        ..Default::default()
+    };
+    let err = persist::apply_group_policy(CONFIG, "staff", &huge).unwrap_err();
+    assert!(format!("{err:#}").contains("max_bandwidth_kbps"), "{err:#}");
+
+    let body = r#"{"max_bandwidth_kbps": 100, "allow_sftp": true}"#;
+    assert!(serde_json::from_str::<GroupPolicy>(body).is_err());
+    let body = r#"{"max_bandwidth_kbps": 100, "allow_shell": null}"#;
+    assert_eq!(
+        serde_json::from_str::<GroupPolicy>(body).unwrap(),
+        GroupPolicy {
+            max_bandwidth_kbps: Some(100),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn set_group_policy_writes_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("s5.toml");
+    std::fs::write(&path, CONFIG).unwrap();
+    let policy = GroupPolicy {
+        max_connections_per_user: Some(2),
+        ..Default::default()
+    };
+    let written = persist::set_group_policy(&path, "staff", &policy).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+    assert_eq!(
+        group(&parse_config(&written).unwrap(), "staff").max_connections_per_user,
+        Some(2)
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Reload and audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn auth_service_keeps_configured_groups() {
+    let mut auth = AuthService::new(&parse_config(CONFIG).unwrap()).unwrap();
+    assert_eq!(auth.groups().len(), 1);
+    assert_eq!(auth.groups()[0].max_bandwidth_kbps, Some(512));
+
+    let policy = GroupPolicy {
+        max_bandwidth_kbps: Some(128),
+        ..Default::default()
+    };
+    let updated = persist::apply_group_policy(CONFIG, "contractors", &policy).unwrap();
+    auth.reload(&parse_config(&updated).unwrap()).unwrap();
+    assert_eq!(auth.groups().len(), 2);
+    let bob = auth.user_store().get("bob").unwrap();
+    assert_eq!(bob.max_bandwidth_kbps, 128);
+}
+
+#[test]
+fn group_updated_event() {
+    let policy = GroupPolicy {
+        allow_forwarding: Some(false),
+        ..Default::default()
+    };
+    let event = AuditEvent::group_policy_updated("staff", &policy, "ops");
+    assert_eq!(event.event_type(), "config.group_updated");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["group"], "staff");
+    assert_eq!(json["updated_by"], "ops");
+    assert_eq!(json["policy"]["allow_forwarding"], false);
+    assert!(json["policy"]["max_bandwidth_kbps"].is_null());
+}
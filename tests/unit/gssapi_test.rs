@@ -0,0 +1,102 @@
+use russh::server::GssapiStep;
+use s5::auth::gssapi::{Gssapi, PrincipalMap};
+use s5::config::types::{GssapiConfig, GssapiRule};
+
+fn config(principals: &[(&str, &str)], rules: &[(&str, &str)]) -> GssapiConfig {
+    GssapiConfig {
+        keytab: None,
+        principals: principals
+            .iter()
+            .map(|(p, u)| (p.to_string(), u.to_string()))
+            .collect(),
+        rules: rules
+            .iter()
+            .map(|(pattern, user)| GssapiRule {
+                pattern: pattern.to_string(),
+                user: user.to_string(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn exact_principal_maps_to_its_user() {
+    let map = PrincipalMap::new(&config(&[("alice@EXAMPLE.COM", "alice")], &[])).unwrap();
+    assert_eq!(map.user("alice@EXAMPLE.COM").as_deref(), Some("alice"));
+    assert_eq!(map.user("alice@OTHER.COM"), None);
+    assert_eq!(map.user("ALICE@EXAMPLE.COM"), None);
+}
+
+#[test]
+fn rule_expands_groups() {
+    let map = PrincipalMap::new(&config(
+        &[],
+        &[
+            (r"(\w+)/admin@EXAMPLE\.COM", "admin-$1"),
+            (r"(?P<name>[a-z]+)@EXAMPLE\.COM", "$name"),
+        ],
+    ))
+    .unwrap();
+    assert_eq!(map.user("bob@EXAMPLE.COM").as_deref(), Some("bob"));
+    assert_eq!(
+        map.user("carol/admin@EXAMPLE.COM").as_deref(),
+        Some("admin-carol")
+    );
+    assert_eq!(map.user("bob@EXAMPLE.ORG"), None);
+}
+
+#[test]
+fn rule_must_match_the_whole_principal() {
+    let map = PrincipalMap::new(&config(&[], &[(r"[a-z]+", "$0")])).unwrap();
+    assert_eq!(map.user("bob").as_deref(), Some("bob"));
+    assert_eq!(map.user("bob@EXAMPLE.COM"), None);
+}
+
+#[test]
+fn table_wins_over_rules() {
+    let map = PrincipalMap::new(&config(
+        &[("bob@EXAMPLE.COM", "robert")],
+        &[(r"(.+)@EXAMPLE\.COM", "$1")],
+    ))
+    .unwrap();
+    assert_eq!(map.user("bob@EXAMPLE.COM").as_deref(), Some("robert"));
+    assert_eq!(map.user("eve@EXAMPLE.COM").as_deref(), Some("eve"));
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let map = PrincipalMap::new(&config(
+        &[],
+        &[(r"(.+)@EXAMPLE\.COM", "$1"), (r".+", "guest")],
+    ))
+    .unwrap();
+    assert_eq!(map.user("bob@EXAMPLE.COM").as_deref(), Some("bob"));
+    assert_eq!(map.user("bob@OTHER.COM").as_deref(), Some("guest"));
+}
+
+#[test]
+fn rule_expanding_to_nothing_maps_nobody() {
+    let map = PrincipalMap::new(&config(&[], &[(r"(x*)@EXAMPLE\.COM", "$1")])).unwrap();
+    assert_eq!(map.user("@EXAMPLE.COM"), None);
+}
+
+#[test]
+fn invalid_pattern_is_an_error() {
+    let err = PrincipalMap::new(&config(&[], &[("(", "$1")])).unwrap_err();
+    assert!(err.to_string().contains("invalid pattern"), "{err}");
+}
+
+#[test]
+fn garbage_token_is_rejected() {
+    let gssapi = Gssapi::new(&config(&[], &[])).unwrap();
+    // Hosts without libgssapi_krb5 cannot run the exchange at all
+    let Ok(mut acceptor) = gssapi.acceptor() else {
+        return;
+    };
+    assert!(matches!(
+        acceptor.accept(b"not a kerberos token"),
+        GssapiStep::Reject(_)
+    ));
+    assert_eq!(acceptor.principal(), None);
+    assert!(!acceptor.verify_mic(b"data", b"mic"));
+}
@@ -0,0 +1,118 @@
+use s5::config::parse_config;
+use s5::handover::{listen_fds, Listeners};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A listening socket as the parent process would pass it, and its address.
+fn passed() -> (std::net::TcpListener, String) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+// ---------------------------------------------------------------------------
+// Environment
+// ---------------------------------------------------------------------------
+
+#[test]
+fn systemd_variables_parsed() {
+    assert_eq!(
+        listen_fds(Some("42"), Some("2"), Some("ssh:socks5"), 42),
+        vec![("ssh".to_string(), 3), ("socks5".to_string(), 4)]
+    );
+    // Unnamed sockets, and no LISTEN_PID after an upgrade
+    assert_eq!(
+        listen_fds(None, Some("2"), None, 42),
+        vec![("unknown".to_string(), 3), ("unknown".to_string(), 4)]
+    );
+    // Meant for another process
+    assert!(listen_fds(Some("7"), Some("2"), None, 42).is_empty());
+    assert!(listen_fds(None, None, None, 42).is_empty());
+    assert!(listen_fds(None, Some("two"), None, 42).is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Binding
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn passed_socket_claimed_by_name_keeps_its_queue() {
+    let (socket, addr) = passed();
+    // A client that connects before the new process accepts is not refused
+    let mut client = tokio::net::TcpStream::connect(&addr).await.unwrap();
+    let listeners = Listeners::new(vec![("ssh".to_string(), socket)]);
+
+    let listener = listeners.bind("ssh", "127.0.0.1:0").await.unwrap();
+    assert_eq!(listener.local_addr().unwrap().to_string(), addr);
+    assert!(listeners.unclaimed().is_empty());
+
+    let (mut server, _) = listener.accept().await.unwrap();
+    client.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    server.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+#[tokio::test]
+async fn passed_socket_claimed_by_address() {
+    let (socket, addr) = passed();
+    let listeners = Listeners::new(vec![("unknown".to_string(), socket)]);
+
+    // Binding the address again would fail: the passed socket is used
+    let listener = listeners.bind("socks5", &addr).await.unwrap();
+    assert_eq!(listener.local_addr().unwrap().to_string(), addr);
+    assert!(listeners.unclaimed().is_empty());
+}
+
+#[tokio::test]
+async fn unmatched_sockets_left_then_closed_when_ready() {
+    let (socket, addr) = passed();
+    let listeners = Listeners::new(vec![("metrics".to_string(), socket)]);
+
+    let listener = listeners.bind("api", "127.0.0.1:0").await.unwrap();
+    assert_ne!(listener.local_addr().unwrap().to_string(), addr);
+    assert_eq!(listeners.unclaimed(), vec!["metrics"]);
+
+    listeners.notify_ready();
+    assert!(listeners.unclaimed().is_empty());
+    assert!(tokio::net::TcpStream::connect(&addr).await.is_err());
+}
+
+#[tokio::test]
+async fn upgrade_refused_when_disabled() {
+    let listeners = Listeners::new(Vec::new());
+    assert!(!listeners.enabled());
+    let err = listeners.upgrade().await.unwrap_err();
+    assert!(err.to_string().contains("socket_handover"));
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn handover_config_validated() {
+    let parse = |extra: &str| {
+        parse_config(&format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+{extra}
+
+[[users]]
+username = "test"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+        ))
+    };
+    assert!(!parse("").unwrap().server.socket_handover);
+    assert!(
+        parse("socket_handover = true")
+            .unwrap()
+            .server
+            .socket_handover
+    );
+
+    let err =
+        parse("socket_handover = true\n\n[api]\nunix_socket = \"/run/s5/api.sock\"").unwrap_err();
+    assert!(err.to_string().contains("api.unix_socket"));
+}
@@ -0,0 +1,139 @@
+use crate::test_support::default_server_config;
+use s5::config::parse_config;
+use s5::config::types::ServerConfig;
+use s5::ssh::host_keys::{fingerprint, HostKeyRing};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+fn server(dir: &Path, new_key: Option<&str>) -> ServerConfig {
+    let mut server = default_server_config();
+    server.host_key_path = dir.join("host_key");
+    server.host_key_rotation.new_key_path = new_key.map(|name| dir.join(name));
+    server.host_key_rotation.retire_after_days = 30;
+    server
+}
+
+fn fingerprints(keys: &[russh::keys::PrivateKey]) -> Vec<String> {
+    keys.iter().map(fingerprint).collect()
+}
+
+// ---------------------------------------------------------------------------
+// Serving
+// ---------------------------------------------------------------------------
+
+#[test]
+fn single_key_without_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let ring = HostKeyRing::load(&server(dir.path(), None)).unwrap();
+    assert!(dir.path().join("host_key").exists());
+
+    let (_, keys) = ring.served(SystemTime::now());
+    assert_eq!(keys.len(), 1);
+    let status = ring.status(SystemTime::now());
+    assert_eq!(status.keys.len(), 1);
+    assert_eq!(status.keys[0].role, "current");
+    assert!(status.keys[0].served);
+    assert!(status.keys[0].fingerprint.starts_with("SHA256:"));
+    assert!(status.keys[0].public_key.starts_with("ssh-ed25519 "));
+    assert_eq!(status.retire_at, None);
+    assert!(!status.retired);
+}
+
+#[test]
+fn rotation_serves_old_key_first_then_retires_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let ring = HostKeyRing::load(&server(dir.path(), Some("host_key.new"))).unwrap();
+    assert!(dir.path().join("host_key.new").exists());
+
+    let now = SystemTime::now();
+    let (version, keys) = ring.served(now);
+    let status = ring.status(now);
+    assert_eq!(keys.len(), 2);
+    assert_eq!(
+        fingerprints(&keys),
+        vec![
+            status.keys[0].fingerprint.clone(),
+            status.keys[1].fingerprint.clone()
+        ]
+    );
+    assert_eq!(status.keys[1].role, "new");
+    assert!(status.retire_at.is_some());
+
+    // Nothing changes until the retirement date
+    let mut seen = version;
+    assert!(ring.served_if_changed(&mut seen, now + 29 * DAY).is_none());
+
+    let later = now + 31 * DAY;
+    let keys = ring.served_if_changed(&mut seen, later).unwrap();
+    assert_eq!(
+        fingerprints(&keys),
+        vec![status.keys[1].fingerprint.clone()]
+    );
+    assert!(ring.served_if_changed(&mut seen, later).is_none());
+
+    let status = ring.status(later);
+    assert!(status.retired);
+    assert!(!status.keys[0].served);
+    assert!(status.keys[1].served);
+}
+
+#[test]
+fn reload_picks_up_new_key_only_when_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let ring = HostKeyRing::load(&server(dir.path(), None)).unwrap();
+    let (mut seen, _) = ring.served(SystemTime::now());
+
+    ring.reload(&server(dir.path(), None)).unwrap();
+    assert!(ring
+        .served_if_changed(&mut seen, SystemTime::now())
+        .is_none());
+
+    ring.reload(&server(dir.path(), Some("host_key.new")))
+        .unwrap();
+    let keys = ring
+        .served_if_changed(&mut seen, SystemTime::now())
+        .unwrap();
+    assert_eq!(keys.len(), 2);
+
+    // A key that fails to load keeps the current set
+    std::fs::write(dir.path().join("broken"), "not a key").unwrap();
+    assert!(ring.reload(&server(dir.path(), Some("broken"))).is_err());
+    assert_eq!(ring.served(SystemTime::now()).1.len(), 2);
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn rotation_config_validated() {
+    let parse = |rotation: &str| {
+        parse_config(&format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+host_key_path = "host_key"
+
+[server.host_key_rotation]
+{rotation}
+
+[[users]]
+username = "test"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+        ))
+    };
+    let config = parse("new_key_path = \"host_key.new\"").unwrap();
+    assert_eq!(config.server.host_key_rotation.retire_after_days, 30);
+    assert!(parse("")
+        .unwrap()
+        .server
+        .host_key_rotation
+        .new_key_path
+        .is_none());
+
+    assert!(parse("new_key_path = \"host_key\"").is_err());
+    assert!(parse("new_key_path = \"host_key.new\"\nretire_after_days = 0").is_err());
+}
@@ -0,0 +1,193 @@
+use clap::Parser;
+use s5::cli::{Cli, Command};
+use s5::config::import::{self, Import, ImportedUser};
+use s5::config::{parse_config, persist};
+
+const ARGON2_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA";
+
+fn key_line(comment: &str) -> String {
+    let private =
+        russh::keys::PrivateKey::random(&mut rand::rngs::OsRng, russh::keys::Algorithm::Ed25519)
+            .unwrap();
+    let key = russh::keys::PublicKey::from(&private);
+    format!("{} {}", key.to_openssh().unwrap(), comment)
+}
+
+// ---------------------------------------------------------------------------
+// Parsing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn htpasswd_keeps_verifiable_hashes() {
+    let content = format!(
+        "# managed by ansible\n\nalice:{ARGON2_HASH}\nbob:$2y$05$abcdefghijklmnopqrstuv\n\
+         carol:$apr1$salt$hash\nbroken line\n:nohash\n"
+    );
+    let mut warnings = Vec::new();
+    let users = import::parse_htpasswd(&content, &mut warnings);
+    assert_eq!(
+        users,
+        vec![
+            ("alice".to_string(), Some(ARGON2_HASH.to_string())),
+            (
+                "bob".to_string(),
+                Some("$2y$05$abcdefghijklmnopqrstuv".to_string())
+            ),
+            ("carol".to_string(), None),
+        ]
+    );
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].contains("MD5"));
+}
+
+#[test]
+fn authorized_keys_options_dropped_and_invalid_lines_skipped() {
+    let plain = key_line("alice@laptop");
+    let restricted = key_line("alice@ci");
+    let content = format!(
+        "# keys\n{plain}\nno-pty,from=\"10.0.0.0/8\" {restricted}\n\
+         ssh-ed25519 not-base64 bad\ngarbage\n{plain}\n"
+    );
+    let mut warnings = Vec::new();
+    let keys = import::parse_authorized_keys(&content, "alice", &mut warnings);
+    assert_eq!(keys, vec![plain, restricted]);
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings.iter().any(|w| w.contains("key options dropped")));
+    assert!(warnings.iter().all(|w| w.starts_with("alice: ")));
+}
+
+#[test]
+fn authorized_keys_dir_flat_and_home_layouts() {
+    let dir = tempfile::tempdir().unwrap();
+    let alice = key_line("alice");
+    let bob = key_line("bob");
+    std::fs::write(dir.path().join("alice.pub"), &alice).unwrap();
+    std::fs::create_dir_all(dir.path().join("bob/.ssh")).unwrap();
+    std::fs::write(dir.path().join("bob/.ssh/authorized_keys"), &bob).unwrap();
+    std::fs::create_dir(dir.path().join("nokeys")).unwrap();
+    std::fs::write(dir.path().join("empty"), "# nothing\n").unwrap();
+    std::fs::write(dir.path().join(".hidden"), &alice).unwrap();
+
+    let mut warnings = Vec::new();
+    let users = import::read_authorized_keys_dir(dir.path(), &mut warnings).unwrap();
+    assert_eq!(users.keys().collect::<Vec<_>>(), vec!["alice", "bob"]);
+    assert_eq!(users["alice"], vec![alice]);
+    assert_eq!(users["bob"], vec![bob]);
+}
+
+#[test]
+fn passwd_and_keys_merged_per_user() {
+    let dir = tempfile::tempdir().unwrap();
+    let passwd = dir.path().join("htpasswd");
+    std::fs::write(
+        &passwd,
+        format!("alice:{ARGON2_HASH}\nbob:$2y$05$x\ncarol:$6$x\n"),
+    )
+    .unwrap();
+    let keys_dir = dir.path().join("keys");
+    std::fs::create_dir(&keys_dir).unwrap();
+    let alice = key_line("alice");
+    let bob = key_line("bob");
+    std::fs::write(keys_dir.join("alice"), &alice).unwrap();
+    std::fs::write(keys_dir.join("bob"), &bob).unwrap();
+
+    let import = Import::read(Some(passwd.as_path()), Some(keys_dir.as_path())).unwrap();
+    assert_eq!(
+        import.users,
+        vec![
+            ImportedUser {
+                username: "alice".into(),
+                password_hash: Some(ARGON2_HASH.into()),
+                authorized_keys: vec![alice],
+            },
+            ImportedUser {
+                username: "bob".into(),
+                password_hash: Some("$2y$05$x".into()),
+                authorized_keys: vec![bob],
+            },
+        ]
+    );
+    // carol has neither a usable hash nor a key
+    assert!(import
+        .warnings
+        .iter()
+        .any(|w| w.starts_with("carol: no usable")));
+}
+
+// ---------------------------------------------------------------------------
+// Output
+// ---------------------------------------------------------------------------
+
+fn imported(username: &str) -> ImportedUser {
+    ImportedUser {
+        username: username.into(),
+        password_hash: Some(ARGON2_HASH.into()),
+        authorized_keys: vec![key_line(username)],
+    }
+}
+
+#[test]
+fn printed_entries_parse_as_config() {
+    let users = vec![imported("alice"), imported("bob")];
+    let toml = format!(
+        "[server]\nssh_listen = \"0.0.0.0:2222\"\n\n{}",
+        import::to_toml(&users)
+    );
+    let config = parse_config(&toml).unwrap();
+    assert_eq!(config.users.len(), 2);
+    assert_eq!(config.users[1].username, "bob");
+    assert_eq!(config.users[1].password_hash.as_deref(), Some(ARGON2_HASH));
+    assert_eq!(config.users[1].authorized_keys, users[1].authorized_keys);
+}
+
+#[test]
+fn write_skips_existing_users_and_keeps_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("s5.toml");
+    let original = format!(
+        "# production\n[server]\nssh_listen = \"0.0.0.0:2222\"\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"{ARGON2_HASH}\"\nrole = \"admin\"\n"
+    );
+    std::fs::write(&path, &original).unwrap();
+
+    let added = persist::add_users(&path, &[imported("alice"), imported("bob")]).unwrap();
+    assert_eq!(added, vec!["bob"]);
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("# production\n"));
+    let config = parse_config(&written).unwrap();
+    assert_eq!(config.users.len(), 2);
+    assert!(config.users[0].authorized_keys.is_empty());
+
+    // Nothing new: file left as is
+    assert!(persist::add_users(&path, &[imported("bob")])
+        .unwrap()
+        .is_empty());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+}
+
+#[test]
+fn import_cli_args() {
+    let cli = Cli::try_parse_from([
+        "s5",
+        "import",
+        "--passwd-file",
+        "/etc/htpasswd",
+        "--authorized-keys-dir",
+        "/home",
+        "--write",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Import {
+            passwd_file,
+            authorized_keys_dir,
+            write,
+        }) => {
+            assert_eq!(passwd_file.unwrap().to_str(), Some("/etc/htpasswd"));
+            assert_eq!(authorized_keys_dir.unwrap().to_str(), Some("/home"));
+            assert!(write);
+        }
+        other => panic!("expected Import, got {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["s5", "import"]).is_err());
+}
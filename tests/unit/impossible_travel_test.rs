@@ -0,0 +1,226 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::auth::AuthService;
+use s5::config::types::{AppConfig, ImpossibleTravelAction};
+use s5::security::impossible_travel::{distance_km, ImpossibleTravelDetector};
+use s5::security::SecurityManager;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const PARIS: (f64, f64) = (48.8566, 2.3522);
+const LYON: (f64, f64) = (45.764, 4.8357);
+const NEW_YORK: (f64, f64) = (40.7128, -74.006);
+
+/// `[impossible_travel]` settings on top of a (missing) City database.
+fn config(travel: &str, alice: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!(
+            "[geoip]\nenabled = true\ndatabase_path = \"/nonexistent/GeoLite2-City.mmdb\"\n\n\
+             [impossible_travel]\nenabled = true\n{travel}"
+        ),
+        alice,
+    )
+}
+
+fn detector(travel: &str) -> ImpossibleTravelDetector {
+    ImpossibleTravelDetector::new(&config(travel, "").unwrap())
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn fr() -> Option<String> {
+    Some("FR".to_string())
+}
+
+fn us() -> Option<String> {
+    Some("US".to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Detection
+// ---------------------------------------------------------------------------
+
+#[test]
+fn distance_between_cities() {
+    let km = distance_km(PARIS, NEW_YORK);
+    assert!((5800.0..5880.0).contains(&km), "{km}");
+    assert!((390.0..400.0).contains(&distance_km(PARIS, LYON)));
+    assert_eq!(distance_km(PARIS, PARIS), 0.0);
+}
+
+#[test]
+fn transatlantic_hop_within_the_hour_is_flagged() {
+    let detector = detector("");
+    let start = Instant::now();
+    let paris = ip("198.51.100.1");
+    assert!(detector
+        .observe("alice", &paris, PARIS, fr(), start, false)
+        .is_none());
+    let travel = detector
+        .observe(
+            "alice",
+            &ip("203.0.113.9"),
+            NEW_YORK,
+            us(),
+            start + Duration::from_secs(1800),
+            false,
+        )
+        .unwrap();
+    assert_eq!(travel.previous_ip, paris);
+    assert_eq!(travel.previous_country, fr());
+    assert_eq!(travel.country, us());
+    assert_eq!(travel.elapsed_secs, 1800);
+    assert!((5800..5880).contains(&travel.distance_km));
+    assert!(travel.speed_kmh > 11_000);
+}
+
+#[test]
+fn plausible_travel_is_not_flagged() {
+    let detector = detector("window_secs = 86400");
+    let start = Instant::now();
+    assert!(detector
+        .observe("alice", &ip("198.51.100.1"), PARIS, fr(), start, false)
+        .is_none());
+    // Eight hours is enough to cross the Atlantic
+    assert!(detector
+        .observe(
+            "alice",
+            &ip("203.0.113.9"),
+            NEW_YORK,
+            us(),
+            start + Duration::from_secs(8 * 3600),
+            false,
+        )
+        .is_none());
+}
+
+#[test]
+fn short_distances_and_same_ip_ignored() {
+    let detector = detector("");
+    let start = Instant::now();
+    let paris = ip("198.51.100.1");
+    assert!(detector
+        .observe("alice", &paris, PARIS, fr(), start, false)
+        .is_none());
+    // Below min_distance_km, however fast
+    assert!(detector
+        .observe("alice", &ip("198.51.100.2"), LYON, fr(), start, false)
+        .is_none());
+    // The database moved the same IP: not a second location
+    assert!(detector
+        .observe("alice", &paris, NEW_YORK, us(), start, false)
+        .is_none());
+}
+
+#[test]
+fn users_tracked_separately() {
+    let detector = detector("");
+    let start = Instant::now();
+    assert!(detector
+        .observe("alice", &ip("198.51.100.1"), PARIS, fr(), start, false)
+        .is_none());
+    assert!(detector
+        .observe("bob", &ip("203.0.113.9"), NEW_YORK, us(), start, false)
+        .is_none());
+    assert_eq!(detector.len(), 2);
+}
+
+#[test]
+fn logins_expire_after_window() {
+    let detector = detector("window_secs = 600");
+    let start = Instant::now();
+    assert!(detector
+        .observe("alice", &ip("198.51.100.1"), PARIS, fr(), start, false)
+        .is_none());
+    assert!(detector
+        .observe(
+            "alice",
+            &ip("203.0.113.9"),
+            NEW_YORK,
+            us(),
+            start + Duration::from_secs(601),
+            false,
+        )
+        .is_none());
+}
+
+#[test]
+fn blocked_login_not_recorded() {
+    let detector = detector("");
+    let start = Instant::now();
+    assert!(detector
+        .observe("alice", &ip("198.51.100.1"), PARIS, fr(), start, true)
+        .is_none());
+    let stolen = ip("203.0.113.9");
+    let later = start + Duration::from_secs(60);
+    assert!(detector
+        .observe("alice", &stolen, NEW_YORK, us(), later, true)
+        .is_some());
+    // The owner logging in again from Paris is not held against them
+    assert!(detector
+        .observe("alice", &ip("198.51.100.2"), PARIS, fr(), later, true)
+        .is_none());
+
+    // A flagged login is recorded
+    assert!(detector
+        .observe("alice", &stolen, NEW_YORK, us(), later, false)
+        .is_some());
+    assert!(detector
+        .observe("alice", &ip("198.51.100.3"), PARIS, fr(), later, false)
+        .is_some());
+}
+
+// ---------------------------------------------------------------------------
+// Overrides, reload and audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn user_override_resolved() {
+    let block = config("", "impossible_travel = \"block\"").unwrap();
+    let auth = AuthService::new(&block).unwrap();
+    let alice = auth.user_store().get("alice").unwrap();
+    assert_eq!(alice.impossible_travel, Some(ImpossibleTravelAction::Block));
+
+    let auth = AuthService::new(&config("", "").unwrap()).unwrap();
+    assert_eq!(
+        auth.user_store().get("alice").unwrap().impossible_travel,
+        None
+    );
+}
+
+#[test]
+fn security_manager_follows_reload() {
+    let mut security = SecurityManager::new(&config("", "").unwrap());
+    assert!(security.impossible_travel().is_some());
+
+    security.reload(&config("action = \"off\"", "").unwrap());
+    let detector = security.impossible_travel().unwrap();
+    assert_eq!(detector.action(), ImpossibleTravelAction::Off);
+
+    security.reload(&parse_app_config("", "").unwrap());
+    assert!(security.impossible_travel().is_none());
+}
+
+#[test]
+fn audit_event_is_critical() {
+    let detector = detector("");
+    let start = Instant::now();
+    detector.observe("alice", &ip("198.51.100.1"), PARIS, fr(), start, false);
+    let travel = detector
+        .observe("alice", &ip("203.0.113.9"), NEW_YORK, us(), start, true)
+        .unwrap();
+    assert_eq!(travel.elapsed_secs, 0);
+
+    let source = "203.0.113.9:40000".parse().unwrap();
+    let event =
+        AuditEvent::impossible_travel_with_cid("alice", &source, "ssh", "conn-1", &travel, true);
+    assert_eq!(event.event_type(), "auth.impossible_travel");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["previous_ip"], "198.51.100.1");
+    assert_eq!(json["previous_country"], "FR");
+    assert_eq!(json["country"], "US");
+    assert_eq!(json["blocked"], true);
+}
@@ -0,0 +1,137 @@
+use crate::test_support::parse_app_config;
+use chrono::Duration;
+use s5::audit::events::AuditEvent;
+use s5::auth::invitation::{valid_username, InvitationError, InvitationStore};
+use s5::config::types::AppConfig;
+
+fn config(invitations: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[invitations]\n{invitations}"), "")
+}
+
+#[test]
+fn link_is_single_use() {
+    let store = InvitationStore::default();
+    let (invitation, token) = store
+        .create(
+            "carol",
+            Some("staff".into()),
+            "admin",
+            Duration::hours(1),
+            10,
+        )
+        .unwrap();
+    assert_eq!(token.len(), 64);
+    assert_eq!(invitation.group.as_deref(), Some("staff"));
+    assert_eq!(invitation.created_by, "admin");
+
+    assert_eq!(store.find(&token).unwrap().id, invitation.id);
+    assert!(store.find("not-the-token").is_none());
+    assert_eq!(store.redeem(&token).unwrap().username, "carol");
+    assert!(store.find(&token).is_none());
+    assert!(store.redeem(&token).is_none());
+    assert!(store.list().is_empty());
+}
+
+#[test]
+fn expired_and_revoked_links_are_dropped() {
+    let store = InvitationStore::default();
+    let (_, expired) = store
+        .create("carol", None, "admin", Duration::seconds(-1), 10)
+        .unwrap();
+    assert!(store.find(&expired).is_none());
+    assert!(store.list().is_empty());
+
+    let (invitation, token) = store
+        .create("dave", None, "admin", Duration::hours(1), 10)
+        .unwrap();
+    assert_eq!(store.revoke(&invitation.id).unwrap().username, "dave");
+    assert!(store.revoke(&invitation.id).is_none());
+    assert!(store.find(&token).is_none());
+}
+
+#[test]
+fn one_pending_link_per_username_within_limit() {
+    let store = InvitationStore::default();
+    let ttl = Duration::hours(1);
+    store.create("carol", None, "admin", ttl, 2).unwrap();
+    assert_eq!(
+        store.create("carol", None, "admin", ttl, 2).unwrap_err(),
+        InvitationError::AlreadyInvited("carol".into())
+    );
+    store.create("dave", None, "admin", ttl, 2).unwrap();
+    assert_eq!(
+        store.create("erin", None, "admin", ttl, 2).unwrap_err(),
+        InvitationError::TooMany(2)
+    );
+    assert_eq!(store.list().len(), 2);
+}
+
+#[test]
+fn username_rules() {
+    for name in ["carol", "c.smith", "c_smith-2", "carol@acme"] {
+        assert!(valid_username(name), "{name}");
+    }
+    for name in [
+        "",
+        "-carol",
+        ".carol",
+        "@acme",
+        "carol smith",
+        "carol/..",
+        "é",
+    ] {
+        assert!(!valid_username(name), "{name}");
+    }
+    assert!(!valid_username(&"a".repeat(65)));
+    let store = InvitationStore::default();
+    assert_eq!(
+        store
+            .create("bad name", None, "admin", Duration::hours(1), 10)
+            .unwrap_err(),
+        InvitationError::InvalidUsername
+    );
+}
+
+#[test]
+fn config_validation() {
+    let cfg = config("enabled = true").unwrap();
+    assert_eq!(cfg.invitations.ttl_hours, 72);
+    assert_eq!(cfg.invitations.max_pending, 100);
+    assert!(config("enabled = true\npublic_url = \"https://proxy.example.com\"").is_ok());
+
+    assert!(config("enabled = true\nttl_hours = 0").is_err());
+    assert!(config("enabled = true\nttl_hours = 9000").is_err());
+    assert!(config("enabled = true\nmax_pending = 0").is_err());
+    assert!(config("enabled = true\npublic_url = \"proxy.example.com\"").is_err());
+    // Settings are not checked while invitations are off
+    assert!(config("ttl_hours = 0").is_ok());
+}
+
+#[test]
+fn invitations_are_audited() {
+    let store = InvitationStore::default();
+    let (invitation, _) = store
+        .create(
+            "carol",
+            Some("staff".into()),
+            "admin",
+            Duration::hours(1),
+            10,
+        )
+        .unwrap();
+
+    let event = AuditEvent::user_invited(&invitation);
+    assert_eq!(event.event_type(), "user.invited");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["username"], "carol");
+    assert_eq!(json["group"], "staff");
+    assert_eq!(json["created_by"], "admin");
+
+    let event = AuditEvent::invitation_accepted(&invitation, "203.0.113.7", true);
+    assert_eq!(event.event_type(), "user.invitation_accepted");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["invitation_id"], invitation.id.as_str());
+    assert_eq!(json["public_key"], true);
+}
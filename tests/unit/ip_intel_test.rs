@@ -0,0 +1,195 @@
+use crate::test_support::parse_app_config;
+use s5::config::types::{AppConfig, IpIntelConfig, SourceCondition};
+use s5::security::ip_intel::{Dataset, DatasetSource, IpIntel, EMBEDDED};
+use s5::security::SecurityManager;
+use std::net::IpAddr;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn config(extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(extra, "")
+}
+
+// ---------------------------------------------------------------------------
+// Dataset
+// ---------------------------------------------------------------------------
+
+#[test]
+fn dataset_matches_ranges_and_single_addresses() {
+    let (dataset, invalid) = Dataset::parse(
+        "# comment\n\
+         tor 198.51.100.7\n\
+         tor 2001:db8::7 # exit\n\
+         datacenter 203.0.113.0/24\n\
+         datacenter 203.0.113.128/25\n\
+         datacenter 2001:db8:100::/48\n\
+         residential 192.0.2.0/24\n\
+         datacenter not-an-ip\n",
+    );
+    assert_eq!(invalid, 2);
+    assert_eq!(dataset.tor_len(), 2);
+    assert_eq!(dataset.datacenter_len(), 3);
+
+    assert!(dataset.is_tor(&ip("198.51.100.7")));
+    assert!(!dataset.is_tor(&ip("198.51.100.8")));
+    assert!(dataset.is_tor(&ip("2001:db8::7")));
+    assert!(dataset.is_datacenter(&ip("203.0.113.0")));
+    assert!(dataset.is_datacenter(&ip("203.0.113.255")));
+    assert!(!dataset.is_datacenter(&ip("203.0.114.0")));
+    assert!(dataset.is_datacenter(&ip("2001:db8:100:ffff::1")));
+    assert!(!dataset.is_datacenter(&ip("192.0.2.1")));
+    assert!(dataset.matches(&ip("198.51.100.7"), SourceCondition::SourceIsTor));
+    assert!(!dataset.matches(&ip("198.51.100.7"), SourceCondition::SourceIsDatacenter));
+}
+
+#[test]
+fn rendered_dataset_parses_back() {
+    let tor = vec!["198.51.100.7/32".parse().unwrap()];
+    let datacenter = vec!["203.0.113.9/24".parse().unwrap()];
+    let text = Dataset::render(&tor, &datacenter);
+    assert!(text.contains("datacenter 203.0.113.0/24\n"));
+    let (dataset, invalid) = Dataset::parse(&text);
+    assert_eq!(invalid, 0);
+    assert!(dataset.is_tor(&ip("198.51.100.7")));
+    assert!(dataset.is_datacenter(&ip("203.0.113.200")));
+}
+
+#[test]
+fn embedded_dataset_is_valid() {
+    let (dataset, invalid) = Dataset::parse(EMBEDDED);
+    assert_eq!(invalid, 0);
+    assert!(dataset.datacenter_len() > 0);
+    // AWS
+    assert!(dataset.is_datacenter(&ip("3.80.0.1")));
+    assert!(!dataset.is_datacenter(&ip("192.168.1.1")));
+}
+
+// ---------------------------------------------------------------------------
+// Loading
+// ---------------------------------------------------------------------------
+
+#[test]
+fn dataset_file_replaces_embedded_ranges() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ip-intel.txt");
+    let cfg = IpIntelConfig {
+        path: Some(path.clone()),
+        ..IpIntelConfig::default()
+    };
+
+    // Not written yet
+    let intel = IpIntel::new(&cfg);
+    assert_eq!(intel.status().source, DatasetSource::Embedded);
+    assert!(intel.dataset().is_datacenter(&ip("3.80.0.1")));
+
+    // Picked up on reload
+    std::fs::write(&path, "tor 198.51.100.7\n").unwrap();
+    intel.configure(&cfg);
+    let status = intel.status();
+    assert_eq!(status.source, DatasetSource::File);
+    assert_eq!((status.tor_entries, status.datacenter_entries), (1, 0));
+    assert!(intel.dataset().is_tor(&ip("198.51.100.7")));
+    assert!(!intel.dataset().is_datacenter(&ip("3.80.0.1")));
+}
+
+#[test]
+fn denied_follows_configured_conditions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ip-intel.txt");
+    std::fs::write(&path, "tor 198.51.100.7\ndatacenter 203.0.113.0/24\n").unwrap();
+    let mut cfg = IpIntelConfig {
+        path: Some(path),
+        ..IpIntelConfig::default()
+    };
+    let intel = IpIntel::new(&cfg);
+    assert_eq!(intel.denied(&ip("198.51.100.7")), None);
+
+    cfg.deny = vec![SourceCondition::SourceIsTor];
+    intel.configure(&cfg);
+    assert_eq!(
+        intel.denied(&ip("198.51.100.7")),
+        Some(SourceCondition::SourceIsTor)
+    );
+    assert_eq!(intel.denied(&ip("203.0.113.9")), None);
+}
+
+// ---------------------------------------------------------------------------
+// Configuration and pre-auth check
+// ---------------------------------------------------------------------------
+
+#[test]
+fn deny_conditions_parse_and_urls_are_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ip-intel.txt");
+    std::fs::write(&path, "tor 198.51.100.7\ndatacenter 203.0.113.0/24\n").unwrap();
+    let cfg = config(&format!(
+        "[ip_intel]\ndeny = [\"source_is_tor\", \"source_is_datacenter\"]\npath = {:?}",
+        path.display().to_string()
+    ))
+    .unwrap();
+    assert_eq!(
+        cfg.ip_intel.deny,
+        vec![
+            SourceCondition::SourceIsTor,
+            SourceCondition::SourceIsDatacenter
+        ]
+    );
+    assert_eq!(cfg.ip_intel.tor_urls.len(), 1);
+    assert!(config("[ip_intel]\ndeny = [\"source_is_vpn\"]").is_err());
+    assert!(config("[ip_intel]\ntor_urls = [\"ftp://example.com/tor\"]").is_err());
+}
+
+#[test]
+fn deny_conditions_without_ranges_are_refused() {
+    // The embedded dataset has datacenter ranges but no Tor exits
+    assert!(config("[ip_intel]\ndeny = [\"source_is_datacenter\"]").is_ok());
+    let err = config("[ip_intel]\ndeny = [\"source_is_tor\"]").unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("source_is_tor"), "{err}");
+    assert!(err.contains("embedded ranges"), "{err}");
+    assert!(err.contains("s5 update-intel"), "{err}");
+
+    // A missing dataset file leaves the embedded ranges
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ip-intel.txt");
+    let with_path = |deny: &str| {
+        config(&format!(
+            "[ip_intel]\ndeny = [\"{deny}\"]\npath = {:?}",
+            path.display().to_string()
+        ))
+    };
+    assert!(with_path("source_is_tor").is_err());
+    std::fs::write(&path, "tor 198.51.100.7\n").unwrap();
+    assert!(with_path("source_is_tor").is_ok());
+    let err = format!("{:#}", with_path("source_is_datacenter").unwrap_err());
+    assert!(err.contains("ranges in ip_intel.path"), "{err}");
+}
+
+#[test]
+fn pre_auth_check_refuses_denied_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ip-intel.txt");
+    std::fs::write(&path, "tor 198.51.100.7\ndatacenter 203.0.113.0/24\n").unwrap();
+    let cfg = config(&format!(
+        "[security]\nban_whitelist = [\"203.0.113.50\"]\n\n\
+         [ip_intel]\ndeny = [\"source_is_tor\", \"source_is_datacenter\"]\npath = {:?}",
+        path.display().to_string()
+    ))
+    .unwrap();
+    let security = SecurityManager::new(&cfg);
+
+    assert_eq!(
+        security.pre_auth_check(&ip("198.51.100.7")),
+        Err("Tor exit source")
+    );
+    // IPv4-mapped addresses are normalized first
+    assert_eq!(
+        security.pre_auth_check(&ip("::ffff:203.0.113.9")),
+        Err("datacenter source")
+    );
+    // Whitelisted sources are exempt
+    assert_eq!(security.pre_auth_check(&ip("203.0.113.50")), Ok(()));
+    assert_eq!(security.pre_auth_check(&ip("192.0.2.1")), Ok(()));
+}
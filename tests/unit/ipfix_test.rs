@@ -0,0 +1,204 @@
+use s5::config::types::IpfixConfig;
+use s5::flows::ipfix::{
+    template_id, IpfixEncoder, IpfixExporter, END_OF_FLOW_DETECTED, FIELD_USERNAME, FORCED_END,
+    IPFIX_VERSION, REVERSE_PEN, TEMPLATE_ID_BASE, TEMPLATE_SET_ID,
+};
+use s5::flows::FlowRecord;
+use std::time::Duration;
+
+const PEN: u32 = 32473;
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes(b[i..i + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], i: usize) -> u64 {
+    u64::from_be_bytes(b[i..i + 8].try_into().unwrap())
+}
+
+fn flow(source_ip: &str, dest_ip: Option<&str>) -> FlowRecord {
+    FlowRecord {
+        dest_ip: dest_ip.map(str::to_string),
+        bytes_up: 1200,
+        bytes_down: 64_000,
+        ..FlowRecord::closing(
+            "socks5",
+            "alice",
+            source_ip,
+            "example.com",
+            443,
+            Duration::from_millis(2500),
+        )
+    }
+}
+
+/// Split a message into (set ID, set body) pairs after checking the header.
+fn sets(msg: &[u8]) -> Vec<(u16, &[u8])> {
+    assert_eq!(u16_at(msg, 0), IPFIX_VERSION);
+    assert_eq!(u16_at(msg, 2) as usize, msg.len());
+    let mut out = Vec::new();
+    let mut i = 16;
+    while i < msg.len() {
+        let id = u16_at(msg, i);
+        let len = u16_at(msg, i + 2) as usize;
+        out.push((id, &msg[i + 4..i + len]));
+        i += len;
+    }
+    assert_eq!(i, msg.len());
+    out
+}
+
+#[test]
+fn template_ids_cover_address_families() {
+    assert_eq!(template_id(false, false), TEMPLATE_ID_BASE);
+    assert_eq!(template_id(false, true), TEMPLATE_ID_BASE + 1);
+    assert_eq!(template_id(true, false), TEMPLATE_ID_BASE + 2);
+    assert_eq!(template_id(true, true), TEMPLATE_ID_BASE + 3);
+}
+
+#[test]
+fn template_message_declares_enterprise_username() {
+    let encoder = IpfixEncoder::new(7, PEN);
+    let msg = encoder.template_message(1_700_000_000);
+    assert_eq!(u32_at(&msg, 4), 1_700_000_000);
+    assert_eq!(u32_at(&msg, 8), 0);
+    assert_eq!(u32_at(&msg, 12), 7);
+
+    let sets = sets(&msg);
+    assert_eq!(sets.len(), 1);
+    let (set_id, body) = sets[0];
+    assert_eq!(set_id, TEMPLATE_SET_ID);
+
+    // First template: IPv4/IPv4
+    assert_eq!(u16_at(body, 0), TEMPLATE_ID_BASE);
+    let field_count = u16_at(body, 2) as usize;
+    let mut i = 4;
+    let mut fields = Vec::new();
+    for _ in 0..field_count {
+        let id = u16_at(body, i);
+        let len = u16_at(body, i + 2);
+        i += 4;
+        let pen = if id & 0x8000 != 0 {
+            i += 4;
+            Some(u32_at(body, i - 4))
+        } else {
+            None
+        };
+        fields.push((id & 0x7fff, len, pen));
+    }
+    assert_eq!(fields[0], (8, 4, None)); // sourceIPv4Address
+    assert_eq!(fields[1], (12, 4, None)); // destinationIPv4Address
+    assert!(fields.contains(&(1, 8, Some(REVERSE_PEN))));
+    assert!(fields.contains(&(FIELD_USERNAME, 65535, Some(PEN))));
+    // Next template follows immediately
+    assert_eq!(u16_at(body, i), TEMPLATE_ID_BASE + 1);
+}
+
+#[test]
+fn data_record_encodes_flow_fields() {
+    let mut encoder = IpfixEncoder::new(1, PEN);
+    let record = flow("198.51.100.1", Some("203.0.113.7"));
+    let msgs = encoder.data_messages(std::slice::from_ref(&record), 1_700_000_000);
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(encoder.sequence(), 1);
+
+    let sets = sets(&msgs[0]);
+    assert_eq!(sets.len(), 1);
+    let (set_id, body) = sets[0];
+    assert_eq!(set_id, TEMPLATE_ID_BASE);
+    assert_eq!(&body[0..4], &[198, 51, 100, 1]);
+    assert_eq!(&body[4..8], &[203, 0, 113, 7]);
+    assert_eq!(u16_at(body, 8), 443);
+    assert_eq!(body[10], 6);
+    let start = u64_at(body, 11);
+    assert_eq!(start, record.started_at.timestamp_millis() as u64);
+    assert_eq!(u64_at(body, 19), start + 2500);
+    assert_eq!(u64_at(body, 27), 1200);
+    assert_eq!(u64_at(body, 35), 64_000);
+    assert_eq!(body[43], END_OF_FLOW_DETECTED);
+    // Variable-length strings: username, dest host, protocol, close reason
+    let mut i = 44;
+    let mut strings = Vec::new();
+    while i < body.len() {
+        let len = body[i] as usize;
+        strings.push(std::str::from_utf8(&body[i + 1..i + 1 + len]).unwrap());
+        i += 1 + len;
+    }
+    assert_eq!(strings, ["alice", "example.com", "socks5", "closed"]);
+}
+
+#[test]
+fn failed_and_ipv6_flows_pick_matching_templates() {
+    let mut encoder = IpfixEncoder::new(1, PEN);
+    let mut failed = flow("2001:db8::1", None);
+    failed.close_reason = "acl_denied".to_string();
+    let msgs = encoder.data_messages(
+        &[failed, flow("192.0.2.10", Some("2001:db8::80"))],
+        1_700_000_000,
+    );
+    let sets = sets(&msgs[0]);
+    assert_eq!(sets.len(), 2);
+    assert_eq!(sets[0].0, template_id(true, true));
+    // Unspecified destination address for flows that never connected
+    assert_eq!(&sets[0].1[16..32], &[0u8; 16]);
+    assert_eq!(sets[0].1[67], FORCED_END);
+    assert_eq!(sets[1].0, template_id(false, true));
+}
+
+#[test]
+fn large_batches_split_below_mtu_with_running_sequence() {
+    let sample = flow("198.51.100.1", Some("203.0.113.7"));
+    let one = IpfixEncoder::new(1, PEN).data_messages(std::slice::from_ref(&sample), 0);
+    let record_len = sets(&one[0])[0].1.len();
+
+    let mut encoder = IpfixEncoder::new(1, PEN);
+    let records = vec![sample; 100];
+    let msgs = encoder.data_messages(&records, 1_700_000_000);
+    assert!(msgs.len() > 1);
+    assert_eq!(encoder.sequence(), 100);
+
+    // Each header carries the number of records exported before it
+    let mut exported = 0u32;
+    for msg in &msgs {
+        assert!(msg.len() <= 1400);
+        assert_eq!(u32_at(msg, 8), exported);
+        let body_len: usize = sets(msg).iter().map(|(_, b)| b.len()).sum();
+        assert_eq!(body_len % record_len, 0);
+        exported += (body_len / record_len) as u32;
+    }
+    assert_eq!(exported, 100);
+}
+
+async fn recv_message(socket: &tokio::net::UdpSocket) -> Vec<u8> {
+    let mut buf = [0u8; 2048];
+    let n = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+        .await
+        .expect("IPFIX message received")
+        .unwrap();
+    buf[..n].to_vec()
+}
+
+#[tokio::test]
+async fn exporter_sends_templates_then_data_over_udp() {
+    let collector = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = IpfixConfig {
+        enabled: true,
+        collector: collector.local_addr().unwrap().to_string(),
+        observation_domain_id: 42,
+        enterprise_number: PEN,
+        template_refresh_secs: 600,
+    };
+    let exporter = IpfixExporter::start(&config);
+    exporter.record(&flow("198.51.100.1", Some("203.0.113.7")));
+
+    let templates = recv_message(&collector).await;
+    assert_eq!(sets(&templates)[0].0, TEMPLATE_SET_ID);
+    assert_eq!(u32_at(&templates, 12), 42);
+    let data = recv_message(&collector).await;
+    assert_eq!(sets(&data)[0].0, TEMPLATE_ID_BASE);
+    assert_eq!(exporter.dropped_count(), 0);
+}
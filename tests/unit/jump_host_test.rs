@@ -0,0 +1,178 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::audit::events::AuditEvent;
+use s5::auth::AuthService;
+use s5::config::acl::AclPolicy;
+use s5::config::types::AppConfig;
+use s5::proxy::ip_guard::classify_guarded_ip;
+use s5::proxy::jump::JumpPolicy;
+use std::net::IpAddr;
+
+const OPS: &str =
+    "[[groups]]\nname = \"ops\"\njump_targets = [\"10.0.0.0/8:22\", \"*.internal:22\"]\n";
+
+/// `[security]` with `security`, group `ops`, and `alice` appended to alice.
+fn config(security: &str, alice: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[security]\n{security}\n\n{OPS}"), alice)
+}
+
+/// Another `[[users]]` entry, to append after alice's.
+fn user(name: &str, extra: &str) -> String {
+    format!("\n[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\n{extra}\n")
+}
+
+fn ip(s: &str) -> Option<IpAddr> {
+    Some(s.parse().unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn jump_targets_resolve_user_over_group() {
+    let alice = [
+        "group = \"ops\"\n".to_string(),
+        user(
+            "bob",
+            "group = \"ops\"\njump_targets = [\"bastion-2.internal:22\"]",
+        ),
+        user("carol", ""),
+    ]
+    .concat();
+    let config = config("", &alice).unwrap();
+    assert_eq!(config.security.jump_ports, vec![22]);
+
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    let alice = store.get("alice").unwrap();
+    assert_eq!(alice.jump_targets.as_ref().unwrap().len(), 2);
+    let bob = store.get("bob").unwrap();
+    let bob_targets = bob.jump_targets.as_ref().unwrap();
+    assert_eq!(bob_targets.len(), 1);
+    assert_eq!(bob_targets[0].to_string(), "bastion-2.internal:22");
+    assert!(store.get("carol").unwrap().jump_targets.is_none());
+}
+
+#[test]
+fn jump_config_validation() {
+    let ports = config("jump_ports = [22, 2222]", "")
+        .unwrap()
+        .security
+        .jump_ports;
+    assert_eq!(ports, vec![22, 2222]);
+
+    assert!(config("jump_ports = [0]", "").is_err());
+    assert!(config("", "jump_targets = [\"no-port\"]").is_err());
+    let bad_group = OPS.replace("\"*.internal:22\"", "\"*.internal:x\"");
+    assert!(parse_app_config(&bad_group, "").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Policy
+// ---------------------------------------------------------------------------
+
+#[test]
+fn no_jump_targets_means_regular_acl() {
+    let config = config("", "").unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let alice = auth.user_store().get("alice").unwrap().clone();
+    assert!(JumpPolicy::for_target(&alice, "10.0.0.5", 22).is_none());
+}
+
+#[test]
+fn jump_policy_allows_listed_targets_only() {
+    let config = config(
+        "",
+        "group = \"ops\"\n[users.acl]\ndeny = [\"secret.internal:*\"]",
+    )
+    .unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let alice = auth.user_store().get("alice").unwrap().clone();
+
+    let policy = JumpPolicy::for_target(&alice, "db.internal", 22).unwrap();
+    let acl = &policy.acl;
+    assert_eq!(
+        acl.check("db.internal", 22, ip("10.1.2.3")),
+        AclPolicy::Allow
+    );
+    assert_eq!(acl.check("10.9.9.9", 22, None), AclPolicy::Allow);
+    assert_eq!(
+        acl.check("example.com", 22, ip("10.4.4.4")),
+        AclPolicy::Allow
+    );
+    assert_eq!(
+        acl.check("example.com", 22, ip("93.184.216.34")),
+        AclPolicy::Deny
+    );
+    assert_eq!(acl.check("192.168.1.1", 22, None), AclPolicy::Deny);
+    // Other ports of a jump target are not hops
+    assert_eq!(
+        acl.check("db.internal", 2222, ip("10.1.2.3")),
+        AclPolicy::Deny
+    );
+    // The user's deny rules still win
+    assert_eq!(
+        acl.check("secret.internal", 22, ip("10.1.2.4")),
+        AclPolicy::Deny
+    );
+}
+
+#[test]
+fn jump_targets_are_exempt_from_ip_guard() {
+    let config = config(
+        "",
+        "group = \"ops\"\nip_guard_exemptions = [\"100.64.0.0/10\"]",
+    )
+    .unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let alice = auth.user_store().get("alice").unwrap().clone();
+
+    // Named target: wherever it resolves
+    let named = JumpPolicy::for_target(&alice, "db.internal", 22).unwrap();
+    let exempt = &named.ip_guard_exemptions;
+    assert!(classify_guarded_ip(&ip("192.168.7.7").unwrap(), exempt).is_none());
+
+    // Other names: only the listed ranges and the user's own exemptions
+    let other = JumpPolicy::for_target(&alice, "example.com", 22).unwrap();
+    let exempt = &other.ip_guard_exemptions;
+    assert!(classify_guarded_ip(&ip("10.0.0.5").unwrap(), exempt).is_none());
+    assert!(classify_guarded_ip(&ip("100.64.0.1").unwrap(), exempt).is_none());
+    assert!(classify_guarded_ip(&ip("192.168.7.7").unwrap(), exempt).is_some());
+    assert!(classify_guarded_ip(&ip("127.0.0.1").unwrap(), exempt).is_some());
+}
+
+// ---------------------------------------------------------------------------
+// Audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn ssh_jump_event_records_final_destination() {
+    let event = AuditEvent::ssh_jump_with_cid(
+        "alice",
+        "db.internal",
+        22,
+        Some("10.1.2.3".to_string()),
+        "203.0.113.9",
+        Some("allow:*.internal:22".to_string()),
+        "ssh-42",
+    );
+    assert_eq!(event.event_type(), "ssh.jump");
+    assert!(!event.is_critical());
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["event_type"], "ssh.jump");
+    assert_eq!(json["correlation_id"], "ssh-42");
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["source_ip"], "203.0.113.9");
+    assert_eq!(json["target_host"], "db.internal");
+    assert_eq!(json["target_port"], 22);
+    assert_eq!(json["resolved_ip"], "10.1.2.3");
+    assert_eq!(json["matched_rule"], "allow:*.internal:22");
+
+    // Hop through an upstream proxy: the address is unknown
+    let event =
+        AuditEvent::ssh_jump_with_cid("alice", "db.internal", 22, None, "203.0.113.9", None, "c");
+    let json = serde_json::to_value(&event).unwrap();
+    assert!(json.get("resolved_ip").is_none());
+    assert!(json.get("matched_rule").is_none());
+}
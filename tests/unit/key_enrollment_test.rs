@@ -0,0 +1,235 @@
+use crate::test_support::{app_config_toml, parse_app_config, FAKE_HASH};
+use s5::audit::events::AuditEvent;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::persist;
+use s5::notifications::Notifier;
+use s5::security::key_enrollment::{Enrollment, EnrollmentStatus, KeyEnrollment};
+use s5::security::SecurityManager;
+
+fn queue(options: &str) -> KeyEnrollment {
+    let config =
+        parse_app_config(&format!("[key_enrollment]\nenabled = true\n{options}"), "").unwrap();
+    KeyEnrollment::new(&config)
+}
+
+fn new_key() -> russh::keys::PublicKey {
+    let private =
+        russh::keys::PrivateKey::random(&mut rand::rngs::OsRng, russh::keys::Algorithm::Ed25519)
+            .unwrap();
+    russh::keys::PublicKey::from(&private)
+}
+
+/// `authorized_keys` line as written on approval.
+fn authorized_line(key: &russh::keys::PublicKey, id: u32) -> String {
+    format!("{} s5-enrolled-{}", key.to_openssh().unwrap(), id)
+}
+
+fn ip() -> std::net::IpAddr {
+    "203.0.113.7".parse().unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Queue
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn approval_wakes_held_logins() {
+    let q = queue("");
+    let key = new_key();
+    let Enrollment::New(request, mut first) = q.request("alice", &key, ip(), None) else {
+        panic!("expected a new request");
+    };
+    assert_eq!(request.status, EnrollmentStatus::Pending);
+    assert!(request.fingerprint.starts_with("SHA256:"));
+    assert!(request.public_key.starts_with("ssh-ed25519 "));
+
+    // A second login with the same key joins the request
+    let Enrollment::Pending(mut second) = q.request("alice", &key, ip(), None) else {
+        panic!("expected the pending request");
+    };
+    assert_eq!(q.list().len(), 1);
+
+    let decided = q.decide(&request.id, true, "admin").unwrap();
+    assert_eq!(decided.status, EnrollmentStatus::Approved);
+    assert_eq!(decided.decided_by.as_deref(), Some("admin"));
+    assert!(
+        q.decide(&request.id, false, "admin").is_none(),
+        "decided once"
+    );
+
+    for rx in [&mut first, &mut second] {
+        let status = *rx
+            .wait_for(|s| *s != EnrollmentStatus::Pending)
+            .await
+            .unwrap();
+        assert_eq!(status, EnrollmentStatus::Approved);
+    }
+}
+
+#[test]
+fn rejected_key_is_refused_without_new_request() {
+    let q = queue("");
+    let key = new_key();
+    let Enrollment::New(request, _) = q.request("alice", &key, ip(), None) else {
+        panic!("expected a new request");
+    };
+    q.decide(&request.id, false, "admin").unwrap();
+    assert!(matches!(
+        q.request("alice", &key, ip(), None),
+        Enrollment::Refused
+    ));
+    assert_eq!(q.list().len(), 1);
+    assert_eq!(
+        q.get(&request.id).unwrap().status,
+        EnrollmentStatus::Rejected
+    );
+}
+
+#[test]
+fn pending_requests_capped_per_user() {
+    let q = queue("max_pending_per_user = 2");
+    for _ in 0..2 {
+        assert!(matches!(
+            q.request("alice", &new_key(), ip(), None),
+            Enrollment::New(..)
+        ));
+    }
+    assert!(matches!(
+        q.request("alice", &new_key(), ip(), None),
+        Enrollment::Refused
+    ));
+    // Other users have their own allowance
+    assert!(matches!(
+        q.request("bob", &new_key(), ip(), None),
+        Enrollment::New(..)
+    ));
+}
+
+#[tokio::test]
+async fn expired_requests_are_dropped_and_release_logins() {
+    let q = queue("pending_hours = 1");
+    let Enrollment::New(_, mut rx) = q.request("alice", &new_key(), ip(), None) else {
+        panic!("expected a new request");
+    };
+    q.expire(chrono::Utc::now() + chrono::Duration::minutes(59));
+    assert_eq!(q.list().len(), 1);
+
+    q.expire(chrono::Utc::now() + chrono::Duration::hours(2));
+    assert!(q.list().is_empty());
+    let status = *rx
+        .wait_for(|s| *s != EnrollmentStatus::Pending)
+        .await
+        .unwrap();
+    assert_eq!(status, EnrollmentStatus::Rejected);
+}
+
+// ---------------------------------------------------------------------------
+// Approval side effects
+// ---------------------------------------------------------------------------
+
+#[test]
+fn approved_key_added_to_user_store() {
+    let config = parse_app_config("", "").unwrap();
+    let mut auth = AuthService::new(&config).unwrap();
+    let key = new_key();
+    assert!(!auth.auth_publickey("alice", &key));
+
+    let line = authorized_line(&key, 1);
+    assert!(auth.add_authorized_key("alice", &line));
+    assert!(auth.auth_publickey("alice", &key));
+    assert_eq!(
+        auth.user_store().get("alice").unwrap().authorized_keys,
+        vec![line.clone()]
+    );
+    // Idempotent
+    assert!(auth.add_authorized_key("alice", &line));
+    assert_eq!(
+        auth.user_store()
+            .get("alice")
+            .unwrap()
+            .authorized_keys
+            .len(),
+        1
+    );
+
+    assert!(!auth.add_authorized_key("mallory", &line));
+    assert!(!auth.add_authorized_key("alice", "not a key"));
+}
+
+#[test]
+fn persist_appends_to_authorized_keys() {
+    let line = authorized_line(&new_key(), 1);
+    let content = app_config_toml("", "");
+
+    let updated = persist::add_authorized_key(&content, "alice", &line).unwrap();
+    let parsed = parse_config(&updated).unwrap();
+    assert_eq!(parsed.users[0].authorized_keys, vec![line.clone()]);
+    assert!(updated.contains(FAKE_HASH), "rest of the entry untouched");
+
+    let second = authorized_line(&new_key(), 2);
+    let updated = persist::add_authorized_key(&updated, "alice", &second).unwrap();
+    let updated = persist::add_authorized_key(&updated, "alice", &second).unwrap();
+    let parsed = parse_config(&updated).unwrap();
+    assert_eq!(parsed.users[0].authorized_keys, vec![line.clone(), second]);
+
+    assert!(persist::add_authorized_key(&content, "mallory", &line).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Reload and reporting
+// ---------------------------------------------------------------------------
+
+#[test]
+fn reload_keeps_queue() {
+    let enabled = parse_app_config("[key_enrollment]\nenabled = true", "").unwrap();
+    let mut security = SecurityManager::new(&enabled);
+    let key = new_key();
+    security
+        .key_enrollment()
+        .unwrap()
+        .request("alice", &key, ip(), None);
+
+    security.reload(&enabled);
+    assert_eq!(security.key_enrollment().unwrap().list().len(), 1);
+
+    security.reload(&parse_app_config("", "").unwrap());
+    assert!(security.key_enrollment().is_none());
+}
+
+#[test]
+fn enrollment_events_and_notification() {
+    let hassh = Some("101e2cda8da5b95e5e95f62308cd6b89".to_string());
+    let Enrollment::New(request, _) = queue("").request("alice", &new_key(), ip(), hassh) else {
+        panic!("expected a new request");
+    };
+    let event = AuditEvent::key_enrollment_requested_with_cid(&request, "cid-1");
+    assert_eq!(event.event_type(), "key_enrollment.requested");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["request_id"], request.id.as_str());
+    assert_eq!(json["fingerprint"], request.fingerprint.as_str());
+    assert_eq!(json["hassh"], "101e2cda8da5b95e5e95f62308cd6b89");
+
+    let decided = AuditEvent::key_enrollment_decided(&request, true, "ops");
+    assert_eq!(decided.event_type(), "key_enrollment.decided");
+    let json = serde_json::to_value(&decided).unwrap();
+    assert_eq!(json["approved"], true);
+    assert_eq!(json["decided_by"], "ops");
+
+    let config = parse_app_config(
+        "[[notifications.rules]]\nevent = \"key_enrollment\"\n\
+         [notifications.smtp]\nhost = \"mail.example.com\"\n\
+         from = \"s5@example.com\"\nto = [\"ops@example.com\"]",
+        "",
+    )
+    .unwrap();
+    let notifier = Notifier::new(&config);
+    notifier.observe(&event);
+    let digests = notifier.flush();
+    assert_eq!(digests.len(), 1);
+    assert!(digests[0].body.contains(&format!(
+        "User alice presented new key {} from 203.0.113.7, awaiting approval (request {})",
+        request.fingerprint, request.id
+    )));
+}
@@ -0,0 +1,208 @@
+use crate::test_support::parse_app_config;
+use chrono::{Duration, TimeZone, Utc};
+use russh::keys::{Algorithm, PrivateKey, PublicKey, PublicKeyBase64};
+use s5::audit::events::AuditEvent;
+use s5::auth::key_fetch::parse_keys;
+use s5::auth::pubkey::{self, KeyOptions};
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::notifications::Notifier;
+
+fn key() -> (PublicKey, String) {
+    let private = PrivateKey::random(&mut rand::rngs::OsRng, Algorithm::Ed25519).unwrap();
+    let public = PublicKey::from(&private);
+    let base64 = public.public_key_base64();
+    (public, format!("ssh-ed25519 {base64} user@laptop"))
+}
+
+/// Time spec `days` from now, at minute precision.
+fn spec(days: i64) -> String {
+    (Utc::now() + Duration::days(days))
+        .format("%Y%m%d%H%M")
+        .to_string()
+}
+
+/// alice with `keys`, and `extra` sections.
+fn config(keys: &[String], extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(extra, &authorized_keys(keys))
+}
+
+fn authorized_keys(keys: &[String]) -> String {
+    let keys: Vec<String> = keys.iter().map(|k| format!("'{k}'")).collect();
+    format!("authorized_keys = [{}]\n", keys.join(", "))
+}
+
+#[test]
+fn time_spec_formats() {
+    let day = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+    assert_eq!(pubkey::parse_time_spec("20260301").unwrap(), day);
+    assert_eq!(
+        pubkey::parse_time_spec("202603011230").unwrap(),
+        Utc.with_ymd_and_hms(2026, 3, 1, 12, 30, 0).unwrap()
+    );
+    assert_eq!(
+        pubkey::parse_time_spec("20260301123045Z").unwrap(),
+        Utc.with_ymd_and_hms(2026, 3, 1, 12, 30, 45).unwrap()
+    );
+    assert!(pubkey::parse_time_spec("2026-03-01").is_err());
+    assert!(pubkey::parse_time_spec("20261301").is_err());
+    assert!(pubkey::parse_time_spec("").is_err());
+}
+
+#[test]
+fn key_options_are_parsed() {
+    let (public, line) = key();
+    assert_eq!(pubkey::key_options(&line).unwrap(), KeyOptions::default());
+
+    let dated = format!("created-time=\"20260101\",expiry-time=\"20270101\" {line}");
+    let options = pubkey::key_options(&dated).unwrap();
+    assert_eq!(
+        options.created_at,
+        Some(Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap())
+    );
+    assert_eq!(
+        options.expires_at,
+        Some(Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap())
+    );
+    assert_eq!(pubkey::parse_authorized_key(&dated).unwrap(), public);
+
+    let dated = pubkey::dated_keys(&[line.clone(), dated]);
+    assert_eq!(dated.len(), 1);
+    assert_eq!(dated[0].key, public);
+    assert_eq!(dated[0].comment.as_deref(), Some("user@laptop"));
+}
+
+#[test]
+fn unsupported_options_are_refused() {
+    let (_, line) = key();
+    for options in [
+        "command=\"/bin/true\"",
+        "no-pty",
+        "expiry-time=\"tomorrow\"",
+        "expiry-time=20270101",
+    ] {
+        let line = format!("{options} {line}");
+        assert!(pubkey::key_options(&line).is_err(), "{options}");
+        assert!(pubkey::parse_authorized_key(&line).is_err(), "{options}");
+    }
+}
+
+#[test]
+fn config_validation() {
+    let (_, line) = key();
+    let dated = format!("expiry-time=\"{}\" {line}", spec(30));
+    assert!(config(&[dated], "").is_ok());
+    let err = config(&[format!("from=\"10.0.0.0/8\" {line}")], "").unwrap_err();
+    assert!(format!("{err:#}").contains("unsupported authorized_keys option"));
+    assert!(config(&[format!("expiry-time=\"soon\" {line}")], "").is_err());
+}
+
+#[test]
+fn expired_key_is_refused() {
+    let (current, current_line) = key();
+    let (expired, expired_line) = key();
+    let (soon, soon_line) = key();
+    let cfg = config(
+        &[
+            format!("expiry-time=\"{}\" {current_line}", spec(365)),
+            format!("expiry-time=\"{}\" {expired_line}", spec(-1)),
+            format!("expiry-time=\"{}\" {soon_line}", spec(3)),
+        ],
+        "",
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    assert!(auth.auth_publickey("alice", &current));
+    assert!(auth.auth_publickey("alice", &soon));
+    assert!(!auth.auth_publickey("alice", &expired));
+
+    let user = auth.user_store().get("alice").unwrap();
+    assert!(user.key_expired(&expired, Utc::now()));
+    assert!(!user.key_expired(&soon, Utc::now()));
+    assert!(user.key_expired(&soon, Utc::now() + Duration::days(4)));
+}
+
+#[test]
+fn expiring_keys_are_listed_soonest_first() {
+    let (_, plain) = key();
+    let (_, later) = key();
+    let (_, soon) = key();
+    let (_, expired) = key();
+    let alice = authorized_keys(&[
+        plain,
+        format!("expiry-time=\"{}\" {later}", spec(60)),
+        format!(
+            "created-time=\"20250101\",expiry-time=\"{}\" {soon}",
+            spec(10)
+        ),
+    ]);
+    let bob = authorized_keys(&[format!("expiry-time=\"{}\" {expired}", spec(-2))]);
+    let cfg = parse_app_config(
+        "",
+        &format!("{alice}\n[[users]]\nusername = \"bob\"\n{bob}"),
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let now = Utc::now();
+
+    let keys = auth.expiring_keys(now, Duration::days(pubkey::KEY_EXPIRY_REMINDER_DAYS));
+    let users: Vec<&str> = keys.iter().map(|k| k.username.as_str()).collect();
+    assert_eq!(users, ["bob", "alice"]);
+    assert!(keys[0].expired);
+    assert!(!keys[1].expired);
+    assert!(keys[1].fingerprint.starts_with("SHA256:"));
+    assert_eq!(keys[1].comment.as_deref(), Some("user@laptop"));
+    assert_eq!(
+        keys[1].created_at,
+        Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap())
+    );
+
+    assert_eq!(auth.expiring_keys(now, Duration::days(90)).len(), 3);
+    assert_eq!(auth.expiring_keys(now, Duration::zero()).len(), 1);
+}
+
+#[test]
+fn fetched_lists_drop_expired_keys() {
+    let (current, current_line) = key();
+    let (_, expired_line) = key();
+    let body = format!(
+        "expiry-time=\"{}\" {current_line}\nexpiry-time=\"{}\" {expired_line}\n",
+        spec(30),
+        spec(-1)
+    );
+    assert_eq!(parse_keys(&body), vec![current]);
+}
+
+#[test]
+fn expiring_event_and_notification() {
+    let (_, line) = key();
+    let cfg = config(
+        &[format!("expiry-time=\"{}\" {line}", spec(5))],
+        "[[notifications.rules]]\nevent = \"key_expiring\"\n\
+         [notifications.smtp]\nhost = \"mail.example.com\"\n\
+         from = \"s5@example.com\"\nto = [\"ops@example.com\"]",
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let now = Utc::now();
+    let keys = auth.expiring_keys(now, Duration::days(pubkey::KEY_EXPIRY_REMINDER_DAYS));
+    assert_eq!(keys.len(), 1);
+
+    let event = AuditEvent::key_expiring(&keys[0], now);
+    assert_eq!(event.event_type(), "key.expiring");
+    assert!(!event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["fingerprint"], keys[0].fingerprint.as_str());
+    assert_eq!(json["days_left"], 4);
+
+    let notifier = Notifier::new(&cfg);
+    notifier.observe(&event);
+    let digests = notifier.flush();
+    assert_eq!(digests.len(), 1);
+    assert!(digests[0].body.contains(&format!(
+        "Public key {} of user alice expires on",
+        keys[0].fingerprint
+    )));
+    assert!(digests[0].body.contains("(4 days left)"));
+}
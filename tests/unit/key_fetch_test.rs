@@ -0,0 +1,170 @@
+use russh::keys::{Algorithm, PrivateKey, PublicKey, PublicKeyBase64};
+use s5::auth::key_fetch::{parse_keys, KeyFetcher};
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::AuthorizedKeysFetchConfig;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+fn key() -> (PublicKey, String) {
+    let private = PrivateKey::random(&mut rand::rngs::OsRng, Algorithm::Ed25519).unwrap();
+    let public = PublicKey::from(&private);
+    let line = format!("ssh-ed25519 {} user@laptop", public.public_key_base64());
+    (public, line)
+}
+
+/// Key list server: answers `GET /keys` with the shared body and its ETag,
+/// or 304 when `If-None-Match` matches. `None` = 503.
+struct KeyServer {
+    url: String,
+    body: Arc<Mutex<Option<String>>>,
+    requests: Arc<AtomicUsize>,
+    not_modified: Arc<AtomicUsize>,
+}
+
+async fn key_server(body: &str) -> KeyServer {
+    let shared = Arc::new(Mutex::new(Some(body.to_string())));
+    let requests = Arc::new(AtomicUsize::new(0));
+    let not_modified = Arc::new(AtomicUsize::new(0));
+    let (state, count, unchanged) = (shared.clone(), requests.clone(), not_modified.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().route(
+            "/keys",
+            axum::routing::get(move |headers: axum::http::HeaderMap| async move {
+                use axum::http::{header, StatusCode};
+                use axum::response::IntoResponse;
+                count.fetch_add(1, Ordering::SeqCst);
+                let Some(body) = state.lock().unwrap().clone() else {
+                    return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                };
+                let etag = format!("\"{}\"", body.len());
+                if headers
+                    .get(header::IF_NONE_MATCH)
+                    .and_then(|v| v.to_str().ok())
+                    == Some(etag.as_str())
+                {
+                    unchanged.fetch_add(1, Ordering::SeqCst);
+                    return StatusCode::NOT_MODIFIED.into_response();
+                }
+                ([(header::ETAG, etag)], body).into_response()
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    KeyServer {
+        url: format!("http://127.0.0.1:{port}/keys"),
+        body: shared,
+        requests,
+        not_modified,
+    }
+}
+
+fn fetch_config(refresh_secs: u64, max_stale_secs: u64) -> AuthorizedKeysFetchConfig {
+    AuthorizedKeysFetchConfig {
+        refresh_secs,
+        timeout_secs: 5,
+        max_stale_secs,
+    }
+}
+
+#[test]
+fn parse_skips_comments_and_bad_lines() {
+    let (first, first_line) = key();
+    let (second, second_line) = key();
+    let body = format!("# team keys\n\n{first_line}\nnot-a-key\n  {second_line}  \n");
+    assert_eq!(parse_keys(&body), vec![first, second]);
+    assert!(parse_keys("").is_empty());
+}
+
+#[tokio::test]
+async fn keys_are_cached_for_refresh_secs() {
+    let (alice, line) = key();
+    let (other, _) = key();
+    let server = key_server(&line).await;
+    let fetcher = KeyFetcher::new(&fetch_config(300, 3600));
+
+    assert!(fetcher.authorizes(&server.url, &alice).await);
+    assert!(!fetcher.authorizes(&server.url, &other).await);
+    assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn unchanged_list_is_revalidated_with_etag() {
+    let (alice, line) = key();
+    let (rotated, rotated_line) = key();
+    let server = key_server(&line).await;
+    let fetcher = KeyFetcher::new(&fetch_config(0, 3600));
+
+    assert!(fetcher.authorizes(&server.url, &alice).await);
+    assert!(fetcher.authorizes(&server.url, &alice).await);
+    assert_eq!(server.requests.load(Ordering::SeqCst), 2);
+    assert_eq!(server.not_modified.load(Ordering::SeqCst), 1);
+
+    // Rotated upstream: the old key stops working
+    *server.body.lock().unwrap() = Some(format!("{rotated_line}\n# rotated\n"));
+    assert!(fetcher.authorizes(&server.url, &rotated).await);
+    assert!(!fetcher.authorizes(&server.url, &alice).await);
+}
+
+#[tokio::test]
+async fn cached_keys_survive_outage_until_stale() {
+    let (alice, line) = key();
+    let server = key_server(&line).await;
+    let tolerant = KeyFetcher::new(&fetch_config(0, 3600));
+    let strict = KeyFetcher::new(&fetch_config(0, 0));
+    assert!(tolerant.authorizes(&server.url, &alice).await);
+    assert!(strict.authorizes(&server.url, &alice).await);
+
+    *server.body.lock().unwrap() = None;
+    assert!(tolerant.authorizes(&server.url, &alice).await);
+    assert!(!strict.authorizes(&server.url, &alice).await);
+
+    // Nothing cached and nothing reachable
+    let fresh = KeyFetcher::new(&fetch_config(0, 3600));
+    assert!(!fresh.authorizes(&server.url, &alice).await);
+}
+
+#[test]
+fn config_validation() {
+    let config = |user: &str, extra: &str| {
+        parse_config(&format!(
+            "[server]\nssh_listen = \"127.0.0.1:0\"\n\n{extra}\n\n\
+             [[users]]\nusername = \"alice\"\n{user}\n"
+        ))
+    };
+    // A URL alone is enough credentials
+    let cfg = config(
+        "authorized_keys_url = \"https://github.com/alice.keys\"",
+        "",
+    )
+    .unwrap();
+    assert_eq!(cfg.authorized_keys_fetch.refresh_secs, 300);
+    assert!(config("", "").is_err());
+    assert!(config("authorized_keys_url = \"github.com/alice.keys\"", "").is_err());
+    assert!(config(
+        "authorized_keys_url = \"https://github.com/alice.keys\"",
+        "[authorized_keys_fetch]\ntimeout_secs = 0"
+    )
+    .is_err());
+}
+
+#[test]
+fn remote_keys_only_for_users_with_a_url() {
+    let cfg = parse_config(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+         [[users]]\nusername = \"alice\"\n\
+         authorized_keys_url = \"https://github.com/alice.keys\"\n\n\
+         [[users]]\nusername = \"bob\"\npassword_hash = \"x\"\n\n\
+         [[users]]\nusername = \"carol\"\nauthorized_keys_url = \"https://github.com/carol.keys\"\n\
+         disabled = true\n",
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let (_, url) = auth.remote_keys("alice").unwrap();
+    assert_eq!(url, "https://github.com/alice.keys");
+    assert!(auth.remote_keys("bob").is_none());
+    assert!(auth.remote_keys("carol").is_none());
+    assert!(auth.remote_keys("nobody").is_none());
+}
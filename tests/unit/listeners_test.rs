@@ -0,0 +1,128 @@
+use s5::config::parse_config;
+use std::path::PathBuf;
+
+fn config_with_listeners(listeners: &str) -> anyhow::Result<s5::config::types::AppConfig> {
+    parse_config(&format!(
+        r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+{listeners}
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+    ))
+}
+
+#[test]
+fn no_extra_listeners_by_default() {
+    let config = config_with_listeners("").unwrap();
+    assert!(config.server.listeners.is_empty());
+}
+
+#[test]
+fn listeners_parse_with_policies() {
+    let config = config_with_listeners(
+        r#"
+[[server.listeners]]
+name = "internal"
+listen = "10.0.0.1:2222"
+host_key_path = "internal_host_key"
+ip_guard_enabled = false
+allowed_source_ips = ["10.0.0.0/8"]
+
+[[server.listeners]]
+name = "public"
+listen = "0.0.0.0:22"
+tarpit_enabled = true
+"#,
+    )
+    .unwrap();
+    let listeners = &config.server.listeners;
+    assert_eq!(listeners.len(), 2);
+
+    let internal = &listeners[0];
+    assert_eq!(internal.name, "internal");
+    assert_eq!(internal.listen, "10.0.0.1:2222");
+    assert_eq!(
+        internal.host_key_path,
+        Some(PathBuf::from("internal_host_key"))
+    );
+    assert_eq!(internal.ip_guard_enabled, Some(false));
+    assert_eq!(internal.allowed_source_ips.len(), 1);
+    assert!(internal.tarpit_enabled.is_none());
+
+    // Unset fields follow the server-wide settings
+    let public = &listeners[1];
+    assert!(public.host_key_path.is_none());
+    assert!(public.ip_guard_enabled.is_none());
+    assert!(public.allowed_source_ips.is_empty());
+    assert_eq!(public.tarpit_enabled, Some(true));
+}
+
+#[test]
+fn duplicate_listener_names_rejected() {
+    let err = config_with_listeners(
+        r#"
+[[server.listeners]]
+name = "internal"
+listen = "10.0.0.1:2222"
+
+[[server.listeners]]
+name = "internal"
+listen = "10.0.0.2:2222"
+"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("duplicate"), "{err}");
+
+    // `main` names server.ssh_listen
+    assert!(config_with_listeners(
+        "[[server.listeners]]\nname = \"main\"\nlisten = \"10.0.0.1:2222\""
+    )
+    .is_err());
+}
+
+#[test]
+fn listener_address_must_be_unique() {
+    assert!(config_with_listeners(
+        "[[server.listeners]]\nname = \"again\"\nlisten = \"0.0.0.0:2222\""
+    )
+    .is_err());
+    assert!(config_with_listeners(
+        r#"
+[[server.listeners]]
+name = "a"
+listen = "10.0.0.1:2222"
+
+[[server.listeners]]
+name = "b"
+listen = "10.0.0.1:2222"
+"#
+    )
+    .is_err());
+}
+
+#[test]
+fn invalid_listener_fields_rejected() {
+    assert!(
+        config_with_listeners("[[server.listeners]]\nname = \"\"\nlisten = \"10.0.0.1:22\"")
+            .is_err()
+    );
+    assert!(config_with_listeners(
+        "[[server.listeners]]\nname = \"bad name\"\nlisten = \"10.0.0.1:22\""
+    )
+    .is_err());
+    assert!(config_with_listeners("[[server.listeners]]\nname = \"x\"\nlisten = \"\"").is_err());
+    assert!(config_with_listeners(
+        r#"
+[[server.listeners]]
+name = "x"
+listen = "10.0.0.1:22"
+allowed_source_ips = ["nope"]
+"#
+    )
+    .is_err());
+}
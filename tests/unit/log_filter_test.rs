@@ -0,0 +1,52 @@
+use s5::audit::events::AuditEvent;
+use s5::log_filter::{self, LogFilterError};
+use tracing::Level;
+use tracing_subscriber::prelude::*;
+
+// The filter handle is process-wide: a single test owns it.
+#[test]
+fn level_changed_and_reset_at_runtime() {
+    let subscriber = tracing_subscriber::registry().with(log_filter::layer("info"));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let level = log_filter::level().unwrap();
+    assert_eq!(level.level, "info");
+    assert_eq!(level.default, "info");
+    assert!(tracing::enabled!(Level::INFO));
+    assert!(!tracing::enabled!(Level::DEBUG));
+
+    // Per-module directives
+    assert_eq!(
+        log_filter::set_level(" warn,s5::proxy=trace ").unwrap(),
+        "info"
+    );
+    assert_eq!(log_filter::level().unwrap().level, "warn,s5::proxy=trace");
+    assert!(tracing::enabled!(target: "s5::proxy::relay", Level::TRACE));
+    assert!(!tracing::enabled!(target: "s5::ssh", Level::INFO));
+
+    // Rejected directives leave the filter alone
+    assert!(matches!(
+        log_filter::set_level("s5::proxy=loud"),
+        Err(LogFilterError::Invalid(_))
+    ));
+    assert!(matches!(
+        log_filter::set_level("  "),
+        Err(LogFilterError::Invalid(_))
+    ));
+    assert_eq!(log_filter::level().unwrap().level, "warn,s5::proxy=trace");
+
+    assert_eq!(log_filter::reset_level().unwrap(), "warn,s5::proxy=trace");
+    assert_eq!(log_filter::level().unwrap().level, "info");
+    assert!(!tracing::enabled!(Level::DEBUG));
+}
+
+#[test]
+fn level_change_audit_event_is_critical() {
+    let event = AuditEvent::log_level_changed("info", "info,s5::proxy=trace", "admin");
+    assert_eq!(event.event_type(), "logging.level_changed");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["previous"], "info");
+    assert_eq!(json["level"], "info,s5::proxy=trace");
+    assert_eq!(json["changed_by"], "admin");
+}
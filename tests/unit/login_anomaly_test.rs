@@ -0,0 +1,174 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::notifications::Notifier;
+use s5::security::login_anomaly::{
+    LoginAnomaly, LoginAnomalyDetector, NEW_ASN, NEW_COUNTRY, UNUSUAL_HOUR,
+};
+use s5::security::SecurityManager;
+
+fn detector(options: &str) -> LoginAnomalyDetector {
+    let config =
+        parse_app_config(&format!("[login_anomaly]\nenabled = true\n{options}"), "").unwrap();
+    LoginAnomalyDetector::new(&config)
+}
+
+fn at(hour: u32) -> chrono::DateTime<chrono::Utc> {
+    use chrono::TimeZone;
+    chrono::Utc
+        .with_ymd_and_hms(2026, 3, 2, hour, 15, 0)
+        .unwrap()
+}
+
+/// Five logins from FR / AS3215 around 09:00 UTC.
+fn learn(detector: &LoginAnomalyDetector, user: &str) {
+    for hour in [8, 9, 9, 10, 9] {
+        assert!(detector
+            .observe(user, Some("FR"), Some(3215), at(hour))
+            .is_none());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Detection
+// ---------------------------------------------------------------------------
+
+#[test]
+fn learning_logins_are_never_flagged() {
+    let d = detector("");
+    for (country, asn, hour) in [("FR", 3215, 9), ("US", 15169, 22), ("BR", 28573, 3)] {
+        assert!(d
+            .observe("alice", Some(country), Some(asn), at(hour))
+            .is_none());
+    }
+}
+
+#[test]
+fn new_country_asn_and_hour_are_flagged_once() {
+    let d = detector("");
+    learn(&d, "alice");
+    assert!(d.observe("alice", Some("FR"), Some(3215), at(11)).is_none());
+
+    let anomaly = d.observe("alice", Some("DE"), Some(3320), at(3)).unwrap();
+    assert_eq!(
+        anomaly,
+        LoginAnomaly {
+            reasons: vec![NEW_COUNTRY, NEW_ASN, UNUSUAL_HOUR],
+            country: Some("DE".to_string()),
+            asn: Some(3320),
+            hour: 3,
+            known_logins: 6,
+        }
+    );
+    // Now part of the history
+    assert!(d.observe("alice", Some("DE"), Some(3320), at(3)).is_none());
+
+    // Users have separate histories
+    assert!(d.observe("bob", Some("DE"), Some(3320), at(3)).is_none());
+}
+
+#[test]
+fn unknown_location_only_checks_hours() {
+    let d = detector("");
+    learn(&d, "alice");
+    assert!(d.observe("alice", None, None, at(9)).is_none());
+    let anomaly = d.observe("alice", None, None, at(17)).unwrap();
+    assert_eq!(anomaly.reasons, vec![UNUSUAL_HOUR]);
+}
+
+#[test]
+fn hour_tolerance_wraps_around_midnight() {
+    let d = detector("learning_logins = 1\nhour_tolerance = 2\nnew_country = false");
+    assert!(d.observe("alice", Some("FR"), None, at(23)).is_none());
+    assert!(d.observe("alice", Some("US"), None, at(1)).is_none());
+    assert_eq!(
+        d.observe("alice", None, None, at(4)).unwrap().reasons,
+        vec![UNUSUAL_HOUR]
+    );
+}
+
+#[test]
+fn checks_can_be_disabled() {
+    let d = detector("new_asn = false\nunusual_hours = false");
+    learn(&d, "alice");
+    let anomaly = d.observe("alice", Some("DE"), Some(3320), at(3)).unwrap();
+    assert_eq!(anomaly.reasons, vec![NEW_COUNTRY]);
+}
+
+#[test]
+fn history_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.json");
+    let options = format!("history_path = \"{}\"", path.display());
+
+    learn(&detector(&options), "alice");
+    assert!(path.exists());
+
+    let restarted = detector(&options);
+    assert!(restarted
+        .observe("alice", Some("DE"), Some(3215), at(9))
+        .is_some());
+
+    // A corrupt file starts a fresh history
+    std::fs::write(&path, "{not json").unwrap();
+    assert!(detector(&options)
+        .observe("alice", Some("DE"), Some(3215), at(9))
+        .is_none());
+}
+
+// ---------------------------------------------------------------------------
+// Reload and reporting
+// ---------------------------------------------------------------------------
+
+#[test]
+fn reload_keeps_history() {
+    let enabled = parse_app_config("[login_anomaly]\nenabled = true", "").unwrap();
+    let mut security = SecurityManager::new(&enabled);
+    learn(security.login_anomaly().unwrap(), "alice");
+
+    security.reload(&enabled);
+    assert!(security
+        .login_anomaly()
+        .unwrap()
+        .observe("alice", Some("DE"), None, at(9))
+        .is_some());
+
+    security.reload(&parse_app_config("", "").unwrap());
+    assert!(security.login_anomaly().is_none());
+}
+
+#[test]
+fn anomaly_event_and_notification() {
+    let anomaly = LoginAnomaly {
+        reasons: vec![NEW_COUNTRY, UNUSUAL_HOUR],
+        country: Some("DE".to_string()),
+        asn: None,
+        hour: 3,
+        known_logins: 12,
+    };
+    let source = "203.0.113.7:50000".parse().unwrap();
+    let event = AuditEvent::login_anomaly_with_cid("alice", &source, "ssh", "cid-1", &anomaly);
+    assert_eq!(event.event_type(), "auth.anomaly");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        json["reasons"],
+        serde_json::json!(["new_country", "unusual_hour"])
+    );
+    assert_eq!(json["country"], "DE");
+    assert!(json.get("asn").is_none());
+
+    let config = parse_app_config(
+        "[[notifications.rules]]\nevent = \"login_anomaly\"\n\
+         [notifications.smtp]\nhost = \"mail.example.com\"\n\
+         from = \"s5@example.com\"\nto = [\"ops@example.com\"]",
+        "",
+    )
+    .unwrap();
+    let notifier = Notifier::new(&config);
+    notifier.observe(&event);
+    let digests = notifier.flush();
+    assert_eq!(digests.len(), 1);
+    assert!(digests[0].body.contains(
+        "Unusual login for user alice from 203.0.113.7 (new_country, unusual_hour; 03:00 UTC, DE)"
+    ));
+}
@@ -1,10 +1,16 @@
 mod test_support;
 
+mod account_lockout_test;
 mod acl_proptest;
 mod acl_test;
 mod alerting_test;
+mod api_cors_test;
+mod api_guard_test;
 mod api_middleware_test;
+mod api_session_test;
 mod api_test;
+mod audit_archive_test;
+mod audit_chain_test;
 mod audit_dropped_test;
 mod audit_events_serde_test;
 mod audit_improvements_test;
@@ -12,8 +18,15 @@ mod audit_logger_test;
 mod audit_rotation_test;
 mod audit_test;
 mod auth_service_test;
+mod blocked_ports_test;
+mod buffer_pool_test;
+mod capture_test;
 mod certificate_auth_test;
+mod circuit_breaker_test;
 mod cli_test;
+mod client_caps_test;
+mod cluster_test;
+mod config_history_test;
 mod config_merge_edge_cases_test;
 mod config_proptest;
 mod config_test;
@@ -21,35 +34,83 @@ mod config_validation_test;
 mod connector_test;
 mod connector_unit_test;
 mod context_test;
+mod credential_spraying_test;
+mod ctl_test;
 mod demo_scenarios_test;
+mod diagnostics_test;
 mod dns_cache_test;
+mod external_auth_test;
+mod flows_test;
 mod forwarder_test;
 mod forwarder_unit_test;
+mod geo_map_test;
 mod geoip_test;
 mod geoip_unit_test;
+mod group_inheritance_test;
+mod group_manager_test;
+mod group_policy_test;
+#[cfg(feature = "gssapi")]
+mod gssapi_test;
+mod handover_test;
+mod host_keys_test;
+mod import_test;
+mod impossible_travel_test;
+mod invitation_test;
 mod ip_guard_test;
+mod ip_intel_test;
 mod ip_rate_limiter_test;
 mod ip_reputation_test;
+mod ipfix_test;
+mod jump_host_test;
+mod key_enrollment_test;
+mod key_expiry_test;
+mod key_fetch_test;
+mod listeners_test;
+mod log_filter_test;
+mod login_anomaly_test;
 mod maintenance_window_edge_cases_test;
+#[cfg(feature = "masque")]
+mod masque_test;
 mod metrics_cardinality_test;
 mod metrics_extended_test;
 mod metrics_unit_test;
 mod new_features_test;
+mod notifications_test;
+mod openapi_test;
+mod password_policy_test;
 mod password_test;
+mod paths_test;
+mod payload_metadata_test;
+mod personal_token_test;
+#[cfg(feature = "wasm-plugins")]
+mod policy_plugin_test;
+mod policy_test;
 mod pool_test;
 mod pre_auth_check_test;
+mod priority_test;
 mod proxy_acl_decision_test;
 mod proxy_connection_details_test;
 mod proxy_engine_extended_test;
 mod proxy_engine_test;
 mod proxy_engine_unit_test;
 mod pubkey_test;
+mod quota_plan_test;
 mod quota_test;
 mod rate_limit_test;
 mod rate_limiter_extended_test;
+mod rehash_test;
 mod retry_test;
+mod sandbox_test;
+#[cfg(feature = "lua-hooks")]
+mod scripting_test;
+mod security_key_test;
 mod security_test;
+mod security_timeline_test;
+mod self_service_test;
 mod server_logic_test;
+mod server_name_acl_test;
+mod session_close_test;
+mod session_memory_test;
 mod shell_commands_test;
 mod shell_parser_proptest;
 mod shell_parser_test;
@@ -62,8 +123,23 @@ mod socks_protocol_test;
 mod socks_reply_codes_test;
 mod sse_ticket_test;
 mod ssh_handler_test;
+mod ssh_handshake_test;
 mod ssh_keys_test;
+mod ssh_pre_auth_test;
+mod static_hosts_test;
+mod streamlocal_test;
+mod subsystem_test;
+mod tarpit_test;
+mod tcp_options_test;
+mod tenant_test;
+mod terminal_policy_test;
+mod threat_intel_test;
+mod throughput_test;
 mod totp_extraction_test;
+mod traffic_classify_test;
+mod transfer_limit_test;
 mod upstream_proxy_test;
+mod usage_report_test;
 mod user_source_ip_test;
+mod vpn_test;
 mod webhook_test;
@@ -0,0 +1,418 @@
+use crate::test_support::parse_app_config;
+use bytes::{Buf, Bytes};
+use s5::audit::AuditLogger;
+use s5::auth::password;
+use s5::auth::AuthService;
+use s5::config::types::{AppConfig, MasqueConfig};
+use s5::context::AppContext;
+use s5::masque::handler::{credentials, parse_target, parse_udp_path, Target};
+use s5::masque::udp::{decode_varint, encode_datagram, encode_varint, CapsuleDecoder};
+use s5::metrics::MetricsRegistry;
+use s5::proxy::ProxyEngine;
+use s5::quota::QuotaTracker;
+use s5::security::SecurityManager;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+use tokio_rustls::rustls;
+use tokio_util::sync::CancellationToken;
+
+// ---------------------------------------------------------------------------
+// Request parsing
+// ---------------------------------------------------------------------------
+
+fn connect(uri: &str) -> http::Request<()> {
+    http::Request::builder()
+        .method(http::Method::CONNECT)
+        .uri(uri)
+        .body(())
+        .unwrap()
+}
+
+fn connect_udp(path: &str) -> http::Request<()> {
+    let mut req = connect(&format!("https://proxy.example{path}"));
+    req.extensions_mut().insert(h3::ext::Protocol::CONNECT_UDP);
+    req
+}
+
+#[test]
+fn connect_targets_the_authority() {
+    assert_eq!(
+        parse_target(&connect("example.com:443")),
+        Ok(Target::Tcp {
+            host: "example.com".to_string(),
+            port: 443
+        })
+    );
+    assert_eq!(
+        parse_target(&connect("[2001:db8::1]:22")),
+        Ok(Target::Tcp {
+            host: "2001:db8::1".to_string(),
+            port: 22
+        })
+    );
+}
+
+#[test]
+fn connect_without_port_is_a_bad_request() {
+    assert_eq!(
+        parse_target(&connect("example.com")),
+        Err(http::StatusCode::BAD_REQUEST)
+    );
+}
+
+#[test]
+fn other_methods_are_refused() {
+    let req = http::Request::get("https://example.com/").body(()).unwrap();
+    assert_eq!(
+        parse_target(&req),
+        Err(http::StatusCode::METHOD_NOT_ALLOWED)
+    );
+}
+
+#[test]
+fn connect_udp_targets_the_path() {
+    assert_eq!(
+        parse_target(&connect_udp("/.well-known/masque/udp/dns.example/53/")),
+        Ok(Target::Udp {
+            host: "dns.example".to_string(),
+            port: 53
+        })
+    );
+}
+
+#[test]
+fn udp_path_decodes_ipv6_hosts() {
+    assert_eq!(
+        parse_udp_path("/.well-known/masque/udp/2001%3Adb8%3A%3A1/443/"),
+        Some(("2001:db8::1".to_string(), 443))
+    );
+}
+
+#[test]
+fn udp_path_rejects_other_paths() {
+    assert_eq!(parse_udp_path("/udp/example.com/53/"), None);
+    assert_eq!(parse_udp_path("/.well-known/masque/udp/example.com/"), None);
+    assert_eq!(parse_udp_path("/.well-known/masque/udp//53/"), None);
+    assert_eq!(
+        parse_udp_path("/.well-known/masque/udp/example.com/dns/"),
+        None
+    );
+}
+
+#[test]
+fn basic_credentials_are_decoded() {
+    let mut headers = http::HeaderMap::new();
+    // alice:pass:word
+    headers.insert(
+        http::header::PROXY_AUTHORIZATION,
+        "Basic YWxpY2U6cGFzczp3b3Jk".parse().unwrap(),
+    );
+    assert_eq!(
+        credentials(&headers),
+        Some(("alice".to_string(), "pass:word".to_string()))
+    );
+}
+
+#[test]
+fn other_schemes_have_no_credentials() {
+    let mut headers = http::HeaderMap::new();
+    assert_eq!(credentials(&headers), None);
+    headers.insert(
+        http::header::PROXY_AUTHORIZATION,
+        "Bearer YWxpY2U6cGFzcw==".parse().unwrap(),
+    );
+    assert_eq!(credentials(&headers), None);
+}
+
+// ---------------------------------------------------------------------------
+// Capsules
+// ---------------------------------------------------------------------------
+
+#[test]
+fn varints_round_trip() {
+    for value in [0, 63, 64, 16383, 16384, 1_073_741_823, 1_073_741_824] {
+        let mut out = Vec::new();
+        encode_varint(value, &mut out);
+        assert_eq!(decode_varint(&out), Some((value, out.len())));
+    }
+}
+
+#[test]
+fn truncated_varint_is_incomplete() {
+    let mut out = Vec::new();
+    encode_varint(16384, &mut out);
+    assert_eq!(decode_varint(&out[..2]), None);
+}
+
+#[test]
+fn capsules_split_across_reads_are_reassembled() {
+    let mut stream = Vec::new();
+    encode_datagram(b"first", &mut stream);
+    encode_datagram(b"second", &mut stream);
+    let mut decoder = CapsuleDecoder::default();
+    let (a, b) = stream.split_at(9);
+    assert_eq!(decoder.push(a).unwrap(), vec![b"first".to_vec()]);
+    assert_eq!(decoder.push(b).unwrap(), vec![b"second".to_vec()]);
+}
+
+#[test]
+fn other_capsules_and_contexts_are_skipped() {
+    let mut stream = Vec::new();
+    // Unknown capsule type 0x2a
+    encode_varint(0x2a, &mut stream);
+    encode_varint(2, &mut stream);
+    stream.extend_from_slice(b"xx");
+    // Datagram of context 2
+    encode_varint(0, &mut stream);
+    encode_varint(3, &mut stream);
+    stream.extend_from_slice(&[2, b'y', b'y']);
+    encode_datagram(b"payload", &mut stream);
+    let mut decoder = CapsuleDecoder::default();
+    assert_eq!(decoder.push(&stream).unwrap(), vec![b"payload".to_vec()]);
+}
+
+#[test]
+fn oversized_capsule_is_an_error() {
+    let mut stream = Vec::new();
+    encode_varint(0, &mut stream);
+    encode_varint(1 << 20, &mut stream);
+    assert!(CapsuleDecoder::default().push(&stream).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// HTTP/3 end to end
+// ---------------------------------------------------------------------------
+
+fn make_config(password_hash: &str) -> AppConfig {
+    let mut config = parse_app_config(
+        "[limits]\nconnection_timeout = 2\nidle_timeout = 10\n\n\
+         [security]\nban_enabled = false\nip_guard_enabled = false",
+        "allow_forwarding = true\n\n[users.acl]\ndeny = [\"127.0.0.1:7\"]",
+    )
+    .unwrap();
+    config.users[0].password_hash = Some(password_hash.to_string());
+    config
+}
+
+fn setup(app_config: AppConfig) -> Arc<AppContext> {
+    let config = Arc::new(app_config);
+    let audit = Arc::new(AuditLogger::new(None, 0, 0, None));
+    Arc::new(AppContext {
+        config: config.clone(),
+        auth_service: Arc::new(RwLock::new(AuthService::new(&config).unwrap())),
+        proxy_engine: Arc::new(ProxyEngine::new(config.clone(), audit.clone())),
+        security: Arc::new(RwLock::new(SecurityManager::new(&config))),
+        audit,
+        metrics: Arc::new(MetricsRegistry::new()),
+        quota_tracker: Arc::new(QuotaTracker::new(&config.limits)),
+        webhook_dispatcher: None,
+        alert_engine: None,
+        start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+    })
+}
+
+type SendRequest = h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>;
+type ClientStream = h3::client::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
+
+/// A MASQUE listener on a free port with a self-signed certificate, and an
+/// HTTP/3 client connected to it.
+struct Proxy {
+    send: SendRequest,
+    _endpoint: quinn::Endpoint,
+    _dir: tempfile::TempDir,
+    shutdown: CancellationToken,
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+async fn start_proxy() -> Proxy {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let tls_cert = dir.path().join("cert.pem");
+    let tls_key = dir.path().join("key.pem");
+    std::fs::write(&tls_cert, cert.cert.pem()).unwrap();
+    std::fs::write(&tls_key, cert.key_pair.serialize_pem()).unwrap();
+
+    let port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listen: SocketAddr = format!("127.0.0.1:{port}").parse().unwrap();
+    let masque = MasqueConfig {
+        listen: listen.to_string(),
+        tls_cert,
+        tls_key,
+    };
+    let hash = password::hash_password("secret").unwrap();
+    let ctx = setup(make_config(&hash));
+    let shutdown = CancellationToken::new();
+    let server_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        s5::masque::start_masque_server(&masque, ctx, server_shutdown)
+            .await
+            .unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert.cert.der().clone()).unwrap();
+    let mut tls = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap();
+    let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+    let conn = endpoint
+        .connect(listen, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+    let (mut driver, send) = h3::client::builder()
+        .enable_extended_connect(true)
+        .build::<_, _, Bytes>(h3_quinn::Connection::new(conn))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+    });
+
+    Proxy {
+        send,
+        _endpoint: endpoint,
+        _dir: dir,
+        shutdown,
+    }
+}
+
+fn basic(username: &str, password: &str) -> String {
+    use base64::Engine;
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"))
+    )
+}
+
+async fn send(
+    proxy: &mut Proxy,
+    mut req: http::Request<()>,
+    auth: Option<&str>,
+) -> (http::StatusCode, ClientStream) {
+    if let Some(auth) = auth {
+        req.headers_mut()
+            .insert(http::header::PROXY_AUTHORIZATION, auth.parse().unwrap());
+    }
+    let mut stream = proxy.send.send_request(req).await.unwrap();
+    let resp = tokio::time::timeout(Duration::from_secs(10), stream.recv_response())
+        .await
+        .unwrap()
+        .unwrap();
+    (resp.status(), stream)
+}
+
+async fn recv_some(stream: &mut ClientStream) -> Vec<u8> {
+    let mut data = tokio::time::timeout(Duration::from_secs(10), stream.recv_data())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    data.copy_to_bytes(data.remaining()).to_vec()
+}
+
+#[tokio::test]
+async fn connect_without_credentials_asks_for_them() {
+    let mut proxy = start_proxy().await;
+    let (status, _) = send(&mut proxy, connect("127.0.0.1:9"), None).await;
+    assert_eq!(status, http::StatusCode::PROXY_AUTHENTICATION_REQUIRED);
+}
+
+#[tokio::test]
+async fn connect_with_wrong_password_is_refused() {
+    let mut proxy = start_proxy().await;
+    let auth = basic("alice", "wrong");
+    let (status, _) = send(&mut proxy, connect("127.0.0.1:9"), Some(&auth)).await;
+    assert_eq!(status, http::StatusCode::PROXY_AUTHENTICATION_REQUIRED);
+}
+
+#[tokio::test]
+async fn connect_relays_a_tcp_stream() {
+    let echo = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_addr = echo.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut sock, _) = echo.accept().await.unwrap();
+        let mut buf = [0u8; 64];
+        let n = sock.read(&mut buf).await.unwrap();
+        sock.write_all(&buf[..n]).await.unwrap();
+    });
+
+    let mut proxy = start_proxy().await;
+    let auth = basic("alice", "secret");
+    let (status, mut stream) = send(&mut proxy, connect(&echo_addr.to_string()), Some(&auth)).await;
+    assert_eq!(status, http::StatusCode::OK);
+    stream
+        .send_data(Bytes::from_static(b"hello over h3"))
+        .await
+        .unwrap();
+    assert_eq!(recv_some(&mut stream).await, b"hello over h3");
+}
+
+#[tokio::test]
+async fn connect_udp_relays_datagrams() {
+    let echo = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let echo_port = echo.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1500];
+        let (n, from) = echo.recv_from(&mut buf).await.unwrap();
+        echo.send_to(&buf[..n], from).await.unwrap();
+    });
+
+    let mut proxy = start_proxy().await;
+    let auth = basic("alice", "secret");
+    let req = connect_udp(&format!("/.well-known/masque/udp/127.0.0.1/{echo_port}/"));
+    let (status, mut stream) = send(&mut proxy, req, Some(&auth)).await;
+    assert_eq!(status, http::StatusCode::OK);
+
+    let mut capsule = Vec::new();
+    encode_datagram(b"ping", &mut capsule);
+    stream.send_data(Bytes::from(capsule)).await.unwrap();
+
+    let mut decoder = CapsuleDecoder::default();
+    let mut payloads = Vec::new();
+    while payloads.is_empty() {
+        payloads = decoder.push(&recv_some(&mut stream).await).unwrap();
+    }
+    assert_eq!(payloads, vec![b"ping".to_vec()]);
+}
+
+#[tokio::test]
+async fn connect_denied_by_the_acl_is_forbidden() {
+    let mut proxy = start_proxy().await;
+    let auth = basic("alice", "secret");
+    let (status, _) = send(&mut proxy, connect("127.0.0.1:7"), Some(&auth)).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn connect_to_blocked_port_is_forbidden() {
+    let mut proxy = start_proxy().await;
+    let auth = basic("alice", "secret");
+    // Port 25 is in the default security.blocked_ports
+    let (status, _) = send(&mut proxy, connect("127.0.0.1:25"), Some(&auth)).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+}
@@ -370,3 +370,69 @@ fn connection_duration_accumulates() {
         count_line.unwrap()
     );
 }
+
+// Test 14: user labels are opt-in; disabled aggregates everyone under "_all"
+#[test]
+fn user_labels_disabled_aggregates_under_all() {
+    let config = s5::config::types::MetricsConfig::default();
+    assert!(!config.user_labels);
+    let metrics = MetricsRegistry::from_config(&config);
+
+    metrics.record_bytes_transferred("alice", 100);
+    metrics.record_bytes_transferred("bob", 50);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+
+    assert!(!buffer.contains("alice") && !buffer.contains("bob"));
+    assert!(buffer.contains(r#"user="_all""#));
+    // Aggregation is not a cardinality cap hit
+    assert_eq!(metrics.cardinality_capped_total.get(), 0);
+}
+
+// Test 15: user labels enabled via config still honour max_metric_labels
+#[test]
+fn user_labels_enabled_respects_cap() {
+    let config = s5::config::types::MetricsConfig {
+        user_labels: true,
+        max_metric_labels: 1,
+        ..Default::default()
+    };
+    let metrics = MetricsRegistry::from_config(&config);
+
+    metrics.record_bytes_transferred("alice", 100);
+    metrics.record_bytes_transferred("bob", 50);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+
+    assert!(buffer.contains(r#"user="alice""#));
+    assert!(buffer.contains(r#"user="_other""#));
+    assert!(!buffer.contains("bob"));
+}
+
+// Test 16: per-user active connections gauge goes up and back down
+#[test]
+fn user_connection_open_close_tracks_active_gauge() {
+    let metrics = MetricsRegistry::new();
+
+    let first = metrics.record_user_connection_open("alice");
+    let second = metrics.record_user_connection_open("alice");
+    assert_eq!(first, "alice");
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    assert!(buffer.contains(r#"s5_connections_active{user="alice"} 2"#));
+
+    metrics.record_user_connection_close(&first);
+    metrics.record_user_connection_close(&second);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    assert!(buffer.contains(r#"s5_connections_active{user="alice"} 0"#));
+    let total_line = buffer
+        .lines()
+        .find(|l| l.starts_with("s5_user_connections_total") && l.contains("alice"))
+        .expect("lifetime per-user connection counter");
+    assert!(total_line.ends_with(" 2"), "got: {}", total_line);
+}
@@ -1,6 +1,8 @@
 use prometheus_client::encoding::text::encode;
+use s5::metrics::collectors::WorkerLabel;
 use s5::metrics::error_types;
 use s5::metrics::MetricsRegistry;
+use s5::proxy::forwarder::forwarding_tasks;
 
 // ---------------------------------------------------------------------------
 // record_typed_connection_duration: dual histogram recording
@@ -50,6 +52,30 @@ fn typed_connection_duration_records_in_both_histograms() {
     );
 }
 
+#[test]
+fn typed_connection_duration_carries_correlation_exemplar() {
+    let metrics = MetricsRegistry::new();
+
+    metrics.record_typed_connection_duration_cid("alice", "socks5", 0.7, "a1b2c3d4");
+
+    let mut buf = String::new();
+    encode(&mut buf, &metrics.registry).unwrap();
+
+    // OpenMetrics exemplar on the bucket the sample fell into (le=1.0)
+    let bucket = buf
+        .lines()
+        .find(|l| {
+            l.starts_with("s5_connection_duration_by_type_seconds_bucket")
+                && l.contains(r#"le="1.0""#)
+        })
+        .expect("1s bucket line");
+    assert!(
+        bucket.contains(r#"# {correlation_id="a1b2c3d4"} 0.7"#),
+        "Bucket should carry the exemplar, got: {}",
+        bucket
+    );
+}
+
 #[test]
 fn typed_connection_duration_socks5_type() {
     let metrics = MetricsRegistry::new();
@@ -823,3 +849,114 @@ fn http_request_duration_different_paths_are_separate() {
         metrics_count.unwrap()
     );
 }
+
+// ---------------------------------------------------------------------------
+// Connection-setup latency histograms
+// ---------------------------------------------------------------------------
+
+#[test]
+fn latency_histograms_are_labeled_by_outcome() {
+    use s5::metrics::outcomes;
+
+    let metrics = MetricsRegistry::new();
+    metrics.record_dns_resolution(outcomes::SUCCESS, 0.004);
+    metrics.record_tcp_connect(outcomes::TIMEOUT, 5.0);
+    metrics.record_ssh_auth("password", outcomes::FAILURE, 0.08);
+    metrics.record_channel_setup(outcomes::DENIED, 0.0002);
+
+    let mut buf = String::new();
+    encode(&mut buf, &metrics.registry).unwrap();
+
+    for (name, labels) in [
+        ("s5_dns_resolution_duration_seconds", r#"outcome="success""#),
+        ("s5_tcp_connect_duration_seconds", r#"outcome="timeout""#),
+        (
+            "s5_ssh_auth_duration_seconds",
+            r#"method="password",outcome="failure""#,
+        ),
+        ("s5_channel_setup_duration_seconds", r#"outcome="denied""#),
+    ] {
+        let count_line = format!("{name}_count{{{labels}}} 1");
+        assert!(buf.contains(&count_line), "missing {count_line} in:\n{buf}");
+    }
+}
+
+#[test]
+fn latency_outcome_from_error() {
+    use s5::metrics::outcomes;
+
+    let denied = anyhow::anyhow!("ACL denied: example.com:22");
+    assert_eq!(outcomes::from_error(&denied), outcomes::DENIED);
+
+    let timed_out = anyhow::Error::new(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        "connection timeout",
+    ));
+    assert_eq!(outcomes::from_error(&timed_out), outcomes::TIMEOUT);
+
+    let refused = anyhow::Error::new(std::io::Error::new(
+        std::io::ErrorKind::ConnectionRefused,
+        "refused",
+    ));
+    assert_eq!(outcomes::from_error(&refused), outcomes::FAILURE);
+}
+
+// ---------------------------------------------------------------------------
+// update_runtime_metrics (Tokio runtime and relay tasks)
+// ---------------------------------------------------------------------------
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn update_runtime_metrics_reports_workers() {
+    let metrics = MetricsRegistry::new();
+    let handle = tokio::runtime::Handle::current();
+
+    // Give both workers something to run
+    let tasks: Vec<_> = (0..8)
+        .map(|_| tokio::spawn(async { std::thread::sleep(std::time::Duration::from_millis(5)) }))
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    metrics.update_runtime_metrics(&handle);
+    assert_eq!(metrics.tokio_workers.get(), 2);
+    assert!(metrics.tokio_alive_tasks.get() >= 0);
+
+    let worker = WorkerLabel {
+        worker: "0".to_string(),
+    };
+    let busy = metrics
+        .tokio_worker_busy_seconds_total
+        .get_or_create(&worker)
+        .get();
+
+    // A second sample follows the runtime's totals instead of adding them again
+    metrics.update_runtime_metrics(&handle);
+    let runtime = handle.metrics();
+    let busy_again = metrics
+        .tokio_worker_busy_seconds_total
+        .get_or_create(&worker)
+        .get();
+    assert!(busy_again >= busy);
+    assert!(busy_again <= runtime.worker_total_busy_duration(0).as_secs_f64());
+    let parks = metrics.tokio_worker_park_total.get_or_create(&worker).get();
+    assert!(parks <= runtime.worker_park_count(0));
+
+    let mut buf = String::new();
+    encode(&mut buf, &metrics.registry).unwrap();
+    assert!(buf.contains("s5_tokio_workers 2"), "{buf}");
+    assert!(buf.contains("s5_tokio_global_queue_depth"));
+    assert!(buf.contains("s5_tokio_worker_busy_seconds_total{worker=\"1\"}"));
+    assert!(buf.contains("s5_tokio_worker_park_total{worker=\"0\"}"));
+    assert!(buf.contains("s5_forwarding_tasks_active"));
+    assert!(buf.contains("s5_forwarding_tasks_spawned_total"));
+}
+
+#[tokio::test]
+async fn update_runtime_metrics_mirrors_forwarding_tasks() {
+    let metrics = MetricsRegistry::new();
+    let before = forwarding_tasks();
+    metrics.update_runtime_metrics(&tokio::runtime::Handle::current());
+    assert!(metrics.forwarding_tasks_spawned_total.get() >= before.spawned);
+    assert!(metrics.forwarding_tasks_spawned_total.get() <= forwarding_tasks().spawned);
+}
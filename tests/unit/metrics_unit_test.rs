@@ -629,3 +629,77 @@ fn record_quota_exceeded_creates_metric() {
         "Should contain connections type"
     );
 }
+
+// ---------------------------------------------------------------------------
+// JSON snapshot (GET /api/metrics)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn json_snapshot_has_every_family() {
+    let metrics = MetricsRegistry::new();
+    metrics.record_user_connection_open("alice");
+    metrics.record_tcp_connect("success", 0.02);
+
+    let mut buf = String::new();
+    encode(&mut buf, &metrics.registry).unwrap();
+    let snapshot = s5::metrics::json::snapshot(&metrics).unwrap();
+    assert_eq!(
+        snapshot.metrics.len(),
+        buf.lines().filter(|l| l.starts_with("# TYPE ")).count()
+    );
+
+    let family = |name: &str| {
+        snapshot
+            .metrics
+            .iter()
+            .find(|f| f.name == name)
+            .unwrap_or_else(|| panic!("missing family {}", name))
+    };
+    let active = family("s5_connections_active");
+    assert_eq!(active.kind, "gauge");
+    assert!(active.help.starts_with("Currently active connections"));
+    assert_eq!(active.samples.len(), 1);
+    assert_eq!(active.samples[0].labels["user"], "alice");
+    assert_eq!(active.samples[0].value, 1.0);
+
+    let connect = family("s5_tcp_connect_duration_seconds");
+    assert_eq!(connect.kind, "histogram");
+    let inf_bucket = connect
+        .samples
+        .iter()
+        .find(|s| s.name.ends_with("_bucket") && s.labels["le"] == "+Inf")
+        .unwrap();
+    assert_eq!(inf_bucket.value, 1.0);
+    assert_eq!(inf_bucket.labels["outcome"], "success");
+    let count = connect
+        .samples
+        .iter()
+        .find(|s| s.name.ends_with("_count"))
+        .unwrap();
+    assert_eq!(count.value, 1.0);
+}
+
+#[test]
+fn json_snapshot_parses_escapes_and_skips_exemplars() {
+    let text = "# HELP s5_x Line one\\nline two.\n\
+                # TYPE s5_x histogram\n\
+                # UNIT s5_x seconds\n\
+                s5_x_bucket{path=\"/a,\\\"b\\\"\",le=\"+Inf\"} 3 # {correlation_id=\"c1\"} 0.5\n\
+                s5_x_count 3\n\
+                not a sample\n\
+                # EOF\n";
+    let families = s5::metrics::json::parse_openmetrics(text);
+    assert_eq!(families.len(), 1);
+    let f = &families[0];
+    assert_eq!(f.help, "Line one\nline two.");
+    assert_eq!(f.unit.as_deref(), Some("seconds"));
+    assert_eq!(f.samples.len(), 2);
+    assert_eq!(f.samples[0].labels["path"], "/a,\"b\"");
+    assert_eq!(f.samples[0].labels["le"], "+Inf");
+    assert_eq!(f.samples[0].value, 3.0);
+    assert!(!f.samples[0].labels.contains_key("correlation_id"));
+
+    let json = serde_json::to_value(f).unwrap();
+    assert_eq!(json["type"], "histogram");
+    assert_eq!(json["samples"][1]["name"], "s5_x_count");
+}
@@ -601,6 +601,7 @@ fn make_shell_context(start: Instant) -> ShellContext {
         proxy_engine: None,
         quota_tracker: None,
         quota_config: None,
+        env: Vec::new(),
     }
 }
 
@@ -0,0 +1,553 @@
+use s5::audit::events::AuditEvent;
+use s5::config::parse_config;
+use s5::config::redact::redact_config;
+use s5::config::types::{
+    NotificationEvent, NotificationRule, NotificationSinkConfig, NotificationSinkKind,
+    NotificationsConfig, SmtpTlsMode,
+};
+use s5::notifications::sinks::Sink;
+use s5::notifications::smtp::{format_message, SmtpConnection};
+use s5::notifications::{
+    CountryTracker, Digest, FailureWindow, Notification, NotificationBatcher, Notifier, Route,
+};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn config_with(notifications: &str) -> String {
+    format!(
+        r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+server_id = "SSH-2.0-edge-1"
+
+{notifications}
+
+[[users]]
+username = "alice"
+password_hash = "{FAKE_HASH}"
+"##
+    )
+}
+
+const SMTP: &str = r#"
+[notifications.smtp]
+host = "mail.example.com"
+from = "s5@example.com"
+to = ["ops@example.com"]
+"#;
+
+fn rule(event: NotificationEvent, users: &[&str], to: &[&str]) -> NotificationRule {
+    NotificationRule {
+        event,
+        users: users.iter().map(|s| s.to_string()).collect(),
+        to: to.iter().map(|s| s.to_string()).collect(),
+        sinks: Vec::new(),
+    }
+}
+
+fn email(to: &[&str]) -> Route {
+    Route::Email(to.iter().map(|s| s.to_string()).collect())
+}
+
+fn batcher(rules: Vec<NotificationRule>) -> NotificationBatcher {
+    let config = parse_config(&config_with(SMTP)).unwrap();
+    let notifications = NotificationsConfig {
+        rules,
+        dedup_window_secs: 60,
+        max_events_per_email: 2,
+        ..config.notifications
+    };
+    NotificationBatcher::new(notifications, "edge-1")
+}
+
+fn event(kind: NotificationEvent, user: Option<&str>, key: &str) -> Notification {
+    Notification {
+        event: kind,
+        username: user.map(str::to_string),
+        key: key.to_string(),
+        summary: format!("summary for {}", key),
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+#[test]
+fn notification_config_defaults_and_validation() {
+    let config = parse_config(&config_with("")).unwrap();
+    assert!(!config.notifications.is_enabled());
+    assert_eq!(config.notifications.batch_window_secs, 60);
+    assert_eq!(config.notifications.dedup_window_secs, 3600);
+
+    let config = parse_config(&config_with(&format!(
+        "[notifications]\n[[notifications.rules]]\nevent = \"ban\"\n{SMTP}"
+    )))
+    .unwrap();
+    let smtp = config.notifications.smtp.as_ref().unwrap();
+    assert_eq!(smtp.port, 587);
+    assert_eq!(smtp.tls, SmtpTlsMode::Starttls);
+    assert!(config.notifications.is_enabled());
+
+    // Rules without an SMTP server
+    assert!(parse_config(&config_with(
+        "[[notifications.rules]]\nevent = \"quota_exceeded\""
+    ))
+    .is_err());
+    // No recipients anywhere
+    assert!(parse_config(&config_with(
+        "[notifications.smtp]\nhost = \"mail.example.com\"\nfrom = \"s5@example.com\"\n\
+         [[notifications.rules]]\nevent = \"ban\""
+    ))
+    .is_err());
+    // Header injection through an address
+    let injected = SMTP.replace("s5@example.com", "s5@example.com\\r\\nBcc: x@y");
+    assert!(parse_config(&config_with(&injected)).is_err());
+    // new_country needs a GeoIP database
+    assert!(parse_config(&config_with(&format!(
+        "[[notifications.rules]]\nevent = \"new_country\"\n{SMTP}"
+    )))
+    .is_err());
+    // Username without password
+    assert!(parse_config(&config_with(&format!("{SMTP}username = \"relay\"\n"))).is_err());
+}
+
+#[test]
+fn smtp_password_is_redacted() {
+    let config = parse_config(&config_with(&format!(
+        "{SMTP}username = \"relay\"\npassword = \"hunter2\"\n"
+    )))
+    .unwrap();
+    let redacted = redact_config(&config);
+    let smtp = redacted.notifications.smtp.unwrap();
+    assert_eq!(smtp.password.as_deref(), Some("***"));
+    assert!(!format!("{:?}", config.notifications.smtp).contains("hunter2"));
+}
+
+#[test]
+fn batcher_deduplicates_within_window() {
+    let b = batcher(vec![rule(NotificationEvent::Ban, &[], &[])]);
+    let t0 = Instant::now();
+    assert!(b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), t0));
+    assert!(!b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), t0));
+    assert!(b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.2"), t0));
+
+    let digests = b.flush(t0);
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests[0].route, email(&["ops@example.com"]));
+    assert_eq!(digests[0].suppressed, 1);
+    assert_eq!(digests[0].subject, "[s5 edge-1] 2 security events");
+    assert!(digests[0].body.contains("1 repeated event(s) suppressed"));
+    assert!(b.flush(t0).is_empty());
+
+    // Still inside the window after a flush, then allowed again
+    let later = t0 + Duration::from_secs(30);
+    assert!(!b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), later));
+    let after = t0 + Duration::from_secs(61);
+    assert!(b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), after));
+}
+
+#[test]
+fn batcher_routes_by_rule_and_caps_listed_events() {
+    let b = batcher(vec![
+        rule(
+            NotificationEvent::QuotaExceeded,
+            &["alice"],
+            &["alice-owner@example.com"],
+        ),
+        rule(NotificationEvent::QuotaExceeded, &[], &[]),
+    ]);
+    let now = Instant::now();
+    // No rule for bans
+    assert!(!b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), now));
+    assert!(b.push(
+        event(NotificationEvent::QuotaExceeded, Some("alice"), "q:alice"),
+        now
+    ));
+    for user in ["bob", "carol", "dave"] {
+        let key = format!("q:{}", user);
+        assert!(b.push(
+            event(NotificationEvent::QuotaExceeded, Some(user), &key),
+            now
+        ));
+    }
+
+    let digests = b.flush(now);
+    assert_eq!(digests.len(), 2);
+    let alice_route = email(&["alice-owner@example.com", "ops@example.com"]);
+    let alice = digests.iter().find(|d| d.route == alice_route).unwrap();
+    assert_eq!(alice.subject, "[s5 edge-1] summary for q:alice");
+    let ops_route = email(&["ops@example.com"]);
+    let others = digests.iter().find(|d| d.route == ops_route).unwrap();
+    assert_eq!(others.events.len(), 3);
+    assert!(others.body.contains("q:bob"));
+    assert!(others.body.contains("q:carol"));
+    assert!(!others.body.contains("q:dave"));
+    assert!(others.body.contains("... and 1 more"));
+}
+
+#[test]
+fn country_tracker_skips_first_login() {
+    let tracker = CountryTracker::default();
+    assert_eq!(tracker.record("alice", "FR"), None);
+    assert_eq!(tracker.record("alice", "FR"), None);
+    assert_eq!(tracker.record("alice", "US"), Some(vec!["FR".to_string()]));
+    assert_eq!(
+        tracker.record("alice", "DE"),
+        Some(vec!["FR".to_string(), "US".to_string()])
+    );
+    assert_eq!(tracker.record("alice", "US"), None);
+    assert_eq!(tracker.record("bob", "US"), None);
+}
+
+#[test]
+fn notifier_turns_audit_events_into_digests() {
+    let config = parse_config(&config_with(&format!(
+        "[[notifications.rules]]\nevent = \"ban\"\n\
+         [[notifications.rules]]\nevent = \"quota_exceeded\"\n{SMTP}"
+    )))
+    .unwrap();
+    let notifier = Notifier::new(&config);
+    let ip = "203.0.113.7".parse().unwrap();
+    notifier.observe(&AuditEvent::ban_created(&ip, 300));
+    notifier.observe(&AuditEvent::ban_created(&ip, 300));
+    notifier.observe(&AuditEvent::quota_exceeded(
+        "alice",
+        "daily_bandwidth",
+        2048,
+        1024,
+    ));
+    notifier.observe(&AuditEvent::ban_expired(&ip));
+
+    let digests = notifier.flush();
+    assert_eq!(digests.len(), 1);
+    let body = &digests[0].body;
+    assert!(body.contains("IP 203.0.113.7 banned for 300s"));
+    assert!(body.contains("User alice exhausted the daily_bandwidth quota (2048/1024)"));
+    assert!(body.contains("1 repeated event(s) suppressed"));
+}
+
+const SINKS: &str = r#"
+[[notifications.sinks]]
+name = "oncall"
+kind = "slack"
+url = "https://hooks.slack.com/services/T000/B000/XXXX"
+
+[[notifications.sinks]]
+name = "chat"
+kind = "matrix"
+url = "https://matrix.example.org"
+room_id = "!room:example.org"
+access_token = "syt_token"
+"#;
+
+fn sink(kind: NotificationSinkKind, url: &str) -> NotificationSinkConfig {
+    NotificationSinkConfig {
+        name: "test".to_string(),
+        kind,
+        url: url.to_string(),
+        room_id: None,
+        access_token: None,
+        secret: None,
+        allow_private_ips: false,
+    }
+}
+
+fn digest(subject: &str, body: &str) -> Digest {
+    Digest {
+        route: Route::Sink("test".to_string()),
+        subject: subject.to_string(),
+        body: body.to_string(),
+        events: vec![event(NotificationEvent::Ban, None, "ban:192.0.2.1")],
+        suppressed: 2,
+    }
+}
+
+#[test]
+fn sink_config_validation() {
+    // Sinks alone are enough, no SMTP server needed
+    let config = parse_config(&config_with(&format!(
+        "{SINKS}[[notifications.rules]]\nevent = \"ban\"\nsinks = [\"oncall\", \"chat\"]\n"
+    )))
+    .unwrap();
+    assert!(config.notifications.is_enabled());
+    assert_eq!(
+        config.notifications.rules[0].sink_names(),
+        vec!["oncall", "chat"]
+    );
+
+    // Unknown sink
+    assert!(parse_config(&config_with(&format!(
+        "{SINKS}[[notifications.rules]]\nevent = \"ban\"\nsinks = [\"pager\"]\n"
+    )))
+    .is_err());
+    // Default route is email, which needs SMTP
+    assert!(parse_config(&config_with(&format!(
+        "{SINKS}[[notifications.rules]]\nevent = \"ban\"\n"
+    )))
+    .is_err());
+    // Matrix without a room
+    let no_room = SINKS.replace("room_id = \"!room:example.org\"", "");
+    assert!(parse_config(&config_with(&no_room)).is_err());
+    // Reserved name
+    let reserved = SINKS.replace("\"oncall\"", "\"email\"");
+    assert!(parse_config(&config_with(&reserved)).is_err());
+    // Loopback destination
+    let local = SINKS.replace("https://matrix.example.org", "http://localhost:8008");
+    assert!(parse_config(&config_with(&local)).is_err());
+    // Secrets are for generic webhooks only
+    assert!(parse_config(&config_with(&format!("{SINKS}secret = \"s\"\n"))).is_err());
+}
+
+#[test]
+fn sink_secrets_are_redacted() {
+    let config = parse_config(&config_with(SINKS)).unwrap();
+    let redacted = redact_config(&config);
+    let sinks = &redacted.notifications.sinks;
+    assert_eq!(sinks[0].url, "https://hooks.slack.com/***");
+    assert_eq!(sinks[1].url, "https://matrix.example.org");
+    assert_eq!(sinks[1].access_token.as_deref(), Some("***"));
+    let debug = format!("{:?}", config.notifications.sinks);
+    assert!(!debug.contains("XXXX"));
+    assert!(!debug.contains("syt_token"));
+}
+
+#[test]
+fn batcher_routes_to_sinks_and_email() {
+    let mut both = rule(NotificationEvent::Ban, &[], &[]);
+    both.sinks = vec!["email".to_string(), "oncall".to_string()];
+    let mut chat_only = rule(NotificationEvent::QuotaExceeded, &[], &[]);
+    chat_only.sinks = vec!["oncall".to_string()];
+    let b = batcher(vec![both, chat_only]);
+    let now = Instant::now();
+    assert!(b.push(event(NotificationEvent::Ban, None, "ban:192.0.2.1"), now));
+    assert!(b.push(
+        event(NotificationEvent::QuotaExceeded, Some("bob"), "q:bob"),
+        now
+    ));
+
+    let digests = b.flush(now);
+    assert_eq!(digests.len(), 2);
+    let mail = digests
+        .iter()
+        .find(|d| d.route == email(&["ops@example.com"]))
+        .unwrap();
+    assert_eq!(mail.events.len(), 1);
+    let oncall = digests
+        .iter()
+        .find(|d| d.route == Route::Sink("oncall".to_string()))
+        .unwrap();
+    assert_eq!(oncall.events.len(), 2);
+}
+
+#[test]
+fn failure_window_slides() {
+    let window = FailureWindow::new(Duration::from_secs(10));
+    let t0 = Instant::now();
+    assert_eq!(window.record(t0), 1);
+    assert_eq!(window.record(t0 + Duration::from_secs(5)), 2);
+    assert_eq!(window.record(t0 + Duration::from_secs(9)), 3);
+    // The first failure has aged out
+    assert_eq!(window.record(t0 + Duration::from_secs(10)), 3);
+    assert_eq!(window.record(t0 + Duration::from_secs(30)), 1);
+}
+
+#[test]
+fn notifier_reports_auth_failure_spikes() {
+    let config = parse_config(&config_with(&format!(
+        "[notifications]\nauth_failure_threshold = 3\n\
+         [[notifications.rules]]\nevent = \"auth_failure_spike\"\n{SMTP}"
+    )))
+    .unwrap();
+    let notifier = Notifier::new(&config);
+    let source = "198.51.100.9:40000".parse().unwrap();
+    for _ in 0..2 {
+        notifier.observe(&AuditEvent::auth_failure("root", &source, "password"));
+    }
+    assert!(notifier.flush().is_empty());
+
+    for _ in 0..2 {
+        notifier.observe(&AuditEvent::auth_failure("admin", &source, "password"));
+    }
+    let digests = notifier.flush();
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests[0].events.len(), 1);
+    assert_eq!(digests[0].suppressed, 1);
+    assert!(digests[0]
+        .body
+        .contains("3 authentication failures in 60s (latest: admin from 198.51.100.9)"));
+}
+
+#[test]
+fn sink_requests_match_each_service() {
+    let slack = Sink::new(sink(
+        NotificationSinkKind::Slack,
+        "https://hooks.slack.com/x",
+    ));
+    let request = slack
+        .request(&digest("Ban <@here>", "IP banned\n"))
+        .unwrap();
+    assert_eq!(request.method, reqwest::Method::POST);
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["text"], "*Ban &lt;@here&gt;*\n```IP banned```");
+
+    let discord = Sink::new(sink(NotificationSinkKind::Discord, "https://discord.com/x"));
+    let request = discord.request(&digest("Ban", &"x".repeat(5000))).unwrap();
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    let content = body["content"].as_str().unwrap();
+    assert!(content.starts_with("**Ban**\n```\n"));
+    assert!(content.ends_with("\n```"));
+    assert!(content.chars().count() <= 2000);
+    assert_eq!(body["allowed_mentions"]["parse"], serde_json::json!([]));
+
+    let mut matrix = sink(NotificationSinkKind::Matrix, "https://matrix.example.org/");
+    matrix.room_id = Some("!room:example.org".to_string());
+    matrix.access_token = Some("syt_token".to_string());
+    let request = Sink::new(matrix).request(&digest("Ban", "body")).unwrap();
+    assert_eq!(request.method, reqwest::Method::PUT);
+    assert!(request.url.starts_with(
+        "https://matrix.example.org/_matrix/client/v3/rooms/%21room%3Aexample%2Eorg\
+         /send/m.room.message/"
+    ));
+    assert!(request
+        .headers
+        .contains(&("Authorization", "Bearer syt_token".to_string())));
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["msgtype"], "m.text");
+    assert_eq!(body["formatted_body"], "<b>Ban</b><pre>body</pre>");
+
+    let mut webhook = sink(
+        NotificationSinkKind::Webhook,
+        "https://hooks.example.com/s5",
+    );
+    webhook.secret = Some("s3cret".to_string());
+    let request = Sink::new(webhook).request(&digest("Ban", "body")).unwrap();
+    let (_, signature) = request
+        .headers
+        .iter()
+        .find(|(name, _)| *name == "X-Signature-256")
+        .unwrap();
+    assert!(signature.starts_with("sha256="));
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["source"], "s5");
+    assert_eq!(body["suppressed"], 2);
+    assert_eq!(body["events"][0]["event"], "ban");
+    assert_eq!(body["events"][0]["key"], "ban:192.0.2.1");
+}
+
+#[test]
+fn message_is_encoded_for_smtp() {
+    let config = parse_config(&config_with(SMTP)).unwrap();
+    let smtp = config.notifications.smtp.unwrap();
+    let digest = Digest {
+        route: email(&["ops@example.com"]),
+        subject: "Ban for José\r\nBcc: evil@example.com".to_string(),
+        body: "line one\n.dot line\n".to_string(),
+        events: Vec::new(),
+        suppressed: 0,
+    };
+    let to = vec!["ops@example.com".to_string()];
+    let message = format_message(&smtp, &to, &digest, chrono::Utc::now());
+    assert!(message.contains("From: <s5@example.com>\r\n"));
+    assert!(message.contains("To: <ops@example.com>\r\n"));
+    assert!(message.contains("Subject: =?utf-8?B?"));
+    assert!(!message.contains("\r\nBcc:"));
+    assert!(message.ends_with("\r\n\r\nline one\r\n.dot line\r\n"));
+}
+
+#[tokio::test]
+async fn smtp_session_authenticates_and_dot_stuffs() {
+    let config = parse_config(&config_with(&format!(
+        "{SMTP}tls = \"none\"\nusername = \"relay\"\npassword = \"secret\"\n"
+    )))
+    .unwrap();
+    let smtp = config.notifications.smtp.unwrap();
+    let (client, server) = tokio::io::duplex(8192);
+
+    let server = tokio::spawn(async move {
+        let mut server = BufReader::new(server);
+        let mut received = Vec::new();
+        server
+            .get_mut()
+            .write_all(b"220 mail ESMTP\r\n")
+            .await
+            .unwrap();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if server.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            let reply: &[u8] = if in_data {
+                if line == "." {
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else {
+                    received.push(line);
+                    continue;
+                }
+            } else if line.starts_with("EHLO") {
+                b"250-mail\r\n250 AUTH PLAIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go\r\n"
+            } else if line == "QUIT" {
+                received.push(line);
+                server.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            if !in_data || line == "DATA" {
+                received.push(line);
+            }
+            server.get_mut().write_all(reply).await.unwrap();
+        }
+        received
+    });
+
+    let mut conn = SmtpConnection::open(client, Duration::from_secs(5))
+        .await
+        .unwrap();
+    let to = vec!["ops@example.com".to_string(), "sec@example.com".to_string()];
+    conn.deliver(&smtp, &to, "Subject: x\r\n\r\n.hidden\r\nok\r\n")
+        .await
+        .unwrap();
+    drop(conn);
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0], "EHLO localhost");
+    // base64("\0relay\0secret")
+    assert_eq!(received[1], "AUTH PLAIN AHJlbGF5AHNlY3JldA==");
+    assert_eq!(received[2], "MAIL FROM:<s5@example.com>");
+    assert_eq!(received[3], "RCPT TO:<ops@example.com>");
+    assert_eq!(received[4], "RCPT TO:<sec@example.com>");
+    assert_eq!(received[5], "DATA");
+    assert!(received.contains(&"..hidden".to_string()));
+    assert_eq!(received.last().unwrap(), "QUIT");
+}
+
+#[tokio::test]
+async fn smtp_rejection_is_an_error() {
+    let config = parse_config(&config_with(&format!("{SMTP}tls = \"none\"\n"))).unwrap();
+    let smtp = config.notifications.smtp.unwrap();
+    let (client, mut server) = tokio::io::duplex(1024);
+    tokio::spawn(async move {
+        server
+            .write_all(b"220 hi\r\n250 ok\r\n550 relay denied\r\n")
+            .await
+            .unwrap();
+        // Keep the stream open until the client gives up
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+    let mut conn = SmtpConnection::open(client, Duration::from_secs(5))
+        .await
+        .unwrap();
+    let err = conn
+        .deliver(&smtp, &["ops@example.com".to_string()], "x\r\n")
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("550"));
+}
@@ -0,0 +1,517 @@
+use crate::api_test::{build_test_app_state, start_api_server_with_state};
+use s5::api::openapi::{spec, Auth, Endpoint, ENDPOINTS};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+const API_SRC: &str = include_str!("../../src/api/mod.rs");
+
+/// Routes registered in `start_api_server`, converted to OpenAPI path syntax.
+fn router_routes() -> BTreeSet<String> {
+    let start = API_SRC
+        .find("pub async fn start_api_server")
+        .expect("start_api_server present");
+    routes_in(&API_SRC[start..])
+}
+
+/// Routes of one `let <name> = Router::new()...;` group in `start_api_server`.
+fn group_routes(name: &str) -> BTreeSet<String> {
+    let start = API_SRC
+        .find(&format!("let {} = Router::new()", name))
+        .unwrap_or_else(|| panic!("router group {} present", name));
+    let end = start + API_SRC[start..].find(";\n").unwrap();
+    routes_in(&API_SRC[start..end])
+}
+
+fn routes_in(src: &str) -> BTreeSet<String> {
+    let mut routes = BTreeSet::new();
+    let mut rest = src;
+    while let Some(idx) = rest.find(".route(") {
+        rest = &rest[idx + ".route(".len()..];
+        let open = rest.find('"').unwrap();
+        let close = open + 1 + rest[open + 1..].find('"').unwrap();
+        let path = &rest[open + 1..close];
+        let converted: Vec<String> = path
+            .split('/')
+            .map(|seg| match seg.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => seg.to_string(),
+            })
+            .collect();
+        routes.insert(converted.join("/"));
+    }
+    routes
+}
+
+#[test]
+fn every_router_path_is_documented() {
+    let documented: BTreeSet<String> = ENDPOINTS.iter().map(|e| e.path.to_string()).collect();
+    for route in router_routes() {
+        assert!(
+            documented.contains(&route),
+            "route {} missing from openapi::ENDPOINTS",
+            route
+        );
+    }
+}
+
+#[test]
+fn every_documented_path_is_routed() {
+    let routes = router_routes();
+    for e in ENDPOINTS {
+        assert!(
+            routes.contains(e.path),
+            "{} is documented but not routed",
+            e.path
+        );
+    }
+}
+
+#[test]
+fn spec_has_openapi_3_header() {
+    let doc = spec();
+    assert_eq!(doc["openapi"], "3.0.3");
+    assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn schema_references_resolve() {
+    let doc = spec();
+    let text = doc.to_string();
+    let schemas = doc["components"]["schemas"].as_object().unwrap();
+    for part in text.split("#/components/schemas/").skip(1) {
+        let name: String = part.chars().take_while(|c| *c != '"').collect();
+        assert!(schemas.contains_key(&name), "dangling $ref to {}", name);
+    }
+}
+
+#[test]
+fn path_parameters_are_declared() {
+    let doc = spec();
+    let op = &doc["paths"]["/api/quotas/{username}/reset"]["post"];
+    assert_eq!(op["parameters"][0]["name"], "username");
+    assert_eq!(op["parameters"][0]["in"], "path");
+    assert_eq!(op["parameters"][0]["required"], true);
+}
+
+#[test]
+fn probes_are_unauthenticated_and_admin_routes_are_not() {
+    let doc = spec();
+    assert_eq!(
+        doc["paths"]["/readyz"]["get"]["security"],
+        serde_json::json!([])
+    );
+    assert_eq!(
+        doc["paths"]["/api/openapi.json"]["get"]["security"],
+        serde_json::json!([])
+    );
+    assert_eq!(
+        doc["paths"]["/api/status"]["get"]["security"][0]["adminToken"],
+        serde_json::json!([])
+    );
+    assert_eq!(
+        doc["paths"]["/api/self/password"]["post"]["security"][0]["userToken"],
+        serde_json::json!([])
+    );
+    assert!(ENDPOINTS
+        .iter()
+        .filter(|e| e.path.starts_with("/api/"))
+        .filter(|e| {
+            ![
+                "/api/openapi.json",
+                "/api/csrf-token",
+                "/api/login",
+                "/api/logout",
+                "/api/self/login",
+                "/api/self/logout",
+                "/api/invite",
+                "/api/invite/accept",
+            ]
+            .contains(&e.path)
+        })
+        .all(|e| e.auth != Auth::None));
+}
+
+#[test]
+fn request_bodies_reference_schemas() {
+    let doc = spec();
+    let body = &doc["paths"]["/api/broadcast"]["post"]["requestBody"];
+    assert_eq!(
+        body["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/BroadcastRequest"
+    );
+}
+
+#[test]
+fn documented_roles_match_router_groups() {
+    for (group, auth) in [("operator", Auth::Operator), ("admin", Auth::Admin)] {
+        let routed = group_routes(group);
+        let documented: BTreeSet<String> = ENDPOINTS
+            .iter()
+            .filter(|e| e.auth == auth)
+            .map(|e| e.path.to_string())
+            .collect();
+        assert_eq!(routed, documented, "{} group out of sync", group);
+    }
+}
+
+#[test]
+fn role_gated_operations_document_min_role() {
+    let doc = spec();
+    assert_eq!(doc["paths"]["/api/users"]["get"]["x-s5-min-role"], "viewer");
+    assert_eq!(
+        doc["paths"]["/api/kick/{username}"]["post"]["x-s5-min-role"],
+        "operator"
+    );
+    assert_eq!(
+        doc["paths"]["/api/reload"]["post"]["x-s5-min-role"],
+        "admin"
+    );
+    assert!(doc["paths"]["/api/reload"]["post"]["responses"]["403"].is_object());
+    assert!(doc["paths"]["/api/users"]["get"]["responses"]["403"].is_null());
+    assert_eq!(
+        doc["paths"]["/api/users"]["get"]["security"][1]["sessionCookie"],
+        serde_json::json!([])
+    );
+}
+
+/// Why `value` does not match `schema` (empty if it does). Checks types,
+/// required and undocumented properties, enums and nullability: enough to
+/// catch a serde type drifting away from its documented schema.
+fn mismatches(doc: &Value, schema: &Value, value: &Value, at: &str) -> Vec<String> {
+    if let Some(name) = schema["$ref"].as_str() {
+        let name = name.trim_start_matches("#/components/schemas/");
+        return mismatches(doc, &doc["components"]["schemas"][name], value, at);
+    }
+    if let Some(parts) = schema["allOf"].as_array() {
+        return parts
+            .iter()
+            .flat_map(|part| mismatches(doc, part, value, at))
+            .collect();
+    }
+    if value.is_null() {
+        return match schema["nullable"].as_bool() == Some(true) || schema.get("type").is_none() {
+            true => vec![],
+            false => vec![format!("{at}: null, schema not nullable")],
+        };
+    }
+    let kind = schema["type"].as_str().unwrap_or_default();
+    let ok = match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" if schema["minimum"] == 0 => value.is_u64(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        _ => true,
+    };
+    if !ok {
+        return vec![format!("{at}: {value} is not {kind}")];
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            return vec![format!("{at}: {value} not in {allowed:?}")];
+        }
+    }
+    let mut found = Vec::new();
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                found.extend(mismatches(
+                    doc,
+                    &schema["items"],
+                    item,
+                    &format!("{at}[{i}]"),
+                ));
+            }
+        }
+        Value::Object(fields) => {
+            for name in schema["required"].as_array().into_iter().flatten() {
+                if !fields.contains_key(name.as_str().unwrap()) {
+                    found.push(format!("{at}: missing required {name}"));
+                }
+            }
+            let props = schema["properties"].as_object();
+            for (name, field) in fields {
+                let at = format!("{at}.{name}");
+                match (
+                    props.and_then(|p| p.get(name)),
+                    &schema["additionalProperties"],
+                ) {
+                    (Some(prop), _) => found.extend(mismatches(doc, prop, field, &at)),
+                    (None, extra) if extra.is_object() => {
+                        found.extend(mismatches(doc, extra, field, &at))
+                    }
+                    // A free-form object documents no fields
+                    (None, _) if props.is_none() => {}
+                    (None, _) => found.push(format!("{at}: undocumented field")),
+                }
+            }
+        }
+        _ => {}
+    }
+    found
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+/// A value of the schema `name` with every documented property set, to feed
+/// the request types. Properties take their value from `values` by
+/// `Schema.property` or `property` when listed there.
+fn sample(doc: &Value, name: &str, values: &HashMap<&str, Value>) -> Value {
+    fn of(doc: &Value, schema: &Value, values: &HashMap<&str, Value>) -> Value {
+        if let Some(name) = schema["$ref"].as_str() {
+            let name = name.trim_start_matches("#/components/schemas/");
+            return sample(doc, name, values);
+        }
+        if let Some(first) = schema["enum"].as_array().and_then(|e| e.first()) {
+            return first.clone();
+        }
+        match schema["type"].as_str() {
+            Some("array") => json!([of(doc, &schema["items"], values)]),
+            Some("string") if schema["format"] == "date-time" => json!("2030-01-01T00:00:00Z"),
+            Some("string") => json!("sample"),
+            Some("integer") | Some("number") => json!(60),
+            Some("boolean") => json!(true),
+            _ => json!({}),
+        }
+    }
+    let schema = &doc["components"]["schemas"][name];
+    let Some(props) = schema["properties"].as_object() else {
+        return of(doc, schema, values);
+    };
+    Value::Object(
+        props
+            .iter()
+            .map(|(prop, prop_schema)| {
+                let value = values
+                    .get(format!("{name}.{prop}").as_str())
+                    .or_else(|| values.get(prop.as_str()))
+                    .cloned()
+                    .unwrap_or_else(|| of(doc, prop_schema, values));
+                (prop.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+/// The `{id}` of `path`'s resource family, from the fixtures and from what
+/// earlier calls returned.
+fn fill_path(path: &str, ids: &HashMap<&str, String>) -> String {
+    let family = [
+        "invitations",
+        "self/tokens",
+        "key-enrollments",
+        "config",
+        "sessions",
+    ]
+    .into_iter()
+    .find(|f| path.starts_with(&format!("/api/{f}/")))
+    .unwrap_or_default();
+    path.replace("{username}", "alice")
+        .replace("{name}", "staff")
+        .replace("{ip}", "198.51.100.2")
+        .replace("{id}", ids.get(family).map_or("none", String::as_str))
+}
+
+/// Schemas no call below returns, and why.
+const NOT_EXERCISED: &[(&str, &str)] = &[
+    ("ClusterInfo", "needs a cluster backend"),
+    (
+        "LogLevel",
+        "the log filter is process-wide (log_filter_test)",
+    ),
+];
+
+/// Every endpoint with a documented body is called against a server with
+/// every feature on: the request is a sample of its schema, which the
+/// handler's serde type must accept, and a successful answer must match the
+/// response schema.
+#[tokio::test]
+async fn schemas_match_the_serde_types() {
+    let dir = tempfile::tempdir().unwrap();
+    let password = s5::auth::password::hash_password("testuser").unwrap();
+    let token_hash = s5::auth::password::hash_password("lead-token").unwrap();
+    let content = format!(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\nhost_key_path = \"{dir}/host_key\"\n\n\
+         [security]\nban_enabled = true\nban_whitelist = []\nlock_after_failures = 1\n\n\
+         [invitations]\nenabled = true\n\n\
+         [key_enrollment]\nenabled = true\n\n\
+         [logging.capture]\nenabled = true\ndirectory = \"{dir}/captures\"\n\n\
+         [logging.flows]\nenabled = true\npath = \"{dir}/flows.db\"\n\n\
+         [[api.accounts]]\nusername = \"carol\"\npassword_hash = \"{password}\"\nrole = \"admin\"\n\n\
+         [[tenants]]\nname = \"acme\"\n\n\
+         [[groups]]\nname = \"staff\"\nmanagers = [\"lead\"]\n\n\
+         [[users]]\nusername = \"lead\"\npassword_hash = \"{password}\"\ngroup = \"staff\"\n\
+         api_token_hash = \"{token_hash}\"\n\
+         api_tokens = [{{ id = \"t1\", sha256 = \"{read}\" }}]\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"x\"\ngroup = \"staff\"\n\n\
+         [[users]]\nusername = \"carol\"\npassword_hash = \"{password}\"\n",
+        dir = dir.path().display(),
+        read = s5::auth::personal_token::digest("s5r_lead-read"),
+    );
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, &content).unwrap();
+    let config = Arc::new(s5::config::parse_config(&content).unwrap());
+    let audit = Arc::new(s5::audit::AuditLogger::new(None, 0, 0, None));
+
+    let token = "test-openapi-schema-token";
+    let mut state = build_test_app_state(token);
+    state.auth_service = Arc::new(tokio::sync::RwLock::new(
+        s5::auth::AuthService::new(&config).unwrap(),
+    ));
+    state.security = Arc::new(tokio::sync::RwLock::new(
+        s5::security::SecurityManager::new(&config),
+    ));
+    state.proxy_engine = Arc::new(s5::proxy::ProxyEngine::new(config.clone(), audit.clone()));
+    state.quota_tracker = Some(Arc::new(s5::quota::QuotaTracker::new(&config.limits)));
+    state.audit = Some(audit);
+    state.flow_log = Some(s5::flows::FlowLog::start(&config.logging.flows).unwrap());
+    state.host_keys = Some(Arc::new(
+        s5::ssh::host_keys::HostKeyRing::load(&config.server).unwrap(),
+    ));
+    let history = Arc::new(s5::config::history::ConfigHistory::new(10));
+    let snapshot = history.record(&content, "startup", 3).unwrap();
+    state.config_history = Some(history);
+    state.config_path = Some(path);
+    state.tenants = Arc::new(config.tenants.clone());
+    state.dashboard_accounts = Arc::new(config.api.accounts.clone());
+
+    // Fixtures the calls act on
+    let session =
+        state
+            .proxy_engine
+            .register_session("alice", "example.com", 443, "192.0.2.1", "ssh");
+    let private =
+        russh::keys::PrivateKey::random(&mut rand::rngs::OsRng, russh::keys::Algorithm::Ed25519)
+            .unwrap();
+    let key = russh::keys::PublicKey::from(&private);
+    let enrollment = {
+        let security = state.security.read().await;
+        security.ban_manager().ban(
+            "198.51.100.2".parse::<std::net::IpAddr>().unwrap(),
+            Duration::from_secs(600),
+        );
+        security.account_lockout().record_failure("alice");
+        let queue = security.key_enrollment().unwrap();
+        let s5::security::key_enrollment::Enrollment::New(request, _) =
+            queue.request("alice", &key, "192.0.2.1".parse().unwrap(), None)
+        else {
+            panic!("enrollment not queued");
+        };
+        request.id
+    };
+    let day = chrono::Duration::days(1);
+    let (_, invite_token) = state
+        .invitations
+        .create("dave", None, "api-token", day, 10)
+        .unwrap();
+    let (revoked, _) = state
+        .invitations
+        .create("frank", None, "api-token", day, 10)
+        .unwrap();
+    let mut ids: HashMap<&str, String> = HashMap::from([
+        ("sessions", session.session_id.clone()),
+        ("key-enrollments", enrollment),
+        ("invitations", revoked.id),
+        ("self/tokens", "t1".to_string()),
+        ("config", snapshot.id.to_string()),
+    ]);
+    let values: HashMap<&str, Value> = HashMap::from([
+        // Log in with the password, not a token
+        ("LoginRequest.token", Value::Null),
+        ("username", json!("carol")),
+        ("password", json!("testuser")),
+        ("current_password", json!("testuser")),
+        ("new_password", json!("N3w-Passw0rd-long!")),
+        ("CreateInvitationRequest.username", json!("erin")),
+        ("group", json!("staff")),
+        ("ip", json!("198.51.100.3")),
+        ("tenant", json!("acme")),
+        ("token", json!(invite_token)),
+        ("public_key", json!(key.to_openssh().unwrap())),
+    ]);
+
+    let (port, _cancel) = start_api_server_with_state(state).await;
+    let client = reqwest::Client::new();
+    let doc = spec();
+    let documented: Vec<&Endpoint> = ENDPOINTS
+        .iter()
+        .filter(|e| e.request.is_some() || e.response.is_some())
+        // Downloads the intel lists; changes the process-wide log filter
+        .filter(|e| !["/api/security/ip-intel/refresh", "/api/logging/level"].contains(&e.path))
+        .collect();
+    // Reads, writes, reads of what the writes made, deletions; the session
+    // goes last as the capture needs it
+    let pass = |methods: &[&str]| -> Vec<&Endpoint> {
+        documented
+            .iter()
+            .copied()
+            .filter(|e| methods.contains(&e.method))
+            .collect()
+    };
+    let mut calls = pass(&["get"]);
+    calls.extend(pass(&["post", "put", "patch"]));
+    calls.extend(pass(&["get"]));
+    let mut deletes = pass(&["delete"]);
+    deletes.sort_by_key(|e| e.path == "/api/sessions/{id}");
+    calls.extend(deletes);
+
+    let mut checked = BTreeSet::new();
+    let mut found = Vec::new();
+    for e in calls {
+        let url = format!("http://127.0.0.1:{}{}", port, fill_path(e.path, &ids));
+        let mut req = client.request(e.method.to_uppercase().parse().unwrap(), &url);
+        req = match e.auth {
+            Auth::None => req,
+            Auth::User | Auth::UserRead => req.bearer_auth("lead:lead-token"),
+            _ => req.bearer_auth(token),
+        };
+        if let Some(name) = e.request {
+            req = req.json(&sample(&doc, name, &values));
+        }
+        let resp = req.send().await.unwrap();
+        let status = resp.status();
+        let body: Value = resp.json().await.unwrap_or(Value::Null);
+        let at = format!("{} {}", e.method, e.path);
+        if status == 422 {
+            found.push(format!(
+                "{at}: {} refused by the handler: {body}",
+                e.request.unwrap()
+            ));
+        }
+        let (true, Some(name)) = (status.is_success(), e.response) else {
+            continue;
+        };
+        let data = if e.path.starts_with("/api/") {
+            &body["data"]
+        } else {
+            &body
+        };
+        if let (Some(family), Some(id)) = (
+            ids.keys().copied().find(|f| e.path == format!("/api/{f}")),
+            data["id"].as_str(),
+        ) {
+            ids.insert(family, id.to_string());
+        }
+        found.extend(mismatches(&doc, &schema_ref(name), data, &at));
+        checked.insert(name);
+    }
+    assert!(
+        found.is_empty(),
+        "schemas out of date:\n{}",
+        found.join("\n")
+    );
+
+    let unchecked: BTreeSet<&str> = ENDPOINTS
+        .iter()
+        .filter_map(|e| e.response)
+        .filter(|name| !checked.contains(name))
+        .collect();
+    let expected: BTreeSet<&str> = NOT_EXERCISED.iter().map(|(name, _)| *name).collect();
+    assert_eq!(unchecked, expected, "response schemas not exercised");
+}
@@ -0,0 +1,188 @@
+use crate::test_support::{app_config_toml, parse_app_config, FAKE_HASH};
+use chrono::{Duration, TimeZone, Utc};
+use s5::auth::password;
+use s5::auth::password_policy::{self, PolicyViolation};
+use s5::auth::self_service::{self, PasswordChangeError};
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::{AppConfig, PasswordPolicyConfig, SecurityConfig};
+use tokio::sync::RwLock;
+
+/// `[security.password_policy]` with `policy`, and alice hashed `alice_hash`.
+fn config_toml(policy: &str, alice_hash: &str, alice_extra: &str) -> String {
+    app_config_toml(
+        &format!("[security.password_policy]\n{policy}"),
+        alice_extra,
+    )
+    .replace(FAKE_HASH, alice_hash)
+}
+
+fn config(policy: &str, alice_extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!("[security.password_policy]\n{policy}"),
+        alice_extra,
+    )
+}
+
+fn policy(toml: &str) -> PasswordPolicyConfig {
+    config(toml, "").unwrap().security.password_policy
+}
+
+// ---------------------------------------------------------------------------
+// Rules
+// ---------------------------------------------------------------------------
+
+#[test]
+fn defaults_only_refuse_short_and_common() {
+    let policy = policy("");
+    assert_eq!(policy.min_length, 8);
+    assert_eq!(policy.min_classes, 1);
+    assert!(policy.deny_common);
+    assert_eq!(policy.max_age_days, 0);
+
+    assert!(password_policy::check(&policy, "correct horse").is_ok());
+    assert_eq!(
+        password_policy::check(&policy, "short"),
+        Err(PolicyViolation::TooShort(8))
+    );
+    assert_eq!(
+        password_policy::check(&policy, "12345678"),
+        Err(PolicyViolation::Common)
+    );
+}
+
+#[test]
+fn min_length_counts_characters() {
+    let policy = policy("min_length = 12");
+    assert_eq!(
+        password_policy::check(&policy, "éèàùçôîâêë"),
+        Err(PolicyViolation::TooShort(12))
+    );
+    assert!(password_policy::check(&policy, "éèàùçôîâêëïü").is_ok());
+}
+
+#[test]
+fn character_classes() {
+    assert_eq!(password_policy::character_classes("abcdef"), 1);
+    assert_eq!(password_policy::character_classes("abcDEF"), 2);
+    assert_eq!(password_policy::character_classes("abcDEF12"), 3);
+    assert_eq!(password_policy::character_classes("abcDEF12-"), 4);
+    assert_eq!(password_policy::character_classes("ÉCOLE été"), 3);
+
+    let policy = policy("min_classes = 3");
+    assert_eq!(
+        password_policy::check(&policy, "lowercaseonly"),
+        Err(PolicyViolation::TooFewClasses(3))
+    );
+    assert!(password_policy::check(&policy, "Mixed-case words").is_ok());
+}
+
+#[test]
+fn common_passwords_match_with_suffixes() {
+    assert!(password_policy::is_common("password"));
+    assert!(password_policy::is_common("Password123!"));
+    assert!(password_policy::is_common("QWERTY"));
+    assert!(password_policy::is_common("P@ssw0rd"));
+    assert!(!password_policy::is_common("n3w-passw0rd"));
+    assert!(!password_policy::is_common("password manager"));
+
+    let policy = policy("deny_common = false");
+    assert!(password_policy::check(&policy, "password123").is_ok());
+}
+
+#[test]
+fn max_age() {
+    let now = Utc.with_ymd_and_hms(2026, 6, 1, 0, 0, 0).unwrap();
+    let old = Some(now - Duration::days(91));
+    let recent = Some(now - Duration::days(89));
+
+    let never = policy("");
+    assert!(!password_policy::is_expired(&never, old, now));
+
+    let policy = policy("max_age_days = 90");
+    assert!(password_policy::is_expired(&policy, old, now));
+    assert!(!password_policy::is_expired(&policy, recent, now));
+    // No recorded change date: not flagged
+    assert!(!password_policy::is_expired(&policy, None, now));
+}
+
+#[test]
+fn config_validation() {
+    let parse = |toml: &str| config(toml, "");
+    assert!(parse("min_length = 16\nmin_classes = 4").is_ok());
+    assert!(parse("min_length = 7").is_err());
+    assert!(parse("min_length = 100000").is_err());
+    assert!(parse("min_classes = 0").is_err());
+    assert!(parse("min_classes = 5").is_err());
+}
+
+#[test]
+fn password_changed_at_is_parsed() {
+    let parsed = config("", "password_changed_at = \"2026-01-02T03:04:05Z\"").unwrap();
+    let auth = AuthService::new(&parsed).unwrap();
+    let alice = auth.user_store().get("alice").unwrap();
+    assert_eq!(
+        alice.password_changed_at,
+        Some(Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap())
+    );
+
+    let bad = config("", "password_changed_at = \"yesterday\"").unwrap();
+    assert!(AuthService::new(&bad).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Enforcement
+// ---------------------------------------------------------------------------
+
+#[test]
+fn hash_password_cli_applies_policy() {
+    let security = config("min_classes = 2", "").unwrap().security;
+    assert!(password::hash_password_cli(Some("alllowercase"), &security).is_err());
+    assert!(password::hash_password_cli(Some("letmein123"), &SecurityConfig::default()).is_err());
+    assert!(password::hash_password_cli(Some("Two classes"), &security).is_ok());
+}
+
+#[tokio::test]
+async fn change_password_applies_policy() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let config = parse_config(&config_toml("min_classes = 3", &hash, "")).unwrap();
+    let auth = RwLock::new(AuthService::new(&config).unwrap());
+
+    let err = self_service::change_password(&auth, None, "alice", "oldpassword", "n3wpassw0rd")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        PasswordChangeError::Policy(PolicyViolation::TooFewClasses(3))
+    ));
+    assert!(err.is_client_error());
+    assert!(auth.read().await.auth_password("alice", "oldpassword"));
+
+    self_service::change_password(&auth, None, "alice", "oldpassword", "N3w-passw0rd")
+        .await
+        .unwrap();
+    let guard = auth.read().await;
+    let changed_at = guard.user_store().get("alice").unwrap().password_changed_at;
+    assert!(changed_at.is_some_and(|t| Utc::now() - t < Duration::minutes(1)));
+}
+
+#[tokio::test]
+async fn change_password_records_change_date() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let toml = config_toml("", &hash, "password_changed_at = \"2020-01-01T00:00:00Z\"");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, &toml).unwrap();
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+
+    self_service::change_password(&auth, Some(&path), "alice", "oldpassword", "n3wpassw0rd")
+        .await
+        .unwrap();
+
+    let reloaded = s5::config::load_config(&path).unwrap();
+    let alice = &reloaded.users[0];
+    assert!(alice.password_hash.as_deref() != Some(hash.as_str()));
+    let changed_at = alice.password_changed_at.as_deref().unwrap();
+    assert_ne!(changed_at, "2020-01-01T00:00:00Z");
+    assert!(changed_at.parse::<chrono::DateTime<Utc>>().is_ok());
+}
@@ -1,4 +1,5 @@
-use s5::auth::password;
+use s5::auth::password::{self, HashSettings};
+use s5::config::types::PasswordHashAlgorithm;
 
 #[test]
 fn test_password_hash_verify_cycle() {
@@ -29,3 +30,71 @@ fn test_long_password() {
     let hash = password::hash_password(&pass).unwrap();
     assert!(password::verify_password(&pass, &hash));
 }
+
+// ---------------------------------------------------------------------------
+// Hash formats and rehash detection
+// ---------------------------------------------------------------------------
+
+fn settings(algorithm: PasswordHashAlgorithm) -> HashSettings {
+    HashSettings {
+        algorithm,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_bcrypt_and_scrypt_hashes() {
+    for algorithm in [PasswordHashAlgorithm::Bcrypt, PasswordHashAlgorithm::Scrypt] {
+        let hash = settings(algorithm).hash("hunter2-but-longer").unwrap();
+        assert_eq!(password::hash_algorithm(&hash), Some(algorithm));
+        assert!(password::verify_password("hunter2-but-longer", &hash));
+        assert!(!password::verify_password("wrong-password", &hash));
+    }
+}
+
+#[test]
+fn test_bcrypt_known_answer() {
+    // OpenBSD bcrypt test vector
+    let hash = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+    assert!(password::verify_password("U*U", hash));
+    assert!(!password::verify_password("U*V", hash));
+}
+
+#[test]
+fn test_hash_algorithm_from_prefix() {
+    use PasswordHashAlgorithm::*;
+    let argon2 = password::hash_password("x").unwrap();
+    assert_eq!(password::hash_algorithm(&argon2), Some(Argon2id));
+    assert_eq!(password::hash_algorithm("$2y$10$abc"), Some(Bcrypt));
+    assert_eq!(
+        password::hash_algorithm("$scrypt$ln=17,r=8,p=1$abc"),
+        Some(Scrypt)
+    );
+    assert_eq!(password::hash_algorithm("$apr1$salt$hash"), None);
+    assert_eq!(password::hash_algorithm("plaintext"), None);
+}
+
+#[test]
+fn test_needs_rehash() {
+    let default = HashSettings::default();
+    assert_eq!(default.algorithm, PasswordHashAlgorithm::Argon2id);
+    let current = password::hash_password("x").unwrap();
+    assert!(!default.needs_rehash(&current));
+
+    // Other Argon2 parameters or variant
+    let weak = password::hash_password_with_params("x", 8192, 1, 1).unwrap();
+    assert!(default.needs_rehash(&weak));
+    assert!(
+        default.needs_rehash("$argon2i$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA")
+    );
+
+    // Other algorithm, in either direction
+    let bcrypt = settings(PasswordHashAlgorithm::Bcrypt);
+    let bcrypt_hash = bcrypt.hash("x").unwrap();
+    assert!(default.needs_rehash(&bcrypt_hash));
+    assert!(!bcrypt.needs_rehash(&bcrypt_hash));
+    assert!(bcrypt.needs_rehash(&current));
+
+    // Unknown formats are left alone
+    assert!(!default.needs_rehash("not-a-hash"));
+}
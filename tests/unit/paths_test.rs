@@ -0,0 +1,48 @@
+use s5::paths::{state_dir, temp_file, NULL_DEVICE, STATE_DIR_ENV};
+use std::path::PathBuf;
+
+#[test]
+fn state_dir_env_override() {
+    std::env::set_var(STATE_DIR_ENV, "/srv/s5-state");
+    assert_eq!(state_dir(), PathBuf::from("/srv/s5-state"));
+
+    // Empty means unset
+    std::env::set_var(STATE_DIR_ENV, "");
+    let default = state_dir();
+    std::env::remove_var(STATE_DIR_ENV);
+    assert_eq!(default, state_dir());
+    if cfg!(windows) {
+        assert!(default.ends_with("s5"), "{}", default.display());
+    } else {
+        assert_eq!(default, PathBuf::from("/var/lib/s5"));
+    }
+}
+
+#[test]
+fn temp_files_live_in_os_temp_dir() {
+    let path = temp_file("s5-demo-host-key");
+    assert_eq!(path.parent(), Some(std::env::temp_dir().as_path()));
+    assert!(path.ends_with("s5-demo-host-key"));
+}
+
+#[test]
+fn null_device_matches_platform() {
+    let expected = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    assert_eq!(NULL_DEVICE, expected);
+}
+
+#[test]
+fn demo_host_key_not_hardcoded_to_tmp() {
+    let config = s5::demo::build_demo_config(2222, 1080, 9091, "hash");
+    assert_eq!(config.server.host_key_path, temp_file("s5-demo-host-key"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn service_commands_unsupported_off_windows() {
+    let err = s5::service::install("s5", std::path::Path::new("config.toml")).unwrap_err();
+    assert!(err.to_string().contains("contrib/s5.service"), "{err}");
+    assert!(s5::service::uninstall("s5").is_err());
+    assert!(s5::service::run("s5", Box::new(|_| Ok(()))).is_err());
+    assert!(s5::service::event_log_layer("s5").is_err());
+}
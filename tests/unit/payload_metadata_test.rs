@@ -0,0 +1,195 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::flows::{FlowQuery, FlowRecord, FlowStore};
+use s5::proxy::classify::{classify, first_line, AppProtocol};
+use s5::proxy::forwarder::{self, RelayConfig};
+use s5::proxy::ProxyEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Flow records with `flows` settings, `groups`, alice in group `mail`, and bob.
+fn config(flows: &str, groups: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!("[logging.flows]\nenabled = true\n{flows}\n\n{groups}"),
+        &format!(
+            "group = \"mail\"\n\n[[users]]\nusername = \"bob\"\npassword_hash = \"{FAKE_HASH}\""
+        ),
+    )
+}
+
+fn engine(cfg: AppConfig) -> ProxyEngine {
+    ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)))
+}
+
+fn sampled(first: &[u8], max_len: usize) -> Option<String> {
+    first_line(first, classify(first).protocol, max_len)
+}
+
+// ---------------------------------------------------------------------------
+// First line
+// ---------------------------------------------------------------------------
+
+#[test]
+fn http_request_line_is_kept() {
+    let request = b"GET /index.html?q=1 HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    assert_eq!(
+        sampled(request, 256).as_deref(),
+        Some("GET /index.html?q=1 HTTP/1.1")
+    );
+    // Cut to the cap
+    assert_eq!(sampled(request, 10).as_deref(), Some("GET /index"));
+    // A request line split over reads keeps what arrived
+    assert_eq!(sampled(b"POST /up", 256).as_deref(), Some("POST /up"));
+}
+
+#[test]
+fn smtp_greeting_is_kept() {
+    assert_eq!(
+        sampled(b"EHLO client.example.com\r\n", 256).as_deref(),
+        Some("EHLO client.example.com")
+    );
+    assert_eq!(
+        sampled(b"helo relay\r\n", 256).as_deref(),
+        Some("helo relay")
+    );
+    assert_eq!(
+        sampled(b"LHLO lmtp.local\n", 256).as_deref(),
+        Some("LHLO lmtp.local")
+    );
+}
+
+#[test]
+fn other_streams_are_not_sampled() {
+    assert_eq!(sampled(b"SSH-2.0-OpenSSH_9.6\r\n", 256), None);
+    assert_eq!(sampled(b"\x16\x03\x01\x00\x05hello", 256), None);
+    assert_eq!(sampled(b"MAIL FROM:<a@example.com>\r\n", 256), None);
+    assert_eq!(sampled(b"EHLO\tbad\x01\r\n", 256), None);
+    assert_eq!(sampled(b"GET /caf\xc3\xa9 HTTP/1.1\r\n", 256), None);
+    assert_eq!(first_line(b"", AppProtocol::Http, 256), None);
+    assert_eq!(classify(b"EHLO x\r\n").first_line, None);
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn config_defaults_and_validation() {
+    let cfg = config("", "").unwrap();
+    assert!(!cfg.logging.flows.payload_metadata);
+    assert_eq!(cfg.logging.flows.payload_metadata_max_len, 256);
+    assert!(config("payload_metadata_max_len = 0", "").is_err());
+    assert!(config("payload_metadata_max_len = 1025", "").is_err());
+    assert!(config("payload_metadata_max_len = 1024", "").is_ok());
+}
+
+#[test]
+fn groups_override_the_default() {
+    let groups = "[[groups]]\nname = \"base\"\npayload_metadata = true\n\n\
+                  [[groups]]\nname = \"mail\"\ninherits = \"base\"\n";
+    let auth = AuthService::new(&config("", groups).unwrap()).unwrap();
+    let users = auth.user_store();
+    assert_eq!(users.get("alice").unwrap().payload_metadata, Some(true));
+    assert_eq!(users.get("bob").unwrap().payload_metadata, None);
+}
+
+#[tokio::test]
+async fn sessions_are_sampled_when_opted_in() {
+    let len = |flows: &str, group: Option<bool>| {
+        let engine = engine(config(flows, "").unwrap());
+        let session = engine.register_session("alice", "smtp.example", 25, "10.0.0.1", "ssh");
+        engine.sample_first_line(&session, group);
+        session.first_line_len.get().copied()
+    };
+    assert_eq!(len("", None), None);
+    assert_eq!(len("", Some(true)), Some(256));
+    assert_eq!(len("payload_metadata = true", None), Some(256));
+    assert_eq!(len("payload_metadata = true", Some(false)), None);
+    assert_eq!(
+        len(
+            "payload_metadata = true\npayload_metadata_max_len = 64",
+            None
+        ),
+        Some(64)
+    );
+
+    // Nothing is sampled without a flow database to keep it
+    let mut cfg = config("payload_metadata = true", "").unwrap();
+    cfg.logging.flows.enabled = false;
+    let engine = engine(cfg);
+    let session = engine.register_session("alice", "smtp.example", 25, "10.0.0.1", "ssh");
+    engine.sample_first_line(&session, Some(true));
+    assert!(session.first_line_len.get().is_none());
+}
+
+// ---------------------------------------------------------------------------
+// Relay and flow records
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn relay_records_the_first_line() {
+    let engine = engine(config("payload_metadata_max_len = 16", "").unwrap());
+    let session = engine.register_session("alice", "smtp.example", 25, "10.0.0.1", "socks5");
+    engine.sample_first_line(&session, Some(true));
+
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        RelayConfig {
+            idle_timeout: Duration::from_secs(5),
+            context: "alice@smtp.example:25".to_string(),
+            per_conn_bandwidth_kbps: 0,
+            aggregate_bandwidth_kbps: 0,
+            quota_tracker: None,
+            username: Some("alice".to_string()),
+            quotas: None,
+            audit: None,
+            session: Some(session.clone()),
+            stall_watch: None,
+            client_window: None,
+            server_names: None,
+        },
+    ));
+
+    let greeting = b"EHLO client.example.com\r\n";
+    client.write_all(greeting).await.unwrap();
+    let mut buf = vec![0u8; greeting.len()];
+    server.read_exact(&mut buf).await.unwrap();
+    drop(client);
+    drop(server);
+
+    let outcome = relay.await.unwrap().unwrap();
+    let traffic = outcome.traffic.as_ref().unwrap();
+    assert_eq!(traffic.protocol, AppProtocol::Unknown);
+    assert_eq!(traffic.first_line.as_deref(), Some("EHLO client.exam"));
+
+    let store = FlowStore::open_in_memory().unwrap();
+    let flow = FlowRecord::closing(
+        "socks5",
+        "alice",
+        "10.0.0.1",
+        "smtp.example",
+        25,
+        Duration::ZERO,
+    );
+    store
+        .insert(&[
+            flow.clone().with_traffic(Some(traffic)),
+            flow.with_traffic(Some(&classify(b"EHLO client.example.com\r\n"))),
+        ])
+        .unwrap();
+    let rows = store.query(&FlowQuery::default()).unwrap();
+    assert_eq!(rows[0].first_line, None);
+    assert_eq!(rows[1].first_line.as_deref(), Some("EHLO client.exam"));
+    let json = serde_json::to_value(&rows[1]).unwrap();
+    assert_eq!(json["first_line"], "EHLO client.exam");
+    assert!(serde_json::to_value(&rows[0])
+        .unwrap()
+        .get("first_line")
+        .is_none());
+}
@@ -0,0 +1,193 @@
+use s5::audit::events::AuditEvent;
+use s5::auth::personal_token::{self, TokenError, MAX_TOKENS_PER_USER, TOKEN_PREFIX};
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::persist;
+use tokio::sync::RwLock;
+
+const CONFIG: &str = r#"[server]
+ssh_listen = "0.0.0.0:2222"
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+
+# Contractor account
+[[users]]
+username = "bob"
+password_hash = "argon2id-fakehash-for-testing"
+"#;
+
+fn auth() -> RwLock<AuthService> {
+    RwLock::new(AuthService::new(&parse_config(CONFIG).unwrap()).unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Tokens
+// ---------------------------------------------------------------------------
+
+#[test]
+fn generated_token_verifies_by_digest() {
+    let (token, secret) = personal_token::generate("ci");
+    assert!(secret.starts_with(TOKEN_PREFIX));
+    assert_eq!(secret.len(), TOKEN_PREFIX.len() + 64);
+    assert_eq!(token.id.len(), 8);
+    assert_eq!(token.name, "ci");
+    assert_eq!(token.sha256, personal_token::digest(&secret));
+    assert!(token.created_at.is_some());
+
+    let (other, _) = personal_token::generate("laptop");
+    let tokens = vec![other, token.clone()];
+    assert_eq!(personal_token::verify(&tokens, &secret), Some(&token));
+    assert_eq!(personal_token::verify(&tokens, "s5r_nope"), None);
+    assert_eq!(personal_token::verify(&[], &secret), None);
+}
+
+// ---------------------------------------------------------------------------
+// Config write and validation
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tokens_round_trip_through_config() {
+    let (a, _) = personal_token::generate("ci");
+    let (b, _) = personal_token::generate("laptop");
+    let tokens = vec![a, b];
+    let updated = persist::apply_user_api_tokens(CONFIG, "bob", &tokens).unwrap();
+    assert!(updated.contains("# Contractor account"));
+
+    let config = parse_config(&updated).unwrap();
+    assert_eq!(config.users[1].api_tokens, tokens);
+    assert!(config.users[0].api_tokens.is_empty());
+
+    // An empty list removes the key
+    let cleared = persist::apply_user_api_tokens(&updated, "bob", &[]).unwrap();
+    assert!(!cleared.contains("api_tokens"));
+    assert!(parse_config(&cleared).unwrap().users[1]
+        .api_tokens
+        .is_empty());
+
+    assert!(persist::apply_user_api_tokens(CONFIG, "carol", &tokens).is_err());
+}
+
+#[test]
+fn invalid_tokens_refused_by_validation() {
+    let (token, _) = personal_token::generate("ci");
+    let mut bad_digest = token.clone();
+    bad_digest.sha256 = "not-hex".to_string();
+    let err = persist::apply_user_api_tokens(CONFIG, "alice", &[bad_digest]).unwrap_err();
+    assert!(format!("{err:#}").contains("sha256"), "{err:#}");
+
+    let duplicate = vec![token.clone(), token];
+    let err = persist::apply_user_api_tokens(CONFIG, "alice", &duplicate).unwrap_err();
+    assert!(format!("{err:#}").contains("duplicate id"), "{err:#}");
+}
+
+#[test]
+fn token_digests_are_redacted() {
+    let (token, _) = personal_token::generate("ci");
+    let updated =
+        persist::apply_user_api_tokens(CONFIG, "alice", std::slice::from_ref(&token)).unwrap();
+    let redacted = s5::config::redact::redact_config(&parse_config(&updated).unwrap());
+    assert_eq!(redacted.users[0].api_tokens[0].sha256, "***");
+    assert_eq!(redacted.users[0].api_tokens[0].id, token.id);
+    assert!(!format!("{:?}", token).contains(&token.sha256));
+}
+
+// ---------------------------------------------------------------------------
+// Create and revoke
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn create_and_revoke_in_memory() {
+    let auth = auth();
+    let (token, secret) = personal_token::create(&auth, None, "alice", " ci ")
+        .await
+        .unwrap();
+    assert_eq!(token.name, "ci");
+    assert_eq!(
+        auth.read().await.auth_read_token("alice", &secret),
+        Some(token.id.clone())
+    );
+    // Only for its owner, and not as the full-scope API token
+    assert_eq!(auth.read().await.auth_read_token("bob", &secret), None);
+    assert!(!auth.read().await.auth_api_token("alice", &secret));
+
+    let revoked = personal_token::revoke(&auth, None, "alice", &token.id)
+        .await
+        .unwrap();
+    assert_eq!(revoked, token);
+    assert_eq!(auth.read().await.auth_read_token("alice", &secret), None);
+    assert!(matches!(
+        personal_token::revoke(&auth, None, "alice", &token.id).await,
+        Err(TokenError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn create_refuses_bad_requests() {
+    let auth = auth();
+    let long = "x".repeat(65);
+    for (user, name) in [("alice", "  "), ("alice", long.as_str()), ("carol", "ci")] {
+        let err = personal_token::create(&auth, None, user, name)
+            .await
+            .unwrap_err();
+        assert!(err.is_client_error(), "{err}");
+    }
+
+    for i in 0..MAX_TOKENS_PER_USER {
+        personal_token::create(&auth, None, "alice", &format!("t{i}"))
+            .await
+            .unwrap();
+    }
+    assert!(matches!(
+        personal_token::create(&auth, None, "alice", "one-more").await,
+        Err(TokenError::TooMany)
+    ));
+}
+
+#[tokio::test]
+async fn create_writes_config_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("s5.toml");
+    std::fs::write(&path, CONFIG).unwrap();
+    let auth = auth();
+
+    let (token, secret) = personal_token::create(&auth, Some(&path), "bob", "ci")
+        .await
+        .unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(!written.contains(secret.as_str()));
+    let config = parse_config(&written).unwrap();
+    assert_eq!(config.users[1].api_tokens, vec![token.clone()]);
+
+    // Survives a reload from the file
+    let reloaded = AuthService::new(&config).unwrap();
+    assert_eq!(
+        reloaded.auth_read_token("bob", &secret),
+        Some(token.id.clone())
+    );
+
+    personal_token::revoke(&auth, Some(&path), "bob", &token.id)
+        .await
+        .unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(parse_config(&written).unwrap().users[1]
+        .api_tokens
+        .is_empty());
+}
+
+#[test]
+fn token_events_are_critical() {
+    let (token, _) = personal_token::generate("ci");
+    let created = AuditEvent::api_token_created("alice", "10.0.0.1", &token);
+    assert_eq!(created.event_type(), "user.token_created");
+    assert!(created.is_critical());
+    let json = serde_json::to_value(&created).unwrap();
+    assert_eq!(json["token_id"], token.id);
+    assert_eq!(json["name"], "ci");
+    assert!(!json.to_string().contains(&token.sha256));
+
+    let revoked = AuditEvent::api_token_revoked("alice", "10.0.0.1", &token);
+    assert_eq!(revoked.event_type(), "user.token_revoked");
+    assert!(revoked.is_critical());
+}
@@ -0,0 +1,194 @@
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::{AppConfig, PolicyPluginConfig};
+use s5::proxy::plugin::{Plugin, PluginReply, PluginRequest, Verdict};
+use s5::proxy::retry::RetryPolicy;
+use s5::proxy::ProxyEngine;
+use s5::ssh::handler::classify_relay_error;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+/// A module answering `reply` to every request.
+fn replying(reply: &str) -> String {
+    format!(
+        r#"(module
+             (memory (export "memory") 1)
+             (data (i32.const 0) "{}")
+             (func (export "alloc") (param i32) (result i32) i32.const 1024)
+             (func (export "decide") (param i32 i32) (result i64) i64.const {}))"#,
+        reply.replace('"', "\\\""),
+        reply.len()
+    )
+}
+
+/// A module that never answers.
+const LOOPING: &str = r#"(module
+    (memory (export "memory") 1)
+    (func (export "alloc") (param i32) (result i32) i32.const 0)
+    (func (export "decide") (param i32 i32) (result i64) (loop (br 0)) i64.const 0))"#;
+
+/// Compile `wat` into `dir`; the plugin config pointing at it.
+fn plugin_config(dir: &TempDir, wat: &str, fuel: u64, timeout_ms: u64) -> PolicyPluginConfig {
+    let path = dir.path().join("policy.wasm");
+    std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+    PolicyPluginConfig {
+        path,
+        fuel,
+        timeout_ms,
+    }
+}
+
+fn load(wat: &str) -> anyhow::Result<Plugin> {
+    let dir = TempDir::new().unwrap();
+    Plugin::load(&plugin_config(&dir, wat, 1_000_000, 1000))
+}
+
+fn request() -> PluginRequest {
+    PluginRequest {
+        user: "alice".to_string(),
+        group: Some("eng".to_string()),
+        tags: vec!["dev".to_string()],
+        source_ip: "203.0.113.9".to_string(),
+        host: "db.example.com".to_string(),
+        port: 5432,
+        ip: None,
+        time: chrono::Utc::now(),
+    }
+}
+
+fn config(dir: &TempDir, wat: &str, extra: &str) -> anyhow::Result<AppConfig> {
+    let plugin = plugin_config(dir, wat, 1_000_000, 1000);
+    parse_config(&format!(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+         [security]\nip_guard_enabled = false\n\n{extra}\n\n\
+         [policy.plugin]\npath = {:?}\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"x\"\n",
+        plugin.path.display().to_string()
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Plugin
+// ---------------------------------------------------------------------------
+
+#[test]
+fn replies_are_decoded() {
+    let plugin = load(&replying(r#"{"decision":"deny","reason":"no databases"}"#)).unwrap();
+    assert_eq!(
+        plugin.decide(&request()).unwrap(),
+        PluginReply {
+            decision: Verdict::Deny,
+            reason: Some("no databases".to_string()),
+        }
+    );
+
+    let plugin = load(&replying(r#"{"decision":"pass"}"#)).unwrap();
+    assert_eq!(plugin.decide(&request()).unwrap().decision, Verdict::Pass);
+
+    let plugin = load(&replying(r#"{"decision":"maybe"}"#)).unwrap();
+    assert!(plugin.decide(&request()).is_err());
+}
+
+#[test]
+fn runaway_modules_are_stopped() {
+    let dir = TempDir::new().unwrap();
+    let plugin = Plugin::load(&plugin_config(&dir, LOOPING, 10_000, 60_000)).unwrap();
+    let err = plugin.decide(&request()).unwrap_err();
+    assert_eq!(err.to_string(), "out of fuel");
+
+    let plugin = Plugin::load(&plugin_config(&dir, LOOPING, u64::MAX, 20)).unwrap();
+    let started = std::time::Instant::now();
+    let err = plugin.decide(&request()).unwrap_err();
+    assert_eq!(err.to_string(), "timed out");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn modules_get_no_imports() {
+    let err = load(
+        r#"(module
+             (import "env" "now" (func (result i64)))
+             (memory (export "memory") 1)
+             (func (export "alloc") (param i32) (result i32) i32.const 0)
+             (func (export "decide") (param i32 i32) (result i64) i64.const 0))"#,
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("env::now"), "{err}");
+
+    let err = load(r#"(module (memory (export "memory") 1))"#)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("'alloc'"), "{err}");
+}
+
+#[test]
+fn request_json() {
+    let json = serde_json::to_value(request()).unwrap();
+    assert_eq!(json["user"], "alice");
+    assert_eq!(json["group"], "eng");
+    assert_eq!(json["tags"][0], "dev");
+    assert_eq!(json["port"], 5432);
+    assert!(json["ip"].is_null());
+    assert!(json["time"].is_string());
+}
+
+#[test]
+fn config_validation() {
+    let dir = TempDir::new().unwrap();
+    assert!(config(&dir, &replying(r#"{"decision":"pass"}"#), "").is_ok());
+    let err = config(&dir, r#"(module)"#, "").unwrap_err();
+    assert!(format!("{err:#}").contains("policy.plugin"), "{err:#}");
+}
+
+// ---------------------------------------------------------------------------
+// Engine
+// ---------------------------------------------------------------------------
+
+async fn connect(wat: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = TempDir::new().unwrap();
+    let cfg = config(&dir, wat, "[acl]\ndefault_policy = \"deny\"").unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+    let user = auth.user_store().get("alice").unwrap();
+    engine
+        .connect_for_socks(
+            &user.username,
+            "127.0.0.1",
+            port,
+            &user.acl,
+            &[],
+            None,
+            user.group.as_deref(),
+            &user.tags,
+            "203.0.113.9",
+            0,
+            None,
+            RetryPolicy::default(),
+            "c1",
+        )
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn engine_asks_the_plugin_before_the_acl() {
+    // Allowed despite the ACL's default deny
+    connect(&replying(r#"{"decision":"allow"}"#)).await.unwrap();
+
+    // Left to the ACL, which denies it
+    let err = connect(&replying(r#"{"decision":"pass"}"#))
+        .await
+        .unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+}
+
+#[tokio::test]
+async fn failing_plugins_deny_the_channel() {
+    let err = connect(LOOPING).await.unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+}
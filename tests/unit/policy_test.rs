@@ -0,0 +1,288 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use chrono::{DateTime, TimeZone, Utc};
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::proxy::policy::{Effect, Policy, Subject};
+use s5::proxy::retry::RetryPolicy;
+use s5::proxy::ProxyEngine;
+use s5::ssh::handler::classify_relay_error;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const TAGS: &str = "[[policy.destinations]]\ntag = \"staging\"\n\
+                    targets = [\"*.staging.example.com\", \"10.20.0.0/16\"]\n\n\
+                    [[policy.time_windows]]\ntag = \"office\"\nhours = \"08:00-18:00\"\n\
+                    days = [\"mon\", \"tue\", \"wed\", \"thu\", \"fri\"]\n\n\
+                    [[policy.time_windows]]\ntag = \"night\"\nhours = \"22:00-06:00\"\n\
+                    timezone = \"+02:00\"\n";
+
+/// `[policy]` with `policy` and the [`TAGS`], `extra` sections, alice in
+/// group `eng` with tag `oncall`, and bob.
+fn config(policy: &str, extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!(
+            "[security]\nip_guard_enabled = false\n\n{extra}\n\n\
+             [policy]\n{policy}\n\n{TAGS}\n\n\
+             [[groups]]\nname = \"eng\"\ntags = [\"dev\"]"
+        ),
+        &format!(
+            "group = \"eng\"\ntags = [\"oncall\"]\n\n\
+             [[users]]\nusername = \"bob\"\npassword_hash = \"{FAKE_HASH}\""
+        ),
+    )
+}
+
+fn policy(rules: &[&str]) -> Policy {
+    let rules = rules
+        .iter()
+        .map(|r| format!("{r:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let cfg = config(&format!("rules = [{rules}]"), "").unwrap();
+    Policy::from_config(&cfg.policy).unwrap().unwrap()
+}
+
+/// Wednesday 2026-03-04 at `hour`:30 UTC.
+fn wednesday(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 4, hour, 30, 0).unwrap()
+}
+
+fn dev() -> Vec<String> {
+    vec!["dev".to_string()]
+}
+
+// ---------------------------------------------------------------------------
+// Rules
+// ---------------------------------------------------------------------------
+
+#[test]
+fn first_matching_rule_decides() {
+    let policy = policy(&[
+        "deny user:mallory",
+        "allow user:tag(dev) dest:tag(staging) port:443",
+        "deny dest:tag(staging)",
+    ]);
+    let tags = dev();
+    let alice = Subject {
+        username: "alice",
+        group: Some("eng"),
+        tags: &tags,
+    };
+    let now = wednesday(12);
+    let decide = |subject: &Subject<'_>, host: &str, port: u16| {
+        policy
+            .decide(subject, host, port, None, now)
+            .map(|d| (d.effect, d.rule))
+    };
+
+    assert_eq!(
+        decide(&alice, "api.staging.example.com", 443),
+        Some((
+            Effect::Allow,
+            "allow user:tag(dev) dest:tag(staging) port:443"
+        ))
+    );
+    assert_eq!(
+        decide(&alice, "api.staging.example.com", 22),
+        Some((Effect::Deny, "deny dest:tag(staging)"))
+    );
+    // No rule matches: left to the ACL
+    assert_eq!(decide(&alice, "example.com", 443), None);
+
+    let mallory = Subject {
+        username: "mallory",
+        group: Some("eng"),
+        tags: &tags,
+    };
+    assert_eq!(
+        decide(&mallory, "api.staging.example.com", 443).map(|d| d.0),
+        Some(Effect::Deny)
+    );
+}
+
+#[test]
+fn cidr_targets_match_once_resolved() {
+    let policy = policy(&["deny dest:tag(staging)"]);
+    let bob = Subject {
+        username: "bob",
+        group: None,
+        tags: &[],
+    };
+    let now = wednesday(12);
+    assert!(policy
+        .decide(&bob, "db.internal", 5432, None, now)
+        .is_none());
+    let ip = "10.20.1.5".parse().ok();
+    assert!(policy.decide(&bob, "db.internal", 5432, ip, now).is_some());
+    // A literal address needs no resolution
+    assert!(policy.decide(&bob, "10.20.1.5", 5432, None, now).is_some());
+}
+
+#[test]
+fn groups_and_time_windows() {
+    let policy = policy(&[
+        "allow group:eng time:tag(office)",
+        "allow user:bob time:tag(night)",
+        "deny",
+    ]);
+    let tags = dev();
+    let alice = Subject {
+        username: "alice",
+        group: Some("eng"),
+        tags: &tags,
+    };
+    let bob = Subject {
+        username: "bob",
+        group: None,
+        tags: &[],
+    };
+    let effect = |subject: &Subject<'_>, now| {
+        policy
+            .decide(subject, "example.com", 443, None, now)
+            .map(|d| d.effect)
+    };
+
+    assert_eq!(effect(&alice, wednesday(12)), Some(Effect::Allow));
+    assert_eq!(effect(&alice, wednesday(18)), Some(Effect::Deny));
+    let saturday = Utc.with_ymd_and_hms(2026, 3, 7, 12, 0, 0).unwrap();
+    assert_eq!(effect(&alice, saturday), Some(Effect::Deny));
+
+    // 22:00-06:00 at +02:00 runs past midnight: 20:00-04:00 UTC
+    assert_eq!(effect(&bob, wednesday(21)), Some(Effect::Allow));
+    assert_eq!(effect(&bob, wednesday(3)), Some(Effect::Allow));
+    assert_eq!(effect(&bob, wednesday(4)), Some(Effect::Deny));
+    assert_eq!(effect(&bob, wednesday(19)), Some(Effect::Deny));
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn config_validation() {
+    let rules = |rule: &str| config(&format!("rules = [{rule:?}]"), "");
+    let cfg = config("", "").unwrap();
+    assert!(cfg.policy.rules.is_empty());
+    assert!(Policy::from_config(&cfg.policy).unwrap().is_none());
+
+    assert!(rules("deny").is_ok());
+    assert!(rules("allow user:tag(dev) dest:*.example.com:443 port:8000-9000").is_ok());
+    assert!(rules("permit user:alice").is_err());
+    assert!(rules("allow dest:tag(prod)").is_err());
+    assert!(rules("allow time:tag(weekend)").is_err());
+    assert!(rules("allow port:http").is_err());
+    assert!(rules("allow group:tag(eng)").is_err());
+    assert!(rules("allow host:example.com").is_err());
+    assert!(rules("allow user:").is_err());
+
+    let window = |body: &str| config("", &format!("[[policy.time_windows]]\n{body}"));
+    assert!(window("tag = \"w\"\nhours = \"8-18\"").is_err());
+    assert!(window("tag = \"w\"\ndays = [\"someday\"]").is_err());
+    assert!(window("tag = \"w\"\ntimezone = \"Europe/Paris\"").is_ok());
+    assert!(window("tag = \"w\"\ntimezone = \"Europe/Pariss\"").is_err());
+    assert!(window("tag = \"\"").is_err());
+    let dest = |body: &str| config("", &format!("[[policy.destinations]]\n{body}"));
+    assert!(dest("tag = \"prod\"\ntargets = []").is_err());
+    assert!(dest("tag = \"prod\"\ntargets = [\"db:99999\"]").is_err());
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+#[test]
+fn plugin_needs_the_wasm_plugins_feature() {
+    let err = config("", "[policy.plugin]\npath = \"/etc/s5/policy.wasm\"").unwrap_err();
+    assert!(
+        format!("{err:#}").contains("--features wasm-plugins"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn group_tags_are_added_to_users() {
+    let auth = AuthService::new(&config("", "").unwrap()).unwrap();
+    let users = auth.user_store();
+    assert_eq!(users.get("alice").unwrap().tags, ["oncall", "dev"]);
+    assert!(users.get("bob").unwrap().tags.is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Engine
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn engine_applies_rules_before_the_acl() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let cfg = config(
+        &format!("rules = [\"deny user:bob port:{port}\", \"allow user:tag(dev)\"]"),
+        "[acl]\ndefault_policy = \"deny\"",
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+    let connect = |name: &str| {
+        let user = auth.user_store().get(name).unwrap();
+        let engine = &engine;
+        async move {
+            engine
+                .connect_for_socks(
+                    &user.username,
+                    "127.0.0.1",
+                    port,
+                    &user.acl,
+                    &[],
+                    None,
+                    user.group.as_deref(),
+                    &user.tags,
+                    "203.0.113.9",
+                    0,
+                    None,
+                    RetryPolicy::default(),
+                    "c1",
+                )
+                .await
+                .map(|_| ())
+        }
+    };
+
+    // Allowed by its group's tag despite the ACL's default deny
+    connect("alice").await.unwrap();
+
+    let err = connect("bob").await.unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+    assert_eq!(engine.active_connections(), 0);
+}
+
+#[tokio::test]
+async fn unmatched_channels_are_left_to_the_acl() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let cfg = config(
+        "rules = [\"allow user:tag(dev) port:443\"]",
+        "[acl]\ndefault_policy = \"deny\"",
+    )
+    .unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+    let user = auth.user_store().get("alice").unwrap();
+    let err = engine
+        .connect_for_socks(
+            &user.username,
+            "127.0.0.1",
+            port,
+            &user.acl,
+            &[],
+            None,
+            user.group.as_deref(),
+            &user.tags,
+            "203.0.113.9",
+            0,
+            None,
+            RetryPolicy::default(),
+            "c1",
+        )
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+}
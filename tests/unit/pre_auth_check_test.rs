@@ -55,7 +55,7 @@ fn pre_auth_check_rejects_banned_ip() {
     let ip: IpAddr = "5.5.5.5".parse().unwrap();
 
     // Record a failure to trigger ban
-    sm.record_auth_failure(&ip);
+    sm.record_auth_failure(&ip, None);
 
     assert_eq!(sm.pre_auth_check(&ip), Err("banned IP"));
 }
@@ -66,7 +66,7 @@ fn pre_auth_check_ip_allowlist_checked_before_ban() {
     let config = config_with_allowed_ips(vec!["10.0.0.0/8"]);
     let sm = SecurityManager::new(&config);
     let ip: IpAddr = "5.5.5.5".parse().unwrap();
-    sm.record_auth_failure(&ip);
+    sm.record_auth_failure(&ip, None);
 
     assert_eq!(sm.pre_auth_check(&ip), Err("disallowed source IP"));
 }
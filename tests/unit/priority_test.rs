@@ -0,0 +1,252 @@
+use crate::test_support::parse_app_config;
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::proxy::forwarder::{self, RelayConfig};
+use s5::proxy::priority::{self, Scheduler, DEFAULT_CLASS};
+use s5::proxy::ProxyEngine;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const CLASSES: &str = "[[proxy.priority.classes]]\nname = \"interactive\"\nweight = 8\n\
+                       destinations = [\"*:22\", \"10.0.0.0/8:3389\"]\n\n\
+                       [[proxy.priority.classes]]\nname = \"bulk\"\ntags = [\"backup\"]\n";
+
+/// Alice carries the `backup` tag of the bulk class.
+fn config(priority: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(priority, "tags = [\"backup\"]")
+}
+
+fn scheduler(settings: &str) -> Arc<Scheduler> {
+    let cfg = config(&format!("[proxy.priority]\n{settings}\n\n{CLASSES}")).unwrap();
+    Scheduler::from_config(&cfg.proxy.priority)
+        .unwrap()
+        .unwrap()
+}
+
+/// Wait until `n` chunks queue for a slot.
+async fn until_waiting(scheduler: &Scheduler, n: usize) {
+    for _ in 0..200 {
+        if scheduler.waiting() == n {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    panic!("{} chunks waiting, expected {}", scheduler.waiting(), n);
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn user_tags_reach_the_user_store() {
+    let auth = AuthService::new(&config("").unwrap()).unwrap();
+    assert_eq!(auth.user_store().get("alice").unwrap().tags, ["backup"]);
+}
+
+// ---------------------------------------------------------------------------
+// Classes
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sessions_take_the_first_matching_class() {
+    let scheduler = scheduler("");
+    let ip = "10.1.2.3".parse().ok();
+    let none: &[String] = &[];
+    let backup = ["backup".to_string()];
+
+    let flow = scheduler.flow("bastion.example.com", 22, None, none);
+    assert_eq!((flow.class(), flow.weight()), ("interactive", 8));
+    assert_eq!(
+        scheduler.flow("rdp.internal", 3389, ip, none).class(),
+        "interactive"
+    );
+    assert_eq!(
+        scheduler.flow("rdp.internal", 3389, None, none).class(),
+        DEFAULT_CLASS
+    );
+    assert_eq!(
+        scheduler
+            .flow("files.example.com", 443, None, &backup)
+            .class(),
+        "bulk"
+    );
+    // The earlier class wins: a tagged user's SSH hop stays interactive
+    assert_eq!(
+        scheduler.flow("git.example.com", 22, None, &backup).class(),
+        "interactive"
+    );
+
+    let flow = scheduler.flow("files.example.com", 443, None, none);
+    assert_eq!((flow.class(), flow.weight()), (DEFAULT_CLASS, 1));
+}
+
+#[tokio::test]
+async fn engine_classifies_sessions() {
+    let engine = ProxyEngine::new(Arc::new(config(CLASSES).unwrap()), test_audit());
+    assert!(engine.priority_scheduler().is_some());
+    let session =
+        engine.register_session_cid("alice", "files.example.com", 443, "10.0.0.1", "ssh", "c1");
+    assert!(engine.session_priority(&session.session_id).is_none());
+    engine.prioritize(&session, None, &["backup".to_string()]);
+    assert_eq!(
+        engine.session_priority(&session.session_id).as_deref(),
+        Some("bulk")
+    );
+
+    let engine = ProxyEngine::new(Arc::new(config("").unwrap()), test_audit());
+    assert!(engine.priority_scheduler().is_none());
+    let session = engine.register_session("alice", "bastion", 22, "10.0.0.1", "ssh");
+    engine.prioritize(&session, None, &[]);
+    assert!(engine.session_priority(&session.session_id).is_none());
+}
+
+fn test_audit() -> Arc<s5::audit::AuditLogger> {
+    Arc::new(s5::audit::AuditLogger::new(None, 0, 0, None))
+}
+
+// ---------------------------------------------------------------------------
+// Scheduling
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn free_slots_are_taken_at_once() {
+    let scheduler = scheduler("max_inflight = 2");
+    let flow = scheduler.flow("files.example.com", 443, None, &[]);
+    let first = flow.slot(8192).await;
+    let second = flow.slot(8192).await;
+    assert_eq!(scheduler.in_flight(), 2);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), flow.slot(8192))
+            .await
+            .is_err()
+    );
+    // The abandoned chunk is skipped once a slot frees up
+    assert_eq!(scheduler.waiting(), 1);
+    drop(first);
+    assert_eq!((scheduler.in_flight(), scheduler.waiting()), (1, 0));
+    drop(second);
+    assert_eq!(scheduler.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn interactive_chunks_go_ahead_of_bulk() {
+    let scheduler = scheduler("max_inflight = 1");
+    let bulk = Arc::new(scheduler.flow("files.example.com", 443, None, &[]));
+    let ssh = Arc::new(scheduler.flow("bastion", 22, None, &[]));
+    let held = bulk.slot(8192).await;
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut tasks = Vec::new();
+    for (name, flow, bytes) in [
+        ("bulk-1", bulk.clone(), 8192),
+        ("bulk-2", bulk.clone(), 8192),
+        ("ssh", ssh.clone(), 64),
+    ] {
+        let order = order.clone();
+        tasks.push(tokio::spawn(async move {
+            let _slot = flow.slot(bytes).await;
+            order.lock().unwrap().push(name);
+        }));
+        until_waiting(&scheduler, tasks.len()).await;
+    }
+
+    drop(held);
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), ["ssh", "bulk-1", "bulk-2"]);
+    assert_eq!(scheduler.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn weights_share_slots_under_contention() {
+    let scheduler = scheduler("max_inflight = 1");
+    let bulk = Arc::new(scheduler.flow("files.example.com", 443, None, &[]));
+    let ssh = Arc::new(scheduler.flow("bastion", 22, None, &[]));
+    let held = scheduler.flow("other", 443, None, &[]).slot(1).await;
+
+    // Same chunk size: eight interactive chunks per bulk chunk
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut tasks = Vec::new();
+    for i in 0..18 {
+        let (name, flow) = if i < 9 {
+            ("bulk", bulk.clone())
+        } else {
+            ("ssh", ssh.clone())
+        };
+        let order = order.clone();
+        tasks.push(tokio::spawn(async move {
+            let _slot = flow.slot(1024).await;
+            order.lock().unwrap().push(name);
+        }));
+        until_waiting(&scheduler, tasks.len()).await;
+    }
+
+    drop(held);
+    for task in tasks {
+        task.await.unwrap();
+    }
+    let order = order.lock().unwrap();
+    let first: Vec<&str> = order.iter().take(9).copied().collect();
+    assert_eq!(first.iter().filter(|&&n| n == "ssh").count(), 8);
+}
+
+#[tokio::test]
+async fn slow_writes_give_their_slot_back() {
+    let scheduler = scheduler("max_inflight = 1\nhold_ms = 10");
+    let flow = scheduler.flow("bastion", 22, None, &[]);
+    let slot = flow.slot(64).await;
+    let stuck = tokio::spawn(priority::holding(Some(slot), std::future::pending::<()>()));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(scheduler.in_flight(), 0);
+    let _next = tokio::time::timeout(Duration::from_millis(100), flow.slot(64))
+        .await
+        .expect("slot should be free");
+    stuck.abort();
+
+    // Without a slot the write just runs
+    assert_eq!(priority::holding(None, async { 7 }).await, 7);
+}
+
+#[tokio::test]
+async fn prioritized_relay_moves_bytes() {
+    let engine = ProxyEngine::new(Arc::new(config(CLASSES).unwrap()), test_audit());
+    let session = engine.register_session("alice", "bastion", 22, "10.0.0.1", "ssh");
+    engine.prioritize(&session, None, &[]);
+
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        RelayConfig {
+            idle_timeout: Duration::from_secs(5),
+            context: "alice@bastion:22".to_string(),
+            per_conn_bandwidth_kbps: 0,
+            aggregate_bandwidth_kbps: 0,
+            quota_tracker: None,
+            username: Some("alice".to_string()),
+            quotas: None,
+            audit: None,
+            session: Some(session.clone()),
+            stall_watch: None,
+            client_window: None,
+            server_names: None,
+        },
+    ));
+
+    client.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(server.read(&mut buf).await.unwrap(), 4);
+    server.write_all(b"pong").await.unwrap();
+    assert_eq!(client.read(&mut buf).await.unwrap(), 4);
+    drop(client);
+    drop(server);
+
+    let outcome = handle.await.unwrap().unwrap();
+    assert_eq!((outcome.bytes_up, outcome.bytes_down), (4, 4));
+    assert_eq!(engine.priority_scheduler().unwrap().in_flight(), 0);
+}
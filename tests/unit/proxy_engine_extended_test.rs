@@ -116,6 +116,7 @@ fn connection_guard_drop_frees_slot_for_reuse() {
 fn live_session_snapshot_captures_initial_values() {
     let session = LiveSession {
         session_id: "s42".to_string(),
+        correlation_id: None,
         username: "alice".to_string(),
         target_host: "example.com".to_string(),
         target_port: 443,
@@ -124,6 +125,13 @@ fn live_session_snapshot_captures_initial_values() {
         bytes_up: AtomicU64::new(0),
         bytes_down: AtomicU64::new(0),
         protocol: "ssh".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Default::default(),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
     };
 
     let snap = session.snapshot();
@@ -141,6 +149,7 @@ fn live_session_snapshot_captures_initial_values() {
 fn live_session_snapshot_reflects_updated_atomic_counters() {
     let session = LiveSession {
         session_id: "s99".to_string(),
+        correlation_id: None,
         username: "bob".to_string(),
         target_host: "proxy.test".to_string(),
         target_port: 8080,
@@ -149,6 +158,13 @@ fn live_session_snapshot_reflects_updated_atomic_counters() {
         bytes_up: AtomicU64::new(0),
         bytes_down: AtomicU64::new(0),
         protocol: "socks".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Default::default(),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
     };
 
     // Simulate traffic
@@ -164,6 +180,7 @@ fn live_session_snapshot_reflects_updated_atomic_counters() {
 fn live_session_snapshot_is_independent_copy() {
     let session = LiveSession {
         session_id: "s1".to_string(),
+        correlation_id: None,
         username: "alice".to_string(),
         target_host: "host.test".to_string(),
         target_port: 80,
@@ -172,6 +189,13 @@ fn live_session_snapshot_is_independent_copy() {
         bytes_up: AtomicU64::new(100),
         bytes_down: AtomicU64::new(200),
         protocol: "ssh".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Default::default(),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
     };
 
     let snap = session.snapshot();
@@ -189,6 +213,7 @@ fn live_session_snapshot_is_independent_copy() {
 fn live_session_snapshot_with_incremental_updates() {
     let session = LiveSession {
         session_id: "s7".to_string(),
+        correlation_id: None,
         username: "charlie".to_string(),
         target_host: "api.example.com".to_string(),
         target_port: 443,
@@ -197,6 +222,13 @@ fn live_session_snapshot_with_incremental_updates() {
         bytes_up: AtomicU64::new(0),
         bytes_down: AtomicU64::new(0),
         protocol: "socks".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Default::default(),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
     };
 
     // First snapshot: zero
@@ -234,6 +266,7 @@ fn session_snapshot_serializes_to_json_with_all_fields() {
     let started = Utc::now();
     let snap = SessionSnapshot {
         session_id: "s123".to_string(),
+        correlation_id: None,
         username: "alice".to_string(),
         target_host: "example.com".to_string(),
         target_port: 443,
@@ -269,6 +302,7 @@ fn session_snapshot_serializes_to_json_with_all_fields() {
 fn session_snapshot_serializes_zero_bytes() {
     let snap = SessionSnapshot {
         session_id: "s0".to_string(),
+        correlation_id: None,
         username: "bob".to_string(),
         target_host: "localhost".to_string(),
         target_port: 80,
@@ -290,6 +324,7 @@ fn session_snapshot_serializes_zero_bytes() {
 fn session_snapshot_serializes_large_byte_counts() {
     let snap = SessionSnapshot {
         session_id: "s999".to_string(),
+        correlation_id: None,
         username: "heavyuser".to_string(),
         target_host: "cdn.example.com".to_string(),
         target_port: 443,
@@ -311,6 +346,7 @@ fn session_snapshot_serializes_large_byte_counts() {
 fn session_snapshot_json_has_exactly_expected_fields() {
     let snap = SessionSnapshot {
         session_id: "s1".to_string(),
+        correlation_id: None,
         username: "test".to_string(),
         target_host: "host".to_string(),
         target_port: 22,
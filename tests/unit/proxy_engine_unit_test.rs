@@ -196,6 +196,76 @@ fn get_user_sessions_filters_by_username() {
     assert_eq!(bob_sessions[0].username, "bob");
 }
 
+#[test]
+fn correlated_sessions_share_the_connection_id() {
+    let config = create_test_config(100, 10);
+    let engine = create_engine(config);
+
+    engine.register_session_cid("alice", "host1", 80, "10.0.0.1", "ssh", "0badc0de");
+    engine.register_session_cid("alice", "host2", 443, "10.0.0.1", "ssh", "0badc0de");
+    engine.register_session_cid("alice", "host3", 443, "10.0.0.1", "ssh", "feedf00d");
+    engine.register_session("bob", "host4", 443, "10.0.0.2", "socks5");
+
+    let sessions = engine.get_correlated_sessions("0badc0de");
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions
+        .iter()
+        .all(|s| s.correlation_id.as_deref() == Some("0badc0de")));
+
+    let bob = engine.get_user_sessions("bob");
+    assert!(bob[0].correlation_id.is_none());
+    // Absent IDs are left out of API payloads
+    let json = serde_json::to_value(&bob[0]).unwrap();
+    assert!(json.get("correlation_id").is_none());
+}
+
+#[test]
+fn connection_registry_tracks_auth_and_channels() {
+    let config = create_test_config(100, 10);
+    let engine = create_engine(config);
+
+    let conn = engine.register_connection("0badc0de", "10.0.0.1");
+    assert!(engine
+        .get_connection("0badc0de")
+        .unwrap()
+        .auth_method
+        .is_none());
+
+    conn.set_auth_method("password");
+    conn.session_channels
+        .store(1, std::sync::atomic::Ordering::Relaxed);
+    let s = engine.register_session_cid("alice", "host1", 80, "10.0.0.1", "ssh", "0badc0de");
+
+    let snap = engine.get_connection("0badc0de").unwrap();
+    assert_eq!(snap.auth_method.as_deref(), Some("password"));
+    // One shell plus one forwarding channel
+    assert_eq!(snap.open_channels, 2);
+    assert_eq!((snap.session_channels, snap.forwarding_channels), (1, 1));
+
+    engine.unregister_session(&s.session_id);
+    assert_eq!(engine.get_connection("0badc0de").unwrap().open_channels, 1);
+    engine.unregister_connection("0badc0de");
+    assert!(engine.get_connection("0badc0de").is_none());
+}
+
+#[test]
+fn session_rate_measures_bytes_between_samples() {
+    let config = create_test_config(100, 10);
+    let engine = create_engine(config);
+    let s = engine.register_session("alice", "host1", 80, "10.0.0.1", "socks5");
+
+    assert_eq!(engine.session_rate(&s.session_id), Some((0.0, 0.0)));
+    s.bytes_up
+        .fetch_add(10_000, std::sync::atomic::Ordering::Relaxed);
+    std::thread::sleep(std::time::Duration::from_millis(600));
+    let (up, down) = engine.session_rate(&s.session_id).unwrap();
+    assert!(up > 0.0 && up <= 10_000.0 / 0.6, "up = {}", up);
+    assert_eq!(down, 0.0);
+
+    engine.unregister_session(&s.session_id);
+    assert!(engine.session_rate(&s.session_id).is_none());
+}
+
 #[test]
 fn get_user_sessions_returns_empty_for_unknown_user() {
     let config = create_test_config(100, 10);
@@ -0,0 +1,388 @@
+use chrono::{DateTime, Utc};
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::LimitsConfig;
+use s5::quota::plan::{self, ResetSchedule};
+use s5::quota::{QuotaCounter, QuotaResult, QuotaTracker};
+
+const CONFIG: &str = r#"[server]
+ssh_listen = "0.0.0.0:2222"
+
+[quota_plans.basic]
+daily_bandwidth_bytes = 1000
+monthly_bandwidth_bytes = 10000
+
+[quota_plans.billing]
+monthly_bandwidth_bytes = 50000
+reset_schedule = "monthly 15 00:00"
+reset_timezone = "+02:00"
+reset_counters = ["monthly_bytes"]
+
+[[groups]]
+name = "customers"
+quota_plan = "billing"
+
+[[groups]]
+name = "resellers"
+inherits = "customers"
+
+[[groups]]
+name = "staff"
+inherits = "customers"
+
+[groups.quotas]
+daily_bandwidth_bytes = 5
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+quota_plan = "basic"
+
+[[users]]
+username = "bob"
+password_hash = "argon2id-fakehash-for-testing"
+group = "customers"
+
+[[users]]
+username = "carol"
+password_hash = "argon2id-fakehash-for-testing"
+group = "resellers"
+
+[[users]]
+username = "dave"
+password_hash = "argon2id-fakehash-for-testing"
+group = "staff"
+
+[[users]]
+username = "erin"
+password_hash = "argon2id-fakehash-for-testing"
+group = "customers"
+
+[users.quotas]
+daily_bandwidth_bytes = 7
+"#;
+
+fn at(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+}
+
+// ---------------------------------------------------------------------------
+// Schedules
+// ---------------------------------------------------------------------------
+
+#[test]
+fn daily_and_weekly_schedules() {
+    let daily = ResetSchedule::parse("daily 06:00", "UTC").unwrap();
+    assert_eq!(
+        daily.next_after(at("2026-03-10T05:00:00Z")),
+        at("2026-03-10T06:00:00Z")
+    );
+    // Strictly after: a reset exactly now is the next day's
+    assert_eq!(
+        daily.next_after(at("2026-03-10T06:00:00Z")),
+        at("2026-03-11T06:00:00Z")
+    );
+
+    // 2026-03-10 is a Tuesday
+    let weekly = ResetSchedule::parse("Mon 00:00", "utc").unwrap();
+    assert_eq!(
+        weekly.next_after(at("2026-03-10T12:00:00Z")),
+        at("2026-03-16T00:00:00Z")
+    );
+}
+
+#[test]
+fn monthly_schedule_clamps_to_month_end() {
+    let schedule = ResetSchedule::parse("monthly 31 00:00", "UTC").unwrap();
+    assert_eq!(
+        schedule.next_after(at("2026-02-10T00:00:00Z")),
+        at("2026-02-28T00:00:00Z")
+    );
+    assert_eq!(
+        schedule.next_after(at("2026-02-28T00:00:00Z")),
+        at("2026-03-31T00:00:00Z")
+    );
+    assert_eq!(
+        schedule.next_after(at("2028-02-01T00:00:00Z")),
+        at("2028-02-29T00:00:00Z")
+    );
+}
+
+#[test]
+fn schedule_time_is_in_its_timezone() {
+    // Midnight at +02:00 is 22:00 UTC the day before
+    let schedule = ResetSchedule::parse("monthly 15 00:00", "+02:00").unwrap();
+    assert_eq!(
+        schedule.next_after(at("2026-03-01T00:00:00Z")),
+        at("2026-03-14T22:00:00Z")
+    );
+    let schedule = ResetSchedule::parse("daily 01:00", "-05:30").unwrap();
+    assert_eq!(
+        schedule.next_after(at("2026-03-10T00:00:00Z")),
+        at("2026-03-10T06:30:00Z")
+    );
+}
+
+#[test]
+fn invalid_schedules_and_timezones() {
+    for schedule in [
+        "",
+        "daily",
+        "daily 25:00",
+        "someday 06:00",
+        "monthly 0 00:00",
+        "monthly 32 00:00",
+        "monthly 06:00",
+        "weekly mon 06:00",
+    ] {
+        assert!(
+            ResetSchedule::parse(schedule, "UTC").is_err(),
+            "{schedule:?} should be refused"
+        );
+    }
+    for tz in ["Europe/Pariss", "+2", "+15:00", "+02:60", "Mars/Olympus"] {
+        assert!(
+            plan::parse_timezone(tz).is_err(),
+            "{tz:?} should be refused"
+        );
+    }
+    let noon = at("2026-03-10T12:00:00Z");
+    assert_eq!(
+        plan::parse_timezone("+05:30")
+            .unwrap()
+            .local(noon)
+            .to_string(),
+        "2026-03-10 17:30:00"
+    );
+    assert_eq!(
+        plan::parse_timezone("Z").unwrap().local(noon).to_string(),
+        "2026-03-10 12:00:00"
+    );
+    assert_eq!(
+        plan::parse_timezone("America/New_York")
+            .unwrap()
+            .local(noon)
+            .to_string(),
+        "2026-03-10 08:00:00"
+    );
+}
+
+#[test]
+fn named_timezones_follow_daylight_saving() {
+    // Paris is at +01:00 until 2026-03-29 01:00 UTC, then +02:00 until
+    // 2026-10-25 01:00 UTC
+    let midnight = ResetSchedule::parse("daily 00:00", "Europe/Paris").unwrap();
+    assert_eq!(
+        midnight.next_after(at("2026-03-28T12:00:00Z")),
+        at("2026-03-28T23:00:00Z")
+    );
+    assert_eq!(
+        midnight.next_after(at("2026-03-29T12:00:00Z")),
+        at("2026-03-29T22:00:00Z")
+    );
+    let monthly = ResetSchedule::parse("monthly 1 00:00", "Europe/Paris").unwrap();
+    assert_eq!(
+        monthly.next_after(at("2026-10-15T00:00:00Z")),
+        at("2026-10-31T23:00:00Z")
+    );
+
+    let night = ResetSchedule::parse("daily 02:30", "Europe/Paris").unwrap();
+    // Skipped hour: once the clocks have moved on, at 03:30
+    assert_eq!(
+        night.next_after(at("2026-03-28T12:00:00Z")),
+        at("2026-03-29T01:30:00Z")
+    );
+    // Repeated hour: the first 02:30 only
+    assert_eq!(
+        night.next_after(at("2026-10-24T12:00:00Z")),
+        at("2026-10-25T00:30:00Z")
+    );
+    assert_eq!(
+        night.next_after(at("2026-10-25T00:30:00Z")),
+        at("2026-10-26T01:30:00Z")
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn plans_resolve_through_users_and_groups() {
+    let config = parse_config(CONFIG).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    let user = |name: &str| store.get(name).unwrap().clone();
+
+    let alice = user("alice");
+    assert_eq!(alice.quota_plan.as_deref(), Some("basic"));
+    assert_eq!(alice.quotas.as_ref().unwrap().daily_bandwidth_bytes, 1000);
+
+    // From the group, and from a parent group
+    for name in ["bob", "carol"] {
+        let u = user(name);
+        assert_eq!(u.quota_plan.as_deref(), Some("billing"), "{name}");
+        assert_eq!(u.quotas.as_ref().unwrap().monthly_bandwidth_bytes, 50000);
+    }
+
+    // Quotas set on a child group or the user replace the plan
+    let dave = user("dave");
+    assert_eq!(dave.quota_plan, None);
+    assert_eq!(dave.quotas.as_ref().unwrap().daily_bandwidth_bytes, 5);
+    let erin = user("erin");
+    assert_eq!(erin.quota_plan, None);
+    assert_eq!(erin.quotas.as_ref().unwrap().daily_bandwidth_bytes, 7);
+
+    let billing = &auth.quota_plans()["billing"];
+    assert_eq!(billing.reset_counters, vec![QuotaCounter::MonthlyBytes]);
+    assert!(billing.schedule().is_some());
+    assert!(auth.quota_plans()["basic"].schedule().is_none());
+}
+
+#[test]
+fn invalid_plans_refused_by_validation() {
+    let cases = [
+        (
+            CONFIG.replace("quota_plan = \"basic\"", "quota_plan = \"gold\""),
+            "unknown quota plan 'gold'",
+        ),
+        (
+            CONFIG.replace(
+                "quota_plan = \"basic\"",
+                "quota_plan = \"basic\"\n\n[users.quotas]\ndaily_bandwidth_bytes = 1",
+            ),
+            "sets both quotas and quota_plan",
+        ),
+        (
+            CONFIG.replace("\"monthly 15 00:00\"", "\"monthly 15\""),
+            "invalid reset_schedule",
+        ),
+        (
+            CONFIG.replace("\"+02:00\"", "\"Europe/Pariss\""),
+            "invalid timezone",
+        ),
+        (
+            CONFIG.replace("[\"monthly_bytes\"]", "[\"weekly_bytes\"]"),
+            "weekly_bytes",
+        ),
+    ];
+    for (config, expected) in cases {
+        let err = parse_config(&config).unwrap_err();
+        assert!(format!("{err:#}").contains(expected), "{err:#}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Scheduled resets
+// ---------------------------------------------------------------------------
+
+#[test]
+fn due_resets_clear_plan_members_only() {
+    let config = parse_config(CONFIG).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let tracker = QuotaTracker::new(&LimitsConfig::default());
+    let audit = AuditLogger::new_noop();
+    for user in ["alice", "bob", "carol", "dave"] {
+        assert!(matches!(
+            tracker.record_bytes(user, 100, 0, 0, None),
+            QuotaResult::Ok(_)
+        ));
+    }
+
+    // Nothing due before the 15th (00:00 at +02:00)
+    let since = at("2026-03-14T21:00:00Z");
+    let reset = plan::run_due_resets(
+        &auth,
+        &tracker,
+        Some(&audit),
+        since,
+        at("2026-03-14T21:59:59Z"),
+    );
+    assert_eq!(reset, 0);
+
+    let reset = plan::run_due_resets(
+        &auth,
+        &tracker,
+        Some(&audit),
+        since,
+        at("2026-03-14T22:00:30Z"),
+    );
+    assert_eq!(reset, 2);
+    for user in ["bob", "carol"] {
+        let usage = tracker.get_user_usage(user);
+        assert_eq!(usage.monthly_bytes, 0, "{user}");
+        // Only the plan's counters
+        assert_eq!(usage.daily_bytes, 100, "{user}");
+    }
+    for user in ["alice", "dave"] {
+        assert_eq!(tracker.get_user_usage(user).monthly_bytes, 100, "{user}");
+    }
+
+    let events = audit.get_recent_events(10);
+    assert_eq!(events.len(), 2);
+    for event in &events {
+        assert!(matches!(
+            event,
+            AuditEvent::QuotaReset { reset_by, counters, .. }
+                if reset_by == "plan:billing" && counters == &[QuotaCounter::MonthlyBytes]
+        ));
+    }
+}
+
+#[test]
+fn reset_state_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("quota_resets.json");
+    assert_eq!(plan::load_applied_until(&path), None);
+
+    let t = at("2026-03-14T22:00:30Z");
+    plan::save_applied_until(&path, t).unwrap();
+    assert_eq!(plan::load_applied_until(&path), Some(t));
+
+    std::fs::write(&path, "not json").unwrap();
+    assert_eq!(plan::load_applied_until(&path), None);
+}
+
+#[tokio::test]
+async fn scheduler_runs_resets_missed_while_down() {
+    let config = parse_config(CONFIG).unwrap();
+    let auth = std::sync::Arc::new(tokio::sync::RwLock::new(AuthService::new(&config).unwrap()));
+    let tracker = std::sync::Arc::new(QuotaTracker::new(&LimitsConfig::default()));
+    let audit = std::sync::Arc::new(AuditLogger::new_noop());
+    for user in ["alice", "bob"] {
+        assert!(matches!(
+            tracker.record_bytes(user, 100, 0, 0, None),
+            QuotaResult::Ok(_)
+        ));
+    }
+
+    // Down since before the last billing day
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("quota_resets.json");
+    let started = Utc::now();
+    plan::save_applied_until(&path, started - chrono::Duration::days(40)).unwrap();
+
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    plan::spawn_scheduler(
+        auth,
+        tracker.clone(),
+        audit,
+        Some(path.clone()),
+        shutdown.clone(),
+    );
+    // The first check runs at startup and saves its time once done
+    let mut applied = false;
+    for _ in 0..50 {
+        applied = plan::load_applied_until(&path).is_some_and(|t| t >= started);
+        if applied {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    shutdown.cancel();
+    assert!(applied);
+    assert_eq!(tracker.get_user_usage("bob").monthly_bytes, 0);
+    assert_eq!(tracker.get_user_usage("alice").monthly_bytes, 100);
+}
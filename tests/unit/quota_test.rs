@@ -1,5 +1,5 @@
 use s5::config::types::{LimitsConfig, QuotaConfig, RateLimitsConfig};
-use s5::quota::{QuotaResult, QuotaTracker};
+use s5::quota::{QuotaCounter, QuotaGrant, QuotaResult, QuotaTracker};
 use std::time::Duration;
 
 fn test_limits() -> LimitsConfig {
@@ -268,6 +268,86 @@ fn reset_nonexistent_user_is_noop() {
     tracker.reset_user("nonexistent"); // should not panic
 }
 
+#[test]
+fn reset_counters_clears_only_those_named() {
+    let tracker = QuotaTracker::new(&test_limits());
+    tracker.record_connection("alice", None).unwrap();
+    assert!(matches!(
+        tracker.record_bytes("alice", 1000, 0, 0, None),
+        QuotaResult::Ok(_)
+    ));
+    tracker.reset_counters(
+        "alice",
+        &[QuotaCounter::DailyBytes, QuotaCounter::HourlyBytes],
+    );
+    let usage = tracker.get_user_usage("alice");
+    assert_eq!(usage.daily_bytes, 0);
+    assert_eq!(usage.hourly_bytes, 0);
+    assert_eq!(usage.monthly_bytes, 1000);
+    assert_eq!(usage.total_bytes, 1000);
+    assert_eq!(usage.daily_connections, 1);
+
+    tracker.reset_counters("alice", &QuotaCounter::ALL);
+    let usage = tracker.get_user_usage("alice");
+    assert_eq!(usage.monthly_bytes, 0);
+    assert_eq!(usage.total_bytes, 0);
+    assert_eq!(usage.monthly_connections, 0);
+    tracker.reset_counters("nonexistent", &QuotaCounter::ALL);
+}
+
+#[test]
+fn granted_extra_raises_limits() {
+    let tracker = QuotaTracker::new(&test_limits());
+    let quota = QuotaConfig {
+        daily_bandwidth_bytes: 1000,
+        daily_connection_limit: 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        tracker.record_bytes("alice", 1000, 0, 0, Some(&quota)),
+        QuotaResult::Exceeded(_)
+    ));
+    tracker.record_connection("alice", Some(&quota)).unwrap();
+    assert!(tracker.record_connection("alice", Some(&quota)).is_err());
+
+    let grant = QuotaGrant {
+        daily_bytes: 500,
+        daily_connections: 1,
+        ..Default::default()
+    };
+    assert_eq!(tracker.grant_user("alice", &grant), grant);
+    assert!(tracker.check_bandwidth_quota("alice", Some(&quota)).is_ok());
+    assert!(matches!(
+        tracker.record_bytes("alice", 400, 0, 0, Some(&quota)),
+        QuotaResult::Ok(_)
+    ));
+    assert!(matches!(
+        tracker.record_bytes("alice", 100, 0, 0, Some(&quota)),
+        QuotaResult::Exceeded(_)
+    ));
+    tracker.record_connection("alice", Some(&quota)).unwrap();
+    assert!(tracker.record_connection("alice", Some(&quota)).is_err());
+
+    // Grants add up, and usage reports them
+    let total = tracker.grant_user("alice", &grant);
+    assert_eq!(total.daily_bytes, 1000);
+    assert_eq!(tracker.get_user_usage("alice").granted, total);
+    // Resetting counters keeps the extras
+    tracker.reset_user("alice");
+    assert_eq!(tracker.get_user_usage("alice").granted, total);
+}
+
+#[test]
+fn quota_grant_rejects_unknown_fields() {
+    let grant: QuotaGrant = serde_json::from_str(r#"{"total_bytes": 1048576}"#).unwrap();
+    assert_eq!(grant.total_bytes, 1_048_576);
+    assert!(!grant.is_empty());
+    assert!(QuotaGrant::default().is_empty());
+    assert!(serde_json::from_str::<QuotaGrant>(r#"{"hourly_bytes": 1}"#).is_err());
+    let counter: QuotaCounter = serde_json::from_str(r#""monthly_connections""#).unwrap();
+    assert_eq!(counter, QuotaCounter::MonthlyConnections);
+}
+
 #[test]
 fn cleanup_stale_removes_inactive_users() {
     let tracker = QuotaTracker::new(&test_limits());
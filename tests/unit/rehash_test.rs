@@ -0,0 +1,200 @@
+use crate::test_support::{app_config_toml, parse_app_config, FAKE_HASH};
+use s5::auth::password::{self, HashSettings};
+use s5::auth::rehash::upgrade_password_hash;
+use s5::auth::AuthService;
+use s5::config::types::{AppConfig, PasswordHashAlgorithm};
+use s5::config::{parse_config, persist};
+use tokio::sync::RwLock;
+
+fn bcrypt_hash(password: &str) -> String {
+    HashSettings {
+        algorithm: PasswordHashAlgorithm::Bcrypt,
+        ..Default::default()
+    }
+    .hash(password)
+    .unwrap()
+}
+
+/// `[security]` with `security`; alice (with a comment after her hash and a
+/// change date) and bob.
+fn config_toml(security: &str, alice_hash: &str, bob_hash: &str) -> String {
+    let alice = format!(
+        "password_changed_at = \"2026-01-01T00:00:00Z\"\n\n\
+         [[users]]\nusername = \"bob\"\npassword_hash = \"{bob_hash}\""
+    );
+    app_config_toml(&format!("[security]\n{security}"), &alice).replace(
+        &format!("\"{FAKE_HASH}\""),
+        &format!("\"{alice_hash}\" # kept comment"),
+    )
+}
+
+fn config(security: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[security]\n{security}"), "")
+}
+
+fn hash_of(auth: &AuthService, username: &str) -> String {
+    auth.user_store()
+        .get(username)
+        .unwrap()
+        .password_hash
+        .clone()
+        .unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Upgrade on login
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn bcrypt_hash_upgraded_and_persisted() {
+    let old = bcrypt_hash("alice-secret");
+    let current = password::hash_password("bob-secret").unwrap();
+    let toml = config_toml("", &old, &current);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, &toml).unwrap();
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+    assert!(auth.read().await.auth_password("alice", "alice-secret"));
+    assert!(auth.read().await.needs_rehash("alice"));
+    assert!(!auth.read().await.needs_rehash("bob"));
+
+    assert!(
+        upgrade_password_hash(&auth, Some(&path), "alice", "alice-secret")
+            .await
+            .unwrap()
+    );
+
+    let guard = auth.read().await;
+    let upgraded = hash_of(&guard, "alice");
+    assert!(upgraded.starts_with("$argon2id$"));
+    assert!(guard.auth_password("alice", "alice-secret"));
+    assert!(!guard.needs_rehash("alice"));
+    // Not a password change
+    assert!(guard
+        .user_store()
+        .get("alice")
+        .unwrap()
+        .password_changed_at
+        .is_some_and(|t| t.to_rfc3339().starts_with("2026-01-01")));
+    drop(guard);
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(written.contains(&upgraded));
+    assert!(written.contains("# kept comment"));
+    assert!(written.contains("2026-01-01T00:00:00Z"));
+    assert!(written.contains(&current));
+
+    // Nothing left to do
+    assert!(
+        !upgrade_password_hash(&auth, Some(&path), "alice", "alice-secret")
+            .await
+            .unwrap()
+    );
+    assert!(
+        !upgrade_password_hash(&auth, Some(&path), "bob", "bob-secret")
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn weaker_argon2_parameters_upgraded_in_memory() {
+    let old = password::hash_password_with_params("alice-secret", 8192, 1, 1).unwrap();
+    let toml = config_toml("argon2_time_cost = 3", &old, &old);
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+
+    assert!(upgrade_password_hash(&auth, None, "alice", "alice-secret")
+        .await
+        .unwrap());
+    let upgraded = hash_of(&*auth.read().await, "alice");
+    assert!(upgraded.contains("m=19456,t=3,p=1"));
+    // bob is untouched until he logs in
+    assert_eq!(hash_of(&*auth.read().await, "bob"), old);
+}
+
+#[tokio::test]
+async fn preferred_algorithm_can_be_bcrypt() {
+    let old = password::hash_password("alice-secret").unwrap();
+    let toml = config_toml("password_hash_algorithm = \"bcrypt\"", &old, &old);
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+
+    assert!(upgrade_password_hash(&auth, None, "alice", "alice-secret")
+        .await
+        .unwrap());
+    let upgraded = hash_of(&*auth.read().await, "alice");
+    assert_eq!(
+        password::hash_algorithm(&upgraded),
+        Some(PasswordHashAlgorithm::Bcrypt)
+    );
+    assert!(auth.read().await.auth_password("alice", "alice-secret"));
+}
+
+#[tokio::test]
+async fn rehash_on_login_can_be_disabled() {
+    let old = bcrypt_hash("alice-secret");
+    let toml = config_toml("rehash_on_login = false", &old, &old);
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+
+    assert!(!auth.read().await.needs_rehash("alice"));
+    assert!(!upgrade_password_hash(&auth, None, "alice", "alice-secret")
+        .await
+        .unwrap());
+    assert_eq!(hash_of(&*auth.read().await, "alice"), old);
+}
+
+#[tokio::test]
+async fn changed_file_entry_is_not_overwritten() {
+    let old = bcrypt_hash("alice-secret");
+    let toml = config_toml("", &old, &old);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    // The password was changed on disk since the server loaded it
+    std::fs::write(&path, toml.replacen(&old, "NEWHASH", 1)).unwrap();
+    let auth = RwLock::new(AuthService::new(&parse_config(&toml).unwrap()).unwrap());
+
+    assert!(
+        !upgrade_password_hash(&auth, Some(&path), "alice", "alice-secret")
+            .await
+            .unwrap()
+    );
+    assert!(std::fs::read_to_string(&path).unwrap().contains("NEWHASH"));
+    assert_eq!(hash_of(&*auth.read().await, "alice"), old);
+}
+
+#[test]
+fn upgrade_user_password_hash_compares_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, config_toml("", "OLD", "OLD")).unwrap();
+
+    assert!(!persist::upgrade_user_password_hash(&path, "alice", "OTHER", "NEW").unwrap());
+    assert!(persist::upgrade_user_password_hash(&path, "alice", "OLD", "NEW").unwrap());
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(written.matches("\"NEW\"").count(), 1);
+    assert_eq!(written.matches("\"OLD\"").count(), 1);
+    assert!(persist::upgrade_user_password_hash(&path, "mallory", "OLD", "NEW").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn hashing_config() {
+    let security = config("").unwrap().security;
+    assert_eq!(
+        security.password_hash_algorithm,
+        PasswordHashAlgorithm::Argon2id
+    );
+    assert!(security.rehash_on_login);
+
+    assert_eq!(
+        config("password_hash_algorithm = \"scrypt\"")
+            .unwrap()
+            .security
+            .password_hash_algorithm,
+        PasswordHashAlgorithm::Scrypt
+    );
+    assert!(config("password_hash_algorithm = \"md5\"").is_err());
+    assert!(config("argon2_time_cost = 0").is_err());
+}
@@ -1,4 +1,12 @@
-use s5::proxy::retry::connect_with_retry;
+use prometheus_client::encoding::text::encode;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::{AddressFamily, TcpConfig};
+use s5::metrics::MetricsRegistry;
+use s5::proxy::connector;
+use s5::proxy::dns_cache::DnsCache;
+use s5::proxy::retry::{connect_with_retry, is_transient, RetryPolicy};
+use std::io::{Error, ErrorKind};
 use std::time::{Duration, Instant};
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
@@ -495,3 +503,159 @@ async fn test_ipv6_failure_retries() {
         elapsed
     );
 }
+
+// -------------------------------------------------------------------------
+// RetryPolicy: jittered backoff used by the proxy connector
+// -------------------------------------------------------------------------
+#[test]
+fn retry_policy_backoff_is_jittered_within_bounds() {
+    let policy = RetryPolicy::new(5, 100);
+    assert_eq!(policy.max_backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.max_backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.max_backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.max_backoff(40), Duration::from_secs(10));
+
+    for retry in 1..=4 {
+        let max = policy.max_backoff(retry);
+        for _ in 0..50 {
+            let delay = policy.backoff(retry);
+            assert!(delay >= max / 2 && delay <= max, "{delay:?} vs {max:?}");
+        }
+    }
+    assert_eq!(RetryPolicy::default().max_retries, 0);
+}
+
+#[test]
+fn only_refused_and_timeout_are_transient() {
+    assert!(is_transient(&Error::from(ErrorKind::ConnectionRefused)));
+    assert!(is_transient(&Error::from(ErrorKind::TimedOut)));
+    assert!(!is_transient(&Error::from(ErrorKind::PermissionDenied)));
+    assert!(!is_transient(&Error::from(ErrorKind::AddrNotAvailable)));
+}
+
+#[test]
+fn retry_policy_follows_user_group_server() {
+    let config = parse_config(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:2222"
+connect_retry = 2
+connect_retry_delay_ms = 250
+
+[[groups]]
+name = "flaky"
+connect_retry = 4
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+
+[[users]]
+username = "bob"
+password_hash = "argon2id-fakehash-for-testing"
+group = "flaky"
+connect_retry_delay_ms = 50
+"##,
+    )
+    .unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    assert_eq!(
+        RetryPolicy::for_user(store.get("alice").unwrap()),
+        RetryPolicy::new(2, 250)
+    );
+    assert_eq!(
+        RetryPolicy::for_user(store.get("bob").unwrap()),
+        RetryPolicy::new(4, 50)
+    );
+}
+
+// -------------------------------------------------------------------------
+// Connector: refused connects are retried and counted
+// -------------------------------------------------------------------------
+async fn closed_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[tokio::test]
+async fn connector_retries_refused_connects() {
+    let port = closed_port().await;
+    let metrics = MetricsRegistry::new();
+    let cache = DnsCache::new(-1, 100);
+
+    let start = Instant::now();
+    let result = connector::connect_with_cache(
+        "127.0.0.1",
+        port,
+        2,
+        false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::new(2, 40),
+        &cache,
+        Some(&metrics),
+    )
+    .await;
+    assert!(result.is_err());
+    // Jittered backoff: at least 20 + 40 ms
+    assert!(start.elapsed() >= Duration::from_millis(60));
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    let line = buffer
+        .lines()
+        .find(|l| l.starts_with("s5_connect_retries_total") && l.contains("refused"))
+        .expect("refused retry series present");
+    assert!(line.ends_with(" 2"), "{line}");
+}
+
+#[tokio::test]
+async fn connector_connects_once_target_comes_up() {
+    let port = closed_port().await;
+    let server = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let _conn = listener.accept().await.unwrap();
+    });
+
+    let cache = DnsCache::new(-1, 100);
+    let result = connector::connect_with_cache(
+        "127.0.0.1",
+        port,
+        2,
+        false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::new(5, 80),
+        &cache,
+        None,
+    )
+    .await;
+    assert!(result.is_ok(), "{:?}", result.err());
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn connector_without_retry_fails_at_once() {
+    let port = closed_port().await;
+    let cache = DnsCache::new(-1, 100);
+    let start = Instant::now();
+    let result = connector::connect_with_cache(
+        "127.0.0.1",
+        port,
+        2,
+        false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &cache,
+        None,
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
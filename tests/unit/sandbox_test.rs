@@ -0,0 +1,224 @@
+use s5::config::parse_config;
+use s5::sandbox::parse_proc_status;
+
+const PROC_STATUS: &str = "\
+Name:\ts5
+Umask:\t0022
+State:\tS (sleeping)
+Uid:\t0\t994\t994\t994
+Gid:\t0\t993\t993\t993
+CapInh:\t0000000000000000
+CapPrm:\t0000000000000000
+CapEff:\t0000000000000000
+CapBnd:\t000001ffffffffff
+CapAmb:\t0000000000000000
+NoNewPrivs:\t1
+Seccomp:\t2
+Seccomp_filters:\t1
+";
+
+// ---------------------------------------------------------------------------
+// Status
+// ---------------------------------------------------------------------------
+
+#[test]
+fn proc_status_parsed() {
+    let status = parse_proc_status(PROC_STATUS);
+    assert_eq!(status.uid, Some(994), "effective UID");
+    assert_eq!(status.gid, Some(993));
+    assert_eq!(status.capabilities.as_deref(), Some("0000000000000000"));
+    assert_eq!(
+        status.capability_bounding_set.as_deref(),
+        Some("000001ffffffffff")
+    );
+    assert_eq!(status.no_new_privs, Some(true));
+    assert_eq!(status.seccomp.as_deref(), Some("filter"));
+    assert!(!status.applied);
+
+    let empty = parse_proc_status("");
+    assert_eq!(empty.uid, None);
+    assert_eq!(empty.seccomp, None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn own_status_read_from_kernel() {
+    let status = s5::sandbox::status();
+    assert!(!status.applied);
+    assert_eq!(status.denied_syscalls, 0);
+    assert!(status.uid.is_some());
+    // Combined over every thread
+    let caps = status.capabilities.unwrap();
+    assert_eq!(caps.len(), 16);
+    assert!(u64::from_str_radix(&caps, 16).is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Seccomp filter
+// ---------------------------------------------------------------------------
+
+/// Run the classic BPF `program` on a `seccomp_data` with `arch` and `nr`.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn run(program: &[libc::sock_filter], arch: u32, nr: u32) -> u32 {
+    let mut acc = 0u32;
+    let mut pc = 0usize;
+    loop {
+        let insn = program[pc];
+        pc += 1;
+        match insn.code {
+            0x20 => acc = if insn.k == 4 { arch } else { nr },
+            0x15 => pc += usize::from(if acc == insn.k { insn.jt } else { insn.jf }),
+            0x35 => pc += usize::from(if acc >= insn.k { insn.jt } else { insn.jf }),
+            0x06 => return insn.k,
+            other => panic!("unexpected BPF opcode {other:#x}"),
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[test]
+fn seccomp_filter_denies_listed_syscalls() {
+    use s5::sandbox::{denied_syscalls, seccomp_filter};
+
+    let arch = if cfg!(target_arch = "x86_64") {
+        0xc000_003e
+    } else {
+        0xc000_00b7
+    };
+    let denied = denied_syscalls();
+    assert!(denied.contains(&libc::SYS_ptrace));
+    assert!(denied.contains(&libc::SYS_init_module));
+    let program = seccomp_filter(&denied);
+
+    let eperm = 0x0005_0000 | libc::EPERM as u32;
+    for &nr in &denied {
+        assert_eq!(run(&program, arch, nr as u32), eperm, "syscall {nr}");
+    }
+    for nr in [
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_accept4,
+        libc::SYS_execve,
+    ] {
+        assert_eq!(run(&program, arch, nr as u32), 0x7fff_0000, "syscall {nr}");
+    }
+    // Foreign architectures are killed
+    assert_eq!(run(&program, 0x4000_0003, 0), 0x8000_0000);
+}
+
+// ---------------------------------------------------------------------------
+// Filesystem confinement
+// ---------------------------------------------------------------------------
+
+#[cfg(target_os = "linux")]
+#[test]
+fn fs_confinement_limits_commands_to_allowed_paths() {
+    use s5::sandbox::FsConfinement;
+    use std::os::unix::process::CommandExt;
+
+    if FsConfinement::new(&[]).is_err() {
+        return; // No Landlock in this kernel
+    }
+    let allowed = tempfile::tempdir().unwrap();
+    let other = tempfile::tempdir().unwrap();
+    std::fs::write(allowed.path().join("in"), "in ").unwrap();
+    std::fs::write(other.path().join("out"), "out").unwrap();
+    let script = format!(
+        "cat {0}/in; cat {1}/out 2>/dev/null || printf denied; printf x > {0}/new",
+        allowed.path().display(),
+        other.path().display()
+    );
+    let confinement = FsConfinement::new(&[allowed.path().to_string_lossy().into_owned()]).unwrap();
+    let mut command = std::process::Command::new("/bin/sh");
+    command.arg("-c").arg(&script);
+    // SAFETY: `restrict_self` only makes system calls
+    unsafe {
+        command.pre_exec(move || confinement.restrict_self());
+    }
+    let output = command.output().unwrap();
+    assert_eq!(output.stdout, b"in denied");
+    assert!(allowed.path().join("new").exists());
+
+    assert!(FsConfinement::new(&["/nonexistent/data".to_string()]).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sandbox_config_validated() {
+    let parse = |sandbox: &str| {
+        parse_config(&format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[sandbox]
+{sandbox}
+
+[[users]]
+username = "test"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+        ))
+    };
+    let config = parse("").unwrap();
+    assert!(!config.sandbox.enabled);
+    assert!(!config.sandbox.seccomp);
+
+    let config = parse("enabled = true\nuser = \"s5\"\ngroup = \"s5\"\nseccomp = true").unwrap();
+    assert_eq!(config.sandbox.user.as_deref(), Some("s5"));
+
+    let err = parse("enabled = true\ngroup = \"s5\"").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("sandbox.group requires sandbox.user"));
+    assert!(parse("enabled = true\nuser = \"\"").is_err());
+    // Not checked while disabled
+    assert!(parse("group = \"s5\"").is_ok());
+}
+
+#[test]
+fn fs_allow_comes_from_the_group() {
+    let parse = |groups: &str| {
+        parse_config(&format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+{groups}
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+group = "netops"
+
+[[users]]
+username = "bob"
+password_hash = "argon2id-fakehash-for-testing"
+"##
+        ))
+    };
+    let config = parse(
+        "[[groups]]\nname = \"base\"\nfs_allow = [\"/srv/data\"]\n\n\
+         [[groups]]\nname = \"netops\"\ninherits = \"base\"",
+    )
+    .unwrap();
+    let auth = s5::auth::AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    assert_eq!(
+        store.get("alice").unwrap().fs_allow.as_deref(),
+        Some(&["/srv/data".to_string()][..])
+    );
+    assert!(store.get("bob").unwrap().fs_allow.is_none());
+
+    let err = parse("[[groups]]\nname = \"netops\"\nfs_allow = [\"srv/data\"]").unwrap_err();
+    assert!(err.to_string().contains("must be an absolute path"));
+}
@@ -0,0 +1,230 @@
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::config::types::{AppConfig, ScriptingConfig};
+use s5::proxy::retry::RetryPolicy;
+use s5::proxy::ProxyEngine;
+use s5::scripting::{ChannelOpen, Hook, Hooks, Labels, Outcome};
+use s5::ssh::handler::classify_relay_error;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+/// Write `source` into `dir`; the scripting config pointing at it.
+fn scripting_config(dir: &TempDir, source: &str, timeout_ms: u64) -> ScriptingConfig {
+    let path = dir.path().join("hooks.lua");
+    std::fs::write(&path, source).unwrap();
+    ScriptingConfig {
+        path,
+        timeout_ms,
+        memory_limit_kb: 4096,
+    }
+}
+
+fn load(source: &str) -> anyhow::Result<Hooks> {
+    let dir = TempDir::new().unwrap();
+    Hooks::load(&scripting_config(&dir, source, 1000))
+}
+
+fn channel() -> ChannelOpen<'static> {
+    ChannelOpen {
+        username: "alice",
+        group: Some("eng"),
+        tags: &[],
+        source_ip: "203.0.113.9",
+        target_host: "db.example.com",
+        target_port: 5432,
+        correlation_id: "c1",
+    }
+}
+
+fn config(dir: &TempDir, source: &str, extra: &str) -> anyhow::Result<AppConfig> {
+    let scripting = scripting_config(dir, source, 1000);
+    parse_config(&format!(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\n\n\
+         [security]\nip_guard_enabled = false\n\n{extra}\n\n\
+         [scripting]\npath = {:?}\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"x\"\n",
+        scripting.path.display().to_string()
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Hooks
+// ---------------------------------------------------------------------------
+
+#[test]
+fn return_values_are_decoded() {
+    let hooks = load(
+        r#"function on_channel_open(e)
+             if e.target_port == 5432 then return false, "no databases for " .. e.username end
+           end"#,
+    )
+    .unwrap();
+    assert_eq!(
+        hooks.call(Hook::ChannelOpen, &channel()).unwrap(),
+        Outcome::Refuse(Some("no databases for alice".to_string()))
+    );
+
+    let hooks =
+        load("function on_channel_open(e) return { team = e.group, port = e.target_port } end")
+            .unwrap();
+    let Outcome::Continue(labels) = hooks.call(Hook::ChannelOpen, &channel()).unwrap() else {
+        panic!("refused");
+    };
+    assert_eq!(labels["team"], "eng");
+    assert_eq!(labels["port"], "5432");
+
+    let hooks = load("function on_channel_open(e) return 42 end").unwrap();
+    assert!(hooks.call(Hook::ChannelOpen, &channel()).is_err());
+}
+
+#[test]
+fn undefined_hooks_continue() {
+    let hooks = load("function on_auth(e) return false end").unwrap();
+    assert!(hooks.defines(Hook::Auth));
+    assert!(!hooks.defines(Hook::ChannelOpen));
+    assert_eq!(
+        hooks.call(Hook::ChannelOpen, &channel()).unwrap(),
+        Outcome::Continue(Labels::new())
+    );
+}
+
+#[test]
+fn scripts_are_sandboxed() {
+    for global in ["io", "os", "require", "load", "dofile", "pcall", "debug"] {
+        let hooks = load(&format!(
+            "function on_channel_open(e) return {{ present = tostring({global} ~= nil) }} end"
+        ))
+        .unwrap();
+        assert_eq!(
+            hooks.call(Hook::ChannelOpen, &channel()).unwrap(),
+            Outcome::Continue(Labels::from([("present".to_string(), "false".to_string())])),
+            "{global}"
+        );
+    }
+}
+
+#[test]
+fn runaway_scripts_are_stopped() {
+    let dir = TempDir::new().unwrap();
+    let hooks = Hooks::load(&scripting_config(
+        &dir,
+        "function on_channel_open(e) while true do end end",
+        20,
+    ))
+    .unwrap();
+    let started = std::time::Instant::now();
+    let err = hooks.call(Hook::ChannelOpen, &channel()).unwrap_err();
+    assert_eq!(err.to_string(), "timed out");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    let hooks = load(
+        r#"function on_channel_open(e)
+             local t = {}
+             while true do t[#t + 1] = string.rep("x", 4096) end
+           end"#,
+    )
+    .unwrap();
+    let err = hooks.call(Hook::ChannelOpen, &channel()).unwrap_err();
+    assert_eq!(err.to_string(), "out of memory");
+}
+
+#[test]
+fn config_validation() {
+    let dir = TempDir::new().unwrap();
+    assert!(config(&dir, "function on_auth(e) end", "").is_ok());
+    let err = config(&dir, "function on_auth(e", "").unwrap_err();
+    assert!(format!("{err:#}").contains("scripting"), "{err:#}");
+}
+
+#[test]
+fn labels_are_serialized_on_the_event() {
+    let peer = "203.0.113.9:50000".parse().unwrap();
+    let event = AuditEvent::auth_success_with_cid("alice", &peer, "password", "c1");
+    let json = serde_json::to_value(&event).unwrap();
+    assert!(json.get("labels").is_none());
+
+    let event = event.with_labels(Labels::from([("team".to_string(), "eng".to_string())]));
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["labels"]["team"], "eng");
+}
+
+// ---------------------------------------------------------------------------
+// Engine
+// ---------------------------------------------------------------------------
+
+async fn connect(source: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = TempDir::new().unwrap();
+    let cfg = config(&dir, source, "").unwrap();
+    let auth = AuthService::new(&cfg).unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+    let user = auth.user_store().get("alice").unwrap();
+    engine
+        .connect_for_socks(
+            &user.username,
+            "127.0.0.1",
+            port,
+            &user.acl,
+            &[],
+            None,
+            user.group.as_deref(),
+            &user.tags,
+            "203.0.113.9",
+            0,
+            None,
+            RetryPolicy::default(),
+            "c1",
+        )
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn engine_asks_on_channel_open_before_connecting() {
+    connect("function on_channel_open(e) return true end")
+        .await
+        .unwrap();
+
+    let err = connect("function on_channel_open(e) return false, 'closed' end")
+        .await
+        .unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+}
+
+#[tokio::test]
+async fn failing_hooks_deny_the_channel() {
+    let err = connect("function on_channel_open(e) error('boom') end")
+        .await
+        .unwrap_err();
+    assert_eq!(classify_relay_error(&err), "acl_denied");
+}
+
+#[tokio::test]
+async fn session_close_labels_the_event() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config(
+        &dir,
+        "function on_session_close(e) return { big = tostring(e.bytes_uploaded > 100) } end",
+        "",
+    )
+    .unwrap();
+    let engine = ProxyEngine::new(Arc::new(cfg), Arc::new(AuditLogger::new(None, 0, 0, None)));
+    let peer = "203.0.113.9:50000".parse().unwrap();
+    let event = AuditEvent::proxy_complete_with_cid(
+        "alice",
+        "db.example.com",
+        5432,
+        1000,
+        10,
+        5,
+        &peer,
+        None,
+        "c1",
+    );
+    let json = serde_json::to_value(engine.label_session_close(event).await).unwrap();
+    assert_eq!(json["labels"]["big"], "true");
+}
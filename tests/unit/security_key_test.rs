@@ -0,0 +1,100 @@
+use crate::test_support::parse_app_config;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use russh::keys::{Algorithm, PrivateKey, PublicKey, PublicKeyBase64};
+use s5::auth::pubkey;
+use s5::auth::AuthService;
+
+/// An `sk-ssh-ed25519@openssh.com` authorized_keys line, as written by
+/// `ssh-keygen -t ed25519-sk`.
+fn sk_ed25519_line() -> String {
+    let mut blob = Vec::new();
+    let point = rand::random::<[u8; 32]>();
+    for field in [
+        b"sk-ssh-ed25519@openssh.com".as_slice(),
+        point.as_slice(),
+        b"ssh:".as_slice(),
+    ] {
+        blob.extend((field.len() as u32).to_be_bytes());
+        blob.extend(field);
+    }
+    format!(
+        "sk-ssh-ed25519@openssh.com {} alice@yubikey",
+        STANDARD.encode(blob)
+    )
+}
+
+fn ed25519_line() -> String {
+    let private = PrivateKey::random(&mut rand::rngs::OsRng, Algorithm::Ed25519).unwrap();
+    let public = PublicKey::from(&private);
+    format!("ssh-ed25519 {} alice@laptop", public.public_key_base64())
+}
+
+fn auth(sk: &str, plain: &str, extra: &str) -> AuthService {
+    let alice = format!("authorized_keys = ['{sk}', '{plain}']\n{extra}");
+    let cfg = parse_app_config("", &alice).unwrap();
+    AuthService::new(&cfg).unwrap()
+}
+
+#[test]
+fn security_keys_are_parsed() {
+    let line = sk_ed25519_line();
+    let key = pubkey::parse_authorized_key(&line).unwrap();
+    assert_eq!(key.algorithm().as_str(), "sk-ssh-ed25519@openssh.com");
+    assert!(pubkey::is_security_key(key.algorithm().as_str()));
+    assert!(pubkey::is_security_key(
+        "sk-ecdsa-sha2-nistp256@openssh.com"
+    ));
+    assert!(!pubkey::is_security_key("ssh-ed25519"));
+    assert!(!pubkey::is_security_key("ecdsa-sha2-nistp256"));
+
+    // Options work in front of security keys too
+    let dated = format!("expiry-time=\"20990101\" {line}");
+    assert_eq!(pubkey::parse_authorized_key(&dated).unwrap(), key);
+}
+
+#[test]
+fn security_keys_authenticate() {
+    let (sk, plain) = (sk_ed25519_line(), ed25519_line());
+    let auth = auth(&sk, &plain, "");
+    let sk_key = pubkey::parse_authorized_key(&sk).unwrap();
+    let plain_key = pubkey::parse_authorized_key(&plain).unwrap();
+    assert!(auth.auth_publickey("alice", &sk_key));
+    assert!(auth.auth_publickey("alice", &plain_key));
+    assert!(!auth.user_store().get("alice").unwrap().require_security_key);
+}
+
+#[test]
+fn require_security_key_refuses_other_keys() {
+    let (sk, plain) = (sk_ed25519_line(), ed25519_line());
+    let auth = auth(&sk, &plain, "require_security_key = true");
+    let sk_key = pubkey::parse_authorized_key(&sk).unwrap();
+    let plain_key = pubkey::parse_authorized_key(&plain).unwrap();
+    assert!(auth.auth_publickey("alice", &sk_key));
+    assert!(!auth.auth_publickey("alice", &plain_key));
+
+    let user = auth.user_store().get("alice").unwrap();
+    assert!(user.accepts_key_type("sk-ecdsa-sha2-nistp256@openssh.com"));
+    assert!(!user.accepts_key_type("ssh-rsa"));
+}
+
+#[test]
+fn security_key_flags() {
+    let (sk, plain) = (sk_ed25519_line(), ed25519_line());
+    let user = auth(&sk, &plain, "")
+        .user_store()
+        .get("alice")
+        .cloned()
+        .unwrap();
+    assert!(user.accepts_security_key_flags(pubkey::SK_USER_PRESENT));
+    assert!(!user.accepts_security_key_flags(pubkey::SK_USER_VERIFIED));
+    assert!(!user.accepts_security_key_flags(0));
+
+    let user = auth(&sk, &plain, "require_security_key = true")
+        .user_store()
+        .get("alice")
+        .cloned()
+        .unwrap();
+    assert!(user.accepts_security_key_flags(pubkey::SK_USER_PRESENT | pubkey::SK_USER_VERIFIED));
+    assert!(!user.accepts_security_key_flags(pubkey::SK_USER_PRESENT));
+}
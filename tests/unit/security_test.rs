@@ -123,7 +123,7 @@ fn test_ban_manager_toctou_fix() {
     let ip: IpAddr = "1.2.3.4".parse().unwrap();
 
     // Trigger a ban
-    mgr.record_failure(&ip);
+    mgr.record_failure(&ip, None);
     assert!(mgr.is_banned(&ip));
 
     // Wait for ban to expire
@@ -155,12 +155,12 @@ ban_duration = 60
     assert!(!security.is_banned(&ip));
 
     // Record failures below threshold
-    security.record_auth_failure(&ip);
-    security.record_auth_failure(&ip);
+    security.record_auth_failure(&ip, None);
+    security.record_auth_failure(&ip, None);
     assert!(!security.is_banned(&ip));
 
     // Third failure should trigger ban
-    security.record_auth_failure(&ip);
+    security.record_auth_failure(&ip, None);
     assert!(security.is_banned(&ip));
 }
 
@@ -199,9 +199,9 @@ fn test_ban_manager_whitelist_never_bans() {
     let ip: IpAddr = "127.0.0.1".parse().unwrap();
 
     // Even with threshold of 1, whitelisted IP should never be banned
-    mgr.record_failure(&ip);
-    mgr.record_failure(&ip);
-    mgr.record_failure(&ip);
+    mgr.record_failure(&ip, None);
+    mgr.record_failure(&ip, None);
+    mgr.record_failure(&ip, None);
 
     assert!(!mgr.is_banned(&ip));
 }
@@ -220,20 +220,20 @@ fn test_ban_whitelist_cidr_range() {
     let ip3: IpAddr = "11.0.0.1".parse().unwrap();
 
     // IPs within the CIDR range should never be banned
-    mgr.record_failure(&ip1);
+    mgr.record_failure(&ip1, None);
     assert!(
         !mgr.is_banned(&ip1),
         "10.1.2.3 should be whitelisted by 10.0.0.0/8"
     );
 
-    mgr.record_failure(&ip2);
+    mgr.record_failure(&ip2, None);
     assert!(
         !mgr.is_banned(&ip2),
         "10.255.255.254 should be whitelisted by 10.0.0.0/8"
     );
 
     // IP outside the range should be banned after 1 failure
-    mgr.record_failure(&ip3);
+    mgr.record_failure(&ip3, None);
     assert!(mgr.is_banned(&ip3), "11.0.0.1 should NOT be whitelisted");
 }
 
@@ -251,19 +251,19 @@ fn test_ban_whitelist_mixed_ip_and_cidr() {
     let private: IpAddr = "192.168.50.100".parse().unwrap();
     let public: IpAddr = "8.8.8.8".parse().unwrap();
 
-    mgr.record_failure(&loopback);
+    mgr.record_failure(&loopback, None);
     assert!(
         !mgr.is_banned(&loopback),
         "127.0.0.1 whitelisted as single IP"
     );
 
-    mgr.record_failure(&private);
+    mgr.record_failure(&private, None);
     assert!(
         !mgr.is_banned(&private),
         "192.168.50.100 whitelisted by CIDR"
     );
 
-    mgr.record_failure(&public);
+    mgr.record_failure(&public, None);
     assert!(mgr.is_banned(&public), "8.8.8.8 not whitelisted");
 }
 
@@ -274,13 +274,13 @@ fn test_ban_whitelist_cidr_ipv6() {
     let ip_in: IpAddr = "fd12:3456:789a::1".parse().unwrap();
     let ip_out: IpAddr = "2001:db8::1".parse().unwrap();
 
-    mgr.record_failure(&ip_in);
+    mgr.record_failure(&ip_in, None);
     assert!(
         !mgr.is_banned(&ip_in),
         "fd12::1 should be whitelisted by fd00::/8"
     );
 
-    mgr.record_failure(&ip_out);
+    mgr.record_failure(&ip_out, None);
     assert!(
         mgr.is_banned(&ip_out),
         "2001:db8::1 should NOT be whitelisted"
@@ -305,13 +305,13 @@ fn test_ban_whitelist_invalid_entries_skipped() {
     let ip_in: IpAddr = "192.168.1.50".parse().unwrap();
     let ip_out: IpAddr = "192.168.2.50".parse().unwrap();
 
-    mgr.record_failure(&ip_in);
+    mgr.record_failure(&ip_in, None);
     assert!(
         !mgr.is_banned(&ip_in),
         "192.168.1.50 whitelisted despite invalid entries"
     );
 
-    mgr.record_failure(&ip_out);
+    mgr.record_failure(&ip_out, None);
     assert!(mgr.is_banned(&ip_out), "192.168.2.50 not in whitelist");
 }
 
@@ -333,11 +333,11 @@ ban_whitelist = ["10.0.0.0/8"]
     let normal_ip: IpAddr = "203.0.113.50".parse().unwrap();
 
     // Whitelisted IP should never be banned even after failures
-    security.record_auth_failure(&whitelisted_ip);
-    security.record_auth_failure(&whitelisted_ip);
+    security.record_auth_failure(&whitelisted_ip, None);
+    security.record_auth_failure(&whitelisted_ip, None);
     assert!(!security.is_banned(&whitelisted_ip));
 
     // Non-whitelisted IP should be banned after threshold
-    security.record_auth_failure(&normal_ip);
+    security.record_auth_failure(&normal_ip, None);
     assert!(security.is_banned(&normal_ip));
 }
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::proxy::connector::{self, IpGuardBlocked};
+use s5::security::timeline::{SecurityKind, SecurityTimeline, MAX_EVENTS, MAX_POINTS};
+use std::net::SocketAddr;
+
+fn source() -> SocketAddr {
+    "203.0.113.7:50000".parse().unwrap()
+}
+
+fn failure(user: &str) -> AuditEvent {
+    AuditEvent::auth_failure(user, &source(), "password")
+}
+
+fn ip_guard_deny() -> AuditEvent {
+    AuditEvent::acl_deny(
+        "alice",
+        "internal.example",
+        80,
+        Some("10.0.0.5".to_string()),
+        "203.0.113.7",
+        Some("private-10".to_string()),
+        "ip_guard",
+    )
+}
+
+/// 12:00:00 UTC on a fixed day
+fn noon() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// Classification
+// ---------------------------------------------------------------------------
+
+#[test]
+fn kinds_of_audit_events() {
+    assert_eq!(
+        SecurityKind::of(&failure("bob")),
+        Some(SecurityKind::AuthFailure)
+    );
+    let ip = source().ip();
+    assert_eq!(
+        SecurityKind::of(&AuditEvent::ban_created(&ip, 300)),
+        Some(SecurityKind::Ban)
+    );
+    assert_eq!(
+        SecurityKind::of(&ip_guard_deny()),
+        Some(SecurityKind::IpGuard)
+    );
+
+    // Other ACL denials and unrelated events stay off the timeline
+    let acl = AuditEvent::acl_deny("alice", "x.example", 22, None, "203.0.113.7", None, "deny");
+    assert_eq!(SecurityKind::of(&acl), None);
+    assert_eq!(SecurityKind::of(&AuditEvent::ban_expired(&ip)), None);
+    assert_eq!(
+        SecurityKind::of(&AuditEvent::auth_success("bob", &source(), "password")),
+        None
+    );
+
+    let names: Vec<_> = SecurityKind::ALL.iter().map(|k| k.as_str()).collect();
+    assert_eq!(names, ["auth_failure", "ban", "ip_guard"]);
+    assert_eq!(
+        serde_json::to_value(SecurityKind::IpGuard).unwrap(),
+        "ip_guard"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Buckets
+// ---------------------------------------------------------------------------
+
+#[test]
+fn buckets_are_dense_and_counted() {
+    let timeline = SecurityTimeline::new();
+    let now = noon() + Duration::seconds(30);
+    timeline.record_at(
+        SecurityKind::AuthFailure,
+        noon() - Duration::minutes(2),
+        &failure("a"),
+    );
+    timeline.record_at(
+        SecurityKind::AuthFailure,
+        noon() - Duration::seconds(90),
+        &failure("b"),
+    );
+    timeline.record_at(
+        SecurityKind::IpGuard,
+        noon() + Duration::seconds(5),
+        &ip_guard_deny(),
+    );
+
+    let buckets = timeline.buckets(now, 600, 60);
+    assert_eq!(buckets.len(), 10);
+    // Oldest first, the last bucket holds `now`
+    assert_eq!(buckets.last().unwrap().start, noon());
+    assert!(buckets
+        .windows(2)
+        .all(|w| w[1].start - w[0].start == Duration::seconds(60)));
+
+    let at = |t: DateTime<Utc>| buckets.iter().find(|b| b.start == t).unwrap();
+    assert_eq!(at(noon() - Duration::minutes(2)).auth_failure, 2);
+    assert_eq!(at(noon()).ip_guard, 1);
+    assert_eq!(buckets.iter().map(|b| b.ban).sum::<u64>(), 0);
+
+    // Wider steps add up the minute buckets
+    let coarse = timeline.buckets(now, 600, 300);
+    assert_eq!(coarse.len(), 2);
+    assert_eq!(coarse.iter().map(|b| b.auth_failure).sum::<u64>(), 2);
+}
+
+#[test]
+fn step_keeps_point_count_bounded() {
+    assert_eq!(SecurityTimeline::step_for(3600), 60);
+    assert_eq!(SecurityTimeline::step_for(6 * 3600), 180);
+    assert_eq!(SecurityTimeline::step_for(24 * 3600), 720);
+    for window in [60, 3600, 6 * 3600, 24 * 3600] {
+        let step = SecurityTimeline::step_for(window);
+        assert_eq!(step % 60, 0);
+        let points = SecurityTimeline::new().buckets(noon(), window, step).len() as i64;
+        assert!(
+            points <= MAX_POINTS,
+            "{window}s window gives {points} points"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Drill-down
+// ---------------------------------------------------------------------------
+
+#[test]
+fn events_filtered_by_kind_and_range() {
+    let timeline = SecurityTimeline::new();
+    for (i, user) in ["a", "b", "c"].iter().enumerate() {
+        let at = noon() + Duration::seconds(10 * i as i64);
+        timeline.record_at(SecurityKind::AuthFailure, at, &failure(user));
+    }
+    timeline.record_at(SecurityKind::IpGuard, noon(), &ip_guard_deny());
+    let to = noon() + Duration::minutes(1);
+
+    let all = timeline.events(None, noon(), to, 100);
+    assert_eq!(all.len(), 4);
+    let failures = timeline.events(Some(SecurityKind::AuthFailure), noon(), to, 100);
+    let users: Vec<_> = failures
+        .iter()
+        .map(|e| serde_json::to_value(e).unwrap()["username"].clone())
+        .collect();
+    // Newest first
+    assert_eq!(users, ["c", "b", "a"]);
+
+    assert_eq!(
+        timeline
+            .events(Some(SecurityKind::AuthFailure), noon(), to, 1)
+            .len(),
+        1
+    );
+    // `to` is exclusive
+    let early = timeline.events(None, noon(), noon() + Duration::seconds(10), 100);
+    assert_eq!(early.len(), 2);
+    assert!(timeline
+        .events(Some(SecurityKind::Ban), noon(), to, 100)
+        .is_empty());
+}
+
+#[test]
+fn old_and_excess_entries_pruned() {
+    let timeline = SecurityTimeline::new();
+    let old = noon() - Duration::hours(25);
+    timeline.record_at(SecurityKind::Ban, old, &failure("old"));
+    timeline.record_at(SecurityKind::AuthFailure, noon(), &failure("new"));
+    assert!(timeline.events(None, old, noon(), 10).is_empty());
+    let day = timeline.buckets(noon(), 24 * 3600, SecurityTimeline::step_for(24 * 3600));
+    assert_eq!(day.iter().map(|b| b.ban).sum::<u64>(), 0);
+
+    for i in 0..MAX_EVENTS + 10 {
+        let at = noon() + Duration::milliseconds(i as i64);
+        timeline.record_at(SecurityKind::AuthFailure, at, &failure("x"));
+    }
+    let kept = timeline.events(None, noon(), noon() + Duration::minutes(1), usize::MAX);
+    assert_eq!(kept.len(), MAX_EVENTS);
+    // Bucket counts are not capped
+    let now = timeline.buckets(noon(), 60, 60);
+    assert_eq!(now[0].auth_failure, MAX_EVENTS as u64 + 11);
+}
+
+// ---------------------------------------------------------------------------
+// Sources
+// ---------------------------------------------------------------------------
+
+#[test]
+fn audit_logger_feeds_the_timeline() {
+    let logger = AuditLogger::new_noop();
+    logger.log_event(failure("bob"));
+    logger.log_event(AuditEvent::auth_success("bob", &source(), "password"));
+    logger.log_event(ip_guard_deny());
+
+    let now = Utc::now();
+    let events = logger.security_timeline().events(
+        None,
+        now - Duration::minutes(1),
+        now + Duration::minutes(1),
+        10,
+    );
+    let types: Vec<_> = events.iter().map(|e| e.event_type()).collect();
+    assert_eq!(types, ["acl.deny", "auth.failure"]);
+}
+
+#[tokio::test]
+async fn ip_guard_block_is_typed() {
+    let err = connector::resolve_and_check("127.0.0.1", 80, 5, true)
+        .await
+        .unwrap_err();
+    // Message unchanged for logs and clients
+    assert_eq!(
+        err.to_string(),
+        "all resolved addresses for 127.0.0.1 are blocked by ip_guard"
+    );
+    let blocked = err.downcast_ref::<IpGuardBlocked>().unwrap();
+    assert_eq!(blocked.host, "127.0.0.1");
+    assert_eq!(blocked.ip.to_string(), "127.0.0.1");
+    assert_eq!(blocked.range, "loopback");
+}
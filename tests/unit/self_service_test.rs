@@ -0,0 +1,245 @@
+use crate::test_support::{app_config_toml, FAKE_HASH};
+use s5::auth::password;
+use s5::auth::self_service::{self, PasswordChangeError};
+use s5::auth::AuthService;
+use s5::config::persist;
+use tokio::sync::RwLock;
+
+/// alice (with a shell) and bob sharing `hash`, under an operator comment.
+fn config_toml(hash: &str) -> String {
+    let toml = app_config_toml("", "allow_shell = true");
+    format!(
+        "# operator comment that must survive a write-back{toml}\n\
+         [[users]]\nusername = \"bob\"\npassword_hash = \"{FAKE_HASH}\"\n"
+    )
+    .replace(FAKE_HASH, hash)
+}
+
+fn auth_for(toml_str: &str) -> RwLock<AuthService> {
+    let config = s5::config::parse_config(toml_str).unwrap();
+    RwLock::new(AuthService::new(&config).unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Policy
+// ---------------------------------------------------------------------------
+
+#[test]
+fn validate_rejects_short_password() {
+    let err = self_service::validate_new_password("oldpassword", "short").unwrap_err();
+    assert!(matches!(err, PasswordChangeError::TooShort));
+    assert!(err.is_client_error());
+}
+
+#[test]
+fn validate_rejects_unchanged_password() {
+    let err = self_service::validate_new_password("samepassword", "samepassword").unwrap_err();
+    assert!(matches!(err, PasswordChangeError::Unchanged));
+}
+
+#[test]
+fn validate_rejects_overlong_password() {
+    let long = "x".repeat(self_service::MAX_PASSWORD_LENGTH + 1);
+    let err = self_service::validate_new_password("oldpassword", &long).unwrap_err();
+    assert!(matches!(err, PasswordChangeError::TooLong));
+}
+
+#[test]
+fn validate_accepts_good_password() {
+    assert!(self_service::validate_new_password("oldpassword", "n3w-passw0rd").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_user_field_preserves_comments_and_other_users() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let content = config_toml(&hash);
+    let updated = persist::set_user_field(&content, "alice", "password_hash", "NEWHASH").unwrap();
+
+    assert!(updated.contains("# operator comment that must survive a write-back"));
+    assert!(updated.contains("password_hash = \"NEWHASH\""));
+    // bob keeps the old hash
+    assert_eq!(updated.matches(&hash).count(), 1);
+}
+
+#[test]
+fn set_user_field_unknown_user_fails() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let err =
+        persist::set_user_field(&config_toml(&hash), "mallory", "password_hash", "x").unwrap_err();
+    assert!(err.to_string().contains("mallory"));
+}
+
+// ---------------------------------------------------------------------------
+// End-to-end change
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn change_password_in_memory() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let auth = auth_for(&config_toml(&hash));
+
+    self_service::change_password(&auth, None, "alice", "oldpassword", "n3w-passw0rd")
+        .await
+        .unwrap();
+
+    let guard = auth.read().await;
+    assert!(guard.auth_password("alice", "n3w-passw0rd"));
+    assert!(!guard.auth_password("alice", "oldpassword"));
+    // Other users untouched
+    assert!(guard.auth_password("bob", "oldpassword"));
+}
+
+#[tokio::test]
+async fn change_password_wrong_current_rejected() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let auth = auth_for(&config_toml(&hash));
+
+    let err = self_service::change_password(&auth, None, "alice", "wrong", "n3w-passw0rd")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PasswordChangeError::InvalidCurrentPassword));
+    assert!(auth.read().await.auth_password("alice", "oldpassword"));
+}
+
+#[tokio::test]
+async fn change_password_unknown_user_rejected() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let auth = auth_for(&config_toml(&hash));
+
+    let err = self_service::change_password(&auth, None, "mallory", "oldpassword", "n3w-pass")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PasswordChangeError::InvalidCurrentPassword));
+}
+
+#[tokio::test]
+async fn change_password_persists_to_config_file() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, config_toml(&hash)).unwrap();
+    let auth = auth_for(&config_toml(&hash));
+
+    self_service::change_password(&auth, Some(&path), "alice", "oldpassword", "n3w-passw0rd")
+        .await
+        .unwrap();
+
+    // A reload from disk must keep the new password
+    let reloaded = s5::config::load_config(&path).unwrap();
+    let fresh = AuthService::new(&reloaded).unwrap();
+    assert!(fresh.auth_password("alice", "n3w-passw0rd"));
+    assert!(fresh.auth_password("bob", "oldpassword"));
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1, "temp file left behind");
+}
+
+#[tokio::test]
+async fn change_password_persist_failure_keeps_old_password() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.toml");
+    let auth = auth_for(&config_toml(&hash));
+
+    let err = self_service::change_password(
+        &auth,
+        Some(&missing),
+        "alice",
+        "oldpassword",
+        "n3w-passw0rd",
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, PasswordChangeError::Persist(_)));
+    assert!(!err.is_client_error());
+    assert!(auth.read().await.auth_password("alice", "oldpassword"));
+}
+
+#[test]
+fn concurrent_writes_keep_every_edit() {
+    let mut toml = config_toml("x");
+    for i in 0..8 {
+        toml.push_str(&format!(
+            "\n[[users]]\nusername = \"user{i}\"\npassword_hash = \"x\"\n"
+        ));
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, toml).unwrap();
+
+    std::thread::scope(|scope| {
+        for i in 0..8 {
+            let path = &path;
+            scope.spawn(move || {
+                for round in 0..5 {
+                    let hash = format!("hash-{i}-{round}");
+                    persist::set_user_password_hash(path, &format!("user{i}"), &hash).unwrap();
+                }
+            });
+        }
+        scope.spawn(|| {
+            for round in 0..5 {
+                let key = format!("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJ{round} alice");
+                persist::add_user_authorized_key(&path, "alice", &key).unwrap();
+            }
+        });
+    });
+
+    let config = s5::config::load_config(&path).unwrap();
+    for i in 0..8 {
+        let user = config
+            .users
+            .iter()
+            .find(|u| u.username == format!("user{i}"))
+            .unwrap();
+        assert_eq!(
+            user.password_hash.as_deref(),
+            Some(format!("hash-{i}-4").as_str())
+        );
+    }
+    let alice = config.users.iter().find(|u| u.username == "alice").unwrap();
+    assert_eq!(alice.authorized_keys.len(), 5);
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1, "temp file left behind");
+}
+
+#[test]
+fn edit_by_hand_during_write_back_is_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, config_toml("x")).unwrap();
+    let by_hand = format!("# edited by hand\n{}", config_toml("y"));
+
+    let err = persist::update_config(&path, |content| {
+        std::fs::write(&path, &by_hand).unwrap();
+        persist::set_user_field(content, "alice", "password_hash", "z").map(Some)
+    })
+    .unwrap_err();
+    assert!(matches!(err, persist::UpdateError::Changed(_)));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), by_hand);
+}
+
+// ---------------------------------------------------------------------------
+// Personal API tokens
+// ---------------------------------------------------------------------------
+
+#[test]
+fn api_token_auth() {
+    let hash = password::hash_password("oldpassword").unwrap();
+    let token_hash = password::hash_password("tok-123").unwrap();
+    let toml_str = config_toml(&hash).replace(
+        "allow_shell = true",
+        &format!("allow_shell = true\napi_token_hash = \"{token_hash}\""),
+    );
+    let config = s5::config::parse_config(&toml_str).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+
+    assert!(auth.auth_api_token("alice", "tok-123"));
+    assert!(!auth.auth_api_token("alice", "wrong"));
+    // bob has no token configured
+    assert!(!auth.auth_api_token("bob", "tok-123"));
+    assert!(!auth.auth_api_token("nobody", "tok-123"));
+}
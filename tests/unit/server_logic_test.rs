@@ -14,6 +14,7 @@ use s5::proxy::ProxyEngine;
 use std::sync::Arc;
 
 const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+const ACCOUNT_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaGhhc2g";
 
 fn make_engine_with_limits(max_global: u32, max_per_user: u32) -> ProxyEngine {
     let toml = format!(
@@ -141,6 +142,322 @@ password_hash = "{FAKE_HASH}"
     assert!(err.to_string().contains("too short"));
 }
 
+#[test]
+fn api_allowed_origins_must_be_bare_origins() {
+    let make = |origin: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[api]
+allowed_origins = ["{origin}"]
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    assert!(parse_config(&make("https://dash.example.com")).is_ok());
+    let err = parse_config(&make("*")).unwrap_err();
+    assert!(err.to_string().contains("api.allowed_origins"));
+    assert!(parse_config(&make("https://dash.example.com/admin")).is_err());
+}
+
+#[test]
+fn api_accounts_parse_roles_and_validate() {
+    let make = |accounts: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+{accounts}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let cfg = parse_config(&make(&format!(
+        r##"
+[[api.accounts]]
+username = "ops"
+password_hash = "{ACCOUNT_HASH}"
+role = "operator"
+
+[[api.accounts]]
+username = "ro"
+password_hash = "{ACCOUNT_HASH}"
+"##
+    )))
+    .unwrap();
+    assert_eq!(cfg.api.accounts.len(), 2);
+    assert_eq!(cfg.api.accounts[0].role, DashboardRole::Operator);
+    assert_eq!(
+        cfg.api.accounts[1].role,
+        DashboardRole::Viewer,
+        "role defaults to viewer"
+    );
+    assert!(!format!("{:?}", cfg.api).contains(ACCOUNT_HASH));
+    assert_eq!(redact_config(&cfg).api.accounts[0].password_hash, "***");
+
+    let dup = make(&format!(
+        r##"
+[[api.accounts]]
+username = "ops"
+password_hash = "{ACCOUNT_HASH}"
+
+[[api.accounts]]
+username = "ops"
+password_hash = "{ACCOUNT_HASH}"
+"##
+    ));
+    assert!(parse_config(&dup)
+        .unwrap_err()
+        .to_string()
+        .contains("duplicate username"));
+
+    let plain = make(
+        r##"
+[[api.accounts]]
+username = "ops"
+password_hash = "hunter2"
+"##,
+    );
+    assert!(parse_config(&plain).is_err());
+
+    let bad_role = make(&format!(
+        r##"
+[[api.accounts]]
+username = "ops"
+password_hash = "{ACCOUNT_HASH}"
+role = "root"
+"##
+    ));
+    assert!(parse_config(&bad_role).is_err());
+}
+
+#[test]
+fn audit_chain_requires_log_path_and_key_requires_chain() {
+    let make = |logging: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[logging]
+{logging}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let ok = parse_config(&make(
+        r#"audit_log_path = "/tmp/audit.json"
+audit_chain = true
+audit_signing_key = "0123456789abcdef""#,
+    ))
+    .unwrap();
+    assert_eq!(ok.logging.audit_checkpoint_interval, 300);
+    assert!(!format!("{:?}", ok.logging).contains("0123456789abcdef"));
+    assert_eq!(
+        redact_config(&ok).logging.audit_signing_key.as_deref(),
+        Some("***")
+    );
+
+    assert!(parse_config(&make("audit_chain = true")).is_err());
+    assert!(parse_config(&make(
+        r#"audit_log_path = "/tmp/audit.json"
+audit_signing_key = "0123456789abcdef""#
+    ))
+    .is_err());
+    assert!(parse_config(&make(
+        r#"audit_log_path = "/tmp/audit.json"
+audit_chain = true
+audit_signing_key = "short""#
+    ))
+    .is_err());
+}
+
+#[test]
+fn flow_log_defaults_and_validation() {
+    let make = |flows: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[logging.flows]
+{flows}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make("enabled = true")).unwrap();
+    assert!(config.logging.flows.enabled);
+    assert_eq!(
+        config.logging.flows.path,
+        std::path::PathBuf::from("flows.db")
+    );
+    assert_eq!(config.logging.flows.retention_days, 30);
+
+    assert!(!parse_config(&make("")).unwrap().logging.flows.enabled);
+    assert!(parse_config(&make("enabled = true\npath = \"\"")).is_err());
+}
+
+#[test]
+fn ipfix_requires_collector_address() {
+    let make = |ipfix: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[logging.ipfix]
+{ipfix}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make(
+        r#"enabled = true
+collector = "collector.example.net:4739""#,
+    ))
+    .unwrap();
+    assert_eq!(config.logging.ipfix.observation_domain_id, 1);
+    assert_eq!(config.logging.ipfix.enterprise_number, 32473);
+    assert_eq!(config.logging.ipfix.template_refresh_secs, 600);
+    assert!(parse_config(&make(
+        r#"enabled = true
+collector = "[2001:db8::5]:4739""#
+    ))
+    .is_ok());
+
+    for bad in [
+        "enabled = true",
+        "enabled = true\ncollector = \"collector.example.net\"",
+        "enabled = true\ncollector = \":4739\"",
+        "enabled = true\ncollector = \"c:4739\"\ntemplate_refresh_secs = 0",
+        "enabled = true\ncollector = \"c:4739\"\nenterprise_number = 0",
+    ] {
+        assert!(parse_config(&make(bad)).is_err(), "{}", bad);
+    }
+    // Disabled: collector not required
+    assert!(parse_config(&make("enabled = false")).is_ok());
+}
+
+#[test]
+fn threat_intel_validation() {
+    let make = |ti: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+{ti}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make(
+        r#"[threat_intel.crowdsec]
+url = "http://127.0.0.1:8080"
+api_key = "bouncer-key"
+
+[[threat_intel.denylists]]
+name = "spamhaus-drop"
+url = "https://www.spamhaus.org/drop/drop.txt""#,
+    ))
+    .unwrap();
+    assert!(config.threat_intel.is_enabled());
+    assert_eq!(config.threat_intel.refresh_interval, 60);
+    assert!(!format!("{:?}", config.threat_intel).contains("bouncer-key"));
+    assert!(!parse_config(&make("")).unwrap().threat_intel.is_enabled());
+
+    for bad in [
+        "[threat_intel.crowdsec]\nurl = \"ftp://lapi\"\napi_key = \"k\"",
+        "[threat_intel.crowdsec]\nurl = \"http://lapi:8080\"\napi_key = \"\"",
+        "[[threat_intel.denylists]]\nname = \"crowdsec\"\nurl = \"http://l/x\"",
+        "[[threat_intel.denylists]]\nname = \"a\"\nurl = \"http://l/x\"\n[[threat_intel.denylists]]\nname = \"a\"\nurl = \"http://l/y\"",
+        "[threat_intel]\nrefresh_interval = 5\n[[threat_intel.denylists]]\nname = \"a\"\nurl = \"http://l/x\"",
+    ] {
+        assert!(parse_config(&make(bad)).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn honeypot_validation() {
+    let make = |honeypot: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+{honeypot}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make(
+        r#"[[honeypot.credentials]]
+username = "admin"
+password = "admin"
+
+[[honeypot.credentials]]
+username = "oracle""#,
+    ))
+    .unwrap();
+    assert!(config.honeypot.ban);
+    assert_eq!(config.honeypot.ban_duration, 86_400);
+    assert_eq!(config.honeypot.credentials[1].password, None);
+
+    for bad in [
+        "[[honeypot.credentials]]\nusername = \"\"",
+        "[[honeypot.credentials]]\nusername = \"test\"",
+        "[[honeypot.credentials]]\nusername = \"admin\"\npassword = \"\"",
+        "[[honeypot.credentials]]\nusername = \"a\"\n[[honeypot.credentials]]\nusername = \"a\"",
+        "[honeypot]\nban_duration = 0\n[[honeypot.credentials]]\nusername = \"a\"",
+    ] {
+        assert!(parse_config(&make(bad)).is_err(), "{}", bad);
+    }
+    // No ban: duration is not checked
+    assert!(parse_config(&make(
+        "[honeypot]\nban = false\nban_duration = 0\n[[honeypot.credentials]]\nusername = \"a\""
+    ))
+    .is_ok());
+}
+
+#[test]
+fn dashboard_roles_are_ordered() {
+    assert!(DashboardRole::Viewer < DashboardRole::Operator);
+    assert!(DashboardRole::Operator < DashboardRole::Admin);
+    for role in [
+        DashboardRole::Viewer,
+        DashboardRole::Operator,
+        DashboardRole::Admin,
+    ] {
+        assert_eq!(DashboardRole::parse(role.as_str()), Some(role));
+    }
+    assert_eq!(DashboardRole::parse("Admin"), None);
+}
+
 #[test]
 fn api_token_exactly_16_chars_accepted() {
     let toml = format!(
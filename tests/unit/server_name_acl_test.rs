@@ -0,0 +1,473 @@
+use s5::audit::AuditLogger;
+use s5::config::acl::ParsedAcl;
+use s5::config::parse_config;
+use s5::config::types::AclPolicyConfig;
+use s5::flows::{FlowRecord, CLOSE_NORMAL};
+use s5::proxy::acl::check_server_name_and_log;
+use s5::proxy::classify::{AppProtocol, Classification};
+use s5::proxy::close::CloseReason;
+use s5::proxy::forwarder::{self, RelayConfig, RelayOutcome, ServerNameCheck};
+use s5::proxy::inspect::Inspector;
+use s5::proxy::ProxyEngine;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn acl(policy: AclPolicyConfig, allow: &[&str], deny: &[&str]) -> ParsedAcl {
+    let rules = |r: &[&str]| r.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    ParsedAcl::from_config(policy, &rules(allow), &rules(deny)).unwrap()
+}
+
+fn make_engine(acl_section: &str) -> ProxyEngine {
+    let config = parse_config(&format!(
+        "[server]\nssh_listen = \"127.0.0.1:0\"\n\n{acl_section}\n\n\
+         [[users]]\nusername = \"alice\"\npassword_hash = \"{FAKE_HASH}\"\n"
+    ))
+    .unwrap();
+    ProxyEngine::new(
+        Arc::new(config),
+        Arc::new(AuditLogger::new(None, 0, 0, None)),
+    )
+}
+
+/// A TLS 1.3 ClientHello record, with an SNI extension when `sni` is set.
+fn client_hello(sni: Option<&str>) -> Vec<u8> {
+    let mut extensions = vec![0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04]; // supported_versions
+    if let Some(name) = sni {
+        let name = name.as_bytes();
+        let entry_len = name.len() + 3;
+        extensions.extend_from_slice(&[0x00, 0x00]);
+        extensions.extend_from_slice(&((entry_len + 2) as u16).to_be_bytes());
+        extensions.extend_from_slice(&(entry_len as u16).to_be_bytes());
+        extensions.push(0x00);
+        extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(name);
+    }
+
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]); // random
+    body.push(0); // session ID
+    body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // one cipher suite
+    body.extend_from_slice(&[0x01, 0x00]); // null compression
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+// ---------------------------------------------------------------------------
+// Rule matching
+// ---------------------------------------------------------------------------
+
+#[test]
+fn server_name_checked_like_requested_host() {
+    let ip = Some("10.1.2.3".parse().unwrap());
+    let acl = acl(
+        AclPolicyConfig::Deny,
+        &["10.0.0.0/8:*", "*.example.com:443"],
+        &["*.blocked.example:*"],
+    );
+    let check = |name: &str, port| check_server_name_and_log(&acl, "alice", name, port, ip);
+
+    // Allowed address, forbidden name
+    assert!(!check("www.blocked.example", 443).allowed);
+    // CIDR rules match the address connected to
+    assert!(check("intranet", 80).allowed);
+    assert_eq!(
+        check("www.blocked.example", 443).matched_rule.as_deref(),
+        Some("deny:*.blocked.example:*")
+    );
+
+    // Without an address, the name alone must be allowed
+    let denied = check_server_name_and_log(&acl, "alice", "evil.test", 443, None);
+    assert!(!denied.allowed);
+    let allowed = check_server_name_and_log(&acl, "alice", "api.example.com", 443, None);
+    assert!(allowed.allowed);
+}
+
+#[test]
+fn http_host_port_ignored() {
+    let acl = acl(
+        AclPolicyConfig::Allow,
+        &[],
+        &["blocked.example:*", "[fd00::1]:*"],
+    );
+    let denied = |name: &str| !check_server_name_and_log(&acl, "alice", name, 80, None).allowed;
+    assert!(denied("blocked.example:8080"));
+    assert!(denied("blocked.example"));
+    assert!(denied("[fd00::1]:8080"));
+    assert!(!denied("other.example:8080"));
+}
+
+// ---------------------------------------------------------------------------
+// Relay
+// ---------------------------------------------------------------------------
+
+fn relay_config(acl: ParsedAcl) -> RelayConfig {
+    let engine = make_engine("");
+    let session = engine.register_session("alice", "203.0.113.9", 80, "10.0.0.1", "socks5");
+    RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "test@sni:80".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit: None,
+        session: Some(session),
+        stall_watch: None,
+        client_window: None,
+        server_names: Some(ServerNameCheck {
+            acl: Arc::new(acl),
+            port: 80,
+            connected_ip: Some("203.0.113.9".parse().unwrap()),
+        }),
+    }
+}
+
+/// Send `request` through a relay whose session is checked by `acl`.
+/// Returns whether it reached the destination, and the outcome.
+async fn relay_first_bytes(acl: ParsedAcl, request: &[u8]) -> (bool, RelayOutcome) {
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(acl),
+    ));
+
+    client.write_all(request).await.unwrap();
+    let mut received = vec![0u8; request.len()];
+    // A denied relay ends at once, so the destination sees EOF
+    let read = tokio::time::timeout(Duration::from_secs(5), server.read_exact(&mut received));
+    let forwarded = read.await.expect("relay should forward or end").is_ok();
+    assert!(!forwarded || received == request);
+    drop(client);
+    drop(server);
+    (forwarded, relay.await.unwrap().unwrap())
+}
+
+/// Send `parts` one write at a time, then close the client. Returns what
+/// reached the destination, and the outcome.
+async fn relay_parts(acl: ParsedAcl, parts: &[&[u8]]) -> (Vec<u8>, RelayOutcome) {
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(acl),
+    ));
+
+    for part in parts {
+        // A denied relay may already be gone
+        let _ = client.write_all(part).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    drop(client);
+    let mut received = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), server.read_to_end(&mut received));
+    read.await.expect("relay should end").unwrap();
+    drop(server);
+    (received, relay.await.unwrap().unwrap())
+}
+
+/// Send `request` and what follows it in one write; the destination answers
+/// the request with `answer` and the client closes once it has read it,
+/// the destination shortly after. Returns what the destination received
+/// after the request.
+async fn relay_answered(
+    acl: ParsedAcl,
+    request: &[u8],
+    answer: &[u8],
+    next: &[u8],
+) -> (Vec<u8>, RelayOutcome) {
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(acl),
+    ));
+
+    client.write_all(&[request, next].concat()).await.unwrap();
+    let mut received = vec![0u8; request.len()];
+    let read = tokio::time::timeout(Duration::from_secs(5), server.read_exact(&mut received));
+    read.await.expect("request should be relayed").unwrap();
+    assert_eq!(received, request);
+    server.write_all(answer).await.unwrap();
+    let mut answered = vec![0u8; answer.len()];
+    client.read_exact(&mut answered).await.unwrap();
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    server.shutdown().await.unwrap();
+
+    let mut received = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), server.read_to_end(&mut received));
+    read.await.expect("relay should end").unwrap();
+    drop(server);
+    (received, relay.await.unwrap().unwrap())
+}
+
+#[tokio::test]
+async fn denied_host_never_reaches_destination() {
+    let acl = acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let request = b"GET / HTTP/1.1\r\nHost: www.blocked.example\r\n\r\n";
+    let (forwarded, outcome) = relay_first_bytes(acl, request).await;
+    assert!(!forwarded);
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+    assert_eq!(outcome.bytes_up, 0);
+    let traffic = outcome.traffic.unwrap();
+    assert_eq!(traffic.protocol, AppProtocol::Http);
+    assert_eq!(traffic.server_name.as_deref(), Some("www.blocked.example"));
+}
+
+#[tokio::test]
+async fn allowed_or_nameless_traffic_relayed() {
+    let request = b"GET / HTTP/1.1\r\nHost: www.example.com\r\n\r\n";
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let (forwarded, outcome) = relay_first_bytes(blocked(), request).await;
+    assert!(forwarded);
+    assert_ne!(outcome.reason, CloseReason::AclDenied);
+    assert_eq!(outcome.bytes_up, request.len() as u64);
+
+    let (forwarded, outcome) = relay_first_bytes(blocked(), b"\x00\x01binary").await;
+    assert!(forwarded);
+    assert_eq!(outcome.traffic.unwrap().protocol, AppProtocol::Unknown);
+}
+
+#[tokio::test]
+async fn split_client_hello_checked_whole() {
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    // The SNI only arrives with the second write
+    let hello = client_hello(Some("www.blocked.example"));
+    let (received, outcome) = relay_parts(blocked(), &[&hello[..20], &hello[20..]]).await;
+    assert!(received.is_empty());
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+    assert_eq!(
+        outcome.traffic.unwrap().server_name.as_deref(),
+        Some("www.blocked.example")
+    );
+
+    let hello = client_hello(Some("www.example.com"));
+    let (received, outcome) = relay_parts(blocked(), &[&hello[..20], &hello[20..]]).await;
+    assert_eq!(received, hello);
+    assert_eq!(outcome.bytes_up, hello.len() as u64);
+}
+
+#[tokio::test]
+async fn every_keep_alive_request_checked() {
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let first = b"POST /a HTTP/1.1\r\nHost: www.example.com\r\nContent-Length: 5\r\n\r\nhello";
+    let chunked = b"POST /b HTTP/1.1\r\nHost: www.example.com\r\n\
+                    Transfer-Encoding: chunked\r\n\r\n5\r\nHost:\r\n0\r\n\r\n";
+    let denied = b"GET / HTTP/1.1\r\nHost: www.blocked.example\r\n\r\n";
+
+    // Each request is held until its head is complete, bodies are not heads
+    let stream = [&first[..], &chunked[..]].concat();
+    let (received, outcome) = relay_parts(blocked(), &[&stream[..30], &stream[30..]]).await;
+    assert_eq!(received, stream);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+
+    // The second request of the connection is refused
+    let (received, outcome) = relay_parts(blocked(), &[first, denied]).await;
+    assert_eq!(received, first);
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+    // Along with what came in the same read before it
+    let stream = [&first[..], &denied[..]].concat();
+    let (received, outcome) = relay_parts(blocked(), &[&stream]).await;
+    assert!(received.is_empty());
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+    // The session is still classified by its first request
+    let traffic = outcome.traffic.unwrap();
+    assert_eq!(traffic.server_name.as_deref(), Some("www.example.com"));
+}
+
+#[tokio::test]
+async fn nameless_heads_denied_under_host_rules() {
+    let hello = client_hello(None);
+    let request = b"GET / HTTP/1.0\r\n\r\n";
+    let hosts = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    for head in [&hello[..], &request[..]] {
+        let (received, outcome) = relay_parts(hosts(), &[head]).await;
+        assert!(received.is_empty());
+        assert_eq!(outcome.reason, CloseReason::AclDenied);
+    }
+
+    // Without hostname rules a name has nothing to match
+    let cidrs = || acl(AclPolicyConfig::Allow, &[], &["192.0.2.0/24:*"]);
+    for head in [&hello[..], &request[..]] {
+        let (received, _) = relay_parts(cidrs(), &[head]).await;
+        assert_eq!(received, head);
+    }
+}
+
+#[tokio::test]
+async fn ambiguous_request_framing_denied_under_host_rules() {
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let denied = b"GET / HTTP/1.1\r\nHost: www.blocked.example\r\n\r\n";
+    // Each of these could hide `denied` in a body the destination does not read
+    for framing in [
+        "Content-Length: 0\r\nContent-Length: 44",
+        "Content-Length: 44\r\nContent-Length: 44",
+        "Content-Length: 44, 44",
+        "Content-Length: 44x",
+        "Content-Length: +44",
+        "Transfer-Encoding: chunked\r\nContent-Length: 0",
+        "Transfer-Encoding: identity",
+        "Content-Length : 44",
+        "X: a\r\n Content-Length: 44",
+    ] {
+        let head = format!("POST / HTTP/1.1\r\nHost: www.example.com\r\n{framing}\r\n\r\n");
+        let stream = [head.as_bytes(), &denied[..]].concat();
+        let (received, outcome) = relay_parts(blocked(), &[&stream]).await;
+        assert!(received.is_empty(), "{framing}");
+        assert_eq!(outcome.reason, CloseReason::AclDenied, "{framing}");
+    }
+
+    // Without hostname rules there is nothing to hide from
+    let cidrs = || acl(AclPolicyConfig::Allow, &[], &["192.0.2.0/24:*"]);
+    let head = b"POST / HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 44\r\n\r\n";
+    let stream = [&head[..], &denied[..]].concat();
+    let (received, _) = relay_parts(cidrs(), &[&stream]).await;
+    assert_eq!(received, stream);
+}
+
+#[tokio::test]
+async fn upgrades_stay_inspected_until_switched() {
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let upgrade = b"GET /ws HTTP/1.1\r\nHost: www.example.com\r\n\
+                    Connection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+    let connect = b"CONNECT www.example.com:443 HTTP/1.1\r\nHost: www.example.com:443\r\n\r\n";
+    let denied = b"GET / HTTP/1.1\r\nHost: www.blocked.example\r\n\r\n";
+    let refused = b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+
+    // The destination refusing the switch keeps reading HTTP
+    for request in [&upgrade[..], &connect[..]] {
+        let (received, outcome) = relay_answered(blocked(), request, refused, denied).await;
+        assert!(received.is_empty());
+        assert_eq!(outcome.reason, CloseReason::AclDenied);
+    }
+    // As does one that never answers
+    let stream = [&upgrade[..], &denied[..]].concat();
+    let (received, outcome) = relay_parts(blocked(), &[&stream]).await;
+    assert_eq!(received, upgrade);
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+
+    // Once switched the bytes belong to the new protocol
+    let switched = b"HTTP/1.1 100 Continue\r\n\r\n\
+                     HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+    let (received, outcome) = relay_answered(blocked(), upgrade, switched, denied).await;
+    assert_eq!(received, denied);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+    let established = b"HTTP/1.1 200 Connection established\r\n\r\n";
+    let (received, outcome) = relay_answered(blocked(), connect, established, denied).await;
+    assert_eq!(received, denied);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+    // A 200 is no answer to an upgrade
+    let ok = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+    let (received, outcome) = relay_answered(blocked(), upgrade, ok, denied).await;
+    assert!(received.is_empty());
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+}
+
+#[tokio::test]
+async fn head_cut_short_by_eof_is_judged() {
+    let blocked = || acl(AclPolicyConfig::Allow, &[], &["*.blocked.example:*"]);
+    let partial = b"GET / HTTP/1.1\r\nHost: www.blocked.example\r\n";
+    let (received, outcome) = relay_parts(blocked(), &[partial]).await;
+    assert!(received.is_empty());
+    assert_eq!(outcome.reason, CloseReason::AclDenied);
+
+    let partial = b"GET / HTTP/1.1\r\nHost: www.example.com\r\n";
+    let (received, outcome) = relay_parts(blocked(), &[partial]).await;
+    assert_eq!(received, partial);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+}
+
+#[test]
+fn inspector_holds_heads_up_to_the_cap() {
+    let mut heads = Vec::new();
+    let mut allow = |head: &[u8], _: &Classification, first: bool| {
+        heads.push((head.len(), first));
+        true
+    };
+    let mut inspector = Inspector::new();
+    // No end of head yet: nothing released, nothing judged
+    let start = b"GET / HTTP/1.1\r\nX: ";
+    let out = inspector.feed(start, &mut allow).unwrap();
+    assert!(out.is_empty());
+    let filler = vec![b'a'; s5::proxy::inspect::MAX_HEAD_LEN];
+    let out = inspector.feed(&filler, &mut allow).unwrap();
+    // Past the cap the head is judged as it stands
+    assert_eq!(out.len(), start.len() + filler.len());
+    assert_eq!(heads, [(start.len() + filler.len(), true)]);
+
+    // Streams without a head go through at once
+    let mut inspector = Inspector::new();
+    let mut allow = |_: &[u8], _: &Classification, _: bool| true;
+    assert_eq!(
+        inspector.feed(b"\x00\x01", &mut allow).unwrap(),
+        b"\x00\x01"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Configuration and flows
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn inspection_is_opt_in() {
+    let user_acl = acl(AclPolicyConfig::Allow, &[], &[]);
+    let connected: SocketAddr = "198.51.100.7:443".parse().unwrap();
+    assert!(make_engine("")
+        .server_name_check(&user_acl, 443, connected)
+        .is_none());
+
+    let engine = make_engine("[acl]\ninspect_server_names = true");
+    let check = engine.server_name_check(&user_acl, 443, connected).unwrap();
+    assert_eq!(check.port, 443);
+    assert_eq!(check.connected_ip, Some(connected.ip()));
+    // Through an upstream proxy the address is unknown
+    let proxied = engine
+        .server_name_check(&user_acl, 443, "0.0.0.0:0".parse().unwrap())
+        .unwrap();
+    assert_eq!(proxied.connected_ip, None);
+}
+
+#[test]
+fn denied_relay_recorded_in_flows() {
+    let flow = || {
+        FlowRecord::closing(
+            "socks5",
+            "alice",
+            "10.0.0.1",
+            "203.0.113.9",
+            80,
+            Duration::ZERO,
+        )
+    };
+    assert_eq!(
+        flow().with_relay_close(CloseReason::AclDenied).close_reason,
+        "acl_denied"
+    );
+    assert_eq!(
+        flow().with_relay_close(CloseReason::ClientEof).close_reason,
+        CLOSE_NORMAL
+    );
+    assert_eq!(CloseReason::AclDenied.as_str(), "acl_denied");
+    assert_eq!(
+        CloseReason::combine(CloseReason::AclDenied, CloseReason::UpstreamReset),
+        CloseReason::AclDenied
+    );
+}
@@ -0,0 +1,283 @@
+use prometheus_client::encoding::text::encode;
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::config::parse_config;
+use s5::metrics::MetricsRegistry;
+use s5::proxy::close::{CloseReason, KillSwitch, HISTORY_CAPACITY};
+use s5::proxy::forwarder::{self, RelayConfig};
+use s5::proxy::{LiveSession, ProxyEngine};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn make_engine() -> ProxyEngine {
+    let config = parse_config(&format!(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:0"
+
+[security]
+ban_enabled = false
+
+[[users]]
+username = "alice"
+password_hash = "{FAKE_HASH}"
+"##
+    ))
+    .unwrap();
+    let audit = Arc::new(AuditLogger::new(None, 0, 0, None));
+    ProxyEngine::new(Arc::new(config), audit)
+}
+
+fn relay_config(idle_timeout: Duration, session: Option<Arc<LiveSession>>) -> RelayConfig {
+    RelayConfig {
+        idle_timeout,
+        context: "test@close:80".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit: None,
+        session,
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CloseReason
+// ---------------------------------------------------------------------------
+
+#[test]
+fn close_reason_names_round_trip() {
+    for reason in CloseReason::ALL {
+        let json = serde_json::to_value(reason).unwrap();
+        assert_eq!(json, reason.as_str());
+        assert_eq!(reason.to_string(), reason.as_str());
+        let back: CloseReason = serde_json::from_value(json).unwrap();
+        assert_eq!(back, reason);
+    }
+}
+
+#[test]
+fn combine_keeps_the_most_telling_reason() {
+    use CloseReason::*;
+    // The first end explains an ordinary close
+    assert_eq!(CloseReason::combine(ClientEof, UpstreamEof), ClientEof);
+    assert_eq!(CloseReason::combine(UpstreamEof, ClientEof), UpstreamEof);
+    // Errors beat EOFs, quota and kills beat errors
+    assert_eq!(
+        CloseReason::combine(ClientEof, UpstreamReset),
+        UpstreamReset
+    );
+    assert_eq!(CloseReason::combine(Error, QuotaExceeded), QuotaExceeded);
+    assert_eq!(CloseReason::combine(QuotaExceeded, AdminKill), AdminKill);
+    assert_eq!(CloseReason::combine(Ban, AdminKill), Ban);
+    // Idle timeout only when nothing else happened
+    assert_eq!(CloseReason::combine(IdleTimeout, UpstreamEof), UpstreamEof);
+    assert_eq!(CloseReason::combine(IdleTimeout, IdleTimeout), IdleTimeout);
+}
+
+#[tokio::test]
+async fn kill_switch_keeps_the_first_reason() {
+    let kill = KillSwitch::default();
+    assert!(kill.reason().is_none());
+    assert!(kill.kill(CloseReason::Ban));
+    assert!(!kill.kill(CloseReason::AdminKill));
+    assert_eq!(kill.reason(), Some(CloseReason::Ban));
+    assert_eq!(kill.killed().await, CloseReason::Ban);
+}
+
+// ---------------------------------------------------------------------------
+// Relay classification
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn client_eof_closes_with_client_eof() {
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(Duration::from_secs(5), None),
+    ));
+
+    client.write_all(b"ping").await.unwrap();
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(server);
+
+    let outcome = handle.await.unwrap().unwrap();
+    assert_eq!(outcome.bytes_up, 4);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+}
+
+#[tokio::test]
+async fn upstream_eof_closes_with_upstream_eof() {
+    let (client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(Duration::from_secs(5), None),
+    ));
+
+    server.write_all(b"pong").await.unwrap();
+    drop(server);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(client);
+
+    let outcome = handle.await.unwrap().unwrap();
+    assert_eq!(outcome.bytes_down, 4);
+    assert_eq!(outcome.reason, CloseReason::UpstreamEof);
+}
+
+#[tokio::test]
+async fn silent_relay_closes_with_idle_timeout() {
+    let (_client, relay_client) = tokio::io::duplex(4096);
+    let (_server, relay_server) = tokio::io::duplex(4096);
+    let outcome = forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(Duration::from_millis(100), None),
+    )
+    .await
+    .unwrap();
+    assert_eq!(outcome.reason, CloseReason::IdleTimeout);
+}
+
+#[tokio::test]
+async fn killed_session_closes_with_kill_reason() {
+    let engine = make_engine();
+    let session = engine.register_session("alice", "example.com", 80, "10.0.0.1", "ssh");
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(Duration::from_secs(5), Some(session.clone())),
+    ));
+
+    client.write_all(b"hello").await.unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(server.read(&mut buf).await.unwrap(), 5);
+
+    assert!(engine.kill_session(&session.session_id, CloseReason::AdminKill));
+    let outcome = tokio::time::timeout(Duration::from_secs(2), handle)
+        .await
+        .expect("relay should stop when killed")
+        .unwrap()
+        .unwrap();
+    assert_eq!(outcome.reason, CloseReason::AdminKill);
+    assert!(!engine.kill_session("no-such-session", CloseReason::AdminKill));
+}
+
+// ---------------------------------------------------------------------------
+// Engine: history and metrics
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn kill_sessions_matches_filter_once() {
+    let engine = make_engine();
+    let a = engine.register_session("alice", "a.example", 80, "10.0.0.1", "ssh");
+    let b = engine.register_session("alice", "b.example", 80, "10.0.0.2", "socks5");
+    let c = engine.register_session("bob", "c.example", 80, "10.0.0.1", "ssh");
+
+    let killed = engine.kill_sessions(CloseReason::Ban, |s| s.source_ip == "10.0.0.1");
+    assert_eq!(killed, 2);
+    assert_eq!(a.kill.reason(), Some(CloseReason::Ban));
+    assert!(b.kill.reason().is_none());
+    assert_eq!(c.kill.reason(), Some(CloseReason::Ban));
+
+    // Already killed sessions are not counted again
+    assert_eq!(engine.kill_sessions(CloseReason::Ban, |_| true), 1);
+    assert_eq!(b.kill.reason(), Some(CloseReason::Ban));
+}
+
+#[tokio::test]
+async fn closed_sessions_are_kept_newest_first() {
+    let engine = make_engine();
+    let a = engine.register_session("alice", "a.example", 80, "10.0.0.1", "ssh");
+    let b = engine.register_session("bob", "b.example", 443, "10.0.0.2", "socks5");
+    engine.close_session(&a, CloseReason::ClientEof);
+    engine.close_session(&b, CloseReason::IdleTimeout);
+    assert!(engine.get_sessions().is_empty());
+
+    let all = engine.closed_sessions(10, |_| true);
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[0].session.username, "bob");
+    assert_eq!(all[0].close_reason, CloseReason::IdleTimeout);
+    assert_eq!(all[1].session.username, "alice");
+
+    let idle = engine.closed_sessions(10, |c| c.close_reason == CloseReason::IdleTimeout);
+    assert_eq!(idle.len(), 1);
+    assert_eq!(engine.closed_sessions(1, |_| true).len(), 1);
+
+    let json = serde_json::to_value(&all[1]).unwrap();
+    assert_eq!(json["close_reason"], "client_eof");
+    assert_eq!(json["target_host"], "a.example");
+    assert!(json["ended_at"].is_string());
+}
+
+#[tokio::test]
+async fn history_is_bounded() {
+    let engine = make_engine();
+    for i in 0..HISTORY_CAPACITY + 5 {
+        let s = engine.register_session("alice", &format!("h{i}"), 80, "10.0.0.1", "ssh");
+        engine.close_session(&s, CloseReason::UpstreamEof);
+    }
+    let all = engine.closed_sessions(usize::MAX, |_| true);
+    assert_eq!(all.len(), HISTORY_CAPACITY);
+    assert_eq!(
+        all[0].session.target_host,
+        format!("h{}", HISTORY_CAPACITY + 4)
+    );
+}
+
+#[tokio::test]
+async fn close_session_counts_reason_metric() {
+    let mut engine = make_engine();
+    let metrics = Arc::new(MetricsRegistry::new());
+    engine.set_metrics(metrics.clone());
+    let s = engine.register_session("alice", "a.example", 80, "10.0.0.1", "socks5");
+    engine.close_session(&s, CloseReason::QuotaExceeded);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    let line = buffer
+        .lines()
+        .find(|l| l.starts_with("s5_sessions_closed_total") && l.contains("quota_exceeded"))
+        .expect("close reason series present");
+    assert!(line.contains("protocol=\"socks5\""), "{line}");
+    assert!(line.ends_with(" 1"), "{line}");
+}
+
+// ---------------------------------------------------------------------------
+// Audit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn proxy_complete_carries_close_reason() {
+    let source = "203.0.113.9:40000".parse().unwrap();
+    let event = AuditEvent::proxy_complete_with_cid(
+        "alice",
+        "example.com",
+        443,
+        10,
+        20,
+        1500,
+        &source,
+        None,
+        "c1",
+    );
+    let json = serde_json::to_value(&event).unwrap();
+    assert!(json.get("close_reason").is_none());
+
+    let json = serde_json::to_value(event.with_close_reason(CloseReason::UpstreamReset)).unwrap();
+    assert_eq!(json["event_type"], "proxy.complete");
+    assert_eq!(json["close_reason"], "upstream_reset");
+}
@@ -0,0 +1,179 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::config::types::AppConfig;
+use s5::proxy::close::CloseReason;
+use s5::proxy::forwarder::{self, RelayConfig};
+use s5::proxy::memory::{total_buffered, SessionMemory};
+use s5::proxy::LiveSession;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn session(budget: u64) -> Arc<LiveSession> {
+    Arc::new(LiveSession {
+        session_id: "s7".to_string(),
+        correlation_id: Some("0badc0de".to_string()),
+        username: "alice".to_string(),
+        target_host: "example.com".to_string(),
+        target_port: 443,
+        source_ip: "10.0.0.1".to_string(),
+        started_at: chrono::Utc::now(),
+        bytes_up: AtomicU64::new(0),
+        bytes_down: AtomicU64::new(0),
+        protocol: "ssh".to_string(),
+        kill: Default::default(),
+        capture: Default::default(),
+        memory: Arc::new(SessionMemory::new(budget)),
+        traffic: Default::default(),
+        priority: Default::default(),
+        first_line_len: Default::default(),
+        transfer: Default::default(),
+    })
+}
+
+fn relay_config(session: Arc<LiveSession>) -> RelayConfig {
+    RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "alice@example.com:443".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: Some("alice".to_string()),
+        quotas: None,
+        audit: None,
+        session: Some(session),
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    }
+}
+
+fn config(limits: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[limits]\n{limits}"), "")
+}
+
+// ---------------------------------------------------------------------------
+// Accounting
+// ---------------------------------------------------------------------------
+
+#[test]
+fn charges_are_given_back_on_drop() {
+    let memory = Arc::new(SessionMemory::new(100));
+    let first = memory.charge(60);
+    assert!(!first.over_budget());
+    let second = memory.charge(50);
+    assert_eq!(second.buffered(), 110);
+    assert!(second.over_budget());
+    assert_eq!(memory.buffered(), 110);
+    assert!(total_buffered() >= 110);
+
+    drop(second);
+    drop(first);
+    assert_eq!(memory.buffered(), 0);
+    assert_eq!(memory.peak(), 110);
+}
+
+#[test]
+fn zero_budget_is_unlimited() {
+    let memory = Arc::new(SessionMemory::default());
+    let charge = memory.charge(1 << 40);
+    assert!(!charge.over_budget());
+}
+
+// ---------------------------------------------------------------------------
+// Enforcement
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn relay_within_budget_leaves_nothing_buffered() {
+    let session = session(64 * 1024);
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(session.clone()),
+    ));
+
+    client.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(server.read(&mut buf).await.unwrap(), 4);
+    drop(client);
+    drop(server);
+
+    let outcome = handle.await.unwrap().unwrap();
+    assert_ne!(outcome.reason, CloseReason::MemoryLimit);
+    assert_eq!(session.memory.buffered(), 0);
+    assert_eq!(session.memory.peak(), 4);
+}
+
+#[tokio::test]
+async fn relay_past_budget_closes_the_session() {
+    let session = session(1024);
+    // Bytes already held elsewhere, e.g. a capture queue behind a slow disk
+    let queued = session.memory.charge(1000);
+
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (_server, relay_server) = tokio::io::duplex(4096);
+    let handle = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        relay_config(session.clone()),
+    ));
+    client.write_all(&[0u8; 100]).await.unwrap();
+
+    let outcome = tokio::time::timeout(Duration::from_secs(2), handle)
+        .await
+        .expect("relay should stop")
+        .unwrap()
+        .unwrap();
+    assert_eq!(outcome.reason, CloseReason::MemoryLimit);
+    assert_eq!(outcome.bytes_up, 0);
+    assert_eq!(session.kill.reason(), Some(CloseReason::MemoryLimit));
+
+    drop(queued);
+    assert_eq!(session.memory.buffered(), 0);
+}
+
+#[test]
+fn memory_limit_close_reason() {
+    assert_eq!(CloseReason::MemoryLimit.as_str(), "memory_limit");
+    assert_eq!(
+        CloseReason::combine(CloseReason::Error, CloseReason::MemoryLimit),
+        CloseReason::MemoryLimit
+    );
+    assert_eq!(
+        CloseReason::combine(CloseReason::MemoryLimit, CloseReason::AdminKill),
+        CloseReason::AdminKill
+    );
+}
+
+#[test]
+fn memory_exceeded_audit_event_is_critical() {
+    let session = session(16384);
+    let event = AuditEvent::session_memory_exceeded(&session, 20000);
+    assert_eq!(event.event_type(), "session.memory_exceeded");
+    assert!(event.is_critical());
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["session_id"], "s7");
+    assert_eq!(json["correlation_id"], "0badc0de");
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["buffered_bytes"], 20000);
+    assert_eq!(json["max_buffered_bytes"], 16384);
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn budget_config_defaults_and_validation() {
+    assert_eq!(config("").unwrap().limits.max_buffered_bytes, 0);
+    let cfg = config("max_buffered_bytes = 1048576").unwrap();
+    assert_eq!(cfg.limits.max_buffered_bytes, 1_048_576);
+
+    // Below two relay buffers (2 x 8192) every busy session would be cut
+    assert!(config("max_buffered_bytes = 16383").is_err());
+    assert!(config("max_buffered_bytes = 16384").is_ok());
+}
@@ -69,6 +69,10 @@ fn setup(app_config: AppConfig) -> Arc<AppContext> {
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     })
 }
 
@@ -600,3 +604,62 @@ async fn concurrent_connections_both_succeed() {
     // (connect failures are ok, we only care about concurrent auth)
     let _ = (server_r1, server_r2);
 }
+
+// ---------------------------------------------------------------------------
+// Test 10: Honeypot credential - auth succeeds, CONNECT refused, source banned
+// ---------------------------------------------------------------------------
+#[tokio::test]
+async fn honeypot_login_is_sandboxed_and_banned() {
+    let hash = password::hash_password("pass").unwrap();
+    let mut config = make_config_with_hash(&hash);
+    config.honeypot.credentials = vec![s5::config::types::HoneypotCredential {
+        username: "admin".to_string(),
+        password: Some("admin".to_string()),
+    }];
+    let ctx = setup(config);
+    let server_ctx = ctx.clone();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server_handle = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        s5::socks::handler::handle_connection(stream, server_ctx).await
+    });
+
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    write_greeting_password(&mut client).await;
+    let mut resp = [0u8; 2];
+    client.read_exact(&mut resp).await.unwrap();
+
+    write_credentials(&mut client, "admin", "admin").await;
+    let mut auth_resp = [0u8; 2];
+    client.read_exact(&mut auth_resp).await.unwrap();
+    assert_eq!(auth_resp[1], 0x00, "decoy login must look successful");
+
+    write_connect_domain(&mut client, "example.com", 80).await;
+    let mut reply = [0u8; 10];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(reply[1], protocol::REPLY_HOST_UNREACHABLE);
+
+    assert!(server_handle.await.unwrap().is_ok());
+    assert!(ctx
+        .security
+        .read()
+        .await
+        .is_banned(&"127.0.0.1".parse().unwrap()));
+    assert!(ctx
+        .audit
+        .get_recent_events(10)
+        .iter()
+        .any(|e| e.event_type() == "honeypot.triggered"));
+    assert_eq!(
+        ctx.metrics
+            .honeypot_triggers_total
+            .get_or_create(&s5::metrics::collectors::ProtocolLabel {
+                protocol: "socks5".to_string(),
+            })
+            .get(),
+        1
+    );
+}
@@ -73,3 +73,51 @@ fn empty_token_ticket() {
     let ticket = make_ticket("", ts);
     assert!(s5::api::verify_sse_ticket(&ticket, ""));
 }
+
+fn make_role_ticket(api_token: &str, timestamp: u64, role: &str) -> String {
+    let nonce: u128 = rand::random();
+    let signing_key = format!("s5-sse-ticket:{}", api_token);
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()).unwrap();
+    mac.update(format!("{}:{}:{}", timestamp, nonce, role).as_bytes());
+    let sig = hex::encode(mac.finalize().into_bytes());
+    format!("{}:{}:{}:{}", timestamp, nonce, sig, role)
+}
+
+#[test]
+fn ticket_without_role_is_admin() {
+    use s5::config::types::DashboardRole;
+    let token = "my-secret-token";
+    let ticket = make_ticket(token, current_timestamp());
+    assert_eq!(
+        s5::api::verify_sse_ticket_role(&ticket, token),
+        Some(DashboardRole::Admin)
+    );
+}
+
+#[test]
+fn ticket_carries_signed_role() {
+    use s5::config::types::DashboardRole;
+    let token = "my-secret-token";
+    let ticket = make_role_ticket(token, current_timestamp(), "viewer");
+    assert_eq!(
+        s5::api::verify_sse_ticket_role(&ticket, token),
+        Some(DashboardRole::Viewer)
+    );
+}
+
+#[test]
+fn ticket_role_cannot_be_changed_or_stripped() {
+    let token = "my-secret-token";
+    let ts = current_timestamp();
+
+    let upgraded = make_role_ticket(token, ts, "viewer").replace(":viewer", ":operator");
+    assert!(!s5::api::verify_sse_ticket(&upgraded, token));
+
+    let viewer = make_role_ticket(token, ts, "viewer");
+    let stripped = viewer.strip_suffix(":viewer").unwrap();
+    assert!(!s5::api::verify_sse_ticket(stripped, token));
+
+    // Admin tickets never carry an explicit role
+    let explicit_admin = make_role_ticket(token, ts, "admin");
+    assert!(!s5::api::verify_sse_ticket(&explicit_admin, token));
+}
@@ -4,10 +4,11 @@
 // - SshHandler struct creation and field initialization
 // - Session state management (auth, username, fingerprint)
 // - Auth attempt tracking
-// - Channel limit enforcement (MAX_CHANNELS_PER_CONNECTION)
+// - Channel limit enforcement (limits.max_channels_per_connection)
 // - classify_relay_error() error classification
 // - record_auth_failure() behavior (below/at/above max attempts)
 // - validate_forwarding_request() for various conditions
+// - Maintenance mode login rejection
 
 use s5::audit::AuditLogger;
 use s5::auth::AuthService;
@@ -143,6 +144,10 @@ fn setup(app_config: AppConfig) -> Arc<AppContext> {
         webhook_dispatcher: None,
         alert_engine: None,
         start_time: std::time::Instant::now(),
+        config_path: None,
+        flow_log: None,
+        ipfix: None,
+        maintenance: Arc::new(std::sync::atomic::AtomicBool::new(false)),
     })
 }
 
@@ -358,6 +363,21 @@ async fn new_handler_would_accept_new_channel() {
     assert!(handler.would_accept_new_channel());
 }
 
+#[tokio::test]
+async fn channel_limit_follows_config() {
+    let config = make_config("");
+    assert_eq!(
+        config.limits.max_channels_per_connection,
+        MAX_CHANNELS_PER_CONNECTION
+    );
+
+    // 0 = unlimited
+    let mut config = make_config("");
+    config.limits.max_channels_per_connection = 0;
+    let handler = make_handler(setup(config));
+    assert!(handler.would_accept_new_channel());
+}
+
 // ===========================================================================
 // 6. classify_relay_error - ACL denied
 // ===========================================================================
@@ -1546,3 +1566,213 @@ max_new_connections_per_minute = 100
         .unwrap();
     assert!(result.is_some());
 }
+
+// ===========================================================================
+// 48. Honeypot credential - accepted into a sandboxed session, source banned
+// ===========================================================================
+
+#[tokio::test]
+async fn honeypot_password_login_is_sandboxed() {
+    use russh::server::Handler as _;
+
+    let mut config = make_config(
+        r#"
+[[honeypot.credentials]]
+username = "admin"
+password = "admin123"
+
+[[honeypot.credentials]]
+username = "oracle"
+"#,
+    );
+    config.security.ban_enabled = true;
+    let ctx = setup(config);
+
+    // Wrong decoy password is a plain failure
+    let mut handler = make_handler(ctx.clone());
+    let auth = handler.auth_password("admin", "nope").await.unwrap();
+    assert!(!matches!(auth, russh::server::Auth::Accept));
+    assert!(!handler.is_honeypot());
+
+    let auth = handler.auth_password("admin", "admin123").await.unwrap();
+    assert!(matches!(auth, russh::server::Auth::Accept));
+    assert!(handler.is_authenticated());
+    assert!(handler.is_honeypot());
+    assert_eq!(handler.session_username(), Some("admin"));
+
+    // No forwarding from a decoy session
+    let result = handler
+        .test_validate_forwarding_request("example.com", 443)
+        .await
+        .unwrap();
+    assert!(result.is_none());
+
+    assert!(ctx
+        .security
+        .read()
+        .await
+        .is_banned(&default_peer_addr().ip()));
+    let events = ctx.audit.get_recent_events(10);
+    assert!(events
+        .iter()
+        .any(|e| e.event_type() == "honeypot.triggered" && e.is_critical()));
+
+    // Username-only decoy accepts any password (new connection, new source)
+    let mut handler = SshHandler::new(ctx.clone(), "127.0.0.2:40000".parse().unwrap());
+    let auth = handler.auth_password("oracle", "whatever").await.unwrap();
+    assert!(matches!(auth, russh::server::Auth::Accept));
+    assert!(handler.is_honeypot());
+}
+
+// ---------------------------------------------------------------------------
+// 49. Maintenance mode - non-admin logins refused with the configured message
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn maintenance_refuses_non_admin_logins() {
+    let mut config = make_config(&format!(
+        "[[users]]\nusername = \"root\"\npassword_hash = \"{FAKE_HASH}\"\nrole = \"admin\""
+    ));
+    assert_eq!(
+        config.server.maintenance_message,
+        "Server is under maintenance. Please try again later."
+    );
+    config.server.maintenance_message = "Backends upgrading, back at 14:00".to_string();
+    let ctx = setup(config);
+    assert!(ctx.maintenance_rejection("alice").await.is_none());
+
+    ctx.maintenance
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    assert_eq!(
+        ctx.maintenance_rejection("alice").await.as_deref(),
+        Some("Backends upgrading, back at 14:00")
+    );
+    assert!(ctx.maintenance_rejection("root").await.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// 50. Keyboard-interactive - password prompt, plus a code prompt with TOTP
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn keyboard_interactive_prompts() {
+    use russh::server::Handler as _;
+
+    let mut handler = make_handler(setup(make_config("")));
+    match handler
+        .auth_keyboard_interactive("alice", "", None)
+        .await
+        .unwrap()
+    {
+        russh::server::Auth::Partial { prompts, .. } => {
+            assert_eq!(prompts.len(), 1);
+            assert_eq!(prompts[0].0, "Password: ");
+            assert!(!prompts[0].1, "password not echoed");
+        }
+        _ => panic!("expected keyboard-interactive prompts"),
+    }
+    // Sending the prompts is not an attempt
+    assert_eq!(handler.total_auth_attempts(), 0);
+    assert!(!handler.is_authenticated());
+
+    let mut config = make_config("");
+    config.security.totp_required_for = vec!["ssh".to_string()];
+    let mut handler = make_handler(setup(config));
+    // Unknown users get the same prompts
+    match handler
+        .auth_keyboard_interactive("nobody", "", None)
+        .await
+        .unwrap()
+    {
+        russh::server::Auth::Partial { prompts, .. } => {
+            assert_eq!(prompts.len(), 2);
+            assert_eq!(prompts[1].0, "Verification code: ");
+        }
+        _ => panic!("expected keyboard-interactive prompts"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 51. Credential spraying - one source failing as many usernames is banned
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn credential_spraying_bans_source() {
+    use russh::server::Handler as _;
+
+    let mut config = make_config("");
+    config.security.ban_enabled = true;
+    config.security.credential_spraying.min_usernames = 3;
+    let ctx = setup(config);
+    let peer = default_peer_addr();
+
+    // One failure per username, well under ban_threshold
+    for user in ["alice", "bob"] {
+        let mut handler = make_handler(ctx.clone());
+        handler.auth_password(user, "wrong").await.unwrap();
+    }
+    assert!(!ctx.security.read().await.is_banned(&peer.ip()));
+
+    let mut handler = make_handler(ctx.clone());
+    handler.auth_password("carol", "wrong").await.unwrap();
+    assert!(ctx.security.read().await.is_banned(&peer.ip()));
+
+    let events = ctx.audit.get_recent_events(20);
+    assert!(events
+        .iter()
+        .any(|e| e.event_type() == "auth.spraying" && e.is_critical()));
+    let family = &ctx.metrics.credential_spraying_detected_total;
+    let label = s5::metrics::collectors::ProtocolLabel {
+        protocol: "ssh".to_string(),
+    };
+    assert_eq!(family.get_or_create(&label).get(), 1);
+}
+
+// ---------------------------------------------------------------------------
+// 52. Account lockout - locked account refuses the right password until unlocked
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn locked_account_refuses_correct_password() {
+    use russh::server::Handler as _;
+
+    let mut config = make_config("");
+    config.security.lock_after_failures = 2;
+    config.users[0].password_hash = Some(s5::auth::password::hash_password("right").unwrap());
+    let ctx = setup(config);
+
+    // Two failures from two different sources lock alice
+    for peer in ["198.51.100.1:40000", "198.51.100.2:40000"] {
+        let mut handler = SshHandler::new(ctx.clone(), peer.parse().unwrap());
+        handler.auth_password("alice", "wrong").await.unwrap();
+    }
+    let mut handler = make_handler(ctx.clone());
+    let auth = handler.auth_password("alice", "right").await.unwrap();
+    assert!(!matches!(auth, russh::server::Auth::Accept));
+    assert!(!handler.is_authenticated());
+    assert!(ctx
+        .audit
+        .get_recent_events(20)
+        .iter()
+        .any(|e| e.event_type() == "user.locked" && e.is_critical()));
+    assert_eq!(ctx.metrics.account_lockouts_total.get(), 1);
+
+    assert!(ctx.security.read().await.account_lockout().unlock("alice"));
+    let mut handler = make_handler(ctx.clone());
+    let auth = handler.auth_password("alice", "right").await.unwrap();
+    assert!(matches!(auth, russh::server::Auth::Accept));
+
+    // A success clears the count: failure, success, failure does not lock
+    let mut handler = make_handler(ctx.clone());
+    handler.auth_password("alice", "wrong").await.unwrap();
+    let mut handler = make_handler(ctx.clone());
+    handler.auth_password("alice", "right").await.unwrap();
+    let mut handler = make_handler(ctx.clone());
+    handler.auth_password("alice", "wrong").await.unwrap();
+    assert!(!ctx
+        .security
+        .read()
+        .await
+        .account_lockout()
+        .is_locked("alice"));
+}
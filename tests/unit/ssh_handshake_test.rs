@@ -0,0 +1,244 @@
+use s5::audit::events::AuditEvent;
+use s5::auth::AuthService;
+use s5::config::parse_config;
+use s5::ssh::handshake::{
+    hassh, negotiate, parse_kexinit, HandshakeInfo, HandshakeTap, IMPLICIT_MAC,
+};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Build an `SSH_MSG_KEXINIT` payload from ten comma-separated name-lists.
+fn kexinit_payload(lists: [&str; 10]) -> Vec<u8> {
+    let mut p = vec![20u8];
+    p.extend_from_slice(&[0xAB; 16]);
+    for list in lists {
+        p.extend_from_slice(&(list.len() as u32).to_be_bytes());
+        p.extend_from_slice(list.as_bytes());
+    }
+    // first_kex_packet_follows + reserved
+    p.push(0);
+    p.extend_from_slice(&[0; 4]);
+    p
+}
+
+/// Wrap a payload in an unencrypted binary packet.
+fn packet(payload: &[u8]) -> Vec<u8> {
+    let padding = 4 + (8 - (payload.len() + 9) % 8) % 8;
+    let mut out = ((payload.len() + padding + 1) as u32)
+        .to_be_bytes()
+        .to_vec();
+    out.push(padding as u8);
+    out.extend_from_slice(payload);
+    out.extend(std::iter::repeat_n(0u8, padding));
+    out
+}
+
+fn client_lists() -> [&'static str; 10] {
+    [
+        "curve25519-sha256,diffie-hellman-group14-sha256",
+        "ssh-ed25519,rsa-sha2-512",
+        "chacha20-poly1305@openssh.com,aes256-ctr",
+        "aes256-ctr",
+        "hmac-sha2-512,hmac-sha2-256",
+        "hmac-sha2-256",
+        "none,zlib@openssh.com",
+        "none",
+        "",
+        "",
+    ]
+}
+
+fn server_lists() -> [&'static str; 10] {
+    [
+        "diffie-hellman-group14-sha256,curve25519-sha256",
+        "rsa-sha2-512,ssh-ed25519",
+        "aes256-ctr,chacha20-poly1305@openssh.com",
+        "aes128-ctr,aes256-ctr",
+        "hmac-sha2-256",
+        "hmac-sha2-512,hmac-sha2-256",
+        "none",
+        "none",
+        "",
+        "",
+    ]
+}
+
+/// MD5 of `kex;cipher_c2s;mac_c2s;compression_c2s` from [`client_lists`]
+const CLIENT_HASSH: &str = "101e2cda8da5b95e5e95f62308cd6b89";
+
+#[test]
+fn parse_kexinit_reads_all_name_lists() {
+    let k = parse_kexinit(&kexinit_payload(client_lists())).unwrap();
+    assert_eq!(
+        k.kex,
+        ["curve25519-sha256", "diffie-hellman-group14-sha256"]
+    );
+    assert_eq!(k.host_key, ["ssh-ed25519", "rsa-sha2-512"]);
+    assert_eq!(k.compression_client_to_server, ["none", "zlib@openssh.com"]);
+}
+
+#[test]
+fn parse_kexinit_rejects_other_messages_and_truncation() {
+    let mut payload = kexinit_payload(client_lists());
+    assert!(parse_kexinit(&payload[..40]).is_none());
+    payload[0] = 21;
+    assert!(parse_kexinit(&payload).is_none());
+}
+
+#[test]
+fn negotiation_follows_client_preference() {
+    let client = parse_kexinit(&kexinit_payload(client_lists())).unwrap();
+    let server = parse_kexinit(&kexinit_payload(server_lists())).unwrap();
+    let alg = negotiate(&client, &server);
+    assert_eq!(alg.kex.as_deref(), Some("curve25519-sha256"));
+    assert_eq!(alg.host_key.as_deref(), Some("ssh-ed25519"));
+    assert_eq!(
+        alg.cipher_client_to_server.as_deref(),
+        Some("chacha20-poly1305@openssh.com")
+    );
+    assert_eq!(alg.cipher_server_to_client.as_deref(), Some("aes256-ctr"));
+    // AEAD cipher carries its own MAC
+    assert_eq!(alg.mac_client_to_server.as_deref(), Some(IMPLICIT_MAC));
+    assert_eq!(alg.mac_server_to_client.as_deref(), Some("hmac-sha2-256"));
+    assert_eq!(alg.compression_client_to_server.as_deref(), Some("none"));
+}
+
+#[test]
+fn negotiation_reports_no_common_algorithm() {
+    let client = parse_kexinit(&kexinit_payload(client_lists())).unwrap();
+    let mut lists = server_lists();
+    lists[0] = "diffie-hellman-group1-sha1";
+    let server = parse_kexinit(&kexinit_payload(lists)).unwrap();
+    assert!(negotiate(&client, &server).kex.is_none());
+}
+
+#[tokio::test]
+async fn tap_records_version_and_algorithms() {
+    let (server_io, mut client) = tokio::io::duplex(64 * 1024);
+    let info = Arc::new(Mutex::new(HandshakeInfo::default()));
+    let mut tap = HandshakeTap::new(server_io, info.clone());
+
+    // Client: version line then KEXINIT, split across writes
+    let mut hello = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+    hello.extend(packet(&kexinit_payload(client_lists())));
+    let (a, b) = hello.split_at(30);
+    client.write_all(a).await.unwrap();
+    let mut buf = vec![0u8; 4096];
+    let n = tap.read(&mut buf).await.unwrap();
+    assert_eq!(n, 30);
+    client.write_all(b).await.unwrap();
+    tap.read_exact(&mut buf[..b.len()]).await.unwrap();
+    assert_eq!(
+        info.lock().unwrap().client_version.as_deref(),
+        Some("SSH-2.0-OpenSSH_9.6")
+    );
+    assert!(info.lock().unwrap().algorithms.is_none());
+    // The HASSH only needs the client side
+    assert_eq!(info.lock().unwrap().hassh.as_deref(), Some(CLIENT_HASSH));
+
+    // Server side goes through the write half
+    let mut reply = b"SSH-2.0-s5\r\n".to_vec();
+    reply.extend(packet(&kexinit_payload(server_lists())));
+    tap.write_all(&reply).await.unwrap();
+
+    let algorithms = info.lock().unwrap().algorithms.clone().unwrap();
+    assert_eq!(algorithms.kex.as_deref(), Some("curve25519-sha256"));
+
+    // Bytes pass through unchanged
+    let mut echoed = vec![0u8; reply.len()];
+    client.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(echoed, reply);
+}
+
+// ---------------------------------------------------------------------------
+// HASSH
+// ---------------------------------------------------------------------------
+
+#[test]
+fn hassh_uses_client_to_server_lists() {
+    let client = parse_kexinit(&kexinit_payload(client_lists())).unwrap();
+    assert_eq!(hassh(&client), CLIENT_HASSH);
+
+    // Server-to-client preferences and host key types are not part of it
+    let mut lists = client_lists();
+    lists[1] = "rsa-sha2-512";
+    lists[3] = "aes128-ctr";
+    let same = parse_kexinit(&kexinit_payload(lists)).unwrap();
+    assert_eq!(hassh(&same), CLIENT_HASSH);
+
+    lists[2] = "aes256-ctr";
+    let other = parse_kexinit(&kexinit_payload(lists)).unwrap();
+    assert_ne!(hassh(&other), CLIENT_HASSH);
+}
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn pinned_config(group: &str, users: &str) -> anyhow::Result<s5::config::types::AppConfig> {
+    parse_config(&format!(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:2222"
+
+[[groups]]
+name = "ops"
+{group}
+
+{users}
+"##
+    ))
+}
+
+fn user(name: &str, extra: &str) -> String {
+    format!("[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\n{extra}\n")
+}
+
+#[test]
+fn allowed_hassh_resolves_user_over_group() {
+    let other = "0123456789abcdef0123456789abcdef";
+    let users = [
+        user("alice", "group = \"ops\""),
+        user(
+            "bob",
+            &format!("group = \"ops\"\nallowed_hassh = [\"{other}\"]"),
+        ),
+        user("carol", ""),
+    ]
+    .concat();
+    let group = format!("allowed_hassh = [\"{}\"]", CLIENT_HASSH.to_uppercase());
+    let config = pinned_config(&group, &users).unwrap();
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+
+    let alice = store.get("alice").unwrap();
+    assert_eq!(alice.allowed_hassh, vec![CLIENT_HASSH.to_string()]);
+    assert!(alice.is_hassh_allowed(Some(CLIENT_HASSH)));
+    assert!(!alice.is_hassh_allowed(Some(other)));
+    assert!(!alice.is_hassh_allowed(None));
+
+    let bob = store.get("bob").unwrap();
+    assert!(bob.is_hassh_allowed(Some(other)));
+    assert!(!bob.is_hassh_allowed(Some(CLIENT_HASSH)));
+
+    let carol = store.get("carol").unwrap();
+    assert!(carol.allowed_hassh.is_empty());
+    assert!(carol.is_hassh_allowed(None));
+}
+
+#[test]
+fn invalid_allowed_hassh_rejected() {
+    assert!(pinned_config("", &user("alice", "allowed_hassh = [\"OpenSSH_9.6\"]")).is_err());
+    assert!(pinned_config("allowed_hassh = [\"abc\"]", &user("bob", "")).is_err());
+}
+
+#[test]
+fn auth_events_carry_hassh() {
+    let peer = "192.0.2.1:50000".parse().unwrap();
+    let event = AuditEvent::auth_success_with_cid("alice", &peer, "password", "cid")
+        .with_hassh(Some(CLIENT_HASSH.to_string()));
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["hassh"], CLIENT_HASSH);
+
+    let socks = AuditEvent::auth_failure_with_cid("alice", &peer, "socks5", "cid");
+    let json = serde_json::to_value(&socks).unwrap();
+    assert!(json.get("hassh").is_none());
+}
@@ -0,0 +1,207 @@
+use prometheus_client::encoding::text::encode;
+use s5::config::parse_config;
+use s5::config::types::OverloadPolicy;
+use s5::metrics::MetricsRegistry;
+use s5::ssh::pre_auth::{PreAuthDeadline, PreAuthLimiter, PreAuthRejection};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn limiter_caps_per_ip_and_globally() {
+    let metrics = MetricsRegistry::new();
+    let limiter = Arc::new(PreAuthLimiter::new(3, 2).with_metrics(&metrics));
+
+    let a1 = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    let _a2 = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    assert_eq!(
+        limiter.try_acquire(ip("192.0.2.1")).err(),
+        Some(PreAuthRejection::PerIp)
+    );
+    let _b1 = limiter.try_acquire(ip("192.0.2.2")).unwrap();
+    assert_eq!(
+        limiter.try_acquire(ip("192.0.2.3")).err(),
+        Some(PreAuthRejection::Global)
+    );
+    assert_eq!(limiter.in_flight(), 3);
+    assert_eq!(limiter.in_flight_for(&ip("192.0.2.1")), 2);
+    assert_eq!(metrics.ssh_unauthenticated_connections.get(), 3);
+
+    // Disconnect before auth frees the slot
+    drop(a1);
+    assert_eq!(limiter.in_flight_for(&ip("192.0.2.1")), 1);
+    let _c1 = limiter.try_acquire(ip("192.0.2.3")).unwrap();
+    assert_eq!(metrics.ssh_unauthenticated_connections.get(), 3);
+}
+
+#[test]
+fn authentication_releases_the_slot_once() {
+    let limiter = Arc::new(PreAuthLimiter::new(1, 1));
+    let mut slot = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    let flag = slot.flag();
+    assert!(limiter.try_acquire(ip("192.0.2.2")).is_err());
+
+    slot.authenticated();
+    assert!(flag.load(Ordering::Acquire));
+    assert_eq!(limiter.in_flight(), 0);
+    let _other = limiter.try_acquire(ip("192.0.2.2")).unwrap();
+
+    // Dropping an authenticated slot must not release twice
+    drop(slot);
+    assert_eq!(limiter.in_flight(), 1);
+}
+
+#[test]
+fn zero_means_unlimited() {
+    let limiter = Arc::new(PreAuthLimiter::new(0, 0));
+    let slots: Vec<_> = (0..100)
+        .map(|_| limiter.try_acquire(ip("127.0.0.1")).unwrap())
+        .collect();
+    assert_eq!(limiter.in_flight(), 100);
+    drop(slots);
+    assert_eq!(limiter.in_flight(), 0);
+}
+
+#[test]
+fn drop_oldest_preauth_sheds_the_longest_waiting() {
+    let metrics = MetricsRegistry::new();
+    let limiter = Arc::new(
+        PreAuthLimiter::new(2, 1)
+            .with_policy(OverloadPolicy::DropOldestPreauth)
+            .with_metrics(&metrics),
+    );
+    let oldest = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    let newer = limiter.try_acquire(ip("192.0.2.2")).unwrap();
+
+    // Full: the newcomer gets the oldest connection's slot
+    let newest = limiter.try_acquire(ip("192.0.2.3")).unwrap();
+    assert!(oldest.shed_signal().is_cancelled());
+    assert!(!newer.shed_signal().is_cancelled());
+    assert_eq!(limiter.in_flight(), 2);
+    assert_eq!(limiter.in_flight_for(&ip("192.0.2.1")), 0);
+    assert_eq!(metrics.ssh_unauthenticated_connections.get(), 2);
+
+    // The shed slot was given back already; its drop frees nothing more
+    drop(oldest);
+    assert_eq!(limiter.in_flight(), 2);
+    assert_eq!(metrics.ssh_unauthenticated_connections.get(), 2);
+
+    // The per-IP limit still refuses instead of shedding
+    assert_eq!(
+        limiter.try_acquire(ip("192.0.2.3")).err(),
+        Some(PreAuthRejection::PerIp)
+    );
+    assert!(!newer.shed_signal().is_cancelled());
+    drop(newest);
+
+    let mut out = String::new();
+    encode(&mut out, &metrics.registry).unwrap();
+    assert!(
+        out.contains("s5_connections_shed_total{reason=\"oldest_pre_auth\"} 1"),
+        "{out}"
+    );
+}
+
+#[test]
+fn authenticated_connections_are_never_shed() {
+    let limiter =
+        Arc::new(PreAuthLimiter::new(1, 0).with_policy(OverloadPolicy::DropOldestPreauth));
+    let mut logged_in = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    logged_in.authenticated();
+    let waiting = limiter.try_acquire(ip("192.0.2.2")).unwrap();
+
+    let _newest = limiter.try_acquire(ip("192.0.2.3")).unwrap();
+    assert!(!logged_in.shed_signal().is_cancelled());
+    assert!(waiting.shed_signal().is_cancelled());
+}
+
+#[test]
+fn reject_new_counts_shed_connections() {
+    let metrics = MetricsRegistry::new();
+    let limiter = Arc::new(PreAuthLimiter::new(1, 0).with_metrics(&metrics));
+    let first = limiter.try_acquire(ip("192.0.2.1")).unwrap();
+    assert_eq!(
+        limiter.try_acquire(ip("192.0.2.2")).err(),
+        Some(PreAuthRejection::Global)
+    );
+    assert!(!first.shed_signal().is_cancelled());
+
+    let mut out = String::new();
+    encode(&mut out, &metrics.registry).unwrap();
+    assert!(
+        out.contains("s5_connections_shed_total{reason=\"pre_auth_full\"} 1"),
+        "{out}"
+    );
+}
+
+#[tokio::test]
+async fn deadline_fails_reads_until_authenticated() {
+    let (server, mut client) = tokio::io::duplex(1024);
+    let flag = Arc::new(AtomicBool::new(false));
+    let mut stream = PreAuthDeadline::new(server, Duration::from_millis(50), flag.clone());
+
+    client.write_all(b"SSH-2.0-slow").await.unwrap();
+    let mut buf = [0u8; 64];
+    assert_eq!(stream.read(&mut buf).await.unwrap(), 12);
+
+    // Stalled client: the pending read errors out at the deadline
+    let err = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    // Writes are untouched, and authentication lifts the deadline
+    stream.write_all(b"bye").await.unwrap();
+    flag.store(true, Ordering::Release);
+    client.write_all(b"data").await.unwrap();
+    assert_eq!(stream.read(&mut buf).await.unwrap(), 4);
+}
+
+#[test]
+fn pre_auth_limit_config_defaults_and_validation() {
+    let make = |limits: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[limits]
+{limits}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make("")).unwrap();
+    assert_eq!(config.limits.max_unauthenticated_connections, 256);
+    assert_eq!(config.limits.max_unauthenticated_per_ip, 64);
+    assert_eq!(config.limits.overload_policy, OverloadPolicy::RejectNew);
+
+    let config = parse_config(&make("overload_policy = \"drop-oldest-preauth\"")).unwrap();
+    assert_eq!(
+        config.limits.overload_policy,
+        OverloadPolicy::DropOldestPreauth
+    );
+    assert!(parse_config(&make("overload_policy = \"drop-newest\"")).is_err());
+
+    assert!(parse_config(&make("max_unauthenticated_connections = 0")).is_ok());
+    assert!(parse_config(&make(
+        "max_unauthenticated_connections = 0\nmax_unauthenticated_per_ip = 0"
+    ))
+    .is_ok());
+    assert!(parse_config(&make(
+        "max_unauthenticated_connections = 10\nmax_unauthenticated_per_ip = 20"
+    ))
+    .is_err());
+}
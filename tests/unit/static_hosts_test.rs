@@ -0,0 +1,150 @@
+use crate::test_support::parse_app_config;
+use s5::config::types::{AddressFamily, AppConfig, TcpConfig};
+use s5::proxy::connector;
+use s5::proxy::dns_cache::DnsCache;
+use s5::proxy::retry::RetryPolicy;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+fn config_with_hosts(hosts: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[proxy.hosts]\n{hosts}"), "")
+}
+
+fn pinned_cache(hosts: &str) -> DnsCache {
+    DnsCache::from_config(&config_with_hosts(hosts).unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn hosts_parse_from_config() {
+    let config = config_with_hosts("\"db.internal\" = [\"10.0.0.5\", \"fd00::5\"]").unwrap();
+    let addrs = &config.proxy.hosts["db.internal"];
+    assert_eq!(addrs.len(), 2);
+    assert!(addrs[1].is_ipv6());
+}
+
+#[test]
+fn invalid_hosts_rejected() {
+    let err = config_with_hosts("\"api.example.com\" = []").unwrap_err();
+    assert!(err.to_string().contains("api.example.com"), "{err}");
+    assert!(config_with_hosts("\"db.internal\" = [\"not-an-ip\"]").is_err());
+    assert!(config_with_hosts("\"db.internal:22\" = [\"10.0.0.5\"]").is_err());
+    assert!(config_with_hosts("\"\" = [\"10.0.0.5\"]").is_err());
+    assert!(config_with_hosts("").unwrap().proxy.hosts.is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Lookup
+// ---------------------------------------------------------------------------
+
+#[test]
+fn static_lookup_matches_names_loosely() {
+    let cache = pinned_cache("\"DB.Internal\" = [\"10.0.0.5\"]");
+    let expected: Vec<SocketAddr> = vec!["10.0.0.5:5432".parse().unwrap()];
+    assert_eq!(
+        cache.static_lookup("db.internal", 5432),
+        Some(expected.clone())
+    );
+    assert_eq!(cache.static_lookup("DB.INTERNAL.", 5432), Some(expected));
+    assert!(cache.static_lookup("other.internal", 5432).is_none());
+}
+
+#[test]
+fn static_hosts_work_with_cache_disabled() {
+    let config = config_with_hosts("\"db.internal\" = [\"10.0.0.5\"]").unwrap();
+    let cache = DnsCache::new(0, 100).with_static_hosts(&config.proxy.hosts);
+    assert!(!cache.is_enabled());
+    assert!(cache.static_lookup("db.internal", 80).is_some());
+}
+
+// ---------------------------------------------------------------------------
+// Connect
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn connect_uses_pinned_address_instead_of_dns() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // .invalid never resolves: only the pin can make this connect
+    let cache = pinned_cache("\"pinned.invalid\" = [\"127.0.0.1\"]");
+
+    let (_stream, addr) = connector::connect_with_cache(
+        "pinned.invalid",
+        port,
+        5,
+        false,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &cache,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(addr, listener.local_addr().unwrap());
+    // Pins are not cached lookups
+    assert!(cache.is_empty());
+}
+
+#[tokio::test]
+async fn pinned_addresses_still_pass_ip_guard() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let cache = pinned_cache("\"pinned.invalid\" = [\"127.0.0.1\"]");
+
+    let err = connector::connect_with_cache(
+        "pinned.invalid",
+        port,
+        5,
+        true,
+        &[],
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &cache,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("ip_guard"), "{err}");
+
+    let exemptions: Vec<ipnet::IpNet> = vec!["127.0.0.0/8".parse().unwrap()];
+    let result = connector::connect_with_cache(
+        "pinned.invalid",
+        port,
+        5,
+        true,
+        &exemptions,
+        AddressFamily::Any,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &cache,
+        None,
+    )
+    .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn pinned_addresses_follow_address_family() {
+    let cache = pinned_cache("\"pinned.invalid\" = [\"127.0.0.1\"]");
+    let err = connector::connect_with_cache(
+        "pinned.invalid",
+        80,
+        5,
+        false,
+        &[],
+        AddressFamily::Ipv6Only,
+        &TcpConfig::default(),
+        RetryPolicy::default(),
+        &cache,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("address_family"), "{err}");
+}
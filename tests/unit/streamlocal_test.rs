@@ -0,0 +1,100 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::auth::AuthService;
+use s5::config::types::AppConfig;
+use s5::proxy::streamlocal::{is_allowed, validate_pattern};
+
+/// Group `db` with `group` settings, and `alice` appended to alice.
+fn config_with(group: &str, alice: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[[groups]]\nname = \"db\"\n{group}"), alice)
+}
+
+/// Another `[[users]]` entry, to append after alice's.
+fn user(name: &str, extra: &str) -> String {
+    format!("\n[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\n{extra}\n")
+}
+
+fn list(paths: &[&str]) -> Vec<String> {
+    paths.iter().map(|p| p.to_string()).collect()
+}
+
+// ---------------------------------------------------------------------------
+// Allowlist matching
+// ---------------------------------------------------------------------------
+
+#[test]
+fn exact_path_matches_only_itself() {
+    let allowed = list(&["/run/postgresql/.s.PGSQL.5432"]);
+    assert!(is_allowed(&allowed, "/run/postgresql/.s.PGSQL.5432"));
+    assert!(!is_allowed(&allowed, "/run/postgresql/.s.PGSQL.5433"));
+    assert!(!is_allowed(&allowed, "/run/postgresql"));
+}
+
+#[test]
+fn directory_pattern_matches_direct_children() {
+    let allowed = list(&["/run/app/*"]);
+    assert!(is_allowed(&allowed, "/run/app/api.sock"));
+    assert!(!is_allowed(&allowed, "/run/app/nested/api.sock"));
+    assert!(!is_allowed(&allowed, "/run/app"));
+    assert!(!is_allowed(&allowed, "/run/application.sock"));
+}
+
+#[test]
+fn traversal_and_relative_paths_denied() {
+    let allowed = list(&["/run/app/*", "/var/run/docker.sock"]);
+    assert!(!is_allowed(&allowed, "/run/app/../../var/run/docker.sock"));
+    assert!(!is_allowed(&allowed, "/run/app/./api.sock"));
+    assert!(!is_allowed(&allowed, "run/app/api.sock"));
+    assert!(!is_allowed(&allowed, ""));
+    assert!(!is_allowed(&allowed, "/run/app/a\0b"));
+}
+
+#[test]
+fn empty_allowlist_denies_everything() {
+    assert!(!is_allowed(&[], "/run/app/api.sock"));
+}
+
+#[test]
+fn patterns_validated() {
+    assert!(validate_pattern("/run/db.sock").is_ok());
+    assert!(validate_pattern("/run/app/*").is_ok());
+    assert!(validate_pattern("run/db.sock").is_err());
+    assert!(validate_pattern("/run/../db.sock").is_err());
+    assert!(validate_pattern("/run/*.sock").is_err());
+    assert!(validate_pattern("/*").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn unix_sockets_resolve_user_over_group() {
+    let alice = [
+        "group = \"db\"\n".to_string(),
+        user(
+            "bob",
+            "group = \"db\"\nunix_sockets = [\"/run/redis.sock\"]",
+        ),
+        user("carol", ""),
+    ]
+    .concat();
+    let config = config_with("unix_sockets = [\"/run/postgresql/*\"]", &alice).unwrap();
+
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    assert_eq!(
+        store.get("alice").unwrap().unix_sockets,
+        list(&["/run/postgresql/*"])
+    );
+    assert_eq!(
+        store.get("bob").unwrap().unix_sockets,
+        list(&["/run/redis.sock"])
+    );
+    assert!(store.get("carol").unwrap().unix_sockets.is_empty());
+}
+
+#[test]
+fn invalid_unix_sockets_rejected() {
+    assert!(config_with("", "unix_sockets = [\"relative.sock\"]").is_err());
+    assert!(config_with("unix_sockets = [\"/run/*/x.sock\"]", "").is_err());
+}
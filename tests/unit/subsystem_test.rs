@@ -0,0 +1,231 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::auth::AuthService;
+use s5::config::types::{AppConfig, SubsystemConfig};
+use s5::ssh::subsystem::{is_allowed, validate};
+
+/// Subsystem `netconf`, `extra` sections, and `alice` appended to alice.
+fn config_with(extra: &str, alice: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(
+        &format!(
+            "[[subsystems]]\nname = \"netconf\"\nsocket = \"/run/netconf/netconf.sock\"\n\n{extra}"
+        ),
+        alice,
+    )
+}
+
+/// Another `[[users]]` entry, to append after alice's.
+fn user(name: &str, extra: &str) -> String {
+    format!("\n[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\n{extra}\n")
+}
+
+fn command(name: &str, argv: &[&str]) -> SubsystemConfig {
+    SubsystemConfig {
+        name: name.to_string(),
+        command: Some(argv.iter().map(|a| a.to_string()).collect()),
+        socket: None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry entries
+// ---------------------------------------------------------------------------
+
+#[test]
+fn entries_validated() {
+    assert!(validate(&command("netconf", &["/usr/bin/netconf-proxy", "--stdio"])).is_ok());
+    assert!(validate(&command("netconf", &["netconf-proxy"])).is_err());
+    assert!(validate(&command("netconf", &[])).is_err());
+    assert!(validate(&command("", &["/bin/cat"])).is_err());
+    assert!(validate(&command("net conf", &["/bin/cat"])).is_err());
+    // The virtual shell has no host filesystem to serve over SFTP
+    assert!(validate(&command("sftp", &["/usr/lib/openssh/sftp-server"])).is_err());
+
+    let socket = |path: &str| SubsystemConfig {
+        name: "netconf".to_string(),
+        command: None,
+        socket: Some(path.to_string()),
+    };
+    assert!(validate(&socket("/run/netconf.sock")).is_ok());
+    assert!(validate(&socket("netconf.sock")).is_err());
+    let both = SubsystemConfig {
+        command: Some(vec!["/bin/cat".to_string()]),
+        ..socket("/run/netconf.sock")
+    };
+    assert!(validate(&both).is_err());
+}
+
+#[test]
+fn allowlist_matches_exact_names() {
+    let allowed = vec!["netconf".to_string()];
+    assert!(is_allowed(&allowed, "netconf"));
+    assert!(!is_allowed(&allowed, "NETCONF"));
+    assert!(!is_allowed(&allowed, "sftp"));
+    assert!(!is_allowed(&[], "netconf"));
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn subsystems_resolve_user_over_group() {
+    let groups = "[[subsystems]]\nname = \"yang\"\ncommand = [\"/usr/bin/yang\"]\n\n\
+                  [[groups]]\nname = \"netops\"\nsubsystems = [\"netconf\", \"yang\"]";
+    let alice = [
+        "group = \"netops\"\n".to_string(),
+        user("bob", "group = \"netops\"\nsubsystems = [\"netconf\"]"),
+        user("carol", ""),
+    ]
+    .concat();
+    let config = config_with(groups, &alice).unwrap();
+    assert_eq!(config.subsystems.len(), 2);
+
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    assert_eq!(
+        store.get("alice").unwrap().subsystems,
+        vec!["netconf", "yang"]
+    );
+    assert_eq!(store.get("bob").unwrap().subsystems, vec!["netconf"]);
+    assert!(store.get("carol").unwrap().subsystems.is_empty());
+}
+
+#[test]
+fn invalid_registry_rejected() {
+    let err = config_with("", "subsystems = [\"yang\"]").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("'yang' is not a [[subsystems]] entry"));
+
+    let duplicate = "[[subsystems]]\nname = \"netconf\"\ncommand = [\"/usr/bin/netconf\"]";
+    let err = config_with(duplicate, "").unwrap_err();
+    assert!(err.to_string().contains("duplicate subsystem"));
+
+    let sftp = "[[subsystems]]\nname = \"sftp\"\ncommand = [\"/usr/lib/sftp-server\"]";
+    assert!(config_with(sftp, "").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Relay
+// ---------------------------------------------------------------------------
+
+#[cfg(unix)]
+mod relay {
+    use super::command;
+    use s5::ssh::subsystem::{open, relay, Outcome};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Send `input` then EOF through a subsystem, returning its output.
+    async fn run(
+        subsystem: &s5::config::types::SubsystemConfig,
+        input: &[u8],
+    ) -> (Vec<u8>, Outcome) {
+        let backend = open(subsystem, None, "alice", "192.0.2.7", TIMEOUT)
+            .await
+            .unwrap();
+        let (mut client, channel) = tokio::io::duplex(64 * 1024);
+        let task = tokio::spawn(relay(channel, backend));
+        client.write_all(input).await.unwrap();
+        client.shutdown().await.unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        (output, task.await.unwrap().unwrap())
+    }
+
+    #[tokio::test]
+    async fn command_stdio_relayed_after_half_close() {
+        let (output, outcome) = run(&command("echo", &["/bin/cat"]), b"<hello/>").await;
+        assert_eq!(output, b"<hello/>");
+        assert_eq!(
+            outcome,
+            Outcome {
+                bytes_up: 8,
+                bytes_down: 8,
+                exit_status: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn command_gets_only_session_environment() {
+        let script = "printf '%s %s %s' \"$S5_USER\" \"$S5_SOURCE_IP\" \"${HOME:-none}\"; exit 3";
+        let (output, outcome) = run(&command("env", &["/bin/sh", "-c", script]), b"").await;
+        assert_eq!(output, b"alice 192.0.2.7 none");
+        assert_eq!(outcome.exit_status, 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn fs_allow_confines_commands() {
+        if s5::sandbox::FsConfinement::new(&[]).is_err() {
+            return; // No Landlock in this kernel
+        }
+        let allowed = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        std::fs::write(allowed.path().join("in"), "in ").unwrap();
+        std::fs::write(other.path().join("out"), "out").unwrap();
+        let script = format!(
+            "cat {0}/in; cat {1}/out || printf denied; printf x > {0}/new",
+            allowed.path().display(),
+            other.path().display()
+        );
+        let subsystem = command("data", &["/bin/sh", "-c", &script]);
+        let fs_allow = [allowed.path().to_string_lossy().into_owned()];
+        let backend = open(&subsystem, Some(&fs_allow), "alice", "192.0.2.7", TIMEOUT)
+            .await
+            .unwrap();
+        let (mut client, channel) = tokio::io::duplex(1024);
+        let task = tokio::spawn(relay(channel, backend));
+        client.shutdown().await.unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        task.await.unwrap().unwrap();
+        assert_eq!(output, b"in denied");
+        assert!(allowed.path().join("new").exists());
+
+        let missing = ["/nonexistent/data".to_string()];
+        assert!(
+            open(&subsystem, Some(&missing), "alice", "192.0.2.7", TIMEOUT)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn socket_relayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("netconf.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            stream.read_to_end(&mut request).await.unwrap();
+            stream.write_all(b"reply:").await.unwrap();
+            stream.write_all(&request).await.unwrap();
+        });
+
+        let subsystem = s5::config::types::SubsystemConfig {
+            name: "netconf".to_string(),
+            command: None,
+            socket: Some(path.to_string_lossy().into_owned()),
+        };
+        let (output, outcome) = run(&subsystem, b"get").await;
+        assert_eq!(output, b"reply:get");
+        assert_eq!((outcome.bytes_up, outcome.bytes_down), (3, 9));
+    }
+
+    #[tokio::test]
+    async fn missing_socket_fails_to_open() {
+        let subsystem = s5::config::types::SubsystemConfig {
+            name: "netconf".to_string(),
+            command: None,
+            socket: Some("/nonexistent/netconf.sock".to_string()),
+        };
+        assert!(open(&subsystem, None, "alice", "192.0.2.7", TIMEOUT)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use s5::config::parse_config;
+use s5::metrics::MetricsRegistry;
+use s5::security::tarpit::{tarpit_line, Tarpit, MAX_LINE_LEN};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio_util::sync::CancellationToken;
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+#[test]
+fn tarpit_lines_are_short_and_never_an_ssh_banner() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let line = tarpit_line(&mut rng);
+        assert!(line.ends_with(b"\r\n"));
+        let body = &line[..line.len() - 2];
+        assert!((3..=MAX_LINE_LEN).contains(&body.len()));
+        assert!(body.iter().all(u8::is_ascii_alphanumeric));
+        assert!(!line.starts_with(b"SSH-"));
+    }
+}
+
+#[tokio::test]
+async fn tarpit_drips_lines_until_max_duration() {
+    let metrics = MetricsRegistry::new();
+    let tarpit = Tarpit::new(
+        4,
+        Duration::from_millis(20),
+        Some(Duration::from_millis(200)),
+    )
+    .with_metrics(&metrics);
+    let (server, client) = tokio::io::duplex(4096);
+
+    assert!(tarpit.try_trap(server, CancellationToken::new()));
+    assert_eq!(tarpit.active(), 1);
+    assert_eq!(metrics.tarpit_connections_total.get(), 1);
+
+    let mut reader = BufReader::new(client);
+    let mut lines = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        if n == 0 {
+            break;
+        }
+        assert!(!line.starts_with("SSH-"));
+        lines += 1;
+    }
+    assert!(lines >= 2, "got {} lines", lines);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(tarpit.active(), 0);
+    assert_eq!(metrics.tarpit_active.get(), 0);
+}
+
+#[tokio::test]
+async fn tarpit_caps_concurrent_sockets() {
+    let metrics = MetricsRegistry::new();
+    let tarpit = Tarpit::new(1, Duration::from_millis(10), None).with_metrics(&metrics);
+    let shutdown = CancellationToken::new();
+
+    let (first, mut first_client) = tokio::io::duplex(4096);
+    assert!(tarpit.try_trap(first, shutdown.clone()));
+    let (second, _second_client) = tokio::io::duplex(4096);
+    assert!(!tarpit.try_trap(second, shutdown.clone()));
+    assert_eq!(metrics.tarpit_rejected_total.get(), 1);
+
+    // Client hangs up: the slot is released
+    let mut buf = [0u8; 64];
+    assert!(first_client.read(&mut buf).await.unwrap() > 0);
+    drop(first_client);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while tarpit.active() > 0 && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(tarpit.active(), 0);
+
+    let (third, mut third_client) = tokio::io::duplex(4096);
+    assert!(tarpit.try_trap(third, shutdown.clone()));
+
+    // Shutdown releases held sockets
+    shutdown.cancel();
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(2), third_client.read_to_end(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metrics.tarpit_connections_total.get(), 2);
+}
+
+#[test]
+fn tarpit_config_defaults_and_validation() {
+    let make = |security: &str| {
+        format!(
+            r##"
+[server]
+ssh_listen = "0.0.0.0:2222"
+
+[security]
+{security}
+
+[[users]]
+username = "test"
+password_hash = "{FAKE_HASH}"
+"##
+        )
+    };
+    let config = parse_config(&make("tarpit_enabled = true")).unwrap();
+    assert_eq!(config.security.tarpit_max_connections, 256);
+    assert_eq!(config.security.tarpit_interval, 10);
+    assert_eq!(config.security.tarpit_max_duration, 3600);
+    assert!(parse_config(&make("tarpit_enabled = true\ntarpit_max_duration = 0")).is_ok());
+
+    for bad in [
+        "tarpit_enabled = true\ntarpit_max_connections = 0",
+        "tarpit_enabled = true\ntarpit_interval = 0",
+    ] {
+        assert!(parse_config(&make(bad)).is_err(), "{}", bad);
+    }
+    // Disabled: values are not checked
+    assert!(parse_config(&make("tarpit_interval = 0")).is_ok());
+}
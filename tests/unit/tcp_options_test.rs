@@ -0,0 +1,117 @@
+use crate::test_support::parse_app_config;
+use s5::config::types::{AddressFamily, AppConfig, TcpConfig};
+use s5::proxy::connector;
+use s5::proxy::dns_cache::DnsCache;
+use s5::proxy::retry::RetryPolicy;
+use tokio::net::{TcpListener, TcpStream};
+
+fn config_with_tcp(tcp: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[proxy.tcp]\n{tcp}"), "")
+}
+
+async fn connect_local(tcp: &TcpConfig) -> (TcpStream, TcpListener) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stream, _) = connector::connect_with_cache(
+        "127.0.0.1",
+        port,
+        5,
+        false,
+        &[],
+        AddressFamily::Any,
+        tcp,
+        RetryPolicy::default(),
+        &DnsCache::new(0, 100),
+        None,
+    )
+    .await
+    .unwrap();
+    (stream, listener)
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tcp_defaults_match_previous_behavior() {
+    let tcp = config_with_tcp("").unwrap().proxy.tcp;
+    assert_eq!(tcp.keepalive_time_secs, 60);
+    assert_eq!(tcp.keepalive_interval_secs, 15);
+    assert!(tcp.keepalive_retries.is_none());
+    assert_eq!(tcp.user_timeout_ms, 0);
+    assert!(!tcp.fast_open);
+}
+
+#[test]
+fn tcp_options_parse_from_config() {
+    let config = config_with_tcp(
+        "keepalive_time_secs = 30\nkeepalive_interval_secs = 5\nkeepalive_retries = 4\n\
+         user_timeout_ms = 20000\nfast_open = true",
+    )
+    .unwrap();
+    let tcp = &config.proxy.tcp;
+    assert_eq!(tcp.keepalive_time_secs, 30);
+    assert_eq!(tcp.keepalive_interval_secs, 5);
+    assert_eq!(tcp.keepalive_retries, Some(4));
+    assert_eq!(tcp.user_timeout_ms, 20000);
+    assert!(tcp.fast_open);
+}
+
+#[test]
+fn invalid_tcp_options_rejected() {
+    assert!(config_with_tcp("keepalive_interval_secs = 0").is_err());
+    assert!(config_with_tcp("keepalive_retries = 0").is_err());
+    assert!(config_with_tcp("user_timeout_ms = 5000000000").is_err());
+    // Keepalive off: the interval is not used
+    assert!(config_with_tcp("keepalive_time_secs = 0\nkeepalive_interval_secs = 0").is_ok());
+}
+
+// ---------------------------------------------------------------------------
+// Socket options
+// ---------------------------------------------------------------------------
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn connect_applies_tcp_options() {
+    let tcp = TcpConfig {
+        keepalive_time_secs: 30,
+        keepalive_interval_secs: 5,
+        keepalive_retries: Some(4),
+        user_timeout_ms: 20_000,
+        fast_open: false,
+    };
+    let (stream, _listener) = connect_local(&tcp).await;
+    let sock = socket2::SockRef::from(&stream);
+    assert!(sock.keepalive().unwrap());
+    assert_eq!(sock.tcp_keepalive_time().unwrap().as_secs(), 30);
+    assert_eq!(sock.tcp_keepalive_interval().unwrap().as_secs(), 5);
+    assert_eq!(sock.tcp_keepalive_retries().unwrap(), 4);
+    assert_eq!(
+        sock.tcp_user_timeout().unwrap(),
+        Some(std::time::Duration::from_millis(20_000))
+    );
+    assert!(stream.nodelay().unwrap());
+}
+
+#[tokio::test]
+async fn keepalive_zero_leaves_keepalive_off() {
+    let tcp = TcpConfig {
+        keepalive_time_secs: 0,
+        ..TcpConfig::default()
+    };
+    let (stream, _listener) = connect_local(&tcp).await;
+    assert!(!socket2::SockRef::from(&stream).keepalive().unwrap());
+}
+
+#[tokio::test]
+async fn fast_open_connects() {
+    // Without a cookie (or kernel support) this is a regular handshake
+    let tcp = TcpConfig {
+        fast_open: true,
+        ..TcpConfig::default()
+    };
+    let (stream, listener) = connect_local(&tcp).await;
+    let (_accepted, peer) = listener.accept().await.unwrap();
+    assert_eq!(peer, stream.local_addr().unwrap());
+}
@@ -0,0 +1,99 @@
+use crate::test_support::parse_app_config;
+use s5::auth::tenant::{in_tenant, login_name, tenant_of};
+use s5::config::types::{AppConfig, TenantConfig};
+
+const ARGON_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$AAAAAAAAAAAAAAAAAAAAAA$\
+                          +gVb4WOMEuMQxSgOBapKnZaHMIDjQJF3Tv7RCyKp9Bo";
+
+fn tenants() -> Vec<TenantConfig> {
+    ["acme", "globex.example"]
+        .into_iter()
+        .map(|name| TenantConfig {
+            name: name.to_string(),
+            api_token: None,
+        })
+        .collect()
+}
+
+fn config(extra: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(extra, "")
+}
+
+#[test]
+fn suffix_selects_the_tenant() {
+    let tenants = tenants();
+    assert_eq!(tenant_of(&tenants, "alice@acme"), Some("acme"));
+    assert_eq!(
+        tenant_of(&tenants, "bob@globex.example"),
+        Some("globex.example")
+    );
+    // Not a configured tenant: a plain username of the default namespace
+    assert_eq!(tenant_of(&tenants, "carol@example.com"), None);
+    assert_eq!(tenant_of(&tenants, "dave"), None);
+
+    assert!(in_tenant("alice@acme", "acme"));
+    assert!(!in_tenant("alice@notacme", "acme"));
+    assert!(!in_tenant("@acme", "acme"));
+    assert!(!in_tenant("acme", "acme"));
+}
+
+#[test]
+fn tenant_listener_qualifies_and_refuses() {
+    let tenants = tenants();
+    // Default listener: names are taken as sent
+    assert_eq!(
+        login_name(&tenants, None, "alice@acme").as_deref(),
+        Some("alice@acme")
+    );
+    assert_eq!(login_name(&tenants, None, "dave").as_deref(), Some("dave"));
+
+    let acme = Some("acme");
+    assert_eq!(
+        login_name(&tenants, acme, "alice").as_deref(),
+        Some("alice@acme")
+    );
+    assert_eq!(
+        login_name(&tenants, acme, "alice@acme").as_deref(),
+        Some("alice@acme")
+    );
+    assert_eq!(
+        login_name(&tenants, acme, "carol@example.com").as_deref(),
+        Some("carol@example.com@acme")
+    );
+    assert_eq!(login_name(&tenants, acme, "bob@globex.example"), None);
+}
+
+#[test]
+fn tenant_config_is_validated() {
+    let cfg = config(
+        "[[tenants]]\nname = \"acme\"\napi_token = \"acme-token-0123456789\"\n\n\
+         [[server.listeners]]\nname = \"acme\"\nlisten = \"127.0.0.1:2223\"\ntenant = \"acme\"",
+    )
+    .unwrap();
+    assert_eq!(cfg.tenants[0].name, "acme");
+    assert_eq!(cfg.server.listeners[0].tenant.as_deref(), Some("acme"));
+    // The token stays out of debug output
+    assert!(!format!("{:?}", cfg.tenants).contains("acme-token"));
+
+    assert!(config("[[tenants]]\nname = \"Acme\"").is_err());
+    assert!(config("[[tenants]]\nname = \"a:b\"").is_err());
+    assert!(config("[[tenants]]\nname = \"acme\"\n\n[[tenants]]\nname = \"acme\"").is_err());
+    assert!(config("[[tenants]]\nname = \"acme\"\napi_token = \"short\"").is_err());
+    assert!(config(
+        "[[server.listeners]]\nname = \"x\"\nlisten = \"127.0.0.1:2223\"\ntenant = \"acme\""
+    )
+    .is_err());
+}
+
+#[test]
+fn tenant_accounts_cannot_administer() {
+    let account = |role: &str| {
+        config(&format!(
+            "[[tenants]]\nname = \"acme\"\n\n\
+             [[api.accounts]]\nusername = \"ops\"\npassword_hash = \"{ARGON_HASH}\"\n\
+             role = \"{role}\"\ntenant = \"acme\""
+        ))
+    };
+    assert!(account("operator").is_ok());
+    assert!(account("admin").is_err());
+}
@@ -0,0 +1,185 @@
+use crate::test_support::{parse_app_config, FAKE_HASH};
+use s5::auth::AuthService;
+use s5::config::acl::ParsedAcl;
+use s5::config::types::{AclPolicyConfig, AppConfig, ShellPermissions, UserRole};
+use s5::shell::context::ShellContext;
+use s5::shell::executor::CommandExecutor;
+use s5::ssh::terminal::{validate_env_pattern, validate_term, TerminalPolicy, DEFAULT_TERM};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Group `devs` with `group` settings, and `alice` appended to alice.
+fn config_with(group: &str, alice: &str) -> anyhow::Result<AppConfig> {
+    parse_app_config(&format!("[[groups]]\nname = \"devs\"\n{group}"), alice)
+}
+
+/// Another `[[users]]` entry, to append after alice's.
+fn user(name: &str, extra: &str) -> String {
+    format!("\n[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\n{extra}\n")
+}
+
+fn policy(accept_env: &[&str]) -> TerminalPolicy {
+    TerminalPolicy {
+        accept_env: accept_env.iter().map(|p| p.to_string()).collect(),
+        ..TerminalPolicy::default()
+    }
+}
+
+fn shell_context(env: Vec<(String, String)>) -> ShellContext {
+    ShellContext {
+        username: "alice".to_string(),
+        auth_method: "password".to_string(),
+        source_ip: "127.0.0.1".to_string(),
+        role: UserRole::User,
+        group: None,
+        permissions: ShellPermissions::default(),
+        acl: ParsedAcl::from_config(AclPolicyConfig::Allow, &[], &[]).unwrap(),
+        colors: false,
+        expires_at: None,
+        max_bandwidth_kbps: 0,
+        server_start_time: Instant::now(),
+        bookmarks: HashMap::new(),
+        aliases: HashMap::new(),
+        ssh_key_fingerprint: None,
+        proxy_engine: None,
+        quota_tracker: None,
+        quota_config: None,
+        env,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Environment
+// ---------------------------------------------------------------------------
+
+#[test]
+fn nothing_accepted_by_default() {
+    let policy = TerminalPolicy::default();
+    assert!(!policy.accepts_env("LANG", "C.UTF-8"));
+    assert!(!policy.accepts_env("TERM", "vt100"));
+}
+
+#[test]
+fn accept_env_wildcards() {
+    let policy = policy(&["LANG", "LC_*", "GIT_?UTHOR_NAME"]);
+    assert!(policy.accepts_env("LANG", "fr_FR.UTF-8"));
+    assert!(policy.accepts_env("LC_ALL", "C"));
+    assert!(policy.accepts_env("LC_", "C"));
+    assert!(policy.accepts_env("GIT_AUTHOR_NAME", "Alice"));
+    assert!(!policy.accepts_env("LANGUAGE", "fr"));
+    assert!(!policy.accepts_env("XLC_ALL", "C"));
+    assert!(!policy.accepts_env("GIT_COMMITTER_NAME", "Alice"));
+}
+
+#[test]
+fn unsafe_names_and_values_refused() {
+    let policy = policy(&["*"]);
+    assert!(policy.accepts_env("EDITOR", "vim"));
+    assert!(!policy.accepts_env("", "x"));
+    assert!(!policy.accepts_env("1ABC", "x"));
+    assert!(!policy.accepts_env("A=B", "x"));
+    // Printed by `env`: no terminal escape sequences
+    assert!(!policy.accepts_env("EDITOR", "\x1b]0;pwned\x07"));
+    assert!(!policy.accepts_env("EDITOR", "a\r\nPATH=/tmp"));
+    assert!(!policy.accepts_env("EDITOR", &"x".repeat(2000)));
+}
+
+// ---------------------------------------------------------------------------
+// TERM and window size
+// ---------------------------------------------------------------------------
+
+#[test]
+fn client_term_replaces_default() {
+    let policy = TerminalPolicy {
+        default_term: "vt100".to_string(),
+        ..TerminalPolicy::default()
+    };
+    assert_eq!(policy.term("screen-256color"), "screen-256color");
+    assert_eq!(policy.term(""), "vt100");
+    assert_eq!(policy.term("xterm\x1b[31m"), "vt100");
+    assert_eq!(TerminalPolicy::default().term(""), DEFAULT_TERM);
+}
+
+#[test]
+fn window_size_clamped() {
+    let policy = TerminalPolicy {
+        max_cols: 200,
+        max_rows: 60,
+        ..TerminalPolicy::default()
+    };
+    assert_eq!(policy.clamp_size(80, 24), (80, 24));
+    assert_eq!(policy.clamp_size(u32::MAX, 1000), (200, 60));
+    // 0 = no limit
+    assert_eq!(
+        TerminalPolicy::default().clamp_size(u32::MAX, u32::MAX),
+        (u32::MAX, u32::MAX)
+    );
+}
+
+#[test]
+fn env_command_shows_session_variables() {
+    let mut exec = CommandExecutor::new("alice".to_string(), "bastion".to_string());
+    exec.set_context(shell_context(vec![
+        ("TERM".to_string(), "screen".to_string()),
+        ("LANG".to_string(), "C.UTF-8".to_string()),
+    ]));
+    let output = exec.execute("env").output;
+    assert!(output.contains("USER=alice\r\n"));
+    assert!(output.contains("TERM=screen\r\n"));
+    assert!(!output.contains("TERM=xterm-256color"));
+    assert!(output.ends_with("LANG=C.UTF-8\r\n"));
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn terminal_resolved_field_by_field() {
+    let alice = [
+        "group = \"devs\"\n".to_string(),
+        user(
+            "bob",
+            "group = \"devs\"\n\n[users.terminal]\naccept_env = [\"LANG\"]\nmax_cols = 300",
+        ),
+        user("carol", ""),
+    ]
+    .concat();
+    let group = "[groups.terminal]\naccept_env = [\"LC_*\"]\nallow_pty = false\nmax_cols = 120";
+    let config = config_with(group, &alice).unwrap();
+
+    let auth = AuthService::new(&config).unwrap();
+    let store = auth.user_store();
+    let alice = &store.get("alice").unwrap().terminal;
+    assert_eq!(alice.accept_env, vec!["LC_*".to_string()]);
+    assert!(!alice.allow_pty);
+    assert_eq!(alice.max_cols, 120);
+
+    let bob = &store.get("bob").unwrap().terminal;
+    assert_eq!(bob.accept_env, vec!["LANG".to_string()]);
+    assert!(!bob.allow_pty);
+    assert_eq!(bob.max_cols, 300);
+    assert_eq!(bob.default_term, DEFAULT_TERM);
+
+    assert_eq!(
+        store.get("carol").unwrap().terminal,
+        TerminalPolicy::default()
+    );
+}
+
+#[test]
+fn invalid_terminal_rejected() {
+    assert!(validate_env_pattern("LC_*").is_ok());
+    assert!(validate_env_pattern("").is_err());
+    assert!(validate_env_pattern("LC ALL").is_err());
+    assert!(validate_term("xterm-256color").is_ok());
+    assert!(validate_term("").is_err());
+    assert!(validate_term("xterm\n").is_err());
+
+    let err = config_with("", "[users.terminal]\naccept_env = [\"A=B\"]").unwrap_err();
+    assert!(err.to_string().contains("terminal.accept_env"));
+    let err = config_with("[groups.terminal]\ndefault_term = \"\"", "").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("group 'devs' terminal.default_term"));
+}
@@ -25,12 +25,24 @@ pub fn default_server_config() -> ServerConfig {
         socks5_tls_key: None,
         dns_cache_ttl: -1,
         dns_cache_max_entries: 1000,
+        dns_prefetch_hosts: 0,
         connect_retry: 0,
         connect_retry_delay_ms: 1000,
         bookmarks_path: None,
         ssh_keepalive_interval_secs: 15,
         ssh_keepalive_max: 3,
         ssh_auth_timeout: 120,
+        ssh_window_size: 2 * 1024 * 1024,
+        ssh_max_packet_size: 32 * 1024,
+        ssh_stall_threshold_ms: 5000,
+        max_connections: 0,
+        max_connections_per_user: 0,
+        connection_queue_timeout_ms: 0,
+        connection_queue_size: 0,
+        listeners: Vec::new(),
+        host_key_rotation: HostKeyRotationConfig::default(),
+        maintenance_message: "Server is under maintenance. Please try again later.".to_string(),
+        socket_handover: false,
     }
 }
 
@@ -40,12 +52,18 @@ pub fn default_user_config(username: &str) -> UserConfig {
         username: username.to_string(),
         password_hash: Some("hash".to_string()),
         authorized_keys: Vec::new(),
-        allow_forwarding: true,
+        allow_forwarding: Some(true),
         allow_shell: true,
         max_new_connections_per_minute: 0,
         max_bandwidth_kbps: 0,
         source_ips: Vec::new(),
         expires_at: None,
+        disabled: false,
+        require_security_key: false,
+        allow_vpn: false,
+        vpn_address: None,
+        tags: Vec::new(),
+        authorized_keys_url: None,
         upstream_proxy: None,
         acl: Default::default(),
         totp_secret: None,
@@ -54,8 +72,10 @@ pub fn default_user_config(username: &str) -> UserConfig {
         group: None,
         role: UserRole::default(),
         shell_permissions: None,
+        terminal: None,
         motd: None,
         quotas: None,
+        quota_plan: None,
         time_access: None,
         auth_methods: None,
         idle_warning_secs: None,
@@ -65,6 +85,15 @@ pub fn default_user_config(username: &str) -> UserConfig {
         aliases: HashMap::new(),
         max_connections: None,
         rate_limits: None,
+        api_token_hash: None,
+        api_tokens: Vec::new(),
+        ip_guard_exemptions: None,
+        jump_targets: None,
+        unix_sockets: None,
+        allowed_hassh: None,
+        subsystems: None,
+        impossible_travel: None,
+        password_changed_at: None,
     }
 }
 
@@ -100,6 +129,30 @@ allow_shell = true
     toml::from_str(&toml_str).unwrap()
 }
 
+/// TOML of a config with `extra_toml` after `[server]` and `user_toml` appended
+/// to alice's `[[users]]` entry.
+pub fn app_config_toml(extra_toml: &str, user_toml: &str) -> String {
+    format!(
+        r##"
+[server]
+ssh_listen = "127.0.0.1:2222"
+
+{extra_toml}
+
+[[users]]
+username = "alice"
+password_hash = "{FAKE_HASH}"
+{user_toml}
+"##
+    )
+}
+
+/// Parse and validate [`app_config_toml`], for tests of config validation and
+/// of components built from a config.
+pub fn parse_app_config(extra_toml: &str, user_toml: &str) -> anyhow::Result<AppConfig> {
+    s5::config::parse_config(&app_config_toml(extra_toml, user_toml))
+}
+
 /// Build a minimal `AppConfig` with a custom password hash (for auth tests).
 pub fn config_with_hash(password_hash: &str) -> AppConfig {
     let toml_str = format!(
@@ -195,9 +248,30 @@ pub fn full_app_config(users: Vec<UserConfig>, security: SecurityConfig) -> AppC
         acl: GlobalAclConfig::default(),
         users,
         groups: Vec::new(),
+        tenants: Default::default(),
         motd: MotdConfig::default(),
         alerting: AlertingConfig::default(),
         maintenance_windows: Vec::new(),
+        quota_plans: Default::default(),
         connection_pool: ConnectionPoolConfig::default(),
+        threat_intel: Default::default(),
+        ip_intel: Default::default(),
+        honeypot: Default::default(),
+        login_anomaly: Default::default(),
+        impossible_travel: Default::default(),
+        invitations: Default::default(),
+        key_enrollment: Default::default(),
+        authorized_keys_fetch: Default::default(),
+        notifications: Default::default(),
+        reports: Default::default(),
+        cluster: Default::default(),
+        sandbox: Default::default(),
+        vpn: Default::default(),
+        subsystems: Vec::new(),
+        proxy: Default::default(),
+        policy: Default::default(),
+        scripting: None,
+        masque: None,
+        gssapi: None,
     }
 }
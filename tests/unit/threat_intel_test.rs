@@ -0,0 +1,303 @@
+use s5::config::types::{CrowdSecConfig, DenylistConfig, ThreatIntelConfig};
+use s5::metrics::collectors::FeedLabel;
+use s5::metrics::MetricsRegistry;
+use s5::security::ban::BanManager;
+use s5::security::threat_intel::{
+    parse_crowdsec_stream, parse_denylist, parse_go_duration, spawn_refresh_task, Decision,
+    ThreatIntel, CROWDSEC_FEED,
+};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn decision(net: &str, expires: Option<Instant>) -> Decision {
+    Decision {
+        net: net.parse().unwrap(),
+        expires,
+    }
+}
+
+fn label(feed: &str) -> FeedLabel {
+    FeedLabel {
+        feed: feed.to_string(),
+    }
+}
+
+#[test]
+fn denylist_parsing_skips_comments_and_invalid_lines() {
+    let text = "\
+; Spamhaus DROP List
+192.0.2.0/24 ; SBL123
+198.51.100.7
+# comment
+
+2001:db8::/32
+not-an-ip
+203.0.113.9   trailing words
+";
+    let (nets, invalid) = parse_denylist(text);
+    let nets: Vec<String> = nets.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        nets,
+        [
+            "192.0.2.0/24",
+            "198.51.100.7/32",
+            "2001:db8::/32",
+            "203.0.113.9/32"
+        ]
+    );
+    assert_eq!(invalid, 1);
+}
+
+#[test]
+fn go_durations_parse() {
+    assert_eq!(parse_go_duration("4h"), Some(Duration::from_secs(14_400)));
+    assert_eq!(
+        parse_go_duration("3h59m58.5s"),
+        Some(Duration::from_millis(14_398_500))
+    );
+    assert_eq!(parse_go_duration("250ms"), Some(Duration::from_millis(250)));
+    assert_eq!(parse_go_duration("0"), Some(Duration::ZERO));
+    assert_eq!(parse_go_duration("-12s"), Some(Duration::ZERO));
+    assert_eq!(parse_go_duration("5d"), None);
+    assert_eq!(parse_go_duration("h"), None);
+}
+
+#[test]
+fn crowdsec_stream_keeps_ip_and_range_bans_only() {
+    let body = r#"{
+        "new": [
+            {"scope": "Ip", "value": "192.0.2.1", "type": "ban", "duration": "1h"},
+            {"scope": "Range", "value": "198.51.100.0/24", "type": "ban", "duration": "10m"},
+            {"scope": "Ip", "value": "192.0.2.2", "type": "captcha", "duration": "1h"},
+            {"scope": "Country", "value": "XX", "type": "ban", "duration": "1h"},
+            {"scope": "Ip", "value": "garbage", "type": "ban", "duration": "1h"}
+        ],
+        "deleted": [
+            {"scope": "Ip", "value": "203.0.113.5", "type": "ban", "duration": "-3s"}
+        ]
+    }"#;
+    let now = Instant::now();
+    let (new, deleted) = parse_crowdsec_stream(body, now).unwrap();
+    assert_eq!(
+        new,
+        [
+            decision("192.0.2.1/32", Some(now + Duration::from_secs(3600))),
+            decision("198.51.100.0/24", Some(now + Duration::from_secs(600))),
+        ]
+    );
+    assert_eq!(deleted, ["203.0.113.5/32".parse().unwrap()]);
+
+    // LAPI sends null when there is nothing new
+    let (new, deleted) = parse_crowdsec_stream(r#"{"new": null, "deleted": null}"#, now).unwrap();
+    assert!(new.is_empty() && deleted.is_empty());
+    assert!(parse_crowdsec_stream("not json", now).is_err());
+}
+
+#[test]
+fn lookup_matches_ips_and_ranges_and_ignores_expired() {
+    let ti = ThreatIntel::new();
+    let past = Instant::now() - Duration::from_secs(1);
+    ti.replace_feed(
+        "drop",
+        vec![
+            decision("192.0.2.0/24", None),
+            decision("2001:db8::1/128", None),
+        ],
+    );
+    ti.apply_delta(
+        CROWDSEC_FEED,
+        vec![
+            decision("198.51.100.7/32", None),
+            decision("203.0.113.1/32", Some(past)),
+        ],
+        &[],
+    );
+
+    assert_eq!(ti.lookup(&ip("192.0.2.200")).as_deref(), Some("drop"));
+    assert_eq!(ti.lookup(&ip("2001:db8::1")).as_deref(), Some("drop"));
+    assert_eq!(
+        ti.lookup(&ip("198.51.100.7")).as_deref(),
+        Some(CROWDSEC_FEED)
+    );
+    assert_eq!(ti.lookup(&ip("203.0.113.1")), None);
+    assert_eq!(ti.lookup(&ip("10.0.0.1")), None);
+    // Expired entries are purged on update
+    assert_eq!(ti.feed_len(CROWDSEC_FEED), 1);
+}
+
+#[test]
+fn delta_removes_deleted_decisions_and_replace_resets_feed() {
+    let ti = ThreatIntel::new();
+    ti.apply_delta(
+        CROWDSEC_FEED,
+        vec![
+            decision("192.0.2.1/32", None),
+            decision("198.51.100.0/24", None),
+        ],
+        &[],
+    );
+    ti.apply_delta(
+        CROWDSEC_FEED,
+        vec![decision("192.0.2.3/32", None)],
+        &[
+            "192.0.2.1/32".parse().unwrap(),
+            "198.51.100.0/24".parse().unwrap(),
+        ],
+    );
+    assert!(ti.lookup(&ip("192.0.2.1")).is_none());
+    assert!(ti.lookup(&ip("198.51.100.9")).is_none());
+    assert!(ti.lookup(&ip("192.0.2.3")).is_some());
+
+    ti.replace_feed(CROWDSEC_FEED, vec![decision("203.0.113.0/24", None)]);
+    assert!(ti.lookup(&ip("192.0.2.3")).is_none());
+    assert_eq!(ti.feed_len(CROWDSEC_FEED), 1);
+}
+
+#[test]
+fn check_counts_blocked_hits_per_feed() {
+    let metrics = MetricsRegistry::new();
+    let ti = ThreatIntel::with_metrics(&metrics);
+    ti.replace_feed(
+        "drop",
+        vec![
+            decision("192.0.2.0/24", None),
+            decision("192.0.2.9/32", None),
+        ],
+    );
+
+    assert!(ti.check(&ip("192.0.2.9")));
+    assert!(ti.check(&ip("192.0.2.10")));
+    assert!(!ti.check(&ip("10.0.0.1")));
+    assert_eq!(
+        metrics
+            .threat_intel_blocked_total
+            .get_or_create(&label("drop"))
+            .get(),
+        2
+    );
+    assert_eq!(
+        metrics
+            .threat_intel_entries
+            .get_or_create(&label("drop"))
+            .get(),
+        2
+    );
+}
+
+#[test]
+fn ban_manager_applies_threat_intel_even_when_local_bans_disabled() {
+    let ti = Arc::new(ThreatIntel::new());
+    ti.replace_feed("drop", vec![decision("192.0.2.0/24", None)]);
+
+    let mut bans = BanManager::new(false, 3, 300, 900, vec!["192.0.2.50".to_string()]);
+    assert!(!bans.is_banned(&ip("192.0.2.1")));
+    bans.set_threat_intel(ti);
+
+    assert!(bans.is_banned(&ip("192.0.2.1")));
+    // Whitelist wins over external feeds
+    assert!(!bans.is_banned(&ip("192.0.2.50")));
+    assert!(!bans.is_banned(&ip("10.0.0.1")));
+    // Feed entries are not local bans
+    assert!(bans.banned_ips().is_empty());
+    assert!(!bans.unban(ip("192.0.2.1")));
+}
+
+#[tokio::test]
+async fn refresh_task_pulls_crowdsec_stream_and_denylists() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let pulls = Arc::new(AtomicUsize::new(0));
+
+    let stream_pulls = pulls.clone();
+    let app = axum::Router::new()
+        .route(
+            "/v1/decisions/stream",
+            axum::routing::get(
+                move |headers: axum::http::HeaderMap,
+                      axum::extract::Query(q): axum::extract::Query<
+                    std::collections::HashMap<String, String>,
+                >| {
+                    let pulls = stream_pulls.clone();
+                    async move {
+                        if headers.get("x-api-key").and_then(|v| v.to_str().ok())
+                            != Some("bouncer-key")
+                        {
+                            return (axum::http::StatusCode::FORBIDDEN, String::new());
+                        }
+                        pulls.fetch_add(1, Ordering::SeqCst);
+                        let body = if q.get("startup").map(String::as_str) == Some("true") {
+                            r#"{"new":[{"scope":"Ip","value":"192.0.2.1","type":"ban","duration":"4h"}],"deleted":null}"#
+                        } else {
+                            r#"{"new":null,"deleted":[{"scope":"Ip","value":"192.0.2.1","type":"ban","duration":"0s"}]}"#
+                        };
+                        (axum::http::StatusCode::OK, body.to_string())
+                    }
+                },
+            ),
+        )
+        .route(
+            "/drop.txt",
+            axum::routing::get(|| async { "; DROP\n198.51.100.0/24 ; SBL1\n" }),
+        )
+        .route(
+            "/missing.txt",
+            axum::routing::get(|| async { axum::http::StatusCode::NOT_FOUND }),
+        );
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let metrics = MetricsRegistry::new();
+    let ti = Arc::new(ThreatIntel::with_metrics(&metrics));
+    let base = format!("http://127.0.0.1:{port}");
+    let config = ThreatIntelConfig {
+        refresh_interval: 1,
+        crowdsec: Some(CrowdSecConfig {
+            url: base.clone(),
+            api_key: "bouncer-key".to_string(),
+        }),
+        denylists: vec![
+            DenylistConfig {
+                name: "drop".to_string(),
+                url: format!("{base}/drop.txt"),
+            },
+            DenylistConfig {
+                name: "missing".to_string(),
+                url: format!("{base}/missing.txt"),
+            },
+        ],
+    };
+    let shutdown = CancellationToken::new();
+    let handle = spawn_refresh_task(ti.clone(), config, shutdown.clone());
+
+    // First round: startup pull loads the ban, the missing list fails last
+    let missing_failures = metrics
+        .threat_intel_refresh_failures_total
+        .get_or_create(&label("missing"))
+        .clone();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while missing_failures.get() == 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(missing_failures.get(), 1);
+    assert_eq!(ti.lookup(&ip("192.0.2.1")).as_deref(), Some(CROWDSEC_FEED));
+    assert_eq!(ti.lookup(&ip("198.51.100.20")).as_deref(), Some("drop"));
+
+    // Second pull (startup=false) applies the deletion
+    while pulls.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(ti.lookup(&ip("192.0.2.1")).is_none());
+    assert_eq!(ti.lookup(&ip("198.51.100.20")).as_deref(), Some("drop"));
+
+    shutdown.cancel();
+    handle.await.unwrap();
+}
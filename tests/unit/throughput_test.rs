@@ -0,0 +1,52 @@
+use s5::proxy::throughput::ThroughputHistory;
+use std::time::{Duration, Instant};
+
+fn counters(list: &[(&str, u64, u64)]) -> Vec<(String, u64, u64)> {
+    list.iter()
+        .map(|(id, up, down)| (id.to_string(), *up, *down))
+        .collect()
+}
+
+#[test]
+fn first_sample_is_a_baseline() {
+    let history = ThroughputHistory::new(60);
+    let sample = history.record(counters(&[("s1", 5_000, 9_000)]), Instant::now(), 100);
+    assert_eq!((sample.up_bps, sample.down_bps), (0, 0));
+    assert_eq!(history.samples().len(), 1);
+}
+
+#[test]
+fn rate_is_the_per_second_delta_across_sessions() {
+    let history = ThroughputHistory::new(60);
+    let t0 = Instant::now();
+    history.record(counters(&[("s1", 1_000, 2_000)]), t0, 100);
+
+    // s1 moved 1000/500 bytes, s2 is new and counts in full
+    let sample = history.record(
+        counters(&[("s1", 2_000, 2_500), ("s2", 300, 700)]),
+        t0 + Duration::from_secs(1),
+        101,
+    );
+    assert_eq!(sample.timestamp, 101);
+    assert_eq!((sample.up_bps, sample.down_bps), (1_300, 1_200));
+
+    // A late tick is normalised to bytes per second; closed sessions drop out
+    let sample = history.record(
+        counters(&[("s2", 4_300, 700)]),
+        t0 + Duration::from_secs(3),
+        103,
+    );
+    assert_eq!((sample.up_bps, sample.down_bps), (2_000, 0));
+    assert_eq!(history.latest(), Some(sample));
+}
+
+#[test]
+fn ring_buffer_keeps_the_newest_samples() {
+    let history = ThroughputHistory::new(3);
+    let t0 = Instant::now();
+    for i in 0..5u64 {
+        history.record(Vec::new(), t0 + Duration::from_secs(i), i as i64);
+    }
+    let timestamps: Vec<i64> = history.samples().iter().map(|s| s.timestamp).collect();
+    assert_eq!(timestamps, [2, 3, 4]);
+}
@@ -0,0 +1,285 @@
+use crate::test_support::parse_app_config;
+use prometheus_client::encoding::text::encode;
+use s5::audit::AuditLogger;
+use s5::flows::{FlowQuery, FlowRecord, FlowStore};
+use s5::metrics::MetricsRegistry;
+use s5::proxy::classify::{classify, AppProtocol, Classification};
+use s5::proxy::close::CloseReason;
+use s5::proxy::forwarder::{self, RelayConfig};
+use s5::proxy::ProxyEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A TLS 1.3 ClientHello record, with an SNI extension when `sni` is set.
+fn client_hello(sni: Option<&str>) -> Vec<u8> {
+    let mut extensions = vec![0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04]; // supported_versions
+    if let Some(name) = sni {
+        let name = name.as_bytes();
+        let entry_len = name.len() + 3;
+        extensions.extend_from_slice(&[0x00, 0x00]);
+        extensions.extend_from_slice(&((entry_len + 2) as u16).to_be_bytes());
+        extensions.extend_from_slice(&(entry_len as u16).to_be_bytes());
+        extensions.push(0x00);
+        extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(name);
+    }
+
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]); // random
+    body.push(0); // session ID
+    body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // one cipher suite
+    body.extend_from_slice(&[0x01, 0x00]); // null compression
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+fn classified(protocol: AppProtocol, server_name: Option<&str>) -> Classification {
+    Classification {
+        protocol,
+        server_name: server_name.map(str::to_string),
+        first_line: None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Classification
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tls_client_hello_gives_sni() {
+    let hello = client_hello(Some("API.Example.com"));
+    assert_eq!(
+        classify(&hello),
+        classified(AppProtocol::Tls, Some("api.example.com"))
+    );
+    assert_eq!(
+        classify(&client_hello(None)),
+        classified(AppProtocol::Tls, None)
+    );
+    // First read cut inside the extensions: still TLS
+    assert_eq!(
+        classify(&hello[..hello.len() - 4]),
+        classified(AppProtocol::Tls, None)
+    );
+    assert_eq!(classify(&hello[..3]), classified(AppProtocol::Tls, None));
+}
+
+#[test]
+fn http_request_gives_host() {
+    let request =
+        b"GET /index.html HTTP/1.1\r\nUser-Agent: curl/8.5\r\nhost: Example.org:8080\r\n\r\n";
+    assert_eq!(
+        classify(request),
+        classified(AppProtocol::Http, Some("example.org:8080"))
+    );
+    assert_eq!(
+        classify(b"POST /api HTTP/1.1\r\nContent-Length: 0\r\n\r\nHost: body.example"),
+        classified(AppProtocol::Http, None)
+    );
+    assert_eq!(
+        classify(b"GET / HTTP/1.1\r\nHost: a\x1b[31m\r\n\r\n"),
+        classified(AppProtocol::Http, None)
+    );
+}
+
+#[test]
+fn ssh_and_unknown_streams() {
+    assert_eq!(
+        classify(b"SSH-2.0-OpenSSH_9.6\r\n"),
+        classified(AppProtocol::Ssh, None)
+    );
+    assert_eq!(
+        classify(b"GETX / HTTP/1.1\r\n").protocol,
+        AppProtocol::Unknown
+    );
+    assert_eq!(classify(b"\x00\x01binary").protocol, AppProtocol::Unknown);
+    assert_eq!(classify(b"").protocol, AppProtocol::Unknown);
+    assert_eq!(AppProtocol::Tls.to_string(), "tls");
+}
+
+// ---------------------------------------------------------------------------
+// Relay, metrics and flow records
+// ---------------------------------------------------------------------------
+
+fn make_engine() -> ProxyEngine {
+    let config = parse_app_config("", "").unwrap();
+    ProxyEngine::new(
+        Arc::new(config),
+        Arc::new(AuditLogger::new(None, 0, 0, None)),
+    )
+}
+
+#[tokio::test]
+async fn relay_classifies_first_client_bytes() {
+    let mut engine = make_engine();
+    let metrics = Arc::new(MetricsRegistry::new());
+    engine.set_metrics(metrics.clone());
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let config = RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "test@classify:443".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit: None,
+        session: Some(session.clone()),
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    };
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        config,
+    ));
+
+    let hello = client_hello(Some("example.com"));
+    client.write_all(&hello).await.unwrap();
+    let mut buf = vec![0u8; hello.len()];
+    server.read_exact(&mut buf).await.unwrap();
+    // Later bytes do not change the classification
+    client.write_all(b"SSH-2.0-late\r\n").await.unwrap();
+    server.write_all(b"server hello").await.unwrap();
+    let mut reply = [0u8; 12];
+    client.read_exact(&mut reply).await.unwrap();
+    drop(client);
+    drop(server);
+
+    let outcome = relay.await.unwrap().unwrap();
+    assert_eq!(
+        outcome.traffic,
+        Some(classified(AppProtocol::Tls, Some("example.com")))
+    );
+    engine.close_session(&session, outcome.reason);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    let series = |direction: &str| {
+        buffer
+            .lines()
+            .find(|l| {
+                l.starts_with("s5_app_protocol_bytes_total")
+                    && l.contains("app_protocol=\"tls\"")
+                    && l.contains(&format!("direction=\"{direction}\""))
+            })
+            .unwrap_or_else(|| panic!("no {direction} series in {buffer}"))
+            .to_string()
+    };
+    let up = hello.len() + 14;
+    assert!(series("up").ends_with(&format!(" {up}")));
+    assert!(series("down").ends_with(" 12"));
+}
+
+#[tokio::test]
+async fn session_without_client_bytes_counts_as_unknown() {
+    let mut engine = make_engine();
+    let metrics = Arc::new(MetricsRegistry::new());
+    engine.set_metrics(metrics.clone());
+    let session = engine.register_session("alice", "example.com", 25, "10.0.0.1", "socks5");
+    session
+        .bytes_down
+        .store(40, std::sync::atomic::Ordering::Relaxed);
+    engine.close_session(&session, CloseReason::UpstreamEof);
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).unwrap();
+    assert!(buffer
+        .lines()
+        .any(|l| l.starts_with("s5_app_protocol_bytes_total")
+            && l.contains("app_protocol=\"unknown\"")
+            && l.contains("direction=\"down\"")
+            && l.ends_with(" 40")));
+}
+
+#[test]
+fn flow_records_keep_classification() {
+    let flow = |host: &str, traffic: Option<Classification>| {
+        FlowRecord::closing("ssh", "alice", "10.0.0.1", host, 443, Duration::ZERO)
+            .with_traffic(traffic.as_ref())
+    };
+    let store = FlowStore::open_in_memory().unwrap();
+    store
+        .insert(&[
+            flow(
+                "203.0.113.9",
+                Some(classified(AppProtocol::Tls, Some("example.com"))),
+            ),
+            flow("db.internal", Some(classified(AppProtocol::Unknown, None))),
+            flow("idle.example", None),
+        ])
+        .unwrap();
+
+    let tls = store
+        .query(&FlowQuery {
+            app_protocol: Some("tls".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(tls.len(), 1);
+    assert_eq!(tls[0].dest_host, "203.0.113.9");
+    assert_eq!(tls[0].server_name.as_deref(), Some("example.com"));
+
+    let rows = store.query(&FlowQuery::default()).unwrap();
+    assert_eq!(rows[0].app_protocol, None);
+    assert_eq!(rows[1].app_protocol.as_deref(), Some("unknown"));
+    let json = serde_json::to_value(&rows[0]).unwrap();
+    assert!(json.get("app_protocol").is_none());
+}
+
+#[test]
+fn flow_database_from_older_release_gains_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("flows.db");
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE flows (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, started_at INTEGER NOT NULL,
+                username TEXT NOT NULL, protocol TEXT NOT NULL, source_ip TEXT NOT NULL,
+                dest_host TEXT NOT NULL, dest_ip TEXT, dest_port INTEGER NOT NULL,
+                bytes_up INTEGER NOT NULL, bytes_down INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL, close_reason TEXT NOT NULL, correlation_id TEXT
+            );
+            INSERT INTO flows (started_at, username, protocol, source_ip, dest_host,
+                dest_port, bytes_up, bytes_down, duration_ms, close_reason)
+            VALUES (0, 'bob', 'socks5', '10.0.0.2', 'old.example', 80, 1, 2, 3, 'closed');",
+        )
+        .unwrap();
+    }
+
+    let store = FlowStore::open(&path).unwrap();
+    let flow = FlowRecord::closing(
+        "ssh",
+        "alice",
+        "10.0.0.1",
+        "new.example",
+        80,
+        Duration::ZERO,
+    )
+    .with_traffic(Some(&classified(AppProtocol::Http, Some("new.example"))));
+    store.insert(&[flow]).unwrap();
+
+    let rows = store.query(&FlowQuery::default()).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].app_protocol.as_deref(), Some("http"));
+    assert_eq!(rows[1].dest_host, "old.example");
+    assert_eq!(rows[1].app_protocol, None);
+    // Opening again leaves the migrated table alone
+    drop(store);
+    assert_eq!(FlowStore::open(&path).unwrap().count().unwrap(), 2);
+}
@@ -0,0 +1,165 @@
+use crate::test_support::parse_app_config;
+use s5::audit::events::AuditEvent;
+use s5::audit::AuditLogger;
+use s5::proxy::close::CloseReason;
+use s5::proxy::forwarder::{self, RelayConfig, RelayOutcome};
+use s5::proxy::transfer::TransferLimits;
+use s5::proxy::ProxyEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn make_engine(limits: &str) -> ProxyEngine {
+    let config = parse_app_config(&format!("[limits]\n{limits}"), "").unwrap();
+    ProxyEngine::new(
+        Arc::new(config),
+        Arc::new(AuditLogger::new(None, 0, 0, None)),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Limits
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn limits_read_from_config() {
+    let engine = make_engine(
+        "max_connection_bytes = 1000\nupload_alert_bytes = 500\ndownload_alert_bytes = 800",
+    );
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    assert_eq!(
+        session.transfer,
+        TransferLimits {
+            max_bytes: 1000,
+            upload_alert: 500,
+            download_alert: 800,
+        }
+    );
+
+    let engine = make_engine("");
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    assert_eq!(session.transfer, TransferLimits::default());
+    assert!(!session.transfer.would_exceed(u64::MAX, 1));
+}
+
+#[test]
+fn cap_and_thresholds() {
+    let limits = TransferLimits {
+        max_bytes: 100,
+        upload_alert: 50,
+        download_alert: 0,
+    };
+    assert!(!limits.would_exceed(60, 40));
+    assert!(limits.would_exceed(60, 41));
+
+    assert_eq!(limits.crossed_alert(true, 0, 50), None);
+    assert_eq!(limits.crossed_alert(true, 40, 51), Some(50));
+    assert_eq!(limits.crossed_alert(true, 50, 60), Some(50));
+    // Already past: no second alert
+    assert_eq!(limits.crossed_alert(true, 51, 200), None);
+    assert_eq!(limits.crossed_alert(false, 0, u64::MAX), None);
+}
+
+// ---------------------------------------------------------------------------
+// Relay
+// ---------------------------------------------------------------------------
+
+/// Relay `chunks` from the client, each one after the previous arrived.
+/// Returns the bytes the destination got and the outcome.
+async fn relay_chunks(
+    engine: &ProxyEngine,
+    audit: Option<Arc<AuditLogger>>,
+    chunks: &[&[u8]],
+) -> (Vec<u8>, RelayOutcome) {
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    let (mut client, relay_client) = tokio::io::duplex(4096);
+    let (mut server, relay_server) = tokio::io::duplex(4096);
+    let config = RelayConfig {
+        idle_timeout: Duration::from_secs(5),
+        context: "test@transfer:443".to_string(),
+        per_conn_bandwidth_kbps: 0,
+        aggregate_bandwidth_kbps: 0,
+        quota_tracker: None,
+        username: None,
+        quotas: None,
+        audit,
+        session: Some(session),
+        stall_watch: None,
+        client_window: None,
+        server_names: None,
+    };
+    let relay = tokio::spawn(forwarder::relay_with_reason(
+        relay_client,
+        relay_server,
+        config,
+    ));
+
+    let mut received = Vec::new();
+    for chunk in chunks {
+        client.write_all(chunk).await.unwrap();
+        let mut buf = vec![0u8; chunk.len()];
+        let read = tokio::time::timeout(Duration::from_secs(5), server.read_exact(&mut buf));
+        if read.await.unwrap().is_err() {
+            break;
+        }
+        received.extend_from_slice(&buf);
+    }
+    drop(client);
+    drop(server);
+    (received, relay.await.unwrap().unwrap())
+}
+
+#[tokio::test]
+async fn session_closed_before_passing_cap() {
+    let engine = make_engine("max_connection_bytes = 100");
+    let (received, outcome) = relay_chunks(&engine, None, &[&[1; 60], &[2; 30], &[3; 20]]).await;
+    assert_eq!(received.len(), 90);
+    assert_eq!(outcome.reason, CloseReason::TransferLimit);
+    assert_eq!(outcome.bytes_up, 90);
+}
+
+#[tokio::test]
+async fn upload_alert_raised_once_and_session_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.log");
+    let audit = Arc::new(AuditLogger::new(Some(path.clone()), 0, 0, None));
+    let engine = make_engine("upload_alert_bytes = 50");
+
+    let (received, outcome) =
+        relay_chunks(&engine, Some(audit), &[&[1; 40], &[2; 40], &[3; 40]]).await;
+    assert_eq!(received.len(), 120);
+    assert_eq!(outcome.reason, CloseReason::ClientEof);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let content = tokio::fs::read_to_string(&path).await.unwrap();
+    let alerts: Vec<serde_json::Value> = content
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .filter(|e: &serde_json::Value| e["event_type"] == "session.transfer_alert")
+        .collect();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0]["direction"], "upload");
+    assert_eq!(alerts[0]["bytes"], 80);
+    assert_eq!(alerts[0]["threshold"], 50);
+    assert_eq!(alerts[0]["source_ip"], "10.0.0.1");
+}
+
+#[tokio::test]
+async fn transfer_events_are_critical() {
+    let engine = make_engine("max_connection_bytes = 1000");
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    let exceeded = AuditEvent::session_transfer_exceeded(&session);
+    assert_eq!(exceeded.event_type(), "session.transfer_exceeded");
+    assert!(exceeded.is_critical());
+    let json = serde_json::to_value(&exceeded).unwrap();
+    assert_eq!(json["max_connection_bytes"], 1000);
+
+    let alert = AuditEvent::session_transfer_alert(&session, false, 900, 800);
+    assert_eq!(alert.event_type(), "session.transfer_alert");
+    assert!(alert.is_critical());
+    assert_eq!(
+        serde_json::to_value(&alert).unwrap()["direction"],
+        "download"
+    );
+    assert_eq!(CloseReason::TransferLimit.as_str(), "transfer_limit");
+}
@@ -95,8 +95,8 @@ fn test_display_addr() {
 
 mod resolve_priority {
     use s5::config::types::{
-        AppConfig, ConnectionPoolConfig, GlobalAclConfig, LimitsConfig, ServerConfig, ShellConfig,
-        UpstreamProxyConfig,
+        AppConfig, ConnectionPoolConfig, GlobalAclConfig, HostKeyRotationConfig, LimitsConfig,
+        ServerConfig, ShellConfig, UpstreamProxyConfig,
     };
 
     fn make_minimal_config(upstream: Option<&str>) -> AppConfig {
@@ -116,12 +116,25 @@ mod resolve_priority {
                 socks5_tls_key: None,
                 dns_cache_ttl: -1,
                 dns_cache_max_entries: 1000,
+                dns_prefetch_hosts: 0,
                 connect_retry: 0,
                 connect_retry_delay_ms: 1000,
                 bookmarks_path: None,
                 ssh_keepalive_interval_secs: 15,
                 ssh_keepalive_max: 3,
                 ssh_auth_timeout: 120,
+                ssh_window_size: 2 * 1024 * 1024,
+                ssh_max_packet_size: 32 * 1024,
+                ssh_stall_threshold_ms: 5000,
+                max_connections: 0,
+                max_connections_per_user: 0,
+                connection_queue_timeout_ms: 0,
+                connection_queue_size: 0,
+                listeners: Vec::new(),
+                host_key_rotation: HostKeyRotationConfig::default(),
+                maintenance_message: "Server is under maintenance. Please try again later."
+                    .to_string(),
+                socket_handover: false,
             },
             shell: ShellConfig::default(),
             limits: LimitsConfig::default(),
@@ -135,10 +148,31 @@ mod resolve_priority {
             acl: GlobalAclConfig::default(),
             users: Vec::new(),
             groups: Vec::new(),
+            tenants: Default::default(),
             motd: Default::default(),
             alerting: Default::default(),
             maintenance_windows: Vec::new(),
+            quota_plans: Default::default(),
             connection_pool: ConnectionPoolConfig::default(),
+            threat_intel: Default::default(),
+            ip_intel: Default::default(),
+            honeypot: Default::default(),
+            login_anomaly: Default::default(),
+            impossible_travel: Default::default(),
+            invitations: Default::default(),
+            key_enrollment: Default::default(),
+            authorized_keys_fetch: Default::default(),
+            notifications: Default::default(),
+            reports: Default::default(),
+            cluster: Default::default(),
+            sandbox: Default::default(),
+            vpn: Default::default(),
+            subsystems: Vec::new(),
+            proxy: Default::default(),
+            policy: Default::default(),
+            scripting: None,
+            masque: None,
+            gssapi: None,
         }
     }
 
@@ -152,12 +186,29 @@ mod resolve_priority {
             password_hash: None,
             authorized_keys: Vec::new(),
             parsed_authorized_keys: Vec::new(),
+            dated_keys: Vec::new(),
             allow_forwarding: true,
             allow_shell: true,
             max_new_connections_per_minute: 0,
             max_bandwidth_kbps: 0,
             source_ips: Vec::new(),
+            ip_guard_exemptions: Vec::new(),
+            jump_targets: None,
+            unix_sockets: Vec::new(),
+            allowed_hassh: Vec::new(),
+            fs_allow: None,
+            subsystems: Vec::new(),
+            impossible_travel: None,
             expires_at: None,
+            disabled: false,
+            require_security_key: false,
+            allow_vpn: false,
+            vpn_address: None,
+            tags: Vec::new(),
+            payload_metadata: None,
+            blocked_ports: None,
+            authorized_keys_url: None,
+            password_changed_at: None,
             upstream_proxy: upstream.map(|u| u.to_string()),
             acl: ParsedAcl::from_config(AclPolicyConfig::Allow, &[], &[]).unwrap(),
             totp_enabled: false,
@@ -166,8 +217,10 @@ mod resolve_priority {
             group: None,
             role: UserRole::User,
             shell_permissions: ShellPermissions::default(),
+            terminal: Default::default(),
             motd_config: None,
             quotas: None,
+            quota_plan: None,
             time_access: None,
             auth_methods: None,
             idle_warning_secs: 0,
@@ -177,6 +230,8 @@ mod resolve_priority {
             aliases: HashMap::new(),
             max_connections: 0,
             rate_limits: RateLimitsConfig::default(),
+            api_token_hash: None,
+            api_tokens: Vec::new(),
         }
     }
 
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use s5::audit::AuditLogger;
+use s5::config::parse_config;
+use s5::config::types::ReportsConfig;
+use s5::notifications::Route;
+use s5::proxy::close::CloseReason;
+use s5::proxy::ProxyEngine;
+use s5::reports::{self, UsageHistory, UsageReport, UsageTotals};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+const CONFIG: &str = r#"[server]
+ssh_listen = "0.0.0.0:2222"
+
+[[users]]
+username = "alice"
+password_hash = "argon2id-fakehash-for-testing"
+"#;
+
+fn at(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+}
+
+fn usage(bytes_up: u64, bytes_down: u64, session_secs: u64) -> UsageTotals {
+    UsageTotals {
+        bytes_up,
+        bytes_down,
+        connections: 1,
+        logins: 1,
+        session_secs,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Months
+// ---------------------------------------------------------------------------
+
+#[test]
+fn months_parse_and_step_back() {
+    assert!(reports::parse_month("2026-03").is_some());
+    for month in ["2026-3", "2026-13", "2026-03-01", "march", ""] {
+        assert!(reports::parse_month(month).is_none(), "{month:?}");
+    }
+    assert_eq!(reports::month_of(at("2026-03-31T23:59:59Z")), "2026-03");
+    assert_eq!(
+        reports::previous_month(at("2026-03-31T12:00:00Z")),
+        "2026-02"
+    );
+    assert_eq!(
+        reports::previous_month(at("2026-01-01T00:00:00Z")),
+        "2025-12"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Totals and reports
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sessions_add_up_per_user_and_month() {
+    let history = UsageHistory::new(&ReportsConfig::default());
+    history.record("bob", at("2026-03-02T10:00:00Z"), &usage(100, 1000, 1800));
+    history.record("bob", at("2026-03-20T10:00:00Z"), &usage(50, 500, 3600));
+    history.record("alice", at("2026-03-05T10:00:00Z"), &usage(1, 2, 60));
+    history.record("alice", at("2026-04-01T00:00:00Z"), &usage(7, 7, 7));
+
+    let report = history.report("2026-03");
+    assert_eq!(report.month, "2026-03");
+    let names: Vec<_> = report.users.iter().map(|u| u.username.as_str()).collect();
+    assert_eq!(names, ["alice", "bob"]);
+    let bob = &report.users[1];
+    assert_eq!(bob.totals.bytes_up, 150);
+    assert_eq!(bob.totals.connections, 2);
+    assert_eq!(bob.bytes_total, 1650);
+    assert_eq!(bob.session_hours, 1.5);
+    assert_eq!(report.total.totals.connections, 3);
+    assert_eq!(report.total.bytes_total, 1653);
+
+    assert_eq!(history.months(), ["2026-03", "2026-04"]);
+    assert!(history.report("2026-05").users.is_empty());
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["users"][1]["bytes_up"], 150);
+    assert_eq!(json["users"][1]["session_hours"], 1.5);
+}
+
+#[test]
+fn csv_has_a_total_row_and_defuses_formulas() {
+    let mut users = BTreeMap::new();
+    users.insert("=cmd".to_string(), usage(1, 2, 36));
+    users.insert("a,\"b\"".to_string(), usage(3, 4, 0));
+    let csv = UsageReport::new("2026-03", &users).to_csv();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "month,username,bytes_up,bytes_down,bytes_total,connections,logins,session_hours",
+            "2026-03,'=cmd,1,2,3,1,1,0.01",
+            "2026-03,\"a,\"\"b\"\"\",3,4,7,1,1,0.00",
+            "2026-03,TOTAL,4,6,10,2,2,0.01",
+        ]
+    );
+}
+
+#[test]
+fn report_email_carries_the_csv() {
+    let mut users = BTreeMap::new();
+    users.insert("alice".to_string(), usage(1, 2, 3600));
+    let report = UsageReport::new("2026-02", &users);
+    let to = vec![
+        "ops@example.com".to_string(),
+        "billing@example.com".to_string(),
+    ];
+    let digest = reports::report_email(&report, &to, "s5-prod");
+    assert_eq!(digest.subject, "[s5-prod] Usage report 2026-02");
+    assert_eq!(
+        digest.route,
+        Route::Email(vec![
+            "billing@example.com".to_string(),
+            "ops@example.com".to_string()
+        ])
+    );
+    assert!(digest.body.contains("2026-02,alice,1,2,3,1,1,1.00"));
+}
+
+// ---------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------
+
+#[test]
+fn history_survives_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ReportsConfig {
+        usage_history_path: Some(dir.path().join("usage.json")),
+        ..Default::default()
+    };
+    let history = UsageHistory::new(&config);
+    history.record("alice", at("2026-03-02T10:00:00Z"), &usage(10, 20, 30));
+    history.save();
+
+    let reloaded = UsageHistory::new(&config);
+    assert_eq!(reloaded.month("2026-03"), history.month("2026-03"));
+
+    // An unreadable file starts over
+    std::fs::write(dir.path().join("usage.json"), "not json").unwrap();
+    assert!(UsageHistory::new(&config).months().is_empty());
+}
+
+#[test]
+fn old_months_are_dropped() {
+    let history = UsageHistory::new(&ReportsConfig {
+        usage_history_months: 2,
+        ..Default::default()
+    });
+    for month in ["2026-01", "2026-02", "2026-03"] {
+        history.record(
+            "alice",
+            at(&format!("{month}-10T00:00:00Z")),
+            &usage(1, 1, 1),
+        );
+    }
+    assert_eq!(history.months(), ["2026-02", "2026-03"]);
+}
+
+// ---------------------------------------------------------------------------
+// Config and proxy
+// ---------------------------------------------------------------------------
+
+#[test]
+fn invalid_reports_refused_by_validation() {
+    let smtp = "\n[notifications.smtp]\nhost = \"mail.example.com\"\nfrom = \"s5@example.com\"\n";
+    let cases = [
+        (
+            format!("{CONFIG}\n[reports]\nusage_history_months = 0\n"),
+            "usage_history_months",
+        ),
+        (
+            format!("{CONFIG}\n[reports]\nmonthly_email = true\n"),
+            "requires [notifications.smtp]",
+        ),
+        (
+            format!("{CONFIG}\n[reports]\nmonthly_email = true\n{smtp}"),
+            "needs reports.email_to",
+        ),
+        (
+            format!("{CONFIG}\n[reports]\nemail_to = [\"not an address\"]\n"),
+            "reports.email_to",
+        ),
+    ];
+    for (config, expected) in cases {
+        let err = parse_config(&config).unwrap_err();
+        assert!(format!("{err:#}").contains(expected), "{err:#}");
+    }
+
+    let config = format!(
+        "{CONFIG}\n[reports]\nmonthly_email = true\nemail_to = [\"billing@example.com\"]\n{smtp}"
+    );
+    let config = parse_config(&config).unwrap();
+    assert!(config.reports.monthly_email);
+    assert_eq!(config.reports.usage_history_months, 13);
+}
+
+#[test]
+fn closed_sessions_are_counted() {
+    let config = parse_config(CONFIG).unwrap();
+    let engine = ProxyEngine::new(Arc::new(config), Arc::new(AuditLogger::new_noop()));
+    let session = engine.register_session("alice", "example.com", 443, "10.0.0.1", "ssh");
+    session.bytes_up.store(100, Ordering::Relaxed);
+    session.bytes_down.store(2000, Ordering::Relaxed);
+    engine.close_session(&session, CloseReason::ClientEof);
+
+    let report = engine
+        .usage_history()
+        .report(&reports::month_of(Utc::now()));
+    assert_eq!(report.users.len(), 1);
+    assert_eq!(report.users[0].username, "alice");
+    assert_eq!(report.users[0].totals.connections, 1);
+    assert_eq!(report.users[0].bytes_total, 2100);
+    // Time is billed per login, not per channel
+    assert_eq!(report.users[0].totals.session_secs, 0);
+}
+
+#[test]
+fn logins_bill_their_time_once_across_channels() {
+    let history = UsageHistory::new(&ReportsConfig::default());
+    history.record_login(
+        "bob",
+        at("2026-03-02T10:00:00Z"),
+        at("2026-03-02T11:30:00Z"),
+    );
+    // Channels opened during the login add bytes and connections only
+    for _ in 0..3 {
+        history.record(
+            "bob",
+            at("2026-03-02T11:00:00Z"),
+            &UsageTotals {
+                bytes_up: 10,
+                connections: 1,
+                ..UsageTotals::default()
+            },
+        );
+    }
+
+    let bob = &history.report("2026-03").users[0];
+    assert_eq!(bob.totals.connections, 3);
+    assert_eq!(bob.totals.logins, 1);
+    assert_eq!(bob.totals.bytes_up, 30);
+    assert_eq!(bob.session_hours, 1.5);
+}
+
+#[test]
+fn logins_across_months_split_their_time() {
+    let history = UsageHistory::new(&ReportsConfig::default());
+    history.record_login(
+        "bob",
+        at("2026-03-31T23:00:00Z"),
+        at("2026-04-01T00:30:00Z"),
+    );
+
+    let march = &history.report("2026-03").users[0];
+    assert_eq!(march.totals.logins, 0);
+    assert_eq!(march.session_hours, 1.0);
+    // The login counts in the month it ended
+    let april = &history.report("2026-04").users[0];
+    assert_eq!(april.totals.logins, 1);
+    assert_eq!(april.session_hours, 0.5);
+}
+
+#[test]
+fn login_guard_counts_on_drop() {
+    let history = Arc::new(UsageHistory::new(&ReportsConfig::default()));
+    let login = history.start_login("alice");
+    assert!(history
+        .report(&reports::month_of(Utc::now()))
+        .users
+        .is_empty());
+    drop(login);
+
+    let report = history.report(&reports::month_of(Utc::now()));
+    assert_eq!(report.users[0].username, "alice");
+    assert_eq!(report.users[0].totals.logins, 1);
+    assert_eq!(report.users[0].totals.connections, 0);
+}
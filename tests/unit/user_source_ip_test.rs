@@ -8,12 +8,18 @@ fn user_with_source_ips(ips: &[&str]) -> User {
         username: "test".to_string(),
         password_hash: Some("argon2id-fake".to_string()),
         authorized_keys: vec![],
-        allow_forwarding: true,
+        allow_forwarding: Some(true),
         allow_shell: true,
         max_new_connections_per_minute: 60,
         max_bandwidth_kbps: 0,
         source_ips: ips.iter().map(|s| s.parse().unwrap()).collect(),
         expires_at: None,
+        disabled: false,
+        require_security_key: false,
+        allow_vpn: false,
+        vpn_address: None,
+        tags: Vec::new(),
+        authorized_keys_url: None,
         upstream_proxy: None,
         acl: Default::default(),
         totp_secret: None,
@@ -22,8 +28,10 @@ fn user_with_source_ips(ips: &[&str]) -> User {
         group: None,
         role: UserRole::User,
         shell_permissions: None,
+        terminal: None,
         motd: None,
         quotas: None,
+        quota_plan: None,
         time_access: None,
         auth_methods: None,
         idle_warning_secs: None,
@@ -33,6 +41,15 @@ fn user_with_source_ips(ips: &[&str]) -> User {
         aliases: HashMap::new(),
         max_connections: None,
         rate_limits: None,
+        api_token_hash: None,
+        api_tokens: Vec::new(),
+        ip_guard_exemptions: None,
+        jump_targets: None,
+        unix_sockets: None,
+        allowed_hassh: None,
+        subsystems: None,
+        impossible_travel: None,
+        password_changed_at: None,
     };
     User::from_config(
         &cfg,
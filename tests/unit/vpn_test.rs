@@ -0,0 +1,246 @@
+use s5::config::acl::ParsedAcl;
+use s5::config::parse_config;
+use s5::config::types::AclPolicyConfig;
+use s5::proxy::tun::{
+    encode_packet, is_client_address, parse_packet, AddressPool, PacketDecoder, PacketFilter,
+};
+use std::net::Ipv4Addr;
+
+const FAKE_HASH: &str = "argon2id-fakehash-for-testing";
+
+/// IPv4 packet header (no options) followed by the destination port.
+fn ipv4(source: [u8; 4], destination: [u8; 4], protocol: u8, port: u16) -> Vec<u8> {
+    let mut packet = vec![0x45, 0, 0, 24, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+    packet.extend_from_slice(&source);
+    packet.extend_from_slice(&destination);
+    packet.extend_from_slice(&[0x9c, 0x40]);
+    packet.extend_from_slice(&port.to_be_bytes());
+    packet
+}
+
+const CLIENT: [u8; 4] = [10, 99, 0, 2];
+
+fn filter(deny: &[&str], ip_guard_enabled: bool, blocked_ports: Vec<u16>) -> PacketFilter {
+    let deny: Vec<String> = deny.iter().map(|r| r.to_string()).collect();
+    PacketFilter::new(
+        "alice",
+        CLIENT.into(),
+        ParsedAcl::from_config(AclPolicyConfig::Allow, &[], &deny).unwrap(),
+        ip_guard_enabled,
+        Vec::new(),
+        blocked_ports,
+        None,
+        "203.0.113.9",
+        "c1",
+    )
+}
+
+fn vpn_config(vpn: &str, users: &str) -> anyhow::Result<s5::config::types::AppConfig> {
+    parse_config(&format!(
+        "[server]\nssh_listen = \"127.0.0.1:2222\"\n\n[vpn]\nenabled = true\n{vpn}\n\n{users}"
+    ))
+}
+
+fn user(name: &str, extra: &str) -> String {
+    format!("[[users]]\nusername = \"{name}\"\npassword_hash = \"{FAKE_HASH}\"\nallow_vpn = true\n{extra}\n")
+}
+
+// ---------------------------------------------------------------------------
+// Packets
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tcp_and_udp_packets_carry_their_port() {
+    let info = parse_packet(&ipv4(CLIENT, [192, 0, 2, 1], 6, 443)).unwrap();
+    assert_eq!(info.source, Ipv4Addr::from(CLIENT));
+    assert_eq!(info.destination, Ipv4Addr::new(192, 0, 2, 1));
+    assert_eq!((info.protocol, info.port), (6, 443));
+    assert_eq!(
+        parse_packet(&ipv4(CLIENT, [192, 0, 2, 1], 17, 53))
+            .unwrap()
+            .port,
+        53
+    );
+    // ICMP, and fragments after the first, are matched as port 0
+    assert_eq!(
+        parse_packet(&ipv4(CLIENT, [192, 0, 2, 1], 1, 443))
+            .unwrap()
+            .port,
+        0
+    );
+    let mut fragment = ipv4(CLIENT, [192, 0, 2, 1], 6, 443);
+    fragment[6..8].copy_from_slice(&[0x00, 0x10]);
+    assert_eq!(parse_packet(&fragment).unwrap().port, 0);
+}
+
+#[test]
+fn malformed_and_ipv6_packets_are_not_parsed() {
+    assert!(parse_packet(&[0x45, 0, 0]).is_none());
+    let mut ipv6 = vec![0x60; 40];
+    ipv6[4..6].copy_from_slice(&[0, 0]);
+    assert!(parse_packet(&ipv6).is_none());
+    let mut short_header = ipv4(CLIENT, [192, 0, 2, 1], 6, 443);
+    short_header[0] = 0x44;
+    assert!(parse_packet(&short_header).is_none());
+}
+
+#[test]
+fn channel_data_round_trips() {
+    let first = ipv4(CLIENT, [192, 0, 2, 1], 6, 443);
+    let second = ipv4(CLIENT, [192, 0, 2, 2], 17, 53);
+    let mut data = Vec::new();
+    encode_packet(&first, &mut data);
+    assert_eq!(&data[..4], &2u32.to_be_bytes());
+    assert_eq!(data.len(), 4 + first.len());
+    encode_packet(&second, &mut data);
+
+    // A message read in several parts is put back together
+    let mut decoder = PacketDecoder::default();
+    assert!(decoder.push(&data[..7]).unwrap().is_empty());
+    assert_eq!(decoder.push(&data[7..]).unwrap(), vec![first, second]);
+}
+
+#[test]
+fn ipv6_packets_are_delimited_by_their_payload_length() {
+    let mut packet = vec![0; 48];
+    packet[0] = 0x60;
+    packet[4..6].copy_from_slice(&8u16.to_be_bytes());
+    let mut data = Vec::new();
+    encode_packet(&packet, &mut data);
+    assert_eq!(&data[..4], &24u32.to_be_bytes());
+    assert_eq!(PacketDecoder::default().push(&data).unwrap(), vec![packet]);
+}
+
+#[test]
+fn garbage_channel_data_is_an_error() {
+    // Address family that does not match the IP version
+    let mut data = 24u32.to_be_bytes().to_vec();
+    data.extend_from_slice(&ipv4(CLIENT, [192, 0, 2, 1], 6, 443));
+    assert!(PacketDecoder::default().push(&data).is_err());
+    // Total length shorter than a header
+    let mut data = Vec::new();
+    encode_packet(&ipv4(CLIENT, [192, 0, 2, 1], 6, 443), &mut data);
+    data[6..8].copy_from_slice(&[0, 4]);
+    assert!(PacketDecoder::default().push(&data).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Address pool
+// ---------------------------------------------------------------------------
+
+#[test]
+fn client_addresses_exclude_the_server_end() {
+    let pool = "10.99.0.0/29".parse().unwrap();
+    assert!(is_client_address(&pool, Ipv4Addr::new(10, 99, 0, 2)));
+    assert!(is_client_address(&pool, Ipv4Addr::new(10, 99, 0, 6)));
+    for excluded in [0, 1, 7] {
+        assert!(!is_client_address(
+            &pool,
+            Ipv4Addr::new(10, 99, 0, excluded)
+        ));
+    }
+    assert!(!is_client_address(&pool, Ipv4Addr::new(10, 99, 1, 2)));
+}
+
+#[test]
+fn leases_skip_reserved_addresses_and_are_returned() {
+    let pool = AddressPool::new(
+        "10.99.0.0/29".parse().unwrap(),
+        [Ipv4Addr::new(10, 99, 0, 2)],
+    );
+    assert_eq!(pool.server_address(), Ipv4Addr::new(10, 99, 0, 1));
+
+    let fixed = pool.lease(Some(Ipv4Addr::new(10, 99, 0, 2))).unwrap();
+    assert_eq!(fixed.address(), Ipv4Addr::new(10, 99, 0, 2));
+    assert!(pool.lease(Some(Ipv4Addr::new(10, 99, 0, 2))).is_none());
+    assert!(pool.lease(Some(Ipv4Addr::new(10, 99, 0, 1))).is_none());
+
+    let leases: Vec<_> = std::iter::from_fn(|| pool.lease(None)).collect();
+    let addresses: Vec<_> = leases.iter().map(|l| l.address().octets()[3]).collect();
+    assert_eq!(addresses, vec![3, 4, 5, 6]);
+
+    drop(leases);
+    assert_eq!(
+        pool.lease(None).unwrap().address(),
+        Ipv4Addr::new(10, 99, 0, 3)
+    );
+    drop(fixed);
+    assert!(pool.lease(Some(Ipv4Addr::new(10, 99, 0, 2))).is_some());
+}
+
+// ---------------------------------------------------------------------------
+// Packet filter
+// ---------------------------------------------------------------------------
+
+#[test]
+fn only_the_leased_source_is_routed() {
+    let mut filter = filter(&[], false, Vec::new());
+    assert!(filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 6, 443)));
+    assert!(!filter.allows(&ipv4([10, 99, 0, 3], [192, 0, 2, 1], 6, 443)));
+    assert!(!filter.allows(&[0x60; 40]));
+}
+
+#[test]
+fn acl_applies_to_destinations() {
+    let mut filter = filter(&["192.0.2.0/24:22", "198.51.100.7:*"], false, Vec::new());
+    assert!(!filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 6, 22)));
+    assert!(filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 6, 443)));
+    assert!(!filter.allows(&ipv4(CLIENT, [198, 51, 100, 7], 1, 0)));
+    // Cached decisions give the same answer
+    assert!(!filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 6, 22)));
+}
+
+#[test]
+fn ip_guard_applies_to_destinations() {
+    let mut guarded = filter(&[], true, Vec::new());
+    assert!(!guarded.allows(&ipv4(CLIENT, [169, 254, 169, 254], 6, 80)));
+    assert!(!guarded.allows(&ipv4(CLIENT, [127, 0, 0, 1], 17, 53)));
+    assert!(guarded.allows(&ipv4(CLIENT, [93, 184, 216, 34], 6, 80)));
+
+    let mut open = filter(&[], false, Vec::new());
+    assert!(open.allows(&ipv4(CLIENT, [169, 254, 169, 254], 6, 80)));
+}
+
+#[test]
+fn blocked_ports_apply_to_tcp_and_udp_only() {
+    let mut filter = filter(&[], false, vec![25]);
+    assert!(!filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 6, 25)));
+    assert!(!filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 17, 25)));
+    // The bytes after an ICMP header are not a port
+    assert!(filter.allows(&ipv4(CLIENT, [192, 0, 2, 1], 1, 25)));
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn vpn_config_validation() {
+    let users = format!(
+        "{}{}",
+        user("alice", "vpn_address = \"10.99.0.5\""),
+        user("bob", "")
+    );
+    let config = vpn_config("pool = \"10.99.0.0/24\"", &users).unwrap();
+    assert_eq!(config.vpn.mtu, 1420);
+    assert!(config.users[0].allow_vpn);
+    assert_eq!(
+        config.users[0].vpn_address,
+        Some(Ipv4Addr::new(10, 99, 0, 5))
+    );
+
+    for (vpn, users) in [
+        ("pool = \"10.99.0.0/31\"", user("alice", "")),
+        ("mtu = 500", user("alice", "")),
+        ("", user("alice", "vpn_address = \"10.99.0.1\"")),
+        ("", user("alice", "vpn_address = \"10.98.0.5\"")),
+        (
+            "",
+            user("alice", "vpn_address = \"10.99.0.5\"")
+                + &user("bob", "vpn_address = \"10.99.0.5\""),
+        ),
+        ("mtu = 9000", user("alice", "")),
+    ] {
+        assert!(vpn_config(vpn, &users).is_err(), "{vpn} {users}");
+    }
+}